@@ -0,0 +1,4 @@
+-- Link the two sides of an account-to-account transfer to each other
+ALTER TABLE transactions ADD COLUMN transfer_pair_id INTEGER REFERENCES transactions(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_transactions_transfer_pair ON transactions(transfer_pair_id);
@@ -0,0 +1,2 @@
+-- Advisory warnings on trading import rows (e.g. unknown symbols)
+ALTER TABLE trading_import_rows ADD COLUMN warning TEXT;
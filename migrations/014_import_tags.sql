@@ -0,0 +1,4 @@
+-- Tags applied to every row of an import session, plus per-row overrides.
+-- Both hold JSON arrays of tag ids; a NULL row value inherits the session tags.
+ALTER TABLE import_sessions ADD COLUMN tag_ids TEXT;
+ALTER TABLE import_rows ADD COLUMN tag_ids TEXT;
@@ -0,0 +1,3 @@
+-- Checkpoint for resuming an interrupted import: the row index the next
+-- batch starts at. Rows before it have been committed.
+ALTER TABLE import_sessions ADD COLUMN resume_from_row_index INTEGER NOT NULL DEFAULT 0;
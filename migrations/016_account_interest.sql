@@ -0,0 +1,4 @@
+-- Interest paid on cash accounts, used to project interest income.
+-- The rate is stored in basis points (250 = 2.50% per year).
+ALTER TABLE accounts ADD COLUMN interest_rate_bps INTEGER;
+ALTER TABLE accounts ADD COLUMN interest_compounding TEXT NOT NULL DEFAULT 'monthly';
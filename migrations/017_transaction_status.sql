@@ -0,0 +1,10 @@
+-- Pending transactions are not yet booked by the bank and are left out of
+-- balances and analytics until they are marked as posted.
+ALTER TABLE transactions ADD COLUMN status TEXT NOT NULL DEFAULT 'posted';
+
+CREATE INDEX idx_transactions_status ON transactions(status);
+
+-- Pending transaction an import row probably books, and whether the row
+-- should be merged into it instead of being imported as a new transaction.
+ALTER TABLE import_rows ADD COLUMN pending_match_id INTEGER REFERENCES transactions(id) ON DELETE SET NULL;
+ALTER TABLE import_rows ADD COLUMN merge_pending INTEGER NOT NULL DEFAULT 1;
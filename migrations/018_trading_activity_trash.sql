@@ -0,0 +1,5 @@
+-- Deleted trading activities are kept in a trash until they are restored
+-- or purged. NULL means the activity is live.
+ALTER TABLE trading_activities ADD COLUMN deleted_at TEXT;
+
+CREATE INDEX idx_trading_activities_deleted_at ON trading_activities(deleted_at);
@@ -0,0 +1,12 @@
+-- Read-only links to report snapshots that can be opened without logging in.
+-- The scope names the single page a link grants access to. Revoking a link
+-- deletes its row.
+CREATE TABLE share_links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token TEXT NOT NULL UNIQUE,
+    scope TEXT NOT NULL CHECK (scope IN ('spending-report', 'net-worth', 'positions')),
+    label TEXT NOT NULL DEFAULT '',
+    password_hash TEXT,
+    expires_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,8 @@
+-- Identifiers supplied by API clients, so that re-importing a record updates
+-- the row created for it instead of inserting a duplicate. NULL for rows
+-- created any other way; SQLite allows any number of NULLs in a unique index.
+ALTER TABLE transactions ADD COLUMN external_id TEXT;
+CREATE UNIQUE INDEX idx_transactions_external_id ON transactions(external_id);
+
+ALTER TABLE trading_activities ADD COLUMN external_id TEXT;
+CREATE UNIQUE INDEX idx_trading_activities_external_id ON trading_activities(external_id);
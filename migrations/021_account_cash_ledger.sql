@@ -0,0 +1,3 @@
+-- Securities accounts can derive their cash balance from trading activities
+-- (see services::cash_ledger) instead of showing only their positions.
+ALTER TABLE accounts ADD COLUMN derive_cash_from_trading INTEGER NOT NULL DEFAULT 0;
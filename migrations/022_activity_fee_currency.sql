@@ -0,0 +1,4 @@
+-- Fees charged in another currency than the trade, with the rate to convert
+-- them (units of the activity currency per unit of the fee currency).
+ALTER TABLE trading_activities ADD COLUMN fee_currency TEXT;
+ALTER TABLE trading_activities ADD COLUMN exchange_rate REAL;
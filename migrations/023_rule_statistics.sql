@@ -0,0 +1,16 @@
+-- How often each rule matched, and which transactions it changed.
+-- Counts grow by the number of transactions per import or apply run.
+ALTER TABLE rules ADD COLUMN match_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE rules ADD COLUMN last_matched_at TEXT;
+
+CREATE TABLE rule_matches (
+    rule_id INTEGER NOT NULL REFERENCES rules(id) ON DELETE CASCADE,
+    transaction_id INTEGER NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+    matched_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (rule_id, transaction_id)
+);
+
+CREATE INDEX idx_rule_matches_recent ON rule_matches(rule_id, matched_at);
+
+-- Rules that changed an import row, recorded when the row is imported
+ALTER TABLE import_rows ADD COLUMN rule_ids TEXT;
@@ -0,0 +1,6 @@
+-- File each import row came from, and per-file parse statistics of a
+-- session (JSON array of {file_name, rows, duplicates, errors})
+ALTER TABLE import_rows ADD COLUMN source_file TEXT;
+ALTER TABLE import_sessions ADD COLUMN file_stats TEXT;
+ALTER TABLE trading_import_rows ADD COLUMN source_file TEXT;
+ALTER TABLE trading_import_sessions ADD COLUMN file_stats TEXT;
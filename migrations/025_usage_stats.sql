@@ -0,0 +1,14 @@
+-- Opt-in usage statistics, aggregated per day. Requests are counted per
+-- route pattern, method, status and latency bucket; no client addresses,
+-- query strings or bodies are stored.
+CREATE TABLE usage_stats (
+    date TEXT NOT NULL,
+    method TEXT NOT NULL,
+    route TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    -- Upper bound of the latency bucket in milliseconds
+    duration_bucket_ms INTEGER NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    total_duration_ms REAL NOT NULL DEFAULT 0,
+    PRIMARY KEY (date, method, route, status, duration_bucket_ms)
+);
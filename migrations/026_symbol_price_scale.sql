@@ -0,0 +1,6 @@
+-- Currency that Yahoo quotes a symbol in, and the factor turning stored
+-- prices into it: London listings come back in pence ("GBp"), stored with
+-- a scale of 0.01 and GBP. Applied when prices are read, so stored market
+-- data stays as fetched.
+ALTER TABLE symbol_metadata ADD COLUMN price_scale REAL NOT NULL DEFAULT 1;
+ALTER TABLE symbol_metadata ADD COLUMN price_currency TEXT;
@@ -0,0 +1,3 @@
+-- Transaction field a rule's pattern is matched against: the description,
+-- the counterparty (payee or payer) or the counterparty IBAN.
+ALTER TABLE rules ADD COLUMN match_field TEXT NOT NULL DEFAULT 'description';
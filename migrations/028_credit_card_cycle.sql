@@ -0,0 +1,39 @@
+-- Credit card accounts, with the statement closing day and payment due day
+-- (1-31, clamped to the length of shorter months). The days are NULL for
+-- other accounts and for cards without a known cycle.
+--
+-- SQLite cannot change a CHECK constraint in place, so the table is rebuilt.
+-- Foreign keys are off while the old table is dropped so transactions and
+-- trading activities keep their account.
+PRAGMA foreign_keys = OFF;
+
+BEGIN;
+
+CREATE TABLE accounts_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    account_type TEXT NOT NULL CHECK (account_type IN ('Cash', 'Securities', 'CreditCard')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    active INTEGER NOT NULL DEFAULT 1,
+    interest_rate_bps INTEGER,
+    interest_compounding TEXT NOT NULL DEFAULT 'monthly',
+    derive_cash_from_trading INTEGER NOT NULL DEFAULT 0,
+    statement_day INTEGER CHECK (statement_day BETWEEN 1 AND 31),
+    due_day INTEGER CHECK (due_day BETWEEN 1 AND 31)
+);
+
+INSERT INTO accounts_new (id, name, account_type, created_at, updated_at, active,
+                          interest_rate_bps, interest_compounding, derive_cash_from_trading)
+SELECT id, name, account_type, created_at, updated_at, active,
+       interest_rate_bps, interest_compounding, derive_cash_from_trading
+FROM accounts;
+
+DROP TABLE accounts;
+ALTER TABLE accounts_new RENAME TO accounts;
+
+CREATE INDEX idx_accounts_type ON accounts(account_type);
+
+COMMIT;
+
+PRAGMA foreign_keys = ON;
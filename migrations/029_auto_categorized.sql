@@ -0,0 +1,9 @@
+-- Categories assigned by a rule rather than by hand, kept for review until
+-- accepted or changed, and the rule that assigned them.
+ALTER TABLE transactions ADD COLUMN auto_categorized INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE transactions ADD COLUMN auto_category_rule_id INTEGER REFERENCES rules(id) ON DELETE SET NULL;
+CREATE INDEX idx_transactions_auto_categorized ON transactions(auto_category_rule_id)
+    WHERE auto_categorized = 1;
+
+-- Rule that set an import row's category; cleared when it is set by hand
+ALTER TABLE import_rows ADD COLUMN category_rule_id INTEGER;
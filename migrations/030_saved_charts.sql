@@ -0,0 +1,11 @@
+-- Spending charts saved with their settings, shown on the spending page.
+-- `params` is a JSON object such as {"category_ids": [4, 7], "mode": "income"};
+-- the date range comes from the page.
+CREATE TABLE IF NOT EXISTS saved_charts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    chart_type TEXT NOT NULL CHECK (chart_type IN ('monthly-by-category', 'spending-over-time', 'comparison')),
+    params TEXT NOT NULL DEFAULT '{}',
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
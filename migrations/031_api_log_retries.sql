@@ -0,0 +1,5 @@
+-- Log entry a request was retried from, linking a retry chain on the log
+-- detail page
+ALTER TABLE api_logs ADD COLUMN retried_from INTEGER REFERENCES api_logs(id) ON DELETE SET NULL;
+CREATE INDEX idx_api_logs_retried_from ON api_logs(retried_from)
+    WHERE retried_from IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Transactions mirroring a dividend, fee or tax activity, so those show up
+-- in income and spending analytics
+ALTER TABLE transactions ADD COLUMN source_activity_id INTEGER
+    REFERENCES trading_activities(id) ON DELETE CASCADE;
+CREATE UNIQUE INDEX idx_transactions_source_activity ON transactions(source_activity_id)
+    WHERE source_activity_id IS NOT NULL;
+
+-- Cash account that mirrored transactions of a securities account are booked to
+ALTER TABLE accounts ADD COLUMN cash_account_id INTEGER REFERENCES accounts(id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- IBAN of an own account, normalized to upper case without spaces. A
+-- transaction whose counterparty IBAN is one of these is a transfer.
+ALTER TABLE accounts ADD COLUMN iban TEXT;
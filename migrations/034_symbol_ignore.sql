@@ -0,0 +1,4 @@
+-- Symbols left out of market data refreshes, e.g. defunct tickers that will
+-- never fetch again, with an optional note on why. Their stored prices stay.
+ALTER TABLE symbol_metadata ADD COLUMN ignored INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE symbol_metadata ADD COLUMN ignore_note TEXT;
@@ -0,0 +1,4 @@
+-- Close as quoted, unrounded. Exchange rates need more than the two
+-- decimals of close_price_cents: JPYEUR rounds to 0. NULL for prices
+-- stored before; they fall back to close_price_cents.
+ALTER TABLE market_data ADD COLUMN close_price REAL;
@@ -0,0 +1,9 @@
+-- Date windows a quote fetch returned nothing for, e.g. before a listing or
+-- after a delisting. Refreshes count them as covered instead of fetching
+-- them again.
+CREATE TABLE market_data_empty_ranges (
+    symbol TEXT NOT NULL,
+    start_date TEXT NOT NULL,
+    end_date TEXT NOT NULL,
+    PRIMARY KEY (symbol, start_date, end_date)
+);
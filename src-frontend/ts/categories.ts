@@ -23,6 +23,8 @@ interface CategoryNode extends Category {
 let categories: Category[] = [];
 let sortableInstances: any[] = [];
 let svgMap: Record<string, string> = {};
+// Expense sparklines rendered by the server, keyed by category id
+let sparklines: Record<number, string> = {};
 
 async function fetchIcons(): Promise<void> {
   try {
@@ -38,6 +40,14 @@ function inlineSvg(svg: string, classes: string): string {
   return svg.replace("<svg", `<svg class="${classes}"`);
 }
 
+function loadSparklines(): void {
+  const template = document.getElementById('category-sparklines') as HTMLTemplateElement | null;
+  if (!template) return;
+  for (const el of template.content.querySelectorAll<HTMLElement>('[data-category-id]')) {
+    sparklines[Number(el.dataset.categoryId)] = el.outerHTML;
+  }
+}
+
 // Decode HTML entities for plain text usage (API calls, form values)
 function decodeHtml(html: string): string {
   const txt = document.createElement('textarea');
@@ -115,6 +125,7 @@ function renderNodes(nodes: CategoryNode[]): string {
         <div class="category-row group">
           ${dragHandle}
           ${nameContent}
+          ${sparklines[node.id] || ''}
           ${cloneBtn}
         </div>
         <div class="tree-children" data-parent-id="${node.id}">
@@ -222,6 +233,7 @@ async function updateCategoryParent(id: number, parentId: number | null): Promis
 // Initialize
 async function init(initialCategories: Category[]): Promise<void> {
   categories = initialCategories;
+  loadSparklines();
   await fetchIcons();
   renderTree();
 }
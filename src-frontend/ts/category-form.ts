@@ -36,7 +36,8 @@ async function initCategoryForm(): Promise<void> {
 
   function updatePreview(): void {
     const name = iconInput.value.trim();
-    const color = colorSelect.value;
+    // "Automatic" has no color until the server picks one
+    const color = colorSelect.value || "#6b7280";
 
     if (name && iconSet.has(name)) {
       const icon = svgHtml(svgMap[name], "w-5 h-5 lucide-icon");
@@ -72,7 +73,8 @@ async function initCategoryForm(): Promise<void> {
       return;
     }
 
-    const color = colorSelect.value;
+    // "Automatic" has no color until the server picks one
+    const color = colorSelect.value || "#6b7280";
     let html = "";
     for (let i = 0; i < capped.length; i++) {
       const name = capped[i];
@@ -1,12 +1,24 @@
 declare const echarts: any;
 
-import { isDarkMode, getTheme, getCurrencySymbol } from "./utils";
+import {
+  isDarkMode,
+  getTheme,
+  getCurrencySymbol,
+  formatMoney,
+  escapeHtml,
+  NO_PERCENT,
+} from "./utils";
 
 interface CategoryTreeNode {
   name: string;
   color: string;
+  text_color: string;
   id?: number;
   amount_cents?: number;
+  category_id?: number;
+  transaction_count?: number;
+  drilldown_url?: string;
+  direction: "expense" | "income" | "mixed";
   children: CategoryTreeNode[];
 }
 
@@ -26,6 +38,11 @@ interface MonthlySummary {
   total_cents: number;
   transaction_count: number;
   average_cents: number;
+  income_cents: number;
+  expense_cents: number;
+  net_cents: number;
+  savings_rate: number | null;
+  savings_rate_formatted: string | null;
 }
 
 interface SankeyData {
@@ -40,17 +57,64 @@ interface MonthlyByCategoryResponse {
   series: {
     category: string;
     color: string;
+    direction: "expense" | "income";
     totals: number[];
   }[];
 }
 
+interface CategoryComparison {
+  category_id: number | null;
+  category: string;
+  color: string;
+  current_cents: number;
+  previous_cents: number;
+  delta_cents: number;
+  percent_change: number | null;
+  percent_change_formatted: string | null;
+}
+
+interface SpendingComparison {
+  from_date: string;
+  to_date: string;
+  previous_from_date: string;
+  previous_to_date: string;
+  current_total_cents: number;
+  previous_total_cents: number;
+  total_percent_change: number | null;
+  total_percent_change_formatted: string | null;
+  categories: CategoryComparison[];
+}
+
+interface SpendingPatternBucket {
+  index: number;
+  label: string;
+  total_cents: number;
+  transaction_count: number;
+  average_cents: number;
+}
+
+interface SpendingPatterns {
+  weekdays: SpendingPatternBucket[];
+  month_days: SpendingPatternBucket[];
+}
+
 let activeChart: any = null;
+let patternCharts: any[] = [];
+let savedCharts: any[] = [];
 let activeMonth: string | null = null;
 let activeCategory: number | null = null;
 
 const initialParams = new URLSearchParams(window.location.search);
 let categoryMode: string = initialParams.get("category_mode") || "expenses";
 let monthlyMode: string = initialParams.get("monthly_mode") || "expenses";
+// Extra sankey layers, "accounts" or empty
+let flowLayers: string = initialParams.get("flow_layers") || "";
+
+const MODE_LABELS: Record<string, string> = {
+  expenses: "Expenses",
+  income: "Income",
+  net: "Net",
+};
 
 function showEmptyState(container: HTMLElement): void {
   if (activeChart) {
@@ -84,15 +148,21 @@ function mapTreeToSunburst(nodes: CategoryTreeNode[]): any[] {
       return {
         name: node.name,
         categoryId: node.id,
+        drilldownUrl: node.drilldown_url,
+        transactionCount: node.transaction_count,
         itemStyle: { color: node.color },
+        label: { color: node.text_color },
         children: mapTreeToSunburst(node.children),
       };
     }
     return {
       name: node.name,
       categoryId: node.id,
+      drilldownUrl: node.drilldown_url,
+      transactionCount: node.transaction_count,
       value: (node.amount_cents || 0) / 100,
       itemStyle: { color: node.color },
+      label: { color: node.text_color },
     };
   });
 }
@@ -215,6 +285,21 @@ function setupCategoryShiftClick(fromDate?: string, toDate?: string): void {
   });
 }
 
+// Ctrl/Cmd-click a slice to open the matching transaction list.
+function setupCategoryDeepLink(): void {
+  if (!activeChart) return;
+
+  activeChart.on("click", (params: any) => {
+    const event = params.event?.event;
+    if (!event?.ctrlKey && !event?.metaKey) return;
+
+    const drilldownUrl: string | undefined = params.data?.drilldownUrl;
+    if (!drilldownUrl) return;
+
+    window.open(drilldownUrl, "_blank");
+  });
+}
+
 async function updateCategoryChart(params: URLSearchParams): Promise<void> {
   const container = document.getElementById("category-chart");
   if (!container) return;
@@ -252,14 +337,20 @@ async function updateCategoryChart(params: URLSearchParams): Promise<void> {
       trigger: "item",
       formatter: (params: any) => {
         const value = params.value;
+        const count: number | undefined = params.data?.transactionCount;
+        const countLine =
+          count != null
+            ? `<br/><span style="font-size:0.85em;opacity:0.7">${count} transaction${count === 1 ? "" : "s"}</span>`
+            : "";
         if (value == null) {
-          return `<strong>${params.name}</strong>`;
+          return `<strong>${params.name}</strong>${countLine}`;
         }
         const amount = formatCurrency(value * 100);
         const perMonth = formatCurrency((value * 100) / months);
         return (
           `${params.name}: ${amount}` +
-          `<br/><span style="font-size:0.85em;opacity:0.7">${perMonth}/mo</span>`
+          `<br/><span style="font-size:0.85em;opacity:0.7">${perMonth}/mo</span>` +
+          countLine
         );
       },
     },
@@ -288,6 +379,7 @@ async function updateCategoryChart(params: URLSearchParams): Promise<void> {
 
   activeChart.setOption(option);
   setupCategoryShiftClick(data.from_date, data.to_date);
+  setupCategoryDeepLink();
 }
 
 async function updateTimeChart(params: URLSearchParams): Promise<void> {
@@ -481,6 +573,11 @@ async function updateMonthlyChart(params: URLSearchParams): Promise<void> {
             `Total: ${formatCurrency(item.total_cents)}`,
             `Transactions: ${item.transaction_count}`,
             `Average: ${formatCurrency(item.average_cents)}`,
+            `Income: ${formatCurrency(item.income_cents)} / Expenses: ${formatCurrency(item.expense_cents)}`,
+            `Net: ${formatCurrency(item.net_cents)}` +
+              (item.savings_rate_formatted != null
+                ? ` (${item.savings_rate_formatted} saved)`
+                : ""),
           ].join("<br/>");
         },
       },
@@ -602,9 +699,13 @@ async function updateFlowChart(params: URLSearchParams): Promise<void> {
   const container = document.getElementById("flow-chart");
   if (!container) return;
 
+  const flowParams = new URLSearchParams(params);
+  if (flowLayers) {
+    flowParams.set("layers", flowLayers);
+  }
   const data = await fetchData<SankeyData>(
     "/api/analytics/flow-sankey",
-    params,
+    flowParams,
   );
 
   if (data.nodes.length === 0) {
@@ -674,6 +775,151 @@ async function updateFlowChart(params: URLSearchParams): Promise<void> {
   activeChart.resize();
 }
 
+function comparisonRow(
+  row: CategoryComparison,
+  currency: string,
+  locale: string,
+): string {
+  const money = (cents: number) => formatMoney(cents, currency, locale);
+  // More spending is shown in red, less in green
+  const deltaClass =
+    row.delta_cents > 0
+      ? "text-red-600 dark:text-red-400"
+      : row.delta_cents < 0
+        ? "text-green-600 dark:text-green-400"
+        : "";
+  const sign = row.delta_cents > 0 ? "+" : row.delta_cents < 0 ? "-" : "";
+  const percent = row.percent_change_formatted ?? NO_PERCENT;
+  return (
+    "<tr>" +
+    '<td class="px-6 py-3 text-sm"><span class="inline-flex items-center gap-2">' +
+    `<span class="w-2.5 h-2.5 rounded-full shrink-0" style="background: ${escapeHtml(row.color)}"></span>` +
+    `${escapeHtml(row.category)}</span></td>` +
+    `<td class="px-6 py-3 text-sm text-right tabular-nums">${money(row.current_cents)}</td>` +
+    `<td class="px-6 py-3 text-sm text-right tabular-nums">${money(row.previous_cents)}</td>` +
+    `<td class="px-6 py-3 text-sm text-right tabular-nums ${deltaClass}">${sign}${money(Math.abs(row.delta_cents))}</td>` +
+    `<td class="px-6 py-3 text-sm text-right tabular-nums ${deltaClass}">${percent}</td>` +
+    "</tr>"
+  );
+}
+
+async function updateSpendingComparison(params: URLSearchParams): Promise<void> {
+  const section = document.getElementById("spending-comparison");
+  const body = document.getElementById("spending-comparison-body");
+  if (!section || !body) return;
+  if (!params.get("from_date") || !params.get("to_date")) return;
+
+  const currency = section.dataset.currency || "USD";
+  const locale = section.dataset.locale || "en-US";
+  const data = await fetchData<SpendingComparison>(
+    "/api/analytics/spending-comparison",
+    params,
+  );
+
+  const period = document.getElementById("spending-comparison-period");
+  if (period) {
+    period.textContent = `(${data.previous_from_date} – ${data.previous_to_date})`;
+  }
+  if (data.categories.length === 0) {
+    body.innerHTML =
+      '<tr><td colspan="5" class="px-6 py-4 text-sm text-center text-neutral-500 dark:text-neutral-400">No data for the selected period</td></tr>';
+    return;
+  }
+  const total: CategoryComparison = {
+    category_id: null,
+    category: "Total",
+    color: "transparent",
+    current_cents: data.current_total_cents,
+    previous_cents: data.previous_total_cents,
+    delta_cents: data.current_total_cents - data.previous_total_cents,
+    percent_change: data.total_percent_change,
+    percent_change_formatted: data.total_percent_change_formatted,
+  };
+  body.innerHTML =
+    data.categories.map((row) => comparisonRow(row, currency, locale)).join("") +
+    comparisonRow(total, currency, locale).replace(
+      "<tr>",
+      '<tr class="font-semibold bg-neutral-50 dark:bg-neutral-900">',
+    );
+}
+
+function renderPatternChart(
+  container: HTMLElement,
+  buckets: SpendingPatternBucket[],
+  currency: string,
+  locale: string,
+): any {
+  const chart = echarts.init(container, getTheme());
+  chart.setOption({
+    backgroundColor: "transparent",
+    tooltip: {
+      trigger: "axis",
+      formatter: (params: any) => {
+        const bucket = buckets[params[0].dataIndex];
+        return (
+          `${escapeHtml(bucket.label)}<br/>` +
+          `Total: ${formatMoney(bucket.total_cents, currency, locale)}<br/>` +
+          `Transactions: ${bucket.transaction_count}<br/>` +
+          `Average: ${formatMoney(bucket.average_cents, currency, locale)}`
+        );
+      },
+    },
+    grid: {
+      left: "3%",
+      right: "4%",
+      top: "8%",
+      bottom: "3%",
+      containLabel: true,
+    },
+    xAxis: {
+      type: "category",
+      data: buckets.map((b) => b.label),
+      axisLabel: { interval: 0 },
+    },
+    yAxis: {
+      type: "value",
+      axisLabel: {
+        formatter: (value: number) =>
+          formatMoney(value * 100, currency, locale, 0),
+      },
+    },
+    series: [
+      {
+        type: "bar",
+        itemStyle: { color: "#ef4444" },
+        data: buckets.map((b) => b.total_cents / 100),
+      },
+    ],
+  });
+  return chart;
+}
+
+async function updateSpendingPatterns(params: URLSearchParams): Promise<void> {
+  const section = document.getElementById("spending-patterns");
+  const weekdayContainer = document.getElementById("weekday-chart");
+  const monthDayContainer = document.getElementById("month-day-chart");
+  if (!section || !weekdayContainer || !monthDayContainer) return;
+
+  const currency = section.dataset.currency || "USD";
+  const locale = section.dataset.locale || "en-US";
+  const data = await fetchData<SpendingPatterns>(
+    "/api/analytics/spending-patterns",
+    params,
+  );
+
+  patternCharts.forEach((chart) => chart.dispose());
+  patternCharts = [];
+  // Weekday labels are short enough once abbreviated to three letters
+  const weekdays = data.weekdays.map((b) => ({
+    ...b,
+    label: b.label.slice(0, 3),
+  }));
+  patternCharts.push(
+    renderPatternChart(weekdayContainer, weekdays, currency, locale),
+    renderPatternChart(monthDayContainer, data.month_days, currency, locale),
+  );
+}
+
 function getMonthSpan(fromDate?: string, toDate?: string): number {
   const fromStr =
     fromDate ||
@@ -706,6 +952,13 @@ async function updateCharts(): Promise<void> {
   const tabEl = document.querySelector("[data-active-tab]");
   const activeTab = tabEl?.getAttribute("data-active-tab") || "category";
 
+  updateSpendingComparison(params).catch((error) =>
+    console.error("Failed to update spending comparison:", error),
+  );
+  updateSpendingPatterns(params).catch((error) =>
+    console.error("Failed to update spending patterns:", error),
+  );
+
   try {
     if (activeTab === "category") {
       await updateCategoryChart(params);
@@ -721,8 +974,120 @@ async function updateCharts(): Promise<void> {
   }
 }
 
+// Draw one saved chart from the data URL the server built for it
+async function renderSavedChart(
+  container: HTMLElement,
+  currency: string,
+  locale: string,
+): Promise<void> {
+  const url = container.dataset.url;
+  if (!url) return;
+  const response = await fetch(url);
+  if (!response.ok) throw new Error("Failed to fetch data");
+  const money = (value: number) =>
+    formatMoney(value * 100, currency, locale, 0);
+  const noData = () => {
+    container.innerHTML =
+      '<div class="flex items-center justify-center h-full text-neutral-400 dark:text-neutral-500 text-sm">' +
+      "No data for the selected period" +
+      "</div>";
+  };
+
+  let option: any;
+  const chartType = container.dataset.chartType;
+  if (chartType === "monthly-by-category") {
+    const data: MonthlyByCategoryResponse = await response.json();
+    if (data.series.length === 0) return noData();
+    option = {
+      tooltip: { trigger: "axis", axisPointer: { type: "shadow" } },
+      legend: { bottom: 0 },
+      xAxis: { type: "category", data: data.months },
+      series: data.series.map((s) => ({
+        name: s.category,
+        type: "bar",
+        itemStyle: { color: s.color, borderRadius: [4, 4, 0, 0] },
+        data: s.totals.map((v) => v / 100),
+      })),
+    };
+  } else if (chartType === "spending-over-time") {
+    const data: TimeSeriesData[] = await response.json();
+    if (data.length === 0) return noData();
+    option = {
+      tooltip: { trigger: "axis" },
+      xAxis: {
+        type: "category",
+        boundaryGap: false,
+        data: data.map((d) => d.date),
+      },
+      series: [
+        {
+          type: "line",
+          smooth: true,
+          areaStyle: { opacity: 0.1 },
+          itemStyle: { color: "#22c55e" },
+          data: data.map((d) => d.amount_cents / 100),
+        },
+      ],
+    };
+  } else if (chartType === "comparison") {
+    const data: SpendingComparison = await response.json();
+    if (data.categories.length === 0) return noData();
+    option = {
+      tooltip: { trigger: "axis", axisPointer: { type: "shadow" } },
+      legend: { bottom: 0 },
+      xAxis: {
+        type: "category",
+        data: data.categories.map((c) => c.category),
+      },
+      series: [
+        {
+          name: `${data.previous_from_date} – ${data.previous_to_date}`,
+          type: "bar",
+          itemStyle: { color: "#a3a3a3" },
+          data: data.categories.map((c) => c.previous_cents / 100),
+        },
+        {
+          name: `${data.from_date} – ${data.to_date}`,
+          type: "bar",
+          data: data.categories.map((c) => ({
+            value: c.current_cents / 100,
+            itemStyle: { color: c.color },
+          })),
+        },
+      ],
+    };
+  } else {
+    return;
+  }
+
+  const chart = echarts.init(container, getTheme());
+  chart.setOption({
+    backgroundColor: "transparent",
+    grid: { left: "3%", right: "4%", bottom: "12%", containLabel: true },
+    yAxis: { type: "value", axisLabel: { formatter: money } },
+    ...option,
+  });
+  savedCharts.push(chart);
+}
+
+function renderSavedCharts(): void {
+  const section = document.getElementById("saved-charts");
+  if (!section) return;
+  const currency = section.dataset.currency || "USD";
+  const locale = section.dataset.locale || "en-US";
+  section
+    .querySelectorAll<HTMLElement>(".saved-chart")
+    .forEach((container) =>
+      renderSavedChart(container, currency, locale).catch((error) =>
+        console.error("Failed to render saved chart:", error),
+      ),
+    );
+}
+
 function handleResize(): void {
   if (activeChart) activeChart.resize();
+  patternCharts.forEach((chart) => chart.resize());
+  savedCharts.forEach((chart) => chart.resize());
 }
 
 // Update a single URL param via replaceState, omitting defaults.
@@ -957,7 +1322,9 @@ function filterCategoryDropdown(): void {
   const dropdown = document.getElementById("category-filter-dropdown");
   if (!dropdown) return;
 
-  const rootName = monthlyMode === "income" ? "Income" : "Expenses";
+  // Net mode covers both roots
+  const rootName =
+    monthlyMode === "net" ? null : MODE_LABELS[monthlyMode] || "Expenses";
 
   let anyChanged = false;
   const boxes =
@@ -966,7 +1333,7 @@ function filterCategoryDropdown(): void {
     const lbl = cb.closest("label") as HTMLElement | null;
     if (!lbl) continue;
 
-    if (lbl.dataset.root === rootName) {
+    if (rootName === null || lbl.dataset.root === rootName) {
       lbl.style.removeProperty("display");
     } else {
       lbl.style.setProperty("display", "none", "important");
@@ -1023,10 +1390,14 @@ function setupCategoryModeFilter(): void {
       }
       categoryMode = mode;
       setUrlParam("category_mode", mode, "expenses");
-      label.textContent = mode === "income" ? "Income" : "Expenses";
+      label.textContent = MODE_LABELS[mode] || "Expenses";
       if (title) {
         title.textContent =
-          mode === "income" ? "Income by Category" : "Spending by Category";
+          mode === "income"
+            ? "Income by Category"
+            : mode === "net"
+              ? "Net by Category"
+              : "Spending by Category";
       }
       dropdown.classList.add("hidden");
       updateNavLinks();
@@ -1035,6 +1406,23 @@ function setupCategoryModeFilter(): void {
   }
 }
 
+function setupFlowLayersToggle(): void {
+  const toggle = document.getElementById(
+    "flow-accounts-toggle",
+  ) as HTMLInputElement | null;
+  if (!toggle) return;
+
+  toggle.checked = flowLayers === "accounts";
+  toggle.addEventListener("change", () => {
+    flowLayers = toggle.checked ? "accounts" : "";
+    setUrlParam("flow_layers", flowLayers, "");
+    updateNavLinks();
+    updateFlowChart(getFilterParams()).catch((error) =>
+      console.error("Failed to update chart:", error),
+    );
+  });
+}
+
 function setupMonthlyModeFilter(): void {
   const btn = document.getElementById("monthly-mode-btn");
   const dropdown = document.getElementById("monthly-mode-dropdown");
@@ -1066,10 +1454,14 @@ function setupMonthlyModeFilter(): void {
       }
       monthlyMode = mode;
       setUrlParam("monthly_mode", mode, "expenses");
-      label.textContent = mode === "income" ? "Income" : "Expenses";
+      label.textContent = MODE_LABELS[mode] || "Expenses";
       if (title) {
         title.textContent =
-          mode === "income" ? "Monthly Income" : "Monthly Summary";
+          mode === "income"
+            ? "Monthly Income"
+            : mode === "net"
+              ? "Monthly Net"
+              : "Monthly Summary";
       }
       dropdown.classList.add("hidden");
       updateNavLinks();
@@ -1086,8 +1478,10 @@ document.addEventListener("DOMContentLoaded", () => {
     setupCategoryFilter();
     setupCategoryModeFilter();
     setupMonthlyModeFilter();
+    setupFlowLayersToggle();
     filterCategoryDropdown();
     updateCharts();
+    renderSavedCharts();
     window.addEventListener("resize", handleResize);
   }
 });
@@ -1,3 +1,5 @@
+import { escapeHtml } from "./utils";
+
 // XSRF token handling
 const XSRF_HEADER = "X-XSRF-Token";
 const XSRF_FORM_FIELD = "_xsrf_token";
@@ -155,6 +157,26 @@ function showToast(message: string, options: ToastOptions = {}): void {
 // Make available globally
 (window as unknown as Record<string, unknown>).showToast = showToast;
 
+interface ToastEventDetail {
+  message: string;
+  type?: ToastOptions["type"];
+}
+
+// Flash messages rendered by the server for this page load, and toasts
+// requested by HTMX responses via the HX-Trigger header
+function initFlashMessages(): void {
+  document.querySelectorAll<HTMLElement>("[data-flash-message]").forEach((el) => {
+    const type = el.dataset.flashType as ToastOptions["type"];
+    showToast(escapeHtml(el.dataset.flashMessage || ""), { type });
+    el.remove();
+  });
+
+  document.body.addEventListener("showToast", (event: Event) => {
+    const detail = (event as CustomEvent<ToastEventDetail>).detail;
+    showToast(escapeHtml(detail.message), { type: detail.type });
+  });
+}
+
 function initTheme(): void {
   const stored = localStorage.getItem("theme");
   const systemDark = window.matchMedia("(prefers-color-scheme: dark)").matches;
@@ -368,10 +390,13 @@ function initConfirmModal(): void {
     const message = el.getAttribute("data-confirm-modal");
     if (!message) return;
 
+    const detail = (event as CustomEvent).detail;
+    // The follow-up request of a two-step delete was already confirmed
+    if (CONFIRM_TOKEN_PARAM.test(detail.path)) return;
+
     event.preventDefault();
     const title = el.getAttribute("data-confirm-title") || "Confirm";
     const action = el.getAttribute("data-confirm-action") || "Confirm";
-    const detail = (event as CustomEvent).detail;
     openModal(title, message, action, () => {
       detail.issueRequest(true);
     });
@@ -400,6 +425,10 @@ function initHtmx(): void {
 
   document.body.addEventListener("htmx:beforeSwap", (event: Event) => {
     const detail = (event as CustomEvent).detail;
+    if (detail.xhr.status === 409 && repeatWithConfirmToken(detail)) {
+      detail.shouldSwap = false;
+      return;
+    }
     // Swap error responses so the server-rendered error HTML is shown to the user.
     if (detail.xhr.status >= 400) {
       detail.shouldSwap = true;
@@ -408,6 +437,51 @@ function initHtmx(): void {
   });
 }
 
+const CONFIRM_TOKEN_PARAM = /[?&]confirm=/;
+
+// Delete-all endpoints answer 409 with a one-time token and a message saying
+// what would be deleted. Show the message and repeat the request with the
+// token only once the user accepts; the response then reaches the element's
+// own after-request handler as usual.
+function repeatWithConfirmToken(detail: {
+  xhr: XMLHttpRequest;
+  elt: HTMLElement;
+  requestConfig: { verb: string; path: string };
+}): boolean {
+  let token: unknown;
+  let message: unknown;
+  try {
+    ({ confirm_token: token, message } = JSON.parse(detail.xhr.responseText));
+  } catch {
+    return false;
+  }
+  const { verb, path } = detail.requestConfig;
+  if (typeof token !== "string" || CONFIRM_TOKEN_PARAM.test(path)) return false;
+
+  const separator = path.includes("?") ? "&" : "?";
+  const confirmedPath = `${path}${separator}confirm=${encodeURIComponent(token)}`;
+  const resend = () => {
+    const htmx = (window as unknown as { htmx: HtmxApi }).htmx;
+    htmx.ajax(verb.toUpperCase(), confirmedPath, { source: detail.elt, swap: "none" });
+  };
+
+  const text = typeof message === "string" ? message : "Delete all records?";
+  const openModal = (window as unknown as { openConfirmModal?: ConfirmModalFn }).openConfirmModal;
+  if (openModal) {
+    const title = detail.elt.getAttribute("data-confirm-title") || "Confirm deletion";
+    openModal(title, `${text} This cannot be undone.`, "Delete", resend);
+  } else if (window.confirm(text)) {
+    resend();
+  }
+  return true;
+}
+
+type ConfirmModalFn = (title: string, message: string, action: string, onConfirm: () => void) => void;
+
+interface HtmxApi {
+  ajax(verb: string, path: string, context: { source: HTMLElement; swap: string }): Promise<void>;
+}
+
 // Keyboard shortcuts
 function initKeyboardShortcuts(): void {
   let pendingKey: string | null = null;
@@ -537,7 +611,7 @@ function initColorSelects(): void {
 
 function decorateColorSelect(select: HTMLSelectElement): void {
   function applyDot(): void {
-    const color = select.value;
+    const color = select.value || "transparent";
     const dot = encodeURIComponent(
       `<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><circle cx="5" cy="5" r="5" fill="${color}"/></svg>`,
     );
@@ -550,6 +624,28 @@ function decorateColorSelect(select: HTMLSelectElement): void {
   applyDot();
 }
 
+/**
+ * Export links marked with data-export-with-query take the filters from the
+ * current URL, which htmx updates when filters change without a reload.
+ */
+function initExportLinks(): void {
+  document.addEventListener("click", (event: MouseEvent) => {
+    const link = (event.target as HTMLElement).closest(
+      "a[data-export-with-query]",
+    ) as HTMLAnchorElement | null;
+    if (!link || !window.location.search) return;
+
+    const url = new URL(link.href);
+    const params = new URLSearchParams(window.location.search);
+    params.delete("page");
+    params.delete("format");
+    const format = url.searchParams.get("format");
+    if (format) params.set("format", format);
+    url.search = params.toString();
+    link.href = url.toString();
+  });
+}
+
 function initPreviewTableSort(): void {
   document.addEventListener("click", (event: MouseEvent) => {
     const th = (event.target as HTMLElement).closest(
@@ -624,6 +720,8 @@ document.addEventListener("DOMContentLoaded", () => {
   initColorSelects();
   registerServiceWorker();
   initPreviewTableSort();
+  initExportLinks();
+  initFlashMessages();
 
   // Initialize XSRF protection
   injectXsrfTokenToAllForms();
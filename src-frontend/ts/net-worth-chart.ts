@@ -37,11 +37,52 @@ function computeMilestones(
   return points;
 }
 
+interface ComponentSeries {
+  key: string;
+  label: string;
+  values: number[];
+}
+
 interface NetWorthChartResponse {
   labels: string[];
   net_worth: number[];
-  transaction_component: number[];
-  portfolio_component: number[];
+  components: ComponentSeries[];
+  contributions: number[];
+  growth: number[];
+  /** Requested range clamped to the dates with data, null without data. */
+  from_date: string | null;
+  to_date: string | null;
+}
+
+const COMPONENT_COLORS: Record<string, string> = {
+  cash: "#22c55e",
+  securities: "#f59e0b",
+};
+
+/** Component keys ticked in the checkbox row above the chart. */
+function selectedComponents(): string[] {
+  const boxes = document.querySelectorAll<HTMLInputElement>(
+    '#net-worth-components input[name="components"]',
+  );
+  return Array.from(boxes)
+    .filter((box) => box.checked)
+    .map((box) => box.value);
+}
+
+function componentSeries(component: ComponentSeries): any {
+  const color = COMPONENT_COLORS[component.key] || "#64748b";
+  return {
+    name: component.label,
+    type: "line",
+    stack: "components",
+    smooth: true,
+    lineStyle: { width: 1, color },
+    itemStyle: { color },
+    areaStyle: { opacity: isDarkMode() ? 0.35 : 0.25 },
+    symbol: "none",
+    data: component.values.map((c) => c / 100),
+    z: 2,
+  };
 }
 
 interface AllocationNode {
@@ -118,12 +159,22 @@ async function loadNetWorthChart(): Promise<void> {
   const locale = container.dataset.locale || "en-US";
 
   try {
-    const response = await fetch("/api/net-worth/chart");
+    const params = new URLSearchParams();
+    if (container.dataset.fromDate) params.set("from_date", container.dataset.fromDate);
+    if (container.dataset.toDate) params.set("to_date", container.dataset.toDate);
+    params.set("components", selectedComponents().join(","));
+    const response = await fetch(`/api/net-worth/chart?${params}`);
     if (!response.ok) throw new Error("Failed to fetch data");
 
     const data: NetWorthChartResponse = await response.json();
     chartLabels = data.labels;
 
+    const rangeLabel = document.getElementById("net-worth-range");
+    if (rangeLabel) {
+      rangeLabel.textContent =
+        data.from_date && data.to_date ? `Showing ${data.from_date} to ${data.to_date}` : "";
+    }
+
     if (netWorthChart) {
       netWorthChart.dispose();
     }
@@ -132,8 +183,9 @@ async function loadNetWorthChart(): Promise<void> {
 
     // Convert cents to dollars for display
     const netWorthDollars = data.net_worth.map((c) => c / 100);
-    const transactionDollars = data.transaction_component.map((c) => c / 100);
-    const portfolioDollars = data.portfolio_component.map((c) => c / 100);
+    const componentLabels = data.components.map((c) => c.label);
+    const contributionsDollars = data.contributions.map((c) => c / 100);
+    const growthDollars = data.growth.map((c) => c / 100);
 
     const milestonePoints = computeMilestones(data.labels, netWorthDollars, currency);
 
@@ -157,12 +209,18 @@ async function loadNetWorthChart(): Promise<void> {
         },
       },
       legend: {
-        data: ["Net Worth", "Transactions (Cumulative)", "Portfolio Value", "Milestones"],
+        data: [
+          "Net Worth",
+          ...componentLabels,
+          "Contributions",
+          "Growth",
+          "Milestones",
+        ],
         top: 0,
         selected: {
           "Net Worth": true,
-          "Transactions (Cumulative)": false,
-          "Portfolio Value": false,
+          "Contributions": false,
+          "Growth": false,
           "Milestones": true,
         },
       },
@@ -250,36 +308,37 @@ async function loadNetWorthChart(): Promise<void> {
           data: netWorthDollars,
           z: 3,
         },
+        ...data.components.map(componentSeries),
         {
-          name: "Transactions (Cumulative)",
+          name: "Contributions",
           type: "line",
           smooth: true,
           lineStyle: {
             width: 1.5,
-            color: "#22c55e",
-            type: "dashed",
+            color: "#64748b",
+            type: "dotted",
           },
           itemStyle: {
-            color: "#22c55e",
+            color: "#64748b",
           },
           symbol: "none",
-          data: transactionDollars,
-          z: 2,
+          data: contributionsDollars,
+          z: 1,
         },
         {
-          name: "Portfolio Value",
+          name: "Growth",
           type: "line",
           smooth: true,
           lineStyle: {
             width: 1.5,
-            color: "#f59e0b",
-            type: "dashed",
+            color: "#14b8a6",
+            type: "dotted",
           },
           itemStyle: {
-            color: "#f59e0b",
+            color: "#14b8a6",
           },
           symbol: "none",
-          data: portfolioDollars,
+          data: growthDollars,
           z: 1,
         },
         {
@@ -477,6 +536,9 @@ document.addEventListener("DOMContentLoaded", () => {
   const chartElement = document.getElementById("net-worth-chart");
   if (chartElement) {
     loadNetWorthChart();
+    document
+      .getElementById("net-worth-components")
+      ?.addEventListener("change", () => loadNetWorthChart());
   }
 
   const allocationElement = document.getElementById("allocation-chart");
@@ -5,6 +5,7 @@ import { getCurrencySymbol, isDarkMode, getTheme } from "./utils";
 interface PriceData {
   date: string;
   price_cents: number;
+  value_cents?: number;
 }
 
 interface ActivityMarker {
@@ -15,19 +16,54 @@ interface ActivityMarker {
   total_cents: number;
 }
 
+interface CostBasisPoint {
+  date: string;
+  avg_cost_cents: number | null;
+  cost_cents: number | null;
+}
+
+interface AppliedRange {
+  range: string | null;
+  from_date: string | null;
+  to_date: string | null;
+}
+
+type ChartSeries = "price" | "value";
+
 interface ChartResponse {
   symbol: string;
+  series: ChartSeries;
+  range: AppliedRange;
   data: PriceData[];
+  cost_basis: CostBasisPoint[];
+  break_even_cents: number | null;
   activities: ActivityMarker[];
   is_approximated: boolean;
 }
 
 let positionChart: any = null;
+let selectedRange = "max";
+let selectedSeries: ChartSeries = "price";
 
 function formatQuantity(qty: number): string {
   return qty.toFixed(4).replace(/\.?0+$/, "");
 }
 
+/** Highlight the buttons of the range and series the server applied. */
+function markActiveButtons(applied: ChartResponse): void {
+  const toggle = (button: HTMLElement, active: boolean) => {
+    button.classList.toggle("btn-primary", active);
+    button.classList.toggle("btn-secondary", !active);
+    button.setAttribute("aria-pressed", String(active));
+  };
+  document.querySelectorAll<HTMLElement>("[data-chart-range]").forEach((button) => {
+    toggle(button, button.dataset.chartRange === applied.range.range);
+  });
+  document.querySelectorAll<HTMLElement>("[data-chart-series]").forEach((button) => {
+    toggle(button, button.dataset.chartSeries === applied.series);
+  });
+}
+
 async function loadPositionChart(symbol: string): Promise<void> {
   const container = document.getElementById("position-chart");
   if (!container) return;
@@ -36,10 +72,14 @@ async function loadPositionChart(symbol: string): Promise<void> {
   const sym = getCurrencySymbol(currency);
 
   try {
-    const response = await fetch(`/api/positions/${encodeURIComponent(symbol)}/chart`);
+    const params = new URLSearchParams({ range: selectedRange, series: selectedSeries });
+    const response = await fetch(`/api/positions/${encodeURIComponent(symbol)}/chart?${params}`);
     if (!response.ok) throw new Error("Failed to fetch data");
 
     const chartData: ChartResponse = await response.json();
+    markActiveButtons(chartData);
+    const isValue = chartData.series === "value";
+    const pointValue = (d: PriceData) => (isValue ? (d.value_cents ?? 0) : d.price_cents) / 100;
 
     if (positionChart) {
       positionChart.dispose();
@@ -47,15 +87,15 @@ async function loadPositionChart(symbol: string): Promise<void> {
 
     positionChart = echarts.init(container, getTheme());
 
-    // Create a map of date -> price for quick lookup
+    // Create a map of date -> plotted value for quick lookup
     const priceMap = new Map<string, number>();
     for (const d of chartData.data) {
-      priceMap.set(d.date, d.price_cents / 100);
+      priceMap.set(d.date, pointValue(d));
     }
 
     // Create markPoint data for buy/sell activities
     const markPointData = chartData.activities.map((activity) => {
-      const priceAtDate = priceMap.get(activity.date) ?? activity.price_cents / 100;
+      const priceAtDate = priceMap.get(activity.date) ?? (isValue ? 0 : activity.price_cents / 100);
       const isBuy = activity.activity_type === "BUY";
 
       return {
@@ -73,7 +113,15 @@ async function loadPositionChart(symbol: string): Promise<void> {
     });
 
     const dates = chartData.data.map((d) => d.date);
-    const prices = chartData.data.map((d) => d.price_cents / 100);
+    const prices = chartData.data.map(pointValue);
+    // null where no shares were held, which breaks the line. The value chart
+    // compares against the cost of all shares held instead of per share.
+    const avgCosts = chartData.cost_basis.map((p) => {
+      const cents = isValue ? p.cost_cents : p.avg_cost_cents;
+      return cents === null ? null : cents / 100;
+    });
+    const breakEven =
+      isValue || chartData.break_even_cents === null ? null : chartData.break_even_cents / 100;
     const showSymbols = chartData.data.length <= 100;
 
     // Create activity lookup map for tooltip
@@ -93,21 +141,25 @@ async function loadPositionChart(symbol: string): Promise<void> {
           const point = params[0];
           const date = point.axisValue;
           const price = sym + point.value.toFixed(2);
+          const lines = [`<strong>${date}</strong>`, `${isValue ? "Value" : "Price"}: ${price}`];
+
+          const avgCost = params.find((p: any) => p.seriesIndex === 1)?.value;
+          if (avgCost !== null && avgCost !== undefined) {
+            lines.push(`${isValue ? "Cost" : "Avg cost"}: ${sym + avgCost.toFixed(2)}`);
+          }
 
           const activity = activityMap.get(date);
           if (activity) {
             const type = activity.activity_type === "BUY" ? "Buy" : "Sell";
             const qty = formatQuantity(activity.quantity);
             const total = sym + (activity.total_cents / 100).toFixed(2);
-            return [
-              `<strong>${date}</strong>`,
-              `Price: ${price}`,
+            lines.push(
               `${type}: ${qty} shares @ ${sym + (activity.price_cents / 100).toFixed(2)}`,
               `Total: ${total}`,
-            ].join("<br/>");
+            );
           }
 
-          return `<strong>${date}</strong><br/>Price: ${price}`;
+          return lines.join("<br/>");
         },
       },
       grid: {
@@ -133,7 +185,7 @@ async function loadPositionChart(symbol: string): Promise<void> {
       },
       series: [
         {
-          name: `${symbol} Price`,
+          name: isValue ? `${symbol} Value` : `${symbol} Price`,
           type: "line",
           smooth: !chartData.is_approximated,
           step: chartData.is_approximated ? "end" : false,
@@ -157,14 +209,39 @@ async function loadPositionChart(symbol: string): Promise<void> {
             },
           },
         },
+        {
+          name: isValue ? "Cost" : "Average Cost",
+          type: "line",
+          step: "end",
+          connectNulls: false,
+          lineStyle: {
+            width: 1.5,
+            type: "dashed",
+            color: "#8b5cf6",
+          },
+          itemStyle: {
+            color: "#8b5cf6",
+          },
+          symbol: "none",
+          data: avgCosts,
+          markLine:
+            breakEven === null
+              ? undefined
+              : {
+                  symbol: "none",
+                  silent: true,
+                  lineStyle: { type: "dotted", color: "#8b5cf6" },
+                  label: {
+                    formatter: () => `Break-even ${sym + breakEven.toFixed(2)}`,
+                    position: "insideEndTop",
+                  },
+                  data: [{ yAxis: breakEven }],
+                },
+        },
       ],
     };
 
     positionChart.setOption(option);
-
-    window.addEventListener("resize", () => {
-      if (positionChart) positionChart.resize();
-    });
   } catch (error) {
     console.error("Failed to load position chart:", error);
     container.innerHTML = `
@@ -181,6 +258,23 @@ document.addEventListener("DOMContentLoaded", () => {
     const symbol = chartElement.dataset.symbol;
     if (symbol) {
       loadPositionChart(symbol);
+
+      document.querySelectorAll<HTMLElement>("[data-chart-range]").forEach((button) => {
+        button.addEventListener("click", () => {
+          selectedRange = button.dataset.chartRange ?? "max";
+          loadPositionChart(symbol);
+        });
+      });
+      document.querySelectorAll<HTMLElement>("[data-chart-series]").forEach((button) => {
+        button.addEventListener("click", () => {
+          selectedSeries = button.dataset.chartSeries === "value" ? "value" : "price";
+          loadPositionChart(symbol);
+        });
+      });
     }
   }
+
+  window.addEventListener("resize", () => {
+    if (positionChart) positionChart.resize();
+  });
 });
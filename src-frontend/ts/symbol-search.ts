@@ -0,0 +1,109 @@
+// Symbol autocomplete for the trading activity forms.
+// Queries /api/symbols/search while typing and fills the input's datalist with
+// matching Yahoo tickers. Picking a result stores its metadata server-side.
+// When Yahoo is unreachable the input keeps working as plain text entry.
+
+interface SymbolSearchResult {
+  symbol: string;
+  name: string | null;
+  exchange: string;
+  quote_type: string;
+}
+
+interface SymbolSearchResponse {
+  results: SymbolSearchResult[];
+  available: boolean;
+}
+
+const DEBOUNCE_MS = 250;
+// Slightly above the server's per-session minimum interval.
+const THROTTLE_RETRY_MS = 350;
+const MIN_QUERY_LENGTH = 2;
+
+function getXsrfToken(): string {
+  const meta = document.querySelector('meta[name="xsrf-token"]');
+  return meta?.getAttribute("content") ?? "";
+}
+
+function resultLabel(result: SymbolSearchResult): string {
+  const parts = [result.name, result.exchange, result.quote_type];
+  return parts.filter((p) => p).join(" · ");
+}
+
+function initSymbolSearch(input: HTMLInputElement): void {
+  const datalist = input.list;
+  if (!datalist) return;
+
+  // Symbols already in use stay suggested alongside search results.
+  const knownOptions = Array.from(datalist.options).map((o) =>
+    o.cloneNode(true),
+  );
+  let results: SymbolSearchResult[] = [];
+  let debounceTimer: ReturnType<typeof setTimeout> | null = null;
+  let offline = false;
+
+  function render(): void {
+    datalist!.replaceChildren(...knownOptions.map((o) => o.cloneNode(true)));
+    for (const result of results) {
+      const option = document.createElement("option");
+      option.value = result.symbol;
+      option.label = resultLabel(result);
+      datalist!.appendChild(option);
+    }
+  }
+
+  async function search(query: string): Promise<void> {
+    try {
+      const resp = await fetch(
+        `/api/symbols/search?q=${encodeURIComponent(query)}`,
+      );
+      if (resp.status === 429) {
+        debounceTimer = setTimeout(() => search(query), THROTTLE_RETRY_MS);
+        return;
+      }
+      if (!resp.ok) return;
+
+      const data: SymbolSearchResponse = await resp.json();
+      if (!data.available) {
+        offline = true;
+        return;
+      }
+      // Ignore responses for queries the user has already typed past.
+      if (input.value.trim() !== query) return;
+      results = data.results;
+      render();
+    } catch {
+      offline = true;
+    }
+  }
+
+  function select(symbol: string): void {
+    const match = results.find(
+      (r) => r.symbol.toUpperCase() === symbol.toUpperCase(),
+    );
+    if (!match) return;
+    input.value = match.symbol;
+
+    const body = new URLSearchParams({ symbol: match.symbol });
+    fetch("/api/symbols/select", {
+      method: "POST",
+      headers: { "X-XSRF-Token": getXsrfToken() },
+      body,
+    }).catch(() => {});
+  }
+
+  input.addEventListener("input", () => {
+    if (debounceTimer) clearTimeout(debounceTimer);
+    const query = input.value.trim();
+    if (offline || query.length < MIN_QUERY_LENGTH) return;
+    debounceTimer = setTimeout(() => search(query), DEBOUNCE_MS);
+  });
+
+  input.addEventListener("change", () => select(input.value.trim()));
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  document
+    .querySelectorAll<HTMLInputElement>("input[data-symbol-search]")
+    .forEach(initSymbolSearch);
+});
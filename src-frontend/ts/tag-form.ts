@@ -1,5 +1,7 @@
 // Tag form: color/style dropdowns + live badge preview
 
+import { escapeHtml } from "./utils";
+
 function initTagForm(): void {
   const form = document.getElementById("tag-form");
   if (!form) return;
@@ -89,10 +91,4 @@ function ghostTextColor(hex: string): string {
   return `#${r.toString(16).padStart(2, "0")}${g.toString(16).padStart(2, "0")}${b.toString(16).padStart(2, "0")}`;
 }
 
-function escapeHtml(s: string): string {
-  const el = document.createElement("span");
-  el.textContent = s;
-  return el.innerHTML;
-}
-
 document.addEventListener("DOMContentLoaded", initTagForm);
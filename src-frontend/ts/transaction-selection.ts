@@ -0,0 +1,115 @@
+// Transactions table: row checkboxes + bulk action bar for the selected rows.
+// The selection lives in sessionStorage so it survives pagination, sorting
+// and filtering; the bar posts it as repeated `transaction_ids[]` fields.
+
+const STORAGE_KEY = "solvency.transactions.selected";
+const IDS_FIELD = "transaction_ids[]";
+// Mirrors MAX_BULK_IDS in src/handlers/transactions.rs
+const MAX_SELECTED = 1000;
+
+function loadSelection(): Set<string> {
+  try {
+    const raw = sessionStorage.getItem(STORAGE_KEY);
+    return new Set(raw ? (JSON.parse(raw) as string[]) : []);
+  } catch {
+    return new Set();
+  }
+}
+
+function saveSelection(selection: Set<string>): void {
+  sessionStorage.setItem(STORAGE_KEY, JSON.stringify([...selection]));
+}
+
+function initTransactionSelection(): void {
+  const form = document.getElementById("bulk-selection-bar") as HTMLFormElement | null;
+  if (!form) return;
+  const bar: HTMLFormElement = form;
+
+  const selection = loadSelection();
+
+  function rowBoxes(): HTMLInputElement[] {
+    return Array.from(document.querySelectorAll<HTMLInputElement>("#transaction-table .row-select"));
+  }
+
+  // Reflect the selection in the bar: count, visibility and hidden id fields.
+  function renderBar(): void {
+    bar.classList.toggle("hidden", selection.size === 0);
+    bar.classList.toggle("flex", selection.size > 0);
+    const count = bar.querySelector("[data-selection-count]");
+    if (count) {
+      count.textContent =
+        selection.size > MAX_SELECTED ? `${selection.size} (max ${MAX_SELECTED})` : String(selection.size);
+    }
+    for (const input of bar.querySelectorAll(`input[name="${IDS_FIELD}"]`)) {
+      input.remove();
+    }
+    for (const id of selection) {
+      const input = document.createElement("input");
+      input.type = "hidden";
+      input.name = IDS_FIELD;
+      input.value = id;
+      bar.appendChild(input);
+    }
+  }
+
+  // Check the rows of the current page that are in the selection.
+  function syncRows(): void {
+    const boxes = rowBoxes();
+    for (const box of boxes) {
+      box.checked = selection.has(box.value);
+    }
+    const pageBox = document.getElementById("select-page-rows") as HTMLInputElement | null;
+    if (pageBox) {
+      pageBox.checked = boxes.length > 0 && boxes.every((box) => box.checked);
+    }
+  }
+
+  function update(): void {
+    saveSelection(selection);
+    syncRows();
+    renderBar();
+  }
+
+  document.addEventListener("change", (event: Event) => {
+    const target = event.target as HTMLInputElement;
+    if (target.classList.contains("row-select")) {
+      if (target.checked) selection.add(target.value);
+      else selection.delete(target.value);
+      update();
+    } else if (target.id === "select-page-rows") {
+      for (const box of rowBoxes()) {
+        if (target.checked) selection.add(box.value);
+        else selection.delete(box.value);
+      }
+      update();
+    }
+  });
+
+  bar.querySelector("[data-selection-clear]")?.addEventListener("click", () => {
+    selection.clear();
+    update();
+  });
+
+  // Sorting and filtering swap the table without a page load.
+  document.body.addEventListener("htmx:afterSwap", (event: Event) => {
+    const target = (event as CustomEvent).detail.target as HTMLElement;
+    if (target.id === "transaction-table") syncRows();
+  });
+
+  // Deleted rows leave the selection; other actions keep it so several
+  // changes can be applied to the same rows.
+  bar.addEventListener("htmx:afterRequest", (event: Event) => {
+    const detail = (event as CustomEvent).detail;
+    if (!detail.successful) return;
+    const path: string = detail.requestConfig?.path ?? "";
+    if (path.endsWith("/delete")) {
+      selection.clear();
+      saveSelection(selection);
+    }
+    window.location.reload();
+  });
+
+  update();
+}
+
+document.addEventListener("DOMContentLoaded", initTransactionSelection);
@@ -1,5 +1,12 @@
-// Shared utilities for chart files. Imported by each chart entry point;
-// esbuild inlines this into each bundle.
+// Shared utilities for the entry points; esbuild inlines this into each
+// bundle.
+
+// Escape text for use in HTML built from strings.
+export function escapeHtml(text: string): string {
+  const div = document.createElement("div");
+  div.textContent = text;
+  return div.innerHTML;
+}
 
 export function isDarkMode(): boolean {
   return document.documentElement.classList.contains("dark");
@@ -9,6 +16,10 @@ export function getTheme(): string | undefined {
   return isDarkMode() ? "dark" : undefined;
 }
 
+// Placeholder for a percentage that cannot be shown; mirrors
+// filters.rs::NO_PERCENT — keep in sync.
+export const NO_PERCENT = "\u2014";
+
 // Symbol table mirrors filters.rs::currency_symbol() — keep in sync.
 const CURRENCY_SYMBOLS: Record<string, string> = {
   USD: "$",
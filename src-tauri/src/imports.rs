@@ -0,0 +1,186 @@
+//! Native file imports for the desktop window.
+//!
+//! Files dropped onto the window or picked in the native file dialog are
+//! posted to the embedded router through the same multipart upload
+//! endpoints the web import forms use, and the window is then navigated to
+//! the resulting import wizard page. Whether a file goes to the transaction
+//! or the trading importer is decided by sniffing its CSV header.
+
+use axum::body::Body;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tauri::WebviewWindow;
+use tauri_plugin_dialog::DialogExt;
+use tower::ServiceExt;
+
+const BOUNDARY: &str = "----solvency-desktop-upload";
+
+/// Which import wizard a set of files belongs to.
+struct ImportTarget {
+    upload_path: &'static str,
+    fallback_path: &'static str,
+}
+
+const TRANSACTIONS: ImportTarget = ImportTarget {
+    upload_path: "/import/upload",
+    fallback_path: "/import",
+};
+
+const TRADING: ImportTarget = ImportTarget {
+    upload_path: "/trading/import/upload",
+    fallback_path: "/trading/import",
+};
+
+/// Read the files and build a `multipart/form-data` body with one
+/// `files` part per file, matching the upload forms.
+fn multipart_body(paths: &[PathBuf]) -> std::io::Result<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    let mut is_trading = false;
+    for (i, path) in paths.iter().enumerate() {
+        let content = std::fs::read(path)?;
+        if i == 0 {
+            is_trading = solvency::services::trading_csv_parser::looks_like_trading_csv(&content);
+        }
+        let filename = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("upload.csv")
+            .replace('"', "");
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{filename}\"\r\nContent-Type: text/csv\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+    Ok((body, is_trading))
+}
+
+/// Post the files to the matching upload endpoint and return the page the
+/// window should show next: the new import session, or the upload form if
+/// the upload was rejected.
+async fn upload(router: axum::Router, xsrf_token: &str, paths: &[PathBuf]) -> String {
+    let (body, is_trading) = match multipart_body(paths) {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to read import file");
+            return TRANSACTIONS.fallback_path.to_string();
+        }
+    };
+    let target = if is_trading { TRADING } else { TRANSACTIONS };
+
+    let request = http::Request::post(target.upload_path)
+        .header(
+            http::header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header(solvency::xsrf::XSRF_HEADER, xsrf_token)
+        .body(Body::from(body))
+        .expect("Valid upload request");
+
+    let response = router.oneshot(request).await.expect("Infallible");
+    let location = response
+        .headers()
+        .get(http::header::LOCATION)
+        .and_then(|v| v.to_str().ok());
+    match location {
+        Some(location) if response.status().is_redirection() => location.to_string(),
+        _ => {
+            tracing::warn!(status = %response.status(), "Import file upload was rejected");
+            target.fallback_path.to_string()
+        }
+    }
+}
+
+/// Navigate `window` to a page of the embedded app.
+fn open_wizard(window: &WebviewWindow, location: &str) {
+    match format!("solvency://localhost{location}").parse() {
+        Ok(url) => {
+            if let Err(e) = window.navigate(url) {
+                tracing::error!(error = %e, "Failed to open import wizard");
+            }
+        }
+        Err(e) => tracing::error!(error = %e, "Invalid import wizard URL"),
+    }
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+}
+
+/// Start an import wizard for files dropped onto `window`.
+pub fn register_drop_handler(
+    window: &WebviewWindow,
+    router: Arc<OnceLock<axum::Router>>,
+    xsrf_token: String,
+) {
+    let target_window = window.clone();
+    window.on_window_event(move |event| {
+        let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event else {
+            return;
+        };
+        let paths: Vec<PathBuf> = paths.iter().filter(|p| is_csv(p)).cloned().collect();
+        if paths.is_empty() {
+            return;
+        }
+        let Some(router) = router.get().cloned() else {
+            return;
+        };
+        let window = target_window.clone();
+        let xsrf_token = xsrf_token.clone();
+        tauri::async_runtime::spawn(async move {
+            let location = upload(router, &xsrf_token, &paths).await;
+            open_wizard(&window, &location);
+        });
+    });
+}
+
+/// Router and XSRF token the import commands upload with, managed as app
+/// state.
+pub struct Uploader {
+    pub router: Arc<OnceLock<axum::Router>>,
+    pub xsrf_token: String,
+}
+
+/// Open the native file dialog and start an import wizard for the picked
+/// CSV files, as if they had been dropped onto the window. Cancelling the
+/// dialog leaves the page as it is.
+#[tauri::command]
+pub async fn pick_import_files(
+    window: WebviewWindow,
+    uploader: tauri::State<'_, Uploader>,
+) -> Result<(), String> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    window
+        .dialog()
+        .file()
+        .set_parent(&window)
+        .add_filter("CSV files", &["csv"])
+        .pick_files(move |picked| {
+            let _ = tx.send(picked);
+        });
+    let Ok(Some(picked)) = rx.await else {
+        return Ok(());
+    };
+    let paths: Vec<PathBuf> = picked
+        .into_iter()
+        .filter_map(|file| file.into_path().ok())
+        .filter(|p| is_csv(p))
+        .collect();
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let router = uploader
+        .router
+        .get()
+        .cloned()
+        .ok_or("Router not initialized")?;
+    let location = upload(router, &uploader.xsrf_token, &paths).await;
+    open_wizard(&window, &location);
+    Ok(())
+}
@@ -1,28 +1,47 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod imports;
+
 use http_body_util::BodyExt;
+use solvency::body_limit::DEFAULT_MAX_UPLOAD_MB;
 use solvency::config::{AuthMode, Config};
+use solvency::date_utils;
+use solvency::db::pool::{DEFAULT_CONNECTION_LIFETIME_SECS, DEFAULT_POOL_SIZE};
+use solvency::db::queries::settings;
+use solvency::logging;
 use solvency::server;
+use solvency::services::backup;
+use solvency::state::AppState;
+use solvency::usage;
 use std::path::PathBuf;
 use std::sync::{Arc, OnceLock};
 use tauri::Manager;
 use tower::ServiceExt;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
+
+/// On first run, default the timezone setting to the operating system's.
+fn default_timezone(state: &AppState) {
+    let Some(timezone) = date_utils::system_timezone() else {
+        return;
+    };
+    match state.db.get() {
+        Ok(conn) => match settings::set_setting_if_missing(&conn, "timezone", &timezone) {
+            Ok(true) => tracing::info!(%timezone, "Timezone set from operating system"),
+            Ok(false) => {}
+            Err(e) => tracing::warn!(error = %e, "Failed to set default timezone"),
+        },
+        Err(e) => tracing::warn!(error = %e, "Failed to set default timezone"),
+    }
+}
 
 fn main() {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "solvency=info,tower_http=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    logging::init("solvency=info,tower_http=info");
 
     let router: Arc<OnceLock<axum::Router>> = Arc::new(OnceLock::new());
 
     let protocol_router = Arc::clone(&router);
     tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .invoke_handler(tauri::generate_handler![imports::pick_import_files])
         .register_asynchronous_uri_scheme_protocol("solvency", move |_ctx, request, responder| {
             let router = Arc::clone(&protocol_router);
             tauri::async_runtime::spawn(async move {
@@ -69,6 +88,7 @@ fn main() {
                 .join("solvency");
             std::fs::create_dir_all(&data_dir).expect("Failed to create data directory");
             let database_path = data_dir.join("solvency.db");
+            let backup_dir = data_dir.join("backups");
 
             let config = Config {
                 host: "127.0.0.1".into(),
@@ -78,6 +98,14 @@ fn main() {
                 static_path,
                 auth_mode: AuthMode::Unauthenticated,
                 secure_cookies: false,
+                slow_query_ms: None,
+                backup_dir: Some(backup_dir),
+                allow_force_delete: false,
+                allow_dirty_migrations: false,
+                max_upload_mb: DEFAULT_MAX_UPLOAD_MB,
+                csp: None,
+                db_pool_size: DEFAULT_POOL_SIZE,
+                db_connection_lifetime_secs: Some(DEFAULT_CONNECTION_LIFETIME_SECS),
             };
 
             tracing::info!(
@@ -86,14 +114,28 @@ fn main() {
                 "Starting embedded Solvency server"
             );
 
-            let (_state, app_router) =
+            let (state, app_router) =
                 server::build_app(config).expect("Failed to build Solvency app");
+            let xsrf_token = state.xsrf_token.value().to_string();
+            default_timezone(&state);
+            let start_path = state
+                .load_settings()
+                .map(|s| s.start_page_path())
+                .unwrap_or("/");
+            tauri::async_runtime::spawn(usage::run_flusher(state.clone()));
+            tauri::async_runtime::spawn(backup::run_scheduler(state));
             router.set(app_router).expect("Router already initialized");
+            app.manage(imports::Uploader {
+                router: Arc::clone(&router),
+                xsrf_token: xsrf_token.clone(),
+            });
 
             let window = tauri::WebviewWindowBuilder::new(
                 app.handle(),
                 "main",
-                tauri::WebviewUrl::CustomProtocol("solvency://localhost".parse().unwrap()),
+                tauri::WebviewUrl::CustomProtocol(
+                    format!("solvency://localhost{start_path}").parse().unwrap(),
+                ),
             )
             .title("Solvency")
             .inner_size(1280.0, 800.0)
@@ -101,7 +143,7 @@ fn main() {
             .build()
             .expect("Failed to create window");
 
-            let _ = window;
+            imports::register_drop_handler(&window, Arc::clone(&router), xsrf_token);
 
             Ok(())
         })
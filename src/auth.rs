@@ -5,17 +5,24 @@
 //! `DANGEROUSLY_ALLOW_UNAUTHENTICATED_USERS` or by not setting it at all.
 //!
 //! Session tokens are cryptographically random UUIDs, validated against a
-//! server-side session store. Tokens are invalidated on logout or server restart.
+//! server-side session store. Tokens are invalidated on logout, server restart,
+//! or once the session TTL from the advanced settings has passed.
+//!
+//! Share links (`/share/:token`) are the only pages reachable without a
+//! session. Requests there are confined to viewing and unlocking the link,
+//! and a share cookie is never accepted as a login session.
 
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use askama::Template;
 use axum::body::Body;
-use axum::extract::State;
-use axum::http::{Request, StatusCode};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{Method, Request, StatusCode};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Redirect, Response};
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Mutex;
 use std::time::Instant;
 use tower_cookies::{Cookie, Cookies};
@@ -27,7 +34,7 @@ use crate::state::{AppState, JsManifest};
 use crate::VERSION;
 
 /// Cookie name for the session token.
-const SESSION_COOKIE: &str = "session";
+pub(crate) const SESSION_COOKIE: &str = "session";
 
 /// Maximum number of failed login attempts before rate-limiting kicks in.
 const MAX_ATTEMPTS: u32 = 5;
@@ -54,7 +61,7 @@ impl LoginRateLimiter {
     }
 
     /// Returns `true` if the IP is currently locked out.
-    fn is_locked_out(&self, ip: &str) -> bool {
+    pub(crate) fn is_locked_out(&self, ip: &str) -> bool {
         let mut map = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
         if let Some((count, first_time)) = map.get(ip) {
             if *count >= MAX_ATTEMPTS {
@@ -69,7 +76,7 @@ impl LoginRateLimiter {
     }
 
     /// Record a failed login attempt for the given IP.
-    fn record_failure(&self, ip: &str) {
+    pub(crate) fn record_failure(&self, ip: &str) {
         let mut map = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
         let entry = map.entry(ip.to_string()).or_insert((0, Instant::now()));
         // Reset window if the previous window has expired
@@ -80,7 +87,7 @@ impl LoginRateLimiter {
     }
 
     /// Clear attempts for the given IP (call on successful login).
-    fn reset(&self, ip: &str) {
+    pub(crate) fn reset(&self, ip: &str) {
         self.attempts
             .lock()
             .unwrap_or_else(|e| e.into_inner())
@@ -96,6 +103,7 @@ pub struct LoginTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub error: Option<String>,
 }
 
@@ -112,6 +120,14 @@ pub async fn auth_middleware(
     request: Request<Body>,
     next: Next,
 ) -> Response {
+    // Share links bypass authentication but may only view or unlock the link
+    if let Some(rest) = request.uri().path().strip_prefix("/share/") {
+        if !is_share_request_allowed(request.method(), rest) {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        return next.run(request).await;
+    }
+
     // Skip auth entirely when no password is configured
     if matches!(state.config.auth_mode, AuthMode::Unauthenticated) {
         return next.run(request).await;
@@ -119,12 +135,19 @@ pub async fn auth_middleware(
 
     // Check for valid session cookie against server-side store
     if let Some(session_cookie) = cookies.get(SESSION_COOKIE) {
-        let token = session_cookie.value().to_string();
-        let is_valid = state
-            .sessions
-            .lock()
-            .unwrap_or_else(|e| e.into_inner())
-            .contains(&token);
+        let ttl = state.load_settings().ok().and_then(|s| s.session_ttl());
+        let is_valid = {
+            let mut sessions = state.sessions.lock().unwrap_or_else(|e| e.into_inner());
+            match sessions.get(session_cookie.value()) {
+                Some(created) if ttl.is_none_or(|ttl| created.elapsed() < ttl) => true,
+                Some(_) => {
+                    // Expired: forget the token so the user has to log in again
+                    sessions.remove(session_cookie.value());
+                    false
+                }
+                None => false,
+            }
+        };
         if is_valid {
             return next.run(request).await;
         }
@@ -148,6 +171,16 @@ pub async fn auth_middleware(
     Redirect::to("/login").into_response()
 }
 
+/// Whether a request below `/share/` stays within a share link's scope:
+/// viewing (`GET`) or unlocking (`POST`) the snapshot page of a single token.
+fn is_share_request_allowed(method: &Method, token_path: &str) -> bool {
+    let is_token = !token_path.is_empty()
+        && token_path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    is_token && matches!(*method, Method::GET | Method::HEAD | Method::POST)
+}
+
 /// Render the login page.
 pub async fn login_page(State(state): State<AppState>) -> impl IntoResponse {
     // If authentication is not required, redirect to home
@@ -160,6 +193,7 @@ pub async fn login_page(State(state): State<AppState>) -> impl IntoResponse {
         manifest: state.manifest.clone(),
         version: VERSION,
         xsrf_token: state.xsrf_token.value().to_string(),
+        csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
         error: None,
     };
 
@@ -169,9 +203,19 @@ pub async fn login_page(State(state): State<AppState>) -> impl IntoResponse {
     }
 }
 
-/// Extract a client identifier from request headers for rate-limiting.
-/// Checks X-Forwarded-For and X-Real-Ip headers, falling back to "unknown".
-fn client_ip(request: &Request<Body>) -> String {
+/// Extract a client identifier for rate-limiting. With `trust_proxy` set,
+/// checks X-Forwarded-For and X-Real-Ip headers first; otherwise only the
+/// peer address is used. Falls back to "unknown".
+pub(crate) fn client_ip(request: &Request<Body>, trust_proxy: bool) -> String {
+    let peer = || {
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip().to_string())
+    };
+    if !trust_proxy {
+        return peer().unwrap_or_else(|| "unknown".to_string());
+    }
     request
         .headers()
         .get("X-Forwarded-For")
@@ -185,6 +229,7 @@ fn client_ip(request: &Request<Body>) -> String {
                 .and_then(|v| v.to_str().ok())
                 .map(|s| s.trim().to_string())
         })
+        .or_else(peer)
         .unwrap_or_else(|| "unknown".to_string())
 }
 
@@ -199,7 +244,11 @@ pub async fn login_submit(
         AuthMode::Password(hash) => hash,
     };
 
-    let ip = client_ip(&request);
+    let trust_proxy = state
+        .load_settings()
+        .map(|s| s.trust_proxy_headers)
+        .unwrap_or(true);
+    let ip = client_ip(&request, trust_proxy);
 
     // Rate-limit check
     if state.login_rate_limiter.is_locked_out(&ip) {
@@ -209,6 +258,7 @@ pub async fn login_submit(
             manifest: state.manifest.clone(),
             version: VERSION,
             xsrf_token: state.xsrf_token.value().to_string(),
+            csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
             error: Some("Too many failed attempts. Please try again later.".into()),
         };
         return match template.render_html() {
@@ -237,7 +287,7 @@ pub async fn login_submit(
             .sessions
             .lock()
             .unwrap_or_else(|e| e.into_inner())
-            .insert(session_token.clone());
+            .insert(session_token.clone(), Instant::now());
 
         // Rotate the XSRF token so it is bound to this session
         state.xsrf_token.regenerate();
@@ -262,6 +312,7 @@ pub async fn login_submit(
         manifest: state.manifest.clone(),
         version: VERSION,
         xsrf_token: state.xsrf_token.value().to_string(),
+        csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
         error: Some("Invalid password".into()),
     };
 
@@ -292,8 +343,17 @@ pub async fn logout(State(state): State<AppState>, cookies: Cookies) -> impl Int
     Redirect::to("/login")
 }
 
+/// Hash a password with Argon2 and a random salt.
+pub(crate) fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>()).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
 /// Verify a password against an Argon2 hash.
-fn verify_password(password: &str, hash: &str) -> bool {
+pub(crate) fn verify_password(password: &str, hash: &str) -> bool {
     let Ok(parsed_hash) = PasswordHash::new(hash) else {
         tracing::error!("Invalid password hash format in PASSWORD_HASH");
         return false;
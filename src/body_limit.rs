@@ -0,0 +1,119 @@
+//! Request body size limits.
+//!
+//! Form routes accept small bodies only. Imports and database restores get
+//! a larger limit, configured with `SOLVENCY_MAX_UPLOAD_MB`. Bodies over a
+//! limit are rejected with a 413 that names the limit, rendered like any
+//! other error: as a page, an HTMX fragment or JSON for API routes.
+
+use std::path::Path;
+
+use axum::body::Body;
+use axum::extract::multipart::{Field, MultipartError};
+use axum::extract::DefaultBodyLimit;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use tokio::io::AsyncWriteExt;
+
+use crate::error::{AppError, AppResult};
+use crate::error_pages::ErrorMessage;
+use crate::state::AppState;
+
+/// Body size limit of form routes.
+pub const FORM_BODY_LIMIT_BYTES: usize = 2 * 1024 * 1024;
+
+/// Default body size limit of imports and database restores, in megabytes.
+pub const DEFAULT_MAX_UPLOAD_MB: usize = 50;
+
+/// Apply a body size limit to all routes of `router`.
+pub fn with_body_limit(router: Router<AppState>, limit_bytes: usize) -> Router<AppState> {
+    router
+        .layer(middleware::from_fn(move |req, next| {
+            reject_oversized(limit_bytes, req, next)
+        }))
+        .layer(DefaultBodyLimit::max(limit_bytes))
+}
+
+/// The error for a body over `limit_bytes`.
+pub fn payload_too_large(limit_bytes: usize) -> AppError {
+    AppError::PayloadTooLarge(format!(
+        "The request body is larger than the {} limit",
+        format_limit(limit_bytes)
+    ))
+}
+
+/// Map a multipart read error, telling an upload over `limit_bytes` apart
+/// from other failures, which go through `other`.
+pub fn multipart_error(
+    e: MultipartError,
+    limit_bytes: usize,
+    other: impl FnOnce(MultipartError) -> AppError,
+) -> AppError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        payload_too_large(limit_bytes)
+    } else {
+        other(e)
+    }
+}
+
+/// Write a multipart field to `path` chunk by chunk, so an upload is never
+/// held in memory as a whole. Returns the number of bytes written; the file
+/// is removed again if the upload fails.
+pub async fn stream_field_to_file(
+    mut field: Field<'_>,
+    path: &Path,
+    limit_bytes: usize,
+) -> AppResult<u64> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut written: u64 = 0;
+    let result: AppResult<()> =
+        async {
+            while let Some(chunk) = field.chunk().await.map_err(|e| {
+                multipart_error(e, limit_bytes, |e| AppError::CsvParse(e.to_string()))
+            })? {
+                file.write_all(&chunk).await?;
+                written += chunk.len() as u64;
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+
+    if let Err(e) = result {
+        drop(file);
+        let _ = tokio::fs::remove_file(path).await;
+        return Err(e);
+    }
+    Ok(written)
+}
+
+/// Reject bodies announced as too large up front, and give the bare 413 of
+/// extractors that hit the limit a message naming it.
+async fn reject_oversized(limit_bytes: usize, request: Request<Body>, next: Next) -> Response {
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if content_length.is_some_and(|len| len > limit_bytes) {
+        return payload_too_large(limit_bytes).into_response();
+    }
+
+    let response = next.run(request).await;
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE
+        && response.extensions().get::<ErrorMessage>().is_none()
+    {
+        return payload_too_large(limit_bytes).into_response();
+    }
+    response
+}
+
+fn format_limit(limit_bytes: usize) -> String {
+    const MB: usize = 1024 * 1024;
+    if limit_bytes >= MB && limit_bytes.is_multiple_of(MB) {
+        format!("{} MB", limit_bytes / MB)
+    } else {
+        format!("{} KB", limit_bytes.div_ceil(1024))
+    }
+}
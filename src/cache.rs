@@ -1,20 +1,104 @@
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use axum::body::Body;
 use axum::extract::State;
 use axum::http::Request;
 use axum::middleware::Next;
 use axum::response::Response;
+use serde::Serialize;
 
 use crate::config::AuthMode;
-use crate::db::queries::{accounts, categories, settings as db_settings, tags, transactions};
+use crate::db::queries::{accounts, categories, settings as db_settings, tags, transaction_sums};
 use crate::db::DbPool;
 use crate::error::AppResult;
-use crate::handlers::recurring_expenses::{self, RecurringExpense};
-use crate::models::{Account, Category, CategoryWithPath, Settings, Tag};
+use crate::models::{Account, Category, CategoryWithPath, NetWorthSummary, Settings, Tag};
+use crate::services::market_data::SymbolMetadata;
+use crate::services::net_worth::calculate_net_worth_history;
+use crate::services::recurring_detection::{self, RecurringExpense};
 use crate::state::AppState;
 
+/// How long symbol search results are reused before asking Yahoo again.
+const SYMBOL_SEARCH_TTL: Duration = Duration::from_secs(300);
+
+/// An area of stored data. Each domain has its own version counter, and
+/// cached values are keyed by the versions of the domains they are computed
+/// from, so a write only invalidates what depends on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDomain {
+    Transactions,
+    Trading,
+    MarketData,
+    Categories,
+    Tags,
+    Accounts,
+    Settings,
+}
+
+impl DataDomain {
+    pub const ALL: [DataDomain; 7] = [
+        DataDomain::Transactions,
+        DataDomain::Trading,
+        DataDomain::MarketData,
+        DataDomain::Categories,
+        DataDomain::Tags,
+        DataDomain::Accounts,
+        DataDomain::Settings,
+    ];
+
+    /// Domains a successful mutating request to `path` may have written to.
+    /// Paths without a known owner bump every domain.
+    pub fn for_path(path: &str) -> &'static [DataDomain] {
+        use DataDomain::*;
+        let under = |prefix: &str| {
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+        if under("/transactions") || under("/rules") {
+            &[Transactions]
+        } else if under("/import") {
+            // Imports create the tags named in the file
+            &[Transactions, Tags]
+        } else if under("/trading/market-data") || under("/api/symbols") {
+            &[MarketData]
+        } else if under("/trading/api-logs") {
+            // Retries write in the background, which invalidates market data
+            &[]
+        } else if under("/trading/activities") {
+            // Dividends, fees and taxes may be mirrored into transactions
+            &[Trading, Transactions]
+        } else if under("/trading") {
+            &[Trading]
+        } else if under("/categories") {
+            &[Categories]
+        } else if under("/tags") {
+            &[Tags]
+        } else if under("/accounts") {
+            &[Accounts]
+        } else if under("/settings/import-database") {
+            &Self::ALL
+        } else if under("/settings/clear-database") {
+            // Invalidated by the handler, depending on the scopes cleared
+            &[]
+        } else if under("/settings") {
+            &[Settings]
+        } else if under("/retirement")
+            || under("/api/retirement")
+            || under("/spending/charts")
+            || under("/share")
+            || under("/login")
+            || under("/logout")
+        {
+            &[]
+        } else {
+            &Self::ALL
+        }
+    }
+}
+
 struct Slot<T> {
     inner: RwLock<Option<(u64, T)>>,
 }
@@ -42,14 +126,21 @@ impl<T: Clone> Slot<T> {
 }
 
 pub struct AppCache {
-    generation: AtomicU64,
+    /// One counter per [`DataDomain`], indexed by its discriminant
+    versions: [AtomicU64; DataDomain::ALL.len()],
     settings: Slot<Settings>,
     categories_with_path: Slot<Vec<CategoryWithPath>>,
     categories: Slot<Vec<Category>>,
     tags: Slot<Vec<Tag>>,
     accounts: Slot<Vec<Account>>,
+    /// Accounts transactions are booked to: cash and credit card accounts
     cash_accounts: Slot<Vec<Account>>,
     recurring_expenses: Slot<Vec<RecurringExpense>>,
+    /// The net worth series with the Transfers subtree it was computed with
+    net_worth: Slot<(Vec<i64>, Arc<NetWorthSummary>)>,
+    /// Symbol search results keyed by normalized query. External data, so
+    /// expiry is time-based instead of tied to the generation counter.
+    symbol_search: Mutex<HashMap<String, (Instant, Vec<SymbolMetadata>)>>,
 }
 
 impl Default for AppCache {
@@ -61,7 +152,7 @@ impl Default for AppCache {
 impl AppCache {
     pub fn new() -> Self {
         Self {
-            generation: AtomicU64::new(0),
+            versions: Default::default(),
             settings: Slot::new(),
             categories_with_path: Slot::new(),
             categories: Slot::new(),
@@ -69,19 +160,41 @@ impl AppCache {
             accounts: Slot::new(),
             cash_accounts: Slot::new(),
             recurring_expenses: Slot::new(),
+            net_worth: Slot::new(),
+            symbol_search: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Invalidate everything, e.g. after the whole database was replaced.
     pub fn invalidate(&self) {
-        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.invalidate_domains(&DataDomain::ALL);
+    }
+
+    pub fn invalidate_domains(&self, domains: &[DataDomain]) {
+        for &domain in domains {
+            self.versions[domain as usize].fetch_add(1, Ordering::SeqCst);
+        }
     }
 
-    fn gen(&self) -> u64 {
-        self.generation.load(Ordering::SeqCst)
+    /// Current version of every domain, for debugging.
+    pub fn versions(&self) -> Vec<(DataDomain, u64)> {
+        DataDomain::ALL
+            .iter()
+            .map(|&d| (d, self.versions[d as usize].load(Ordering::SeqCst)))
+            .collect()
+    }
+
+    /// Combined version of the given domains. Counters only ever grow, so
+    /// the sum changes whenever any one of them is bumped.
+    fn gen(&self, domains: &[DataDomain]) -> u64 {
+        domains
+            .iter()
+            .map(|&d| self.versions[d as usize].load(Ordering::SeqCst))
+            .sum()
     }
 
     pub fn load_settings(&self, pool: &DbPool, auth_mode: &AuthMode) -> AppResult<Settings> {
-        let gen = self.gen();
+        let gen = self.gen(&[DataDomain::Settings]);
         if let Some(cached) = self.settings.get(gen) {
             return Ok(cached);
         }
@@ -93,7 +206,7 @@ impl AppCache {
     }
 
     pub fn load_categories_with_path(&self, pool: &DbPool) -> AppResult<Vec<CategoryWithPath>> {
-        let gen = self.gen();
+        let gen = self.gen(&[DataDomain::Categories]);
         if let Some(cached) = self.categories_with_path.get(gen) {
             return Ok(cached);
         }
@@ -104,7 +217,7 @@ impl AppCache {
     }
 
     pub fn load_categories(&self, pool: &DbPool) -> AppResult<Vec<Category>> {
-        let gen = self.gen();
+        let gen = self.gen(&[DataDomain::Categories]);
         if let Some(cached) = self.categories.get(gen) {
             return Ok(cached);
         }
@@ -115,7 +228,7 @@ impl AppCache {
     }
 
     pub fn load_tags(&self, pool: &DbPool) -> AppResult<Vec<Tag>> {
-        let gen = self.gen();
+        let gen = self.gen(&[DataDomain::Tags]);
         if let Some(cached) = self.tags.get(gen) {
             return Ok(cached);
         }
@@ -126,7 +239,7 @@ impl AppCache {
     }
 
     pub fn load_accounts(&self, pool: &DbPool) -> AppResult<Vec<Account>> {
-        let gen = self.gen();
+        let gen = self.gen(&[DataDomain::Accounts]);
         if let Some(cached) = self.accounts.get(gen) {
             return Ok(cached);
         }
@@ -137,12 +250,15 @@ impl AppCache {
     }
 
     pub fn load_cash_accounts(&self, pool: &DbPool) -> AppResult<Vec<Account>> {
-        let gen = self.gen();
+        let gen = self.gen(&[DataDomain::Accounts]);
         if let Some(cached) = self.cash_accounts.get(gen) {
             return Ok(cached);
         }
         let conn = pool.get()?;
-        let val = accounts::list_accounts_by_type(&conn, crate::models::AccountType::Cash)?;
+        let val: Vec<Account> = accounts::list_accounts(&conn)?
+            .into_iter()
+            .filter(|a| a.account_type.holds_transactions())
+            .collect();
         self.cash_accounts.set(gen, val.clone());
         Ok(val)
     }
@@ -152,27 +268,85 @@ impl AppCache {
         pool: &DbPool,
         auth_mode: &AuthMode,
     ) -> AppResult<Vec<RecurringExpense>> {
-        let gen = self.gen();
+        let gen = self.gen(&[
+            DataDomain::Transactions,
+            DataDomain::Categories,
+            DataDomain::Settings,
+        ]);
         if let Some(cached) = self.recurring_expenses.get(gen) {
             return Ok(cached);
         }
         let settings = self.load_settings(pool, auth_mode)?;
         let categories = self.load_categories(pool)?;
-        let excluded: Vec<i64> = recurring_expenses::transfers_excluded_ids(&categories)
+        let excluded: Vec<i64> = categories::transfers_excluded_ids(&categories)
             .into_iter()
             .collect();
         let conn = pool.get()?;
-        let rows = transactions::fetch_expenses_for_recurring_detection(&conn, &excluded)?;
-        let today = chrono::Utc::now().date_naive();
-        let val = recurring_expenses::detect_recurring_expenses(
+        let rows = transaction_sums::fetch_expenses_for_recurring_detection(&conn, &excluded)?;
+        let today = crate::date_utils::today_in(&settings);
+        let val = recurring_detection::detect_recurring_expenses(
             rows,
-            &settings.currency,
+            &settings.currency_format(&settings.currency),
             &settings.locale,
             today,
         );
         self.recurring_expenses.set(gen, val.clone());
         Ok(val)
     }
+
+    /// The net worth series. Categories only matter through the Transfers
+    /// subtree, so category edits that leave it alone keep the series.
+    pub fn load_net_worth(&self, pool: &DbPool) -> AppResult<Arc<NetWorthSummary>> {
+        let gen = self.gen(&[
+            DataDomain::Transactions,
+            DataDomain::Trading,
+            DataDomain::MarketData,
+            DataDomain::Accounts,
+        ]);
+        let mut transfer_ids: Vec<i64> =
+            categories::transfers_excluded_ids(&self.load_categories(pool)?)
+                .into_iter()
+                .collect();
+        transfer_ids.sort_unstable();
+        if let Some((cached_ids, summary)) = self.net_worth.get(gen) {
+            if cached_ids == transfer_ids {
+                return Ok(summary);
+            }
+        }
+        let conn = pool.get()?;
+        let summary = Arc::new(calculate_net_worth_history(&conn)?);
+        self.net_worth.set(gen, (transfer_ids, summary.clone()));
+        Ok(summary)
+    }
+
+    /// Cached search results for a query, if still fresh.
+    pub fn symbol_search(&self, query: &str) -> Option<Vec<SymbolMetadata>> {
+        let map = self.symbol_search.lock().ok()?;
+        map.get(&normalize_query(query))
+            .filter(|(stored_at, _)| stored_at.elapsed() < SYMBOL_SEARCH_TTL)
+            .map(|(_, results)| results.clone())
+    }
+
+    pub fn store_symbol_search(&self, query: &str, results: Vec<SymbolMetadata>) {
+        if let Ok(mut map) = self.symbol_search.lock() {
+            map.retain(|_, (stored_at, _)| stored_at.elapsed() < SYMBOL_SEARCH_TTL);
+            map.insert(normalize_query(query), (Instant::now(), results));
+        }
+    }
+
+    /// Find a symbol among the fresh cached search results.
+    pub fn searched_symbol(&self, symbol: &str) -> Option<SymbolMetadata> {
+        let map = self.symbol_search.lock().ok()?;
+        map.values()
+            .filter(|(stored_at, _)| stored_at.elapsed() < SYMBOL_SEARCH_TTL)
+            .flat_map(|(_, results)| results)
+            .find(|m| m.symbol.eq_ignore_ascii_case(symbol))
+            .cloned()
+    }
+}
+
+fn normalize_query(query: &str) -> String {
+    query.trim().to_lowercase()
 }
 
 pub async fn cache_invalidation_middleware(
@@ -180,6 +354,7 @@ pub async fn cache_invalidation_middleware(
     req: Request<Body>,
     next: Next,
 ) -> Response {
+    let path = req.uri().path().to_string();
     let mutating = matches!(
         *req.method(),
         axum::http::Method::POST
@@ -189,7 +364,7 @@ pub async fn cache_invalidation_middleware(
     );
     let resp = next.run(req).await;
     if mutating && !resp.status().is_client_error() && !resp.status().is_server_error() {
-        state.cache.invalidate();
+        state.cache.invalidate_domains(DataDomain::for_path(&path));
     }
     resp
 }
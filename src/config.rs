@@ -1,6 +1,12 @@
 use std::env;
 use std::path::PathBuf;
 
+use std::time::Duration;
+
+use crate::body_limit::DEFAULT_MAX_UPLOAD_MB;
+use crate::db::pool::{DEFAULT_CONNECTION_LIFETIME_SECS, DEFAULT_POOL_SIZE};
+use crate::db::PoolSettings;
+
 /// Authentication mode for the application.
 #[derive(Debug, Clone)]
 pub enum AuthMode {
@@ -21,6 +27,29 @@ pub struct Config {
     /// Whether to set the Secure flag on session cookies (requires HTTPS).
     /// Defaults to true. Set `SOLVENCY_SECURE_COOKIES=false` for local HTTP dev.
     pub secure_cookies: bool,
+    /// Log SQL statements that take at least this many milliseconds
+    /// (`SOLVENCY_SLOW_QUERY_MS`). Disabled when unset.
+    pub slow_query_ms: Option<u64>,
+    /// Default directory for scheduled backups (`SOLVENCY_BACKUP_DIR`).
+    /// Falls back to a `backups` folder next to the database when unset.
+    pub backup_dir: Option<PathBuf>,
+    /// Accept `force=1` on delete-all endpoints, skipping the confirmation
+    /// step. Never set from the environment; the test suite enables it.
+    pub allow_force_delete: bool,
+    /// Start even if an applied migration file was edited afterwards
+    /// (`SOLVENCY_ALLOW_DIRTY_MIGRATIONS=1`).
+    pub allow_dirty_migrations: bool,
+    /// Body size limit of imports and database restores, in megabytes
+    /// (`SOLVENCY_MAX_UPLOAD_MB`). Other routes accept 2 MB.
+    pub max_upload_mb: usize,
+    /// Content-Security-Policy replacing the default (`SOLVENCY_CSP`);
+    /// `{nonce}` stands for the per-request script nonce.
+    pub csp: Option<String>,
+    /// Database connections kept open at most (`SOLVENCY_DB_POOL_SIZE`).
+    pub db_pool_size: u32,
+    /// Seconds before a database connection is replaced
+    /// (`SOLVENCY_DB_CONNECTION_LIFETIME_SECS`); `None` keeps it open.
+    pub db_connection_lifetime_secs: Option<u64>,
 }
 
 /// The magic value that disables authentication.
@@ -81,6 +110,33 @@ impl Config {
             secure_cookies: env::var("SOLVENCY_SECURE_COOKIES")
                 .map(|v| v != "false" && v != "0")
                 .unwrap_or(true),
+            slow_query_ms: env::var("SOLVENCY_SLOW_QUERY_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|ms| *ms > 0),
+            backup_dir: env::var("SOLVENCY_BACKUP_DIR").ok().map(PathBuf::from),
+            allow_force_delete: false,
+            allow_dirty_migrations: env::var("SOLVENCY_ALLOW_DIRTY_MIGRATIONS")
+                .is_ok_and(|v| v == "1" || v == "true"),
+            max_upload_mb: env::var("SOLVENCY_MAX_UPLOAD_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|mb| *mb > 0)
+                .unwrap_or(DEFAULT_MAX_UPLOAD_MB),
+            csp: env::var("SOLVENCY_CSP")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            db_pool_size: env::var("SOLVENCY_DB_POOL_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(DEFAULT_POOL_SIZE),
+            db_connection_lifetime_secs: env::var("SOLVENCY_DB_CONNECTION_LIFETIME_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map_or(Some(DEFAULT_CONNECTION_LIFETIME_SECS), |secs| {
+                    (secs > 0).then_some(secs)
+                }),
             auth_mode,
         }
     }
@@ -88,4 +144,17 @@ impl Config {
     pub fn address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
+
+    /// Body size limit of imports and database restores, in bytes.
+    pub fn max_upload_bytes(&self) -> usize {
+        self.max_upload_mb.saturating_mul(1024 * 1024)
+    }
+
+    /// Sizing of the database connection pool.
+    pub fn pool_settings(&self) -> PoolSettings {
+        PoolSettings {
+            max_size: self.db_pool_size,
+            max_lifetime: self.db_connection_lifetime_secs.map(Duration::from_secs),
+        }
+    }
 }
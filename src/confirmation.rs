@@ -0,0 +1,177 @@
+//! Two-step confirmation for destructive delete-all endpoints.
+//!
+//! A DELETE without a `confirm` parameter does nothing and answers
+//! `409 Conflict` with a one-time token and a message saying how much would
+//! be deleted. Repeating the request with `confirm=<token>` within
+//! [`TOKEN_TTL`] performs the deletion. This keeps a single stray request
+//! from wiping a table.
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::error::AppResult;
+use crate::state::AppState;
+
+/// How long a confirmation token stays valid.
+pub const TOKEN_TTL: Duration = Duration::from_secs(120);
+
+/// Query parameters accepted by delete-all endpoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfirmParams {
+    pub confirm: Option<String>,
+    /// Skip confirmation; only honored when `Config::allow_force_delete` is set.
+    pub force: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ConfirmationRequired {
+    confirm_token: String,
+    expires_in_secs: u64,
+    /// What the deletion would remove, shown to the user before confirming.
+    message: String,
+}
+
+/// Number of deleted rows per kind of record, returned after a delete-all.
+#[derive(Debug, Default, Serialize)]
+pub struct DeletedCounts {
+    pub deleted: BTreeMap<String, usize>,
+}
+
+impl DeletedCounts {
+    pub fn single(kind: &str, count: usize) -> Self {
+        Self {
+            deleted: BTreeMap::from([(kind.to_string(), count)]),
+        }
+    }
+}
+
+impl IntoResponse for DeletedCounts {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Outstanding confirmation tokens, each bound to one endpoint scope.
+pub struct DeleteConfirmations {
+    /// Maps token → (scope, issue time).
+    tokens: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl Default for DeleteConfirmations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeleteConfirmations {
+    pub fn new() -> Self {
+        Self {
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Issue a new token for `scope`, dropping any expired ones.
+    pub fn issue(&self, scope: &str) -> String {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens.retain(|_, (_, issued)| issued.elapsed() < TOKEN_TTL);
+        let token = Uuid::new_v4().to_string();
+        tokens.insert(token.clone(), (scope.to_string(), Instant::now()));
+        token
+    }
+
+    /// Consume `token`. Returns `true` if it was issued for `scope` and has
+    /// not expired. A token can only be used once.
+    pub fn consume(&self, scope: &str, token: &str) -> bool {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        match tokens.remove(token) {
+            Some((issued_scope, issued)) => issued_scope == scope && issued.elapsed() < TOKEN_TTL,
+            None => false,
+        }
+    }
+}
+
+/// Check that a delete-all request for `scope` is confirmed.
+///
+/// Returns a `409 Conflict` response carrying a fresh token when the request
+/// has no valid confirmation; the handler should return it as-is. `describe`
+/// is only called then, and says what the deletion would remove (see
+/// [`deletion_message`]).
+pub fn confirmation_pending(
+    state: &AppState,
+    scope: &str,
+    params: &ConfirmParams,
+    describe: impl FnOnce() -> AppResult<String>,
+) -> AppResult<Option<Response>> {
+    if state.config.allow_force_delete && params.force.as_deref() == Some("1") {
+        return Ok(None);
+    }
+    if let Some(token) = params.confirm.as_deref() {
+        if state.delete_confirmations.consume(scope, token) {
+            return Ok(None);
+        }
+    }
+
+    let body = ConfirmationRequired {
+        message: describe()?,
+        confirm_token: state.delete_confirmations.issue(scope),
+        expires_in_secs: TOKEN_TTL.as_secs(),
+    };
+    Ok(Some((StatusCode::CONFLICT, Json(body)).into_response()))
+}
+
+/// Confirmation message for deleting the given (count, singular, plural)
+/// kinds of records, e.g. "This permanently deletes 12 transactions and
+/// 1 category."
+pub fn deletion_message(counts: &[(usize, &str, &str)]) -> String {
+    let parts: Vec<String> = counts
+        .iter()
+        .filter(|(count, _, _)| *count > 0)
+        .map(|&(count, singular, plural)| {
+            format!("{} {}", count, if count == 1 { singular } else { plural })
+        })
+        .collect();
+    match parts.as_slice() {
+        [] => "There is nothing to delete.".to_string(),
+        [only] => format!("This permanently deletes {}.", only),
+        [rest @ .., last] => format!("This permanently deletes {} and {}.", rest.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_is_single_use_and_scoped() {
+        let confirmations = DeleteConfirmations::new();
+        let token = confirmations.issue("transactions");
+        assert!(!confirmations.consume("categories", &token));
+
+        let token = confirmations.issue("transactions");
+        assert!(confirmations.consume("transactions", &token));
+        assert!(!confirmations.consume("transactions", &token));
+        assert!(!confirmations.consume("transactions", "bogus"));
+    }
+
+    #[test]
+    fn test_deletion_message() {
+        assert_eq!(
+            deletion_message(&[(0, "transaction", "transactions")]),
+            "There is nothing to delete."
+        );
+        assert_eq!(
+            deletion_message(&[
+                (12, "transaction", "transactions"),
+                (0, "trading activity", "trading activities"),
+                (1, "category", "categories"),
+            ]),
+            "This permanently deletes 12 transactions and 1 category."
+        );
+    }
+}
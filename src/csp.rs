@@ -0,0 +1,113 @@
+//! Security response headers and the Content-Security-Policy nonce.
+//!
+//! [`security_headers_middleware`] generates a random nonce per request, runs
+//! the request with it in scope and sends a Content-Security-Policy that only
+//! runs inline `<script>` blocks carrying that nonce. Templates get it as
+//! `csp_nonce` through [`crate::state::PageBase`], which reads [`current_nonce`].
+//!
+//! The default policy still allows inline event handler attributes
+//! (`script-src-attr`) and `'unsafe-eval'`, which htmx needs for `hx-on`.
+//! `SOLVENCY_CSP` replaces it for setups that embed extra resources; `{nonce}`
+//! in the override is replaced by the request's nonce.
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use uuid::Uuid;
+
+use crate::state::AppState;
+
+/// Placeholder for the request's nonce in a policy.
+pub const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// Policy sent unless `SOLVENCY_CSP` is set.
+pub const DEFAULT_POLICY: &str = "default-src 'self'; \
+     script-src 'self' 'nonce-{nonce}' 'unsafe-eval'; \
+     script-src-attr 'unsafe-inline'; \
+     style-src 'self' 'unsafe-inline' https://fonts.googleapis.com; \
+     font-src 'self' https://fonts.gstatic.com; \
+     img-src 'self' data:; \
+     connect-src 'self'; \
+     object-src 'none'; \
+     base-uri 'self'; \
+     form-action 'self'; \
+     frame-ancestors 'none'";
+
+/// HSTS max-age when cookies are marked secure: one year.
+const HSTS: &str = "max-age=31536000; includeSubDomains";
+
+tokio::task_local! {
+    static NONCE: String;
+}
+
+/// The nonce of the request being handled, or `None` outside the middleware
+/// (background tasks, tests without the middleware).
+pub fn current_nonce() -> Option<String> {
+    NONCE.try_with(Clone::clone).ok()
+}
+
+/// The policy for one request: `policy` with every `{nonce}` replaced.
+pub fn policy_with_nonce(policy: &str, nonce: &str) -> String {
+    policy.replace(NONCE_PLACEHOLDER, nonce)
+}
+
+/// Middleware adding the CSP and other security headers to every response.
+pub async fn security_headers_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let nonce = Uuid::new_v4().simple().to_string();
+    let mut response = NONCE.scope(nonce.clone(), next.run(request)).await;
+
+    let policy = state.config.csp.as_deref().unwrap_or(DEFAULT_POLICY);
+    let headers = response.headers_mut();
+    match HeaderValue::from_str(&policy_with_nonce(policy, &nonce)) {
+        Ok(value) => {
+            headers.insert(header::CONTENT_SECURITY_POLICY, value);
+        }
+        Err(_) => tracing::warn!("SOLVENCY_CSP is not a valid header value; not sent"),
+    }
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("same-origin"),
+    );
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    if state.config.secure_cookies {
+        headers.insert(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_static(HSTS),
+        );
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_with_nonce() {
+        let policy = policy_with_nonce(DEFAULT_POLICY, "abc123");
+        assert!(policy.contains("script-src 'self' 'nonce-abc123'"));
+        assert!(!policy.contains(NONCE_PLACEHOLDER));
+        assert_eq!(
+            policy_with_nonce("script-src 'self'", "abc123"),
+            "script-src 'self'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_current_nonce_in_scope() {
+        assert_eq!(current_nonce(), None);
+        let nonce = NONCE
+            .scope("n1".to_string(), async { current_nonce() })
+            .await;
+        assert_eq!(nonce.as_deref(), Some("n1"));
+    }
+}
@@ -1,6 +1,155 @@
 use std::str::FromStr;
+use std::sync::LazyLock;
 
-use chrono::{Datelike, Local, NaiveDate};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use crate::models::Settings;
+
+/// Today's date in the configured timezone. Falls back to the server's local
+/// time when the `timezone` setting is empty or not a known IANA name.
+pub fn today_in(settings: &Settings) -> NaiveDate {
+    date_at(&settings.timezone, Utc::now())
+}
+
+/// The calendar date in timezone `tz` at instant `now`.
+fn date_at(tz: &str, now: DateTime<Utc>) -> NaiveDate {
+    match tz.parse::<Tz>() {
+        Ok(tz) => now.with_timezone(&tz).date_naive(),
+        Err(_) => now.with_timezone(&Local).date_naive(),
+    }
+}
+
+/// Whether `name` is a known IANA timezone name.
+pub fn is_valid_timezone(name: &str) -> bool {
+    name.parse::<Tz>().is_ok()
+}
+
+/// All known IANA timezone names, for the settings form.
+pub fn timezone_names() -> Vec<&'static str> {
+    chrono_tz::TZ_VARIANTS.iter().map(|tz| tz.name()).collect()
+}
+
+/// IANA name of the operating system's timezone, if it can be determined.
+pub fn system_timezone() -> Option<String> {
+    iana_time_zone::get_timezone()
+        .ok()
+        .filter(|name| is_valid_timezone(name))
+}
+
+/// How dates are typed and displayed. Dates are always stored as ISO
+/// `YYYY-MM-DD`, and ISO input is accepted whatever the setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    #[default]
+    Iso,
+    /// `DD.MM.YYYY`, as in Germany.
+    DayMonthYear,
+    /// `DD/MM/YYYY`, as in the UK.
+    DayMonthYearSlash,
+    /// `MM/DD/YYYY`, as in the US.
+    MonthDayYear,
+}
+
+impl FromStr for DateFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "YYYY-MM-DD" => Ok(Self::Iso),
+            "DD.MM.YYYY" => Ok(Self::DayMonthYear),
+            "DD/MM/YYYY" => Ok(Self::DayMonthYearSlash),
+            "MM/DD/YYYY" => Ok(Self::MonthDayYear),
+            _ => Err(()),
+        }
+    }
+}
+
+impl DateFormat {
+    pub fn all() -> &'static [DateFormat] {
+        &[
+            Self::Iso,
+            Self::DayMonthYear,
+            Self::DayMonthYearSlash,
+            Self::MonthDayYear,
+        ]
+    }
+
+    /// The value stored in the `date_format` setting.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Iso => "YYYY-MM-DD",
+            Self::DayMonthYear => "DD.MM.YYYY",
+            Self::DayMonthYearSlash => "DD/MM/YYYY",
+            Self::MonthDayYear => "MM/DD/YYYY",
+        }
+    }
+
+    fn pattern(&self) -> &'static str {
+        match self {
+            Self::Iso => "%Y-%m-%d",
+            Self::DayMonthYear => "%d.%m.%Y",
+            Self::DayMonthYearSlash => "%d/%m/%Y",
+            Self::MonthDayYear => "%m/%d/%Y",
+        }
+    }
+
+    pub fn format(&self, date: NaiveDate) -> String {
+        date.format(self.pattern()).to_string()
+    }
+
+    /// Parse a date typed by a user or read from a CSV file. Dates starting
+    /// with a four-digit year are read as ISO. Otherwise the year comes last
+    /// and an ambiguous date like 01/02/2024 is read in this format's day
+    /// and month order. Without a preference (ISO), dots mean day first and
+    /// slashes month first. A date that is only valid the other way round,
+    /// like 13/01/2024 in `MM/DD/YYYY`, is still accepted.
+    pub fn parse(&self, input: &str) -> Result<NaiveDate, String> {
+        let input = input.trim();
+        let invalid = || format!("Invalid date \"{}\", expected {}", input, self.as_str());
+        let separator = input
+            .chars()
+            .find(|c| matches!(c, '-' | '.' | '/'))
+            .ok_or_else(invalid)?;
+        let parts: Vec<&str> = input.split(separator).collect();
+        let [first, second, third] = parts[..] else {
+            return Err(invalid());
+        };
+        let number = |part: &str| -> Result<u32, String> {
+            if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse().map_err(|_| invalid())
+        };
+        let (a, b, c) = (number(first)?, number(second)?, number(third)?);
+
+        let date = if first.len() == 4 {
+            NaiveDate::from_ymd_opt(a as i32, b, c)
+        } else if third.len() == 4 {
+            let day_first = match self {
+                Self::DayMonthYear | Self::DayMonthYearSlash => true,
+                Self::MonthDayYear => false,
+                Self::Iso => separator != '/',
+            };
+            let (day, month) = if day_first { (a, b) } else { (b, a) };
+            NaiveDate::from_ymd_opt(c as i32, month, day)
+                .or_else(|| NaiveDate::from_ymd_opt(c as i32, day, month))
+        } else {
+            None
+        };
+        date.ok_or_else(invalid)
+    }
+}
+
+/// Parse a stored ISO `YYYY-MM-DD` date.
+pub fn parse_iso_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Parse a date entered in a form according to the `date_format` setting.
+pub fn parse_user_date(input: &str, settings: &Settings) -> Result<NaiveDate, String> {
+    settings.date_format().parse(input)
+}
 
 /// Trait for filter params that support date filtering with presets and navigation.
 #[allow(clippy::wrong_self_convention)]
@@ -14,18 +163,28 @@ pub trait DateFilterable {
         None
     }
 
-    fn resolve_date_range(&self) -> DateRange {
+    /// The presets the page offers. Others are treated like unknown ones,
+    /// falling back to the default range. Defaults to [`DatePreset::all`].
+    fn presets(&self) -> &'static [DatePreset] {
+        DatePreset::all()
+    }
+
+    /// Resolve the requested range. Presets are relative to `today`
+    /// (see [`today_in`]).
+    fn resolve_date_range(&self, today: NaiveDate) -> DateRange {
         let base_range = if let Some(preset_str) = self.preset() {
             preset_str
                 .parse::<DatePreset>()
-                .map(DateRange::from_preset)
+                .ok()
+                .filter(|preset| self.presets().contains(preset))
+                .map(|preset| DateRange::from_preset(preset, today))
                 .unwrap_or_default()
         } else if let (Some(from), Some(to)) = (self.from_date(), self.to_date()) {
             if let (Ok(from_date), Ok(to_date)) = (
                 NaiveDate::parse_from_str(from, "%Y-%m-%d"),
                 NaiveDate::parse_from_str(to, "%Y-%m-%d"),
             ) {
-                DateRange::from_dates(from_date, to_date)
+                DateRange::from_dates(from_date, to_date, today)
             } else {
                 DateRange::default()
             }
@@ -41,12 +200,15 @@ pub trait DateFilterable {
     }
 }
 
+/// The unit a range spans, which decides how prev/next shift it.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PeriodType {
     Week,
     Month,
     Quarter,
     Year,
+    /// Several whole months that are not a quarter or a year.
+    Months(i32),
     All,
     Custom,
 }
@@ -62,6 +224,8 @@ pub enum DatePreset {
     LastQuarter,
     LastYear,
     All,
+    /// From the first trading activity on, offered on the net worth page.
+    SinceFirstInvestment,
 }
 
 impl FromStr for DatePreset {
@@ -78,6 +242,7 @@ impl FromStr for DatePreset {
             "last_quarter" => Ok(Self::LastQuarter),
             "last_year" => Ok(Self::LastYear),
             "all" => Ok(Self::All),
+            "since_first_investment" => Ok(Self::SinceFirstInvestment),
             _ => Err(()),
         }
     }
@@ -95,6 +260,7 @@ impl DatePreset {
             Self::LastQuarter => "last_quarter",
             Self::LastYear => "last_year",
             Self::All => "all",
+            Self::SinceFirstInvestment => "since_first_investment",
         }
     }
 
@@ -109,6 +275,7 @@ impl DatePreset {
             Self::LastQuarter => "Last Quarter",
             Self::LastYear => "Last Year",
             Self::All => "All",
+            Self::SinceFirstInvestment => "Since First Investment",
         }
     }
 
@@ -125,6 +292,19 @@ impl DatePreset {
             Self::All,
         ]
     }
+
+    /// The presets of the net worth page: the usual ones plus
+    /// "Since First Investment".
+    pub fn net_worth() -> &'static [DatePreset] {
+        static NET_WORTH: LazyLock<Vec<DatePreset>> = LazyLock::new(|| {
+            DatePreset::all()
+                .iter()
+                .copied()
+                .chain([DatePreset::SinceFirstInvestment])
+                .collect()
+        });
+        &NET_WORTH
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -132,11 +312,12 @@ pub struct DateRange {
     pub from: NaiveDate,
     pub to: NaiveDate,
     pub preset: Option<DatePreset>,
+    /// The date presets are relative to, kept for prev/next navigation.
+    today: NaiveDate,
 }
 
 impl DateRange {
-    pub fn from_preset(preset: DatePreset) -> Self {
-        let today = Local::now().date_naive();
+    pub fn from_preset(preset: DatePreset, today: NaiveDate) -> Self {
         let (from, to) = match preset {
             DatePreset::ThisWeek => {
                 let start = week_start(today);
@@ -183,7 +364,8 @@ impl DateRange {
                 let end = year_end(last_year);
                 (start, end)
             }
-            DatePreset::All => {
+            // Open-ended until resolved against the data extent
+            DatePreset::All | DatePreset::SinceFirstInvestment => {
                 let start = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
                 let end = NaiveDate::from_ymd_opt(2099, 12, 31).unwrap();
                 (start, end)
@@ -193,29 +375,38 @@ impl DateRange {
             from,
             to,
             preset: Some(preset),
+            today,
         }
     }
 
-    pub fn from_dates(from: NaiveDate, to: NaiveDate) -> Self {
-        let preset = detect_preset(from, to);
-        Self { from, to, preset }
+    pub fn from_dates(from: NaiveDate, to: NaiveDate, today: NaiveDate) -> Self {
+        let preset = detect_preset(from, to, today);
+        Self {
+            from,
+            to,
+            preset,
+            today,
+        }
     }
 
     pub fn prev(&self) -> Self {
         let period = self.detect_period_type();
         let (new_from, new_to) = shift_by_period(self.from, self.to, period, -1);
-        Self::from_dates(new_from, new_to)
+        Self::from_dates(new_from, new_to, self.today)
     }
 
     pub fn next(&self) -> Self {
         let period = self.detect_period_type();
         let (new_from, new_to) = shift_by_period(self.from, self.to, period, 1);
-        Self::from_dates(new_from, new_to)
+        Self::from_dates(new_from, new_to, self.today)
     }
 
     fn detect_period_type(&self) -> PeriodType {
         // Check if it's "All" (the widest possible range)
-        if self.preset == Some(DatePreset::All) {
+        if matches!(
+            self.preset,
+            Some(DatePreset::All | DatePreset::SinceFirstInvestment)
+        ) {
             return PeriodType::All;
         }
 
@@ -226,28 +417,22 @@ impl DateRange {
             return PeriodType::Week;
         }
 
-        // Check if it's a month
-        let start = month_start(self.from);
-        let end = month_end(self.from);
-        if self.from == start && self.to == end {
-            return PeriodType::Month;
-        }
-
-        // Check if it's a quarter
-        let start = quarter_start(self.from);
-        let end = quarter_end(self.from);
-        if self.from == start && self.to == end {
-            return PeriodType::Quarter;
+        // Whole months, from the first of a month to the last of one
+        if self.from != month_start(self.from) || self.to != month_end(self.to) {
+            return PeriodType::Custom;
         }
-
-        // Check if it's a year
-        let start = year_start(self.from);
-        let end = year_end(self.from);
-        if self.from == start && self.to == end {
-            return PeriodType::Year;
+        let months = months_between(self.from, self.to) + 1;
+        if months == 1 {
+            PeriodType::Month
+        } else if months == 3 && self.from == quarter_start(self.from) {
+            PeriodType::Quarter
+        } else if months == 12 && self.from == year_start(self.from) {
+            PeriodType::Year
+        } else if months > 0 {
+            PeriodType::Months(months)
+        } else {
+            PeriodType::Custom
         }
-
-        PeriodType::Custom
     }
 
     /// Human-readable label for the current range, e.g. "January 2026", "Q1 2026",
@@ -289,6 +474,20 @@ impl DateRange {
                 format!("Q{} {}", q, self.from.year())
             }
             PeriodType::Year => self.from.format("%Y").to_string(),
+            PeriodType::Months(_) => {
+                if self.from.year() == self.to.year() {
+                    format!("{} – {}", self.from.format("%b"), self.to.format("%b %Y"))
+                } else {
+                    format!(
+                        "{} – {}",
+                        self.from.format("%b %Y"),
+                        self.to.format("%b %Y")
+                    )
+                }
+            }
+            PeriodType::All if self.preset == Some(DatePreset::SinceFirstInvestment) => {
+                "Since First Investment".to_string()
+            }
             PeriodType::All => "All Time".to_string(),
             PeriodType::Custom => {
                 let from_fmt = self.from.format("%b %-d");
@@ -332,6 +531,30 @@ impl DateRange {
         if self.preset != Some(DatePreset::All) {
             return self;
         }
+        self.narrow_to(extent)
+    }
+
+    /// Like [`resolve_all`](Self::resolve_all), and for "Since First
+    /// Investment" the range from the first date of `investments` to the
+    /// end of `extent`. Without investments it covers all of `extent`.
+    pub fn resolve_since_first_investment(
+        self,
+        extent: Option<(String, String)>,
+        investments: Option<(String, String)>,
+    ) -> Self {
+        if self.preset != Some(DatePreset::SinceFirstInvestment) {
+            return self.resolve_all(extent);
+        }
+        let extent = match (extent, investments) {
+            (Some((min_date, max_date)), Some((first_investment, _))) => {
+                Some((first_investment.max(min_date), max_date))
+            }
+            (extent, _) => extent,
+        };
+        self.narrow_to(extent)
+    }
+
+    fn narrow_to(self, extent: Option<(String, String)>) -> Self {
         match extent {
             Some((min_date, max_date)) => {
                 let from = NaiveDate::parse_from_str(&min_date, "%Y-%m-%d").unwrap_or(self.from);
@@ -339,13 +562,23 @@ impl DateRange {
                 Self {
                     from,
                     to,
-                    preset: Some(DatePreset::All),
+                    preset: self.preset,
+                    today: self.today,
                 }
             }
             None => self,
         }
     }
 
+    /// The part of the range within `extent`, as `(from, to)` strings, or
+    /// `None` when there is no extent or the range lies outside it.
+    pub fn clamp_to(&self, extent: Option<&(String, String)>) -> Option<(String, String)> {
+        let (min_date, max_date) = extent?;
+        let from = self.from_str().max(min_date.clone());
+        let to = self.to_str().min(max_date.clone());
+        (from <= to).then_some((from, to))
+    }
+
     /// Returns query string for preserving date range state in URLs.
     /// Format: `from_date=YYYY-MM-DD&to_date=YYYY-MM-DD` with optional `&preset=X`
     pub fn query_string(&self) -> String {
@@ -358,9 +591,22 @@ impl DateRange {
     }
 }
 
+/// The extent covering both `a` and `b`, each `(min_date, max_date)` as
+/// returned by the `date_extent` queries.
+pub fn combine_extents(
+    a: Option<(String, String)>,
+    b: Option<(String, String)>,
+) -> Option<(String, String)> {
+    match (a, b) {
+        (Some((a_min, a_max)), Some((b_min, b_max))) => Some((a_min.min(b_min), a_max.max(b_max))),
+        (a, b) => a.or(b),
+    }
+}
+
 impl Default for DateRange {
     fn default() -> Self {
-        Self::from_preset(DatePreset::All)
+        // "All" does not depend on the current date
+        Self::from_preset(DatePreset::All, NaiveDate::default())
     }
 }
 
@@ -377,7 +623,7 @@ fn month_start(date: NaiveDate) -> NaiveDate {
     NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap()
 }
 
-fn month_end(date: NaiveDate) -> NaiveDate {
+pub(crate) fn month_end(date: NaiveDate) -> NaiveDate {
     let next_month = if date.month() == 12 {
         NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
     } else {
@@ -411,9 +657,9 @@ fn year_end(date: NaiveDate) -> NaiveDate {
     NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap()
 }
 
-fn detect_preset(from: NaiveDate, to: NaiveDate) -> Option<DatePreset> {
+fn detect_preset(from: NaiveDate, to: NaiveDate, today: NaiveDate) -> Option<DatePreset> {
     for preset in DatePreset::all() {
-        let range = DateRange::from_preset(*preset);
+        let range = DateRange::from_preset(*preset, today);
         if range.from == from && range.to == to {
             return Some(*preset);
         }
@@ -421,6 +667,10 @@ fn detect_preset(from: NaiveDate, to: NaiveDate) -> Option<DatePreset> {
     None
 }
 
+/// Move a range by its own span: whole periods for week, month, quarter and
+/// year ranges (so January is followed by all of February), and by exactly
+/// its number of days for custom ranges, so consecutive ranges neither
+/// overlap nor leave gaps.
 fn shift_by_period(
     from: NaiveDate,
     to: NaiveDate,
@@ -448,6 +698,11 @@ fn shift_by_period(
             let new_to = NaiveDate::from_ymd_opt(new_year, 12, 31).unwrap();
             (new_from, new_to)
         }
+        PeriodType::Months(months) => {
+            let new_from = shift_months(from, direction * months);
+            let new_to = month_end(shift_months(new_from, months - 1));
+            (new_from, new_to)
+        }
         PeriodType::All => {
             // "All" covers everything; navigation is a no-op
             (from, to)
@@ -461,9 +716,257 @@ fn shift_by_period(
     }
 }
 
-fn shift_months(date: NaiveDate, months: i32) -> NaiveDate {
+/// Calendar months from `from`'s month to `to`'s, ignoring the days.
+fn months_between(from: NaiveDate, to: NaiveDate) -> i32 {
+    (to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32
+}
+
+pub(crate) fn shift_months(date: NaiveDate, months: i32) -> NaiveDate {
     let total_months = date.year() * 12 + date.month() as i32 - 1 + months;
     let new_year = total_months.div_euclid(12);
     let new_month = (total_months.rem_euclid(12) + 1) as u32;
     NaiveDate::from_ymd_opt(new_year, new_month, 1).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_date_at_crosses_midnight_by_timezone() {
+        let now = utc("2024-03-31T14:30:00Z");
+        assert_eq!(date_at("UTC", now), date("2024-03-31"));
+        assert_eq!(date_at("Australia/Sydney", now), date("2024-04-01"));
+        assert_eq!(date_at("America/Los_Angeles", now), date("2024-03-31"));
+
+        let now = utc("2024-04-01T03:00:00Z");
+        assert_eq!(date_at("America/Los_Angeles", now), date("2024-03-31"));
+    }
+
+    #[test]
+    fn test_preset_boundaries_around_midnight() {
+        // 23:59 on March 31 in New York is already April 1 in UTC
+        let now = utc("2024-04-01T03:59:00Z");
+
+        let ny = DateRange::from_preset(DatePreset::ThisMonth, date_at("America/New_York", now));
+        assert_eq!(
+            (ny.from_str(), ny.to_str()),
+            ("2024-03-01".into(), "2024-03-31".into())
+        );
+
+        let utc_range = DateRange::from_preset(DatePreset::ThisMonth, date_at("UTC", now));
+        assert_eq!(
+            (utc_range.from_str(), utc_range.to_str()),
+            ("2024-04-01".into(), "2024-04-30".into())
+        );
+
+        let quarter =
+            DateRange::from_preset(DatePreset::ThisQuarter, date_at("America/New_York", now));
+        assert_eq!(quarter.from_str(), "2024-01-01");
+        let last_quarter = DateRange::from_preset(DatePreset::LastQuarter, date_at("UTC", now));
+        assert_eq!(last_quarter.from_str(), "2024-01-01");
+    }
+
+    #[test]
+    fn test_detect_preset_relative_to_today() {
+        let range =
+            DateRange::from_dates(date("2024-03-01"), date("2024-03-31"), date("2024-03-31"));
+        assert_eq!(range.preset, Some(DatePreset::ThisMonth));
+        let range =
+            DateRange::from_dates(date("2024-03-01"), date("2024-03-31"), date("2024-04-01"));
+        assert_eq!(range.preset, Some(DatePreset::LastMonth));
+    }
+
+    fn range(from: &str, to: &str) -> DateRange {
+        DateRange::from_dates(date(from), date(to), date("2025-06-15"))
+    }
+
+    fn dates(range: &DateRange) -> (String, String) {
+        (range.from_str(), range.to_str())
+    }
+
+    fn pair(from: &str, to: &str) -> (String, String) {
+        (from.into(), to.into())
+    }
+
+    #[test]
+    fn test_nav_months_anchor_to_month_end() {
+        let jan = range("2023-01-01", "2023-01-31");
+        assert_eq!(dates(&jan.next()), pair("2023-02-01", "2023-02-28"));
+        assert_eq!(dates(&jan.next().next()), pair("2023-03-01", "2023-03-31"));
+        assert_eq!(dates(&jan.prev()), pair("2022-12-01", "2022-12-31"));
+
+        // Leap years
+        let jan = range("2024-01-01", "2024-01-31");
+        assert_eq!(dates(&jan.next()), pair("2024-02-01", "2024-02-29"));
+        let mar = range("2024-03-01", "2024-03-31");
+        assert_eq!(dates(&mar.prev()), pair("2024-02-01", "2024-02-29"));
+        let year = range("2024-01-01", "2024-12-31");
+        assert_eq!(dates(&year.next()), pair("2025-01-01", "2025-12-31"));
+        let quarter = range("2024-01-01", "2024-03-31");
+        assert_eq!(dates(&quarter.next()), pair("2024-04-01", "2024-06-30"));
+    }
+
+    #[test]
+    fn test_nav_multi_month_spans_keep_alignment() {
+        // Two whole months shift by two months, whatever their lengths
+        let span = range("2024-01-01", "2024-02-29");
+        assert_eq!(dates(&span.next()), pair("2024-03-01", "2024-04-30"));
+        assert_eq!(dates(&span.prev()), pair("2023-11-01", "2023-12-31"));
+        assert_eq!(span.display_label(), "Jan – Feb 2024");
+
+        // Not a quarter, as it does not start one
+        let span = range("2023-11-01", "2024-01-31");
+        assert_eq!(dates(&span.next()), pair("2024-02-01", "2024-04-30"));
+        assert_eq!(span.display_label(), "Nov 2023 – Jan 2024");
+    }
+
+    #[test]
+    fn test_nav_custom_ranges_shift_by_their_length() {
+        // 20 days, adjoining the original range on both sides
+        let custom = range("2024-01-15", "2024-02-03");
+        assert_eq!(dates(&custom.next()), pair("2024-02-04", "2024-02-23"));
+        assert_eq!(dates(&custom.prev()), pair("2023-12-26", "2024-01-14"));
+
+        // Across a leap day
+        let custom = range("2024-02-20", "2024-03-05");
+        assert_eq!(dates(&custom.next()), pair("2024-03-06", "2024-03-20"));
+        assert_eq!(dates(&custom.prev()), pair("2024-02-05", "2024-02-19"));
+
+        // Partial months stay custom rather than snapping to whole months
+        let custom = range("2024-01-10", "2024-03-31");
+        assert_eq!(dates(&custom.next()), pair("2024-04-01", "2024-06-21"));
+    }
+
+    #[test]
+    fn test_nav_keeps_matching_preset() {
+        let today = date("2025-06-15");
+        let this_month = DateRange::from_preset(DatePreset::ThisMonth, today);
+        let last_month = this_month.prev();
+        assert_eq!(last_month.preset, Some(DatePreset::LastMonth));
+        assert_eq!(
+            last_month.query_string(),
+            "from_date=2025-05-01&to_date=2025-05-31&preset=last_month"
+        );
+        let this_year = DateRange::from_preset(DatePreset::ThisYear, today);
+        assert_eq!(this_year.prev().preset, Some(DatePreset::LastYear));
+        assert_eq!(this_year.next().preset, None);
+    }
+
+    #[test]
+    fn test_since_first_investment_and_clamping() {
+        let today = date("2025-06-15");
+        let extent = combine_extents(
+            Some(pair("2019-03-01", "2025-05-30")),
+            Some(pair("2020-01-10", "2025-06-02")),
+        );
+        let investments = Some(pair("2020-01-10", "2025-06-02"));
+
+        let all = DateRange::from_preset(DatePreset::All, today)
+            .resolve_since_first_investment(extent.clone(), investments.clone());
+        assert_eq!(dates(&all), pair("2019-03-01", "2025-06-02"));
+        assert_eq!(all.display_label(), "All Time");
+
+        let since = DateRange::from_preset(DatePreset::SinceFirstInvestment, today)
+            .resolve_since_first_investment(extent.clone(), investments);
+        assert_eq!(dates(&since), pair("2020-01-10", "2025-06-02"));
+        assert_eq!(since.display_label(), "Since First Investment");
+        assert_eq!(dates(&since.prev()), dates(&since));
+
+        // Without trading activities it covers everything
+        let since = DateRange::from_preset(DatePreset::SinceFirstInvestment, today)
+            .resolve_since_first_investment(extent.clone(), None);
+        assert_eq!(dates(&since), pair("2019-03-01", "2025-06-02"));
+
+        let custom = DateRange::from_dates(date("2018-01-01"), date("2019-12-31"), today);
+        assert_eq!(
+            custom.clamp_to(extent.as_ref()),
+            Some(pair("2019-03-01", "2019-12-31"))
+        );
+        let after = DateRange::from_dates(date("2026-01-01"), date("2026-12-31"), today);
+        assert_eq!(after.clamp_to(extent.as_ref()), None);
+        assert_eq!(DateRange::default().clamp_to(None), None);
+    }
+
+    #[test]
+    fn test_parse_date_formats() {
+        let iso = DateFormat::Iso;
+        let dmy = DateFormat::DayMonthYear;
+        let mdy = DateFormat::MonthDayYear;
+        for format in DateFormat::all() {
+            assert_eq!(format.parse("2024-11-03"), Ok(date("2024-11-03")));
+        }
+        assert_eq!(dmy.parse("03.11.2024"), Ok(date("2024-11-03")));
+        assert_eq!(mdy.parse("11/03/2024"), Ok(date("2024-11-03")));
+        assert_eq!(iso.parse("03.11.2024"), Ok(date("2024-11-03")));
+        assert_eq!(iso.parse("11/03/2024"), Ok(date("2024-11-03")));
+
+        // Ambiguous dates follow the setting
+        assert_eq!(dmy.parse("01/02/2024"), Ok(date("2024-02-01")));
+        assert_eq!(mdy.parse("01/02/2024"), Ok(date("2024-01-02")));
+        assert_eq!(mdy.parse("01.02.2024"), Ok(date("2024-01-02")));
+        // Unambiguous ones are read either way
+        assert_eq!(mdy.parse("13/01/2024"), Ok(date("2024-01-13")));
+        assert_eq!(dmy.parse("01/13/2024"), Ok(date("2024-01-13")));
+    }
+
+    #[test]
+    fn test_parse_invalid_dates() {
+        let dmy = DateFormat::DayMonthYear;
+        assert_eq!(
+            dmy.parse("32.01.2024"),
+            Err("Invalid date \"32.01.2024\", expected DD.MM.YYYY".into())
+        );
+        for input in [
+            "",
+            "yesterday",
+            "30.02.2024",
+            "2024-02-30",
+            "1.2.24",
+            "01.02",
+            "1.2.3.2024",
+            "+1.02.2024",
+        ] {
+            assert!(dmy.parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_date() {
+        let day = date("2024-11-03");
+        assert_eq!(DateFormat::Iso.format(day), "2024-11-03");
+        assert_eq!(DateFormat::DayMonthYear.format(day), "03.11.2024");
+        assert_eq!(DateFormat::MonthDayYear.format(day), "11/03/2024");
+        assert_eq!(
+            "DD.MM.YYYY".parse::<DateFormat>(),
+            Ok(DateFormat::DayMonthYear)
+        );
+    }
+
+    #[test]
+    fn test_stored_day_month_year_slash_setting() {
+        // Stored by earlier versions, which offered it as the only day-first format
+        let settings =
+            Settings::from_map([("date_format".to_string(), "DD/MM/YYYY".to_string())].into());
+        let format = settings.date_format();
+        assert_eq!(format, DateFormat::DayMonthYearSlash);
+        assert_eq!(format.parse("01/02/2024"), Ok(date("2024-02-01")));
+        assert_eq!(format.format(date("2024-11-03")), "03/11/2024");
+    }
+
+    #[test]
+    fn test_invalid_timezone_falls_back() {
+        assert!(!is_valid_timezone("Mars/Olympus"));
+        assert!(is_valid_timezone("Europe/Berlin"));
+        let now = utc("2024-03-31T12:00:00Z");
+        assert_eq!(date_at("", now), now.with_timezone(&Local).date_naive());
+    }
+}
@@ -1,55 +1,99 @@
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-pub fn run_migrations(conn: &Connection, migrations_dir: &Path) -> rusqlite::Result<()> {
-    tracing::debug!(dir = %migrations_dir.display(), "Checking for database migrations");
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS _migrations (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            applied_at TEXT NOT NULL DEFAULT (datetime('now'))
-        )",
-        [],
-    )?;
+    #[error("Failed to read migration {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 
-    let mut entries: Vec<_> = fs::read_dir(migrations_dir)
-        .map(|rd| {
-            rd.filter_map(|e| e.ok())
-                .filter(|e| {
-                    e.path()
-                        .extension()
-                        .map(|ext| ext == "sql")
-                        .unwrap_or(false)
-                })
-                .collect()
-        })
-        .unwrap_or_default();
+    #[error(
+        "Migration {0} was modified after it was applied. Restore the original file, \
+         or set SOLVENCY_ALLOW_DIRTY_MIGRATIONS=1 to start anyway."
+    )]
+    ChecksumMismatch(String),
+}
 
-    entries.sort_by_key(|e| e.file_name());
-    tracing::debug!(count = entries.len(), "Found migration files");
+/// Applied or pending state of one migration, as reported by `migrate --status`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub name: String,
+    /// When the migration was applied; `None` if it is still pending.
+    pub applied_at: Option<String>,
+    /// Whether the file differs from the version that was applied.
+    pub modified: bool,
+    /// Whether the file is missing from the migrations directory.
+    pub missing: bool,
+}
 
-    let mut applied_count = 0;
-    for entry in entries {
-        let file_name = entry.file_name();
-        let name = file_name.to_string_lossy();
+/// SHA-256 of a migration file's contents, hex encoded.
+pub fn checksum(sql: &str) -> String {
+    format!("{:x}", Sha256::digest(sql.as_bytes()))
+}
 
-        let already_applied: bool = conn.query_row(
-            "SELECT EXISTS(SELECT 1 FROM _migrations WHERE name = ?)",
-            [&*name],
-            |row| row.get(0),
-        )?;
+/// Apply pending migrations in file name order and verify that already
+/// applied ones were not edited since. A modified migration is an error
+/// unless `allow_dirty` is set, in which case it is only logged.
+pub fn run_migrations(
+    conn: &Connection,
+    migrations_dir: &Path,
+    allow_dirty: bool,
+) -> Result<(), MigrationError> {
+    tracing::debug!(dir = %migrations_dir.display(), "Checking for database migrations");
 
-        if !already_applied {
-            let sql = fs::read_to_string(entry.path())
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    ensure_migrations_table(conn)?;
+
+    let files = migration_files(migrations_dir);
+    tracing::debug!(count = files.len(), "Found migration files");
+
+    let mut applied_count = 0;
+    for path in files {
+        let name = file_name(&path);
+        let sql = fs::read_to_string(&path).map_err(|source| MigrationError::Read {
+            path: path.clone(),
+            source,
+        })?;
+        let sum = checksum(&sql);
 
-            tracing::info!(migration = %name, "Applying migration");
-            conn.execute_batch(&sql)?;
+        let applied: Option<Option<String>> = conn
+            .query_row(
+                "SELECT checksum FROM _migrations WHERE name = ?",
+                [&name],
+                |row| row.get(0),
+            )
+            .optional()?;
 
-            conn.execute("INSERT INTO _migrations (name) VALUES (?)", [&*name])?;
-            applied_count += 1;
+        match applied {
+            None => {
+                tracing::info!(migration = %name, "Applying migration");
+                conn.execute_batch(&sql)?;
+                conn.execute(
+                    "INSERT INTO _migrations (name, checksum) VALUES (?, ?)",
+                    [&name, &sum],
+                )?;
+                applied_count += 1;
+            }
+            // Applied before checksums were recorded: trust the current file
+            Some(None) => {
+                conn.execute(
+                    "UPDATE _migrations SET checksum = ? WHERE name = ?",
+                    [&sum, &name],
+                )?;
+            }
+            Some(Some(recorded)) if recorded != sum => {
+                if !allow_dirty {
+                    return Err(MigrationError::ChecksumMismatch(name));
+                }
+                tracing::warn!(migration = %name, "Migration was modified after it was applied");
+            }
+            Some(Some(_)) => {}
         }
     }
 
@@ -61,3 +105,105 @@ pub fn run_migrations(conn: &Connection, migrations_dir: &Path) -> rusqlite::Res
 
     Ok(())
 }
+
+/// List every migration file and every recorded migration with its state,
+/// without applying anything.
+pub fn migration_status(
+    conn: &Connection,
+    migrations_dir: &Path,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let table_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_migrations')",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut applied: Vec<(String, String, Option<String>)> = Vec::new();
+    if table_exists {
+        let sql = if has_checksum_column(conn)? {
+            "SELECT name, applied_at, checksum FROM _migrations"
+        } else {
+            "SELECT name, applied_at, NULL FROM _migrations"
+        };
+        let mut stmt = conn.prepare(sql)?;
+        applied = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<_, _>>()?;
+    }
+
+    let mut statuses = Vec::new();
+    for path in migration_files(migrations_dir) {
+        let name = file_name(&path);
+        let sql = fs::read_to_string(&path).map_err(|source| MigrationError::Read {
+            path: path.clone(),
+            source,
+        })?;
+        let record = applied.iter().find(|(n, _, _)| *n == name);
+        statuses.push(MigrationStatus {
+            modified: record
+                .and_then(|(_, _, sum)| sum.as_ref())
+                .is_some_and(|sum| *sum != checksum(&sql)),
+            applied_at: record.map(|(_, at, _)| at.clone()),
+            missing: false,
+            name,
+        });
+    }
+    for (name, applied_at, _) in applied {
+        if !statuses.iter().any(|s| s.name == name) {
+            statuses.push(MigrationStatus {
+                name,
+                applied_at: Some(applied_at),
+                modified: false,
+                missing: true,
+            });
+        }
+    }
+    statuses.sort_by_key(|s| s.name.clone());
+
+    Ok(statuses)
+}
+
+fn ensure_migrations_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS _migrations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            applied_at TEXT NOT NULL DEFAULT (datetime('now')),
+            checksum TEXT
+        )",
+        [],
+    )?;
+
+    // Databases created before checksums were tracked lack the column
+    if !has_checksum_column(conn)? {
+        conn.execute("ALTER TABLE _migrations ADD COLUMN checksum TEXT", [])?;
+    }
+    Ok(())
+}
+
+fn has_checksum_column(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('_migrations') WHERE name = 'checksum')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+fn migration_files(migrations_dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(migrations_dir)
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map(|ext| ext == "sql").unwrap_or(false))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    files.sort_by_key(|p| p.file_name().map(|n| n.to_os_string()));
+    files
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
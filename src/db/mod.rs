@@ -2,4 +2,4 @@ pub mod migrations;
 pub mod pool;
 pub mod queries;
 
-pub use pool::{create_in_memory_pool, create_pool, DbPool};
+pub use pool::{create_in_memory_pool, create_pool, DbPool, PoolSettings};
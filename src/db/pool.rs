@@ -1,10 +1,76 @@
-use r2d2::Pool;
+use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+use std::ops::{Deref, DerefMut};
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-pub type DbPool = Pool<SqliteConnectionManager>;
+/// Connection pool whose checkouts are timed (see [`DbPool::get`]).
+#[derive(Clone)]
+pub struct DbPool(Pool<SqliteConnectionManager>);
 
-pub fn create_pool(database_path: &Path) -> Result<DbPool, r2d2::Error> {
+impl DbPool {
+    /// Check out a connection. Waiting for a free connection counts towards
+    /// the request's database time, like the statements run on it (see
+    /// [`crate::timing`]).
+    pub fn get(&self) -> Result<TimedConnection, r2d2::Error> {
+        let start = Instant::now();
+        let conn = self.0.get()?;
+        crate::timing::record_checkout(start.elapsed());
+        Ok(TimedConnection(conn))
+    }
+}
+
+/// A connection checked out of a [`DbPool`], returned to it when dropped.
+/// Its statements are timed by the profile hook every pooled connection
+/// gets.
+pub struct TimedConnection(PooledConnection<SqliteConnectionManager>);
+
+impl Deref for TimedConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.0
+    }
+}
+
+impl DerefMut for TimedConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.0
+    }
+}
+
+/// Connections kept open at most, unless `SOLVENCY_DB_POOL_SIZE` says otherwise.
+pub const DEFAULT_POOL_SIZE: u32 = 10;
+
+/// Seconds a connection lives before it is replaced, unless
+/// `SOLVENCY_DB_CONNECTION_LIFETIME_SECS` says otherwise.
+pub const DEFAULT_CONNECTION_LIFETIME_SECS: u64 = 30 * 60;
+
+/// Prepared statements kept per connection for `prepare_cached`. Covers the
+/// distinct queries of a page render with room to spare. SQL with a variable
+/// `IN (...)` list or filter clauses uses plain `prepare`, so it does not
+/// crowd out the fixed queries.
+pub const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// Sizing of the connection pool.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSettings {
+    pub max_size: u32,
+    /// `None` keeps connections open until the pool drops them.
+    pub max_lifetime: Option<Duration>,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_POOL_SIZE,
+            max_lifetime: Some(Duration::from_secs(DEFAULT_CONNECTION_LIFETIME_SECS)),
+        }
+    }
+}
+
+pub fn create_pool(database_path: &Path, settings: PoolSettings) -> Result<DbPool, r2d2::Error> {
     tracing::info!(path = %database_path.display(), "Creating database connection pool");
 
     if let Some(parent) = database_path.parent() {
@@ -14,6 +80,8 @@ pub fn create_pool(database_path: &Path) -> Result<DbPool, r2d2::Error> {
     }
 
     let manager = SqliteConnectionManager::file(database_path).with_init(|conn| {
+        conn.profile(Some(crate::timing::record_query));
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
         conn.execute_batch(
             "PRAGMA journal_mode = WAL;
                  PRAGMA synchronous = NORMAL;
@@ -22,9 +90,16 @@ pub fn create_pool(database_path: &Path) -> Result<DbPool, r2d2::Error> {
         )
     });
 
-    let pool = Pool::builder().max_size(10).build(manager)?;
-    tracing::info!(max_size = 10, "Database connection pool created");
-    Ok(pool)
+    let pool = Pool::builder()
+        .max_size(settings.max_size)
+        .max_lifetime(settings.max_lifetime)
+        .build(manager)?;
+    tracing::info!(
+        max_size = settings.max_size,
+        max_lifetime_secs = settings.max_lifetime.map(|d| d.as_secs()),
+        "Database connection pool created"
+    );
+    Ok(DbPool(pool))
 }
 
 /// Create an in-memory database pool for testing.
@@ -40,11 +115,13 @@ pub fn create_in_memory_pool() -> Result<DbPool, r2d2::Error> {
     );
 
     let manager = SqliteConnectionManager::file(&db_name).with_init(|conn| {
+        conn.profile(Some(crate::timing::record_query));
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
         conn.execute_batch(
             "PRAGMA foreign_keys = ON;
              PRAGMA busy_timeout = 5000;",
         )
     });
 
-    Pool::builder().max_size(5).build(manager)
+    Pool::builder().max_size(5).build(manager).map(DbPool)
 }
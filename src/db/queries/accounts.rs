@@ -1,4 +1,4 @@
-use crate::models::account::{Account, AccountType, NewAccount};
+use crate::models::account::{Account, AccountType, InterestCompounding, NewAccount};
 use rusqlite::{params, Connection, OptionalExtension};
 use tracing::{info, warn};
 
@@ -11,10 +11,20 @@ fn row_to_account(row: &rusqlite::Row) -> rusqlite::Result<Account> {
         active: row.get(3)?,
         created_at: row.get(4)?,
         updated_at: row.get(5)?,
+        interest_rate_bps: row.get(6)?,
+        interest_compounding: InterestCompounding::parse(&row.get::<_, String>(7)?)
+            .unwrap_or_default(),
+        derive_cash_from_trading: row.get(8)?,
+        statement_day: row.get(9)?,
+        due_day: row.get(10)?,
+        cash_account_id: row.get(11)?,
+        iban: row.get(12)?,
     })
 }
 
-const SELECT_COLS: &str = "id, name, account_type, active, created_at, updated_at";
+const SELECT_COLS: &str = "id, name, account_type, active, created_at, updated_at, \
+                           interest_rate_bps, interest_compounding, derive_cash_from_trading, \
+                           statement_day, due_day, cash_account_id, iban";
 
 pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<Account>> {
     let mut stmt = conn.prepare(&format!("SELECT {SELECT_COLS} FROM accounts ORDER BY name"))?;
@@ -52,8 +62,22 @@ pub fn get_account(conn: &Connection, id: i64) -> rusqlite::Result<Option<Accoun
 
 pub fn create_account(conn: &Connection, account: &NewAccount) -> rusqlite::Result<i64> {
     conn.execute(
-        "INSERT INTO accounts (name, account_type, active) VALUES (?, ?, ?)",
-        params![account.name, account.account_type.as_str(), account.active],
+        "INSERT INTO accounts (name, account_type, active, interest_rate_bps, interest_compounding,
+                               derive_cash_from_trading, statement_day, due_day, cash_account_id,
+                               iban)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            account.name,
+            account.account_type.as_str(),
+            account.active,
+            account.interest_rate_bps,
+            account.interest_compounding.as_str(),
+            account.derive_cash_from_trading,
+            account.statement_day,
+            account.due_day,
+            account.cash_account_id,
+            account.iban
+        ],
     )?;
     let id = conn.last_insert_rowid();
     info!(account_id = id, name = %account.name, "Created account");
@@ -62,8 +86,23 @@ pub fn create_account(conn: &Connection, account: &NewAccount) -> rusqlite::Resu
 
 pub fn update_account(conn: &Connection, id: i64, account: &NewAccount) -> rusqlite::Result<bool> {
     let rows = conn.execute(
-        "UPDATE accounts SET name = ?, account_type = ?, active = ?, updated_at = datetime('now') WHERE id = ?",
-        params![account.name, account.account_type.as_str(), account.active, id],
+        "UPDATE accounts SET name = ?, account_type = ?, active = ?, interest_rate_bps = ?,
+                interest_compounding = ?, derive_cash_from_trading = ?, statement_day = ?,
+                due_day = ?, cash_account_id = ?, iban = ?, updated_at = datetime('now')
+         WHERE id = ?",
+        params![
+            account.name,
+            account.account_type.as_str(),
+            account.active,
+            account.interest_rate_bps,
+            account.interest_compounding.as_str(),
+            account.derive_cash_from_trading,
+            account.statement_day,
+            account.due_day,
+            account.cash_account_id,
+            account.iban,
+            id
+        ],
     )?;
     if rows > 0 {
         info!(account_id = id, name = %account.name, "Updated account");
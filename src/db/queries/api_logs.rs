@@ -1,11 +1,29 @@
 use crate::models::api_log::{ApiLog, NewApiLog};
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+const LOG_COLUMNS: &str = "id, api_name, action, symbol, request_params, status, response_summary, response_details, duration_ms, created_at, retried_from";
+
+fn log_from_row(row: &Row) -> rusqlite::Result<ApiLog> {
+    Ok(ApiLog {
+        id: row.get(0)?,
+        api_name: row.get(1)?,
+        action: row.get(2)?,
+        symbol: row.get(3)?,
+        request_params: row.get(4)?,
+        status: row.get(5)?,
+        response_summary: row.get(6)?,
+        response_details: row.get(7)?,
+        duration_ms: row.get(8)?,
+        created_at: row.get(9)?,
+        retried_from: row.get(10)?,
+    })
+}
 
 /// Insert a new API log entry
 pub fn insert_api_log(conn: &Connection, log: &NewApiLog) -> rusqlite::Result<i64> {
     conn.execute(
-        "INSERT INTO api_logs (api_name, action, symbol, request_params, status, response_summary, response_details, duration_ms)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "INSERT INTO api_logs (api_name, action, symbol, request_params, status, response_summary, response_details, duration_ms, retried_from)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         params![
             log.api_name,
             log.action,
@@ -15,6 +33,7 @@ pub fn insert_api_log(conn: &Connection, log: &NewApiLog) -> rusqlite::Result<i6
             log.response_summary,
             log.response_details,
             log.duration_ms,
+            log.retried_from,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -22,28 +41,15 @@ pub fn insert_api_log(conn: &Connection, log: &NewApiLog) -> rusqlite::Result<i6
 
 /// Get all API logs, most recent first
 pub fn get_all_logs(conn: &Connection, limit: i64) -> rusqlite::Result<Vec<ApiLog>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, api_name, action, symbol, request_params, status, response_summary, response_details, duration_ms, created_at
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LOG_COLUMNS}
          FROM api_logs
          ORDER BY created_at DESC
-         LIMIT ?1",
-    )?;
+         LIMIT ?1"
+    ))?;
 
     let logs = stmt
-        .query_map([limit], |row| {
-            Ok(ApiLog {
-                id: row.get(0)?,
-                api_name: row.get(1)?,
-                action: row.get(2)?,
-                symbol: row.get(3)?,
-                request_params: row.get(4)?,
-                status: row.get(5)?,
-                response_summary: row.get(6)?,
-                response_details: row.get(7)?,
-                duration_ms: row.get(8)?,
-                created_at: row.get(9)?,
-            })
-        })?
+        .query_map([limit], log_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(logs)
@@ -52,51 +58,40 @@ pub fn get_all_logs(conn: &Connection, limit: i64) -> rusqlite::Result<Vec<ApiLo
 /// Get a single log entry by ID
 pub fn get_log_by_id(conn: &Connection, id: i64) -> rusqlite::Result<Option<ApiLog>> {
     conn.query_row(
-        "SELECT id, api_name, action, symbol, request_params, status, response_summary, response_details, duration_ms, created_at
-         FROM api_logs WHERE id = ?1",
+        &format!("SELECT {LOG_COLUMNS} FROM api_logs WHERE id = ?1"),
         [id],
-        |row| {
-            Ok(ApiLog {
-                id: row.get(0)?,
-                api_name: row.get(1)?,
-                action: row.get(2)?,
-                symbol: row.get(3)?,
-                request_params: row.get(4)?,
-                status: row.get(5)?,
-                response_summary: row.get(6)?,
-                response_details: row.get(7)?,
-                duration_ms: row.get(8)?,
-                created_at: row.get(9)?,
-            })
-        },
+        log_from_row,
     )
     .optional()
 }
 
+/// Get the entries written by retrying the given entry, oldest first
+pub fn get_retries(conn: &Connection, id: i64) -> rusqlite::Result<Vec<ApiLog>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LOG_COLUMNS}
+         FROM api_logs
+         WHERE retried_from = ?1
+         ORDER BY id ASC"
+    ))?;
+
+    let logs = stmt
+        .query_map([id], log_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(logs)
+}
+
 /// Get failed logs since a given ID (for polling)
 pub fn get_failed_logs_since(conn: &Connection, since_id: i64) -> rusqlite::Result<Vec<ApiLog>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, api_name, action, symbol, request_params, status, response_summary, response_details, duration_ms, created_at
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LOG_COLUMNS}
          FROM api_logs
          WHERE id > ?1 AND status = 'error'
-         ORDER BY id ASC",
-    )?;
+         ORDER BY id ASC"
+    ))?;
 
     let logs = stmt
-        .query_map([since_id], |row| {
-            Ok(ApiLog {
-                id: row.get(0)?,
-                api_name: row.get(1)?,
-                action: row.get(2)?,
-                symbol: row.get(3)?,
-                request_params: row.get(4)?,
-                status: row.get(5)?,
-                response_summary: row.get(6)?,
-                response_details: row.get(7)?,
-                duration_ms: row.get(8)?,
-                created_at: row.get(9)?,
-            })
-        })?
+        .query_map([since_id], log_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(logs)
@@ -1,32 +1,214 @@
 use rusqlite::Connection;
 use std::collections::HashMap;
 
-/// Returns a map of account_id -> sum of amount_cents for all transactions
-/// that have an account_id set.
-pub fn get_cash_account_balances(conn: &Connection) -> rusqlite::Result<HashMap<i64, i64>> {
+use crate::db::queries::trading;
+
+/// Returns account_id -> (sum of amount_cents, date of the last transaction)
+/// for posted transactions, only counting those on or before `as_of` if set.
+pub fn get_account_totals(
+    conn: &Connection,
+    as_of: Option<&str>,
+) -> rusqlite::Result<HashMap<i64, (i64, String)>> {
     let mut stmt = conn.prepare(
-        "SELECT account_id, COALESCE(SUM(amount_cents), 0)
+        "SELECT account_id, COALESCE(SUM(amount_cents), 0), MAX(date)
          FROM transactions
-         WHERE account_id IS NOT NULL
+         WHERE account_id IS NOT NULL AND status = 'posted'
+           AND (?1 IS NULL OR date <= ?1)
          GROUP BY account_id",
     )?;
+    let rows = stmt
+        .query_map([as_of], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(rows)
+}
 
-    let rows: Vec<(i64, i64)> = stmt
-        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    let mut map = HashMap::new();
-    for (account_id, balance) in rows {
-        map.insert(account_id, balance);
-    }
-    Ok(map)
+/// Returns a map of account_id -> sum of amount_cents for all posted
+/// transactions that have an account_id set.
+pub fn get_cash_account_balances(conn: &Connection) -> rusqlite::Result<HashMap<i64, i64>> {
+    Ok(get_account_totals(conn, None)?
+        .into_iter()
+        .map(|(account_id, (balance, _))| (account_id, balance))
+        .collect())
 }
 
 /// Returns the sum of amount_cents for all transactions without an account.
 pub fn get_unassociated_cash_balance(conn: &Connection) -> rusqlite::Result<i64> {
     conn.query_row(
-        "SELECT COALESCE(SUM(amount_cents), 0) FROM transactions WHERE account_id IS NULL",
+        "SELECT COALESCE(SUM(amount_cents), 0) FROM transactions
+         WHERE account_id IS NULL AND status = 'posted'",
         [],
         |row| row.get(0),
     )
 }
+
+/// Returns the sum of amount_cents for all transactions of one account.
+pub fn get_account_balance(conn: &Connection, account_id: i64) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(amount_cents), 0) FROM transactions
+         WHERE account_id = ? AND status = 'posted'",
+        [account_id],
+        |row| row.get(0),
+    )
+}
+
+/// Returns the sum of amount_cents for an account's transactions that come
+/// before the transaction `(date, id)`, ordered by date with ties broken by ID.
+pub fn get_account_balance_before(
+    conn: &Connection,
+    account_id: i64,
+    date: &str,
+    id: i64,
+) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(amount_cents), 0) FROM transactions
+         WHERE account_id = ?1 AND status = 'posted'
+           AND (date < ?2 OR (date = ?2 AND id < ?3))",
+        rusqlite::params![account_id, date, id],
+        |row| row.get(0),
+    )
+}
+
+/// Returns the sum of amount_cents for an account's transactions on or
+/// before `date`.
+pub fn get_account_balance_as_of(
+    conn: &Connection,
+    account_id: i64,
+    date: &str,
+) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(amount_cents), 0) FROM transactions
+         WHERE account_id = ?1 AND status = 'posted' AND date <= ?2",
+        rusqlite::params![account_id, date],
+        |row| row.get(0),
+    )
+}
+
+/// Returns (spending, payments) of an account between `from` and `to`
+/// inclusive: spending is the negated sum of transactions that are not
+/// transfers (refunds reduce it), payments the sum of incoming transfers.
+pub fn get_card_cycle_totals(
+    conn: &Connection,
+    account_id: i64,
+    from: &str,
+    to: &str,
+) -> rusqlite::Result<(i64, i64)> {
+    conn.query_row(
+        "SELECT COALESCE(-SUM(CASE WHEN transfer_pair_id IS NULL THEN amount_cents END), 0),
+                COALESCE(SUM(CASE WHEN transfer_pair_id IS NOT NULL AND amount_cents > 0
+                                  THEN amount_cents END), 0)
+         FROM transactions
+         WHERE account_id = ?1 AND status = 'posted' AND date >= ?2 AND date <= ?3",
+        rusqlite::params![account_id, from, to],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Returns (date, sum of amount_cents) per day for an account's transactions
+/// on or after `from_date`, newest first.
+pub fn get_account_daily_sums(
+    conn: &Connection,
+    account_id: i64,
+    from_date: &str,
+) -> rusqlite::Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, SUM(amount_cents)
+         FROM transactions
+         WHERE account_id = ?1 AND date >= ?2 AND status = 'posted'
+         GROUP BY date
+         ORDER BY date DESC",
+    )?;
+    let rows = stmt
+        .query_map(rusqlite::params![account_id, from_date], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Returns month (`YYYY-MM`) -> sum of amount_cents for an account's
+/// transactions on or after `from_date` in a category named "Interest".
+pub fn get_monthly_interest(
+    conn: &Connection,
+    account_id: i64,
+    from_date: &str,
+) -> rusqlite::Result<HashMap<String, i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT substr(e.date, 1, 7) AS month, SUM(e.amount_cents)
+         FROM transactions e
+         JOIN categories c ON c.id = e.category_id
+         WHERE e.account_id = ?1 AND e.date >= ?2 AND e.status = 'posted' AND c.name = 'Interest' COLLATE NOCASE
+         GROUP BY month",
+    )?;
+    let rows = stmt
+        .query_map(rusqlite::params![account_id, from_date], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(rows)
+}
+
+/// Trading activity row for the cash ledger:
+/// (account_id, date, activity_type, quantity, unit_price_cents, fee_cents in the activity currency)
+pub type LedgerActivityRow = (i64, String, String, Option<f64>, Option<i64>, i64);
+
+/// Transaction row for the cash ledger:
+/// (account_id, date, amount_cents, category_id, has_transfer_pair)
+pub type LedgerTransactionRow = (i64, String, i64, Option<i64>, bool);
+
+const LEDGER_ACCOUNTS: &str = "SELECT id FROM accounts
+     WHERE derive_cash_from_trading = 1 AND account_type = 'Securities'";
+
+/// IDs of securities accounts that derive their cash balance from trading.
+pub fn get_ledger_account_ids(conn: &Connection) -> rusqlite::Result<Vec<i64>> {
+    let mut stmt = conn.prepare(LEDGER_ACCOUNTS)?;
+    let rows = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Trading activities of cash ledger accounts, oldest first.
+pub fn get_ledger_activities(conn: &Connection) -> rusqlite::Result<Vec<LedgerActivityRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT account_id, date, activity_type, quantity, unit_price_cents, {}
+         FROM trading_activities t
+         WHERE deleted_at IS NULL AND account_id IN ({LEDGER_ACCOUNTS})
+         ORDER BY date ASC, id ASC",
+        trading::fee_in_currency_sql("t")
+    ))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Posted transactions of cash ledger accounts, oldest first.
+pub fn get_ledger_transactions(conn: &Connection) -> rusqlite::Result<Vec<LedgerTransactionRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT account_id, date, amount_cents, category_id, transfer_pair_id IS NOT NULL
+         FROM transactions
+         WHERE status = 'posted' AND account_id IN ({LEDGER_ACCOUNTS})
+         ORDER BY date ASC, id ASC"
+    ))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
@@ -1,7 +1,20 @@
 use crate::models::category::{Category, CategoryWithPath, NewCategory};
+use crate::palette;
 use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
 use tracing::{info, warn};
 
+/// Map a category row followed by `path` and `depth` columns.
+fn category_with_path_from_row(row: &rusqlite::Row) -> rusqlite::Result<CategoryWithPath> {
+    let category = category_from_row(row)?;
+    Ok(CategoryWithPath {
+        text_color: palette::text_color(&category.color).to_string(),
+        category,
+        path: row.get(8)?,
+        depth: row.get(9)?,
+    })
+}
+
 fn category_from_row(row: &rusqlite::Row) -> rusqlite::Result<Category> {
     Ok(Category {
         id: row.get(0)?,
@@ -47,13 +60,7 @@ pub fn list_categories_with_path(conn: &Connection) -> rusqlite::Result<Vec<Cate
     )?;
 
     let categories = stmt
-        .query_map([], |row| {
-            Ok(CategoryWithPath {
-                category: category_from_row(row)?,
-                path: row.get(8)?,
-                depth: row.get(9)?,
-            })
-        })?
+        .query_map([], category_with_path_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(categories)
@@ -106,6 +113,15 @@ pub fn update_category(
     Ok(rows > 0)
 }
 
+/// Change only the color, which is allowed for built-in categories too.
+pub fn set_category_color(conn: &Connection, id: i64, color: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE categories SET color = ?, updated_at = datetime('now') WHERE id = ?",
+        params![color, id],
+    )?;
+    Ok(())
+}
+
 pub fn delete_category(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
     let rows = conn.execute("DELETE FROM categories WHERE id = ? AND built_in = 0", [id])?;
     if rows > 0 {
@@ -129,6 +145,50 @@ pub fn get_top_level_categories(conn: &Connection) -> rusqlite::Result<Vec<Categ
     Ok(categories)
 }
 
+/// ID of the built-in Transfers category.
+pub fn transfers_category_id(all_categories: &[Category]) -> Option<i64> {
+    all_categories
+        .iter()
+        .find(|c| c.built_in && c.name == "Transfers")
+        .map(|c| c.id)
+}
+
+/// IDs of the built-in Transfers category and its descendants, which
+/// analytics leave out as money moving between the user's own accounts.
+pub fn transfers_excluded_ids(all_categories: &[Category]) -> HashSet<i64> {
+    let mut children_map: HashMap<i64, Vec<i64>> = HashMap::new();
+    for cat in all_categories {
+        if let Some(parent_id) = cat.parent_id {
+            children_map.entry(parent_id).or_default().push(cat.id);
+        }
+    }
+    transfers_category_id(all_categories)
+        .map(|transfers_id| collect_subtree_ids(transfers_id, &children_map))
+        .unwrap_or_default()
+}
+
+/// Collect a category and all its descendants into a set of IDs.
+fn collect_subtree_ids(root_id: i64, children_map: &HashMap<i64, Vec<i64>>) -> HashSet<i64> {
+    let mut ids = HashSet::new();
+    let mut stack = vec![root_id];
+    while let Some(id) = stack.pop() {
+        ids.insert(id);
+        if let Some(children) = children_map.get(&id) {
+            stack.extend(children);
+        }
+    }
+    ids
+}
+
+/// Count the categories [`delete_all_categories`] would remove.
+pub fn count_custom_categories(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM categories WHERE built_in = 0",
+        [],
+        |row| row.get(0),
+    )
+}
+
 pub fn delete_all_categories(conn: &Connection) -> rusqlite::Result<usize> {
     let rows = conn.execute("DELETE FROM categories WHERE built_in = 0", [])?;
     warn!(count = rows, "Deleted all non-built-in categories");
@@ -154,13 +214,7 @@ pub fn get_category_with_path(
         FROM category_path
         WHERE id = ?",
         [id],
-        |row| {
-            Ok(CategoryWithPath {
-                category: category_from_row(row)?,
-                path: row.get(8)?,
-                depth: row.get(9)?,
-            })
-        },
+        category_with_path_from_row,
     )
     .optional()
 }
@@ -0,0 +1,74 @@
+use crate::models::NetWorthSummary;
+use rusqlite::Connection;
+use serde::Serialize;
+
+/// Counts and deltas of everything recorded after a point in time.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Digest {
+    /// Lower bound (UTC, `YYYY-MM-DD HH:MM:SS`), inclusive.
+    pub since: String,
+    pub transactions_added: i64,
+    /// Older transactions changed since, e.g. by applying a rule or bulk edit.
+    pub transactions_updated: i64,
+    pub trading_activities_added: i64,
+    pub market_data_fetched: i64,
+    pub api_errors: i64,
+    /// Change of net worth from the day before `since` to the latest day of
+    /// the net worth series, market moves included. Set by
+    /// [`Digest::with_net_worth_change`].
+    pub net_worth_change_cents: i64,
+}
+
+impl Digest {
+    /// Date part of `since`, for display.
+    pub fn since_date(&self) -> &str {
+        self.since.get(..10).unwrap_or(&self.since)
+    }
+
+    /// Fill in the net worth change from the net worth series.
+    pub fn with_net_worth_change(mut self, summary: &NetWorthSummary) -> Self {
+        let since_date = self.since_date();
+        let before = summary
+            .data_points
+            .iter()
+            .take_while(|p| p.date.as_str() < since_date)
+            .last()
+            .map_or(0, |p| p.net_worth_cents);
+        self.net_worth_change_cents = summary.current_net_worth_cents - before;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions_added == 0
+            && self.transactions_updated == 0
+            && self.trading_activities_added == 0
+            && self.market_data_fetched == 0
+            && self.api_errors == 0
+    }
+}
+
+/// Aggregate what changed from `since` on, using the `created_at`/`updated_at`
+/// columns (and `fetched_at` for market data). The net worth change is left
+/// at zero, see [`Digest::with_net_worth_change`].
+pub fn get_digest(conn: &Connection, since: &str) -> rusqlite::Result<Digest> {
+    conn.query_row(
+        "SELECT
+            (SELECT COUNT(*) FROM transactions WHERE created_at >= ?1),
+            (SELECT COUNT(*) FROM transactions WHERE created_at < ?1 AND updated_at >= ?1),
+            (SELECT COUNT(*) FROM trading_activities WHERE created_at >= ?1 AND deleted_at IS NULL),
+            (SELECT COUNT(*) FROM market_data WHERE fetched_at >= ?1),
+            (SELECT COUNT(*) FROM api_logs WHERE status = 'error' AND created_at >= ?1)",
+        [since],
+        |row| {
+            Ok(Digest {
+                since: since.to_string(),
+                transactions_added: row.get(0)?,
+                transactions_updated: row.get(1)?,
+                trading_activities_added: row.get(2)?,
+                market_data_fetched: row.get(3)?,
+                api_errors: row.get(4)?,
+                net_worth_change_cents: 0,
+            })
+        },
+    )
+}
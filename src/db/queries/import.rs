@@ -2,7 +2,9 @@ use rusqlite::{params, Connection};
 use tracing::{debug, info};
 
 use crate::error::AppResult;
-use crate::models::{ImportRow, ImportSession, ImportStatus};
+use crate::models::{
+    ImportFileStats, ImportRow, ImportSession, ImportStatus, OpenImportSession, PendingMatch,
+};
 use crate::services::csv_parser::ParsedTransaction;
 
 // Session operations
@@ -16,36 +18,105 @@ pub fn create_session(conn: &Connection, id: &str) -> AppResult<ImportSession> {
     get_session(conn, id)
 }
 
-pub fn get_session(conn: &Connection, id: &str) -> AppResult<ImportSession> {
-    let mut stmt = conn.prepare(
-        "SELECT id, status, total_rows, processed_rows, error_count, errors, created_at, updated_at
-         FROM import_sessions WHERE id = ?1",
-    )?;
+const SESSION_COLUMNS: &str =
+    "id, status, total_rows, processed_rows, error_count, errors, created_at, updated_at,
+     tag_ids, resume_from_row_index, file_stats";
 
-    let session = stmt.query_row(params![id], |row| {
-        let errors_json: Option<String> = row.get(5)?;
-        let errors: Vec<String> = errors_json
+fn session_from_row(row: &rusqlite::Row) -> rusqlite::Result<ImportSession> {
+    let errors_json: Option<String> = row.get(5)?;
+    let errors: Vec<String> = errors_json
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+
+    Ok(ImportSession {
+        id: row.get(0)?,
+        status: row
+            .get::<_, String>(1)?
+            .parse()
+            .unwrap_or(ImportStatus::Failed),
+        total_rows: row.get(2)?,
+        processed_rows: row.get(3)?,
+        error_count: row.get(4)?,
+        errors,
+        tag_ids: parse_id_list(row.get(8)?).unwrap_or_default(),
+        resume_from_row_index: row.get(9)?,
+        file_stats: row
+            .get::<_, Option<String>>(10)?
             .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
-
-        Ok(ImportSession {
-            id: row.get(0)?,
-            status: row
-                .get::<_, String>(1)?
-                .parse()
-                .unwrap_or(ImportStatus::Failed),
-            total_rows: row.get(2)?,
-            processed_rows: row.get(3)?,
-            error_count: row.get(4)?,
-            errors,
-            created_at: row.get(6)?,
-            updated_at: row.get(7)?,
-        })
-    })?;
+            .unwrap_or_default(),
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+pub fn get_session(conn: &Connection, id: &str) -> AppResult<ImportSession> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SESSION_COLUMNS} FROM import_sessions WHERE id = ?1"
+    ))?;
+
+    let session = stmt.query_row(params![id], session_from_row)?;
 
     Ok(session)
 }
 
+/// Sessions in preview or left importing, most recently changed first, with
+/// how many of their rows have a category.
+pub fn list_open_sessions(conn: &Connection) -> AppResult<Vec<OpenImportSession>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SESSION_COLUMNS},
+                (SELECT COUNT(*) FROM import_rows r
+                 WHERE r.session_id = import_sessions.id AND r.category_id IS NOT NULL)
+         FROM import_sessions
+         WHERE status IN (?1, ?2)
+         ORDER BY updated_at DESC, created_at DESC"
+    ))?;
+
+    let sessions = stmt
+        .query_map(
+            params![
+                ImportStatus::Preview.as_str(),
+                ImportStatus::Importing.as_str()
+            ],
+            |row| {
+                Ok(OpenImportSession {
+                    session: session_from_row(row)?,
+                    categorized_rows: row.get(11)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(sessions)
+}
+
+/// Decode a JSON array of tag or rule ids stored in a TEXT column.
+fn parse_id_list(json: Option<String>) -> Option<Vec<i64>> {
+    json.and_then(|s| serde_json::from_str(&s).ok())
+}
+
+pub fn update_session_tags(conn: &Connection, id: &str, tag_ids: &[i64]) -> AppResult<()> {
+    let tag_ids_json = serde_json::to_string(tag_ids).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE import_sessions SET tag_ids = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![id, tag_ids_json],
+    )?;
+    debug!(session_id = %id, count = tag_ids.len(), "Updated import session tags");
+    Ok(())
+}
+
+pub fn update_session_file_stats(
+    conn: &Connection,
+    id: &str,
+    file_stats: &[ImportFileStats],
+) -> AppResult<()> {
+    let file_stats_json = serde_json::to_string(file_stats).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE import_sessions SET file_stats = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![id, file_stats_json],
+    )?;
+    Ok(())
+}
+
 pub fn update_session_status(conn: &Connection, id: &str, status: ImportStatus) -> AppResult<()> {
     conn.execute(
         "UPDATE import_sessions SET status = ?2, updated_at = datetime('now') WHERE id = ?1",
@@ -82,18 +153,22 @@ pub fn update_session_errors(
     Ok(())
 }
 
-pub fn increment_session_processed(conn: &Connection, id: &str) -> AppResult<()> {
-    conn.execute(
-        "UPDATE import_sessions SET processed_rows = processed_rows + 1, updated_at = datetime('now') WHERE id = ?1",
-        params![id],
-    )?;
-    Ok(())
-}
-
-pub fn increment_session_error_count(conn: &Connection, id: &str) -> AppResult<()> {
+/// Record a committed import batch: add its row and error counts to the
+/// session and move the resume point past it. Call inside the batch's
+/// transaction so the checkpoint commits together with the rows.
+pub fn checkpoint_session(
+    conn: &Connection,
+    id: &str,
+    processed_rows: i64,
+    error_count: i64,
+    resume_from_row_index: i64,
+) -> AppResult<()> {
     conn.execute(
-        "UPDATE import_sessions SET error_count = error_count + 1, updated_at = datetime('now') WHERE id = ?1",
-        params![id],
+        "UPDATE import_sessions
+         SET processed_rows = processed_rows + ?2, error_count = error_count + ?3,
+             resume_from_row_index = ?4, updated_at = datetime('now')
+         WHERE id = ?1",
+        params![id, processed_rows, error_count, resume_from_row_index],
     )?;
     Ok(())
 }
@@ -126,66 +201,89 @@ pub fn insert_row(
     session_id: &str,
     row_index: i64,
     data: &ParsedTransaction,
+    source_file: Option<&str>,
 ) -> AppResult<i64> {
     let data_json = serde_json::to_string(data).unwrap();
     conn.execute(
-        "INSERT INTO import_rows (session_id, row_index, data) VALUES (?1, ?2, ?3)",
-        params![session_id, row_index, data_json],
+        "INSERT INTO import_rows (session_id, row_index, data, source_file) VALUES (?1, ?2, ?3, ?4)",
+        params![session_id, row_index, data_json, source_file],
     )?;
     Ok(conn.last_insert_rowid())
 }
 
+const ROW_COLUMNS: &str =
+    "r.id, r.session_id, r.row_index, r.data, r.category_id, c.name, r.status, r.error,
+     r.tag_ids, r.merge_pending, p.id, p.date, p.description, r.rule_ids,
+     r.source_file, r.category_rule_id
+     FROM import_rows r
+     LEFT JOIN categories c ON r.category_id = c.id
+     LEFT JOIN transactions p ON p.id = r.pending_match_id AND p.status = 'pending'";
+
+fn import_row_from_row(row: &rusqlite::Row) -> rusqlite::Result<ImportRow> {
+    let data_json: String = row.get(3)?;
+    let data: ParsedTransaction =
+        serde_json::from_str(&data_json).unwrap_or_else(|_| ParsedTransaction {
+            date: String::new(),
+            amount: String::new(),
+            currency: "USD".to_string(),
+            description: String::new(),
+            category: None,
+            account_id: None,
+            tags: vec![],
+            notes: None,
+            value_date: None,
+            payer: None,
+            payee: None,
+            reference: None,
+            transaction_type: None,
+            counterparty_iban: None,
+            creditor_id: None,
+            mandate_reference: None,
+            customer_reference: None,
+            row_number: 0,
+        });
+    let pending_match = match row.get::<_, Option<i64>>(10)? {
+        Some(transaction_id) => Some(PendingMatch {
+            transaction_id,
+            date: row.get(11)?,
+            description: row.get(12)?,
+        }),
+        None => None,
+    };
+
+    Ok(ImportRow {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        row_index: row.get(2)?,
+        data,
+        source_file: row.get(14)?,
+        category_id: row.get(4)?,
+        category_name: row.get(5)?,
+        status: row.get(6)?,
+        error: row.get(7)?,
+        tag_ids: parse_id_list(row.get(8)?),
+        merge_pending: row.get(9)?,
+        pending_match,
+        rule_ids: parse_id_list(row.get(13)?).unwrap_or_default(),
+        category_rule_id: row.get(15)?,
+    })
+}
+
 pub fn get_rows_paginated(
     conn: &Connection,
     session_id: &str,
     limit: i64,
     offset: i64,
 ) -> AppResult<Vec<ImportRow>> {
-    let mut stmt = conn.prepare(
-        "SELECT r.id, r.session_id, r.row_index, r.data, r.category_id, c.name, r.status, r.error
-         FROM import_rows r
-         LEFT JOIN categories c ON r.category_id = c.id
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ROW_COLUMNS}
          WHERE r.session_id = ?1
          ORDER BY r.row_index
-         LIMIT ?2 OFFSET ?3",
-    )?;
+         LIMIT ?2 OFFSET ?3"
+    ))?;
 
     let rows = stmt
-        .query_map(params![session_id, limit, offset], |row| {
-            let data_json: String = row.get(3)?;
-            let data: ParsedTransaction =
-                serde_json::from_str(&data_json).unwrap_or_else(|_| ParsedTransaction {
-                    date: String::new(),
-                    amount: String::new(),
-                    currency: "USD".to_string(),
-                    description: String::new(),
-                    category: None,
-                    account_id: None,
-                    tags: vec![],
-                    notes: None,
-                    value_date: None,
-                    payer: None,
-                    payee: None,
-                    reference: None,
-                    transaction_type: None,
-                    counterparty_iban: None,
-                    creditor_id: None,
-                    mandate_reference: None,
-                    customer_reference: None,
-                    row_number: 0,
-                });
-
-            Ok(ImportRow {
-                id: row.get(0)?,
-                session_id: row.get(1)?,
-                row_index: row.get(2)?,
-                data,
-                category_id: row.get(4)?,
-                category_name: row.get(5)?,
-                status: row.get(6)?,
-                error: row.get(7)?,
-            })
-        })?
+        .query_map(params![session_id, limit, offset], import_row_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(rows)
@@ -201,50 +299,14 @@ pub fn count_rows(conn: &Connection, session_id: &str) -> AppResult<i64> {
 }
 
 pub fn get_pending_rows(conn: &Connection, session_id: &str) -> AppResult<Vec<ImportRow>> {
-    let mut stmt = conn.prepare(
-        "SELECT r.id, r.session_id, r.row_index, r.data, r.category_id, c.name, r.status, r.error
-         FROM import_rows r
-         LEFT JOIN categories c ON r.category_id = c.id
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ROW_COLUMNS}
          WHERE r.session_id = ?1 AND r.status = 'pending'
-         ORDER BY r.row_index",
-    )?;
+         ORDER BY r.row_index"
+    ))?;
 
     let rows = stmt
-        .query_map(params![session_id], |row| {
-            let data_json: String = row.get(3)?;
-            let data: ParsedTransaction =
-                serde_json::from_str(&data_json).unwrap_or_else(|_| ParsedTransaction {
-                    date: String::new(),
-                    amount: String::new(),
-                    currency: "USD".to_string(),
-                    description: String::new(),
-                    category: None,
-                    account_id: None,
-                    tags: vec![],
-                    notes: None,
-                    value_date: None,
-                    payer: None,
-                    payee: None,
-                    reference: None,
-                    transaction_type: None,
-                    counterparty_iban: None,
-                    creditor_id: None,
-                    mandate_reference: None,
-                    customer_reference: None,
-                    row_number: 0,
-                });
-
-            Ok(ImportRow {
-                id: row.get(0)?,
-                session_id: row.get(1)?,
-                row_index: row.get(2)?,
-                data,
-                category_id: row.get(4)?,
-                category_name: row.get(5)?,
-                status: row.get(6)?,
-                error: row.get(7)?,
-            })
-        })?
+        .query_map(params![session_id], import_row_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(rows)
@@ -256,25 +318,100 @@ pub fn update_row_category(
     category_id: Option<i64>,
 ) -> AppResult<()> {
     conn.execute(
-        "UPDATE import_rows SET category_id = ?2 WHERE id = ?1",
+        "UPDATE import_rows SET category_id = ?2, category_rule_id = NULL WHERE id = ?1",
         params![row_id, category_id],
     )?;
     Ok(())
 }
 
+/// Set a row's category as assigned by a rule.
+pub fn set_row_rule_category(
+    conn: &Connection,
+    row_id: i64,
+    category_id: i64,
+    rule_id: i64,
+) -> AppResult<()> {
+    conn.execute(
+        "UPDATE import_rows SET category_id = ?2, category_rule_id = ?3 WHERE id = ?1",
+        params![row_id, category_id, rule_id],
+    )?;
+    Ok(())
+}
+
+/// Set a row's tag override. `None` makes the row inherit the session tags.
+pub fn update_row_tags(conn: &Connection, row_id: i64, tag_ids: Option<&[i64]>) -> AppResult<()> {
+    let tag_ids_json =
+        tag_ids.map(|ids| serde_json::to_string(ids).unwrap_or_else(|_| "[]".to_string()));
+    conn.execute(
+        "UPDATE import_rows SET tag_ids = ?2 WHERE id = ?1",
+        params![row_id, tag_ids_json],
+    )?;
+    Ok(())
+}
+
+/// Remember which rules changed a row, to credit them once it is imported.
+pub fn update_row_rules(conn: &Connection, row_id: i64, rule_ids: &[i64]) -> AppResult<()> {
+    let rule_ids_json = serde_json::to_string(rule_ids).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE import_rows SET rule_ids = ?2 WHERE id = ?1",
+        params![row_id, rule_ids_json],
+    )?;
+    Ok(())
+}
+
 pub fn update_all_rows_category(
     conn: &Connection,
     session_id: &str,
     category_id: Option<i64>,
 ) -> AppResult<usize> {
     let updated = conn.execute(
-        "UPDATE import_rows SET category_id = ?2 WHERE session_id = ?1 AND status = 'pending'",
+        "UPDATE import_rows SET category_id = ?2, category_rule_id = NULL
+         WHERE session_id = ?1 AND status = 'pending'",
         params![session_id, category_id],
     )?;
     debug!(session_id = %session_id, count = updated, "Updated category for all import rows");
     Ok(updated)
 }
 
+/// Set the category of the given rows that are still pending.
+pub fn update_rows_category(
+    conn: &Connection,
+    row_ids: &[i64],
+    category_id: Option<i64>,
+) -> AppResult<usize> {
+    let mut stmt = conn.prepare(
+        "UPDATE import_rows SET category_id = ?2, category_rule_id = NULL
+         WHERE id = ?1 AND status = 'pending'",
+    )?;
+    let mut updated = 0;
+    for row_id in row_ids {
+        updated += stmt.execute(params![row_id, category_id])?;
+    }
+    Ok(updated)
+}
+
+/// Remember the pending transaction an import row probably books.
+pub fn set_row_pending_match(
+    conn: &Connection,
+    row_id: i64,
+    transaction_id: Option<i64>,
+) -> AppResult<()> {
+    conn.execute(
+        "UPDATE import_rows SET pending_match_id = ?2 WHERE id = ?1",
+        params![row_id, transaction_id],
+    )?;
+    Ok(())
+}
+
+/// Choose whether a row is merged into its pending match or imported as new.
+pub fn update_row_merge(conn: &Connection, row_id: i64, merge: bool) -> AppResult<()> {
+    conn.execute(
+        "UPDATE import_rows SET merge_pending = ?2 WHERE id = ?1",
+        params![row_id, merge],
+    )?;
+    Ok(())
+}
+
 pub fn update_row_data(conn: &Connection, row_id: i64, data: &ParsedTransaction) -> AppResult<()> {
     let data_json = serde_json::to_string(data).unwrap();
     conn.execute(
@@ -299,3 +436,20 @@ pub fn mark_row_error(conn: &Connection, row_id: i64, error: &str) -> AppResult<
     )?;
     Ok(())
 }
+
+/// Error messages of all failed rows, prefixed with their 1-based row number.
+pub fn list_row_errors(conn: &Connection, session_id: &str) -> AppResult<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT row_index, error FROM import_rows
+         WHERE session_id = ?1 AND status = 'error'
+         ORDER BY row_index",
+    )?;
+    let errors = stmt
+        .query_map(params![session_id], |row| {
+            let index: i64 = row.get(0)?;
+            let error: Option<String> = row.get(1)?;
+            Ok(format!("Row {}: {}", index + 1, error.unwrap_or_default()))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(errors)
+}
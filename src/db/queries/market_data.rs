@@ -1,5 +1,10 @@
-use crate::models::market_data::{MarketData, NewMarketData, SymbolDataCoverage, SymbolMetadata};
+use crate::models::market_data::{
+    fx_pair, fx_rate, minor_currency_unit, MarketData, NewMarketData, SymbolDataCoverage,
+    SymbolMetadata,
+};
+use chrono::Datelike;
 use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
 use tracing::{info, warn};
 
 /// Maximum gap in days that's considered acceptable (weekends + holidays)
@@ -8,16 +13,18 @@ pub const MAX_GAP_DAYS: i64 = 5;
 /// Insert or update market data for a symbol on a date
 pub fn upsert_market_data(conn: &Connection, data: &NewMarketData) -> rusqlite::Result<()> {
     conn.execute(
-        "INSERT INTO market_data (symbol, date, close_price_cents, currency)
-         VALUES (?1, ?2, ?3, ?4)
+        "INSERT INTO market_data (symbol, date, close_price_cents, close_price, currency)
+         VALUES (?1, ?2, ?3, ?4, ?5)
          ON CONFLICT(symbol, date) DO UPDATE SET
          close_price_cents = excluded.close_price_cents,
+         close_price = excluded.close_price,
          currency = excluded.currency,
          fetched_at = datetime('now')",
         params![
             data.symbol,
             data.date,
             data.close_price_cents,
+            data.close_price,
             data.currency
         ],
     )?;
@@ -108,8 +115,42 @@ pub fn get_prices_for_symbol(conn: &Connection, symbol: &str) -> rusqlite::Resul
     Ok(data)
 }
 
-/// Get data coverage summary for all symbols that have positions (both open and closed)
-pub fn get_symbol_coverage(conn: &Connection) -> rusqlite::Result<Vec<SymbolDataCoverage>> {
+/// Get market data for a symbol between two dates (inclusive, `YYYY-MM-DD`)
+pub fn get_prices_between(
+    conn: &Connection,
+    symbol: &str,
+    from: &str,
+    to: &str,
+) -> rusqlite::Result<Vec<MarketData>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, symbol, date, close_price_cents, currency, fetched_at
+         FROM market_data
+         WHERE symbol = ?1 AND date >= ?2 AND date <= ?3
+         ORDER BY date",
+    )?;
+
+    let data = stmt
+        .query_map([symbol, from, to], |row| {
+            Ok(MarketData {
+                id: row.get(0)?,
+                symbol: row.get(1)?,
+                date: row.get(2)?,
+                close_price_cents: row.get(3)?,
+                currency: row.get(4)?,
+                fetched_at: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(data)
+}
+
+/// Get data coverage summary for all symbols that have positions (both open and closed).
+/// `today` (`YYYY-MM-DD`) is the date open positions need data up to.
+pub fn get_symbol_coverage(
+    conn: &Connection,
+    today: &str,
+) -> rusqlite::Result<Vec<SymbolDataCoverage>> {
     // Get symbols with their activity date ranges (all non-cash symbols)
     let mut stmt = conn.prepare(
         "WITH position_symbols AS (
@@ -117,11 +158,14 @@ pub fn get_symbol_coverage(conn: &Connection) -> rusqlite::Result<Vec<SymbolData
                    MIN(date) as first_activity_date,
                    MAX(date) as last_activity_date,
                    SUM(CASE
-                       WHEN activity_type = 'BUY' THEN COALESCE(quantity, 0)
-                       WHEN activity_type = 'SELL' THEN -COALESCE(quantity, 0)
+                       WHEN activity_type IN ('BUY', 'TRANSFER_IN', 'ADD_HOLDING')
+                           THEN COALESCE(quantity, 0)
+                       WHEN activity_type IN ('SELL', 'TRANSFER_OUT', 'REMOVE_HOLDING')
+                           THEN -COALESCE(quantity, 0)
                        ELSE 0
                    END) as net_quantity
             FROM trading_activities
+            WHERE deleted_at IS NULL
             GROUP BY symbol
         ),
         market_data_summary AS (
@@ -140,14 +184,15 @@ pub fn get_symbol_coverage(conn: &Connection) -> rusqlite::Result<Vec<SymbolData
             mds.first_data_date,
             mds.last_data_date,
             COALESCE(mds.data_points, 0) as data_points,
-            ps.net_quantity
+            ps.net_quantity,
+            COALESCE(sm.ignored, 0),
+            sm.ignore_note
         FROM position_symbols ps
         LEFT JOIN market_data_summary mds ON ps.symbol = mds.symbol
+        LEFT JOIN symbol_metadata sm ON ps.symbol = sm.symbol
         ORDER BY ps.net_quantity > 0 DESC, ps.symbol",
     )?;
 
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-
     let coverage = stmt
         .query_map([], |row| {
             let last_data_date: Option<String> = row.get(5)?;
@@ -157,16 +202,16 @@ pub fn get_symbol_coverage(conn: &Connection) -> rusqlite::Result<Vec<SymbolData
 
             // For closed positions, check if data covers up to last_activity_date
             // For open positions, check if data covers up to today
-            let target_date = if is_closed {
+            let target_date: &str = if is_closed {
                 &last_activity_date
             } else {
-                &today
+                today
             };
 
             let has_current_price = last_data_date
                 .as_ref()
                 .map(|d| {
-                    d >= target_date || {
+                    d.as_str() >= target_date || {
                         // Check if within acceptable gap (for weekends/holidays)
                         if let (Ok(last), Ok(target)) = (
                             chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d"),
@@ -208,6 +253,8 @@ pub fn get_symbol_coverage(conn: &Connection) -> rusqlite::Result<Vec<SymbolData
                 missing_days,
                 has_current_price,
                 is_closed,
+                ignored: row.get(8)?,
+                ignore_note: row.get(9)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -215,70 +262,238 @@ pub fn get_symbol_coverage(conn: &Connection) -> rusqlite::Result<Vec<SymbolData
     Ok(coverage)
 }
 
-/// Get symbols that need market data updates
-/// Includes both open positions (end_date = today) and closed positions (end_date = last_activity_date)
-pub fn get_symbols_needing_data(
-    conn: &Connection,
-) -> rusqlite::Result<Vec<(String, String, String)>> {
-    // Returns (symbol, start_date, end_date) for symbols that need data
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+/// Minimum number of consecutive missing weekdays to count as a significant gap
+/// Smaller gaps are likely market holidays (Christmas, New Year, etc.)
+pub const MIN_GAP_WEEKDAYS: i64 = 5;
 
+/// A symbol with its missing `(start_date, end_date)` windows.
+pub type SymbolMissingRanges = (String, Vec<(String, String)>);
+
+/// Missing market data windows per symbol, one fetch each.
+/// Includes both open positions (end_date = today) and closed positions (end_date = last_activity_date),
+/// but not ignored symbols or windows a fetch already came back empty for.
+/// Scans every stored date, so only refreshes call it.
+pub fn get_missing_ranges_per_symbol(
+    conn: &Connection,
+    today: &str,
+) -> rusqlite::Result<Vec<SymbolMissingRanges>> {
+    // (symbol, first_activity_date, end_date, last_data_date, tail_missing)
     let mut stmt = conn.prepare(
         "WITH all_traded_symbols AS (
             SELECT symbol,
                    MIN(date) as first_activity_date,
                    MAX(date) as last_activity_date,
                    SUM(CASE
-                       WHEN activity_type = 'BUY' THEN COALESCE(quantity, 0)
-                       WHEN activity_type = 'SELL' THEN -COALESCE(quantity, 0)
+                       WHEN activity_type IN ('BUY', 'TRANSFER_IN', 'ADD_HOLDING')
+                           THEN COALESCE(quantity, 0)
+                       WHEN activity_type IN ('SELL', 'TRANSFER_OUT', 'REMOVE_HOLDING')
+                           THEN -COALESCE(quantity, 0)
                        ELSE 0
                    END) as net_quantity
             FROM trading_activities
+            WHERE deleted_at IS NULL
             GROUP BY symbol
         ),
         latest_data AS (
             SELECT symbol, MAX(date) as last_data_date
-            FROM market_data
+            FROM (
+                SELECT symbol, date FROM market_data
+                UNION ALL
+                SELECT symbol, end_date FROM market_data_empty_ranges
+            )
             GROUP BY symbol
         )
         SELECT
             ats.symbol,
-            COALESCE(ld.last_data_date, ats.first_activity_date) as start_date,
+            ats.first_activity_date,
             CASE
                 WHEN ats.net_quantity > 0 THEN ?1
                 ELSE ats.last_activity_date
             END as end_date,
-            ats.net_quantity
+            ld.last_data_date,
+            (ats.net_quantity > 0 AND ld.last_data_date < date(?1, '-' || ?2 || ' days'))
+               OR (ats.net_quantity <= 0 AND ld.last_data_date < ats.last_activity_date)
         FROM all_traded_symbols ats
         LEFT JOIN latest_data ld ON ats.symbol = ld.symbol
-        WHERE ld.last_data_date IS NULL
-           OR (ats.net_quantity > 0 AND ld.last_data_date < date(?1, '-' || ?2 || ' days'))
-           OR (ats.net_quantity <= 0 AND ld.last_data_date < ats.last_activity_date)
+        WHERE ats.symbol NOT IN (SELECT symbol FROM symbol_metadata WHERE ignored)
         ORDER BY ats.symbol",
     )?;
-
     let symbols = stmt
-        .query_map(rusqlite::params![&today, MAX_GAP_DAYS], |row| {
-            let symbol: String = row.get(0)?;
-            let start_date: String = row.get(1)?;
-            let end_date: String = row.get(2)?;
-            Ok((symbol, start_date, end_date))
+        .query_map(params![today, MAX_GAP_DAYS], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<bool>>(4)?.unwrap_or(false),
+            ))
         })?
         .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(symbols)
+    let mut dates_stmt = conn.prepare("SELECT date FROM market_data WHERE symbol = ?1")?;
+    let mut empty_stmt = conn
+        .prepare("SELECT start_date, end_date FROM market_data_empty_ranges WHERE symbol = ?1")?;
+    let mut missing = Vec::new();
+    for (symbol, first_activity_date, end_date, last_data_date, tail_missing) in symbols {
+        let Some(last_data_date) = last_data_date else {
+            missing.push((symbol, vec![(first_activity_date, end_date)]));
+            continue;
+        };
+
+        let mut dates = dates_stmt
+            .query_map([&symbol], |row| row.get(0))?
+            .collect::<Result<HashSet<String>, _>>()?;
+        // Days a fetch already came back empty for count as covered
+        for range in empty_stmt.query_map([&symbol], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })? {
+            let (start, end) = range?;
+            dates.extend(days_between(&start, &end));
+        }
+        let mut ranges = find_missing_ranges(&dates, &first_activity_date, &last_data_date);
+        // The recent end only counts once it is older than MAX_GAP_DAYS, as
+        // today's quote may simply not be published yet.
+        if tail_missing {
+            ranges.push((last_data_date, end_date));
+        }
+        if !ranges.is_empty() {
+            missing.push((symbol, ranges));
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Every date from `start` to `end` (inclusive) as `YYYY-MM-DD`.
+fn days_between(start: &str, end: &str) -> Vec<String> {
+    let start = chrono::NaiveDate::parse_from_str(start, "%Y-%m-%d");
+    let end = chrono::NaiveDate::parse_from_str(end, "%Y-%m-%d");
+    let (Ok(start), Ok(end)) = (start, end) else {
+        return Vec::new();
+    };
+    start
+        .iter_days()
+        .take_while(|d| *d <= end)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .collect()
+}
+
+/// Remember that a quote fetch for `start` to `end` returned no data, so
+/// [`get_missing_ranges_per_symbol`] stops offering the window.
+pub fn record_empty_range(
+    conn: &Connection,
+    symbol: &str,
+    start: &str,
+    end: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO market_data_empty_ranges (symbol, start_date, end_date)
+         VALUES (?1, ?2, ?3)",
+        [symbol, start, end],
+    )?;
+    Ok(())
+}
+
+/// Date ranges between `start` and `end` (inclusive) without data, skipping
+/// gaps shorter than [`MIN_GAP_WEEKDAYS`] weekdays like market holidays.
+pub fn find_missing_ranges(
+    dates: &HashSet<String>,
+    start: &str,
+    end: &str,
+) -> Vec<(String, String)> {
+    let mut missing = Vec::new();
+    let start = chrono::NaiveDate::parse_from_str(start, "%Y-%m-%d");
+    let end = chrono::NaiveDate::parse_from_str(end, "%Y-%m-%d");
+    let (Ok(start_date), Ok(end_date)) = (start, end) else {
+        return missing;
+    };
+
+    let mut current = start_date;
+    let mut gap_start: Option<chrono::NaiveDate> = None;
+    let mut gap_weekday_count = 0i64;
+
+    while current <= end_date {
+        let date_str = current.format("%Y-%m-%d").to_string();
+        let is_weekday = current.weekday().num_days_from_monday() < 5;
+
+        if is_weekday {
+            if dates.contains(&date_str) {
+                // We have data for this day - end any current gap
+                if let Some(gs) = gap_start {
+                    // Only add gap if it's significant (>= MIN_GAP_WEEKDAYS)
+                    if gap_weekday_count >= MIN_GAP_WEEKDAYS {
+                        let prev_day = current - chrono::Duration::days(1);
+                        missing.push((
+                            gs.format("%Y-%m-%d").to_string(),
+                            prev_day.format("%Y-%m-%d").to_string(),
+                        ));
+                    }
+                    gap_start = None;
+                    gap_weekday_count = 0;
+                }
+            } else {
+                // Missing data for this weekday
+                if gap_start.is_none() {
+                    gap_start = Some(current);
+                    gap_weekday_count = 1;
+                } else {
+                    gap_weekday_count += 1;
+                }
+            }
+        }
+
+        current += chrono::Duration::days(1);
+    }
+
+    // If we ended in a significant gap
+    if let Some(gs) = gap_start {
+        if gap_weekday_count >= MIN_GAP_WEEKDAYS {
+            missing.push((
+                gs.format("%Y-%m-%d").to_string(),
+                end_date.format("%Y-%m-%d").to_string(),
+            ));
+        }
+    }
+
+    missing
 }
 
 /// Delete all market data for a symbol
 pub fn delete_market_data_for_symbol(conn: &Connection, symbol: &str) -> rusqlite::Result<usize> {
     let rows = conn.execute("DELETE FROM market_data WHERE symbol = ?1", [symbol])?;
+    conn.execute(
+        "DELETE FROM market_data_empty_ranges WHERE symbol = ?1",
+        [symbol],
+    )?;
     info!(symbol = %symbol, count = rows, "Deleted market data for symbol");
     Ok(rows)
 }
 
+/// Delete market data for a symbol within an inclusive date range
+pub fn delete_range(
+    conn: &Connection,
+    symbol: &str,
+    from: &str,
+    to: &str,
+) -> rusqlite::Result<usize> {
+    let rows = conn.execute(
+        "DELETE FROM market_data WHERE symbol = ?1 AND date >= ?2 AND date <= ?3",
+        [symbol, from, to],
+    )?;
+    // Fetch the range again on the next refresh
+    conn.execute(
+        "DELETE FROM market_data_empty_ranges
+         WHERE symbol = ?1 AND start_date <= ?3 AND end_date >= ?2",
+        [symbol, from, to],
+    )?;
+    info!(symbol = %symbol, from = %from, to = %to, count = rows, "Deleted market data range");
+    Ok(rows)
+}
+
 /// Delete all market data
 pub fn delete_all_market_data(conn: &Connection) -> rusqlite::Result<usize> {
     let rows = conn.execute("DELETE FROM market_data", [])?;
+    conn.execute("DELETE FROM market_data_empty_ranges", [])?;
     warn!(count = rows, "Deleted all market data");
     Ok(rows)
 }
@@ -305,23 +520,170 @@ pub fn get_symbol_metadata(
     symbol: &str,
 ) -> rusqlite::Result<Option<SymbolMetadata>> {
     conn.query_row(
-        "SELECT symbol, short_name, long_name, exchange, quote_type
-         FROM symbol_metadata
-         WHERE symbol = ?1",
+        &format!("SELECT {SYMBOL_METADATA_COLUMNS} FROM symbol_metadata WHERE symbol = ?1"),
         [symbol],
-        |row| {
-            Ok(SymbolMetadata {
-                symbol: row.get(0)?,
-                short_name: row.get(1)?,
-                long_name: row.get(2)?,
-                exchange: row.get(3)?,
-                quote_type: row.get(4)?,
-            })
-        },
+        map_symbol_metadata,
     )
     .optional()
 }
 
+const SYMBOL_METADATA_COLUMNS: &str = "symbol, short_name, long_name, exchange, quote_type, \
+     price_scale, price_currency, ignored, ignore_note";
+
+fn map_symbol_metadata(row: &rusqlite::Row) -> rusqlite::Result<SymbolMetadata> {
+    Ok(SymbolMetadata {
+        symbol: row.get(0)?,
+        short_name: row.get(1)?,
+        long_name: row.get(2)?,
+        exchange: row.get(3)?,
+        quote_type: row.get(4)?,
+        price_scale: row.get(5)?,
+        price_currency: row.get(6)?,
+        ignored: row.get(7)?,
+        ignore_note: row.get(8)?,
+    })
+}
+
+/// Symbols left out of market data refreshes.
+pub fn get_ignored_symbols(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT symbol FROM symbol_metadata WHERE ignored")?;
+    let symbols = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<HashSet<_>, _>>()?;
+    Ok(symbols)
+}
+
+/// Leave a symbol out of market data refreshes, or take it back in. Its
+/// stored prices are kept either way.
+pub fn set_symbol_ignored(
+    conn: &Connection,
+    symbol: &str,
+    ignored: bool,
+    note: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO symbol_metadata (symbol, ignored, ignore_note)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT(symbol) DO UPDATE SET
+         ignored = excluded.ignored,
+         ignore_note = excluded.ignore_note",
+        params![symbol, ignored, note],
+    )?;
+    info!(symbol = %symbol, ignored, "Updated symbol ignore flag");
+    Ok(())
+}
+
+/// Metadata of all symbols whose prices need scaling or converting, by
+/// symbol.
+pub fn get_price_conversions(
+    conn: &Connection,
+) -> rusqlite::Result<HashMap<String, SymbolMetadata>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SYMBOL_METADATA_COLUMNS} FROM symbol_metadata
+         WHERE price_scale != 1 OR price_currency IS NOT NULL"
+    ))?;
+    let rows = stmt
+        .query_map([], map_symbol_metadata)?
+        .map(|m| m.map(|m| (m.symbol.clone(), m)))
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(rows)
+}
+
+/// Set how stored prices of a symbol are scaled and which currency they
+/// are in, as edited on the symbol page.
+pub fn set_price_scale(
+    conn: &Connection,
+    symbol: &str,
+    price_scale: f64,
+    price_currency: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO symbol_metadata (symbol, price_scale, price_currency)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT(symbol) DO UPDATE SET
+         price_scale = excluded.price_scale,
+         price_currency = excluded.price_currency",
+        params![symbol, price_scale, price_currency],
+    )?;
+    info!(symbol = %symbol, price_scale, "Updated price scale");
+    Ok(())
+}
+
+/// Record the currency Yahoo quotes a symbol in, unless one is set already.
+/// Minor units like "GBp" get their major currency and a scale of 0.01.
+pub fn detect_price_currency(
+    conn: &Connection,
+    symbol: &str,
+    quote_currency: &str,
+) -> rusqlite::Result<()> {
+    let (currency, scale) = minor_currency_unit(quote_currency).unwrap_or((quote_currency, 1.0));
+    let changed = conn.execute(
+        "INSERT INTO symbol_metadata (symbol, price_scale, price_currency)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT(symbol) DO UPDATE SET
+         price_scale = excluded.price_scale,
+         price_currency = excluded.price_currency
+         WHERE price_currency IS NULL",
+        params![symbol, scale, currency],
+    )?;
+    if changed > 0 && scale != 1.0 {
+        info!(symbol = %symbol, quote_currency = %quote_currency, scale, "Detected minor currency unit");
+    }
+    Ok(())
+}
+
+/// Factor turning stored prices of `symbol` into `currency` on `date`: the
+/// price scale times the exchange rate on that day (see [`get_fx_rate`]).
+/// Without a rate the currencies are taken to match, as before any price
+/// currency was known.
+pub fn get_price_factor(
+    conn: &Connection,
+    symbol: &str,
+    currency: &str,
+    date: &str,
+) -> rusqlite::Result<f64> {
+    let Some(meta) = get_symbol_metadata(conn, symbol)? else {
+        return Ok(1.0);
+    };
+    let rate = match meta.fx_from(currency) {
+        Some(from) => get_fx_rate(conn, from, currency, Some(date))?.unwrap_or(1.0),
+        None => 1.0,
+    };
+    Ok(meta.price_scale * rate)
+}
+
+/// Exchange rate from `from` into `to` in the stored market data: the last
+/// close on or before `as_of` (any day if `None`) of the pair "FROMTO=X",
+/// else the inverse of "TOFROM=X" (see [`fx_rate`]). Closes are read
+/// unrounded where stored. `None` if neither pair has a price.
+pub fn get_fx_rate(
+    conn: &Connection,
+    from: &str,
+    to: &str,
+    as_of: Option<&str>,
+) -> rusqlite::Result<Option<f64>> {
+    if from.eq_ignore_ascii_case(to) {
+        return Ok(Some(1.0));
+    }
+    let close = |pair: String| {
+        conn.query_row(
+            "SELECT COALESCE(close_price, close_price_cents / 100.0) FROM market_data
+             WHERE symbol = ?1 AND (?2 IS NULL OR date <= ?2)
+               AND COALESCE(close_price, close_price_cents) > 0
+             ORDER BY date DESC LIMIT 1",
+            params![pair, as_of],
+            |row| row.get::<_, f64>(0),
+        )
+        .optional()
+    };
+    let direct = close(fx_pair(from, to))?;
+    let inverse = match direct {
+        Some(_) => None,
+        None => close(fx_pair(to, from))?,
+    };
+    Ok(fx_rate(direct, inverse))
+}
+
 /// Insert or update symbol metadata
 pub fn upsert_symbol_metadata(
     conn: &Connection,
@@ -2,12 +2,20 @@ pub mod accounts;
 pub mod api_logs;
 pub mod balances;
 pub mod categories;
+pub mod digest;
 pub mod import;
 pub mod market_data;
 pub mod net_worth;
+pub mod positions;
 pub mod retirement;
 pub mod rules;
+pub mod saved_charts;
 pub mod settings;
+pub mod share_links;
+pub mod split_adjustments;
 pub mod tags;
 pub mod trading;
+pub mod trading_import;
+pub mod transaction_sums;
 pub mod transactions;
+pub mod usage_stats;
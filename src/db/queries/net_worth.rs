@@ -3,7 +3,7 @@ use rusqlite::Connection;
 /// Daily transaction sum: (date, amount_cents)
 pub type DailyTransactionSum = (String, i64);
 
-/// Activity row for net worth: (date, symbol, activity_type, quantity, unit_price_cents, fee_cents, currency)
+/// Activity row for net worth: (date, symbol, activity_type, quantity, unit_price_cents, fee_cents, currency, account_id)
 pub type NetWorthActivityRow = (
     String,
     String,
@@ -12,10 +12,11 @@ pub type NetWorthActivityRow = (
     Option<i64>,
     i64,
     String,
+    Option<i64>,
 );
 
-/// Market data row: (symbol, date, close_price_cents)
-pub type MarketDataRow = (String, String, i64);
+/// Market data row: (symbol, date, close), the close unrounded where stored
+pub type MarketDataRow = (String, String, f64);
 
 /// Last trade price row: (symbol, price_cents, date)
 pub type LastTradePriceRow = (String, i64, String);
@@ -25,6 +26,7 @@ pub fn get_daily_transaction_sums(conn: &Connection) -> rusqlite::Result<Vec<Dai
     let mut stmt = conn.prepare(
         "SELECT date, SUM(amount_cents) as daily_sum
          FROM transactions
+         WHERE status = 'posted'
          GROUP BY date
          ORDER BY date ASC",
     )?;
@@ -39,8 +41,10 @@ pub fn get_daily_transaction_sums(conn: &Connection) -> rusqlite::Result<Vec<Dai
 /// Get all trading activities ordered by date (for chronological processing)
 pub fn get_all_activities_ordered(conn: &Connection) -> rusqlite::Result<Vec<NetWorthActivityRow>> {
     let mut stmt = conn.prepare(
-        "SELECT date, symbol, activity_type, quantity, unit_price_cents, fee_cents, currency
+        "SELECT date, symbol, activity_type, quantity, unit_price_cents, fee_cents, currency,
+                account_id
          FROM trading_activities
+         WHERE deleted_at IS NULL
          ORDER BY date ASC, id ASC",
     )?;
 
@@ -54,6 +58,7 @@ pub fn get_all_activities_ordered(conn: &Connection) -> rusqlite::Result<Vec<Net
                 row.get(4)?,
                 row.get(5)?,
                 row.get(6)?,
+                row.get(7)?,
             ))
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -64,7 +69,7 @@ pub fn get_all_activities_ordered(conn: &Connection) -> rusqlite::Result<Vec<Net
 /// Get all market data for price lookups
 pub fn get_all_market_data(conn: &Connection) -> rusqlite::Result<Vec<MarketDataRow>> {
     let mut stmt = conn.prepare(
-        "SELECT symbol, date, close_price_cents
+        "SELECT symbol, date, COALESCE(close_price, close_price_cents / 100.0)
          FROM market_data
          ORDER BY symbol, date ASC",
     )?;
@@ -84,13 +89,15 @@ pub fn get_last_trade_prices(conn: &Connection) -> rusqlite::Result<Vec<LastTrad
          INNER JOIN (
              SELECT symbol, MAX(date || '-' || printf('%010d', id)) as max_key
              FROM trading_activities
-             WHERE activity_type IN ('BUY', 'SELL')
+             WHERE deleted_at IS NULL
+               AND activity_type IN ('BUY', 'SELL')
                AND unit_price_cents IS NOT NULL
              GROUP BY symbol
          ) latest ON t.symbol = latest.symbol
              AND (t.date || '-' || printf('%010d', t.id)) = latest.max_key
          WHERE t.activity_type IN ('BUY', 'SELL')
-           AND t.unit_price_cents IS NOT NULL",
+           AND t.unit_price_cents IS NOT NULL
+           AND t.deleted_at IS NULL",
     )?;
 
     let rows = stmt
@@ -106,7 +113,7 @@ pub fn get_earliest_date(conn: &Connection) -> rusqlite::Result<Option<String>>
         "SELECT MIN(date) FROM (
             SELECT MIN(date) as date FROM transactions
             UNION ALL
-            SELECT MIN(date) as date FROM trading_activities
+            SELECT MIN(date) as date FROM trading_activities WHERE deleted_at IS NULL
         )",
         [],
         |row| row.get(0),
@@ -119,7 +126,7 @@ pub fn get_latest_date(conn: &Connection) -> rusqlite::Result<Option<String>> {
         "SELECT MAX(date) FROM (
             SELECT MAX(date) as date FROM transactions
             UNION ALL
-            SELECT MAX(date) as date FROM trading_activities
+            SELECT MAX(date) as date FROM trading_activities WHERE deleted_at IS NULL
         )",
         [],
         |row| row.get(0),
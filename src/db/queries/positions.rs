@@ -0,0 +1,577 @@
+//! Open and closed positions, computed from the stored trading activities.
+
+use crate::models::trading::{
+    format_quantity, ClosedPosition, NewTradingActivity, Position, PositionDelta,
+    TradingActivityType,
+};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use tracing::warn;
+
+#[derive(Clone)]
+struct ActivityRow {
+    symbol: String,
+    activity_type: String,
+    quantity: Option<f64>,
+    unit_price_cents: Option<i64>,
+    _fee_cents: i64,
+    currency: String,
+    date: String,
+}
+
+struct ClosedPositionActivityRow {
+    symbol: String,
+    activity_type: String,
+    quantity: Option<f64>,
+    unit_price_cents: Option<i64>,
+    currency: String,
+    date: String,
+}
+
+struct PositionAccumulator {
+    quantity: f64,
+    total_cost: i64,
+    /// Average cost basis of the shares still held
+    held_cost: i64,
+    total_proceeds: i64,
+    total_fees: i64,
+    total_taxes: i64,
+    total_dividends: i64,
+    currency: String,
+    first_date: String,
+    last_date: String,
+    /// Whether shares were held (or shorted) at some point
+    opened: bool,
+}
+
+impl PositionAccumulator {
+    fn new(currency: String, date: String) -> Self {
+        Self {
+            quantity: 0.0,
+            total_cost: 0,
+            held_cost: 0,
+            total_proceeds: 0,
+            total_fees: 0,
+            total_taxes: 0,
+            total_dividends: 0,
+            currency,
+            first_date: date.clone(),
+            last_date: date,
+            opened: false,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.quantity.abs() < QUANTITY_EPSILON
+    }
+
+    fn into_closed_position(self, symbol: String) -> ClosedPosition {
+        // Net realized gain/loss = proceeds - cost + dividends - fees - taxes
+        let realized_gain_loss_cents = self.total_proceeds - self.total_cost + self.total_dividends
+            - self.total_fees
+            - self.total_taxes;
+        ClosedPosition {
+            symbol,
+            total_cost_cents: self.total_cost,
+            total_proceeds_cents: self.total_proceeds,
+            realized_gain_loss_cents,
+            total_fees_cents: self.total_fees,
+            total_taxes_cents: self.total_taxes,
+            currency: self.currency,
+            first_activity_date: self.first_date,
+            last_activity_date: self.last_date,
+        }
+    }
+
+    /// Average cost basis of `qty` shares of the current holding.
+    fn cost_of(&self, qty: f64) -> i64 {
+        if self.quantity <= 0.0 {
+            return 0;
+        }
+        let qty = qty.min(self.quantity);
+        (qty * self.held_cost as f64 / self.quantity).round() as i64
+    }
+}
+
+/// Quantities closer to zero than this count as a closed position, so
+/// floating point residue from fractional shares doesn't leave dust behind.
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+/// A disposal of more shares than were held, found while computing positions
+/// with short positions disabled. The excess is ignored.
+#[derive(Debug, Clone)]
+pub struct OversoldWarning {
+    pub symbol: String,
+    pub date: String,
+    pub excess_quantity: f64,
+}
+
+impl OversoldWarning {
+    pub fn excess_quantity_display(&self) -> String {
+        format_quantity(self.excess_quantity)
+    }
+}
+
+/// Apply a trade of `delta` shares (negative for disposals) at `price` to a
+/// position's signed quantity and cost basis. Short positions carry their
+/// entry proceeds as negative cost. Returns the quantity that would flip
+/// the position to the other side when `allow_flip` is false.
+fn apply_trade(
+    quantity: &mut f64,
+    cost: &mut i64,
+    delta: f64,
+    price: i64,
+    allow_flip: bool,
+) -> Option<f64> {
+    let opening = quantity.abs() < QUANTITY_EPSILON || quantity.signum() == delta.signum();
+    if opening {
+        if !allow_flip && delta < 0.0 {
+            return Some(-delta);
+        }
+        *quantity += delta;
+        *cost += (delta * price as f64).round() as i64;
+        return None;
+    }
+
+    // Reduce the position proportionally to its average cost (or entry proceeds)
+    let closing = delta.abs().min(quantity.abs());
+    let average = *cost as f64 / *quantity;
+    let remaining = delta.abs() - closing;
+    if quantity.abs() - closing < QUANTITY_EPSILON {
+        *quantity = 0.0;
+        *cost = 0;
+    } else {
+        *cost -= (closing * quantity.signum() * average).round() as i64;
+        *quantity += closing * delta.signum();
+    }
+
+    if remaining < QUANTITY_EPSILON {
+        None
+    } else if allow_flip {
+        *quantity = remaining * delta.signum();
+        *cost = (*quantity * price as f64).round() as i64;
+        None
+    } else {
+        Some(remaining)
+    }
+}
+
+/// Shared position calculation logic: takes raw activity rows and produces
+/// positions. Only sells may open short positions, and only if
+/// `allow_short` is set; other excess disposals are clamped and reported.
+fn calculate_positions_from_activities(
+    activities: Vec<ActivityRow>,
+    allow_short: bool,
+) -> (Vec<Position>, Vec<OversoldWarning>) {
+    let mut positions_map: HashMap<String, (f64, i64, String)> = HashMap::new();
+    let mut warnings = Vec::new();
+
+    for row in activities {
+        let activity_type: TradingActivityType = row
+            .activity_type
+            .parse()
+            .unwrap_or(TradingActivityType::Buy);
+        let qty = row.quantity.unwrap_or(0.0);
+        let price = row.unit_price_cents.unwrap_or(0);
+
+        let entry = positions_map
+            .entry(row.symbol.clone())
+            .or_insert((0.0, 0, row.currency));
+
+        let excess = match activity_type {
+            // Transferred-in shares carry their cost basis like a buy
+            TradingActivityType::Buy
+            | TradingActivityType::TransferIn
+            | TradingActivityType::AddHolding => {
+                apply_trade(&mut entry.0, &mut entry.1, qty, price, allow_short)
+            }
+            TradingActivityType::Sell => {
+                apply_trade(&mut entry.0, &mut entry.1, -qty, price, allow_short)
+            }
+            TradingActivityType::TransferOut | TradingActivityType::RemoveHolding => {
+                apply_trade(&mut entry.0, &mut entry.1, -qty, price, false)
+            }
+            TradingActivityType::Split => {
+                // Split adjustments are pre-applied to the quantities of
+                // earlier acquisitions and disposals. No runtime adjustment needed.
+                None
+            }
+            TradingActivityType::Fee | TradingActivityType::Tax => {
+                // These reduce cost basis (they're expenses associated with the position)
+                entry.1 += (qty * price as f64).round() as i64;
+                None
+            }
+            TradingActivityType::Dividend => {
+                // Dividends don't affect position quantity or cost basis
+                // They're just income events
+                None
+            }
+        };
+
+        if let Some(excess_quantity) = excess {
+            warn!(
+                symbol = %row.symbol,
+                date = %row.date,
+                excess_quantity,
+                "Disposal exceeds held quantity, clamping position to zero"
+            );
+            warnings.push(OversoldWarning {
+                symbol: row.symbol,
+                date: row.date,
+                excess_quantity,
+            });
+        }
+    }
+
+    // Convert to Position structs, filtering out zero positions
+    let mut positions: Vec<Position> = positions_map
+        .into_iter()
+        .filter(|(_, (qty, _, _))| *qty != 0.0)
+        .map(
+            |(symbol, (quantity, total_cost_cents, currency))| Position {
+                symbol,
+                quantity,
+                total_cost_cents,
+                currency,
+            },
+        )
+        .collect();
+
+    // Sort alphabetically by symbol
+    positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+    (positions, warnings)
+}
+
+/// Compute positions from the activities matching `where_sql`.
+fn query_positions(
+    conn: &Connection,
+    where_sql: &str,
+    params: &[&dyn rusqlite::ToSql],
+    allow_short: bool,
+) -> rusqlite::Result<(Vec<Position>, Vec<OversoldWarning>)> {
+    let activities = query_activity_rows(conn, where_sql, params)?;
+    Ok(calculate_positions_from_activities(activities, allow_short))
+}
+
+/// The activities matching `where_sql`, ordered for position calculation.
+fn query_activity_rows(
+    conn: &Connection,
+    where_sql: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> rusqlite::Result<Vec<ActivityRow>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT symbol, activity_type, quantity, unit_price_cents, fee_cents, currency, date
+         FROM trading_activities
+         WHERE deleted_at IS NULL AND {}
+         ORDER BY symbol, date ASC, id ASC",
+        where_sql
+    ))?;
+
+    let activities: Vec<ActivityRow> = stmt
+        .query_map(params, |row| {
+            Ok(ActivityRow {
+                symbol: row.get(0)?,
+                activity_type: row.get(1)?,
+                quantity: row.get(2)?,
+                unit_price_cents: row.get(3)?,
+                _fee_cents: row.get(4)?,
+                currency: row.get(5)?,
+                date: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(activities)
+}
+
+/// How adding `pending` activities would change the positions of the
+/// symbols they touch, computed in memory without storing them. Splits
+/// adjust quantities as they would on import: pending splits adjust the
+/// earlier activities of their symbol, and stored splits adjust the
+/// pending activities before them.
+pub fn preview_position_changes(
+    conn: &Connection,
+    pending: &[NewTradingActivity],
+    allow_short: bool,
+) -> rusqlite::Result<Vec<PositionDelta>> {
+    let existing = query_activity_rows(conn, "1=1", &[])?;
+    let mut combined = existing.clone();
+    for activity in pending {
+        let mut row = ActivityRow {
+            symbol: activity.symbol.clone(),
+            activity_type: activity.activity_type.as_str().to_string(),
+            quantity: activity.quantity,
+            unit_price_cents: activity.unit_price_cents,
+            _fee_cents: activity.fee_cents,
+            currency: activity.currency.clone(),
+            date: activity.date.clone(),
+        };
+        if is_split_adjusted(&activity.activity_type) {
+            let ratios: Vec<f64> = existing
+                .iter()
+                .filter(|s| {
+                    s.activity_type == "SPLIT" && s.symbol == row.symbol && s.date > row.date
+                })
+                .map(|s| s.quantity.unwrap_or(0.0))
+                .collect();
+            for ratio in ratios {
+                adjust_for_split(&mut row, ratio);
+            }
+        }
+        combined.push(row);
+    }
+    for split in pending
+        .iter()
+        .filter(|a| a.activity_type == TradingActivityType::Split)
+    {
+        let ratio = split.quantity.unwrap_or(0.0);
+        for row in combined.iter_mut().filter(|r| {
+            r.symbol == split.symbol
+                && r.date < split.date
+                && r.activity_type
+                    .parse()
+                    .is_ok_and(|t: TradingActivityType| is_split_adjusted(&t))
+        }) {
+            adjust_for_split(row, ratio);
+        }
+    }
+    // Stable, so pending rows follow the stored ones of the same day as
+    // their higher ids would after the import
+    combined.sort_by(|a, b| a.symbol.cmp(&b.symbol).then_with(|| a.date.cmp(&b.date)));
+
+    let (before, before_warnings) = calculate_positions_from_activities(existing, allow_short);
+    let (after, after_warnings) = calculate_positions_from_activities(combined, allow_short);
+
+    let mut symbols: Vec<&str> = pending.iter().map(|a| a.symbol.as_str()).collect();
+    symbols.sort_unstable();
+    symbols.dedup();
+    let find = |positions: &[Position], symbol: &str| {
+        positions.iter().find(|p| p.symbol == symbol).cloned()
+    };
+    let oversold = |warnings: &[OversoldWarning], symbol: &str| {
+        warnings.iter().filter(|w| w.symbol == symbol).count()
+    };
+    Ok(symbols
+        .into_iter()
+        .map(|symbol| {
+            let before = find(&before, symbol);
+            let after = find(&after, symbol);
+            let quantity_before = before.as_ref().map_or(0.0, |p| p.quantity);
+            let quantity_after = after.as_ref().map_or(0.0, |p| p.quantity);
+            PositionDelta {
+                symbol: symbol.to_string(),
+                currency: after
+                    .as_ref()
+                    .or(before.as_ref())
+                    .map(|p| p.currency.clone())
+                    .or_else(|| {
+                        pending
+                            .iter()
+                            .find(|a| a.symbol == symbol)
+                            .map(|a| a.currency.clone())
+                    })
+                    .unwrap_or_default(),
+                quantity_before,
+                quantity_after,
+                average_cost_before_cents: before.and_then(|p| p.average_cost_cents()),
+                average_cost_after_cents: after.and_then(|p| p.average_cost_cents()),
+                goes_negative: oversold(&after_warnings, symbol)
+                    > oversold(&before_warnings, symbol)
+                    || (quantity_after < 0.0 && quantity_before >= 0.0),
+            }
+        })
+        .collect())
+}
+
+/// Activities whose quantity and price are adjusted by later splits.
+fn is_split_adjusted(activity_type: &TradingActivityType) -> bool {
+    activity_type.is_acquisition() || activity_type.is_disposal()
+}
+
+fn adjust_for_split(row: &mut ActivityRow, ratio: f64) {
+    if ratio <= 0.0 {
+        return;
+    }
+    if let Some(quantity) = row.quantity.as_mut() {
+        *quantity *= ratio;
+        row.unit_price_cents = row
+            .unit_price_cents
+            .map(|p| (p as f64 / ratio).round() as i64);
+    }
+}
+
+pub fn get_positions(conn: &Connection, allow_short: bool) -> rusqlite::Result<Vec<Position>> {
+    Ok(get_positions_with_warnings(conn, allow_short)?.0)
+}
+
+/// Open positions along with the disposals that were clamped because they
+/// exceeded the held quantity.
+pub fn get_positions_with_warnings(
+    conn: &Connection,
+    allow_short: bool,
+) -> rusqlite::Result<(Vec<Position>, Vec<OversoldWarning>)> {
+    query_positions(conn, "1=1", &[], allow_short)
+}
+
+pub fn get_positions_for_account(
+    conn: &Connection,
+    account_id: i64,
+    allow_short: bool,
+) -> rusqlite::Result<Vec<Position>> {
+    Ok(query_positions(conn, "account_id = ?", &[&account_id], allow_short)?.0)
+}
+
+pub fn get_positions_without_account(
+    conn: &Connection,
+    allow_short: bool,
+) -> rusqlite::Result<Vec<Position>> {
+    Ok(query_positions(conn, "account_id IS NULL", &[], allow_short)?.0)
+}
+
+/// Get closed positions (where all securities have been sold, or a short
+/// position has been covered).
+///
+/// Without a range, each symbol is aggregated over its lifetime and listed
+/// only if it is closed now. With an inclusive `(from, to)` date range, every
+/// holding period that ended with the position closed is a separate entry,
+/// listed if its last activity falls in the range, so a symbol that was
+/// closed and later reopened still shows up for the period it was closed in.
+pub fn get_closed_positions(
+    conn: &Connection,
+    allow_short: bool,
+    range: Option<(&str, &str)>,
+) -> rusqlite::Result<Vec<ClosedPosition>> {
+    // Get all activities grouped by symbol
+    let mut stmt = conn.prepare_cached(
+        "SELECT symbol, activity_type, quantity, unit_price_cents, currency, date
+         FROM trading_activities
+         WHERE deleted_at IS NULL
+         ORDER BY symbol, date ASC, id ASC",
+    )?;
+
+    let activities: Vec<ClosedPositionActivityRow> = stmt
+        .query_map([], |row| {
+            Ok(ClosedPositionActivityRow {
+                symbol: row.get(0)?,
+                activity_type: row.get(1)?,
+                quantity: row.get(2)?,
+                unit_price_cents: row.get(3)?,
+                currency: row.get(4)?,
+                date: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Calculate positions by symbol, tracking cost, proceeds, fees, taxes, dividends, and dates
+    let mut positions_map: HashMap<String, PositionAccumulator> = HashMap::new();
+    // Holding periods that ended before the symbol was traded again
+    let mut episodes: Vec<ClosedPosition> = Vec::new();
+
+    for row in activities {
+        let activity_type: TradingActivityType = row
+            .activity_type
+            .parse()
+            .unwrap_or(TradingActivityType::Buy);
+        let qty = row.quantity.unwrap_or(0.0);
+        let price = row.unit_price_cents.unwrap_or(0);
+        let changes_quantity = !matches!(
+            activity_type,
+            TradingActivityType::Split
+                | TradingActivityType::Fee
+                | TradingActivityType::Tax
+                | TradingActivityType::Dividend
+        );
+
+        // Trading a closed symbol again starts a new holding period
+        let reopens = positions_map
+            .get(&row.symbol)
+            .is_some_and(|acc| acc.opened && acc.is_closed());
+        if range.is_some() && changes_quantity && reopens {
+            if let Some(acc) = positions_map.remove(&row.symbol) {
+                episodes.push(acc.into_closed_position(row.symbol.clone()));
+            }
+        }
+
+        let entry = positions_map
+            .entry(row.symbol.clone())
+            .or_insert_with(|| PositionAccumulator::new(row.currency, row.date.clone()));
+
+        // Update last activity date
+        if row.date > entry.last_date {
+            entry.last_date = row.date.clone();
+        }
+
+        match activity_type {
+            TradingActivityType::Buy
+            | TradingActivityType::TransferIn
+            | TradingActivityType::AddHolding => {
+                let cost = (qty * price as f64).round() as i64;
+                // Shares covering a short position are not held afterwards
+                let covered = qty.min(-entry.quantity).max(0.0);
+                entry.quantity += qty;
+                entry.total_cost += cost;
+                entry.held_cost += cost - (covered * price as f64).round() as i64;
+            }
+            TradingActivityType::Sell => {
+                let proceeds = (qty * price as f64).round() as i64;
+                entry.held_cost -= entry.cost_of(qty);
+                entry.quantity -= qty;
+                entry.total_proceeds += proceeds;
+                // Short sales stay open until a later buy covers them
+                if entry.quantity < 0.0 && !allow_short {
+                    entry.quantity = 0.0;
+                }
+            }
+            TradingActivityType::TransferOut | TradingActivityType::RemoveHolding => {
+                // Shares leave at cost, so their basis is not part of the realized result
+                let cost = entry.cost_of(qty);
+                entry.held_cost -= cost;
+                entry.total_cost -= cost;
+                entry.quantity -= qty;
+                if entry.quantity < 0.0 {
+                    entry.quantity = 0.0;
+                }
+            }
+            TradingActivityType::Split => {
+                // Split adjustments are pre-applied to the quantities of
+                // earlier acquisitions and disposals. No runtime adjustment needed.
+            }
+            TradingActivityType::Fee => {
+                // Fee amount is stored in unit_price_cents
+                entry.total_fees += price;
+            }
+            TradingActivityType::Tax => {
+                // Tax amount is stored in unit_price_cents
+                entry.total_taxes += price;
+            }
+            TradingActivityType::Dividend => {
+                // Dividend amount is stored in unit_price_cents
+                entry.total_dividends += price;
+            }
+        }
+        if !entry.is_closed() {
+            entry.opened = true;
+        }
+    }
+
+    // Convert to ClosedPosition structs, filtering to only zero positions
+    let mut closed_positions: Vec<ClosedPosition> = positions_map
+        .into_iter()
+        .filter(|(_, acc)| acc.is_closed())
+        .map(|(symbol, acc)| acc.into_closed_position(symbol))
+        .chain(episodes)
+        .filter(|p| {
+            range.is_none_or(|(from, to)| (from..=to).contains(&p.last_activity_date.as_str()))
+        })
+        .collect();
+
+    // Sort alphabetically by symbol, then chronologically
+    closed_positions.sort_by(|a, b| {
+        a.symbol
+            .cmp(&b.symbol)
+            .then_with(|| a.last_activity_date.cmp(&b.last_activity_date))
+    });
+
+    Ok(closed_positions)
+}
@@ -167,14 +167,17 @@ pub fn get_current_net_worth_cents(conn: &Connection) -> AppResult<i64> {
 /// Sums BUY cost (quantity × unit_price + fees) minus SELL proceeds.
 /// Returns 0 if no trading history exists.
 pub fn get_total_invested_cents(conn: &Connection) -> AppResult<i64> {
+    let fee = crate::db::queries::trading::fee_in_currency_sql("t");
     let result: i64 = conn.query_row(
-        "SELECT COALESCE(SUM(
-            CASE
-                WHEN activity_type = 'BUY'  THEN CAST(quantity * unit_price_cents AS INTEGER) + fee_cents
-                WHEN activity_type = 'SELL' THEN -(CAST(quantity * unit_price_cents AS INTEGER) - fee_cents)
-                ELSE 0
-            END
-         ), 0) FROM trading_activities",
+        &format!(
+            "SELECT COALESCE(SUM(
+                CASE
+                    WHEN activity_type = 'BUY'  THEN CAST(quantity * unit_price_cents AS INTEGER) + {fee}
+                    WHEN activity_type = 'SELL' THEN -(CAST(quantity * unit_price_cents AS INTEGER) - {fee})
+                    ELSE 0
+                END
+             ), 0) FROM trading_activities t WHERE deleted_at IS NULL"
+        ),
         [],
         |row| row.get(0),
     )?;
@@ -1,10 +1,11 @@
-use crate::models::rule::{NewRule, Rule, RuleActionType};
+use crate::models::rule::{NewRule, Rule, RuleActionType, RuleMatchField};
 use rusqlite::{params, Connection, OptionalExtension};
 use tracing::{info, warn};
 
 pub fn list_rules(conn: &Connection) -> rusqlite::Result<Vec<Rule>> {
     let mut stmt = conn.prepare(
-        "SELECT id, name, pattern, action_type, action_value, created_at, updated_at
+        "SELECT id, name, pattern, action_type, action_value, created_at, updated_at,
+                match_count, last_matched_at, match_field
          FROM rules
          ORDER BY name",
     )?;
@@ -16,9 +17,12 @@ pub fn list_rules(conn: &Connection) -> rusqlite::Result<Vec<Rule>> {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 pattern: row.get(2)?,
+                match_field: RuleMatchField::parse(&row.get::<_, String>(9)?).unwrap_or_default(),
                 action_type: RuleActionType::parse(&action_type_str)
                     .unwrap_or(RuleActionType::AssignCategory),
                 action_value: row.get(4)?,
+                match_count: row.get(7)?,
+                last_matched_at: row.get(8)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
             })
@@ -30,7 +34,8 @@ pub fn list_rules(conn: &Connection) -> rusqlite::Result<Vec<Rule>> {
 
 pub fn get_rule(conn: &Connection, id: i64) -> rusqlite::Result<Option<Rule>> {
     conn.query_row(
-        "SELECT id, name, pattern, action_type, action_value, created_at, updated_at
+        "SELECT id, name, pattern, action_type, action_value, created_at, updated_at,
+                match_count, last_matched_at, match_field
          FROM rules WHERE id = ?",
         [id],
         |row| {
@@ -39,9 +44,12 @@ pub fn get_rule(conn: &Connection, id: i64) -> rusqlite::Result<Option<Rule>> {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 pattern: row.get(2)?,
+                match_field: RuleMatchField::parse(&row.get::<_, String>(9)?).unwrap_or_default(),
                 action_type: RuleActionType::parse(&action_type_str)
                     .unwrap_or(RuleActionType::AssignCategory),
                 action_value: row.get(4)?,
+                match_count: row.get(7)?,
+                last_matched_at: row.get(8)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
             })
@@ -52,10 +60,12 @@ pub fn get_rule(conn: &Connection, id: i64) -> rusqlite::Result<Option<Rule>> {
 
 pub fn create_rule(conn: &Connection, rule: &NewRule) -> rusqlite::Result<i64> {
     conn.execute(
-        "INSERT INTO rules (name, pattern, action_type, action_value) VALUES (?, ?, ?, ?)",
+        "INSERT INTO rules (name, pattern, match_field, action_type, action_value)
+         VALUES (?, ?, ?, ?, ?)",
         params![
             rule.name,
             rule.pattern,
+            rule.match_field.as_str(),
             rule.action_type.as_str(),
             rule.action_value
         ],
@@ -67,10 +77,13 @@ pub fn create_rule(conn: &Connection, rule: &NewRule) -> rusqlite::Result<i64> {
 
 pub fn update_rule(conn: &Connection, id: i64, rule: &NewRule) -> rusqlite::Result<bool> {
     let rows = conn.execute(
-        "UPDATE rules SET name = ?, pattern = ?, action_type = ?, action_value = ?, updated_at = datetime('now') WHERE id = ?",
+        "UPDATE rules SET name = ?, pattern = ?, match_field = ?, action_type = ?, action_value = ?,
+                          updated_at = datetime('now')
+         WHERE id = ?",
         params![
             rule.name,
             rule.pattern,
+            rule.match_field.as_str(),
             rule.action_type.as_str(),
             rule.action_value,
             id
@@ -96,9 +109,11 @@ pub fn delete_all_rules(conn: &Connection) -> rusqlite::Result<usize> {
     Ok(rows)
 }
 
-/// Batch-assign a category to the given transaction IDs.
+/// Batch-assign a category to the given transaction IDs, marked as assigned
+/// by `rule_id` until reviewed.
 pub fn apply_rule_category(
     conn: &Connection,
+    rule_id: i64,
     transaction_ids: &[i64],
     category_id: i64,
 ) -> rusqlite::Result<usize> {
@@ -111,11 +126,13 @@ pub fn apply_rule_category(
         .collect::<Vec<_>>()
         .join(",");
     let sql = format!(
-        "UPDATE transactions SET category_id = ?, updated_at = datetime('now') WHERE id IN ({})",
+        "UPDATE transactions SET category_id = ?, auto_categorized = 1, auto_category_rule_id = ?,
+         updated_at = datetime('now') WHERE id IN ({})",
         placeholders
     );
     let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
     params_vec.push(Box::new(category_id));
+    params_vec.push(Box::new(rule_id));
     for id in transaction_ids {
         params_vec.push(Box::new(*id));
     }
@@ -144,3 +161,60 @@ pub fn apply_rule_tag(
     info!(count, tag_id, "Applied rule: assigned tag");
     Ok(count)
 }
+
+/// Record that a rule changed the given transactions in one run: count them
+/// and remember them for the rule's matches page.
+pub fn record_matches(
+    conn: &Connection,
+    rule_id: i64,
+    transaction_ids: &[i64],
+) -> rusqlite::Result<()> {
+    if transaction_ids.is_empty() {
+        return Ok(());
+    }
+    let mut stmt = conn.prepare(
+        "INSERT INTO rule_matches (rule_id, transaction_id) VALUES (?, ?)
+         ON CONFLICT (rule_id, transaction_id) DO UPDATE SET matched_at = datetime('now')",
+    )?;
+    for tx_id in transaction_ids {
+        stmt.execute(params![rule_id, tx_id])?;
+    }
+    conn.execute(
+        "UPDATE rules SET match_count = match_count + ?, last_matched_at = datetime('now')
+         WHERE id = ?",
+        params![transaction_ids.len() as i64, rule_id],
+    )?;
+    Ok(())
+}
+
+/// Most recently matched transaction IDs of a rule, with when they matched.
+pub fn list_recent_matches(
+    conn: &Connection,
+    rule_id: i64,
+    limit: i64,
+) -> rusqlite::Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT transaction_id, matched_at FROM rule_matches
+         WHERE rule_id = ?
+         ORDER BY matched_at DESC, transaction_id DESC
+         LIMIT ?",
+    )?;
+    let rows = stmt
+        .query_map(params![rule_id, limit], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect();
+    rows
+}
+
+pub fn reset_statistics(conn: &Connection, rule_id: i64) -> rusqlite::Result<bool> {
+    conn.execute("DELETE FROM rule_matches WHERE rule_id = ?", [rule_id])?;
+    let rows = conn.execute(
+        "UPDATE rules SET match_count = 0, last_matched_at = NULL WHERE id = ?",
+        [rule_id],
+    )?;
+    if rows > 0 {
+        info!(rule_id, "Reset rule statistics");
+    }
+    Ok(rows > 0)
+}
@@ -0,0 +1,81 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::models::{NewSavedChart, SavedChart, SavedChartType};
+
+const SELECT_COLS: &str = "id, name, chart_type, params, created_at, updated_at";
+
+fn row_to_saved_chart(row: &rusqlite::Row) -> rusqlite::Result<SavedChart> {
+    let chart_type: String = row.get(2)?;
+    let params: String = row.get(3)?;
+    Ok(SavedChart {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        chart_type: SavedChartType::parse(&chart_type).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                rusqlite::types::Type::Text,
+                format!("unknown chart type: {}", chart_type).into(),
+            )
+        })?,
+        // Unreadable settings fall back to charting all categories
+        params: serde_json::from_str(&params).unwrap_or_default(),
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+fn params_json(chart: &NewSavedChart) -> String {
+    serde_json::to_string(&chart.params).unwrap_or_else(|_| "{}".into())
+}
+
+/// All saved charts in the order they were created.
+pub fn list_saved_charts(conn: &Connection) -> rusqlite::Result<Vec<SavedChart>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SELECT_COLS} FROM saved_charts ORDER BY id"
+    ))?;
+    let rows = stmt
+        .query_map([], row_to_saved_chart)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+pub fn get_saved_chart(conn: &Connection, id: i64) -> rusqlite::Result<Option<SavedChart>> {
+    conn.query_row(
+        &format!("SELECT {SELECT_COLS} FROM saved_charts WHERE id = ?"),
+        [id],
+        row_to_saved_chart,
+    )
+    .optional()
+}
+
+pub fn create_saved_chart(conn: &Connection, chart: &NewSavedChart) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO saved_charts (name, chart_type, params) VALUES (?, ?, ?)",
+        params![chart.name, chart.chart_type.as_str(), params_json(chart)],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Returns `false` if the chart does not exist.
+pub fn update_saved_chart(
+    conn: &Connection,
+    id: i64,
+    chart: &NewSavedChart,
+) -> rusqlite::Result<bool> {
+    let updated = conn.execute(
+        "UPDATE saved_charts SET name = ?, chart_type = ?, params = ?, updated_at = datetime('now')
+         WHERE id = ?",
+        params![
+            chart.name,
+            chart.chart_type.as_str(),
+            params_json(chart),
+            id
+        ],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Returns `false` if the chart did not exist.
+pub fn delete_saved_chart(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    Ok(conn.execute("DELETE FROM saved_charts WHERE id = ?", [id])? > 0)
+}
@@ -36,11 +36,48 @@ pub fn set_setting(conn: &Connection, key: &str, value: &str) -> rusqlite::Resul
     Ok(())
 }
 
+/// Store `value` under `key` unless the key already has a value.
+/// Returns true if the value was written.
+pub fn set_setting_if_missing(conn: &Connection, key: &str, value: &str) -> rusqlite::Result<bool> {
+    let rows = conn.execute(
+        "INSERT OR IGNORE INTO settings (key, value, updated_at) VALUES (?, ?, datetime('now'))",
+        params![key, value],
+    )?;
+    Ok(rows > 0)
+}
+
 pub fn delete_setting(conn: &Connection, key: &str) -> rusqlite::Result<bool> {
     let rows = conn.execute("DELETE FROM settings WHERE key = ?", [key])?;
     Ok(rows > 0)
 }
 
+/// Settings that hold an account id.
+const ACCOUNT_REFERENCES: &[&str] = &["default_account_id", "default_trading_account_id"];
+
+/// Settings that hold a category id.
+const CATEGORY_REFERENCES: &[&str] = &["default_category_id"];
+
+/// Remove default account and category settings whose entity no longer
+/// exists. Called after deletions so no dangling id is left behind.
+pub fn clear_missing_references(conn: &Connection) -> rusqlite::Result<usize> {
+    let mut rows = 0;
+    for (keys, table) in [
+        (ACCOUNT_REFERENCES, "accounts"),
+        (CATEGORY_REFERENCES, "categories"),
+    ] {
+        for key in keys {
+            rows += conn.execute(
+                &format!(
+                    "DELETE FROM settings WHERE key = ?
+                     AND CAST(value AS INTEGER) NOT IN (SELECT id FROM {table})"
+                ),
+                [key],
+            )?;
+        }
+    }
+    Ok(rows)
+}
+
 /// Fetch all settings and convert to Settings struct.
 /// This is a convenience function that combines get_all_settings and Settings::from_map.
 pub fn get_settings(conn: &Connection) -> AppResult<Settings> {
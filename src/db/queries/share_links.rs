@@ -0,0 +1,89 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+
+use crate::models::{NewShareLink, ShareLink, ShareScope};
+
+const SELECT_COLS: &str = "id, token, scope, label, password_hash, expires_at, created_at";
+
+fn row_to_share_link(row: &rusqlite::Row) -> rusqlite::Result<ShareLink> {
+    let scope: String = row.get(2)?;
+    Ok(ShareLink {
+        id: row.get(0)?,
+        token: row.get(1)?,
+        scope: ShareScope::parse(&scope).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                rusqlite::types::Type::Text,
+                format!("unknown share scope: {}", scope).into(),
+            )
+        })?,
+        label: row.get(3)?,
+        password_hash: row.get(4)?,
+        expires_at: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// Create a share link with a fresh random token.
+pub fn create_share_link(conn: &Connection, link: &NewShareLink) -> rusqlite::Result<ShareLink> {
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let expires_modifier = link.expires_in_days.map(|days| format!("+{} days", days));
+    conn.execute(
+        "INSERT INTO share_links (token, scope, label, password_hash, expires_at)
+         VALUES (?, ?, ?, ?, datetime('now', ?))",
+        params![
+            token,
+            link.scope.as_str(),
+            link.label,
+            link.password_hash,
+            // A NULL modifier makes datetime() NULL: the link never expires
+            expires_modifier,
+        ],
+    )?;
+    conn.query_row(
+        &format!("SELECT {SELECT_COLS} FROM share_links WHERE id = ?"),
+        [conn.last_insert_rowid()],
+        row_to_share_link,
+    )
+}
+
+/// All share links, newest first, including expired ones.
+pub fn list_share_links(conn: &Connection) -> rusqlite::Result<Vec<ShareLink>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SELECT_COLS} FROM share_links ORDER BY created_at DESC, id DESC"
+    ))?;
+    let rows = stmt
+        .query_map([], row_to_share_link)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+pub fn get_share_link(conn: &Connection, id: i64) -> rusqlite::Result<Option<ShareLink>> {
+    conn.query_row(
+        &format!("SELECT {SELECT_COLS} FROM share_links WHERE id = ?"),
+        [id],
+        row_to_share_link,
+    )
+    .optional()
+}
+
+/// Look up a link by token, ignoring links that have expired.
+pub fn get_active_share_link(
+    conn: &Connection,
+    token: &str,
+) -> rusqlite::Result<Option<ShareLink>> {
+    conn.query_row(
+        &format!(
+            "SELECT {SELECT_COLS} FROM share_links
+             WHERE token = ? AND (expires_at IS NULL OR expires_at > datetime('now'))"
+        ),
+        [token],
+        row_to_share_link,
+    )
+    .optional()
+}
+
+/// Revoke a link. Returns `false` if it did not exist.
+pub fn delete_share_link(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    Ok(conn.execute("DELETE FROM share_links WHERE id = ?", [id])? > 0)
+}
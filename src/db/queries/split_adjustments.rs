@@ -0,0 +1,387 @@
+//! Split adjustments: the activities a split rewrote, with their values from
+//! before, so that the split can be undone.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::{debug, info};
+
+/// Apply a split to all prior activities that move shares (buys, sells,
+/// transfers and holding adjustments) for the same symbol.
+/// Multiplies their quantity by the ratio and divides their unit_price by it,
+/// recording original values in `trading_split_adjustments` for reversal.
+pub fn apply_split_to_past_activities(
+    conn: &Connection,
+    split_activity_id: i64,
+    symbol: &str,
+    split_date: &str,
+    ratio: f64,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, quantity, unit_price_cents
+         FROM trading_activities
+         WHERE symbol = ?1
+           AND date < ?2
+           AND deleted_at IS NULL
+           AND activity_type IN ('BUY', 'SELL', 'TRANSFER_IN', 'TRANSFER_OUT',
+                                 'ADD_HOLDING', 'REMOVE_HOLDING')
+           AND quantity IS NOT NULL
+           AND id NOT IN (
+               SELECT target_activity_id FROM trading_split_adjustments
+               WHERE split_activity_id = ?3
+           )",
+    )?;
+
+    let targets: Vec<(i64, f64, Option<i64>)> = stmt
+        .query_map(params![symbol, split_date, split_activity_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (target_id, original_qty, original_price) in &targets {
+        conn.execute(
+            "INSERT INTO trading_split_adjustments
+             (split_activity_id, target_activity_id, original_quantity, original_unit_price_cents, split_ratio)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![split_activity_id, target_id, original_qty, original_price, ratio],
+        )?;
+
+        let new_qty = original_qty * ratio;
+        let new_price = original_price.map(|p| (p as f64 / ratio).round() as i64);
+
+        conn.execute(
+            "UPDATE trading_activities
+             SET quantity = ?1, unit_price_cents = ?2, updated_at = datetime('now')
+             WHERE id = ?3",
+            params![new_qty, new_price, target_id],
+        )?;
+
+        debug!(
+            split_id = split_activity_id,
+            target_id = target_id,
+            original_qty = original_qty,
+            new_qty = new_qty,
+            "Applied split adjustment"
+        );
+    }
+
+    if !targets.is_empty() {
+        info!(
+            symbol = %symbol,
+            ratio = ratio,
+            adjusted_count = targets.len(),
+            "Applied split to past activities"
+        );
+    }
+
+    Ok(())
+}
+
+/// Apply all existing splits (dated after this activity) to a newly created
+/// activity that moves shares.
+pub fn apply_existing_splits_to_activity(
+    conn: &Connection,
+    activity_id: i64,
+    symbol: &str,
+    activity_date: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, quantity
+         FROM trading_activities
+         WHERE symbol = ?1
+           AND date > ?2
+           AND deleted_at IS NULL
+           AND activity_type = 'SPLIT'
+           AND quantity IS NOT NULL
+           AND quantity > 0
+         ORDER BY date ASC, id ASC",
+    )?;
+
+    let splits: Vec<(i64, f64)> = stmt
+        .query_map(params![symbol, activity_date], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if splits.is_empty() {
+        return Ok(());
+    }
+
+    let (current_qty, current_price): (Option<f64>, Option<i64>) = conn.query_row(
+        "SELECT quantity, unit_price_cents FROM trading_activities WHERE id = ?1",
+        [activity_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let Some(mut running_qty) = current_qty else {
+        return Ok(());
+    };
+    let mut running_price = current_price;
+
+    for (split_id, ratio) in &splits {
+        let already_applied: bool = conn.query_row(
+            "SELECT COUNT(*) FROM trading_split_adjustments
+             WHERE split_activity_id = ?1 AND target_activity_id = ?2",
+            params![split_id, activity_id],
+            |row| Ok(row.get::<_, i64>(0)? > 0),
+        )?;
+
+        if already_applied {
+            continue;
+        }
+
+        conn.execute(
+            "INSERT INTO trading_split_adjustments
+             (split_activity_id, target_activity_id, original_quantity, original_unit_price_cents, split_ratio)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![split_id, activity_id, running_qty, running_price, ratio],
+        )?;
+
+        running_qty *= ratio;
+        running_price = running_price.map(|p| (p as f64 / ratio).round() as i64);
+    }
+
+    if Some(running_qty) != current_qty || running_price != current_price {
+        conn.execute(
+            "UPDATE trading_activities
+             SET quantity = ?1, unit_price_cents = ?2, updated_at = datetime('now')
+             WHERE id = ?3",
+            params![running_qty, running_price, activity_id],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Reverse all adjustments made by a specific split activity, restoring
+/// target activities to the values they would have without this split.
+///
+/// Handles the case of multiple overlapping splits by recomputing from
+/// the base (pre-any-split) values and re-applying remaining splits.
+pub fn reverse_split_adjustments(
+    conn: &Connection,
+    split_activity_id: i64,
+) -> rusqlite::Result<()> {
+    // Collect the target activity ids affected by this split.
+    let mut target_stmt = conn.prepare_cached(
+        "SELECT target_activity_id FROM trading_split_adjustments
+         WHERE split_activity_id = ?1",
+    )?;
+    let target_ids: Vec<i64> = target_stmt
+        .query_map([split_activity_id], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(target_stmt);
+
+    let target_count = target_ids.len();
+    for target_id in target_ids {
+        recompute_target_without_split(conn, target_id, split_activity_id)?;
+    }
+
+    // Delete the adjustment records for this split.
+    conn.execute(
+        "DELETE FROM trading_split_adjustments WHERE split_activity_id = ?1",
+        [split_activity_id],
+    )?;
+
+    info!(
+        split_activity_id = split_activity_id,
+        reversed_count = target_count,
+        "Reversed split adjustments"
+    );
+
+    Ok(())
+}
+
+/// Remove adjustment records that target a specific activity (for cleanup
+/// when a BUY/SELL is deleted—no reversal needed since the activity is going away).
+pub fn delete_adjustments_targeting_activity(
+    conn: &Connection,
+    target_activity_id: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM trading_split_adjustments WHERE target_activity_id = ?1",
+        [target_activity_id],
+    )?;
+    Ok(())
+}
+
+/// Quantity and unit price of an activity before any split adjusted it, or
+/// `None` if no split has.
+pub fn get_pre_split_values(
+    conn: &Connection,
+    target_activity_id: i64,
+) -> rusqlite::Result<Option<(f64, Option<i64>)>> {
+    conn.query_row(
+        "SELECT sa.original_quantity, sa.original_unit_price_cents
+         FROM trading_split_adjustments sa
+         JOIN trading_activities s ON s.id = sa.split_activity_id
+         WHERE sa.target_activity_id = ?1
+         ORDER BY s.date ASC, s.id ASC
+         LIMIT 1",
+        [target_activity_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Quantity and unit price of every split-adjusted activity before its
+/// first split, keyed by activity id.
+pub fn get_all_pre_split_values(
+    conn: &Connection,
+) -> rusqlite::Result<HashMap<i64, (f64, Option<i64>)>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT sa.target_activity_id, sa.original_quantity, sa.original_unit_price_cents
+         FROM trading_split_adjustments sa
+         JOIN trading_activities s ON s.id = sa.split_activity_id
+         ORDER BY s.date DESC, s.id DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, (row.get(1)?, row.get(2)?)))
+    })?;
+    // Later inserts are earlier splits and win
+    rows.collect()
+}
+
+/// One split adjustment of an activity: the values it had before the split.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SplitAdjustment {
+    pub target_activity_id: i64,
+    pub split_activity_id: i64,
+    pub split_date: String,
+    pub original_quantity: f64,
+    pub original_unit_price_cents: Option<i64>,
+    pub split_ratio: f64,
+}
+
+/// Split adjustments of all activities of a symbol, per activity in the
+/// order the splits happened.
+pub fn get_adjustments_for_symbol(
+    conn: &Connection,
+    symbol: &str,
+) -> rusqlite::Result<Vec<SplitAdjustment>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT sa.target_activity_id, sa.split_activity_id, s.date,
+                sa.original_quantity, sa.original_unit_price_cents, sa.split_ratio
+         FROM trading_split_adjustments sa
+         JOIN trading_activities s ON s.id = sa.split_activity_id
+         JOIN trading_activities t ON t.id = sa.target_activity_id
+         WHERE t.symbol = ?1 AND t.deleted_at IS NULL
+         ORDER BY sa.target_activity_id ASC, s.date ASC, s.id ASC",
+    )?;
+    let adjustments = stmt
+        .query_map([symbol], |row| {
+            Ok(SplitAdjustment {
+                target_activity_id: row.get(0)?,
+                split_activity_id: row.get(1)?,
+                split_date: row.get(2)?,
+                original_quantity: row.get(3)?,
+                original_unit_price_cents: row.get(4)?,
+                split_ratio: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(adjustments)
+}
+
+/// Reset an activity to its pre-split quantity and price and drop the
+/// adjustment records targeting it, so it can sit in the trash unaffected
+/// by splits that are added or removed meanwhile.
+pub fn remove_split_adjustments_from_activity(
+    conn: &Connection,
+    target_activity_id: i64,
+) -> rusqlite::Result<()> {
+    if let Some((base_qty, base_price)) = get_pre_split_values(conn, target_activity_id)? {
+        conn.execute(
+            "UPDATE trading_activities
+             SET quantity = ?1, unit_price_cents = ?2, updated_at = datetime('now')
+             WHERE id = ?3",
+            params![base_qty, base_price, target_activity_id],
+        )?;
+    }
+
+    delete_adjustments_targeting_activity(conn, target_activity_id)
+}
+
+/// Recompute a target activity's quantity/price after removing one split.
+///
+/// Algorithm:
+/// 1. Find the base value (original before any split) from the earliest adjustment.
+/// 2. Delete the adjustment record for the removed split.
+/// 3. Re-apply remaining splits in chronological order, updating their
+///    stored originals along the way.
+/// 4. Write the final value back to the activity.
+fn recompute_target_without_split(
+    conn: &Connection,
+    target_id: i64,
+    removed_split_id: i64,
+) -> rusqlite::Result<()> {
+    // Get the base (pre-any-split) values: the original from the earliest adjustment.
+    let base: Option<(f64, Option<i64>)> = conn
+        .query_row(
+            "SELECT sa.original_quantity, sa.original_unit_price_cents
+             FROM trading_split_adjustments sa
+             JOIN trading_activities s ON s.id = sa.split_activity_id
+             WHERE sa.target_activity_id = ?1
+             ORDER BY s.date ASC, s.id ASC
+             LIMIT 1",
+            [target_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    let Some((base_qty, base_price)) = base else {
+        return Ok(());
+    };
+
+    // Delete the record for the removed split.
+    conn.execute(
+        "DELETE FROM trading_split_adjustments
+         WHERE split_activity_id = ?1 AND target_activity_id = ?2",
+        params![removed_split_id, target_id],
+    )?;
+
+    // Collect remaining adjustments in chronological order.
+    let mut remaining_stmt = conn.prepare_cached(
+        "SELECT sa.id, sa.split_ratio
+         FROM trading_split_adjustments sa
+         JOIN trading_activities s ON s.id = sa.split_activity_id
+         WHERE sa.target_activity_id = ?1
+         ORDER BY s.date ASC, s.id ASC",
+    )?;
+    let remaining: Vec<(i64, f64)> = remaining_stmt
+        .query_map([target_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Replay remaining adjustments from the base values.
+    let mut running_qty = base_qty;
+    let mut running_price = base_price;
+
+    for (adj_id, ratio) in &remaining {
+        conn.execute(
+            "UPDATE trading_split_adjustments
+             SET original_quantity = ?1, original_unit_price_cents = ?2
+             WHERE id = ?3",
+            params![running_qty, running_price, adj_id],
+        )?;
+
+        running_qty *= ratio;
+        running_price = running_price.map(|p| (p as f64 / ratio).round() as i64);
+    }
+
+    // Write the final computed values to the activity.
+    conn.execute(
+        "UPDATE trading_activities
+         SET quantity = ?1, unit_price_cents = ?2, updated_at = datetime('now')
+         WHERE id = ?3",
+        params![running_qty, running_price, target_id],
+    )?;
+
+    debug!(
+        target_id = target_id,
+        removed_split = removed_split_id,
+        final_qty = running_qty,
+        "Recomputed target after split removal"
+    );
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::models::tag::{NewTag, Tag, TagStyle, TagWithUsage};
+use crate::models::tag::{NewTag, Tag, TagMatchKind, TagStyle, TagWithUsage};
 use crate::models::DEFAULT_COLOR;
 use rusqlite::{params, Connection, OptionalExtension};
 use tracing::{info, warn};
@@ -55,29 +55,84 @@ pub fn list_tags_with_usage(conn: &Connection) -> rusqlite::Result<Vec<TagWithUs
     Ok(tags)
 }
 
-pub fn search_tags(conn: &Connection, query: &str) -> rusqlite::Result<Vec<Tag>> {
+/// Maximum number of tags returned by [`search_tags`].
+const SEARCH_LIMIT: usize = 20;
+
+/// How many of the most recently used tags get a ranking boost.
+const RECENT_TAGS: usize = 10;
+
+/// Search tags by name for the tag picker.
+///
+/// Matches are ranked prefix first, then substring, then by fuzzy distance.
+/// Within a rank, the most recently used tags come first, then the most used
+/// ones. An empty query returns the most recently and frequently used tags.
+pub fn search_tags(conn: &Connection, query: &str) -> rusqlite::Result<Vec<TagWithUsage>> {
     let mut stmt = conn.prepare(
-        "SELECT id, name, color, style, created_at
-         FROM tags
-         WHERE name LIKE ?
-         ORDER BY name
-         LIMIT 10",
+        "SELECT t.id, t.name, t.color, t.style, t.created_at,
+                COUNT(tt.transaction_id) AS usage_count,
+                MAX(e.created_at) AS last_used_at
+         FROM tags t
+         LEFT JOIN transaction_tags tt ON t.id = tt.tag_id
+         LEFT JOIN transactions e ON e.id = tt.transaction_id
+         GROUP BY t.id",
     )?;
 
-    let tags = stmt
-        .query_map([format!("%{}%", query)], |row| {
+    let candidates = stmt
+        .query_map([], |row| {
             let style_str: String = row.get(3)?;
-            Ok(Tag {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                color: row.get(2)?,
-                style: TagStyle::parse(&style_str),
-                created_at: row.get(4)?,
-            })
+            let tag = TagWithUsage {
+                tag: Tag {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    color: row.get(2)?,
+                    style: TagStyle::parse(&style_str),
+                    created_at: row.get(4)?,
+                },
+                usage_count: row.get(5)?,
+            };
+            Ok((tag, row.get::<_, Option<String>>(6)?))
         })?
         .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(tags)
+    Ok(rank_tags(candidates, &query.trim().to_lowercase()))
+}
+
+fn rank_tags(candidates: Vec<(TagWithUsage, Option<String>)>, query: &str) -> Vec<TagWithUsage> {
+    let mut recent: Vec<&str> = candidates
+        .iter()
+        .filter_map(|(_, last_used)| last_used.as_deref())
+        .collect();
+    recent.sort_unstable_by(|a, b| b.cmp(a));
+    let recent_cutoff = recent.get(RECENT_TAGS - 1).map(|s| s.to_string());
+    let is_recent = |last_used: &Option<String>| match (last_used, &recent_cutoff) {
+        (Some(used), Some(cutoff)) => used >= cutoff,
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+
+    let mut ranked: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|(tag, last_used)| {
+            let kind = if query.is_empty() {
+                TagMatchKind::Prefix
+            } else {
+                TagMatchKind::of(&tag.tag.name, query)?
+            };
+            Some(((kind, !is_recent(&last_used)), tag))
+        })
+        .collect();
+    ranked.sort_by(|(a_rank, a), (b_rank, b)| {
+        a_rank
+            .cmp(b_rank)
+            .then(b.usage_count.cmp(&a.usage_count))
+            .then_with(|| a.tag.name.cmp(&b.tag.name))
+    });
+
+    ranked
+        .into_iter()
+        .take(SEARCH_LIMIT)
+        .map(|(_, tag)| tag)
+        .collect()
 }
 
 pub fn get_tag(conn: &Connection, id: i64) -> rusqlite::Result<Option<Tag>> {
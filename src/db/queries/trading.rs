@@ -1,42 +1,9 @@
-use crate::error::AppResult;
 use crate::models::trading::{
-    ClosedPosition, NewTradingActivity, Position, TradingActivity, TradingActivityType,
-    TradingImportRow, TradingImportSession, TradingImportStatus,
+    format_quantity, NewTradingActivity, TradingActivity, TradingActivityType,
 };
-use crate::services::trading_csv_parser::ParsedTradingActivity;
 use rusqlite::{params, Connection, OptionalExtension};
-use std::collections::HashMap;
-use tracing::{debug, info};
-
-struct ActivityRow {
-    symbol: String,
-    activity_type: String,
-    quantity: Option<f64>,
-    unit_price_cents: Option<i64>,
-    _fee_cents: i64,
-    currency: String,
-}
-
-struct ClosedPositionActivityRow {
-    symbol: String,
-    activity_type: String,
-    quantity: Option<f64>,
-    unit_price_cents: Option<i64>,
-    currency: String,
-    date: String,
-}
-
-struct PositionAccumulator {
-    quantity: f64,
-    total_cost: i64,
-    total_proceeds: i64,
-    total_fees: i64,
-    total_taxes: i64,
-    total_dividends: i64,
-    currency: String,
-    first_date: String,
-    last_date: String,
-}
+use serde::Serialize;
+use tracing::info;
 
 fn trading_activity_from_row(row: &rusqlite::Row) -> rusqlite::Result<TradingActivity> {
     let activity_type_str: String = row.get(4)?;
@@ -51,10 +18,12 @@ fn trading_activity_from_row(row: &rusqlite::Row) -> rusqlite::Result<TradingAct
         unit_price_cents: row.get(5)?,
         currency: row.get(6)?,
         fee_cents: row.get(7)?,
-        account_id: row.get(8)?,
-        notes: row.get(9)?,
-        created_at: row.get(10)?,
-        updated_at: row.get(11)?,
+        fee_currency: row.get(8)?,
+        exchange_rate: row.get(9)?,
+        account_id: row.get(10)?,
+        notes: row.get(11)?,
+        created_at: row.get(12)?,
+        updated_at: row.get(13)?,
     })
 }
 
@@ -72,16 +41,28 @@ pub struct TradingActivityFilter {
     pub sort_sql: Option<String>,
 }
 
-pub fn list_activities(
-    conn: &Connection,
-    filter: &TradingActivityFilter,
-) -> rusqlite::Result<Vec<TradingActivity>> {
-    let mut sql = String::from(
-        "SELECT id, date, symbol, quantity, activity_type, unit_price_cents,
-                currency, fee_cents, account_id, notes, created_at, updated_at
-         FROM trading_activities
-         WHERE 1=1",
-    );
+/// Per-symbol summary row for the grouped activities view.
+#[derive(Debug, Clone)]
+pub struct ActivityGroup {
+    pub symbol: String,
+    pub activity_count: i64,
+    /// Acquired minus disposed quantity within the filter.
+    pub net_quantity: f64,
+    /// Cost of all acquisitions within the filter, excluding fees.
+    pub total_invested_cents: i64,
+    pub currency: String,
+    pub last_date: String,
+}
+
+impl ActivityGroup {
+    pub fn net_quantity_display(&self) -> String {
+        format_quantity(self.net_quantity)
+    }
+}
+
+/// WHERE conditions (each prefixed with " AND") and their parameters for a filter.
+fn filter_conditions(filter: &TradingActivityFilter) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+    let mut sql = String::new();
     let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
     if let Some(ref symbol) = filter.symbol {
@@ -101,6 +82,23 @@ pub fn list_activities(
         params_vec.push(Box::new(to_date.clone()));
     }
 
+    (sql, params_vec)
+}
+
+pub fn list_activities(
+    conn: &Connection,
+    filter: &TradingActivityFilter,
+) -> rusqlite::Result<Vec<TradingActivity>> {
+    let mut sql = String::from(
+        "SELECT id, date, symbol, quantity, activity_type, unit_price_cents,
+                currency, fee_cents, fee_currency, exchange_rate, account_id, notes,
+                created_at, updated_at
+         FROM trading_activities
+         WHERE deleted_at IS NULL",
+    );
+    let (where_sql, mut params_vec) = filter_conditions(filter);
+    sql.push_str(&where_sql);
+
     // Use provided sort or default to date DESC
     let order_by = filter.sort_sql.as_deref().unwrap_or("date DESC");
     sql.push_str(&format!(" ORDER BY {}, id DESC", order_by));
@@ -115,7 +113,7 @@ pub fn list_activities(
     }
 
     let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    let mut stmt = conn.prepare(&sql)?;
+    let mut stmt = conn.prepare_cached(&sql)?;
 
     let activities = stmt
         .query_map(params_refs.as_slice(), trading_activity_from_row)?
@@ -126,60 +124,163 @@ pub fn list_activities(
 
 /// Returns the earliest and latest trading activity dates, or `None` when the table is empty.
 pub fn date_extent(conn: &Connection) -> rusqlite::Result<Option<(String, String)>> {
-    conn.query_row(
-        "SELECT MIN(date), MAX(date) FROM trading_activities",
-        [],
-        |row| {
-            let min: Option<String> = row.get(0)?;
-            let max: Option<String> = row.get(1)?;
-            Ok(min.zip(max))
-        },
-    )
+    conn.prepare_cached(
+        "SELECT MIN(date), MAX(date) FROM trading_activities WHERE deleted_at IS NULL",
+    )?
+    .query_row([], |row| {
+        let min: Option<String> = row.get(0)?;
+        let max: Option<String> = row.get(1)?;
+        Ok(min.zip(max))
+    })
 }
 
 pub fn count_activities(
     conn: &Connection,
     filter: &TradingActivityFilter,
 ) -> rusqlite::Result<i64> {
-    let mut sql = String::from("SELECT COUNT(*) FROM trading_activities WHERE 1=1");
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    let (where_sql, params_vec) = filter_conditions(filter);
+    let sql = format!(
+        "SELECT COUNT(*) FROM trading_activities WHERE deleted_at IS NULL{}",
+        where_sql
+    );
 
-    if let Some(ref symbol) = filter.symbol {
-        sql.push_str(" AND symbol = ?");
-        params_vec.push(Box::new(symbol.clone()));
-    }
-    if let Some(ref activity_type) = filter.activity_type {
-        sql.push_str(" AND activity_type = ?");
-        params_vec.push(Box::new(activity_type.as_str().to_string()));
-    }
-    if let Some(ref from_date) = filter.from_date {
-        sql.push_str(" AND date >= ?");
-        params_vec.push(Box::new(from_date.clone()));
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    conn.prepare_cached(&sql)?
+        .query_row(params_refs.as_slice(), |row| row.get(0))
+}
+
+/// Summarize the filtered activities per symbol. `sort_sql` may reference the
+/// `ActivityGroup` column names; defaults to the most recently active symbol first.
+pub fn list_activity_groups(
+    conn: &Connection,
+    filter: &TradingActivityFilter,
+) -> rusqlite::Result<Vec<ActivityGroup>> {
+    let (where_sql, mut params_vec) = filter_conditions(filter);
+    let order_by = filter.sort_sql.as_deref().unwrap_or("last_date DESC");
+    let mut sql = format!(
+        "SELECT symbol,
+                COUNT(*) AS activity_count,
+                COALESCE(SUM(CASE
+                    WHEN activity_type IN ('BUY', 'TRANSFER_IN', 'ADD_HOLDING') THEN quantity
+                    WHEN activity_type IN ('SELL', 'TRANSFER_OUT', 'REMOVE_HOLDING') THEN -quantity
+                    ELSE 0 END), 0) AS net_quantity,
+                COALESCE(SUM(CASE
+                    WHEN activity_type IN ('BUY', 'TRANSFER_IN', 'ADD_HOLDING')
+                    THEN CAST(ROUND(COALESCE(quantity, 0) * COALESCE(unit_price_cents, 0)) AS INTEGER)
+                    ELSE 0 END), 0) AS total_invested_cents,
+                MAX(currency) AS currency,
+                MAX(date) AS last_date
+         FROM trading_activities
+         WHERE deleted_at IS NULL{}
+         GROUP BY symbol
+         ORDER BY {}, symbol ASC",
+        where_sql, order_by
+    );
+
+    if let Some(limit) = filter.limit {
+        sql.push_str(" LIMIT ?");
+        params_vec.push(Box::new(limit));
     }
-    if let Some(ref to_date) = filter.to_date {
-        sql.push_str(" AND date <= ?");
-        params_vec.push(Box::new(to_date.clone()));
+    if let Some(offset) = filter.offset {
+        sql.push_str(" OFFSET ?");
+        params_vec.push(Box::new(offset));
     }
 
     let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    conn.query_row(&sql, params_refs.as_slice(), |row| row.get(0))
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let groups = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            Ok(ActivityGroup {
+                symbol: row.get(0)?,
+                activity_count: row.get(1)?,
+                net_quantity: row.get(2)?,
+                total_invested_cents: row.get(3)?,
+                currency: row.get(4)?,
+                last_date: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(groups)
+}
+
+/// Number of distinct symbols among the filtered activities.
+pub fn count_activity_groups(
+    conn: &Connection,
+    filter: &TradingActivityFilter,
+) -> rusqlite::Result<i64> {
+    let (where_sql, params_vec) = filter_conditions(filter);
+    let sql = format!(
+        "SELECT COUNT(DISTINCT symbol) FROM trading_activities WHERE deleted_at IS NULL{}",
+        where_sql
+    );
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    conn.prepare_cached(&sql)?
+        .query_row(params_refs.as_slice(), |row| row.get(0))
 }
 
 pub fn get_activity(conn: &Connection, id: i64) -> rusqlite::Result<Option<TradingActivity>> {
-    conn.query_row(
+    conn.prepare_cached(
         "SELECT id, date, symbol, quantity, activity_type, unit_price_cents,
-                currency, fee_cents, account_id, notes, created_at, updated_at
-         FROM trading_activities WHERE id = ?",
-        [id],
-        trading_activity_from_row,
-    )
+                currency, fee_cents, fee_currency, exchange_rate, account_id, notes,
+                created_at, updated_at
+         FROM trading_activities WHERE id = ? AND deleted_at IS NULL",
+    )?
+    .query_row([id], trading_activity_from_row)
     .optional()
 }
 
+/// Symbol of an activity, including activities in the trash.
+pub fn activity_symbol(conn: &Connection, id: i64) -> rusqlite::Result<Option<String>> {
+    conn.prepare_cached("SELECT symbol FROM trading_activities WHERE id = ?")?
+        .query_row([id], |row| row.get(0))
+        .optional()
+}
+
+/// An activity created for a client-supplied external id, including
+/// activities in the trash.
+pub struct ExternalActivity {
+    pub id: i64,
+    pub account_id: Option<i64>,
+    pub deleted: bool,
+}
+
+pub fn find_activity_by_external_id(
+    conn: &Connection,
+    external_id: &str,
+) -> rusqlite::Result<Option<ExternalActivity>> {
+    conn.prepare_cached(
+        "SELECT id, account_id, deleted_at IS NOT NULL
+         FROM trading_activities WHERE external_id = ?",
+    )?
+    .query_row([external_id], |row| {
+        Ok(ExternalActivity {
+            id: row.get(0)?,
+            account_id: row.get(1)?,
+            deleted: row.get(2)?,
+        })
+    })
+    .optional()
+}
+
+pub fn set_activity_external_id(
+    conn: &Connection,
+    id: i64,
+    external_id: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE trading_activities SET external_id = ? WHERE id = ?",
+        params![external_id, id],
+    )?;
+    Ok(())
+}
+
 pub fn create_activity(conn: &Connection, activity: &NewTradingActivity) -> rusqlite::Result<i64> {
     conn.execute(
-        "INSERT INTO trading_activities (date, symbol, quantity, activity_type, unit_price_cents, currency, fee_cents, account_id, notes)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        "INSERT INTO trading_activities (date, symbol, quantity, activity_type, unit_price_cents, currency, fee_cents,
+                                         fee_currency, exchange_rate, account_id, notes)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         params![
             activity.date,
             activity.symbol,
@@ -188,6 +289,8 @@ pub fn create_activity(conn: &Connection, activity: &NewTradingActivity) -> rusq
             activity.unit_price_cents,
             activity.currency,
             activity.fee_cents,
+            activity.fee_currency,
+            activity.exchange_rate,
             activity.account_id,
             activity.notes,
         ],
@@ -209,7 +312,8 @@ pub fn update_activity(
 ) -> rusqlite::Result<()> {
     conn.execute(
         "UPDATE trading_activities SET date = ?, symbol = ?, quantity = ?, activity_type = ?,
-         unit_price_cents = ?, currency = ?, fee_cents = ?, account_id = ?, notes = ?, updated_at = datetime('now')
+         unit_price_cents = ?, currency = ?, fee_cents = ?, fee_currency = ?, exchange_rate = ?,
+         account_id = ?, notes = ?, updated_at = datetime('now')
          WHERE id = ?",
         params![
             activity.date,
@@ -219,6 +323,8 @@ pub fn update_activity(
             activity.unit_price_cents,
             activity.currency,
             activity.fee_cents,
+            activity.fee_currency,
+            activity.exchange_rate,
             activity.account_id,
             activity.notes,
             id,
@@ -228,281 +334,126 @@ pub fn update_activity(
     Ok(())
 }
 
-pub fn delete_activity(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
-    let rows = conn.execute("DELETE FROM trading_activities WHERE id = ?", [id])?;
-    if rows > 0 {
-        info!(activity_id = id, "Deleted trading activity");
-    }
-    Ok(rows > 0)
+pub fn set_activity_notes(conn: &Connection, id: i64, notes: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE trading_activities SET notes = ?, updated_at = datetime('now') WHERE id = ?",
+        params![notes, id],
+    )?;
+    Ok(())
 }
 
-pub fn delete_all_activities(conn: &Connection) -> rusqlite::Result<usize> {
-    let rows = conn.execute("DELETE FROM trading_activities", [])?;
-    tracing::warn!(count = rows, "Deleted all trading activities");
-    Ok(rows)
+/// A trading activity in the trash.
+#[derive(Debug, Clone)]
+pub struct DeletedTradingActivity {
+    pub activity: TradingActivity,
+    pub deleted_at: String,
 }
 
-// Position calculations
-
-/// Shared position calculation logic: takes raw activity rows and produces positions.
-fn calculate_positions_from_activities(activities: Vec<ActivityRow>) -> Vec<Position> {
-    let mut positions_map: HashMap<String, (f64, i64, String)> = HashMap::new();
+/// Days a deleted activity stays in the trash before it is purged.
+pub const TRASH_RETENTION_DAYS: i64 = 30;
 
-    for row in activities {
-        let activity_type: TradingActivityType = row
-            .activity_type
-            .parse()
-            .unwrap_or(TradingActivityType::Buy);
-        let qty = row.quantity.unwrap_or(0.0);
-        let price = row.unit_price_cents.unwrap_or(0);
-
-        let entry = positions_map
-            .entry(row.symbol.clone())
-            .or_insert((0.0, 0, row.currency));
-
-        match activity_type {
-            TradingActivityType::Buy => {
-                let cost = (qty * price as f64).round() as i64;
-                entry.0 += qty;
-                entry.1 += cost;
-            }
-            TradingActivityType::Sell => {
-                // For sells, reduce quantity and proportionally reduce cost basis
-                if entry.0 > 0.0 {
-                    let avg_cost = entry.1 as f64 / entry.0;
-                    let cost_reduction = (qty * avg_cost).round() as i64;
-                    entry.0 -= qty;
-                    entry.1 -= cost_reduction;
-                    if entry.0 < 0.0 {
-                        entry.0 = 0.0;
-                    }
-                    if entry.1 < 0 {
-                        entry.1 = 0;
-                    }
-                }
-            }
-            TradingActivityType::Split => {
-                // Split adjustments are pre-applied to BUY/SELL quantities
-                // when activities are created. No runtime adjustment needed.
-            }
-            TradingActivityType::Fee | TradingActivityType::Tax => {
-                // These reduce cost basis (they're expenses associated with the position)
-                entry.1 += (qty * price as f64).round() as i64;
-            }
-            TradingActivityType::Dividend => {
-                // Dividends don't affect position quantity or cost basis
-                // They're just income events
-            }
-        }
+/// Move an activity to the trash. Returns false if it does not exist or is
+/// already deleted.
+pub fn soft_delete_activity(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    let rows = conn.execute(
+        "UPDATE trading_activities SET deleted_at = datetime('now')
+         WHERE id = ? AND deleted_at IS NULL",
+        [id],
+    )?;
+    if rows > 0 {
+        info!(activity_id = id, "Moved trading activity to trash");
     }
-
-    // Convert to Position structs, filtering out zero positions
-    let mut positions: Vec<Position> = positions_map
-        .into_iter()
-        .filter(|(_, (qty, _, _))| *qty != 0.0)
-        .map(
-            |(symbol, (quantity, total_cost_cents, currency))| Position {
-                symbol,
-                quantity,
-                total_cost_cents,
-                currency,
-            },
-        )
-        .collect();
-
-    // Sort alphabetically by symbol
-    positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
-
-    positions
+    Ok(rows > 0)
 }
 
-pub fn get_positions(conn: &Connection) -> rusqlite::Result<Vec<Position>> {
-    let mut stmt = conn.prepare(
-        "SELECT symbol, activity_type, quantity, unit_price_cents, fee_cents, currency
-         FROM trading_activities
-         ORDER BY symbol, date ASC, id ASC",
+/// Take an activity out of the trash. Returns it, or `None` if it is not
+/// in the trash.
+pub fn restore_activity(conn: &Connection, id: i64) -> rusqlite::Result<Option<TradingActivity>> {
+    let rows = conn.execute(
+        "UPDATE trading_activities SET deleted_at = NULL
+         WHERE id = ? AND deleted_at IS NOT NULL",
+        [id],
     )?;
-
-    let activities: Vec<ActivityRow> = stmt
-        .query_map([], |row| {
-            Ok(ActivityRow {
-                symbol: row.get(0)?,
-                activity_type: row.get(1)?,
-                quantity: row.get(2)?,
-                unit_price_cents: row.get(3)?,
-                _fee_cents: row.get(4)?,
-                currency: row.get(5)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(calculate_positions_from_activities(activities))
+    if rows == 0 {
+        return Ok(None);
+    }
+    info!(activity_id = id, "Restored trading activity from trash");
+    get_activity(conn, id)
 }
 
-pub fn get_positions_for_account(
-    conn: &Connection,
-    account_id: i64,
-) -> rusqlite::Result<Vec<Position>> {
-    let mut stmt = conn.prepare(
-        "SELECT symbol, activity_type, quantity, unit_price_cents, fee_cents, currency
+/// Activities in the trash, most recently deleted first.
+pub fn list_deleted_activities(conn: &Connection) -> rusqlite::Result<Vec<DeletedTradingActivity>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, date, symbol, quantity, activity_type, unit_price_cents,
+                currency, fee_cents, fee_currency, exchange_rate, account_id, notes,
+                created_at, updated_at, deleted_at
          FROM trading_activities
-         WHERE account_id = ?
-         ORDER BY symbol, date ASC, id ASC",
+         WHERE deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC, id DESC",
     )?;
-
-    let activities: Vec<ActivityRow> = stmt
-        .query_map([account_id], |row| {
-            Ok(ActivityRow {
-                symbol: row.get(0)?,
-                activity_type: row.get(1)?,
-                quantity: row.get(2)?,
-                unit_price_cents: row.get(3)?,
-                _fee_cents: row.get(4)?,
-                currency: row.get(5)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(calculate_positions_from_activities(activities))
+    let rows = stmt.query_map([], |row| {
+        Ok(DeletedTradingActivity {
+            activity: trading_activity_from_row(row)?,
+            deleted_at: row.get(14)?,
+        })
+    })?;
+    rows.collect()
 }
 
-pub fn get_positions_without_account(conn: &Connection) -> rusqlite::Result<Vec<Position>> {
-    let mut stmt = conn.prepare(
-        "SELECT symbol, activity_type, quantity, unit_price_cents, fee_cents, currency
-         FROM trading_activities
-         WHERE account_id IS NULL
-         ORDER BY symbol, date ASC, id ASC",
-    )?;
-
-    let activities: Vec<ActivityRow> = stmt
-        .query_map([], |row| {
-            Ok(ActivityRow {
-                symbol: row.get(0)?,
-                activity_type: row.get(1)?,
-                quantity: row.get(2)?,
-                unit_price_cents: row.get(3)?,
-                _fee_cents: row.get(4)?,
-                currency: row.get(5)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(calculate_positions_from_activities(activities))
+/// Permanently delete activities that have been in the trash for longer
+/// than `retention_days`. A negative value empties the whole trash.
+pub fn purge_deleted_activities(conn: &Connection, retention_days: i64) -> rusqlite::Result<usize> {
+    let rows = if retention_days < 0 {
+        conn.execute(
+            "DELETE FROM trading_activities WHERE deleted_at IS NOT NULL",
+            [],
+        )?
+    } else {
+        conn.execute(
+            "DELETE FROM trading_activities
+             WHERE deleted_at IS NOT NULL AND deleted_at < datetime('now', ?)",
+            [format!("-{} days", retention_days)],
+        )?
+    };
+    if rows > 0 {
+        info!(count = rows, "Purged trading activities from trash");
+    }
+    Ok(rows)
 }
 
-/// Get closed positions (where all securities have been sold)
-pub fn get_closed_positions(conn: &Connection) -> rusqlite::Result<Vec<ClosedPosition>> {
-    // Get all activities grouped by symbol
-    let mut stmt = conn.prepare(
-        "SELECT symbol, activity_type, quantity, unit_price_cents, currency, date
-         FROM trading_activities
-         ORDER BY symbol, date ASC, id ASC",
-    )?;
-
-    let activities: Vec<ClosedPositionActivityRow> = stmt
-        .query_map([], |row| {
-            Ok(ClosedPositionActivityRow {
-                symbol: row.get(0)?,
-                activity_type: row.get(1)?,
-                quantity: row.get(2)?,
-                unit_price_cents: row.get(3)?,
-                currency: row.get(4)?,
-                date: row.get(5)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    // Calculate positions by symbol, tracking cost, proceeds, fees, taxes, dividends, and dates
-    let mut positions_map: HashMap<String, PositionAccumulator> = HashMap::new();
-
-    for row in activities {
-        let activity_type: TradingActivityType = row
-            .activity_type
-            .parse()
-            .unwrap_or(TradingActivityType::Buy);
-        let qty = row.quantity.unwrap_or(0.0);
-        let price = row.unit_price_cents.unwrap_or(0);
-
-        let entry = positions_map
-            .entry(row.symbol.clone())
-            .or_insert(PositionAccumulator {
-                quantity: 0.0,
-                total_cost: 0,
-                total_proceeds: 0,
-                total_fees: 0,
-                total_taxes: 0,
-                total_dividends: 0,
-                currency: row.currency,
-                first_date: row.date.clone(),
-                last_date: row.date.clone(),
-            });
-
-        // Update last activity date
-        if row.date > entry.last_date {
-            entry.last_date = row.date.clone();
-        }
-
-        match activity_type {
-            TradingActivityType::Buy => {
-                let cost = (qty * price as f64).round() as i64;
-                entry.quantity += qty;
-                entry.total_cost += cost;
-            }
-            TradingActivityType::Sell => {
-                let proceeds = (qty * price as f64).round() as i64;
-                entry.quantity -= qty;
-                entry.total_proceeds += proceeds;
-                if entry.quantity < 0.0 {
-                    entry.quantity = 0.0;
-                }
-            }
-            TradingActivityType::Split => {
-                // Split adjustments are pre-applied to BUY/SELL quantities
-                // when activities are created. No runtime adjustment needed.
-            }
-            TradingActivityType::Fee => {
-                // Fee amount is stored in unit_price_cents
-                entry.total_fees += price;
-            }
-            TradingActivityType::Tax => {
-                // Tax amount is stored in unit_price_cents
-                entry.total_taxes += price;
-            }
-            TradingActivityType::Dividend => {
-                // Dividend amount is stored in unit_price_cents
-                entry.total_dividends += price;
-            }
-        }
+pub fn delete_activity(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    let rows = conn.execute("DELETE FROM trading_activities WHERE id = ?", [id])?;
+    if rows > 0 {
+        info!(activity_id = id, "Deleted trading activity");
     }
+    Ok(rows > 0)
+}
 
-    // Convert to ClosedPosition structs, filtering to only zero positions
-    let mut closed_positions: Vec<ClosedPosition> = positions_map
-        .into_iter()
-        .filter(|(_, acc)| acc.quantity == 0.0)
-        .map(|(symbol, acc)| {
-            // Net realized gain/loss = proceeds - cost + dividends - fees - taxes
-            let realized_gain_loss_cents = acc.total_proceeds - acc.total_cost
-                + acc.total_dividends
-                - acc.total_fees
-                - acc.total_taxes;
-            ClosedPosition {
-                symbol,
-                total_cost_cents: acc.total_cost,
-                total_proceeds_cents: acc.total_proceeds,
-                realized_gain_loss_cents,
-                total_fees_cents: acc.total_fees,
-                total_taxes_cents: acc.total_taxes,
-                currency: acc.currency,
-                first_activity_date: acc.first_date,
-                last_activity_date: acc.last_date,
-            }
-        })
-        .collect();
+/// Count all activities, including those in the trash.
+pub fn count_all_activities(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM trading_activities", [], |row| {
+        row.get(0)
+    })
+}
 
-    // Sort alphabetically by symbol
-    closed_positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+pub fn delete_all_activities(conn: &Connection) -> rusqlite::Result<usize> {
+    let rows = conn.execute("DELETE FROM trading_activities", [])?;
+    tracing::warn!(count = rows, "Deleted all trading activities");
+    Ok(rows)
+}
 
-    Ok(closed_positions)
+/// SQL expression for an activity's per-trade fee in the activity currency,
+/// for the table aliased as `alias`. Fees in a foreign currency without an
+/// exchange rate are taken at face value, as in
+/// [`TradingActivity::fee_converted_cents`].
+pub fn fee_in_currency_sql(alias: &str) -> String {
+    format!(
+        "(CASE WHEN {a}.fee_currency IS NOT NULL AND {a}.fee_currency != {a}.currency
+                    AND {a}.exchange_rate > 0
+               THEN CAST(ROUND({a}.fee_cents * {a}.exchange_rate) AS INTEGER)
+               ELSE {a}.fee_cents END)",
+        a = alias
+    )
 }
 
 /// Aggregate total fees and taxes across all trading activities.
@@ -512,15 +463,105 @@ pub fn get_portfolio_fee_tax_totals(conn: &Connection) -> rusqlite::Result<(i64,
         "SELECT
             COALESCE(SUM(CASE WHEN activity_type = 'FEE' THEN unit_price_cents ELSE 0 END), 0),
             COALESCE(SUM(CASE WHEN activity_type = 'TAX' THEN unit_price_cents ELSE 0 END), 0)
-         FROM trading_activities",
+         FROM trading_activities
+         WHERE deleted_at IS NULL",
         [],
         |row| Ok((row.get(0)?, row.get(1)?)),
     )
 }
 
+/// Dimension for grouping fee and tax totals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeGrouping {
+    Symbol,
+    Year,
+    Account,
+}
+
+impl FeeGrouping {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "symbol" => Some(Self::Symbol),
+            "year" => Some(Self::Year),
+            "account" => Some(Self::Account),
+            _ => None,
+        }
+    }
+
+    fn sql_expression(&self) -> &'static str {
+        match self {
+            Self::Symbol => "t.symbol",
+            Self::Year => "substr(t.date, 1, 4)",
+            Self::Account => "COALESCE(a.name, 'No account')",
+        }
+    }
+}
+
+/// Fees and taxes for one group and currency.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeTaxTotal {
+    pub group: String,
+    pub currency: String,
+    /// Per-trade fees plus FEE activities.
+    pub fees_cents: i64,
+    pub taxes_cents: i64,
+    /// Cost of all acquisitions, excluding fees.
+    pub invested_cents: i64,
+    /// Fees as a percentage of invested capital.
+    pub fee_drag_percent: Option<f64>,
+}
+
+/// Fee and tax totals grouped by `grouping` and currency, leaving out groups
+/// without any fees or taxes. Amounts in different currencies are never added up.
+pub fn get_fee_tax_totals(
+    conn: &Connection,
+    grouping: FeeGrouping,
+) -> rusqlite::Result<Vec<FeeTaxTotal>> {
+    let sql = format!(
+        "SELECT {} AS grp, t.currency,
+                COALESCE(SUM({}), 0)
+                    + COALESCE(SUM(CASE WHEN t.activity_type = 'FEE' THEN t.unit_price_cents ELSE 0 END), 0)
+                    AS fees,
+                COALESCE(SUM(CASE WHEN t.activity_type = 'TAX' THEN t.unit_price_cents ELSE 0 END), 0)
+                    AS taxes,
+                COALESCE(SUM(CASE
+                    WHEN t.activity_type IN ('BUY', 'TRANSFER_IN', 'ADD_HOLDING')
+                    THEN CAST(ROUND(COALESCE(t.quantity, 0) * COALESCE(t.unit_price_cents, 0)) AS INTEGER)
+                    ELSE 0 END), 0) AS invested
+         FROM trading_activities t
+         LEFT JOIN accounts a ON a.id = t.account_id
+         WHERE t.deleted_at IS NULL
+         GROUP BY grp, t.currency
+         HAVING fees != 0 OR taxes != 0
+         ORDER BY grp, t.currency",
+        grouping.sql_expression(),
+        fee_in_currency_sql("t"),
+    );
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let totals = stmt
+        .query_map([], |row| {
+            let fees_cents: i64 = row.get(2)?;
+            let invested_cents: i64 = row.get(4)?;
+            Ok(FeeTaxTotal {
+                group: row.get(0)?,
+                currency: row.get(1)?,
+                fees_cents,
+                taxes_cents: row.get(3)?,
+                invested_cents,
+                fee_drag_percent: (invested_cents > 0)
+                    .then(|| fees_cents as f64 / invested_cents as f64 * 100.0),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(totals)
+}
+
 pub fn get_unique_symbols(conn: &Connection) -> rusqlite::Result<Vec<String>> {
-    let mut stmt =
-        conn.prepare("SELECT DISTINCT symbol FROM trading_activities ORDER BY symbol")?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT DISTINCT symbol FROM trading_activities
+             WHERE deleted_at IS NULL
+             ORDER BY symbol",
+    )?;
 
     let symbols: Vec<String> = stmt
         .query_map([], |row| row.get(0))?
@@ -534,11 +575,12 @@ pub fn get_activities_for_symbol(
     conn: &Connection,
     symbol: &str,
 ) -> rusqlite::Result<Vec<TradingActivity>> {
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare_cached(
         "SELECT id, date, symbol, quantity, activity_type, unit_price_cents,
-                currency, fee_cents, account_id, notes, created_at, updated_at
+                currency, fee_cents, fee_currency, exchange_rate, account_id, notes,
+                created_at, updated_at
          FROM trading_activities
-         WHERE symbol = ?
+         WHERE symbol = ? AND deleted_at IS NULL
          ORDER BY date ASC, id ASC",
     )?;
 
@@ -559,6 +601,7 @@ pub fn get_last_trade_price(
         "SELECT unit_price_cents, date
          FROM trading_activities
          WHERE symbol = ?
+           AND deleted_at IS NULL
            AND activity_type IN ('BUY', 'SELL')
            AND unit_price_cents IS NOT NULL
          ORDER BY date DESC, id DESC
@@ -576,10 +619,11 @@ pub fn get_all_trade_prices(
     conn: &Connection,
     symbol: &str,
 ) -> rusqlite::Result<Vec<(String, i64)>> {
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare_cached(
         "SELECT date, unit_price_cents
          FROM trading_activities
          WHERE symbol = ?
+           AND deleted_at IS NULL
            AND activity_type IN ('BUY', 'SELL')
            AND unit_price_cents IS NOT NULL
          ORDER BY date ASC, id ASC",
@@ -593,515 +637,3 @@ pub fn get_all_trade_prices(
     }
     Ok(prices)
 }
-
-// Import session operations
-
-pub fn create_import_session(conn: &Connection, id: &str) -> AppResult<TradingImportSession> {
-    conn.execute(
-        "INSERT INTO trading_import_sessions (id, status) VALUES (?1, ?2)",
-        params![id, TradingImportStatus::Parsing.as_str()],
-    )?;
-    info!(session_id = %id, "Created trading import session");
-    get_import_session(conn, id)
-}
-
-pub fn get_import_session(conn: &Connection, id: &str) -> AppResult<TradingImportSession> {
-    let mut stmt = conn.prepare(
-        "SELECT id, status, total_rows, processed_rows, error_count, errors, created_at, updated_at
-         FROM trading_import_sessions WHERE id = ?1",
-    )?;
-
-    let session = stmt.query_row(params![id], |row| {
-        let errors_json: Option<String> = row.get(5)?;
-        let errors: Vec<String> = errors_json
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default();
-
-        Ok(TradingImportSession {
-            id: row.get(0)?,
-            status: row
-                .get::<_, String>(1)?
-                .parse()
-                .unwrap_or(TradingImportStatus::Failed),
-            total_rows: row.get(2)?,
-            processed_rows: row.get(3)?,
-            error_count: row.get(4)?,
-            errors,
-            created_at: row.get(6)?,
-            updated_at: row.get(7)?,
-        })
-    })?;
-
-    Ok(session)
-}
-
-pub fn update_import_session_status(
-    conn: &Connection,
-    id: &str,
-    status: TradingImportStatus,
-) -> AppResult<()> {
-    conn.execute(
-        "UPDATE trading_import_sessions SET status = ?2, updated_at = datetime('now') WHERE id = ?1",
-        params![id, status.as_str()],
-    )?;
-    info!(session_id = %id, status = %status.as_str(), "Updated trading import session status");
-    Ok(())
-}
-
-pub fn update_import_session_progress(
-    conn: &Connection,
-    id: &str,
-    total_rows: i64,
-    processed_rows: i64,
-) -> AppResult<()> {
-    conn.execute(
-        "UPDATE trading_import_sessions SET total_rows = ?2, processed_rows = ?3, updated_at = datetime('now') WHERE id = ?1",
-        params![id, total_rows, processed_rows],
-    )?;
-    Ok(())
-}
-
-pub fn update_import_session_errors(
-    conn: &Connection,
-    id: &str,
-    error_count: i64,
-    errors: &[String],
-) -> AppResult<()> {
-    let errors_json = serde_json::to_string(errors).unwrap_or_else(|_| "[]".to_string());
-    conn.execute(
-        "UPDATE trading_import_sessions SET error_count = ?2, errors = ?3, updated_at = datetime('now') WHERE id = ?1",
-        params![id, error_count, errors_json],
-    )?;
-    Ok(())
-}
-
-pub fn increment_import_session_processed(conn: &Connection, id: &str) -> AppResult<()> {
-    conn.execute(
-        "UPDATE trading_import_sessions SET processed_rows = processed_rows + 1, updated_at = datetime('now') WHERE id = ?1",
-        params![id],
-    )?;
-    Ok(())
-}
-
-pub fn increment_import_session_error_count(conn: &Connection, id: &str) -> AppResult<()> {
-    conn.execute(
-        "UPDATE trading_import_sessions SET error_count = error_count + 1, updated_at = datetime('now') WHERE id = ?1",
-        params![id],
-    )?;
-    Ok(())
-}
-
-pub fn delete_import_session(conn: &Connection, id: &str) -> AppResult<()> {
-    conn.execute(
-        "DELETE FROM trading_import_sessions WHERE id = ?1",
-        params![id],
-    )?;
-    debug!(session_id = %id, "Deleted trading import session");
-    Ok(())
-}
-
-// Import row operations
-
-pub fn insert_import_row(
-    conn: &Connection,
-    session_id: &str,
-    row_index: i64,
-    data: &ParsedTradingActivity,
-) -> AppResult<i64> {
-    let data_json = serde_json::to_string(data).unwrap();
-    conn.execute(
-        "INSERT INTO trading_import_rows (session_id, row_index, data) VALUES (?1, ?2, ?3)",
-        params![session_id, row_index, data_json],
-    )?;
-    Ok(conn.last_insert_rowid())
-}
-
-pub fn get_import_rows_paginated(
-    conn: &Connection,
-    session_id: &str,
-    limit: i64,
-    offset: i64,
-) -> AppResult<Vec<TradingImportRow>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, session_id, row_index, data, status, error
-         FROM trading_import_rows
-         WHERE session_id = ?1
-         ORDER BY row_index
-         LIMIT ?2 OFFSET ?3",
-    )?;
-
-    let rows = stmt
-        .query_map(params![session_id, limit, offset], |row| {
-            let data_json: String = row.get(3)?;
-            let data: ParsedTradingActivity =
-                serde_json::from_str(&data_json).unwrap_or_else(|_| ParsedTradingActivity {
-                    date: String::new(),
-                    symbol: String::new(),
-                    quantity: None,
-                    activity_type: String::new(),
-                    unit_price: None,
-                    currency: "USD".to_string(),
-                    fee: None,
-                    account_id: None,
-                    row_number: 0,
-                });
-
-            Ok(TradingImportRow {
-                id: row.get(0)?,
-                session_id: row.get(1)?,
-                row_index: row.get(2)?,
-                data,
-                status: row.get(4)?,
-                error: row.get(5)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(rows)
-}
-
-pub fn count_import_rows(conn: &Connection, session_id: &str) -> AppResult<i64> {
-    let count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM trading_import_rows WHERE session_id = ?1",
-        params![session_id],
-        |row| row.get(0),
-    )?;
-    Ok(count)
-}
-
-pub fn get_pending_import_rows(
-    conn: &Connection,
-    session_id: &str,
-) -> AppResult<Vec<TradingImportRow>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, session_id, row_index, data, status, error
-         FROM trading_import_rows
-         WHERE session_id = ?1 AND status = 'pending'
-         ORDER BY row_index",
-    )?;
-
-    let rows = stmt
-        .query_map(params![session_id], |row| {
-            let data_json: String = row.get(3)?;
-            let data: ParsedTradingActivity =
-                serde_json::from_str(&data_json).unwrap_or_else(|_| ParsedTradingActivity {
-                    date: String::new(),
-                    symbol: String::new(),
-                    quantity: None,
-                    activity_type: String::new(),
-                    unit_price: None,
-                    currency: "USD".to_string(),
-                    fee: None,
-                    account_id: None,
-                    row_number: 0,
-                });
-
-            Ok(TradingImportRow {
-                id: row.get(0)?,
-                session_id: row.get(1)?,
-                row_index: row.get(2)?,
-                data,
-                status: row.get(4)?,
-                error: row.get(5)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(rows)
-}
-
-pub fn mark_import_row_imported(conn: &Connection, row_id: i64) -> AppResult<()> {
-    conn.execute(
-        "UPDATE trading_import_rows SET status = 'imported' WHERE id = ?1",
-        params![row_id],
-    )?;
-    Ok(())
-}
-
-pub fn mark_import_row_error(conn: &Connection, row_id: i64, error: &str) -> AppResult<()> {
-    conn.execute(
-        "UPDATE trading_import_rows SET status = 'error', error = ?2 WHERE id = ?1",
-        params![row_id, error],
-    )?;
-    Ok(())
-}
-
-// Split adjustment operations
-
-/// Apply a split to all prior BUY/SELL activities for the same symbol.
-/// Multiplies their quantity by the ratio and divides their unit_price by it,
-/// recording original values in `trading_split_adjustments` for reversal.
-pub fn apply_split_to_past_activities(
-    conn: &Connection,
-    split_activity_id: i64,
-    symbol: &str,
-    split_date: &str,
-    ratio: f64,
-) -> rusqlite::Result<()> {
-    let mut stmt = conn.prepare(
-        "SELECT id, quantity, unit_price_cents
-         FROM trading_activities
-         WHERE symbol = ?1
-           AND date < ?2
-           AND activity_type IN ('BUY', 'SELL')
-           AND quantity IS NOT NULL
-           AND id NOT IN (
-               SELECT target_activity_id FROM trading_split_adjustments
-               WHERE split_activity_id = ?3
-           )",
-    )?;
-
-    let targets: Vec<(i64, f64, Option<i64>)> = stmt
-        .query_map(params![symbol, split_date, split_activity_id], |row| {
-            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    for (target_id, original_qty, original_price) in &targets {
-        conn.execute(
-            "INSERT INTO trading_split_adjustments
-             (split_activity_id, target_activity_id, original_quantity, original_unit_price_cents, split_ratio)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![split_activity_id, target_id, original_qty, original_price, ratio],
-        )?;
-
-        let new_qty = original_qty * ratio;
-        let new_price = original_price.map(|p| (p as f64 / ratio).round() as i64);
-
-        conn.execute(
-            "UPDATE trading_activities
-             SET quantity = ?1, unit_price_cents = ?2, updated_at = datetime('now')
-             WHERE id = ?3",
-            params![new_qty, new_price, target_id],
-        )?;
-
-        debug!(
-            split_id = split_activity_id,
-            target_id = target_id,
-            original_qty = original_qty,
-            new_qty = new_qty,
-            "Applied split adjustment"
-        );
-    }
-
-    if !targets.is_empty() {
-        info!(
-            symbol = %symbol,
-            ratio = ratio,
-            adjusted_count = targets.len(),
-            "Applied split to past activities"
-        );
-    }
-
-    Ok(())
-}
-
-/// Apply all existing splits (dated after this activity) to a newly created BUY/SELL.
-pub fn apply_existing_splits_to_activity(
-    conn: &Connection,
-    activity_id: i64,
-    symbol: &str,
-    activity_date: &str,
-) -> rusqlite::Result<()> {
-    let mut stmt = conn.prepare(
-        "SELECT id, quantity
-         FROM trading_activities
-         WHERE symbol = ?1
-           AND date > ?2
-           AND activity_type = 'SPLIT'
-           AND quantity IS NOT NULL
-           AND quantity > 0
-         ORDER BY date ASC, id ASC",
-    )?;
-
-    let splits: Vec<(i64, f64)> = stmt
-        .query_map(params![symbol, activity_date], |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    if splits.is_empty() {
-        return Ok(());
-    }
-
-    let (current_qty, current_price): (Option<f64>, Option<i64>) = conn.query_row(
-        "SELECT quantity, unit_price_cents FROM trading_activities WHERE id = ?1",
-        [activity_id],
-        |row| Ok((row.get(0)?, row.get(1)?)),
-    )?;
-
-    let Some(mut running_qty) = current_qty else {
-        return Ok(());
-    };
-    let mut running_price = current_price;
-
-    for (split_id, ratio) in &splits {
-        let already_applied: bool = conn.query_row(
-            "SELECT COUNT(*) FROM trading_split_adjustments
-             WHERE split_activity_id = ?1 AND target_activity_id = ?2",
-            params![split_id, activity_id],
-            |row| Ok(row.get::<_, i64>(0)? > 0),
-        )?;
-
-        if already_applied {
-            continue;
-        }
-
-        conn.execute(
-            "INSERT INTO trading_split_adjustments
-             (split_activity_id, target_activity_id, original_quantity, original_unit_price_cents, split_ratio)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![split_id, activity_id, running_qty, running_price, ratio],
-        )?;
-
-        running_qty *= ratio;
-        running_price = running_price.map(|p| (p as f64 / ratio).round() as i64);
-    }
-
-    if Some(running_qty) != current_qty || running_price != current_price {
-        conn.execute(
-            "UPDATE trading_activities
-             SET quantity = ?1, unit_price_cents = ?2, updated_at = datetime('now')
-             WHERE id = ?3",
-            params![running_qty, running_price, activity_id],
-        )?;
-    }
-
-    Ok(())
-}
-
-/// Reverse all adjustments made by a specific split activity, restoring
-/// target activities to the values they would have without this split.
-///
-/// Handles the case of multiple overlapping splits by recomputing from
-/// the base (pre-any-split) values and re-applying remaining splits.
-pub fn reverse_split_adjustments(
-    conn: &Connection,
-    split_activity_id: i64,
-) -> rusqlite::Result<()> {
-    // Collect the target activity ids affected by this split.
-    let mut target_stmt = conn.prepare(
-        "SELECT target_activity_id FROM trading_split_adjustments
-         WHERE split_activity_id = ?1",
-    )?;
-    let target_ids: Vec<i64> = target_stmt
-        .query_map([split_activity_id], |row| row.get(0))?
-        .collect::<Result<Vec<_>, _>>()?;
-    drop(target_stmt);
-
-    let target_count = target_ids.len();
-    for target_id in target_ids {
-        recompute_target_without_split(conn, target_id, split_activity_id)?;
-    }
-
-    // Delete the adjustment records for this split.
-    conn.execute(
-        "DELETE FROM trading_split_adjustments WHERE split_activity_id = ?1",
-        [split_activity_id],
-    )?;
-
-    info!(
-        split_activity_id = split_activity_id,
-        reversed_count = target_count,
-        "Reversed split adjustments"
-    );
-
-    Ok(())
-}
-
-/// Remove adjustment records that target a specific activity (for cleanup
-/// when a BUY/SELL is deleted—no reversal needed since the activity is going away).
-pub fn delete_adjustments_targeting_activity(
-    conn: &Connection,
-    target_activity_id: i64,
-) -> rusqlite::Result<()> {
-    conn.execute(
-        "DELETE FROM trading_split_adjustments WHERE target_activity_id = ?1",
-        [target_activity_id],
-    )?;
-    Ok(())
-}
-
-/// Recompute a target activity's quantity/price after removing one split.
-///
-/// Algorithm:
-/// 1. Find the base value (original before any split) from the earliest adjustment.
-/// 2. Delete the adjustment record for the removed split.
-/// 3. Re-apply remaining splits in chronological order, updating their
-///    stored originals along the way.
-/// 4. Write the final value back to the activity.
-fn recompute_target_without_split(
-    conn: &Connection,
-    target_id: i64,
-    removed_split_id: i64,
-) -> rusqlite::Result<()> {
-    // Get the base (pre-any-split) values: the original from the earliest adjustment.
-    let base: Option<(f64, Option<i64>)> = conn
-        .query_row(
-            "SELECT sa.original_quantity, sa.original_unit_price_cents
-             FROM trading_split_adjustments sa
-             JOIN trading_activities s ON s.id = sa.split_activity_id
-             WHERE sa.target_activity_id = ?1
-             ORDER BY s.date ASC, s.id ASC
-             LIMIT 1",
-            [target_id],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .optional()?;
-
-    let Some((base_qty, base_price)) = base else {
-        return Ok(());
-    };
-
-    // Delete the record for the removed split.
-    conn.execute(
-        "DELETE FROM trading_split_adjustments
-         WHERE split_activity_id = ?1 AND target_activity_id = ?2",
-        params![removed_split_id, target_id],
-    )?;
-
-    // Collect remaining adjustments in chronological order.
-    let mut remaining_stmt = conn.prepare(
-        "SELECT sa.id, sa.split_ratio
-         FROM trading_split_adjustments sa
-         JOIN trading_activities s ON s.id = sa.split_activity_id
-         WHERE sa.target_activity_id = ?1
-         ORDER BY s.date ASC, s.id ASC",
-    )?;
-    let remaining: Vec<(i64, f64)> = remaining_stmt
-        .query_map([target_id], |row| Ok((row.get(0)?, row.get(1)?)))?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    // Replay remaining adjustments from the base values.
-    let mut running_qty = base_qty;
-    let mut running_price = base_price;
-
-    for (adj_id, ratio) in &remaining {
-        conn.execute(
-            "UPDATE trading_split_adjustments
-             SET original_quantity = ?1, original_unit_price_cents = ?2
-             WHERE id = ?3",
-            params![running_qty, running_price, adj_id],
-        )?;
-
-        running_qty *= ratio;
-        running_price = running_price.map(|p| (p as f64 / ratio).round() as i64);
-    }
-
-    // Write the final computed values to the activity.
-    conn.execute(
-        "UPDATE trading_activities
-         SET quantity = ?1, unit_price_cents = ?2, updated_at = datetime('now')
-         WHERE id = ?3",
-        params![running_qty, running_price, target_id],
-    )?;
-
-    debug!(
-        target_id = target_id,
-        removed_split = removed_split_id,
-        final_qty = running_qty,
-        "Recomputed target after split removal"
-    );
-
-    Ok(())
-}
@@ -0,0 +1,279 @@
+//! Sessions and rows of the background trading activity import.
+
+use crate::error::AppResult;
+use crate::models::trading::{TradingImportRow, TradingImportSession, TradingImportStatus};
+use crate::models::ImportFileStats;
+use crate::services::trading_csv_parser::ParsedTradingActivity;
+use rusqlite::{params, Connection};
+use tracing::{debug, info};
+
+pub fn create_import_session(conn: &Connection, id: &str) -> AppResult<TradingImportSession> {
+    conn.execute(
+        "INSERT INTO trading_import_sessions (id, status) VALUES (?1, ?2)",
+        params![id, TradingImportStatus::Parsing.as_str()],
+    )?;
+    info!(session_id = %id, "Created trading import session");
+    get_import_session(conn, id)
+}
+
+pub fn get_import_session(conn: &Connection, id: &str) -> AppResult<TradingImportSession> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, status, total_rows, processed_rows, error_count, errors, created_at, updated_at,
+                file_stats
+         FROM trading_import_sessions WHERE id = ?1",
+    )?;
+
+    let session = stmt.query_row(params![id], |row| {
+        let errors_json: Option<String> = row.get(5)?;
+        let errors: Vec<String> = errors_json
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Ok(TradingImportSession {
+            id: row.get(0)?,
+            status: row
+                .get::<_, String>(1)?
+                .parse()
+                .unwrap_or(TradingImportStatus::Failed),
+            total_rows: row.get(2)?,
+            processed_rows: row.get(3)?,
+            error_count: row.get(4)?,
+            errors,
+            file_stats: row
+                .get::<_, Option<String>>(8)?
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+        })
+    })?;
+
+    Ok(session)
+}
+
+pub fn update_import_session_file_stats(
+    conn: &Connection,
+    id: &str,
+    file_stats: &[ImportFileStats],
+) -> AppResult<()> {
+    let file_stats_json = serde_json::to_string(file_stats).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE trading_import_sessions SET file_stats = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![id, file_stats_json],
+    )?;
+    Ok(())
+}
+
+pub fn update_import_session_status(
+    conn: &Connection,
+    id: &str,
+    status: TradingImportStatus,
+) -> AppResult<()> {
+    conn.execute(
+        "UPDATE trading_import_sessions SET status = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![id, status.as_str()],
+    )?;
+    info!(session_id = %id, status = %status.as_str(), "Updated trading import session status");
+    Ok(())
+}
+
+pub fn update_import_session_progress(
+    conn: &Connection,
+    id: &str,
+    total_rows: i64,
+    processed_rows: i64,
+) -> AppResult<()> {
+    conn.execute(
+        "UPDATE trading_import_sessions SET total_rows = ?2, processed_rows = ?3, updated_at = datetime('now') WHERE id = ?1",
+        params![id, total_rows, processed_rows],
+    )?;
+    Ok(())
+}
+
+pub fn update_import_session_errors(
+    conn: &Connection,
+    id: &str,
+    error_count: i64,
+    errors: &[String],
+) -> AppResult<()> {
+    let errors_json = serde_json::to_string(errors).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE trading_import_sessions SET error_count = ?2, errors = ?3, updated_at = datetime('now') WHERE id = ?1",
+        params![id, error_count, errors_json],
+    )?;
+    Ok(())
+}
+
+pub fn increment_import_session_processed(conn: &Connection, id: &str) -> AppResult<()> {
+    conn.execute(
+        "UPDATE trading_import_sessions SET processed_rows = processed_rows + 1, updated_at = datetime('now') WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(())
+}
+
+pub fn increment_import_session_error_count(conn: &Connection, id: &str) -> AppResult<()> {
+    conn.execute(
+        "UPDATE trading_import_sessions SET error_count = error_count + 1, updated_at = datetime('now') WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_import_session(conn: &Connection, id: &str) -> AppResult<()> {
+    conn.execute(
+        "DELETE FROM trading_import_sessions WHERE id = ?1",
+        params![id],
+    )?;
+    debug!(session_id = %id, "Deleted trading import session");
+    Ok(())
+}
+
+// Import row operations
+
+pub fn insert_import_row(
+    conn: &Connection,
+    session_id: &str,
+    row_index: i64,
+    data: &ParsedTradingActivity,
+    source_file: Option<&str>,
+) -> AppResult<i64> {
+    let data_json = serde_json::to_string(data).unwrap();
+    conn.execute(
+        "INSERT INTO trading_import_rows (session_id, row_index, data, source_file) VALUES (?1, ?2, ?3, ?4)",
+        params![session_id, row_index, data_json, source_file],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn get_import_rows_paginated(
+    conn: &Connection,
+    session_id: &str,
+    limit: i64,
+    offset: i64,
+) -> AppResult<Vec<TradingImportRow>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, session_id, row_index, data, status, error, warning, source_file
+         FROM trading_import_rows
+         WHERE session_id = ?1
+         ORDER BY row_index
+         LIMIT ?2 OFFSET ?3",
+    )?;
+
+    let rows = stmt
+        .query_map(params![session_id, limit, offset], |row| {
+            let data_json: String = row.get(3)?;
+            let data: ParsedTradingActivity =
+                serde_json::from_str(&data_json).unwrap_or_else(|_| ParsedTradingActivity {
+                    date: String::new(),
+                    symbol: String::new(),
+                    quantity: None,
+                    activity_type: String::new(),
+                    unit_price: None,
+                    currency: "USD".to_string(),
+                    fee: None,
+                    fee_currency: None,
+                    exchange_rate: None,
+                    account_id: None,
+                    row_number: 0,
+                });
+
+            Ok(TradingImportRow {
+                id: row.get(0)?,
+                session_id: row.get(1)?,
+                row_index: row.get(2)?,
+                data,
+                status: row.get(4)?,
+                error: row.get(5)?,
+                warning: row.get(6)?,
+                source_file: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+pub fn count_import_rows(conn: &Connection, session_id: &str) -> AppResult<i64> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM trading_import_rows WHERE session_id = ?1",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+
+pub fn get_pending_import_rows(
+    conn: &Connection,
+    session_id: &str,
+) -> AppResult<Vec<TradingImportRow>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, session_id, row_index, data, status, error, warning, source_file
+         FROM trading_import_rows
+         WHERE session_id = ?1 AND status = 'pending'
+         ORDER BY row_index",
+    )?;
+
+    let rows = stmt
+        .query_map(params![session_id], |row| {
+            let data_json: String = row.get(3)?;
+            let data: ParsedTradingActivity =
+                serde_json::from_str(&data_json).unwrap_or_else(|_| ParsedTradingActivity {
+                    date: String::new(),
+                    symbol: String::new(),
+                    quantity: None,
+                    activity_type: String::new(),
+                    unit_price: None,
+                    currency: "USD".to_string(),
+                    fee: None,
+                    fee_currency: None,
+                    exchange_rate: None,
+                    account_id: None,
+                    row_number: 0,
+                });
+
+            Ok(TradingImportRow {
+                id: row.get(0)?,
+                session_id: row.get(1)?,
+                row_index: row.get(2)?,
+                data,
+                status: row.get(4)?,
+                error: row.get(5)?,
+                warning: row.get(6)?,
+                source_file: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+pub fn mark_import_row_imported(conn: &Connection, row_id: i64) -> AppResult<()> {
+    conn.execute(
+        "UPDATE trading_import_rows SET status = 'imported' WHERE id = ?1",
+        params![row_id],
+    )?;
+    Ok(())
+}
+
+pub fn mark_import_row_error(conn: &Connection, row_id: i64, error: &str) -> AppResult<()> {
+    conn.execute(
+        "UPDATE trading_import_rows SET status = 'error', error = ?2 WHERE id = ?1",
+        params![row_id, error],
+    )?;
+    Ok(())
+}
+
+pub fn set_import_row_warning(
+    conn: &Connection,
+    row_id: i64,
+    warning: Option<&str>,
+) -> AppResult<()> {
+    conn.execute(
+        "UPDATE trading_import_rows SET warning = ?2 WHERE id = ?1",
+        params![row_id, warning],
+    )?;
+    Ok(())
+}
+
+// Split adjustment operations
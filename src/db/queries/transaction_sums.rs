@@ -0,0 +1,494 @@
+//! Aggregate queries over transactions, with the grouping and summing done
+//! in SQL. Pending transactions are left out until they are posted.
+
+use crate::db::queries::transactions::SKIP_DUPLICATE_MIRRORS;
+use rusqlite::Connection;
+
+/// Sum `amount_cents` for all transactions matching the given date range.
+pub fn sum_amount_cents(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+) -> rusqlite::Result<i64> {
+    let mut sql =
+        "SELECT COALESCE(SUM(e.amount_cents), 0) FROM transactions e WHERE e.status = 'posted'"
+            .to_string();
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        sql.push_str(" AND e.date >= ?");
+        params_vec.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        sql.push_str(" AND e.date <= ?");
+        params_vec.push(Box::new(to.to_string()));
+    }
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    conn.prepare_cached(&sql)?
+        .query_row(params_refs.as_slice(), |row| row.get(0))
+}
+
+/// Result of a per-category aggregation.
+pub struct CategorySum {
+    pub category_id: Option<i64>,
+    pub category_name: String,
+    pub category_color: String,
+    pub total_cents: i64,
+    pub count: i64,
+}
+
+/// Sum transactions grouped by category, excluding the given category IDs.
+pub fn sum_by_category(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    exclude_ids: &[i64],
+) -> rusqlite::Result<Vec<CategorySum>> {
+    let mut sql = String::from(
+        "SELECT e.category_id, COALESCE(c.name, 'Uncategorized'), COALESCE(c.color, '#6b7280'), \
+         SUM(e.amount_cents), COUNT(*) \
+         FROM transactions e \
+         LEFT JOIN categories c ON e.category_id = c.id \
+         WHERE e.status = 'posted'",
+    );
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        sql.push_str(" AND e.date >= ?");
+        params_vec.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        sql.push_str(" AND e.date <= ?");
+        params_vec.push(Box::new(to.to_string()));
+    }
+    if !exclude_ids.is_empty() {
+        let placeholders: String = exclude_ids
+            .iter()
+            .map(|_| "?")
+            .collect::<Vec<_>>()
+            .join(",");
+        sql.push_str(&format!(
+            " AND (e.category_id IS NULL OR e.category_id NOT IN ({}))",
+            placeholders
+        ));
+        for &id in exclude_ids {
+            params_vec.push(Box::new(id));
+        }
+    }
+    sql.push_str(" GROUP BY e.category_id ORDER BY SUM(e.amount_cents)");
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            Ok(CategorySum {
+                category_id: row.get(0)?,
+                category_name: row.get(1)?,
+                category_color: row.get(2)?,
+                total_cents: row.get(3)?,
+                count: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Sum transactions grouped by date, limited to `category_ids` unless it is
+/// empty. Linked transfer pairs are skipped.
+pub fn sum_by_date(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    category_ids: &[i64],
+) -> rusqlite::Result<Vec<(String, i64)>> {
+    let mut sql = "SELECT e.date, SUM(e.amount_cents) FROM transactions e
+                   WHERE e.transfer_pair_id IS NULL AND e.status = 'posted'"
+        .to_string();
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        sql.push_str(" AND e.date >= ?");
+        params_vec.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        sql.push_str(" AND e.date <= ?");
+        params_vec.push(Box::new(to.to_string()));
+    }
+    if !category_ids.is_empty() {
+        let placeholders = vec!["?"; category_ids.len()].join(",");
+        sql.push_str(&format!(" AND e.category_id IN ({})", placeholders));
+        for &id in category_ids {
+            params_vec.push(Box::new(id));
+        }
+    }
+    sql.push_str(" GROUP BY e.date ORDER BY e.date");
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Result of a per-month aggregation.
+pub struct MonthSum {
+    pub month: String,
+    pub total_cents: i64,
+    pub count: i64,
+}
+
+/// Sum transactions grouped by month (YYYY-MM), filtering to only income
+/// (positive amounts) or only expenses (negative amounts, returned as positive).
+/// Linked transfer pairs are skipped.
+pub fn sum_by_month(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    income_mode: bool,
+    excluded_category_ids: &std::collections::HashSet<i64>,
+) -> rusqlite::Result<Vec<MonthSum>> {
+    let amount_expr = if income_mode {
+        "e.amount_cents"
+    } else {
+        "-e.amount_cents"
+    };
+    let sign_filter = if income_mode {
+        " AND e.amount_cents > 0"
+    } else {
+        " AND e.amount_cents < 0"
+    };
+
+    let mut sql = format!(
+        "SELECT substr(e.date, 1, 7), SUM({}), COUNT(*) FROM transactions e
+         WHERE e.transfer_pair_id IS NULL AND e.status = 'posted'{}",
+        amount_expr, sign_filter
+    );
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        sql.push_str(" AND e.date >= ?");
+        params_vec.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        sql.push_str(" AND e.date <= ?");
+        params_vec.push(Box::new(to.to_string()));
+    }
+    if !excluded_category_ids.is_empty() {
+        let placeholders = vec!["?"; excluded_category_ids.len()].join(",");
+        sql.push_str(&format!(
+            " AND (e.category_id IS NULL OR e.category_id NOT IN ({}))",
+            placeholders
+        ));
+        for &id in excluded_category_ids {
+            params_vec.push(Box::new(id));
+        }
+    }
+    sql.push_str(" GROUP BY substr(e.date, 1, 7) ORDER BY 1");
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            Ok(MonthSum {
+                month: row.get(0)?,
+                total_cents: row.get(1)?,
+                count: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// `(category_id, month, total_cents)`
+pub type CategoryMonthSum = (i64, String, i64);
+
+/// Expenses (returned as positive amounts) of every category per month
+/// (YYYY-MM) in one pass, for the `months` months up to and including the
+/// month of `today`. Uncategorized transactions, linked transfer pairs and
+/// the excluded categories are skipped.
+pub fn monthly_sums_for_all_categories(
+    conn: &Connection,
+    months: u32,
+    today: chrono::NaiveDate,
+    excluded_category_ids: &std::collections::HashSet<i64>,
+) -> rusqlite::Result<Vec<CategoryMonthSum>> {
+    use chrono::{Datelike, Months};
+    let month_start = today.with_day(1).unwrap_or(today);
+    let from = month_start - Months::new(months.saturating_sub(1));
+    let until = month_start + Months::new(1);
+
+    let mut sql = "SELECT e.category_id, substr(e.date, 1, 7), SUM(-e.amount_cents) \
+         FROM transactions e \
+         WHERE e.transfer_pair_id IS NULL AND e.status = 'posted' AND e.amount_cents < 0 \
+         AND e.category_id IS NOT NULL AND e.date >= ? AND e.date < ?"
+        .to_string();
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![
+        Box::new(from.format("%Y-%m-%d").to_string()),
+        Box::new(until.format("%Y-%m-%d").to_string()),
+    ];
+    if !excluded_category_ids.is_empty() {
+        let placeholders = vec!["?"; excluded_category_ids.len()].join(",");
+        sql.push_str(&format!(" AND e.category_id NOT IN ({})", placeholders));
+        for &id in excluded_category_ids {
+            params_vec.push(Box::new(id));
+        }
+    }
+    sql.push_str(" GROUP BY e.category_id, substr(e.date, 1, 7) ORDER BY 1, 2");
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Expense total and count for one group of a spending pattern.
+pub struct PatternSum {
+    pub key: i64,
+    pub total_cents: i64,
+    pub count: i64,
+}
+
+/// Sum expenses (returned as positive amounts) grouped by weekday, keyed by
+/// `strftime('%w')` (0 = Sunday). Linked transfer pairs are skipped.
+pub fn sum_expenses_by_weekday(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    excluded_category_ids: &std::collections::HashSet<i64>,
+) -> rusqlite::Result<Vec<PatternSum>> {
+    sum_expenses_grouped(
+        conn,
+        "CAST(strftime('%w', e.date) AS INTEGER)",
+        from_date,
+        to_date,
+        excluded_category_ids,
+    )
+}
+
+/// Sum expenses grouped into day-of-month buckets: 0 for days 1–10, 1 for
+/// 11–20 and 2 for 21–31. Linked transfer pairs are skipped.
+pub fn sum_expenses_by_month_day_bucket(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    excluded_category_ids: &std::collections::HashSet<i64>,
+) -> rusqlite::Result<Vec<PatternSum>> {
+    sum_expenses_grouped(
+        conn,
+        "CASE WHEN CAST(strftime('%d', e.date) AS INTEGER) <= 10 THEN 0
+              WHEN CAST(strftime('%d', e.date) AS INTEGER) <= 20 THEN 1
+              ELSE 2 END",
+        from_date,
+        to_date,
+        excluded_category_ids,
+    )
+}
+
+fn sum_expenses_grouped(
+    conn: &Connection,
+    group_expr: &str,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+    excluded_category_ids: &std::collections::HashSet<i64>,
+) -> rusqlite::Result<Vec<PatternSum>> {
+    let mut sql = format!(
+        "SELECT {} AS grp, SUM(-e.amount_cents), COUNT(*) FROM transactions e
+         WHERE e.transfer_pair_id IS NULL AND e.status = 'posted' AND e.amount_cents < 0",
+        group_expr
+    );
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        sql.push_str(" AND e.date >= ?");
+        params_vec.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        sql.push_str(" AND e.date <= ?");
+        params_vec.push(Box::new(to.to_string()));
+    }
+    if !excluded_category_ids.is_empty() {
+        let placeholders = vec!["?"; excluded_category_ids.len()].join(",");
+        sql.push_str(&format!(
+            " AND (e.category_id IS NULL OR e.category_id NOT IN ({}))",
+            placeholders
+        ));
+        for &id in excluded_category_ids {
+            params_vec.push(Box::new(id));
+        }
+    }
+    sql.push_str(" GROUP BY grp ORDER BY grp");
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            Ok(PatternSum {
+                key: row.get(0)?,
+                total_cents: row.get(1)?,
+                count: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Result of a category-id aggregation with date range.
+pub struct CategoryIdSumsWithDates {
+    /// `(category_id, total_cents, transaction_count)` per category.
+    pub sums: Vec<(Option<i64>, i64, i64)>,
+    pub min_date: Option<String>,
+    pub max_date: Option<String>,
+}
+
+/// Sum transactions grouped by category_id, also returning the actual date
+/// range (MIN/MAX date) of the matched transactions.  Used by the sankey endpoint.
+pub fn sum_by_category_id_with_dates(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+) -> rusqlite::Result<CategoryIdSumsWithDates> {
+    // Date extent
+    let mut date_sql =
+        "SELECT MIN(e.date), MAX(e.date) FROM transactions e WHERE e.status = 'posted'".to_string();
+    date_sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        date_sql.push_str(" AND e.date >= ?");
+        params_vec.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        date_sql.push_str(" AND e.date <= ?");
+        params_vec.push(Box::new(to.to_string()));
+    }
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let (min_date, max_date): (Option<String>, Option<String>) =
+        conn.query_row(&date_sql, params_refs.as_slice(), |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+
+    // Category sums
+    let mut agg_sql = "SELECT e.category_id, SUM(e.amount_cents), COUNT(*) FROM transactions e \
+         WHERE e.status = 'posted'"
+        .to_string();
+    agg_sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec2: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        agg_sql.push_str(" AND e.date >= ?");
+        params_vec2.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        agg_sql.push_str(" AND e.date <= ?");
+        params_vec2.push(Box::new(to.to_string()));
+    }
+    agg_sql.push_str(" GROUP BY e.category_id");
+    let params_refs2: Vec<&dyn rusqlite::ToSql> = params_vec2.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare_cached(&agg_sql)?;
+    let rows = stmt
+        .query_map(params_refs2.as_slice(), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CategoryIdSumsWithDates {
+        sums: rows,
+        min_date,
+        max_date,
+    })
+}
+
+/// `(category_id, account_id, total_cents)`
+pub type CategoryAccountSum = (Option<i64>, Option<i64>, i64);
+
+/// Sum posted transactions grouped by category and account.  Used by the
+/// sankey endpoint's accounts layer.
+pub fn sum_by_category_and_account(
+    conn: &Connection,
+    from_date: Option<&str>,
+    to_date: Option<&str>,
+) -> rusqlite::Result<Vec<CategoryAccountSum>> {
+    let mut sql = "SELECT e.category_id, e.account_id, SUM(e.amount_cents) FROM transactions e \
+         WHERE e.status = 'posted'"
+        .to_string();
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(from) = from_date {
+        sql.push_str(" AND e.date >= ?");
+        params_vec.push(Box::new(from.to_string()));
+    }
+    if let Some(to) = to_date {
+        sql.push_str(" AND e.date <= ?");
+        params_vec.push(Box::new(to.to_string()));
+    }
+    sql.push_str(" GROUP BY e.category_id, e.account_id");
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Raw expense row used for recurring expense detection.
+pub struct ExpenseRow {
+    pub date: String,
+    pub amount_cents: i64,
+    pub description: String,
+    pub payee: Option<String>,
+    pub counterparty_iban: Option<String>,
+}
+
+/// Fetch all expense transactions (amount_cents < 0), excluding the given
+/// category IDs (typically the Transfers subtree), ordered by date.
+/// Used for recurring expense detection in the handler.
+pub fn fetch_expenses_for_recurring_detection(
+    conn: &Connection,
+    exclude_category_ids: &[i64],
+) -> rusqlite::Result<Vec<ExpenseRow>> {
+    let mut sql = String::from(
+        "SELECT e.date, e.amount_cents, e.description, e.payee, e.counterparty_iban \
+         FROM transactions e \
+         WHERE e.amount_cents < 0 AND e.status = 'posted'",
+    );
+    sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if !exclude_category_ids.is_empty() {
+        let placeholders: String = exclude_category_ids
+            .iter()
+            .map(|_| "?")
+            .collect::<Vec<_>>()
+            .join(",");
+        sql.push_str(&format!(
+            " AND (e.category_id IS NULL OR e.category_id NOT IN ({}))",
+            placeholders
+        ));
+        for &id in exclude_category_ids {
+            params_vec.push(Box::new(id));
+        }
+    }
+
+    sql.push_str(" ORDER BY e.date");
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            Ok(ExpenseRow {
+                date: row.get(0)?,
+                amount_cents: row.get(1)?,
+                description: row.get(2)?,
+                payee: row.get(3)?,
+                counterparty_iban: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
@@ -1,5 +1,7 @@
 use crate::models::tag::{Tag, TagStyle};
-use crate::models::transaction::{NewTransaction, Transaction, TransactionWithRelations};
+use crate::models::transaction::{
+    NewTransaction, Transaction, TransactionStatus, TransactionWithRelations,
+};
 use rusqlite::{params, Connection, OptionalExtension};
 use tracing::{info, trace};
 
@@ -27,21 +29,31 @@ fn transaction_with_relations_from_row(
             creditor_id: row.get(16)?,
             mandate_reference: row.get(17)?,
             customer_reference: row.get(18)?,
+            transfer_pair_id: row.get(19)?,
+            status: TransactionStatus::parse(&row.get::<_, String>(20)?).unwrap_or_default(),
+            auto_categorized: row.get(24)?,
+            auto_category_rule_id: row.get(25)?,
+            source_activity_id: row.get(26)?,
         },
-        category_name: row.get(19)?,
-        category_color: row.get(20)?,
-        account_name: row.get(21)?,
+        category_name: row.get(21)?,
+        category_color: row.get(22)?,
+        account_name: row.get(23)?,
         tags: Vec::new(),
     })
 }
 
-#[derive(Default)]
 pub struct TransactionFilter {
+    /// Match descriptions containing this text.
     pub search: Option<String>,
+    /// Match payees containing this text.
+    pub payee: Option<String>,
     pub category_id: Option<i64>,
+    /// Also match the descendants of `category_id`.
+    pub include_children: bool,
     /// Filter by multiple category IDs (OR). Takes precedence over `category_id` when non-empty.
     pub category_ids: Vec<i64>,
     pub tag_id: Option<i64>,
+    pub account_id: Option<i64>,
     pub from_date: Option<String>,
     pub to_date: Option<String>,
     pub limit: Option<i64>,
@@ -50,8 +62,58 @@ pub struct TransactionFilter {
     pub sort_sql: Option<String>,
     /// When true, only return transactions without a category.
     pub uncategorized_only: bool,
+    pub status: Option<TransactionStatus>,
+    /// When true, only return transactions whose category a rule assigned
+    /// and that were not reviewed yet.
+    pub auto_categorized_only: bool,
+    /// Load each transaction's tags. Analytics and previews that never show
+    /// tags turn this off to skip the extra query.
+    pub with_tags: bool,
+    /// `Some(true)` returns only transactions mirroring a trading activity,
+    /// `Some(false)` leaves them out.
+    pub mirrored: Option<bool>,
+    /// Leave out mirrored transactions that duplicate an imported statement
+    /// line, as the analytics aggregates do.
+    pub skip_duplicate_mirrors: bool,
+    /// Restrict to these transaction IDs when non-empty.
+    pub ids: Vec<i64>,
+}
+
+impl Default for TransactionFilter {
+    fn default() -> Self {
+        Self {
+            search: None,
+            payee: None,
+            category_id: None,
+            include_children: false,
+            category_ids: Vec::new(),
+            tag_id: None,
+            account_id: None,
+            from_date: None,
+            to_date: None,
+            limit: None,
+            offset: None,
+            sort_sql: None,
+            uncategorized_only: false,
+            status: None,
+            auto_categorized_only: false,
+            with_tags: true,
+            mirrored: None,
+            skip_duplicate_mirrors: false,
+            ids: Vec::new(),
+        }
+    }
 }
 
+/// Leaves out transactions mirrored from a trading activity when an imported
+/// statement line books the same amount to the same account on the same day,
+/// so importing broker cash statements does not count dividends twice.
+pub(crate) const SKIP_DUPLICATE_MIRRORS: &str =
+    " AND (e.source_activity_id IS NULL OR NOT EXISTS ( \
+     SELECT 1 FROM transactions s WHERE s.source_activity_id IS NULL \
+     AND s.status = 'posted' AND s.account_id = e.account_id \
+     AND s.date = e.date AND s.amount_cents = e.amount_cents))";
+
 /// Build the WHERE clause fragments and params for a TransactionFilter.
 /// Returns SQL conditions (without leading WHERE/AND) appended after "WHERE 1=1",
 /// and the corresponding parameter vector.
@@ -63,6 +125,18 @@ fn build_filter_where(filter: &TransactionFilter) -> (String, Vec<Box<dyn rusqli
         sql.push_str(" AND e.description LIKE ?");
         params_vec.push(Box::new(format!("%{}%", search)));
     }
+    if let Some(payee) = filter.payee.as_deref().filter(|p| !p.is_empty()) {
+        sql.push_str(" AND e.payee LIKE ?");
+        params_vec.push(Box::new(format!("%{}%", payee)));
+    }
+    if !filter.ids.is_empty() {
+        let placeholders: String = filter.ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        sql.push_str(&format!(" AND e.id IN ({})", placeholders));
+        for &id in &filter.ids {
+            params_vec.push(Box::new(id));
+        }
+    }
+
     if !filter.category_ids.is_empty() {
         let placeholders: String = filter
             .category_ids
@@ -75,9 +149,25 @@ fn build_filter_where(filter: &TransactionFilter) -> (String, Vec<Box<dyn rusqli
             params_vec.push(Box::new(id));
         }
     } else if let Some(category_id) = filter.category_id {
-        sql.push_str(" AND e.category_id = ?");
+        if filter.include_children {
+            sql.push_str(
+                " AND e.category_id IN (
+                    WITH RECURSIVE subtree(id) AS (
+                        SELECT ?
+                        UNION ALL
+                        SELECT c.id FROM categories c JOIN subtree s ON c.parent_id = s.id
+                    )
+                    SELECT id FROM subtree)",
+            );
+        } else {
+            sql.push_str(" AND e.category_id = ?");
+        }
         params_vec.push(Box::new(category_id));
     }
+    if let Some(account_id) = filter.account_id {
+        sql.push_str(" AND e.account_id = ?");
+        params_vec.push(Box::new(account_id));
+    }
     if let Some(ref from_date) = filter.from_date {
         sql.push_str(" AND e.date >= ?");
         params_vec.push(Box::new(from_date.clone()));
@@ -93,6 +183,21 @@ fn build_filter_where(filter: &TransactionFilter) -> (String, Vec<Box<dyn rusqli
     if filter.uncategorized_only {
         sql.push_str(" AND e.category_id IS NULL");
     }
+    if let Some(status) = filter.status {
+        sql.push_str(" AND e.status = ?");
+        params_vec.push(Box::new(status.as_str()));
+    }
+    if filter.auto_categorized_only {
+        sql.push_str(" AND e.auto_categorized = 1");
+    }
+    match filter.mirrored {
+        Some(true) => sql.push_str(" AND e.source_activity_id IS NOT NULL"),
+        Some(false) => sql.push_str(" AND e.source_activity_id IS NULL"),
+        None => {}
+    }
+    if filter.skip_duplicate_mirrors {
+        sql.push_str(SKIP_DUPLICATE_MIRRORS);
+    }
 
     (sql, params_vec)
 }
@@ -108,7 +213,9 @@ pub fn list_transactions(
                 e.category_id, e.account_id, e.notes, e.created_at, e.updated_at,
                 e.value_date, e.payer, e.payee, e.reference, e.transaction_type,
                 e.counterparty_iban, e.creditor_id, e.mandate_reference, e.customer_reference,
-                c.name as category_name, c.color as category_color, a.name as account_name
+                e.transfer_pair_id, e.status,
+                c.name as category_name, c.color as category_color, a.name as account_name,
+                e.auto_categorized, e.auto_category_rule_id, e.source_activity_id
          FROM transactions e
          LEFT JOIN categories c ON e.category_id = c.id
          LEFT JOIN accounts a ON e.account_id = a.id
@@ -139,13 +246,15 @@ pub fn list_transactions(
     let mut transactions: Vec<TransactionWithRelations> =
         transaction_iter.collect::<Result<Vec<_>, _>>()?;
 
-    let transaction_ids: Vec<i64> = transactions.iter().map(|e| e.transaction.id).collect();
-    let mut tags_map = get_tags_for_transactions(conn, &transaction_ids)?;
+    if filter.with_tags {
+        let transaction_ids: Vec<i64> = transactions.iter().map(|e| e.transaction.id).collect();
+        let mut tags_map = get_tags_for_transactions(conn, &transaction_ids)?;
 
-    for transaction in &mut transactions {
-        transaction.tags = tags_map
-            .remove(&transaction.transaction.id)
-            .unwrap_or_default();
+        for transaction in &mut transactions {
+            transaction.tags = tags_map
+                .remove(&transaction.transaction.id)
+                .unwrap_or_default();
+        }
     }
 
     trace!(count = transactions.len(), "Listed transactions");
@@ -154,11 +263,12 @@ pub fn list_transactions(
 
 /// Returns the earliest and latest transaction dates, or `None` when the table is empty.
 pub fn date_extent(conn: &Connection) -> rusqlite::Result<Option<(String, String)>> {
-    conn.query_row("SELECT MIN(date), MAX(date) FROM transactions", [], |row| {
-        let min: Option<String> = row.get(0)?;
-        let max: Option<String> = row.get(1)?;
-        Ok(min.zip(max))
-    })
+    conn.prepare_cached("SELECT MIN(date), MAX(date) FROM transactions")?
+        .query_row([], |row| {
+            let min: Option<String> = row.get(0)?;
+            let max: Option<String> = row.get(1)?;
+            Ok(min.zip(max))
+        })
 }
 
 pub fn count_transactions(conn: &Connection, filter: &TransactionFilter) -> rusqlite::Result<i64> {
@@ -168,7 +278,8 @@ pub fn count_transactions(conn: &Connection, filter: &TransactionFilter) -> rusq
         where_clause,
     );
     let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    conn.query_row(&sql, params_refs.as_slice(), |row| row.get(0))
+    conn.prepare(&sql)?
+        .query_row(params_refs.as_slice(), |row| row.get(0))
 }
 
 pub fn bulk_set_category(
@@ -178,7 +289,8 @@ pub fn bulk_set_category(
 ) -> rusqlite::Result<usize> {
     let (where_clause, mut params_vec) = build_filter_where(filter);
     let sql = format!(
-        "UPDATE transactions SET category_id = ?, updated_at = datetime('now') \
+        "UPDATE transactions SET category_id = ?, auto_categorized = 0, \
+         auto_category_rule_id = NULL, updated_at = datetime('now') \
          WHERE id IN (SELECT e.id FROM transactions e WHERE 1=1{})",
         where_clause,
     );
@@ -230,25 +342,75 @@ pub fn bulk_add_tag(
     Ok(rows)
 }
 
+/// Remove one tag from all matching transactions. Returns the number of
+/// transactions that had it.
+pub fn bulk_remove_tag(
+    conn: &Connection,
+    filter: &TransactionFilter,
+    tag_id: i64,
+) -> rusqlite::Result<usize> {
+    let (where_clause, mut params_vec) = build_filter_where(filter);
+    let sql = format!(
+        "DELETE FROM transaction_tags WHERE tag_id = ? AND transaction_id IN \
+         (SELECT e.id FROM transactions e WHERE 1=1{})",
+        where_clause,
+    );
+    params_vec.insert(0, Box::new(tag_id));
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let rows = conn.execute(&sql, params_refs.as_slice())?;
+    info!(
+        count = rows,
+        tag_id = tag_id,
+        "Bulk removed tag from transactions"
+    );
+    Ok(rows)
+}
+
+/// Remove all tags from all matching transactions. Returns the number of
+/// transactions that had any.
+pub fn bulk_clear_tags(conn: &Connection, filter: &TransactionFilter) -> rusqlite::Result<usize> {
+    let (where_clause, params_vec) = build_filter_where(filter);
+    let subquery = format!("SELECT e.id FROM transactions e WHERE 1=1{}", where_clause);
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let count = conn.query_row(
+        &format!(
+            "SELECT COUNT(DISTINCT transaction_id) FROM transaction_tags \
+             WHERE transaction_id IN ({})",
+            subquery
+        ),
+        params_refs.as_slice(),
+        |row| row.get::<_, i64>(0),
+    )? as usize;
+    conn.execute(
+        &format!(
+            "DELETE FROM transaction_tags WHERE transaction_id IN ({})",
+            subquery
+        ),
+        params_refs.as_slice(),
+    )?;
+    info!(count, "Bulk cleared tags from transactions");
+    Ok(count)
+}
+
 pub fn get_transaction(
     conn: &Connection,
     id: i64,
 ) -> rusqlite::Result<Option<TransactionWithRelations>> {
     trace!(transaction_id = id, "Fetching transaction");
     let transaction = conn
-        .query_row(
+        .prepare_cached(
             "SELECT e.id, e.date, e.amount_cents, e.currency, e.description,
                     e.category_id, e.account_id, e.notes, e.created_at, e.updated_at,
                     e.value_date, e.payer, e.payee, e.reference, e.transaction_type,
                     e.counterparty_iban, e.creditor_id, e.mandate_reference, e.customer_reference,
-                    c.name, c.color, a.name
+                    e.transfer_pair_id, e.status, c.name, c.color, a.name,
+                    e.auto_categorized, e.auto_category_rule_id, e.source_activity_id
              FROM transactions e
              LEFT JOIN categories c ON e.category_id = c.id
              LEFT JOIN accounts a ON e.account_id = a.id
              WHERE e.id = ?",
-            [id],
-            transaction_with_relations_from_row,
-        )
+        )?
+        .query_row([id], transaction_with_relations_from_row)
         .optional()?;
 
     if let Some(mut exp) = transaction {
@@ -266,8 +428,8 @@ pub fn create_transaction(
     conn.execute(
         "INSERT INTO transactions (date, amount_cents, currency, description, category_id, account_id, notes,
          value_date, payer, payee, reference, transaction_type, counterparty_iban,
-         creditor_id, mandate_reference, customer_reference)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+         creditor_id, mandate_reference, customer_reference, status)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         params![
             transaction.date,
             transaction.amount_cents,
@@ -285,6 +447,7 @@ pub fn create_transaction(
             transaction.creditor_id,
             transaction.mandate_reference,
             transaction.customer_reference,
+            transaction.status.as_str(),
         ],
     )?;
 
@@ -305,19 +468,26 @@ pub fn create_transaction(
     Ok(id)
 }
 
+/// Update a transaction. A changed category counts as reviewed, so it is no
+/// longer marked as assigned by a rule.
 pub fn update_transaction(
     conn: &Connection,
     id: i64,
     transaction: &NewTransaction,
 ) -> rusqlite::Result<()> {
     conn.execute(
-        "UPDATE transactions SET date = ?, amount_cents = ?, currency = ?,
+        "UPDATE transactions SET
+         auto_categorized = auto_categorized AND category_id IS ?,
+         auto_category_rule_id = CASE WHEN category_id IS ? THEN auto_category_rule_id END,
+         date = ?, amount_cents = ?, currency = ?,
          description = ?, category_id = ?, account_id = ?, notes = ?,
          value_date = ?, payer = ?, payee = ?, reference = ?, transaction_type = ?,
          counterparty_iban = ?, creditor_id = ?, mandate_reference = ?, customer_reference = ?,
-         updated_at = datetime('now')
+         status = ?, updated_at = datetime('now')
          WHERE id = ?",
         params![
+            transaction.category_id,
+            transaction.category_id,
             transaction.date,
             transaction.amount_cents,
             transaction.currency,
@@ -334,6 +504,7 @@ pub fn update_transaction(
             transaction.creditor_id,
             transaction.mandate_reference,
             transaction.customer_reference,
+            transaction.status.as_str(),
             id,
         ],
     )?;
@@ -354,321 +525,342 @@ pub fn update_transaction(
     Ok(())
 }
 
-pub fn delete_transaction(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
-    let rows = conn.execute("DELETE FROM transactions WHERE id = ?", [id])?;
-    if rows > 0 {
-        info!(transaction_id = id, "Deleted transaction");
+/// Mark a transaction's category as assigned by `rule_id`, if it is still
+/// `category_id` (a merged pending transaction may keep its own). A rule
+/// deleted in the meantime is not recorded.
+pub fn mark_auto_categorized(
+    conn: &Connection,
+    id: i64,
+    category_id: i64,
+    rule_id: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE transactions SET auto_categorized = 1,
+         auto_category_rule_id = (SELECT id FROM rules WHERE id = ?)
+         WHERE id = ? AND category_id = ?",
+        params![rule_id, id, category_id],
+    )?;
+    Ok(())
+}
+
+/// Accept the categories rules assigned to the given transactions. Returns
+/// how many were still waiting for review.
+pub fn accept_auto_categories(conn: &Connection, ids: &[i64]) -> rusqlite::Result<usize> {
+    let mut stmt = conn.prepare_cached(
+        "UPDATE transactions SET auto_categorized = 0 WHERE id = ? AND auto_categorized = 1",
+    )?;
+    let mut accepted = 0;
+    for id in ids {
+        accepted += stmt.execute([id])?;
     }
-    Ok(rows > 0)
+    info!(count = accepted, "Accepted rule-assigned categories");
+    Ok(accepted)
 }
 
-pub fn unset_category(conn: &Connection, category_id: i64) -> rusqlite::Result<usize> {
+/// Set a transaction's category by hand from the review list. Returns
+/// whether the transaction exists.
+pub fn set_reviewed_category(
+    conn: &Connection,
+    id: i64,
+    category_id: Option<i64>,
+) -> rusqlite::Result<bool> {
     let rows = conn.execute(
-        "UPDATE transactions SET category_id = NULL, updated_at = datetime('now') WHERE category_id = ?",
-        [category_id],
+        "UPDATE transactions SET category_id = ?, auto_categorized = 0,
+         auto_category_rule_id = NULL, updated_at = datetime('now') WHERE id = ?",
+        params![category_id, id],
     )?;
-    info!(
-        category_id = category_id,
-        count = rows,
-        "Unset category from transactions"
-    );
-    Ok(rows)
+    Ok(rows > 0)
 }
 
-pub fn delete_all_transactions(conn: &Connection) -> rusqlite::Result<usize> {
-    conn.execute("DELETE FROM transaction_tags", [])?;
-    let rows = conn.execute("DELETE FROM transactions", [])?;
-    tracing::warn!(count = rows, "Deleted all transactions");
-    Ok(rows)
+/// Number of transactions with a rule-assigned category not yet reviewed.
+pub fn count_auto_categorized(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM transactions WHERE auto_categorized = 1",
+        [],
+        |row| row.get(0),
+    )
 }
 
-// ---------------------------------------------------------------------------
-// Aggregate query helpers (push GROUP BY / SUM into SQL instead of Rust).
-// ---------------------------------------------------------------------------
+/// Link two transactions as the two sides of one transfer.
+pub fn link_transfer_pair(conn: &Connection, a: i64, b: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE transactions SET transfer_pair_id = ? WHERE id = ?",
+        params![b, a],
+    )?;
+    conn.execute(
+        "UPDATE transactions SET transfer_pair_id = ? WHERE id = ?",
+        params![a, b],
+    )?;
+    info!(transaction_id = a, pair_id = b, "Linked transfer pair");
+    Ok(())
+}
 
-/// Sum `amount_cents` for all transactions matching the given date range.
-pub fn sum_amount_cents(
+/// Book both sides of a detected transfer to `category_id`.
+pub fn set_transfer_category(
     conn: &Connection,
-    from_date: Option<&str>,
-    to_date: Option<&str>,
-) -> rusqlite::Result<i64> {
-    let mut sql =
-        "SELECT COALESCE(SUM(e.amount_cents), 0) FROM transactions e WHERE 1=1".to_string();
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    if let Some(from) = from_date {
-        sql.push_str(" AND e.date >= ?");
-        params_vec.push(Box::new(from.to_string()));
-    }
-    if let Some(to) = to_date {
-        sql.push_str(" AND e.date <= ?");
-        params_vec.push(Box::new(to.to_string()));
-    }
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    conn.query_row(&sql, params_refs.as_slice(), |row| row.get(0))
+    a: i64,
+    b: i64,
+    category_id: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE transactions SET category_id = ?, auto_categorized = 0,
+                auto_category_rule_id = NULL, updated_at = datetime('now')
+         WHERE id IN (?, ?)",
+        params![category_id, a, b],
+    )?;
+    Ok(())
 }
 
-/// Result of a per-category aggregation.
-pub struct CategorySum {
-    pub category_id: Option<i64>,
-    pub category_name: String,
-    pub category_color: String,
-    pub total_cents: i64,
-    pub count: i64,
+/// Unpaired transaction of an account, for transfer detection.
+pub struct UnpairedRow {
+    pub id: i64,
+    pub date: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub account_id: i64,
+    pub counterparty_iban: Option<String>,
 }
 
-/// Sum transactions grouped by category, excluding the given category IDs.
-pub fn sum_by_category(
-    conn: &Connection,
-    from_date: Option<&str>,
-    to_date: Option<&str>,
-    exclude_ids: &[i64],
-) -> rusqlite::Result<Vec<CategorySum>> {
-    let mut sql = String::from(
-        "SELECT e.category_id, COALESCE(c.name, 'Uncategorized'), COALESCE(c.color, '#6b7280'), \
-         SUM(e.amount_cents), COUNT(*) \
-         FROM transactions e \
-         LEFT JOIN categories c ON e.category_id = c.id \
-         WHERE 1=1",
-    );
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    if let Some(from) = from_date {
-        sql.push_str(" AND e.date >= ?");
-        params_vec.push(Box::new(from.to_string()));
-    }
-    if let Some(to) = to_date {
-        sql.push_str(" AND e.date <= ?");
-        params_vec.push(Box::new(to.to_string()));
-    }
-    if !exclude_ids.is_empty() {
-        let placeholders: String = exclude_ids
-            .iter()
-            .map(|_| "?")
-            .collect::<Vec<_>>()
-            .join(",");
-        sql.push_str(&format!(
-            " AND (e.category_id IS NULL OR e.category_id NOT IN ({}))",
-            placeholders
-        ));
-        for &id in exclude_ids {
-            params_vec.push(Box::new(id));
-        }
-    }
-    sql.push_str(" GROUP BY e.category_id ORDER BY SUM(e.amount_cents)");
-
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    let mut stmt = conn.prepare(&sql)?;
+/// Fetch transactions with an account that are not one side of a transfer,
+/// ordered by date.
+pub fn fetch_unpaired_with_account(conn: &Connection) -> rusqlite::Result<Vec<UnpairedRow>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, date, amount_cents, currency, account_id, counterparty_iban
+         FROM transactions
+         WHERE transfer_pair_id IS NULL AND account_id IS NOT NULL AND amount_cents != 0
+         ORDER BY date, id",
+    )?;
     let rows = stmt
-        .query_map(params_refs.as_slice(), |row| {
-            Ok(CategorySum {
-                category_id: row.get(0)?,
-                category_name: row.get(1)?,
-                category_color: row.get(2)?,
-                total_cents: row.get(3)?,
-                count: row.get(4)?,
+        .query_map([], |row| {
+            Ok(UnpairedRow {
+                id: row.get(0)?,
+                date: row.get(1)?,
+                amount_cents: row.get(2)?,
+                currency: row.get(3)?,
+                account_id: row.get(4)?,
+                counterparty_iban: row.get(5)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
     Ok(rows)
 }
 
-/// Sum transactions grouped by date.
-pub fn sum_by_date(
+/// Mirror date, amount, currency and notes onto the other side of a transfer.
+/// The counterpart receives the negated amount.
+pub fn sync_transfer_counterpart(
     conn: &Connection,
-    from_date: Option<&str>,
-    to_date: Option<&str>,
-) -> rusqlite::Result<Vec<(String, i64)>> {
-    let mut sql = "SELECT e.date, SUM(e.amount_cents) FROM transactions e WHERE 1=1".to_string();
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    if let Some(from) = from_date {
-        sql.push_str(" AND e.date >= ?");
-        params_vec.push(Box::new(from.to_string()));
-    }
-    if let Some(to) = to_date {
-        sql.push_str(" AND e.date <= ?");
-        params_vec.push(Box::new(to.to_string()));
+    pair_id: i64,
+    source: &NewTransaction,
+) -> rusqlite::Result<bool> {
+    let rows = conn.execute(
+        "UPDATE transactions SET date = ?, amount_cents = ?, currency = ?, notes = ?,
+         updated_at = datetime('now')
+         WHERE id = ?",
+        params![
+            source.date,
+            -source.amount_cents,
+            source.currency,
+            source.notes,
+            pair_id
+        ],
+    )?;
+    if rows > 0 {
+        info!(transaction_id = pair_id, "Synced transfer counterpart");
     }
-    sql.push_str(" GROUP BY e.date ORDER BY e.date");
-
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt
-        .query_map(params_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?
-        .collect::<Result<Vec<_>, _>>()?;
-    Ok(rows)
-}
-
-/// Result of a per-month aggregation.
-pub struct MonthSum {
-    pub month: String,
-    pub total_cents: i64,
-    pub count: i64,
+    Ok(rows > 0)
 }
 
-/// Sum transactions grouped by month (YYYY-MM), filtering to only income
-/// (positive amounts) or only expenses (negative amounts, returned as positive).
-pub fn sum_by_month(
+/// Book a pending transaction with the data of the matching imported one.
+/// Category, account and notes set on the pending transaction are kept unless
+/// the import provides them; tags are added. Returns false if the transaction
+/// no longer exists or is not pending anymore.
+pub fn merge_into_pending(
     conn: &Connection,
-    from_date: Option<&str>,
-    to_date: Option<&str>,
-    income_mode: bool,
-) -> rusqlite::Result<Vec<MonthSum>> {
-    let amount_expr = if income_mode {
-        "e.amount_cents"
-    } else {
-        "-e.amount_cents"
-    };
-    let sign_filter = if income_mode {
-        " AND e.amount_cents > 0"
-    } else {
-        " AND e.amount_cents < 0"
-    };
+    id: i64,
+    transaction: &NewTransaction,
+) -> rusqlite::Result<bool> {
+    let rows = conn.execute(
+        "UPDATE transactions SET date = ?, amount_cents = ?, currency = ?, description = ?,
+         category_id = COALESCE(?, category_id), account_id = COALESCE(?, account_id),
+         notes = COALESCE(?, notes),
+         value_date = ?, payer = ?, payee = ?, reference = ?, transaction_type = ?,
+         counterparty_iban = ?, creditor_id = ?, mandate_reference = ?, customer_reference = ?,
+         status = 'posted', updated_at = datetime('now')
+         WHERE id = ? AND status = 'pending'",
+        params![
+            transaction.date,
+            transaction.amount_cents,
+            transaction.currency,
+            transaction.description,
+            transaction.category_id,
+            transaction.account_id,
+            transaction.notes,
+            transaction.value_date,
+            transaction.payer,
+            transaction.payee,
+            transaction.reference,
+            transaction.transaction_type,
+            transaction.counterparty_iban,
+            transaction.creditor_id,
+            transaction.mandate_reference,
+            transaction.customer_reference,
+            id,
+        ],
+    )?;
+    if rows == 0 {
+        return Ok(false);
+    }
 
-    let mut sql = format!(
-        "SELECT substr(e.date, 1, 7), SUM({}), COUNT(*) FROM transactions e WHERE 1=1{}",
-        amount_expr, sign_filter
-    );
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    if let Some(from) = from_date {
-        sql.push_str(" AND e.date >= ?");
-        params_vec.push(Box::new(from.to_string()));
+    for tag_id in &transaction.tag_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO transaction_tags (transaction_id, tag_id) VALUES (?, ?)",
+            params![id, tag_id],
+        )?;
     }
-    if let Some(to) = to_date {
-        sql.push_str(" AND e.date <= ?");
-        params_vec.push(Box::new(to.to_string()));
+
+    info!(
+        transaction_id = id,
+        "Merged imported transaction into pending one"
+    );
+    Ok(true)
+}
+
+/// Mark a pending transaction as posted. Returns false if there is no
+/// transaction with this id.
+pub fn mark_posted(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    let rows = conn.execute(
+        "UPDATE transactions SET status = 'posted', updated_at = datetime('now')
+         WHERE id = ?",
+        [id],
+    )?;
+    if rows > 0 {
+        info!(transaction_id = id, "Marked transaction as posted");
     }
-    sql.push_str(" GROUP BY substr(e.date, 1, 7) ORDER BY 1");
+    Ok(rows > 0)
+}
 
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt
-        .query_map(params_refs.as_slice(), |row| {
-            Ok(MonthSum {
-                month: row.get(0)?,
-                total_cents: row.get(1)?,
-                count: row.get(2)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-    Ok(rows)
+/// Id of the transaction created for a client-supplied external id.
+pub fn find_by_external_id(conn: &Connection, external_id: &str) -> rusqlite::Result<Option<i64>> {
+    conn.prepare_cached("SELECT id FROM transactions WHERE external_id = ?")?
+        .query_row([external_id], |row| row.get(0))
+        .optional()
 }
 
-/// Result of a category-id aggregation with date range.
-pub struct CategoryIdSumsWithDates {
-    pub sums: Vec<(Option<i64>, i64)>,
-    pub min_date: Option<String>,
-    pub max_date: Option<String>,
+pub fn set_external_id(conn: &Connection, id: i64, external_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE transactions SET external_id = ? WHERE id = ?",
+        params![external_id, id],
+    )?;
+    Ok(())
 }
 
-/// Sum transactions grouped by category_id, also returning the actual date
-/// range (MIN/MAX date) of the matched transactions.  Used by the sankey endpoint.
-pub fn sum_by_category_id_with_dates(
+/// Id of the transaction mirroring a trading activity.
+pub fn find_by_source_activity(
     conn: &Connection,
-    from_date: Option<&str>,
-    to_date: Option<&str>,
-) -> rusqlite::Result<CategoryIdSumsWithDates> {
-    // Date extent
-    let mut date_sql = "SELECT MIN(e.date), MAX(e.date) FROM transactions e WHERE 1=1".to_string();
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    if let Some(from) = from_date {
-        date_sql.push_str(" AND e.date >= ?");
-        params_vec.push(Box::new(from.to_string()));
-    }
-    if let Some(to) = to_date {
-        date_sql.push_str(" AND e.date <= ?");
-        params_vec.push(Box::new(to.to_string()));
-    }
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    let (min_date, max_date): (Option<String>, Option<String>) =
-        conn.query_row(&date_sql, params_refs.as_slice(), |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        })?;
-
-    // Category sums
-    let mut agg_sql =
-        "SELECT e.category_id, SUM(e.amount_cents) FROM transactions e WHERE 1=1".to_string();
-    let mut params_vec2: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-    if let Some(from) = from_date {
-        agg_sql.push_str(" AND e.date >= ?");
-        params_vec2.push(Box::new(from.to_string()));
-    }
-    if let Some(to) = to_date {
-        agg_sql.push_str(" AND e.date <= ?");
-        params_vec2.push(Box::new(to.to_string()));
-    }
-    agg_sql.push_str(" GROUP BY e.category_id");
-    let params_refs2: Vec<&dyn rusqlite::ToSql> = params_vec2.iter().map(|p| p.as_ref()).collect();
-    let mut stmt = conn.prepare(&agg_sql)?;
-    let rows = stmt
-        .query_map(params_refs2.as_slice(), |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(CategoryIdSumsWithDates {
-        sums: rows,
-        min_date,
-        max_date,
-    })
+    activity_id: i64,
+) -> rusqlite::Result<Option<i64>> {
+    conn.prepare_cached("SELECT id FROM transactions WHERE source_activity_id = ?")?
+        .query_row([activity_id], |row| row.get(0))
+        .optional()
 }
 
-/// Raw expense row used for recurring expense detection.
-pub struct ExpenseRow {
-    pub date: String,
-    pub amount_cents: i64,
-    pub description: String,
-    pub payee: Option<String>,
-    pub counterparty_iban: Option<String>,
+pub fn set_source_activity(conn: &Connection, id: i64, activity_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE transactions SET source_activity_id = ? WHERE id = ?",
+        params![activity_id, id],
+    )?;
+    Ok(())
 }
 
-/// Fetch all expense transactions (amount_cents < 0), excluding the given
-/// category IDs (typically the Transfers subtree), ordered by date.
-/// Used for recurring expense detection in the handler.
-pub fn fetch_expenses_for_recurring_detection(
+/// Update the fields of a mirrored transaction that follow its activity.
+/// Category, tags and notes stay as the user left them.
+pub fn update_mirrored(
     conn: &Connection,
-    exclude_category_ids: &[i64],
-) -> rusqlite::Result<Vec<ExpenseRow>> {
-    let mut sql = String::from(
-        "SELECT e.date, e.amount_cents, e.description, e.payee, e.counterparty_iban \
-         FROM transactions e \
-         WHERE e.amount_cents < 0",
-    );
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    id: i64,
+    transaction: &NewTransaction,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE transactions SET date = ?, amount_cents = ?, currency = ?, description = ?,
+         account_id = ?, updated_at = datetime('now')
+         WHERE id = ?",
+        params![
+            transaction.date,
+            transaction.amount_cents,
+            transaction.currency,
+            transaction.description,
+            transaction.account_id,
+            id,
+        ],
+    )?;
+    info!(transaction_id = id, "Updated mirrored transaction");
+    Ok(())
+}
 
-    if !exclude_category_ids.is_empty() {
-        let placeholders: String = exclude_category_ids
-            .iter()
-            .map(|_| "?")
-            .collect::<Vec<_>>()
-            .join(",");
-        sql.push_str(&format!(
-            " AND (e.category_id IS NULL OR e.category_id NOT IN ({}))",
-            placeholders
-        ));
-        for &id in exclude_category_ids {
-            params_vec.push(Box::new(id));
-        }
+pub fn delete_transaction(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    let rows = conn.execute("DELETE FROM transactions WHERE id = ?", [id])?;
+    if rows > 0 {
+        info!(transaction_id = id, "Deleted transaction");
     }
+    Ok(rows > 0)
+}
 
-    sql.push_str(" ORDER BY e.date");
+/// Delete the given transactions. Returns the number of rows removed.
+pub fn delete_transactions(conn: &Connection, ids: &[i64]) -> rusqlite::Result<usize> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let placeholders: String = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let rows = conn.execute(
+        &format!("DELETE FROM transactions WHERE id IN ({})", placeholders),
+        rusqlite::params_from_iter(ids),
+    )?;
+    info!(count = rows, "Bulk deleted transactions");
+    Ok(rows)
+}
 
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
-    let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt
-        .query_map(params_refs.as_slice(), |row| {
-            Ok(ExpenseRow {
-                date: row.get(0)?,
-                amount_cents: row.get(1)?,
-                description: row.get(2)?,
-                payee: row.get(3)?,
-                counterparty_iban: row.get(4)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
+/// Return those of `ids` that match no transaction.
+pub fn missing_ids(conn: &Connection, ids: &[i64]) -> rusqlite::Result<Vec<i64>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders: String = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id FROM transactions WHERE id IN ({})",
+        placeholders
+    ))?;
+    let found: std::collections::HashSet<i64> = stmt
+        .query_map(rusqlite::params_from_iter(ids), |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(ids
+        .iter()
+        .copied()
+        .filter(|id| !found.contains(id))
+        .collect())
+}
+
+pub fn unset_category(conn: &Connection, category_id: i64) -> rusqlite::Result<usize> {
+    let rows = conn.execute(
+        "UPDATE transactions SET category_id = NULL, auto_categorized = 0, auto_category_rule_id = NULL,
+         updated_at = datetime('now') WHERE category_id = ?",
+        [category_id],
+    )?;
+    info!(
+        category_id = category_id,
+        count = rows,
+        "Unset category from transactions"
+    );
+    Ok(rows)
+}
+
+pub fn delete_all_transactions(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute("DELETE FROM transaction_tags", [])?;
+    let rows = conn.execute("DELETE FROM transactions", [])?;
+    tracing::warn!(count = rows, "Deleted all transactions");
     Ok(rows)
 }
 
 fn get_transaction_tags(conn: &Connection, transaction_id: i64) -> rusqlite::Result<Vec<Tag>> {
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare_cached(
         "SELECT t.id, t.name, t.color, t.style, t.created_at
          FROM tags t
          JOIN transaction_tags et ON t.id = et.tag_id
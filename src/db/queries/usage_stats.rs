@@ -0,0 +1,71 @@
+use rusqlite::{params, Connection};
+
+use crate::error::AppResult;
+use crate::models::usage::{UsageCount, UsageKey};
+
+/// Add aggregated request counts to the stored daily totals.
+pub fn add_counts(conn: &mut Connection, counts: &[(UsageKey, UsageCount)]) -> AppResult<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO usage_stats
+                 (date, method, route, status, duration_bucket_ms, count, total_duration_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (date, method, route, status, duration_bucket_ms) DO UPDATE SET
+                 count = count + excluded.count,
+                 total_duration_ms = total_duration_ms + excluded.total_duration_ms",
+        )?;
+        for (key, count) in counts {
+            stmt.execute(params![
+                key.date,
+                key.method,
+                key.route,
+                key.status,
+                key.duration_bucket_ms,
+                count.count,
+                count.total_duration_ms,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Request counts since `since` (inclusive, `YYYY-MM-DD`), summed over days.
+/// The returned keys carry `since` as their date.
+pub fn counts_since(conn: &Connection, since: &str) -> AppResult<Vec<(UsageKey, UsageCount)>> {
+    let mut stmt = conn.prepare(
+        "SELECT method, route, status, duration_bucket_ms, SUM(count), SUM(total_duration_ms)
+         FROM usage_stats
+         WHERE date >= ?1
+         GROUP BY method, route, status, duration_bucket_ms",
+    )?;
+    let rows = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                UsageKey {
+                    date: since.to_string(),
+                    method: row.get(0)?,
+                    route: row.get(1)?,
+                    status: row.get(2)?,
+                    duration_bucket_ms: row.get(3)?,
+                },
+                UsageCount {
+                    count: row.get(4)?,
+                    total_duration_ms: row.get(5)?,
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+pub fn count_rows(conn: &Connection) -> AppResult<i64> {
+    let count = conn.query_row("SELECT COUNT(*) FROM usage_stats", [], |row| row.get(0))?;
+    Ok(count)
+}
+
+pub fn delete_all(conn: &Connection) -> AppResult<usize> {
+    let deleted = conn.execute("DELETE FROM usage_stats", [])?;
+    Ok(deleted)
+}
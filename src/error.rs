@@ -3,6 +3,7 @@ use axum::http::StatusCode;
 use axum::response::{Html, IntoResponse, Response};
 use thiserror::Error;
 
+use crate::db::migrations::MigrationError;
 use crate::error_pages::ErrorMessage;
 
 #[derive(Error, Debug)]
@@ -19,6 +20,12 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
     #[error("CSV parse error: {0}")]
     CsvParse(String),
 
@@ -29,32 +36,44 @@ pub enum AppError {
     Internal(String),
 }
 
+impl From<MigrationError> for AppError {
+    fn from(e: MigrationError) -> Self {
+        match e {
+            MigrationError::Database(e) => AppError::Database(e),
+            other => AppError::Internal(other.to_string()),
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let request_id = crate::request_id::current().unwrap_or_default();
         let (status, message) = match &self {
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.clone()),
             AppError::CsvParse(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
             AppError::Database(e) => {
-                tracing::error!("Database error: {:?}", e);
+                tracing::error!(%request_id, "Database error: {:?}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     "Database error".to_string(),
                 )
             }
             AppError::Pool(e) => {
-                tracing::error!("Pool error: {:?}", e);
+                tracing::error!(%request_id, "Pool error: {:?}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     "Database connection error".to_string(),
                 )
             }
             AppError::Io(e) => {
-                tracing::error!("IO error: {:?}", e);
+                tracing::error!(%request_id, "IO error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "IO error".to_string())
             }
             AppError::Internal(msg) => {
-                tracing::error!("Internal error: {}", msg);
+                tracing::error!(%request_id, "Internal error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, msg.clone())
             }
         };
@@ -93,3 +112,13 @@ impl<T: Template> RenderHtml for T {
             .map_err(|e| AppError::Internal(format!("Template error: {}", e)))
     }
 }
+
+/// Render a form page, as a 400 response if it shows a validation error.
+pub fn render_form<T: Template>(template: T, has_error: bool) -> AppResult<Response> {
+    let status = if has_error {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, template.render_html()?).into_response())
+}
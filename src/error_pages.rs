@@ -1,15 +1,16 @@
 use askama::Template;
 use axum::body::Body;
 use axum::extract::State;
-use axum::http::Request;
-use axum::http::StatusCode;
+use axum::http::{header, Request, StatusCode};
 use axum::middleware::Next;
 use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
 
 use crate::config::AuthMode;
 use crate::db::queries::settings;
 use crate::filters::Icons;
 use crate::models::Settings;
+use crate::request_id;
 use crate::state::{AppState, JsManifest};
 use crate::VERSION;
 
@@ -26,15 +27,20 @@ struct ErrorPageTemplate {
     manifest: JsManifest,
     version: &'static str,
     xsrf_token: String,
+    csp_nonce: String,
     status_code: u16,
     status_text: &'static str,
     message: String,
+    request_id: Option<String>,
 }
 
 /// Middleware that replaces 4xx/5xx responses with a full error page.
 ///
-/// Skips HTMX requests, API routes, and the health endpoint so they
-/// keep their original (partial/JSON/plain) response bodies.
+/// Skips HTMX requests, the health endpoint, and share links so they keep
+/// their original (partial/plain) response bodies. Share visitors must not
+/// get the app navigation of the full error page. API routes get a JSON
+/// body instead. Both the page and the JSON body show the request id, to
+/// find the matching log lines.
 pub async fn error_page_middleware(
     State(state): State<AppState>,
     request: Request<Body>,
@@ -44,9 +50,11 @@ pub async fn error_page_middleware(
     let path = request.uri().path().to_owned();
     let is_api = path.starts_with("/api/");
     let is_health = path == "/health";
+    let is_share = path.starts_with("/share/");
 
     let method = request.method().clone();
     let response = next.run(request).await;
+    let request_id = request_id::current();
 
     let status = response.status();
     if status.is_client_error() || status.is_server_error() {
@@ -60,19 +68,54 @@ pub async fn error_page_middleware(
             %method,
             %path,
             message,
+            request_id = request_id.as_deref().unwrap_or_default(),
             "request failed"
         );
     }
 
-    if is_htmx || is_api || is_health {
+    if is_htmx || is_health || is_share {
         return response;
     }
 
-    if status.is_client_error() || status.is_server_error() {
-        render_error_page(&state, status, &response)
-    } else {
+    if !status.is_client_error() && !status.is_server_error() {
         response
+    } else if is_api {
+        json_error_response(status, response, request_id).await
+    } else {
+        render_error_page(&state, status, &response, request_id)
+    }
+}
+
+/// Give an API error a JSON body with the request id. A JSON object the
+/// handler already returned is kept, with the id added.
+async fn json_error_response(
+    status: StatusCode,
+    response: Response,
+    request_id: Option<String>,
+) -> Response {
+    let message = response
+        .extensions()
+        .get::<ErrorMessage>()
+        .map(|e| e.0.clone())
+        .unwrap_or_else(|| default_message(status));
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+
+    let mut body = serde_json::Value::Null;
+    if is_json {
+        if let Ok(bytes) = axum::body::to_bytes(response.into_body(), usize::MAX).await {
+            body = serde_json::from_slice(&bytes).unwrap_or_default();
+        }
+    }
+    if !body.is_object() {
+        body = serde_json::json!({ "error": message });
     }
+    body["request_id"] = serde_json::json!(request_id);
+
+    (status, Json(body)).into_response()
 }
 
 /// Fallback handler for unmatched routes.
@@ -84,7 +127,12 @@ pub async fn fallback_handler() -> Response {
     response
 }
 
-fn render_error_page(state: &AppState, status: StatusCode, response: &Response) -> Response {
+fn render_error_page(
+    state: &AppState,
+    status: StatusCode,
+    response: &Response,
+    request_id: Option<String>,
+) -> Response {
     let message = response
         .extensions()
         .get::<ErrorMessage>()
@@ -108,9 +156,11 @@ fn render_error_page(state: &AppState, status: StatusCode, response: &Response)
         manifest: state.manifest.clone(),
         version: VERSION,
         xsrf_token: state.xsrf_token.value().to_string(),
+        csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
         status_code: status.as_u16(),
         status_text,
         message,
+        request_id,
     };
 
     match template.render() {
@@ -128,6 +178,7 @@ fn status_info(status: StatusCode) -> (&'static str, &'static str) {
         403 => ("Forbidden", "You don't have permission to access this."),
         404 => ("Not Found", "The page you're looking for doesn't exist."),
         405 => ("Method Not Allowed", "This action is not supported."),
+        413 => ("Payload Too Large", "The request body is too large."),
         500 => ("Internal Server Error", "Something went wrong on our end."),
         _ => ("Error", ""),
     }
@@ -2,12 +2,17 @@
 //!
 //! ## Money Formatting
 //!
-//! Format order: `<sign> <currency> <amount>`. For percentages: `<sign><value>%`.
+//! Format order: `<sign> <currency> <amount>`, or `<sign> <amount> <currency>` when
+//! the user places the symbol after the amount (see [`CurrencyFormat`]). For
+//! percentages: `<sign><value>%`.
 //!
 //! Four formatter categories:
 //!
 //! 1. **Differences in %** (`format_percent`): always green/red, always with sign (+/-).
-//!    Applies to G/L percentages.
+//!    Applies to G/L percentages, XIRR and changes over time. Shares of a
+//!    total use `format_percent_share`, without the plus sign. Both round to
+//!    the `percent_decimals` setting and show [`NO_PERCENT`] for values that
+//!    are not a number.
 //!
 //! 2. **Differences in absolute** (`format_money_plain`): always green/red, always with
 //!    sign (+/-). Applies to G/L amounts.
@@ -84,80 +89,165 @@ impl Icons {
     }
 }
 
+/// How a currency amount is rendered: symbol, its placement, and the number
+/// of decimals. Defaults come from the symbol table (prefix, two decimals);
+/// the user's settings may override them for their main currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrencyFormat {
+    pub symbol: String,
+    /// Place the symbol after the amount ("1.234,56 €") instead of before it.
+    pub suffix: bool,
+    pub decimals: u32,
+}
+
+impl CurrencyFormat {
+    pub fn for_currency(currency: &str) -> Self {
+        Self {
+            symbol: currency_symbol(currency).to_string(),
+            suffix: false,
+            decimals: 2,
+        }
+    }
+
+    /// Format the absolute value of `cents` with symbol but without sign.
+    fn format_abs(&self, cents: i64, locale: &str) -> String {
+        let (thousands_sep, decimal_sep) = locale_separators(locale);
+        let (whole, fraction) = split_cents(cents.abs(), self.decimals);
+        let mut number = format_with_thousands(whole, thousands_sep);
+        if self.decimals > 0 {
+            number.push(decimal_sep);
+            number.push_str(&format!(
+                "{:0width$}",
+                fraction,
+                width = self.decimals as usize
+            ));
+        }
+
+        if self.suffix {
+            format!("{}\u{00a0}{}", number, self.symbol.trim())
+        } else {
+            format!("{}{}", self.symbol, number)
+        }
+    }
+}
+
+/// Split a non-negative cent amount into whole units and a fraction with
+/// `decimals` digits. Rounds half up when fewer than two decimals are shown.
+fn split_cents(abs_cents: i64, decimals: u32) -> (i64, i64) {
+    if decimals <= 2 {
+        let scale = 10_i64.pow(2 - decimals);
+        let rounded = (abs_cents + scale / 2) / scale;
+        let base = 10_i64.pow(decimals);
+        (rounded / base, rounded % base)
+    } else {
+        (
+            abs_cents / 100,
+            (abs_cents % 100) * 10_i64.pow(decimals - 2),
+        )
+    }
+}
+
 /// Format cents as a colored money display with proper locale formatting.
 /// Returns HTML with appropriate Tailwind color classes.
 pub fn format_money(cents: i64, currency: &str, locale: &str) -> String {
-    let (formatted, color_class) = format_money_impl(cents, currency, locale);
+    format_money_with(cents, &CurrencyFormat::for_currency(currency), locale)
+}
+
+/// Like [`format_money`], with an explicit currency format.
+pub fn format_money_with(cents: i64, format: &CurrencyFormat, locale: &str) -> String {
+    let (formatted, color_class) = format_money_impl(cents, format, locale);
     format!(r#"<span class="{}">{}</span>"#, color_class, formatted)
 }
 
 /// Format cents as plain text (no HTML/color), useful for inputs or exports.
 pub fn format_money_plain(cents: i64, currency: &str, locale: &str) -> String {
-    let (formatted, _) = format_money_impl(cents, currency, locale);
+    format_money_plain_with(cents, &CurrencyFormat::for_currency(currency), locale)
+}
+
+/// Like [`format_money_plain`], with an explicit currency format.
+pub fn format_money_plain_with(cents: i64, format: &CurrencyFormat, locale: &str) -> String {
+    let (formatted, _) = format_money_impl(cents, format, locale);
     formatted
 }
 
 /// Format cents without sign prefix: "-" for negative, no sign for positive/zero.
 /// Shared helper for balance and neutral formatting.
-fn format_unsigned_money(cents: i64, currency: &str, locale: &str) -> String {
-    let abs_cents = cents.abs();
-    let whole = abs_cents / 100;
-    let fractional = abs_cents % 100;
-
-    let (thousands_sep, decimal_sep) = locale_separators(locale);
-    let whole_str = format_with_thousands(whole, thousands_sep);
-    let symbol = currency_symbol(currency);
-
+fn format_unsigned_money(cents: i64, format: &CurrencyFormat, locale: &str) -> String {
+    let amount = format.format_abs(cents, locale);
     if cents < 0 {
-        format!(
-            "-\u{2060}{}{}{}{:02}",
-            symbol, whole_str, decimal_sep, fractional
-        )
+        format!("-\u{2060}{}", amount)
     } else {
-        format!("{}{}{}{:02}", symbol, whole_str, decimal_sep, fractional)
+        amount
     }
 }
 
 /// Format cents for balance display: shows "-" for negative, no sign for positive/zero.
 /// Suitable for account balances where "+" is not expected.
 pub fn format_money_balance(cents: i64, currency: &str, locale: &str) -> String {
-    format_unsigned_money(cents, currency, locale)
+    format_unsigned_money(cents, &CurrencyFormat::for_currency(currency), locale)
 }
 
 /// Format cents as plain text without plus prefix, useful for prices/fees.
 /// This is "neutral" formatting - no color coding.
 /// Positive values have no sign, negative values show "-".
 pub fn format_money_neutral(cents: i64, currency: &str, locale: &str) -> String {
-    format_unsigned_money(cents, currency, locale)
+    format_money_neutral_with(cents, &CurrencyFormat::for_currency(currency), locale)
+}
+
+/// Like [`format_money_neutral`], with an explicit currency format.
+pub fn format_money_neutral_with(cents: i64, format: &CurrencyFormat, locale: &str) -> String {
+    format_unsigned_money(cents, format, locale)
 }
 
+/// Shown instead of a percentage that is NaN, infinite or negative zero.
+pub const NO_PERCENT: &str = "\u{2014}";
+
 /// Format a percentage value with locale-aware decimal and thousands separators.
-/// Shows sign (+/-) and two decimal places.
+/// Shows sign (+/-) and `decimals` decimal places; values that round to zero
+/// get no sign.
 /// Example: 1234.56 -> "+1,234.56%" (en-US) or "+1.234,56%" (de-DE)
-pub fn format_percent(value: f64, locale: &str) -> String {
+pub fn format_percent(value: f64, decimals: u32, locale: &str) -> String {
+    format_percent_impl(value, decimals, true, locale)
+}
+
+/// Format a share of a total like [`format_percent`], but without the plus
+/// sign. Example: 12.5 -> "12.50%"
+pub fn format_percent_share(value: f64, decimals: u32, locale: &str) -> String {
+    format_percent_impl(value, decimals, false, locale)
+}
+
+fn format_percent_impl(value: f64, decimals: u32, plus_sign: bool, locale: &str) -> String {
+    if !value.is_finite() || (value == 0.0 && value.is_sign_negative()) {
+        return NO_PERCENT.to_string();
+    }
     let (thousands_sep, decimal_sep) = locale_separators(locale);
-    let sign = if value > 0.0 {
-        "+"
-    } else if value < 0.0 {
-        "-"
-    } else {
-        ""
+    // Round once in fixed point, so 7.000000000000001 and 1.999 come out
+    // as 7.00 and 2.00
+    let scale = 10_i64.pow(decimals.min(6));
+    let scaled = (value.abs() * scale as f64).round() as i64;
+    let sign = match (scaled == 0, value < 0.0) {
+        (true, _) => "",
+        (false, true) => "-",
+        (false, false) if plus_sign => "+",
+        (false, false) => "",
     };
-    let abs_value = value.abs();
-    let whole = abs_value.trunc() as i64;
-    let fractional = ((abs_value.fract() * 100.0).round() as i64).abs();
-    let whole_str = format_with_thousands(whole, thousands_sep);
+    let whole_str = format_with_thousands(scaled / scale, thousands_sep);
 
-    format!(
-        "{}{}{}{}%",
-        sign,
-        whole_str,
-        decimal_sep,
-        format_args!("{:02}", fractional)
-    )
+    if decimals == 0 {
+        format!("{}{}%", sign, whole_str)
+    } else {
+        format!(
+            "{}{}{}{:0width$}%",
+            sign,
+            whole_str,
+            decimal_sep,
+            scaled % scale,
+            width = decimals.min(6) as usize
+        )
+    }
 }
 
-fn format_money_impl(cents: i64, currency: &str, locale: &str) -> (String, &'static str) {
+fn format_money_impl(cents: i64, format: &CurrencyFormat, locale: &str) -> (String, &'static str) {
     // Determine color class based on amount
     let color_class = if cents > 0 {
         "text-green-600 dark:text-green-400"
@@ -167,41 +257,21 @@ fn format_money_impl(cents: i64, currency: &str, locale: &str) -> (String, &'sta
         "text-gray-900 dark:text-gray-100"
     };
 
-    // Format the amount
-    let is_negative = cents < 0;
-    let abs_cents = cents.abs();
-    let whole = abs_cents / 100;
-    let fractional = abs_cents % 100;
-
-    // Get separators based on locale
-    let (thousands_sep, decimal_sep) = locale_separators(locale);
-
-    // Format with thousands separator
-    let whole_str = format_with_thousands(whole, thousands_sep);
-
-    // Get currency symbol
-    let symbol = currency_symbol(currency);
-
     // Build final string: sign + symbol + formatted number
-    let formatted = if is_negative {
-        format!(
-            "-\u{2060}{}{}{}{:02}",
-            symbol, whole_str, decimal_sep, fractional
-        )
+    let amount = format.format_abs(cents, locale);
+    let formatted = if cents < 0 {
+        format!("-\u{2060}{}", amount)
     } else if cents > 0 {
-        format!(
-            "+\u{2060}{}{}{}{:02}",
-            symbol, whole_str, decimal_sep, fractional
-        )
+        format!("+\u{2060}{}", amount)
     } else {
-        format!("{}{}{}{:02}", symbol, whole_str, decimal_sep, fractional)
+        amount
     };
 
     (formatted, color_class)
 }
 
 /// Get thousands and decimal separators based on locale.
-fn locale_separators(locale: &str) -> (char, char) {
+pub(crate) fn locale_separators(locale: &str) -> (char, char) {
     // Locales that use period as thousands separator and comma as decimal
     match locale {
         "de-DE" | "de-AT" | "de-CH" | "fr-FR" | "fr-BE" | "fr-CA" | "es-ES" | "es-AR" | "it-IT"
@@ -233,6 +303,37 @@ fn format_with_thousands(n: i64, sep: char) -> String {
     result.iter().rev().collect()
 }
 
+/// Size of a sparkline in pixels.
+const SPARKLINE_WIDTH: f64 = 64.0;
+const SPARKLINE_HEIGHT: f64 = 16.0;
+
+/// Render a series of non-negative amounts as a tiny inline SVG line,
+/// scaled from zero to the largest value and drawn in the current text
+/// color. Empty if there are fewer than two values or all are zero.
+pub fn sparkline_svg(values: &[i64]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    if values.len() < 2 || max <= 0 {
+        return String::new();
+    }
+    let step = SPARKLINE_WIDTH / (values.len() - 1) as f64;
+    // Keep a pixel free at the edges so the stroke isn't clipped
+    let scale = (SPARKLINE_HEIGHT - 2.0) / max as f64;
+    let points: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            let y = SPARKLINE_HEIGHT - 1.0 - v.max(0) as f64 * scale;
+            format!("{:.1},{:.1}", i as f64 * step, y)
+        })
+        .collect();
+    format!(
+        r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round" aria-hidden="true"><polyline points="{}"/></svg>"#,
+        points.join(" "),
+        w = SPARKLINE_WIDTH,
+        h = SPARKLINE_HEIGHT,
+    )
+}
+
 /// Get currency symbol for a currency code.
 pub fn currency_symbol(currency: &str) -> &'static str {
     match currency.to_uppercase().as_str() {
@@ -267,6 +368,15 @@ pub fn currency_symbol(currency: &str) -> &'static str {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_sparkline_scales_to_largest_value() {
+        let svg = sparkline_svg(&[0, 500, 1000]);
+        assert!(svg.contains(r#"points="0.0,15.0 32.0,8.0 64.0,1.0""#));
+        assert!(svg.starts_with(r#"<svg width="64" height="16""#));
+        assert_eq!(sparkline_svg(&[0, 0, 0]), "");
+        assert_eq!(sparkline_svg(&[100]), "");
+    }
+
     #[test]
     fn test_positive_amount() {
         let result = format_money_plain(12345, "USD", "en-US");
@@ -363,42 +473,149 @@ mod tests {
         assert_eq!(result, "\u{20ac}1.234.567,89");
     }
 
+    #[test]
+    fn test_custom_symbol_suffix() {
+        let format = CurrencyFormat {
+            symbol: "\u{20ac}".into(),
+            suffix: true,
+            decimals: 2,
+        };
+        assert_eq!(
+            format_money_neutral_with(123456, &format, "de-DE"),
+            "1.234,56\u{00a0}\u{20ac}"
+        );
+        assert_eq!(
+            format_money_plain_with(-123456, &format, "de-DE"),
+            "-\u{2060}1.234,56\u{00a0}\u{20ac}"
+        );
+    }
+
+    #[test]
+    fn test_custom_symbol_prefix() {
+        let format = CurrencyFormat {
+            symbol: "Fr.\u{00a0}".into(),
+            suffix: false,
+            decimals: 2,
+        };
+        assert_eq!(
+            format_money_plain_with(2500, &format, "en-US"),
+            "+\u{2060}Fr.\u{00a0}25.00"
+        );
+    }
+
+    #[test]
+    fn test_zero_decimal_currency() {
+        let format = CurrencyFormat {
+            decimals: 0,
+            ..CurrencyFormat::for_currency("JPY")
+        };
+        assert_eq!(
+            format_money_neutral_with(123456789, &format, "en-US"),
+            "\u{00a5}1,234,568"
+        );
+        assert_eq!(format_money_neutral_with(49, &format, "en-US"), "\u{00a5}0");
+    }
+
+    #[test]
+    fn test_extra_decimals() {
+        let format = CurrencyFormat {
+            decimals: 4,
+            ..CurrencyFormat::for_currency("USD")
+        };
+        assert_eq!(
+            format_money_neutral_with(12345, &format, "en-US"),
+            "$123.4500"
+        );
+    }
+
     #[test]
     fn test_percent_positive_en() {
-        let result = format_percent(12.34, "en-US");
+        let result = format_percent(12.34, 2, "en-US");
         assert_eq!(result, "+12.34%");
     }
 
     #[test]
     fn test_percent_negative_en() {
-        let result = format_percent(-5.67, "en-US");
+        let result = format_percent(-5.67, 2, "en-US");
         assert_eq!(result, "-5.67%");
     }
 
     #[test]
     fn test_percent_zero() {
-        let result = format_percent(0.0, "en-US");
+        let result = format_percent(0.0, 2, "en-US");
         assert_eq!(result, "0.00%");
     }
 
     #[test]
     fn test_percent_de_locale() {
-        let result = format_percent(12.34, "de-DE");
+        let result = format_percent(12.34, 2, "de-DE");
         assert_eq!(result, "+12,34%");
     }
 
     #[test]
     fn test_percent_thousands_separator_en() {
-        let result = format_percent(1234.56, "en-US");
+        let result = format_percent(1234.56, 2, "en-US");
         assert_eq!(result, "+1,234.56%");
     }
 
     #[test]
     fn test_percent_thousands_separator_de() {
-        let result = format_percent(1234.56, "de-DE");
+        let result = format_percent(1234.56, 2, "de-DE");
         assert_eq!(result, "+1.234,56%");
     }
 
+    #[test]
+    fn test_percent_snapshots() {
+        let values = [
+            7.000000000000001,
+            1.999,
+            -0.004,
+            -12.3456,
+            1234.5678,
+            0.0,
+            -0.0,
+            f64::NAN,
+            f64::INFINITY,
+        ];
+        let render = |decimals, locale| -> Vec<String> {
+            values
+                .iter()
+                .map(|v| format_percent(*v, decimals, locale))
+                .collect()
+        };
+        assert_eq!(
+            render(2, "en-US"),
+            [
+                "+7.00%",
+                "+2.00%",
+                "0.00%",
+                "-12.35%",
+                "+1,234.57%",
+                "0.00%",
+                "\u{2014}",
+                "\u{2014}",
+                "\u{2014}"
+            ]
+        );
+        assert_eq!(
+            render(1, "de-DE"),
+            [
+                "+7,0%",
+                "+2,0%",
+                "0,0%",
+                "-12,3%",
+                "+1.234,6%",
+                "0,0%",
+                "\u{2014}",
+                "\u{2014}",
+                "\u{2014}"
+            ]
+        );
+        assert_eq!(render(0, "en-US")[4], "+1,235%");
+        assert_eq!(format_percent_share(12.5, 2, "de-DE"), "12,50%");
+        assert_eq!(format_percent_share(-3.0, 1, "en-US"), "-3.0%");
+    }
+
     #[test]
     fn balance_and_neutral_produce_same_text() {
         let cases: &[(i64, &str, &str)] = &[
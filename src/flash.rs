@@ -0,0 +1,217 @@
+//! One-shot flash messages for POST-redirect flows.
+//!
+//! Handlers queue a message with [`flash_success`] or [`flash_error`]. The
+//! message is kept server-side under a random token stored in the `flash`
+//! cookie, and `base.html` shows it as a toast on the next full page load.
+//! HTMX requests that stay on the page use [`toast_trigger`] instead, which
+//! shows the toast through an `HX-Trigger` response header.
+//!
+//! [`flash_middleware`] makes the current request's token available to the
+//! helpers; without it they do nothing.
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderName, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower_cookies::{Cookie, Cookies};
+use uuid::Uuid;
+
+use crate::state::AppState;
+
+/// Cookie name for the flash token.
+pub const FLASH_COOKIE: &str = "flash";
+
+/// Messages not shown within this time are dropped.
+const MESSAGE_TTL: Duration = Duration::from_secs(600);
+
+/// Client-side event name dispatched by the `HX-Trigger` header.
+const TOAST_EVENT: &str = "showToast";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashLevel {
+    Success,
+    Error,
+}
+
+impl FlashLevel {
+    /// Toast type understood by `showToast` in the frontend.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashMessage {
+    #[serde(rename = "type")]
+    pub level: FlashLevel,
+    pub message: String,
+}
+
+/// Pending messages per flash token.
+pub struct FlashStore {
+    /// Maps token → (messages, time of the last message).
+    messages: Mutex<HashMap<String, (Vec<FlashMessage>, Instant)>>,
+}
+
+impl Default for FlashStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlashStore {
+    pub fn new() -> Self {
+        Self {
+            messages: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Queue a message for `token`, dropping expired ones.
+    pub fn push(&self, token: &str, message: FlashMessage) {
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        messages.retain(|_, (_, added)| added.elapsed() < MESSAGE_TTL);
+        let entry = messages
+            .entry(token.to_string())
+            .or_insert_with(|| (Vec::new(), Instant::now()));
+        entry.0.push(message);
+        entry.1 = Instant::now();
+    }
+
+    /// Remove and return all messages for `token`.
+    pub fn take(&self, token: &str) -> Vec<FlashMessage> {
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        messages
+            .remove(token)
+            .map(|(pending, _)| pending)
+            .unwrap_or_default()
+    }
+
+    pub fn has_pending(&self, token: &str) -> bool {
+        let messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        messages.contains_key(token)
+    }
+}
+
+/// Token and store for the request being handled.
+#[derive(Clone)]
+struct FlashContext {
+    store: Arc<FlashStore>,
+    token: String,
+}
+
+tokio::task_local! {
+    static CONTEXT: FlashContext;
+}
+
+fn push(level: FlashLevel, message: impl Into<String>) {
+    let message = FlashMessage {
+        level,
+        message: message.into(),
+    };
+    let _ = CONTEXT.try_with(|ctx| ctx.store.push(&ctx.token, message));
+}
+
+/// Show a success message on the next page load.
+pub fn flash_success(message: impl Into<String>) {
+    push(FlashLevel::Success, message);
+}
+
+/// Show an error message on the next page load.
+pub fn flash_error(message: impl Into<String>) {
+    push(FlashLevel::Error, message);
+}
+
+/// Take the messages pending for the current request. Called from `base.html`,
+/// so messages are only consumed by responses that render a full page.
+pub fn take_messages() -> Vec<FlashMessage> {
+    CONTEXT
+        .try_with(|ctx| ctx.store.take(&ctx.token))
+        .unwrap_or_default()
+}
+
+/// `HX-Trigger` header that shows a toast after an HTMX request.
+pub fn toast_trigger(level: FlashLevel, message: &str) -> [(HeaderName, String); 1] {
+    let toast = FlashMessage {
+        level,
+        message: message.to_string(),
+    };
+    let payload = serde_json::json!({ TOAST_EVENT: toast });
+    [(HeaderName::from_static("hx-trigger"), payload.to_string())]
+}
+
+/// Middleware that scopes the flash token for the request and keeps the
+/// cookie in sync with the pending messages.
+pub async fn flash_middleware(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let existing = cookies.get(FLASH_COOKIE).map(|c| c.value().to_string());
+    let token = existing
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let context = FlashContext {
+        store: state.flash.clone(),
+        token: token.clone(),
+    };
+
+    let response = CONTEXT.scope(context, next.run(request)).await;
+
+    let pending = state.flash.has_pending(&token);
+    if pending && existing.is_none() {
+        let cookie = Cookie::build((FLASH_COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .secure(state.config.secure_cookies)
+            .same_site(tower_cookies::cookie::SameSite::Lax)
+            .build();
+        cookies.add(cookie);
+    } else if !pending && existing.is_some() {
+        cookies.remove(Cookie::build((FLASH_COOKIE, "")).path("/").build());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_consumes_messages() {
+        let store = FlashStore::new();
+        store.push(
+            "a",
+            FlashMessage {
+                level: FlashLevel::Success,
+                message: "Saved".into(),
+            },
+        );
+        assert!(store.has_pending("a"));
+        assert!(!store.has_pending("b"));
+
+        let taken = store.take("a");
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].message, "Saved");
+        assert!(store.take("a").is_empty());
+    }
+
+    #[test]
+    fn test_toast_trigger_payload() {
+        let [(name, value)] = toast_trigger(FlashLevel::Error, "Failed \"x\"");
+        assert_eq!(name.as_str(), "hx-trigger");
+        assert_eq!(
+            value,
+            r#"{"showToast":{"message":"Failed \"x\"","type":"error"}}"#
+        );
+    }
+}
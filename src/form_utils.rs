@@ -15,3 +15,84 @@ where
         Some(v) => v.parse::<i64>().map(Some).map_err(serde::de::Error::custom),
     }
 }
+
+/// Like [`deserialize_optional_i64`], but tells an omitted field (`None`,
+/// via `#[serde(default)]`) apart from an empty one (`Some(None)`).
+pub fn deserialize_submitted_i64<'de, D>(deserializer: D) -> Result<Option<Option<i64>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_optional_i64(deserializer).map(Some)
+}
+
+/// Collect integer ids submitted under `key` from raw form pairs.
+///
+/// Checkbox groups and multi-selects send one field per selected value, which
+/// `serde_urlencoded` cannot deserialize into a `Vec`. Accepts repeated fields
+/// as well as comma-separated lists; unparseable values are ignored.
+pub fn collect_ids(pairs: &[(String, String)], key: &str) -> Vec<i64> {
+    let mut ids: Vec<i64> = pairs
+        .iter()
+        .filter(|(k, _)| k == key)
+        .flat_map(|(_, v)| v.split(','))
+        .filter_map(|s| s.trim().parse().ok())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// Raw fields of a submitted form, kept so a form that failed validation can
+/// be rendered again with the values the user entered.
+#[derive(Debug, Clone, Default)]
+pub struct SubmittedForm {
+    pairs: Vec<(String, String)>,
+}
+
+impl SubmittedForm {
+    pub fn new(pairs: Vec<(String, String)>) -> Self {
+        Self { pairs }
+    }
+
+    /// Deserialize the fields into `T`, leaving out the form token and the
+    /// `lists` (repeated fields, see [`collect_ids`]).
+    pub fn parse<T: serde::de::DeserializeOwned>(&self, lists: &[&str]) -> Result<T, String> {
+        let fields: Vec<&(String, String)> = self
+            .pairs
+            .iter()
+            .filter(|(k, _)| {
+                k != crate::idempotency::FORM_TOKEN_FIELD && !lists.contains(&k.as_str())
+            })
+            .collect();
+        let encoded = serde_urlencoded::to_string(fields).map_err(|e| e.to_string())?;
+        serde_urlencoded::from_str(&encoded).map_err(|e| format!("Invalid form: {e}"))
+    }
+
+    pub fn pairs(&self) -> &[(String, String)] {
+        &self.pairs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// The first value submitted for `key`, or an empty string.
+    pub fn get(&self, key: &str) -> &str {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map_or("", |(_, v)| v.as_str())
+    }
+
+    /// Whether `value` was submitted for `key`, e.g. a selected option or a
+    /// checked checkbox.
+    pub fn has(&self, key: &str, value: impl std::fmt::Display) -> bool {
+        let value = value.to_string();
+        self.pairs.iter().any(|(k, v)| k == key && *v == value)
+    }
+
+    /// The double-submit token, if the form carried one.
+    pub fn token(&self) -> Option<&str> {
+        Some(self.get(crate::idempotency::FORM_TOKEN_FIELD)).filter(|t| !t.is_empty())
+    }
+}
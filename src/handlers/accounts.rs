@@ -1,16 +1,23 @@
 use askama::Template;
-use axum::extract::{Path, State};
-use axum::http::header;
-use axum::response::{Html, IntoResponse, Json, Redirect};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Json, Redirect, Response};
 use axum::Form;
 use serde::{Deserialize, Serialize};
 
-use crate::db::queries::accounts;
-use crate::error::{AppError, AppResult, RenderHtml};
+use crate::date_utils;
+use crate::db::queries::{accounts, settings};
+use crate::error::{render_form, AppError, AppResult, RenderHtml};
+use crate::flash::{self, FlashLevel};
+use crate::form_utils::SubmittedForm;
 use crate::handlers::import_preview::{
     ImportPreviewForm, ImportPreviewItem, ImportPreviewStatus, ImportPreviewTemplate,
 };
+use crate::models::account::InterestCompounding;
+use crate::models::rule::normalize_iban;
 use crate::models::{Account, AccountType, NewAccount, Settings};
+use crate::services::card_cycle::{self, CycleSummary};
+use crate::services::interest::{self, InterestBasis, InterestProjection};
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Template)]
@@ -22,6 +29,7 @@ pub struct AccountsTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub accounts: Vec<Account>,
     pub delete_count: i64,
 }
@@ -35,7 +43,16 @@ pub struct AccountFormTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub account: Option<Account>,
+    pub compoundings: &'static [InterestCompounding],
+    /// Cash accounts a securities account can book mirrored transactions to.
+    pub cash_accounts: Vec<Account>,
+    /// Double-submit token; only used by the new account form.
+    pub form_token: String,
+    /// Values of a new account submission that failed validation.
+    pub values: SubmittedForm,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,6 +62,132 @@ pub struct AccountFormData {
     /// HTML checkbox: "on" when checked, absent (defaults to "") when unchecked.
     #[serde(default)]
     pub active: String,
+    /// Annual interest rate in percent; empty if the account pays no interest.
+    #[serde(default)]
+    pub interest_rate: String,
+    #[serde(default)]
+    pub interest_compounding: String,
+    /// HTML checkbox, like `active`.
+    #[serde(default)]
+    pub derive_cash_from_trading: String,
+    /// Credit cards only: statement closing day; empty if unknown.
+    #[serde(default)]
+    pub statement_day: String,
+    /// Credit cards only: payment due day; empty if unknown.
+    #[serde(default)]
+    pub due_day: String,
+    /// Securities accounts only: cash account for mirrored transactions.
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub cash_account_id: Option<i64>,
+    /// Empty if unknown; spaces and case do not matter.
+    #[serde(default)]
+    pub iban: String,
+}
+
+/// Normalize an optional IBAN, checking its shape but not its check digits.
+fn parse_iban(value: &str) -> Result<Option<String>, String> {
+    let iban = normalize_iban(value);
+    if iban.is_empty() {
+        return Ok(None);
+    }
+    let valid = (15..=34).contains(&iban.len())
+        && iban.chars().all(|c| c.is_ascii_alphanumeric())
+        && iban[..2].chars().all(|c| c.is_ascii_alphabetic())
+        && iban[2..4].chars().all(|c| c.is_ascii_digit());
+    if valid {
+        Ok(Some(iban))
+    } else {
+        Err(format!("Invalid IBAN: {}", value.trim()))
+    }
+}
+
+/// Parse an optional day of the month (1-31) from a form field.
+fn parse_day_of_month(value: &str, field: &str) -> Result<Option<u32>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .ok()
+        .filter(|day| (1..=31).contains(day))
+        .map(Some)
+        .ok_or_else(|| format!("{} must be a day of the month (1-31)", field))
+}
+
+/// Statement and due day of a credit card: both or neither must be set, and
+/// they are dropped for other account types.
+fn card_cycle_days(
+    account_type: AccountType,
+    statement_day: Option<u32>,
+    due_day: Option<u32>,
+) -> Result<(Option<u32>, Option<u32>), String> {
+    if account_type != AccountType::CreditCard {
+        return Ok((None, None));
+    }
+    if statement_day.is_some() != due_day.is_some() {
+        return Err("Set both the statement day and the due day, or neither".into());
+    }
+    Ok((statement_day, due_day))
+}
+
+impl AccountFormData {
+    fn to_new_account(&self) -> AppResult<NewAccount> {
+        let account_type = AccountType::parse(&self.account_type)
+            .ok_or_else(|| AppError::Validation("Invalid account type".into()))?;
+
+        let rate = self.interest_rate.trim();
+        let interest_rate_bps = if rate.is_empty() {
+            None
+        } else {
+            let percent: f64 = rate
+                .parse()
+                .ok()
+                .filter(|p: &f64| p.is_finite() && (0.0..=100.0).contains(p))
+                .ok_or_else(|| AppError::Validation("Invalid interest rate".into()))?;
+            Some((percent * 100.0).round() as i64)
+        };
+
+        let (statement_day, due_day) = card_cycle_days(
+            account_type,
+            parse_day_of_month(&self.statement_day, "Statement day")
+                .map_err(AppError::Validation)?,
+            parse_day_of_month(&self.due_day, "Due day").map_err(AppError::Validation)?,
+        )
+        .map_err(AppError::Validation)?;
+
+        let interest_compounding = match self.interest_compounding.as_str() {
+            "" => InterestCompounding::default(),
+            s => InterestCompounding::parse(s)
+                .ok_or_else(|| AppError::Validation("Invalid interest compounding".into()))?,
+        };
+
+        Ok(NewAccount {
+            name: self.name.clone(),
+            account_type,
+            active: self.active == "on",
+            interest_rate_bps,
+            interest_compounding,
+            derive_cash_from_trading: account_type == AccountType::Securities
+                && self.derive_cash_from_trading == "on",
+            statement_day,
+            due_day,
+            cash_account_id: self
+                .cash_account_id
+                .filter(|_| account_type == AccountType::Securities),
+            iban: parse_iban(&self.iban).map_err(AppError::Validation)?,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InterestProjectionParams {
+    pub months: Option<u32>,
+    /// `current` (default) or `average` daily balance over the last period.
+    pub basis: Option<String>,
 }
 
 pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
@@ -54,6 +197,7 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let account_list = state.cached_accounts()?;
 
@@ -64,6 +208,7 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         delete_count: account_list.len() as i64,
         accounts: account_list,
     };
@@ -71,13 +216,33 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
     template.render_html()
 }
 
-pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>> {
+fn cash_accounts(state: &AppState) -> AppResult<Vec<Account>> {
+    Ok(state
+        .cached_accounts()?
+        .iter()
+        .filter(|a| a.account_type == AccountType::Cash)
+        .cloned()
+        .collect())
+}
+
+pub async fn new_form(State(state): State<AppState>) -> AppResult<Response> {
+    render_new_form(&state, SubmittedForm::default(), None)
+}
+
+/// Render the new account form, filled with `values` and showing `error`
+/// after a failed submission.
+fn render_new_form(
+    state: &AppState,
+    values: SubmittedForm,
+    error: Option<String>,
+) -> AppResult<Response> {
     let PageBase {
         settings,
         icons,
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let template = AccountFormTemplate {
@@ -87,10 +252,19 @@ pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>>
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         account: None,
+        compoundings: InterestCompounding::all(),
+        cash_accounts: cash_accounts(state)?,
+        form_token: values
+            .token()
+            .map_or_else(|| state.submitted_forms.new_token(), str::to_string),
+        values,
+        error,
     };
 
-    template.render_html()
+    let has_error = template.error.is_some();
+    render_form(template, has_error)
 }
 
 pub async fn edit_form(
@@ -104,6 +278,7 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let account = accounts::get_account(&conn, id)?
@@ -116,7 +291,13 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         account: Some(account),
+        compoundings: InterestCompounding::all(),
+        cash_accounts: cash_accounts(&state)?,
+        form_token: String::new(),
+        values: SubmittedForm::default(),
+        error: None,
     };
 
     template.render_html()
@@ -124,22 +305,33 @@ pub async fn edit_form(
 
 pub async fn create(
     State(state): State<AppState>,
-    Form(form): Form<AccountFormData>,
-) -> AppResult<Redirect> {
-    let conn = state.db.get()?;
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    let submitted = SubmittedForm::new(pairs);
+    let created = state
+        .submitted_forms
+        .create_once(submitted.token(), "/accounts", || {
+            create_from_form(&state, &submitted)
+        });
+    match created {
+        Ok(Some(repeated)) => Ok(repeated.into_response()),
+        Ok(None) => Ok(Redirect::to("/accounts").into_response()),
+        Err(AppError::Validation(message)) => render_new_form(&state, submitted, Some(message)),
+        Err(e) => Err(e),
+    }
+}
 
-    let account_type = AccountType::parse(&form.account_type)
-        .ok_or_else(|| AppError::Validation("Invalid account type".into()))?;
+/// Create an account from the new account form, returning its URL.
+fn create_from_form(state: &AppState, submitted: &SubmittedForm) -> AppResult<String> {
+    let form: AccountFormData = submitted.parse(&[]).map_err(AppError::Validation)?;
+    let conn = state.db.get()?;
 
-    let new_account = NewAccount {
-        name: form.name,
-        account_type,
-        active: form.active == "on",
-    };
+    let new_account = form.to_new_account()?;
 
-    accounts::create_account(&conn, &new_account)?;
+    let id = accounts::create_account(&conn, &new_account)?;
 
-    Ok(Redirect::to("/accounts"))
+    flash::flash_success(format!("Account \"{}\" created", new_account.name));
+    Ok(format!("/accounts/{id}/edit"))
 }
 
 pub async fn update(
@@ -149,32 +341,83 @@ pub async fn update(
 ) -> AppResult<Redirect> {
     let conn = state.db.get()?;
 
-    let account_type = AccountType::parse(&form.account_type)
-        .ok_or_else(|| AppError::Validation("Invalid account type".into()))?;
-
-    let updated_account = NewAccount {
-        name: form.name,
-        account_type,
-        active: form.active == "on",
-    };
+    let updated_account = form.to_new_account()?;
 
     accounts::update_account(&conn, id, &updated_account)?;
 
+    flash::flash_success(format!("Account \"{}\" saved", updated_account.name));
     Ok(Redirect::to("/accounts"))
 }
 
-pub async fn delete(State(state): State<AppState>, Path(id): Path<i64>) -> AppResult<Html<String>> {
+/// Projected interest income for an account over the next `months` months,
+/// with the interest actually booked in each month.
+pub async fn interest_projection(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<InterestProjectionParams>,
+) -> AppResult<Json<InterestProjection>> {
     let conn = state.db.get()?;
+    let account = accounts::get_account(&conn, id)?
+        .ok_or_else(|| AppError::NotFound("Account not found".into()))?;
+    let rate_bps = account
+        .interest_rate_bps
+        .ok_or_else(|| AppError::Validation("Account has no interest rate".into()))?;
+
+    let months = params.months.unwrap_or(12);
+    if !(1..=120).contains(&months) {
+        return Err(AppError::Validation(
+            "Months must be between 1 and 120".into(),
+        ));
+    }
+    let basis = match params.basis.as_deref() {
+        None | Some("") => InterestBasis::Current,
+        Some(s) => InterestBasis::parse(s)
+            .ok_or_else(|| AppError::Validation("Invalid balance basis".into()))?,
+    };
 
-    accounts::delete_account(&conn, id)?;
-
-    Ok(Html(String::new()))
+    let today = date_utils::today_in(&state.load_settings()?);
+    let projection = interest::project_account(&conn, &account, rate_bps, basis, months, today)?;
+    Ok(Json(projection))
 }
 
-pub async fn delete_all(State(state): State<AppState>) -> AppResult<Html<String>> {
+/// Spending and payments of a credit card's current and previous statement
+/// cycle, and the amount due for the last statement.
+pub async fn cycle_summary(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Json<CycleSummary>> {
     let conn = state.db.get()?;
+    let account = accounts::get_account(&conn, id)?
+        .ok_or_else(|| AppError::NotFound("Account not found".into()))?;
+    let today = date_utils::today_in(&state.load_settings()?);
+    let summary = card_cycle::summarize(&conn, &account, today)?.ok_or_else(|| {
+        AppError::Validation("Account is not a credit card with statement and due days".into())
+    })?;
+    Ok(Json(summary))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    state.with_tx(|tx| {
+        accounts::delete_account(tx, id)?;
+        settings::clear_missing_references(tx)?;
+        Ok(())
+    })?;
+
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, "Account deleted"),
+        Html(String::new()),
+    ))
+}
 
-    accounts::delete_all_accounts(&conn)?;
+pub async fn delete_all(State(state): State<AppState>) -> AppResult<Html<String>> {
+    state.with_tx(|tx| {
+        accounts::delete_all_accounts(tx)?;
+        settings::clear_missing_references(tx)?;
+        Ok(())
+    })?;
 
     Ok(Html(String::new()))
 }
@@ -183,6 +426,30 @@ pub async fn delete_all(State(state): State<AppState>) -> AppResult<Html<String>
 struct AccountExport {
     name: String,
     account_type: AccountType,
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interest_rate_bps: Option<i64>,
+    interest_compounding: InterestCompounding,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    derive_cash_from_trading: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    statement_day: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due_day: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iban: Option<String>,
+    /// Name of the linked cash account, as ids differ between databases
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cash_account: Option<String>,
+}
+
+/// Name of the cash account `account` is linked to.
+fn cash_account_name<'a>(accounts: &'a [Account], account: &Account) -> Option<&'a str> {
+    let id = account.cash_account_id?;
+    accounts
+        .iter()
+        .find(|a| a.id == id)
+        .map(|a| a.name.as_str())
 }
 
 pub async fn export(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
@@ -193,6 +460,14 @@ pub async fn export(State(state): State<AppState>) -> AppResult<impl IntoRespons
         .map(|a| AccountExport {
             name: a.name.clone(),
             account_type: a.account_type,
+            active: a.active,
+            interest_rate_bps: a.interest_rate_bps,
+            interest_compounding: a.interest_compounding,
+            derive_cash_from_trading: a.derive_cash_from_trading,
+            statement_day: a.statement_day,
+            due_day: a.due_day,
+            iban: a.iban.clone(),
+            cash_account: cash_account_name(&account_list, a).map(str::to_string),
         })
         .collect();
 
@@ -211,50 +486,307 @@ pub async fn export(State(state): State<AppState>) -> AppResult<impl IntoRespons
     ))
 }
 
+/// One record of an accounts export. Enum fields are kept as strings so
+/// unknown values are reported per record instead of failing the file.
 #[derive(Deserialize)]
 struct AccountImport {
     name: String,
-    account_type: AccountType,
+    account_type: String,
+    #[serde(default = "default_active")]
+    active: bool,
+    #[serde(default)]
+    interest_rate_bps: Option<i64>,
+    #[serde(default)]
+    interest_compounding: Option<String>,
+    #[serde(default)]
+    derive_cash_from_trading: bool,
+    #[serde(default)]
+    statement_day: Option<u32>,
+    #[serde(default)]
+    due_day: Option<u32>,
+    #[serde(default)]
+    iban: Option<String>,
+    #[serde(default)]
+    cash_account: Option<String>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// What importing a valid record does to the accounts table.
+enum AccountImportAction {
+    Create(NewAccount),
+    Update(i64, NewAccount),
+    Unchanged,
+}
+
+/// Validation outcome of one record, in file order.
+struct AccountImportRecord {
+    name: String,
+    account_type: String,
+    /// Name of the cash account to link, resolved to its id when written
+    cash_account: Option<String>,
+    result: Result<AccountImportAction, String>,
+}
+
+/// Validate every record and match it against existing accounts by name.
+fn plan_account_import(
+    values: Vec<serde_json::Value>,
+    existing: &[Account],
+) -> Vec<AccountImportRecord> {
+    let mut seen_names = std::collections::HashSet::new();
+
+    // Accounts a securities account can link to: the cash accounts of the
+    // file, and existing ones the file leaves cash accounts
+    let mut cash_names: std::collections::HashSet<String> = existing
+        .iter()
+        .filter(|a| a.account_type == AccountType::Cash)
+        .map(|a| a.name.clone())
+        .collect();
+    for value in &values {
+        let (Some(name), Some(account_type)) =
+            (value["name"].as_str(), value["account_type"].as_str())
+        else {
+            continue;
+        };
+        if AccountType::parse(account_type) == Some(AccountType::Cash) {
+            cash_names.insert(name.trim().to_string());
+        } else {
+            cash_names.remove(name.trim());
+        }
+    }
+
+    values
+        .into_iter()
+        .map(|value| {
+            let item: AccountImport = match serde_json::from_value(value) {
+                Ok(item) => item,
+                Err(e) => {
+                    return AccountImportRecord {
+                        name: String::new(),
+                        account_type: String::new(),
+                        cash_account: None,
+                        result: Err(format!("invalid record: {}", e)),
+                    }
+                }
+            };
+            let name = item.name.trim().to_string();
+            let (cash_account, result) =
+                match validate_account_import(&item, &name, &mut seen_names, &cash_names) {
+                    Ok((account, cash_account)) => {
+                        let action = match existing.iter().find(|a| a.name == name) {
+                            None => AccountImportAction::Create(account),
+                            Some(current)
+                                if account_matches(current, &account)
+                                    && cash_account_name(existing, current)
+                                        == cash_account.as_deref() =>
+                            {
+                                AccountImportAction::Unchanged
+                            }
+                            Some(current) => AccountImportAction::Update(current.id, account),
+                        };
+                        (cash_account, Ok(action))
+                    }
+                    Err(e) => (None, Err(e)),
+                };
+            AccountImportRecord {
+                name,
+                account_type: item.account_type,
+                cash_account,
+                result,
+            }
+        })
+        .collect()
 }
 
+/// Validate one record. Returns the account, its cash account link still
+/// unset, and the name of the cash account to link it to.
+fn validate_account_import(
+    item: &AccountImport,
+    name: &str,
+    seen_names: &mut std::collections::HashSet<String>,
+    cash_names: &std::collections::HashSet<String>,
+) -> Result<(NewAccount, Option<String>), String> {
+    if name.is_empty() {
+        return Err("name is empty".into());
+    }
+    if !seen_names.insert(name.to_string()) {
+        return Err("duplicate name in file".into());
+    }
+    let account_type = AccountType::parse(&item.account_type)
+        .ok_or_else(|| format!("unknown account type \"{}\"", item.account_type))?;
+    if item.interest_rate_bps.is_some_and(|bps| bps < 0) {
+        return Err("interest rate must not be negative".into());
+    }
+    let interest_compounding = match &item.interest_compounding {
+        None => InterestCompounding::default(),
+        Some(s) => InterestCompounding::parse(s)
+            .ok_or_else(|| format!("unknown interest compounding \"{}\"", s))?,
+    };
+    if [item.statement_day, item.due_day]
+        .iter()
+        .flatten()
+        .any(|day| !(1..=31).contains(day))
+    {
+        return Err("statement and due day must be days of the month (1-31)".into());
+    }
+    let (statement_day, due_day) = card_cycle_days(account_type, item.statement_day, item.due_day)?;
+    let iban = parse_iban(item.iban.as_deref().unwrap_or_default())?;
+    let cash_account = match item.cash_account.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(_) if account_type != AccountType::Securities => {
+            return Err("only securities accounts have a cash account".into())
+        }
+        Some(cash) if !cash_names.contains(cash) => {
+            return Err(format!("cash account \"{}\" not found", cash))
+        }
+        Some(cash) => Some(cash.to_string()),
+    };
+
+    let account = NewAccount {
+        name: name.to_string(),
+        account_type,
+        active: item.active,
+        interest_rate_bps: item.interest_rate_bps,
+        interest_compounding,
+        derive_cash_from_trading: account_type == AccountType::Securities
+            && item.derive_cash_from_trading,
+        statement_day,
+        due_day,
+        // Linked by name when the import is written
+        cash_account_id: None,
+        iban,
+    };
+    Ok((account, cash_account))
+}
+
+fn account_matches(current: &Account, account: &NewAccount) -> bool {
+    current.account_type == account.account_type
+        && current.active == account.active
+        && current.interest_rate_bps == account.interest_rate_bps
+        && current.interest_compounding == account.interest_compounding
+        && current.derive_cash_from_trading == account.derive_cash_from_trading
+        && current.statement_day == account.statement_day
+        && current.due_day == account.due_day
+        && current.iban == account.iban
+}
+
+fn parse_import_records(value: serde_json::Value) -> AppResult<Vec<serde_json::Value>> {
+    serde_json::from_value(value)
+        .map_err(|e| AppError::Validation(format!("Invalid JSON format: {}", e)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountImportParams {
+    /// `1` imports the valid records even if others fail validation.
+    #[serde(default)]
+    pub partial: Option<String>,
+}
+
+impl AccountImportParams {
+    fn is_partial(&self) -> bool {
+        matches!(self.partial.as_deref(), Some("1" | "true"))
+    }
+}
+
+/// Import accounts from an export file. Every record is validated first;
+/// if any fails, nothing is written and the per-record errors are returned
+/// with status 422, unless `partial=1` is passed. Accounts are matched by
+/// name, so re-importing an export changes nothing.
 pub async fn import(
     State(state): State<AppState>,
+    Query(params): Query<AccountImportParams>,
     Json(value): Json<serde_json::Value>,
-) -> AppResult<Json<serde_json::Value>> {
-    let data: Vec<AccountImport> = serde_json::from_value(value)
-        .map_err(|e| AppError::Validation(format!("Invalid JSON format: {}", e)))?;
+) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    let existing = state.cached_accounts()?;
+    let records = plan_account_import(parse_import_records(value)?, &existing);
 
-    let conn = state.db.get()?;
+    let errors: Vec<serde_json::Value> = records
+        .iter()
+        .enumerate()
+        .filter_map(|(index, record)| {
+            record.result.as_ref().err().map(
+                |error| serde_json::json!({ "index": index, "name": record.name, "error": error }),
+            )
+        })
+        .collect();
 
-    let existing = state.cached_accounts()?;
-    let existing_names: std::collections::HashSet<_> =
-        existing.iter().map(|a| a.name.clone()).collect();
-
-    let mut created = 0;
-    for item in data {
-        if !existing_names.contains(&item.name) {
-            let new_account = NewAccount {
-                name: item.name,
-                account_type: item.account_type,
-                active: true,
-            };
-            accounts::create_account(&conn, &new_account)?;
-            created += 1;
+    if !errors.is_empty() && !params.is_partial() {
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "imported": 0,
+                "updated": 0,
+                "unchanged": 0,
+                "errors": errors,
+                "message": format!("{} of {} accounts are invalid; nothing was imported", errors.len(), records.len())
+            })),
+        ));
+    }
+
+    let (created, updated, unchanged) = state.with_tx(|tx| {
+        let (mut created, mut updated, mut unchanged) = (0, 0, 0);
+        // Accounts without a link go first, so the cash accounts created by
+        // this import have ids by the time securities accounts link to them
+        let mut ids: std::collections::HashMap<String, i64> =
+            existing.iter().map(|a| (a.name.clone(), a.id)).collect();
+        let (linked, unlinked): (Vec<_>, Vec<_>) =
+            records.iter().partition(|r| r.cash_account.is_some());
+        for record in unlinked.into_iter().chain(linked) {
+            let cash_account_id = record
+                .cash_account
+                .as_ref()
+                .and_then(|name| ids.get(name).copied());
+            match &record.result {
+                Ok(AccountImportAction::Create(account)) => {
+                    let account = NewAccount {
+                        cash_account_id,
+                        ..account.clone()
+                    };
+                    let id = accounts::create_account(tx, &account)?;
+                    ids.insert(account.name, id);
+                    created += 1;
+                }
+                Ok(AccountImportAction::Update(id, account)) => {
+                    let account = NewAccount {
+                        cash_account_id,
+                        ..account.clone()
+                    };
+                    accounts::update_account(tx, *id, &account)?;
+                    updated += 1;
+                }
+                Ok(AccountImportAction::Unchanged) => unchanged += 1,
+                Err(_) => {}
+            }
         }
+        Ok((created, updated, unchanged))
+    })?;
+
+    let mut message = format!("Imported {} accounts, updated {}", created, updated);
+    if !errors.is_empty() {
+        message.push_str(&format!(" ({} skipped as invalid)", errors.len()));
     }
 
-    Ok(Json(serde_json::json!({
-        "imported": created,
-        "message": format!("Successfully imported {} accounts", created)
-    })))
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "imported": created,
+            "updated": updated,
+            "unchanged": unchanged,
+            "errors": errors,
+            "message": message
+        })),
+    ))
 }
 
 pub async fn import_preview(
     State(state): State<AppState>,
     Form(form): Form<ImportPreviewForm>,
 ) -> AppResult<Html<String>> {
-    let data: Vec<AccountImport> = serde_json::from_str(&form.data)
+    let value: serde_json::Value = serde_json::from_str(&form.data)
         .map_err(|e| AppError::Validation(format!("Invalid JSON format: {}", e)))?;
+    let records = plan_account_import(parse_import_records(value)?, &state.cached_accounts()?);
 
     let PageBase {
         settings,
@@ -262,34 +794,37 @@ pub async fn import_preview(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
-    let existing = state.cached_accounts()?;
-    let existing_names: std::collections::HashSet<String> =
-        existing.iter().map(|a| a.name.clone()).collect();
-
     let mut items = Vec::new();
     let mut ok_count = 0;
     let mut skip_count = 0;
 
-    for item in &data {
-        let cells = vec![item.name.clone(), item.account_type.as_str().to_string()];
-
-        if existing_names.contains(&item.name) {
-            skip_count += 1;
-            items.push(ImportPreviewItem {
-                status: ImportPreviewStatus::Skipped,
-                reason: "already exists".to_string(),
-                cells,
-            });
-        } else {
+    for record in records {
+        let (status, action, reason) = match record.result {
+            Ok(AccountImportAction::Create(_)) => (ImportPreviewStatus::Ok, "New", String::new()),
+            Ok(AccountImportAction::Update(..)) => {
+                (ImportPreviewStatus::Ok, "Update", String::new())
+            }
+            Ok(AccountImportAction::Unchanged) => (
+                ImportPreviewStatus::Skipped,
+                "Skip",
+                "already exists".to_string(),
+            ),
+            Err(e) => (ImportPreviewStatus::Skipped, "Skip", e),
+        };
+        let cells = vec![record.name, record.account_type, action.to_string()];
+        if status == ImportPreviewStatus::Ok {
             ok_count += 1;
-            items.push(ImportPreviewItem {
-                status: ImportPreviewStatus::Ok,
-                reason: String::new(),
-                cells,
-            });
+        } else {
+            skip_count += 1;
         }
+        items.push(ImportPreviewItem {
+            status,
+            reason,
+            cells,
+        });
     }
 
     let template = ImportPreviewTemplate {
@@ -299,10 +834,12 @@ pub async fn import_preview(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         resource_name: "Accounts".to_string(),
         back_url: "/accounts".to_string(),
-        import_url: "/accounts/import".to_string(),
-        columns: vec!["Name".to_string(), "Type".to_string()],
+        // Invalid records are listed as skipped above, so import the rest
+        import_url: "/accounts/import?partial=1".to_string(),
+        columns: vec!["Name".to_string(), "Type".to_string(), "Action".to_string()],
         items,
         ok_count,
         skip_count,
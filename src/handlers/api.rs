@@ -1,49 +1,91 @@
 use axum::extract::{Path, Query, State};
 use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Json};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 
-use crate::db::queries::transactions;
-use crate::error::AppResult;
+use crate::cache::DataDomain;
+use crate::date_utils::{self, DateRange};
+use crate::db::queries::categories::transfers_excluded_ids;
+use crate::db::queries::transaction_sums;
+use crate::error::{AppError, AppResult};
 use crate::filters::Icons;
-use crate::models::DEFAULT_COLOR;
+use crate::models::Settings;
+use crate::palette;
+use crate::services::analytics;
 use crate::state::AppState;
 
-/// Collect a category and all its descendants into a set of IDs.
-fn collect_subtree_ids(
-    root_id: i64,
-    children_map: &std::collections::HashMap<i64, Vec<i64>>,
-) -> std::collections::HashSet<i64> {
-    let mut ids = std::collections::HashSet::new();
-    let mut stack = vec![root_id];
-    while let Some(id) = stack.pop() {
-        ids.insert(id);
-        if let Some(children) = children_map.get(&id) {
-            stack.extend(children);
+/// Which flows the spending endpoints show, from their `mode` parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpendingMode {
+    /// Categories spent in, as positive amounts.
+    #[default]
+    Expenses,
+    /// Categories earned in, as positive amounts.
+    Income,
+    /// Every category with its net flow; the direction tells expenses
+    /// from net income.
+    Net,
+}
+
+impl SpendingMode {
+    pub fn parse(value: Option<&str>) -> AppResult<Self> {
+        match value.unwrap_or_default() {
+            "" | "expenses" => Ok(Self::Expenses),
+            "income" => Ok(Self::Income),
+            "net" => Ok(Self::Net),
+            other => Err(AppError::Validation(format!("Unknown mode: {}", other))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Expenses => "expenses",
+            Self::Income => "income",
+            Self::Net => "net",
+        }
+    }
+
+    /// The amount shown for a net total (income positive) and its
+    /// direction, or `None` if the mode leaves the total out.
+    pub fn amount(&self, net_cents: i64) -> Option<(i64, FlowDirection)> {
+        match self {
+            Self::Expenses => (net_cents < 0).then_some((-net_cents, FlowDirection::Expense)),
+            Self::Income => (net_cents > 0).then_some((net_cents, FlowDirection::Income)),
+            Self::Net => (net_cents != 0).then(|| (net_cents.abs(), FlowDirection::of(net_cents))),
         }
     }
-    ids
 }
 
-/// Build a children map and find the Transfers subtree IDs to exclude from analytics.
-fn transfers_excluded_ids(
-    all_categories: &[crate::models::category::Category],
-) -> std::collections::HashSet<i64> {
-    let mut children_map: std::collections::HashMap<i64, Vec<i64>> =
-        std::collections::HashMap::new();
-    for cat in all_categories {
-        if let Some(parent_id) = cat.parent_id {
-            children_map.entry(parent_id).or_default().push(cat.id);
+/// Whether an amount is spent or earned. Amounts of the spending endpoints
+/// are positive in their direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowDirection {
+    Expense,
+    Income,
+    /// A parent category whose children go both ways (net mode only).
+    Mixed,
+}
+
+impl FlowDirection {
+    /// Direction of a net total, income positive.
+    pub(crate) fn of(net_cents: i64) -> Self {
+        if net_cents > 0 {
+            Self::Income
+        } else {
+            Self::Expense
         }
     }
-    if let Some(transfers) = all_categories
-        .iter()
-        .find(|c| c.built_in && c.name == "Transfers")
-    {
-        collect_subtree_ids(transfers.id, &children_map)
-    } else {
-        std::collections::HashSet::new()
+
+    /// `net_cents` as an amount in this direction, negative if it went the
+    /// other way.
+    pub(crate) fn signed(&self, net_cents: i64) -> i64 {
+        match self {
+            Self::Income => net_cents,
+            _ => -net_cents,
+        }
     }
 }
 
@@ -51,15 +93,49 @@ fn transfers_excluded_ids(
 pub struct AnalyticsParams {
     pub from_date: Option<String>,
     pub to_date: Option<String>,
-    /// "expenses" (default) or "income" — used by the category tree endpoint.
+    /// "expenses", "income" or "net", see [`SpendingMode`].
     pub mode: Option<String>,
+    /// Comma-separated category ids to limit spending over time to.
+    pub category_ids: Option<String>,
+    /// Comma-separated extra sankey layers; only `accounts` is known.
+    pub layers: Option<String>,
+}
+
+impl AnalyticsParams {
+    /// Whether the sankey routes money through account nodes.
+    pub(crate) fn accounts_layer(&self) -> AppResult<bool> {
+        let mut accounts = false;
+        for layer in self
+            .layers
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+        {
+            match layer {
+                "accounts" => accounts = true,
+                other => {
+                    return Err(AppError::Validation(format!(
+                        "Unknown sankey layer: {}",
+                        other
+                    )))
+                }
+            }
+        }
+        Ok(accounts)
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct CategorySpending {
     pub category: String,
     pub color: String,
+    /// Black or white, whichever reads better on `color`.
+    pub text_color: &'static str,
     pub amount_cents: i64,
+    pub direction: FlowDirection,
+    /// Share of the total of all categories returned, in percent.
     pub percentage: f64,
 }
 
@@ -72,9 +148,20 @@ pub struct TimeSeriesPoint {
 #[derive(Debug, Serialize)]
 pub struct MonthlySummary {
     pub month: String,
+    /// Expense total for the month; income in income mode, and income
+    /// minus expenses in net mode.
     pub total_cents: i64,
     pub transaction_count: i64,
     pub average_cents: i64,
+    pub income_cents: i64,
+    /// Expenses as a positive amount.
+    pub expense_cents: i64,
+    /// Income minus expenses.
+    pub net_cents: i64,
+    /// Share of income not spent (`net / income`); `None` without income.
+    pub savings_rate: Option<f64>,
+    /// `savings_rate` formatted per the display settings.
+    pub savings_rate_formatted: Option<String>,
 }
 
 pub async fn spending_by_category(
@@ -87,23 +174,41 @@ pub async fn spending_by_category(
     let excluded = transfers_excluded_ids(&all_cats);
     let excluded_vec: Vec<i64> = excluded.into_iter().collect();
 
-    let sums = transactions::sum_by_category(
+    let sums = transaction_sums::sum_by_category(
         &conn,
         params.from_date.as_deref(),
         params.to_date.as_deref(),
         &excluded_vec,
     )?;
 
-    let grand_total: i64 = sums.iter().map(|s| s.total_cents).sum();
+    // Without a mode, every category's net total as stored (expenses
+    // negative); with one, the mode's amounts
+    let mode = match params.mode.as_deref() {
+        None => None,
+        Some(mode) => Some(SpendingMode::parse(Some(mode))?),
+    };
+    let amounts: Vec<(transaction_sums::CategorySum, i64, FlowDirection)> = sums
+        .into_iter()
+        .filter_map(|s| {
+            let (amount, direction) = match mode {
+                Some(mode) => mode.amount(s.total_cents)?,
+                None => (s.total_cents, FlowDirection::of(s.total_cents)),
+            };
+            Some((s, amount, direction))
+        })
+        .collect();
+    let grand_total: i64 = amounts.iter().map(|(_, amount, _)| amount).sum();
 
-    let result: Vec<CategorySpending> = sums
+    let result: Vec<CategorySpending> = amounts
         .into_iter()
-        .map(|s| CategorySpending {
+        .map(|(s, amount, direction)| CategorySpending {
             category: s.category_name,
+            text_color: palette::text_color(&s.category_color),
             color: s.category_color,
-            amount_cents: s.total_cents,
+            amount_cents: amount,
+            direction,
             percentage: if grand_total != 0 {
-                (s.total_cents as f64 / grand_total as f64) * 100.0
+                (amount as f64 / grand_total as f64) * 100.0
             } else {
                 0.0
             },
@@ -113,6 +218,173 @@ pub async fn spending_by_category(
     Ok(Json(result))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ComparisonParams {
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    /// Comma-separated category ids to compare; all categories if empty.
+    pub category_ids: Option<String>,
+}
+
+/// Spending in one category in the selected and the preceding period.
+/// Spending is the negated net amount, so refunds reduce it.
+#[derive(Debug, Serialize)]
+pub struct CategoryComparison {
+    pub category_id: Option<i64>,
+    pub category: String,
+    pub color: String,
+    /// Black or white, whichever reads better on `color`.
+    pub text_color: &'static str,
+    pub current_cents: i64,
+    pub previous_cents: i64,
+    pub delta_cents: i64,
+    /// Change relative to the previous period in percent; `None` if there
+    /// was no spending before.
+    pub percent_change: Option<f64>,
+    /// `percent_change` formatted per the display settings.
+    pub percent_change_formatted: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpendingComparison {
+    pub from_date: String,
+    pub to_date: String,
+    pub previous_from_date: String,
+    pub previous_to_date: String,
+    pub current_total_cents: i64,
+    pub previous_total_cents: i64,
+    /// Change of the totals in percent; `None` without earlier spending.
+    pub total_percent_change: Option<f64>,
+    pub total_percent_change_formatted: Option<String>,
+    pub categories: Vec<CategoryComparison>,
+}
+
+/// Change from `previous` to `current` in percent of `previous`.
+fn percent_change(current: i64, previous: i64) -> Option<f64> {
+    (previous != 0).then(|| (current - previous) as f64 / previous.abs() as f64 * 100.0)
+}
+
+/// Category ids from a comma-separated list; unparseable entries are skipped.
+pub(crate) fn parse_category_ids(value: Option<&str>) -> Vec<i64> {
+    value
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|s| s.trim().parse::<i64>().ok())
+        .collect()
+}
+
+fn parse_date_param(value: Option<&str>, name: &str) -> AppResult<NaiveDate> {
+    value
+        .and_then(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").ok())
+        .ok_or_else(|| AppError::Validation(format!("Invalid or missing {}", name)))
+}
+
+/// Join per-category sums of two periods, keeping categories with spending
+/// in either of them, sorted by the largest absolute change.
+fn compare_category_sums(
+    current: Vec<transaction_sums::CategorySum>,
+    previous: Vec<transaction_sums::CategorySum>,
+) -> Vec<CategoryComparison> {
+    let mut joined: std::collections::HashMap<Option<i64>, CategoryComparison> =
+        std::collections::HashMap::new();
+    for (sums, is_current) in [(current, true), (previous, false)] {
+        for sum in sums {
+            let entry = joined
+                .entry(sum.category_id)
+                .or_insert_with(|| CategoryComparison {
+                    category_id: sum.category_id,
+                    category: sum.category_name,
+                    text_color: palette::text_color(&sum.category_color),
+                    color: sum.category_color,
+                    current_cents: 0,
+                    previous_cents: 0,
+                    delta_cents: 0,
+                    percent_change: None,
+                    percent_change_formatted: None,
+                });
+            if is_current {
+                entry.current_cents = -sum.total_cents;
+            } else {
+                entry.previous_cents = -sum.total_cents;
+            }
+        }
+    }
+
+    let mut result: Vec<CategoryComparison> = joined
+        .into_values()
+        .filter(|c| c.current_cents > 0 || c.previous_cents > 0)
+        .map(|mut c| {
+            c.delta_cents = c.current_cents - c.previous_cents;
+            c.percent_change = percent_change(c.current_cents, c.previous_cents);
+            c
+        })
+        .collect();
+    result.sort_by(|a, b| {
+        b.delta_cents
+            .abs()
+            .cmp(&a.delta_cents.abs())
+            .then_with(|| a.category.cmp(&b.category))
+    });
+    result
+}
+
+/// Spending per category compared with the preceding period of the same
+/// length (the previous month, quarter or year for calendar ranges).
+pub async fn spending_comparison(
+    State(state): State<AppState>,
+    Query(params): Query<ComparisonParams>,
+) -> AppResult<Json<SpendingComparison>> {
+    let from = parse_date_param(params.from_date.as_deref(), "from_date")?;
+    let to = parse_date_param(params.to_date.as_deref(), "to_date")?;
+    if from > to {
+        return Err(AppError::Validation(
+            "from_date must not be after to_date".into(),
+        ));
+    }
+    let settings = state.load_settings()?;
+    let today = date_utils::today_in(&settings);
+    let previous = DateRange::from_dates(from, to, today).prev();
+
+    let conn = state.db.get()?;
+    let excluded: Vec<i64> = transfers_excluded_ids(&state.cached_categories()?)
+        .into_iter()
+        .collect();
+    let (from_date, to_date) = (from.to_string(), to.to_string());
+    let (previous_from_date, previous_to_date) = (previous.from_str(), previous.to_str());
+    let current =
+        transaction_sums::sum_by_category(&conn, Some(&from_date), Some(&to_date), &excluded)?;
+    let prior = transaction_sums::sum_by_category(
+        &conn,
+        Some(&previous_from_date),
+        Some(&previous_to_date),
+        &excluded,
+    )?;
+
+    let selected = parse_category_ids(params.category_ids.as_deref());
+    let mut categories = compare_category_sums(current, prior);
+    if !selected.is_empty() {
+        categories.retain(|c| c.category_id.is_some_and(|id| selected.contains(&id)));
+    }
+    for c in &mut categories {
+        c.percent_change_formatted = c.percent_change.map(|p| settings.format_percent_val(p));
+    }
+    let current_total_cents = categories.iter().map(|c| c.current_cents).sum();
+    let previous_total_cents = categories.iter().map(|c| c.previous_cents).sum();
+    let total_percent_change = percent_change(current_total_cents, previous_total_cents);
+    Ok(Json(SpendingComparison {
+        current_total_cents,
+        previous_total_cents,
+        total_percent_change,
+        total_percent_change_formatted: total_percent_change
+            .map(|p| settings.format_percent_val(p)),
+        from_date,
+        to_date,
+        previous_from_date,
+        previous_to_date,
+        categories,
+    }))
+}
+
 pub async fn spending_over_time(
     State(state): State<AppState>,
     Query(params): Query<AnalyticsParams>,
@@ -124,10 +396,11 @@ pub async fn spending_over_time(
     );
     let conn = state.db.get()?;
 
-    let rows = transactions::sum_by_date(
+    let rows = transaction_sums::sum_by_date(
         &conn,
         params.from_date.as_deref(),
         params.to_date.as_deref(),
+        &parse_category_ids(params.category_ids.as_deref()),
     )?;
 
     let result: Vec<TimeSeriesPoint> = rows
@@ -149,7 +422,7 @@ pub async fn spending_over_time(
 
 /// Generate all "YYYY-MM" strings for months between two "YYYY-MM-DD" date
 /// strings (inclusive of the months each date falls in).
-fn all_months_in_range(from_date: &str, to_date: &str) -> Vec<String> {
+pub(crate) fn all_months_in_range(from_date: &str, to_date: &str) -> Vec<String> {
     use chrono::{Datelike, NaiveDate};
     let from = match NaiveDate::parse_from_str(from_date, "%Y-%m-%d") {
         Ok(d) => d,
@@ -177,45 +450,34 @@ fn all_months_in_range(from_date: &str, to_date: &str) -> Vec<String> {
     months
 }
 
-pub async fn monthly_summary(
-    State(state): State<AppState>,
-    Query(params): Query<AnalyticsParams>,
-) -> AppResult<Json<Vec<MonthlySummary>>> {
-    debug!(
-        from_date = ?params.from_date,
-        to_date = ?params.to_date,
-        "monthly_summary: fetching data"
-    );
-    let conn = state.db.get()?;
-
-    let income_mode = params.mode.as_deref() == Some("income");
-
-    let sums = transactions::sum_by_month(
-        &conn,
-        params.from_date.as_deref(),
-        params.to_date.as_deref(),
-        income_mode,
-    )?;
-
-    // Seed all months in the requested date range so gaps show as zero bars.
-    let mut monthly_data: std::collections::HashMap<String, (i64, i64)> =
-        std::collections::HashMap::new();
-
-    if let (Some(from), Some(to)) = (&params.from_date, &params.to_date) {
-        for month in all_months_in_range(from, to) {
-            monthly_data.entry(month).or_insert((0, 0));
-        }
-    }
-
-    for s in sums {
-        let entry = monthly_data.entry(s.month).or_insert((0, 0));
-        entry.0 = s.total_cents;
-        entry.1 = s.count;
-    }
+/// Per-month income and expense totals accumulated from the two `sum_by_month` passes.
+#[derive(Default)]
+struct MonthTotals {
+    income_cents: i64,
+    income_count: i64,
+    expense_cents: i64,
+    expense_count: i64,
+}
 
-    let mut result: Vec<MonthlySummary> = monthly_data
-        .into_iter()
-        .map(|(month, (total_cents, transaction_count))| MonthlySummary {
+impl MonthTotals {
+    fn into_summary(
+        self,
+        month: String,
+        mode: SpendingMode,
+        settings: &Settings,
+    ) -> MonthlySummary {
+        let (total_cents, transaction_count) = match mode {
+            SpendingMode::Expenses => (self.expense_cents, self.expense_count),
+            SpendingMode::Income => (self.income_cents, self.income_count),
+            SpendingMode::Net => (
+                self.income_cents - self.expense_cents,
+                self.expense_count + self.income_count,
+            ),
+        };
+        let net_cents = self.income_cents - self.expense_cents;
+        let savings_rate =
+            (self.income_cents > 0).then(|| net_cents as f64 / self.income_cents as f64);
+        MonthlySummary {
             month,
             total_cents,
             transaction_count,
@@ -224,824 +486,140 @@ pub async fn monthly_summary(
             } else {
                 0
             },
-        })
-        .collect();
-
-    result.sort_by(|a, b| a.month.cmp(&b.month));
-
-    if result.is_empty() {
-        warn!("monthly_summary: no monthly data in selected period");
-    } else {
-        debug!(
-            months = result.len(),
-            "monthly_summary: returning summaries"
-        );
-    }
-
-    Ok(Json(result))
-}
-
-#[derive(Debug, Serialize)]
-pub struct CategoryTreeNode {
-    pub name: String,
-    pub color: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub amount_cents: Option<i64>,
-    pub children: Vec<CategoryTreeNode>,
-}
-
-fn tree_node_total(node: &CategoryTreeNode) -> i64 {
-    node.amount_cents.unwrap_or(0) + node.children.iter().map(tree_node_total).sum::<i64>()
-}
-
-fn build_subtree(
-    cat_id: i64,
-    children_map: &std::collections::HashMap<i64, Vec<i64>>,
-    spending_by_id: &std::collections::HashMap<i64, i64>,
-    cat_map: &std::collections::HashMap<i64, &crate::models::category::Category>,
-) -> Option<CategoryTreeNode> {
-    let cat = cat_map.get(&cat_id)?;
-    let child_ids = children_map.get(&cat_id).cloned().unwrap_or_default();
-    let direct_spending = spending_by_id.get(&cat_id).copied().unwrap_or(0);
-
-    let mut child_nodes: Vec<CategoryTreeNode> = child_ids
-        .iter()
-        .filter_map(|&id| build_subtree(id, children_map, spending_by_id, cat_map))
-        .collect();
-
-    child_nodes.sort_by_key(|n| std::cmp::Reverse(tree_node_total(n)));
-
-    let has_children_spending = !child_nodes.is_empty();
-
-    if has_children_spending && direct_spending > 0 {
-        child_nodes.push(CategoryTreeNode {
-            name: format!("Other {}", cat.name),
-            color: cat.color.clone(),
-            id: Some(cat_id),
-            amount_cents: Some(direct_spending),
-            children: Vec::new(),
-        });
-        Some(CategoryTreeNode {
-            name: cat.name.clone(),
-            color: cat.color.clone(),
-            id: Some(cat_id),
-            amount_cents: None,
-            children: child_nodes,
-        })
-    } else if has_children_spending {
-        Some(CategoryTreeNode {
-            name: cat.name.clone(),
-            color: cat.color.clone(),
-            id: Some(cat_id),
-            amount_cents: None,
-            children: child_nodes,
-        })
-    } else if direct_spending > 0 {
-        Some(CategoryTreeNode {
-            name: cat.name.clone(),
-            color: cat.color.clone(),
-            id: Some(cat_id),
-            amount_cents: Some(direct_spending),
-            children: Vec::new(),
-        })
-    } else {
-        None
+            income_cents: self.income_cents,
+            expense_cents: self.expense_cents,
+            net_cents,
+            savings_rate,
+            savings_rate_formatted: savings_rate
+                .map(|rate| settings.format_percent_share(rate * 100.0)),
+        }
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct CategoryTreeResponse {
-    pub categories: Vec<CategoryTreeNode>,
-    /// Earliest transaction date in the result set (YYYY-MM-DD)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub from_date: Option<String>,
-    /// Latest transaction date in the result set (YYYY-MM-DD)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub to_date: Option<String>,
-}
-
-pub async fn spending_by_category_tree(
+pub async fn monthly_summary(
     State(state): State<AppState>,
     Query(params): Query<AnalyticsParams>,
-) -> AppResult<Json<CategoryTreeResponse>> {
+) -> AppResult<Json<Vec<MonthlySummary>>> {
     debug!(
         from_date = ?params.from_date,
         to_date = ?params.to_date,
-        "spending_by_category_tree: fetching data"
+        "monthly_summary: fetching data"
     );
     let conn = state.db.get()?;
 
-    let agg = transactions::sum_by_category_id_with_dates(
-        &conn,
-        params.from_date.as_deref(),
-        params.to_date.as_deref(),
-    )?;
-    let actual_from = agg.min_date;
-    let actual_to = agg.max_date;
-
-    let all_categories = state.cached_categories()?;
-    debug!(
-        category_groups = agg.sums.len(),
-        categories = all_categories.len(),
-        "spending_by_category_tree: loaded aggregate data"
-    );
-
-    let income_mode = params.mode.as_deref() == Some("income");
+    let mode = SpendingMode::parse(params.mode.as_deref())?;
+    let settings = state.load_settings()?;
+    let excluded = transfers_excluded_ids(&state.cached_categories()?);
+    let (from, to) = (params.from_date.as_deref(), params.to_date.as_deref());
+    let income = transaction_sums::sum_by_month(&conn, from, to, true, &excluded)?;
+    let expenses = transaction_sums::sum_by_month(&conn, from, to, false, &excluded)?;
 
-    // Group totals by category_id.
-    let mut totals_by_id: std::collections::HashMap<i64, i64> = std::collections::HashMap::new();
-    let mut uncategorized_total: i64 = 0;
+    // Seed all months in the requested date range so gaps show as zero bars.
+    let mut monthly_data: std::collections::BTreeMap<String, MonthTotals> =
+        std::collections::BTreeMap::new();
 
-    for (cat_id, total) in agg.sums {
-        if let Some(id) = cat_id {
-            *totals_by_id.entry(id).or_insert(0) += total;
-        } else {
-            uncategorized_total += total;
+    if let (Some(from), Some(to)) = (from, to) {
+        for month in all_months_in_range(from, to) {
+            monthly_data.entry(month).or_default();
         }
     }
 
-    // Exclude Transfers subtree
-    let excluded = transfers_excluded_ids(&all_categories);
-
-    // In expense mode: keep net-negative amounts, negate to positive.
-    // In income mode: keep net-positive amounts as-is.
-    let spending_by_id: std::collections::HashMap<i64, i64> = totals_by_id
-        .into_iter()
-        .filter(|(k, _)| !excluded.contains(k))
-        .filter(|(_, v)| if income_mode { *v > 0 } else { *v < 0 })
-        .map(|(k, v)| (k, if income_mode { v } else { -v }))
-        .collect();
-    let uncategorized_total = if income_mode {
-        uncategorized_total.max(0)
-    } else if uncategorized_total < 0 {
-        -uncategorized_total
-    } else {
-        0
-    };
-
-    // Build category lookup maps
-    let cat_map: std::collections::HashMap<i64, &crate::models::category::Category> =
-        all_categories.iter().map(|c| (c.id, c)).collect();
-
-    // Find children for each parent
-    let mut children_map: std::collections::HashMap<i64, Vec<i64>> =
-        std::collections::HashMap::new();
-    let mut top_level_ids: Vec<i64> = Vec::new();
-
-    for cat in &all_categories {
-        if excluded.contains(&cat.id) {
-            continue;
-        }
-        if let Some(parent_id) = cat.parent_id {
-            children_map.entry(parent_id).or_default().push(cat.id);
-        } else {
-            top_level_ids.push(cat.id);
-        }
+    for s in income {
+        let entry = monthly_data.entry(s.month).or_default();
+        entry.income_cents = s.total_cents;
+        entry.income_count = s.count;
     }
-
-    top_level_ids.sort();
-
-    let mut result: Vec<CategoryTreeNode> = top_level_ids
-        .iter()
-        .filter_map(|&id| build_subtree(id, &children_map, &spending_by_id, &cat_map))
-        .collect();
-
-    // Add uncategorized as top-level leaf
-    if uncategorized_total > 0 {
-        result.push(CategoryTreeNode {
-            name: "Uncategorized".into(),
-            color: DEFAULT_COLOR.into(),
-            id: None,
-            amount_cents: Some(uncategorized_total),
-            children: Vec::new(),
-        });
+    for s in expenses {
+        let entry = monthly_data.entry(s.month).or_default();
+        entry.expense_cents = s.total_cents;
+        entry.expense_count = s.count;
     }
 
-    // Sort top-level by total spending (recursive sum of all descendants)
-    result.sort_by_key(|n| std::cmp::Reverse(tree_node_total(n)));
+    let result: Vec<MonthlySummary> = monthly_data
+        .into_iter()
+        .map(|(month, totals)| totals.into_summary(month, mode, &settings))
+        .collect();
 
     if result.is_empty() {
-        warn!("spending_by_category_tree: no spending data in selected period");
+        warn!("monthly_summary: no monthly data in selected period");
     } else {
         debug!(
-            top_level_nodes = result.len(),
-            "spending_by_category_tree: returning tree"
+            months = result.len(),
+            "monthly_summary: returning summaries"
         );
     }
 
-    Ok(Json(CategoryTreeResponse {
-        categories: result,
-        from_date: actual_from,
-        to_date: actual_to,
-    }))
-}
-
-#[derive(Debug, Deserialize)]
-pub struct MonthlyByCategoryParams {
-    pub from_date: Option<String>,
-    pub to_date: Option<String>,
-    pub category_ids: Option<String>,
-    /// "expenses" (default) or "income".
-    pub mode: Option<String>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct MonthlyCategorySeries {
-    pub category: String,
-    pub color: String,
-    pub totals: Vec<i64>,
+    Ok(Json(result))
 }
 
+/// Expense totals for one weekday or day-of-month bucket.
 #[derive(Debug, Serialize)]
-pub struct MonthlyByCategoryResponse {
-    pub months: Vec<String>,
-    pub series: Vec<MonthlyCategorySeries>,
+pub struct SpendingPatternBucket {
+    /// Weekday as `strftime('%w')` (0 = Sunday), or bucket 0–2 for days
+    /// 1–10, 11–20 and 21–31.
+    pub index: i64,
+    pub label: String,
+    pub total_cents: i64,
+    pub transaction_count: i64,
+    pub average_cents: i64,
 }
 
-pub async fn monthly_by_category(
-    State(state): State<AppState>,
-    Query(params): Query<MonthlyByCategoryParams>,
-) -> AppResult<Json<MonthlyByCategoryResponse>> {
-    debug!(
-        from_date = ?params.from_date,
-        to_date = ?params.to_date,
-        category_ids = ?params.category_ids,
-        "monthly_by_category: fetching data"
-    );
-    let conn = state.db.get()?;
-
-    let selected_ids: std::collections::HashSet<i64> = params
-        .category_ids
-        .as_deref()
-        .unwrap_or("")
-        .split(',')
-        .filter_map(|s| s.trim().parse::<i64>().ok())
-        .collect();
-
-    if selected_ids.is_empty() {
-        debug!("monthly_by_category: no category IDs provided, returning empty");
-        return Ok(Json(MonthlyByCategoryResponse {
-            months: Vec::new(),
-            series: Vec::new(),
-        }));
-    }
-
-    let from_date_str = params.from_date.clone();
-    let to_date_str = params.to_date.clone();
-
-    let filter = transactions::TransactionFilter {
-        from_date: params.from_date,
-        to_date: params.to_date,
-        ..Default::default()
-    };
-
-    let transaction_list = transactions::list_transactions(&conn, &filter)?;
-    let all_categories = state.cached_categories()?;
-
-    let cat_map: std::collections::HashMap<i64, &crate::models::category::Category> =
-        all_categories.iter().map(|c| (c.id, c)).collect();
-
-    // Group by (category_id, month) → amount_cents
-    let mut data: std::collections::HashMap<i64, std::collections::HashMap<String, i64>> =
-        std::collections::HashMap::new();
-    let mut all_months: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
-
-    // Seed all months in the requested date range so gaps show as zero bars.
-    if let (Some(from), Some(to)) = (&from_date_str, &to_date_str) {
-        for month in all_months_in_range(from, to) {
-            all_months.insert(month);
-        }
-    }
-
-    for transaction in &transaction_list {
-        let cat_id = match transaction.transaction.category_id {
-            Some(id) if selected_ids.contains(&id) => id,
-            _ => continue,
-        };
-
-        let month = if transaction.transaction.date.len() >= 7 {
-            transaction.transaction.date[..7].to_string()
-        } else {
-            continue;
-        };
-
-        all_months.insert(month.clone());
-        *data.entry(cat_id).or_default().entry(month).or_insert(0) +=
-            transaction.transaction.amount_cents;
-    }
-
-    let months: Vec<String> = all_months.into_iter().collect();
-
-    let income_mode = params.mode.as_deref() == Some("income");
-
-    // Build series, filtering by sign and normalising to positive values
-    let mut series: Vec<MonthlyCategorySeries> = Vec::new();
-    for &cat_id in &selected_ids {
-        let cat = match cat_map.get(&cat_id) {
-            Some(c) => c,
-            None => continue,
-        };
-
-        let month_totals = match data.get(&cat_id) {
-            Some(m) => m,
-            None => continue,
-        };
-
-        let totals: Vec<i64> = months
+impl SpendingPatternBucket {
+    fn new(index: i64, label: String, sums: &[transaction_sums::PatternSum]) -> Self {
+        let (total_cents, transaction_count) = sums
             .iter()
-            .map(|m| {
-                let raw = month_totals.get(m).copied().unwrap_or(0);
-                if income_mode {
-                    raw.max(0)
-                } else if raw < 0 {
-                    -raw
-                } else {
-                    0
-                }
-            })
-            .collect();
-
-        // Skip categories with no data at all
-        if totals.iter().all(|&v| v == 0) {
-            continue;
+            .find(|s| s.key == index)
+            .map_or((0, 0), |s| (s.total_cents, s.count));
+        Self {
+            index,
+            label,
+            total_cents,
+            transaction_count,
+            average_cents: if transaction_count > 0 {
+                total_cents / transaction_count
+            } else {
+                0
+            },
         }
-
-        series.push(MonthlyCategorySeries {
-            category: cat.name.clone(),
-            color: cat.color.clone(),
-            totals,
-        });
     }
-
-    // Sort series by total spending descending
-    series.sort_by(|a, b| {
-        let sum_a: i64 = a.totals.iter().sum();
-        let sum_b: i64 = b.totals.iter().sum();
-        sum_b.cmp(&sum_a)
-    });
-
-    if series.is_empty() {
-        warn!(
-            selected = selected_ids.len(),
-            "monthly_by_category: no spending data for selected categories"
-        );
-    } else {
-        debug!(
-            months = months.len(),
-            series = series.len(),
-            "monthly_by_category: returning data"
-        );
-    }
-
-    Ok(Json(MonthlyByCategoryResponse { months, series }))
-}
-
-#[derive(Debug, Serialize)]
-pub struct SankeyNode {
-    pub name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub color: Option<String>,
-    pub depth: u32,
 }
 
 #[derive(Debug, Serialize)]
-pub struct SankeyLink {
-    pub source: String,
-    pub target: String,
-    pub value: f64,
+pub struct SpendingPatternsResponse {
+    /// Weekdays in the locale's display order.
+    pub weekdays: Vec<SpendingPatternBucket>,
+    pub month_days: Vec<SpendingPatternBucket>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct SankeyResponse {
-    pub nodes: Vec<SankeyNode>,
-    pub links: Vec<SankeyLink>,
-    /// Earliest transaction date in the result set (YYYY-MM-DD)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub from_date: Option<String>,
-    /// Latest transaction date in the result set (YYYY-MM-DD)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub to_date: Option<String>,
-}
-
-pub async fn flow_sankey(
+/// Expenses by weekday and by part of the month, excluding Transfers.
+pub async fn spending_patterns(
     State(state): State<AppState>,
-    Query(params): Query<AnalyticsParams>,
-) -> AppResult<Json<SankeyResponse>> {
-    debug!(
-        from_date = ?params.from_date,
-        to_date = ?params.to_date,
-        "flow_sankey: fetching data"
-    );
+    Query(params): Query<ComparisonParams>,
+) -> AppResult<Json<SpendingPatternsResponse>> {
     let conn = state.db.get()?;
+    let locale = state.load_settings()?.locale;
 
-    let agg = transactions::sum_by_category_id_with_dates(
-        &conn,
-        params.from_date.as_deref(),
-        params.to_date.as_deref(),
-    )?;
-
-    let all_categories = state.cached_categories()?;
-
-    let cat_map: std::collections::HashMap<i64, &crate::models::category::Category> =
-        all_categories.iter().map(|c| (c.id, c)).collect();
-
-    let actual_from = agg.min_date;
-    let actual_to = agg.max_date;
+    let excluded = transfers_excluded_ids(&state.cached_categories()?);
+    let (from, to) = (params.from_date.as_deref(), params.to_date.as_deref());
+    let by_weekday = transaction_sums::sum_expenses_by_weekday(&conn, from, to, &excluded)?;
+    let by_bucket = transaction_sums::sum_expenses_by_month_day_bucket(&conn, from, to, &excluded)?;
 
-    debug!(
-        category_groups = agg.sums.len(),
-        categories = all_categories.len(),
-        "flow_sankey: loaded aggregate data"
-    );
-
-    let mut totals_by_id: std::collections::HashMap<Option<i64>, i64> =
-        std::collections::HashMap::new();
-    for (cat_id, total) in agg.sums {
-        totals_by_id.insert(cat_id, total);
-    }
-
-    // Build category hierarchy, excluding Transfers subtree
-    let excluded = transfers_excluded_ids(&all_categories);
-
-    let mut children_map: std::collections::HashMap<i64, Vec<i64>> =
-        std::collections::HashMap::new();
-    let mut top_level_ids: Vec<i64> = Vec::new();
-
-    for cat in &all_categories {
-        if excluded.contains(&cat.id) {
-            continue;
-        }
-        if let Some(parent_id) = cat.parent_id {
-            children_map.entry(parent_id).or_default().push(cat.id);
-        } else {
-            top_level_ids.push(cat.id);
-        }
-    }
-    top_level_ids.sort();
-
-    // Classify each category's net total as income or expense
-    let mut income_by_id: std::collections::HashMap<i64, i64> = std::collections::HashMap::new();
-    let mut expense_by_id: std::collections::HashMap<i64, i64> = std::collections::HashMap::new();
-    let mut uncategorized_income: i64 = 0;
-    let mut uncategorized_expense: i64 = 0;
-
-    for (&cat_id, &total) in &totals_by_id {
-        if total == 0 {
-            continue;
-        }
-        match cat_id {
-            Some(id) => {
-                if !cat_map.contains_key(&id) || excluded.contains(&id) {
-                    continue;
-                }
-                if total > 0 {
-                    income_by_id.insert(id, total);
-                } else {
-                    expense_by_id.insert(id, -total);
-                }
-            }
-            None => {
-                if total > 0 {
-                    uncategorized_income = total;
-                } else {
-                    uncategorized_expense = -total;
-                }
-            }
-        }
-    }
-
-    if income_by_id.is_empty()
-        && expense_by_id.is_empty()
-        && uncategorized_income == 0
-        && uncategorized_expense == 0
-    {
-        warn!("flow_sankey: no data in selected period");
-        return Ok(Json(SankeyResponse {
-            nodes: Vec::new(),
-            links: Vec::new(),
-            from_date: None,
-            to_date: None,
-        }));
-    }
-
-    // Build recursive tree for each top-level category, separately for
-    // income and expense.  Each node tracks its subtotal (direct +
-    // descendants) and max subtree depth so we can assign Sankey columns.
-    struct SankeyTreeNode<'a> {
-        cat: &'a crate::models::category::Category,
-        direct: i64,
-        children: Vec<SankeyTreeNode<'a>>,
-        subtotal: i64,
-        max_subtree_depth: u32,
-    }
-
-    fn build_sankey_tree<'a>(
-        cat_id: i64,
-        amount_map: &std::collections::HashMap<i64, i64>,
-        children_map: &std::collections::HashMap<i64, Vec<i64>>,
-        cat_map: &std::collections::HashMap<i64, &'a crate::models::category::Category>,
-    ) -> Option<SankeyTreeNode<'a>> {
-        let cat = cat_map.get(&cat_id)?;
-        let direct = amount_map.get(&cat_id).copied().unwrap_or(0);
-        let child_ids = children_map.get(&cat_id).cloned().unwrap_or_default();
-
-        let mut children: Vec<SankeyTreeNode<'a>> = child_ids
-            .iter()
-            .filter_map(|&id| build_sankey_tree(id, amount_map, children_map, cat_map))
-            .collect();
-        children.sort_by(|a, b| b.subtotal.cmp(&a.subtotal));
-
-        let child_sum: i64 = children.iter().map(|c| c.subtotal).sum();
-        let subtotal = direct + child_sum;
-        if subtotal == 0 {
-            return None;
-        }
-
-        let max_subtree_depth = children
-            .iter()
-            .map(|c| c.max_subtree_depth + 1)
-            .max()
-            .unwrap_or(0);
-
-        Some(SankeyTreeNode {
-            cat,
-            direct,
-            children,
-            subtotal,
-            max_subtree_depth,
-        })
-    }
-
-    let mut income_trees: Vec<SankeyTreeNode> = top_level_ids
+    let names = analytics::weekday_names(&locale);
+    let weekdays = analytics::weekday_order(&locale)
         .iter()
-        .filter_map(|&id| build_sankey_tree(id, &income_by_id, &children_map, &cat_map))
+        .map(|&day| {
+            SpendingPatternBucket::new(day as i64, names[day as usize].to_string(), &by_weekday)
+        })
         .collect();
-    income_trees.sort_by(|a, b| b.subtotal.cmp(&a.subtotal));
-
-    let mut expense_trees: Vec<SankeyTreeNode> = top_level_ids
+    let month_days = ["1–10", "11–20", "21–31"]
         .iter()
-        .filter_map(|&id| build_sankey_tree(id, &expense_by_id, &children_map, &cat_map))
+        .enumerate()
+        .map(|(i, label)| SpendingPatternBucket::new(i as i64, label.to_string(), &by_bucket))
         .collect();
-    expense_trees.sort_by(|a, b| b.subtotal.cmp(&a.subtotal));
-
-    // Detect category names that appear on both income and expense sides.
-    // Without disambiguation, shared names create cycles in the DAG
-    // (e.g. "Expenses" → Budget → "Expenses").
-    fn collect_tree_names(node: &SankeyTreeNode, names: &mut std::collections::HashSet<String>) {
-        names.insert(node.cat.name.clone());
-        if !node.children.is_empty() {
-            for child in &node.children {
-                collect_tree_names(child, names);
-            }
-            if node.direct > 0 {
-                names.insert(format!("Other {}", node.cat.name));
-            }
-        }
-    }
-
-    let mut income_names: std::collections::HashSet<String> = std::collections::HashSet::new();
-    let mut expense_names: std::collections::HashSet<String> = std::collections::HashSet::new();
-    for tree in &income_trees {
-        collect_tree_names(tree, &mut income_names);
-    }
-    for tree in &expense_trees {
-        collect_tree_names(tree, &mut expense_names);
-    }
-    let overlap: std::collections::HashSet<String> =
-        income_names.intersection(&expense_names).cloned().collect();
-
-    // Column layout based on actual max depth on each side:
-    //   Income leaves (col 0) ... income roots (col max_inc)
-    //   | Budget (col max_inc+1) |
-    //   expense roots (col max_inc+2) ... expense leaves (col max_inc+2+max_exp)
-    let max_income_depth = income_trees
-        .iter()
-        .map(|t| t.max_subtree_depth)
-        .max()
-        .unwrap_or(0);
-    let income_root_col = max_income_depth;
-    let budget_depth = income_root_col + 1;
-    let expense_root_col = budget_depth + 1;
-
-    let budget_name = "Budget".to_string();
-    let mut nodes: Vec<SankeyNode> = Vec::new();
-    let mut links: Vec<SankeyLink> = Vec::new();
-    let mut node_names: std::collections::HashSet<String> = std::collections::HashSet::new();
-
-    fn ensure_node(
-        nodes: &mut Vec<SankeyNode>,
-        seen: &mut std::collections::HashSet<String>,
-        name: &str,
-        color: &str,
-        depth: u32,
-    ) {
-        if seen.insert(name.to_string()) {
-            nodes.push(SankeyNode {
-                name: name.to_string(),
-                color: Some(color.to_string()),
-                depth,
-            });
-        }
-    }
-
-    // Recursively emit income nodes.  Income flows left-to-right:
-    // deepest leaves at col 0 → parents → roots → Budget.
-    // A node at tree_depth d gets Sankey col = max_depth - d.
-    // Names in `overlap` are suffixed with " (In)" to avoid cycles.
-    fn emit_income(
-        node: &SankeyTreeNode,
-        tree_depth: u32,
-        max_depth: u32,
-        nodes: &mut Vec<SankeyNode>,
-        links: &mut Vec<SankeyLink>,
-        seen: &mut std::collections::HashSet<String>,
-        overlap: &std::collections::HashSet<String>,
-    ) {
-        let col = max_depth - tree_depth;
-        let name = if overlap.contains(&node.cat.name) {
-            format!("{} (In)", node.cat.name)
-        } else {
-            node.cat.name.clone()
-        };
-        ensure_node(nodes, seen, &name, &node.cat.color, col);
-
-        if !node.children.is_empty() {
-            for child in &node.children {
-                emit_income(
-                    child,
-                    tree_depth + 1,
-                    max_depth,
-                    nodes,
-                    links,
-                    seen,
-                    overlap,
-                );
-                let child_name = if overlap.contains(&child.cat.name) {
-                    format!("{} (In)", child.cat.name)
-                } else {
-                    child.cat.name.clone()
-                };
-                links.push(SankeyLink {
-                    source: child_name,
-                    target: name.clone(),
-                    value: child.subtotal as f64 / 100.0,
-                });
-            }
-            if node.direct > 0 {
-                let other_base = format!("Other {}", node.cat.name);
-                let other = if overlap.contains(&other_base) {
-                    format!("{} (In)", other_base)
-                } else {
-                    other_base
-                };
-                let child_col = max_depth - (tree_depth + 1);
-                ensure_node(nodes, seen, &other, &node.cat.color, child_col);
-                links.push(SankeyLink {
-                    source: other,
-                    target: name.clone(),
-                    value: node.direct as f64 / 100.0,
-                });
-            }
-        }
-    }
-
-    // --- Income side ---
-    for tree in &income_trees {
-        emit_income(
-            tree,
-            0,
-            income_root_col,
-            &mut nodes,
-            &mut links,
-            &mut node_names,
-            &overlap,
-        );
-        let root_name = if overlap.contains(&tree.cat.name) {
-            format!("{} (In)", tree.cat.name)
-        } else {
-            tree.cat.name.clone()
-        };
-        links.push(SankeyLink {
-            source: root_name,
-            target: budget_name.clone(),
-            value: tree.subtotal as f64 / 100.0,
-        });
-    }
-
-    if uncategorized_income > 0 {
-        ensure_node(
-            &mut nodes,
-            &mut node_names,
-            "Uncategorized",
-            DEFAULT_COLOR,
-            income_root_col,
-        );
-        links.push(SankeyLink {
-            source: "Uncategorized".into(),
-            target: budget_name.clone(),
-            value: uncategorized_income as f64 / 100.0,
-        });
-    }
-
-    ensure_node(
-        &mut nodes,
-        &mut node_names,
-        &budget_name,
-        "#3b82f6",
-        budget_depth,
-    );
-
-    // Recursively emit expense nodes.  Expenses flow left-to-right:
-    // Budget → roots → parents → deepest leaves.
-    // A node at tree_depth d gets Sankey col = base_col + d.
-    fn emit_expense(
-        node: &SankeyTreeNode,
-        tree_depth: u32,
-        base_col: u32,
-        nodes: &mut Vec<SankeyNode>,
-        links: &mut Vec<SankeyLink>,
-        seen: &mut std::collections::HashSet<String>,
-    ) {
-        let col = base_col + tree_depth;
-        ensure_node(nodes, seen, &node.cat.name, &node.cat.color, col);
-
-        if !node.children.is_empty() {
-            for child in &node.children {
-                emit_expense(child, tree_depth + 1, base_col, nodes, links, seen);
-                links.push(SankeyLink {
-                    source: node.cat.name.clone(),
-                    target: child.cat.name.clone(),
-                    value: child.subtotal as f64 / 100.0,
-                });
-            }
-            if node.direct > 0 {
-                let other = format!("Other {}", node.cat.name);
-                ensure_node(
-                    nodes,
-                    seen,
-                    &other,
-                    &node.cat.color,
-                    base_col + tree_depth + 1,
-                );
-                links.push(SankeyLink {
-                    source: node.cat.name.clone(),
-                    target: other,
-                    value: node.direct as f64 / 100.0,
-                });
-            }
-        }
-    }
 
-    // --- Expense side ---
-    for tree in &expense_trees {
-        links.push(SankeyLink {
-            source: budget_name.clone(),
-            target: tree.cat.name.clone(),
-            value: tree.subtotal as f64 / 100.0,
-        });
-        emit_expense(
-            tree,
-            0,
-            expense_root_col,
-            &mut nodes,
-            &mut links,
-            &mut node_names,
-        );
-    }
-
-    if uncategorized_expense > 0 {
-        let name = if node_names.contains("Uncategorized") {
-            "Uncategorized Expenses"
-        } else {
-            "Uncategorized"
-        };
-        ensure_node(
-            &mut nodes,
-            &mut node_names,
-            name,
-            DEFAULT_COLOR,
-            expense_root_col,
-        );
-        links.push(SankeyLink {
-            source: budget_name.clone(),
-            target: name.into(),
-            value: uncategorized_expense as f64 / 100.0,
-        });
-    }
-
-    debug!(
-        nodes = nodes.len(),
-        links = links.len(),
-        "flow_sankey: returning hierarchical data"
-    );
-
-    Ok(Json(SankeyResponse {
-        nodes,
-        links,
-        from_date: actual_from,
-        to_date: actual_to,
+    Ok(Json(SpendingPatternsResponse {
+        weekdays,
+        month_days,
     }))
 }
 
@@ -1075,3 +653,27 @@ pub async fn icon_svg(Path(name): Path<String>) -> impl IntoResponse {
         None => StatusCode::NOT_FOUND.into_response(),
     }
 }
+
+// --- Cache API ---
+
+#[derive(Debug, Serialize)]
+pub struct DomainVersion {
+    pub domain: DataDomain,
+    pub version: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub versions: Vec<DomainVersion>,
+}
+
+/// Current cache version of every data domain, for debugging stale pages.
+pub async fn cache_stats(State(state): State<AppState>) -> Json<CacheStats> {
+    let versions = state
+        .cache
+        .versions()
+        .into_iter()
+        .map(|(domain, version)| DomainVersion { domain, version })
+        .collect();
+    Json(CacheStats { versions })
+}
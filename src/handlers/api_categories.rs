@@ -0,0 +1,464 @@
+//! Spending broken down along the category hierarchy: as a tree for the
+//! sunburst chart and as monthly series per category.
+
+use axum::extract::{Query, State};
+use axum::response::Json;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::db::queries::categories::transfers_excluded_ids;
+use crate::db::queries::{transaction_sums, transactions};
+use crate::error::AppResult;
+use crate::handlers::api::{
+    all_months_in_range, parse_category_ids, AnalyticsParams, FlowDirection, SpendingMode,
+};
+use crate::models::{TransactionStatus, DEFAULT_COLOR};
+use crate::palette;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct CategoryTreeNode {
+    pub name: String,
+    pub color: String,
+    /// Black or white, whichever reads better on `color`.
+    pub text_color: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_cents: Option<i64>,
+    /// Value for the transaction list's `category_id` filter (`0` = uncategorized).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<i64>,
+    /// Number of transactions in this node, including all descendants.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_count: Option<i64>,
+    /// Transaction list showing exactly the transactions behind this node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drilldown_url: Option<String>,
+    pub direction: FlowDirection,
+    pub children: Vec<CategoryTreeNode>,
+}
+
+/// A category's amount in the selected mode, its direction and its number
+/// of transactions.
+#[derive(Debug, Clone, Copy)]
+struct CategoryAmount {
+    cents: i64,
+    direction: FlowDirection,
+    count: i64,
+}
+
+impl CategoryTreeNode {
+    fn leaf(cat: &crate::models::category::Category, name: String, amount: CategoryAmount) -> Self {
+        Self {
+            name,
+            color: cat.color.clone(),
+            text_color: palette::text_color(&cat.color),
+            id: Some(cat.id),
+            amount_cents: Some(amount.cents),
+            category_id: Some(cat.id),
+            transaction_count: Some(amount.count),
+            drilldown_url: None,
+            direction: amount.direction,
+            children: Vec::new(),
+        }
+    }
+
+    fn parent(cat: &crate::models::category::Category, children: Vec<CategoryTreeNode>) -> Self {
+        let count = children.iter().filter_map(|c| c.transaction_count).sum();
+        let direction = match children.first().map(|c| c.direction) {
+            Some(first) if children.iter().all(|c| c.direction == first) => first,
+            _ => FlowDirection::Mixed,
+        };
+        Self {
+            name: cat.name.clone(),
+            color: cat.color.clone(),
+            text_color: palette::text_color(&cat.color),
+            id: Some(cat.id),
+            amount_cents: None,
+            category_id: Some(cat.id),
+            transaction_count: Some(count),
+            drilldown_url: None,
+            direction,
+            children,
+        }
+    }
+
+    /// Set drill-down links for this node and its descendants. Leaves (a
+    /// category without spending children, or an "Other X" node) link to the
+    /// category alone; parents link to the whole subtree.
+    fn attach_drilldown_urls(&mut self, from_date: &str, to_date: &str) {
+        if let Some(id) = self.category_id {
+            let include_children = if self.children.is_empty() {
+                ""
+            } else {
+                "&include_children=true"
+            };
+            self.drilldown_url = Some(format!(
+                "/transactions?category_id={}{}&from_date={}&to_date={}&status=posted",
+                id, include_children, from_date, to_date
+            ));
+        }
+        for child in &mut self.children {
+            child.attach_drilldown_urls(from_date, to_date);
+        }
+    }
+}
+
+fn tree_node_total(node: &CategoryTreeNode) -> i64 {
+    node.amount_cents.unwrap_or(0) + node.children.iter().map(tree_node_total).sum::<i64>()
+}
+
+/// Build the subtree rooted at `cat_id`. `spending_by_id` maps category ids
+/// to their amount in the selected mode.
+fn build_subtree(
+    cat_id: i64,
+    children_map: &std::collections::HashMap<i64, Vec<i64>>,
+    spending_by_id: &std::collections::HashMap<i64, CategoryAmount>,
+    cat_map: &std::collections::HashMap<i64, &crate::models::category::Category>,
+) -> Option<CategoryTreeNode> {
+    let cat = cat_map.get(&cat_id)?;
+    let child_ids = children_map.get(&cat_id).cloned().unwrap_or_default();
+    let direct = spending_by_id.get(&cat_id).copied();
+
+    let mut child_nodes: Vec<CategoryTreeNode> = child_ids
+        .iter()
+        .filter_map(|&id| build_subtree(id, children_map, spending_by_id, cat_map))
+        .collect();
+
+    child_nodes.sort_by_key(|n| std::cmp::Reverse(tree_node_total(n)));
+
+    let has_children_spending = !child_nodes.is_empty();
+
+    match direct {
+        Some(direct) if has_children_spending => {
+            // Direct transactions on the parent link back to the parent category itself
+            child_nodes.push(CategoryTreeNode::leaf(
+                cat,
+                format!("Other {}", cat.name),
+                direct,
+            ));
+            Some(CategoryTreeNode::parent(cat, child_nodes))
+        }
+        None if has_children_spending => Some(CategoryTreeNode::parent(cat, child_nodes)),
+        Some(direct) => Some(CategoryTreeNode::leaf(cat, cat.name.clone(), direct)),
+        None => None,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryTreeResponse {
+    pub categories: Vec<CategoryTreeNode>,
+    /// Earliest transaction date in the result set (YYYY-MM-DD)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_date: Option<String>,
+    /// Latest transaction date in the result set (YYYY-MM-DD)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_date: Option<String>,
+}
+
+pub async fn spending_by_category_tree(
+    State(state): State<AppState>,
+    Query(params): Query<AnalyticsParams>,
+) -> AppResult<Json<CategoryTreeResponse>> {
+    debug!(
+        from_date = ?params.from_date,
+        to_date = ?params.to_date,
+        "spending_by_category_tree: fetching data"
+    );
+    let conn = state.db.get()?;
+
+    let agg = transaction_sums::sum_by_category_id_with_dates(
+        &conn,
+        params.from_date.as_deref(),
+        params.to_date.as_deref(),
+    )?;
+    let actual_from = agg.min_date;
+    let actual_to = agg.max_date;
+
+    let all_categories = state.cached_categories()?;
+    debug!(
+        category_groups = agg.sums.len(),
+        categories = all_categories.len(),
+        "spending_by_category_tree: loaded aggregate data"
+    );
+
+    let mode = SpendingMode::parse(params.mode.as_deref())?;
+
+    // Group totals and counts by category_id.
+    let mut totals_by_id: std::collections::HashMap<i64, (i64, i64)> =
+        std::collections::HashMap::new();
+    let mut uncategorized_total: i64 = 0;
+    let mut uncategorized_count: i64 = 0;
+
+    for (cat_id, total, count) in agg.sums {
+        if let Some(id) = cat_id {
+            let entry = totals_by_id.entry(id).or_insert((0, 0));
+            entry.0 += total;
+            entry.1 += count;
+        } else {
+            uncategorized_total += total;
+            uncategorized_count += count;
+        }
+    }
+
+    // Exclude Transfers subtree
+    let excluded = transfers_excluded_ids(&all_categories);
+
+    // Keep the net totals the mode shows, as positive amounts
+    let spending_by_id: std::collections::HashMap<i64, CategoryAmount> = totals_by_id
+        .into_iter()
+        .filter(|(k, _)| !excluded.contains(k))
+        .filter_map(|(k, (v, count))| {
+            let (cents, direction) = mode.amount(v)?;
+            Some((
+                k,
+                CategoryAmount {
+                    cents,
+                    direction,
+                    count,
+                },
+            ))
+        })
+        .collect();
+
+    // Build category lookup maps
+    let cat_map: std::collections::HashMap<i64, &crate::models::category::Category> =
+        all_categories.iter().map(|c| (c.id, c)).collect();
+
+    // Find children for each parent
+    let mut children_map: std::collections::HashMap<i64, Vec<i64>> =
+        std::collections::HashMap::new();
+    let mut top_level_ids: Vec<i64> = Vec::new();
+
+    for cat in &all_categories {
+        if excluded.contains(&cat.id) {
+            continue;
+        }
+        if let Some(parent_id) = cat.parent_id {
+            children_map.entry(parent_id).or_default().push(cat.id);
+        } else {
+            top_level_ids.push(cat.id);
+        }
+    }
+
+    top_level_ids.sort();
+
+    let mut result: Vec<CategoryTreeNode> = top_level_ids
+        .iter()
+        .filter_map(|&id| build_subtree(id, &children_map, &spending_by_id, &cat_map))
+        .collect();
+
+    // Add uncategorized as top-level leaf
+    if let Some((cents, direction)) = mode.amount(uncategorized_total) {
+        result.push(CategoryTreeNode {
+            name: "Uncategorized".into(),
+            color: DEFAULT_COLOR.into(),
+            text_color: palette::text_color(DEFAULT_COLOR),
+            id: None,
+            amount_cents: Some(cents),
+            category_id: Some(0),
+            transaction_count: Some(uncategorized_count),
+            drilldown_url: None,
+            direction,
+            children: Vec::new(),
+        });
+    }
+
+    // Requested bounds win; otherwise pin the links to the data's extent
+    let link_from = params.from_date.clone().or_else(|| actual_from.clone());
+    let link_to = params.to_date.clone().or_else(|| actual_to.clone());
+    if let (Some(from), Some(to)) = (link_from, link_to) {
+        for node in &mut result {
+            node.attach_drilldown_urls(&from, &to);
+        }
+    }
+
+    // Sort top-level by total spending (recursive sum of all descendants)
+    result.sort_by_key(|n| std::cmp::Reverse(tree_node_total(n)));
+
+    if result.is_empty() {
+        warn!("spending_by_category_tree: no spending data in selected period");
+    } else {
+        debug!(
+            top_level_nodes = result.len(),
+            "spending_by_category_tree: returning tree"
+        );
+    }
+
+    Ok(Json(CategoryTreeResponse {
+        categories: result,
+        from_date: actual_from,
+        to_date: actual_to,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MonthlyByCategoryParams {
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub category_ids: Option<String>,
+    /// "expenses", "income" or "net", see [`SpendingMode`].
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonthlyCategorySeries {
+    pub category: String,
+    pub color: String,
+    /// Black or white, whichever reads better on `color`.
+    pub text_color: &'static str,
+    /// Direction of the category's total over all months.
+    pub direction: FlowDirection,
+    /// Amount per month in `direction`; in net mode, months that went the
+    /// other way are negative.
+    pub totals: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonthlyByCategoryResponse {
+    pub months: Vec<String>,
+    pub series: Vec<MonthlyCategorySeries>,
+}
+
+pub async fn monthly_by_category(
+    State(state): State<AppState>,
+    Query(params): Query<MonthlyByCategoryParams>,
+) -> AppResult<Json<MonthlyByCategoryResponse>> {
+    debug!(
+        from_date = ?params.from_date,
+        to_date = ?params.to_date,
+        category_ids = ?params.category_ids,
+        "monthly_by_category: fetching data"
+    );
+    let mode = SpendingMode::parse(params.mode.as_deref())?;
+    let conn = state.db.get()?;
+
+    let selected_ids: std::collections::HashSet<i64> =
+        parse_category_ids(params.category_ids.as_deref())
+            .into_iter()
+            .collect();
+
+    if selected_ids.is_empty() {
+        debug!("monthly_by_category: no category IDs provided, returning empty");
+        return Ok(Json(MonthlyByCategoryResponse {
+            months: Vec::new(),
+            series: Vec::new(),
+        }));
+    }
+
+    let from_date_str = params.from_date.clone();
+    let to_date_str = params.to_date.clone();
+
+    let filter = transactions::TransactionFilter {
+        from_date: params.from_date,
+        to_date: params.to_date,
+        status: Some(TransactionStatus::Posted),
+        skip_duplicate_mirrors: true,
+        with_tags: false,
+        ..Default::default()
+    };
+
+    let transaction_list = transactions::list_transactions(&conn, &filter)?;
+    let all_categories = state.cached_categories()?;
+
+    let cat_map: std::collections::HashMap<i64, &crate::models::category::Category> =
+        all_categories.iter().map(|c| (c.id, c)).collect();
+
+    // Group by (category_id, month) → amount_cents
+    let mut data: std::collections::HashMap<i64, std::collections::HashMap<String, i64>> =
+        std::collections::HashMap::new();
+    let mut all_months: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
+
+    // Seed all months in the requested date range so gaps show as zero bars.
+    if let (Some(from), Some(to)) = (&from_date_str, &to_date_str) {
+        for month in all_months_in_range(from, to) {
+            all_months.insert(month);
+        }
+    }
+
+    for transaction in &transaction_list {
+        let cat_id = match transaction.transaction.category_id {
+            Some(id) if selected_ids.contains(&id) => id,
+            _ => continue,
+        };
+
+        let month = if transaction.transaction.date.len() >= 7 {
+            transaction.transaction.date[..7].to_string()
+        } else {
+            continue;
+        };
+
+        all_months.insert(month.clone());
+        *data.entry(cat_id).or_default().entry(month).or_insert(0) +=
+            transaction.transaction.amount_cents;
+    }
+
+    let months: Vec<String> = all_months.into_iter().collect();
+
+    // Build series, filtering by sign and normalising to positive values
+    let mut series: Vec<MonthlyCategorySeries> = Vec::new();
+    for &cat_id in &selected_ids {
+        let cat = match cat_map.get(&cat_id) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let month_totals = match data.get(&cat_id) {
+            Some(m) => m,
+            None => continue,
+        };
+
+        let raw_totals: Vec<i64> = months
+            .iter()
+            .map(|m| month_totals.get(m).copied().unwrap_or(0))
+            .collect();
+        let direction = match mode {
+            SpendingMode::Expenses => FlowDirection::Expense,
+            SpendingMode::Income => FlowDirection::Income,
+            SpendingMode::Net => FlowDirection::of(raw_totals.iter().sum()),
+        };
+        let totals: Vec<i64> = raw_totals
+            .iter()
+            .map(|&raw| match mode {
+                SpendingMode::Net => direction.signed(raw),
+                _ => mode.amount(raw).map_or(0, |(cents, _)| cents),
+            })
+            .collect();
+
+        // Skip categories with no data at all
+        if totals.iter().all(|&v| v == 0) {
+            continue;
+        }
+
+        series.push(MonthlyCategorySeries {
+            category: cat.name.clone(),
+            color: cat.color.clone(),
+            text_color: palette::text_color(&cat.color),
+            direction,
+            totals,
+        });
+    }
+
+    // Sort series by total spending descending
+    series.sort_by(|a, b| {
+        let sum_a: i64 = a.totals.iter().sum();
+        let sum_b: i64 = b.totals.iter().sum();
+        sum_b.cmp(&sum_a)
+    });
+
+    if series.is_empty() {
+        warn!(
+            selected = selected_ids.len(),
+            "monthly_by_category: no spending data for selected categories"
+        );
+    } else {
+        debug!(
+            months = months.len(),
+            series = series.len(),
+            "monthly_by_category: returning data"
+        );
+    }
+
+    Ok(Json(MonthlyByCategoryResponse { months, series }))
+}
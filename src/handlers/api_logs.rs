@@ -1,11 +1,13 @@
 use askama::Template;
 use axum::extract::{Path, Query, State};
-use axum::response::{Html, Json};
+use axum::response::{Html, Json, Redirect};
 use serde::{Deserialize, Serialize};
 
 use crate::db::queries::api_logs;
-use crate::error::{AppResult, RenderHtml};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash;
 use crate::models::{ApiLog, Settings};
+use crate::services::market_data_refresh;
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Template)]
@@ -17,6 +19,7 @@ pub struct ApiLogsTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub logs: Vec<ApiLog>,
     pub latest_log_id: i64,
 }
@@ -29,6 +32,7 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let logs = api_logs::get_all_logs(&conn, 100)?;
     let latest_log_id = api_logs::get_latest_log_id(&conn)?;
@@ -40,6 +44,7 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         logs,
         latest_log_id,
     };
@@ -56,7 +61,10 @@ pub struct ApiLogDetailTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub log: ApiLog,
+    /// Entries written by retrying this one, oldest first
+    pub retries: Vec<ApiLog>,
 }
 
 pub async fn detail(State(state): State<AppState>, Path(id): Path<i64>) -> AppResult<Html<String>> {
@@ -67,9 +75,11 @@ pub async fn detail(State(state): State<AppState>, Path(id): Path<i64>) -> AppRe
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let log = api_logs::get_log_by_id(&conn, id)?
-        .ok_or_else(|| crate::error::AppError::NotFound("API log not found".into()))?;
+        .ok_or_else(|| AppError::NotFound("API log not found".into()))?;
+    let retries = api_logs::get_retries(&conn, id)?;
 
     let template = ApiLogDetailTemplate {
         title: format!("API Log #{}", id),
@@ -78,12 +88,53 @@ pub async fn detail(State(state): State<AppState>, Path(id): Path<i64>) -> AppRe
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         log,
+        retries,
     };
 
     template.render_html()
 }
 
+/// The stored `request_params` of a `fetch_historical_quotes` entry
+#[derive(Deserialize)]
+struct FetchParams {
+    symbol: String,
+    start_date: String,
+    end_date: String,
+}
+
+/// Re-run the request of a log entry in the background, linking the new
+/// entry to this one. Only quote fetches can be retried.
+pub async fn retry(State(state): State<AppState>, Path(id): Path<i64>) -> AppResult<Redirect> {
+    let conn = state.db.get()?;
+    let log = api_logs::get_log_by_id(&conn, id)?
+        .ok_or_else(|| AppError::NotFound("API log not found".into()))?;
+    if !log.is_retryable() {
+        return Err(AppError::Validation(format!(
+            "Requests of action {} cannot be retried",
+            log.action
+        )));
+    }
+    let params: FetchParams = serde_json::from_str(&log.request_params)
+        .map_err(|e| AppError::Validation(format!("Invalid request parameters: {}", e)))?;
+
+    let redirect = Redirect::to(&format!("/trading/api-logs/{}", id));
+    if state.market_data_refresh.lock().unwrap().is_refreshing {
+        flash::flash_error("A market data refresh is already running");
+        return Ok(redirect);
+    }
+
+    market_data_refresh::spawn_symbol_fetch(
+        &state,
+        params.symbol,
+        vec![(params.start_date, params.end_date)],
+        Some(id),
+    );
+    flash::flash_success("Retry started; its log entry appears below when done");
+    Ok(redirect)
+}
+
 #[derive(Deserialize)]
 pub struct PollQuery {
     since_id: i64,
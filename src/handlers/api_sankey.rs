@@ -0,0 +1,583 @@
+//! Income and expense flows as a Sankey diagram: income categories flow into
+//! the budget, which flows out into expense categories.
+
+use axum::extract::{Query, State};
+use axum::response::Json;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::db::queries::categories::transfers_excluded_ids;
+use crate::db::queries::transaction_sums;
+use crate::error::AppResult;
+use crate::handlers::api::{AnalyticsParams, FlowDirection};
+use crate::handlers::sankey_accounts::{AccountLayer, ACCOUNT_COLOR};
+use crate::models::DEFAULT_COLOR;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct SankeyNode {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub depth: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SankeyLink {
+    pub source: String,
+    pub target: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SankeyResponse {
+    pub nodes: Vec<SankeyNode>,
+    pub links: Vec<SankeyLink>,
+    /// Earliest transaction date in the result set (YYYY-MM-DD)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_date: Option<String>,
+    /// Latest transaction date in the result set (YYYY-MM-DD)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_date: Option<String>,
+}
+
+pub async fn flow_sankey(
+    State(state): State<AppState>,
+    Query(params): Query<AnalyticsParams>,
+) -> AppResult<Json<SankeyResponse>> {
+    debug!(
+        from_date = ?params.from_date,
+        to_date = ?params.to_date,
+        "flow_sankey: fetching data"
+    );
+    let accounts_layer = params.accounts_layer()?;
+    let conn = state.db.get()?;
+
+    let agg = transaction_sums::sum_by_category_id_with_dates(
+        &conn,
+        params.from_date.as_deref(),
+        params.to_date.as_deref(),
+    )?;
+
+    let all_categories = state.cached_categories()?;
+
+    let cat_map: std::collections::HashMap<i64, &crate::models::category::Category> =
+        all_categories.iter().map(|c| (c.id, c)).collect();
+
+    let actual_from = agg.min_date;
+    let actual_to = agg.max_date;
+
+    debug!(
+        category_groups = agg.sums.len(),
+        categories = all_categories.len(),
+        "flow_sankey: loaded aggregate data"
+    );
+
+    let mut totals_by_id: std::collections::HashMap<Option<i64>, i64> =
+        std::collections::HashMap::new();
+    for (cat_id, total, _) in agg.sums {
+        totals_by_id.insert(cat_id, total);
+    }
+
+    // Build category hierarchy, excluding Transfers subtree
+    let excluded = transfers_excluded_ids(&all_categories);
+
+    let mut children_map: std::collections::HashMap<i64, Vec<i64>> =
+        std::collections::HashMap::new();
+    let mut top_level_ids: Vec<i64> = Vec::new();
+
+    for cat in &all_categories {
+        if excluded.contains(&cat.id) {
+            continue;
+        }
+        if let Some(parent_id) = cat.parent_id {
+            children_map.entry(parent_id).or_default().push(cat.id);
+        } else {
+            top_level_ids.push(cat.id);
+        }
+    }
+    top_level_ids.sort();
+
+    // Classify each category's net total as income or expense
+    let mut income_by_id: std::collections::HashMap<i64, i64> = std::collections::HashMap::new();
+    let mut expense_by_id: std::collections::HashMap<i64, i64> = std::collections::HashMap::new();
+    let mut uncategorized_income: i64 = 0;
+    let mut uncategorized_expense: i64 = 0;
+
+    for (&cat_id, &total) in &totals_by_id {
+        if total == 0 {
+            continue;
+        }
+        match cat_id {
+            Some(id) => {
+                if !cat_map.contains_key(&id) || excluded.contains(&id) {
+                    continue;
+                }
+                if total > 0 {
+                    income_by_id.insert(id, total);
+                } else {
+                    expense_by_id.insert(id, -total);
+                }
+            }
+            None => {
+                if total > 0 {
+                    uncategorized_income = total;
+                } else {
+                    uncategorized_expense = -total;
+                }
+            }
+        }
+    }
+
+    if income_by_id.is_empty()
+        && expense_by_id.is_empty()
+        && uncategorized_income == 0
+        && uncategorized_expense == 0
+    {
+        warn!("flow_sankey: no data in selected period");
+        return Ok(Json(SankeyResponse {
+            nodes: Vec::new(),
+            links: Vec::new(),
+            from_date: None,
+            to_date: None,
+        }));
+    }
+
+    // Build recursive tree for each top-level category, separately for
+    // income and expense.  Each node tracks its subtotal (direct +
+    // descendants) and max subtree depth so we can assign Sankey columns.
+    struct SankeyTreeNode<'a> {
+        cat: &'a crate::models::category::Category,
+        direct: i64,
+        children: Vec<SankeyTreeNode<'a>>,
+        subtotal: i64,
+        max_subtree_depth: u32,
+    }
+
+    fn build_sankey_tree<'a>(
+        cat_id: i64,
+        amount_map: &std::collections::HashMap<i64, i64>,
+        children_map: &std::collections::HashMap<i64, Vec<i64>>,
+        cat_map: &std::collections::HashMap<i64, &'a crate::models::category::Category>,
+    ) -> Option<SankeyTreeNode<'a>> {
+        let cat = cat_map.get(&cat_id)?;
+        let direct = amount_map.get(&cat_id).copied().unwrap_or(0);
+        let child_ids = children_map.get(&cat_id).cloned().unwrap_or_default();
+
+        let mut children: Vec<SankeyTreeNode<'a>> = child_ids
+            .iter()
+            .filter_map(|&id| build_sankey_tree(id, amount_map, children_map, cat_map))
+            .collect();
+        children.sort_by(|a, b| b.subtotal.cmp(&a.subtotal));
+
+        let child_sum: i64 = children.iter().map(|c| c.subtotal).sum();
+        let subtotal = direct + child_sum;
+        if subtotal == 0 {
+            return None;
+        }
+
+        let max_subtree_depth = children
+            .iter()
+            .map(|c| c.max_subtree_depth + 1)
+            .max()
+            .unwrap_or(0);
+
+        Some(SankeyTreeNode {
+            cat,
+            direct,
+            children,
+            subtotal,
+            max_subtree_depth,
+        })
+    }
+
+    let mut income_trees: Vec<SankeyTreeNode> = top_level_ids
+        .iter()
+        .filter_map(|&id| build_sankey_tree(id, &income_by_id, &children_map, &cat_map))
+        .collect();
+    income_trees.sort_by(|a, b| b.subtotal.cmp(&a.subtotal));
+
+    let mut expense_trees: Vec<SankeyTreeNode> = top_level_ids
+        .iter()
+        .filter_map(|&id| build_sankey_tree(id, &expense_by_id, &children_map, &cat_map))
+        .collect();
+    expense_trees.sort_by(|a, b| b.subtotal.cmp(&a.subtotal));
+
+    // Detect category names that appear on both income and expense sides.
+    // Without disambiguation, shared names create cycles in the DAG
+    // (e.g. "Expenses" → Budget → "Expenses").
+    fn collect_tree_names(node: &SankeyTreeNode, names: &mut std::collections::HashSet<String>) {
+        names.insert(node.cat.name.clone());
+        if !node.children.is_empty() {
+            for child in &node.children {
+                collect_tree_names(child, names);
+            }
+            if node.direct > 0 {
+                names.insert(format!("Other {}", node.cat.name));
+            }
+        }
+    }
+
+    let mut income_names: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut expense_names: std::collections::HashSet<String> = std::collections::HashSet::new();
+    for tree in &income_trees {
+        collect_tree_names(tree, &mut income_names);
+    }
+    for tree in &expense_trees {
+        collect_tree_names(tree, &mut expense_names);
+    }
+    let overlap: std::collections::HashSet<String> =
+        income_names.intersection(&expense_names).cloned().collect();
+
+    // Column layout based on actual max depth on each side:
+    //   Income leaves (col 0) ... income roots (col max_inc)
+    //   | Budget (col max_inc+1) |
+    //   expense roots (col max_inc+2) ... expense leaves (col max_inc+2+max_exp)
+    // The accounts layer adds a column on each side of Budget.
+    let account_cols = u32::from(accounts_layer);
+    let max_income_depth = income_trees
+        .iter()
+        .map(|t| t.max_subtree_depth)
+        .max()
+        .unwrap_or(0);
+    let income_root_col = max_income_depth;
+    let budget_depth = income_root_col + 1 + account_cols;
+    let expense_root_col = budget_depth + 1 + account_cols;
+
+    let mut layer = AccountLayer::default();
+    if accounts_layer {
+        layer = AccountLayer::load(
+            &conn,
+            params.from_date.as_deref(),
+            params.to_date.as_deref(),
+            &cat_map,
+            |cat_id| match cat_id {
+                Some(id) if income_by_id.contains_key(&id) => Some(FlowDirection::Income),
+                Some(id) if expense_by_id.contains_key(&id) => Some(FlowDirection::Expense),
+                None if uncategorized_income > 0 => Some(FlowDirection::Income),
+                None if uncategorized_expense > 0 => Some(FlowDirection::Expense),
+                _ => None,
+            },
+        )?;
+
+        let mut taken: std::collections::HashSet<String> = expense_names.clone();
+        taken.extend(income_names.iter().map(|name| {
+            if overlap.contains(name) {
+                format!("{} (In)", name)
+            } else {
+                name.clone()
+            }
+        }));
+        taken.extend(["Budget", "Uncategorized", "Uncategorized Expenses"].map(String::from));
+        let names: std::collections::HashMap<i64, String> = state
+            .cached_accounts()?
+            .into_iter()
+            .map(|a| (a.id, a.name))
+            .collect();
+        layer.label_accounts(&names, &taken);
+    }
+
+    let mut account_totals: std::collections::HashMap<Option<i64>, i64> =
+        std::collections::HashMap::new();
+
+    let budget_name = "Budget".to_string();
+    let mut nodes: Vec<SankeyNode> = Vec::new();
+    let mut links: Vec<SankeyLink> = Vec::new();
+    let mut node_names: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+    fn ensure_node(
+        nodes: &mut Vec<SankeyNode>,
+        seen: &mut std::collections::HashSet<String>,
+        name: &str,
+        color: &str,
+        depth: u32,
+    ) {
+        if seen.insert(name.to_string()) {
+            nodes.push(SankeyNode {
+                name: name.to_string(),
+                color: Some(color.to_string()),
+                depth,
+            });
+        }
+    }
+
+    // Recursively emit income nodes.  Income flows left-to-right:
+    // deepest leaves at col 0 → parents → roots → Budget.
+    // A node at tree_depth d gets Sankey col = max_depth - d.
+    // Names in `overlap` are suffixed with " (In)" to avoid cycles.
+    fn emit_income(
+        node: &SankeyTreeNode,
+        tree_depth: u32,
+        max_depth: u32,
+        nodes: &mut Vec<SankeyNode>,
+        links: &mut Vec<SankeyLink>,
+        seen: &mut std::collections::HashSet<String>,
+        overlap: &std::collections::HashSet<String>,
+    ) {
+        let col = max_depth - tree_depth;
+        let name = if overlap.contains(&node.cat.name) {
+            format!("{} (In)", node.cat.name)
+        } else {
+            node.cat.name.clone()
+        };
+        ensure_node(nodes, seen, &name, &node.cat.color, col);
+
+        if !node.children.is_empty() {
+            for child in &node.children {
+                emit_income(
+                    child,
+                    tree_depth + 1,
+                    max_depth,
+                    nodes,
+                    links,
+                    seen,
+                    overlap,
+                );
+                let child_name = if overlap.contains(&child.cat.name) {
+                    format!("{} (In)", child.cat.name)
+                } else {
+                    child.cat.name.clone()
+                };
+                links.push(SankeyLink {
+                    source: child_name,
+                    target: name.clone(),
+                    value: child.subtotal as f64 / 100.0,
+                });
+            }
+            if node.direct > 0 {
+                let other_base = format!("Other {}", node.cat.name);
+                let other = if overlap.contains(&other_base) {
+                    format!("{} (In)", other_base)
+                } else {
+                    other_base
+                };
+                let child_col = max_depth - (tree_depth + 1);
+                ensure_node(nodes, seen, &other, &node.cat.color, child_col);
+                links.push(SankeyLink {
+                    source: other,
+                    target: name.clone(),
+                    value: node.direct as f64 / 100.0,
+                });
+            }
+        }
+    }
+
+    // --- Income side ---
+    for tree in &income_trees {
+        emit_income(
+            tree,
+            0,
+            income_root_col,
+            &mut nodes,
+            &mut links,
+            &mut node_names,
+            &overlap,
+        );
+        let root_name = if overlap.contains(&tree.cat.name) {
+            format!("{} (In)", tree.cat.name)
+        } else {
+            tree.cat.name.clone()
+        };
+        if !accounts_layer {
+            links.push(SankeyLink {
+                source: root_name,
+                target: budget_name.clone(),
+                value: tree.subtotal as f64 / 100.0,
+            });
+            continue;
+        }
+        for (account_id, cents) in layer.income.flows(Some(tree.cat.id)) {
+            *account_totals.entry(account_id).or_default() += cents;
+            links.push(SankeyLink {
+                source: root_name.clone(),
+                target: layer.labels[&account_id].0.clone(),
+                value: cents as f64 / 100.0,
+            });
+        }
+    }
+
+    if uncategorized_income > 0 {
+        ensure_node(
+            &mut nodes,
+            &mut node_names,
+            "Uncategorized",
+            DEFAULT_COLOR,
+            income_root_col,
+        );
+        if accounts_layer {
+            for (account_id, cents) in layer.income.flows(None) {
+                *account_totals.entry(account_id).or_default() += cents;
+                links.push(SankeyLink {
+                    source: "Uncategorized".into(),
+                    target: layer.labels[&account_id].0.clone(),
+                    value: cents as f64 / 100.0,
+                });
+            }
+        } else {
+            links.push(SankeyLink {
+                source: "Uncategorized".into(),
+                target: budget_name.clone(),
+                value: uncategorized_income as f64 / 100.0,
+            });
+        }
+    }
+
+    // Income accounts → Budget
+    let mut income_accounts: Vec<(Option<i64>, i64)> = account_totals.drain().collect();
+    income_accounts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (account_id, cents) in income_accounts {
+        let name = &layer.labels[&account_id].0;
+        ensure_node(
+            &mut nodes,
+            &mut node_names,
+            name,
+            ACCOUNT_COLOR,
+            income_root_col + 1,
+        );
+        links.push(SankeyLink {
+            source: name.clone(),
+            target: budget_name.clone(),
+            value: cents as f64 / 100.0,
+        });
+    }
+
+    ensure_node(
+        &mut nodes,
+        &mut node_names,
+        &budget_name,
+        "#3b82f6",
+        budget_depth,
+    );
+
+    // Recursively emit expense nodes.  Expenses flow left-to-right:
+    // Budget → roots → parents → deepest leaves.
+    // A node at tree_depth d gets Sankey col = base_col + d.
+    fn emit_expense(
+        node: &SankeyTreeNode,
+        tree_depth: u32,
+        base_col: u32,
+        nodes: &mut Vec<SankeyNode>,
+        links: &mut Vec<SankeyLink>,
+        seen: &mut std::collections::HashSet<String>,
+    ) {
+        let col = base_col + tree_depth;
+        ensure_node(nodes, seen, &node.cat.name, &node.cat.color, col);
+
+        if !node.children.is_empty() {
+            for child in &node.children {
+                emit_expense(child, tree_depth + 1, base_col, nodes, links, seen);
+                links.push(SankeyLink {
+                    source: node.cat.name.clone(),
+                    target: child.cat.name.clone(),
+                    value: child.subtotal as f64 / 100.0,
+                });
+            }
+            if node.direct > 0 {
+                let other = format!("Other {}", node.cat.name);
+                ensure_node(
+                    nodes,
+                    seen,
+                    &other,
+                    &node.cat.color,
+                    base_col + tree_depth + 1,
+                );
+                links.push(SankeyLink {
+                    source: node.cat.name.clone(),
+                    target: other,
+                    value: node.direct as f64 / 100.0,
+                });
+            }
+        }
+    }
+
+    // --- Expense side ---
+    for tree in &expense_trees {
+        if accounts_layer {
+            for (account_id, cents) in layer.expense.flows(Some(tree.cat.id)) {
+                *account_totals.entry(account_id).or_default() += cents;
+                links.push(SankeyLink {
+                    source: layer.labels[&account_id].1.clone(),
+                    target: tree.cat.name.clone(),
+                    value: cents as f64 / 100.0,
+                });
+            }
+        } else {
+            links.push(SankeyLink {
+                source: budget_name.clone(),
+                target: tree.cat.name.clone(),
+                value: tree.subtotal as f64 / 100.0,
+            });
+        }
+        emit_expense(
+            tree,
+            0,
+            expense_root_col,
+            &mut nodes,
+            &mut links,
+            &mut node_names,
+        );
+    }
+
+    if uncategorized_expense > 0 {
+        let name = if node_names.contains("Uncategorized") {
+            "Uncategorized Expenses"
+        } else {
+            "Uncategorized"
+        };
+        ensure_node(
+            &mut nodes,
+            &mut node_names,
+            name,
+            DEFAULT_COLOR,
+            expense_root_col,
+        );
+        if accounts_layer {
+            for (account_id, cents) in layer.expense.flows(None) {
+                *account_totals.entry(account_id).or_default() += cents;
+                links.push(SankeyLink {
+                    source: layer.labels[&account_id].1.clone(),
+                    target: name.into(),
+                    value: cents as f64 / 100.0,
+                });
+            }
+        } else {
+            links.push(SankeyLink {
+                source: budget_name.clone(),
+                target: name.into(),
+                value: uncategorized_expense as f64 / 100.0,
+            });
+        }
+    }
+
+    // Budget → expense accounts
+    let mut expense_accounts: Vec<(Option<i64>, i64)> = account_totals.drain().collect();
+    expense_accounts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (account_id, cents) in expense_accounts {
+        let name = &layer.labels[&account_id].1;
+        ensure_node(
+            &mut nodes,
+            &mut node_names,
+            name,
+            ACCOUNT_COLOR,
+            budget_depth + 1,
+        );
+        links.push(SankeyLink {
+            source: budget_name.clone(),
+            target: name.clone(),
+            value: cents as f64 / 100.0,
+        });
+    }
+
+    debug!(
+        nodes = nodes.len(),
+        links = links.len(),
+        "flow_sankey: returning hierarchical data"
+    );
+
+    Ok(Json(SankeyResponse {
+        nodes,
+        links,
+        from_date: actual_from,
+        to_date: actual_to,
+    }))
+}
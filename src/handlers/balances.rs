@@ -1,13 +1,14 @@
 use askama::Template;
-use axum::extract::State;
-use axum::response::Html;
+use axum::extract::{Query, State};
+use axum::response::{Html, Json};
+use serde::{Deserialize, Serialize};
 
-use crate::db::queries::{balances, market_data, trading};
-use crate::error::{AppResult, RenderHtml};
-use crate::filters;
+use crate::db::queries::{balances, positions};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::handlers::trading_positions::enrich_position;
 use crate::models::account::{Account, AccountType};
-use crate::models::trading::PositionWithMarketData;
 use crate::models::Settings;
+use crate::services::cash_ledger;
 use crate::state::{AppState, JsManifest, PageBase};
 
 pub struct AccountBalance {
@@ -15,6 +16,15 @@ pub struct AccountBalance {
     pub balance_cents: i64,
     pub balance_formatted: String,
     pub balance_color: &'static str,
+    /// Cash part of the balance, for accounts that derive it from trading
+    pub cash_formatted: Option<String>,
+}
+
+/// An account whose cash ledger leaves some of its transactions out.
+pub struct IgnoredTransactionsWarning {
+    pub account_id: i64,
+    pub account_name: String,
+    pub count: usize,
 }
 
 #[derive(Template)]
@@ -26,11 +36,13 @@ pub struct BalancesTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub accounts: Vec<AccountBalance>,
     pub inactive_accounts: Vec<AccountBalance>,
     pub total_balance_cents: i64,
     pub total_balance_formatted: String,
     pub total_balance_color: &'static str,
+    pub ignored_transaction_warnings: Vec<IgnoredTransactionsWarning>,
 }
 
 fn gain_loss_color(cents: i64) -> &'static str {
@@ -51,39 +63,40 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let all_accounts = state.cached_accounts()?;
     let cash_balances = balances::get_cash_account_balances(&conn)?;
-
-    let currency = settings.currency.clone();
-    let locale = settings.locale.clone();
+    let ledgers = cash_ledger::replay(&conn)?;
 
     let mut account_balances: Vec<AccountBalance> = Vec::new();
+    let mut ignored_transaction_warnings = Vec::new();
 
     for account in all_accounts {
-        let balance_cents = match account.account_type {
-            AccountType::Cash => *cash_balances.get(&account.id).unwrap_or(&0),
+        let ledger = ledgers.accounts.get(&account.id);
+        if let Some(ledger) = ledger.filter(|l| l.ignored_transactions > 0) {
+            ignored_transaction_warnings.push(IgnoredTransactionsWarning {
+                account_id: account.id,
+                account_name: account.name.clone(),
+                count: ledger.ignored_transactions,
+            });
+        }
+
+        let positions_cents = match account.account_type {
+            AccountType::Cash | AccountType::CreditCard => {
+                *cash_balances.get(&account.id).unwrap_or(&0)
+            }
             AccountType::Securities => {
-                let positions = trading::get_positions_for_account(&conn, account.id)?;
+                let positions = positions::get_positions_for_account(
+                    &conn,
+                    account.id,
+                    settings.allow_short_positions,
+                )?;
 
                 let mut total: i64 = 0;
                 for pos in positions {
-                    // Try to get current market value
-                    let enriched =
-                        if let Ok(Some(data)) = market_data::get_latest_price(&conn, &pos.symbol) {
-                            PositionWithMarketData::with_market_data(
-                                pos,
-                                data.close_price_cents,
-                                data.date,
-                            )
-                        } else if let Ok(Some((price_cents, date))) =
-                            trading::get_last_trade_price(&conn, &pos.symbol)
-                        {
-                            PositionWithMarketData::with_approximated_price(pos, price_cents, date)
-                        } else {
-                            PositionWithMarketData::from_position(pos)
-                        };
+                    let enriched = enrich_position(&conn, pos, &settings);
 
                     total += enriched
                         .current_value_cents
@@ -92,10 +105,12 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
                 total
             }
         };
+        let balance_cents = positions_cents.saturating_add(ledger.map_or(0, |l| l.balance_cents));
 
         account_balances.push(AccountBalance {
-            balance_formatted: filters::format_money_balance(balance_cents, &currency, &locale),
+            balance_formatted: settings.format_money_balance(&balance_cents),
             balance_color: gain_loss_color(balance_cents),
+            cash_formatted: ledger.map(|l| settings.format_money_balance(&l.balance_cents)),
             account,
             balance_cents,
         });
@@ -111,6 +126,8 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         .map(|a| a.balance_cents)
         .sum();
 
+    let total_balance_formatted = settings.format_money_balance(&total_balance_cents);
+
     let template = BalancesTemplate {
         title: "Balances".into(),
         settings,
@@ -118,16 +135,74 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         accounts: active_accounts,
         inactive_accounts,
-        total_balance_formatted: filters::format_money_balance(
-            total_balance_cents,
-            &currency,
-            &locale,
-        ),
+        total_balance_formatted,
         total_balance_color: gain_loss_color(total_balance_cents),
         total_balance_cents,
+        ignored_transaction_warnings,
     };
 
     template.render_html()
 }
+
+#[derive(Debug, Deserialize)]
+pub struct BalancesApiParams {
+    /// Only count transactions on or before this date (`YYYY-MM-DD`).
+    pub as_of: Option<String>,
+}
+
+/// Cash balance of one account for external tools.
+#[derive(Debug, Serialize)]
+pub struct AccountBalanceEntry {
+    pub id: i64,
+    pub name: String,
+    pub account_type: AccountType,
+    pub currency: String,
+    pub balance_cents: i64,
+    pub last_transaction_date: Option<String>,
+}
+
+/// Balances of all active accounts: the sum of their posted transactions,
+/// or the cash ledger for accounts that derive their cash from trading.
+/// Unlike the balances page, positions are not valued.
+pub async fn api_balances(
+    State(state): State<AppState>,
+    Query(params): Query<BalancesApiParams>,
+) -> AppResult<Json<Vec<AccountBalanceEntry>>> {
+    let as_of = params.as_of.as_deref().filter(|s| !s.is_empty());
+    if let Some(date) = as_of {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| AppError::Validation(format!("Invalid date: {}", date)))?;
+    }
+
+    let settings = state.load_settings()?;
+    let conn = state.db.get()?;
+    let mut totals = balances::get_account_totals(&conn, as_of)?;
+    let ledgers = cash_ledger::replay_until(&conn, as_of)?;
+
+    let entries = state
+        .cached_accounts()?
+        .into_iter()
+        .filter(|account| account.active)
+        .map(|account| {
+            let (sum_cents, last_date) = totals
+                .remove(&account.id)
+                .map_or((0, None), |(sum, date)| (sum, Some(date)));
+            let balance_cents = ledgers
+                .accounts
+                .get(&account.id)
+                .map_or(sum_cents, |l| l.balance_cents);
+            AccountBalanceEntry {
+                id: account.id,
+                name: account.name,
+                account_type: account.account_type,
+                currency: settings.currency.clone(),
+                balance_cents,
+                last_transaction_date: last_date,
+            }
+        })
+        .collect();
+    Ok(Json(entries))
+}
@@ -0,0 +1,197 @@
+use askama::Template;
+use axum::extract::{Query, State};
+use axum::response::{Html, IntoResponse};
+use axum::Json;
+use chrono::Datelike;
+use serde::Deserialize;
+
+use crate::date_utils;
+use crate::db::queries::trading;
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::handlers::position_export::{check_export_format, csv_response};
+use crate::models::{Settings, TradingActivity};
+use crate::services::analytics::format_cents;
+use crate::services::capital_gains::{self, CapitalGain};
+use crate::state::{AppState, JsManifest, PageBase};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CapitalGainsParams {
+    /// Tax year; defaults to the current year.
+    pub year: Option<String>,
+    /// Export format; only `csv` is supported.
+    pub format: Option<String>,
+}
+
+impl CapitalGainsParams {
+    fn year(&self, settings: &Settings) -> AppResult<i32> {
+        match self.year.as_deref().map(str::trim) {
+            None | Some("") => Ok(date_utils::today_in(settings).year()),
+            Some(s) => s
+                .parse()
+                .ok()
+                .filter(|y| (1900..=9999).contains(y))
+                .ok_or_else(|| AppError::Validation(format!("Invalid year: {}", s))),
+        }
+    }
+}
+
+fn all_activities(state: &AppState) -> AppResult<Vec<TradingActivity>> {
+    let conn = state.db.get()?;
+    Ok(trading::list_activities(
+        &conn,
+        &trading::TradingActivityFilter {
+            sort_sql: Some("date ASC".to_string()),
+            ..Default::default()
+        },
+    )?)
+}
+
+#[derive(Template)]
+#[template(path = "pages/capital_gains.html")]
+pub struct CapitalGainsTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub year: i32,
+    /// Years with sales, newest first, always including `year`.
+    pub years: Vec<i32>,
+    pub gains: Vec<CapitalGain>,
+    pub total_gain_formatted: String,
+    pub total_gain_color: &'static str,
+    pub wash_sale_count: usize,
+    pub wash_sale_loss_formatted: String,
+}
+
+pub async fn index(
+    State(state): State<AppState>,
+    Query(params): Query<CapitalGainsParams>,
+) -> AppResult<Html<String>> {
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+    let year = params.year(&settings)?;
+    let activities = all_activities(&state)?;
+
+    let mut years = capital_gains::sale_years(&activities);
+    if !years.contains(&year) {
+        years.push(year);
+        years.sort_unstable_by(|a, b| b.cmp(a));
+    }
+    let gains = capital_gains::capital_gains(&activities, year, settings.wash_sale_window_days);
+
+    let total_gain: i64 = gains.iter().map(|g| g.sale.gain_cents).sum();
+    let total_gain_color = if total_gain > 0 {
+        "text-green-600 dark:text-green-400"
+    } else if total_gain < 0 {
+        "text-red-600 dark:text-red-400"
+    } else {
+        "text-neutral-600 dark:text-neutral-400"
+    };
+    let wash_sales: Vec<&CapitalGain> = gains.iter().filter(|g| g.is_wash_sale()).collect();
+    let wash_sale_loss: i64 = wash_sales.iter().map(|g| -g.sale.gain_cents).sum();
+
+    let template = CapitalGainsTemplate {
+        title: "Capital Gains".into(),
+        total_gain_formatted: settings.format_money_plain(&total_gain),
+        total_gain_color,
+        wash_sale_count: wash_sales.len(),
+        wash_sale_loss_formatted: settings.format_money_neutral(&wash_sale_loss),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        year,
+        years,
+        gains,
+    };
+
+    template.render_html()
+}
+
+const CAPITAL_GAIN_HEADERS: [&str; 12] = [
+    "activity_id",
+    "date",
+    "symbol",
+    "quantity",
+    "proceeds",
+    "cost_basis",
+    "fee",
+    "gain",
+    "currency",
+    "wash_sale",
+    "repurchase_date",
+    "repurchase_quantity",
+];
+
+fn capital_gain_record(gain: &CapitalGain) -> Vec<String> {
+    let sale = &gain.sale;
+    vec![
+        sale.activity_id.to_string(),
+        sale.date.clone(),
+        gain.symbol.clone(),
+        sale.quantity.to_string(),
+        format_cents(sale.proceeds_cents),
+        format_cents(sale.cost_basis_cents),
+        format_cents(sale.fee_cents),
+        format_cents(sale.gain_cents),
+        gain.currency.clone(),
+        gain.is_wash_sale().to_string(),
+        gain.repurchase_date().unwrap_or_default().to_string(),
+        if gain.is_wash_sale() {
+            gain.repurchase_quantity().to_string()
+        } else {
+            String::new()
+        },
+    ]
+}
+
+/// Export the sales of a year as CSV, in the order of the report.
+pub async fn export(
+    State(state): State<AppState>,
+    Query(params): Query<CapitalGainsParams>,
+) -> AppResult<impl IntoResponse> {
+    check_export_format(params.format.as_deref())?;
+    let settings = state.load_settings()?;
+    let year = params.year(&settings)?;
+    let gains = capital_gains::capital_gains(
+        &all_activities(&state)?,
+        year,
+        settings.wash_sale_window_days,
+    );
+
+    let records = gains.iter().map(capital_gain_record).collect();
+    csv_response(
+        &format!("capital_gains_{}.csv", year),
+        &CAPITAL_GAIN_HEADERS,
+        records,
+    )
+}
+
+/// Loss sales of a year with a repurchase of the same symbol within the
+/// configured window.
+pub async fn wash_sales(
+    State(state): State<AppState>,
+    Query(params): Query<CapitalGainsParams>,
+) -> AppResult<Json<Vec<CapitalGain>>> {
+    let settings = state.load_settings()?;
+    let year = params.year(&settings)?;
+    let gains = capital_gains::capital_gains(
+        &all_activities(&state)?,
+        year,
+        settings.wash_sale_window_days,
+    );
+    Ok(Json(
+        gains.into_iter().filter(|g| g.is_wash_sale()).collect(),
+    ))
+}
@@ -1,15 +1,18 @@
 use askama::Template;
 use axum::extract::{Path, Query, State};
-use axum::response::{Html, IntoResponse, Redirect};
-use axum::Form;
-use serde::Deserialize;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{Form, Json};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::db::queries::{categories, transactions};
+use crate::confirmation::{confirmation_pending, deletion_message, ConfirmParams, DeletedCounts};
+use crate::db::queries::{categories, settings, transactions};
 use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash;
 use crate::models::{
-    Category, CategoryWithPath, NewCategory, Settings, DEFAULT_COLOR, DEFAULT_ICON, TAG_PALETTE,
+    Category, CategoryWithPath, NewCategory, Settings, DEFAULT_COLOR, DEFAULT_ICON,
 };
+use crate::palette;
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Template)]
@@ -21,6 +24,7 @@ pub struct CategoryFormTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub categories: Vec<CategoryWithPath>,
     pub editing: Option<Category>,
     pub prefill: Option<Category>,
@@ -37,6 +41,7 @@ pub struct CategoryDetailTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub category: CategoryWithPath,
     pub transaction_count: i64,
     pub children: Vec<CategoryWithPath>,
@@ -69,6 +74,7 @@ pub async fn new_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let cats = state.cached_categories_with_path()?;
 
@@ -86,10 +92,11 @@ pub async fn new_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         categories: cats,
         editing: None,
         prefill,
-        palette: TAG_PALETTE,
+        palette: palette::PALETTE,
         back_url: "/manage?tab=categories".into(),
     };
 
@@ -115,6 +122,7 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let cats = state.cached_categories_with_path()?;
 
@@ -126,10 +134,11 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         categories: cats,
         editing: Some(category),
         prefill: None,
-        palette: TAG_PALETTE,
+        palette: palette::PALETTE,
         back_url,
     };
 
@@ -148,6 +157,7 @@ pub async fn show(State(state): State<AppState>, Path(id): Path<i64>) -> AppResu
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let filter = transactions::TransactionFilter {
@@ -169,6 +179,7 @@ pub async fn show(State(state): State<AppState>, Path(id): Path<i64>) -> AppResu
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         category,
         transaction_count,
         children,
@@ -177,16 +188,35 @@ pub async fn show(State(state): State<AppState>, Path(id): Path<i64>) -> AppResu
     template.render_html()
 }
 
+/// Validate a submitted color as hex; an empty or missing one is `None`.
+fn parse_color(color: Option<&str>) -> AppResult<Option<String>> {
+    match color.map(str::trim).filter(|c| !c.is_empty()) {
+        None => Ok(None),
+        Some(c) => palette::normalize_hex(c)
+            .map(Some)
+            .ok_or_else(|| AppError::Validation(format!("Invalid color: {}", c))),
+    }
+}
+
 pub async fn create(
     State(state): State<AppState>,
     Form(form): Form<CategoryFormData>,
 ) -> AppResult<Redirect> {
     let conn = state.db.get()?;
 
+    // Without a color, pick the palette color fewest categories use
+    let color = match parse_color(form.color.as_deref())? {
+        Some(color) => color,
+        None => {
+            let existing = categories::list_categories(&conn)?;
+            palette::least_used(existing.iter().map(|c| c.color.as_str())).to_string()
+        }
+    };
+
     let new_category = NewCategory {
         name: form.name,
         parent_id: form.parent_id,
-        color: form.color.unwrap_or_else(|| DEFAULT_COLOR.into()),
+        color,
         icon: form.icon.unwrap_or_else(|| DEFAULT_ICON.into()),
     };
 
@@ -198,7 +228,7 @@ pub async fn create(
 /// Walk the ancestor chain of `proposed_parent_id`; if we encounter
 /// `category_id` it means setting this parent would create a cycle.
 fn check_circular_parent(
-    conn: &r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>,
+    conn: &rusqlite::Connection,
     category_id: i64,
     proposed_parent_id: Option<i64>,
 ) -> AppResult<()> {
@@ -240,7 +270,7 @@ pub async fn update(
     let new_category = NewCategory {
         name: form.name,
         parent_id: form.parent_id,
-        color: form.color.unwrap_or_else(|| DEFAULT_COLOR.into()),
+        color: parse_color(form.color.as_deref())?.unwrap_or_else(|| DEFAULT_COLOR.into()),
         icon: form.icon.unwrap_or_else(|| DEFAULT_ICON.into()),
     };
 
@@ -268,7 +298,7 @@ pub async fn update_form(
     let new_category = NewCategory {
         name: form.name,
         parent_id: form.parent_id,
-        color: form.color.unwrap_or_else(|| DEFAULT_COLOR.into()),
+        color: parse_color(form.color.as_deref())?.unwrap_or_else(|| DEFAULT_COLOR.into()),
         icon: form.icon.unwrap_or_else(|| DEFAULT_ICON.into()),
     };
 
@@ -277,29 +307,83 @@ pub async fn update_form(
     Ok(Redirect::to(&format!("/categories/{}", id)))
 }
 
-pub async fn delete(State(state): State<AppState>, Path(id): Path<i64>) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-
-    let category = categories::get_category(&conn, id)?;
-    if category.map(|c| c.built_in).unwrap_or(false) {
-        return Err(AppError::Validation(
-            "Built-in categories cannot be deleted".into(),
-        ));
-    }
+/// Give all categories fresh palette colors, cycling through the palette
+/// in tree order so that neighbours differ.
+pub async fn repalette(State(state): State<AppState>) -> AppResult<Redirect> {
+    let count = state.with_tx(|tx| {
+        let cats = categories::list_categories_with_path(tx)?;
+        for (cat, color) in cats.iter().zip(palette::distribute(cats.len())) {
+            categories::set_category_color(tx, cat.category.id, color)?;
+        }
+        Ok(cats.len())
+    })?;
+    flash::flash_success(format!("Recolored {} categories", count));
+    Ok(Redirect::to("/manage?tab=categories"))
+}
 
-    categories::delete_category(&conn, id)?;
+#[derive(Serialize)]
+pub struct PaletteColor {
+    pub name: &'static str,
+    pub color: &'static str,
+    pub text_color: &'static str,
+}
 
-    Ok(Html(String::new()))
+/// The curated color palette with a readable text color for each entry.
+pub async fn palette_colors() -> Json<Vec<PaletteColor>> {
+    Json(
+        palette::PALETTE
+            .iter()
+            .map(|&(name, color)| PaletteColor {
+                name,
+                color,
+                text_color: palette::text_color(color),
+            })
+            .collect(),
+    )
 }
 
-pub async fn delete_all(State(state): State<AppState>) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
+pub async fn delete(State(state): State<AppState>, Path(id): Path<i64>) -> AppResult<Html<String>> {
+    state.with_tx(|tx| {
+        let category = categories::get_category(tx, id)?;
+        if category.map(|c| c.built_in).unwrap_or(false) {
+            return Err(AppError::Validation(
+                "Built-in categories cannot be deleted".into(),
+            ));
+        }
 
-    categories::delete_all_categories(&conn)?;
+        categories::delete_category(tx, id)?;
+        settings::clear_missing_references(tx)?;
+        Ok(())
+    })?;
 
     Ok(Html(String::new()))
 }
 
+pub async fn delete_all(
+    State(state): State<AppState>,
+    Query(params): Query<ConfirmParams>,
+) -> AppResult<Response> {
+    let pending = confirmation_pending(&state, "categories", &params, || {
+        let conn = state.db.get()?;
+        let count = categories::count_custom_categories(&conn)?;
+        Ok(deletion_message(&[(
+            count as usize,
+            "category",
+            "categories",
+        )]))
+    })?;
+    if let Some(pending) = pending {
+        return Ok(pending);
+    }
+    let count = state.with_tx(|tx| {
+        let count = categories::delete_all_categories(tx)?;
+        settings::clear_missing_references(tx)?;
+        Ok(count)
+    })?;
+
+    Ok(DeletedCounts::single("categories", count).into_response())
+}
+
 pub async fn unset_transactions(
     State(state): State<AppState>,
     Path(id): Path<i64>,
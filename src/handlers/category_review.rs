@@ -0,0 +1,182 @@
+//! Review of categories assigned by rules.
+//!
+//! Rules that categorize a transaction during import or when applied mark it
+//! as auto-categorized. The review page lists those transactions grouped by
+//! the rule, to accept a rule's assignments at once or fix single ones;
+//! either clears the mark.
+
+use std::collections::HashMap;
+
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::{Html, Redirect};
+use axum::Form;
+use serde::Deserialize;
+
+use crate::db::queries::{rules, transactions};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash;
+use crate::models::{CategoryWithPath, Settings, TransactionWithRelations};
+use crate::state::{AppState, JsManifest, PageBase};
+
+/// Transactions listed per rule; the rest are counted.
+const ROWS_PER_RULE: usize = 50;
+
+/// Unreviewed transactions categorized by one rule.
+pub struct ReviewGroup {
+    /// `None` for rules deleted since
+    pub rule_id: Option<i64>,
+    pub rule_name: Option<String>,
+    pub total_count: usize,
+    pub transactions: Vec<TransactionWithRelations>,
+}
+
+impl ReviewGroup {
+    pub fn hidden_count(&self) -> usize {
+        self.total_count - self.transactions.len()
+    }
+
+    /// Value of the accept form's `rule_id` field; empty for deleted rules.
+    pub fn rule_id_value(&self) -> String {
+        self.rule_id.map(|id| id.to_string()).unwrap_or_default()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "pages/transaction_review.html")]
+pub struct TransactionReviewTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub groups: Vec<ReviewGroup>,
+    pub total_count: usize,
+    pub categories: Vec<CategoryWithPath>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptForm {
+    /// Accept the group of this rule; empty for the group of deleted rules,
+    /// absent to accept everything.
+    #[serde(default)]
+    pub rule_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FixForm {
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub category_id: Option<i64>,
+}
+
+/// Unreviewed transactions grouped by rule, largest groups first.
+fn review_groups(conn: &rusqlite::Connection) -> AppResult<Vec<ReviewGroup>> {
+    let filter = transactions::TransactionFilter {
+        auto_categorized_only: true,
+        with_tags: false,
+        ..Default::default()
+    };
+    let rule_names: HashMap<i64, String> = rules::list_rules(conn)?
+        .into_iter()
+        .map(|r| (r.id, r.name))
+        .collect();
+
+    let mut groups: Vec<ReviewGroup> = Vec::new();
+    for t in transactions::list_transactions(conn, &filter)? {
+        let rule_id = t.transaction.auto_category_rule_id;
+        let group = match groups.iter().position(|g| g.rule_id == rule_id) {
+            Some(i) => &mut groups[i],
+            None => {
+                groups.push(ReviewGroup {
+                    rule_id,
+                    rule_name: rule_id.and_then(|id| rule_names.get(&id).cloned()),
+                    total_count: 0,
+                    transactions: Vec::new(),
+                });
+                groups.last_mut().unwrap()
+            }
+        };
+        group.total_count += 1;
+        if group.transactions.len() < ROWS_PER_RULE {
+            group.transactions.push(t);
+        }
+    }
+    groups.sort_by_key(|g| std::cmp::Reverse(g.total_count));
+    Ok(groups)
+}
+
+pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    let groups = review_groups(&conn)?;
+    let template = TransactionReviewTemplate {
+        title: "Review Categories".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        total_count: groups.iter().map(|g| g.total_count).sum(),
+        groups,
+        categories: state.cached_categories_with_path()?,
+    };
+    template.render_html()
+}
+
+/// Accept the categories of one rule's group, or of all groups.
+pub async fn accept(
+    State(state): State<AppState>,
+    Form(form): Form<AcceptForm>,
+) -> AppResult<Redirect> {
+    let conn = state.db.get()?;
+    let rule_id = match form.rule_id.as_deref().map(str::trim) {
+        None => None,
+        Some("") => Some(None),
+        Some(v) => Some(Some(v.parse::<i64>().map_err(|_| {
+            AppError::Validation(format!("Invalid rule id '{}'", v))
+        })?)),
+    };
+
+    let filter = transactions::TransactionFilter {
+        auto_categorized_only: true,
+        with_tags: false,
+        ..Default::default()
+    };
+    let ids: Vec<i64> = transactions::list_transactions(&conn, &filter)?
+        .iter()
+        .filter(|t| rule_id.is_none_or(|id| t.transaction.auto_category_rule_id == id))
+        .map(|t| t.transaction.id)
+        .collect();
+    let accepted = transactions::accept_auto_categories(&conn, &ids)?;
+
+    flash::flash_success(format!("Accepted {} categorization(s)", accepted));
+    Ok(Redirect::to("/transactions/review"))
+}
+
+/// Set the right category of one transaction. Returns an empty row, as the
+/// transaction leaves the review list.
+pub async fn fix(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<FixForm>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    if !transactions::set_reviewed_category(&conn, id, form.category_id)? {
+        return Err(AppError::NotFound("Transaction not found".into()));
+    }
+    Ok(Html(String::new()))
+}
@@ -1,11 +1,19 @@
 use askama::Template;
-use axum::extract::State;
-use axum::response::Html;
+use axum::extract::{Query, State};
+use axum::response::{Html, IntoResponse, Json, Redirect, Response};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::Connection;
+use serde::Deserialize;
 use tracing::debug;
 
-use crate::db::queries::transactions;
-use crate::error::{AppResult, RenderHtml};
+use crate::date_utils;
+use crate::db::queries::digest::{self, Digest};
+use crate::db::queries::{accounts, settings as db_settings, transaction_sums, transactions};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::handlers::trading_positions::count_stale_positions;
 use crate::models::{Settings, TransactionWithRelations};
+use crate::services::card_cycle::{self, CycleSummary};
+use crate::services::interest::{self, InterestBasis};
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Template)]
@@ -17,10 +25,76 @@ pub struct DashboardTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub recent_transactions: Vec<TransactionWithRelations>,
     pub total_this_month: i64,
     pub total_last_month: i64,
     pub transaction_count: i64,
+    /// Changes since the previous dashboard visit, if there were any.
+    pub digest: Option<Digest>,
+    /// Interest expected over the next 12 months across active accounts
+    /// with an interest rate; `None` if no account has one.
+    pub projected_interest_cents: Option<i64>,
+    /// Number of open positions valued with a stale or approximated price.
+    pub stale_price_count: usize,
+    /// Number of rule-assigned categories waiting for review.
+    pub auto_categorized_count: i64,
+    /// Credit card statements with an amount due, soonest first.
+    pub card_payments: Vec<CycleSummary>,
+}
+
+/// Settings key holding when the dashboard was last opened (UTC).
+const LAST_SEEN_KEY: &str = "dashboard_last_seen_at";
+
+/// Format of SQLite's `datetime('now')`, used by all `created_at` columns.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Deserialize)]
+pub struct DigestParams {
+    pub since: Option<String>,
+}
+
+/// Normalize a `since` parameter (RFC 3339, `YYYY-MM-DD HH:MM:SS` in UTC,
+/// or a plain date) to the format of the `created_at` columns.
+fn parse_since(value: &str) -> AppResult<String> {
+    let value = value.trim();
+    let parsed = DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc).naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default())
+        })
+        .map_err(|_| AppError::Validation(format!("Invalid since timestamp: {}", value)))?;
+    Ok(parsed.format(TIMESTAMP_FORMAT).to_string())
+}
+
+/// Digest since the last visit, then move the marker to now.
+fn take_visit_digest(state: &AppState, conn: &Connection) -> AppResult<Option<Digest>> {
+    let digest = match db_settings::get_setting(conn, LAST_SEEN_KEY)? {
+        Some(since) => Some(digest::get_digest(conn, &since)?)
+            .filter(|d| !d.is_empty())
+            .map(|d| {
+                state
+                    .cached_net_worth()
+                    .map(|nw| d.with_net_worth_change(&nw))
+            })
+            .transpose()?,
+        None => None,
+    };
+    let now = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+    db_settings::set_setting(conn, LAST_SEEN_KEY, &now)?;
+    Ok(digest)
+}
+
+/// `GET /`: the dashboard, or a redirect to the start page chosen in the settings.
+pub async fn start(State(state): State<AppState>) -> AppResult<Response> {
+    let path = state.load_settings()?.start_page_path();
+    if path == "/dashboard" {
+        return Ok(index(State(state)).await?.into_response());
+    }
+    Ok(Redirect::to(path).into_response())
 }
 
 pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
@@ -33,27 +107,36 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
-    let now = chrono::Local::now();
-    let this_month_start = now.format("%Y-%m-01").to_string();
-    let last_month = now - chrono::Duration::days(30);
+    let today = date_utils::today_in(&settings);
+    let this_month_start = today.format("%Y-%m-01").to_string();
+    let last_month = today - chrono::Duration::days(30);
     let last_month_start = last_month.format("%Y-%m-01").to_string();
-    let last_month_end = now.format("%Y-%m-01").to_string();
+    let last_month_end = today.format("%Y-%m-01").to_string();
 
     let filter = transactions::TransactionFilter {
         limit: Some(5),
+        with_tags: false,
         ..Default::default()
     };
     let recent_transactions = transactions::list_transactions(&conn, &filter)?;
 
-    let total_this_month = transactions::sum_amount_cents(&conn, Some(&this_month_start), None)?;
+    let total_this_month =
+        transaction_sums::sum_amount_cents(&conn, Some(&this_month_start), None)?;
     let total_last_month =
-        transactions::sum_amount_cents(&conn, Some(&last_month_start), Some(&last_month_end))?;
+        transaction_sums::sum_amount_cents(&conn, Some(&last_month_start), Some(&last_month_end))?;
 
     let transaction_count =
         transactions::count_transactions(&conn, &transactions::TransactionFilter::default())?;
 
+    let digest = take_visit_digest(&state, &conn)?;
+    let projected_interest_cents = projected_interest(&conn, today)?;
+    let stale_price_count = count_stale_positions(&conn, &settings)?;
+    let auto_categorized_count = transactions::count_auto_categorized(&conn)?;
+    let card_payments = card_cycle::upcoming_payments(&conn, today)?;
+
     debug!(
         transaction_count = transaction_count,
         total_this_month = total_this_month,
@@ -68,11 +151,67 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         recent_transactions,
         total_this_month,
         total_last_month,
         transaction_count,
+        digest,
+        projected_interest_cents,
+        stale_price_count,
+        auto_categorized_count,
+        card_payments,
     };
 
     template.render_html()
 }
+
+/// Sum of the 12-month projections of all active interest-bearing accounts.
+fn projected_interest(conn: &Connection, today: NaiveDate) -> AppResult<Option<i64>> {
+    let mut total = None;
+    for account in accounts::list_accounts(conn)? {
+        let Some(rate_bps) = account.interest_rate_bps.filter(|_| account.active) else {
+            continue;
+        };
+        let projection =
+            interest::project_account(conn, &account, rate_bps, InterestBasis::Current, 12, today)?;
+        *total.get_or_insert(0) += projection.total_projected_cents;
+    }
+    Ok(total)
+}
+
+/// Changes since `since`, or since the last dashboard visit if omitted.
+pub async fn digest(
+    State(state): State<AppState>,
+    Query(params): Query<DigestParams>,
+) -> AppResult<Json<Digest>> {
+    let conn = state.db.get()?;
+    let since = match params.since.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(since) => parse_since(since)?,
+        None => db_settings::get_setting(&conn, LAST_SEEN_KEY)?.ok_or_else(|| {
+            AppError::Validation("No since timestamp given and no previous visit recorded".into())
+        })?,
+    };
+    let net_worth = state.cached_net_worth()?;
+    let digest = digest::get_digest(&conn, &since)?.with_net_worth_change(&net_worth);
+    Ok(Json(digest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            parse_since("2024-03-10T12:30:00+02:00").unwrap(),
+            "2024-03-10 10:30:00"
+        );
+        assert_eq!(
+            parse_since("2024-03-10 12:30:00").unwrap(),
+            "2024-03-10 12:30:00"
+        );
+        assert_eq!(parse_since("2024-03-10").unwrap(), "2024-03-10 00:00:00");
+        assert!(parse_since("last tuesday").is_err());
+    }
+}
@@ -1,19 +1,28 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 use askama::Template;
 use axum::extract::{Multipart, Path, Query, State};
 use axum::response::{Html, Redirect};
-use axum::Form;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use regex::RegexBuilder;
-
-use crate::db::queries::{categories, import, rules, tags, transactions};
-use crate::error::{html_escape, AppError, AppResult, RenderHtml};
+use crate::body_limit;
+use crate::date_utils::DateFormat;
+use crate::db::queries::{import, rules, tags, transactions};
+use crate::error::{AppError, AppResult, RenderHtml};
 use crate::models::{
-    CategoryWithPath, ImportSession, ImportStatus, NewTransaction, RuleActionType, Settings,
+    CategoryWithPath, ImportFileStats, ImportRow, ImportSession, ImportStatus, NewTransaction,
+    OpenImportSession, Settings, Tag, TransactionStatus,
 };
 use crate::services::csv_parser::parse_csv;
+use crate::services::import_matching;
+use crate::services::import_overlap::OverlapFilter;
+use crate::services::import_preview::{self, CategoryImpact, PayeeGroup};
+use crate::services::money;
+use crate::services::notify::{self, Severity};
+use crate::services::transfer_detection;
 use crate::state::{AppState, JsManifest, PageBase};
 
 const PREVIEW_PAGE_SIZE: i64 = 50;
@@ -29,6 +38,22 @@ pub struct ImportTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
+    /// Number of sessions that can be resumed, see [`open_sessions`].
+    pub open_session_count: usize,
+}
+
+#[derive(Template)]
+#[template(path = "pages/import_sessions.html")]
+pub struct ImportSessionsTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub sessions: Vec<OpenImportSession>,
 }
 
 #[derive(Template)]
@@ -40,6 +65,7 @@ pub struct ImportFormatTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
 }
 
 #[derive(Template)]
@@ -51,8 +77,11 @@ pub struct ImportWizardTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub session: ImportSession,
     pub categories: Vec<CategoryWithPath>,
+    pub tags: Vec<Tag>,
+    pub resumable: bool,
 }
 
 #[derive(Template)]
@@ -61,19 +90,31 @@ pub struct ImportStatusTemplate {
     pub icons: crate::filters::Icons,
     pub session: ImportSession,
     pub categories: Vec<CategoryWithPath>,
+    pub tags: Vec<Tag>,
+    /// The import was interrupted and confirming again continues it.
+    pub resumable: bool,
 }
 
 #[derive(Template)]
 #[template(path = "partials/import_preview_table.html")]
 pub struct ImportPreviewTableTemplate {
     pub session_id: String,
-    pub rows: Vec<crate::models::ImportRow>,
+    pub rows: Vec<ImportRow>,
     pub categories: Vec<CategoryWithPath>,
+    pub tags: Vec<Tag>,
     pub page: i64,
     pub page_size: i64,
     pub total_count: i64,
 }
 
+#[derive(Template)]
+#[template(path = "partials/import_payee_groups.html")]
+pub struct ImportPayeeGroupsTemplate {
+    pub session_id: String,
+    pub groups: Vec<PayeeGroup>,
+    pub categories: Vec<CategoryWithPath>,
+}
+
 #[derive(Template)]
 #[template(path = "partials/import_result.html")]
 pub struct ImportResultTemplate {
@@ -88,15 +129,8 @@ pub struct ImportResultTemplate {
 #[derive(Debug, Deserialize)]
 pub struct PageQuery {
     pub page: Option<i64>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct CategoryForm {
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub category_id: Option<i64>,
+    /// `payee` lists the pending rows grouped by payee instead.
+    pub group: Option<String>,
 }
 
 // Status response for JSON endpoint
@@ -108,6 +142,11 @@ pub struct StatusResponse {
     pub processed_rows: i64,
     pub error_count: i64,
     pub progress_percent: i64,
+    pub resumable: bool,
+    pub resume_from_row_index: i64,
+    pub file_stats: Vec<ImportFileStats>,
+    /// Projected spending per month and category, while in preview.
+    pub category_impact: Vec<CategoryImpact>,
 }
 
 // Handlers
@@ -119,7 +158,9 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
+    let conn = state.db.get()?;
 
     let template = ImportTemplate {
         title: "Import".into(),
@@ -128,11 +169,51 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
+        open_session_count: resumable_sessions(&state, &conn)?.len(),
+    };
+
+    template.render_html()
+}
+
+/// List the sessions left unconfirmed, e.g. after the browser was closed
+/// mid-wizard, with links back to the wizard.
+pub async fn open_sessions(State(state): State<AppState>) -> AppResult<Html<String>> {
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+    let conn = state.db.get()?;
+
+    let template = ImportSessionsTemplate {
+        title: "Import".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        sessions: resumable_sessions(&state, &conn)?,
     };
 
     template.render_html()
 }
 
+/// Sessions in preview, and interrupted imports that no task is working on.
+/// All wizard choices are stored with the session, so resuming is lossless.
+fn resumable_sessions(
+    state: &AppState,
+    conn: &rusqlite::Connection,
+) -> AppResult<Vec<OpenImportSession>> {
+    let mut sessions = import::list_open_sessions(conn)?;
+    sessions.retain(|open| open.session.is_preview() || is_resumable(state, &open.session));
+    Ok(sessions)
+}
+
 pub async fn format(State(state): State<AppState>) -> AppResult<Html<String>> {
     let PageBase {
         settings,
@@ -140,6 +221,7 @@ pub async fn format(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let template = ImportFormatTemplate {
@@ -149,51 +231,24 @@ pub async fn format(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     };
 
     template.render_html()
 }
 
-pub async fn upload(
-    State(state): State<AppState>,
-    mut multipart: Multipart,
-) -> AppResult<Redirect> {
+pub async fn upload(State(state): State<AppState>, multipart: Multipart) -> AppResult<Redirect> {
     let session_id = Uuid::new_v4().to_string();
     info!(session_id = %session_id, "Starting CSV upload");
 
+    let files = receive_files(multipart, &session_id, state.config.max_upload_bytes()).await?;
+
     // Create session
     {
         let conn = state.db.get()?;
         import::create_session(&conn, &session_id)?;
     }
 
-    // Collect files
-    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
-
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|e| AppError::CsvParse(e.to_string()))?
-    {
-        if field.name() == Some("files") {
-            let file_name = field
-                .file_name()
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| format!("file_{}", files.len() + 1));
-
-            let content = field
-                .bytes()
-                .await
-                .map_err(|e| AppError::CsvParse(e.to_string()))?
-                .to_vec();
-
-            if !content.is_empty() {
-                debug!(file_name = %file_name, size_bytes = content.len(), "Received CSV file");
-                files.push((file_name, content));
-            }
-        }
-    }
-
     if files.is_empty() {
         warn!(session_id = %session_id, "No files uploaded");
         let conn = state.db.get()?;
@@ -205,28 +260,98 @@ pub async fn upload(
     info!(session_id = %session_id, file_count = files.len(), "Processing uploaded files");
 
     // Spawn background parsing task
+    let settings = state.load_settings()?;
+    let (locale, date_format) = (settings.locale.clone(), settings.date_format());
     let state_clone = state.clone();
     let session_id_clone = session_id.clone();
 
     tokio::spawn(async move {
-        parse_files_background(state_clone, session_id_clone, files).await;
+        parse_files_background(state_clone, session_id_clone, files, locale, date_format).await;
     });
 
     Ok(Redirect::to(&format!("/import/{}", session_id)))
 }
 
+/// Stream the uploaded `files` fields to temporary files, returning their
+/// names and paths. Empty files are skipped; on failure all files written
+/// so far are removed.
+async fn receive_files(
+    mut multipart: Multipart,
+    session_id: &str,
+    limit_bytes: usize,
+) -> AppResult<Vec<(String, PathBuf)>> {
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    let result: AppResult<()> = async {
+        while let Some(field) = multipart.next_field().await.map_err(|e| {
+            body_limit::multipart_error(e, limit_bytes, |e| AppError::CsvParse(e.to_string()))
+        })? {
+            if field.name() != Some("files") {
+                continue;
+            }
+            let file_name = field
+                .file_name()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("file_{}", files.len() + 1));
+            let path = std::env::temp_dir().join(format!(
+                "solvency-upload-{}-{}.csv",
+                session_id,
+                files.len()
+            ));
+
+            let size = body_limit::stream_field_to_file(field, &path, limit_bytes).await?;
+            if size == 0 {
+                let _ = tokio::fs::remove_file(&path).await;
+            } else {
+                debug!(file_name = %file_name, size_bytes = size, "Received CSV file");
+                files.push((file_name, path));
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        for (_, path) in &files {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        return Err(e);
+    }
+    Ok(files)
+}
+
 async fn parse_files_background(
     state: AppState,
     session_id: String,
-    files: Vec<(String, Vec<u8>)>,
+    files: Vec<(String, PathBuf)>,
+    locale: String,
+    date_format: DateFormat,
 ) {
     debug!(session_id = %session_id, file_count = files.len(), "Starting background CSV parsing");
     let mut all_errors: Vec<String> = Vec::new();
+    let mut file_stats: Vec<ImportFileStats> = Vec::new();
+    let mut overlap = OverlapFilter::new();
     let mut row_index: i64 = 0;
 
-    for (file_name, content) in &files {
+    for (file_name, path) in &files {
         debug!(session_id = %session_id, file_name = %file_name, "Parsing CSV file");
-        match parse_csv(content) {
+        overlap.start_file();
+        let mut stats = ImportFileStats {
+            file_name: file_name.clone(),
+            ..Default::default()
+        };
+        let content = tokio::fs::read(path).await;
+        let _ = tokio::fs::remove_file(path).await;
+        let content = match content {
+            Ok(content) => content,
+            Err(e) => {
+                warn!(file_name = %file_name, error = %e, "Failed to read uploaded file");
+                all_errors.push(format!("{}: {}", file_name, e));
+                stats.errors += 1;
+                file_stats.push(stats);
+                continue;
+            }
+        };
+        match parse_csv(&content, &locale, date_format) {
             Ok(result) => {
                 debug!(
                     file_name = %file_name,
@@ -237,10 +362,21 @@ async fn parse_files_background(
                 // Insert rows into database
                 if let Ok(conn) = state.db.get() {
                     for transaction in result.transactions {
-                        if let Err(e) =
-                            import::insert_row(&conn, &session_id, row_index, &transaction)
-                        {
+                        if overlap.is_duplicate(&transaction) {
+                            stats.duplicates += 1;
+                            continue;
+                        }
+                        if let Err(e) = import::insert_row(
+                            &conn,
+                            &session_id,
+                            row_index,
+                            &transaction,
+                            Some(file_name),
+                        ) {
                             all_errors.push(format!("{}: Failed to store row: {}", file_name, e));
+                            stats.errors += 1;
+                        } else {
+                            stats.rows += 1;
                         }
                         row_index += 1;
 
@@ -255,6 +391,7 @@ async fn parse_files_background(
                         }
                     }
 
+                    stats.errors += result.errors.len() as i64;
                     for error in result.errors {
                         all_errors.push(format!("{}: {}", file_name, error));
                     }
@@ -263,13 +400,24 @@ async fn parse_files_background(
             Err(e) => {
                 warn!(file_name = %file_name, error = %e, "Failed to parse CSV file");
                 all_errors.push(format!("{}: {}", file_name, e));
+                stats.errors += 1;
             }
         }
+        if stats.duplicates > 0 {
+            info!(
+                session_id = %session_id,
+                file_name = %file_name,
+                duplicates = stats.duplicates,
+                "Skipped rows contained in an earlier file"
+            );
+        }
+        file_stats.push(stats);
     }
 
     // Finalize session
     if let Ok(conn) = state.db.get() {
         let _ = import::update_session_progress(&conn, &session_id, row_index, row_index);
+        let _ = import::update_session_file_stats(&conn, &session_id, &file_stats);
         let _ =
             import::update_session_errors(&conn, &session_id, all_errors.len() as i64, &all_errors);
 
@@ -283,7 +431,8 @@ async fn parse_files_background(
                 error_count = all_errors.len(),
                 "CSV parsing completed, applying rules"
             );
-            apply_rules_to_import_rows(&conn, &session_id);
+            import_matching::apply_rules_to_import_rows(&conn, &session_id);
+            import_matching::match_pending_transactions(&conn, &session_id);
             let _ = import::update_session_status(&conn, &session_id, ImportStatus::Preview);
         }
     }
@@ -302,6 +451,7 @@ pub async fn wizard(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let cats = state.cached_categories_with_path()?;
 
@@ -312,8 +462,11 @@ pub async fn wizard(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
+        resumable: is_resumable(&state, &session),
         session,
         categories: cats,
+        tags: state.cached_tags()?,
     };
 
     template.render_html()
@@ -329,8 +482,10 @@ pub async fn status(
 
     let template = ImportStatusTemplate {
         icons: crate::filters::Icons,
+        resumable: is_resumable(&state, &session),
         session,
         categories: cats,
+        tags: state.cached_tags()?,
     };
     template.render_html()
 }
@@ -341,6 +496,11 @@ pub async fn status_json(
 ) -> AppResult<axum::Json<StatusResponse>> {
     let conn = state.db.get()?;
     let session = import::get_session(&conn, &session_id)?;
+    let category_impact = if session.status == ImportStatus::Preview {
+        import_preview::category_impact(&conn, &session_id)?
+    } else {
+        Vec::new()
+    };
 
     Ok(axum::Json(StatusResponse {
         status: session.status.as_str().to_string(),
@@ -348,6 +508,10 @@ pub async fn status_json(
         processed_rows: session.processed_rows,
         error_count: session.error_count,
         progress_percent: session.progress_percent(),
+        resumable: is_resumable(&state, &session),
+        resume_from_row_index: session.resume_from_row_index,
+        file_stats: session.file_stats,
+        category_impact,
     }))
 }
 
@@ -358,6 +522,15 @@ pub async fn rows(
 ) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
 
+    if query.group.as_deref() == Some("payee") {
+        let template = ImportPayeeGroupsTemplate {
+            groups: import_preview::payee_groups(&import::get_pending_rows(&conn, &session_id)?),
+            session_id,
+            categories: state.cached_categories_with_path()?,
+        };
+        return template.render_html();
+    }
+
     let page = query.page.unwrap_or(1).max(1);
     let offset = (page - 1) * PREVIEW_PAGE_SIZE;
 
@@ -369,6 +542,7 @@ pub async fn rows(
         session_id,
         rows,
         categories: cats,
+        tags: state.cached_tags()?,
         page,
         page_size: PREVIEW_PAGE_SIZE,
         total_count,
@@ -377,76 +551,42 @@ pub async fn rows(
     template.render_html()
 }
 
-pub async fn update_row_category(
-    State(state): State<AppState>,
-    Path((_session_id, row_id)): Path<(String, i64)>,
-    Form(form): Form<CategoryForm>,
-) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-    import::update_row_category(&conn, row_id, form.category_id)?;
-
-    // Return updated category display
-    let cat_name = if let Some(cat_id) = form.category_id {
-        categories::get_category(&conn, cat_id)
-            .ok()
-            .flatten()
-            .map(|c| c.name)
-            .unwrap_or_default()
-    } else {
-        String::new()
-    };
-
-    let display_name = if cat_name.is_empty() {
-        "Uncategorized".to_string()
-    } else {
-        html_escape(&cat_name)
-    };
-
-    Ok(Html(format!(
-        r#"<span class="text-gray-600 dark:text-gray-400">{}</span>"#,
-        display_name
-    )))
-}
-
-pub async fn update_all_categories(
-    State(state): State<AppState>,
-    Path(session_id): Path<String>,
-    Form(form): Form<CategoryForm>,
-) -> AppResult<Redirect> {
-    let conn = state.db.get()?;
-    import::update_all_rows_category(&conn, &session_id, form.category_id)?;
-
-    Ok(Redirect::to(&format!("/import/{}", session_id)))
-}
-
 pub async fn confirm(
     State(state): State<AppState>,
     Path(session_id): Path<String>,
 ) -> AppResult<Html<String>> {
     info!(session_id = %session_id, "Confirming import");
 
-    // Update status to importing
-    {
+    // Update status to importing, or pick up an interrupted import
+    let running = {
         let conn = state.db.get()?;
         let session = import::get_session(&conn, &session_id)?;
+        let resuming = is_resumable(&state, &session);
 
-        if session.status != ImportStatus::Preview {
+        if session.status != ImportStatus::Preview && !resuming {
             warn!(session_id = %session_id, status = %session.status.as_str(), "Import session not ready");
             return Err(AppError::Validation(
                 "Session is not ready for import".into(),
             ));
         }
+        let running = RunningImport::start(&state, &session_id)
+            .ok_or_else(|| AppError::Validation("Import is already running".into()))?;
 
-        import::update_session_status(&conn, &session_id, ImportStatus::Importing)?;
-        import::update_session_progress(&conn, &session_id, session.total_rows, 0)?;
-    }
+        if resuming {
+            info!(session_id = %session_id, from_row = session.resume_from_row_index, "Resuming import");
+        } else {
+            import::update_session_status(&conn, &session_id, ImportStatus::Importing)?;
+            import::update_session_progress(&conn, &session_id, session.total_rows, 0)?;
+        }
+        running
+    };
 
     // Spawn background import task
     let state_clone = state.clone();
     let session_id_clone = session_id.clone();
 
     tokio::spawn(async move {
-        import_rows_background(state_clone, session_id_clone).await;
+        import_rows_background(state_clone, session_id_clone, running).await;
     });
 
     // Return status template for polling
@@ -458,254 +598,238 @@ pub async fn confirm(
         icons: crate::filters::Icons,
         session,
         categories: cats,
+        tags: state.cached_tags()?,
+        resumable: false,
     };
     template.render_html()
 }
 
-fn apply_rules_to_import_rows(conn: &rusqlite::Connection, session_id: &str) {
-    let all_rules = match rules::list_rules(conn) {
-        Ok(r) => r,
-        Err(e) => {
-            warn!(session_id = %session_id, error = %e, "Failed to load rules for import");
-            return;
-        }
-    };
+/// Marks an import session as being confirmed by a background task for as
+/// long as the guard lives.
+struct RunningImport {
+    state: AppState,
+    session_id: String,
+}
 
-    if all_rules.is_empty() {
-        return;
+impl RunningImport {
+    /// Returns `None` if the session is already being imported.
+    fn start(state: &AppState, session_id: &str) -> Option<Self> {
+        let mut running = state.running_imports.lock().unwrap();
+        running.insert(session_id.to_string()).then(|| Self {
+            state: state.clone(),
+            session_id: session_id.to_string(),
+        })
     }
+}
 
-    struct CompiledRule {
-        regex: regex::Regex,
-        action_type: RuleActionType,
-        category_id: Option<i64>,
-        tag_name: Option<String>,
+impl Drop for RunningImport {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.state.running_imports.lock() {
+            running.remove(&self.session_id);
+        }
     }
+}
 
-    let compiled: Vec<CompiledRule> = all_rules
+/// An import is resumable if it was left in the importing state (the server
+/// stopped or a batch failed) and no task is working on it.
+fn is_resumable(state: &AppState, session: &ImportSession) -> bool {
+    session.status == ImportStatus::Importing
+        && !state.running_imports.lock().unwrap().contains(&session.id)
+}
+
+/// Tags chosen in the wizard, split into the session-wide selection and the
+/// set of tag ids that still exist (tags deleted since selection are dropped).
+struct SelectedTags {
+    session: Vec<i64>,
+    existing: std::collections::HashSet<i64>,
+}
+
+fn selected_session_tags(conn: &rusqlite::Connection, session_id: &str) -> AppResult<SelectedTags> {
+    let session = import::get_session(conn, session_id)?;
+    let existing = tags::list_tags(conn)?.into_iter().map(|t| t.id).collect();
+    Ok(SelectedTags {
+        session: session.tag_ids,
+        existing,
+    })
+}
+
+/// Resolve the tag ids for an import row: tags named in the CSV (created on
+/// demand) plus the row's override or, failing that, the session tags.
+fn row_tag_ids(conn: &rusqlite::Connection, row: &ImportRow, selected: &SelectedTags) -> Vec<i64> {
+    let mut tag_ids: Vec<i64> = row
+        .data
+        .tags
         .iter()
-        .filter_map(|rule| {
-            let regex = RegexBuilder::new(&rule.pattern)
-                .case_insensitive(true)
-                .build()
-                .ok()?;
-
-            match rule.action_type {
-                RuleActionType::AssignCategory => {
-                    let cat_id: i64 = rule.action_value.parse().ok()?;
-                    Some(CompiledRule {
-                        regex,
-                        action_type: rule.action_type,
-                        category_id: Some(cat_id),
-                        tag_name: None,
-                    })
-                }
-                RuleActionType::AssignTag => {
-                    let tag_id: i64 = rule.action_value.parse().ok()?;
-                    let tag = tags::get_tag(conn, tag_id).ok()??;
-                    Some(CompiledRule {
-                        regex,
-                        action_type: rule.action_type,
-                        category_id: None,
-                        tag_name: Some(tag.name),
-                    })
-                }
+        .filter_map(|name| {
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
             }
+            tags::create_or_get_tag(conn, name).ok().map(|t| t.id)
         })
         .collect();
 
-    if compiled.is_empty() {
-        return;
-    }
-
-    let rows = match import::get_pending_rows(conn, session_id) {
-        Ok(r) => r,
-        Err(e) => {
-            warn!(session_id = %session_id, error = %e, "Failed to load rows for rule application");
-            return;
-        }
-    };
-
-    let mut affected = 0u64;
-
-    for row in &rows {
-        let mut matched_category: Option<i64> = None;
-        let mut extra_tags: Vec<String> = Vec::new();
-
-        for cr in &compiled {
-            if !cr.regex.is_match(&row.data.description) {
-                continue;
-            }
-            match cr.action_type {
-                RuleActionType::AssignCategory => {
-                    if matched_category.is_none() {
-                        matched_category = cr.category_id;
-                    }
-                }
-                RuleActionType::AssignTag => {
-                    if let Some(ref name) = cr.tag_name {
-                        if !row.data.tags.contains(name) && !extra_tags.contains(name) {
-                            extra_tags.push(name.clone());
-                        }
-                    }
-                }
-            }
-        }
+    let chosen = row.tag_ids.as_deref().unwrap_or(&selected.session);
+    tag_ids.extend(chosen.iter().filter(|id| selected.existing.contains(id)));
+    tag_ids.sort_unstable();
+    tag_ids.dedup();
+    tag_ids
+}
 
-        let has_changes = matched_category.is_some() || !extra_tags.is_empty();
-        if !has_changes {
-            continue;
-        }
-        affected += 1;
+/// Rows imported per SQL transaction. Each committed batch is checkpointed
+/// on the session so an interrupted import resumes after it.
+pub(crate) const IMPORT_BATCH_SIZE: usize = 500;
 
-        if let Some(cat_id) = matched_category {
-            let _ = import::update_row_category(conn, row.id, Some(cat_id));
-        }
+async fn import_rows_background(state: AppState, session_id: String, _running: RunningImport) {
+    debug!(session_id = %session_id, "Starting background import");
 
-        if !extra_tags.is_empty() {
-            let mut data = row.data.clone();
-            data.tags.extend(extra_tags);
-            let _ = import::update_row_data(conn, row.id, &data);
+    match import_pending_rows(&state, &session_id) {
+        Ok(error_count) => info!(
+            session_id = %session_id,
+            error_count = error_count,
+            "Import completed"
+        ),
+        Err(e) => {
+            tracing::error!(
+                session_id = %session_id,
+                error = %e,
+                "Import interrupted; confirm again to resume"
+            );
+            notify::notify(
+                &state,
+                "transaction_import",
+                Severity::Error,
+                "Transaction import failed",
+                &format!("Import {} was interrupted: {}", session_id, e),
+            )
+            .await;
         }
     }
+}
+
+/// Import all pending rows from the session's checkpoint on, one batch per
+/// SQL transaction. Returns the number of rows that failed.
+fn import_pending_rows(state: &AppState, session_id: &str) -> AppResult<usize> {
+    let conn = state.db.get()?;
+    let session = import::get_session(&conn, session_id)?;
+    let pending_rows: Vec<ImportRow> = import::get_pending_rows(&conn, session_id)?
+        .into_iter()
+        .filter(|row| row.row_index >= session.resume_from_row_index)
+        .collect();
+    let selected_tags = selected_session_tags(&conn, session_id)?;
 
     info!(
         session_id = %session_id,
-        rules_compiled = compiled.len(),
-        rows_affected = affected,
-        "Applied rules to import rows"
+        row_count = pending_rows.len(),
+        resume_from = session.resume_from_row_index,
+        "Importing rows"
     );
-}
 
-async fn import_rows_background(state: AppState, session_id: String) {
-    debug!(session_id = %session_id, "Starting background import");
-
-    let mut conn = match state.db.get() {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::error!(session_id = %session_id, error = %e, "Failed to get database connection");
-            return;
-        }
-    };
+    for batch in pending_rows.chunks(IMPORT_BATCH_SIZE) {
+        let next_row_index = batch.last().map_or(0, |row| row.row_index + 1);
+        state.with_tx(|tx| {
+            let error_count = import_batch(tx, batch, &selected_tags)?;
+            import::checkpoint_session(
+                tx,
+                session_id,
+                batch.len() as i64,
+                error_count,
+                next_row_index,
+            )?;
+            Ok(())
+        })?;
+        debug!(session_id = %session_id, next_row_index, "Committed import batch");
+    }
 
-    let pending_rows = match import::get_pending_rows(&conn, &session_id) {
-        Ok(r) => r,
-        Err(e) => {
-            tracing::error!(session_id = %session_id, error = %e, "Failed to get pending rows");
-            return;
-        }
-    };
+    // Pair transfers between own accounts now that both sides may be in
+    state.with_tx(|tx| transfer_detection::detect_and_link(tx))?;
 
-    info!(session_id = %session_id, row_count = pending_rows.len(), "Importing rows");
+    let errors = import::list_row_errors(&conn, session_id)?;
+    import::update_session_errors(&conn, session_id, errors.len() as i64, &errors)?;
+    import::update_session_status(&conn, session_id, ImportStatus::Completed)?;
+    Ok(errors.len())
+}
 
+/// Import a batch of rows. Rows with invalid data are marked as failed and
+/// counted; any other error aborts the batch so its transaction rolls back.
+fn import_batch(
+    conn: &rusqlite::Connection,
+    rows: &[ImportRow],
+    selected_tags: &SelectedTags,
+) -> AppResult<i64> {
     let mut error_count = 0;
-    let mut errors: Vec<String> = Vec::new();
-    const BATCH_SIZE: usize = 100;
-
-    let mut tx = match conn.transaction() {
-        Ok(t) => t,
-        Err(e) => {
-            tracing::error!(session_id = %session_id, error = %e, "Failed to start transaction");
-            return;
-        }
-    };
-
-    for (i, row) in pending_rows.into_iter().enumerate() {
-        // Commit in batches for performance while keeping progress visible
-        if i > 0 && i % BATCH_SIZE == 0 {
-            if let Err(e) = tx.commit() {
-                tracing::error!(session_id = %session_id, error = %e, "Failed to commit batch");
-                return;
-            }
-            tx = match conn.transaction() {
-                Ok(t) => t,
-                Err(e) => {
-                    tracing::error!(session_id = %session_id, error = %e, "Failed to start transaction");
-                    return;
+    let mut rule_matches: HashMap<i64, Vec<i64>> = HashMap::new();
+    for row in rows {
+        match import_row(conn, row, selected_tags) {
+            Ok(transaction_id) => {
+                import::mark_row_imported(conn, row.id)?;
+                for &rule_id in &row.rule_ids {
+                    rule_matches
+                        .entry(rule_id)
+                        .or_default()
+                        .push(transaction_id);
                 }
-            };
-        }
-
-        let amount: f64 = match row.data.amount.parse() {
-            Ok(a) => a,
-            Err(_) => {
-                error_count += 1;
-                errors.push(format!(
-                    "Row {}: Invalid amount '{}'",
-                    row.row_index + 1,
-                    row.data.amount
-                ));
-                let _ = import::mark_row_error(&tx, row.id, "Invalid amount");
-                let _ = import::increment_session_processed(&tx, &session_id);
-                let _ = import::increment_session_error_count(&tx, &session_id);
-                continue;
-            }
-        };
-
-        // Handle tags
-        let tag_ids: Vec<i64> = row
-            .data
-            .tags
-            .iter()
-            .filter_map(|name| {
-                let name = name.trim();
-                if name.is_empty() {
-                    return None;
-                }
-                tags::create_or_get_tag(&tx, name).ok().map(|t| t.id)
-            })
-            .collect();
-
-        let new_transaction = NewTransaction {
-            date: row.data.date.clone(),
-            amount_cents: (amount * 100.0).round() as i64,
-            currency: row.data.currency.clone(),
-            description: row.data.description.clone(),
-            category_id: row.category_id,
-            account_id: row.data.account_id,
-            notes: row.data.notes.clone(),
-            tag_ids,
-            value_date: row.data.value_date.clone(),
-            payer: row.data.payer.clone(),
-            payee: row.data.payee.clone(),
-            reference: row.data.reference.clone(),
-            transaction_type: row.data.transaction_type.clone(),
-            counterparty_iban: row.data.counterparty_iban.clone(),
-            creditor_id: row.data.creditor_id.clone(),
-            mandate_reference: row.data.mandate_reference.clone(),
-            customer_reference: row.data.customer_reference.clone(),
-        };
-
-        match transactions::create_transaction(&tx, &new_transaction) {
-            Ok(_) => {
-                let _ = import::mark_row_imported(&tx, row.id);
             }
-            Err(e) => {
+            Err(message) => {
                 error_count += 1;
-                errors.push(format!("Row {}: {}", row.row_index + 1, e));
-                let _ = import::mark_row_error(&tx, row.id, &e.to_string());
-                let _ = import::increment_session_error_count(&tx, &session_id);
+                import::mark_row_error(conn, row.id, &message)?;
             }
         }
-
-        let _ = import::increment_session_processed(&tx, &session_id);
     }
-
-    // Commit final batch
-    if let Err(e) = tx.commit() {
-        tracing::error!(session_id = %session_id, error = %e, "Failed to commit final batch");
-        return;
+    for (rule_id, transaction_ids) in rule_matches {
+        rules::record_matches(conn, rule_id, &transaction_ids)?;
     }
+    Ok(error_count)
+}
 
-    // Finalize
-    let _ = import::update_session_errors(&conn, &session_id, error_count, &errors);
-    let _ = import::update_session_status(&conn, &session_id, ImportStatus::Completed);
+/// Create the transaction for an import row and return its ID, or return
+/// why the row failed.
+fn import_row(
+    conn: &rusqlite::Connection,
+    row: &ImportRow,
+    selected_tags: &SelectedTags,
+) -> Result<i64, String> {
+    let amount_cents = money::parse_amount(&row.data.amount, money::INPUT_LOCALE)
+        .map_err(|_| format!("Invalid amount '{}'", row.data.amount))?;
+
+    let new_transaction = NewTransaction {
+        date: row.data.date.clone(),
+        amount_cents,
+        currency: row.data.currency.clone(),
+        description: row.data.description.clone(),
+        category_id: row.category_id,
+        account_id: row.data.account_id,
+        notes: row.data.notes.clone(),
+        tag_ids: row_tag_ids(conn, row, selected_tags),
+        value_date: row.data.value_date.clone(),
+        payer: row.data.payer.clone(),
+        payee: row.data.payee.clone(),
+        reference: row.data.reference.clone(),
+        transaction_type: row.data.transaction_type.clone(),
+        counterparty_iban: row.data.counterparty_iban.clone(),
+        creditor_id: row.data.creditor_id.clone(),
+        mandate_reference: row.data.mandate_reference.clone(),
+        customer_reference: row.data.customer_reference.clone(),
+        status: TransactionStatus::Posted,
+    };
 
-    info!(
-        session_id = %session_id,
-        error_count = error_count,
-        "Import completed"
-    );
+    let merged = match row.merge_target() {
+        Some(pending_id) => transactions::merge_into_pending(conn, pending_id, &new_transaction)
+            .map_err(|e| e.to_string())?
+            .then_some(pending_id),
+        None => None,
+    };
+    let id = match merged {
+        Some(id) => id,
+        None => {
+            transactions::create_transaction(conn, &new_transaction).map_err(|e| e.to_string())?
+        }
+    };
+    if let (Some(category_id), Some(rule_id)) = (row.category_id, row.category_rule_id) {
+        transactions::mark_auto_categorized(conn, id, category_id, rule_id)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(id)
 }
 
 pub async fn result(
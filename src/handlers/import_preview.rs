@@ -42,6 +42,7 @@ pub struct ImportPreviewTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub resource_name: String,
     pub back_url: String,
     pub import_url: String,
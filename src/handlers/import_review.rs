@@ -0,0 +1,167 @@
+//! Edits to the rows of an import while it is reviewed in the wizard: the
+//! category of a row, of all rows or of a payee's rows, and the session tags.
+
+use axum::extract::{Path, State};
+use axum::response::{Html, Redirect};
+use axum::Form;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::db::queries::{categories, import};
+use crate::error::{html_escape, AppError, AppResult};
+use crate::form_utils::collect_ids;
+use crate::models::ImportStatus;
+use crate::services::rule_suggestion::counterparty_key;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryForm {
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub category_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PayeeCategoryForm {
+    /// A payee (or description) as shown in the wizard; normalized before matching.
+    pub payee: String,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub category_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PayeeCategoryResponse {
+    /// Normalized payee the rows were matched on
+    pub payee: String,
+    pub updated: usize,
+}
+
+/// Update a single preview row. Accepts `category_id`, `tag_ids` and
+/// `merge_pending`; fields that are absent are left unchanged. An empty `tag_ids` selection
+/// drops the row's override so it inherits the session tags again.
+pub async fn update_row_category(
+    State(state): State<AppState>,
+    Path((_session_id, row_id)): Path<(String, i64)>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Html<String>> {
+    let updated = state.with_tx(|tx| {
+        if fields.iter().any(|(k, _)| k == "merge_pending") {
+            // A hidden empty value comes first so an unchecked box is still sent
+            let merge = fields
+                .iter()
+                .any(|(k, v)| k == "merge_pending" && v == "on");
+            import::update_row_merge(tx, row_id, merge)?;
+            debug!(row_id, merge, "Updated import row merge choice");
+        }
+
+        if fields.iter().any(|(k, _)| k == "tag_ids") {
+            let tag_ids = collect_ids(&fields, "tag_ids");
+            let tag_override = (!tag_ids.is_empty()).then_some(tag_ids.as_slice());
+            import::update_row_tags(tx, row_id, tag_override)?;
+            debug!(row_id, tags = ?tag_ids, "Updated import row tags");
+        }
+
+        let Some((_, category_value)) = fields.iter().find(|(k, _)| k == "category_id") else {
+            return Ok(None);
+        };
+        let category_id = match category_value.trim() {
+            "" => None,
+            v => Some(
+                v.parse::<i64>()
+                    .map_err(|_| AppError::Validation(format!("Invalid category id '{}'", v)))?,
+            ),
+        };
+        import::update_row_category(tx, row_id, category_id)?;
+        Ok(Some(category_id))
+    })?;
+    let Some(category_id) = updated else {
+        return Ok(Html(String::new()));
+    };
+
+    // Return updated category display
+    let conn = state.db.get()?;
+    let cat_name = if let Some(cat_id) = category_id {
+        categories::get_category(&conn, cat_id)
+            .ok()
+            .flatten()
+            .map(|c| c.name)
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    let display_name = if cat_name.is_empty() {
+        "Uncategorized".to_string()
+    } else {
+        html_escape(&cat_name)
+    };
+
+    Ok(Html(format!(
+        r#"<span class="text-gray-600 dark:text-gray-400">{}</span>"#,
+        display_name
+    )))
+}
+
+pub async fn update_all_categories(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Form(form): Form<CategoryForm>,
+) -> AppResult<Redirect> {
+    let conn = state.db.get()?;
+    import::update_all_rows_category(&conn, &session_id, form.category_id)?;
+
+    Ok(Redirect::to(&format!("/import/{}", session_id)))
+}
+
+/// Set the category of every pending row whose payee (or description, for
+/// rows without one) matches `payee` after normalization.
+pub async fn update_payee_categories(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Form(form): Form<PayeeCategoryForm>,
+) -> AppResult<axum::Json<PayeeCategoryResponse>> {
+    let conn = state.db.get()?;
+    let session = import::get_session(&conn, &session_id)?;
+    if session.status != ImportStatus::Preview {
+        return Err(AppError::Validation(
+            "Categories can only be changed during preview".into(),
+        ));
+    }
+
+    let payee = counterparty_key(&form.payee);
+    if payee.is_empty() {
+        return Err(AppError::Validation("Payee is required".into()));
+    }
+    let row_ids: Vec<i64> = import::get_pending_rows(&conn, &session_id)?
+        .iter()
+        .filter(|row| row.payee_key() == payee)
+        .map(|row| row.id)
+        .collect();
+    let updated = import::update_rows_category(&conn, &row_ids, form.category_id)?;
+    debug!(session_id = %session_id, payee = %payee, updated, "Updated category by payee");
+
+    Ok(axum::Json(PayeeCategoryResponse { payee, updated }))
+}
+
+/// Set the tags applied to every transaction of the session.
+pub async fn update_session_tags(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Redirect> {
+    let conn = state.db.get()?;
+    let session = import::get_session(&conn, &session_id)?;
+    if session.status != ImportStatus::Preview {
+        return Err(AppError::Validation(
+            "Tags can only be changed during preview".into(),
+        ));
+    }
+    import::update_session_tags(&conn, &session_id, &collect_ids(&fields, "tag_ids"))?;
+
+    Ok(Redirect::to(&format!("/import/{}", session_id)))
+}
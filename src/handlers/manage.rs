@@ -6,19 +6,131 @@ use axum::Form;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::db::queries::{rules, tags};
+use crate::date_utils;
+use crate::db::queries::{rules, tags, transaction_sums};
 use crate::error::{AppError, AppResult, RenderHtml};
 use crate::handlers::import_preview::{ImportPreviewForm, ImportPreviewItem, ImportPreviewStatus};
 use crate::models::{
-    CategoryWithPath, NewCategory, NewRule, NewTag, Rule, RuleActionType, Settings, Tag, TagStyle,
-    TagWithUsage, DEFAULT_COLOR, DEFAULT_ICON, TAG_PALETTE,
+    CategoryWithPath, NewCategory, NewRule, NewTag, Rule, RuleActionType, RuleMatchField, Settings,
+    Tag, TagStyle, TagWithUsage, DEFAULT_COLOR, DEFAULT_ICON,
 };
+use crate::palette;
+use crate::sort_utils::{SortDirection, Sortable, SortableColumn, TableSort};
 use crate::state::{AppState, JsManifest, PageBase};
 use crate::VERSION;
 
 #[derive(Debug, Deserialize)]
 pub struct ManageQuery {
     pub tab: Option<String>,
+    pub sort: Option<String>,
+    pub dir: Option<String>,
+}
+
+impl Sortable for ManageQuery {
+    fn sort_by(&self) -> Option<&String> {
+        self.sort.as_ref()
+    }
+
+    fn sort_dir(&self) -> Option<&String> {
+        self.dir.as_ref()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum RuleSortColumn {
+    #[default]
+    Name,
+    Matches,
+    LastMatched,
+}
+
+impl SortableColumn for RuleSortColumn {
+    fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "name" => Some(Self::Name),
+            "matches" => Some(Self::Matches),
+            "lastmatched" => Some(Self::LastMatched),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Matches => "matches",
+            Self::LastMatched => "lastmatched",
+        }
+    }
+
+    fn sql_expression(&self) -> &'static str {
+        // Not used for in-memory sorting
+        ""
+    }
+}
+
+/// Sort rules in memory. Rules that never matched sort before all others
+/// by last match, so ascending order puts the stale ones first.
+fn sort_rules(rules: &mut [Rule], sort: &TableSort<RuleSortColumn>) {
+    rules.sort_by(|a, b| {
+        let cmp = match sort.column {
+            RuleSortColumn::Name => a.name.cmp(&b.name),
+            RuleSortColumn::Matches => a.match_count.cmp(&b.match_count),
+            RuleSortColumn::LastMatched => a.last_matched_at.cmp(&b.last_matched_at),
+        };
+        match sort.direction {
+            SortDirection::Asc => cmp,
+            SortDirection::Desc => cmp.reverse(),
+        }
+    });
+}
+
+/// Months shown in the category sparklines, the current one included.
+pub const TREND_MONTHS: u32 = 12;
+
+/// Monthly expenses of a category over the last [`TREND_MONTHS`], oldest
+/// first. Categories without expenses in that time have none.
+pub struct CategoryTrend {
+    pub category_id: i64,
+    pub monthly_cents: Vec<i64>,
+}
+
+impl CategoryTrend {
+    pub fn sparkline_svg(&self) -> String {
+        crate::filters::sparkline_svg(&self.monthly_cents)
+    }
+}
+
+fn category_trends(
+    conn: &rusqlite::Connection,
+    settings: &Settings,
+    all_categories: &[crate::models::category::Category],
+) -> AppResult<Vec<CategoryTrend>> {
+    use chrono::{Datelike, Months};
+    let today = date_utils::today_in(settings);
+    let month_start = today.with_day(1).unwrap_or(today);
+    let months: Vec<String> = (0..TREND_MONTHS)
+        .rev()
+        .map(|i| (month_start - Months::new(i)).format("%Y-%m").to_string())
+        .collect();
+
+    let excluded = crate::db::queries::categories::transfers_excluded_ids(all_categories);
+    let mut by_category: std::collections::BTreeMap<i64, Vec<i64>> = Default::default();
+    for (category_id, month, total) in
+        transaction_sums::monthly_sums_for_all_categories(conn, TREND_MONTHS, today, &excluded)?
+    {
+        if let Some(i) = months.iter().position(|m| *m == month) {
+            by_category
+                .entry(category_id)
+                .or_insert_with(|| vec![0; months.len()])[i] = total;
+        }
+    }
+    Ok(by_category
+        .into_iter()
+        .map(|(category_id, monthly_cents)| CategoryTrend {
+            category_id,
+            monthly_cents,
+        })
+        .collect())
 }
 
 #[derive(Template)]
@@ -30,14 +142,19 @@ pub struct ManageTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub active_tab: String,
     pub categories: Vec<CategoryWithPath>,
+    /// Expense trends of the categories, only loaded for the categories tab.
+    pub category_trends: Vec<CategoryTrend>,
+    pub trend_months: u32,
     pub tags_with_usage: Vec<TagWithUsage>,
     pub tags: Vec<Tag>,
     pub rules: Vec<Rule>,
     pub category_count: i64,
     pub tag_count: i64,
     pub rule_count: i64,
+    pub rules_sort: TableSort<RuleSortColumn>,
     pub palette: &'static [(&'static str, &'static str)],
 }
 
@@ -52,14 +169,29 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
-    let active_tab = params.tab.unwrap_or_else(|| "categories".into());
+    let active_tab = params.tab.clone().unwrap_or_else(|| "categories".into());
+    // Rules come ordered by name unless a column was picked
+    let rules_sort = match params.sort {
+        Some(_) => params.resolve_sort(),
+        None => TableSort {
+            column: RuleSortColumn::Name,
+            direction: SortDirection::Asc,
+        },
+    };
 
     let categories = state.cached_categories_with_path()?;
+    let category_trends = if active_tab == "categories" {
+        category_trends(&conn, &settings, &state.cached_categories()?)?
+    } else {
+        Vec::new()
+    };
     let tags_with_usage = tags::list_tags_with_usage(&conn)?;
     let tag_list = state.cached_tags()?;
-    let rule_list = rules::list_rules(&conn)?;
+    let mut rule_list = rules::list_rules(&conn)?;
+    sort_rules(&mut rule_list, &rules_sort);
 
     let category_count = categories.len() as i64;
     let tag_count = tags_with_usage.len() as i64;
@@ -72,15 +204,19 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         active_tab,
         categories,
+        category_trends,
+        trend_months: TREND_MONTHS,
         tags_with_usage,
         tags: tag_list,
         rules: rule_list,
         category_count,
         tag_count,
         rule_count,
-        palette: TAG_PALETTE,
+        rules_sort,
+        palette: palette::PALETTE,
     };
 
     template.render_html()
@@ -107,6 +243,7 @@ struct TagExport {
 struct RuleExport {
     name: String,
     pattern: String,
+    match_field: RuleMatchField,
     action_type: RuleActionType,
     action_value: String,
 }
@@ -184,6 +321,7 @@ pub async fn export(State(state): State<AppState>) -> AppResult<impl IntoRespons
             RuleExport {
                 name: r.name.clone(),
                 pattern: r.pattern.clone(),
+                match_field: r.match_field,
                 action_type: r.action_type,
                 action_value,
             }
@@ -266,6 +404,8 @@ struct TagImport {
 struct RuleImport {
     name: String,
     pattern: String,
+    #[serde(default)]
+    match_field: RuleMatchField,
     action_type: RuleActionType,
     action_value: String,
 }
@@ -291,144 +431,149 @@ pub async fn import(
         ));
     }
 
-    let conn = state.db.get()?;
+    let existing_cats = state.cached_categories()?;
     let mut errors: Vec<String> = Vec::new();
 
-    // 1. Import categories (parents before children, iterative passes)
-    let mut name_to_id: HashMap<String, i64> = HashMap::new();
-    let existing_cats = state.cached_categories()?;
-    for cat in &existing_cats {
-        name_to_id.insert(cat.name.clone(), cat.id);
-    }
+    // Items that fail are reported and skipped, anything else rolls back the whole import
+    let (categories_created, tags_created, rules_created) = state.with_tx(|tx| {
+        // 1. Import categories (parents before children, iterative passes)
+        let mut name_to_id: HashMap<String, i64> = HashMap::new();
+        for cat in &existing_cats {
+            name_to_id.insert(cat.name.clone(), cat.id);
+        }
 
-    let mut remaining: Vec<&CategoryImport> = envelope.body.categories.iter().collect();
-    let mut categories_created = 0;
-    loop {
-        let mut next_remaining: Vec<&CategoryImport> = Vec::new();
-        let mut progress = false;
-        for item in remaining {
-            if name_to_id.contains_key(&item.name) {
-                continue;
-            }
-            let parent_resolved = match &item.parent_name {
-                None => true,
-                Some(pn) => name_to_id.contains_key(pn),
-            };
-            if parent_resolved {
-                let parent_id = item
-                    .parent_name
-                    .as_ref()
-                    .and_then(|pn| name_to_id.get(pn).copied());
-                let new_cat = NewCategory {
-                    name: item.name.clone(),
-                    parent_id,
-                    color: item.color.clone(),
-                    icon: item.icon.clone(),
+        let mut remaining: Vec<&CategoryImport> = envelope.body.categories.iter().collect();
+        let mut categories_created = 0;
+        loop {
+            let mut next_remaining: Vec<&CategoryImport> = Vec::new();
+            let mut progress = false;
+            for item in remaining {
+                if name_to_id.contains_key(&item.name) {
+                    continue;
+                }
+                let parent_resolved = match &item.parent_name {
+                    None => true,
+                    Some(pn) => name_to_id.contains_key(pn),
                 };
-                match crate::db::queries::categories::create_category(&conn, &new_cat) {
-                    Ok(id) => {
-                        name_to_id.insert(item.name.clone(), id);
-                        categories_created += 1;
-                        progress = true;
-                    }
-                    Err(e) => {
-                        errors.push(format!("category \"{}\": {}", item.name, e));
+                if parent_resolved {
+                    let parent_id = item
+                        .parent_name
+                        .as_ref()
+                        .and_then(|pn| name_to_id.get(pn).copied());
+                    let new_cat = NewCategory {
+                        name: item.name.clone(),
+                        parent_id,
+                        color: item.color.clone(),
+                        icon: item.icon.clone(),
+                    };
+                    match crate::db::queries::categories::create_category(tx, &new_cat) {
+                        Ok(id) => {
+                            name_to_id.insert(item.name.clone(), id);
+                            categories_created += 1;
+                            progress = true;
+                        }
+                        Err(e) => {
+                            errors.push(format!("category \"{}\": {}", item.name, e));
+                        }
                     }
+                } else {
+                    next_remaining.push(item);
                 }
-            } else {
-                next_remaining.push(item);
             }
-        }
-        if !progress {
-            for item in &next_remaining {
-                errors.push(format!(
-                    "category \"{}\": parent \"{}\" not found",
-                    item.name,
-                    item.parent_name.as_deref().unwrap_or("?")
-                ));
+            if !progress {
+                for item in &next_remaining {
+                    errors.push(format!(
+                        "category \"{}\": parent \"{}\" not found",
+                        item.name,
+                        item.parent_name.as_deref().unwrap_or("?")
+                    ));
+                }
+                break;
             }
-            break;
+            remaining = next_remaining;
         }
-        remaining = next_remaining;
-    }
 
-    // 2. Import tags
-    let fresh_tags = tags::list_tags(&conn)?;
-    let existing_tag_names: std::collections::HashSet<String> =
-        fresh_tags.iter().map(|t| t.name.clone()).collect();
+        // 2. Import tags
+        let fresh_tags = tags::list_tags(tx)?;
+        let existing_tag_names: std::collections::HashSet<String> =
+            fresh_tags.iter().map(|t| t.name.clone()).collect();
 
-    let mut tags_created = 0;
-    for item in &envelope.body.tags {
-        if existing_tag_names.contains(&item.name) {
-            continue;
-        }
-        let new_tag = NewTag {
-            name: item.name.clone(),
-            color: item.color.clone(),
-            style: item.style,
-        };
-        match tags::create_tag(&conn, &new_tag) {
-            Ok(_) => tags_created += 1,
-            Err(e) => errors.push(format!("tag \"{}\": {}", item.name, e)),
+        let mut tags_created = 0;
+        for item in &envelope.body.tags {
+            if existing_tag_names.contains(&item.name) {
+                continue;
+            }
+            let new_tag = NewTag {
+                name: item.name.clone(),
+                color: item.color.clone(),
+                style: item.style,
+            };
+            match tags::create_tag(tx, &new_tag) {
+                Ok(_) => tags_created += 1,
+                Err(e) => errors.push(format!("tag \"{}\": {}", item.name, e)),
+            }
         }
-    }
-
-    // 3. Import rules (resolve category/tag names to IDs from fresh data)
-    let fresh_cats = crate::db::queries::categories::list_categories(&conn)?;
-    let cat_name_to_id: HashMap<String, i64> =
-        fresh_cats.iter().map(|c| (c.name.clone(), c.id)).collect();
-    let fresh_tags_2 = tags::list_tags(&conn)?;
-    let tag_name_to_id: HashMap<String, i64> = fresh_tags_2
-        .iter()
-        .map(|t| (t.name.clone(), t.id))
-        .collect();
 
-    let existing_rules = rules::list_rules(&conn)?;
-    let existing_rule_names: std::collections::HashSet<String> =
-        existing_rules.iter().map(|r| r.name.clone()).collect();
-
-    let mut rules_created = 0;
-    for item in &envelope.body.rules {
-        if existing_rule_names.contains(&item.name) {
-            continue;
-        }
+        // 3. Import rules (resolve category/tag names to IDs from fresh data)
+        let fresh_cats = crate::db::queries::categories::list_categories(tx)?;
+        let cat_name_to_id: HashMap<String, i64> =
+            fresh_cats.iter().map(|c| (c.name.clone(), c.id)).collect();
+        let fresh_tags_2 = tags::list_tags(tx)?;
+        let tag_name_to_id: HashMap<String, i64> = fresh_tags_2
+            .iter()
+            .map(|t| (t.name.clone(), t.id))
+            .collect();
+
+        let existing_rules = rules::list_rules(tx)?;
+        let existing_rule_names: std::collections::HashSet<String> =
+            existing_rules.iter().map(|r| r.name.clone()).collect();
+
+        let mut rules_created = 0;
+        for item in &envelope.body.rules {
+            if existing_rule_names.contains(&item.name) {
+                continue;
+            }
 
-        let action_value = match item.action_type {
-            RuleActionType::AssignCategory => {
-                if let Some(id) = cat_name_to_id.get(&item.action_value) {
-                    id.to_string()
-                } else {
-                    errors.push(format!(
-                        "rule \"{}\": category \"{}\" not found",
-                        item.name, item.action_value
-                    ));
-                    continue;
+            let action_value = match item.action_type {
+                RuleActionType::AssignCategory => {
+                    if let Some(id) = cat_name_to_id.get(&item.action_value) {
+                        id.to_string()
+                    } else {
+                        errors.push(format!(
+                            "rule \"{}\": category \"{}\" not found",
+                            item.name, item.action_value
+                        ));
+                        continue;
+                    }
                 }
-            }
-            RuleActionType::AssignTag => {
-                if let Some(id) = tag_name_to_id.get(&item.action_value) {
-                    id.to_string()
-                } else {
-                    errors.push(format!(
-                        "rule \"{}\": tag \"{}\" not found",
-                        item.name, item.action_value
-                    ));
-                    continue;
+                RuleActionType::AssignTag => {
+                    if let Some(id) = tag_name_to_id.get(&item.action_value) {
+                        id.to_string()
+                    } else {
+                        errors.push(format!(
+                            "rule \"{}\": tag \"{}\" not found",
+                            item.name, item.action_value
+                        ));
+                        continue;
+                    }
                 }
-            }
-        };
+            };
 
-        let new_rule = NewRule {
-            name: item.name.clone(),
-            pattern: item.pattern.clone(),
-            action_type: item.action_type,
-            action_value,
-        };
-        match rules::create_rule(&conn, &new_rule) {
-            Ok(_) => rules_created += 1,
-            Err(e) => errors.push(format!("rule \"{}\": {}", item.name, e)),
+            let new_rule = NewRule {
+                name: item.name.clone(),
+                pattern: item.pattern.clone(),
+                match_field: item.match_field,
+                action_type: item.action_type,
+                action_value,
+            };
+            match rules::create_rule(tx, &new_rule) {
+                Ok(_) => rules_created += 1,
+                Err(e) => errors.push(format!("rule \"{}\": {}", item.name, e)),
+            }
         }
-    }
+
+        Ok((categories_created, tags_created, rules_created))
+    })?;
 
     tracing::info!(
         categories = categories_created,
@@ -472,6 +617,7 @@ pub struct ManageImportPreviewTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub category_items: Vec<ImportPreviewItem>,
     pub tag_items: Vec<ImportPreviewItem>,
     pub rule_items: Vec<ImportPreviewItem>,
@@ -505,6 +651,7 @@ pub async fn import_preview(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     // Categories preview
@@ -677,6 +824,7 @@ pub async fn import_preview(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         category_items,
         tag_items,
         rule_items,
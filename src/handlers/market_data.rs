@@ -1,15 +1,22 @@
 use askama::Template;
 use axum::extract::{Path, Query, State};
-use axum::response::{Html, Redirect};
-use axum::Json;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{Form, Json};
 use serde::{Deserialize, Serialize};
+use tower_cookies::Cookies;
 
-use chrono::Datelike;
-
+use crate::auth::SESSION_COOKIE;
+use crate::cache::DataDomain;
+use crate::date_utils;
 use crate::db::queries::{api_logs, market_data};
-use crate::error::{AppResult, RenderHtml};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash;
 use crate::models::{MarketData, NewApiLog, Settings, SymbolDataCoverage};
 use crate::services::market_data as market_data_service;
+use crate::services::market_data_refresh;
+use crate::services::money;
+use crate::services::notify;
 use crate::sort_utils::{SortDirection, Sortable, SortableColumn, TableSort};
 use crate::state::{AppState, JsManifest, MarketDataRefreshState, PageBase};
 
@@ -102,8 +109,12 @@ pub struct MarketDataTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub coverage: Vec<SymbolDataCoverage>,
+    /// Symbols left out of refreshes, listed apart below the coverage table.
+    pub ignored_coverage: Vec<SymbolDataCoverage>,
     pub total_data_points: i64,
+    /// Symbols without data or without a recent price.
     pub symbols_needing_data: usize,
     pub is_refreshing: bool,
     pub refresh_message: Option<String>,
@@ -116,6 +127,7 @@ pub async fn index(
     Query(params): Query<MarketDataFilterParams>,
 ) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
+    let today = today_str(&state)?;
 
     let PageBase {
         settings,
@@ -123,14 +135,23 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
-    let sort: TableSort<MarketDataSortColumn> = params.resolve_sort();
+    let sort: TableSort<MarketDataSortColumn> =
+        params.resolve_sort_or(settings.default_sort("market_data"));
 
-    let mut coverage = market_data::get_symbol_coverage(&conn)?;
+    let (mut coverage, ignored_coverage): (Vec<_>, Vec<_>) =
+        market_data::get_symbol_coverage(&conn, &today)?
+            .into_iter()
+            .partition(|c| !c.ignored);
     sort_coverage(&mut coverage, &sort);
 
     let total_data_points = market_data::count_market_data(&conn)?;
-    let symbols_needing_data = market_data::get_symbols_needing_data(&conn)?.len();
+    // Gaps inside the stored history are only scanned for on refresh
+    let symbols_needing_data = coverage
+        .iter()
+        .filter(|c| !c.has_current_price || c.data_points == 0)
+        .count();
     let latest_log_id = api_logs::get_latest_log_id(&conn).unwrap_or(0);
 
     // Get refresh state
@@ -163,7 +184,9 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         coverage,
+        ignored_coverage,
         total_data_points,
         symbols_needing_data,
         is_refreshing,
@@ -179,9 +202,6 @@ pub async fn index(
 #[template(path = "partials/market_data_status.html")]
 pub struct MarketDataStatusTemplate {
     pub icons: crate::filters::Icons,
-    pub coverage: Vec<SymbolDataCoverage>,
-    pub total_data_points: i64,
-    pub symbols_needing_data: usize,
     pub is_refreshing: bool,
     pub refresh_message: Option<String>,
     pub progress_percent: u8,
@@ -197,9 +217,10 @@ pub async fn refresh(State(state): State<AppState>) -> AppResult<Redirect> {
     }
 
     let conn = state.db.get()?;
+    let today = today_str(&state)?;
 
-    // Get symbols that need data
-    let symbols_to_fetch = market_data::get_symbols_needing_data(&conn)?;
+    // Get the missing date ranges of each symbol
+    let symbols_to_fetch = market_data::get_missing_ranges_per_symbol(&conn, &today)?;
 
     if symbols_to_fetch.is_empty() {
         return Ok(Redirect::to("/trading/market-data"));
@@ -212,104 +233,23 @@ pub async fn refresh(State(state): State<AppState>) -> AppResult<Redirect> {
             is_refreshing: true,
             processed_symbols: 0,
             total_symbols: symbols_to_fetch.len(),
-            current_symbol: symbols_to_fetch.first().map(|(s, _, _)| s.clone()),
+            current_symbol: symbols_to_fetch.first().map(|(s, _)| s.clone()),
         };
     }
 
     // Spawn background task for fetching
     let state_clone = state.clone();
     tokio::spawn(async move {
-        for (i, (symbol, start_date, end_date)) in symbols_to_fetch.iter().enumerate() {
+        let mut failed = Vec::new();
+        for (i, (symbol, ranges)) in symbols_to_fetch.iter().enumerate() {
             // Update current symbol in state
             {
                 let mut refresh_state = state_clone.market_data_refresh.lock().unwrap();
                 refresh_state.current_symbol = Some(symbol.clone());
             }
 
-            let start_time = std::time::Instant::now();
-            let request_params = serde_json::json!({
-                "symbol": &symbol,
-                "start_date": &start_date,
-                "end_date": &end_date
-            })
-            .to_string();
-
-            match market_data_service::fetch_historical_quotes(symbol, start_date, end_date).await {
-                Ok(data) => {
-                    let duration_ms = start_time.elapsed().as_millis() as i64;
-                    if let Ok(conn) = state_clone.db.get() {
-                        // Log success
-                        let _ = api_logs::insert_api_log(
-                            &conn,
-                            &NewApiLog {
-                                api_name: "yahoo_finance".to_string(),
-                                action: "fetch_historical_quotes".to_string(),
-                                symbol: Some(symbol.clone()),
-                                request_params: request_params.clone(),
-                                status: "success".to_string(),
-                                response_summary: Some(format!(
-                                    "Retrieved {} data points",
-                                    data.len()
-                                )),
-                                response_details: Some(
-                                    serde_json::json!({
-                                        "data_points": data.len(),
-                                        "first_date": data.first().map(|d| &d.date),
-                                        "last_date": data.last().map(|d| &d.date),
-                                    })
-                                    .to_string(),
-                                ),
-                                duration_ms: Some(duration_ms),
-                            },
-                        );
-
-                        if let Err(e) = market_data::insert_market_data_batch(&conn, &data) {
-                            tracing::error!("Failed to insert market data for {}: {}", symbol, e);
-                        } else {
-                            tracing::info!("Fetched {} data points for {}", data.len(), symbol);
-                        }
-
-                        // Also fetch and store symbol metadata if not already cached
-                        if market_data::get_symbol_metadata(&conn, symbol)
-                            .ok()
-                            .flatten()
-                            .is_none()
-                        {
-                            if let Ok(Some(meta)) =
-                                market_data_service::fetch_symbol_metadata(symbol).await
-                            {
-                                let _ = market_data::upsert_symbol_metadata(
-                                    &conn,
-                                    symbol,
-                                    meta.short_name.as_deref(),
-                                    meta.long_name.as_deref(),
-                                    Some(&meta.exchange),
-                                    Some(&meta.quote_type),
-                                );
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    let duration_ms = start_time.elapsed().as_millis() as i64;
-                    if let Ok(conn) = state_clone.db.get() {
-                        // Log error
-                        let _ = api_logs::insert_api_log(
-                            &conn,
-                            &NewApiLog {
-                                api_name: "yahoo_finance".to_string(),
-                                action: "fetch_historical_quotes".to_string(),
-                                symbol: Some(symbol.clone()),
-                                request_params,
-                                status: "error".to_string(),
-                                response_summary: Some(format!("{}", e)),
-                                response_details: Some(format!("{:?}", e)),
-                                duration_ms: Some(duration_ms),
-                            },
-                        );
-                    }
-                    tracing::error!("Failed to fetch market data for {}: {}", symbol, e);
-                }
+            if !market_data_refresh::fetch_symbol_ranges(&state_clone, symbol, ranges, None).await {
+                failed.push(symbol.clone());
             }
 
             // Update progress after each symbol
@@ -319,7 +259,7 @@ pub async fn refresh(State(state): State<AppState>) -> AppResult<Redirect> {
             }
 
             // Rate limiting between symbols
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            tokio::time::sleep(state_clone.market_data_delay()).await;
         }
 
         // Clear refresh state when done
@@ -327,6 +267,8 @@ pub async fn refresh(State(state): State<AppState>) -> AppResult<Redirect> {
             let mut refresh_state = state_clone.market_data_refresh.lock().unwrap();
             *refresh_state = MarketDataRefreshState::default();
         }
+
+        notify::notify_refresh_failures(&state_clone, &failed).await;
     });
 
     Ok(Redirect::to("/trading/market-data"))
@@ -345,122 +287,14 @@ pub async fn refresh_symbol(
     }
 
     let conn = state.db.get()?;
+    let today = today_str(&state)?;
 
-    // Get the date range for this symbol
-    let symbols_needing = market_data::get_symbols_needing_data(&conn)?;
-    let symbol_info = symbols_needing.iter().find(|(s, _, _)| s == &symbol);
-
-    if let Some((_, start_date, end_date)) = symbol_info {
-        let start = start_date.clone();
-        let end = end_date.clone();
-        let sym = symbol.clone();
-
-        // Set refresh state for single symbol
-        {
-            let mut refresh_state = state.market_data_refresh.lock().unwrap();
-            *refresh_state = MarketDataRefreshState {
-                is_refreshing: true,
-                processed_symbols: 0,
-                total_symbols: 1,
-                current_symbol: Some(sym.clone()),
-            };
-        }
-
-        // Spawn background task
-        let state_clone = state.clone();
-        tokio::spawn(async move {
-            let start_time = std::time::Instant::now();
-            let request_params = serde_json::json!({
-                "symbol": &sym,
-                "start_date": &start,
-                "end_date": &end
-            })
-            .to_string();
-
-            match market_data_service::fetch_historical_quotes(&sym, &start, &end).await {
-                Ok(data) => {
-                    let duration_ms = start_time.elapsed().as_millis() as i64;
-                    if let Ok(conn) = state_clone.db.get() {
-                        // Log success
-                        let _ = api_logs::insert_api_log(
-                            &conn,
-                            &NewApiLog {
-                                api_name: "yahoo_finance".to_string(),
-                                action: "fetch_historical_quotes".to_string(),
-                                symbol: Some(sym.clone()),
-                                request_params: request_params.clone(),
-                                status: "success".to_string(),
-                                response_summary: Some(format!(
-                                    "Retrieved {} data points",
-                                    data.len()
-                                )),
-                                response_details: Some(
-                                    serde_json::json!({
-                                        "data_points": data.len(),
-                                        "first_date": data.first().map(|d| &d.date),
-                                        "last_date": data.last().map(|d| &d.date),
-                                    })
-                                    .to_string(),
-                                ),
-                                duration_ms: Some(duration_ms),
-                            },
-                        );
-
-                        if let Err(e) = market_data::insert_market_data_batch(&conn, &data) {
-                            tracing::error!("Failed to insert market data for {}: {}", sym, e);
-                        } else {
-                            tracing::info!("Fetched {} data points for {}", data.len(), sym);
-                        }
-
-                        // Also fetch and store symbol metadata if not already cached
-                        if market_data::get_symbol_metadata(&conn, &sym)
-                            .ok()
-                            .flatten()
-                            .is_none()
-                        {
-                            if let Ok(Some(meta)) =
-                                market_data_service::fetch_symbol_metadata(&sym).await
-                            {
-                                let _ = market_data::upsert_symbol_metadata(
-                                    &conn,
-                                    &sym,
-                                    meta.short_name.as_deref(),
-                                    meta.long_name.as_deref(),
-                                    Some(&meta.exchange),
-                                    Some(&meta.quote_type),
-                                );
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    let duration_ms = start_time.elapsed().as_millis() as i64;
-                    if let Ok(conn) = state_clone.db.get() {
-                        // Log error
-                        let _ = api_logs::insert_api_log(
-                            &conn,
-                            &NewApiLog {
-                                api_name: "yahoo_finance".to_string(),
-                                action: "fetch_historical_quotes".to_string(),
-                                symbol: Some(sym.clone()),
-                                request_params,
-                                status: "error".to_string(),
-                                response_summary: Some(format!("{}", e)),
-                                response_details: Some(format!("{:?}", e)),
-                                duration_ms: Some(duration_ms),
-                            },
-                        );
-                    }
-                    tracing::error!("Failed to fetch market data for {}: {}", sym, e);
-                }
-            }
+    // Get the missing date ranges of this symbol
+    let symbols_needing = market_data::get_missing_ranges_per_symbol(&conn, &today)?;
+    let symbol_info = symbols_needing.into_iter().find(|(s, _)| s == &symbol);
 
-            // Clear refresh state when done
-            {
-                let mut refresh_state = state_clone.market_data_refresh.lock().unwrap();
-                *refresh_state = MarketDataRefreshState::default();
-            }
-        });
+    if let Some((_, ranges)) = symbol_info {
+        market_data_refresh::spawn_symbol_fetch(&state, symbol, ranges, None);
     }
 
     Ok(Redirect::to("/trading/market-data"))
@@ -471,12 +305,6 @@ pub async fn status(
 ) -> AppResult<axum::response::Response<axum::body::Body>> {
     use axum::response::IntoResponse;
 
-    let conn = state.db.get()?;
-
-    let coverage = market_data::get_symbol_coverage(&conn)?;
-    let total_data_points = market_data::count_market_data(&conn)?;
-    let symbols_needing_data = market_data::get_symbols_needing_data(&conn)?.len();
-
     // Get refresh state
     let (is_refreshing, refresh_message, progress_percent) = {
         let refresh_state = state.market_data_refresh.lock().unwrap();
@@ -502,9 +330,6 @@ pub async fn status(
 
     let template = MarketDataStatusTemplate {
         icons: crate::filters::Icons,
-        coverage,
-        total_data_points,
-        symbols_needing_data,
         is_refreshing,
         refresh_message,
         progress_percent,
@@ -523,12 +348,31 @@ pub async fn status(
 // Symbol detail page
 
 /// Symbol metadata for display
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct SymbolInfo {
     pub short_name: Option<String>,
     pub long_name: Option<String>,
     pub exchange: Option<String>,
     pub quote_type: Option<String>,
+    pub price_scale: f64,
+    pub price_currency: Option<String>,
+    pub ignored: bool,
+    pub ignore_note: Option<String>,
+}
+
+impl Default for SymbolInfo {
+    fn default() -> Self {
+        Self {
+            short_name: None,
+            long_name: None,
+            exchange: None,
+            quote_type: None,
+            price_scale: 1.0,
+            price_currency: None,
+            ignored: false,
+            ignore_note: None,
+        }
+    }
 }
 
 impl SymbolInfo {
@@ -539,6 +383,11 @@ impl SymbolInfo {
     pub fn display_name_str(&self) -> &str {
         self.display_name().map(|s| s.as_str()).unwrap_or("")
     }
+
+    /// Price scale for the form input, e.g. "0.01".
+    pub fn price_scale_display(&self) -> String {
+        money::format_decimal(self.price_scale, 6)
+    }
 }
 
 #[derive(Template)]
@@ -550,6 +399,7 @@ pub struct MarketDataSymbolTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub symbol: String,
     pub symbol_info: SymbolInfo,
     pub coverage: Option<SymbolDataCoverage>,
@@ -564,6 +414,7 @@ pub async fn symbol_detail(
     Path(symbol): Path<String>,
 ) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
+    let today = today_str(&state)?;
 
     let PageBase {
         settings,
@@ -571,6 +422,7 @@ pub async fn symbol_detail(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     // Get cached symbol metadata from DB
@@ -580,12 +432,16 @@ pub async fn symbol_detail(
             long_name: meta.long_name,
             exchange: meta.exchange,
             quote_type: meta.quote_type,
+            price_scale: meta.price_scale,
+            price_currency: meta.price_currency,
+            ignored: meta.ignored,
+            ignore_note: meta.ignore_note,
         },
         _ => SymbolInfo::default(),
     };
 
     // Get coverage info for this symbol
-    let all_coverage = market_data::get_symbol_coverage(&conn)?;
+    let all_coverage = market_data::get_symbol_coverage(&conn, &today)?;
     let coverage = all_coverage.into_iter().find(|c| c.symbol == symbol);
 
     // Get all price data for this symbol
@@ -595,7 +451,7 @@ pub async fn symbol_detail(
     let latest_price = market_data::get_latest_price(&conn, &symbol)?;
 
     // Calculate missing date ranges using ALL data (before limiting for display)
-    let missing_ranges = calculate_missing_ranges(&all_data, coverage.as_ref());
+    let missing_ranges = calculate_missing_ranges(&all_data, coverage.as_ref(), &today);
 
     // Track total count before limiting for display
     let data_points_total = all_data.len();
@@ -615,6 +471,7 @@ pub async fn symbol_detail(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         symbol: symbol.clone(),
         symbol_info,
         coverage,
@@ -627,93 +484,31 @@ pub async fn symbol_detail(
     template.render_html()
 }
 
-/// Minimum number of consecutive missing weekdays to count as a significant gap
-/// Smaller gaps are likely market holidays (Christmas, New Year, etc.)
-const MIN_GAP_WEEKDAYS: i64 = 5;
+/// Today in the configured timezone, as `YYYY-MM-DD`.
+fn today_str(state: &AppState) -> AppResult<String> {
+    Ok(date_utils::today_in(&state.load_settings()?)
+        .format("%Y-%m-%d")
+        .to_string())
+}
 
 /// Calculate date ranges where data is missing (filtering out small gaps like holidays)
 fn calculate_missing_ranges(
     data_points: &[MarketData],
     coverage: Option<&SymbolDataCoverage>,
+    today: &str,
 ) -> Vec<(String, String)> {
-    let mut missing = Vec::new();
-
     let Some(cov) = coverage else {
-        return missing;
+        return Vec::new();
     };
 
     if data_points.is_empty() {
         // All data is missing
-        missing.push((
-            cov.first_activity_date.clone(),
-            chrono::Local::now().format("%Y-%m-%d").to_string(),
-        ));
-        return missing;
+        return vec![(cov.first_activity_date.clone(), today.to_string())];
     }
 
-    // Create a set of dates we have
-    let mut dates_set: std::collections::HashSet<String> = std::collections::HashSet::new();
-    for dp in data_points {
-        dates_set.insert(dp.date.clone());
-    }
-
-    // Walk through the expected date range and find gaps
-    let start = chrono::NaiveDate::parse_from_str(&cov.first_activity_date, "%Y-%m-%d");
-    let end = chrono::NaiveDate::parse_from_str(
-        &chrono::Local::now().format("%Y-%m-%d").to_string(),
-        "%Y-%m-%d",
-    );
-
-    if let (Ok(start_date), Ok(end_date)) = (start, end) {
-        let mut current = start_date;
-        let mut gap_start: Option<chrono::NaiveDate> = None;
-        let mut gap_weekday_count = 0i64;
-
-        while current <= end_date {
-            let date_str = current.format("%Y-%m-%d").to_string();
-            let is_weekday = current.weekday().num_days_from_monday() < 5;
-
-            if is_weekday {
-                if dates_set.contains(&date_str) {
-                    // We have data for this day - end any current gap
-                    if let Some(gs) = gap_start {
-                        // Only add gap if it's significant (>= MIN_GAP_WEEKDAYS)
-                        if gap_weekday_count >= MIN_GAP_WEEKDAYS {
-                            let prev_day = current - chrono::Duration::days(1);
-                            missing.push((
-                                gs.format("%Y-%m-%d").to_string(),
-                                prev_day.format("%Y-%m-%d").to_string(),
-                            ));
-                        }
-                        gap_start = None;
-                        gap_weekday_count = 0;
-                    }
-                } else {
-                    // Missing data for this weekday
-                    if gap_start.is_none() {
-                        gap_start = Some(current);
-                        gap_weekday_count = 1;
-                    } else {
-                        gap_weekday_count += 1;
-                    }
-                }
-            }
-
-            current += chrono::Duration::days(1);
-        }
-
-        // If we ended in a significant gap
-        if let Some(gs) = gap_start {
-            if gap_weekday_count >= MIN_GAP_WEEKDAYS {
-                missing.push((
-                    gs.format("%Y-%m-%d").to_string(),
-                    end_date.format("%Y-%m-%d").to_string(),
-                ));
-            }
-        }
-    }
-
-    missing
+    let dates: std::collections::HashSet<String> =
+        data_points.iter().map(|dp| dp.date.clone()).collect();
+    market_data::find_missing_ranges(&dates, &cov.first_activity_date, today)
 }
 
 // API endpoint for chart data
@@ -736,15 +531,16 @@ pub async fn symbol_chart_data(
     Path(symbol): Path<String>,
 ) -> AppResult<Json<PriceChartResponse>> {
     let conn = state.db.get()?;
+    let today = today_str(&state)?;
 
     // Get all price data for this symbol (ordered by date descending, we need ascending)
     let mut data_points = market_data::get_prices_for_symbol(&conn, &symbol)?;
     data_points.reverse(); // Now oldest first
 
     // Get coverage for missing ranges
-    let all_coverage = market_data::get_symbol_coverage(&conn)?;
+    let all_coverage = market_data::get_symbol_coverage(&conn, &today)?;
     let coverage = all_coverage.into_iter().find(|c| c.symbol == symbol);
-    let missing_ranges = calculate_missing_ranges(&data_points, coverage.as_ref());
+    let missing_ranges = calculate_missing_ranges(&data_points, coverage.as_ref(), &today);
 
     let data: Vec<PriceChartData> = data_points
         .into_iter()
@@ -770,8 +566,274 @@ pub async fn delete_symbol(
     Ok(Redirect::to("/trading/market-data"))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PriceScaleForm {
+    pub price_scale: String,
+    #[serde(default)]
+    pub price_currency: String,
+}
+
+/// Correct how a symbol's stored prices are read, e.g. a scale of 0.01
+/// for prices quoted in pence. Stored prices are left as they are.
+pub async fn update_price_scale(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Form(form): Form<PriceScaleForm>,
+) -> AppResult<Redirect> {
+    let price_scale = money::parse_quantity(form.price_scale.trim(), money::INPUT_LOCALE)
+        .ok()
+        .filter(|s| s.is_finite() && *s > 0.0)
+        .ok_or_else(|| AppError::Validation("Price scale must be a positive number".into()))?;
+    let price_currency = form.price_currency.trim().to_uppercase();
+    let is_code =
+        price_currency.len() == 3 && price_currency.chars().all(|c| c.is_ascii_alphabetic());
+    if !price_currency.is_empty() && !is_code {
+        return Err(AppError::Validation(format!(
+            "Invalid currency: {}",
+            price_currency
+        )));
+    }
+
+    let conn = state.db.get()?;
+    market_data::set_price_scale(
+        &conn,
+        &symbol,
+        price_scale,
+        Some(price_currency.as_str()).filter(|c| !c.is_empty()),
+    )?;
+    state.cache.invalidate_domains(&[DataDomain::MarketData]);
+    flash::flash_success(format!("Price settings of {} saved", symbol));
+    Ok(Redirect::to(&format!("/trading/market-data/{}", symbol)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IgnoreSymbolForm {
+    pub ignored: bool,
+    #[serde(default)]
+    pub note: String,
+    /// Market data page to return to
+    #[serde(default)]
+    pub return_to: String,
+}
+
+/// Leave a symbol out of market data refreshes, e.g. a defunct ticker that
+/// will never fetch again, or take it back in. Stored prices are kept.
+pub async fn ignore_symbol(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Form(form): Form<IgnoreSymbolForm>,
+) -> AppResult<Redirect> {
+    let note = form.note.trim();
+    let note = (form.ignored && !note.is_empty()).then_some(note);
+
+    let conn = state.db.get()?;
+    market_data::set_symbol_ignored(&conn, &symbol, form.ignored, note)?;
+    state.cache.invalidate_domains(&[DataDomain::MarketData]);
+    if form.ignored {
+        flash::flash_success(format!("{} is no longer refreshed", symbol));
+    } else {
+        flash::flash_success(format!("{} is refreshed again", symbol));
+    }
+
+    // Only return to a market data page, never to an arbitrary URL
+    let return_to = if form.return_to.starts_with("/trading/market-data") {
+        form.return_to
+    } else {
+        format!("/trading/market-data/{}", symbol)
+    };
+    Ok(Redirect::to(&return_to))
+}
+
+/// Inclusive date window used by the range delete and re-fetch actions
+#[derive(Debug, Deserialize)]
+pub struct DateRangeParams {
+    pub from: String,
+    pub to: String,
+}
+
+impl DateRangeParams {
+    /// Validate that both dates are YYYY-MM-DD and that `from` is not after `to`
+    fn validate(&self) -> AppResult<()> {
+        let parse = |s: &str| {
+            chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map_err(|_| AppError::Validation(format!("Invalid date: {}", s)))
+        };
+        if parse(&self.from)? > parse(&self.to)? {
+            return Err(AppError::Validation(
+                "Start date must not be after end date".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub async fn delete_range(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(params): Query<DateRangeParams>,
+) -> AppResult<Html<String>> {
+    params.validate()?;
+    let conn = state.db.get()?;
+    market_data::delete_range(&conn, &symbol, &params.from, &params.to)?;
+    Ok(Html(String::new()))
+}
+
+pub async fn refetch_range(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Form(params): Form<DateRangeParams>,
+) -> AppResult<Redirect> {
+    params.validate()?;
+    let redirect = Redirect::to(&format!("/trading/market-data/{}", symbol));
+
+    {
+        let refresh_state = state.market_data_refresh.lock().unwrap();
+        if refresh_state.is_refreshing {
+            return Ok(redirect);
+        }
+    }
+
+    market_data_refresh::spawn_symbol_fetch(&state, symbol, vec![(params.from, params.to)], None);
+    Ok(redirect)
+}
+
 pub async fn delete_all(State(state): State<AppState>) -> AppResult<Redirect> {
     let conn = state.db.get()?;
     market_data::delete_all_market_data(&conn)?;
     Ok(Redirect::to("/trading/market-data"))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct SymbolSearchParams {
+    #[serde(default)]
+    pub q: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SymbolSearchResult {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub exchange: String,
+    pub quote_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SymbolSearchResponse {
+    pub results: Vec<SymbolSearchResult>,
+    /// False when Yahoo could not be reached; the form then falls back to
+    /// plain text entry.
+    pub available: bool,
+}
+
+impl SymbolSearchResponse {
+    fn new(results: Vec<market_data_service::SymbolMetadata>) -> Self {
+        Self {
+            results: results
+                .into_iter()
+                .map(|m| SymbolSearchResult {
+                    symbol: m.symbol,
+                    name: m.long_name.or(m.short_name),
+                    exchange: m.exchange,
+                    quote_type: m.quote_type,
+                })
+                .collect(),
+            available: true,
+        }
+    }
+}
+
+/// Autocomplete proxy for Yahoo's symbol search.
+pub async fn search_symbols(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Query(params): Query<SymbolSearchParams>,
+) -> AppResult<Response> {
+    let query = params.q.trim();
+    if query.is_empty() {
+        return Ok(Json(SymbolSearchResponse::new(Vec::new())).into_response());
+    }
+    if let Some(results) = state.cache.symbol_search(query) {
+        return Ok(Json(SymbolSearchResponse::new(results)).into_response());
+    }
+
+    let session = cookies
+        .get(SESSION_COOKIE)
+        .map(|c| c.value().to_string())
+        .unwrap_or_default();
+    if !state.symbol_search_throttle.try_acquire(&session) {
+        return Ok(StatusCode::TOO_MANY_REQUESTS.into_response());
+    }
+
+    let start_time = std::time::Instant::now();
+    let result = market_data_service::search_symbols(query).await;
+    let duration_ms = start_time.elapsed().as_millis() as i64;
+
+    let (status, summary) = match &result {
+        Ok(results) => ("success", format!("Found {} matches", results.len())),
+        Err(e) => ("error", e.to_string()),
+    };
+    let conn = state.db.get()?;
+    api_logs::insert_api_log(
+        &conn,
+        &NewApiLog {
+            api_name: "yahoo_finance".to_string(),
+            action: "search_symbols".to_string(),
+            symbol: None,
+            request_params: serde_json::json!({ "query": query }).to_string(),
+            status: status.to_string(),
+            response_summary: Some(summary),
+            response_details: None,
+            duration_ms: Some(duration_ms),
+            retried_from: None,
+        },
+    )?;
+
+    match result {
+        Ok(results) => {
+            state.cache.store_symbol_search(query, results.clone());
+            Ok(Json(SymbolSearchResponse::new(results)).into_response())
+        }
+        Err(e) => {
+            tracing::warn!(query = %query, error = %e, "Symbol search failed");
+            Ok(Json(SymbolSearchResponse {
+                results: Vec::new(),
+                available: false,
+            })
+            .into_response())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SymbolSelectForm {
+    pub symbol: String,
+}
+
+/// Store metadata for a symbol picked from the autocomplete, so its name
+/// shows up before any market data has been fetched.
+pub async fn select_symbol(
+    State(state): State<AppState>,
+    Form(form): Form<SymbolSelectForm>,
+) -> AppResult<StatusCode> {
+    let meta = state
+        .cache
+        .searched_symbol(form.symbol.trim())
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Symbol {} not in recent search results",
+                form.symbol
+            ))
+        })?;
+
+    let conn = state.db.get()?;
+    market_data::upsert_symbol_metadata(
+        &conn,
+        &meta.symbol,
+        meta.short_name.as_deref(),
+        meta.long_name.as_deref(),
+        Some(&meta.exchange),
+        Some(&meta.quote_type),
+    )?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,34 +1,85 @@
 pub mod accounts;
 pub mod api;
+pub mod api_categories;
 pub mod api_logs;
+pub mod api_sankey;
 pub mod balances;
+pub mod capital_gains;
 pub mod categories;
+pub mod category_review;
 pub mod dashboard;
 pub mod import;
 pub mod import_preview;
+pub mod import_review;
 pub mod manage;
 pub mod market_data;
 pub mod net_worth;
+pub mod portfolio_metrics;
+pub mod position_chart;
+pub mod position_detail;
+pub mod position_export;
 pub mod recurring_expenses;
 pub mod retirement;
 pub mod rules;
+pub mod sankey_accounts;
+pub mod saved_charts;
 pub mod settings;
+pub mod settings_advanced;
+pub mod settings_database;
+pub mod share;
 pub mod spending;
 pub mod tags;
 pub mod trading_activities;
+pub mod trading_activities_export;
+pub mod trading_activities_form;
 pub mod trading_import;
 pub mod trading_positions;
 pub mod transactions;
+pub mod transactions_bulk;
+pub mod transactions_form;
+pub mod transactions_json;
+pub mod transfers;
+pub mod usage;
 
 use axum::routing::{delete, get, post, put};
 use axum::Router;
 
+use crate::body_limit::{with_body_limit, FORM_BODY_LIMIT_BYTES};
+use crate::config::Config;
 use crate::state::AppState;
 
-pub fn routes() -> Router<AppState> {
+pub fn routes(config: &Config) -> Router<AppState> {
+    with_body_limit(page_routes(), FORM_BODY_LIMIT_BYTES)
+        .merge(with_body_limit(upload_routes(), config.max_upload_bytes()))
+}
+
+/// Imports and database restores, which accept larger bodies.
+fn upload_routes() -> Router<AppState> {
+    Router::new()
+        .route("/transactions/import", post(transactions_json::import))
+        .route("/manage/import", post(manage::import))
+        .route("/manage/import/preview", post(manage::import_preview))
+        .route("/accounts/import", post(accounts::import))
+        .route("/accounts/import/preview", post(accounts::import_preview))
+        .route("/import/upload", post(import::upload))
+        .route(
+            "/trading/activities/import",
+            post(trading_activities_export::import),
+        )
+        .route("/trading/import/upload", post(trading_import::upload))
+        .route(
+            "/settings/import-database",
+            post(settings_database::import_database),
+        )
+}
+
+fn page_routes() -> Router<AppState> {
     Router::new()
         // Pages
-        .route("/", get(dashboard::index))
+        .route("/", get(dashboard::start))
+        .route("/dashboard", get(dashboard::index))
+        .route("/api/dashboard/digest", get(dashboard::digest))
+        .route("/api/palette", get(categories::palette_colors))
         .route("/balances", get(balances::index))
         // Retirement Calculator
         .route("/retirement", get(retirement::index))
@@ -49,40 +100,98 @@ pub fn routes() -> Router<AppState> {
             "/spending/category-transactions",
             get(spending::category_transactions),
         )
+        .route(
+            "/spending/charts",
+            get(saved_charts::list).post(saved_charts::create),
+        )
+        .route(
+            "/spending/charts/:id",
+            get(saved_charts::detail)
+                .post(saved_charts::update)
+                .delete(saved_charts::delete),
+        )
+        .route(
+            "/spending/subscriptions",
+            get(recurring_expenses::subscriptions),
+        )
+        .route(
+            "/api/spending/subscriptions",
+            get(recurring_expenses::subscriptions_json),
+        )
         .route("/recurring-expenses", get(recurring_expenses::index))
         .route("/transactions", get(transactions::index))
         .route("/import", get(import::index))
         .route("/settings", get(settings::index))
         // Transaction CRUD
-        .route("/transactions/new", get(transactions::new_form))
-        .route("/transactions/create", post(transactions::create))
+        .route("/transactions/new", get(transactions_form::new_form))
+        .route("/transactions/create", post(transactions_form::create))
+        .route("/transactions/transfer", get(transfers::new_form))
+        .route("/transactions/transfer/create", post(transfers::create))
+        .route("/transactions/detect-transfers", post(transfers::detect))
         .route("/transactions/table", get(transactions::table_partial))
         .route("/transactions/bulk", get(transactions::bulk_page))
+        .route("/transactions/review", get(category_review::index))
+        .route("/transactions/review/accept", post(category_review::accept))
+        .route("/transactions/:id/review", post(category_review::fix))
         .route("/transactions/:id", get(transactions::show))
-        .route("/transactions/:id/edit", get(transactions::edit_form))
-        .route("/transactions/:id/update", post(transactions::update))
+        .route("/transactions/:id/edit", get(transactions_form::edit_form))
+        .route(
+            "/transactions/:id/duplicate",
+            get(transactions_form::duplicate_form).post(transactions_form::duplicate),
+        )
+        .route("/transactions/:id/rule-suggestion", get(rules::suggestion))
+        .route("/transactions/:id/update", post(transactions_form::update))
         .route("/transactions/:id/delete", delete(transactions::delete))
+        .route(
+            "/transactions/:id/mark-posted",
+            post(transactions::mark_posted),
+        )
         .route("/transactions/delete-all", delete(transactions::delete_all))
         .route(
             "/transactions/bulk-category",
-            post(transactions::bulk_set_category),
+            post(transactions_bulk::bulk_set_category),
+        )
+        .route(
+            "/transactions/bulk-tag",
+            post(transactions_bulk::bulk_add_tag),
+        )
+        .route(
+            "/transactions/bulk/remove-tag",
+            post(transactions_bulk::bulk_remove_tag),
+        )
+        .route(
+            "/transactions/bulk/clear-tags",
+            post(transactions_bulk::bulk_clear_tags),
         )
-        .route("/transactions/bulk-tag", post(transactions::bulk_add_tag))
         .route(
             "/transactions/bulk-account",
-            post(transactions::bulk_set_account),
+            post(transactions_bulk::bulk_set_account),
+        )
+        .route(
+            "/transactions/bulk/by-ids/category",
+            post(transactions_bulk::bulk_ids_set_category),
+        )
+        .route(
+            "/transactions/bulk/by-ids/tag",
+            post(transactions_bulk::bulk_ids_add_tag),
         )
-        .route("/transactions/export", get(transactions::export))
-        .route("/transactions/import", post(transactions::import))
+        .route(
+            "/transactions/bulk/by-ids/account",
+            post(transactions_bulk::bulk_ids_set_account),
+        )
+        .route(
+            "/transactions/bulk/by-ids/delete",
+            post(transactions_bulk::bulk_ids_delete),
+        )
+        .route("/transactions/export", get(transactions_json::export))
         // Manage (unified categories/tags/rules)
         .route("/manage", get(manage::index))
         .route("/manage/export", get(manage::export))
-        .route("/manage/import", post(manage::import))
-        .route("/manage/import/preview", post(manage::import_preview))
         // Category management
         .route("/categories/new", get(categories::new_form))
         .route("/categories/create", post(categories::create))
         .route("/categories/delete-all", delete(categories::delete_all))
+        .route("/categories/repalette", post(categories::repalette))
         .route("/categories/:id", get(categories::show))
         .route("/categories/:id/edit", get(categories::edit_form))
         .route("/categories/:id/update", post(categories::update_form))
@@ -97,10 +206,17 @@ pub fn routes() -> Router<AppState> {
         .route("/accounts/new", get(accounts::new_form))
         .route("/accounts/create", post(accounts::create))
         .route("/accounts/export", get(accounts::export))
-        .route("/accounts/import", post(accounts::import))
-        .route("/accounts/import/preview", post(accounts::import_preview))
         .route("/accounts/:id/edit", get(accounts::edit_form))
         .route("/accounts/:id/update", post(accounts::update))
+        .route("/api/accounts/balances", get(balances::api_balances))
+        .route(
+            "/api/accounts/:id/interest-projection",
+            get(accounts::interest_projection),
+        )
+        .route(
+            "/api/accounts/:id/cycle-summary",
+            get(accounts::cycle_summary),
+        )
         .route("/accounts/:id", delete(accounts::delete))
         .route("/accounts/delete-all", delete(accounts::delete_all))
         // Tag management
@@ -119,72 +235,121 @@ pub fn routes() -> Router<AppState> {
         .route("/rules/:id/update", post(rules::update))
         .route("/rules/:id/preview", get(rules::preview))
         .route("/rules/:id/apply", post(rules::apply))
+        .route("/rules/:id/matches", get(rules::matches))
+        .route("/rules/:id/reset-stats", post(rules::reset_statistics))
         .route("/rules/:id/delete", delete(rules::delete))
         .route("/rules/delete-all", delete(rules::delete_all))
         // Import
         .route("/import/format", get(import::format))
-        .route("/import/upload", post(import::upload))
+        .route("/import/sessions/open", get(import::open_sessions))
         .route("/import/:session_id", get(import::wizard))
         .route("/import/:session_id/status", get(import::status))
         .route("/import/:session_id/status.json", get(import::status_json))
         .route("/import/:session_id/rows", get(import::rows))
         .route(
             "/import/:session_id/rows/:row_id/category",
-            post(import::update_row_category),
+            post(import_review::update_row_category),
         )
         .route(
             "/import/:session_id/categories",
-            post(import::update_all_categories),
+            post(import_review::update_all_categories),
+        )
+        .route(
+            "/import/:session_id/categories/by-payee",
+            post(import_review::update_payee_categories),
+        )
+        .route(
+            "/import/:session_id/tags",
+            post(import_review::update_session_tags),
         )
         .route("/import/:session_id/confirm", post(import::confirm))
         .route("/import/:session_id/result", get(import::result))
         .route("/import/:session_id/cancel", get(import::cancel))
         // Trading Activities
         .route("/trading/activities", get(trading_activities::index))
-        .route("/trading/activities/new", get(trading_activities::new_form))
+        .route(
+            "/trading/activities/new",
+            get(trading_activities_form::new_form),
+        )
         .route(
             "/trading/activities/create",
-            post(trading_activities::create),
+            post(trading_activities_form::create),
         )
         .route(
             "/trading/activities/table",
             get(trading_activities::table_partial),
         )
+        .route(
+            "/trading/activities/drip",
+            post(trading_activities_form::drip),
+        )
+        .route(
+            "/trading/activities/split-preview",
+            get(trading_activities::split_preview),
+        )
+        .route(
+            "/trading/activities/trash",
+            get(trading_activities::trash).delete(trading_activities::empty_trash),
+        )
         .route("/trading/activities/:id", get(trading_activities::detail))
         .route(
             "/trading/activities/:id/edit",
-            get(trading_activities::edit_form),
+            get(trading_activities_form::edit_form),
+        )
+        .route(
+            "/trading/activities/:id/duplicate",
+            get(trading_activities_form::duplicate_form).post(trading_activities_form::duplicate),
         )
         .route(
             "/trading/activities/:id/update",
-            post(trading_activities::update),
+            post(trading_activities_form::update),
         )
         .route(
             "/trading/activities/:id/delete",
             delete(trading_activities::delete),
         )
+        .route(
+            "/trading/activities/:id/restore",
+            post(trading_activities::restore),
+        )
         .route(
             "/trading/activities/delete-all",
             delete(trading_activities::delete_all),
         )
         .route(
             "/trading/activities/export",
-            get(trading_activities::export),
-        )
-        .route(
-            "/trading/activities/import",
-            post(trading_activities::import),
+            get(trading_activities_export::export),
         )
         // Trading Positions
         .route("/trading/positions", get(trading_positions::index))
+        .route(
+            "/trading/positions/export",
+            get(position_export::export_positions),
+        )
         .route(
             "/trading/positions/closed",
             get(trading_positions::closed_positions),
         )
-        .route("/trading/positions/:symbol", get(trading_positions::detail))
+        .route(
+            "/trading/positions/closed/export",
+            get(position_export::export_closed_positions),
+        )
+        .route("/trading/positions/:symbol", get(position_detail::detail))
+        .route(
+            "/trading/positions/:symbol/export",
+            get(position_export::export_position_history),
+        )
+        .route("/api/trading/fees", get(trading_positions::fee_totals))
+        .route(
+            "/api/trading/portfolio-metrics",
+            get(portfolio_metrics::portfolio_metrics),
+        )
+        .route("/trading/capital-gains", get(capital_gains::index))
+        .route("/trading/capital-gains/export", get(capital_gains::export))
+        .route("/api/trading/wash-sales", get(capital_gains::wash_sales))
         .route(
             "/api/positions/:symbol/chart",
-            get(trading_positions::position_chart_data),
+            get(position_chart::position_chart_data),
         )
         // Net Worth
         .route("/trading/net-worth", get(net_worth::index))
@@ -217,18 +382,36 @@ pub fn routes() -> Router<AppState> {
             "/trading/market-data/:symbol/delete",
             delete(market_data::delete_symbol),
         )
+        .route(
+            "/trading/market-data/:symbol/price-scale",
+            post(market_data::update_price_scale),
+        )
+        .route(
+            "/trading/market-data/:symbol/ignore",
+            post(market_data::ignore_symbol),
+        )
+        .route(
+            "/trading/market-data/:symbol/range",
+            delete(market_data::delete_range),
+        )
+        .route(
+            "/trading/market-data/:symbol/refetch-range",
+            post(market_data::refetch_range),
+        )
         .route(
             "/api/market-data/:symbol",
             get(market_data::symbol_chart_data),
         )
+        .route("/api/symbols/search", get(market_data::search_symbols))
+        .route("/api/symbols/select", post(market_data::select_symbol))
         // API Logs
         .route("/trading/api-logs", get(api_logs::index))
         .route("/trading/api-logs/:id", get(api_logs::detail))
+        .route("/trading/api-logs/:id/retry", post(api_logs::retry))
         .route("/api/api-logs/poll", get(api_logs::poll_errors))
         // Trading Import
         .route("/trading/import", get(trading_import::index))
         .route("/trading/import/format", get(trading_import::format))
-        .route("/trading/import/upload", post(trading_import::upload))
         .route("/trading/import/:session_id", get(trading_import::wizard))
         .route(
             "/trading/import/:session_id/status",
@@ -238,10 +421,26 @@ pub fn routes() -> Router<AppState> {
             "/trading/import/:session_id/status.json",
             get(trading_import::status_json),
         )
+        .route(
+            "/trading/import/:session_id/preview",
+            get(trading_import::preview),
+        )
+        .route(
+            "/trading/import/:session_id/preview.json",
+            get(trading_import::preview_json),
+        )
         .route(
             "/trading/import/:session_id/rows",
             get(trading_import::rows),
         )
+        .route(
+            "/trading/import/:session_id/validate-symbols",
+            get(trading_import::validation_status).post(trading_import::validate_symbols),
+        )
+        .route(
+            "/trading/import/:session_id/validate-symbols/cancel",
+            post(trading_import::cancel_validation),
+        )
         .route(
             "/trading/import/:session_id/confirm",
             post(trading_import::confirm),
@@ -256,33 +455,90 @@ pub fn routes() -> Router<AppState> {
         )
         // Settings
         .route("/settings/update", post(settings::update))
+        .route(
+            "/settings/advanced",
+            get(settings_advanced::advanced).post(settings_advanced::update_advanced),
+        )
+        .route(
+            "/settings/share-links",
+            get(share::index).post(share::create),
+        )
+        .route("/settings/share-links/:id", delete(share::revoke))
+        .route(
+            "/settings/usage",
+            get(usage::index).post(usage::update).delete(usage::purge),
+        )
+        // Public share links (no login; see auth::auth_middleware)
+        .route("/share/:token", get(share::view).post(share::unlock))
         .route("/settings/theme", post(settings::toggle_theme))
-        .route("/settings/export-database", get(settings::export_database))
-        .route("/settings/import-database", post(settings::import_database))
-        .route("/settings/clear-database", delete(settings::clear_database))
+        .route(
+            "/settings/table-columns",
+            post(settings::update_table_columns),
+        )
+        .route("/settings/navigation", post(settings::update_navigation))
+        .route(
+            "/settings/default-sort",
+            post(settings::update_default_sort),
+        )
+        .route("/settings/backup", post(settings::update_backup))
+        .route("/settings/backup-now", post(settings::backup_now))
+        .route(
+            "/settings/notifications",
+            post(settings::update_notifications),
+        )
+        .route(
+            "/settings/notifications/test",
+            post(settings::test_notification),
+        )
+        .route(
+            "/settings/export-database",
+            get(settings_database::export_database),
+        )
+        .route(
+            "/settings/export-anonymized",
+            get(settings_database::export_anonymized),
+        )
+        .route(
+            "/settings/diagnostics-bundle",
+            get(settings_database::diagnostics_bundle),
+        )
+        .route(
+            "/settings/clear-database",
+            delete(settings_database::clear_database),
+        )
         // API (JSON for charts)
         .route(
             "/api/analytics/spending-by-category",
             get(api::spending_by_category),
         )
+        .route(
+            "/api/analytics/spending-comparison",
+            get(api::spending_comparison),
+        )
         .route(
             "/api/analytics/spending-over-time",
             get(api::spending_over_time),
         )
         .route("/api/analytics/monthly-summary", get(api::monthly_summary))
+        .route(
+            "/api/analytics/spending-patterns",
+            get(api::spending_patterns),
+        )
         .route(
             "/api/analytics/spending-by-category-tree",
-            get(api::spending_by_category_tree),
+            get(api_categories::spending_by_category_tree),
         )
         .route(
             "/api/analytics/monthly-by-category",
-            get(api::monthly_by_category),
+            get(api_categories::monthly_by_category),
         )
-        .route("/api/analytics/flow-sankey", get(api::flow_sankey))
+        .route("/api/analytics/flow-sankey", get(api_sankey::flow_sankey))
         // Icons API
         .route("/api/icons", get(api::icon_names))
         .route("/api/icons/all", get(api::icon_all))
         .route("/api/icons/:name", get(api::icon_svg))
+        // Cache versions
+        .route("/api/cache/stats", get(api::cache_stats))
         // Health check
         .route("/health", get(health))
 }
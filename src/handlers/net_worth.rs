@@ -2,17 +2,23 @@ use askama::Template;
 use axum::extract::{Query, State};
 use axum::response::Html;
 use axum::Json;
+use chrono::NaiveDate;
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 
-use crate::db::queries::{balances, market_data, trading, transactions};
+use crate::date_utils::{self, DateFilterable, DatePreset, DateRange};
+use crate::db::queries::{balances, positions, trading, transactions};
 use crate::error::{AppResult, RenderHtml};
-use crate::filters;
+use crate::handlers::trading_positions::{
+    count_stale_positions, enrich_position, position_value_cents,
+};
 use crate::handlers::transactions::TransactionPreviewTemplate;
 use crate::models::account::AccountType;
 use crate::models::net_worth::NetWorthDataPoint;
-use crate::models::trading::{Position, PositionWithMarketData};
-use crate::models::Settings;
-use crate::services::net_worth::{calculate_net_worth_history, decimate_for_display};
+use crate::models::trading::Position;
+use crate::models::{Settings, TransactionStatus};
+use crate::services::cash_ledger;
+use crate::services::net_worth::decimate_for_display;
 use crate::state::{AppState, JsManifest, PageBase};
 
 const MAX_CHART_POINTS: usize = 500;
@@ -25,6 +31,64 @@ const PALETTE: &[&str] = &[
 #[derive(Debug, Default, Deserialize)]
 pub struct NetWorthParams {
     pub tab: Option<String>,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub preset: Option<String>,
+    /// Comma-separated component series to include in the chart (all when absent).
+    pub components: Option<String>,
+}
+
+impl DateFilterable for NetWorthParams {
+    fn from_date(&self) -> Option<&String> {
+        self.from_date.as_ref()
+    }
+    fn to_date(&self) -> Option<&String> {
+        self.to_date.as_ref()
+    }
+    fn preset(&self) -> Option<&String> {
+        self.preset.as_ref()
+    }
+    fn presets(&self) -> &'static [DatePreset] {
+        DatePreset::net_worth()
+    }
+}
+
+/// First and last date of any transaction or trading activity, and of the
+/// trading activities alone.
+type Extents = (Option<(String, String)>, Option<(String, String)>);
+
+fn data_extents(conn: &Connection) -> AppResult<Extents> {
+    let investments = trading::date_extent(conn)?;
+    let extent = date_utils::combine_extents(transactions::date_extent(conn)?, investments.clone());
+    Ok((extent, investments))
+}
+
+/// Resolve the selected date range: "All" starts with the first transaction
+/// or trading activity, whichever came first, and "Since First Investment"
+/// with the first trading activity.
+fn resolve_range(params: &NetWorthParams, extents: Extents, today: NaiveDate) -> DateRange {
+    let (extent, investments) = extents;
+    params
+        .resolve_date_range(today)
+        .resolve_since_first_investment(extent, investments)
+}
+
+/// Data points within `range`, plus the baseline point the period's change is
+/// measured against: the last point before the range, or the first one inside it.
+fn points_in_range<'a>(
+    data_points: &'a [NetWorthDataPoint],
+    range: &DateRange,
+) -> (&'a [NetWorthDataPoint], Option<&'a NetWorthDataPoint>) {
+    let from = range.from_str();
+    let to = range.to_str();
+    let start = data_points.partition_point(|p| p.date < from);
+    let end = data_points.partition_point(|p| p.date <= to);
+    let in_range = &data_points[start..end.max(start)];
+    let baseline = start
+        .checked_sub(1)
+        .map(|i| &data_points[i])
+        .or(in_range.first());
+    (in_range, baseline)
 }
 
 #[derive(Template)]
@@ -36,6 +100,7 @@ pub struct NetWorthTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub has_data: bool,
     pub current_net_worth_formatted: String,
     pub highest_net_worth_formatted: String,
@@ -48,39 +113,57 @@ pub struct NetWorthTemplate {
     pub end_date: String,
     pub current_net_worth_cents: i64,
     pub change_cents: i64,
+    pub contributions_formatted: String,
+    pub growth_formatted: String,
+    pub contributions_cents: i64,
+    pub growth_cents: i64,
     pub active_tab: String,
+    /// Number of positions valued with a stale or approximated price.
+    pub stale_price_count: usize,
+    pub date_range: DateRange,
+    pub presets: &'static [DatePreset],
+    pub base_qs: String,
+    pub components: &'static [NetWorthComponent],
 }
 
 pub async fn index(
     State(state): State<AppState>,
     Query(params): Query<NetWorthParams>,
 ) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
     let PageBase {
         settings,
         icons,
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
-    let summary = calculate_net_worth_history(&conn)?;
-
-    let currency = settings.currency.clone();
-    let locale = settings.locale.clone();
+    let summary = state.cached_net_worth()?;
+    let conn = state.db.get()?;
+    let stale_price_count = count_stale_positions(&conn, &settings)?;
+    let date_range = resolve_range(
+        &params,
+        data_extents(&conn)?,
+        date_utils::today_in(&settings),
+    );
+    let (points, baseline) = points_in_range(&summary.data_points, &date_range);
 
     let has_data = !summary.data_points.is_empty();
-    let total_days = summary.data_points.len();
-
-    // Get starting net worth (first data point)
-    let starting_net_worth_cents = summary
-        .data_points
-        .first()
-        .map(|p| p.net_worth_cents)
-        .unwrap_or(0);
-
-    // Calculate change from start
-    let change_cents = summary.current_net_worth_cents - starting_net_worth_cents;
+    let total_days = points.len();
+
+    // Period values are measured against the baseline point
+    let starting_net_worth_cents = baseline.map(|p| p.net_worth_cents).unwrap_or(0);
+    let ending = points.last().or(baseline);
+    let ending_net_worth_cents = ending.map(|p| p.net_worth_cents).unwrap_or(0);
+    let highest_net_worth_cents = points.iter().map(|p| p.net_worth_cents).max().unwrap_or(0);
+    let lowest_net_worth_cents = points.iter().map(|p| p.net_worth_cents).min().unwrap_or(0);
+
+    // Calculate change over the period, split into contributions and growth
+    let change_cents = ending_net_worth_cents - starting_net_worth_cents;
+    let contributions_cents = ending.map(|p| p.contributions_cents).unwrap_or(0)
+        - baseline.map(|p| p.contributions_cents).unwrap_or(0);
+    let growth_cents = change_cents - contributions_cents;
     let change_percent = if starting_net_worth_cents != 0 {
         (change_cents as f64 / starting_net_worth_cents.abs() as f64) * 100.0
     } else {
@@ -88,24 +171,20 @@ pub async fn index(
     };
 
     let current_net_worth_formatted =
-        filters::format_money_neutral(summary.current_net_worth_cents, &currency, &locale);
-    let highest_net_worth_formatted =
-        filters::format_money_neutral(summary.highest_net_worth_cents, &currency, &locale);
-    let lowest_net_worth_formatted =
-        filters::format_money_neutral(summary.lowest_net_worth_cents, &currency, &locale);
-    let starting_net_worth_formatted =
-        filters::format_money_neutral(starting_net_worth_cents, &currency, &locale);
-    let change_formatted = filters::format_money_plain(change_cents, &currency, &locale);
-    let change_percent_formatted = format!(
-        "{}{:.2}%",
-        if change_percent >= 0.0 { "+" } else { "" },
-        change_percent
-    );
+        settings.format_money_neutral(&summary.current_net_worth_cents);
+    let highest_net_worth_formatted = settings.format_money_neutral(&highest_net_worth_cents);
+    let lowest_net_worth_formatted = settings.format_money_neutral(&lowest_net_worth_cents);
+    let starting_net_worth_formatted = settings.format_money_neutral(&starting_net_worth_cents);
+    let change_formatted = settings.format_money_plain(&change_cents);
+    let contributions_formatted = settings.format_money_plain(&contributions_cents);
+    let growth_formatted = settings.format_money_plain(&growth_cents);
+    let change_percent_formatted = settings.format_percent_val(change_percent);
 
     let active_tab = match params.tab.as_deref() {
         Some("allocation") => "allocation".to_string(),
         _ => "overview".to_string(),
     };
+    let base_qs = format!("tab={}", active_tab);
 
     let template = NetWorthTemplate {
         title: "Net Worth".into(),
@@ -114,6 +193,7 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         has_data,
         current_net_worth_formatted,
         highest_net_worth_formatted,
@@ -122,53 +202,149 @@ pub async fn index(
         change_formatted,
         change_percent_formatted,
         total_days,
-        start_date: summary.start_date,
-        end_date: summary.end_date,
+        start_date: points.first().map(|p| p.date.clone()).unwrap_or_default(),
+        end_date: points.last().map(|p| p.date.clone()).unwrap_or_default(),
         current_net_worth_cents: summary.current_net_worth_cents,
         change_cents,
+        contributions_formatted,
+        growth_formatted,
+        contributions_cents,
+        growth_cents,
         active_tab,
+        stale_price_count,
+        date_range,
+        presets: DatePreset::net_worth(),
+        base_qs,
+        components: NetWorthComponent::ALL,
     };
 
     template.render_html()
 }
 
+/// A part of net worth that can be charted as its own stacked series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetWorthComponent {
+    /// Balance of all cash transactions
+    Cash,
+    /// Market value of securities positions
+    Securities,
+}
+
+impl NetWorthComponent {
+    pub const ALL: &'static [NetWorthComponent] = &[Self::Cash, Self::Securities];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Cash => "cash",
+            Self::Securities => "securities",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Cash => "Cash",
+            Self::Securities => "Securities",
+        }
+    }
+
+    /// Parse the `components` query parameter; unknown names are ignored.
+    fn parse_list(value: Option<&str>) -> Vec<Self> {
+        let Some(value) = value else {
+            return Self::ALL.to_vec();
+        };
+        let requested: Vec<&str> = value.split(',').map(str::trim).collect();
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(|c| requested.contains(&c.key()))
+            .collect()
+    }
+}
+
+/// One stacked component series of the net worth chart.
+#[derive(Serialize)]
+pub struct ComponentSeries {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub values: Vec<i64>,
+}
+
 /// Chart data response
 #[derive(Serialize)]
 pub struct NetWorthChartResponse {
     pub labels: Vec<String>,
     pub net_worth: Vec<i64>,
-    pub transaction_component: Vec<i64>,
-    pub portfolio_component: Vec<i64>,
+    /// Requested component series; the full set sums to `net_worth` at every point.
+    pub components: Vec<ComponentSeries>,
+    pub contributions: Vec<i64>,
+    pub growth: Vec<i64>,
+    /// Requested range clamped to the dates with data; `None` without data.
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
 }
 
-impl NetWorthChartResponse {
-    fn from_data_points(data_points: Vec<NetWorthDataPoint>) -> Self {
-        let decimated = decimate_for_display(&data_points, MAX_CHART_POINTS);
+// Cap values to JavaScript's safe integer range to prevent precision loss
+const MAX_SAFE: i64 = 9_007_199_254_740_991; // 2^53 - 1
 
-        // Cap values to JavaScript's safe integer range to prevent precision loss
-        const MAX_SAFE: i64 = 9_007_199_254_740_991; // 2^53 - 1
-        let clamp = |v: i64| v.clamp(-MAX_SAFE, MAX_SAFE);
+fn clamp(v: i64) -> i64 {
+    v.clamp(-MAX_SAFE, MAX_SAFE)
+}
+
+/// Value of a component at a data point. Securities are derived from the
+/// (clamped) total so that cash and securities always add up to it exactly.
+fn component_value(component: NetWorthComponent, point: &NetWorthDataPoint) -> i64 {
+    let cash = clamp(point.transaction_component_cents);
+    match component {
+        NetWorthComponent::Cash => cash,
+        NetWorthComponent::Securities => clamp(point.net_worth_cents).saturating_sub(cash),
+    }
+}
+
+impl NetWorthChartResponse {
+    fn from_data_points(
+        data_points: &[NetWorthDataPoint],
+        components: &[NetWorthComponent],
+    ) -> Self {
+        let decimated = decimate_for_display(data_points, MAX_CHART_POINTS);
 
         Self {
             labels: decimated.iter().map(|p| p.date.clone()).collect(),
             net_worth: decimated.iter().map(|p| clamp(p.net_worth_cents)).collect(),
-            transaction_component: decimated
+            components: components
                 .iter()
-                .map(|p| clamp(p.transaction_component_cents))
+                .map(|&c| ComponentSeries {
+                    key: c.key(),
+                    label: c.label(),
+                    values: decimated.iter().map(|p| component_value(c, p)).collect(),
+                })
                 .collect(),
-            portfolio_component: decimated
+            contributions: decimated
                 .iter()
-                .map(|p| clamp(p.portfolio_component_cents))
+                .map(|p| clamp(p.contributions_cents))
                 .collect(),
+            growth: decimated.iter().map(|p| clamp(p.growth_cents)).collect(),
+            from_date: None,
+            to_date: None,
         }
     }
 }
 
-pub async fn chart_data(State(state): State<AppState>) -> AppResult<Json<NetWorthChartResponse>> {
+pub async fn chart_data(
+    State(state): State<AppState>,
+    Query(params): Query<NetWorthParams>,
+) -> AppResult<Json<NetWorthChartResponse>> {
+    let summary = state.cached_net_worth()?;
+    let today = date_utils::today_in(&state.load_settings()?);
     let conn = state.db.get()?;
-    let summary = calculate_net_worth_history(&conn)?;
+    let extents = data_extents(&conn)?;
+    let extent = extents.0.clone();
+    let date_range = resolve_range(&params, extents, today);
+    let effective = date_range.clamp_to(extent.as_ref());
+    let (points, _) = points_in_range(&summary.data_points, &date_range);
 
-    let response = NetWorthChartResponse::from_data_points(summary.data_points);
+    let components = NetWorthComponent::parse_list(params.components.as_deref());
+    let mut response = NetWorthChartResponse::from_data_points(points, &components);
+    (response.from_date, response.to_date) = effective.unzip();
 
     Ok(Json(response))
 }
@@ -195,6 +371,8 @@ pub async fn top_transactions(
         to_date: Some(params.to_date.clone()),
         sort_sql: Some("ABS(e.amount_cents) DESC".to_string()),
         limit: Some(20),
+        status: Some(TransactionStatus::Posted),
+        with_tags: false,
         ..Default::default()
     };
 
@@ -226,20 +404,30 @@ pub struct AllocationNode {
     pub color: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub amount_cents: Option<i64>,
+    /// Date of the price a position node is valued at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_date: Option<String>,
+    /// Whether the value rests on a stale or approximated price.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_stale: bool,
     pub children: Vec<AllocationNode>,
 }
 
 /// Returns the account allocation tree for the sunburst chart.
-/// Cash accounts are leaf nodes; securities accounts have children for each position.
+/// Cash accounts are leaf nodes; securities accounts have children for each position,
+/// plus a "Cash" child if they derive their cash from trading activities.
 /// Transactions and trading activities not linked to any account are shown under
 /// virtual "Other Cash" / "Other Securities" nodes.
 pub async fn account_allocation(
     State(state): State<AppState>,
 ) -> AppResult<Json<Vec<AllocationNode>>> {
     let conn = state.db.get()?;
+    let settings = state.load_settings()?;
+    let allow_short = settings.allow_short_positions;
 
     let all_accounts = state.cached_accounts()?;
     let cash_balances = balances::get_cash_account_balances(&conn)?;
+    let ledgers = cash_ledger::replay(&conn)?;
 
     let mut nodes: Vec<AllocationNode> = Vec::new();
     let mut color_index = 0;
@@ -249,29 +437,46 @@ pub async fn account_allocation(
         color_index += 1;
 
         match account.account_type {
-            AccountType::Cash => {
+            AccountType::Cash | AccountType::CreditCard => {
                 let balance = *cash_balances.get(&account.id).unwrap_or(&0);
                 if balance > 0 {
                     nodes.push(AllocationNode {
                         name: account.name.clone(),
                         color,
                         amount_cents: Some(balance),
+                        price_date: None,
+                        is_stale: false,
                         children: vec![],
                     });
                 }
             }
             AccountType::Securities => {
-                let children = positions_to_allocation_nodes(
+                let mut children = positions_to_allocation_nodes(
                     &conn,
-                    &trading::get_positions_for_account(&conn, account.id)?,
+                    &positions::get_positions_for_account(&conn, account.id, allow_short)?,
                     &color,
+                    &settings,
                 )?;
+                if let Some(ledger) = ledgers.accounts.get(&account.id) {
+                    if ledger.balance_cents > 0 {
+                        children.push(AllocationNode {
+                            name: "Cash".into(),
+                            color: color.clone(),
+                            amount_cents: Some(ledger.balance_cents),
+                            price_date: None,
+                            is_stale: false,
+                            children: vec![],
+                        });
+                    }
+                }
 
                 if !children.is_empty() {
                     nodes.push(AllocationNode {
                         name: account.name.clone(),
                         color,
                         amount_cents: None,
+                        price_date: None,
+                        is_stale: false,
                         children,
                     });
                 }
@@ -288,19 +493,24 @@ pub async fn account_allocation(
             name: "Other Cash".into(),
             color,
             amount_cents: Some(unassociated_cash),
+            price_date: None,
+            is_stale: false,
             children: vec![],
         });
     }
 
     // Virtual node for unassociated trading positions
-    let unassociated_positions = trading::get_positions_without_account(&conn)?;
+    let unassociated_positions = positions::get_positions_without_account(&conn, allow_short)?;
     let color = PALETTE[color_index % PALETTE.len()].to_string();
-    let children = positions_to_allocation_nodes(&conn, &unassociated_positions, &color)?;
+    let children =
+        positions_to_allocation_nodes(&conn, &unassociated_positions, &color, &settings)?;
     if !children.is_empty() {
         nodes.push(AllocationNode {
             name: "Other Securities".into(),
             color,
             amount_cents: None,
+            price_date: None,
+            is_stale: false,
             children,
         });
     }
@@ -309,34 +519,45 @@ pub async fn account_allocation(
 }
 
 /// Convert a list of positions into allocation child nodes, enriching with market data.
+/// Dust positions are bucketed into a single "Other" node.
 fn positions_to_allocation_nodes(
     conn: &rusqlite::Connection,
     positions: &[Position],
     color: &str,
+    settings: &Settings,
 ) -> AppResult<Vec<AllocationNode>> {
     let mut children = Vec::new();
+    let mut dust_cents = 0;
+    let mut dust_stale = false;
     for pos in positions {
-        let enriched = if let Ok(Some(data)) = market_data::get_latest_price(conn, &pos.symbol) {
-            PositionWithMarketData::with_market_data(pos.clone(), data.close_price_cents, data.date)
-        } else if let Ok(Some((price_cents, date))) =
-            trading::get_last_trade_price(conn, &pos.symbol)
-        {
-            PositionWithMarketData::with_approximated_price(pos.clone(), price_cents, date)
-        } else {
-            PositionWithMarketData::from_position(pos.clone())
-        };
-
-        let value = enriched
-            .current_value_cents
-            .unwrap_or(enriched.position.total_cost_cents);
-        if value > 0 {
-            children.push(AllocationNode {
-                name: pos.symbol.clone(),
-                color: color.to_string(),
-                amount_cents: Some(value),
-                children: vec![],
-            });
+        let enriched = enrich_position(conn, pos.clone(), settings);
+        let value = position_value_cents(&enriched);
+        if value <= 0 {
+            continue;
         }
+        if settings.is_dust(value) {
+            dust_cents += value;
+            dust_stale |= enriched.is_stale;
+            continue;
+        }
+        children.push(AllocationNode {
+            name: pos.symbol.clone(),
+            color: color.to_string(),
+            amount_cents: Some(value),
+            price_date: enriched.price_date,
+            is_stale: enriched.is_stale,
+            children: vec![],
+        });
+    }
+    if dust_cents > 0 {
+        children.push(AllocationNode {
+            name: "Other".into(),
+            color: color.to_string(),
+            amount_cents: Some(dust_cents),
+            price_date: None,
+            is_stale: dust_stale,
+            children: vec![],
+        });
     }
     Ok(children)
 }
@@ -0,0 +1,94 @@
+//! Portfolio totals and positions as JSON, for external dashboards.
+
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::Serialize;
+
+use crate::date_utils;
+use crate::db::queries::{market_data, positions, trading};
+use crate::error::AppResult;
+use crate::handlers::trading_positions::{
+    calculate_portfolio_xirr, convert_positions, enrich_position, PositionFilterParams,
+};
+use crate::models::trading::PositionWithMarketData;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct PortfolioMetrics {
+    /// Currency of the totals, `display_currency` if given.
+    pub currency: String,
+    /// Totals of the long positions that can be converted into `currency`,
+    /// like on the positions page.
+    pub total_cost_cents: i64,
+    pub total_value_cents: Option<i64>,
+    pub total_gain_loss_cents: Option<i64>,
+    pub xirr: Option<f64>,
+    /// At least one position has no price or an approximated one.
+    pub xirr_incomplete: bool,
+    /// Positions valued with a stale or approximated price, not counting
+    /// ignored symbols.
+    pub stale_price_count: usize,
+    /// Open positions with their price date and `is_stale` flag.
+    pub positions: Vec<PositionWithMarketData>,
+}
+
+/// Totals, XIRR and positions of the portfolio, with the stale price flags
+/// of the positions page.
+pub async fn portfolio_metrics(
+    State(state): State<AppState>,
+    Query(params): Query<PositionFilterParams>,
+) -> AppResult<Json<PortfolioMetrics>> {
+    let settings = state.load_settings()?;
+    let currency = params.display_currency(&settings)?;
+    let conn = state.db.get()?;
+
+    let mut positions: Vec<PositionWithMarketData> =
+        positions::get_positions(&conn, settings.allow_short_positions)?
+            .into_iter()
+            .map(|pos| enrich_position(&conn, pos, &settings))
+            .collect();
+    convert_positions(&conn, &mut positions, &currency)?;
+
+    let totaled: Vec<&PositionWithMarketData> = positions
+        .iter()
+        .filter(|p| !p.position.is_short())
+        .filter(|p| p.converted.is_some() || p.position.currency.eq_ignore_ascii_case(&currency))
+        .collect();
+    let total_cost_cents: i64 = totaled.iter().map(|p| p.display_cost_cents()).sum();
+    let values: Vec<i64> = totaled
+        .iter()
+        .filter_map(|p| p.display_value_cents())
+        .collect();
+    let total_value_cents = (!values.is_empty()).then(|| values.iter().sum::<i64>());
+
+    let activities = trading::list_activities(
+        &conn,
+        &trading::TradingActivityFilter {
+            sort_sql: Some("date ASC".to_string()),
+            ..Default::default()
+        },
+    )?;
+    let (xirr, xirr_incomplete) = calculate_portfolio_xirr(
+        &activities,
+        &positions,
+        settings.xirr_transfers_at_cost(),
+        date_utils::today_in(&settings),
+    );
+
+    let ignored = market_data::get_ignored_symbols(&conn)?;
+    let stale_price_count = positions
+        .iter()
+        .filter(|p| p.is_stale && !ignored.contains(&p.position.symbol))
+        .count();
+
+    Ok(Json(PortfolioMetrics {
+        currency,
+        total_cost_cents,
+        total_value_cents,
+        total_gain_loss_cents: total_value_cents.map(|value| value - total_cost_cents),
+        xirr,
+        xirr_incomplete,
+        stale_price_count,
+        positions,
+    }))
+}
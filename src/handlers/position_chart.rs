@@ -0,0 +1,389 @@
+//! Price chart data of a position: prices or values over a date range, the
+//! buy and sell markers, and the running cost basis.
+
+use std::collections::HashSet;
+
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::db::queries::{market_data, trading};
+use crate::error::{AppError, AppResult};
+use crate::models::trading::{TradingActivity, TradingActivityType};
+use crate::services::positions::{self, CostBasisStep};
+use crate::state::AppState;
+
+/// Points of the position chart at most; longer ranges are thinned out.
+const MAX_CHART_POINTS: usize = 500;
+
+/// Activity markers at most; beyond that only the largest trades are shown.
+const MAX_CHART_MARKERS: usize = MAX_CHART_POINTS / 5;
+
+/// Quick ranges of the position chart, counted back from the latest price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartRange {
+    OneMonth,
+    ThreeMonths,
+    OneYear,
+    Max,
+}
+
+impl ChartRange {
+    pub fn all() -> &'static [ChartRange] {
+        &[
+            ChartRange::OneMonth,
+            ChartRange::ThreeMonths,
+            ChartRange::OneYear,
+            ChartRange::Max,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChartRange::OneMonth => "1m",
+            ChartRange::ThreeMonths => "3m",
+            ChartRange::OneYear => "1y",
+            ChartRange::Max => "max",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChartRange::OneMonth => "1M",
+            ChartRange::ThreeMonths => "3M",
+            ChartRange::OneYear => "1Y",
+            ChartRange::Max => "Max",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|r| r.as_str() == s)
+    }
+
+    fn months(&self) -> Option<u32> {
+        match self {
+            ChartRange::OneMonth => Some(1),
+            ChartRange::ThreeMonths => Some(3),
+            ChartRange::OneYear => Some(12),
+            ChartRange::Max => None,
+        }
+    }
+}
+
+/// What the position chart plots: the price per share, or the price times
+/// the quantity held on each date.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ChartSeries {
+    #[default]
+    Price,
+    Value,
+}
+
+impl ChartSeries {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChartSeries::Price => "price",
+            ChartSeries::Value => "value",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "price" => Some(ChartSeries::Price),
+            "value" => Some(ChartSeries::Value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PositionChartParams {
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    /// `1m`, `3m`, `1y` or `max` (default); ignored when dates are given.
+    pub range: Option<String>,
+    /// `price` (default) or `value`
+    pub series: Option<String>,
+}
+
+/// Validated chart parameters.
+struct ChartRequest {
+    series: ChartSeries,
+    /// The quick range; `None` when dates were given.
+    range: Option<ChartRange>,
+    from_date: Option<NaiveDate>,
+    to_date: Option<NaiveDate>,
+}
+
+impl PositionChartParams {
+    fn validate(&self) -> AppResult<ChartRequest> {
+        let series = match self.series.as_deref() {
+            None | Some("") => ChartSeries::default(),
+            Some(s) => ChartSeries::parse(s)
+                .ok_or_else(|| AppError::Validation(format!("Invalid series: {}", s)))?,
+        };
+        let from_date = parse_chart_date(self.from_date.as_deref(), "from_date")?;
+        let to_date = parse_chart_date(self.to_date.as_deref(), "to_date")?;
+        let range = if from_date.is_some() || to_date.is_some() {
+            None
+        } else {
+            match self.range.as_deref() {
+                None | Some("") => Some(ChartRange::Max),
+                Some(s) => Some(
+                    ChartRange::parse(s)
+                        .ok_or_else(|| AppError::Validation(format!("Invalid range: {}", s)))?,
+                ),
+            }
+        };
+        Ok(ChartRequest {
+            series,
+            range,
+            from_date,
+            to_date,
+        })
+    }
+}
+
+fn parse_chart_date(value: Option<&str>, name: &str) -> AppResult<Option<NaiveDate>> {
+    match value.filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(v) => NaiveDate::parse_from_str(v, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| AppError::Validation(format!("Invalid {}: {}", name, v))),
+    }
+}
+
+#[derive(Serialize)]
+pub struct PositionChartData {
+    pub date: String,
+    pub price_cents: i64,
+    /// Price times the quantity held, with `series=value` only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_cents: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ActivityMarker {
+    pub date: String,
+    pub activity_type: String,
+    pub quantity: f64,
+    pub price_cents: i64,
+    pub total_cents: i64,
+}
+
+#[derive(Serialize)]
+pub struct CostBasisPoint {
+    pub date: String,
+    /// Average cost per share; `null` while no shares are held.
+    pub avg_cost_cents: Option<i64>,
+    /// Cost of all shares held; `null` while no shares are held.
+    pub cost_cents: Option<i64>,
+}
+
+/// The date range a chart response covers.
+#[derive(Serialize)]
+pub struct AppliedChartRange {
+    /// The quick range in effect; `null` when dates were given.
+    pub range: Option<&'static str>,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PositionChartResponse {
+    pub symbol: String,
+    pub series: &'static str,
+    pub range: AppliedChartRange,
+    pub data: Vec<PositionChartData>,
+    /// Average cost per share on each date of `data`.
+    pub cost_basis: Vec<CostBasisPoint>,
+    /// Current break-even price, including fees, taxes and dividends.
+    pub break_even_cents: Option<i64>,
+    pub activities: Vec<ActivityMarker>,
+    pub is_approximated: bool,
+}
+
+/// Thin `data` out to at most `max_points`, keeping the first and last
+/// point, the extremes of `key`, and the points on `keep_dates` so activity
+/// markers stay on the axis. The kept points count against the limit.
+fn decimate_chart_points(
+    data: Vec<PositionChartData>,
+    max_points: usize,
+    key: impl Fn(&PositionChartData) -> i64,
+    keep_dates: &[&str],
+) -> Vec<PositionChartData> {
+    if data.len() <= max_points || max_points == 0 {
+        return data;
+    }
+
+    let keep_dates: HashSet<&str> = keep_dates.iter().copied().collect();
+    let min_idx = (0..data.len()).min_by_key(|&i| key(&data[i])).unwrap_or(0);
+    let max_idx = (0..data.len()).max_by_key(|&i| key(&data[i])).unwrap_or(0);
+    let budget = max_points.saturating_sub(keep_dates.len() + 3).max(1);
+    let step = data.len().div_ceil(budget);
+    let last = data.len() - 1;
+
+    data.into_iter()
+        .enumerate()
+        .filter(|(i, point)| {
+            i % step == 0
+                || *i == last
+                || *i == min_idx
+                || *i == max_idx
+                || keep_dates.contains(point.date.as_str())
+        })
+        .map(|(_, point)| point)
+        .collect()
+}
+
+/// Keep the `max_markers` largest trades of `markers`, in date order.
+fn thin_markers(mut markers: Vec<ActivityMarker>, max_markers: usize) -> Vec<ActivityMarker> {
+    if markers.len() <= max_markers {
+        return markers;
+    }
+    markers.sort_by_key(|m| std::cmp::Reverse(m.total_cents.abs()));
+    markers.truncate(max_markers);
+    markers.sort_by(|a, b| a.date.cmp(&b.date));
+    markers
+}
+
+/// Closing prices of `symbol`, oldest first, or the trade prices when there
+/// is no market data. The flag tells whether trade prices were used.
+fn price_points(conn: &Connection, symbol: &str) -> AppResult<(Vec<PositionChartData>, bool)> {
+    let point = |date, price_cents| PositionChartData {
+        date,
+        price_cents,
+        value_cents: None,
+    };
+    let mut prices = market_data::get_prices_for_symbol(conn, symbol)?;
+    if prices.is_empty() {
+        let trades = trading::get_all_trade_prices(conn, symbol).unwrap_or_default();
+        return Ok((trades.into_iter().map(|(d, p)| point(d, p)).collect(), true));
+    }
+    prices.reverse();
+    let data = prices
+        .into_iter()
+        .map(|p| point(p.date, p.close_price_cents))
+        .collect();
+    Ok((data, false))
+}
+
+/// Buy and sell markers of the activities `in_range`.
+fn activity_markers(
+    activities: Vec<TradingActivity>,
+    in_range: impl Fn(&str) -> bool,
+) -> Vec<ActivityMarker> {
+    activities
+        .into_iter()
+        .filter(|a| {
+            matches!(
+                a.activity_type,
+                TradingActivityType::Buy | TradingActivityType::Sell
+            ) && in_range(&a.date)
+        })
+        .filter_map(|a| {
+            let qty = a.quantity?;
+            let price = a.unit_price_cents?;
+            Some(ActivityMarker {
+                date: a.date,
+                activity_type: a.activity_type.as_str().to_string(),
+                quantity: qty,
+                price_cents: price,
+                total_cents: (qty * price as f64).round() as i64,
+            })
+        })
+        .collect()
+}
+
+/// Set the value held on each date of `data` from the cost basis `steps`.
+fn fill_values(data: &mut [PositionChartData], steps: &[CostBasisStep]) {
+    let dates: Vec<String> = data.iter().map(|d| d.date.clone()).collect();
+    for (point, step) in data
+        .iter_mut()
+        .zip(positions::steps_on_dates(steps, &dates))
+    {
+        let quantity = step.map_or(0.0, |s| s.quantity);
+        point.value_cents = Some((quantity * point.price_cents as f64).round() as i64);
+    }
+}
+
+/// The average and total cost on each date of `data`.
+fn cost_basis_points(data: &[PositionChartData], steps: &[CostBasisStep]) -> Vec<CostBasisPoint> {
+    let dates: Vec<String> = data.iter().map(|d| d.date.clone()).collect();
+    positions::steps_on_dates(steps, &dates)
+        .into_iter()
+        .zip(dates)
+        .map(|(step, date)| {
+            let avg_cost_cents = step.and_then(CostBasisStep::average_cost_cents);
+            CostBasisPoint {
+                date,
+                avg_cost_cents,
+                cost_cents: step
+                    .filter(|_| avg_cost_cents.is_some())
+                    .map(|s| s.cost_cents),
+            }
+        })
+        .collect()
+}
+
+pub async fn position_chart_data(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(params): Query<PositionChartParams>,
+) -> AppResult<Json<PositionChartResponse>> {
+    let request = params.validate()?;
+    let conn = state.db.get()?;
+    let (data, is_approximated) = price_points(&conn, &symbol)?;
+
+    // Quick ranges end at the latest price, so a symbol whose prices stop
+    // early still shows its last month
+    let from = match request.range.and_then(|r| r.months()) {
+        Some(months) => data
+            .last()
+            .and_then(|p| NaiveDate::parse_from_str(&p.date, "%Y-%m-%d").ok())
+            .and_then(|latest| latest.checked_sub_months(chrono::Months::new(months))),
+        None => request.from_date,
+    }
+    .map(|d| d.format("%Y-%m-%d").to_string());
+    let to = request.to_date.map(|d| d.format("%Y-%m-%d").to_string());
+    let in_range = |date: &str| {
+        from.as_deref().is_none_or(|from| date >= from) && to.as_deref().is_none_or(|to| date <= to)
+    };
+
+    let mut data: Vec<PositionChartData> = data.into_iter().filter(|p| in_range(&p.date)).collect();
+    let all_activities = trading::get_activities_for_symbol(&conn, &symbol)?;
+    let steps = positions::cost_basis_steps(&all_activities);
+    if request.series == ChartSeries::Value {
+        fill_values(&mut data, &steps);
+    }
+
+    let activities = thin_markers(
+        activity_markers(all_activities, in_range),
+        MAX_CHART_MARKERS,
+    );
+    let marker_dates: Vec<&str> = activities.iter().map(|a| a.date.as_str()).collect();
+    let data = decimate_chart_points(
+        data,
+        MAX_CHART_POINTS,
+        |p| p.value_cents.unwrap_or(p.price_cents),
+        &marker_dates,
+    );
+
+    let applied = AppliedChartRange {
+        range: request.range.map(|r| r.as_str()),
+        from_date: from.or_else(|| data.first().map(|p| p.date.clone())),
+        to_date: to.or_else(|| data.last().map(|p| p.date.clone())),
+    };
+    Ok(Json(PositionChartResponse {
+        symbol,
+        series: request.series.as_str(),
+        range: applied,
+        cost_basis: cost_basis_points(&data, &steps),
+        break_even_cents: steps.last().and_then(CostBasisStep::break_even_cents),
+        data,
+        activities,
+        is_approximated,
+    }))
+}
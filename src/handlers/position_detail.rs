@@ -0,0 +1,332 @@
+//! Detail page of one position: its latest activities, returns, fees and
+//! the form for recording a dividend reinvestment.
+
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::Html;
+use chrono::NaiveDate;
+
+use crate::date_utils;
+use crate::db::queries::{market_data, positions as position_queries, trading};
+use crate::error::{AppResult, RenderHtml};
+use crate::handlers::position_chart::ChartRange;
+use crate::handlers::trading_positions::{activity_to_cash_flow, enrich_position};
+use crate::models::trading::{PositionWithMarketData, TradingActivity, TradingActivityType};
+use crate::models::{MarketData, Settings};
+use crate::services::positions;
+use crate::services::xirr::{calculate_xirr, CashFlow};
+use crate::state::{AppState, JsManifest, PageBase};
+
+/// Symbol metadata for display
+#[derive(Debug, Clone, Default)]
+pub struct SymbolInfo {
+    pub short_name: Option<String>,
+    pub long_name: Option<String>,
+    pub exchange: Option<String>,
+    pub quote_type: Option<String>,
+}
+
+impl SymbolInfo {
+    pub fn display_name(&self) -> Option<&String> {
+        self.long_name.as_ref().or(self.short_name.as_ref())
+    }
+
+    pub fn display_name_str(&self) -> &str {
+        self.display_name().map(|s| s.as_str()).unwrap_or("")
+    }
+}
+
+#[derive(Template)]
+#[template(path = "pages/position_detail.html")]
+pub struct PositionDetailTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub symbol: String,
+    pub symbol_info: SymbolInfo,
+    pub position: Option<PositionWithMarketData>,
+    pub activities: Vec<TradingActivity>,
+    pub total_activity_count: usize,
+    pub xirr: Option<f64>,
+    pub xirr_formatted: Option<String>,
+    pub latest_price: Option<MarketData>,
+    pub total_fees_cents: i64,
+    pub total_fees_formatted: String,
+    pub total_taxes_cents: i64,
+    pub total_taxes_formatted: String,
+    pub total_dividends_cents: i64,
+    pub total_dividends_formatted: String,
+    pub realized_gain_loss_cents: i64,
+    pub realized_gain_loss_formatted: String,
+    pub realized_gain_loss_color: &'static str,
+    /// Price at which selling everything recovers cost, fees and taxes net of dividends.
+    pub break_even_formatted: Option<String>,
+    /// Currency and account of the latest activity, used by the DRIP form.
+    pub currency: String,
+    pub drip_account_id: Option<i64>,
+    pub chart_ranges: &'static [ChartRange],
+}
+
+pub async fn detail(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    // Get cached symbol metadata from DB
+    let symbol_info = match market_data::get_symbol_metadata(&conn, &symbol) {
+        Ok(Some(meta)) => SymbolInfo {
+            short_name: meta.short_name,
+            long_name: meta.long_name,
+            exchange: meta.exchange,
+            quote_type: meta.quote_type,
+        },
+        _ => SymbolInfo::default(),
+    };
+
+    // Get all positions and find the one for this symbol
+    let all_positions = position_queries::get_positions(&conn, settings.allow_short_positions)?;
+    let position_opt = all_positions.into_iter().find(|p| p.symbol == symbol);
+
+    // Enrich with market data if position exists (same logic as positions list)
+    let position = position_opt.map(|pos| enrich_position(&conn, pos, &settings));
+
+    // Get activities for this symbol
+    let all_activities = trading::get_activities_for_symbol(&conn, &symbol)?;
+    let total_activity_count = all_activities.len();
+
+    // Get latest price
+    let latest_price = market_data::get_latest_price(&conn, &symbol)?;
+
+    // Calculate XIRR
+    let xirr = calculate_position_xirr(
+        &all_activities,
+        &position,
+        &latest_price,
+        settings.xirr_transfers_at_cost(),
+        date_utils::today_in(&settings),
+    );
+    let xirr_formatted = xirr.map(|x| settings.format_percent_val(x * 100.0));
+
+    // Calculate total fees, taxes, dividends, and realized gain/loss
+    let (total_fees_cents, total_taxes_cents, total_dividends_cents, realized_gain_loss_cents) =
+        calculate_position_totals(&all_activities);
+    let break_even_cents = positions::break_even_price_cents(&all_activities);
+
+    // Keep only last 10 activities for display (most recent, since sorted by date ASC)
+    let activities: Vec<TradingActivity> = all_activities
+        .into_iter()
+        .rev()
+        .take(10)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+
+    let currency = position
+        .as_ref()
+        .map(|p| p.position.currency.clone())
+        .unwrap_or_else(|| settings.currency.clone());
+
+    let total_fees_formatted =
+        settings.format_money_neutral_with_currency(&total_fees_cents, &currency);
+    let total_taxes_formatted =
+        settings.format_money_neutral_with_currency(&total_taxes_cents, &currency);
+    let total_dividends_formatted =
+        settings.format_money_neutral_with_currency(&total_dividends_cents, &currency);
+    let realized_gain_loss_formatted =
+        settings.format_money_plain_with_currency(&realized_gain_loss_cents, &currency);
+    let break_even_formatted = break_even_cents
+        .map(|cents| settings.format_money_neutral_with_currency(&cents, &currency));
+
+    let realized_gain_loss_color = if realized_gain_loss_cents > 0 {
+        "text-green-600 dark:text-green-400"
+    } else if realized_gain_loss_cents < 0 {
+        "text-red-600 dark:text-red-400"
+    } else {
+        "text-neutral-600 dark:text-neutral-400"
+    };
+
+    let display_name = symbol_info
+        .display_name()
+        .map(|n| format!("{} ({})", n, symbol))
+        .unwrap_or_else(|| symbol.clone());
+
+    let template = PositionDetailTemplate {
+        title: display_name,
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        symbol: symbol.clone(),
+        symbol_info,
+        position,
+        drip_account_id: activities.last().and_then(|a| a.account_id),
+        chart_ranges: ChartRange::all(),
+        activities,
+        total_activity_count,
+        xirr,
+        xirr_formatted,
+        latest_price,
+        total_fees_cents,
+        total_fees_formatted,
+        total_taxes_cents,
+        total_taxes_formatted,
+        total_dividends_cents,
+        total_dividends_formatted,
+        realized_gain_loss_cents,
+        realized_gain_loss_formatted,
+        realized_gain_loss_color,
+        break_even_formatted,
+        currency,
+    };
+
+    template.render_html()
+}
+
+/// Calculate XIRR for a single position based on its activities and current market value.
+fn calculate_position_xirr(
+    activities: &[TradingActivity],
+    position: &Option<PositionWithMarketData>,
+    latest_price: &Option<MarketData>,
+    transfers_at_cost: bool,
+    today: NaiveDate,
+) -> Option<f64> {
+    let mut cash_flows: Vec<CashFlow> = activities
+        .iter()
+        .filter_map(|a| activity_to_cash_flow(a, transfers_at_cost))
+        .collect();
+
+    if let (Some(pos), Some(price_data)) = (position, latest_price) {
+        if let Some(current_value) = pos.current_value_cents {
+            let date = NaiveDate::parse_from_str(&price_data.date, "%Y-%m-%d").unwrap_or(today);
+            cash_flows.push(CashFlow {
+                date,
+                amount: current_value as f64 / 100.0,
+            });
+        }
+    }
+
+    calculate_xirr(&cash_flows)
+}
+
+/// Calculate total fees, taxes, dividends, and realized gain/loss for a position
+/// Returns (total_fees_cents, total_taxes_cents, total_dividends_cents, realized_gain_loss_cents)
+/// Note: Dividends are included in realized_gain_loss_cents
+fn calculate_position_totals(activities: &[TradingActivity]) -> (i64, i64, i64, i64) {
+    let mut total_fees_cents: i64 = 0;
+    let mut total_taxes_cents: i64 = 0;
+    let mut total_dividends_cents: i64 = 0;
+    let mut realized_gain_loss_cents: i64 = 0;
+
+    // Track running position for average cost calculation
+    let mut running_quantity: f64 = 0.0;
+    let mut running_cost_cents: i64 = 0;
+
+    for activity in activities {
+        // Fee activity type stores fee amount in unit_price_cents
+        if activity.activity_type == TradingActivityType::Fee {
+            if let Some(price) = activity.unit_price_cents {
+                total_fees_cents += price;
+            }
+        }
+
+        // Sum taxes (Tax activity type stores total tax amount in unit_price_cents)
+        if activity.activity_type == TradingActivityType::Tax {
+            if let Some(price) = activity.unit_price_cents {
+                total_taxes_cents += price;
+            }
+        }
+
+        // Sum dividends and include in realized gain/loss
+        // Dividend activity type stores total dividend amount in unit_price_cents
+        if activity.activity_type == TradingActivityType::Dividend {
+            if let Some(price) = activity.unit_price_cents {
+                total_dividends_cents += price;
+                realized_gain_loss_cents += price;
+            }
+        }
+
+        // Calculate realized gain/loss from sell activities
+        match activity.activity_type {
+            TradingActivityType::Buy
+            | TradingActivityType::TransferIn
+            | TradingActivityType::AddHolding => {
+                let qty = activity.quantity.unwrap_or(0.0);
+                let price = activity.unit_price_cents.unwrap_or(0);
+                let cost = (qty * price as f64).round() as i64;
+                running_quantity += qty;
+                running_cost_cents += cost;
+            }
+            TradingActivityType::Sell => {
+                let qty = activity.quantity.unwrap_or(0.0);
+                let sell_price = activity.unit_price_cents.unwrap_or(0);
+                let sell_value = (qty * sell_price as f64).round() as i64;
+
+                // Calculate cost basis using average cost
+                if running_quantity > 0.0 {
+                    let avg_cost = running_cost_cents as f64 / running_quantity;
+                    let cost_basis = (qty * avg_cost).round() as i64;
+
+                    // Realized gain/loss = sell value - cost basis
+                    realized_gain_loss_cents += sell_value - cost_basis;
+
+                    // Update running position
+                    running_quantity -= qty;
+                    running_cost_cents -= cost_basis;
+
+                    if running_quantity < 0.0 {
+                        running_quantity = 0.0;
+                    }
+                    if running_cost_cents < 0 {
+                        running_cost_cents = 0;
+                    }
+                }
+            }
+            TradingActivityType::TransferOut | TradingActivityType::RemoveHolding
+                if running_quantity > 0.0 =>
+            {
+                // Shares leave at cost basis, so nothing is realized
+                let qty = activity.quantity.unwrap_or(0.0).min(running_quantity);
+                let avg_cost = running_cost_cents as f64 / running_quantity;
+                running_cost_cents -= (qty * avg_cost).round() as i64;
+                running_quantity -= qty;
+            }
+            TradingActivityType::Split => {
+                // Split adjusts quantity but not cost
+                if let Some(ratio) = activity.quantity {
+                    if ratio > 0.0 {
+                        running_quantity *= ratio;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Subtract fees and taxes from realized gain/loss
+    let net_realized_gain_loss_cents =
+        realized_gain_loss_cents - total_fees_cents - total_taxes_cents;
+
+    (
+        total_fees_cents,
+        total_taxes_cents,
+        total_dividends_cents,
+        net_realized_gain_loss_cents,
+    )
+}
@@ -0,0 +1,234 @@
+//! CSV exports of the open and closed positions, and the full history of
+//! one symbol as CSV or JSON.
+
+use std::collections::HashMap;
+
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use rusqlite::Connection;
+use serde::Deserialize;
+
+use crate::date_utils::{self, DateFilterable};
+use crate::db::queries::{market_data, positions};
+use crate::error::{AppError, AppResult};
+use crate::handlers::trading_positions::{
+    closed_positions_in, enrich_position, sort_closed_positions, sort_positions,
+    ClosedPositionFilterParams, ClosedPositionSortColumn, PositionFilterParams, PositionSortColumn,
+};
+use crate::models::trading::{ClosedPosition, PositionWithMarketData};
+use crate::services::analytics::{format_cents, optional_cents};
+use crate::services::position_history;
+use crate::sort_utils::{Sortable, TableSort};
+use crate::state::AppState;
+
+const OPEN_POSITION_HEADERS: [&str; 12] = [
+    "symbol",
+    "name",
+    "quantity",
+    "average_cost",
+    "total_cost",
+    "price",
+    "price_date",
+    "current_value",
+    "gain_loss",
+    "gain_loss_percent",
+    "currency",
+    "price_approximated",
+];
+
+const CLOSED_POSITION_HEADERS: [&str; 10] = [
+    "symbol",
+    "name",
+    "total_cost",
+    "total_proceeds",
+    "realized_gain_loss",
+    "fees",
+    "taxes",
+    "currency",
+    "first_activity_date",
+    "last_activity_date",
+];
+
+fn symbol_name(conn: &Connection, symbol: &str) -> String {
+    market_data::get_symbol_metadata(conn, symbol)
+        .ok()
+        .flatten()
+        .and_then(|meta| meta.display_name().cloned())
+        .unwrap_or_default()
+}
+
+fn open_position_record(conn: &Connection, p: &PositionWithMarketData) -> Vec<String> {
+    vec![
+        p.position.symbol.clone(),
+        symbol_name(conn, &p.position.symbol),
+        p.position.quantity.to_string(),
+        optional_cents(p.position.average_cost_cents()),
+        format_cents(p.position.total_cost_cents),
+        optional_cents(p.current_price_cents),
+        p.price_date.clone().unwrap_or_default(),
+        optional_cents(p.current_value_cents),
+        optional_cents(p.gain_loss_cents),
+        p.gain_loss_percent
+            .map(|pct| format!("{:.2}", pct))
+            .unwrap_or_default(),
+        p.position.currency.clone(),
+        p.price_is_approximated.to_string(),
+    ]
+}
+
+fn closed_position_record(conn: &Connection, p: &ClosedPosition) -> Vec<String> {
+    vec![
+        p.symbol.clone(),
+        symbol_name(conn, &p.symbol),
+        format_cents(p.total_cost_cents),
+        format_cents(p.total_proceeds_cents),
+        format_cents(p.realized_gain_loss_cents),
+        format_cents(p.total_fees_cents),
+        format_cents(p.total_taxes_cents),
+        p.currency.clone(),
+        p.first_activity_date.clone(),
+        p.last_activity_date.clone(),
+    ]
+}
+
+pub(crate) fn csv_response(
+    filename: &str,
+    headers: &[&str],
+    records: Vec<Vec<String>>,
+) -> AppResult<impl IntoResponse> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let write_err = |e: csv::Error| AppError::Internal(format!("Failed to write CSV: {}", e));
+    writer.write_record(headers).map_err(write_err)?;
+    for record in records {
+        writer.write_record(&record).map_err(write_err)?;
+    }
+    let body = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("Failed to write CSV: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    ))
+}
+
+pub(crate) fn check_export_format(format: Option<&str>) -> AppResult<()> {
+    match format {
+        None | Some("csv") => Ok(()),
+        Some(other) => Err(AppError::Validation(format!(
+            "Unsupported export format: {}",
+            other
+        ))),
+    }
+}
+
+/// Export open positions as CSV, in the same order as the positions table.
+pub async fn export_positions(
+    State(state): State<AppState>,
+    Query(params): Query<PositionFilterParams>,
+) -> AppResult<impl IntoResponse> {
+    check_export_format(params.format.as_deref())?;
+    let conn = state.db.get()?;
+    let settings = state.load_settings()?;
+    let sort: TableSort<PositionSortColumn> =
+        params.resolve_sort_or(settings.default_sort("positions"));
+
+    let mut positions: Vec<PositionWithMarketData> =
+        positions::get_positions(&conn, settings.allow_short_positions)?
+            .into_iter()
+            .map(|pos| enrich_position(&conn, pos, &settings))
+            .collect();
+    sort_positions(&mut positions, &sort);
+
+    let records = positions
+        .iter()
+        .map(|p| open_position_record(&conn, p))
+        .collect();
+    csv_response("positions.csv", &OPEN_POSITION_HEADERS, records)
+}
+
+/// Export closed positions as CSV, in the same order as the closed positions table.
+pub async fn export_closed_positions(
+    State(state): State<AppState>,
+    Query(params): Query<ClosedPositionFilterParams>,
+) -> AppResult<impl IntoResponse> {
+    check_export_format(params.format.as_deref())?;
+    let conn = state.db.get()?;
+    let settings = state.load_settings()?;
+    let sort: TableSort<ClosedPositionSortColumn> =
+        params.resolve_sort_or(settings.default_sort("closed_positions"));
+    let date_range = params.resolve_date_range(date_utils::today_in(&settings));
+
+    let mut positions = closed_positions_in(&conn, settings.allow_short_positions, &date_range)?;
+    sort_closed_positions(&mut positions, &sort);
+
+    let records = positions
+        .iter()
+        .map(|p| closed_position_record(&conn, p))
+        .collect();
+    csv_response("closed_positions.csv", &CLOSED_POSITION_HEADERS, records)
+}
+
+// Position history export
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryExportParams {
+    /// `csv` (default) or `json`
+    pub format: Option<String>,
+}
+
+/// Export the full history of one symbol: activities with their pre-split
+/// values, realized gains per sale, dividends, fees and taxes, and the
+/// current position.
+pub async fn export_position_history(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(params): Query<HistoryExportParams>,
+) -> AppResult<axum::response::Response> {
+    let format = params.format.as_deref().unwrap_or("csv");
+    if !matches!(format, "csv" | "json") {
+        return Err(AppError::Validation(format!(
+            "Unsupported export format: {}",
+            format
+        )));
+    }
+    let conn = state.db.get()?;
+    let settings = state.load_settings()?;
+    let position = positions::get_positions(&conn, settings.allow_short_positions)?
+        .into_iter()
+        .find(|p| p.symbol == symbol)
+        .map(|pos| enrich_position(&conn, pos, &settings));
+    let account_names: HashMap<i64, String> = state
+        .cached_accounts()?
+        .iter()
+        .map(|a| (a.id, a.name.clone()))
+        .collect();
+    let name = Some(symbol_name(&conn, &symbol)).filter(|n| !n.is_empty());
+    let history = position_history::build(&conn, &symbol, name, position, &account_names)?;
+    let filename = position_history::filename(&symbol, history.name.as_deref(), format);
+
+    if format == "csv" {
+        let records = position_history::csv_records(&history);
+        return Ok(csv_response(&filename, &position_history::HEADERS, records)?.into_response());
+    }
+    let json = serde_json::to_string_pretty(&history)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize: {}", e)))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        json,
+    )
+        .into_response())
+}
@@ -2,9 +2,10 @@ use askama::Template;
 use axum::extract::{Path, Query, State};
 use axum::response::{Html, Redirect};
 use axum::{Form, Json};
-use chrono::Utc;
+use chrono::Datelike;
 use serde::Deserialize;
 
+use crate::date_utils;
 use crate::db::queries::retirement as db;
 use crate::error::{AppError, AppResult, RenderHtml};
 use crate::filters;
@@ -17,12 +18,8 @@ use crate::services::retirement::{
 };
 use crate::state::{AppState, JsManifest, PageBase};
 
-fn today_year() -> i32 {
-    Utc::now()
-        .format("%Y")
-        .to_string()
-        .parse::<i32>()
-        .unwrap_or(2025)
+fn today_year(settings: &Settings) -> i32 {
+    date_utils::today_in(settings).year()
 }
 
 fn success_color_class(p: f64) -> &'static str {
@@ -47,6 +44,7 @@ pub struct RetirementTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub all_scenarios: Vec<Scenario>,
     pub projection: Option<RetirementProjection>,
     pub show_new_form: bool,
@@ -68,15 +66,12 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let all_scenarios = db::list_scenarios(&conn)?;
     let current_net_worth_cents = db::get_current_net_worth_cents(&conn)?;
-    let current_net_worth_formatted = filters::format_money_neutral(
-        current_net_worth_cents,
-        &settings.currency,
-        &settings.locale,
-    );
+    let current_net_worth_formatted = settings.format_money_neutral(&current_net_worth_cents);
 
     let (projection, show_new_form, slider_state_json) = if all_scenarios.is_empty() {
         (None, true, "{}".to_string())
@@ -97,7 +92,7 @@ pub async fn index(
             .deposits_cents
             .unwrap_or_else(|| db::get_total_invested_cents(&conn).unwrap_or(0));
 
-        let year = today_year();
+        let year = today_year(&settings);
         let inputs =
             ProjectionInputs::from_scenario(&scenario, portfolio_cents, cost_basis_cents, year);
 
@@ -165,6 +160,7 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         all_scenarios,
         projection,
         show_new_form,
@@ -185,6 +181,7 @@ pub struct RetirementFormTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub form_scenario: Option<Scenario>,
     pub current_net_worth_cents: i64,
     pub current_net_worth_formatted: String,
@@ -198,13 +195,10 @@ pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>>
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let current_net_worth_cents = db::get_current_net_worth_cents(&conn)?;
-    let current_net_worth_formatted = filters::format_money_neutral(
-        current_net_worth_cents,
-        &settings.currency,
-        &settings.locale,
-    );
+    let current_net_worth_formatted = settings.format_money_neutral(&current_net_worth_cents);
 
     RetirementFormTemplate {
         title: "New Scenario".into(),
@@ -213,6 +207,7 @@ pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>>
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         form_scenario: None,
         current_net_worth_cents,
         current_net_worth_formatted,
@@ -231,15 +226,12 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let scenario = db::get_scenario(&conn, &id)?
         .ok_or_else(|| AppError::NotFound(format!("Scenario {id} not found")))?;
     let current_net_worth_cents = db::get_current_net_worth_cents(&conn)?;
-    let current_net_worth_formatted = filters::format_money_neutral(
-        current_net_worth_cents,
-        &settings.currency,
-        &settings.locale,
-    );
+    let current_net_worth_formatted = settings.format_money_neutral(&current_net_worth_cents);
 
     RetirementFormTemplate {
         title: "Edit Scenario".into(),
@@ -248,6 +240,7 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         form_scenario: Some(scenario),
         current_net_worth_cents,
         current_net_worth_formatted,
@@ -423,7 +416,7 @@ pub async fn chart_data(
         .deposits_cents
         .unwrap_or_else(|| db::get_total_invested_cents(&conn).unwrap_or(0));
 
-    let year = today_year();
+    let year = today_year(&state.load_settings()?);
     let inputs =
         ProjectionInputs::from_scenario(&scenario, portfolio_cents, cost_basis_cents, year)
             .ok_or_else(|| {
@@ -463,7 +456,7 @@ pub async fn simulate(
     Json(req): Json<SimulateRequest>,
 ) -> AppResult<Json<SimulateResponse>> {
     let conn = state.db.get()?;
-    let year = today_year();
+    let year = today_year(&state.load_settings()?);
 
     let current_net_worth_cents = db::get_current_net_worth_cents(&conn)?;
     let portfolio_cents = req
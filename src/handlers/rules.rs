@@ -8,9 +8,12 @@ use serde::Deserialize;
 use crate::db::queries::transactions::TransactionFilter;
 use crate::db::queries::{rules, transactions};
 use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash;
 use crate::models::{
-    CategoryWithPath, NewRule, Rule, RuleActionType, Settings, Tag, TransactionWithRelations,
+    CategoryWithPath, NewRule, Rule, RuleActionType, RuleMatchField, Settings, Tag,
+    TransactionWithRelations,
 };
+use crate::services::rule_suggestion::{self, RuleSuggestion};
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Template)]
@@ -22,6 +25,7 @@ pub struct RuleFormTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub categories: Vec<CategoryWithPath>,
     pub tags: Vec<Tag>,
 }
@@ -35,6 +39,7 @@ pub struct RuleDetailTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub rule: Rule,
     pub categories: Vec<CategoryWithPath>,
     pub tags: Vec<Tag>,
@@ -49,6 +54,7 @@ pub struct RuleEditTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub rule: Rule,
     pub categories: Vec<CategoryWithPath>,
     pub tags: Vec<Tag>,
@@ -72,6 +78,7 @@ pub struct RulePreviewTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub rule: Rule,
     pub scope: String,
     pub matched: Vec<TransactionWithRelations>,
@@ -79,12 +86,70 @@ pub struct RulePreviewTemplate {
     pub tags: Vec<Tag>,
 }
 
+#[derive(Template)]
+#[template(path = "pages/rule_matches.html")]
+pub struct RuleMatchesTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub rule: Rule,
+    /// Recently changed transactions with when the rule matched them
+    pub matches: Vec<(TransactionWithRelations, String)>,
+}
+
+/// How many of a rule's most recent matches its matches page lists.
+const RECENT_MATCHES_LIMIT: i64 = 100;
+
+#[derive(Template)]
+#[template(path = "partials/rule_suggestion.html")]
+pub struct RuleSuggestionTemplate {
+    pub transaction_id: i64,
+    pub suggestion: RuleSuggestion,
+    pub category_id: Option<i64>,
+    pub categories: Vec<CategoryWithPath>,
+    pub match_fields: &'static [RuleMatchField],
+    /// Existing transactions the suggested pattern matches
+    pub match_count: usize,
+    /// Of those, the ones without a category
+    pub uncategorized_count: usize,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RuleFormData {
     pub name: String,
     pub pattern: String,
+    /// Empty or omitted matches the description.
+    #[serde(default)]
+    pub match_field: String,
     pub action_type: String,
     pub action_value: String,
+    /// HTML checkbox: "on" to apply a new rule to the uncategorized
+    /// transactions it matches right away.
+    #[serde(default)]
+    pub apply_uncategorized: String,
+}
+
+impl RuleFormData {
+    fn to_new_rule(&self) -> AppResult<NewRule> {
+        let action_type = RuleActionType::parse(&self.action_type)
+            .ok_or_else(|| AppError::Validation("Invalid action type".into()))?;
+        let match_field = match self.match_field.as_str() {
+            "" => RuleMatchField::default(),
+            s => RuleMatchField::parse(s)
+                .ok_or_else(|| AppError::Validation("Invalid match field".into()))?,
+        };
+        Ok(NewRule {
+            name: self.name.clone(),
+            pattern: self.pattern.clone(),
+            match_field,
+            action_type,
+            action_value: self.action_value.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -104,6 +169,7 @@ pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>>
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
     let category_list = state.cached_categories_with_path()?;
     let tag_list = state.cached_tags()?;
@@ -115,6 +181,7 @@ pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>>
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         categories: category_list,
         tags: tag_list,
     };
@@ -130,6 +197,7 @@ pub async fn detail(State(state): State<AppState>, Path(id): Path<i64>) -> AppRe
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let rule =
@@ -144,6 +212,7 @@ pub async fn detail(State(state): State<AppState>, Path(id): Path<i64>) -> AppRe
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         rule,
         categories: category_list,
         tags: tag_list,
@@ -163,6 +232,7 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let rule =
@@ -177,6 +247,7 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         rule,
         categories: category_list,
         tags: tag_list,
@@ -189,19 +260,40 @@ pub async fn create(
     State(state): State<AppState>,
     Form(form): Form<RuleFormData>,
 ) -> AppResult<Redirect> {
-    let conn = state.db.get()?;
+    let new_rule = form.to_new_rule()?;
 
-    let action_type = RuleActionType::parse(&form.action_type)
-        .ok_or_else(|| AppError::Validation("Invalid action type".into()))?;
+    // A rule that fails to apply is not stored either
+    let applied = state.with_tx(|tx| {
+        let matched = if form.apply_uncategorized == "on" {
+            match_transactions(tx, &new_rule.pattern, new_rule.match_field, "uncategorized")?
+        } else {
+            Vec::new()
+        };
 
-    let new_rule = NewRule {
-        name: form.name,
-        pattern: form.pattern,
-        action_type,
-        action_value: form.action_value,
-    };
+        let id = rules::create_rule(tx, &new_rule)?;
 
-    rules::create_rule(&conn, &new_rule)?;
+        if matched.is_empty() {
+            return Ok(0);
+        }
+        let ids: Vec<i64> = matched.iter().map(|t| t.transaction.id).collect();
+        let target_id = new_rule
+            .action_value
+            .parse::<i64>()
+            .map_err(|_| AppError::Validation("Invalid rule value".into()))?;
+        match new_rule.action_type {
+            RuleActionType::AssignCategory => rules::apply_rule_category(tx, id, &ids, target_id)?,
+            RuleActionType::AssignTag => rules::apply_rule_tag(tx, &ids, target_id)?,
+        };
+        rules::record_matches(tx, id, &ids)?;
+        Ok(ids.len())
+    })?;
+
+    if applied > 0 {
+        flash::flash_success(format!(
+            "Rule \"{}\" created and applied to {} transactions",
+            new_rule.name, applied
+        ));
+    }
 
     Ok(Redirect::to("/manage?tab=rules"))
 }
@@ -213,15 +305,7 @@ pub async fn update(
 ) -> AppResult<Redirect> {
     let conn = state.db.get()?;
 
-    let action_type = RuleActionType::parse(&form.action_type)
-        .ok_or_else(|| AppError::Validation("Invalid action type".into()))?;
-
-    let updated_rule = NewRule {
-        name: form.name,
-        pattern: form.pattern,
-        action_type,
-        action_value: form.action_value,
-    };
+    let updated_rule = form.to_new_rule()?;
 
     rules::update_rule(&conn, id, &updated_rule)?;
 
@@ -244,13 +328,15 @@ pub async fn delete_all(State(state): State<AppState>) -> AppResult<Html<String>
     Ok(Html(String::new()))
 }
 
-/// Fetch transactions matching a rule's regex pattern, filtered by scope.
+/// Fetch transactions whose `match_field` matches the regex `pattern`,
+/// filtered by scope.
 fn match_transactions(
     conn: &rusqlite::Connection,
-    rule: &Rule,
+    pattern: &str,
+    match_field: RuleMatchField,
     scope: &str,
 ) -> AppResult<Vec<TransactionWithRelations>> {
-    let re = RegexBuilder::new(&rule.pattern)
+    let re = RegexBuilder::new(pattern)
         .case_insensitive(true)
         .build()
         .map_err(|e| AppError::Validation(format!("Invalid regex pattern: {e}")))?;
@@ -263,7 +349,16 @@ fn match_transactions(
 
     let matched: Vec<TransactionWithRelations> = all
         .into_iter()
-        .filter(|t| re.is_match(&t.transaction.description))
+        .filter(|t| {
+            let t = &t.transaction;
+            match_field.is_match(
+                &re,
+                &t.description,
+                t.payer.as_deref(),
+                t.payee.as_deref(),
+                t.counterparty_iban.as_deref(),
+            )
+        })
         .collect();
 
     Ok(matched)
@@ -281,13 +376,14 @@ pub async fn preview(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let rule =
         rules::get_rule(&conn, id)?.ok_or_else(|| AppError::NotFound("Rule not found".into()))?;
 
     let scope = params.scope.unwrap_or_else(|| "all".into());
-    let matched = match_transactions(&conn, &rule, &scope)?;
+    let matched = match_transactions(&conn, &rule.pattern, rule.match_field, &scope)?;
     let category_list = state.cached_categories_with_path()?;
     let tag_list = state.cached_tags()?;
 
@@ -298,6 +394,7 @@ pub async fn preview(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         rule,
         scope,
         matched,
@@ -318,25 +415,132 @@ pub async fn apply(
     let rule =
         rules::get_rule(&conn, id)?.ok_or_else(|| AppError::NotFound("Rule not found".into()))?;
 
-    let matched = match_transactions(&conn, &rule, &form.scope)?;
+    let matched = match_transactions(&conn, &rule.pattern, rule.match_field, &form.scope)?;
     let ids: Vec<i64> = matched.iter().map(|t| t.transaction.id).collect();
 
-    match rule.action_type {
-        RuleActionType::AssignCategory => {
-            let category_id: i64 = rule
-                .action_value
-                .parse()
-                .map_err(|_| AppError::Validation("Invalid category ID".into()))?;
-            rules::apply_rule_category(&conn, &ids, category_id)?;
-        }
-        RuleActionType::AssignTag => {
-            let tag_id: i64 = rule
-                .action_value
-                .parse()
-                .map_err(|_| AppError::Validation("Invalid tag ID".into()))?;
-            rules::apply_rule_tag(&conn, &ids, tag_id)?;
+    let redirect = Redirect::to(&format!("/rules/{id}"));
+    let Ok(target_id) = rule.action_value.parse::<i64>() else {
+        flash::flash_error(format!(
+            "Rule \"{}\" has an invalid {}, nothing was changed",
+            rule.name,
+            match rule.action_type {
+                RuleActionType::AssignCategory => "category",
+                RuleActionType::AssignTag => "tag",
+            }
+        ));
+        return Ok(redirect);
+    };
+    drop(conn);
+    state.with_tx(|tx| {
+        match rule.action_type {
+            RuleActionType::AssignCategory => {
+                rules::apply_rule_category(tx, rule.id, &ids, target_id)?
+            }
+            RuleActionType::AssignTag => rules::apply_rule_tag(tx, &ids, target_id)?,
+        };
+        rules::record_matches(tx, rule.id, &ids)?;
+        Ok(())
+    })?;
+
+    flash::flash_success(format!(
+        "Rule \"{}\" applied to {} transactions",
+        rule.name,
+        ids.len()
+    ));
+    Ok(redirect)
+}
+
+pub async fn matches(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    let rule =
+        rules::get_rule(&conn, id)?.ok_or_else(|| AppError::NotFound("Rule not found".into()))?;
+    let mut matches = Vec::new();
+    for (transaction_id, matched_at) in rules::list_recent_matches(&conn, id, RECENT_MATCHES_LIMIT)?
+    {
+        if let Some(transaction) = transactions::get_transaction(&conn, transaction_id)? {
+            matches.push((transaction, matched_at));
         }
     }
 
+    let template = RuleMatchesTemplate {
+        title: format!("Matches: {}", rule.name),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        rule,
+        matches,
+    };
+
+    template.render_html()
+}
+
+pub async fn reset_statistics(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Redirect> {
+    let conn = state.db.get()?;
+
+    if !rules::reset_statistics(&conn, id)? {
+        return Err(AppError::NotFound("Rule not found".into()));
+    }
+
+    flash::flash_success("Rule statistics reset");
     Ok(Redirect::to(&format!("/rules/{id}")))
 }
+
+/// Prefilled form for a rule that categorizes transactions like this one.
+pub async fn suggestion(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let transaction = transactions::get_transaction(&conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", id)))?
+        .transaction;
+
+    let filter = TransactionFilter {
+        with_tags: false,
+        ..Default::default()
+    };
+    let all = transactions::list_transactions(&conn, &filter)?;
+    let descriptions: Vec<String> = all
+        .iter()
+        .map(|t| t.transaction.description.clone())
+        .collect();
+    let suggestion = rule_suggestion::suggest(&transaction, &descriptions).ok_or_else(|| {
+        AppError::Validation("No pattern could be derived from this transaction".into())
+    })?;
+
+    let matched = match_transactions(&conn, &suggestion.pattern, suggestion.match_field, "all")?;
+    let uncategorized_count = matched
+        .iter()
+        .filter(|t| t.transaction.category_id.is_none())
+        .count();
+
+    let template = RuleSuggestionTemplate {
+        transaction_id: id,
+        category_id: transaction.category_id,
+        categories: state.cached_categories_with_path()?,
+        match_fields: RuleMatchField::all(),
+        match_count: matched.len(),
+        uncategorized_count,
+        suggestion,
+    };
+
+    template.render_html()
+}
@@ -0,0 +1,135 @@
+//! The accounts layer of the flow Sankey: the accounts income was received
+//! into and expenses were paid from, between the categories and Budget.
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::Connection;
+
+use crate::db::queries::transaction_sums;
+use crate::handlers::api::FlowDirection;
+use crate::models::category::Category;
+
+/// Color of the account nodes.
+pub(crate) const ACCOUNT_COLOR: &str = "#64748b";
+
+/// Amounts of one side of the diagram by top-level category (`None`:
+/// uncategorized) and account (`None`: no account), positive in the side's
+/// direction.
+#[derive(Debug, Default)]
+pub(crate) struct AccountSplit(HashMap<Option<i64>, HashMap<Option<i64>, i64>>);
+
+impl AccountSplit {
+    /// Accounts a top-level category's money flowed through, largest first.
+    /// Accounts whose net flow runs against the category's direction, like a
+    /// refund to another card than the one paid with, are netted against the
+    /// others in proportion to their amounts, so that the flows add up to the
+    /// category's total.
+    pub(crate) fn flows(&self, root: Option<i64>) -> Vec<(Option<i64>, i64)> {
+        let by_account = self.0.get(&root);
+        let mut flows: Vec<(Option<i64>, i64)> = by_account
+            .into_iter()
+            .flatten()
+            .filter(|(_, &cents)| cents > 0)
+            .map(|(&account_id, &cents)| (account_id, cents))
+            .collect();
+        flows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let gross: i64 = flows.iter().map(|(_, cents)| cents).sum();
+        let net: i64 = by_account
+            .into_iter()
+            .flatten()
+            .map(|(_, cents)| cents)
+            .sum();
+        if net < gross {
+            for flow in &mut flows {
+                flow.1 = (i128::from(flow.1) * i128::from(net) / i128::from(gross)) as i64;
+            }
+            // The largest flow takes the rounding remainder
+            let remainder = net - flows.iter().map(|(_, cents)| cents).sum::<i64>();
+            if let Some(largest) = flows.first_mut() {
+                largest.1 += remainder;
+            }
+            flows.retain(|(_, cents)| *cents > 0);
+        }
+        flows
+    }
+
+    fn accounts(&self) -> impl Iterator<Item = &Option<i64>> {
+        self.0.values().flat_map(|by_account| by_account.keys())
+    }
+}
+
+/// Both sides of the accounts layer and the names of their account nodes.
+#[derive(Debug, Default)]
+pub(crate) struct AccountLayer {
+    pub income: AccountSplit,
+    pub expense: AccountSplit,
+    /// Node names of each account on the income and on the expense side.
+    pub labels: HashMap<Option<i64>, (String, String)>,
+}
+
+impl AccountLayer {
+    /// Split the totals of the period by account. `direction` gives the side
+    /// of a category (`None`: uncategorized), or `None` for categories that
+    /// are not in the diagram.
+    pub(crate) fn load(
+        conn: &Connection,
+        from_date: Option<&str>,
+        to_date: Option<&str>,
+        cat_map: &HashMap<i64, &Category>,
+        direction: impl Fn(Option<i64>) -> Option<FlowDirection>,
+    ) -> rusqlite::Result<Self> {
+        let mut layer = Self::default();
+        for (cat_id, account_id, total) in
+            transaction_sums::sum_by_category_and_account(conn, from_date, to_date)?
+        {
+            let Some(direction) = direction(cat_id) else {
+                continue;
+            };
+            let split = match direction {
+                FlowDirection::Income => &mut layer.income,
+                _ => &mut layer.expense,
+            };
+            let root = cat_id.map(|id| root_category_id(id, cat_map));
+            *split
+                .0
+                .entry(root)
+                .or_default()
+                .entry(account_id)
+                .or_default() += direction.signed(total);
+        }
+        Ok(layer)
+    }
+
+    /// Give each account a node per side, named apart from each other and
+    /// from the `taken` category node names.
+    pub(crate) fn label_accounts(&mut self, names: &HashMap<i64, String>, taken: &HashSet<String>) {
+        let label = |name: &str, side: &str| {
+            let label = format!("{} ({})", name, side);
+            if taken.contains(&label) {
+                format!("{} (Account {})", name, side)
+            } else {
+                label
+            }
+        };
+        for account_id in self.income.accounts().chain(self.expense.accounts()) {
+            let name = account_id
+                .and_then(|id| names.get(&id))
+                .map_or("No Account", String::as_str);
+            self.labels
+                .entry(*account_id)
+                .or_insert_with(|| (label(name, "In"), label(name, "Out")));
+        }
+    }
+}
+
+/// The top-level ancestor of category `id`.
+fn root_category_id(mut id: i64, cat_map: &HashMap<i64, &Category>) -> i64 {
+    for _ in 0..cat_map.len() {
+        match cat_map.get(&id).and_then(|c| c.parent_id) {
+            Some(parent_id) => id = parent_id,
+            None => break,
+        }
+    }
+    id
+}
@@ -0,0 +1,134 @@
+//! Spending charts saved with a name, a chart type and their settings.
+//!
+//! The spending page draws every saved chart from its API endpoint for the
+//! selected date range. Categories deleted after a chart was saved are left
+//! out of its request, so the chart loses that series instead of failing.
+//! Once all of them are gone, the chart shows an empty state.
+
+use axum::extract::{Path, State};
+use axum::response::{Html, IntoResponse, Redirect};
+use axum::{Form, Json};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::db::queries::saved_charts;
+use crate::error::{AppError, AppResult};
+use crate::flash::{self, FlashLevel};
+use crate::form_utils::{collect_ids, SubmittedForm};
+use crate::models::{NewSavedChart, SavedChart, SavedChartParams, SavedChartType};
+use crate::state::AppState;
+
+const MAX_NAME_LEN: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct SavedChartFormData {
+    name: String,
+    chart_type: String,
+    #[serde(default)]
+    mode: String,
+}
+
+/// Read a saved chart from the create or update form. Categories come as
+/// repeated `category_ids` fields or a comma-separated list.
+fn parse_form(pairs: Vec<(String, String)>) -> AppResult<NewSavedChart> {
+    let category_ids = collect_ids(&pairs, "category_ids");
+    let form: SavedChartFormData = SubmittedForm::new(pairs)
+        .parse(&["category_ids"])
+        .map_err(AppError::Validation)?;
+
+    let name = form.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::Validation("Name is required".into()));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(AppError::Validation(format!(
+            "Name must be at most {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    let chart_type = SavedChartType::parse(&form.chart_type)
+        .ok_or_else(|| AppError::Validation(format!("Unknown chart type: {}", form.chart_type)))?;
+    let mode = match form.mode.as_str() {
+        "" | "expenses" => None,
+        "income" => Some("income".to_string()),
+        other => return Err(AppError::Validation(format!("Unknown mode: {}", other))),
+    };
+    if mode.is_some() && !chart_type.supports_income() {
+        return Err(AppError::Validation(format!(
+            "{} charts show expenses only",
+            chart_type.label()
+        )));
+    }
+
+    if chart_type == SavedChartType::MonthlyByCategory && category_ids.is_empty() {
+        return Err(AppError::Validation(
+            "Select at least one category for a monthly chart by category".into(),
+        ));
+    }
+
+    Ok(NewSavedChart {
+        name,
+        chart_type,
+        params: SavedChartParams { category_ids, mode },
+    })
+}
+
+pub async fn list(State(state): State<AppState>) -> AppResult<Json<Vec<SavedChart>>> {
+    let conn = state.db.get()?;
+    Ok(Json(saved_charts::list_saved_charts(&conn)?))
+}
+
+pub async fn detail(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Json<SavedChart>> {
+    let conn = state.db.get()?;
+    saved_charts::get_saved_chart(&conn, id)?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("Saved chart {} not found", id)))
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<Redirect> {
+    let chart = parse_form(pairs)?;
+    let conn = state.db.get()?;
+    let id = saved_charts::create_saved_chart(&conn, &chart)?;
+    info!(chart_id = id, chart_type = %chart.chart_type, "Saved chart created");
+
+    flash::flash_success(format!("Chart \"{}\" saved", chart.name));
+    Ok(Redirect::to("/spending"))
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<Redirect> {
+    let chart = parse_form(pairs)?;
+    let conn = state.db.get()?;
+    if !saved_charts::update_saved_chart(&conn, id, &chart)? {
+        return Err(AppError::NotFound(format!("Saved chart {} not found", id)));
+    }
+    info!(chart_id = id, "Saved chart updated");
+
+    flash::flash_success(format!("Chart \"{}\" updated", chart.name));
+    Ok(Redirect::to("/spending"))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let conn = state.db.get()?;
+    if !saved_charts::delete_saved_chart(&conn, id)? {
+        return Err(AppError::NotFound(format!("Saved chart {} not found", id)));
+    }
+    info!(chart_id = id, "Saved chart deleted");
+
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, "Chart deleted"),
+        Html(String::new()),
+    ))
+}
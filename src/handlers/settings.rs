@@ -1,17 +1,28 @@
 use askama::Template;
-use axum::extract::{Multipart, State};
-use axum::http::header;
-use axum::response::{Html, IntoResponse};
+use axum::extract::State;
+use axum::response::{Html, IntoResponse, Redirect};
 use axum::Form;
+use rusqlite::Connection;
 use serde::Deserialize;
 use std::fs;
-use std::path::Path;
 
-use tracing::{info, warn};
+use tracing::info;
 
-use crate::db::queries::settings;
+use crate::date_utils;
+use crate::db::queries::{accounts, categories, settings};
 use crate::error::{AppError, AppResult, RenderHtml};
-use crate::models::Settings;
+use crate::flash::{self, FlashLevel};
+use crate::handlers::market_data::MarketDataSortColumn;
+use crate::handlers::trading_positions::{ClosedPositionSortColumn, PositionSortColumn};
+use crate::models::settings::{
+    MAX_PERCENT_DECIMALS, MAX_WASH_SALE_WINDOW_DAYS, TRANSACTION_COLUMNS,
+};
+use crate::models::{Account, AccountType, CategoryWithPath, Settings};
+use crate::nav::{self, NavItem, NAV_ITEMS};
+use crate::services::backup::{self, BackupStatus};
+use crate::services::money;
+use crate::services::notify;
+use crate::sort_utils::{SortDirection, SortableColumn, TableSort};
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Template)]
@@ -23,7 +34,20 @@ pub struct SettingsTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub database_size: String,
+    pub backup_status: BackupStatus,
+    /// Directory used when the backup directory setting is empty.
+    pub default_backup_dir: String,
+    /// Known IANA timezone names for the timezone field.
+    pub timezones: Vec<&'static str>,
+    /// Choices for the default account and category of new transactions.
+    pub cash_accounts: Vec<Account>,
+    pub categories: Vec<CategoryWithPath>,
+    /// Choices for the default account of new trading activities.
+    pub securities_accounts: Vec<Account>,
+    /// Choices for the start page.
+    pub start_pages: Vec<&'static NavItem>,
 }
 
 #[derive(Template)]
@@ -40,6 +64,344 @@ pub struct SettingsFormData {
     pub date_format: String,
     pub page_size: String,
     pub locale: String,
+    #[serde(default)]
+    pub timezone: String,
+    #[serde(default)]
+    pub currency_symbol: String,
+    #[serde(default = "default_symbol_position")]
+    pub symbol_position: String,
+    #[serde(default)]
+    pub currency_decimals: String,
+    #[serde(default = "default_xirr_transfers")]
+    pub xirr_transfers: String,
+    /// HTML checkbox: "on" when checked, absent (defaults to "") when unchecked.
+    #[serde(default)]
+    pub allow_short_positions: String,
+    /// Dust threshold in the main currency; empty keeps the current value.
+    #[serde(default)]
+    pub dust_threshold: String,
+    /// Days until a price counts as stale; empty keeps the current value.
+    #[serde(default)]
+    pub price_staleness_days: String,
+    /// Wash-sale window in days; empty keeps the current value.
+    #[serde(default)]
+    pub wash_sale_window_days: String,
+    /// Decimal places of percentages; empty keeps the current value.
+    #[serde(default)]
+    pub percent_decimals: String,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub default_account_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub default_category_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub default_trading_account_id: Option<i64>,
+    /// HTML checkbox: "on" when checked.
+    #[serde(default)]
+    pub mirror_trading_cash: String,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub mirror_dividend_category_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub mirror_fee_category_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub mirror_tax_category_id: Option<i64>,
+}
+
+fn default_symbol_position() -> String {
+    "prefix".into()
+}
+
+fn default_xirr_transfers() -> String {
+    "exclude".into()
+}
+
+impl SettingsFormData {
+    fn dust_threshold_cents(&self) -> AppResult<Option<i64>> {
+        let input = self.dust_threshold.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match money::parse_amount(input, money::INPUT_LOCALE) {
+            Ok(cents) if cents >= 0 => Ok(Some(cents)),
+            _ => Err(AppError::Validation(
+                "Dust threshold must be a non-negative amount".into(),
+            )),
+        }
+    }
+
+    fn price_staleness_days(&self) -> AppResult<Option<i64>> {
+        let input = self.price_staleness_days.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match input.parse::<i64>() {
+            Ok(days) if days >= 1 => Ok(Some(days)),
+            _ => Err(AppError::Validation(
+                "Price staleness must be at least one day".into(),
+            )),
+        }
+    }
+
+    fn wash_sale_window_days(&self) -> AppResult<Option<i64>> {
+        let input = self.wash_sale_window_days.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match input.parse::<i64>() {
+            Ok(days) if (0..=MAX_WASH_SALE_WINDOW_DAYS).contains(&days) => Ok(Some(days)),
+            _ => Err(AppError::Validation(format!(
+                "Wash-sale window must be between 0 and {} days",
+                MAX_WASH_SALE_WINDOW_DAYS
+            ))),
+        }
+    }
+
+    fn percent_decimals(&self) -> AppResult<Option<u32>> {
+        let input = self.percent_decimals.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match input.parse::<u32>() {
+            Ok(n) if n <= MAX_PERCENT_DECIMALS => Ok(Some(n)),
+            _ => Err(AppError::Validation(format!(
+                "Percentage decimals must be between 0 and {}",
+                MAX_PERCENT_DECIMALS
+            ))),
+        }
+    }
+
+    /// Validate the date format, timezone, currency display and XIRR fields. Returns the parsed decimals
+    /// (`None` when left empty).
+    fn validate(&self) -> AppResult<Option<u32>> {
+        if !matches!(self.symbol_position.as_str(), "prefix" | "suffix") {
+            return Err(AppError::Validation(format!(
+                "Invalid symbol position: {}",
+                self.symbol_position
+            )));
+        }
+        if self.date_format.parse::<date_utils::DateFormat>().is_err() {
+            return Err(AppError::Validation(format!(
+                "Invalid date format: {}",
+                self.date_format
+            )));
+        }
+        let timezone = self.timezone.trim();
+        if !timezone.is_empty() && !date_utils::is_valid_timezone(timezone) {
+            return Err(AppError::Validation(format!(
+                "Unknown timezone: {}",
+                timezone
+            )));
+        }
+        if !matches!(self.xirr_transfers.as_str(), "exclude" | "at_cost") {
+            return Err(AppError::Validation(format!(
+                "Invalid XIRR transfer handling: {}",
+                self.xirr_transfers
+            )));
+        }
+        if self.currency_symbol.trim().chars().count() > 8 {
+            return Err(AppError::Validation(
+                "Currency symbol must be at most 8 characters".into(),
+            ));
+        }
+        self.dust_threshold_cents()?;
+        self.price_staleness_days()?;
+        self.wash_sale_window_days()?;
+        self.percent_decimals()?;
+        let decimals = self.currency_decimals.trim();
+        if decimals.is_empty() {
+            return Ok(None);
+        }
+        match decimals.parse::<u32>() {
+            Ok(n) if n <= 4 => Ok(Some(n)),
+            _ => Err(AppError::Validation(
+                "Decimals must be between 0 and 4".into(),
+            )),
+        }
+    }
+
+    /// Check that the default account, trading account and category exist
+    /// and that the accounts have the right type.
+    fn validate_defaults(&self, conn: &Connection) -> AppResult<()> {
+        for (id, trading) in [
+            (self.default_account_id, false),
+            (self.default_trading_account_id, true),
+        ] {
+            let Some(id) = id else { continue };
+            let (allowed, reason): (fn(&AccountType) -> bool, _) = if trading {
+                (
+                    |t| *t == AccountType::Securities,
+                    "is not a Securities account",
+                )
+            } else {
+                (
+                    AccountType::holds_transactions,
+                    "does not hold transactions",
+                )
+            };
+            match accounts::get_account(conn, id)? {
+                Some(account) if allowed(&account.account_type) => {}
+                Some(_) => {
+                    return Err(AppError::Validation(format!(
+                        "Default account {} {}",
+                        id, reason
+                    )))
+                }
+                None => {
+                    return Err(AppError::Validation(format!(
+                        "Account {} does not exist",
+                        id
+                    )))
+                }
+            }
+        }
+        for id in [
+            self.default_category_id,
+            self.mirror_dividend_category_id,
+            self.mirror_fee_category_id,
+            self.mirror_tax_category_id,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if categories::get_category(conn, id)?.is_none() {
+                return Err(AppError::Validation(format!(
+                    "Category {} does not exist",
+                    id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Store an optional id setting, with an empty value for `None`.
+fn set_id_setting(conn: &Connection, key: &str, id: Option<i64>) -> AppResult<()> {
+    let value = id.map(|id| id.to_string()).unwrap_or_default();
+    settings::set_setting(conn, key, &value)?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackupSettingsFormData {
+    pub backup_dir: String,
+    pub backup_frequency: String,
+    pub backup_retention: String,
+}
+
+impl BackupSettingsFormData {
+    fn validate(&self) -> AppResult<i64> {
+        if !matches!(self.backup_frequency.as_str(), "off" | "daily" | "weekly") {
+            return Err(AppError::Validation(format!(
+                "Invalid backup frequency: {}",
+                self.backup_frequency
+            )));
+        }
+        match self.backup_retention.trim().parse::<i64>() {
+            Ok(n) if (1..=365).contains(&n) => Ok(n),
+            _ => Err(AppError::Validation(
+                "Retention must be between 1 and 365 backups".into(),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationSettingsFormData {
+    pub notify_channel: String,
+    #[serde(default)]
+    pub notify_webhook_url: String,
+    #[serde(default)]
+    pub notify_smtp_host: String,
+    #[serde(default)]
+    pub notify_smtp_port: String,
+    #[serde(default)]
+    pub notify_smtp_username: String,
+    /// Empty keeps the stored password.
+    #[serde(default)]
+    pub notify_smtp_password: String,
+    #[serde(default)]
+    pub notify_smtp_from: String,
+    #[serde(default)]
+    pub notify_smtp_to: String,
+    #[serde(default)]
+    pub notify_failure_threshold: String,
+}
+
+impl NotificationSettingsFormData {
+    /// Validate the form and apply it to `settings`.
+    fn apply(&self, settings: &mut Settings) -> AppResult<()> {
+        let channel = self.notify_channel.as_str();
+        if !matches!(channel, "off" | "smtp" | "webhook") {
+            return Err(AppError::Validation(format!(
+                "Invalid notification channel: {}",
+                channel
+            )));
+        }
+        let webhook_url = self.notify_webhook_url.trim();
+        if channel == "webhook"
+            && !(webhook_url.starts_with("http://") || webhook_url.starts_with("https://"))
+        {
+            return Err(AppError::Validation(
+                "The webhook URL must start with http:// or https://".into(),
+            ));
+        }
+        let port = match self.notify_smtp_port.trim() {
+            "" => crate::models::settings::DEFAULT_SMTP_PORT,
+            port => port
+                .parse::<u16>()
+                .ok()
+                .filter(|&p| p > 0)
+                .ok_or_else(|| AppError::Validation(format!("Invalid SMTP port: {}", port)))?,
+        };
+        let threshold = match self.notify_failure_threshold.trim().parse::<u32>() {
+            Ok(n) if (1..=1000).contains(&n) => n,
+            _ => {
+                return Err(AppError::Validation(
+                    "The failure threshold must be between 1 and 1000 symbols".into(),
+                ))
+            }
+        };
+
+        settings.notify_channel = channel.to_string();
+        settings.notify_webhook_url = webhook_url.to_string();
+        settings.notify_smtp_host = self.notify_smtp_host.trim().to_string();
+        settings.notify_smtp_port = port;
+        settings.notify_smtp_username = self.notify_smtp_username.trim().to_string();
+        if !self.notify_smtp_password.is_empty() {
+            settings.notify_smtp_password = self.notify_smtp_password.clone();
+        }
+        settings.notify_smtp_from = self.notify_smtp_from.trim().to_string();
+        settings.notify_smtp_to = self.notify_smtp_to.trim().to_string();
+        settings.notify_failure_threshold = threshold;
+
+        if channel == "smtp"
+            && (settings.notify_smtp_host.is_empty()
+                || settings.notify_smtp_from.is_empty()
+                || settings.notify_recipients().next().is_none())
+        {
+            return Err(AppError::Validation(
+                "Email notifications need an SMTP server, a sender and a recipient".into(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +409,16 @@ pub struct ThemeFormData {
     pub theme: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DefaultSortFormData {
+    pub table: String,
+    /// Column to sort by; empty restores the built-in default.
+    #[serde(default)]
+    pub sort: String,
+    #[serde(default)]
+    pub dir: String,
+}
+
 pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
     let PageBase {
         settings,
@@ -54,9 +426,14 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let database_size = get_database_size(&state.config.database_path);
+    let conn = state.db.get()?;
+    let backup_status = BackupStatus::load(&conn)?;
+    let securities_accounts = accounts::list_accounts_by_type(&conn, AccountType::Securities)?;
+    let default_backup_dir = backup::backup_dir(&state, "").display().to_string();
 
     let template = SettingsTemplate {
         title: "Settings".into(),
@@ -65,7 +442,18 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         database_size,
+        backup_status,
+        default_backup_dir,
+        timezones: date_utils::timezone_names(),
+        cash_accounts: state.cached_cash_accounts()?,
+        categories: state.cached_categories_with_path()?,
+        securities_accounts,
+        start_pages: nav::START_PAGES
+            .iter()
+            .filter_map(|key| nav::find(key))
+            .collect(),
     };
 
     template.render_html()
@@ -100,177 +488,348 @@ fn format_size(bytes: u64) -> String {
 pub async fn update(
     State(state): State<AppState>,
     Form(form): Form<SettingsFormData>,
+) -> AppResult<impl IntoResponse> {
+    let decimals = form.validate()?;
+    form.validate_defaults(&*state.db.get()?)?;
+    state.with_tx(|tx| {
+        settings::set_setting(tx, "theme", &form.theme)?;
+        settings::set_setting(tx, "currency", &form.currency)?;
+        settings::set_setting(tx, "date_format", &form.date_format)?;
+        settings::set_setting(tx, "page_size", &form.page_size)?;
+        settings::set_setting(tx, "locale", &form.locale)?;
+        settings::set_setting(tx, "timezone", form.timezone.trim())?;
+        settings::set_setting(tx, "currency_symbol", form.currency_symbol.trim())?;
+        settings::set_setting(tx, "symbol_position", &form.symbol_position)?;
+        settings::set_setting(
+            tx,
+            "currency_decimals",
+            &decimals.map(|d| d.to_string()).unwrap_or_default(),
+        )?;
+        settings::set_setting(tx, "xirr_transfers", &form.xirr_transfers)?;
+        settings::set_setting(
+            tx,
+            "allow_short_positions",
+            if form.allow_short_positions == "on" {
+                "true"
+            } else {
+                "false"
+            },
+        )?;
+        if let Some(cents) = form.dust_threshold_cents()? {
+            settings::set_setting(tx, "dust_threshold_cents", &cents.to_string())?;
+        }
+        if let Some(days) = form.price_staleness_days()? {
+            settings::set_setting(tx, "price_staleness_days", &days.to_string())?;
+        }
+        if let Some(days) = form.wash_sale_window_days()? {
+            settings::set_setting(tx, "wash_sale_window_days", &days.to_string())?;
+        }
+        if let Some(decimals) = form.percent_decimals()? {
+            settings::set_setting(tx, "percent_decimals", &decimals.to_string())?;
+        }
+        set_id_setting(tx, "default_account_id", form.default_account_id)?;
+        set_id_setting(tx, "default_category_id", form.default_category_id)?;
+        set_id_setting(
+            tx,
+            "default_trading_account_id",
+            form.default_trading_account_id,
+        )?;
+        settings::set_setting(
+            tx,
+            "mirror_trading_cash",
+            if form.mirror_trading_cash == "on" {
+                "true"
+            } else {
+                "false"
+            },
+        )?;
+        set_id_setting(
+            tx,
+            "mirror_dividend_category_id",
+            form.mirror_dividend_category_id,
+        )?;
+        set_id_setting(tx, "mirror_fee_category_id", form.mirror_fee_category_id)?;
+        set_id_setting(tx, "mirror_tax_category_id", form.mirror_tax_category_id)?;
+        Ok(())
+    })?;
+
+    let message = "Settings saved successfully";
+    let template = SettingsSavedTemplate {
+        icons: crate::filters::Icons,
+        message: message.into(),
+    };
+
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, message),
+        template.render_html()?,
+    ))
+}
+
+pub async fn update_backup(
+    State(state): State<AppState>,
+    Form(form): Form<BackupSettingsFormData>,
 ) -> AppResult<Html<String>> {
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
+    let retention = form.validate()?;
+    state.with_tx(|tx| {
+        settings::set_setting(tx, "backup_dir", form.backup_dir.trim())?;
+        settings::set_setting(tx, "backup_frequency", &form.backup_frequency)?;
+        settings::set_setting(tx, "backup_retention", &retention.to_string())?;
+        Ok(())
+    })?;
+    info!(frequency = %form.backup_frequency, retention, "Backup settings updated");
+
+    let template = SettingsSavedTemplate {
+        icons: crate::filters::Icons,
+        message: "Backup settings saved".into(),
+    };
 
-    settings::set_setting(&tx, "theme", &form.theme)?;
-    settings::set_setting(&tx, "currency", &form.currency)?;
-    settings::set_setting(&tx, "date_format", &form.date_format)?;
-    settings::set_setting(&tx, "page_size", &form.page_size)?;
-    settings::set_setting(&tx, "locale", &form.locale)?;
+    template.render_html()
+}
 
-    tx.commit()?;
+pub async fn backup_now(State(state): State<AppState>) -> AppResult<Html<String>> {
+    let path = backup::run_backup(&state)?;
 
     let template = SettingsSavedTemplate {
         icons: crate::filters::Icons,
-        message: "Settings saved successfully".into(),
+        message: format!("Backup written to {}", path.display()),
     };
 
     template.render_html()
 }
 
-pub async fn toggle_theme(
+pub async fn update_notifications(
     State(state): State<AppState>,
-    Form(form): Form<ThemeFormData>,
+    Form(form): Form<NotificationSettingsFormData>,
 ) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
+    let mut current = state.load_settings()?;
+    form.apply(&mut current)?;
+
+    state.with_tx(|tx| {
+        let map = current.to_map();
+        for key in [
+            "notify_channel",
+            "notify_webhook_url",
+            "notify_smtp_host",
+            "notify_smtp_port",
+            "notify_smtp_username",
+            "notify_smtp_password",
+            "notify_smtp_from",
+            "notify_smtp_to",
+            "notify_failure_threshold",
+        ] {
+            settings::set_setting(tx, key, &map[key])?;
+        }
+        Ok(())
+    })?;
+    info!(channel = %current.notify_channel, "Notification settings updated");
 
-    settings::set_setting(&conn, "theme", &form.theme)?;
+    let template = SettingsSavedTemplate {
+        icons: crate::filters::Icons,
+        message: "Notification settings saved".into(),
+    };
 
-    Ok(Html(String::new()))
+    template.render_html()
 }
 
-pub async fn export_database(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
-    let conn = state.db.get()?;
+/// Send a test notification with the settings in the form, saved or not.
+pub async fn test_notification(
+    State(state): State<AppState>,
+    Form(form): Form<NotificationSettingsFormData>,
+) -> AppResult<Html<String>> {
+    let mut current = state.load_settings()?;
+    form.apply(&mut current)?;
+    notify::send_test(&state, &current).await?;
 
-    let temp_path = std::env::temp_dir().join(format!("solvency-backup-{}.db", std::process::id()));
-    let path_str = temp_path.display().to_string().replace('\'', "''");
+    let template = SettingsSavedTemplate {
+        icons: crate::filters::Icons,
+        message: "Test notification sent".into(),
+    };
 
-    // VACUUM INTO creates an atomic, consistent snapshot of the database.
-    conn.execute_batch(&format!("VACUUM INTO '{}'", path_str))?;
+    template.render_html()
+}
 
-    let bytes = fs::read(&temp_path)?;
-    let _ = fs::remove_file(&temp_path);
+pub async fn toggle_theme(
+    State(state): State<AppState>,
+    Form(form): Form<ThemeFormData>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
 
-    info!(size_bytes = bytes.len(), "Database exported");
+    settings::set_setting(&conn, "theme", &form.theme)?;
 
-    Ok((
-        [
-            (header::CONTENT_TYPE, "application/x-sqlite3"),
-            (
-                header::CONTENT_DISPOSITION,
-                "attachment; filename=\"solvency-backup.db\"",
-            ),
-        ],
-        bytes,
-    ))
+    Ok(Html(String::new()))
 }
 
-pub async fn import_database(
+/// Save the visible transactions table columns and the table row density.
+/// Accepts one `columns` field per visible column, `density` and an optional
+/// `return_to` transactions URL to go back to.
+pub async fn update_table_columns(
     State(state): State<AppState>,
-    mut multipart: Multipart,
-) -> AppResult<Html<String>> {
-    let mut file_bytes = Vec::new();
-
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to read upload: {}", e)))?
-    {
-        if field.name() == Some("file") {
-            file_bytes = field
-                .bytes()
-                .await
-                .map_err(|e| AppError::Internal(format!("Failed to read file: {}", e)))?
-                .to_vec();
-            break;
-        }
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Redirect> {
+    let columns: Vec<&str> = TRANSACTION_COLUMNS
+        .iter()
+        .map(|(key, _)| *key)
+        .filter(|key| fields.iter().any(|(k, v)| k == "columns" && v == key))
+        .collect();
+    if columns.is_empty() {
+        return Err(AppError::Validation(
+            "Select at least one column to show".into(),
+        ));
     }
-
-    if file_bytes.is_empty() {
-        return Err(AppError::Validation("No file uploaded".to_string()));
+    let density = fields
+        .iter()
+        .find(|(k, _)| k == "density")
+        .map_or("comfortable", |(_, v)| v.as_str());
+    if !matches!(density, "comfortable" | "compact") {
+        return Err(AppError::Validation(format!(
+            "Invalid table density: {}",
+            density
+        )));
     }
 
-    let mut conn = state.db.get()?;
+    state.with_tx(|tx| {
+        settings::set_setting(tx, "transaction_columns", &columns.join(","))?;
+        settings::set_setting(tx, "table_density", density)?;
+        Ok(())
+    })?;
+
+    // Only return to the transactions list, never to an arbitrary URL
+    let return_to = fields
+        .iter()
+        .find(|(k, _)| k == "return_to")
+        .map(|(_, v)| v.as_str())
+        .filter(|url| *url == "/transactions" || url.starts_with("/transactions?"))
+        .unwrap_or("/transactions");
+
+    flash::flash_success("Table layout saved");
+    Ok(Redirect::to(return_to))
+}
 
-    // Only accept SQLite binary files
-    let is_sqlite = file_bytes.len() >= 16 && file_bytes[..16] == *b"SQLite format 3\0";
+/// Save the start page and the sidebar order and visibility. Accepts
+/// `start_page`, one `visible` field per shown entry and a `position_<key>`
+/// number per entry; entries without a position keep their default order.
+pub async fn update_navigation(
+    State(state): State<AppState>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> AppResult<Redirect> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim())
+    };
 
-    if !is_sqlite {
+    let start_page = field("start_page").unwrap_or("dashboard");
+    if !nav::START_PAGES.contains(&start_page) {
+        return Err(AppError::Validation(format!(
+            "Invalid start page: {}",
+            start_page
+        )));
+    }
+
+    let visible = |key: &str| fields.iter().any(|(k, v)| k == "visible" && v == key);
+    if !visible(nav::SETTINGS_ITEM) {
         return Err(AppError::Validation(
-            "Invalid file format. Please upload a .db SQLite backup file.".to_string(),
+            "The settings page cannot be hidden".into(),
         ));
     }
-
-    let temp_path = std::env::temp_dir().join(format!("solvency-import-{}.db", std::process::id()));
-    fs::write(&temp_path, &file_bytes)?;
-
-    let result = restore_from_db_file(&mut conn, &temp_path, &state.config.migrations_path);
-    let _ = fs::remove_file(&temp_path);
-    result?;
-
+    let hidden: Vec<&str> = NAV_ITEMS
+        .iter()
+        .map(|item| item.key)
+        .filter(|key| !visible(key))
+        .collect();
+
+    let mut positioned: Vec<(i64, &str)> = Vec::new();
+    for item in NAV_ITEMS {
+        let Some(value) = field(&format!("position_{}", item.key)).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let position = value.parse().map_err(|_| {
+            AppError::Validation(format!("Invalid position for {}: {}", item.label, value))
+        })?;
+        positioned.push((position, item.key));
+    }
+    // Stable, so equal positions keep the default order
+    positioned.sort_by_key(|(position, _)| *position);
+    let order: Vec<&str> = positioned.into_iter().map(|(_, key)| key).collect();
+
+    state.with_tx(|tx| {
+        settings::set_setting(tx, "start_page", start_page)?;
+        settings::set_setting(tx, "nav_order", &order.join(","))?;
+        settings::set_setting(tx, "hidden_nav_items", &hidden.join(","))?;
+        Ok(())
+    })?;
     info!(
-        size_bytes = file_bytes.len(),
-        "Database restored from .db backup"
+        start_page,
+        hidden = hidden.len(),
+        "Navigation settings updated"
     );
 
-    state.cache.invalidate();
-
-    let template = SettingsSavedTemplate {
-        icons: crate::filters::Icons,
-        message: "Database imported successfully. Please refresh the page.".into(),
-    };
-
-    template.render_html()
+    flash::flash_success("Navigation saved");
+    Ok(Redirect::to("/settings"))
 }
 
-/// Restore the live database from an uploaded .db file using SQLite's backup API.
-///
-/// This performs a page-level copy from the source into the live database.
-/// All existing pool connections see the new data immediately. After the
-/// backup completes, migrations are re-run to bring the schema up to date
-/// (handles importing backups from older versions).
-fn restore_from_db_file(
-    conn: &mut rusqlite::Connection,
-    src_path: &Path,
-    migrations_path: &Path,
-) -> AppResult<()> {
-    let src = rusqlite::Connection::open(src_path)?;
-    let backup = rusqlite::backup::Backup::new(&src, conn)?;
-    backup
-        .run_to_completion(100, std::time::Duration::ZERO, None)
-        .map_err(|e| AppError::Internal(format!("Backup failed: {}", e)))?;
-    drop(backup);
-    drop(src);
-
-    // Re-run migrations so the schema matches the current app version
-    crate::db::migrations::run_migrations(conn, migrations_path)?;
-
-    // Restore WAL mode and FK checks (backup copies source pragmas)
-    conn.execute_batch(
-        "PRAGMA journal_mode = WAL;\
-         PRAGMA foreign_keys = ON;",
-    )?;
+/// The stored form of sorting by `sort` in `direction`, if it is one of the
+/// table's columns.
+fn stored_sort<C: SortableColumn>(sort: &str, direction: SortDirection) -> Option<String> {
+    C::from_str(sort).map(|column| TableSort { column, direction }.stored_value())
+}
 
-    Ok(())
+/// Page showing the table, and the stored sort order if `sort` is one of its
+/// columns.
+fn default_sort_table(
+    table: &str,
+    sort: &str,
+    direction: SortDirection,
+) -> Option<(&'static str, Option<String>)> {
+    match table {
+        "positions" => Some((
+            "/trading/positions",
+            stored_sort::<PositionSortColumn>(sort, direction),
+        )),
+        "closed_positions" => Some((
+            "/trading/positions/closed",
+            stored_sort::<ClosedPositionSortColumn>(sort, direction),
+        )),
+        "market_data" => Some((
+            "/trading/market-data",
+            stored_sort::<MarketDataSortColumn>(sort, direction),
+        )),
+        _ => None,
+    }
 }
 
-pub async fn clear_database(State(state): State<AppState>) -> AppResult<Html<String>> {
-    warn!("Clearing entire database");
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
-
-    let tables: Vec<String> = {
-        let mut stmt = tx.prepare(
-            "SELECT name FROM sqlite_master
-             WHERE type='table'
-             AND name NOT LIKE 'sqlite_%'
-             AND name != '_migrations'
-             ORDER BY name",
-        )?;
-        let rows = stmt
-            .query_map([], |row| row.get(0))?
-            .collect::<Result<Vec<_>, _>>()?;
-        rows
+/// Store the sort order a table uses when the URL doesn't specify one.
+pub async fn update_default_sort(
+    State(state): State<AppState>,
+    Form(form): Form<DefaultSortFormData>,
+) -> AppResult<Redirect> {
+    let sort = form.sort.trim();
+    let direction = match form.dir.as_str() {
+        "" => SortDirection::default(),
+        "asc" => SortDirection::Asc,
+        "desc" => SortDirection::Desc,
+        other => {
+            return Err(AppError::Validation(format!(
+                "Invalid sort direction: {}",
+                other
+            )))
+        }
     };
+    let (page_url, stored) = default_sort_table(&form.table, sort, direction)
+        .ok_or_else(|| AppError::Validation(format!("Unknown table: {}", form.table)))?;
+    let key = format!("default_sort_{}", form.table);
 
-    for table in &tables {
-        tx.execute(&format!("DELETE FROM \"{}\"", table), [])?;
+    let conn = state.db.get()?;
+    if sort.is_empty() {
+        settings::delete_setting(&conn, &key)?;
+        flash::flash_success("Default sort order reset");
+        return Ok(Redirect::to(page_url));
     }
+    let stored =
+        stored.ok_or_else(|| AppError::Validation(format!("Invalid sort column: {}", sort)))?;
+    settings::set_setting(&conn, &key, &stored)?;
 
-    tx.commit()?;
-    warn!(tables_cleared = tables.len(), "Database cleared");
-
-    Ok(Html(String::new()))
+    flash::flash_success("Default sort order saved");
+    Ok(Redirect::to(page_url))
 }
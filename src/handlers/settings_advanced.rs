@@ -0,0 +1,219 @@
+//! Advanced settings page: logging, market data pacing, sessions and the
+//! startup options the server was launched with.
+
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, IntoResponse};
+use axum::Form;
+use serde::Deserialize;
+
+use tracing::info;
+
+use crate::config::{AuthMode, Config};
+use crate::db::queries::settings;
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash::{self, FlashLevel};
+use crate::handlers::settings::SettingsSavedTemplate;
+use crate::logging;
+use crate::models::Settings;
+use crate::state::{AppState, JsManifest, PageBase};
+
+#[derive(Template)]
+#[template(path = "pages/settings_advanced.html")]
+pub struct AdvancedSettingsTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    /// Filter used when the log filter setting is empty.
+    pub startup_log_filter: String,
+    /// Whether log filter changes apply without a restart.
+    pub log_reloadable: bool,
+    /// Options that are only read from the environment at startup.
+    pub startup_options: Vec<StartupOption>,
+}
+
+/// A read-only configuration value shown on the advanced settings page.
+pub struct StartupOption {
+    pub label: &'static str,
+    pub value: String,
+    pub env_var: &'static str,
+    /// Whether the environment variable is set, as opposed to the default.
+    pub from_env: bool,
+}
+
+impl StartupOption {
+    fn new(label: &'static str, value: String, env_var: &'static str) -> Self {
+        Self {
+            label,
+            value,
+            env_var,
+            from_env: std::env::var_os(env_var).is_some(),
+        }
+    }
+}
+
+fn startup_options(config: &Config) -> Vec<StartupOption> {
+    let on_off = |b: bool| if b { "On" } else { "Off" }.to_string();
+    vec![
+        StartupOption::new("Host", config.host.clone(), "SOLVENCY_HOST"),
+        StartupOption::new("Port", config.port.to_string(), "SOLVENCY_PORT"),
+        StartupOption::new(
+            "Database",
+            config.database_path.display().to_string(),
+            "SOLVENCY_DATABASE_URL",
+        ),
+        StartupOption::new(
+            "Migrations",
+            config.migrations_path.display().to_string(),
+            "SOLVENCY_MIGRATIONS_PATH",
+        ),
+        StartupOption::new(
+            "Static Files",
+            config.static_path.display().to_string(),
+            "SOLVENCY_STATIC_PATH",
+        ),
+        StartupOption::new(
+            "Authentication",
+            match config.auth_mode {
+                AuthMode::Unauthenticated => "Disabled".into(),
+                AuthMode::Password(_) => "Password".into(),
+            },
+            "SOLVENCY_PASSWORD_HASH",
+        ),
+        StartupOption::new(
+            "Secure Cookies",
+            on_off(config.secure_cookies),
+            "SOLVENCY_SECURE_COOKIES",
+        ),
+        StartupOption::new(
+            "Slow Query Log",
+            config
+                .slow_query_ms
+                .map(|ms| format!("{} ms", ms))
+                .unwrap_or_else(|| "Off".into()),
+            "SOLVENCY_SLOW_QUERY_MS",
+        ),
+        StartupOption::new(
+            "Backup Directory",
+            config
+                .backup_dir
+                .as_ref()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_else(|| "Next to the database".into()),
+            "SOLVENCY_BACKUP_DIR",
+        ),
+        StartupOption::new(
+            "Allow Dirty Migrations",
+            on_off(config.allow_dirty_migrations),
+            "SOLVENCY_ALLOW_DIRTY_MIGRATIONS",
+        ),
+    ]
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdvancedSettingsFormData {
+    #[serde(default)]
+    pub log_filter: String,
+    pub market_data_delay_ms: String,
+    pub session_ttl_hours: String,
+    /// HTML checkbox: "on" when checked, absent (defaults to "") when unchecked.
+    #[serde(default)]
+    pub trust_proxy_headers: String,
+}
+
+impl AdvancedSettingsFormData {
+    /// Validate all fields. Returns the parsed delay and session TTL.
+    fn validate(&self) -> AppResult<(u64, u64)> {
+        logging::validate_filter(self.log_filter.trim())
+            .map_err(|e| AppError::Validation(format!("Invalid log filter: {}", e)))?;
+        let delay = match self.market_data_delay_ms.trim().parse::<u64>() {
+            Ok(n) if n <= 60_000 => n,
+            _ => {
+                return Err(AppError::Validation(
+                    "Market data delay must be between 0 and 60000 ms".into(),
+                ))
+            }
+        };
+        let ttl = match self.session_ttl_hours.trim().parse::<u64>() {
+            Ok(n) if n <= 8760 => n,
+            _ => {
+                return Err(AppError::Validation(
+                    "Session lifetime must be between 0 and 8760 hours".into(),
+                ))
+            }
+        };
+        Ok((delay, ttl))
+    }
+}
+
+pub async fn advanced(State(state): State<AppState>) -> AppResult<Html<String>> {
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    let template = AdvancedSettingsTemplate {
+        title: "Advanced Settings".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        startup_log_filter: logging::startup_filter().unwrap_or("RUST_LOG").to_string(),
+        log_reloadable: logging::is_reloadable(),
+        startup_options: startup_options(&state.config),
+    };
+
+    template.render_html()
+}
+
+/// Save the runtime options. The log filter is swapped immediately; the
+/// other values are read from the settings on every use.
+pub async fn update_advanced(
+    State(state): State<AppState>,
+    Form(form): Form<AdvancedSettingsFormData>,
+) -> AppResult<impl IntoResponse> {
+    let (delay, ttl) = form.validate()?;
+    let log_filter = form.log_filter.trim();
+    state.with_tx(|tx| {
+        settings::set_setting(tx, "log_filter", log_filter)?;
+        settings::set_setting(tx, "market_data_delay_ms", &delay.to_string())?;
+        settings::set_setting(tx, "session_ttl_hours", &ttl.to_string())?;
+        settings::set_setting(
+            tx,
+            "trust_proxy_headers",
+            if form.trust_proxy_headers == "on" {
+                "true"
+            } else {
+                "false"
+            },
+        )?;
+        Ok(())
+    })?;
+    logging::set_filter(log_filter).map_err(AppError::Internal)?;
+    info!(
+        delay_ms = delay,
+        session_ttl_hours = ttl,
+        "Advanced settings updated"
+    );
+
+    let message = "Advanced settings saved";
+    let template = SettingsSavedTemplate {
+        icons: crate::filters::Icons,
+        message: message.into(),
+    };
+
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, message),
+        template.render_html()?,
+    ))
+}
@@ -0,0 +1,425 @@
+//! Whole-database maintenance from the settings page: export, anonymized
+//! export, diagnostics bundle, import and clearing data.
+
+use axum::extract::{Multipart, Query, State};
+use axum::http::header;
+use axum::response::{Html, IntoResponse, Response};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use tracing::{info, warn};
+
+use crate::body_limit;
+use crate::cache::DataDomain;
+use crate::config::Config;
+use crate::confirmation::{confirmation_pending, deletion_message, ConfirmParams, DeletedCounts};
+use crate::date_utils;
+use crate::db::queries::{market_data, trading, transactions};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash;
+use crate::handlers::settings::SettingsSavedTemplate;
+use crate::services::anonymize::{self, AnonymizeOptions};
+use crate::services::diagnostics::{self, DiagnosticsOptions};
+use crate::state::AppState;
+
+pub async fn export_database(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let conn = state.db.get()?;
+
+    let temp_path = std::env::temp_dir().join(format!("solvency-backup-{}.db", std::process::id()));
+    let path_str = temp_path.display().to_string().replace('\'', "''");
+
+    // VACUUM INTO creates an atomic, consistent snapshot of the database.
+    conn.execute_batch(&format!("VACUUM INTO '{}'", path_str))?;
+
+    let bytes = fs::read(&temp_path)?;
+    let _ = fs::remove_file(&temp_path);
+
+    info!(size_bytes = bytes.len(), "Database exported");
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-sqlite3"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"solvency-backup.db\"",
+            ),
+        ],
+        bytes,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnonymizedExportParams {
+    /// Seed for the fake values; random if not given.
+    pub seed: Option<u64>,
+    /// Also scale all amounts by a random factor.
+    #[serde(default)]
+    pub scale: bool,
+}
+
+/// Export an anonymized copy of the database that is safe to share.
+/// The seed is part of the file name so the export can be reproduced.
+pub async fn export_anonymized(
+    State(state): State<AppState>,
+    Query(params): Query<AnonymizedExportParams>,
+) -> AppResult<impl IntoResponse> {
+    let conn = state.db.get()?;
+    let options = AnonymizeOptions {
+        seed: params.seed.unwrap_or_else(rand::random),
+        scale_amounts: params.scale,
+    };
+
+    let temp_path =
+        std::env::temp_dir().join(format!("solvency-anonymized-{}.db", std::process::id()));
+    let _ = fs::remove_file(&temp_path);
+    let result =
+        anonymize::export_copy(&conn, &temp_path, &options).and_then(|_| Ok(fs::read(&temp_path)?));
+    let _ = fs::remove_file(&temp_path);
+    let bytes = result?;
+
+    info!(
+        size_bytes = bytes.len(),
+        seed = options.seed,
+        "Anonymized database exported"
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-sqlite3".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"solvency-anonymized-{}.db\"",
+                    options.seed
+                ),
+            ),
+        ],
+        bytes,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiagnosticsBundleParams {
+    /// Keep IBANs, payees and descriptions in the bundle.
+    #[serde(default)]
+    pub include_personal: bool,
+}
+
+/// Download the API logs, import sessions and check results as a ZIP to
+/// attach to a bug report, see [`diagnostics`].
+pub async fn diagnostics_bundle(
+    State(state): State<AppState>,
+    Query(params): Query<DiagnosticsBundleParams>,
+) -> AppResult<impl IntoResponse> {
+    let conn = state.db.get()?;
+    let options = DiagnosticsOptions {
+        include_personal: params.include_personal,
+    };
+    let bytes = diagnostics::build_bundle(&conn, &state.config.migrations_path, &options)?;
+    let today = date_utils::today_in(&state.load_settings()?);
+
+    info!(
+        size_bytes = bytes.len(),
+        include_personal = options.include_personal,
+        "Diagnostics bundle exported"
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"solvency-diagnostics-{}.zip\"",
+                    today.format("%Y-%m-%d")
+                ),
+            ),
+        ],
+        bytes,
+    ))
+}
+
+pub async fn import_database(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> AppResult<Html<String>> {
+    let mut file_bytes = Vec::new();
+    let limit_bytes = state.config.max_upload_bytes();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        body_limit::multipart_error(e, limit_bytes, |e| {
+            AppError::Internal(format!("Failed to read upload: {}", e))
+        })
+    })? {
+        if field.name() == Some("file") {
+            file_bytes = field
+                .bytes()
+                .await
+                .map_err(|e| {
+                    body_limit::multipart_error(e, limit_bytes, |e| {
+                        AppError::Internal(format!("Failed to read file: {}", e))
+                    })
+                })?
+                .to_vec();
+            break;
+        }
+    }
+
+    if file_bytes.is_empty() {
+        return Err(AppError::Validation("No file uploaded".to_string()));
+    }
+
+    let mut conn = state.db.get()?;
+
+    // Only accept SQLite binary files
+    let is_sqlite = file_bytes.len() >= 16 && file_bytes[..16] == *b"SQLite format 3\0";
+
+    if !is_sqlite {
+        return Err(AppError::Validation(
+            "Invalid file format. Please upload a .db SQLite backup file.".to_string(),
+        ));
+    }
+
+    let temp_path = std::env::temp_dir().join(format!("solvency-import-{}.db", std::process::id()));
+    fs::write(&temp_path, &file_bytes)?;
+
+    let result = restore_from_db_file(&mut conn, &temp_path, &state.config);
+    let _ = fs::remove_file(&temp_path);
+    result?;
+
+    info!(
+        size_bytes = file_bytes.len(),
+        "Database restored from .db backup"
+    );
+
+    state.cache.invalidate();
+
+    let template = SettingsSavedTemplate {
+        icons: crate::filters::Icons,
+        message: "Database imported successfully. Please refresh the page.".into(),
+    };
+
+    template.render_html()
+}
+
+/// Restore the live database from an uploaded .db file using SQLite's backup API.
+///
+/// Migrations are first run on the uploaded file (a temporary copy) to
+/// bring backups from older versions up to date. Only if that succeeds is
+/// it copied page by page into the live database, so a backup that fails
+/// its migrations leaves the live data untouched. All existing pool
+/// connections see the new data immediately.
+fn restore_from_db_file(
+    conn: &mut rusqlite::Connection,
+    src_path: &Path,
+    config: &Config,
+) -> AppResult<()> {
+    let src = rusqlite::Connection::open(src_path)?;
+    crate::db::migrations::run_migrations(
+        &src,
+        &config.migrations_path,
+        config.allow_dirty_migrations,
+    )
+    .map_err(|e| AppError::Validation(format!("The backup cannot be migrated: {}", e)))?;
+
+    let backup = rusqlite::backup::Backup::new(&src, conn)?;
+    backup
+        .run_to_completion(100, std::time::Duration::ZERO, None)
+        .map_err(|e| AppError::Internal(format!("Backup failed: {}", e)))?;
+    drop(backup);
+    drop(src);
+
+    // Restore WAL mode and FK checks (backup copies source pragmas)
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;\
+         PRAGMA foreign_keys = ON;",
+    )?;
+
+    Ok(())
+}
+
+/// What `/settings/clear-database` deletes. Everything but `Everything`
+/// keeps categories, tags, rules, accounts and settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearScope {
+    Transactions,
+    Trading,
+    MarketData,
+    Everything,
+}
+
+impl ClearScope {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "transactions" => Some(Self::Transactions),
+            "trading" => Some(Self::Trading),
+            "market_data" => Some(Self::MarketData),
+            "everything" => Some(Self::Everything),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Transactions => "transactions",
+            Self::Trading => "trading",
+            Self::MarketData => "market_data",
+            Self::Everything => "everything",
+        }
+    }
+
+    fn domains(self) -> &'static [DataDomain] {
+        match self {
+            Self::Transactions => &[DataDomain::Transactions],
+            Self::Trading => &[DataDomain::Trading],
+            Self::MarketData => &[DataDomain::MarketData],
+            Self::Everything => &DataDomain::ALL,
+        }
+    }
+}
+
+/// Read the repeated `scope` fields and the confirmation parameters.
+fn parse_clear_params(
+    fields: Vec<(String, String)>,
+) -> AppResult<(Vec<ClearScope>, ConfirmParams)> {
+    let mut scopes = Vec::new();
+    let mut params = ConfirmParams::default();
+    for (key, value) in fields {
+        match key.as_str() {
+            "scope" => {
+                let scope = ClearScope::parse(&value).ok_or_else(|| {
+                    AppError::Validation(format!("Unknown scope to clear: {}", value))
+                })?;
+                if !scopes.contains(&scope) {
+                    scopes.push(scope);
+                }
+            }
+            "confirm" => params.confirm = Some(value),
+            "force" => params.force = Some(value),
+            _ => {}
+        }
+    }
+    if scopes.is_empty() {
+        return Err(AppError::Validation(
+            "Select at least one kind of data to clear".into(),
+        ));
+    }
+    Ok((scopes, params))
+}
+
+/// Tables holding user data: everything except SQLite's own tables and the
+/// migration history.
+fn data_tables(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master
+         WHERE type='table'
+         AND name NOT LIKE 'sqlite_%'
+         AND name != '_migrations'
+         ORDER BY name",
+    )?;
+    let rows = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Confirmation message for clearing `scopes`.
+fn describe_clear(conn: &rusqlite::Connection, scopes: &[ClearScope]) -> AppResult<String> {
+    if scopes.contains(&ClearScope::Everything) {
+        let mut rows = 0;
+        for table in data_tables(conn)? {
+            rows += conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table), [], |row| {
+                row.get::<_, i64>(0)
+            })?;
+        }
+        return Ok(format!(
+            "This permanently deletes all data, {} record{} in total.",
+            rows,
+            if rows == 1 { "" } else { "s" }
+        ));
+    }
+    let mut counts = Vec::new();
+    for scope in scopes {
+        counts.push(match scope {
+            ClearScope::Transactions => (
+                transactions::count_transactions(conn, &Default::default())? as usize,
+                "transaction",
+                "transactions",
+            ),
+            ClearScope::Trading => (
+                trading::count_all_activities(conn)? as usize,
+                "trading activity",
+                "trading activities",
+            ),
+            ClearScope::MarketData => (
+                market_data::count_market_data(conn)? as usize,
+                "market data point",
+                "market data points",
+            ),
+            ClearScope::Everything => continue,
+        });
+    }
+    Ok(deletion_message(&counts))
+}
+
+pub async fn clear_database(
+    State(state): State<AppState>,
+    Query(fields): Query<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    let (scopes, params) = parse_clear_params(fields)?;
+    // Bind the token to the selected scopes so it can't clear more than the
+    // user confirmed.
+    let mut names: Vec<&str> = scopes.iter().map(|scope| scope.as_str()).collect();
+    names.sort_unstable();
+    let confirm_scope = format!("database:{}", names.join(","));
+    let pending = confirmation_pending(&state, &confirm_scope, &params, || {
+        let conn = state.db.get()?;
+        describe_clear(&conn, &scopes)
+    })?;
+    if let Some(pending) = pending {
+        return Ok(pending);
+    }
+    let counts = state.with_tx(|tx| {
+        let mut counts = DeletedCounts::default();
+        if scopes.contains(&ClearScope::Everything) {
+            warn!("Clearing entire database");
+            let tables = data_tables(tx)?;
+            for table in &tables {
+                let count = tx.execute(&format!("DELETE FROM \"{}\"", table), [])?;
+                counts.deleted.insert(table.clone(), count);
+            }
+            warn!(tables_cleared = tables.len(), "Database cleared");
+        } else {
+            for &scope in &scopes {
+                let count = match scope {
+                    ClearScope::Transactions => transactions::delete_all_transactions(tx)?,
+                    ClearScope::Trading => trading::delete_all_activities(tx)?,
+                    ClearScope::MarketData => market_data::delete_all_market_data(tx)?,
+                    ClearScope::Everything => continue,
+                };
+                counts.deleted.insert(scope.as_str().to_string(), count);
+            }
+        }
+        Ok(counts)
+    })?;
+    for scope in &scopes {
+        state.cache.invalidate_domains(scope.domains());
+    }
+
+    let summary = counts
+        .deleted
+        .iter()
+        .filter(|(_, &count)| count > 0)
+        .map(|(kind, count)| format!("{} {}", count, kind.replace('_', " ")))
+        .collect::<Vec<_>>();
+    if scopes.contains(&ClearScope::Everything) {
+        flash::flash_success("Database cleared");
+    } else if summary.is_empty() {
+        flash::flash_success("Nothing to clear");
+    } else {
+        flash::flash_success(format!("Cleared {}", summary.join(", ")));
+    }
+
+    Ok(counts.into_response())
+}
@@ -0,0 +1,617 @@
+//! Share links: read-only snapshots of a single report, reachable without
+//! logging in.
+//!
+//! The snapshot pages are rendered on the server with the data at access
+//! time and do not call the (authenticated) JSON APIs. The auth middleware
+//! only lets `/share/:token` through, so a share visitor can never reach
+//! anything else, whatever the page links to.
+
+use askama::Template;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{Request, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::Form;
+use chrono::{Datelike, Months, NaiveDate, Utc};
+use serde::Deserialize;
+use tower_cookies::{Cookie, Cookies};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::auth;
+use crate::date_utils;
+use crate::db::queries::categories::transfers_excluded_ids;
+use crate::db::queries::{positions, share_links, transaction_sums};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash::{self, FlashLevel};
+use crate::handlers::trading_positions::enrich_position;
+use crate::models::{NewShareLink, Settings, ShareLink, ShareScope};
+use crate::state::{AppState, JsManifest, PageBase};
+use crate::VERSION;
+
+/// Cookie holding the share session of an unlocked, password-protected link.
+/// It is scoped to the link's path and never counts as a login session.
+const SHARE_COOKIE: &str = "share_session";
+
+/// Number of months covered by the spending and net worth snapshots.
+const SNAPSHOT_MONTHS: u32 = 12;
+
+/// Longest expiry that can be chosen for a link.
+const MAX_EXPIRY_DAYS: u32 = 365;
+
+const GAIN_COLOR: &str = "text-green-600 dark:text-green-400";
+const LOSS_COLOR: &str = "text-red-600 dark:text-red-400";
+const NEUTRAL_COLOR: &str = "text-neutral-600 dark:text-neutral-400";
+
+fn amount_color(cents: i64) -> &'static str {
+    match cents {
+        c if c > 0 => GAIN_COLOR,
+        c if c < 0 => LOSS_COLOR,
+        _ => NEUTRAL_COLOR,
+    }
+}
+
+/// A share link as listed on the settings page.
+pub struct ShareLinkRow {
+    pub link: ShareLink,
+    pub expired: bool,
+    pub revoke_url: String,
+}
+
+#[derive(Template)]
+#[template(path = "pages/share_links.html")]
+pub struct ShareLinksTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub links: Vec<ShareLinkRow>,
+    pub scopes: &'static [ShareScope],
+    pub max_expiry_days: u32,
+}
+
+pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    // Expiry timestamps are stored in UTC, in SQLite's datetime() format
+    let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let links = share_links::list_share_links(&conn)?
+        .into_iter()
+        .map(|link| ShareLinkRow {
+            expired: link.expires_at.as_ref().is_some_and(|at| *at <= now),
+            revoke_url: format!("/settings/share-links/{}", link.id),
+            link,
+        })
+        .collect();
+
+    let template = ShareLinksTemplate {
+        title: "Share Links".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        links,
+        scopes: ShareScope::all(),
+        max_expiry_days: MAX_EXPIRY_DAYS,
+    };
+
+    template.render_html()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareLinkFormData {
+    pub scope: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub password: String,
+    /// Days until expiry; empty or 0 for a link that never expires.
+    #[serde(default)]
+    pub expires_in_days: String,
+}
+
+impl ShareLinkFormData {
+    fn validate(&self) -> AppResult<(ShareScope, Option<u32>)> {
+        let scope = ShareScope::parse(&self.scope)
+            .ok_or_else(|| AppError::Validation(format!("Unknown report: {}", self.scope)))?;
+        if self.label.trim().chars().count() > 100 {
+            return Err(AppError::Validation(
+                "Label must be at most 100 characters".into(),
+            ));
+        }
+        if !self.password.is_empty() && self.password.chars().count() < 4 {
+            return Err(AppError::Validation(
+                "Password must be at least 4 characters".into(),
+            ));
+        }
+        let expires_in_days = match self.expires_in_days.trim() {
+            "" | "0" => None,
+            value => match value.parse::<u32>() {
+                Ok(days) if days <= MAX_EXPIRY_DAYS => Some(days),
+                _ => {
+                    return Err(AppError::Validation(format!(
+                        "Expiry must be between 0 and {} days",
+                        MAX_EXPIRY_DAYS
+                    )))
+                }
+            },
+        };
+        Ok((scope, expires_in_days))
+    }
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Form(form): Form<ShareLinkFormData>,
+) -> AppResult<Redirect> {
+    let (scope, expires_in_days) = form.validate()?;
+    let password_hash = if form.password.is_empty() {
+        None
+    } else {
+        Some(auth::hash_password(&form.password).map_err(AppError::Internal)?)
+    };
+
+    let conn = state.db.get()?;
+    let link = share_links::create_share_link(
+        &conn,
+        &NewShareLink {
+            scope,
+            label: form.label.trim().to_string(),
+            password_hash,
+            expires_in_days,
+        },
+    )?;
+    info!(link_id = link.id, scope = %link.scope, "Share link created");
+
+    flash::flash_success("Share link created");
+    Ok(Redirect::to("/settings/share-links"))
+}
+
+pub async fn revoke(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let conn = state.db.get()?;
+    let token = share_links::get_share_link(&conn, id)?
+        .map(|link| link.token)
+        .ok_or_else(|| AppError::NotFound(format!("Share link {} not found", id)))?;
+    share_links::delete_share_link(&conn, id)?;
+
+    // Drop unlocked sessions of the link as well
+    state
+        .share_sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|_, link_token| *link_token != token);
+    info!(link_id = id, "Share link revoked");
+
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, "Share link revoked"),
+        Html(String::new()),
+    ))
+}
+
+fn not_found() -> AppError {
+    AppError::NotFound("This share link does not exist or has expired.".into())
+}
+
+fn load_link(state: &AppState, token: &str) -> AppResult<ShareLink> {
+    let conn = state.db.get()?;
+    share_links::get_active_share_link(&conn, token)?.ok_or_else(not_found)
+}
+
+/// Whether the request carries a share session issued for this link.
+fn is_unlocked(state: &AppState, cookies: &Cookies, link: &ShareLink) -> bool {
+    let Some(cookie) = cookies.get(SHARE_COOKIE) else {
+        return false;
+    };
+    state
+        .share_sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(cookie.value())
+        .is_some_and(|token| *token == link.token)
+}
+
+#[derive(Template)]
+#[template(path = "pages/share_unlock.html")]
+pub struct ShareUnlockTemplate {
+    pub title: String,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub csp_nonce: String,
+    pub action: String,
+    pub error: Option<String>,
+}
+
+fn render_unlock(state: &AppState, link: &ShareLink, error: Option<&str>) -> Response {
+    let template = ShareUnlockTemplate {
+        title: "Shared Report".into(),
+        manifest: state.manifest.clone(),
+        version: VERSION,
+        csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
+        action: link.path(),
+        error: error.map(String::from),
+    };
+    match template.render_html() {
+        Ok(html) if error.is_some() => (StatusCode::UNAUTHORIZED, html).into_response(),
+        Ok(html) => html.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Show the snapshot, or the password prompt for a locked link.
+pub async fn view(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    cookies: Cookies,
+) -> AppResult<Response> {
+    let link = load_link(&state, &token)?;
+    if link.has_password() && !is_unlocked(&state, &cookies, &link) {
+        return Ok(render_unlock(&state, &link, None));
+    }
+
+    let html = match link.scope {
+        ShareScope::SpendingReport => spending_snapshot(&state, &link)?,
+        ShareScope::NetWorth => net_worth_snapshot(&state, &link)?,
+        ShareScope::Positions => positions_snapshot(&state, &link)?,
+    };
+    Ok(html.into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockFormData {
+    pub password: String,
+}
+
+/// Check the link password and issue a share session scoped to the link.
+/// Failed attempts count towards the same per-IP limit as logins.
+pub async fn unlock(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    cookies: Cookies,
+    request: Request<Body>,
+) -> AppResult<Response> {
+    let link = load_link(&state, &token)?;
+    let Some(password_hash) = link.password_hash.as_deref() else {
+        return Ok(Redirect::to(&link.path()).into_response());
+    };
+
+    let trust_proxy = state
+        .load_settings()
+        .map(|s| s.trust_proxy_headers)
+        .unwrap_or(true);
+    let ip = auth::client_ip(&request, trust_proxy);
+    if state.login_rate_limiter.is_locked_out(&ip) {
+        warn!(ip = %ip, "Share link unlock rate-limited");
+        return Ok(render_unlock(
+            &state,
+            &link,
+            Some("Too many failed attempts. Please try again later."),
+        ));
+    }
+
+    let body_bytes = axum::body::to_bytes(request.into_body(), 1024 * 16)
+        .await
+        .map_err(|_| AppError::Validation("Invalid request".into()))?;
+    let form: UnlockFormData = serde_urlencoded::from_bytes(&body_bytes)
+        .map_err(|_| AppError::Validation("Invalid form data".into()))?;
+
+    if !auth::verify_password(&form.password, password_hash) {
+        state.login_rate_limiter.record_failure(&ip);
+        return Ok(render_unlock(&state, &link, Some("Invalid password")));
+    }
+    state.login_rate_limiter.reset(&ip);
+
+    let session_token = Uuid::new_v4().to_string();
+    state
+        .share_sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(session_token.clone(), link.token.clone());
+
+    let cookie = Cookie::build((SHARE_COOKIE, session_token))
+        .path(link.path())
+        .http_only(true)
+        .secure(state.config.secure_cookies)
+        .same_site(tower_cookies::cookie::SameSite::Strict)
+        .build();
+    cookies.add(cookie);
+
+    Ok(Redirect::to(&link.path()).into_response())
+}
+
+/// First day of the month `SNAPSHOT_MONTHS - 1` months before `today`.
+fn snapshot_start(today: NaiveDate) -> NaiveDate {
+    let month_start = today.with_day(1).unwrap_or(today);
+    month_start - Months::new(SNAPSHOT_MONTHS - 1)
+}
+
+pub struct ShareCategoryRow {
+    pub name: String,
+    pub color: String,
+    pub amount_formatted: String,
+    pub share_formatted: String,
+}
+
+pub struct ShareMonthRow {
+    pub month: String,
+    pub income_formatted: String,
+    pub expenses_formatted: String,
+    pub net_formatted: String,
+    pub net_color: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "pages/share_spending.html")]
+pub struct ShareSpendingTemplate {
+    pub title: String,
+    pub csp_nonce: String,
+    pub settings: Settings,
+    pub manifest: JsManifest,
+    pub label: String,
+    pub as_of: String,
+    pub from_date: String,
+    pub to_date: String,
+    pub income_formatted: String,
+    pub expenses_formatted: String,
+    pub net_formatted: String,
+    pub net_color: &'static str,
+    pub categories: Vec<ShareCategoryRow>,
+    pub months: Vec<ShareMonthRow>,
+}
+
+fn spending_snapshot(state: &AppState, link: &ShareLink) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let settings = state.load_settings()?;
+    let today = date_utils::today_in(&settings);
+    let from_date = snapshot_start(today).to_string();
+    let to_date = today.to_string();
+    let (from, to) = (Some(from_date.as_str()), Some(to_date.as_str()));
+
+    let excluded = transfers_excluded_ids(&state.cached_categories()?);
+    let income = transaction_sums::sum_by_month(&conn, from, to, true, &excluded)?;
+    let expenses = transaction_sums::sum_by_month(&conn, from, to, false, &excluded)?;
+
+    let mut month_totals: std::collections::BTreeMap<String, (i64, i64)> =
+        std::collections::BTreeMap::new();
+    for s in income {
+        month_totals.entry(s.month).or_default().0 = s.total_cents;
+    }
+    for s in expenses {
+        month_totals.entry(s.month).or_default().1 = s.total_cents;
+    }
+    let total_income: i64 = month_totals.values().map(|(i, _)| i).sum();
+    let total_expenses: i64 = month_totals.values().map(|(_, e)| e).sum();
+    let months = month_totals
+        .into_iter()
+        .rev()
+        .map(|(month, (income, expenses))| ShareMonthRow {
+            month,
+            income_formatted: settings.format_money_neutral(&income),
+            expenses_formatted: settings.format_money_neutral(&expenses),
+            net_formatted: settings.format_money_plain(&(income - expenses)),
+            net_color: amount_color(income - expenses),
+        })
+        .collect();
+
+    // Spending is the negated net amount per category, so refunds reduce it
+    let excluded_ids: Vec<i64> = excluded.into_iter().collect();
+    let mut spending: Vec<(String, String, i64)> =
+        transaction_sums::sum_by_category(&conn, from, to, &excluded_ids)?
+            .into_iter()
+            .filter(|s| s.total_cents < 0)
+            .map(|s| (s.category_name, s.category_color, -s.total_cents))
+            .collect();
+    spending.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    let spending_total: i64 = spending.iter().map(|(_, _, cents)| cents).sum();
+    let categories = spending
+        .into_iter()
+        .map(|(name, color, cents)| ShareCategoryRow {
+            name,
+            color,
+            amount_formatted: settings.format_money_neutral(&cents),
+            share_formatted: settings
+                .format_percent_share(cents as f64 / spending_total as f64 * 100.0),
+        })
+        .collect();
+
+    let net = total_income - total_expenses;
+    let template = ShareSpendingTemplate {
+        title: "Spending Report".into(),
+        csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
+        manifest: state.manifest.clone(),
+        label: link.label.clone(),
+        as_of: to_date.clone(),
+        from_date,
+        to_date,
+        income_formatted: settings.format_money_neutral(&total_income),
+        expenses_formatted: settings.format_money_neutral(&total_expenses),
+        net_formatted: settings.format_money_plain(&net),
+        net_color: amount_color(net),
+        categories,
+        months,
+        settings,
+    };
+    template.render_html()
+}
+
+pub struct ShareNetWorthRow {
+    pub month: String,
+    pub net_worth_formatted: String,
+    pub cash_formatted: String,
+    pub securities_formatted: String,
+}
+
+#[derive(Template)]
+#[template(path = "pages/share_net_worth.html")]
+pub struct ShareNetWorthTemplate {
+    pub title: String,
+    pub csp_nonce: String,
+    pub settings: Settings,
+    pub manifest: JsManifest,
+    pub label: String,
+    pub as_of: String,
+    pub has_data: bool,
+    pub current_formatted: String,
+    /// Month whose closing value the change is measured against.
+    pub since_month: String,
+    pub change_formatted: String,
+    pub change_color: &'static str,
+    pub months: Vec<ShareNetWorthRow>,
+}
+
+fn net_worth_snapshot(state: &AppState, link: &ShareLink) -> AppResult<Html<String>> {
+    let settings = state.load_settings()?;
+    let today = date_utils::today_in(&settings);
+    let summary = state.cached_net_worth()?;
+
+    // Last data point of each month, most recent months only
+    let mut month_ends: Vec<&crate::models::NetWorthDataPoint> = Vec::new();
+    for point in &summary.data_points {
+        match month_ends.last_mut() {
+            Some(last) if last.date.get(..7) == point.date.get(..7) => *last = point,
+            _ => month_ends.push(point),
+        }
+    }
+    let skip = month_ends.len().saturating_sub(SNAPSHOT_MONTHS as usize);
+    let month_ends = &month_ends[skip..];
+
+    let change = month_ends
+        .first()
+        .map(|first| summary.current_net_worth_cents - first.net_worth_cents)
+        .unwrap_or(0);
+    let months = month_ends
+        .iter()
+        .rev()
+        .map(|p| ShareNetWorthRow {
+            month: p.date[..7].to_string(),
+            net_worth_formatted: settings.format_money_neutral(&p.net_worth_cents),
+            cash_formatted: settings.format_money_neutral(&p.transaction_component_cents),
+            securities_formatted: settings.format_money_neutral(&p.portfolio_component_cents),
+        })
+        .collect();
+
+    let template = ShareNetWorthTemplate {
+        title: "Net Worth".into(),
+        csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
+        manifest: state.manifest.clone(),
+        label: link.label.clone(),
+        as_of: today.to_string(),
+        has_data: !summary.data_points.is_empty(),
+        current_formatted: settings.format_money_neutral(&summary.current_net_worth_cents),
+        since_month: month_ends
+            .first()
+            .map(|p| p.date[..7].to_string())
+            .unwrap_or_default(),
+        change_formatted: settings.format_money_plain(&change),
+        change_color: amount_color(change),
+        months,
+        settings,
+    };
+    template.render_html()
+}
+
+pub struct SharePositionRow {
+    pub symbol: String,
+    pub quantity: String,
+    pub price_formatted: Option<String>,
+    pub value_formatted: Option<String>,
+    pub cost_formatted: String,
+    pub gain_loss_formatted: Option<String>,
+    pub gain_loss_color: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "pages/share_positions.html")]
+pub struct SharePositionsTemplate {
+    pub title: String,
+    pub csp_nonce: String,
+    pub settings: Settings,
+    pub manifest: JsManifest,
+    pub label: String,
+    pub as_of: String,
+    pub positions: Vec<SharePositionRow>,
+    pub total_value_formatted: String,
+    pub total_cost_formatted: String,
+    pub total_gain_loss_formatted: String,
+    pub total_gain_loss_color: &'static str,
+}
+
+fn positions_snapshot(state: &AppState, link: &ShareLink) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let settings = state.load_settings()?;
+    let today = date_utils::today_in(&settings);
+
+    // Long positions only, as in the totals of the positions page
+    let (positions, _) = positions::get_positions_with_warnings(&conn, false)?;
+    let mut enriched: Vec<_> = positions
+        .into_iter()
+        .map(|pos| enrich_position(&conn, pos, &settings))
+        .filter(|p| !p.position.is_short())
+        .collect();
+    enriched.sort_by(|a, b| {
+        b.current_value_cents
+            .cmp(&a.current_value_cents)
+            .then_with(|| a.position.symbol.cmp(&b.position.symbol))
+    });
+
+    let total_cost: i64 = enriched.iter().map(|p| p.position.total_cost_cents).sum();
+    // Positions without any known price count at cost
+    let total_value: i64 = enriched
+        .iter()
+        .map(|p| p.current_value_cents.unwrap_or(p.position.total_cost_cents))
+        .sum();
+    let total_gain_loss = total_value - total_cost;
+
+    let rows = enriched
+        .into_iter()
+        .map(|p| {
+            let currency = &p.position.currency;
+            SharePositionRow {
+                quantity: p.position.quantity_display(),
+                price_formatted: p
+                    .current_price_cents
+                    .map(|c| settings.format_money_neutral_with_currency(&c, currency)),
+                value_formatted: p
+                    .current_value_cents
+                    .map(|c| settings.format_money_neutral_with_currency(&c, currency)),
+                cost_formatted: settings
+                    .format_money_neutral_with_currency(&p.position.total_cost_cents, currency),
+                gain_loss_formatted: p
+                    .gain_loss_cents
+                    .map(|c| settings.format_money_plain_with_currency(&c, currency)),
+                gain_loss_color: amount_color(p.gain_loss_cents.unwrap_or(0)),
+                symbol: p.position.symbol,
+            }
+        })
+        .collect();
+
+    let template = SharePositionsTemplate {
+        title: "Positions".into(),
+        csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
+        manifest: state.manifest.clone(),
+        label: link.label.clone(),
+        as_of: today.to_string(),
+        positions: rows,
+        total_value_formatted: settings.format_money_neutral(&total_value),
+        total_cost_formatted: settings.format_money_neutral(&total_cost),
+        total_gain_loss_formatted: settings.format_money_plain(&total_gain_loss),
+        total_gain_loss_color: amount_color(total_gain_loss),
+        settings,
+    };
+    template.render_html()
+}
@@ -4,12 +4,12 @@ use axum::response::Html;
 use chrono::{Datelike, NaiveDate};
 use serde::Deserialize;
 
-use crate::date_utils::{DatePreset, DateRange};
-use crate::db::queries::transactions;
+use crate::date_utils::{self, DatePreset, DateRange};
+use crate::db::queries::{saved_charts, transactions};
 use crate::error::{AppResult, RenderHtml};
 use crate::handlers::transactions::TransactionPreviewTemplate;
 use crate::models::category::CategoryWithPath;
-use crate::models::Settings;
+use crate::models::{SavedChart, SavedChartType, Settings, TransactionStatus};
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Debug, Default, Deserialize)]
@@ -20,22 +20,26 @@ pub struct SpendingFilterParams {
     pub tab: Option<String>,
     pub category_mode: Option<String>,
     pub monthly_mode: Option<String>,
+    /// Extra layers of the flow chart: "accounts" or none.
+    pub flow_layers: Option<String>,
     pub categories: Option<String>,
 }
 
 impl SpendingFilterParams {
-    pub fn resolve_date_range(&self) -> DateRange {
+    pub fn resolve_date_range(&self, today: NaiveDate) -> DateRange {
         if let Some(preset_str) = &self.preset {
             preset_str
                 .parse::<DatePreset>()
-                .map(DateRange::from_preset)
+                .ok()
+                .filter(|preset| DatePreset::all().contains(preset))
+                .map(|preset| DateRange::from_preset(preset, today))
                 .unwrap_or_default()
         } else if let (Some(from), Some(to)) = (&self.from_date, &self.to_date) {
             if let (Ok(from_date), Ok(to_date)) = (
                 NaiveDate::parse_from_str(from, "%Y-%m-%d"),
                 NaiveDate::parse_from_str(to, "%Y-%m-%d"),
             ) {
-                DateRange::from_dates(from_date, to_date)
+                DateRange::from_dates(from_date, to_date, today)
             } else {
                 DateRange::default()
             }
@@ -54,6 +58,7 @@ pub struct SpendingTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub date_range: DateRange,
     pub presets: &'static [DatePreset],
     pub active_tab: String,
@@ -61,6 +66,19 @@ pub struct SpendingTemplate {
     pub category_mode: String,
     pub monthly_mode: String,
     pub categories: Vec<CategoryWithPath>,
+    /// Categories selected for the monthly chart, preselected when saving one.
+    pub selected_category_ids: Vec<i64>,
+    pub saved_charts: Vec<SavedChartView>,
+    pub chart_types: &'static [SavedChartType],
+}
+
+/// A saved chart with the URL of its data for the selected date range.
+pub struct SavedChartView {
+    pub chart: SavedChart,
+    /// `None` when all of the chart's categories were deleted.
+    pub data_url: Option<String>,
+    /// Names of the chart's categories that still exist.
+    pub category_names: Vec<String>,
 }
 
 pub async fn index(
@@ -75,10 +93,11 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let date_range = params
-        .resolve_date_range()
+        .resolve_date_range(date_utils::today_in(&settings))
         .resolve_all(transactions::date_extent(&conn)?);
 
     let active_tab = match params.tab.as_deref() {
@@ -92,10 +111,12 @@ pub async fn index(
 
     let category_mode = match params.category_mode.as_deref() {
         Some("income") => "income",
+        Some("net") => "net",
         _ => "expenses",
     };
     let monthly_mode = match params.monthly_mode.as_deref() {
         Some("income") => "income",
+        Some("net") => "net",
         _ => "expenses",
     };
 
@@ -106,12 +127,38 @@ pub async fn index(
     if monthly_mode != "expenses" {
         base_qs.push_str(&format!("&monthly_mode={}", monthly_mode));
     }
+    if params.flow_layers.as_deref() == Some("accounts") {
+        base_qs.push_str("&flow_layers=accounts");
+    }
     if let Some(ref sel) = params.categories {
         if !sel.is_empty() {
             base_qs.push_str(&format!("&categories={}", sel));
         }
     }
 
+    let existing_ids: Vec<i64> = cats.iter().map(|c| c.category.id).collect();
+    let saved_charts = saved_charts::list_saved_charts(&conn)?
+        .into_iter()
+        .map(|chart| SavedChartView {
+            data_url: chart.api_url(&date_range.from_str(), &date_range.to_str(), &existing_ids),
+            category_names: chart
+                .params
+                .category_ids
+                .iter()
+                .filter_map(|id| cats.iter().find(|c| c.category.id == *id))
+                .map(|c| c.category.name.clone())
+                .collect(),
+            chart,
+        })
+        .collect();
+    let selected_category_ids = params
+        .categories
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|s| s.trim().parse().ok())
+        .collect();
+
     let template = SpendingTemplate {
         title: "Spending".into(),
         settings,
@@ -119,6 +166,7 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         date_range,
         presets: DatePreset::all(),
         active_tab,
@@ -126,6 +174,9 @@ pub async fn index(
         category_mode: category_mode.to_string(),
         monthly_mode: monthly_mode.to_string(),
         categories: cats,
+        selected_category_ids,
+        saved_charts,
+        chart_types: SavedChartType::all(),
     };
 
     template.render_html()
@@ -163,6 +214,9 @@ pub async fn monthly_transactions(
         category_ids,
         sort_sql: Some("ABS(e.amount_cents) DESC".to_string()),
         limit: Some(20),
+        status: Some(TransactionStatus::Posted),
+        skip_duplicate_mirrors: true,
+        with_tags: false,
         ..Default::default()
     };
 
@@ -207,6 +261,9 @@ pub async fn category_transactions(
         to_date: params.to_date.clone(),
         sort_sql: Some("ABS(e.amount_cents) DESC".to_string()),
         limit: Some(20),
+        status: Some(TransactionStatus::Posted),
+        skip_duplicate_mirrors: true,
+        with_tags: false,
         ..Default::default()
     };
 
@@ -241,9 +298,14 @@ pub async fn category_transactions(
     let total_count = transactions::count_transactions(&conn, &filter)?;
     let transaction_list = transactions::list_transactions(&conn, &filter)?;
 
-    let mut view_all_url = "/transactions?".to_string();
-    if let Some(cat_id) = params.category_id {
-        view_all_url.push_str(&format!("category_id={}", cat_id));
+    let mut view_all_url = "/transactions?status=posted".to_string();
+    if params.uncategorized == Some(true) {
+        view_all_url.push_str("&category_id=0");
+    } else if let Some(cat_id) = params.category_id {
+        view_all_url.push_str(&format!("&category_id={}", cat_id));
+        if params.include_children == Some(true) {
+            view_all_url.push_str("&include_children=true");
+        }
     }
     if let Some(ref from) = params.from_date {
         view_all_url.push_str(&format!("&from_date={}", from));
@@ -6,7 +6,8 @@ use serde::Deserialize;
 
 use crate::db::queries::tags;
 use crate::error::{AppError, AppResult, RenderHtml};
-use crate::models::{NewTag, Settings, Tag, TagStyle, DEFAULT_COLOR, TAG_PALETTE};
+use crate::models::{NewTag, Settings, Tag, TagStyle, TagWithUsage, DEFAULT_COLOR};
+use crate::palette;
 use crate::state::{AppState, JsManifest, PageBase};
 
 #[derive(Template)]
@@ -18,6 +19,7 @@ pub struct TagFormTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub editing: Option<Tag>,
     pub palette: &'static [(&'static str, &'static str)],
 }
@@ -48,6 +50,7 @@ pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>>
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let template = TagFormTemplate {
@@ -57,8 +60,9 @@ pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>>
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         editing: None,
-        palette: TAG_PALETTE,
+        palette: palette::PALETTE,
     };
 
     template.render_html()
@@ -75,6 +79,7 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let tag =
@@ -87,8 +92,9 @@ pub async fn edit_form(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         editing: Some(tag),
-        palette: TAG_PALETTE,
+        palette: palette::PALETTE,
     };
 
     template.render_html()
@@ -115,15 +121,11 @@ pub async fn update(
 pub async fn search(
     State(state): State<AppState>,
     Query(params): Query<TagSearchParams>,
-) -> AppResult<Json<Vec<Tag>>> {
+) -> AppResult<Json<Vec<TagWithUsage>>> {
     let conn = state.db.get()?;
 
     let query = params.q.unwrap_or_default();
-    let tag_list = if query.is_empty() {
-        state.cached_tags()?
-    } else {
-        tags::search_tags(&conn, &query)?
-    };
+    let tag_list = tags::search_tags(&conn, &query)?;
 
     Ok(Json(tag_list))
 }
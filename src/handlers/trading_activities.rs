@@ -1,16 +1,19 @@
 use askama::Template;
 use axum::extract::{Path, Query, State};
-use axum::http::header;
-use axum::response::{Html, IntoResponse, Redirect};
-use axum::{Form, Json};
-use serde::{Deserialize, Serialize};
+use axum::response::{Html, IntoResponse, Response};
+use serde::Deserialize;
 
-use crate::date_utils::{DateFilterable, DatePreset, DateRange};
-use crate::db::queries::trading;
+use crate::confirmation::{confirmation_pending, deletion_message, ConfirmParams, DeletedCounts};
+use crate::date_utils::{self, DateFilterable, DatePreset, DateRange};
+use crate::db::queries::{market_data, split_adjustments, trading};
 use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash::{self, FlashLevel};
 use crate::models::{NewTradingActivity, Settings, TradingActivity, TradingActivityType};
+use crate::services::splits::{self, SplitRatio};
+use crate::services::trading_mirror;
 use crate::sort_utils::{Sortable, SortableColumn, TableSort};
 use crate::state::{AppState, JsManifest, PageBase};
+use crate::symbol_locks::{SymbolGuard, EDIT_LOCK_TIMEOUT};
 
 /// Sortable columns for the trading activities table.
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -64,6 +67,50 @@ impl SortableColumn for ActivitySortColumn {
     }
 }
 
+/// Sortable columns for the grouped (per-symbol) activities view.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum ActivityGroupSortColumn {
+    #[default]
+    LastDate,
+    Symbol,
+    Count,
+    Quantity,
+    Invested,
+}
+
+impl SortableColumn for ActivityGroupSortColumn {
+    fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "last_date" => Some(Self::LastDate),
+            "symbol" => Some(Self::Symbol),
+            "count" => Some(Self::Count),
+            "quantity" => Some(Self::Quantity),
+            "invested" => Some(Self::Invested),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::LastDate => "last_date",
+            Self::Symbol => "symbol",
+            Self::Count => "count",
+            Self::Quantity => "quantity",
+            Self::Invested => "invested",
+        }
+    }
+
+    fn sql_expression(&self) -> &'static str {
+        match self {
+            Self::LastDate => "last_date",
+            Self::Symbol => "symbol",
+            Self::Count => "activity_count",
+            Self::Quantity => "net_quantity",
+            Self::Invested => "total_invested_cents",
+        }
+    }
+}
+
 #[derive(Template)]
 #[template(path = "pages/trading_activities.html")]
 pub struct TradingActivitiesTemplate {
@@ -73,6 +120,7 @@ pub struct TradingActivitiesTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub activities: Vec<TradingActivity>,
     pub symbols: Vec<String>,
     pub activity_types: &'static [TradingActivityType],
@@ -84,6 +132,8 @@ pub struct TradingActivitiesTemplate {
     pub date_range: DateRange,
     pub presets: &'static [DatePreset],
     pub sort: TableSort<ActivitySortColumn>,
+    pub groups: Vec<trading::ActivityGroup>,
+    pub group_sort: TableSort<ActivityGroupSortColumn>,
 }
 
 #[derive(Template)]
@@ -101,26 +151,27 @@ pub struct TradingActivityTableTemplate {
 }
 
 #[derive(Template)]
-#[template(path = "components/trading_activity_form.html")]
-pub struct TradingActivityFormTemplate {
+#[template(path = "partials/trading_activity_groups.html")]
+pub struct TradingActivityGroupsTemplate {
+    pub settings: Settings,
     pub icons: crate::filters::Icons,
-    pub activity: Option<TradingActivity>,
-    pub symbols: Vec<String>,
-    pub activity_types: &'static [TradingActivityType],
-    pub is_edit: bool,
+    pub groups: Vec<trading::ActivityGroup>,
+    pub total_count: i64,
+    pub page: i64,
+    pub page_size: i64,
+    pub filter: TradingActivityFilterParams,
+    pub date_range: DateRange,
+    pub group_sort: TableSort<ActivityGroupSortColumn>,
 }
 
 #[derive(Template)]
-#[template(path = "pages/trading_activity_new.html")]
-pub struct TradingActivityNewTemplate {
-    pub title: String,
-    pub settings: Settings,
+#[template(path = "components/trading_activity_form.html")]
+pub struct TradingActivityFormTemplate {
     pub icons: crate::filters::Icons,
-    pub manifest: JsManifest,
-    pub version: &'static str,
-    pub xsrf_token: String,
+    pub activity: Option<TradingActivity>,
     pub symbols: Vec<String>,
     pub activity_types: &'static [TradingActivityType],
+    pub is_edit: bool,
 }
 
 #[derive(Template)]
@@ -140,21 +191,30 @@ pub struct TradingActivityDetailTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub activity: TradingActivity,
 }
 
 #[derive(Template)]
-#[template(path = "pages/trading_activity_edit.html")]
-pub struct TradingActivityEditTemplate {
+#[template(path = "pages/trading_activity_trash.html")]
+pub struct TradingActivityTrashTemplate {
     pub title: String,
     pub settings: Settings,
     pub icons: crate::filters::Icons,
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
-    pub activity: TradingActivity,
-    pub symbols: Vec<String>,
-    pub activity_types: &'static [TradingActivityType],
+    pub csp_nonce: String,
+    pub deleted: Vec<trading::DeletedTradingActivity>,
+    pub retention_days: i64,
+}
+
+#[derive(Template)]
+#[template(path = "partials/split_preview.html")]
+pub struct SplitPreviewTemplate {
+    pub description: Option<String>,
+    pub error: Option<String>,
+    pub warning: Option<String>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -167,6 +227,10 @@ pub struct TradingActivityFilterParams {
     pub preset: Option<String>,
     pub sort: Option<String>,
     pub dir: Option<String>,
+    /// `grouped` shows one expandable row per symbol instead of a flat list.
+    pub view: Option<String>,
+    /// Export only: `json` (the default) or `csv`.
+    pub format: Option<String>,
 }
 
 impl DateFilterable for TradingActivityFilterParams {
@@ -202,8 +266,44 @@ impl TradingActivityFilterParams {
         self.activity_type.as_deref() == Some(at.as_str())
     }
 
-    /// Returns filter query string (symbol, activity_type).
+    pub fn is_grouped(&self) -> bool {
+        self.view.as_deref() == Some("grouped")
+    }
+
+    /// Returns filter query string (symbol, activity_type, view).
     pub fn base_query_string(&self) -> String {
+        let mut parts = self.filter_parts();
+        if self.is_grouped() {
+            parts.push("view=grouped".to_string());
+        }
+        parts.join("&")
+    }
+
+    /// Query string for switching to `view` while keeping date range and filters.
+    /// Sort parameters are dropped since the two views sort by different columns.
+    pub fn view_query_string(&self, date_range: &DateRange, view: &str) -> String {
+        let mut parts = vec![date_range.query_string()];
+        parts.extend(self.filter_parts());
+        if view == "grouped" {
+            parts.push("view=grouped".to_string());
+        }
+        parts.join("&")
+    }
+
+    /// Query string loading one symbol's activities into an expanded group row.
+    pub fn group_rows_query_string(&self, date_range: &DateRange, symbol: &str) -> String {
+        let mut qs = format!(
+            "{}&symbol={}",
+            date_range.query_string(),
+            urlencoding::encode(symbol)
+        );
+        if let Some(activity_type) = self.activity_type.as_ref().filter(|t| !t.is_empty()) {
+            qs.push_str(&format!("&activity_type={}", activity_type));
+        }
+        qs
+    }
+
+    fn filter_parts(&self) -> Vec<String> {
         let mut parts = Vec::new();
         if let Some(ref symbol) = self.symbol {
             if !symbol.is_empty() {
@@ -215,7 +315,7 @@ impl TradingActivityFilterParams {
                 parts.push(format!("activity_type={}", activity_type));
             }
         }
-        parts.join("&")
+        parts
     }
 
     /// Returns full query string including sort parameters.
@@ -234,6 +334,18 @@ impl TradingActivityFilterParams {
         parts.join("&")
     }
 
+    /// Query string of the export links: the page's date range, filters and
+    /// sort, so the download matches the table.
+    pub fn export_query_string(&self, date_range: &DateRange) -> String {
+        let mut qs = date_range.query_string();
+        let rest = self.full_query_string();
+        if !rest.is_empty() {
+            qs.push('&');
+            qs.push_str(&rest);
+        }
+        qs
+    }
+
     /// Returns query string combining date range and filter params (for preserving state in sort links).
     pub fn preserve_query_string(&self, date_range: &DateRange) -> String {
         let mut qs = date_range.query_string();
@@ -246,74 +358,27 @@ impl TradingActivityFilterParams {
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct TradingActivityFormData {
-    pub date: String,
-    pub symbol: String,
-    pub quantity: Option<String>,
-    pub activity_type: String,
-    pub unit_price: Option<String>,
-    pub currency: String,
-    pub fee: Option<String>,
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub account_id: Option<i64>,
-    pub notes: Option<String>,
-}
-
-impl TradingActivityFormData {
-    fn to_new_activity(&self) -> Result<NewTradingActivity, AppError> {
-        let activity_type: TradingActivityType = self
-            .activity_type
-            .parse()
-            .map_err(|_| AppError::Validation("Invalid activity type".into()))?;
-
-        let quantity = self
-            .quantity
-            .as_ref()
-            .filter(|s| !s.is_empty())
-            .map(|s| {
-                s.parse::<f64>()
-                    .map_err(|_| AppError::Validation("Invalid quantity".into()))
-            })
-            .transpose()?;
-
-        let unit_price_cents = self
-            .unit_price
-            .as_ref()
-            .filter(|s| !s.is_empty())
-            .map(|s| {
-                s.parse::<f64>()
-                    .map(|v| (v * 100.0).round() as i64)
-                    .map_err(|_| AppError::Validation("Invalid unit price".into()))
-            })
-            .transpose()?;
-
-        let fee_cents = self
-            .fee
-            .as_ref()
-            .filter(|s| !s.is_empty())
-            .map(|s| {
-                s.parse::<f64>()
-                    .map(|v| (v * 100.0).round() as i64)
-                    .map_err(|_| AppError::Validation("Invalid fee".into()))
-            })
-            .transpose()?
-            .unwrap_or(0);
-
-        Ok(NewTradingActivity {
-            date: self.date.clone(),
-            symbol: self.symbol.clone(),
-            quantity,
-            activity_type,
-            unit_price_cents,
-            currency: self.currency.clone(),
-            fee_cents,
-            account_id: self.account_id,
-            notes: self.notes.clone().filter(|s| !s.is_empty()),
-        })
+/// Build the query filter shared by the flat and grouped views.
+pub(crate) fn activity_filter(
+    params: &TradingActivityFilterParams,
+    date_range: &DateRange,
+    page: i64,
+    page_size: i64,
+    sort_sql: String,
+) -> trading::TradingActivityFilter {
+    let activity_type = params
+        .activity_type
+        .as_ref()
+        .and_then(|s| s.parse::<TradingActivityType>().ok());
+
+    trading::TradingActivityFilter {
+        symbol: params.symbol.clone().filter(|s| !s.is_empty()),
+        activity_type,
+        from_date: Some(date_range.from_str()),
+        to_date: Some(date_range.to_str()),
+        limit: Some(page_size),
+        offset: Some((page - 1) * page_size),
+        sort_sql: Some(sort_sql),
     }
 }
 
@@ -329,33 +394,36 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let page = params.page.unwrap_or(1).max(1);
     let page_size = settings.page_size;
 
     let date_range = params
-        .resolve_date_range()
+        .resolve_date_range(date_utils::today_in(&settings))
         .resolve_all(trading::date_extent(&conn)?);
     let sort: TableSort<ActivitySortColumn> = params.resolve_sort();
+    let group_sort: TableSort<ActivityGroupSortColumn> = params.resolve_sort();
 
-    let activity_type = params
-        .activity_type
-        .as_ref()
-        .and_then(|s| s.parse::<TradingActivityType>().ok());
-
-    let filter = trading::TradingActivityFilter {
-        symbol: params.symbol.clone().filter(|s| !s.is_empty()),
-        activity_type,
-        from_date: Some(date_range.from_str()),
-        to_date: Some(date_range.to_str()),
-        limit: Some(page_size),
-        offset: Some((page - 1) * page_size),
-        sort_sql: Some(sort.sql_order_by()),
+    let sort_sql = if params.is_grouped() {
+        group_sort.sql_order_by()
+    } else {
+        sort.sql_order_by()
+    };
+    let filter = activity_filter(&params, &date_range, page, page_size, sort_sql);
+    let delete_count = trading::count_activities(&conn, &filter)?;
+    let (activity_list, groups, total_count) = if params.is_grouped() {
+        let groups = trading::list_activity_groups(&conn, &filter)?;
+        (
+            Vec::new(),
+            groups,
+            trading::count_activity_groups(&conn, &filter)?,
+        )
+    } else {
+        let activities = trading::list_activities(&conn, &filter)?;
+        (activities, Vec::new(), delete_count)
     };
-
-    let activity_list = trading::list_activities(&conn, &filter)?;
-    let total_count = trading::count_activities(&conn, &filter)?;
     let symbols = trading::get_unique_symbols(&conn)?;
 
     let template = TradingActivitiesTemplate {
@@ -365,17 +433,20 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         activities: activity_list,
         symbols,
         activity_types: TradingActivityType::all(),
         total_count,
-        delete_count: total_count,
+        delete_count,
         page,
         page_size,
         filter: params,
         date_range,
         presets: DatePreset::all(),
         sort,
+        groups,
+        group_sort,
     };
 
     template.render_html()
@@ -395,33 +466,40 @@ pub async fn table_partial(
     let page_size = settings.page_size;
 
     let date_range = params
-        .resolve_date_range()
+        .resolve_date_range(date_utils::today_in(&settings))
         .resolve_all(trading::date_extent(&conn)?);
-    let sort: TableSort<ActivitySortColumn> = params.resolve_sort();
 
-    let activity_type = params
-        .activity_type
-        .as_ref()
-        .and_then(|s| s.parse::<TradingActivityType>().ok());
-
-    let filter = trading::TradingActivityFilter {
-        symbol: params.symbol.clone().filter(|s| !s.is_empty()),
-        activity_type,
-        from_date: Some(date_range.from_str()),
-        to_date: Some(date_range.to_str()),
-        limit: Some(page_size),
-        offset: Some((page - 1) * page_size),
-        sort_sql: Some(sort.sql_order_by()),
-    };
+    if params.is_grouped() {
+        let group_sort: TableSort<ActivityGroupSortColumn> = params.resolve_sort();
+        let filter = activity_filter(
+            &params,
+            &date_range,
+            page,
+            page_size,
+            group_sort.sql_order_by(),
+        );
+        let template = TradingActivityGroupsTemplate {
+            settings,
+            icons,
+            groups: trading::list_activity_groups(&conn, &filter)?,
+            total_count: trading::count_activity_groups(&conn, &filter)?,
+            page,
+            page_size,
+            filter: params,
+            date_range,
+            group_sort,
+        };
+        return template.render_html();
+    }
 
-    let activity_list = trading::list_activities(&conn, &filter)?;
-    let total_count = trading::count_activities(&conn, &filter)?;
+    let sort: TableSort<ActivitySortColumn> = params.resolve_sort();
+    let filter = activity_filter(&params, &date_range, page, page_size, sort.sql_order_by());
 
     let template = TradingActivityTableTemplate {
         settings,
         icons,
-        activities: activity_list,
-        total_count,
+        activities: trading::list_activities(&conn, &filter)?,
+        total_count: trading::count_activities(&conn, &filter)?,
         page,
         page_size,
         filter: params,
@@ -444,6 +522,7 @@ pub async fn detail(State(state): State<AppState>, Path(id): Path<i64>) -> AppRe
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let template = TradingActivityDetailTemplate {
@@ -453,310 +532,276 @@ pub async fn detail(State(state): State<AppState>, Path(id): Path<i64>) -> AppRe
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         activity,
     };
 
     template.render_html()
 }
 
-pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-
-    let PageBase {
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-    } = state.page_base()?;
-    let symbols = trading::get_unique_symbols(&conn)?;
-
-    let template = TradingActivityNewTemplate {
-        title: "Add Activity".into(),
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-        symbols,
-        activity_types: TradingActivityType::all(),
-    };
-
-    template.render_html()
+/// Lock the split adjustments of `symbols` against a market data refresh,
+/// see [`crate::symbol_locks`].
+pub(crate) async fn lock_symbols(state: &AppState, symbols: &[&str]) -> AppResult<SymbolGuard> {
+    state
+        .symbol_locks
+        .lock_for_edit(symbols, EDIT_LOCK_TIMEOUT)
+        .await
 }
 
-pub async fn edit_form(
-    State(state): State<AppState>,
-    Path(id): Path<i64>,
-) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-
-    let activity = trading::get_activity(&conn, id)?
-        .ok_or_else(|| AppError::NotFound(format!("Activity {} not found", id)))?;
-
-    let PageBase {
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-    } = state.page_base()?;
-
-    let symbols = trading::get_unique_symbols(&conn)?;
-
-    let template = TradingActivityEditTemplate {
-        title: "Edit Activity".into(),
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-        activity,
-        symbols,
-        activity_types: TradingActivityType::all(),
-    };
-
-    template.render_html()
+/// Lock the symbol of a stored activity, trashed or not, and the symbol it
+/// is about to get.
+pub(crate) async fn lock_activity_symbol(
+    state: &AppState,
+    id: i64,
+    new_symbol: Option<&str>,
+) -> AppResult<SymbolGuard> {
+    let symbol = trading::activity_symbol(&*state.db.get()?, id)?;
+    let symbols: Vec<&str> = symbol.as_deref().into_iter().chain(new_symbol).collect();
+    lock_symbols(state, &symbols).await
 }
 
-pub async fn create(
-    State(state): State<AppState>,
-    Form(form): Form<TradingActivityFormData>,
-) -> AppResult<Redirect> {
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
-
-    let new_activity = form.to_new_activity()?;
-    let id = trading::create_activity(&tx, &new_activity)?;
-
-    match new_activity.activity_type {
+/// Adjust past activities for a split, or adjust an activity for the splits
+/// after it.
+pub(crate) fn apply_split_effects(
+    conn: &rusqlite::Connection,
+    id: i64,
+    activity: &NewTradingActivity,
+) -> rusqlite::Result<()> {
+    match activity.activity_type {
         TradingActivityType::Split => {
-            if let Some(ratio) = new_activity.quantity {
-                trading::apply_split_to_past_activities(
-                    &tx,
+            if let Some(ratio) = activity.quantity {
+                split_adjustments::apply_split_to_past_activities(
+                    conn,
                     id,
-                    &new_activity.symbol,
-                    &new_activity.date,
+                    &activity.symbol,
+                    &activity.date,
                     ratio,
                 )?;
             }
         }
-        TradingActivityType::Buy | TradingActivityType::Sell => {
-            trading::apply_existing_splits_to_activity(
-                &tx,
+        t if t.affects_holdings() => {
+            split_adjustments::apply_existing_splits_to_activity(
+                conn,
                 id,
-                &new_activity.symbol,
-                &new_activity.date,
+                &activity.symbol,
+                &activity.date,
             )?;
         }
         _ => {}
     }
-
-    tx.commit()?;
-    Ok(Redirect::to("/trading/activities"))
+    Ok(())
 }
 
-pub async fn update(
-    State(state): State<AppState>,
-    Path(id): Path<i64>,
-    Form(form): Form<TradingActivityFormData>,
-) -> AppResult<Redirect> {
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
-
-    let old_activity = trading::get_activity(&tx, id)?
-        .ok_or_else(|| AppError::NotFound(format!("Activity {} not found", id)))?;
-
-    // Undo split effects from the old version of this activity.
-    if old_activity.activity_type == TradingActivityType::Split {
-        trading::reverse_split_adjustments(&tx, id)?;
-    } else {
-        trading::delete_adjustments_targeting_activity(&tx, id)?;
+/// Flash a warning when the market data around a split doesn't show the
+/// price jump the split implies.
+pub(crate) fn warn_about_adjusted_prices(
+    conn: &rusqlite::Connection,
+    activity: &NewTradingActivity,
+) -> AppResult<()> {
+    if activity.activity_type != TradingActivityType::Split {
+        return Ok(());
     }
-
-    let new_activity = form.to_new_activity()?;
-    trading::update_activity(&tx, id, &new_activity)?;
-
-    // Apply split effects for the new version.
-    match new_activity.activity_type {
-        TradingActivityType::Split => {
-            if let Some(ratio) = new_activity.quantity {
-                trading::apply_split_to_past_activities(
-                    &tx,
-                    id,
-                    &new_activity.symbol,
-                    &new_activity.date,
-                    ratio,
-                )?;
-            }
-        }
-        TradingActivityType::Buy | TradingActivityType::Sell => {
-            trading::apply_existing_splits_to_activity(
-                &tx,
-                id,
-                &new_activity.symbol,
-                &new_activity.date,
-            )?;
-        }
-        _ => {}
+    let Some(ratio) = activity.quantity else {
+        return Ok(());
+    };
+    if let Some(warning) = split_price_warning(conn, &activity.symbol, &activity.date, ratio)? {
+        flash::flash_error(format!("Split saved. {}", warning));
     }
+    Ok(())
+}
 
-    tx.commit()?;
-    Ok(Redirect::to("/trading/activities"))
+fn split_price_warning(
+    conn: &rusqlite::Connection,
+    symbol: &str,
+    date: &str,
+    ratio: f64,
+) -> AppResult<Option<String>> {
+    let Ok(date) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+        return Ok(None);
+    };
+    let window = chrono::Days::new(splits::PRICE_WINDOW_DAYS);
+    let (Some(from), Some(to)) = (date.checked_sub_days(window), date.checked_add_days(window))
+    else {
+        return Ok(None);
+    };
+    let prices = market_data::get_prices_between(conn, symbol, &from.to_string(), &to.to_string())?;
+    Ok(splits::check_against_prices(&prices, date, ratio))
 }
 
-pub async fn delete(State(state): State<AppState>, Path(id): Path<i64>) -> AppResult<Html<String>> {
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
+#[derive(Debug, Default, Deserialize)]
+pub struct SplitPreviewParams {
+    #[serde(default)]
+    pub symbol: String,
+    #[serde(default)]
+    pub date: String,
+    #[serde(default)]
+    pub shares_after: String,
+    #[serde(default)]
+    pub shares_before: String,
+}
 
-    // If this is a split, reverse its adjustments before deleting.
-    if let Some(activity) = trading::get_activity(&tx, id)? {
-        if activity.activity_type == TradingActivityType::Split {
-            trading::reverse_split_adjustments(&tx, id)?;
+/// Describe the split entered in the activity form, and check it against
+/// the market data.
+pub async fn split_preview(
+    State(state): State<AppState>,
+    Query(params): Query<SplitPreviewParams>,
+) -> AppResult<Html<String>> {
+    let mut template = SplitPreviewTemplate {
+        description: None,
+        error: None,
+        warning: None,
+    };
+    if !params.shares_after.trim().is_empty() || !params.shares_before.trim().is_empty() {
+        match SplitRatio::parse(&params.shares_after, &params.shares_before) {
+            Ok(split) => {
+                template.description = Some(split.description());
+                let conn = state.db.get()?;
+                template.warning =
+                    split_price_warning(&conn, params.symbol.trim(), &params.date, split.ratio())?;
+            }
+            Err(e) => template.error = Some(e),
         }
     }
-
-    trading::delete_activity(&tx, id)?;
-
-    tx.commit()?;
-    Ok(Html(String::new()))
+    template.render_html()
 }
 
-pub async fn delete_all(State(state): State<AppState>) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-
-    trading::delete_all_activities(&conn)?;
-
-    Ok(Html(String::new()))
+/// Counterpart of `apply_split_effects` before an activity is rewritten.
+pub(crate) fn undo_split_effects(
+    conn: &rusqlite::Connection,
+    old_activity: &TradingActivity,
+) -> rusqlite::Result<()> {
+    if old_activity.activity_type == TradingActivityType::Split {
+        split_adjustments::reverse_split_adjustments(conn, old_activity.id)
+    } else {
+        split_adjustments::delete_adjustments_targeting_activity(conn, old_activity.id)
+    }
 }
 
-#[derive(Serialize)]
-struct TradingActivityExport {
-    date: String,
-    symbol: String,
-    quantity: Option<f64>,
-    activity_type: TradingActivityType,
-    unit_price_cents: Option<i64>,
-    currency: String,
-    fee_cents: i64,
-    account_name: Option<String>,
-    notes: Option<String>,
+/// Move an activity to the trash, undoing its split adjustments so it can
+/// be restored later as if it had just been created.
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let _guard = lock_activity_symbol(&state, id, None).await?;
+    state.with_tx(|tx| {
+        if let Some(activity) = trading::get_activity(tx, id)? {
+            match activity.activity_type {
+                TradingActivityType::Split => split_adjustments::reverse_split_adjustments(tx, id)?,
+                t if t.affects_holdings() => {
+                    split_adjustments::remove_split_adjustments_from_activity(tx, id)?
+                }
+                _ => {}
+            }
+            trading::soft_delete_activity(tx, id)?;
+            trading_mirror::sync(tx, id)?;
+        }
+        trading::purge_deleted_activities(tx, trading::TRASH_RETENTION_DAYS)?;
+        Ok(())
+    })?;
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, "Activity moved to trash"),
+        Html(String::new()),
+    ))
 }
 
-pub async fn export(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+pub async fn trash(State(state): State<AppState>) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
+    trading::purge_deleted_activities(&conn, trading::TRASH_RETENTION_DAYS)?;
+    let deleted = trading::list_deleted_activities(&conn)?;
 
-    let filter = crate::db::queries::trading::TradingActivityFilter {
-        symbol: None,
-        activity_type: None,
-        from_date: None,
-        to_date: None,
-        limit: None,
-        offset: None,
-        sort_sql: None,
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    let template = TradingActivityTrashTemplate {
+        title: "Deleted Activities".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        deleted,
+        retention_days: trading::TRASH_RETENTION_DAYS,
     };
 
-    let activities = trading::list_activities(&conn, &filter)?;
-
-    // Build account id -> name map for export
-    let account_list = state.cached_accounts()?;
-    let account_id_to_name: std::collections::HashMap<i64, String> = account_list
-        .iter()
-        .map(|a| (a.id, a.name.clone()))
-        .collect();
-
-    let export_data: Vec<TradingActivityExport> = activities
-        .iter()
-        .map(|a| TradingActivityExport {
-            date: a.date.clone(),
-            symbol: a.symbol.clone(),
-            quantity: a.quantity,
-            activity_type: a.activity_type,
-            unit_price_cents: a.unit_price_cents,
-            currency: a.currency.clone(),
-            fee_cents: a.fee_cents,
-            account_name: a
-                .account_id
-                .and_then(|id| account_id_to_name.get(&id).cloned()),
-            notes: a.notes.clone(),
-        })
-        .collect();
-
-    let json = serde_json::to_string_pretty(&export_data)
-        .map_err(|e| AppError::Internal(format!("Failed to serialize: {}", e)))?;
+    template.render_html()
+}
 
+/// Take an activity out of the trash and re-apply the splits that affect it,
+/// mirroring what `delete` undid.
+pub async fn restore(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    let _guard = lock_activity_symbol(&state, id, None).await?;
+    state.with_tx(|tx| {
+        let activity = trading::restore_activity(tx, id)?
+            .ok_or_else(|| AppError::NotFound(format!("Deleted activity {} not found", id)))?;
+
+        match activity.activity_type {
+            TradingActivityType::Split => {
+                if let Some(ratio) = activity.quantity {
+                    split_adjustments::apply_split_to_past_activities(
+                        tx,
+                        id,
+                        &activity.symbol,
+                        &activity.date,
+                        ratio,
+                    )?;
+                }
+            }
+            t if t.affects_holdings() => {
+                split_adjustments::apply_existing_splits_to_activity(
+                    tx,
+                    id,
+                    &activity.symbol,
+                    &activity.date,
+                )?;
+            }
+            _ => {}
+        }
+        trading_mirror::sync(tx, id)?;
+        Ok(())
+    })?;
     Ok((
-        [
-            (header::CONTENT_TYPE, "application/json"),
-            (
-                header::CONTENT_DISPOSITION,
-                "attachment; filename=\"trading_activities.json\"",
-            ),
-        ],
-        json,
+        flash::toast_trigger(FlashLevel::Success, "Activity restored"),
+        Html(String::new()),
     ))
 }
 
-#[derive(Deserialize)]
-struct TradingActivityImport {
-    date: String,
-    symbol: String,
-    quantity: Option<f64>,
-    activity_type: TradingActivityType,
-    unit_price_cents: Option<i64>,
-    #[serde(default = "default_currency")]
-    currency: String,
-    #[serde(default)]
-    fee_cents: i64,
-    account_name: Option<String>,
-    #[serde(default)]
-    notes: Option<String>,
-}
-
-fn default_currency() -> String {
-    "USD".to_string()
+/// Permanently delete everything in the trash.
+pub async fn empty_trash(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let conn = state.db.get()?;
+    let count = trading::purge_deleted_activities(&conn, -1)?;
+    Ok(DeletedCounts::single("trading_activities", count).into_response())
 }
 
-pub async fn import(
+pub async fn delete_all(
     State(state): State<AppState>,
-    Json(value): Json<serde_json::Value>,
-) -> AppResult<Json<serde_json::Value>> {
-    let data: Vec<TradingActivityImport> = serde_json::from_value(value)
-        .map_err(|e| AppError::Validation(format!("Invalid JSON format: {}", e)))?;
-
+    Query(params): Query<ConfirmParams>,
+) -> AppResult<Response> {
+    let pending = confirmation_pending(&state, "trading_activities", &params, || {
+        let conn = state.db.get()?;
+        let count = trading::count_all_activities(&conn)?;
+        Ok(deletion_message(&[(
+            count as usize,
+            "trading activity",
+            "trading activities",
+        )]))
+    })?;
+    if let Some(pending) = pending {
+        return Ok(pending);
+    }
     let conn = state.db.get()?;
 
-    let account_list = state.cached_accounts()?;
-    let account_name_to_id: std::collections::HashMap<String, i64> = account_list
-        .iter()
-        .map(|a| (a.name.clone(), a.id))
-        .collect();
-
-    let mut created = 0;
-    for item in data {
-        let account_id = item
-            .account_name
-            .as_ref()
-            .and_then(|name| account_name_to_id.get(name).copied());
-
-        let new_activity = NewTradingActivity {
-            date: item.date,
-            symbol: item.symbol,
-            quantity: item.quantity,
-            activity_type: item.activity_type,
-            unit_price_cents: item.unit_price_cents,
-            currency: item.currency,
-            fee_cents: item.fee_cents,
-            account_id,
-            notes: item.notes,
-        };
-
-        trading::create_activity(&conn, &new_activity)?;
-        created += 1;
-    }
+    let count = trading::delete_all_activities(&conn)?;
 
-    Ok(Json(serde_json::json!({
-        "imported": created,
-        "message": format!("Successfully imported {} trading activities", created)
-    })))
+    Ok(DeletedCounts::single("trading_activities", count).into_response())
 }
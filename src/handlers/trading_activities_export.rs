@@ -0,0 +1,471 @@
+//! Exporting trading activities as CSV or JSON, and importing the JSON back.
+
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::DataDomain;
+use crate::date_utils::{self, DateFilterable, DatePreset, DateRange};
+use crate::db::queries::{accounts, split_adjustments, trading};
+use crate::error::{AppError, AppResult};
+use crate::handlers::position_export::csv_response;
+use crate::handlers::trading_activities::{
+    activity_filter, apply_split_effects, lock_symbols, undo_split_effects, ActivitySortColumn,
+    TradingActivityFilterParams,
+};
+use crate::handlers::transactions_json::{ImportParams, NameResolver};
+use crate::models::trading::normalize_fee_currency;
+use crate::models::{
+    AccountType, ImportSummary, NewAccount, NewTradingActivity, RecordOutcome, TradingActivityType,
+};
+use crate::services::money;
+use crate::services::trading_mirror;
+use crate::sort_utils::{Sortable, TableSort};
+use crate::state::AppState;
+
+/// One activity of the JSON export. Quantity and unit price are the values
+/// as entered, before any split adjusted them, so that importing the export
+/// replays the splits instead of applying them twice.
+#[derive(Serialize)]
+struct TradingActivityExport {
+    date: String,
+    symbol: String,
+    quantity: Option<f64>,
+    activity_type: TradingActivityType,
+    unit_price_cents: Option<i64>,
+    currency: String,
+    fee_cents: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exchange_rate: Option<f64>,
+    /// The fee in `currency`; `null` for a foreign fee without a rate.
+    fee_in_currency_cents: Option<i64>,
+    account_name: Option<String>,
+    notes: Option<String>,
+    /// Quantity and price after later splits, for split-adjusted activities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    adjusted_quantity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    adjusted_unit_price_cents: Option<i64>,
+}
+
+const CSV_HEADERS: [&str; 11] = [
+    "date",
+    "symbol",
+    "activity_type",
+    "quantity",
+    "unit_price",
+    "currency",
+    "fee",
+    "fee_currency",
+    "exchange_rate",
+    "account",
+    "notes",
+];
+
+/// The period of an export file name: the year or month if the range
+/// covers exactly one, else both dates; `None` for all dates.
+fn export_period(date_range: &DateRange) -> Option<String> {
+    if date_range.preset == Some(DatePreset::All) {
+        return None;
+    }
+    let (from, to) = (date_range.from, date_range.to);
+    let month_end = |d: NaiveDate| d.succ_opt().is_some_and(|next| next.day() == 1);
+    if from.ordinal() == 1 && to.year() == from.year() && to.month() == 12 && to.day() == 31 {
+        Some(from.year().to_string())
+    } else if from.day() == 1
+        && to.month() == from.month()
+        && to.year() == from.year()
+        && month_end(to)
+    {
+        Some(from.format("%Y-%m").to_string())
+    } else {
+        Some(format!("{}_{}", from, to))
+    }
+}
+
+/// File name of an export, naming the symbol, type and period filtered to,
+/// e.g. `trading_activities_AAPL_2024.csv`.
+fn export_filename(
+    params: &TradingActivityFilterParams,
+    date_range: &DateRange,
+    extension: &str,
+) -> String {
+    let file_safe = |s: &str| -> String {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect()
+    };
+    let mut parts = vec!["trading_activities".to_string()];
+    parts.extend(
+        [&params.symbol, &params.activity_type]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .map(|s| file_safe(s)),
+    );
+    parts.extend(export_period(date_range));
+    format!("{}.{}", parts.join("_"), extension)
+}
+
+/// Export the activities matching the page's filters, in its sort order, as
+/// JSON (re-importable) or CSV.
+pub async fn export(
+    State(state): State<AppState>,
+    Query(params): Query<TradingActivityFilterParams>,
+) -> AppResult<Response> {
+    let format = params.format.as_deref().unwrap_or("json");
+    if !matches!(format, "json" | "csv") {
+        return Err(AppError::Validation(format!(
+            "Unsupported export format: {}",
+            format
+        )));
+    }
+    let conn = state.db.get()?;
+    let settings = state.load_settings()?;
+
+    let date_range = params
+        .resolve_date_range(date_utils::today_in(&settings))
+        .resolve_all(trading::date_extent(&conn)?);
+    let sort: TableSort<ActivitySortColumn> = params.resolve_sort();
+    let filter = trading::TradingActivityFilter {
+        limit: None,
+        offset: None,
+        ..activity_filter(&params, &date_range, 1, 0, sort.sql_order_by())
+    };
+
+    let activities = trading::list_activities(&conn, &filter)?;
+    let filename = export_filename(&params, &date_range, format);
+
+    // Build account id -> name map for export
+    let account_list = state.cached_accounts()?;
+    let account_id_to_name: std::collections::HashMap<i64, String> = account_list
+        .iter()
+        .map(|a| (a.id, a.name.clone()))
+        .collect();
+
+    if format == "csv" {
+        // Values as shown in the table, after splits
+        let records = activities
+            .iter()
+            .map(|a| {
+                vec![
+                    a.date.clone(),
+                    a.symbol.clone(),
+                    a.activity_type.as_str().to_string(),
+                    a.quantity.map(|q| q.to_string()).unwrap_or_default(),
+                    a.unit_price_cents
+                        .map(money::format_cents)
+                        .unwrap_or_default(),
+                    a.currency.clone(),
+                    money::format_cents(a.fee_cents),
+                    a.fee_currency.clone().unwrap_or_default(),
+                    a.exchange_rate.map(|r| r.to_string()).unwrap_or_default(),
+                    a.account_id
+                        .and_then(|id| account_id_to_name.get(&id).cloned())
+                        .unwrap_or_default(),
+                    a.notes.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        return Ok(csv_response(&filename, &CSV_HEADERS, records)?.into_response());
+    }
+
+    let pre_split_values = split_adjustments::get_all_pre_split_values(&conn)?;
+
+    let export_data: Vec<TradingActivityExport> = activities
+        .iter()
+        .map(|a| {
+            let pre_split = pre_split_values.get(&a.id);
+            TradingActivityExport {
+                date: a.date.clone(),
+                symbol: a.symbol.clone(),
+                quantity: pre_split.map_or(a.quantity, |(qty, _)| Some(*qty)),
+                activity_type: a.activity_type,
+                unit_price_cents: pre_split.map_or(a.unit_price_cents, |(_, price)| *price),
+                currency: a.currency.clone(),
+                fee_cents: a.fee_cents,
+                fee_currency: a.fee_currency.clone(),
+                exchange_rate: a.exchange_rate,
+                fee_in_currency_cents: a.fee_in_currency_cents(),
+                account_name: a
+                    .account_id
+                    .and_then(|id| account_id_to_name.get(&id).cloned()),
+                notes: a.notes.clone(),
+                adjusted_quantity: pre_split.and(a.quantity),
+                adjusted_unit_price_cents: pre_split.and(a.unit_price_cents),
+            }
+        })
+        .collect();
+
+    let json = serde_json::to_string_pretty(&export_data)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        json,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct TradingActivityImport {
+    /// Client-supplied id; re-importing it updates the same activity.
+    #[serde(default)]
+    external_id: Option<String>,
+    date: String,
+    symbol: String,
+    quantity: Option<f64>,
+    activity_type: TradingActivityType,
+    unit_price_cents: Option<i64>,
+    #[serde(default = "default_currency")]
+    currency: String,
+    #[serde(default)]
+    fee_cents: i64,
+    #[serde(default)]
+    fee_currency: Option<String>,
+    #[serde(default)]
+    exchange_rate: Option<f64>,
+    account_name: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+/// Check an imported record the way the activity form would.
+fn validate_activity_import(item: &TradingActivityImport) -> Result<(), String> {
+    if chrono::NaiveDate::parse_from_str(&item.date, "%Y-%m-%d").is_err() {
+        return Err(format!("invalid date \"{}\"", item.date));
+    }
+    if item.symbol.trim().is_empty() {
+        return Err("symbol is empty".into());
+    }
+    if item.currency.trim().is_empty() {
+        return Err("currency is empty".into());
+    }
+    let activity_type = item.activity_type;
+    if activity_type.is_amount_only() {
+        if item.unit_price_cents.is_none() {
+            return Err(format!("{} needs an amount", activity_type.label()));
+        }
+    } else if !item.quantity.is_some_and(|q| q.is_finite() && q > 0.0) {
+        return Err(format!(
+            "{} needs a positive quantity",
+            activity_type.label()
+        ));
+    }
+    if item.fee_cents < 0 {
+        return Err("fee must not be negative".into());
+    }
+    Ok(())
+}
+
+pub async fn import(
+    State(state): State<AppState>,
+    Query(params): Query<ImportParams>,
+    Json(mut value): Json<serde_json::Value>,
+) -> AppResult<Json<serde_json::Value>> {
+    // A position history export carries its activities in a field
+    if let Some(activities) = value.get_mut("activities") {
+        value = activities.take();
+    }
+    let data: Vec<TradingActivityImport> = serde_json::from_value(value)
+        .map_err(|e| AppError::Validation(format!("Invalid JSON format: {}", e)))?;
+
+    // Every symbol whose splits the batch may rewrite: the imported ones and
+    // those of the activities it updates
+    let mut symbols: Vec<String> = data.iter().map(|i| i.symbol.trim().to_string()).collect();
+    {
+        let conn = state.db.get()?;
+        for ext in data.iter().filter_map(|i| i.external_id.as_deref()) {
+            if let Some(existing) = trading::find_activity_by_external_id(&conn, ext)? {
+                symbols.extend(trading::activity_symbol(&conn, existing.id)?);
+            }
+        }
+    }
+    let symbols: Vec<&str> = symbols.iter().map(String::as_str).collect();
+    let _guard = lock_symbols(&state, &symbols).await?;
+
+    let mut account_names = NameResolver::new(
+        "account",
+        state.cached_accounts()?.into_iter().map(|a| (a.name, a.id)),
+    );
+
+    let mut results = state.with_tx(|tx| {
+        // Import in chronological order, so that splits adjust exactly the
+        // activities before them, as when they are entered one by one
+        let mut records: Vec<(usize, TradingActivityImport)> =
+            data.into_iter().enumerate().collect();
+        records.sort_by(|(_, a), (_, b)| a.date.cmp(&b.date));
+
+        let mut results = Vec::with_capacity(records.len());
+        for (index, item) in records {
+            let external_id = item.external_id.clone().filter(|e| !e.trim().is_empty());
+            let mut warnings = Vec::new();
+
+            if let Err(e) = validate_activity_import(&item) {
+                results.push((index, external_id, Err(e), warnings));
+                continue;
+            }
+
+            let account_id = match item.account_name.as_deref().filter(|n| !n.is_empty()) {
+                Some(name) => account_names.resolve(
+                    name,
+                    params.create_missing.then_some(|name: &str| {
+                        Ok(accounts::create_account(
+                            tx,
+                            &NewAccount {
+                                name: name.to_string(),
+                                account_type: AccountType::Securities,
+                                active: true,
+                                interest_rate_bps: None,
+                                interest_compounding: Default::default(),
+                                derive_cash_from_trading: false,
+                                statement_day: None,
+                                due_day: None,
+                                cash_account_id: None,
+                                iban: None,
+                            },
+                        )?)
+                    }),
+                    &mut warnings,
+                )?,
+                None => None,
+            };
+
+            let (fee_currency, exchange_rate) = match normalize_fee_currency(
+                &item.currency,
+                item.fee_currency.as_deref(),
+                item.exchange_rate,
+            ) {
+                Ok(fee_fields) => fee_fields,
+                Err(e) => {
+                    results.push((index, external_id, Err(e.to_string()), warnings));
+                    continue;
+                }
+            };
+            let new_activity = NewTradingActivity {
+                date: item.date,
+                symbol: item.symbol.trim().to_string(),
+                quantity: item.quantity,
+                activity_type: item.activity_type,
+                unit_price_cents: item.unit_price_cents,
+                currency: item.currency,
+                fee_cents: item.fee_cents,
+                fee_currency,
+                exchange_rate,
+                account_id,
+                notes: item.notes,
+            };
+
+            let result = import_activity(tx, external_id.as_deref(), &new_activity)?;
+            results.push((index, external_id, result, warnings));
+        }
+        Ok(results)
+    })?;
+
+    // The middleware only knows this path writes trading activities
+    if !account_names.unknown.created.is_empty() {
+        state.cache.invalidate_domains(&[DataDomain::Accounts]);
+    }
+
+    // Report the records in the order they were given
+    results.sort_by_key(|(index, ..)| *index);
+    let mut summary = ImportSummary::default();
+    for (index, external_id, result, warnings) in results {
+        summary.record_with_warnings(index, external_id, result, warnings);
+    }
+
+    let mut body = summary.to_json("trading activities");
+    body["unknown_names"] = serde_json::json!({ "accounts": account_names.unknown });
+    Ok(Json(body))
+}
+
+/// Create an activity, or update the one previously imported under the same
+/// external id, keeping split adjustments consistent either way. Conflicts
+/// are returned as the inner `Err` so they are reported per record.
+fn import_activity(
+    conn: &rusqlite::Connection,
+    external_id: Option<&str>,
+    new_activity: &NewTradingActivity,
+) -> AppResult<Result<(RecordOutcome, i64), String>> {
+    let existing = match external_id {
+        Some(ext) => trading::find_activity_by_external_id(conn, ext)?,
+        None => None,
+    };
+
+    let Some(existing) = existing else {
+        let id = trading::create_activity(conn, new_activity)?;
+        if let Some(ext) = external_id {
+            trading::set_activity_external_id(conn, id, ext)?;
+        }
+        apply_split_effects(conn, id, new_activity)?;
+        trading_mirror::sync(conn, id)?;
+        return Ok(Ok((RecordOutcome::Created, id)));
+    };
+
+    let ext = external_id.unwrap_or_default();
+    if existing.deleted {
+        return Ok(Err(format!(
+            "external_id {} belongs to an activity in the trash",
+            ext
+        )));
+    }
+    if existing.account_id != new_activity.account_id {
+        return Ok(Err(format!(
+            "external_id {} belongs to an activity in a different account",
+            ext
+        )));
+    }
+
+    let old_activity = trading::get_activity(conn, existing.id)?
+        .ok_or_else(|| AppError::NotFound(format!("Activity {} not found", existing.id)))?;
+
+    // Compare against the values as imported, before splits adjusted them.
+    let (quantity, unit_price_cents) =
+        match split_adjustments::get_pre_split_values(conn, existing.id)? {
+            Some((qty, price)) => (Some(qty), price),
+            None => (old_activity.quantity, old_activity.unit_price_cents),
+        };
+    let current = NewTradingActivity {
+        date: old_activity.date.clone(),
+        symbol: old_activity.symbol.clone(),
+        quantity,
+        activity_type: old_activity.activity_type,
+        unit_price_cents,
+        currency: old_activity.currency.clone(),
+        fee_cents: old_activity.fee_cents,
+        fee_currency: old_activity.fee_currency.clone(),
+        exchange_rate: old_activity.exchange_rate,
+        account_id: old_activity.account_id,
+        notes: old_activity.notes.clone(),
+    };
+    if current == *new_activity {
+        return Ok(Ok((RecordOutcome::Unchanged, existing.id)));
+    }
+
+    undo_split_effects(conn, &old_activity)?;
+    trading::update_activity(conn, existing.id, new_activity)?;
+    apply_split_effects(conn, existing.id, new_activity)?;
+    trading_mirror::sync(conn, existing.id)?;
+    Ok(Ok((RecordOutcome::Updated, existing.id)))
+}
@@ -0,0 +1,547 @@
+//! Forms for creating, duplicating, reinvesting into and editing a single
+//! trading activity.
+
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::Form;
+use serde::Deserialize;
+
+use crate::date_utils;
+use crate::db::queries::{accounts, settings, trading};
+use crate::error::{render_form, AppError, AppResult, RenderHtml};
+use crate::flash;
+use crate::form_utils::SubmittedForm;
+use crate::handlers::trading_activities::{
+    apply_split_effects, lock_activity_symbol, lock_symbols, undo_split_effects,
+    warn_about_adjusted_prices,
+};
+use crate::models::trading::{drip_note, format_quantity, normalize_fee_currency};
+use crate::models::{
+    Account, AccountType, NewTradingActivity, Settings, TradingActivity, TradingActivityType,
+};
+use crate::services::money;
+use crate::services::splits::SplitRatio;
+use crate::services::trading_mirror;
+use crate::state::{AppState, JsManifest, PageBase};
+
+#[derive(Template)]
+#[template(path = "pages/trading_activity_new.html")]
+pub struct TradingActivityNewTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub symbols: Vec<String>,
+    pub activity_types: &'static [TradingActivityType],
+    pub accounts: Vec<Account>,
+    pub form_token: String,
+    /// Values of a submission that failed validation, empty for a new form.
+    pub values: SubmittedForm,
+    pub error: Option<String>,
+    /// Activity the form was filled in from, see [`duplicate_form`].
+    pub duplicate_of: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "pages/trading_activity_edit.html")]
+pub struct TradingActivityEditTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub activity: TradingActivity,
+    /// Share counts of a split, recovered from its stored ratio.
+    pub split: Option<SplitRatio>,
+    pub symbols: Vec<String>,
+    pub activity_types: &'static [TradingActivityType],
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TradingActivityFormData {
+    pub date: String,
+    pub symbol: String,
+    pub quantity: Option<String>,
+    pub activity_type: String,
+    pub unit_price: Option<String>,
+    pub currency: String,
+    pub fee: Option<String>,
+    /// Advanced: currency the fee was charged in, if not `currency`.
+    pub fee_currency: Option<String>,
+    pub exchange_rate: Option<String>,
+    /// `None` when the form omits the field, `Some(None)` for "No Account".
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_submitted_i64"
+    )]
+    pub account_id: Option<Option<i64>>,
+    pub notes: Option<String>,
+    /// Split helper: a split of 4 for 1 has 4 shares after and 1 before.
+    /// When filled in, they set the quantity of a split.
+    pub shares_after: Option<String>,
+    pub shares_before: Option<String>,
+}
+
+impl TradingActivityFormData {
+    /// The date is read in the configured date format and stored as ISO.
+    fn to_new_activity(&self, settings: &Settings) -> Result<NewTradingActivity, AppError> {
+        let date =
+            date_utils::parse_user_date(&self.date, settings).map_err(AppError::Validation)?;
+        let activity_type: TradingActivityType = self
+            .activity_type
+            .parse()
+            .map_err(|_| AppError::Validation("Invalid activity type".into()))?;
+
+        let quantity = self
+            .quantity
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                money::parse_quantity(s, money::INPUT_LOCALE)
+                    .map_err(|_| AppError::Validation("Invalid quantity".into()))
+            })
+            .transpose()?;
+        let quantity = match (self.split_ratio(activity_type)?, quantity) {
+            (Some(split), Some(q)) if (q - split.ratio()).abs() > 1e-9 * split.ratio() => {
+                return Err(AppError::Validation(format!(
+                    "Quantity {} does not match a split of {} for {}. \
+                     Change one of them or clear the quantity.",
+                    format_quantity(q),
+                    split.shares_after_display(),
+                    split.shares_before_display()
+                )));
+            }
+            (Some(split), _) => Some(split.ratio()),
+            (None, quantity) => quantity,
+        };
+
+        let unit_price_cents = self
+            .unit_price
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                money::parse_amount(s, money::INPUT_LOCALE)
+                    .map_err(|_| AppError::Validation("Invalid unit price".into()))
+            })
+            .transpose()?;
+
+        let fee_cents = self
+            .fee
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                money::parse_amount(s, money::INPUT_LOCALE)
+                    .map_err(|_| AppError::Validation("Invalid fee".into()))
+            })
+            .transpose()?
+            .unwrap_or(0);
+
+        let exchange_rate = self
+            .exchange_rate
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                money::parse_quantity(s, money::INPUT_LOCALE)
+                    .map_err(|_| AppError::Validation("Invalid exchange rate".into()))
+            })
+            .transpose()?;
+        let (fee_currency, exchange_rate) =
+            normalize_fee_currency(&self.currency, self.fee_currency.as_deref(), exchange_rate)
+                .map_err(|e| AppError::Validation(e.into()))?;
+
+        Ok(NewTradingActivity {
+            date: date.to_string(),
+            symbol: self.symbol.clone(),
+            quantity,
+            activity_type,
+            unit_price_cents,
+            currency: self.currency.clone(),
+            fee_cents,
+            fee_currency,
+            exchange_rate,
+            account_id: self.account_id.flatten(),
+            notes: self.notes.clone().filter(|s| !s.is_empty()),
+        })
+    }
+
+    /// The split helper fields of a split, if any of them is filled in.
+    fn split_ratio(
+        &self,
+        activity_type: TradingActivityType,
+    ) -> Result<Option<SplitRatio>, AppError> {
+        if activity_type != TradingActivityType::Split {
+            return Ok(None);
+        }
+        let after = self.shares_after.as_deref().unwrap_or("");
+        let before = self.shares_before.as_deref().unwrap_or("");
+        if after.trim().is_empty() && before.trim().is_empty() {
+            return Ok(None);
+        }
+        SplitRatio::parse(after, before)
+            .map(Some)
+            .map_err(AppError::Validation)
+    }
+}
+
+pub async fn new_form(State(state): State<AppState>) -> AppResult<Response> {
+    render_new_form(&state, SubmittedForm::default(), None, None)
+}
+
+/// Open the new activity form filled in like activity `id`, for recording
+/// the next one of a recurring purchase: dated today, with the quantity and
+/// price left to enter.
+pub async fn duplicate_form(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Response> {
+    let conn = state.db.get()?;
+    let activity = trading::get_activity(&conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Activity {} not found", id)))?;
+    drop(conn);
+    let today = date_utils::today_in(&state.load_settings()?);
+
+    let mut pairs = vec![
+        ("date", today.format("%Y-%m-%d").to_string()),
+        ("symbol", activity.symbol.clone()),
+        ("activity_type", activity.activity_type.as_str().to_string()),
+        ("currency", activity.currency.clone()),
+        ("fee", activity.fee_display()),
+    ];
+    if let Some(account_id) = activity.account_id {
+        pairs.push(("account_id", account_id.to_string()));
+    }
+    if let Some(fee_currency) = &activity.fee_currency {
+        pairs.push(("fee_currency", fee_currency.clone()));
+        pairs.push(("exchange_rate", activity.exchange_rate_display()));
+    }
+    // The DRIP marker links the activities of one reinvestment only
+    if let (Some(notes), None) = (&activity.notes, activity.drip_group()) {
+        pairs.push(("notes", notes.clone()));
+    }
+    let values = SubmittedForm::new(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+    render_new_form(&state, values, None, Some(id))
+}
+
+/// Render the new activity form, filled with `values` and showing `error`
+/// after a failed submission.
+fn render_new_form(
+    state: &AppState,
+    values: SubmittedForm,
+    error: Option<String>,
+    duplicate_of: Option<i64>,
+) -> AppResult<Response> {
+    let conn = state.db.get()?;
+
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+    let symbols = trading::get_unique_symbols(&conn)?;
+    let securities_accounts = accounts::list_accounts_by_type(&conn, AccountType::Securities)?;
+
+    let template = TradingActivityNewTemplate {
+        title: "Add Activity".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        symbols,
+        activity_types: TradingActivityType::all(),
+        accounts: securities_accounts,
+        form_token: values
+            .token()
+            .map_or_else(|| state.submitted_forms.new_token(), str::to_string),
+        values,
+        error,
+        duplicate_of,
+    };
+
+    let has_error = template.error.is_some();
+    render_form(template, has_error)
+}
+
+pub async fn edit_form(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+
+    let activity = trading::get_activity(&conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Activity {} not found", id)))?;
+
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    let symbols = trading::get_unique_symbols(&conn)?;
+    // Keep the current account selectable even if it is not a securities account
+    let account_choices = accounts::list_accounts(&conn)?
+        .into_iter()
+        .filter(|a| a.account_type == AccountType::Securities || activity.account_id == Some(a.id))
+        .collect();
+
+    let template = TradingActivityEditTemplate {
+        title: "Edit Activity".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        split: (activity.activity_type == TradingActivityType::Split)
+            .then(|| activity.quantity.and_then(SplitRatio::from_ratio))
+            .flatten(),
+        activity,
+        symbols,
+        activity_types: TradingActivityType::all(),
+        accounts: account_choices,
+    };
+
+    template.render_html()
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    create_submitted(&state, SubmittedForm::new(pairs), None).await
+}
+
+/// Create the activity submitted from the duplicate form of activity `id`,
+/// like any new activity.
+pub async fn duplicate(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    create_submitted(&state, SubmittedForm::new(pairs), Some(id)).await
+}
+
+async fn create_submitted(
+    state: &AppState,
+    submitted: SubmittedForm,
+    duplicate_of: Option<i64>,
+) -> AppResult<Response> {
+    let _guard = lock_symbols(state, &[submitted.get("symbol").trim()]).await?;
+    let created =
+        state
+            .submitted_forms
+            .create_once(submitted.token(), "/trading/activities", || {
+                create_from_form(state, &submitted)
+            });
+    match created {
+        Ok(Some(repeated)) => Ok(repeated.into_response()),
+        Ok(None) => Ok(Redirect::to("/trading/activities").into_response()),
+        Err(AppError::Validation(message)) => {
+            render_new_form(state, submitted, Some(message), duplicate_of)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Create an activity from the new activity form, returning its URL.
+fn create_from_form(state: &AppState, submitted: &SubmittedForm) -> AppResult<String> {
+    let form: TradingActivityFormData = submitted.parse(&[]).map_err(AppError::Validation)?;
+    let id = state.with_tx(|tx| {
+        let settings = settings::get_settings(tx)?;
+        let mut new_activity = form.to_new_activity(&settings)?;
+        if form.account_id.is_none() {
+            new_activity.account_id = settings.default_trading_account_id;
+        }
+        let id = trading::create_activity(tx, &new_activity)?;
+
+        apply_split_effects(tx, id, &new_activity)?;
+        warn_about_adjusted_prices(tx, &new_activity)?;
+        trading_mirror::sync(tx, id)?;
+        Ok(id)
+    })?;
+    Ok(format!("/trading/activities/{id}"))
+}
+
+/// How far, in cents, a reinvestment may exceed the net dividend: brokers
+/// round the cost of fractional shares.
+const DRIP_TOLERANCE_CENTS: i64 = 2;
+
+#[derive(Debug, Deserialize)]
+pub struct DripFormData {
+    pub symbol: String,
+    pub date: String,
+    pub currency: String,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub account_id: Option<i64>,
+    pub gross_dividend: String,
+    pub tax: Option<String>,
+    pub quantity: String,
+    pub unit_price: String,
+    pub notes: Option<String>,
+}
+
+/// The activities of a dividend reinvestment: the gross dividend, the tax
+/// withheld from it, if any, and the buy of the reinvested shares.
+struct Drip {
+    dividend: NewTradingActivity,
+    tax: Option<NewTradingActivity>,
+    buy: NewTradingActivity,
+}
+
+impl DripFormData {
+    fn to_drip(&self, settings: &Settings) -> Result<Drip, AppError> {
+        let date =
+            date_utils::parse_user_date(&self.date, settings).map_err(AppError::Validation)?;
+        let amount = |value: &str, what: &str| {
+            money::parse_amount(value, money::INPUT_LOCALE)
+                .map_err(|_| AppError::Validation(format!("Invalid {what}")))
+        };
+        let gross_cents = amount(&self.gross_dividend, "dividend amount")?;
+        let tax_cents = match self.tax.as_deref().filter(|s| !s.is_empty()) {
+            Some(tax) => amount(tax, "tax amount")?,
+            None => 0,
+        };
+        let unit_price_cents = amount(&self.unit_price, "price")?;
+        let quantity = money::parse_quantity(&self.quantity, money::INPUT_LOCALE)
+            .map_err(|_| AppError::Validation("Invalid quantity".into()))?;
+
+        if gross_cents <= 0 {
+            return Err(AppError::Validation("The dividend must be positive".into()));
+        }
+        if tax_cents < 0 || tax_cents > gross_cents {
+            return Err(AppError::Validation(
+                "The tax must be between zero and the dividend".into(),
+            ));
+        }
+        if quantity <= 0.0 || unit_price_cents <= 0 {
+            return Err(AppError::Validation(
+                "Quantity and price must be positive".into(),
+            ));
+        }
+        check_reinvestment(gross_cents - tax_cents, quantity * unit_price_cents as f64)
+            .map_err(AppError::Validation)?;
+
+        let activity = |activity_type, quantity, unit_price_cents| NewTradingActivity {
+            date: date.to_string(),
+            symbol: self.symbol.trim().to_string(),
+            quantity,
+            activity_type,
+            unit_price_cents: Some(unit_price_cents),
+            currency: self.currency.clone(),
+            fee_cents: 0,
+            fee_currency: None,
+            exchange_rate: None,
+            account_id: self.account_id,
+            notes: None,
+        };
+        Ok(Drip {
+            dividend: activity(TradingActivityType::Dividend, None, gross_cents),
+            tax: (tax_cents > 0).then(|| activity(TradingActivityType::Tax, None, tax_cents)),
+            buy: activity(TradingActivityType::Buy, Some(quantity), unit_price_cents),
+        })
+    }
+}
+
+/// Check that the shares bought cost no more than the net dividend, give or
+/// take [`DRIP_TOLERANCE_CENTS`].
+fn check_reinvestment(net_cents: i64, reinvested_cents: f64) -> Result<(), String> {
+    let reinvested_cents = reinvested_cents.round() as i64;
+    if reinvested_cents > net_cents + DRIP_TOLERANCE_CENTS {
+        return Err(format!(
+            "The reinvested amount ({}) exceeds the net dividend ({})",
+            money::format_cents(reinvested_cents),
+            money::format_cents(net_cents)
+        ));
+    }
+    Ok(())
+}
+
+/// Record a dividend reinvestment as a dividend, the withheld tax and a buy,
+/// created together with notes that reference each other.
+pub async fn drip(
+    State(state): State<AppState>,
+    Form(form): Form<DripFormData>,
+) -> AppResult<Redirect> {
+    let position_url = format!(
+        "/trading/positions/{}",
+        urlencoding::encode(form.symbol.trim())
+    );
+    let drip = match form.to_drip(&state.load_settings()?) {
+        Ok(drip) => drip,
+        Err(AppError::Validation(message)) => {
+            flash::flash_error(message);
+            return Ok(Redirect::to(&position_url));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let _guard = lock_symbols(&state, &[drip.buy.symbol.as_str()]).await?;
+    state.with_tx(|tx| {
+        let dividend_id = trading::create_activity(tx, &drip.dividend)?;
+        let tax_id = drip
+            .tax
+            .as_ref()
+            .map(|tax| trading::create_activity(tx, tax))
+            .transpose()?;
+        let buy_id = trading::create_activity(tx, &drip.buy)?;
+        // A backdated reinvestment is bought before later splits
+        apply_split_effects(tx, buy_id, &drip.buy)?;
+        let notes = drip_note(dividend_id, tax_id, buy_id, form.notes.as_deref());
+        for id in [Some(dividend_id), tax_id, Some(buy_id)]
+            .into_iter()
+            .flatten()
+        {
+            trading::set_activity_notes(tx, id, &notes)?;
+            trading_mirror::sync(tx, id)?;
+        }
+        Ok(())
+    })?;
+
+    flash::flash_success("Dividend reinvestment recorded");
+    Ok(Redirect::to(&position_url))
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<TradingActivityFormData>,
+) -> AppResult<Redirect> {
+    let _guard = lock_activity_symbol(&state, id, Some(form.symbol.trim())).await?;
+    state.with_tx(|tx| {
+        let old_activity = trading::get_activity(tx, id)?
+            .ok_or_else(|| AppError::NotFound(format!("Activity {} not found", id)))?;
+
+        // Undo split effects from the old version of this activity.
+        undo_split_effects(tx, &old_activity)?;
+
+        let mut new_activity = form.to_new_activity(&state.load_settings()?)?;
+        if form.account_id.is_none() {
+            new_activity.account_id = old_activity.account_id;
+        }
+        trading::update_activity(tx, id, &new_activity)?;
+
+        // Apply split effects for the new version.
+        apply_split_effects(tx, id, &new_activity)?;
+        warn_about_adjusted_prices(tx, &new_activity)?;
+        trading_mirror::sync(tx, id)?;
+        Ok(())
+    })?;
+    Ok(Redirect::to("/trading/activities"))
+}
@@ -4,13 +4,25 @@ use axum::response::{Html, Redirect};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::db::queries::trading;
+use crate::body_limit;
+use crate::cache::DataDomain;
+use crate::date_utils::DateFormat;
+use crate::db::queries::{
+    api_logs, market_data, positions, split_adjustments, trading, trading_import,
+};
 use crate::error::{AppError, AppResult, RenderHtml};
+use crate::handlers::import::IMPORT_BATCH_SIZE;
 use crate::models::{
-    NewTradingActivity, Settings, TradingActivityType, TradingImportSession, TradingImportStatus,
+    ImportFileStats, NewApiLog, NewTradingActivity, PositionDelta, Settings, TradingActivityType,
+    TradingImportRow, TradingImportSession, TradingImportStatus,
 };
+use crate::services::import_overlap::OverlapFilter;
+use crate::services::market_data as market_data_service;
+use crate::services::money;
+use crate::services::notify::{self, Severity};
 use crate::services::trading_csv_parser::parse_csv;
-use crate::state::{AppState, JsManifest, PageBase};
+use crate::services::trading_mirror;
+use crate::state::{AppState, JsManifest, PageBase, SymbolValidationState};
 
 const PREVIEW_PAGE_SIZE: i64 = 50;
 
@@ -25,6 +37,7 @@ pub struct TradingImportTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
 }
 
 #[derive(Template)]
@@ -36,6 +49,7 @@ pub struct TradingImportFormatTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub activity_types: &'static [TradingActivityType],
 }
 
@@ -48,6 +62,7 @@ pub struct TradingImportWizardTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub session: TradingImportSession,
 }
 
@@ -58,6 +73,20 @@ pub struct TradingImportStatusTemplate {
     pub session: TradingImportSession,
 }
 
+#[derive(Template)]
+#[template(path = "partials/trading_import_positions_preview.html")]
+pub struct PositionsPreviewTemplate {
+    pub icons: crate::filters::Icons,
+    pub settings: Settings,
+    pub deltas: Vec<PositionDelta>,
+}
+
+impl PositionsPreviewTemplate {
+    fn negative_count(&self) -> usize {
+        self.deltas.iter().filter(|d| d.goes_negative).count()
+    }
+}
+
 #[derive(Template)]
 #[template(path = "partials/trading_import_preview_table.html")]
 pub struct TradingImportPreviewTableTemplate {
@@ -68,6 +97,14 @@ pub struct TradingImportPreviewTableTemplate {
     pub total_count: i64,
 }
 
+#[derive(Template)]
+#[template(path = "partials/trading_import_symbol_validation.html")]
+pub struct SymbolValidationTemplate {
+    pub icons: crate::filters::Icons,
+    pub session_id: String,
+    pub validation: Option<SymbolValidationState>,
+}
+
 #[derive(Template)]
 #[template(path = "partials/trading_import_result.html")]
 pub struct TradingImportResultTemplate {
@@ -93,6 +130,7 @@ pub struct StatusResponse {
     pub processed_rows: i64,
     pub error_count: i64,
     pub progress_percent: i64,
+    pub file_stats: Vec<ImportFileStats>,
 }
 
 // Handlers
@@ -104,6 +142,7 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let template = TradingImportTemplate {
@@ -113,6 +152,7 @@ pub async fn index(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     };
 
     template.render_html()
@@ -125,6 +165,7 @@ pub async fn format(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let template = TradingImportFormatTemplate {
@@ -134,6 +175,7 @@ pub async fn format(State(state): State<AppState>) -> AppResult<Html<String>> {
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         activity_types: TradingActivityType::all(),
     };
 
@@ -146,20 +188,13 @@ pub async fn upload(
 ) -> AppResult<Redirect> {
     let session_id = Uuid::new_v4().to_string();
 
-    // Create session
-    {
-        let conn = state.db.get()?;
-        trading::create_import_session(&conn, &session_id)?;
-    }
-
     // Collect files
     let mut files: Vec<(String, Vec<u8>)> = Vec::new();
 
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|e| AppError::CsvParse(e.to_string()))?
-    {
+    let limit_bytes = state.config.max_upload_bytes();
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        body_limit::multipart_error(e, limit_bytes, |e| AppError::CsvParse(e.to_string()))
+    })? {
         if field.name() == Some("files") {
             let file_name = field
                 .file_name()
@@ -169,7 +204,11 @@ pub async fn upload(
             let content = field
                 .bytes()
                 .await
-                .map_err(|e| AppError::CsvParse(e.to_string()))?
+                .map_err(|e| {
+                    body_limit::multipart_error(e, limit_bytes, |e| {
+                        AppError::CsvParse(e.to_string())
+                    })
+                })?
                 .to_vec();
 
             if !content.is_empty() {
@@ -178,10 +217,20 @@ pub async fn upload(
         }
     }
 
+    // Create session
+    {
+        let conn = state.db.get()?;
+        trading_import::create_import_session(&conn, &session_id)?;
+    }
+
     if files.is_empty() {
         let conn = state.db.get()?;
-        trading::update_import_session_status(&conn, &session_id, TradingImportStatus::Failed)?;
-        trading::update_import_session_errors(
+        trading_import::update_import_session_status(
+            &conn,
+            &session_id,
+            TradingImportStatus::Failed,
+        )?;
+        trading_import::update_import_session_errors(
             &conn,
             &session_id,
             1,
@@ -191,11 +240,13 @@ pub async fn upload(
     }
 
     // Spawn background parsing task
+    let settings = state.load_settings()?;
+    let (locale, date_format) = (settings.locale.clone(), settings.date_format());
     let state_clone = state.clone();
     let session_id_clone = session_id.clone();
 
     tokio::spawn(async move {
-        parse_files_background(state_clone, session_id_clone, files).await;
+        parse_files_background(state_clone, session_id_clone, files, locale, date_format).await;
     });
 
     Ok(Redirect::to(&format!("/trading/import/{}", session_id)))
@@ -205,26 +256,46 @@ async fn parse_files_background(
     state: AppState,
     session_id: String,
     files: Vec<(String, Vec<u8>)>,
+    locale: String,
+    date_format: DateFormat,
 ) {
     let mut all_errors: Vec<String> = Vec::new();
+    let mut file_stats: Vec<ImportFileStats> = Vec::new();
+    let mut overlap = OverlapFilter::new();
     let mut row_index: i64 = 0;
 
     for (file_name, content) in files {
-        match parse_csv(&content) {
+        overlap.start_file();
+        let mut stats = ImportFileStats {
+            file_name: file_name.clone(),
+            ..Default::default()
+        };
+        match parse_csv(&content, &locale, date_format) {
             Ok(result) => {
                 // Insert rows into database
                 if let Ok(conn) = state.db.get() {
                     for activity in result.activities {
-                        if let Err(e) =
-                            trading::insert_import_row(&conn, &session_id, row_index, &activity)
-                        {
+                        if overlap.is_duplicate(&activity) {
+                            stats.duplicates += 1;
+                            continue;
+                        }
+                        if let Err(e) = trading_import::insert_import_row(
+                            &conn,
+                            &session_id,
+                            row_index,
+                            &activity,
+                            Some(&file_name),
+                        ) {
                             all_errors.push(format!("{}: Failed to store row: {}", file_name, e));
+                            stats.errors += 1;
+                        } else {
+                            stats.rows += 1;
                         }
                         row_index += 1;
 
                         // Update progress periodically
                         if row_index % 100 == 0 {
-                            let _ = trading::update_import_session_progress(
+                            let _ = trading_import::update_import_session_progress(
                                 &conn,
                                 &session_id,
                                 row_index,
@@ -233,6 +304,7 @@ async fn parse_files_background(
                         }
                     }
 
+                    stats.errors += result.errors.len() as i64;
                     for error in result.errors {
                         all_errors.push(format!("{}: {}", file_name, error));
                     }
@@ -240,14 +312,22 @@ async fn parse_files_background(
             }
             Err(e) => {
                 all_errors.push(format!("{}: {}", file_name, e));
+                stats.errors += 1;
             }
         }
+        file_stats.push(stats);
     }
 
     // Finalize session
     if let Ok(conn) = state.db.get() {
-        let _ = trading::update_import_session_progress(&conn, &session_id, row_index, row_index);
-        let _ = trading::update_import_session_errors(
+        let _ = trading_import::update_import_session_progress(
+            &conn,
+            &session_id,
+            row_index,
+            row_index,
+        );
+        let _ = trading_import::update_import_session_file_stats(&conn, &session_id, &file_stats);
+        let _ = trading_import::update_import_session_errors(
             &conn,
             &session_id,
             all_errors.len() as i64,
@@ -255,13 +335,13 @@ async fn parse_files_background(
         );
 
         if row_index == 0 && !all_errors.is_empty() {
-            let _ = trading::update_import_session_status(
+            let _ = trading_import::update_import_session_status(
                 &conn,
                 &session_id,
                 TradingImportStatus::Failed,
             );
         } else {
-            let _ = trading::update_import_session_status(
+            let _ = trading_import::update_import_session_status(
                 &conn,
                 &session_id,
                 TradingImportStatus::Preview,
@@ -276,13 +356,14 @@ pub async fn wizard(
 ) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
 
-    let session = trading::get_import_session(&conn, &session_id)?;
+    let session = trading_import::get_import_session(&conn, &session_id)?;
     let PageBase {
         settings,
         icons,
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let template = TradingImportWizardTemplate {
@@ -292,6 +373,7 @@ pub async fn wizard(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         session,
     };
 
@@ -303,7 +385,7 @@ pub async fn status(
     Path(session_id): Path<String>,
 ) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
-    let session = trading::get_import_session(&conn, &session_id)?;
+    let session = trading_import::get_import_session(&conn, &session_id)?;
 
     let template = TradingImportStatusTemplate {
         icons: crate::filters::Icons,
@@ -317,7 +399,7 @@ pub async fn status_json(
     Path(session_id): Path<String>,
 ) -> AppResult<axum::Json<StatusResponse>> {
     let conn = state.db.get()?;
-    let session = trading::get_import_session(&conn, &session_id)?;
+    let session = trading_import::get_import_session(&conn, &session_id)?;
 
     Ok(axum::Json(StatusResponse {
         status: session.status.as_str().to_string(),
@@ -325,9 +407,52 @@ pub async fn status_json(
         processed_rows: session.processed_rows,
         error_count: session.error_count,
         progress_percent: session.progress_percent(),
+        file_stats: session.file_stats,
     }))
 }
 
+/// Position changes the pending rows would cause. Rows that fail to parse
+/// are left out; they will fail the import as well.
+fn position_deltas(
+    state: &AppState,
+    settings: &Settings,
+    session_id: &str,
+) -> AppResult<Vec<PositionDelta>> {
+    let conn = state.db.get()?;
+    let pending: Vec<NewTradingActivity> =
+        trading_import::get_pending_import_rows(&conn, session_id)?
+            .iter()
+            .filter_map(|row| row_activity(row).ok())
+            .collect();
+    Ok(positions::preview_position_changes(
+        &conn,
+        &pending,
+        settings.allow_short_positions,
+    )?)
+}
+
+pub async fn preview(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> AppResult<Html<String>> {
+    let settings = state.load_settings()?;
+    let deltas = position_deltas(&state, &settings, &session_id)?;
+    let template = PositionsPreviewTemplate {
+        icons: crate::filters::Icons,
+        settings,
+        deltas,
+    };
+    template.render_html()
+}
+
+pub async fn preview_json(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> AppResult<axum::Json<Vec<PositionDelta>>> {
+    let settings = state.load_settings()?;
+    Ok(axum::Json(position_deltas(&state, &settings, &session_id)?))
+}
+
 pub async fn rows(
     State(state): State<AppState>,
     Path(session_id): Path<String>,
@@ -338,8 +463,9 @@ pub async fn rows(
     let page = query.page.unwrap_or(1).max(1);
     let offset = (page - 1) * PREVIEW_PAGE_SIZE;
 
-    let rows = trading::get_import_rows_paginated(&conn, &session_id, PREVIEW_PAGE_SIZE, offset)?;
-    let total_count = trading::count_import_rows(&conn, &session_id)?;
+    let rows =
+        trading_import::get_import_rows_paginated(&conn, &session_id, PREVIEW_PAGE_SIZE, offset)?;
+    let total_count = trading_import::count_import_rows(&conn, &session_id)?;
 
     let template = TradingImportPreviewTableTemplate {
         session_id,
@@ -359,7 +485,7 @@ pub async fn confirm(
     // Update status to importing
     {
         let conn = state.db.get()?;
-        let session = trading::get_import_session(&conn, &session_id)?;
+        let session = trading_import::get_import_session(&conn, &session_id)?;
 
         if session.status != TradingImportStatus::Preview {
             return Err(AppError::Validation(
@@ -367,8 +493,12 @@ pub async fn confirm(
             ));
         }
 
-        trading::update_import_session_status(&conn, &session_id, TradingImportStatus::Importing)?;
-        trading::update_import_session_progress(&conn, &session_id, session.total_rows, 0)?;
+        trading_import::update_import_session_status(
+            &conn,
+            &session_id,
+            TradingImportStatus::Importing,
+        )?;
+        trading_import::update_import_session_progress(&conn, &session_id, session.total_rows, 0)?;
     }
 
     // Spawn background import task
@@ -381,7 +511,7 @@ pub async fn confirm(
 
     // Return status template for polling
     let conn = state.db.get()?;
-    let session = trading::get_import_session(&conn, &session_id)?;
+    let session = trading_import::get_import_session(&conn, &session_id)?;
 
     let template = TradingImportStatusTemplate {
         icons: crate::filters::Icons,
@@ -396,178 +526,186 @@ async fn import_rows_background(state: AppState, session_id: String) {
             Ok(c) => c,
             Err(_) => return,
         };
-        match trading::get_pending_import_rows(&conn, &session_id) {
+        match trading_import::get_pending_import_rows(&conn, &session_id) {
             Ok(r) => r,
             Err(_) => return,
         }
     };
 
-    let mut error_count = 0;
     let mut errors: Vec<String> = Vec::new();
-
-    for row in pending_rows {
-        let conn = match state.db.get() {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-
-        // Parse activity type
-        let activity_type: TradingActivityType = match row.data.activity_type.parse() {
-            Ok(t) => t,
-            Err(_) => {
-                error_count += 1;
-                errors.push(format!(
-                    "Row {}: Invalid activity type '{}'",
-                    row.row_index + 1,
-                    row.data.activity_type
-                ));
-                let _ = trading::mark_import_row_error(&conn, row.id, "Invalid activity type");
-                let _ = trading::increment_import_session_processed(&conn, &session_id);
-                let _ = trading::increment_import_session_error_count(&conn, &session_id);
-                continue;
-            }
-        };
-
-        // Parse quantity
-        let quantity: Option<f64> = match &row.data.quantity {
-            Some(q) => match q.parse() {
-                Ok(v) => Some(v),
-                Err(_) => {
-                    error_count += 1;
-                    errors.push(format!(
-                        "Row {}: Invalid quantity '{}'",
-                        row.row_index + 1,
-                        q
-                    ));
-                    let _ = trading::mark_import_row_error(&conn, row.id, "Invalid quantity");
-                    let _ = trading::increment_import_session_processed(&conn, &session_id);
-                    let _ = trading::increment_import_session_error_count(&conn, &session_id);
-                    continue;
-                }
-            },
-            None => None,
-        };
-
-        // Parse unit price
-        let unit_price_cents: Option<i64> = match &row.data.unit_price {
-            Some(p) => match p.parse::<f64>() {
-                Ok(v) => Some((v * 100.0).round() as i64),
-                Err(_) => {
-                    error_count += 1;
-                    errors.push(format!(
-                        "Row {}: Invalid unit price '{}'",
-                        row.row_index + 1,
-                        p
-                    ));
-                    let _ = trading::mark_import_row_error(&conn, row.id, "Invalid unit price");
-                    let _ = trading::increment_import_session_processed(&conn, &session_id);
-                    let _ = trading::increment_import_session_error_count(&conn, &session_id);
-                    continue;
-                }
-            },
-            None => None,
-        };
-
-        // Parse fee
-        let fee_cents: i64 = match &row.data.fee {
-            Some(f) => match f.parse::<f64>() {
-                Ok(v) => (v * 100.0).round() as i64,
-                Err(_) => {
-                    error_count += 1;
-                    errors.push(format!("Row {}: Invalid fee '{}'", row.row_index + 1, f));
-                    let _ = trading::mark_import_row_error(&conn, row.id, "Invalid fee");
-                    let _ = trading::increment_import_session_processed(&conn, &session_id);
-                    let _ = trading::increment_import_session_error_count(&conn, &session_id);
-                    continue;
-                }
-            },
-            None => 0,
-        };
-
-        let new_activity = NewTradingActivity {
-            date: row.data.date.clone(),
-            symbol: row.data.symbol.clone(),
-            quantity,
-            activity_type,
-            unit_price_cents,
-            currency: row.data.currency.clone(),
-            fee_cents,
-            account_id: row.data.account_id,
-            notes: None,
-        };
-
-        match trading::create_activity(&conn, &new_activity) {
-            Ok(id) => {
-                // Apply split adjustments for the newly imported activity.
-                let split_result = match activity_type {
-                    TradingActivityType::Split => {
-                        if let Some(ratio) = quantity {
-                            trading::apply_split_to_past_activities(
-                                &conn,
-                                id,
-                                &new_activity.symbol,
-                                &new_activity.date,
-                                ratio,
-                            )
-                        } else {
-                            Ok(())
-                        }
-                    }
-                    TradingActivityType::Buy | TradingActivityType::Sell => {
-                        trading::apply_existing_splits_to_activity(
-                            &conn,
-                            id,
-                            &new_activity.symbol,
-                            &new_activity.date,
-                        )
-                    }
-                    _ => Ok(()),
-                };
-                if let Err(e) = split_result {
-                    error_count += 1;
-                    errors.push(format!(
-                        "Row {}: Split adjustment failed: {}",
-                        row.row_index + 1,
-                        e
-                    ));
-                    let _ = trading::mark_import_row_error(
-                        &conn,
-                        row.id,
-                        &format!("Split adjustment failed: {}", e),
-                    );
-                    let _ = trading::increment_import_session_error_count(&conn, &session_id);
-                } else {
-                    let _ = trading::mark_import_row_imported(&conn, row.id);
-                }
-            }
+    let mut status = TradingImportStatus::Completed;
+    let mut failure: Option<String> = None;
+    for batch in pending_rows.chunks(IMPORT_BATCH_SIZE) {
+        let activities: Vec<_> = batch.iter().map(row_activity).collect();
+        // Wait for a refresh of the batch's symbols before adjusting splits
+        let symbols: Vec<&str> = activities
+            .iter()
+            .filter_map(|a| Some(a.as_ref().ok()?.symbol.as_str()))
+            .collect();
+        let _guard = state.symbol_locks.lock(&symbols).await;
+        match state.with_tx(|tx| import_batch(tx, &session_id, batch, &activities)) {
+            Ok(batch_errors) => errors.extend(batch_errors),
             Err(e) => {
-                error_count += 1;
-                errors.push(format!("Row {}: {}", row.row_index + 1, e));
-                let _ = trading::mark_import_row_error(&conn, row.id, &e.to_string());
-                let _ = trading::increment_import_session_error_count(&conn, &session_id);
+                tracing::error!(session_id = %session_id, error = %e, "Trading import failed");
+                failure = Some(e.to_string());
+                status = TradingImportStatus::Failed;
+                break;
             }
         }
-
-        let _ = trading::increment_import_session_processed(&conn, &session_id);
     }
 
+    // Rows were written after the request that started the import returned
+    state
+        .cache
+        .invalidate_domains(&[DataDomain::Trading, DataDomain::Transactions]);
+
     // Finalize
     if let Ok(conn) = state.db.get() {
-        let _ = trading::update_import_session_errors(&conn, &session_id, error_count, &errors);
-        let _ = trading::update_import_session_status(
+        let _ = trading_import::update_import_session_errors(
             &conn,
             &session_id,
-            TradingImportStatus::Completed,
+            errors.len() as i64,
+            &errors,
         );
+        let _ = trading_import::update_import_session_status(&conn, &session_id, status);
+    }
+
+    if let Some(error) = failure {
+        notify::notify(
+            &state,
+            "trading_import",
+            Severity::Error,
+            "Trading import failed",
+            &format!("Import {} failed: {}", session_id, error),
+        )
+        .await;
     }
 }
 
+/// Import a batch of rows in one SQL transaction, returning the messages of
+/// the rows that failed. A failed row is rolled back to a savepoint and
+/// marked; any other error aborts the batch so its transaction rolls back.
+fn import_batch(
+    conn: &rusqlite::Connection,
+    session_id: &str,
+    rows: &[TradingImportRow],
+    activities: &[Result<NewTradingActivity, (&'static str, String)>],
+) -> AppResult<Vec<String>> {
+    let mut errors = Vec::new();
+    for (row, activity) in rows.iter().zip(activities) {
+        let failure = match activity {
+            Err((reason, detail)) => Some((reason.to_string(), detail.clone())),
+            Ok(new_activity) => {
+                conn.execute_batch("SAVEPOINT import_row")?;
+                let result = import_activity(conn, row.id, new_activity);
+                if result.is_err() {
+                    conn.execute_batch("ROLLBACK TO import_row")?;
+                }
+                conn.execute_batch("RELEASE import_row")?;
+                result.err().map(|message| (message.clone(), message))
+            }
+        };
+        if let Some((reason, detail)) = failure {
+            errors.push(format!("Row {}: {}", row.row_index + 1, detail));
+            trading_import::mark_import_row_error(conn, row.id, &reason)?;
+            trading_import::increment_import_session_error_count(conn, session_id)?;
+        }
+        trading_import::increment_import_session_processed(conn, session_id)?;
+    }
+    Ok(errors)
+}
+
+/// Create the activity of an import row with its split adjustments and
+/// mirrored transaction, and mark the row imported. Errors carry the message
+/// stored on the row; the caller rolls back whatever was written before.
+fn import_activity(
+    conn: &rusqlite::Connection,
+    row_id: i64,
+    activity: &NewTradingActivity,
+) -> Result<(), String> {
+    let id = trading::create_activity(conn, activity).map_err(|e| e.to_string())?;
+    let split_result = match activity.activity_type {
+        TradingActivityType::Split => match activity.quantity {
+            Some(ratio) => split_adjustments::apply_split_to_past_activities(
+                conn,
+                id,
+                &activity.symbol,
+                &activity.date,
+                ratio,
+            ),
+            None => Ok(()),
+        },
+        t if t.affects_holdings() => split_adjustments::apply_existing_splits_to_activity(
+            conn,
+            id,
+            &activity.symbol,
+            &activity.date,
+        ),
+        _ => Ok(()),
+    };
+    split_result.map_err(|e| format!("Split adjustment failed: {}", e))?;
+    trading_mirror::sync(conn, id).map_err(|e| format!("Mirroring failed: {}", e))?;
+    trading_import::mark_import_row_imported(conn, row_id).map_err(|e| e.to_string())
+}
+
+/// The activity an import row would create. Errors carry the short reason
+/// stored on the row and the message shown in the import result.
+fn row_activity(row: &TradingImportRow) -> Result<NewTradingActivity, (&'static str, String)> {
+    let data = &row.data;
+    let activity_type: TradingActivityType = data.activity_type.parse().map_err(|_| {
+        (
+            "Invalid activity type",
+            format!("Invalid activity type '{}'", data.activity_type),
+        )
+    })?;
+    let quantity = data
+        .quantity
+        .as_deref()
+        .map(|q| {
+            money::parse_quantity(q, money::INPUT_LOCALE)
+                .map_err(|_| ("Invalid quantity", format!("Invalid quantity '{}'", q)))
+        })
+        .transpose()?;
+    let unit_price_cents = data
+        .unit_price
+        .as_deref()
+        .map(|p| {
+            money::parse_amount(p, money::INPUT_LOCALE)
+                .map_err(|_| ("Invalid unit price", format!("Invalid unit price '{}'", p)))
+        })
+        .transpose()?;
+    let fee_cents = data
+        .fee
+        .as_deref()
+        .map(|f| {
+            money::parse_amount(f, money::INPUT_LOCALE)
+                .map_err(|_| ("Invalid fee", format!("Invalid fee '{}'", f)))
+        })
+        .transpose()?
+        .unwrap_or(0);
+
+    Ok(NewTradingActivity {
+        date: data.date.clone(),
+        symbol: data.symbol.clone(),
+        quantity,
+        activity_type,
+        unit_price_cents,
+        currency: data.currency.clone(),
+        fee_cents,
+        // Both were validated and normalized when the CSV was parsed
+        fee_currency: data.fee_currency.clone(),
+        exchange_rate: data.exchange_rate.as_deref().and_then(|r| r.parse().ok()),
+        account_id: data.account_id,
+        notes: None,
+    })
+}
+
 pub async fn result(
     State(state): State<AppState>,
     Path(session_id): Path<String>,
 ) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
-    let session = trading::get_import_session(&conn, &session_id)?;
+    let session = trading_import::get_import_session(&conn, &session_id)?;
 
     let template = TradingImportResultTemplate {
         icons: crate::filters::Icons,
@@ -584,6 +722,217 @@ pub async fn cancel(
     Path(session_id): Path<String>,
 ) -> AppResult<Redirect> {
     let conn = state.db.get()?;
-    trading::delete_import_session(&conn, &session_id)?;
+    trading_import::delete_import_session(&conn, &session_id)?;
+    // Removing the entry also stops any running symbol validation
+    state.symbol_validation.lock().unwrap().remove(&session_id);
     Ok(Redirect::to("/trading/import"))
 }
+
+// Symbol validation
+
+fn symbol_validation_html(state: &AppState, session_id: String) -> AppResult<Html<String>> {
+    let validation = state
+        .symbol_validation
+        .lock()
+        .unwrap()
+        .get(&session_id)
+        .cloned();
+    SymbolValidationTemplate {
+        icons: crate::filters::Icons,
+        session_id,
+        validation,
+    }
+    .render_html()
+}
+
+/// Start an advisory check of all pending row symbols against Yahoo Finance.
+pub async fn validate_symbols(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> AppResult<Html<String>> {
+    let rows = {
+        let conn = state.db.get()?;
+        let session = trading_import::get_import_session(&conn, &session_id)?;
+        if session.status != TradingImportStatus::Preview {
+            return Err(AppError::Validation(
+                "Session is not ready for validation".into(),
+            ));
+        }
+        trading_import::get_pending_import_rows(&conn, &session_id)?
+    };
+
+    let symbols: Vec<String> = rows
+        .iter()
+        .map(|r| r.data.symbol.clone())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    {
+        let mut store = state.symbol_validation.lock().unwrap();
+        if store.get(&session_id).is_some_and(|v| v.is_running) {
+            drop(store);
+            return symbol_validation_html(&state, session_id);
+        }
+        store.insert(
+            session_id.clone(),
+            SymbolValidationState {
+                is_running: true,
+                total_symbols: symbols.len(),
+                current_symbol: symbols.first().cloned(),
+                ..Default::default()
+            },
+        );
+    }
+
+    let state_clone = state.clone();
+    let session_id_clone = session_id.clone();
+    tokio::spawn(async move {
+        validate_symbols_background(state_clone, session_id_clone, symbols, rows).await;
+    });
+
+    symbol_validation_html(&state, session_id)
+}
+
+pub async fn validation_status(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> AppResult<axum::response::Response> {
+    use axum::response::IntoResponse;
+
+    let finished = state
+        .symbol_validation
+        .lock()
+        .unwrap()
+        .get(&session_id)
+        .is_some_and(|v| !v.is_running);
+    let html = symbol_validation_html(&state, session_id)?;
+
+    // Tell the preview table to reload so the new warnings show up
+    if finished {
+        Ok(([("hx-trigger", "symbols-validated")], html).into_response())
+    } else {
+        Ok(html.into_response())
+    }
+}
+
+pub async fn cancel_validation(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> AppResult<Html<String>> {
+    if let Some(v) = state.symbol_validation.lock().unwrap().get_mut(&session_id) {
+        v.cancelled = true;
+    }
+    symbol_validation_html(&state, session_id)
+}
+
+async fn validate_symbols_background(
+    state: AppState,
+    session_id: String,
+    symbols: Vec<String>,
+    rows: Vec<TradingImportRow>,
+) {
+    for (i, symbol) in symbols.iter().enumerate() {
+        {
+            let mut store = state.symbol_validation.lock().unwrap();
+            let Some(v) = store.get_mut(&session_id) else {
+                break;
+            };
+            if v.cancelled {
+                break;
+            }
+            v.current_symbol = Some(symbol.clone());
+        }
+
+        let (warning, used_network) = check_symbol(&state, symbol).await;
+
+        if let Ok(conn) = state.db.get() {
+            for row in rows.iter().filter(|r| &r.data.symbol == symbol) {
+                let _ = trading_import::set_import_row_warning(&conn, row.id, warning.as_deref());
+            }
+        }
+
+        if let Some(v) = state.symbol_validation.lock().unwrap().get_mut(&session_id) {
+            v.processed_symbols = i + 1;
+            if warning.is_some() {
+                v.unknown_symbols += 1;
+            }
+        }
+
+        // Rate limiting between lookups that hit the API
+        if used_network {
+            tokio::time::sleep(state.market_data_delay()).await;
+        }
+    }
+
+    if let Some(v) = state.symbol_validation.lock().unwrap().get_mut(&session_id) {
+        v.is_running = false;
+        v.current_symbol = None;
+    }
+}
+
+/// Check one symbol, using cached metadata when available.
+/// Returns the row warning (None if the symbol is known) and whether the API was called.
+async fn check_symbol(state: &AppState, symbol: &str) -> (Option<String>, bool) {
+    if let Ok(conn) = state.db.get() {
+        if let Ok(Some(_)) = market_data::get_symbol_metadata(&conn, symbol) {
+            return (None, false);
+        }
+    }
+
+    let start_time = std::time::Instant::now();
+    let result = market_data_service::search_symbols(symbol).await;
+    let duration_ms = start_time.elapsed().as_millis() as i64;
+
+    let Ok(conn) = state.db.get() else {
+        return (None, true);
+    };
+    let (status, summary) = match &result {
+        Ok(results) => ("success", format!("Found {} matches", results.len())),
+        Err(e) => ("error", e.to_string()),
+    };
+    let _ = api_logs::insert_api_log(
+        &conn,
+        &NewApiLog {
+            api_name: "yahoo_finance".to_string(),
+            action: "validate_symbol".to_string(),
+            symbol: Some(symbol.to_string()),
+            request_params: serde_json::json!({ "symbol": symbol }).to_string(),
+            status: status.to_string(),
+            response_summary: Some(summary),
+            response_details: None,
+            duration_ms: Some(duration_ms),
+            retried_from: None,
+        },
+    );
+
+    // Lookup failures are not the symbol's fault; leave the row unannotated
+    let Ok(results) = result else {
+        return (None, true);
+    };
+    let suggestion = market_data_service::suggest_symbol(symbol, &results);
+    match market_data_service::find_exact_match(symbol, results) {
+        Some(meta) => {
+            let _ = market_data::upsert_symbol_metadata(
+                &conn,
+                symbol,
+                meta.short_name.as_deref(),
+                meta.long_name.as_deref(),
+                Some(&meta.exchange),
+                Some(&meta.quote_type),
+            );
+            (None, true)
+        }
+        None => (Some(unknown_symbol_warning(symbol, suggestion)), true),
+    }
+}
+
+fn unknown_symbol_warning(symbol: &str, suggestion: Option<String>) -> String {
+    match suggestion {
+        Some(s) => format!(
+            "Symbol '{}' not found on Yahoo Finance. Did you mean {}?",
+            symbol, s
+        ),
+        None => format!("Symbol '{}' not found on Yahoo Finance", symbol),
+    }
+}
@@ -1,18 +1,20 @@
 use askama::Template;
-use axum::extract::{Path, Query, State};
+use axum::extract::{Query, State};
 use axum::response::Html;
 use axum::Json;
 use chrono::NaiveDate;
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::collections::HashMap;
 
-use crate::db::queries::{market_data, trading};
-use crate::error::{AppResult, RenderHtml};
-use crate::filters;
+use crate::date_utils::{self, DateFilterable, DatePreset, DateRange};
+use crate::db::queries::{market_data, positions, trading};
+use crate::error::{AppError, AppResult, RenderHtml};
 use crate::models::trading::{
     ClosedPosition, PositionWithMarketData, TradingActivity, TradingActivityType,
 };
-use crate::models::{MarketData, Position, Settings};
+use crate::models::{Position, Settings};
+use crate::services::cash_ledger;
 use crate::services::xirr::{calculate_xirr, CashFlow};
 use crate::sort_utils::{SortDirection, Sortable, SortableColumn, TableSort};
 use crate::state::{AppState, JsManifest, PageBase};
@@ -105,6 +107,36 @@ impl SortableColumn for ClosedPositionSortColumn {
 pub struct PositionFilterParams {
     pub sort: Option<String>,
     pub dir: Option<String>,
+    /// Export format; only `csv` is supported.
+    pub format: Option<String>,
+    /// `1` lists dust positions individually instead of as one summary row.
+    pub show_dust: Option<String>,
+    /// Currency of the totals; defaults to the currency from the settings.
+    pub display_currency: Option<String>,
+}
+
+impl PositionFilterParams {
+    pub fn shows_dust(&self) -> bool {
+        self.show_dust.as_deref() == Some("1")
+    }
+
+    fn requested_currency(&self) -> Option<&str> {
+        self.display_currency
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+    }
+
+    /// The currency to show cost, value and totals in.
+    pub fn display_currency(&self, settings: &Settings) -> AppResult<String> {
+        match self.requested_currency() {
+            None => Ok(settings.currency.clone()),
+            Some(c) if c.len() == 3 && c.chars().all(|ch| ch.is_ascii_alphabetic()) => {
+                Ok(c.to_uppercase())
+            }
+            Some(c) => Err(AppError::Validation(format!("Invalid currency: {}", c))),
+        }
+    }
 }
 
 impl Sortable for PositionFilterParams {
@@ -117,8 +149,138 @@ impl Sortable for PositionFilterParams {
     }
 }
 
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct ClosedPositionFilterParams {
+    pub sort: Option<String>,
+    pub dir: Option<String>,
+    /// Export format; only `csv` is supported.
+    pub format: Option<String>,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub preset: Option<String>,
+}
+
+impl Sortable for ClosedPositionFilterParams {
+    fn sort_by(&self) -> Option<&String> {
+        self.sort.as_ref()
+    }
+
+    fn sort_dir(&self) -> Option<&String> {
+        self.dir.as_ref()
+    }
+}
+
+impl DateFilterable for ClosedPositionFilterParams {
+    fn from_date(&self) -> Option<&String> {
+        self.from_date.as_ref()
+    }
+
+    fn to_date(&self) -> Option<&String> {
+        self.to_date.as_ref()
+    }
+
+    fn preset(&self) -> Option<&String> {
+        self.preset.as_ref()
+    }
+}
+
+/// Closed positions whose holding period ended in `date_range`; the "All"
+/// preset lists each symbol that is closed now over its whole lifetime.
+pub(crate) fn closed_positions_in(
+    conn: &Connection,
+    allow_short: bool,
+    date_range: &DateRange,
+) -> AppResult<Vec<ClosedPosition>> {
+    if date_range.is_preset(&DatePreset::All) {
+        return Ok(positions::get_closed_positions(conn, allow_short, None)?);
+    }
+    let (from, to) = (date_range.from_str(), date_range.to_str());
+    Ok(positions::get_closed_positions(
+        conn,
+        allow_short,
+        Some((&from, &to)),
+    )?)
+}
+
+/// Attach the latest known price to a position: stored market data if
+/// available, scaled and converted into the position's currency, else the
+/// last BUY/SELL price as an approximation.
+pub fn enrich_position(
+    conn: &Connection,
+    pos: Position,
+    settings: &Settings,
+) -> PositionWithMarketData {
+    let mut enriched = if let Ok(Some(data)) = market_data::get_latest_price(conn, &pos.symbol) {
+        let factor = market_data::get_price_factor(conn, &pos.symbol, &pos.currency, &data.date)
+            .unwrap_or(1.0);
+        let price_cents = (data.close_price_cents as f64 * factor).round() as i64;
+        PositionWithMarketData::with_market_data(pos, price_cents, data.date)
+    } else if let Ok(Some((price_cents, date))) = trading::get_last_trade_price(conn, &pos.symbol) {
+        PositionWithMarketData::with_approximated_price(pos, price_cents, date)
+    } else {
+        PositionWithMarketData::from_position(pos)
+    };
+    enriched.mark_staleness(
+        date_utils::today_in(settings),
+        settings.price_staleness_days,
+    );
+    enriched
+}
+
+/// Convert each position into `currency` at the latest exchange rate, looked
+/// up once per currency. Positions without a rate are left unconverted.
+pub(crate) fn convert_positions(
+    conn: &Connection,
+    positions: &mut [PositionWithMarketData],
+    currency: &str,
+) -> AppResult<()> {
+    let mut rates: HashMap<String, Option<f64>> = HashMap::new();
+    for pos in positions {
+        if pos.position.currency.eq_ignore_ascii_case(currency) {
+            continue;
+        }
+        let rate = match rates.get(&pos.position.currency) {
+            Some(rate) => *rate,
+            None => {
+                let rate = market_data::get_fx_rate(conn, &pos.position.currency, currency, None)?;
+                rates.insert(pos.position.currency.clone(), rate);
+                rate
+            }
+        };
+        if let Some(rate) = rate {
+            pos.convert(currency, rate);
+        }
+    }
+    Ok(())
+}
+
+/// Number of open positions valued with a stale or approximated price,
+/// not counting symbols left out of market data refreshes.
+pub fn count_stale_positions(conn: &Connection, settings: &Settings) -> AppResult<usize> {
+    let ignored = market_data::get_ignored_symbols(conn)?;
+    Ok(
+        positions::get_positions(conn, settings.allow_short_positions)?
+            .into_iter()
+            .filter(|pos| !ignored.contains(&pos.symbol))
+            .map(|pos| enrich_position(conn, pos, settings))
+            .filter(|p| p.is_stale)
+            .count(),
+    )
+}
+
+/// Current value of a position in the display currency, or its cost when
+/// no price is known.
+pub fn position_value_cents(position: &PositionWithMarketData) -> i64 {
+    position
+        .display_value_cents()
+        .unwrap_or_else(|| position.display_cost_cents())
+}
+
 /// Sort positions in-memory based on sort configuration.
-fn sort_positions(positions: &mut [PositionWithMarketData], sort: &TableSort<PositionSortColumn>) {
+pub(crate) fn sort_positions(
+    positions: &mut [PositionWithMarketData],
+    sort: &TableSort<PositionSortColumn>,
+) {
     positions.sort_by(|a, b| {
         let cmp = match sort.column {
             PositionSortColumn::Symbol => a.position.symbol.cmp(&b.position.symbol),
@@ -132,12 +294,11 @@ fn sort_positions(positions: &mut [PositionWithMarketData], sort: &TableSort<Pos
                 .position
                 .average_cost_cents()
                 .cmp(&b.position.average_cost_cents()),
-            PositionSortColumn::TotalCost => a
-                .position
-                .total_cost_cents
-                .cmp(&b.position.total_cost_cents),
-            PositionSortColumn::Value => a.current_value_cents.cmp(&b.current_value_cents),
-            PositionSortColumn::GainLoss => a.gain_loss_cents.cmp(&b.gain_loss_cents),
+            PositionSortColumn::TotalCost => a.display_cost_cents().cmp(&b.display_cost_cents()),
+            PositionSortColumn::Value => a.display_value_cents().cmp(&b.display_value_cents()),
+            PositionSortColumn::GainLoss => a
+                .display_gain_loss_cents()
+                .cmp(&b.display_gain_loss_cents()),
         };
 
         match sort.direction {
@@ -148,7 +309,7 @@ fn sort_positions(positions: &mut [PositionWithMarketData], sort: &TableSort<Pos
 }
 
 /// Sort closed positions in-memory based on sort configuration.
-fn sort_closed_positions(
+pub(crate) fn sort_closed_positions(
     positions: &mut [ClosedPosition],
     sort: &TableSort<ClosedPositionSortColumn>,
 ) {
@@ -172,6 +333,21 @@ fn sort_closed_positions(
     });
 }
 
+/// Positions below the dust threshold, shown as one collapsed row.
+pub struct DustSummary {
+    pub count: usize,
+    pub threshold_formatted: String,
+    pub total_cost_formatted: String,
+    pub value_formatted: String,
+}
+
+/// A cash ledger account whose cash went negative, see `services::cash_ledger`.
+pub struct CashDriftWarning {
+    pub account_name: String,
+    pub date: String,
+    pub balance_formatted: String,
+}
+
 #[derive(Template)]
 #[template(path = "pages/trading_positions.html")]
 pub struct TradingPositionsTemplate {
@@ -181,8 +357,31 @@ pub struct TradingPositionsTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub positions: Vec<Position>,
     pub security_positions: Vec<PositionWithMarketData>,
+    pub short_positions: Vec<PositionWithMarketData>,
+    /// Collapsed dust positions; `None` when there are none or they are expanded.
+    pub dust: Option<DustSummary>,
+    /// Number of dust positions listed in `security_positions`.
+    pub expanded_dust_count: usize,
+    /// Query string that keeps dust positions expanded and the display
+    /// currency in sort links.
+    pub extra_query: String,
+    /// `display_currency=...` if one was picked, for the other links.
+    pub currency_query: String,
+    pub display_currency: String,
+    /// Currencies the totals can be shown in.
+    pub currency_choices: Vec<String>,
+    /// Positions without an exchange rate into the display currency, left
+    /// out of the totals.
+    pub unconverted_positions: Vec<PositionWithMarketData>,
+    pub oversold_warnings: Vec<positions::OversoldWarning>,
+    pub cash_drift_warnings: Vec<CashDriftWarning>,
+    /// Number of positions valued with a stale or approximated price.
+    pub stale_price_count: usize,
+    /// Fee and tax totals per symbol
+    pub fee_totals: Vec<trading::FeeTaxTotal>,
     pub total_current_value: Option<i64>,
     pub total_current_value_formatted: Option<String>,
     pub total_current_value_color: &'static str,
@@ -214,47 +413,68 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
-    let sort: TableSort<PositionSortColumn> = params.resolve_sort();
+    let sort: TableSort<PositionSortColumn> =
+        params.resolve_sort_or(settings.default_sort("positions"));
+    let display_currency = params.display_currency(&settings)?;
 
-    let all_positions = trading::get_positions(&conn)?;
+    let (all_positions, oversold_warnings) =
+        positions::get_positions_with_warnings(&conn, settings.allow_short_positions)?;
+
+    let ledgers = cash_ledger::replay(&conn)?;
+    let cash_drift_warnings: Vec<CashDriftWarning> = state
+        .cached_accounts()?
+        .iter()
+        .filter_map(|account| {
+            let overdraft = ledgers.accounts.get(&account.id)?.overdraft.as_ref()?;
+            Some(CashDriftWarning {
+                account_name: account.name.clone(),
+                date: overdraft.date.clone(),
+                balance_formatted: settings.format_money_balance(&overdraft.balance_cents),
+            })
+        })
+        .collect();
 
     // Enrich positions with market data
-    let mut security_positions: Vec<PositionWithMarketData> = all_positions
+    let mut enriched_positions: Vec<PositionWithMarketData> = all_positions
         .iter()
         .cloned()
-        .map(|pos| {
-            // First try to get actual market data
-            if let Ok(Some(data)) = market_data::get_latest_price(&conn, &pos.symbol) {
-                return PositionWithMarketData::with_market_data(
-                    pos,
-                    data.close_price_cents,
-                    data.date,
-                );
-            }
-            // Fall back to last BUY/SELL price as approximation
-            if let Ok(Some((price_cents, date))) = trading::get_last_trade_price(&conn, &pos.symbol)
-            {
-                return PositionWithMarketData::with_approximated_price(pos, price_cents, date);
-            }
-            // No price data available
-            PositionWithMarketData::from_position(pos)
-        })
+        .map(|pos| enrich_position(&conn, pos, &settings))
         .collect();
+    convert_positions(&conn, &mut enriched_positions, &display_currency)?;
 
     // Sort positions
-    sort_positions(&mut security_positions, &sort);
+    sort_positions(&mut enriched_positions, &sort);
+    // Ignored symbols are never refreshed, so their prices don't count as stale
+    let ignored = market_data::get_ignored_symbols(&conn)?;
+    let stale_price_count = enriched_positions
+        .iter()
+        .filter(|p| p.is_stale && !ignored.contains(&p.position.symbol))
+        .count();
+
+    // Short positions get their own table and are left out of the totals
+    let (short_positions, security_positions): (Vec<_>, Vec<_>) = enriched_positions
+        .iter()
+        .cloned()
+        .partition(|p| p.position.is_short());
+
+    // Positions that can't be converted are listed apart, not summed up
+    let (security_positions, unconverted_positions): (Vec<_>, Vec<_>) =
+        security_positions.into_iter().partition(|p| {
+            p.converted.is_some() || p.position.currency.eq_ignore_ascii_case(&display_currency)
+        });
 
     // Calculate totals
     let total_cost: i64 = security_positions
         .iter()
-        .map(|p| p.position.total_cost_cents)
+        .map(PositionWithMarketData::display_cost_cents)
         .sum();
 
     let total_current_value: Option<i64> = {
         let values: Vec<_> = security_positions
             .iter()
-            .filter_map(|p| p.current_value_cents)
+            .filter_map(PositionWithMarketData::display_value_cents)
             .collect();
         if !values.is_empty() {
             Some(values.iter().sum())
@@ -265,6 +485,35 @@ pub async fn index(
 
     let total_gain_loss = total_current_value.map(|cv| cv - total_cost);
 
+    // Positions worth less than the dust threshold are collapsed into one
+    // row. They stay open and count towards the totals above.
+    let (dust_positions, mut security_positions): (Vec<_>, Vec<_>) = security_positions
+        .into_iter()
+        .partition(|p| settings.is_dust(position_value_cents(p)));
+    let show_dust = params.shows_dust();
+    let expanded_dust_count = if show_dust { dust_positions.len() } else { 0 };
+    let dust = if show_dust || dust_positions.is_empty() {
+        None
+    } else {
+        let dust_cost: i64 = dust_positions
+            .iter()
+            .map(PositionWithMarketData::display_cost_cents)
+            .sum();
+        let dust_value: i64 = dust_positions.iter().map(position_value_cents).sum();
+        Some(DustSummary {
+            count: dust_positions.len(),
+            threshold_formatted: settings.format_money_plain(&settings.dust_threshold_cents),
+            total_cost_formatted: settings
+                .format_money_neutral_with_currency(&dust_cost, &display_currency),
+            value_formatted: settings
+                .format_money_balance_with_currency(&dust_value, &display_currency),
+        })
+    };
+    if show_dust {
+        security_positions.extend(dust_positions);
+        sort_positions(&mut security_positions, &sort);
+    }
+
     // Compute gain/loss display values
     let total_gain_loss_color = match total_gain_loss {
         Some(gl) if gl > 0 => "text-green-600 dark:text-green-400",
@@ -272,16 +521,14 @@ pub async fn index(
         _ => "text-neutral-600 dark:text-neutral-400",
     };
 
-    let currency = settings.currency.clone();
-    let locale = settings.locale.clone();
-
     let total_gain_loss_formatted =
-        total_gain_loss.map(|gl| filters::format_money_plain(gl, &currency, &locale));
+        total_gain_loss.map(|gl| settings.format_money_plain_with_currency(&gl, &display_currency));
 
-    let total_cost_formatted = filters::format_money_neutral(total_cost, &currency, &locale);
+    let total_cost_formatted =
+        settings.format_money_neutral_with_currency(&total_cost, &display_currency);
 
-    let total_current_value_formatted =
-        total_current_value.map(|val| filters::format_money_balance(val, &currency, &locale));
+    let total_current_value_formatted = total_current_value
+        .map(|val| settings.format_money_balance_with_currency(&val, &display_currency));
 
     let total_current_value_color = match total_current_value {
         Some(v) if v > 0 => "text-green-600 dark:text-green-400",
@@ -290,12 +537,14 @@ pub async fn index(
     };
 
     // Compute hero stats: realized G/L from closed positions, plus portfolio-wide fees/taxes
-    let closed_positions = trading::get_closed_positions(&conn)?;
+    let closed_positions =
+        positions::get_closed_positions(&conn, settings.allow_short_positions, None)?;
     let total_realized_gl: i64 = closed_positions
         .iter()
         .map(|p| p.realized_gain_loss_cents)
         .sum();
     let (total_fees_cents, total_taxes_cents) = trading::get_portfolio_fee_tax_totals(&conn)?;
+    let fee_totals = trading::get_fee_tax_totals(&conn, trading::FeeGrouping::Symbol)?;
     let has_closed_positions =
         !closed_positions.is_empty() || total_fees_cents != 0 || total_taxes_cents != 0;
 
@@ -307,10 +556,13 @@ pub async fn index(
             ..Default::default()
         },
     )?;
-    let (portfolio_xirr, portfolio_xirr_incomplete) =
-        calculate_portfolio_xirr(&all_activities, &security_positions);
-    let portfolio_xirr_formatted =
-        portfolio_xirr.map(|x| filters::format_percent(x * 100.0, &settings.locale));
+    let (portfolio_xirr, portfolio_xirr_incomplete) = calculate_portfolio_xirr(
+        &all_activities,
+        &enriched_positions,
+        settings.xirr_transfers_at_cost(),
+        date_utils::today_in(&settings),
+    );
+    let portfolio_xirr_formatted = portfolio_xirr.map(|x| settings.format_percent_val(x * 100.0));
     let portfolio_xirr_color = xirr_color(portfolio_xirr, portfolio_xirr_incomplete);
 
     let total_realized_gl_color = if total_realized_gl > 0 {
@@ -321,11 +573,26 @@ pub async fn index(
         "text-neutral-600 dark:text-neutral-400"
     };
 
-    let total_realized_gl_formatted =
-        filters::format_money_plain(total_realized_gl, &currency, &locale);
-    let total_fees_formatted = filters::format_money_neutral(total_fees_cents, &currency, &locale);
-    let total_taxes_formatted =
-        filters::format_money_neutral(total_taxes_cents, &currency, &locale);
+    let total_realized_gl_formatted = settings.format_money_plain(&total_realized_gl);
+    let total_fees_formatted = settings.format_money_neutral(&total_fees_cents);
+    let total_taxes_formatted = settings.format_money_neutral(&total_taxes_cents);
+
+    let mut currency_choices: Vec<String> = all_positions
+        .iter()
+        .map(|p| p.currency.to_uppercase())
+        .chain([settings.currency.to_uppercase(), display_currency.clone()])
+        .collect();
+    currency_choices.sort_unstable();
+    currency_choices.dedup();
+    let currency_query = match params.requested_currency() {
+        Some(_) => format!("display_currency={}", display_currency),
+        None => String::new(),
+    };
+    let extra_query = [if show_dust { "show_dust=1" } else { "" }, &currency_query]
+        .into_iter()
+        .filter(|q| !q.is_empty())
+        .collect::<Vec<_>>()
+        .join("&");
 
     let template = TradingPositionsTemplate {
         title: "Positions".into(),
@@ -334,8 +601,21 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         positions: all_positions,
         security_positions,
+        short_positions,
+        dust,
+        expanded_dust_count,
+        extra_query,
+        currency_query,
+        display_currency,
+        currency_choices,
+        unconverted_positions,
+        oversold_warnings,
+        cash_drift_warnings,
+        stale_price_count,
+        fee_totals,
         total_current_value,
         total_current_value_formatted,
         total_current_value_color,
@@ -369,6 +649,7 @@ pub struct ClosedPositionsTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub positions: Vec<ClosedPosition>,
     pub total_cost: i64,
     pub total_cost_formatted: String,
@@ -385,11 +666,13 @@ pub struct ClosedPositionsTemplate {
     pub total_taxes_formatted: String,
     pub closed_xirr_formatted: Option<String>,
     pub closed_xirr_color: &'static str,
+    pub date_range: DateRange,
+    pub presets: &'static [DatePreset],
 }
 
 pub async fn closed_positions(
     State(state): State<AppState>,
-    Query(params): Query<PositionFilterParams>,
+    Query(params): Query<ClosedPositionFilterParams>,
 ) -> AppResult<Html<String>> {
     let conn = state.db.get()?;
 
@@ -399,10 +682,15 @@ pub async fn closed_positions(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
-    let sort: TableSort<ClosedPositionSortColumn> = params.resolve_sort();
+    let sort: TableSort<ClosedPositionSortColumn> =
+        params.resolve_sort_or(settings.default_sort("closed_positions"));
+    let date_range = params
+        .resolve_date_range(date_utils::today_in(&settings))
+        .resolve_all(trading::date_extent(&conn)?);
 
-    let mut positions = trading::get_closed_positions(&conn)?;
+    let mut positions = closed_positions_in(&conn, settings.allow_short_positions, &date_range)?;
 
     // Sort positions
     sort_closed_positions(&mut positions, &sort);
@@ -431,19 +719,14 @@ pub async fn closed_positions(
         "text-neutral-600 dark:text-neutral-400"
     };
 
-    let currency = &settings.currency;
-    let locale = &settings.locale;
-
-    let total_cost_formatted = filters::format_money_neutral(total_cost, currency, locale);
-    let total_proceeds_formatted = filters::format_money_neutral(total_proceeds, currency, locale);
-    let total_gain_loss_formatted = filters::format_money_plain(total_gain_loss, currency, locale);
-    let total_realized_gl_formatted =
-        filters::format_money_plain(total_realized_gl, currency, locale);
-    let total_fees_formatted = filters::format_money_neutral(total_fees_cents, currency, locale);
-    let total_taxes_formatted = filters::format_money_neutral(total_taxes_cents, currency, locale);
+    let total_cost_formatted = settings.format_money_neutral(&total_cost);
+    let total_proceeds_formatted = settings.format_money_neutral(&total_proceeds);
+    let total_gain_loss_formatted = settings.format_money_plain(&total_gain_loss);
+    let total_realized_gl_formatted = settings.format_money_plain(&total_realized_gl);
+    let total_fees_formatted = settings.format_money_neutral(&total_fees_cents);
+    let total_taxes_formatted = settings.format_money_neutral(&total_taxes_cents);
 
     // XIRR for closed positions
-    let closed_symbols: HashSet<String> = positions.iter().map(|p| p.symbol.clone()).collect();
     let all_activities = trading::list_activities(
         &conn,
         &trading::TradingActivityFilter {
@@ -451,8 +734,12 @@ pub async fn closed_positions(
             ..Default::default()
         },
     )?;
-    let closed_xirr = calculate_closed_portfolio_xirr(&all_activities, &closed_symbols);
-    let closed_xirr_formatted = closed_xirr.map(|x| filters::format_percent(x * 100.0, locale));
+    let closed_xirr = calculate_closed_portfolio_xirr(
+        &all_activities,
+        &positions,
+        settings.xirr_transfers_at_cost(),
+    );
+    let closed_xirr_formatted = closed_xirr.map(|x| settings.format_percent_val(x * 100.0));
     let closed_xirr_color = xirr_color(closed_xirr, false);
 
     let template = ClosedPositionsTemplate {
@@ -462,6 +749,7 @@ pub async fn closed_positions(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         positions,
         total_cost,
         total_cost_formatted,
@@ -478,204 +766,53 @@ pub async fn closed_positions(
         total_taxes_formatted,
         closed_xirr_formatted,
         closed_xirr_color,
+        date_range,
+        presets: DatePreset::all(),
     };
 
     template.render_html()
 }
 
-// Position detail page
-
-/// Symbol metadata for display
-#[derive(Debug, Clone, Default)]
-pub struct SymbolInfo {
-    pub short_name: Option<String>,
-    pub long_name: Option<String>,
-    pub exchange: Option<String>,
-    pub quote_type: Option<String>,
-}
-
-impl SymbolInfo {
-    pub fn display_name(&self) -> Option<&String> {
-        self.long_name.as_ref().or(self.short_name.as_ref())
-    }
-
-    pub fn display_name_str(&self) -> &str {
-        self.display_name().map(|s| s.as_str()).unwrap_or("")
-    }
+#[derive(Debug, Deserialize)]
+pub struct FeeParams {
+    /// `symbol` (default), `year` or `account`
+    pub group_by: Option<String>,
 }
 
-#[derive(Template)]
-#[template(path = "pages/position_detail.html")]
-pub struct PositionDetailTemplate {
-    pub title: String,
-    pub settings: Settings,
-    pub icons: crate::filters::Icons,
-    pub manifest: JsManifest,
-    pub version: &'static str,
-    pub xsrf_token: String,
-    pub symbol: String,
-    pub symbol_info: SymbolInfo,
-    pub position: Option<PositionWithMarketData>,
-    pub activities: Vec<TradingActivity>,
-    pub total_activity_count: usize,
-    pub xirr: Option<f64>,
-    pub xirr_formatted: Option<String>,
-    pub latest_price: Option<MarketData>,
-    pub total_fees_cents: i64,
-    pub total_fees_formatted: String,
-    pub total_taxes_cents: i64,
-    pub total_taxes_formatted: String,
-    pub total_dividends_cents: i64,
-    pub total_dividends_formatted: String,
-    pub realized_gain_loss_cents: i64,
-    pub realized_gain_loss_formatted: String,
-    pub realized_gain_loss_color: &'static str,
-}
-
-pub async fn detail(
+/// Fee and tax totals per group and currency.
+pub async fn fee_totals(
     State(state): State<AppState>,
-    Path(symbol): Path<String>,
-) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-
-    let PageBase {
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-    } = state.page_base()?;
-
-    // Get cached symbol metadata from DB
-    let symbol_info = match market_data::get_symbol_metadata(&conn, &symbol) {
-        Ok(Some(meta)) => SymbolInfo {
-            short_name: meta.short_name,
-            long_name: meta.long_name,
-            exchange: meta.exchange,
-            quote_type: meta.quote_type,
-        },
-        _ => SymbolInfo::default(),
-    };
-
-    // Get all positions and find the one for this symbol
-    let all_positions = trading::get_positions(&conn)?;
-    let position_opt = all_positions.into_iter().find(|p| p.symbol == symbol);
-
-    // Enrich with market data if position exists (same logic as positions list)
-    let position = position_opt.map(|pos| {
-        // First try to get actual market data
-        if let Ok(Some(data)) = market_data::get_latest_price(&conn, &pos.symbol) {
-            return PositionWithMarketData::with_market_data(
-                pos,
-                data.close_price_cents,
-                data.date,
-            );
-        }
-        // Fall back to last BUY/SELL price as approximation
-        if let Ok(Some((price_cents, date))) = trading::get_last_trade_price(&conn, &pos.symbol) {
-            return PositionWithMarketData::with_approximated_price(pos, price_cents, date);
-        }
-        // No price data available
-        PositionWithMarketData::from_position(pos)
-    });
-
-    // Get activities for this symbol
-    let all_activities = trading::get_activities_for_symbol(&conn, &symbol)?;
-    let total_activity_count = all_activities.len();
-
-    // Get latest price
-    let latest_price = market_data::get_latest_price(&conn, &symbol)?;
-
-    // Calculate XIRR
-    let xirr = calculate_position_xirr(&all_activities, &position, &latest_price);
-    let xirr_formatted = xirr.map(|x| filters::format_percent(x * 100.0, &settings.locale));
-
-    // Calculate total fees, taxes, dividends, and realized gain/loss
-    let (total_fees_cents, total_taxes_cents, total_dividends_cents, realized_gain_loss_cents) =
-        calculate_position_totals(&all_activities);
-
-    // Keep only last 10 activities for display (most recent, since sorted by date ASC)
-    let activities: Vec<TradingActivity> = all_activities
-        .into_iter()
-        .rev()
-        .take(10)
-        .collect::<Vec<_>>()
-        .into_iter()
-        .rev()
-        .collect();
-
-    let locale = settings.locale.clone();
-    let currency = position
-        .as_ref()
-        .map(|p| p.position.currency.clone())
-        .unwrap_or_else(|| settings.currency.clone());
-
-    let total_fees_formatted = filters::format_money_neutral(total_fees_cents, &currency, &locale);
-    let total_taxes_formatted =
-        filters::format_money_neutral(total_taxes_cents, &currency, &locale);
-    let total_dividends_formatted =
-        filters::format_money_neutral(total_dividends_cents, &currency, &locale);
-    let realized_gain_loss_formatted =
-        filters::format_money_plain(realized_gain_loss_cents, &currency, &locale);
-
-    let realized_gain_loss_color = if realized_gain_loss_cents > 0 {
-        "text-green-600 dark:text-green-400"
-    } else if realized_gain_loss_cents < 0 {
-        "text-red-600 dark:text-red-400"
-    } else {
-        "text-neutral-600 dark:text-neutral-400"
+    Query(params): Query<FeeParams>,
+) -> AppResult<Json<Vec<trading::FeeTaxTotal>>> {
+    let grouping = match params.group_by.as_deref() {
+        None | Some("") => trading::FeeGrouping::Symbol,
+        Some(s) => trading::FeeGrouping::parse(s)
+            .ok_or_else(|| AppError::Validation(format!("Invalid group_by: {}", s)))?,
     };
-
-    let display_name = symbol_info
-        .display_name()
-        .map(|n| format!("{} ({})", n, symbol))
-        .unwrap_or_else(|| symbol.clone());
-
-    let template = PositionDetailTemplate {
-        title: display_name,
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-        symbol: symbol.clone(),
-        symbol_info,
-        position,
-        activities,
-        total_activity_count,
-        xirr,
-        xirr_formatted,
-        latest_price,
-        total_fees_cents,
-        total_fees_formatted,
-        total_taxes_cents,
-        total_taxes_formatted,
-        total_dividends_cents,
-        total_dividends_formatted,
-        realized_gain_loss_cents,
-        realized_gain_loss_formatted,
-        realized_gain_loss_color,
-    };
-
-    template.render_html()
+    let conn = state.db.get()?;
+    Ok(Json(trading::get_fee_tax_totals(&conn, grouping)?))
 }
 
 /// Convert a single trading activity into a (date, amount) cash flow for XIRR.
 /// Returns None for activity types that don't affect XIRR (splits, fees, taxes)
-/// or for amounts too small to matter.
-fn activity_to_cash_flow(activity: &TradingActivity) -> Option<CashFlow> {
+/// or for amounts too small to matter. Transfers and holding adjustments move
+/// no cash, so they only count (at their recorded cost) if `transfers_at_cost`.
+pub fn activity_to_cash_flow(
+    activity: &TradingActivity,
+    transfers_at_cost: bool,
+) -> Option<CashFlow> {
     let date = NaiveDate::parse_from_str(&activity.date, "%Y-%m-%d").ok()?;
     let amount = match activity.activity_type {
         TradingActivityType::Buy => {
             let qty = activity.quantity.unwrap_or(0.0);
             let price = activity.unit_price_cents.unwrap_or(0) as f64 / 100.0;
-            let fee = activity.fee_cents as f64 / 100.0;
+            let fee = activity.fee_converted_cents() as f64 / 100.0;
             -(qty * price + fee)
         }
         TradingActivityType::Sell => {
             let qty = activity.quantity.unwrap_or(0.0);
             let price = activity.unit_price_cents.unwrap_or(0) as f64 / 100.0;
-            let fee = activity.fee_cents as f64 / 100.0;
+            let fee = activity.fee_converted_cents() as f64 / 100.0;
             qty * price - fee
         }
         TradingActivityType::Dividend => {
@@ -683,6 +820,14 @@ fn activity_to_cash_flow(activity: &TradingActivity) -> Option<CashFlow> {
             let price = activity.unit_price_cents.unwrap_or(0) as f64 / 100.0;
             qty * price
         }
+        t if t.is_transfer() && transfers_at_cost => {
+            let value = activity.total_value_cents()? as f64 / 100.0;
+            if t.is_acquisition() {
+                -value
+            } else {
+                value
+            }
+        }
         _ => return None,
     };
     if amount.abs() <= 0.001 {
@@ -691,41 +836,18 @@ fn activity_to_cash_flow(activity: &TradingActivity) -> Option<CashFlow> {
     Some(CashFlow { date, amount })
 }
 
-/// Calculate XIRR for a single position based on its activities and current market value.
-fn calculate_position_xirr(
-    activities: &[TradingActivity],
-    position: &Option<PositionWithMarketData>,
-    latest_price: &Option<MarketData>,
-) -> Option<f64> {
-    let mut cash_flows: Vec<CashFlow> = activities
-        .iter()
-        .filter_map(activity_to_cash_flow)
-        .collect();
-
-    if let (Some(pos), Some(price_data)) = (position, latest_price) {
-        if let Some(current_value) = pos.current_value_cents {
-            let date = NaiveDate::parse_from_str(&price_data.date, "%Y-%m-%d")
-                .unwrap_or_else(|_| chrono::Local::now().date_naive());
-            cash_flows.push(CashFlow {
-                date,
-                amount: current_value as f64 / 100.0,
-            });
-        }
-    }
-
-    calculate_xirr(&cash_flows)
-}
-
 /// Calculate portfolio-wide XIRR across all activities, using current position values
 /// as the terminal cash flows. Returns (xirr, is_incomplete) where is_incomplete
 /// means at least one position has no real market data (missing or approximated price).
-fn calculate_portfolio_xirr(
+pub(crate) fn calculate_portfolio_xirr(
     activities: &[TradingActivity],
     security_positions: &[PositionWithMarketData],
+    transfers_at_cost: bool,
+    today: NaiveDate,
 ) -> (Option<f64>, bool) {
     let mut cash_flows: Vec<CashFlow> = activities
         .iter()
-        .filter_map(activity_to_cash_flow)
+        .filter_map(|a| activity_to_cash_flow(a, transfers_at_cost))
         .collect();
 
     let mut is_incomplete = false;
@@ -738,7 +860,7 @@ fn calculate_portfolio_xirr(
                 .price_date
                 .as_ref()
                 .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
-                .unwrap_or_else(|| chrono::Local::now().date_naive());
+                .unwrap_or(today);
             cash_flows.push(CashFlow {
                 date,
                 amount: value_cents as f64 / 100.0,
@@ -753,12 +875,20 @@ fn calculate_portfolio_xirr(
 /// No terminal cash flow is needed since the positions are fully exited.
 fn calculate_closed_portfolio_xirr(
     activities: &[TradingActivity],
-    closed_symbols: &HashSet<String>,
+    closed_positions: &[ClosedPosition],
+    transfers_at_cost: bool,
 ) -> Option<f64> {
+    // Only the activities of the listed holding periods count
     let cash_flows: Vec<CashFlow> = activities
         .iter()
-        .filter(|a| closed_symbols.contains(&a.symbol))
-        .filter_map(activity_to_cash_flow)
+        .filter(|a| {
+            closed_positions.iter().any(|p| {
+                p.symbol == a.symbol
+                    && (p.first_activity_date.as_str()..=p.last_activity_date.as_str())
+                        .contains(&a.date.as_str())
+            })
+        })
+        .filter_map(|a| activity_to_cash_flow(a, transfers_at_cost))
         .collect();
 
     calculate_xirr(&cash_flows)
@@ -774,184 +904,3 @@ fn xirr_color(xirr: Option<f64>, incomplete: bool) -> &'static str {
         _ => "text-neutral-600 dark:text-neutral-400",
     }
 }
-
-/// Calculate total fees, taxes, dividends, and realized gain/loss for a position
-/// Returns (total_fees_cents, total_taxes_cents, total_dividends_cents, realized_gain_loss_cents)
-/// Note: Dividends are included in realized_gain_loss_cents
-fn calculate_position_totals(activities: &[TradingActivity]) -> (i64, i64, i64, i64) {
-    let mut total_fees_cents: i64 = 0;
-    let mut total_taxes_cents: i64 = 0;
-    let mut total_dividends_cents: i64 = 0;
-    let mut realized_gain_loss_cents: i64 = 0;
-
-    // Track running position for average cost calculation
-    let mut running_quantity: f64 = 0.0;
-    let mut running_cost_cents: i64 = 0;
-
-    for activity in activities {
-        // Fee activity type stores fee amount in unit_price_cents
-        if activity.activity_type == TradingActivityType::Fee {
-            if let Some(price) = activity.unit_price_cents {
-                total_fees_cents += price;
-            }
-        }
-
-        // Sum taxes (Tax activity type stores total tax amount in unit_price_cents)
-        if activity.activity_type == TradingActivityType::Tax {
-            if let Some(price) = activity.unit_price_cents {
-                total_taxes_cents += price;
-            }
-        }
-
-        // Sum dividends and include in realized gain/loss
-        // Dividend activity type stores total dividend amount in unit_price_cents
-        if activity.activity_type == TradingActivityType::Dividend {
-            if let Some(price) = activity.unit_price_cents {
-                total_dividends_cents += price;
-                realized_gain_loss_cents += price;
-            }
-        }
-
-        // Calculate realized gain/loss from sell activities
-        match activity.activity_type {
-            TradingActivityType::Buy => {
-                let qty = activity.quantity.unwrap_or(0.0);
-                let price = activity.unit_price_cents.unwrap_or(0);
-                let cost = (qty * price as f64).round() as i64;
-                running_quantity += qty;
-                running_cost_cents += cost;
-            }
-            TradingActivityType::Sell => {
-                let qty = activity.quantity.unwrap_or(0.0);
-                let sell_price = activity.unit_price_cents.unwrap_or(0);
-                let sell_value = (qty * sell_price as f64).round() as i64;
-
-                // Calculate cost basis using average cost
-                if running_quantity > 0.0 {
-                    let avg_cost = running_cost_cents as f64 / running_quantity;
-                    let cost_basis = (qty * avg_cost).round() as i64;
-
-                    // Realized gain/loss = sell value - cost basis
-                    realized_gain_loss_cents += sell_value - cost_basis;
-
-                    // Update running position
-                    running_quantity -= qty;
-                    running_cost_cents -= cost_basis;
-
-                    if running_quantity < 0.0 {
-                        running_quantity = 0.0;
-                    }
-                    if running_cost_cents < 0 {
-                        running_cost_cents = 0;
-                    }
-                }
-            }
-            TradingActivityType::Split => {
-                // Split adjusts quantity but not cost
-                if let Some(ratio) = activity.quantity {
-                    if ratio > 0.0 {
-                        running_quantity *= ratio;
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-
-    // Subtract fees and taxes from realized gain/loss
-    let net_realized_gain_loss_cents =
-        realized_gain_loss_cents - total_fees_cents - total_taxes_cents;
-
-    (
-        total_fees_cents,
-        total_taxes_cents,
-        total_dividends_cents,
-        net_realized_gain_loss_cents,
-    )
-}
-
-// Chart data API
-
-#[derive(Serialize)]
-pub struct PositionChartData {
-    pub date: String,
-    pub price_cents: i64,
-}
-
-#[derive(Serialize)]
-pub struct ActivityMarker {
-    pub date: String,
-    pub activity_type: String,
-    pub quantity: f64,
-    pub price_cents: i64,
-    pub total_cents: i64,
-}
-
-#[derive(Serialize)]
-pub struct PositionChartResponse {
-    pub symbol: String,
-    pub data: Vec<PositionChartData>,
-    pub activities: Vec<ActivityMarker>,
-    pub is_approximated: bool,
-}
-
-pub async fn position_chart_data(
-    State(state): State<AppState>,
-    Path(symbol): Path<String>,
-) -> AppResult<Json<PositionChartResponse>> {
-    let conn = state.db.get()?;
-
-    // Get all price data for this symbol (ordered by date descending, we need ascending)
-    let mut data_points = market_data::get_prices_for_symbol(&conn, &symbol)?;
-    data_points.reverse(); // Now oldest first
-
-    let (data, is_approximated): (Vec<PositionChartData>, bool) = if !data_points.is_empty() {
-        // Use actual market data
-        let chart_data = data_points
-            .into_iter()
-            .map(|dp| PositionChartData {
-                date: dp.date,
-                price_cents: dp.close_price_cents,
-            })
-            .collect();
-        (chart_data, false)
-    } else {
-        // No market data available - build step function from trade prices
-        let chart_data = trading::get_all_trade_prices(&conn, &symbol)
-            .unwrap_or_default()
-            .into_iter()
-            .map(|(date, price_cents)| PositionChartData { date, price_cents })
-            .collect();
-        (chart_data, true)
-    };
-
-    // Get buy/sell activities for markers
-    let all_activities = trading::get_activities_for_symbol(&conn, &symbol)?;
-    let activities: Vec<ActivityMarker> = all_activities
-        .into_iter()
-        .filter(|a| {
-            matches!(
-                a.activity_type,
-                TradingActivityType::Buy | TradingActivityType::Sell
-            )
-        })
-        .filter_map(|a| {
-            let qty = a.quantity?;
-            let price = a.unit_price_cents?;
-            Some(ActivityMarker {
-                date: a.date,
-                activity_type: a.activity_type.as_str().to_string(),
-                quantity: qty,
-                price_cents: price,
-                total_cents: (qty * price as f64).round() as i64,
-            })
-        })
-        .collect();
-
-    Ok(Json(PositionChartResponse {
-        symbol,
-        data,
-        activities,
-        is_approximated,
-    }))
-}
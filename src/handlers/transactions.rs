@@ -1,18 +1,19 @@
 use askama::Template;
 use axum::extract::{Path, Query, State};
-use axum::http::header;
-use axum::response::{Html, IntoResponse, Redirect};
-use axum::{Form, Json};
-use serde::{Deserialize, Serialize};
-use tracing::{debug, info, warn};
-
-use crate::date_utils::{DateFilterable, DatePreset, DateRange};
-use crate::db::queries::transactions;
+use axum::response::{Html, IntoResponse, Response};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::confirmation::{confirmation_pending, deletion_message, ConfirmParams, DeletedCounts};
+use crate::date_utils::{self, DateFilterable, DatePreset, DateRange};
+use crate::db::queries::{balances, transactions};
 use crate::error::{AppError, AppResult, RenderHtml};
+use crate::flash::{self, FlashLevel};
+use crate::models::settings::TRANSACTION_COLUMNS;
 use crate::models::{
-    Account, CategoryWithPath, NewTransaction, Settings, Tag, TransactionWithRelations,
+    Account, CategoryWithPath, Settings, Tag, TransactionStatus, TransactionWithRelations,
 };
-use crate::sort_utils::{Sortable, SortableColumn, TableSort};
+use crate::sort_utils::{SortDirection, Sortable, SortableColumn, TableSort};
 use crate::state::{AppState, JsManifest, PageBase};
 
 /// Sortable columns for the transactions table.
@@ -68,8 +69,16 @@ pub struct TransactionsTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub transactions: Vec<TransactionWithRelations>,
+    /// Account balance after each row, when `show_running_balance` is set.
+    pub running_balances: Vec<Option<i64>>,
+    pub show_running_balance: bool,
     pub categories: Vec<CategoryWithPath>,
+    /// Cash accounts for the account filter.
+    pub accounts: Vec<Account>,
+    /// Tags offered by the bulk selection bar.
+    pub tags: Vec<Tag>,
     pub total_count: i64,
     pub page: i64,
     pub page_size: i64,
@@ -77,6 +86,8 @@ pub struct TransactionsTemplate {
     pub date_range: DateRange,
     pub presets: &'static [DatePreset],
     pub sort: TableSort<TransactionSortColumn>,
+    /// Column choices of the table layout picker, as (key, label).
+    pub table_columns: &'static [(&'static str, &'static str)],
 }
 
 #[derive(Template)]
@@ -88,6 +99,7 @@ pub struct TransactionBulkTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    pub csp_nonce: String,
     pub categories: Vec<CategoryWithPath>,
     pub tags: Vec<Tag>,
     pub accounts: Vec<Account>,
@@ -103,6 +115,9 @@ pub struct TransactionTableTemplate {
     pub settings: Settings,
     pub icons: crate::filters::Icons,
     pub transactions: Vec<TransactionWithRelations>,
+    /// Account balance after each row, when `show_running_balance` is set.
+    pub running_balances: Vec<Option<i64>>,
+    pub show_running_balance: bool,
     pub total_count: i64,
     pub page: i64,
     pub page_size: i64,
@@ -121,26 +136,14 @@ pub struct TransactionFormTemplate {
     pub is_edit: bool,
 }
 
-#[derive(Template)]
-#[template(path = "pages/transaction_new.html")]
-pub struct TransactionNewTemplate {
-    pub title: String,
-    pub settings: Settings,
-    pub icons: crate::filters::Icons,
-    pub manifest: JsManifest,
-    pub version: &'static str,
-    pub xsrf_token: String,
-    pub categories: Vec<CategoryWithPath>,
-    pub tags: Vec<Tag>,
-    pub accounts: Vec<Account>,
-}
-
 #[derive(Template)]
 #[template(path = "components/transaction_row.html")]
 pub struct TransactionRowTemplate {
     pub settings: Settings,
     pub icons: crate::filters::Icons,
     pub transaction: TransactionWithRelations,
+    pub show_running_balance: bool,
+    pub running_balance: Option<i64>,
 }
 
 #[derive(Template)]
@@ -164,21 +167,7 @@ pub struct TransactionDetailTemplate {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
-    pub transaction: TransactionWithRelations,
-    pub categories: Vec<CategoryWithPath>,
-    pub tags: Vec<Tag>,
-    pub accounts: Vec<Account>,
-}
-
-#[derive(Template)]
-#[template(path = "pages/transaction_edit.html")]
-pub struct TransactionEditTemplate {
-    pub title: String,
-    pub settings: Settings,
-    pub icons: crate::filters::Icons,
-    pub manifest: JsManifest,
-    pub version: &'static str,
-    pub xsrf_token: String,
+    pub csp_nonce: String,
     pub transaction: TransactionWithRelations,
     pub categories: Vec<CategoryWithPath>,
     pub tags: Vec<Tag>,
@@ -188,16 +177,25 @@ pub struct TransactionEditTemplate {
 #[derive(Debug, Default, Clone, Deserialize)]
 pub struct TransactionFilterParams {
     pub search: Option<String>,
+    pub payee: Option<String>,
     #[serde(
         default,
         deserialize_with = "crate::form_utils::deserialize_optional_i64"
     )]
     pub category_id: Option<i64>,
+    /// Also show transactions of the category's subcategories.
+    #[serde(default)]
+    pub include_children: bool,
     #[serde(
         default,
         deserialize_with = "crate::form_utils::deserialize_optional_i64"
     )]
     pub tag_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub account_id: Option<i64>,
     pub from_date: Option<String>,
     pub to_date: Option<String>,
     pub page: Option<i64>,
@@ -205,6 +203,12 @@ pub struct TransactionFilterParams {
     pub nav: Option<String>, // "prev" or "next"
     pub sort: Option<String>,
     pub dir: Option<String>,
+    /// "posted" or "pending"; anything else shows both.
+    pub status: Option<String>,
+    /// "1" shows only categories assigned by rules that await review.
+    pub auto_categorized: Option<String>,
+    /// "1" shows only transactions mirroring trading activities, "0" hides them.
+    pub mirrored: Option<String>,
 }
 
 impl DateFilterable for TransactionFilterParams {
@@ -244,7 +248,42 @@ impl TransactionFilterParams {
         self.category_id == Some(*id)
     }
 
-    /// Returns filter query string (search, category_id, tag_id).
+    /// Whether the table gets a running balance column: only when it lists
+    /// all transactions of one account in date order. Other filters would
+    /// leave gaps that the balance can't be accumulated across.
+    pub fn shows_running_balance(&self, sort: &TableSort<TransactionSortColumn>) -> bool {
+        self.account_id.is_some()
+            && sort.column == TransactionSortColumn::Date
+            && self.search.as_deref().is_none_or(str::is_empty)
+            && self.payee.as_deref().is_none_or(str::is_empty)
+            && self.category_id.is_none()
+            && self.tag_id.is_none()
+            && self.status_filter() != Some(TransactionStatus::Pending)
+            && !self.is_auto_categorized()
+            && self.mirrored_filter().is_none()
+    }
+
+    pub fn status_filter(&self) -> Option<TransactionStatus> {
+        self.status.as_deref().and_then(TransactionStatus::parse)
+    }
+
+    pub fn is_auto_categorized(&self) -> bool {
+        self.auto_categorized.as_deref() == Some("1")
+    }
+
+    pub fn mirrored_filter(&self) -> Option<bool> {
+        match self.mirrored.as_deref() {
+            Some("1") => Some(true),
+            Some("0") => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn matches_status(&self, status: &str) -> bool {
+        self.status_filter().map(|s| s.as_str()) == Some(status)
+    }
+
+    /// Returns filter query string (search, payee, category_id, tag_id, status).
     pub fn base_query_string(&self) -> String {
         let mut parts = Vec::new();
         if let Some(search) = &self.search {
@@ -252,12 +291,32 @@ impl TransactionFilterParams {
                 parts.push(format!("search={}", urlencoding::encode(search)));
             }
         }
+        if let Some(payee) = &self.payee {
+            if !payee.is_empty() {
+                parts.push(format!("payee={}", urlencoding::encode(payee)));
+            }
+        }
         if let Some(cat_id) = self.category_id {
             parts.push(format!("category_id={}", cat_id));
+            if self.include_children {
+                parts.push("include_children=true".to_string());
+            }
         }
         if let Some(tag_id) = self.tag_id {
             parts.push(format!("tag_id={}", tag_id));
         }
+        if let Some(account_id) = self.account_id {
+            parts.push(format!("account_id={}", account_id));
+        }
+        if let Some(status) = self.status_filter() {
+            parts.push(format!("status={}", status.as_str()));
+        }
+        if self.is_auto_categorized() {
+            parts.push("auto_categorized=1".to_string());
+        }
+        if let Some(mirrored) = self.mirrored_filter() {
+            parts.push(format!("mirrored={}", u8::from(mirrored)));
+        }
         parts.join("&")
     }
 
@@ -289,80 +348,60 @@ impl TransactionFilterParams {
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct TransactionFormData {
-    pub date: String,
-    pub amount: String,
-    pub currency: String,
-    pub description: String,
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub category_id: Option<i64>,
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub account_id: Option<i64>,
-    pub notes: Option<String>,
-    #[serde(default)]
-    pub tag_ids: Vec<i64>,
-    // Extended fields
-    #[serde(default)]
-    pub value_date: Option<String>,
-    #[serde(default)]
-    pub payer: Option<String>,
-    #[serde(default)]
-    pub payee: Option<String>,
-    #[serde(default)]
-    pub reference: Option<String>,
-    #[serde(default)]
-    pub transaction_type: Option<String>,
-    #[serde(default)]
-    pub counterparty_iban: Option<String>,
-    #[serde(default)]
-    pub creditor_id: Option<String>,
-    #[serde(default)]
-    pub mandate_reference: Option<String>,
-    #[serde(default)]
-    pub customer_reference: Option<String>,
+/// ORDER BY of the transactions table. Date sorts break ties by ID in the
+/// same direction, so a day's transactions are listed in the order the
+/// running balance accumulates them.
+fn table_order_by(sort: &TableSort<TransactionSortColumn>) -> String {
+    match sort.column {
+        TransactionSortColumn::Date => {
+            format!("{}, e.id {}", sort.sql_order_by(), sort.direction.sql())
+        }
+        _ => sort.sql_order_by(),
+    }
 }
 
-impl TransactionFormData {
-    /// Normalize an optional string: treat empty/whitespace-only as None.
-    fn non_empty(s: &Option<String>) -> Option<String> {
-        s.as_ref()
-            .map(|v| v.trim().to_string())
-            .filter(|v| !v.is_empty())
+/// The account balance after each row of the page, if the table shows a
+/// running balance. The balance before the page comes from SQL; the rows
+/// then add up in Rust. Pending transactions don't count towards balances,
+/// so their rows show none.
+fn running_balances(
+    conn: &rusqlite::Connection,
+    params: &TransactionFilterParams,
+    sort: &TableSort<TransactionSortColumn>,
+    rows: &[TransactionWithRelations],
+) -> AppResult<Vec<Option<i64>>> {
+    let (Some(account_id), Some(first)) = (params.account_id, rows.first()) else {
+        return Ok(Vec::new());
+    };
+    if !params.shows_running_balance(sort) {
+        return Ok(Vec::new());
     }
+    let posted = |t: &TransactionWithRelations| if t.is_pending() { 0 } else { t.amount_cents };
+    let mut balance =
+        balances::get_account_balance_before(conn, account_id, &first.date, first.id)?;
 
-    fn to_new_transaction(&self) -> Result<NewTransaction, AppError> {
-        let amount: f64 = self
-            .amount
-            .parse()
-            .map_err(|_| AppError::Validation("Invalid amount".into()))?;
-
-        Ok(NewTransaction {
-            date: self.date.clone(),
-            amount_cents: (amount * 100.0).round() as i64,
-            currency: self.currency.clone(),
-            description: self.description.clone(),
-            category_id: self.category_id,
-            account_id: self.account_id,
-            notes: self.notes.clone(),
-            tag_ids: self.tag_ids.clone(),
-            value_date: Self::non_empty(&self.value_date),
-            payer: Self::non_empty(&self.payer),
-            payee: Self::non_empty(&self.payee),
-            reference: Self::non_empty(&self.reference),
-            transaction_type: Self::non_empty(&self.transaction_type),
-            counterparty_iban: Self::non_empty(&self.counterparty_iban),
-            creditor_id: Self::non_empty(&self.creditor_id),
-            mandate_reference: Self::non_empty(&self.mandate_reference),
-            customer_reference: Self::non_empty(&self.customer_reference),
-        })
-    }
+    Ok(match sort.direction {
+        // Oldest first: add each row to the balance before it
+        SortDirection::Asc => rows
+            .iter()
+            .map(|t| {
+                balance = balance.saturating_add(posted(t));
+                (!t.is_pending()).then_some(balance)
+            })
+            .collect(),
+        // Newest first: start from the balance after the top row and take
+        // each row back out on the way down
+        SortDirection::Desc => {
+            balance = balance.saturating_add(posted(first));
+            rows.iter()
+                .map(|t| {
+                    let after = (!t.is_pending()).then_some(balance);
+                    balance = balance.saturating_sub(posted(t));
+                    after
+                })
+                .collect()
+        }
+    })
 }
 
 pub async fn index(
@@ -377,36 +416,45 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let page = params.page.unwrap_or(1).max(1);
     let page_size = settings.page_size;
 
     let date_range = params
-        .resolve_date_range()
+        .resolve_date_range(date_utils::today_in(&settings))
         .resolve_all(transactions::date_extent(&conn)?);
     let sort: TableSort<TransactionSortColumn> = params.resolve_sort();
 
     let filter = transactions::TransactionFilter {
         search: params.search.clone(),
+        payee: params.payee.clone(),
         category_id: if params.is_uncategorized() {
             None
         } else {
             params.category_id
         },
+        include_children: params.include_children,
         tag_id: params.tag_id,
+        account_id: params.account_id,
         from_date: Some(date_range.from_str()),
         to_date: Some(date_range.to_str()),
         limit: Some(page_size),
         offset: Some((page - 1) * page_size),
-        sort_sql: Some(sort.sql_order_by()),
+        sort_sql: Some(table_order_by(&sort)),
         uncategorized_only: params.is_uncategorized(),
+        status: params.status_filter(),
+        auto_categorized_only: params.is_auto_categorized(),
+        mirrored: params.mirrored_filter(),
         ..Default::default()
     };
 
     let transaction_list = transactions::list_transactions(&conn, &filter)?;
     let total_count = transactions::count_transactions(&conn, &filter)?;
+    let running_balances = running_balances(&conn, &params, &sort, &transaction_list)?;
     let cats = state.cached_categories_with_path()?;
+    let cash_accounts = state.cached_cash_accounts()?;
 
     let template = TransactionsTemplate {
         title: "Transactions".into(),
@@ -415,8 +463,13 @@ pub async fn index(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
+        show_running_balance: params.shows_running_balance(&sort),
         transactions: transaction_list,
+        running_balances,
         categories: cats,
+        accounts: cash_accounts,
+        tags: state.cached_tags()?,
         total_count,
         page,
         page_size,
@@ -424,6 +477,7 @@ pub async fn index(
         date_range,
         presets: DatePreset::all(),
         sort,
+        table_columns: TRANSACTION_COLUMNS,
     };
 
     template.render_html()
@@ -443,34 +497,43 @@ pub async fn table_partial(
     let page_size = settings.page_size;
 
     let date_range = params
-        .resolve_date_range()
+        .resolve_date_range(date_utils::today_in(&settings))
         .resolve_all(transactions::date_extent(&conn)?);
     let sort: TableSort<TransactionSortColumn> = params.resolve_sort();
 
     let filter = transactions::TransactionFilter {
         search: params.search.clone(),
+        payee: params.payee.clone(),
         category_id: if params.is_uncategorized() {
             None
         } else {
             params.category_id
         },
+        include_children: params.include_children,
         tag_id: params.tag_id,
+        account_id: params.account_id,
         from_date: Some(date_range.from_str()),
         to_date: Some(date_range.to_str()),
         limit: Some(page_size),
         offset: Some((page - 1) * page_size),
-        sort_sql: Some(sort.sql_order_by()),
+        sort_sql: Some(table_order_by(&sort)),
         uncategorized_only: params.is_uncategorized(),
+        status: params.status_filter(),
+        auto_categorized_only: params.is_auto_categorized(),
+        mirrored: params.mirrored_filter(),
         ..Default::default()
     };
 
     let transaction_list = transactions::list_transactions(&conn, &filter)?;
     let total_count = transactions::count_transactions(&conn, &filter)?;
+    let running_balances = running_balances(&conn, &params, &sort, &transaction_list)?;
 
     let template = TransactionTableTemplate {
         settings,
         icons,
+        show_running_balance: params.shows_running_balance(&sort),
         transactions: transaction_list,
+        running_balances,
         total_count,
         page,
         page_size,
@@ -493,23 +556,30 @@ pub async fn bulk_page(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let date_range = params
-        .resolve_date_range()
+        .resolve_date_range(date_utils::today_in(&settings))
         .resolve_all(transactions::date_extent(&conn)?);
 
     let filter = transactions::TransactionFilter {
         search: params.search.clone(),
+        payee: params.payee.clone(),
         category_id: if params.is_uncategorized() {
             None
         } else {
             params.category_id
         },
+        include_children: params.include_children,
         tag_id: params.tag_id,
+        account_id: params.account_id,
         from_date: Some(date_range.from_str()),
         to_date: Some(date_range.to_str()),
         uncategorized_only: params.is_uncategorized(),
+        status: params.status_filter(),
+        auto_categorized_only: params.is_auto_categorized(),
+        mirrored: params.mirrored_filter(),
         ..Default::default()
     };
 
@@ -532,6 +602,7 @@ pub async fn bulk_page(
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         categories: cats,
         tags: tag_list,
         accounts: cash_accounts,
@@ -556,6 +627,7 @@ pub async fn show(State(state): State<AppState>, Path(id): Path<i64>) -> AppResu
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
     } = state.page_base()?;
 
     let cats = state.cached_categories_with_path()?;
@@ -569,6 +641,7 @@ pub async fn show(State(state): State<AppState>, Path(id): Path<i64>) -> AppResu
         manifest,
         version,
         xsrf_token,
+        csp_nonce,
         transaction,
         categories: cats,
         tags: tag_list,
@@ -578,413 +651,66 @@ pub async fn show(State(state): State<AppState>, Path(id): Path<i64>) -> AppResu
     template.render_html()
 }
 
-pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>> {
-    let PageBase {
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-    } = state.page_base()?;
-    let cats = state.cached_categories_with_path()?;
-    let tag_list = state.cached_tags()?;
-    let cash_accounts = state.cached_cash_accounts()?;
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<impl IntoResponse> {
+    info!(transaction_id = id, "Deleting transaction");
+    let conn = state.db.get()?;
 
-    let template = TransactionNewTemplate {
-        title: "Add Transaction".into(),
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
-        categories: cats,
-        tags: tag_list,
-        accounts: cash_accounts,
-    };
+    transactions::delete_transaction(&conn, id)?;
 
-    template.render_html()
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, "Transaction deleted"),
+        Html(String::new()),
+    ))
 }
 
-pub async fn edit_form(
+/// Row action that books a pending transaction. Returns the updated row.
+pub async fn mark_posted(
     State(state): State<AppState>,
     Path(id): Path<i64>,
-) -> AppResult<Html<String>> {
+) -> AppResult<impl IntoResponse> {
     let conn = state.db.get()?;
-
+    if !transactions::mark_posted(&conn, id)? {
+        return Err(AppError::NotFound(format!("Transaction {} not found", id)));
+    }
     let transaction = transactions::get_transaction(&conn, id)?
         .ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", id)))?;
 
     let PageBase {
-        settings,
-        icons,
-        manifest,
-        version,
-        xsrf_token,
+        settings, icons, ..
     } = state.page_base()?;
-
-    let cats = state.cached_categories_with_path()?;
-    let tag_list = state.cached_tags()?;
-    let cash_accounts = state.cached_cash_accounts()?;
-
-    let template = TransactionEditTemplate {
-        title: "Edit Transaction".into(),
+    let template = TransactionRowTemplate {
         settings,
         icons,
-        manifest,
-        version,
-        xsrf_token,
         transaction,
-        categories: cats,
-        tags: tag_list,
-        accounts: cash_accounts,
+        show_running_balance: false,
+        running_balance: None,
     };
-
-    template.render_html()
-}
-
-pub async fn create(
-    State(state): State<AppState>,
-    Form(form): Form<TransactionFormData>,
-) -> AppResult<Redirect> {
-    debug!(description = %form.description, amount = %form.amount, "Creating transaction");
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
-
-    let new_transaction = form.to_new_transaction()?;
-    let id = transactions::create_transaction(&tx, &new_transaction)?;
-    info!(transaction_id = id, "Transaction created via web form");
-
-    tx.commit()?;
-    Ok(Redirect::to("/transactions"))
-}
-
-pub async fn update(
-    State(state): State<AppState>,
-    Path(id): Path<i64>,
-    Form(form): Form<TransactionFormData>,
-) -> AppResult<Redirect> {
-    debug!(transaction_id = id, "Updating transaction");
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
-
-    let new_transaction = form.to_new_transaction()?;
-    transactions::update_transaction(&tx, id, &new_transaction)?;
-    info!(transaction_id = id, "Transaction updated via web form");
-
-    tx.commit()?;
-    Ok(Redirect::to(&format!("/transactions/{}", id)))
-}
-
-pub async fn delete(State(state): State<AppState>, Path(id): Path<i64>) -> AppResult<Html<String>> {
-    info!(transaction_id = id, "Deleting transaction");
-    let conn = state.db.get()?;
-
-    transactions::delete_transaction(&conn, id)?;
-
-    Ok(Html(String::new()))
-}
-
-pub async fn delete_all(State(state): State<AppState>) -> AppResult<Html<String>> {
-    warn!("Deleting all transactions");
-    let mut conn = state.db.get()?;
-    let tx = conn.transaction()?;
-
-    transactions::delete_all_transactions(&tx)?;
-
-    tx.commit()?;
-    Ok(Html(String::new()))
-}
-
-/// Common filter fields shared by bulk-operation forms.
-/// Populated via hx-include from #filter-form on the transactions page.
-#[derive(Debug, Deserialize)]
-pub struct BulkFilterFields {
-    pub search: Option<String>,
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub category_id: Option<i64>,
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub tag_id: Option<i64>,
-    pub from_date: Option<String>,
-    pub to_date: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct BulkCategoryForm {
-    /// Action value: which category to set (0 = clear).
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub set_category_id: Option<i64>,
-    /// Filter fields — included from #filter-form via hx-include.
-    #[serde(flatten)]
-    pub filter: BulkFilterFields,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct BulkTagForm {
-    /// Action value: which tag to add.
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub set_tag_id: Option<i64>,
-    /// Filter fields — included from #filter-form via hx-include.
-    #[serde(flatten)]
-    pub filter: BulkFilterFields,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct BulkAccountForm {
-    /// Action value: which account to set (0 = clear).
-    #[serde(
-        default,
-        deserialize_with = "crate::form_utils::deserialize_optional_i64"
-    )]
-    pub set_account_id: Option<i64>,
-    /// Filter fields — included from #filter-form via hx-include.
-    #[serde(flatten)]
-    pub filter: BulkFilterFields,
-}
-
-fn build_bulk_filter(f: &BulkFilterFields) -> transactions::TransactionFilter {
-    let uncategorized_only = f.category_id == Some(0);
-    transactions::TransactionFilter {
-        search: f.search.clone(),
-        category_id: if uncategorized_only {
-            None
-        } else {
-            f.category_id
-        },
-        tag_id: f.tag_id,
-        from_date: f.from_date.clone(),
-        to_date: f.to_date.clone(),
-        uncategorized_only,
-        ..Default::default()
-    }
-}
-
-pub async fn bulk_set_category(
-    State(state): State<AppState>,
-    Form(form): Form<BulkCategoryForm>,
-) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-    let filter = build_bulk_filter(&form.filter);
-    // set_category_id=0 means "clear category" (set to NULL)
-    let category_id = form
-        .set_category_id
-        .and_then(|id| if id == 0 { None } else { Some(id) });
-    let count = transactions::bulk_set_category(&conn, &filter, category_id)?;
-    info!(count, "Bulk set category via web");
-    Ok(Html(String::new()))
-}
-
-pub async fn bulk_add_tag(
-    State(state): State<AppState>,
-    Form(form): Form<BulkTagForm>,
-) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-    let tag_id = form
-        .set_tag_id
-        .ok_or_else(|| AppError::Validation("Tag is required".into()))?;
-    let filter = build_bulk_filter(&form.filter);
-    let count = transactions::bulk_add_tag(&conn, &filter, tag_id)?;
-    info!(count, tag_id, "Bulk added tag via web");
-    Ok(Html(String::new()))
-}
-
-pub async fn bulk_set_account(
-    State(state): State<AppState>,
-    Form(form): Form<BulkAccountForm>,
-) -> AppResult<Html<String>> {
-    let conn = state.db.get()?;
-    let filter = build_bulk_filter(&form.filter);
-    // set_account_id=0 means "clear account" (set to NULL)
-    let account_id = form
-        .set_account_id
-        .and_then(|id| if id == 0 { None } else { Some(id) });
-    let count = transactions::bulk_set_account(&conn, &filter, account_id)?;
-    info!(count, "Bulk set account via web");
-    Ok(Html(String::new()))
-}
-
-#[derive(Serialize)]
-struct TransactionExport {
-    date: String,
-    amount_cents: i64,
-    currency: String,
-    description: String,
-    category_name: Option<String>,
-    account_name: Option<String>,
-    notes: Option<String>,
-    tags: Vec<String>,
-    value_date: Option<String>,
-    payer: Option<String>,
-    payee: Option<String>,
-    reference: Option<String>,
-    transaction_type: Option<String>,
-    counterparty_iban: Option<String>,
-    creditor_id: Option<String>,
-    mandate_reference: Option<String>,
-    customer_reference: Option<String>,
-}
-
-pub async fn export(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
-    let conn = state.db.get()?;
-
-    let filter = crate::db::queries::transactions::TransactionFilter::default();
-
-    let txns = transactions::list_transactions(&conn, &filter)?;
-
-    let export_data: Vec<TransactionExport> = txns
-        .iter()
-        .map(|t| TransactionExport {
-            date: t.transaction.date.clone(),
-            amount_cents: t.transaction.amount_cents,
-            currency: t.transaction.currency.clone(),
-            description: t.transaction.description.clone(),
-            category_name: t.category_name.clone(),
-            account_name: t.account_name.clone(),
-            notes: t.transaction.notes.clone(),
-            tags: t.tags.iter().map(|tag| tag.name.clone()).collect(),
-            value_date: t.transaction.value_date.clone(),
-            payer: t.transaction.payer.clone(),
-            payee: t.transaction.payee.clone(),
-            reference: t.transaction.reference.clone(),
-            transaction_type: t.transaction.transaction_type.clone(),
-            counterparty_iban: t.transaction.counterparty_iban.clone(),
-            creditor_id: t.transaction.creditor_id.clone(),
-            mandate_reference: t.transaction.mandate_reference.clone(),
-            customer_reference: t.transaction.customer_reference.clone(),
-        })
-        .collect();
-
-    let json = serde_json::to_string_pretty(&export_data)
-        .map_err(|e| AppError::Internal(format!("Failed to serialize: {}", e)))?;
-
     Ok((
-        [
-            (header::CONTENT_TYPE, "application/json"),
-            (
-                header::CONTENT_DISPOSITION,
-                "attachment; filename=\"transactions.json\"",
-            ),
-        ],
-        json,
+        flash::toast_trigger(FlashLevel::Success, "Transaction marked as posted"),
+        template.render_html()?,
     ))
 }
 
-#[derive(Deserialize)]
-struct TransactionImport {
-    date: String,
-    amount_cents: i64,
-    #[serde(default = "default_currency")]
-    currency: String,
-    description: String,
-    category_name: Option<String>,
-    account_name: Option<String>,
-    #[serde(default)]
-    notes: Option<String>,
-    #[serde(default)]
-    tags: Vec<String>,
-    #[serde(default)]
-    value_date: Option<String>,
-    #[serde(default)]
-    payer: Option<String>,
-    #[serde(default)]
-    payee: Option<String>,
-    #[serde(default)]
-    reference: Option<String>,
-    #[serde(default)]
-    transaction_type: Option<String>,
-    #[serde(default)]
-    counterparty_iban: Option<String>,
-    #[serde(default)]
-    creditor_id: Option<String>,
-    #[serde(default)]
-    mandate_reference: Option<String>,
-    #[serde(default)]
-    customer_reference: Option<String>,
-}
-
-fn default_currency() -> String {
-    "USD".to_string()
-}
-
-pub async fn import(
+pub async fn delete_all(
     State(state): State<AppState>,
-    Json(value): Json<serde_json::Value>,
-) -> AppResult<Json<serde_json::Value>> {
-    let data: Vec<TransactionImport> = serde_json::from_value(value)
-        .map_err(|e| AppError::Validation(format!("Invalid JSON format: {}", e)))?;
-
-    let conn = state.db.get()?;
-
-    // Build lookup maps for category and account names
-    let cat_list = state.cached_categories()?;
-    let cat_name_to_id: std::collections::HashMap<String, i64> =
-        cat_list.iter().map(|c| (c.name.clone(), c.id)).collect();
-
-    let account_list = state.cached_accounts()?;
-    let account_name_to_id: std::collections::HashMap<String, i64> = account_list
-        .iter()
-        .map(|a| (a.name.clone(), a.id))
-        .collect();
-
-    let tag_list = state.cached_tags()?;
-    let tag_name_to_id: std::collections::HashMap<String, i64> =
-        tag_list.iter().map(|t| (t.name.clone(), t.id)).collect();
-
-    let mut created = 0;
-    for item in data {
-        let category_id = item
-            .category_name
-            .as_ref()
-            .and_then(|name| cat_name_to_id.get(name).copied());
-
-        let account_id = item
-            .account_name
-            .as_ref()
-            .and_then(|name| account_name_to_id.get(name).copied());
-
-        let tag_ids: Vec<i64> = item
-            .tags
-            .iter()
-            .filter_map(|name| tag_name_to_id.get(name).copied())
-            .collect();
-
-        let new_txn = NewTransaction {
-            date: item.date,
-            amount_cents: item.amount_cents,
-            currency: item.currency,
-            description: item.description,
-            category_id,
-            account_id,
-            notes: item.notes,
-            tag_ids,
-            value_date: item.value_date,
-            payer: item.payer,
-            payee: item.payee,
-            reference: item.reference,
-            transaction_type: item.transaction_type,
-            counterparty_iban: item.counterparty_iban,
-            creditor_id: item.creditor_id,
-            mandate_reference: item.mandate_reference,
-            customer_reference: item.customer_reference,
-        };
-
-        transactions::create_transaction(&conn, &new_txn)?;
-        created += 1;
+    Query(params): Query<ConfirmParams>,
+) -> AppResult<Response> {
+    let pending = confirmation_pending(&state, "transactions", &params, || {
+        let conn = state.db.get()?;
+        let count = transactions::count_transactions(&conn, &Default::default())?;
+        Ok(deletion_message(&[(
+            count as usize,
+            "transaction",
+            "transactions",
+        )]))
+    })?;
+    if let Some(pending) = pending {
+        return Ok(pending);
     }
-
-    Ok(Json(serde_json::json!({
-        "imported": created,
-        "message": format!("Successfully imported {} transactions", created)
-    })))
+    warn!("Deleting all transactions");
+    let count = state.with_tx(|tx| Ok(transactions::delete_all_transactions(tx)?))?;
+    Ok(DeletedCounts::single("transactions", count).into_response())
 }
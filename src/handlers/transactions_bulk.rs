@@ -0,0 +1,343 @@
+//! Bulk edits of transactions, either of all matching the filter of the
+//! transactions page or of an explicit selection of ids.
+
+use axum::extract::State;
+use axum::response::{Html, IntoResponse};
+use axum::{Form, Json};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::db::queries::transactions;
+use crate::error::{AppError, AppResult};
+use crate::flash::{self, FlashLevel};
+use crate::form_utils::{collect_ids, SubmittedForm};
+use crate::models::TransactionStatus;
+use crate::state::AppState;
+
+/// Common filter fields shared by bulk-operation forms.
+/// Populated via hx-include from #filter-form on the transactions page.
+#[derive(Debug, Deserialize)]
+pub struct BulkFilterFields {
+    pub search: Option<String>,
+    pub payee: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub category_id: Option<i64>,
+    pub include_children: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub tag_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub account_id: Option<i64>,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub status: Option<String>,
+    pub auto_categorized: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkCategoryForm {
+    /// Action value: which category to set (0 = clear).
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub set_category_id: Option<i64>,
+    /// Filter fields — included from #filter-form via hx-include.
+    #[serde(flatten)]
+    pub filter: BulkFilterFields,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkTagForm {
+    /// Action value: which tag to add.
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub set_tag_id: Option<i64>,
+    /// Filter fields — included from #filter-form via hx-include.
+    #[serde(flatten)]
+    pub filter: BulkFilterFields,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkAccountForm {
+    /// Action value: which account to set (0 = clear).
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub set_account_id: Option<i64>,
+    /// Filter fields — included from #filter-form via hx-include.
+    #[serde(flatten)]
+    pub filter: BulkFilterFields,
+}
+
+fn build_bulk_filter(f: &BulkFilterFields) -> transactions::TransactionFilter {
+    let uncategorized_only = f.category_id == Some(0);
+    transactions::TransactionFilter {
+        search: f.search.clone(),
+        payee: f.payee.clone(),
+        category_id: if uncategorized_only {
+            None
+        } else {
+            f.category_id
+        },
+        include_children: f.include_children.as_deref() == Some("true"),
+        tag_id: f.tag_id,
+        account_id: f.account_id,
+        from_date: f.from_date.clone(),
+        to_date: f.to_date.clone(),
+        uncategorized_only,
+        status: f.status.as_deref().and_then(TransactionStatus::parse),
+        auto_categorized_only: f.auto_categorized.as_deref() == Some("1"),
+        ..Default::default()
+    }
+}
+
+pub async fn bulk_set_category(
+    State(state): State<AppState>,
+    Form(form): Form<BulkCategoryForm>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let filter = build_bulk_filter(&form.filter);
+    // set_category_id=0 means "clear category" (set to NULL)
+    let category_id = form
+        .set_category_id
+        .and_then(|id| if id == 0 { None } else { Some(id) });
+    let count = transactions::bulk_set_category(&conn, &filter, category_id)?;
+    info!(count, "Bulk set category via web");
+    Ok(Html(String::new()))
+}
+
+pub async fn bulk_add_tag(
+    State(state): State<AppState>,
+    Form(form): Form<BulkTagForm>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let tag_id = form
+        .set_tag_id
+        .ok_or_else(|| AppError::Validation("Tag is required".into()))?;
+    let filter = build_bulk_filter(&form.filter);
+    let count = transactions::bulk_add_tag(&conn, &filter, tag_id)?;
+    info!(count, tag_id, "Bulk added tag via web");
+    Ok(Html(String::new()))
+}
+
+pub async fn bulk_remove_tag(
+    State(state): State<AppState>,
+    Form(form): Form<BulkTagForm>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let tag_id = form
+        .set_tag_id
+        .ok_or_else(|| AppError::Validation("Tag is required".into()))?;
+    let filter = build_bulk_filter(&form.filter);
+    let count = transactions::bulk_remove_tag(&conn, &filter, tag_id)?;
+    info!(count, tag_id, "Bulk removed tag via web");
+    flash::flash_success(format!(
+        "Removed tag from {} transaction{}",
+        count,
+        if count == 1 { "" } else { "s" }
+    ));
+    Ok(Html(String::new()))
+}
+
+pub async fn bulk_clear_tags(
+    State(state): State<AppState>,
+    Form(fields): Form<BulkFilterFields>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let filter = build_bulk_filter(&fields);
+    let count = transactions::bulk_clear_tags(&conn, &filter)?;
+    info!(count, "Bulk cleared tags via web");
+    flash::flash_success(format!(
+        "Cleared tags from {} transaction{}",
+        count,
+        if count == 1 { "" } else { "s" }
+    ));
+    Ok(Html(String::new()))
+}
+
+pub async fn bulk_set_account(
+    State(state): State<AppState>,
+    Form(form): Form<BulkAccountForm>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+    let filter = build_bulk_filter(&form.filter);
+    // set_account_id=0 means "clear account" (set to NULL)
+    let account_id = form
+        .set_account_id
+        .and_then(|id| if id == 0 { None } else { Some(id) });
+    let count = transactions::bulk_set_account(&conn, &filter, account_id)?;
+    info!(count, "Bulk set account via web");
+    Ok(Html(String::new()))
+}
+
+/// Most transactions a single ID-based bulk operation accepts.
+pub const MAX_BULK_IDS: usize = 1000;
+
+/// Repeated form field carrying the selected transaction IDs.
+const BULK_IDS_FIELD: &str = "transaction_ids[]";
+
+/// Action values of the ID-based bulk operations. Each endpoint reads the
+/// field it needs; the selection itself comes from [`BULK_IDS_FIELD`].
+#[derive(Debug, Default, Deserialize)]
+pub struct BulkIdsActionFields {
+    /// Category to set (0 = clear).
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub set_category_id: Option<i64>,
+    /// Tag to add.
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub set_tag_id: Option<i64>,
+    /// Account to set (0 = clear).
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_optional_i64"
+    )]
+    pub set_account_id: Option<i64>,
+}
+
+/// Outcome of an ID-based bulk operation.
+#[derive(Debug, Serialize)]
+pub struct BulkIdsResult {
+    /// Number of transactions selected.
+    pub selected: usize,
+    /// Number of transactions changed or deleted.
+    pub changed: usize,
+}
+
+/// Split a bulk form into the selected IDs and the action fields.
+fn parse_bulk_ids_form(pairs: Vec<(String, String)>) -> AppResult<(Vec<i64>, BulkIdsActionFields)> {
+    let submitted = SubmittedForm::new(pairs);
+    let ids = collect_ids(submitted.pairs(), BULK_IDS_FIELD);
+    if ids.is_empty() {
+        return Err(AppError::Validation(
+            "Select at least one transaction".into(),
+        ));
+    }
+    if ids.len() > MAX_BULK_IDS {
+        return Err(AppError::Validation(format!(
+            "At most {} transactions can be changed at once, {} were selected",
+            MAX_BULK_IDS,
+            ids.len()
+        )));
+    }
+    let fields = submitted
+        .parse(&[BULK_IDS_FIELD])
+        .map_err(AppError::Validation)?;
+    Ok((ids, fields))
+}
+
+/// Fail unless every selected transaction exists, so a stale selection
+/// changes nothing instead of part of it.
+fn ensure_ids_exist(conn: &rusqlite::Connection, ids: &[i64]) -> AppResult<()> {
+    let missing = transactions::missing_ids(conn, ids)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let list: Vec<String> = missing.iter().map(|id| id.to_string()).collect();
+    Err(AppError::Validation(format!(
+        "Transaction{} not found: {}",
+        if missing.len() == 1 { "" } else { "s" },
+        list.join(", ")
+    )))
+}
+
+/// Run `apply` on the selected transactions in one database transaction and
+/// report the outcome as JSON with a toast.
+fn run_bulk_ids(
+    state: &AppState,
+    pairs: Vec<(String, String)>,
+    verb: &str,
+    apply: impl FnOnce(
+        &rusqlite::Connection,
+        &BulkIdsActionFields,
+        &transactions::TransactionFilter,
+    ) -> AppResult<usize>,
+) -> AppResult<impl IntoResponse> {
+    let (ids, fields) = parse_bulk_ids_form(pairs)?;
+    let filter = transactions::TransactionFilter {
+        ids: ids.clone(),
+        with_tags: false,
+        ..Default::default()
+    };
+    let changed = state.with_tx(|tx| {
+        ensure_ids_exist(tx, &ids)?;
+        apply(tx, &fields, &filter)
+    })?;
+    info!(
+        selected = ids.len(),
+        changed, verb, "Bulk operation on selected transactions"
+    );
+    let message = format!(
+        "{} {} transaction{}",
+        verb,
+        changed,
+        if changed == 1 { "" } else { "s" }
+    );
+    Ok((
+        flash::toast_trigger(FlashLevel::Success, &message),
+        Json(BulkIdsResult {
+            selected: ids.len(),
+            changed,
+        }),
+    ))
+}
+
+pub async fn bulk_ids_set_category(
+    State(state): State<AppState>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    run_bulk_ids(&state, pairs, "Recategorized", |conn, fields, filter| {
+        // set_category_id=0 means "clear category" (set to NULL)
+        let category_id = fields.set_category_id.filter(|&id| id != 0);
+        Ok(transactions::bulk_set_category(conn, filter, category_id)?)
+    })
+}
+
+pub async fn bulk_ids_add_tag(
+    State(state): State<AppState>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    run_bulk_ids(&state, pairs, "Tagged", |conn, fields, filter| {
+        let tag_id = fields
+            .set_tag_id
+            .ok_or_else(|| AppError::Validation("Tag is required".into()))?;
+        Ok(transactions::bulk_add_tag(conn, filter, tag_id)?)
+    })
+}
+
+pub async fn bulk_ids_set_account(
+    State(state): State<AppState>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    run_bulk_ids(&state, pairs, "Moved", |conn, fields, filter| {
+        // set_account_id=0 means "clear account" (set to NULL)
+        let account_id = fields.set_account_id.filter(|&id| id != 0);
+        Ok(transactions::bulk_set_account(conn, filter, account_id)?)
+    })
+}
+
+pub async fn bulk_ids_delete(
+    State(state): State<AppState>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<impl IntoResponse> {
+    run_bulk_ids(&state, pairs, "Deleted", |conn, _, filter| {
+        Ok(transactions::delete_transactions(conn, &filter.ids)?)
+    })
+}
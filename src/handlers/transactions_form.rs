@@ -0,0 +1,363 @@
+//! Forms for creating, duplicating and editing a single transaction.
+
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::Form;
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::date_utils;
+use crate::db::queries::{settings, transactions};
+use crate::error::{render_form, AppError, AppResult, RenderHtml};
+use crate::flash;
+use crate::form_utils::{collect_ids, SubmittedForm};
+use crate::models::{
+    Account, CategoryWithPath, NewTransaction, Settings, Tag, TransactionStatus,
+    TransactionWithRelations,
+};
+use crate::services::money;
+use crate::state::{AppState, JsManifest, PageBase};
+
+#[derive(Template)]
+#[template(path = "pages/transaction_new.html")]
+pub struct TransactionNewTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub categories: Vec<CategoryWithPath>,
+    pub tags: Vec<Tag>,
+    pub accounts: Vec<Account>,
+    pub form_token: String,
+    /// Values of a submission that failed validation, empty for a new form.
+    pub values: SubmittedForm,
+    pub error: Option<String>,
+    /// Transaction the form was filled in from, see [`duplicate_form`].
+    pub duplicate_of: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "pages/transaction_edit.html")]
+pub struct TransactionEditTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub transaction: TransactionWithRelations,
+    pub categories: Vec<CategoryWithPath>,
+    pub tags: Vec<Tag>,
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionFormData {
+    pub date: String,
+    pub amount: String,
+    pub currency: String,
+    pub description: String,
+    /// `None` when the form omits the field, `Some(None)` for "No Category".
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_submitted_i64"
+    )]
+    pub category_id: Option<Option<i64>>,
+    #[serde(
+        default,
+        deserialize_with = "crate::form_utils::deserialize_submitted_i64"
+    )]
+    pub account_id: Option<Option<i64>>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub tag_ids: Vec<i64>,
+    // Extended fields
+    #[serde(default)]
+    pub value_date: Option<String>,
+    #[serde(default)]
+    pub payer: Option<String>,
+    #[serde(default)]
+    pub payee: Option<String>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub transaction_type: Option<String>,
+    #[serde(default)]
+    pub counterparty_iban: Option<String>,
+    #[serde(default)]
+    pub creditor_id: Option<String>,
+    #[serde(default)]
+    pub mandate_reference: Option<String>,
+    #[serde(default)]
+    pub customer_reference: Option<String>,
+    /// HTML checkbox: "on" to mirror the edit onto the linked transfer counterpart.
+    #[serde(default)]
+    pub sync_transfer_pair: String,
+    /// HTML checkbox: "on" if the transaction is not yet booked.
+    #[serde(default)]
+    pub pending: String,
+}
+
+impl TransactionFormData {
+    /// Normalize an optional string: treat empty/whitespace-only as None.
+    fn non_empty(s: &Option<String>) -> Option<String> {
+        s.as_ref()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Dates are read in the configured date format and stored as ISO.
+    fn to_new_transaction(&self, settings: &Settings) -> Result<NewTransaction, AppError> {
+        let amount_cents = money::parse_amount(&self.amount, money::INPUT_LOCALE)
+            .map_err(|_| AppError::Validation("Invalid amount".into()))?;
+        let date =
+            date_utils::parse_user_date(&self.date, settings).map_err(AppError::Validation)?;
+        let value_date = Self::non_empty(&self.value_date)
+            .map(|d| date_utils::parse_user_date(&d, settings).map(|d| d.to_string()))
+            .transpose()
+            .map_err(AppError::Validation)?;
+
+        Ok(NewTransaction {
+            date: date.to_string(),
+            amount_cents,
+            currency: self.currency.clone(),
+            description: self.description.clone(),
+            category_id: self.category_id.flatten(),
+            account_id: self.account_id.flatten(),
+            notes: self.notes.clone(),
+            tag_ids: self.tag_ids.clone(),
+            value_date,
+            payer: Self::non_empty(&self.payer),
+            payee: Self::non_empty(&self.payee),
+            reference: Self::non_empty(&self.reference),
+            transaction_type: Self::non_empty(&self.transaction_type),
+            counterparty_iban: Self::non_empty(&self.counterparty_iban),
+            creditor_id: Self::non_empty(&self.creditor_id),
+            mandate_reference: Self::non_empty(&self.mandate_reference),
+            customer_reference: Self::non_empty(&self.customer_reference),
+            status: if self.pending == "on" {
+                TransactionStatus::Pending
+            } else {
+                TransactionStatus::Posted
+            },
+        })
+    }
+}
+
+pub async fn new_form(State(state): State<AppState>) -> AppResult<Response> {
+    render_new_form(&state, SubmittedForm::default(), None, None)
+}
+
+/// Open the new transaction form filled in like transaction `id`, dated
+/// today, for recording the next one of a recurring payment.
+pub async fn duplicate_form(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Response> {
+    let conn = state.db.get()?;
+    let transaction = transactions::get_transaction(&conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", id)))?;
+    drop(conn);
+    let today = date_utils::today_in(&state.load_settings()?);
+
+    let mut pairs = vec![
+        ("date", today.format("%Y-%m-%d").to_string()),
+        ("amount", transaction.amount_display()),
+        ("currency", transaction.currency.clone()),
+        ("description", transaction.description.clone()),
+    ];
+    pairs.extend(
+        [
+            (
+                "category_id",
+                transaction.category_id.map(|id| id.to_string()),
+            ),
+            (
+                "account_id",
+                transaction.account_id.map(|id| id.to_string()),
+            ),
+            ("notes", transaction.notes.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| Some((k, v?))),
+    );
+    pairs.extend(
+        transaction
+            .tags
+            .iter()
+            .map(|t| ("tag_ids", t.id.to_string())),
+    );
+    let values = SubmittedForm::new(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+    render_new_form(&state, values, None, Some(id))
+}
+
+/// Render the new transaction form, filled with `values` and showing `error`
+/// after a failed submission.
+fn render_new_form(
+    state: &AppState,
+    values: SubmittedForm,
+    error: Option<String>,
+    duplicate_of: Option<i64>,
+) -> AppResult<Response> {
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+    let cats = state.cached_categories_with_path()?;
+    let tag_list = state.cached_tags()?;
+    let cash_accounts = state.cached_cash_accounts()?;
+
+    let template = TransactionNewTemplate {
+        title: "Add Transaction".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        categories: cats,
+        tags: tag_list,
+        accounts: cash_accounts,
+        form_token: values
+            .token()
+            .map_or_else(|| state.submitted_forms.new_token(), str::to_string),
+        values,
+        error,
+        duplicate_of,
+    };
+
+    let has_error = template.error.is_some();
+    render_form(template, has_error)
+}
+
+pub async fn edit_form(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> AppResult<Html<String>> {
+    let conn = state.db.get()?;
+
+    let transaction = transactions::get_transaction(&conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", id)))?;
+
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+
+    let cats = state.cached_categories_with_path()?;
+    let tag_list = state.cached_tags()?;
+    let cash_accounts = state.cached_cash_accounts()?;
+
+    let template = TransactionEditTemplate {
+        title: "Edit Transaction".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        transaction,
+        categories: cats,
+        tags: tag_list,
+        accounts: cash_accounts,
+    };
+
+    template.render_html()
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    create_submitted(&state, SubmittedForm::new(pairs), None)
+}
+
+/// Create the transaction submitted from the duplicate form of transaction
+/// `id`, like any new transaction.
+pub async fn duplicate(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> AppResult<Response> {
+    create_submitted(&state, SubmittedForm::new(pairs), Some(id))
+}
+
+fn create_submitted(
+    state: &AppState,
+    submitted: SubmittedForm,
+    duplicate_of: Option<i64>,
+) -> AppResult<Response> {
+    let created = state
+        .submitted_forms
+        .create_once(submitted.token(), "/transactions", || {
+            create_from_form(state, &submitted)
+        });
+    match created {
+        Ok(Some(repeated)) => Ok(repeated.into_response()),
+        Ok(None) => Ok(Redirect::to("/transactions").into_response()),
+        Err(AppError::Validation(message)) => {
+            render_new_form(state, submitted, Some(message), duplicate_of)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Create a transaction from the new transaction form, returning its URL.
+fn create_from_form(state: &AppState, submitted: &SubmittedForm) -> AppResult<String> {
+    let mut form: TransactionFormData = submitted
+        .parse(&["tag_ids"])
+        .map_err(AppError::Validation)?;
+    form.tag_ids = collect_ids(submitted.pairs(), "tag_ids");
+    debug!(description = %form.description, amount = %form.amount, "Creating transaction");
+    let id = state.with_tx(|tx| {
+        let defaults = settings::get_settings(tx)?;
+        let mut new_transaction = form.to_new_transaction(&defaults)?;
+        if form.category_id.is_none() {
+            new_transaction.category_id = defaults.default_category_id;
+        }
+        if form.account_id.is_none() {
+            new_transaction.account_id = defaults.default_account_id;
+        }
+        let id = transactions::create_transaction(tx, &new_transaction)?;
+        info!(transaction_id = id, "Transaction created via web form");
+        Ok(id)
+    })?;
+    flash::flash_success("Transaction created");
+    Ok(format!("/transactions/{id}"))
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<TransactionFormData>,
+) -> AppResult<Redirect> {
+    debug!(transaction_id = id, "Updating transaction");
+    state.with_tx(|tx| {
+        let new_transaction = form.to_new_transaction(&state.load_settings()?)?;
+        transactions::update_transaction(tx, id, &new_transaction)?;
+        info!(transaction_id = id, "Transaction updated via web form");
+
+        if form.sync_transfer_pair == "on" {
+            let pair_id = transactions::get_transaction(tx, id)?.and_then(|t| t.transfer_pair_id);
+            if let Some(pair_id) = pair_id {
+                transactions::sync_transfer_counterpart(tx, pair_id, &new_transaction)?;
+            }
+        }
+        Ok(())
+    })?;
+    flash::flash_success("Transaction saved");
+    Ok(Redirect::to(&format!("/transactions/{}", id)))
+}
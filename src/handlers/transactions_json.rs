@@ -0,0 +1,387 @@
+//! JSON export and import of transactions, resolving category, account and
+//! tag names to ids.
+
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::DataDomain;
+use crate::db::queries::{accounts, categories, tags, transactions};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    AccountType, ImportSummary, NewAccount, NewCategory, NewTransaction, RecordOutcome,
+    TransactionStatus, DEFAULT_ICON,
+};
+use crate::palette;
+use crate::services::transfer_detection;
+use crate::state::AppState;
+
+#[derive(Serialize)]
+struct TransactionExport {
+    date: String,
+    amount_cents: i64,
+    currency: String,
+    description: String,
+    category_name: Option<String>,
+    account_name: Option<String>,
+    notes: Option<String>,
+    tags: Vec<String>,
+    value_date: Option<String>,
+    payer: Option<String>,
+    payee: Option<String>,
+    reference: Option<String>,
+    transaction_type: Option<String>,
+    counterparty_iban: Option<String>,
+    creditor_id: Option<String>,
+    mandate_reference: Option<String>,
+    customer_reference: Option<String>,
+    status: TransactionStatus,
+}
+
+pub async fn export(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let conn = state.db.get()?;
+
+    let filter = crate::db::queries::transactions::TransactionFilter::default();
+
+    let txns = transactions::list_transactions(&conn, &filter)?;
+
+    let export_data: Vec<TransactionExport> = txns
+        .iter()
+        .map(|t| TransactionExport {
+            date: t.transaction.date.clone(),
+            amount_cents: t.transaction.amount_cents,
+            currency: t.transaction.currency.clone(),
+            description: t.transaction.description.clone(),
+            category_name: t.category_name.clone(),
+            account_name: t.account_name.clone(),
+            notes: t.transaction.notes.clone(),
+            tags: t.tags.iter().map(|tag| tag.name.clone()).collect(),
+            value_date: t.transaction.value_date.clone(),
+            payer: t.transaction.payer.clone(),
+            payee: t.transaction.payee.clone(),
+            reference: t.transaction.reference.clone(),
+            transaction_type: t.transaction.transaction_type.clone(),
+            counterparty_iban: t.transaction.counterparty_iban.clone(),
+            creditor_id: t.transaction.creditor_id.clone(),
+            mandate_reference: t.transaction.mandate_reference.clone(),
+            customer_reference: t.transaction.customer_reference.clone(),
+            status: t.transaction.status,
+        })
+        .collect();
+
+    let json = serde_json::to_string_pretty(&export_data)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"transactions.json\"",
+            ),
+        ],
+        json,
+    ))
+}
+
+#[derive(Deserialize)]
+struct TransactionImport {
+    /// Client-supplied id; re-importing it updates the same transaction.
+    #[serde(default)]
+    external_id: Option<String>,
+    date: String,
+    amount_cents: i64,
+    #[serde(default = "default_currency")]
+    currency: String,
+    description: String,
+    category_name: Option<String>,
+    account_name: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    value_date: Option<String>,
+    #[serde(default)]
+    payer: Option<String>,
+    #[serde(default)]
+    payee: Option<String>,
+    #[serde(default)]
+    reference: Option<String>,
+    #[serde(default)]
+    transaction_type: Option<String>,
+    #[serde(default)]
+    counterparty_iban: Option<String>,
+    #[serde(default)]
+    creditor_id: Option<String>,
+    #[serde(default)]
+    mandate_reference: Option<String>,
+    #[serde(default)]
+    customer_reference: Option<String>,
+    #[serde(default)]
+    status: TransactionStatus,
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ImportParams {
+    /// Create categories, accounts and tags the records name but that do
+    /// not exist yet, instead of dropping them
+    #[serde(default)]
+    pub create_missing: bool,
+}
+
+/// Names a JSON import did not find, split by what happened to them.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct UnknownNames {
+    pub(crate) created: std::collections::BTreeSet<String>,
+    pub(crate) dropped: std::collections::BTreeSet<String>,
+}
+
+/// Maps the names of one kind of entity to IDs during a JSON import.
+pub(crate) struct NameResolver {
+    kind: &'static str,
+    ids: std::collections::HashMap<String, i64>,
+    pub(crate) unknown: UnknownNames,
+}
+
+impl NameResolver {
+    pub(crate) fn new(kind: &'static str, names: impl Iterator<Item = (String, i64)>) -> Self {
+        Self {
+            kind,
+            ids: names.collect(),
+            unknown: UnknownNames::default(),
+        }
+    }
+
+    /// The ID for `name`. Unknown names are created with `create` if given,
+    /// otherwise dropped with a warning for the record.
+    pub(crate) fn resolve(
+        &mut self,
+        name: &str,
+        create: Option<impl FnOnce(&str) -> AppResult<i64>>,
+        warnings: &mut Vec<String>,
+    ) -> AppResult<Option<i64>> {
+        if let Some(&id) = self.ids.get(name) {
+            return Ok(Some(id));
+        }
+        let Some(create) = create else {
+            self.unknown.dropped.insert(name.to_string());
+            warnings.push(format!("Unknown {} \"{}\" was dropped", self.kind, name));
+            return Ok(None);
+        };
+        let id = create(name)?;
+        self.ids.insert(name.to_string(), id);
+        self.unknown.created.insert(name.to_string());
+        Ok(Some(id))
+    }
+}
+
+pub async fn import(
+    State(state): State<AppState>,
+    Query(params): Query<ImportParams>,
+    Json(value): Json<serde_json::Value>,
+) -> AppResult<Json<serde_json::Value>> {
+    let data: Vec<TransactionImport> = serde_json::from_value(value)
+        .map_err(|e| AppError::Validation(format!("Invalid JSON format: {}", e)))?;
+
+    // Build lookup maps for category, account and tag names
+    let cat_list = state.cached_categories()?;
+    let mut category_names =
+        NameResolver::new("category", cat_list.iter().map(|c| (c.name.clone(), c.id)));
+    let mut account_names = NameResolver::new(
+        "account",
+        state.cached_accounts()?.into_iter().map(|a| (a.name, a.id)),
+    );
+    let mut tag_names = NameResolver::new(
+        "tag",
+        state.cached_tags()?.into_iter().map(|t| (t.name, t.id)),
+    );
+    let mut category_colors: Vec<String> = cat_list.into_iter().map(|c| c.color).collect();
+
+    let create = params.create_missing;
+    let (summary, transfers) = state.with_tx(|tx| {
+        let mut summary = ImportSummary::default();
+        for (index, item) in data.into_iter().enumerate() {
+            let mut warnings = Vec::new();
+
+            let category_id = match item.category_name.as_deref().filter(|n| !n.is_empty()) {
+                Some(name) => category_names.resolve(
+                    name,
+                    create.then_some(|name: &str| {
+                        let color = palette::least_used(category_colors.iter().map(String::as_str));
+                        category_colors.push(color.to_string());
+                        Ok(categories::create_category(
+                            tx,
+                            &NewCategory {
+                                name: name.to_string(),
+                                parent_id: None,
+                                color: color.to_string(),
+                                icon: DEFAULT_ICON.into(),
+                            },
+                        )?)
+                    }),
+                    &mut warnings,
+                )?,
+                None => None,
+            };
+
+            let account_id = match item.account_name.as_deref().filter(|n| !n.is_empty()) {
+                Some(name) => account_names.resolve(
+                    name,
+                    create.then_some(|name: &str| {
+                        Ok(accounts::create_account(
+                            tx,
+                            &NewAccount {
+                                name: name.to_string(),
+                                account_type: AccountType::Cash,
+                                active: true,
+                                interest_rate_bps: None,
+                                interest_compounding: Default::default(),
+                                derive_cash_from_trading: false,
+                                statement_day: None,
+                                due_day: None,
+                                cash_account_id: None,
+                                iban: None,
+                            },
+                        )?)
+                    }),
+                    &mut warnings,
+                )?,
+                None => None,
+            };
+
+            let mut tag_ids = Vec::new();
+            for name in item.tags.iter().filter(|n| !n.is_empty()) {
+                let created =
+                    create.then_some(|name: &str| Ok(tags::create_or_get_tag(tx, name)?.id));
+                if let Some(id) = tag_names.resolve(name, created, &mut warnings)? {
+                    tag_ids.push(id);
+                }
+            }
+
+            let new_txn = NewTransaction {
+                date: item.date,
+                amount_cents: item.amount_cents,
+                currency: item.currency,
+                description: item.description,
+                category_id,
+                account_id,
+                notes: item.notes,
+                tag_ids,
+                value_date: item.value_date,
+                payer: item.payer,
+                payee: item.payee,
+                reference: item.reference,
+                transaction_type: item.transaction_type,
+                counterparty_iban: item.counterparty_iban,
+                creditor_id: item.creditor_id,
+                mandate_reference: item.mandate_reference,
+                customer_reference: item.customer_reference,
+                status: item.status,
+            };
+
+            let external_id = item.external_id.filter(|e| !e.trim().is_empty());
+            let result = import_transaction(tx, external_id.as_deref(), &new_txn)?;
+            summary.record_with_warnings(index, external_id, result, warnings);
+        }
+        let transfers = transfer_detection::detect_and_link(tx)?;
+        Ok((summary, transfers))
+    })?;
+
+    // The middleware only knows this path writes transactions
+    let created = [
+        (&category_names, DataDomain::Categories),
+        (&account_names, DataDomain::Accounts),
+        (&tag_names, DataDomain::Tags),
+    ]
+    .into_iter()
+    .filter(|(names, _)| !names.unknown.created.is_empty())
+    .map(|(_, domain)| domain)
+    .collect::<Vec<_>>();
+    state.cache.invalidate_domains(&created);
+
+    let mut body = summary.to_json("transactions");
+    body["unknown_names"] = serde_json::json!({
+        "categories": category_names.unknown,
+        "accounts": account_names.unknown,
+        "tags": tag_names.unknown,
+    });
+    body["transfers"] = serde_json::json!(transfers);
+    Ok(Json(body))
+}
+
+/// Create a transaction, or update the one previously imported under the
+/// same external id. Conflicts are returned as the inner `Err` so they are
+/// reported per record instead of failing the whole import.
+fn import_transaction(
+    conn: &rusqlite::Connection,
+    external_id: Option<&str>,
+    new_txn: &NewTransaction,
+) -> AppResult<Result<(RecordOutcome, i64), String>> {
+    let existing = match external_id {
+        Some(ext) => match transactions::find_by_external_id(conn, ext)? {
+            Some(id) => transactions::get_transaction(conn, id)?,
+            None => None,
+        },
+        None => None,
+    };
+
+    let Some(existing) = existing else {
+        let id = transactions::create_transaction(conn, new_txn)?;
+        if let Some(ext) = external_id {
+            transactions::set_external_id(conn, id, ext)?;
+        }
+        return Ok(Ok((RecordOutcome::Created, id)));
+    };
+
+    if existing.account_id != new_txn.account_id {
+        return Ok(Err(format!(
+            "external_id {} belongs to a transaction in account {}",
+            external_id.unwrap_or_default(),
+            existing.account_name.as_deref().unwrap_or("(none)")
+        )));
+    }
+
+    let mut current_tags: Vec<i64> = existing.tags.iter().map(|t| t.id).collect();
+    current_tags.sort_unstable();
+    let mut new_tags = new_txn.tag_ids.clone();
+    new_tags.sort_unstable();
+    new_tags.dedup();
+    let current = NewTransaction {
+        date: existing.date.clone(),
+        amount_cents: existing.amount_cents,
+        currency: existing.currency.clone(),
+        description: existing.description.clone(),
+        category_id: existing.category_id,
+        account_id: existing.account_id,
+        notes: existing.notes.clone(),
+        tag_ids: current_tags,
+        value_date: existing.value_date.clone(),
+        payer: existing.payer.clone(),
+        payee: existing.payee.clone(),
+        reference: existing.reference.clone(),
+        transaction_type: existing.transaction_type.clone(),
+        counterparty_iban: existing.counterparty_iban.clone(),
+        creditor_id: existing.creditor_id.clone(),
+        mandate_reference: existing.mandate_reference.clone(),
+        customer_reference: existing.customer_reference.clone(),
+        status: existing.status,
+    };
+    if current
+        == (NewTransaction {
+            tag_ids: new_tags,
+            ..new_txn.clone()
+        })
+    {
+        return Ok(Ok((RecordOutcome::Unchanged, existing.id)));
+    }
+
+    transactions::update_transaction(conn, existing.id, new_txn)?;
+    Ok(Ok((RecordOutcome::Updated, existing.id)))
+}
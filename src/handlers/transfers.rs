@@ -0,0 +1,187 @@
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, Redirect};
+use axum::{Form, Json};
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::db::queries::{accounts, categories, transactions};
+use crate::error::{AppError, AppResult, RenderHtml};
+use crate::models::{Account, NewTransaction, Settings, TransactionStatus};
+use crate::services::money;
+use crate::services::transfer_detection::{self, TransferDetection};
+use crate::state::{AppState, JsManifest, PageBase};
+
+#[derive(Template)]
+#[template(path = "pages/transfer_new.html")]
+pub struct TransferNewTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferFormData {
+    pub date: String,
+    pub amount: String,
+    pub from_account: i64,
+    pub to_account: i64,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl TransferFormData {
+    fn amount_cents(&self) -> AppResult<i64> {
+        let cents = money::parse_amount(&self.amount, money::INPUT_LOCALE)
+            .map_err(|_| AppError::Validation("Invalid amount".into()))?;
+        if cents <= 0 {
+            return Err(AppError::Validation(
+                "Transfer amount must be positive".into(),
+            ));
+        }
+        Ok(cents)
+    }
+
+    fn note(&self) -> Option<String> {
+        self.note
+            .as_ref()
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+    }
+
+    /// Build one side of the transfer: money leaves `account` when `amount_cents` is negative.
+    fn side(
+        &self,
+        account: &Account,
+        description: String,
+        amount_cents: i64,
+        currency: &str,
+        category_id: i64,
+    ) -> NewTransaction {
+        NewTransaction {
+            date: self.date.clone(),
+            amount_cents,
+            currency: currency.to_string(),
+            description,
+            category_id: Some(category_id),
+            account_id: Some(account.id),
+            notes: self.note(),
+            tag_ids: Vec::new(),
+            value_date: None,
+            payer: None,
+            payee: None,
+            reference: None,
+            transaction_type: None,
+            counterparty_iban: None,
+            creditor_id: None,
+            mandate_reference: None,
+            customer_reference: None,
+            status: TransactionStatus::Posted,
+        }
+    }
+}
+
+/// Look up an account that can take part in a transfer.
+fn active_account(conn: &rusqlite::Connection, id: i64) -> AppResult<Account> {
+    let account = accounts::get_account(conn, id)?
+        .ok_or_else(|| AppError::Validation(format!("Account {} not found", id)))?;
+    if !account.active {
+        return Err(AppError::Validation(format!(
+            "Account '{}' is inactive",
+            account.name
+        )));
+    }
+    Ok(account)
+}
+
+fn transfers_category_id(state: &AppState) -> AppResult<i64> {
+    categories::transfers_category_id(&state.cached_categories()?)
+        .ok_or_else(|| AppError::NotFound("Transfers category not found".into()))
+}
+
+pub async fn new_form(State(state): State<AppState>) -> AppResult<Html<String>> {
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+    let active_accounts = state
+        .cached_cash_accounts()?
+        .into_iter()
+        .filter(|a| a.active)
+        .collect();
+
+    let template = TransferNewTemplate {
+        title: "Transfer Between Accounts".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        accounts: active_accounts,
+    };
+
+    template.render_html()
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Form(form): Form<TransferFormData>,
+) -> AppResult<Redirect> {
+    debug!(from = form.from_account, to = form.to_account, amount = %form.amount, "Creating transfer");
+    if form.from_account == form.to_account {
+        return Err(AppError::Validation(
+            "Source and destination accounts must differ".into(),
+        ));
+    }
+    let amount_cents = form.amount_cents()?;
+    let category_id = transfers_category_id(&state)?;
+    let currency = state.load_settings()?.currency;
+
+    let (out_id, in_id) = state.with_tx(|tx| {
+        let from = active_account(tx, form.from_account)?;
+        let to = active_account(tx, form.to_account)?;
+
+        let outgoing = form.side(
+            &from,
+            format!("Transfer to {}", to.name),
+            -amount_cents,
+            &currency,
+            category_id,
+        );
+        let incoming = form.side(
+            &to,
+            format!("Transfer from {}", from.name),
+            amount_cents,
+            &currency,
+            category_id,
+        );
+        let out_id = transactions::create_transaction(tx, &outgoing)?;
+        let in_id = transactions::create_transaction(tx, &incoming)?;
+        transactions::link_transfer_pair(tx, out_id, in_id)?;
+        Ok((out_id, in_id))
+    })?;
+    info!(
+        outgoing_id = out_id,
+        incoming_id = in_id,
+        "Transfer created via web form"
+    );
+    Ok(Redirect::to("/transactions"))
+}
+
+/// Link transactions paying another own account, by counterparty IBAN, to
+/// their counterpart there, and report the pairs and the transfers without
+/// a counterpart.
+pub async fn detect(State(state): State<AppState>) -> AppResult<Json<TransferDetection>> {
+    let detection = state.with_tx(|tx| transfer_detection::detect_and_link(tx))?;
+    Ok(Json(detection))
+}
@@ -0,0 +1,156 @@
+//! Usage statistics page: which pages are used, how often they fail and how
+//! fast they answer. Collection is opt-in, see [`crate::usage`].
+
+use askama::Template;
+use axum::extract::{Query, State};
+use axum::response::{Html, Redirect};
+use axum::Form;
+use chrono::Days;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::date_utils;
+use crate::db::queries::{settings, usage_stats};
+use crate::error::{AppResult, RenderHtml};
+use crate::flash;
+use crate::models::usage::{error_rate_label, RouteUsage};
+use crate::models::Settings;
+use crate::state::{AppState, JsManifest, PageBase};
+use crate::usage;
+
+/// Selectable windows as (days, label).
+pub const WINDOWS: &[(i64, &str)] = &[
+    (1, "Today"),
+    (7, "7 Days"),
+    (30, "30 Days"),
+    (90, "90 Days"),
+    (365, "1 Year"),
+];
+
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+
+/// Number of routes listed under "Top Pages".
+const TOP_PAGES: usize = 10;
+
+#[derive(Template)]
+#[template(path = "pages/settings_usage.html")]
+pub struct UsageTemplate {
+    pub title: String,
+    pub settings: Settings,
+    pub icons: crate::filters::Icons,
+    pub manifest: JsManifest,
+    pub version: &'static str,
+    pub xsrf_token: String,
+    pub csp_nonce: String,
+    pub days: i64,
+    pub windows: &'static [(i64, &'static str)],
+    /// Most requested pages (GET routes).
+    pub top_pages: Vec<RouteUsage>,
+    /// All routes and methods, busiest first.
+    pub routes: Vec<RouteUsage>,
+    pub total_requests: i64,
+    pub total_errors: i64,
+    /// Whether any statistics are stored, in any window.
+    pub has_data: bool,
+}
+
+impl UsageTemplate {
+    pub fn error_rate_label(&self) -> String {
+        error_rate_label(self.total_errors, self.total_requests)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub days: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageFormData {
+    /// HTML checkbox: "on" when checked, absent (defaults to "") when unchecked.
+    #[serde(default)]
+    pub usage_stats_enabled: String,
+}
+
+pub async fn index(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> AppResult<Html<String>> {
+    // Show the latest requests too, not only those of the last flush
+    usage::flush(&state)?;
+
+    let PageBase {
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+    } = state.page_base()?;
+    let days = query
+        .days
+        .filter(|days| WINDOWS.iter().any(|(d, _)| d == days))
+        .unwrap_or(DEFAULT_WINDOW_DAYS);
+    let since = date_utils::today_in(&settings) - Days::new(days as u64 - 1);
+
+    let conn = state.db.get()?;
+    let routes = usage::summarize(usage_stats::counts_since(&conn, &since.to_string())?);
+    let has_data = usage_stats::count_rows(&conn)? > 0;
+    let top_pages = routes
+        .iter()
+        .filter(|r| r.method == "GET")
+        .take(TOP_PAGES)
+        .cloned()
+        .collect();
+
+    let template = UsageTemplate {
+        title: "Usage Statistics".into(),
+        settings,
+        icons,
+        manifest,
+        version,
+        xsrf_token,
+        csp_nonce,
+        days,
+        windows: WINDOWS,
+        top_pages,
+        total_requests: routes.iter().map(|r| r.requests).sum(),
+        total_errors: routes.iter().map(|r| r.errors).sum(),
+        routes,
+        has_data,
+    };
+
+    template.render_html()
+}
+
+/// Turn collection on or off. Counts gathered so far are kept until purged.
+pub async fn update(
+    State(state): State<AppState>,
+    Form(form): Form<UsageFormData>,
+) -> AppResult<Redirect> {
+    let enabled = form.usage_stats_enabled == "on";
+    if !enabled {
+        usage::flush(&state)?;
+    }
+    let conn = state.db.get()?;
+    settings::set_setting(&conn, "usage_stats_enabled", &enabled.to_string())?;
+    info!(enabled, "Usage statistics setting updated");
+
+    flash::flash_success(if enabled {
+        "Usage statistics enabled"
+    } else {
+        "Usage statistics disabled"
+    });
+    Ok(Redirect::to("/settings/usage"))
+}
+
+/// Delete all stored and pending usage statistics.
+pub async fn purge(State(state): State<AppState>) -> AppResult<Html<String>> {
+    state.usage.take();
+    let conn = state.db.get()?;
+    let deleted = usage_stats::delete_all(&conn)?;
+    info!(rows = deleted, "Purged usage statistics");
+
+    flash::flash_success("Usage statistics deleted");
+    Ok(Html(String::new()))
+}
@@ -0,0 +1,156 @@
+//! Double-submit protection for create forms.
+//!
+//! The new-entity forms carry a one-time [`FORM_TOKEN_FIELD`]. The first
+//! POST with a token claims it; once the entity is created, the token
+//! remembers where it lives. Submitting the same form again (a double click,
+//! a browser retry, going back and resubmitting) creates nothing and
+//! redirects to the entity created the first time. Only the most recent
+//! [`MAX_TOKENS`] tokens are remembered.
+
+use axum::response::Redirect;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+
+/// Name of the hidden form field holding the token.
+pub const FORM_TOKEN_FIELD: &str = "form_token";
+
+/// How many submitted tokens are remembered before the oldest is forgotten.
+pub const MAX_TOKENS: usize = 1024;
+
+/// Outcome of claiming a form token.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Claim {
+    /// First submission; the handler should create the entity.
+    New,
+    /// An earlier submission with this token is still being processed.
+    Pending,
+    /// An earlier submission created the entity at this URL.
+    Done(String),
+}
+
+#[derive(Default)]
+struct Submitted {
+    /// Tokens in claim order, oldest first.
+    order: VecDeque<String>,
+    /// Maps token → URL of the created entity, `None` while pending.
+    redirects: HashMap<String, Option<String>>,
+}
+
+/// Recently submitted form tokens.
+#[derive(Default)]
+pub struct SubmittedForms {
+    submitted: Mutex<Submitted>,
+}
+
+impl SubmittedForms {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A fresh token to embed in a new form.
+    pub fn new_token(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+
+    /// Claim `token` for a submission, forgetting the oldest token if the
+    /// set is full.
+    pub fn claim(&self, token: &str) -> Claim {
+        let mut submitted = self.submitted.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(redirect) = submitted.redirects.get(token) {
+            return match redirect {
+                Some(url) => Claim::Done(url.clone()),
+                None => Claim::Pending,
+            };
+        }
+        submitted.redirects.insert(token.to_string(), None);
+        submitted.order.push_back(token.to_string());
+        while submitted.order.len() > MAX_TOKENS {
+            if let Some(oldest) = submitted.order.pop_front() {
+                submitted.redirects.remove(&oldest);
+            }
+        }
+        Claim::New
+    }
+
+    /// Record that the submission with `token` created the entity at `url`.
+    pub fn complete(&self, token: &str, url: &str) {
+        let mut submitted = self.submitted.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(redirect) = submitted.redirects.get_mut(token) {
+            *redirect = Some(url.to_string());
+        }
+    }
+
+    /// Forget `token` after a failed submission, so the corrected form can be
+    /// submitted with it again.
+    pub fn release(&self, token: &str) {
+        let mut submitted = self.submitted.lock().unwrap_or_else(|e| e.into_inner());
+        if submitted.redirects.remove(token).is_some() {
+            submitted.order.retain(|t| t != token);
+        }
+    }
+
+    /// Run `create` once per form token. `create` returns the URL of the new
+    /// entity. Returns `Some` redirect instead of running it when the token
+    /// was already submitted: to the created entity, or to `pending_url` while
+    /// the first submission is still running. A failed `create` releases the
+    /// token. Forms without a token are always created.
+    pub fn create_once(
+        &self,
+        token: Option<&str>,
+        pending_url: &str,
+        create: impl FnOnce() -> AppResult<String>,
+    ) -> AppResult<Option<Redirect>> {
+        let Some(token) = token else {
+            return create().map(|_| None);
+        };
+        match self.claim(token) {
+            Claim::New => {}
+            Claim::Pending => return Ok(Some(Redirect::to(pending_url))),
+            Claim::Done(url) => return Ok(Some(Redirect::to(&url))),
+        }
+        match create() {
+            Ok(url) => {
+                self.complete(token, &url);
+                Ok(None)
+            }
+            Err(e) => {
+                self.release(token);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_token_returns_redirect() {
+        let forms = SubmittedForms::new();
+        let token = forms.new_token();
+        assert_eq!(forms.claim(&token), Claim::New);
+        assert_eq!(forms.claim(&token), Claim::Pending);
+        forms.complete(&token, "/transactions/1");
+        assert_eq!(forms.claim(&token), Claim::Done("/transactions/1".into()));
+
+        let failed = forms.new_token();
+        assert_eq!(forms.claim(&failed), Claim::New);
+        forms.release(&failed);
+        assert_eq!(forms.claim(&failed), Claim::New);
+    }
+
+    #[test]
+    fn test_oldest_token_is_forgotten() {
+        let forms = SubmittedForms::new();
+        let first = forms.new_token();
+        forms.claim(&first);
+        for _ in 0..MAX_TOKENS {
+            forms.claim(&forms.new_token());
+        }
+        assert_eq!(forms.claim(&first), Claim::New);
+    }
+}
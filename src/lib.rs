@@ -1,18 +1,30 @@
 pub mod auth;
+pub mod body_limit;
 pub mod cache;
 pub mod config;
+pub mod confirmation;
+pub mod csp;
 pub mod date_utils;
 pub mod db;
 pub mod error;
 pub mod error_pages;
 pub mod filters;
+pub mod flash;
 pub mod form_utils;
 pub mod handlers;
+pub mod idempotency;
+pub mod logging;
 pub mod models;
+pub mod nav;
+pub mod palette;
+pub mod request_id;
 pub mod server;
 pub mod services;
 pub mod sort_utils;
 pub mod state;
+pub mod symbol_locks;
+pub mod timing;
+pub mod usage;
 pub mod xsrf;
 
 /// Application version from Cargo.toml (single source of truth)
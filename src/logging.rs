@@ -0,0 +1,74 @@
+//! Tracing setup with a log filter that can be changed while running.
+//!
+//! [`init`] installs the global subscriber with its `EnvFilter` wrapped in a
+//! reload layer. The advanced settings page calls [`set_filter`] to swap the
+//! directives without a restart.
+
+use std::sync::OnceLock;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry};
+
+struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives in effect at startup, restored when the setting is cleared.
+    startup: String,
+}
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// Install the global tracing subscriber. The filter comes from `RUST_LOG`,
+/// falling back to `default_filter`.
+pub fn init(default_filter: &str) {
+    let startup = std::env::var("RUST_LOG")
+        .ok()
+        .filter(|v| EnvFilter::try_new(v).is_ok())
+        .unwrap_or_else(|| default_filter.to_string());
+    let (layer, handle) = reload::Layer::new(EnvFilter::new(&startup));
+
+    tracing_subscriber::registry()
+        .with(layer)
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let _ = LOG_FILTER.set(LogFilter { handle, startup });
+}
+
+/// Whether the filter can be changed at runtime, i.e. [`init`] was called.
+pub fn is_reloadable() -> bool {
+    LOG_FILTER.get().is_some()
+}
+
+/// The filter directives the process started with, if [`init`] was called.
+pub fn startup_filter() -> Option<&'static str> {
+    LOG_FILTER.get().map(|f| f.startup.as_str())
+}
+
+/// Check that `directives` parse as an `EnvFilter`.
+pub fn validate_filter(directives: &str) -> Result<(), String> {
+    EnvFilter::try_new(directives)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Replace the active filter with `directives`; an empty string restores the
+/// startup filter. Does nothing but validate when [`init`] was not called.
+pub fn set_filter(directives: &str) -> Result<(), String> {
+    let directives = directives.trim();
+    validate_filter(directives)?;
+    let Some(filter) = LOG_FILTER.get() else {
+        return Ok(());
+    };
+    let directives = if directives.is_empty() {
+        filter.startup.as_str()
+    } else {
+        directives
+    };
+    filter
+        .handle
+        .reload(EnvFilter::new(directives))
+        .map_err(|e| e.to_string())?;
+    tracing::info!(filter = %directives, "Log filter changed");
+    Ok(())
+}
@@ -1,19 +1,21 @@
 use solvency::config::{AuthMode, Config};
+use solvency::db::migrations;
+use solvency::logging;
 use solvency::server;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
+use solvency::services::backup;
+use solvency::usage;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "solvency=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    logging::init("solvency=debug,tower_http=debug");
 
     let config = Config::from_env();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        std::process::exit(migrate_command(&config, &args[1..]));
+    }
+
     tracing::info!("Starting Solvency on {}", config.address());
 
     match &config.auth_mode {
@@ -28,7 +30,15 @@ async fn main() {
     let host = config.host.clone();
     let port = config.port;
 
-    let (_state, app) = server::build_app(config).expect("Failed to build app");
+    let (state, app) = match server::build_app(config) {
+        Ok(built) => built,
+        Err(e) => {
+            tracing::error!("Failed to build app: {}", e);
+            std::process::exit(1);
+        }
+    };
+    tokio::spawn(usage::run_flusher(state.clone()));
+    tokio::spawn(backup::run_scheduler(state));
 
     let (actual_port, handle) = server::serve(app, &host, port)
         .await
@@ -38,3 +48,56 @@ async fn main() {
 
     handle.await.expect("Server task panicked");
 }
+
+/// `solvency migrate` applies pending migrations; `solvency migrate --status`
+/// lists applied and pending migrations. Returns the process exit code.
+fn migrate_command(config: &Config, args: &[String]) -> i32 {
+    let conn = match rusqlite::Connection::open(&config.database_path) {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("Failed to open {}: {}", config.database_path.display(), e);
+            return 1;
+        }
+    };
+
+    match args.first().map(String::as_str) {
+        None => match migrations::run_migrations(
+            &conn,
+            &config.migrations_path,
+            config.allow_dirty_migrations,
+        ) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("{}", e);
+                1
+            }
+        },
+        Some("--status") => match migrations::migration_status(&conn, &config.migrations_path) {
+            Ok(statuses) => {
+                for status in statuses {
+                    let applied = status.applied_at.as_deref().unwrap_or("pending");
+                    let note = if status.missing {
+                        " (file missing)"
+                    } else if status.modified {
+                        " (modified since applied)"
+                    } else {
+                        ""
+                    };
+                    println!("{:<20} {}{}", applied, status.name, note);
+                }
+                0
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                1
+            }
+        },
+        Some(other) => {
+            eprintln!(
+                "Unknown option: {}\nUsage: solvency migrate [--status]",
+                other
+            );
+            2
+        }
+    }
+}
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 pub enum AccountType {
     Cash,
     Securities,
+    CreditCard,
 }
 
 impl AccountType {
@@ -11,6 +12,7 @@ impl AccountType {
         match self {
             AccountType::Cash => "Cash",
             AccountType::Securities => "Securities",
+            AccountType::CreditCard => "CreditCard",
         }
     }
 
@@ -18,9 +20,24 @@ impl AccountType {
         match s {
             "Cash" => Some(AccountType::Cash),
             "Securities" => Some(AccountType::Securities),
+            "CreditCard" => Some(AccountType::CreditCard),
             _ => None,
         }
     }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccountType::Cash => "Cash",
+            AccountType::Securities => "Securities",
+            AccountType::CreditCard => "Credit Card",
+        }
+    }
+
+    /// Whether transactions are booked to accounts of this type, so their
+    /// balance is the sum of their transactions.
+    pub fn holds_transactions(&self) -> bool {
+        matches!(self, AccountType::Cash | AccountType::CreditCard)
+    }
 }
 
 impl std::fmt::Display for AccountType {
@@ -29,19 +46,102 @@ impl std::fmt::Display for AccountType {
     }
 }
 
+/// How often interest is credited to an account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterestCompounding {
+    #[default]
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl InterestCompounding {
+    pub fn all() -> &'static [InterestCompounding] {
+        &[Self::Monthly, Self::Quarterly, Self::Yearly]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Monthly => "monthly",
+            Self::Quarterly => "quarterly",
+            Self::Yearly => "yearly",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Monthly => "Monthly",
+            Self::Quarterly => "Quarterly",
+            Self::Yearly => "Yearly",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "monthly" => Some(Self::Monthly),
+            "quarterly" => Some(Self::Quarterly),
+            "yearly" => Some(Self::Yearly),
+            _ => None,
+        }
+    }
+
+    /// Number of months between interest credits.
+    pub fn months(&self) -> u32 {
+        match self {
+            Self::Monthly => 1,
+            Self::Quarterly => 3,
+            Self::Yearly => 12,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub id: i64,
     pub name: String,
     pub account_type: AccountType,
     pub active: bool,
+    /// Annual interest rate in basis points, if the account pays interest.
+    pub interest_rate_bps: Option<i64>,
+    pub interest_compounding: InterestCompounding,
+    /// Securities accounts only: show a cash balance replayed from trading
+    /// activities and deposits (see `services::cash_ledger`).
+    pub derive_cash_from_trading: bool,
+    /// Credit cards only: day of the month the statement closes (1-31).
+    pub statement_day: Option<u32>,
+    /// Credit cards only: day of the month the statement balance is due.
+    pub due_day: Option<u32>,
+    /// Securities accounts only: cash account that transactions mirroring
+    /// dividends, fees and taxes are booked to (see
+    /// `services::trading_mirror`).
+    pub cash_account_id: Option<i64>,
+    /// IBAN of the account, normalized (see `models::rule::normalize_iban`).
+    /// Transactions with it as counterparty IBAN are transfers to this account.
+    pub iban: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+impl Account {
+    /// Interest rate as a percentage for form inputs (e.g. "2.5").
+    pub fn interest_rate_percent(&self) -> String {
+        self.interest_rate_bps
+            .map(|bps| crate::services::money::format_decimal(bps as f64 / 100.0, 2))
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct NewAccount {
     pub name: String,
     pub account_type: AccountType,
     pub active: bool,
+    pub interest_rate_bps: Option<i64>,
+    pub interest_compounding: InterestCompounding,
+    pub derive_cash_from_trading: bool,
+    pub statement_day: Option<u32>,
+    pub due_day: Option<u32>,
+    pub cash_account_id: Option<i64>,
+    pub iban: Option<String>,
 }
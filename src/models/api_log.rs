@@ -13,9 +13,18 @@ pub struct ApiLog {
     pub response_details: Option<String>,
     pub duration_ms: Option<i64>,
     pub created_at: String,
+    /// Entry this one retried, if it was started from the log detail page
+    pub retried_from: Option<i64>,
 }
 
+/// Action whose request can be re-run from the log detail page
+pub const RETRYABLE_ACTION: &str = "fetch_historical_quotes";
+
 impl ApiLog {
+    pub fn is_retryable(&self) -> bool {
+        self.action == RETRYABLE_ACTION && self.symbol.is_some()
+    }
+
     pub fn is_error(&self) -> bool {
         self.status == "error"
     }
@@ -40,4 +49,5 @@ pub struct NewApiLog {
     pub response_summary: Option<String>,
     pub response_details: Option<String>,
     pub duration_ms: Option<i64>,
+    pub retried_from: Option<i64>,
 }
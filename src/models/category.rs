@@ -21,6 +21,9 @@ pub struct CategoryWithPath {
     pub category: Category,
     pub path: String,
     pub depth: i64,
+    /// Black or white, whichever reads better on the category color.
+    #[serde(default)]
+    pub text_color: String,
 }
 
 impl CategoryWithPath {
@@ -56,6 +56,12 @@ pub struct ImportSession {
     pub processed_rows: i64,
     pub error_count: i64,
     pub errors: Vec<String>,
+    /// Tags applied to every imported transaction.
+    pub tag_ids: Vec<i64>,
+    /// Row index the next import batch starts at (see `import::checkpoint_session`).
+    pub resume_from_row_index: i64,
+    /// Parse statistics of each uploaded file, in upload order.
+    pub file_stats: Vec<ImportFileStats>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -92,6 +98,54 @@ impl ImportSession {
     pub fn is_failed(&self) -> bool {
         matches!(self.status, ImportStatus::Failed)
     }
+
+    pub fn has_tag(&self, tag_id: &i64) -> bool {
+        self.tag_ids.contains(tag_id)
+    }
+}
+
+/// An import session that can be picked up again from the wizard: still in
+/// preview, or interrupted while importing.
+#[derive(Debug, Clone)]
+pub struct OpenImportSession {
+    pub session: ImportSession,
+    /// Rows with a category, chosen by hand or by a rule.
+    pub categorized_rows: i64,
+}
+
+impl OpenImportSession {
+    /// Names of the uploaded files, comma-separated.
+    pub fn file_names(&self) -> String {
+        self.session
+            .file_stats
+            .iter()
+            .map(|f| f.file_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn progress_label(&self) -> String {
+        let s = &self.session;
+        if s.is_importing() {
+            format!("{} of {} rows imported", s.processed_rows, s.total_rows)
+        } else {
+            format!(
+                "{} of {} rows categorized",
+                self.categorized_rows, s.total_rows
+            )
+        }
+    }
+}
+
+/// How many rows one uploaded file contributed to an import session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportFileStats {
+    pub file_name: String,
+    /// Rows added to the session.
+    pub rows: i64,
+    /// Rows skipped because an earlier file of the upload contained them.
+    pub duplicates: i64,
+    pub errors: i64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -130,8 +184,151 @@ pub struct ImportRow {
     pub session_id: String,
     pub row_index: i64,
     pub data: crate::services::csv_parser::ParsedTransaction,
+    /// Name of the uploaded file the row came from.
+    pub source_file: Option<String>,
     pub category_id: Option<i64>,
     pub category_name: Option<String>,
     pub status: String,
     pub error: Option<String>,
+    /// Per-row tag override; `None` uses the session's tags.
+    pub tag_ids: Option<Vec<i64>>,
+    /// Merge the row into `pending_match` instead of importing a new transaction.
+    pub merge_pending: bool,
+    /// Pending transaction that this row probably books.
+    pub pending_match: Option<PendingMatch>,
+    /// Rules that set the row's category or added tags to it
+    pub rule_ids: Vec<i64>,
+    /// Rule that set the category, unless it was since set by hand
+    pub category_rule_id: Option<i64>,
+}
+
+impl ImportRow {
+    pub fn has_tag(&self, tag_id: &i64) -> bool {
+        self.tag_ids
+            .as_ref()
+            .is_some_and(|ids| ids.contains(tag_id))
+    }
+
+    /// The parsed payee, or the description if the row has none, in the
+    /// normalized form used by rule suggestions. Groups rows in the wizard.
+    pub fn payee_key(&self) -> String {
+        crate::services::rule_suggestion::counterparty_key(self.payee_or_description())
+    }
+
+    pub fn payee_or_description(&self) -> &str {
+        self.data
+            .payee
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or(&self.data.description)
+    }
+
+    /// The pending transaction to merge this row into, if any.
+    pub fn merge_target(&self) -> Option<i64> {
+        self.pending_match
+            .as_ref()
+            .filter(|_| self.merge_pending)
+            .map(|m| m.transaction_id)
+    }
+}
+
+/// A pending transaction that matches an import row by amount and date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingMatch {
+    pub transaction_id: i64,
+    pub date: String,
+    pub description: String,
+}
+
+/// What a JSON import did with one record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordOutcome {
+    Created,
+    Updated,
+    Unchanged,
+    Error,
+}
+
+/// Per-record entry in the response of a JSON import.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordResult {
+    /// Position of the record in the request body.
+    pub index: usize,
+    pub external_id: Option<String>,
+    pub outcome: RecordOutcome,
+    pub id: Option<i64>,
+    pub error: Option<String>,
+    /// Parts of the record that were left out, e.g. unknown names
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Response of a JSON import. Records with an `external_id` seen before
+/// update the row created for it instead of inserting a duplicate.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    pub records: Vec<RecordResult>,
+}
+
+impl ImportSummary {
+    pub fn record(
+        &mut self,
+        index: usize,
+        external_id: Option<String>,
+        result: Result<(RecordOutcome, i64), String>,
+    ) {
+        self.record_with_warnings(index, external_id, result, Vec::new());
+    }
+
+    pub fn record_with_warnings(
+        &mut self,
+        index: usize,
+        external_id: Option<String>,
+        result: Result<(RecordOutcome, i64), String>,
+        warnings: Vec<String>,
+    ) {
+        let (outcome, id, error) = match result {
+            Ok((outcome, id)) => (outcome, Some(id), None),
+            Err(e) => (RecordOutcome::Error, None, Some(e)),
+        };
+        match outcome {
+            RecordOutcome::Created => self.created += 1,
+            RecordOutcome::Updated => self.updated += 1,
+            RecordOutcome::Unchanged => self.unchanged += 1,
+            RecordOutcome::Error => self.failed += 1,
+        }
+        self.records.push(RecordResult {
+            index,
+            external_id,
+            outcome,
+            id,
+            error,
+            warnings,
+        });
+    }
+
+    /// The response body; `imported` counts new rows as it always has.
+    pub fn to_json(&self, noun: &str) -> serde_json::Value {
+        let mut message = format!("Successfully imported {} {}", self.created, noun);
+        if self.updated + self.unchanged + self.failed > 0 {
+            message.push_str(&format!(
+                " ({} updated, {} unchanged, {} failed)",
+                self.updated, self.unchanged, self.failed
+            ));
+        }
+        serde_json::json!({
+            "imported": self.created,
+            "created": self.created,
+            "updated": self.updated,
+            "unchanged": self.unchanged,
+            "failed": self.failed,
+            "records": self.records,
+            "message": message,
+        })
+    }
 }
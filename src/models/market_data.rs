@@ -33,6 +33,8 @@ pub struct NewMarketData {
     pub symbol: String,
     pub date: String,
     pub close_price_cents: i64,
+    /// Close as quoted, unrounded; `None` stores only the cents.
+    pub close_price: Option<f64>,
     pub currency: String,
 }
 
@@ -50,6 +52,9 @@ pub struct SymbolDataCoverage {
     pub has_current_price: bool,
     /// Whether the position is closed (net quantity = 0)
     pub is_closed: bool,
+    /// Left out of market data refreshes
+    pub ignored: bool,
+    pub ignore_note: Option<String>,
 }
 
 impl SymbolDataCoverage {
@@ -92,17 +97,59 @@ fn currency_symbol(currency: &str) -> &'static str {
 }
 
 /// Cached symbol metadata from Yahoo Finance
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct SymbolMetadata {
     pub symbol: String,
     pub short_name: Option<String>,
     pub long_name: Option<String>,
     pub exchange: Option<String>,
     pub quote_type: Option<String>,
+    /// Factor turning stored prices into `price_currency`, e.g. 0.01 for
+    /// prices quoted in pence
+    pub price_scale: f64,
+    /// Currency of the scaled prices, if known
+    pub price_currency: Option<String>,
+    /// Left out of market data refreshes
+    pub ignored: bool,
+    /// Why the symbol is ignored, e.g. "Delisted in 2019"
+    pub ignore_note: Option<String>,
 }
 
 impl SymbolMetadata {
     pub fn display_name(&self) -> Option<&String> {
         self.long_name.as_ref().or(self.short_name.as_ref())
     }
+
+    /// Price currency if prices need converting into `currency`. `None` if
+    /// they are the same or the price currency is unknown.
+    pub fn fx_from(&self, currency: &str) -> Option<&str> {
+        self.price_currency
+            .as_deref()
+            .filter(|c| !c.eq_ignore_ascii_case(currency))
+    }
+}
+
+/// Yahoo ticker of the exchange rate from `from` into `to`, e.g. "GBPEUR=X".
+pub fn fx_pair(from: &str, to: &str) -> String {
+    format!("{}{}=X", from.to_uppercase(), to.to_uppercase())
+}
+
+/// Exchange rate from the close of the direct pair (see [`fx_pair`]), else
+/// the inverse of the close of the reverse pair. Closes of zero or less
+/// count as missing.
+pub fn fx_rate(direct: Option<f64>, inverse: Option<f64>) -> Option<f64> {
+    direct
+        .filter(|rate| *rate > 0.0)
+        .or_else(|| inverse.filter(|rate| *rate > 0.0).map(|rate| 1.0 / rate))
+}
+
+/// Major currency and scale of a quote currency Yahoo reports in minor
+/// units, e.g. "GBp" (pence) is 0.01 GBP.
+pub fn minor_currency_unit(quote_currency: &str) -> Option<(&'static str, f64)> {
+    match quote_currency {
+        "GBp" | "GBX" => Some(("GBP", 0.01)),
+        "ZAc" | "ZAC" => Some(("ZAR", 0.01)),
+        "ILA" => Some(("ILS", 0.01)),
+        _ => None,
+    }
 }
@@ -6,26 +6,35 @@ pub mod market_data;
 pub mod net_worth;
 pub mod retirement;
 pub mod rule;
+pub mod saved_chart;
 pub mod settings;
+pub mod share_link;
 pub mod tag;
 pub mod trading;
 pub mod transaction;
+pub mod usage;
 
 pub use account::{Account, AccountType, NewAccount};
 pub use api_log::{ApiLog, NewApiLog};
 pub use category::{Category, CategoryWithPath, NewCategory, DEFAULT_COLOR, DEFAULT_ICON};
-pub use import::{ImportRow, ImportRowStatus, ImportSession, ImportStatus};
+pub use import::{
+    ImportFileStats, ImportRow, ImportRowStatus, ImportSession, ImportStatus, ImportSummary,
+    OpenImportSession, PendingMatch, RecordOutcome,
+};
 pub use market_data::{MarketData, NewMarketData, SymbolDataCoverage};
 pub use net_worth::{NetWorthDataPoint, NetWorthSummary};
 pub use retirement::{
     MonteCarloResult, RetirementChartData, RetirementProjection, SavingsRow, Scenario,
     SimulateResponse, WithdrawalRow,
 };
-pub use rule::{NewRule, Rule, RuleActionType};
+pub use rule::{NewRule, Rule, RuleActionType, RuleMatchField};
+pub use saved_chart::{NewSavedChart, SavedChart, SavedChartParams, SavedChartType};
 pub use settings::Settings;
-pub use tag::{NewTag, Tag, TagStyle, TagWithUsage, TAG_PALETTE};
+pub use share_link::{NewShareLink, ShareLink, ShareScope};
+pub use tag::{NewTag, Tag, TagStyle, TagWithUsage};
 pub use trading::{
-    NewTradingActivity, Position, PositionWithMarketData, TradingActivity, TradingActivityType,
-    TradingImportRow, TradingImportRowStatus, TradingImportSession, TradingImportStatus,
+    NewTradingActivity, Position, PositionDelta, PositionWithMarketData, TradingActivity,
+    TradingActivityType, TradingImportRow, TradingImportRowStatus, TradingImportSession,
+    TradingImportStatus,
 };
-pub use transaction::{NewTransaction, Transaction, TransactionWithRelations};
+pub use transaction::{NewTransaction, Transaction, TransactionStatus, TransactionWithRelations};
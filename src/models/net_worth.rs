@@ -7,6 +7,10 @@ pub struct NetWorthDataPoint {
     pub net_worth_cents: i64,
     pub transaction_component_cents: i64,
     pub portfolio_component_cents: i64,
+    /// Cumulative external cash flows and trading deposits
+    pub contributions_cents: i64,
+    /// Implied market growth (net worth minus contributions)
+    pub growth_cents: i64,
 }
 
 /// Summary of net worth calculation results
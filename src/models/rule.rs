@@ -1,3 +1,4 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -37,13 +38,90 @@ impl std::fmt::Display for RuleActionType {
     }
 }
 
+/// Transaction field a rule's pattern is matched against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleMatchField {
+    #[default]
+    Description,
+    /// Payee or payer
+    Counterparty,
+    /// Counterparty IBAN, compared without spaces
+    Iban,
+}
+
+impl RuleMatchField {
+    pub fn all() -> &'static [RuleMatchField] {
+        &[
+            RuleMatchField::Description,
+            RuleMatchField::Counterparty,
+            RuleMatchField::Iban,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleMatchField::Description => "description",
+            RuleMatchField::Counterparty => "counterparty",
+            RuleMatchField::Iban => "iban",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "description" => Some(RuleMatchField::Description),
+            "counterparty" => Some(RuleMatchField::Counterparty),
+            "iban" => Some(RuleMatchField::Iban),
+            _ => None,
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            RuleMatchField::Description => "Description",
+            RuleMatchField::Counterparty => "Payee / Payer",
+            RuleMatchField::Iban => "IBAN",
+        }
+    }
+
+    /// Whether `re` matches this field of a transaction.
+    pub fn is_match(
+        &self,
+        re: &Regex,
+        description: &str,
+        payer: Option<&str>,
+        payee: Option<&str>,
+        iban: Option<&str>,
+    ) -> bool {
+        match self {
+            RuleMatchField::Description => re.is_match(description),
+            RuleMatchField::Counterparty => {
+                [payee, payer].into_iter().flatten().any(|c| re.is_match(c))
+            }
+            RuleMatchField::Iban => iban.is_some_and(|iban| re.is_match(&normalize_iban(iban))),
+        }
+    }
+}
+
+/// An IBAN without spaces, in upper case.
+pub fn normalize_iban(iban: &str) -> String {
+    iban.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rule {
     pub id: i64,
     pub name: String,
     pub pattern: String,
+    pub match_field: RuleMatchField,
     pub action_type: RuleActionType,
     pub action_value: String,
+    /// Transactions the rule changed, summed over import and apply runs
+    pub match_count: i64,
+    pub last_matched_at: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -52,6 +130,8 @@ pub struct Rule {
 pub struct NewRule {
     pub name: String,
     pub pattern: String,
+    #[serde(default)]
+    pub match_field: RuleMatchField,
     pub action_type: RuleActionType,
     pub action_value: String,
 }
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+/// The API endpoint a saved chart is drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SavedChartType {
+    MonthlyByCategory,
+    SpendingOverTime,
+    Comparison,
+}
+
+impl SavedChartType {
+    pub fn all() -> &'static [SavedChartType] {
+        &[
+            Self::MonthlyByCategory,
+            Self::SpendingOverTime,
+            Self::Comparison,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MonthlyByCategory => "monthly-by-category",
+            Self::SpendingOverTime => "spending-over-time",
+            Self::Comparison => "comparison",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::MonthlyByCategory => "Monthly by Category",
+            Self::SpendingOverTime => "Spending Over Time",
+            Self::Comparison => "Compared to Previous Period",
+        }
+    }
+
+    pub fn api_path(&self) -> &'static str {
+        match self {
+            Self::MonthlyByCategory => "/api/analytics/monthly-by-category",
+            Self::SpendingOverTime => "/api/analytics/spending-over-time",
+            Self::Comparison => "/api/analytics/spending-comparison",
+        }
+    }
+
+    /// Whether the chart can show income instead of expenses.
+    pub fn supports_income(&self) -> bool {
+        *self == Self::MonthlyByCategory
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "monthly-by-category" => Some(Self::MonthlyByCategory),
+            "spending-over-time" => Some(Self::SpendingOverTime),
+            "comparison" => Some(Self::Comparison),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SavedChartType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Settings of a saved chart, stored as JSON. The date range is not part of
+/// them: charts follow the range selected on the spending page.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedChartParams {
+    /// Categories to chart; empty for all.
+    #[serde(default)]
+    pub category_ids: Vec<i64>,
+    /// "income" to chart income instead of expenses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+}
+
+impl SavedChartParams {
+    pub fn is_income(&self) -> bool {
+        self.mode.as_deref() == Some("income")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedChart {
+    pub id: i64,
+    pub name: String,
+    pub chart_type: SavedChartType,
+    pub params: SavedChartParams,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl SavedChart {
+    /// URL of the chart's data for a date range. Categories not in
+    /// `existing_ids`, deleted since the chart was saved, are left out.
+    /// `None` when all of the chart's categories were deleted.
+    pub fn api_url(&self, from_date: &str, to_date: &str, existing_ids: &[i64]) -> Option<String> {
+        let mut url = format!(
+            "{}?from_date={}&to_date={}",
+            self.chart_type.api_path(),
+            from_date,
+            to_date
+        );
+        let ids: Vec<String> = self
+            .params
+            .category_ids
+            .iter()
+            .filter(|id| existing_ids.contains(id))
+            .map(|id| id.to_string())
+            .collect();
+        if !ids.is_empty() {
+            url.push_str(&format!("&category_ids={}", ids.join(",")));
+        } else if !self.params.category_ids.is_empty() {
+            return None;
+        }
+        if self.params.is_income() {
+            url.push_str("&mode=income");
+        }
+        Some(url)
+    }
+}
+
+/// Data for creating or updating a saved chart.
+#[derive(Debug, Clone)]
+pub struct NewSavedChart {
+    pub name: String,
+    pub chart_type: SavedChartType,
+    pub params: SavedChartParams,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_url_drops_deleted_categories() {
+        let chart = SavedChart {
+            id: 1,
+            name: "Food".into(),
+            chart_type: SavedChartType::MonthlyByCategory,
+            params: SavedChartParams {
+                category_ids: vec![4, 9, 5],
+                mode: Some("income".into()),
+            },
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        assert_eq!(
+            chart.api_url("2024-01-01", "2024-12-31", &[4, 5, 6]).as_deref(),
+            Some("/api/analytics/monthly-by-category?from_date=2024-01-01&to_date=2024-12-31&category_ids=4,5&mode=income")
+        );
+        assert_eq!(chart.api_url("2024-01-01", "2024-12-31", &[6]), None);
+    }
+}
@@ -1,6 +1,56 @@
-use crate::filters;
+use crate::date_utils::DateFormat;
+use crate::filters::{self, CurrencyFormat};
+use crate::models::TradingActivityType;
+use crate::nav::{self, NavItem, NavSection};
+use crate::sort_utils::{SortableColumn, TableSort};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
+
+/// Default pause between market data API requests, in milliseconds.
+pub const DEFAULT_MARKET_DATA_DELAY_MS: u64 = 500;
+
+/// Default SMTP port for notification emails (submission with STARTTLS).
+pub const DEFAULT_SMTP_PORT: u16 = 587;
+
+/// Default number of failed symbols in one market data refresh that triggers
+/// a notification.
+pub const DEFAULT_NOTIFY_FAILURE_THRESHOLD: u32 = 3;
+
+/// Default value below which an open position counts as dust, in cents.
+pub const DEFAULT_DUST_THRESHOLD_CENTS: i64 = 100;
+
+/// Default age in days after which a market price counts as stale.
+pub const DEFAULT_PRICE_STALENESS_DAYS: i64 = 7;
+
+/// Default number of days before and after a loss sale in which buying the
+/// same symbol flags it as a wash sale.
+pub const DEFAULT_WASH_SALE_WINDOW_DAYS: i64 = 30;
+
+/// Largest accepted `wash_sale_window_days` setting.
+pub const MAX_WASH_SALE_WINDOW_DAYS: i64 = 365;
+
+/// Default number of decimal places shown for percentages.
+pub const DEFAULT_PERCENT_DECIMALS: u32 = 2;
+
+/// Largest accepted `percent_decimals` setting.
+pub const MAX_PERCENT_DECIMALS: u32 = 4;
+
+/// Tables whose default sort order can be stored, by name. The setting for
+/// a table is kept under `default_sort_<name>` as `column:dir`.
+pub const DEFAULT_SORT_TABLES: &[&str] = &["positions", "closed_positions", "market_data"];
+
+/// Columns of the transactions table as (key, label), in display order. The
+/// keys double as sort keys, except for the unsortable tags column.
+pub const TRANSACTION_COLUMNS: &[(&str, &str)] = &[
+    ("date", "Date"),
+    ("description", "Description"),
+    ("counterparty", "Payee"),
+    ("category", "Category"),
+    ("tags", "Tags"),
+    ("amount", "Amount"),
+];
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Settings {
@@ -9,6 +59,89 @@ pub struct Settings {
     pub date_format: String,
     pub page_size: i64,
     pub locale: String,
+    /// IANA timezone name used for "today"; empty uses the server's local time.
+    pub timezone: String,
+    /// Symbol override for the main currency; empty uses the built-in symbol.
+    pub currency_symbol: String,
+    /// Placement of the currency symbol: "prefix" or "suffix".
+    pub symbol_position: String,
+    /// Number of decimals shown for the main currency; `None` shows two.
+    pub currency_decimals: Option<u32>,
+    /// How transfers count in XIRR: "exclude" or "at_cost".
+    pub xirr_transfers: String,
+    /// Directory for scheduled backups; empty uses the deployment default.
+    pub backup_dir: String,
+    /// Backup schedule: "off", "daily" or "weekly".
+    pub backup_frequency: String,
+    /// Number of backup files to keep.
+    pub backup_retention: i64,
+    /// Let sales beyond the held quantity open short positions instead of
+    /// clamping the position at zero.
+    pub allow_short_positions: bool,
+    /// Positions worth less than this (in cents) are collapsed into one row
+    /// on the positions page; 0 shows every position.
+    pub dust_threshold_cents: i64,
+    /// Prices older than this many days are flagged as stale.
+    pub price_staleness_days: i64,
+    /// Days before and after a loss sale in which a buy of the same symbol
+    /// flags a wash sale; 0 turns the flag off.
+    pub wash_sale_window_days: i64,
+    /// Mirror new dividend, fee and tax activities into transactions (see
+    /// `services::trading_mirror`).
+    pub mirror_trading_cash: bool,
+    /// Categories of mirrored dividend, fee and tax transactions.
+    pub mirror_dividend_category_id: Option<i64>,
+    pub mirror_fee_category_id: Option<i64>,
+    pub mirror_tax_category_id: Option<i64>,
+    /// Decimal places shown for percentages (0-4).
+    pub percent_decimals: u32,
+    /// Account pre-selected for new transactions.
+    pub default_account_id: Option<i64>,
+    /// Category pre-selected for new transactions.
+    pub default_category_id: Option<i64>,
+    /// Securities account pre-selected for new trading activities.
+    pub default_trading_account_id: Option<i64>,
+    /// Log filter directives (`EnvFilter` syntax); empty uses the startup filter.
+    pub log_filter: String,
+    /// Pause between market data API requests, in milliseconds.
+    pub market_data_delay_ms: u64,
+    /// Hours after login until a session expires; 0 keeps sessions until restart.
+    pub session_ttl_hours: u64,
+    /// Take the client address for login rate limiting from `X-Forwarded-For`
+    /// and `X-Real-Ip`. Only safe behind a reverse proxy that sets them.
+    pub trust_proxy_headers: bool,
+    /// Keys of the visible transactions table columns, in display order.
+    pub transaction_columns: Vec<String>,
+    /// Row density of data tables: "comfortable" or "compact".
+    pub table_density: String,
+    /// Stored default sort orders (`column:dir`) by table name, see
+    /// [`DEFAULT_SORT_TABLES`].
+    pub default_sorts: HashMap<String, String>,
+    /// Where failure notifications go: "off", "smtp" or "webhook".
+    pub notify_channel: String,
+    /// URL that webhook notifications are posted to, e.g. an ntfy.sh topic.
+    pub notify_webhook_url: String,
+    pub notify_smtp_host: String,
+    pub notify_smtp_port: u16,
+    pub notify_smtp_username: String,
+    pub notify_smtp_password: String,
+    pub notify_smtp_from: String,
+    /// Comma-separated recipient addresses.
+    pub notify_smtp_to: String,
+    /// Failed symbols in one market data refresh that trigger a notification.
+    pub notify_failure_threshold: u32,
+    /// Count requests per route for the usage statistics page.
+    pub usage_stats_enabled: bool,
+    /// Key of the page `GET /` leads to, one of [`nav::START_PAGES`].
+    pub start_page: String,
+    /// Keys of sidebar entries in the order they are shown within their section.
+    pub nav_order: Vec<String>,
+    /// Keys of sidebar entries that are not shown.
+    pub hidden_nav_items: Vec<String>,
+    /// Sidebar sections built from `nav_order` and `hidden_nav_items`
+    /// (runtime-only, not persisted).
+    #[serde(skip)]
+    pub nav: Vec<NavSection>,
     /// Whether password authentication is active (runtime-only, not persisted).
     #[serde(skip)]
     pub is_authenticated: bool,
@@ -16,6 +149,8 @@ pub struct Settings {
 
 impl Settings {
     pub fn from_map(map: HashMap<String, String>) -> Self {
+        let nav_order = nav::parse_keys(map.get("nav_order").map(String::as_str));
+        let hidden_nav_items = nav::parse_keys(map.get("hidden_nav_items").map(String::as_str));
         Self {
             theme: map.get("theme").cloned().unwrap_or_else(|| "system".into()),
             currency: map.get("currency").cloned().unwrap_or_else(|| "USD".into()),
@@ -28,6 +163,113 @@ impl Settings {
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(25),
             locale: map.get("locale").cloned().unwrap_or_else(|| "en-US".into()),
+            timezone: map.get("timezone").cloned().unwrap_or_default(),
+            currency_symbol: map.get("currency_symbol").cloned().unwrap_or_default(),
+            symbol_position: map
+                .get("symbol_position")
+                .cloned()
+                .unwrap_or_else(|| "prefix".into()),
+            currency_decimals: map.get("currency_decimals").and_then(|s| s.parse().ok()),
+            xirr_transfers: map
+                .get("xirr_transfers")
+                .cloned()
+                .unwrap_or_else(|| "exclude".into()),
+            backup_dir: map.get("backup_dir").cloned().unwrap_or_default(),
+            backup_frequency: map
+                .get("backup_frequency")
+                .cloned()
+                .unwrap_or_else(|| "off".into()),
+            backup_retention: map
+                .get("backup_retention")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(7),
+            allow_short_positions: map
+                .get("allow_short_positions")
+                .is_some_and(|v| v == "true"),
+            dust_threshold_cents: map
+                .get("dust_threshold_cents")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_DUST_THRESHOLD_CENTS),
+            price_staleness_days: map
+                .get("price_staleness_days")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_PRICE_STALENESS_DAYS),
+            wash_sale_window_days: map
+                .get("wash_sale_window_days")
+                .and_then(|s| s.parse().ok())
+                .filter(|n| (0..=MAX_WASH_SALE_WINDOW_DAYS).contains(n))
+                .unwrap_or(DEFAULT_WASH_SALE_WINDOW_DAYS),
+            mirror_trading_cash: map.get("mirror_trading_cash").is_some_and(|v| v == "true"),
+            mirror_dividend_category_id: map
+                .get("mirror_dividend_category_id")
+                .and_then(|s| s.parse().ok()),
+            mirror_fee_category_id: map
+                .get("mirror_fee_category_id")
+                .and_then(|s| s.parse().ok()),
+            mirror_tax_category_id: map
+                .get("mirror_tax_category_id")
+                .and_then(|s| s.parse().ok()),
+            percent_decimals: map
+                .get("percent_decimals")
+                .and_then(|s| s.parse().ok())
+                .filter(|n| *n <= MAX_PERCENT_DECIMALS)
+                .unwrap_or(DEFAULT_PERCENT_DECIMALS),
+            default_account_id: map.get("default_account_id").and_then(|s| s.parse().ok()),
+            default_category_id: map.get("default_category_id").and_then(|s| s.parse().ok()),
+            default_trading_account_id: map
+                .get("default_trading_account_id")
+                .and_then(|s| s.parse().ok()),
+            log_filter: map.get("log_filter").cloned().unwrap_or_default(),
+            market_data_delay_ms: map
+                .get("market_data_delay_ms")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_MARKET_DATA_DELAY_MS),
+            session_ttl_hours: map
+                .get("session_ttl_hours")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+            trust_proxy_headers: map.get("trust_proxy_headers").is_none_or(|v| v == "true"),
+            transaction_columns: parse_transaction_columns(
+                map.get("transaction_columns").map(String::as_str),
+            ),
+            table_density: map
+                .get("table_density")
+                .cloned()
+                .unwrap_or_else(|| "comfortable".into()),
+            default_sorts: DEFAULT_SORT_TABLES
+                .iter()
+                .filter_map(|table| {
+                    let value = map.get(&format!("default_sort_{}", table))?;
+                    Some((table.to_string(), value.clone()))
+                })
+                .collect(),
+            notify_channel: map
+                .get("notify_channel")
+                .cloned()
+                .unwrap_or_else(|| "off".into()),
+            notify_webhook_url: map.get("notify_webhook_url").cloned().unwrap_or_default(),
+            notify_smtp_host: map.get("notify_smtp_host").cloned().unwrap_or_default(),
+            notify_smtp_port: map
+                .get("notify_smtp_port")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_SMTP_PORT),
+            notify_smtp_username: map.get("notify_smtp_username").cloned().unwrap_or_default(),
+            notify_smtp_password: map.get("notify_smtp_password").cloned().unwrap_or_default(),
+            notify_smtp_from: map.get("notify_smtp_from").cloned().unwrap_or_default(),
+            notify_smtp_to: map.get("notify_smtp_to").cloned().unwrap_or_default(),
+            notify_failure_threshold: map
+                .get("notify_failure_threshold")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_NOTIFY_FAILURE_THRESHOLD),
+            usage_stats_enabled: map.get("usage_stats_enabled").is_some_and(|v| v == "true"),
+            start_page: map
+                .get("start_page")
+                .filter(|v| nav::START_PAGES.contains(&v.as_str()))
+                .cloned()
+                .unwrap_or_else(|| "dashboard".into()),
+            nav: nav::build(&nav_order, &hidden_nav_items),
+            nav_order,
+            hidden_nav_items,
             is_authenticated: false,
         }
     }
@@ -39,9 +281,132 @@ impl Settings {
         map.insert("date_format".into(), self.date_format.clone());
         map.insert("page_size".into(), self.page_size.to_string());
         map.insert("locale".into(), self.locale.clone());
+        map.insert("timezone".into(), self.timezone.clone());
+        map.insert("currency_symbol".into(), self.currency_symbol.clone());
+        map.insert("symbol_position".into(), self.symbol_position.clone());
+        map.insert(
+            "currency_decimals".into(),
+            self.currency_decimals
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+        );
+        map.insert("xirr_transfers".into(), self.xirr_transfers.clone());
+        map.insert("backup_dir".into(), self.backup_dir.clone());
+        map.insert("backup_frequency".into(), self.backup_frequency.clone());
+        map.insert("backup_retention".into(), self.backup_retention.to_string());
+        map.insert(
+            "allow_short_positions".into(),
+            self.allow_short_positions.to_string(),
+        );
+        map.insert(
+            "dust_threshold_cents".into(),
+            self.dust_threshold_cents.to_string(),
+        );
+        map.insert(
+            "price_staleness_days".into(),
+            self.price_staleness_days.to_string(),
+        );
+        map.insert(
+            "wash_sale_window_days".into(),
+            self.wash_sale_window_days.to_string(),
+        );
+        map.insert("percent_decimals".into(), self.percent_decimals.to_string());
+        map.insert(
+            "mirror_trading_cash".into(),
+            self.mirror_trading_cash.to_string(),
+        );
+        for (key, id) in [
+            (
+                "mirror_dividend_category_id",
+                self.mirror_dividend_category_id,
+            ),
+            ("mirror_fee_category_id", self.mirror_fee_category_id),
+            ("mirror_tax_category_id", self.mirror_tax_category_id),
+            ("default_account_id", self.default_account_id),
+            ("default_category_id", self.default_category_id),
+            (
+                "default_trading_account_id",
+                self.default_trading_account_id,
+            ),
+        ] {
+            map.insert(key.into(), id.map(|id| id.to_string()).unwrap_or_default());
+        }
+        map.insert("log_filter".into(), self.log_filter.clone());
+        map.insert(
+            "market_data_delay_ms".into(),
+            self.market_data_delay_ms.to_string(),
+        );
+        map.insert(
+            "session_ttl_hours".into(),
+            self.session_ttl_hours.to_string(),
+        );
+        map.insert(
+            "trust_proxy_headers".into(),
+            self.trust_proxy_headers.to_string(),
+        );
+        map.insert(
+            "transaction_columns".into(),
+            self.transaction_columns.join(","),
+        );
+        map.insert("table_density".into(), self.table_density.clone());
+        for (key, value) in [
+            ("notify_channel", &self.notify_channel),
+            ("notify_webhook_url", &self.notify_webhook_url),
+            ("notify_smtp_host", &self.notify_smtp_host),
+            ("notify_smtp_username", &self.notify_smtp_username),
+            ("notify_smtp_password", &self.notify_smtp_password),
+            ("notify_smtp_from", &self.notify_smtp_from),
+            ("notify_smtp_to", &self.notify_smtp_to),
+        ] {
+            map.insert(key.into(), value.clone());
+        }
+        map.insert("notify_smtp_port".into(), self.notify_smtp_port.to_string());
+        map.insert(
+            "notify_failure_threshold".into(),
+            self.notify_failure_threshold.to_string(),
+        );
+        map.insert(
+            "usage_stats_enabled".into(),
+            self.usage_stats_enabled.to_string(),
+        );
+        map.insert("start_page".into(), self.start_page.clone());
+        map.insert("nav_order".into(), self.nav_order.join(","));
+        map.insert("hidden_nav_items".into(), self.hidden_nav_items.join(","));
+        for (table, value) in &self.default_sorts {
+            map.insert(format!("default_sort_{}", table), value.clone());
+        }
         map
     }
 
+    /// The stored default sort order of `table`, or the column's default
+    /// when none is stored.
+    pub fn default_sort<C: SortableColumn>(&self, table: &str) -> TableSort<C> {
+        self.default_sorts
+            .get(table)
+            .and_then(|value| TableSort::parse(value))
+            .unwrap_or_default()
+    }
+
+    /// Pause between consecutive market data API requests.
+    pub fn market_data_delay(&self) -> Duration {
+        Duration::from_millis(self.market_data_delay_ms)
+    }
+
+    /// How long a session stays valid after login; `None` means until restart.
+    pub fn session_ttl(&self) -> Option<Duration> {
+        (self.session_ttl_hours > 0).then(|| Duration::from_secs(self.session_ttl_hours * 3600))
+    }
+
+    /// Whether a position worth `value_cents` counts as dust.
+    pub fn is_dust(&self, value_cents: i64) -> bool {
+        value_cents.abs() < self.dust_threshold_cents
+    }
+
+    /// The dust threshold as entered in the settings form.
+    pub fn dust_threshold_input(&self) -> String {
+        crate::services::money::format_cents(self.dust_threshold_cents)
+    }
+
     pub fn is_theme(&self, value: &str) -> bool {
         self.theme == value
     }
@@ -54,74 +419,245 @@ impl Settings {
         self.date_format == value
     }
 
+    /// The date format for input and display; unknown values mean ISO.
+    pub fn date_format(&self) -> DateFormat {
+        self.date_format.parse().unwrap_or_default()
+    }
+
+    /// Format an ISO date for display. Other strings are returned as is.
+    pub fn format_date(&self, date: &str) -> String {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|d| self.date_format().format(d))
+            .unwrap_or_else(|_| date.to_string())
+    }
+
     pub fn is_locale(&self, value: &str) -> bool {
         self.locale == value
     }
 
+    pub fn is_backup_frequency(&self, value: &str) -> bool {
+        self.backup_frequency == value
+    }
+
+    pub fn is_notify_channel(&self, value: &str) -> bool {
+        self.notify_channel == value
+    }
+
+    /// Whether failure notifications are sent through a channel.
+    pub fn notifications_enabled(&self) -> bool {
+        matches!(self.notify_channel.as_str(), "smtp" | "webhook")
+    }
+
+    /// Recipients of notification emails.
+    pub fn notify_recipients(&self) -> impl Iterator<Item = &str> {
+        self.notify_smtp_to
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    }
+
+    pub fn is_xirr_transfers(&self, value: &str) -> bool {
+        self.xirr_transfers == value
+    }
+
+    /// Whether transfers and holding adjustments count as cash flows at cost in XIRR.
+    pub fn xirr_transfers_at_cost(&self) -> bool {
+        self.xirr_transfers == "at_cost"
+    }
+
+    pub fn is_symbol_position(&self, value: &str) -> bool {
+        self.symbol_position == value
+    }
+
+    pub fn is_default_account(&self, id: &i64) -> bool {
+        self.default_account_id == Some(*id)
+    }
+
+    pub fn is_default_category(&self, id: &i64) -> bool {
+        self.default_category_id == Some(*id)
+    }
+
+    pub fn is_default_trading_account(&self, id: &i64) -> bool {
+        self.default_trading_account_id == Some(*id)
+    }
+
+    /// Category of transactions mirroring activities of this type.
+    pub fn mirror_category(&self, activity_type: TradingActivityType) -> Option<i64> {
+        match activity_type {
+            TradingActivityType::Dividend => self.mirror_dividend_category_id,
+            TradingActivityType::Fee => self.mirror_fee_category_id,
+            TradingActivityType::Tax => self.mirror_tax_category_id,
+            _ => None,
+        }
+    }
+
+    /// Whether `id` is the mirror category of the activity type, for the
+    /// settings form.
+    pub fn is_mirror_category(&self, activity_type: &TradingActivityType, id: &i64) -> bool {
+        self.mirror_category(*activity_type) == Some(*id)
+    }
+
+    /// Whether the transactions table shows the column with this key.
+    pub fn shows_column(&self, key: &str) -> bool {
+        self.transaction_columns.iter().any(|c| c == key)
+    }
+
+    /// Number of visible transactions table columns, for `colspan`.
+    pub fn visible_column_count(&self) -> usize {
+        self.transaction_columns.len()
+    }
+
+    pub fn is_start_page(&self, value: &str) -> bool {
+        self.start_page == value
+    }
+
+    /// Path that `GET /` leads to.
+    pub fn start_page_path(&self) -> &'static str {
+        nav::find(&self.start_page).map_or("/dashboard", |item| item.href)
+    }
+
+    /// Every sidebar entry in display order, hidden ones included.
+    pub fn nav_items(&self) -> Vec<&'static NavItem> {
+        nav::ordered(&self.nav_order)
+    }
+
+    pub fn is_nav_hidden(&self, key: &str) -> bool {
+        key != nav::SETTINGS_ITEM && self.hidden_nav_items.iter().any(|k| k == key)
+    }
+
+    pub fn is_table_density(&self, value: &str) -> bool {
+        self.table_density == value
+    }
+
     pub fn is_dark(&self) -> bool {
         self.theme == "dark"
     }
 
+    /// Currency format for `currency`, with the user's symbol, placement and
+    /// decimals applied when it is the main currency.
+    pub fn currency_format(&self, currency: &str) -> CurrencyFormat {
+        let mut format = CurrencyFormat::for_currency(currency);
+        if !currency.eq_ignore_ascii_case(&self.currency) {
+            return format;
+        }
+        if !self.currency_symbol.is_empty() {
+            format.symbol = self.currency_symbol.clone();
+        }
+        format.suffix = self.symbol_position == "suffix";
+        if let Some(decimals) = self.currency_decimals {
+            format.decimals = decimals;
+        }
+        format
+    }
+
     /// Format a monetary amount (in cents) with proper locale formatting and color coding.
     /// Returns HTML with Tailwind color classes:
     /// - Positive: green
     /// - Negative: red
     /// - Zero: default text color
     pub fn format_money(&self, cents: &i64) -> String {
-        filters::format_money(*cents, &self.currency, &self.locale)
+        filters::format_money_with(*cents, &self.currency_format(&self.currency), &self.locale)
     }
 
     /// Format a monetary amount (in cents) as plain text without HTML/colors.
     pub fn format_money_plain(&self, cents: &i64) -> String {
-        filters::format_money_plain(*cents, &self.currency, &self.locale)
+        filters::format_money_plain_with(
+            *cents,
+            &self.currency_format(&self.currency),
+            &self.locale,
+        )
     }
 
     /// Format a monetary amount (in cents) with a specific currency and color coding.
     /// Useful for trading items that have their own currency field.
     pub fn format_money_with_currency(&self, cents: &i64, currency: &str) -> String {
-        filters::format_money(*cents, currency, &self.locale)
+        filters::format_money_with(*cents, &self.currency_format(currency), &self.locale)
     }
 
     /// Format a monetary amount (in cents) with a specific currency and color coding.
     /// Takes value by copy - useful for template match expressions.
     pub fn format_money_with_currency_val(&self, cents: i64, currency: &str) -> String {
-        filters::format_money(cents, currency, &self.locale)
+        filters::format_money_with(cents, &self.currency_format(currency), &self.locale)
     }
 
     /// Format a monetary amount (in cents) as neutral text (no sign, no color).
     /// Useful for prices, fees, and other amounts that shouldn't show +/-.
     pub fn format_money_neutral(&self, cents: &i64) -> String {
-        filters::format_money_neutral(*cents, &self.currency, &self.locale)
+        filters::format_money_neutral_with(
+            *cents,
+            &self.currency_format(&self.currency),
+            &self.locale,
+        )
     }
 
     /// Format a monetary amount (in cents) as neutral text with a specific currency.
     /// Useful for trading prices/fees that have their own currency field.
     pub fn format_money_neutral_with_currency(&self, cents: &i64, currency: &str) -> String {
-        filters::format_money_neutral(*cents, currency, &self.locale)
+        filters::format_money_neutral_with(*cents, &self.currency_format(currency), &self.locale)
     }
 
     /// Format a monetary amount (in cents) as plain text with sign (+/-) and specific currency.
     /// Useful for gains/losses in trading that have their own currency field.
     pub fn format_money_plain_with_currency(&self, cents: &i64, currency: &str) -> String {
-        filters::format_money_plain(*cents, currency, &self.locale)
+        filters::format_money_plain_with(*cents, &self.currency_format(currency), &self.locale)
     }
 
     /// Format a monetary amount (in cents) for balance/value display with a specific currency.
     /// Shows "-" for negative values, no sign for positive/zero.
     /// Suitable for balances, position values, and totals.
     pub fn format_money_balance_with_currency(&self, cents: &i64, currency: &str) -> String {
-        filters::format_money_balance(*cents, currency, &self.locale)
+        filters::format_money_neutral_with(*cents, &self.currency_format(currency), &self.locale)
+    }
+
+    /// Format a monetary amount (in cents) for balance/value display in the main currency.
+    /// Shows "-" for negative values, no sign for positive/zero.
+    pub fn format_money_balance(&self, cents: &i64) -> String {
+        filters::format_money_neutral_with(
+            *cents,
+            &self.currency_format(&self.currency),
+            &self.locale,
+        )
     }
 
     /// Format a percentage value with locale-aware decimal separator.
-    /// Shows sign (+/-) and two decimal places.
+    /// Shows sign (+/-) and `percent_decimals` decimal places.
     pub fn format_percent(&self, value: &f64) -> String {
-        filters::format_percent(*value, &self.locale)
+        filters::format_percent(*value, self.percent_decimals, &self.locale)
     }
 
     /// Format a percentage value with locale-aware decimal separator.
     /// Takes value by copy - useful for template match expressions.
     pub fn format_percent_val(&self, value: f64) -> String {
-        filters::format_percent(value, &self.locale)
+        filters::format_percent(value, self.percent_decimals, &self.locale)
+    }
+
+    /// Format a share of a total, such as a category's part of spending,
+    /// without the plus sign.
+    pub fn format_percent_share(&self, value: f64) -> String {
+        filters::format_percent_share(value, self.percent_decimals, &self.locale)
+    }
+}
+
+/// Parse a comma-separated list of transactions table columns. Unknown keys
+/// are dropped and the result follows [`TRANSACTION_COLUMNS`] order; a
+/// missing or empty list shows every column.
+pub fn parse_transaction_columns(value: Option<&str>) -> Vec<String> {
+    let keys: Vec<&str> = value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let columns: Vec<String> = TRANSACTION_COLUMNS
+        .iter()
+        .filter(|(key, _)| keys.contains(key))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    if columns.is_empty() {
+        TRANSACTION_COLUMNS
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .collect()
+    } else {
+        columns
     }
 }
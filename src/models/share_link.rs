@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+/// The report a share link grants read-only access to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShareScope {
+    SpendingReport,
+    NetWorth,
+    Positions,
+}
+
+impl ShareScope {
+    pub fn all() -> &'static [ShareScope] {
+        &[Self::SpendingReport, Self::NetWorth, Self::Positions]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SpendingReport => "spending-report",
+            Self::NetWorth => "net-worth",
+            Self::Positions => "positions",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::SpendingReport => "Spending Report",
+            Self::NetWorth => "Net Worth",
+            Self::Positions => "Positions",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "spending-report" => Some(Self::SpendingReport),
+            "net-worth" => Some(Self::NetWorth),
+            "positions" => Some(Self::Positions),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ShareScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLink {
+    pub id: i64,
+    pub token: String,
+    pub scope: ShareScope,
+    pub label: String,
+    /// Argon2 hash of the link password; `None` for links without one.
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    /// UTC timestamp (`YYYY-MM-DD HH:MM:SS`) after which the link stops working.
+    pub expires_at: Option<String>,
+    pub created_at: String,
+}
+
+impl ShareLink {
+    pub fn has_password(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Path of the public snapshot page.
+    pub fn path(&self) -> String {
+        format!("/share/{}", self.token)
+    }
+}
+
+/// Data for creating a share link.
+#[derive(Debug, Clone)]
+pub struct NewShareLink {
+    pub scope: ShareScope,
+    pub label: String,
+    pub password_hash: Option<String>,
+    /// Days until the link expires; `None` keeps it until revoked.
+    pub expires_in_days: Option<u32>,
+}
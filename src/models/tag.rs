@@ -2,26 +2,6 @@ use serde::{Deserialize, Serialize};
 
 use super::category::DEFAULT_COLOR;
 
-/// Curated color palette for tags (Tailwind 500 values).
-pub const TAG_PALETTE: &[(&str, &str)] = &[
-    ("Red", "#ef4444"),
-    ("Orange", "#f97316"),
-    ("Amber", "#f59e0b"),
-    ("Yellow", "#eab308"),
-    ("Lime", "#84cc16"),
-    ("Green", "#22c55e"),
-    ("Emerald", "#10b981"),
-    ("Teal", "#14b8a6"),
-    ("Cyan", "#06b6d4"),
-    ("Blue", "#3b82f6"),
-    ("Indigo", "#6366f1"),
-    ("Violet", "#8b5cf6"),
-    ("Purple", "#a855f7"),
-    ("Fuchsia", "#d946ef"),
-    ("Pink", "#ec4899"),
-    ("Gray", DEFAULT_COLOR),
-];
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum TagStyle {
@@ -77,13 +57,60 @@ fn default_color() -> String {
     DEFAULT_COLOR.to_string()
 }
 
-/// Tag with its transaction usage count (for listing pages).
-#[derive(Debug, Clone)]
+/// Tag with its transaction usage count (for listing pages and the tag picker).
+#[derive(Debug, Clone, Serialize)]
 pub struct TagWithUsage {
+    #[serde(flatten)]
     pub tag: Tag,
     pub usage_count: i64,
 }
 
+/// How well a tag name matches a search query; lower sorts first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TagMatchKind {
+    Prefix,
+    Substring,
+    /// Edit distance between the query and the closest part of the name.
+    Fuzzy(usize),
+}
+
+impl TagMatchKind {
+    /// Match `name` against a lowercase `query`. Fuzzy matches allow one typo
+    /// per three query characters; shorter queries must match literally.
+    pub fn of(name: &str, query: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.starts_with(query) {
+            return Some(Self::Prefix);
+        }
+        if name.contains(query) {
+            return Some(Self::Substring);
+        }
+        let max_distance = query.chars().count() / 3;
+        if max_distance == 0 {
+            return None;
+        }
+        let distance = fuzzy_distance(&name, query);
+        (distance <= max_distance).then_some(Self::Fuzzy(distance))
+    }
+}
+
+/// Smallest edit distance between `query` and any substring of `name`
+/// (Sellers' variant of Levenshtein, free start and end in `name`).
+fn fuzzy_distance(name: &str, query: &str) -> usize {
+    let name: Vec<char> = name.chars().collect();
+    let mut row: Vec<usize> = vec![0; name.len() + 1];
+    for (i, qc) in query.chars().enumerate() {
+        let mut prev_diag = row[0];
+        row[0] = i + 1;
+        for (j, &nc) in name.iter().enumerate() {
+            let substitution = prev_diag + usize::from(qc != nc);
+            prev_diag = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(prev_diag + 1);
+        }
+    }
+    row.into_iter().min().unwrap_or(0)
+}
+
 // -- Colour helpers for accessible badge rendering --
 
 /// Parse a hex color string (#RRGGBB) into (R, G, B).
@@ -1,4 +1,5 @@
 use crate::filters::currency_symbol;
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
@@ -13,6 +14,14 @@ pub enum TradingActivityType {
     Fee,
     Tax,
     Split,
+    /// Shares moved in from another broker at a known cost basis
+    TransferIn,
+    /// Shares moved out to another broker
+    TransferOut,
+    /// Shares added without a cash trade (e.g. gifts, grants, spin-offs)
+    AddHolding,
+    /// Shares removed without a cash trade
+    RemoveHolding,
 }
 
 impl TradingActivityType {
@@ -24,6 +33,10 @@ impl TradingActivityType {
             Self::Fee => "FEE",
             Self::Tax => "TAX",
             Self::Split => "SPLIT",
+            Self::TransferIn => "TRANSFER_IN",
+            Self::TransferOut => "TRANSFER_OUT",
+            Self::AddHolding => "ADD_HOLDING",
+            Self::RemoveHolding => "REMOVE_HOLDING",
         }
     }
 
@@ -35,6 +48,10 @@ impl TradingActivityType {
             Self::Fee => "Fee",
             Self::Tax => "Tax",
             Self::Split => "Split",
+            Self::TransferIn => "Transfer In",
+            Self::TransferOut => "Transfer Out",
+            Self::AddHolding => "Add Holding",
+            Self::RemoveHolding => "Remove Holding",
         }
     }
 
@@ -46,17 +63,45 @@ impl TradingActivityType {
             Self::Fee,
             Self::Tax,
             Self::Split,
+            Self::TransferIn,
+            Self::TransferOut,
+            Self::AddHolding,
+            Self::RemoveHolding,
         ]
     }
 
     /// Returns true if this activity type affects cash balance
     pub fn affects_cash(&self) -> bool {
-        !matches!(self, Self::Split)
+        !matches!(self, Self::Split) && !self.is_transfer()
     }
 
     /// Returns true if this activity type affects holdings
     pub fn affects_holdings(&self) -> bool {
-        matches!(self, Self::Buy | Self::Sell | Self::Split)
+        self.is_acquisition() || self.is_disposal() || matches!(self, Self::Split)
+    }
+
+    /// Returns true if this activity adds shares to a position
+    pub fn is_acquisition(&self) -> bool {
+        matches!(self, Self::Buy | Self::TransferIn | Self::AddHolding)
+    }
+
+    /// Returns true if this activity removes shares from a position
+    pub fn is_disposal(&self) -> bool {
+        matches!(self, Self::Sell | Self::TransferOut | Self::RemoveHolding)
+    }
+
+    /// Returns true if the activity is a cash amount without a quantity,
+    /// stored in `unit_price_cents`
+    pub fn is_amount_only(&self) -> bool {
+        matches!(self, Self::Dividend | Self::Fee | Self::Tax)
+    }
+
+    /// Returns true if shares move without a cash trade
+    pub fn is_transfer(&self) -> bool {
+        matches!(
+            self,
+            Self::TransferIn | Self::TransferOut | Self::AddHolding | Self::RemoveHolding
+        )
     }
 }
 
@@ -70,18 +115,85 @@ impl FromStr for TradingActivityType {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_uppercase().as_str() {
+        match s.trim().to_uppercase().replace([' ', '-'], "_").as_str() {
             "BUY" => Ok(Self::Buy),
             "SELL" => Ok(Self::Sell),
             "DIVIDEND" => Ok(Self::Dividend),
             "FEE" => Ok(Self::Fee),
             "TAX" => Ok(Self::Tax),
             "SPLIT" => Ok(Self::Split),
+            "TRANSFER_IN" => Ok(Self::TransferIn),
+            "TRANSFER_OUT" => Ok(Self::TransferOut),
+            "ADD_HOLDING" => Ok(Self::AddHolding),
+            "REMOVE_HOLDING" => Ok(Self::RemoveHolding),
             _ => Err(format!("Unknown activity type: {}", s)),
         }
     }
 }
 
+/// Convert a fee charged in `fee_currency` into `currency` at
+/// `exchange_rate` (units of `currency` per unit of `fee_currency`). Fees in
+/// the activity currency are returned as they are; `None` if the currencies
+/// differ and there is no usable rate.
+pub fn convert_fee_cents(
+    fee_cents: i64,
+    currency: &str,
+    fee_currency: Option<&str>,
+    exchange_rate: Option<f64>,
+) -> Option<i64> {
+    match fee_currency {
+        Some(fee_currency) if !fee_currency.eq_ignore_ascii_case(currency) => {
+            let rate = exchange_rate.filter(|r| r.is_finite() && *r > 0.0)?;
+            Some((fee_cents as f64 * rate).round() as i64)
+        }
+        _ => Some(fee_cents),
+    }
+}
+
+/// Start of the notes shared by the activities of one dividend
+/// reinvestment, followed by the dividend's id.
+const DRIP_NOTE_PREFIX: &str = "DRIP #";
+
+/// Notes linking the activities of a dividend reinvestment: the marker
+/// `DRIP #<dividend id>`, the ids of all of them and the notes entered, if any.
+pub fn drip_note(
+    dividend_id: i64,
+    tax_id: Option<i64>,
+    buy_id: i64,
+    notes: Option<&str>,
+) -> String {
+    let mut note = format!("{DRIP_NOTE_PREFIX}{dividend_id}: dividend #{dividend_id}");
+    if let Some(tax_id) = tax_id {
+        note.push_str(&format!(", tax #{tax_id}"));
+    }
+    note.push_str(&format!(", buy #{buy_id}"));
+    if let Some(notes) = notes.map(str::trim).filter(|n| !n.is_empty()) {
+        note.push('\n');
+        note.push_str(notes);
+    }
+    note
+}
+
+/// Normalize a fee currency and exchange rate as entered: the currency is
+/// upper-cased and dropped, along with the rate, if it is empty or the
+/// activity currency itself. A rate must be positive.
+pub fn normalize_fee_currency(
+    currency: &str,
+    fee_currency: Option<&str>,
+    exchange_rate: Option<f64>,
+) -> Result<(Option<String>, Option<f64>), &'static str> {
+    let fee_currency = fee_currency
+        .map(|c| c.trim().to_uppercase())
+        .filter(|c| !c.is_empty() && !c.eq_ignore_ascii_case(currency.trim()));
+    let Some(fee_currency) = fee_currency else {
+        return Ok((None, None));
+    };
+    match exchange_rate {
+        Some(rate) if !(rate.is_finite() && rate > 0.0) => Err("Invalid exchange rate"),
+        rate => Ok((Some(fee_currency), rate)),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradingActivity {
     pub id: i64,
@@ -92,6 +204,10 @@ pub struct TradingActivity {
     pub unit_price_cents: Option<i64>,
     pub currency: String,
     pub fee_cents: i64,
+    /// Currency the fee was charged in, if it differs from `currency`
+    pub fee_currency: Option<String>,
+    /// Units of `currency` per unit of `fee_currency`
+    pub exchange_rate: Option<f64>,
     pub account_id: Option<i64>,
     pub notes: Option<String>,
     pub created_at: String,
@@ -99,6 +215,10 @@ pub struct TradingActivity {
 }
 
 impl TradingActivity {
+    pub fn matches_account(&self, id: &i64) -> bool {
+        self.account_id == Some(*id)
+    }
+
     pub fn unit_price_display(&self) -> Option<String> {
         self.unit_price_cents.map(|cents| {
             let dollars = cents / 100;
@@ -119,7 +239,47 @@ impl TradingActivity {
     }
 
     pub fn fee_formatted(&self) -> String {
-        format!("{}{}", currency_symbol(&self.currency), self.fee_display())
+        format!(
+            "{}{}",
+            currency_symbol(self.fee_currency_code()),
+            self.fee_display()
+        )
+    }
+
+    /// The currency `fee_cents` is in.
+    pub fn fee_currency_code(&self) -> &str {
+        self.fee_currency.as_deref().unwrap_or(&self.currency)
+    }
+
+    /// The fee in the activity currency, or `None` if it was charged in a
+    /// foreign currency without an exchange rate.
+    pub fn fee_in_currency_cents(&self) -> Option<i64> {
+        convert_fee_cents(
+            self.fee_cents,
+            &self.currency,
+            self.fee_currency.as_deref(),
+            self.exchange_rate,
+        )
+    }
+
+    /// The fee in the activity currency for cost calculations. Fees that
+    /// cannot be converted are taken at face value.
+    pub fn fee_converted_cents(&self) -> i64 {
+        self.fee_in_currency_cents().unwrap_or(self.fee_cents)
+    }
+
+    pub fn exchange_rate_display(&self) -> String {
+        self.exchange_rate
+            .map(|r| r.to_string())
+            .unwrap_or_default()
+    }
+
+    /// The dividend id of the reinvestment this activity was recorded with,
+    /// read from the marker [`drip_note`] puts in the notes.
+    pub fn drip_group(&self) -> Option<i64> {
+        let rest = self.notes.as_deref()?.strip_prefix(DRIP_NOTE_PREFIX)?;
+        let (id, _) = rest.split_once(':')?;
+        id.parse().ok()
     }
 
     pub fn total_value_cents(&self) -> Option<i64> {
@@ -143,18 +303,11 @@ impl TradingActivity {
     }
 
     pub fn quantity_display(&self) -> String {
-        self.quantity
-            .map(|q| {
-                format!("{:.4}", q)
-                    .trim_end_matches('0')
-                    .trim_end_matches('.')
-                    .to_string()
-            })
-            .unwrap_or_default()
+        self.quantity.map(format_quantity).unwrap_or_default()
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct NewTradingActivity {
     pub date: String,
     pub symbol: String,
@@ -163,20 +316,12 @@ pub struct NewTradingActivity {
     pub unit_price_cents: Option<i64>,
     pub currency: String,
     pub fee_cents: i64,
+    pub fee_currency: Option<String>,
+    pub exchange_rate: Option<f64>,
     pub account_id: Option<i64>,
     pub notes: Option<String>,
 }
 
-impl NewTradingActivity {
-    pub fn from_decimal_price(price: f64) -> i64 {
-        (price * 100.0).round() as i64
-    }
-
-    pub fn from_decimal_fee(fee: f64) -> i64 {
-        (fee * 100.0).round() as i64
-    }
-}
-
 /// Represents a calculated position from aggregated activities
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Position {
@@ -186,6 +331,49 @@ pub struct Position {
     pub currency: String,
 }
 
+/// How an import would change one position, see
+/// [`crate::db::queries::positions::preview_position_changes`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionDelta {
+    pub symbol: String,
+    pub currency: String,
+    pub quantity_before: f64,
+    pub quantity_after: f64,
+    pub average_cost_before_cents: Option<i64>,
+    pub average_cost_after_cents: Option<i64>,
+    /// The import disposes of more shares than would be held, which usually
+    /// means activities are missing from the file.
+    pub goes_negative: bool,
+}
+
+impl PositionDelta {
+    pub fn quantity_before_display(&self) -> String {
+        format_quantity(self.quantity_before)
+    }
+
+    pub fn quantity_after_display(&self) -> String {
+        format_quantity(self.quantity_after)
+    }
+}
+
+/// Cost, value and gain of a position in another currency, see
+/// [`PositionWithMarketData::convert`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConvertedAmounts {
+    pub currency: String,
+    /// Units of `currency` per unit of the position's currency
+    pub rate: f64,
+    pub total_cost_cents: i64,
+    pub current_value_cents: Option<i64>,
+    pub gain_loss_cents: Option<i64>,
+}
+
+impl ConvertedAmounts {
+    pub fn rate_display(&self) -> String {
+        format!("{:.4}", self.rate)
+    }
+}
+
 /// Position with market data for display
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PositionWithMarketData {
@@ -197,6 +385,12 @@ pub struct PositionWithMarketData {
     pub price_date: Option<String>,
     /// True if the price is approximated from the last BUY/SELL activity
     pub price_is_approximated: bool,
+    /// True if the price is approximated or older than the configured
+    /// staleness threshold, see [`PositionWithMarketData::mark_staleness`].
+    pub is_stale: bool,
+    /// Amounts in the display currency, if it differs from the position's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converted: Option<ConvertedAmounts>,
 }
 
 impl PositionWithMarketData {
@@ -209,6 +403,8 @@ impl PositionWithMarketData {
             gain_loss_percent: None,
             price_date: None,
             price_is_approximated: false,
+            is_stale: false,
+            converted: None,
         }
     }
 
@@ -216,7 +412,7 @@ impl PositionWithMarketData {
         let current_value = (position.quantity * price_cents as f64).round() as i64;
         let gain_loss = current_value - position.total_cost_cents;
         let gain_loss_pct = if position.total_cost_cents != 0 {
-            (gain_loss as f64 / position.total_cost_cents as f64) * 100.0
+            (gain_loss as f64 / position.total_cost_cents.abs() as f64) * 100.0
         } else {
             0.0
         };
@@ -229,6 +425,8 @@ impl PositionWithMarketData {
             gain_loss_percent: Some(gain_loss_pct),
             price_date: Some(price_date),
             price_is_approximated: false,
+            is_stale: false,
+            converted: None,
         }
     }
 
@@ -241,7 +439,7 @@ impl PositionWithMarketData {
         let current_value = (position.quantity * price_cents as f64).round() as i64;
         let gain_loss = current_value - position.total_cost_cents;
         let gain_loss_pct = if position.total_cost_cents != 0 {
-            (gain_loss as f64 / position.total_cost_cents as f64) * 100.0
+            (gain_loss as f64 / position.total_cost_cents.abs() as f64) * 100.0
         } else {
             0.0
         };
@@ -254,9 +452,60 @@ impl PositionWithMarketData {
             gain_loss_percent: Some(gain_loss_pct),
             price_date: Some(price_date),
             price_is_approximated: true,
+            is_stale: true,
+            converted: None,
         }
     }
 
+    /// Convert cost, value and gain into `currency` at `rate`, units of
+    /// `currency` per unit of the position's currency.
+    pub fn convert(&mut self, currency: &str, rate: f64) {
+        let at_rate = |cents: i64| (cents as f64 * rate).round() as i64;
+        let total_cost_cents = at_rate(self.position.total_cost_cents);
+        let current_value_cents = self.current_value_cents.map(at_rate);
+        self.converted = Some(ConvertedAmounts {
+            currency: currency.to_string(),
+            rate,
+            total_cost_cents,
+            current_value_cents,
+            gain_loss_cents: current_value_cents.map(|v| v - total_cost_cents),
+        });
+    }
+
+    /// Total cost in the display currency.
+    pub fn display_cost_cents(&self) -> i64 {
+        self.converted
+            .as_ref()
+            .map_or(self.position.total_cost_cents, |c| c.total_cost_cents)
+    }
+
+    /// Current value in the display currency.
+    pub fn display_value_cents(&self) -> Option<i64> {
+        match &self.converted {
+            Some(c) => c.current_value_cents,
+            None => self.current_value_cents,
+        }
+    }
+
+    /// Gain or loss in the display currency.
+    pub fn display_gain_loss_cents(&self) -> Option<i64> {
+        match &self.converted {
+            Some(c) => c.gain_loss_cents,
+            None => self.gain_loss_cents,
+        }
+    }
+
+    /// Flag the price as stale if it is approximated or more than
+    /// `max_age_days` old on `today`. Positions without a price stay unflagged.
+    pub fn mark_staleness(&mut self, today: NaiveDate, max_age_days: i64) {
+        let too_old = self
+            .price_date
+            .as_deref()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .is_some_and(|date| (today - date).num_days() > max_age_days);
+        self.is_stale = self.price_is_approximated || too_old;
+    }
+
     pub fn current_price_display(&self) -> Option<String> {
         self.current_price_cents.map(|cents| {
             let dollars = cents / 100;
@@ -303,10 +552,6 @@ impl PositionWithMarketData {
             .map(|value| format!("{}{}", currency_symbol(&self.position.currency), value))
     }
 
-    pub fn gain_loss_percent_display(&self) -> Option<String> {
-        self.gain_loss_percent.map(|pct| format!("{:+.2}%", pct))
-    }
-
     pub fn gain_loss_color(&self) -> &'static str {
         match self.gain_loss_cents {
             Some(cents) if cents > 0 => "text-green-600 dark:text-green-400",
@@ -399,10 +644,6 @@ impl ClosedPosition {
         }
     }
 
-    pub fn gain_loss_percent_display(&self) -> String {
-        format!("{:+.2}%", self.gain_loss_percent())
-    }
-
     pub fn gain_loss_color(&self) -> &'static str {
         if self.realized_gain_loss_cents > 0 {
             "text-green-600 dark:text-green-400"
@@ -415,8 +656,15 @@ impl ClosedPosition {
 }
 
 impl Position {
+    /// True for a short position, i.e. more shares sold than held.
+    pub fn is_short(&self) -> bool {
+        self.quantity < 0.0
+    }
+
+    /// Average cost per share; for a short position this is the average
+    /// entry (sale) price.
     pub fn average_cost_cents(&self) -> Option<i64> {
-        if self.quantity > 0.0 {
+        if self.quantity != 0.0 {
             Some((self.total_cost_cents as f64 / self.quantity).round() as i64)
         } else {
             None
@@ -451,10 +699,7 @@ impl Position {
     }
 
     pub fn quantity_display(&self) -> String {
-        format!("{:.4}", self.quantity)
-            .trim_end_matches('0')
-            .trim_end_matches('.')
-            .to_string()
+        format_quantity(self.quantity)
     }
 }
 
@@ -515,6 +760,8 @@ pub struct TradingImportSession {
     pub processed_rows: i64,
     pub error_count: i64,
     pub errors: Vec<String>,
+    /// Parse statistics of each uploaded file, in upload order.
+    pub file_stats: Vec<crate::models::ImportFileStats>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -592,6 +839,136 @@ pub struct TradingImportRow {
     pub session_id: String,
     pub row_index: i64,
     pub data: crate::services::trading_csv_parser::ParsedTradingActivity,
+    /// Name of the uploaded file the row came from.
+    pub source_file: Option<String>,
     pub status: String,
     pub error: Option<String>,
+    /// Advisory warning that does not block the import
+    pub warning: Option<String>,
+}
+
+/// Up to four decimals, without trailing zeros.
+pub fn format_quantity(quantity: f64) -> String {
+    crate::services::money::format_decimal(quantity, 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drip_note_round_trip() {
+        let note = drip_note(12, Some(13), 14, Some(" reinvested "));
+        assert_eq!(note, "DRIP #12: dividend #12, tax #13, buy #14\nreinvested");
+        assert_eq!(
+            drip_note(12, None, 14, Some("")),
+            "DRIP #12: dividend #12, buy #14"
+        );
+
+        let mut activity = TradingActivity {
+            id: 14,
+            date: "2024-03-15".into(),
+            symbol: "VWRL".into(),
+            quantity: Some(0.5),
+            activity_type: TradingActivityType::Buy,
+            unit_price_cents: Some(10_000),
+            currency: "EUR".into(),
+            fee_cents: 0,
+            fee_currency: None,
+            exchange_rate: None,
+            account_id: None,
+            notes: Some(note),
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        assert_eq!(activity.drip_group(), Some(12));
+        activity.notes = Some("DRIP #x: manual".into());
+        assert_eq!(activity.drip_group(), None);
+    }
+
+    #[test]
+    fn test_mark_staleness() {
+        let position = Position {
+            symbol: "AAPL".into(),
+            quantity: 1.0,
+            total_cost_cents: 10_000,
+            currency: "USD".into(),
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+
+        let mut fresh =
+            PositionWithMarketData::with_market_data(position.clone(), 100, "2024-03-08".into());
+        fresh.mark_staleness(today, 7);
+        assert!(!fresh.is_stale);
+
+        let mut old =
+            PositionWithMarketData::with_market_data(position.clone(), 100, "2024-03-07".into());
+        old.mark_staleness(today, 7);
+        assert!(old.is_stale);
+
+        let mut approximated = PositionWithMarketData::with_approximated_price(
+            position.clone(),
+            100,
+            "2024-03-15".into(),
+        );
+        approximated.mark_staleness(today, 7);
+        assert!(approximated.is_stale);
+
+        let mut unpriced = PositionWithMarketData::from_position(position);
+        unpriced.mark_staleness(today, 7);
+        assert!(!unpriced.is_stale);
+    }
+
+    #[test]
+    fn test_convert_position() {
+        let position = Position {
+            symbol: "SAP".into(),
+            quantity: 10.0,
+            total_cost_cents: 100_000,
+            currency: "EUR".into(),
+        };
+        let mut pos =
+            PositionWithMarketData::with_market_data(position.clone(), 12_000, "2024-03-08".into());
+        assert_eq!(pos.display_cost_cents(), 100_000);
+        pos.convert("USD", 1.085);
+        let converted = pos.converted.clone().unwrap();
+        assert_eq!(converted.total_cost_cents, 108_500);
+        assert_eq!(converted.current_value_cents, Some(130_200));
+        assert_eq!(converted.gain_loss_cents, Some(21_700));
+        assert_eq!(pos.display_value_cents(), Some(130_200));
+
+        let mut unpriced = PositionWithMarketData::from_position(position);
+        unpriced.convert("USD", 1.085);
+        assert_eq!(unpriced.display_cost_cents(), 108_500);
+        assert_eq!(unpriced.display_value_cents(), None);
+    }
+
+    #[test]
+    fn test_convert_fee_cents() {
+        assert_eq!(convert_fee_cents(500, "USD", None, None), Some(500));
+        assert_eq!(convert_fee_cents(500, "USD", Some("usd"), None), Some(500));
+        assert_eq!(
+            convert_fee_cents(500, "USD", Some("EUR"), Some(1.085)),
+            Some(543)
+        );
+        assert_eq!(convert_fee_cents(500, "USD", Some("EUR"), None), None);
+        assert_eq!(convert_fee_cents(500, "USD", Some("EUR"), Some(0.0)), None);
+    }
+
+    #[test]
+    fn test_normalize_fee_currency() {
+        assert_eq!(
+            normalize_fee_currency("USD", Some(" eur "), Some(1.1)),
+            Ok((Some("EUR".to_string()), Some(1.1)))
+        );
+        assert_eq!(
+            normalize_fee_currency("USD", Some("usd"), Some(1.1)),
+            Ok((None, None))
+        );
+        assert_eq!(
+            normalize_fee_currency("USD", Some(""), None),
+            Ok((None, None))
+        );
+        assert!(normalize_fee_currency("USD", Some("EUR"), Some(-1.0)).is_err());
+    }
 }
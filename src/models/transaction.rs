@@ -4,6 +4,33 @@ use crate::models::tag::Tag;
 use serde::{Deserialize, Serialize};
 use std::ops::Deref;
 
+/// Whether a transaction has been booked. Pending transactions are left out
+/// of balances and analytics until they are marked as posted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    #[default]
+    Posted,
+    Pending,
+}
+
+impl TransactionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Posted => "posted",
+            Self::Pending => "pending",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "posted" => Some(Self::Posted),
+            "pending" => Some(Self::Pending),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: i64,
@@ -26,6 +53,15 @@ pub struct Transaction {
     pub creditor_id: Option<String>,
     pub mandate_reference: Option<String>,
     pub customer_reference: Option<String>,
+    /// The other side of an account-to-account transfer, if linked
+    pub transfer_pair_id: Option<i64>,
+    pub status: TransactionStatus,
+    /// The category was assigned by a rule and not yet reviewed
+    pub auto_categorized: bool,
+    /// Rule that assigned the category, unless it was deleted since
+    pub auto_category_rule_id: Option<i64>,
+    /// Trading activity this transaction mirrors (dividend, fee or tax)
+    pub source_activity_id: Option<i64>,
 }
 
 impl Transaction {
@@ -132,9 +168,17 @@ impl TransactionWithRelations {
     pub fn has_tag(&self, id: &i64) -> bool {
         self.tags.iter().any(|t| t.id == *id)
     }
+
+    pub fn is_transfer_pair(&self) -> bool {
+        self.transfer_pair_id.is_some()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == TransactionStatus::Pending
+    }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct NewTransaction {
     pub date: String,
     pub amount_cents: i64,
@@ -155,10 +199,6 @@ pub struct NewTransaction {
     pub creditor_id: Option<String>,
     pub mandate_reference: Option<String>,
     pub customer_reference: Option<String>,
-}
-
-impl NewTransaction {
-    pub fn from_decimal(amount: f64) -> i64 {
-        (amount * 100.0).round() as i64
-    }
+    #[serde(default)]
+    pub status: TransactionStatus,
 }
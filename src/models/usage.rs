@@ -0,0 +1,70 @@
+use serde::Serialize;
+
+/// Requests of one day that share route, method, status and latency bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsageKey {
+    pub date: String,
+    pub method: String,
+    /// Route pattern, e.g. `/transactions/:id`.
+    pub route: String,
+    pub status: u16,
+    /// Upper bound of the latency bucket in milliseconds.
+    pub duration_bucket_ms: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UsageCount {
+    pub count: i64,
+    pub total_duration_ms: f64,
+}
+
+/// Share of `requests` that failed, or a dash when there were none.
+pub fn error_rate_label(errors: i64, requests: i64) -> String {
+    if requests == 0 {
+        return "–".into();
+    }
+    format!("{:.1}%", errors as f64 * 100.0 / requests as f64)
+}
+
+/// Usage of one route and method over the selected window.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RouteUsage {
+    pub method: String,
+    pub route: String,
+    pub requests: i64,
+    /// Responses with a 4xx or 5xx status.
+    pub errors: i64,
+    pub total_duration_ms: f64,
+    /// Upper bound of the latency bucket holding the median request.
+    pub median_bucket_ms: i64,
+}
+
+impl RouteUsage {
+    pub fn error_rate_percent(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 * 100.0 / self.requests as f64
+        }
+    }
+
+    pub fn average_ms(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.total_duration_ms / self.requests as f64
+        }
+    }
+
+    pub fn error_rate_label(&self) -> String {
+        error_rate_label(self.errors, self.requests)
+    }
+
+    pub fn average_label(&self) -> String {
+        format!("{:.0} ms", self.average_ms())
+    }
+
+    pub fn median_label(&self) -> String {
+        crate::usage::bucket_label(self.median_bucket_ms)
+    }
+}
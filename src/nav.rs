@@ -0,0 +1,275 @@
+//! Sidebar navigation.
+//!
+//! The entries, the section each belongs to and the page titles that
+//! highlight them are fixed here. The `nav_order` and `hidden_nav_items`
+//! settings only reorder entries within their section and hide them, and
+//! [`build`] turns them into the sections the base template renders.
+
+/// Key of the settings entry, which cannot be hidden.
+pub const SETTINGS_ITEM: &str = "settings";
+
+/// Entries that can serve as the landing page, see the `start_page` setting.
+pub const START_PAGES: &[&str] = &["dashboard", "transactions", "positions", "net_worth"];
+
+/// Sidebar sections as (key, heading), top to bottom. Sections without a
+/// heading are not collapsible.
+const SECTIONS: &[(&str, &str)] = &[
+    ("main", ""),
+    ("insights", "Insights"),
+    ("data", "Data"),
+    ("settings", ""),
+];
+
+#[derive(Debug)]
+pub struct NavItem {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub href: &'static str,
+    pub icon: &'static str,
+    section: &'static str,
+    /// Titles of the pages that highlight this entry.
+    active_titles: &'static [&'static str],
+}
+
+impl NavItem {
+    pub fn is_active(&self, title: &str) -> bool {
+        self.active_titles.contains(&title)
+    }
+}
+
+/// Every sidebar entry in default order.
+pub const NAV_ITEMS: &[NavItem] = &[
+    NavItem {
+        key: "dashboard",
+        label: "Dashboard",
+        href: "/dashboard",
+        icon: "home",
+        section: "main",
+        active_titles: &["Dashboard"],
+    },
+    NavItem {
+        key: "balances",
+        label: "Balances",
+        href: "/balances",
+        icon: "wallet",
+        section: "insights",
+        active_titles: &["Balances"],
+    },
+    NavItem {
+        key: "spending",
+        label: "Spending",
+        href: "/spending",
+        icon: "bar-chart",
+        section: "insights",
+        active_titles: &["Spending"],
+    },
+    NavItem {
+        key: "recurring",
+        label: "Recurring",
+        href: "/recurring-expenses",
+        icon: "repeat",
+        section: "insights",
+        active_titles: &["Recurring Expenses"],
+    },
+    NavItem {
+        key: "positions",
+        label: "Positions",
+        href: "/trading/positions",
+        icon: "package",
+        section: "insights",
+        active_titles: &["Positions"],
+    },
+    NavItem {
+        key: "net_worth",
+        label: "Net Worth",
+        href: "/trading/net-worth",
+        icon: "banknote",
+        section: "insights",
+        active_titles: &["Net Worth"],
+    },
+    NavItem {
+        key: "retirement",
+        label: "Retirement",
+        href: "/retirement",
+        icon: "piggy-bank",
+        section: "insights",
+        active_titles: &["Retirement"],
+    },
+    NavItem {
+        key: "accounts",
+        label: "Accounts",
+        href: "/accounts",
+        icon: "credit-card",
+        section: "data",
+        active_titles: &["Accounts", "Add Account", "Edit Account"],
+    },
+    NavItem {
+        key: "transactions",
+        label: "Transactions",
+        href: "/transactions",
+        icon: "receipt",
+        section: "data",
+        active_titles: &["Transactions"],
+    },
+    NavItem {
+        key: "activities",
+        label: "Activities",
+        href: "/trading/activities",
+        icon: "trending-up",
+        section: "data",
+        active_titles: &["Trading Activities"],
+    },
+    NavItem {
+        key: "market_data",
+        label: "Market Data",
+        href: "/trading/market-data",
+        icon: "line-chart",
+        section: "data",
+        active_titles: &["Market Data"],
+    },
+    NavItem {
+        key: "import",
+        label: "Bulk Import",
+        href: "/import",
+        icon: "upload",
+        section: "data",
+        active_titles: &["Import", "Import Transactions", "Import Trading Activities"],
+    },
+    NavItem {
+        key: "manage",
+        label: "Manage",
+        href: "/manage",
+        icon: "sliders-horizontal",
+        section: "data",
+        active_titles: &[
+            "Manage",
+            "Add Category",
+            "Edit Category",
+            "Add Tag",
+            "Add Rule",
+            "Edit Rule",
+        ],
+    },
+    NavItem {
+        key: SETTINGS_ITEM,
+        label: "Settings",
+        href: "/settings",
+        icon: "settings",
+        section: "settings",
+        active_titles: &["Settings"],
+    },
+];
+
+/// The entry with this key.
+pub fn find(key: &str) -> Option<&'static NavItem> {
+    NAV_ITEMS.iter().find(|item| item.key == key)
+}
+
+/// A sidebar section with its visible entries in display order.
+#[derive(Debug, Clone)]
+pub struct NavSection {
+    pub key: &'static str,
+    /// Heading; empty for the sections above and below the collapsible ones.
+    pub label: &'static str,
+    pub items: Vec<&'static NavItem>,
+}
+
+impl NavSection {
+    /// Whether the page titled `title` belongs to this section, which keeps
+    /// it expanded on small screens.
+    pub fn is_active(&self, title: &str) -> bool {
+        self.items.iter().any(|item| item.is_active(title))
+    }
+}
+
+/// Parse a comma-separated list of entry keys, dropping unknown and
+/// repeated keys.
+pub fn parse_keys(value: Option<&str>) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for key in value.unwrap_or_default().split(',').map(str::trim) {
+        if find(key).is_some() && !keys.iter().any(|k| k == key) {
+            keys.push(key.to_string());
+        }
+    }
+    keys
+}
+
+/// Every entry grouped by section, with the entries of `order` first
+/// within each section and the rest in default order.
+pub fn ordered(order: &[String]) -> Vec<&'static NavItem> {
+    let section = |item: &NavItem| SECTIONS.iter().position(|(key, _)| *key == item.section);
+    let position = |item: &NavItem| order.iter().position(|key| key == item.key);
+    let mut items: Vec<&'static NavItem> = NAV_ITEMS.iter().collect();
+    // Stable, so unlisted entries follow the listed ones in default order
+    items.sort_by_key(|item| (section(item), position(item).is_none(), position(item)));
+    items
+}
+
+/// Sidebar sections in the order of [`ordered`], without `hidden` entries.
+/// Empty sections are dropped.
+pub fn build(order: &[String], hidden: &[String]) -> Vec<NavSection> {
+    let items = ordered(order);
+    SECTIONS
+        .iter()
+        .filter_map(|(key, label)| {
+            let items: Vec<&'static NavItem> = items
+                .iter()
+                .copied()
+                .filter(|item| item.section == *key)
+                .filter(|item| item.key == SETTINGS_ITEM || !hidden.iter().any(|h| h == item.key))
+                .collect();
+            (!items.is_empty()).then_some(NavSection { key, label, items })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(sections: &[NavSection]) -> Vec<Vec<&'static str>> {
+        sections
+            .iter()
+            .map(|s| s.items.iter().map(|item| item.key).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_reorders_within_sections_and_hides() {
+        let order = parse_keys(Some("positions,net_worth,transactions"));
+        let hidden = parse_keys(Some("dashboard,retirement,settings"));
+        let sections = build(&order, &hidden);
+
+        assert_eq!(
+            keys(&sections),
+            vec![
+                vec![
+                    "positions",
+                    "net_worth",
+                    "balances",
+                    "spending",
+                    "recurring"
+                ],
+                vec![
+                    "transactions",
+                    "accounts",
+                    "activities",
+                    "market_data",
+                    "import",
+                    "manage"
+                ],
+                // Settings stays even when listed as hidden
+                vec!["settings"],
+            ]
+        );
+        assert!(sections[0].is_active("Net Worth"));
+    }
+
+    #[test]
+    fn test_parse_keys_drops_unknown_and_repeated() {
+        assert_eq!(
+            parse_keys(Some("spending, bogus,spending,,manage")),
+            vec!["spending", "manage"]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Curated colors for categories and tags, and helpers to keep text on
+//! them readable.
+//!
+//! The palette colors read well on both the light and the dark theme.
+//! New categories without an explicit color get the palette color used
+//! least so far ([`least_used`]), and [`text_color`] picks black or white
+//! text for any background by WCAG contrast.
+
+use crate::models::DEFAULT_COLOR;
+
+/// `(name, hex)` pairs offered in color pickers. Gray is the neutral
+/// default and never assigned automatically.
+pub const PALETTE: &[(&str, &str)] = &[
+    ("Red", "#ef4444"),
+    ("Orange", "#f97316"),
+    ("Amber", "#f59e0b"),
+    ("Yellow", "#eab308"),
+    ("Lime", "#84cc16"),
+    ("Green", "#22c55e"),
+    ("Emerald", "#10b981"),
+    ("Teal", "#14b8a6"),
+    ("Cyan", "#06b6d4"),
+    ("Blue", "#3b82f6"),
+    ("Indigo", "#6366f1"),
+    ("Violet", "#8b5cf6"),
+    ("Purple", "#a855f7"),
+    ("Fuchsia", "#d946ef"),
+    ("Pink", "#ec4899"),
+    ("Gray", DEFAULT_COLOR),
+];
+
+/// Palette colors eligible for automatic assignment, in palette order.
+fn assignable() -> impl Iterator<Item = &'static str> + Clone {
+    PALETTE
+        .iter()
+        .map(|(_, hex)| *hex)
+        .filter(|hex| *hex != DEFAULT_COLOR)
+}
+
+/// Parse `#rgb` or `#rrggbb` (any case) into lowercase `#rrggbb`.
+pub fn normalize_hex(color: &str) -> Option<String> {
+    let digits = color.trim().strip_prefix('#')?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits = match digits.len() {
+        3 => digits.chars().flat_map(|c| [c, c]).collect(),
+        6 => digits.to_string(),
+        _ => return None,
+    };
+    Some(format!("#{}", digits.to_ascii_lowercase()))
+}
+
+fn rgb(color: &str) -> Option<[u8; 3]> {
+    let hex = normalize_hex(color)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(1)?, channel(3)?, channel(5)?])
+}
+
+/// WCAG relative luminance of an sRGB color, between 0 (black) and 1 (white).
+fn relative_luminance([r, g, b]: [u8; 3]) -> f64 {
+    let linear = |c: u8| {
+        let c = f64::from(c) / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// Black or white, whichever contrasts more with `background`. Unparseable
+/// colors are treated like the default gray.
+pub fn text_color(background: &str) -> &'static str {
+    let luminance = relative_luminance(
+        rgb(background)
+            .or_else(|| rgb(DEFAULT_COLOR))
+            .unwrap_or([0; 3]),
+    );
+    // Contrast ratios are (L1 + 0.05) / (L2 + 0.05)
+    let against_white = 1.05 / (luminance + 0.05);
+    let against_black = (luminance + 0.05) / 0.05;
+    if against_black > against_white {
+        "#000000"
+    } else {
+        "#ffffff"
+    }
+}
+
+/// The palette color that occurs least often in `used`, the first one in
+/// palette order on ties.
+pub fn least_used<'a>(used: impl IntoIterator<Item = &'a str>) -> &'static str {
+    let used: Vec<String> = used.into_iter().filter_map(normalize_hex).collect();
+    assignable()
+        .min_by_key(|hex| used.iter().filter(|u| u == hex).count())
+        .unwrap_or(DEFAULT_COLOR)
+}
+
+/// Palette colors for `count` items, cycling through the palette so that
+/// neighbours differ.
+pub fn distribute(count: usize) -> Vec<&'static str> {
+    assignable().cycle().take(count).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_hex() {
+        assert_eq!(normalize_hex("#ABC").as_deref(), Some("#aabbcc"));
+        assert_eq!(normalize_hex(" #3B82F6 ").as_deref(), Some("#3b82f6"));
+        for bad in ["", "3b82f6", "#3b82f", "#ggg", "red", "#+1+2+3"] {
+            assert_eq!(normalize_hex(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_text_color_by_contrast() {
+        assert_eq!(text_color("#ffffff"), "#000000");
+        assert_eq!(text_color("#000000"), "#ffffff");
+        assert_eq!(text_color("#eab308"), "#000000"); // yellow
+        assert_eq!(text_color("#1e3a8a"), "#ffffff"); // dark blue
+    }
+
+    #[test]
+    fn test_least_used_skips_taken_colors() {
+        assert_eq!(least_used([]), "#ef4444");
+        assert_eq!(least_used(["#EF4444", "#6b7280"]), "#f97316");
+        let all: Vec<&str> = assignable().collect();
+        let mut used = all.clone();
+        used.push("#ef4444");
+        assert_eq!(least_used(used), "#f97316");
+    }
+
+    #[test]
+    fn test_distribute_cycles() {
+        let colors = distribute(PALETTE.len() + 1);
+        assert_eq!(colors[0], colors[PALETTE.len() - 1]);
+        assert!(!colors.contains(&DEFAULT_COLOR));
+    }
+}
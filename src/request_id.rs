@@ -0,0 +1,84 @@
+//! Request ids for matching error pages to log lines.
+//!
+//! [`request_id_middleware`] takes the id from an incoming `X-Request-Id`
+//! header or generates a UUID, runs the request inside a tracing span that
+//! carries it, and returns it in the `X-Request-Id` response header. Code
+//! running for the request reads it through [`current`], e.g. to print it on
+//! the error page.
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest incoming id that is accepted instead of generating one.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request being handled, or `None` outside a request scope
+/// (background tasks, startup).
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Accept an incoming id only if it is short, visible ASCII, so it cannot
+/// mangle log lines or the page it is printed on.
+fn incoming_id(value: &HeaderValue) -> Option<String> {
+    let id = value.to_str().ok()?;
+    let valid = !id.is_empty()
+        && id.len() <= MAX_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && b != b'<' && b != b'>');
+    valid.then(|| id.to_string())
+}
+
+/// Middleware that assigns every request an id and echoes it in the response.
+pub async fn request_id_middleware(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(incoming_id)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        uri = %request.uri(),
+    );
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(request).instrument(span))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(X_REQUEST_ID, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming_id_accepts_plain_ids() {
+        let value = HeaderValue::from_static("sync-2024-03-01.42");
+        assert_eq!(incoming_id(&value).as_deref(), Some("sync-2024-03-01.42"));
+    }
+
+    #[test]
+    fn test_incoming_id_rejects_unsafe_values() {
+        for bad in ["", "has space", "<script>", &"x".repeat(MAX_LEN + 1)] {
+            let value = HeaderValue::from_str(bad).unwrap();
+            assert_eq!(incoming_id(&value), None, "{:?}", bad);
+        }
+    }
+}
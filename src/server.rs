@@ -1,7 +1,7 @@
-use axum::extract::DefaultBodyLimit;
 use axum::middleware;
 use axum::routing::{get, post};
 use axum::Router;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use tokio::net::TcpListener;
 use tokio::task::JoinHandle;
@@ -13,10 +13,19 @@ use tower_http::trace::TraceLayer;
 use crate::auth;
 use crate::cache::{cache_invalidation_middleware, AppCache};
 use crate::config::Config;
+use crate::confirmation::DeleteConfirmations;
+use crate::csp::security_headers_middleware;
+use crate::db::queries::settings;
 use crate::db::{create_pool, migrations};
 use crate::error_pages::{error_page_middleware, fallback_handler};
+use crate::flash::{self, FlashStore};
 use crate::handlers;
+use crate::idempotency::SubmittedForms;
+use crate::logging;
+use crate::request_id::request_id_middleware;
 use crate::state::{AppState, JsManifest, MarketDataRefreshState};
+use crate::timing::{self, server_timing_middleware};
+use crate::usage::{usage_middleware, UsageRecorder};
 use crate::xsrf::{xsrf_middleware, XsrfToken};
 
 /// Build the application state and Axum router from a [`Config`].
@@ -25,11 +34,24 @@ use crate::xsrf::{xsrf_middleware, XsrfToken};
 /// assembles the full middleware stack. Returns the shared state and a
 /// ready-to-serve router.
 pub fn build_app(config: Config) -> Result<(AppState, Router), Box<dyn std::error::Error>> {
-    let db = create_pool(&config.database_path)?;
+    timing::set_slow_query_threshold(config.slow_query_ms);
+    let db = create_pool(&config.database_path, config.pool_settings())?;
 
     {
         let conn = db.get()?;
-        migrations::run_migrations(&conn, &config.migrations_path)?;
+        migrations::run_migrations(
+            &conn,
+            &config.migrations_path,
+            config.allow_dirty_migrations,
+        )?;
+
+        // Restore a log filter saved on the advanced settings page
+        let log_filter = settings::get_setting(&conn, "log_filter")?.unwrap_or_default();
+        if !log_filter.is_empty() {
+            if let Err(e) = logging::set_filter(&log_filter) {
+                tracing::warn!(filter = %log_filter, error = %e, "Ignoring invalid saved log filter");
+            }
+        }
     }
 
     let manifest = JsManifest::load(&config.static_path);
@@ -42,22 +64,41 @@ pub fn build_app(config: Config) -> Result<(AppState, Router), Box<dyn std::erro
         manifest,
         xsrf_token: xsrf_token.clone(),
         market_data_refresh: Arc::new(Mutex::new(MarketDataRefreshState::default())),
+        symbol_locks: Arc::new(crate::symbol_locks::SymbolLocks::new()),
+        symbol_validation: Arc::new(Mutex::new(std::collections::HashMap::new())),
+        running_imports: Arc::new(Mutex::new(std::collections::HashSet::new())),
         cache: Arc::new(AppCache::new()),
-        sessions: Arc::new(Mutex::new(std::collections::HashSet::new())),
+        sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
+        share_sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
         login_rate_limiter: Arc::new(crate::auth::LoginRateLimiter::new()),
+        delete_confirmations: Arc::new(DeleteConfirmations::new()),
+        submitted_forms: Arc::new(SubmittedForms::new()),
+        flash: Arc::new(FlashStore::new()),
+        symbol_search_throttle: Arc::new(crate::services::market_data::SearchThrottle::default()),
+        notification_throttle: Arc::new(crate::services::notify::NotificationThrottle::default()),
+        usage: Arc::new(UsageRecorder::new()),
     };
 
     let app = Router::new()
-        .merge(handlers::routes())
+        .merge(handlers::routes(&config))
         .route("/login", get(auth::login_page))
         .route("/login", post(auth::login_submit))
         .route("/logout", post(auth::logout))
         .fallback(fallback_handler)
         .nest_service("/static", ServeDir::new(&config.static_path))
+        // Innermost, so route patterns are known and rejected logins aren't counted
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            usage_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             cache_invalidation_middleware,
         ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            flash::flash_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth::auth_middleware,
@@ -70,10 +111,17 @@ pub fn build_app(config: Config) -> Result<(AppState, Router), Box<dyn std::erro
             state.clone(),
             error_page_middleware,
         ))
-        .layer(DefaultBodyLimit::max(256 * 1024 * 1024))
+        // Outside the error pages, so they get a nonce and the headers too
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security_headers_middleware,
+        ))
+        .layer(middleware::from_fn(server_timing_middleware))
         .layer(CookieManagerLayer::new())
         .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http())
+        // Outermost, so every log line of the request carries its id
+        .layer(middleware::from_fn(request_id_middleware))
         .with_state(state.clone());
 
     Ok((state, app))
@@ -93,7 +141,13 @@ pub async fn serve(
     let actual_port = listener.local_addr()?.port();
 
     let handle = tokio::spawn(async move {
-        axum::serve(listener, app).await.expect("Server error");
+        // Peer addresses are used for login rate limiting without a proxy
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .expect("Server error");
     });
 
     Ok((actual_port, handle))
@@ -142,3 +142,75 @@ pub fn format_cents(cents: i64) -> String {
         format!("{}.{:02}", dollars, remainder)
     }
 }
+
+/// [`format_cents`], or an empty string for `None`.
+pub fn optional_cents(cents: Option<i64>) -> String {
+    cents.map(format_cents).unwrap_or_default()
+}
+
+/// Whether weeks start on Monday in `locale` (everywhere except the US).
+pub fn week_starts_monday(locale: &str) -> bool {
+    locale != "en-US"
+}
+
+/// Weekday names in `locale`'s language, indexed like SQLite's
+/// `strftime('%w')` (0 = Sunday).
+pub fn weekday_names(locale: &str) -> [&'static str; 7] {
+    match locale.split('-').next().unwrap_or_default() {
+        "de" => [
+            "Sonntag",
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+        ],
+        "fr" => [
+            "dimanche", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi",
+        ],
+        "es" => [
+            "domingo",
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+        ],
+        _ => [
+            "Sunday",
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+        ],
+    }
+}
+
+/// `strftime('%w')` weekday indices in display order for `locale`.
+pub fn weekday_order(locale: &str) -> [u32; 7] {
+    if week_starts_monday(locale) {
+        [1, 2, 3, 4, 5, 6, 0]
+    } else {
+        [0, 1, 2, 3, 4, 5, 6]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weekday_order_follows_locale() {
+        assert_eq!(weekday_order("en-US")[0], 0);
+        assert_eq!(weekday_order("de-DE")[0], 1);
+        assert_eq!(
+            weekday_names("de-DE")[weekday_order("de-DE")[0] as usize],
+            "Montag"
+        );
+        assert_eq!(weekday_names("en-GB")[6], "Saturday");
+    }
+}
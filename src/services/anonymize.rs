@@ -0,0 +1,258 @@
+//! Scrub a copy of the database so it can be shared, e.g. with bug reports.
+//!
+//! Free text is replaced by fake strings and the names of accounts,
+//! categories and tags by generic labels. Import leftovers and API logs
+//! are dropped. Symbols, dates and the category hierarchy are kept so every
+//! page still renders. All replacements are drawn from an RNG seeded with
+//! [`AnonymizeOptions::seed`], so the same database and seed always give the
+//! same result.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::{params, Connection, Transaction};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::AppResult;
+use crate::services::backup;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnonymizeOptions {
+    pub seed: u64,
+    /// Multiply all amounts and quantities by one random factor between 0.5 and 2.
+    pub scale_amounts: bool,
+}
+
+/// Kind of fake value that replaces a text column.
+#[derive(Debug, Clone, Copy)]
+enum Fake {
+    Phrase,
+    Code,
+    Iban,
+}
+
+/// Free-text columns to replace. Equal values map to equal fakes, so
+/// grouping by description or payee (e.g. for recurring expenses) still works.
+const TEXT_COLUMNS: &[(&str, &str, Fake)] = &[
+    ("transactions", "description", Fake::Phrase),
+    ("transactions", "payer", Fake::Phrase),
+    ("transactions", "payee", Fake::Phrase),
+    ("transactions", "notes", Fake::Phrase),
+    ("transactions", "reference", Fake::Code),
+    ("transactions", "creditor_id", Fake::Code),
+    ("transactions", "mandate_reference", Fake::Code),
+    ("transactions", "customer_reference", Fake::Code),
+    ("transactions", "external_id", Fake::Code),
+    ("trading_activities", "notes", Fake::Phrase),
+    ("trading_activities", "external_id", Fake::Code),
+    ("symbol_metadata", "ignore_note", Fake::Phrase),
+    ("rules", "name", Fake::Phrase),
+    ("rules", "pattern", Fake::Phrase),
+    ("scenarios", "name", Fake::Phrase),
+];
+
+/// IBAN columns, faked with one shared mapping so a counterparty IBAN that
+/// names an own account still does after anonymizing.
+const IBAN_COLUMNS: &[(&str, &str)] =
+    &[("accounts", "iban"), ("transactions", "counterparty_iban")];
+
+/// Tables with raw import data or logged API requests, which are emptied.
+const CLEARED_TABLES: &[&str] = &[
+    "import_rows",
+    "import_sessions",
+    "trading_import_rows",
+    "trading_import_sessions",
+    "api_logs",
+    // Tokens grant read access to the live instance
+    "share_links",
+];
+
+/// Settings that reveal details about the host.
+pub(crate) const CLEARED_SETTINGS: &[&str] = &[
+    "backup_dir",
+    "backup_last_error",
+    "notify_webhook_url",
+    "notify_smtp_host",
+    "notify_smtp_username",
+    "notify_smtp_password",
+    "notify_smtp_from",
+    "notify_smtp_to",
+];
+
+const ADJECTIVES: &[&str] = &[
+    "Amber", "Brisk", "Cedar", "Coral", "Dusty", "Golden", "Harbor", "Ivory", "Maple", "Misty",
+    "Northern", "Quiet", "Silver", "Sunny", "Velvet", "Willow",
+];
+
+const NOUNS: &[&str] = &[
+    "Bakery",
+    "Cinema",
+    "Garage",
+    "Garden",
+    "Grocer",
+    "Insurance",
+    "Market",
+    "Outfitters",
+    "Pharmacy",
+    "Rentals",
+    "Services",
+    "Studio",
+    "Supply",
+    "Travel",
+    "Utilities",
+    "Workshop",
+];
+
+/// Copy the live database to `path` and anonymize the copy.
+pub fn export_copy(conn: &Connection, path: &Path, options: &AnonymizeOptions) -> AppResult<()> {
+    let mut copy = backup::copy_database(conn, path)?;
+    anonymize(&mut copy, options)
+}
+
+/// Anonymize the database behind `conn` in place.
+pub fn anonymize(conn: &mut Connection, options: &AnonymizeOptions) -> AppResult<()> {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let tx = conn.transaction()?;
+
+    for table in CLEARED_TABLES {
+        tx.execute(&format!("DELETE FROM {}", table), [])?;
+    }
+    for key in CLEARED_SETTINGS {
+        tx.execute("DELETE FROM settings WHERE key = ?", [key])?;
+    }
+    for &(table, column, kind) in TEXT_COLUMNS {
+        replace_text(&tx, &[(table, column)], kind, &mut rng)?;
+    }
+    replace_text(&tx, IBAN_COLUMNS, Fake::Iban, &mut rng)?;
+    relabel(&tx)?;
+    if options.scale_amounts {
+        scale_amounts(&tx, rng.gen_range(0.5..2.0))?;
+    }
+    tx.commit()?;
+
+    // Free pages still hold the original values
+    conn.execute_batch("VACUUM")?;
+    Ok(())
+}
+
+/// Replace every distinct non-empty value of the `(table, column)` pairs
+/// with a fake. A value found in several columns gets the same fake in each.
+fn replace_text(
+    tx: &Transaction,
+    columns: &[(&str, &str)],
+    kind: Fake,
+    rng: &mut StdRng,
+) -> AppResult<()> {
+    let union = columns
+        .iter()
+        .map(|(table, column)| {
+            format!(
+                "SELECT {column} AS value FROM {table}
+                 WHERE {column} IS NOT NULL AND {column} != ''"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ");
+    let originals: Vec<String> = tx
+        .prepare(&format!("{union} ORDER BY value"))?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    tx.execute_batch("CREATE TEMP TABLE anon_map (old TEXT PRIMARY KEY, new TEXT NOT NULL)")?;
+    let mut used = HashSet::new();
+    {
+        let mut insert = tx.prepare("INSERT INTO anon_map (old, new) VALUES (?, ?)")?;
+        for old in &originals {
+            insert.execute(params![old, fake_value(kind, rng, &mut used)])?;
+        }
+    }
+    // A single statement per column, so fakes never get mistaken for originals
+    for (table, column) in columns {
+        tx.execute(
+            &format!(
+                "UPDATE {table} SET {column} = (SELECT new FROM anon_map WHERE old = {table}.{column})
+                 WHERE {column} IN (SELECT old FROM anon_map)"
+            ),
+            [],
+        )?;
+    }
+    tx.execute_batch("DROP TABLE anon_map")?;
+    Ok(())
+}
+
+/// Draw a fake value of the given kind that has not been used yet.
+fn fake_value(kind: Fake, rng: &mut StdRng, used: &mut HashSet<String>) -> String {
+    let base = match kind {
+        Fake::Phrase => format!(
+            "{} {}",
+            ADJECTIVES[rng.gen_range(0..ADJECTIVES.len())],
+            NOUNS[rng.gen_range(0..NOUNS.len())]
+        ),
+        Fake::Code => format!("REF{:08}", rng.gen_range(0..100_000_000)),
+        // The XX country code and 00 check digits make sure it is no real IBAN
+        Fake::Iban => format!("XX00{:018}", rng.gen_range(0..1_000_000_000_000_000_000u64)),
+    };
+    let mut value = base.clone();
+    let mut n = 2;
+    while !used.insert(value.clone()) {
+        value = format!("{} {}", base, n);
+        n += 1;
+    }
+    value
+}
+
+/// Replace names with generic labels that keep the category hierarchy.
+/// Built-in categories keep their names since the app looks them up by name.
+/// Names are unique, so rows first get a placeholder to avoid clashing with
+/// names that have not been replaced yet.
+fn relabel(tx: &Transaction) -> AppResult<()> {
+    tx.execute_batch(
+        "UPDATE accounts SET name = '#' || id;
+         UPDATE accounts SET name = account_type || ' Account ' || id;
+         UPDATE categories SET name = '#' || id WHERE built_in = 0;
+         UPDATE categories SET name = 'Category ' || id WHERE built_in = 0;
+         UPDATE tags SET name = '#' || id;
+         UPDATE tags SET name = 'Tag ' || id;
+         UPDATE saved_charts SET name = 'Chart ' || id;
+         UPDATE scenarios SET birthday = substr(birthday, 1, 4) || '-01-01'
+             WHERE birthday IS NOT NULL;",
+    )?;
+    Ok(())
+}
+
+/// Scale money amounts and share quantities by `factor`. Prices stay as
+/// they are since they are public market data.
+fn scale_amounts(tx: &Transaction, factor: f64) -> AppResult<()> {
+    tx.execute(
+        "UPDATE transactions SET amount_cents = CAST(ROUND(amount_cents * ?1) AS INTEGER)",
+        [factor],
+    )?;
+    tx.execute(
+        "UPDATE trading_activities
+         SET quantity = quantity * ?1, fee_cents = CAST(ROUND(fee_cents * ?1) AS INTEGER)
+         WHERE activity_type != 'SPLIT'",
+        [factor],
+    )?;
+    // Amount-only activities keep their cash amount in the unit price
+    tx.execute(
+        "UPDATE trading_activities
+         SET unit_price_cents = CAST(ROUND(unit_price_cents * ?1) AS INTEGER)
+         WHERE quantity IS NULL AND activity_type IN ('DIVIDEND', 'FEE', 'TAX')",
+        [factor],
+    )?;
+    tx.execute(
+        "UPDATE trading_split_adjustments SET original_quantity = original_quantity * ?1",
+        [factor],
+    )?;
+    tx.execute(
+        "UPDATE scenarios SET
+             current_portfolio_override_cents = CAST(ROUND(current_portfolio_override_cents * ?1) AS INTEGER),
+             monthly_savings_cents = CAST(ROUND(monthly_savings_cents * ?1) AS INTEGER),
+             monthly_living_costs_cents = CAST(ROUND(monthly_living_costs_cents * ?1) AS INTEGER),
+             monthly_pension_cents = CAST(ROUND(monthly_pension_cents * ?1) AS INTEGER),
+             deposits_cents = CAST(ROUND(deposits_cents * ?1) AS INTEGER),
+             monthly_barista_income_cents = CAST(ROUND(monthly_barista_income_cents * ?1) AS INTEGER)",
+        [factor],
+    )?;
+    Ok(())
+}
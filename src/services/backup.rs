@@ -0,0 +1,242 @@
+//! Scheduled database snapshots with rotation.
+//!
+//! Snapshots are taken with SQLite's online backup API, so they are
+//! consistent even while the app keeps writing. Files are named
+//! `solvency-YYYYMMDD-HHMMSS.db`, which lets pruning sort them by name.
+
+use chrono::{Duration, NaiveDateTime};
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::queries::settings;
+use crate::error::{AppError, AppResult};
+use crate::services::notify::{self, Severity};
+use crate::state::AppState;
+
+const FILE_PREFIX: &str = "solvency-";
+const FILE_SUFFIX: &str = ".db";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How often the scheduler checks whether a backup is due.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// Settings keys for the outcome of the most recent backup.
+const LAST_AT_KEY: &str = "backup_last_at";
+const LAST_ERROR_KEY: &str = "backup_last_error";
+
+/// Outcome of the most recent backup attempt, shown on the settings page.
+#[derive(Debug, Clone, Default)]
+pub struct BackupStatus {
+    /// Time of the last successful backup (local time, `YYYY-MM-DD HH:MM:SS`).
+    pub last_backup_at: Option<String>,
+    /// Error message if the last attempt failed.
+    pub last_error: Option<String>,
+}
+
+impl BackupStatus {
+    pub fn load(conn: &Connection) -> AppResult<Self> {
+        Ok(Self {
+            last_backup_at: settings::get_setting(conn, LAST_AT_KEY)?,
+            last_error: settings::get_setting(conn, LAST_ERROR_KEY)?,
+        })
+    }
+
+    fn last_backup_time(&self) -> Option<NaiveDateTime> {
+        self.last_backup_at
+            .as_deref()
+            .and_then(|s| NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT).ok())
+    }
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Local::now().naive_local()
+}
+
+/// Whether a backup with the given frequency is due at `now`.
+pub fn is_due(frequency: &str, last: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+    let interval = match frequency {
+        "daily" => Duration::days(1),
+        "weekly" => Duration::weeks(1),
+        _ => return false,
+    };
+    last.is_none_or(|last| now - last >= interval)
+}
+
+pub fn backup_filename(at: NaiveDateTime) -> String {
+    format!(
+        "{}{}{}",
+        FILE_PREFIX,
+        at.format("%Y%m%d-%H%M%S"),
+        FILE_SUFFIX
+    )
+}
+
+/// Resolve the backup directory: the configured setting, else the
+/// deployment default, else a `backups` folder next to the database.
+pub fn backup_dir(state: &AppState, setting: &str) -> PathBuf {
+    if !setting.trim().is_empty() {
+        return PathBuf::from(setting.trim());
+    }
+    state.config.backup_dir.clone().unwrap_or_else(|| {
+        state
+            .config
+            .database_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("backups")
+    })
+}
+
+/// Copy the live database into a new timestamped file in `dir`.
+pub fn snapshot(conn: &Connection, dir: &Path, at: NaiveDateTime) -> AppResult<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(backup_filename(at));
+    copy_database(conn, &path)?;
+    Ok(path)
+}
+
+/// Copy the live database into the file at `path` and return a connection
+/// to the copy.
+pub fn copy_database(conn: &Connection, path: &Path) -> AppResult<Connection> {
+    let mut dest = Connection::open(path)?;
+    let backup = rusqlite::backup::Backup::new(conn, &mut dest)?;
+    backup
+        .run_to_completion(100, std::time::Duration::ZERO, None)
+        .map_err(|e| AppError::Internal(format!("Backup failed: {}", e)))?;
+    drop(backup);
+    Ok(dest)
+}
+
+/// Delete all but the newest `retention` backup files in `dir`.
+/// Returns the number of files removed.
+pub fn prune(dir: &Path, retention: usize) -> AppResult<usize> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(FILE_PREFIX) && n.ends_with(FILE_SUFFIX))
+        })
+        .collect();
+    files.sort();
+    files.reverse();
+
+    let stale = files.iter().skip(retention.max(1)).collect::<Vec<_>>();
+    for path in &stale {
+        fs::remove_file(path)?;
+    }
+    Ok(stale.len())
+}
+
+/// Take a snapshot now, prune old files, and record the outcome.
+pub fn run_backup(state: &AppState) -> AppResult<PathBuf> {
+    let conn = state.db.get()?;
+    let current = settings::get_settings(&conn)?;
+    let dir = backup_dir(state, &current.backup_dir);
+    let at = now();
+
+    let result = snapshot(&conn, &dir, at).and_then(|path| {
+        let removed = prune(&dir, current.backup_retention.max(1) as usize)?;
+        tracing::info!(path = %path.display(), removed, "Database backup written");
+        Ok(path)
+    });
+
+    match &result {
+        Ok(_) => {
+            settings::set_setting(&conn, LAST_AT_KEY, &at.format(TIMESTAMP_FORMAT).to_string())?;
+            settings::delete_setting(&conn, LAST_ERROR_KEY)?;
+        }
+        Err(e) => {
+            tracing::error!(dir = %dir.display(), error = %e, "Database backup failed");
+            settings::set_setting(&conn, LAST_ERROR_KEY, &e.to_string())?;
+        }
+    }
+    result
+}
+
+/// Run a backup if one is due according to the configured frequency.
+fn run_if_due(state: &AppState) -> AppResult<()> {
+    let conn = state.db.get()?;
+    let frequency = settings::get_settings(&conn)?.backup_frequency;
+    let status = BackupStatus::load(&conn)?;
+    drop(conn);
+
+    if is_due(&frequency, status.last_backup_time(), now()) {
+        run_backup(state)?;
+    }
+    Ok(())
+}
+
+/// Background loop that takes scheduled backups. Spawn once at startup.
+pub async fn run_scheduler(state: AppState) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = run_if_due(&state) {
+            tracing::warn!(error = %e, "Scheduled backup check failed");
+            notify::notify(
+                &state,
+                "scheduled_backup",
+                Severity::Error,
+                "Scheduled backup failed",
+                &e.to_string(),
+            )
+            .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_is_due() {
+        let now = at("2024-03-10 12:00:00");
+        assert!(!is_due("off", None, now));
+        assert!(is_due("daily", None, now));
+        assert!(is_due("daily", Some(at("2024-03-09 12:00:00")), now));
+        assert!(!is_due("daily", Some(at("2024-03-09 12:00:01")), now));
+        assert!(!is_due("weekly", Some(at("2024-03-05 12:00:00")), now));
+        assert!(is_due("weekly", Some(at("2024-03-03 11:00:00")), now));
+    }
+
+    #[test]
+    fn test_backup_filename() {
+        assert_eq!(
+            backup_filename(at("2024-03-10 08:05:09")),
+            "solvency-20240310-080509.db"
+        );
+    }
+
+    #[test]
+    fn test_prune_keeps_newest() {
+        let dir = std::env::temp_dir().join(format!("solvency-prune-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for day in 1..=4 {
+            fs::write(dir.join(format!("solvency-2024030{}-000000.db", day)), b"").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        assert_eq!(prune(&dir, 2).unwrap(), 2);
+        let mut left: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![
+                "notes.txt",
+                "solvency-20240303-000000.db",
+                "solvency-20240304-000000.db"
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+//! Realized gains of all sales in a tax year, with wash-sale flags.
+//!
+//! A sale at a loss is flagged when the same symbol was bought within a
+//! window of days before or after it and those shares replace the sold
+//! ones, since many jurisdictions then defer the loss. Gains and repurchases
+//! come from the same average-cost replay as the positions page. The cost
+//! basis is not adjusted; the flag only lists the repurchases so the numbers
+//! can be reported.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::date_utils::parse_iso_date;
+use crate::models::{TradingActivity, TradingActivityType};
+use crate::services::positions::{self, RealizedSale, ReplayStep};
+
+/// A buy of the sold symbol within the wash-sale window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Repurchase {
+    pub activity_id: i64,
+    pub date: String,
+    pub quantity: f64,
+}
+
+/// One sale of a tax year.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CapitalGain {
+    pub symbol: String,
+    pub currency: String,
+    #[serde(flatten)]
+    pub sale: RealizedSale,
+    /// Buys within the window around a loss sale, oldest first; empty for
+    /// gains and for losses that are not wash sales.
+    pub repurchases: Vec<Repurchase>,
+}
+
+impl CapitalGain {
+    pub fn is_wash_sale(&self) -> bool {
+        !self.repurchases.is_empty()
+    }
+
+    /// Date of the first repurchase, if the sale is a wash sale.
+    pub fn repurchase_date(&self) -> Option<&str> {
+        self.repurchases.first().map(|r| r.date.as_str())
+    }
+
+    /// Shares bought back within the window.
+    pub fn repurchase_quantity(&self) -> f64 {
+        self.repurchases.iter().map(|r| r.quantity).sum()
+    }
+}
+
+/// Buys among the replayed `steps` (all of one symbol) dated within
+/// `window_days` of the sale at `sale_index`, the sale day included, that
+/// replace the sold shares: buys after the sale, and shares bought before it
+/// that are still held after it. Disposals take from every lot alike, as the
+/// cost basis is averaged, so the shares the sale itself closes never count.
+/// Gains and a window of zero days never have repurchases.
+fn wash_sale_repurchases(
+    steps: &[ReplayStep],
+    sale_index: usize,
+    sale: &RealizedSale,
+    window_days: i64,
+) -> Vec<Repurchase> {
+    if sale.gain_cents >= 0 || window_days <= 0 {
+        return Vec::new();
+    }
+    let Some(sale_date) = parse_iso_date(&sale.date) else {
+        return Vec::new();
+    };
+    let in_window = |activity: &TradingActivity| {
+        parse_iso_date(&activity.date)
+            .is_some_and(|date| (date - sale_date).num_days().abs() <= window_days)
+    };
+    let repurchase = |activity: &TradingActivity, quantity: f64| Repurchase {
+        activity_id: activity.id,
+        date: activity.date.clone(),
+        quantity,
+    };
+
+    // Buy lots still held after the sale, as (activity, remaining quantity)
+    let mut lots: Vec<(&TradingActivity, f64)> = Vec::new();
+    for step in &steps[..=sale_index] {
+        if step.activity.activity_type == TradingActivityType::Buy {
+            lots.push((step.activity, step.activity.quantity.unwrap_or(0.0)));
+        }
+        for lot in &mut lots {
+            lot.1 *= 1.0 - step.disposed_share;
+        }
+        if step.quantity <= 0.0 {
+            lots.clear();
+        }
+    }
+
+    lots.into_iter()
+        .filter(|&(lot, remaining)| remaining > 1e-9 && in_window(lot))
+        .map(|(lot, remaining)| repurchase(lot, remaining))
+        .chain(
+            steps[sale_index + 1..]
+                .iter()
+                .map(|step| step.activity)
+                .filter(|a| a.activity_type == TradingActivityType::Buy && in_window(a))
+                .map(|a| repurchase(a, a.quantity.unwrap_or(0.0))),
+        )
+        .collect()
+}
+
+/// Realized gains of the sales dated in `year`, ordered by date and symbol.
+/// Repurchases are looked up in all activities, so buys early in the next
+/// year still flag a sale in December.
+pub fn capital_gains(
+    activities: &[TradingActivity],
+    year: i32,
+    window_days: i64,
+) -> Vec<CapitalGain> {
+    let mut by_symbol: BTreeMap<&str, Vec<&TradingActivity>> = BTreeMap::new();
+    for activity in activities {
+        by_symbol
+            .entry(activity.symbol.as_str())
+            .or_default()
+            .push(activity);
+    }
+
+    let prefix = format!("{:04}-", year);
+    let mut gains: Vec<CapitalGain> = by_symbol
+        .into_iter()
+        .flat_map(|(symbol, activities)| {
+            let currency = activities[0].currency.clone();
+            let steps = positions::replay(activities);
+            steps
+                .iter()
+                .enumerate()
+                .filter_map(|(index, step)| Some((index, step.realized_sale()?)))
+                .filter(|(_, sale)| sale.date.starts_with(&prefix))
+                .map(|(index, sale)| CapitalGain {
+                    symbol: symbol.to_string(),
+                    currency: currency.clone(),
+                    repurchases: wash_sale_repurchases(&steps, index, &sale, window_days),
+                    sale,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    gains.sort_by(|a, b| {
+        a.sale
+            .date
+            .cmp(&b.sale.date)
+            .then_with(|| a.symbol.cmp(&b.symbol))
+            .then(a.sale.activity_id.cmp(&b.sale.activity_id))
+    });
+    gains
+}
+
+/// Years with at least one sale, newest first.
+pub fn sale_years(activities: &[TradingActivity]) -> Vec<i32> {
+    let mut years: Vec<i32> = activities
+        .iter()
+        .filter(|a| a.activity_type == TradingActivityType::Sell)
+        .filter_map(|a| a.date.get(..4)?.parse().ok())
+        .collect();
+    years.sort_unstable_by(|a, b| b.cmp(a));
+    years.dedup();
+    years
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(
+        id: i64,
+        date: &str,
+        activity_type: TradingActivityType,
+        quantity: f64,
+        price_cents: i64,
+    ) -> TradingActivity {
+        TradingActivity {
+            id,
+            date: date.into(),
+            symbol: "AAPL".into(),
+            quantity: Some(quantity),
+            activity_type,
+            unit_price_cents: Some(price_cents),
+            currency: "USD".into(),
+            fee_cents: 0,
+            fee_currency: None,
+            exchange_rate: None,
+            account_id: None,
+            notes: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_loss_with_repurchase_in_window_is_flagged() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2023-06-01", Buy, 10.0, 10_000),
+            activity(2, "2023-12-20", Sell, 10.0, 8_000),
+            activity(3, "2024-01-19", Buy, 4.0, 7_500),
+            activity(4, "2024-01-20", Buy, 2.0, 7_500),
+        ];
+
+        let gains = capital_gains(&activities, 2023, 30);
+        assert_eq!(gains.len(), 1);
+        assert_eq!(gains[0].sale.gain_cents, -20_000);
+        // The second buy is 31 days after the sale
+        assert_eq!(
+            gains[0].repurchases,
+            vec![Repurchase {
+                activity_id: 3,
+                date: "2024-01-19".into(),
+                quantity: 4.0,
+            }]
+        );
+        assert_eq!(gains[0].repurchase_date(), Some("2024-01-19"));
+
+        // A shorter window clears the flag
+        assert!(!capital_gains(&activities, 2023, 20)[0].is_wash_sale());
+    }
+
+    #[test]
+    fn test_gains_and_other_years_are_not_flagged() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000),
+            activity(2, "2024-01-10", Sell, 5.0, 12_000),
+            activity(3, "2024-01-15", Buy, 5.0, 11_000),
+            activity(4, "2025-03-01", Sell, 10.0, 9_000),
+        ];
+
+        let gains = capital_gains(&activities, 2024, 30);
+        assert_eq!(gains.len(), 1);
+        assert_eq!(gains[0].sale.activity_id, 2);
+        assert!(!gains[0].is_wash_sale());
+        assert_eq!(sale_years(&activities), vec![2025, 2024]);
+    }
+
+    #[test]
+    fn test_shares_closed_by_the_sale_are_no_repurchase() {
+        use TradingActivityType::*;
+        // Buying and selling the same shares at a loss is no wash sale
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000),
+            activity(2, "2024-01-20", Sell, 10.0, 9_000),
+        ];
+        assert!(!capital_gains(&activities, 2024, 30)[0].is_wash_sale());
+
+        // Shares bought in the window and still held after the sale count,
+        // as far as they are left over; the sale takes 12 of 18 shares from
+        // every lot alike
+        let activities = vec![
+            activity(1, "2023-06-01", Buy, 10.0, 10_000),
+            activity(2, "2024-01-05", Buy, 8.0, 9_500),
+            activity(3, "2024-01-20", Sell, 12.0, 9_000),
+        ];
+        let gains = capital_gains(&activities, 2024, 30);
+        assert_eq!(gains[0].repurchases.len(), 1);
+        assert_eq!(gains[0].repurchases[0].activity_id, 2);
+        assert!((gains[0].repurchase_quantity() - 8.0 / 3.0).abs() < 1e-9);
+    }
+}
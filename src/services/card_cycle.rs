@@ -0,0 +1,224 @@
+//! Credit card statement cycles: spending per cycle and the amount due.
+//!
+//! A statement closes on the card's statement day and the balance owed at
+//! closing is due on the following due day. Payments to the card (incoming
+//! transfers from another account) after the closing reduce the amount due.
+
+use chrono::{Datelike, NaiveDate};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::date_utils::{month_end, shift_months};
+use crate::db::queries::{accounts, balances};
+use crate::error::AppResult;
+use crate::models::{Account, AccountType};
+
+/// Amounts due within this many days are flagged on the dashboard.
+pub const DUE_SOON_DAYS: i64 = 7;
+
+/// A credit card statement cycle: the days after one statement closing up
+/// to and including the next, and the day its balance is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementCycle {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub due: NaiveDate,
+}
+
+impl StatementCycle {
+    /// The cycle that closed on the day before this one started.
+    pub fn previous(&self, statement_day: u32, due_day: u32) -> Self {
+        statement_cycle(
+            self.start - chrono::Duration::days(1),
+            statement_day,
+            due_day,
+        )
+    }
+}
+
+/// The statement cycle containing `date` for a card whose statement closes
+/// on `statement_day` and is due on the next `due_day`. Days past the end of
+/// a month fall on its last day, so a card closing on the 31st closes on
+/// February 28th (or 29th).
+pub fn statement_cycle(date: NaiveDate, statement_day: u32, due_day: u32) -> StatementCycle {
+    let closing = day_of_month(date, statement_day);
+    let end = if date <= closing {
+        closing
+    } else {
+        day_of_month(shift_months(date, 1), statement_day)
+    };
+    let start = day_of_month(shift_months(end, -1), statement_day) + chrono::Duration::days(1);
+    let due = match day_of_month(end, due_day) {
+        due if due > end => due,
+        _ => day_of_month(shift_months(end, 1), due_day),
+    };
+    StatementCycle { start, end, due }
+}
+
+/// Day `day` of `date`'s month, clamped to the month's last day.
+fn day_of_month(date: NaiveDate, day: u32) -> NaiveDate {
+    let last = month_end(date).day();
+    NaiveDate::from_ymd_opt(date.year(), date.month(), day.clamp(1, last)).unwrap()
+}
+
+/// Totals of one statement cycle.
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleTotals {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub due: NaiveDate,
+    /// Charges minus refunds, positive when money was spent.
+    pub spending_cents: i64,
+    /// Transfers into the card.
+    pub payments_cents: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleSummary {
+    pub account_id: i64,
+    pub account_name: String,
+    pub statement_day: u32,
+    pub due_day: u32,
+    /// The cycle containing today.
+    pub current: CycleTotals,
+    /// The last closed cycle, whose statement is due next.
+    pub previous: CycleTotals,
+    /// Amount owed when the last statement closed.
+    pub statement_balance_cents: i64,
+    /// Statement balance minus payments since, never negative.
+    pub amount_due_cents: i64,
+    pub due_date: NaiveDate,
+    /// Negative once the due date has passed.
+    pub days_until_due: i64,
+}
+
+impl CycleSummary {
+    pub fn is_overdue(&self) -> bool {
+        self.amount_due_cents > 0 && self.days_until_due < 0
+    }
+
+    pub fn is_due_soon(&self) -> bool {
+        self.amount_due_cents > 0 && self.days_until_due <= DUE_SOON_DAYS
+    }
+}
+
+fn cycle_totals(
+    conn: &Connection,
+    account_id: i64,
+    cycle: StatementCycle,
+) -> AppResult<CycleTotals> {
+    let (spending_cents, payments_cents) = balances::get_card_cycle_totals(
+        conn,
+        account_id,
+        &cycle.start.to_string(),
+        &cycle.end.to_string(),
+    )?;
+    Ok(CycleTotals {
+        start: cycle.start,
+        end: cycle.end,
+        due: cycle.due,
+        spending_cents,
+        payments_cents,
+    })
+}
+
+/// Cycle summary of a credit card as of `today`; `None` for other accounts
+/// and cards without statement and due days.
+pub fn summarize(
+    conn: &Connection,
+    account: &Account,
+    today: NaiveDate,
+) -> AppResult<Option<CycleSummary>> {
+    let (Some(statement_day), Some(due_day)) = (account.statement_day, account.due_day) else {
+        return Ok(None);
+    };
+    if account.account_type != AccountType::CreditCard {
+        return Ok(None);
+    }
+
+    let current_cycle = statement_cycle(today, statement_day, due_day);
+    let previous_cycle = current_cycle.previous(statement_day, due_day);
+    let current = cycle_totals(conn, account.id, current_cycle)?;
+    let previous = cycle_totals(conn, account.id, previous_cycle)?;
+
+    let statement_balance_cents =
+        -balances::get_account_balance_as_of(conn, account.id, &previous.end.to_string())?;
+    let amount_due_cents = (statement_balance_cents - current.payments_cents).max(0);
+
+    Ok(Some(CycleSummary {
+        account_id: account.id,
+        account_name: account.name.clone(),
+        statement_day,
+        due_day,
+        due_date: previous.due,
+        days_until_due: (previous.due - today).num_days(),
+        statement_balance_cents,
+        amount_due_cents,
+        current,
+        previous,
+    }))
+}
+
+/// Active credit cards with an amount due, soonest due date first.
+pub fn upcoming_payments(conn: &Connection, today: NaiveDate) -> AppResult<Vec<CycleSummary>> {
+    let mut summaries = Vec::new();
+    for account in accounts::list_accounts_by_type(conn, AccountType::CreditCard)? {
+        if !account.active {
+            continue;
+        }
+        if let Some(summary) = summarize(conn, &account, today)? {
+            if summary.amount_due_cents > 0 {
+                summaries.push(summary);
+            }
+        }
+    }
+    summaries.sort_by_key(|s| s.due_date);
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_statement_cycle_clamps_to_month_end() {
+        let cycle = statement_cycle(date("2024-02-10"), 31, 25);
+        assert_eq!(cycle.start, date("2024-02-01"));
+        assert_eq!(cycle.end, date("2024-02-29"));
+        assert_eq!(cycle.due, date("2024-03-25"));
+
+        let previous = cycle.previous(31, 25);
+        assert_eq!(previous.start, date("2024-01-01"));
+        assert_eq!(previous.end, date("2024-01-31"));
+        assert_eq!(previous.due, date("2024-02-25"));
+
+        // Closing day itself belongs to the cycle it closes
+        let cycle = statement_cycle(date("2023-03-31"), 31, 10);
+        assert_eq!(cycle.start, date("2023-03-01"));
+        assert_eq!(cycle.end, date("2023-03-31"));
+        assert_eq!(cycle.due, date("2023-04-10"));
+        assert_eq!(
+            statement_cycle(date("2023-04-01"), 31, 10).end,
+            date("2023-04-30")
+        );
+    }
+
+    #[test]
+    fn test_statement_cycle_due_later_in_month() {
+        let cycle = statement_cycle(date("2024-12-20"), 15, 28);
+        assert_eq!(cycle.start, date("2024-12-16"));
+        assert_eq!(cycle.end, date("2025-01-15"));
+        assert_eq!(cycle.due, date("2025-01-28"));
+        assert_eq!(cycle.previous(15, 28).end, date("2024-12-15"));
+
+        // Due on the closing day means due a month later
+        assert_eq!(
+            statement_cycle(date("2024-03-01"), 5, 5).due,
+            date("2024-04-05")
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Cash balances of securities accounts with `derive_cash_from_trading`
+//! set, replayed from their trading activities and transfers.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rusqlite::Connection;
+
+use crate::db::queries::{balances, categories};
+use crate::models::trading::TradingActivityType;
+
+/// The first day a ledger ended with a negative cash balance, which usually
+/// means a deposit is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overdraft {
+    pub date: String,
+    pub balance_cents: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CashLedger {
+    pub balance_cents: i64,
+    /// Net transfers into the account
+    pub deposits_cents: i64,
+    /// Net cash from trading activities
+    pub trading_cents: i64,
+    /// Non-transfer transactions left out of the balance
+    pub ignored_transactions: usize,
+    pub ignored_cents: i64,
+    pub overdraft: Option<Overdraft>,
+}
+
+/// Per-day changes the ledgers make to the transaction-based cash totals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DailyAdjustment {
+    pub date: String,
+    /// Cash from trading activities, added to the cash balance
+    pub trading_cents: i64,
+    /// Ignored transactions, taken out of the cash balance
+    pub ignored_cents: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct CashLedgers {
+    /// One ledger for every account with the flag set, by account ID
+    pub accounts: HashMap<i64, CashLedger>,
+    /// Adjustments over all ledger accounts, oldest first
+    pub daily: Vec<DailyAdjustment>,
+}
+
+impl CashLedgers {
+    pub fn contains(&self, account_id: Option<i64>) -> bool {
+        account_id.is_some_and(|id| self.accounts.contains_key(&id))
+    }
+}
+
+/// Cash effect of one trading activity. Fee, tax and dividend activities
+/// store their total in `unit_price_cents`; transfers, holdings changes and
+/// splits move no cash besides their fee.
+pub fn activity_cash_cents(
+    activity_type: TradingActivityType,
+    quantity: Option<f64>,
+    unit_price_cents: Option<i64>,
+    fee_cents: i64,
+) -> i64 {
+    let price = unit_price_cents.unwrap_or(0);
+    let trade = || {
+        let value = quantity.unwrap_or(0.0) * price as f64;
+        if value.is_finite() {
+            value.round() as i64
+        } else {
+            0
+        }
+    };
+    let amount = match activity_type {
+        TradingActivityType::Buy => -trade(),
+        TradingActivityType::Sell => trade(),
+        TradingActivityType::Dividend => price,
+        TradingActivityType::Fee | TradingActivityType::Tax => -price,
+        TradingActivityType::Split
+        | TradingActivityType::TransferIn
+        | TradingActivityType::TransferOut
+        | TradingActivityType::AddHolding
+        | TradingActivityType::RemoveHolding => 0,
+    };
+    amount.saturating_sub(fee_cents)
+}
+
+/// Replay the cash ledgers of all accounts that derive their cash from
+/// trading activities. Only transfers (in the Transfers subtree, or one side
+/// of a transfer pair) count as deposits. Other transactions of the account
+/// would book trades, dividends or fees a second time, so they are ignored
+/// and reported.
+pub fn replay(conn: &Connection) -> rusqlite::Result<CashLedgers> {
+    replay_until(conn, None)
+}
+
+/// Like [`replay`], but stop after the day `as_of` if set.
+pub fn replay_until(conn: &Connection, as_of: Option<&str>) -> rusqlite::Result<CashLedgers> {
+    let account_ids = balances::get_ledger_account_ids(conn)?;
+    if account_ids.is_empty() {
+        return Ok(CashLedgers::default());
+    }
+    let transfer_ids = categories::transfers_excluded_ids(&categories::list_categories(conn)?);
+    let mut activities = balances::get_ledger_activities(conn)?;
+    let mut transactions = balances::get_ledger_transactions(conn)?;
+    if let Some(as_of) = as_of {
+        activities.retain(|a| a.1.as_str() <= as_of);
+        transactions.retain(|t| t.1.as_str() <= as_of);
+    }
+    Ok(replay_rows(
+        &account_ids,
+        &activities,
+        &transactions,
+        &transfer_ids,
+    ))
+}
+
+enum EntryKind {
+    Deposit,
+    Trade,
+    Ignored,
+}
+
+fn replay_rows(
+    account_ids: &[i64],
+    activities: &[balances::LedgerActivityRow],
+    transactions: &[balances::LedgerTransactionRow],
+    transfer_ids: &HashSet<i64>,
+) -> CashLedgers {
+    // Deposits sort before trades on the same day, so funding an account and
+    // buying with the money on one day never looks like an overdraft.
+    let mut entries: Vec<(&str, u8, i64, EntryKind, i64)> = Vec::new();
+    for (account_id, date, amount, category_id, paired) in transactions {
+        let is_transfer = *paired || category_id.is_some_and(|id| transfer_ids.contains(&id));
+        let kind = if is_transfer {
+            EntryKind::Deposit
+        } else {
+            EntryKind::Ignored
+        };
+        entries.push((date, 0, *account_id, kind, *amount));
+    }
+    for (account_id, date, activity_type, quantity, price, fee) in activities {
+        let Ok(activity_type) = activity_type.parse() else {
+            continue;
+        };
+        let cash = activity_cash_cents(activity_type, *quantity, *price, *fee);
+        entries.push((date, 1, *account_id, EntryKind::Trade, cash));
+    }
+    entries.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let mut accounts: HashMap<i64, CashLedger> = account_ids
+        .iter()
+        .map(|&id| (id, CashLedger::default()))
+        .collect();
+    let mut daily: BTreeMap<&str, DailyAdjustment> = BTreeMap::new();
+
+    for (i, (date, _, account_id, kind, cents)) in entries.iter().enumerate() {
+        let Some(ledger) = accounts.get_mut(account_id) else {
+            continue;
+        };
+        match kind {
+            EntryKind::Deposit => {
+                ledger.deposits_cents = ledger.deposits_cents.saturating_add(*cents);
+                ledger.balance_cents = ledger.balance_cents.saturating_add(*cents);
+            }
+            EntryKind::Trade => {
+                ledger.trading_cents = ledger.trading_cents.saturating_add(*cents);
+                ledger.balance_cents = ledger.balance_cents.saturating_add(*cents);
+                let day = daily.entry(date).or_default();
+                day.trading_cents = day.trading_cents.saturating_add(*cents);
+            }
+            EntryKind::Ignored => {
+                ledger.ignored_transactions += 1;
+                ledger.ignored_cents = ledger.ignored_cents.saturating_add(*cents);
+                let day = daily.entry(date).or_default();
+                day.ignored_cents = day.ignored_cents.saturating_add(*cents);
+            }
+        }
+
+        // Check the balances at the end of each day
+        if entries.get(i + 1).is_none_or(|next| next.0 != *date) {
+            for ledger in accounts.values_mut() {
+                if ledger.balance_cents < 0 && ledger.overdraft.is_none() {
+                    ledger.overdraft = Some(Overdraft {
+                        date: date.to_string(),
+                        balance_cents: ledger.balance_cents,
+                    });
+                }
+            }
+        }
+    }
+
+    let daily = daily
+        .into_iter()
+        .map(|(date, day)| DailyAdjustment {
+            date: date.to_string(),
+            ..day
+        })
+        .collect();
+    CashLedgers { accounts, daily }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TradingActivityType::*;
+
+    const TRANSFERS: i64 = 3;
+
+    fn activity(
+        date: &str,
+        activity_type: TradingActivityType,
+        quantity: Option<f64>,
+        price: i64,
+        fee: i64,
+    ) -> balances::LedgerActivityRow {
+        (
+            1,
+            date.into(),
+            activity_type.as_str().into(),
+            quantity,
+            Some(price),
+            fee,
+        )
+    }
+
+    fn transaction(
+        date: &str,
+        cents: i64,
+        category_id: Option<i64>,
+    ) -> balances::LedgerTransactionRow {
+        (1, date.into(), cents, category_id, false)
+    }
+
+    #[test]
+    fn test_activity_cash() {
+        assert_eq!(
+            activity_cash_cents(Buy, Some(10.0), Some(1_000), 100),
+            -10_100
+        );
+        assert_eq!(
+            activity_cash_cents(Sell, Some(4.0), Some(1_500), 100),
+            5_900
+        );
+        assert_eq!(activity_cash_cents(Dividend, None, Some(250), 0), 250);
+        assert_eq!(activity_cash_cents(Tax, Some(1.0), Some(40), 0), -40);
+        assert_eq!(activity_cash_cents(Split, Some(2.0), None, 0), 0);
+        assert_eq!(
+            activity_cash_cents(TransferIn, Some(5.0), Some(900), 50),
+            -50
+        );
+    }
+
+    #[test]
+    fn test_transfers_count_and_other_transactions_are_ignored() {
+        let ledgers = replay_rows(
+            &[1],
+            &[
+                activity("2024-01-02", Buy, Some(10.0), 800, 0),
+                activity("2024-02-01", Dividend, None, 50, 0),
+            ],
+            &[
+                transaction("2024-01-02", 10_000, Some(TRANSFERS)),
+                transaction("2024-02-01", 50, Some(4)),
+            ],
+            &HashSet::from([TRANSFERS]),
+        );
+        let ledger = &ledgers.accounts[&1];
+        assert_eq!(ledger.deposits_cents, 10_000);
+        assert_eq!(ledger.trading_cents, -7_950);
+        assert_eq!(ledger.balance_cents, 2_050);
+        assert_eq!(ledger.ignored_transactions, 1);
+        assert_eq!(ledger.overdraft, None);
+        assert_eq!(
+            ledgers.daily,
+            vec![
+                DailyAdjustment {
+                    date: "2024-01-02".into(),
+                    trading_cents: -8_000,
+                    ignored_cents: 0,
+                },
+                DailyAdjustment {
+                    date: "2024-02-01".into(),
+                    trading_cents: 50,
+                    ignored_cents: 50,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_first_overdraft_is_reported() {
+        let ledgers = replay_rows(
+            &[1, 2],
+            &[
+                activity("2024-01-02", Buy, Some(10.0), 800, 0),
+                activity("2024-03-01", Buy, Some(10.0), 800, 0),
+            ],
+            &[transaction("2024-02-01", 5_000, Some(TRANSFERS))],
+            &HashSet::from([TRANSFERS]),
+        );
+        assert_eq!(
+            ledgers.accounts[&1].overdraft,
+            Some(Overdraft {
+                date: "2024-01-02".into(),
+                balance_cents: -8_000,
+            })
+        );
+        assert_eq!(ledgers.accounts[&2], CashLedger::default());
+    }
+}
@@ -1,4 +1,6 @@
+use crate::date_utils::DateFormat;
 use crate::error::AppError;
+use crate::services::money;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, trace, warn};
 
@@ -44,7 +46,14 @@ pub struct ParseResult {
     pub errors: Vec<String>,
 }
 
-pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
+/// Parse a transactions CSV. Amounts are read according to `locale` and
+/// stored normalized as `[-]123.45`, dates according to `date_format` and
+/// stored as ISO.
+pub fn parse_csv(
+    content: &[u8],
+    locale: &str,
+    date_format: DateFormat,
+) -> Result<ParseResult, AppError> {
     trace!(content_size = content.len(), "Starting CSV parsing");
 
     let content_str =
@@ -113,11 +122,21 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
             continue;
         }
 
-        let amount_clean = clean_amount(&amount);
-        if amount_clean.parse::<f64>().is_err() {
-            errors.push(format!("Row {}: Invalid amount '{}'", row_number, amount));
-            continue;
-        }
+        let date = match date_format.parse(&date) {
+            Ok(date) => date.to_string(),
+            Err(e) => {
+                errors.push(format!("Row {}: {}", row_number, e));
+                continue;
+            }
+        };
+
+        let amount_cents = match money::parse_amount(&amount, locale) {
+            Ok(cents) => cents,
+            Err(_) => {
+                errors.push(format!("Row {}: Invalid amount '{}'", row_number, amount));
+                continue;
+            }
+        };
 
         let currency = currency_col
             .and_then(|col| record.get(col))
@@ -147,7 +166,16 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
             .unwrap_or_default();
 
         let notes = get_optional_field(&record, notes_col);
-        let value_date = get_optional_field(&record, value_date_col);
+        let value_date = match get_optional_field(&record, value_date_col)
+            .map(|d| date_format.parse(&d))
+            .transpose()
+        {
+            Ok(value_date) => value_date.map(|d| d.to_string()),
+            Err(e) => {
+                errors.push(format!("Row {}: Value date: {}", row_number, e));
+                continue;
+            }
+        };
         let payer = get_optional_field(&record, payer_col);
         let payee = get_optional_field(&record, payee_col);
         let reference = get_optional_field(&record, reference_col);
@@ -159,7 +187,7 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
 
         transactions.push(ParsedTransaction {
             date,
-            amount: amount_clean,
+            amount: money::format_cents(amount_cents),
             currency,
             description,
             category,
@@ -209,52 +237,20 @@ fn get_optional_field(record: &csv::StringRecord, col: Option<usize>) -> Option<
         .filter(|s| !s.is_empty())
 }
 
-fn clean_amount(amount: &str) -> String {
-    // Determine which character is the decimal separator:
-    // If both . and , appear, the last one is the decimal separator
-    let last_dot = amount.rfind('.');
-    let last_comma = amount.rfind(',');
-
-    let decimal_char = match (last_dot, last_comma) {
-        (Some(d), Some(c)) => {
-            if d > c {
-                Some('.')
-            } else {
-                Some(',')
-            }
-        }
-        (Some(_), None) => Some('.'),
-        (None, Some(_)) => Some(','),
-        (None, None) => None,
-    };
-
-    let mut result = String::new();
-    let mut has_decimal = false;
-
-    for c in amount.chars() {
-        if c.is_ascii_digit() {
-            result.push(c);
-        } else if Some(c) == decimal_char && !has_decimal {
-            result.push('.');
-            has_decimal = true;
-        } else if c == '-' && result.is_empty() {
-            result.push(c);
-        }
-        // Skip thousand separators and currency symbols
-    }
-
-    result
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// An amount as normalized by the parser.
+    fn amount(s: &str) -> String {
+        money::format_cents(money::parse_amount(s, "en-US").unwrap())
+    }
+
     #[test]
     fn test_parse_simple_csv() {
         let csv = b"date,amount,description\n2024-01-15,50.00,Groceries\n2024-01-16,25.50,Coffee";
 
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 2);
         assert_eq!(result.errors.len(), 0);
 
@@ -264,25 +260,35 @@ mod tests {
     }
 
     #[test]
-    fn test_clean_amount() {
-        assert_eq!(clean_amount("$50.00"), "50.00");
-        assert_eq!(clean_amount("-$25.50"), "-25.50");
-        assert_eq!(clean_amount("1,234.56"), "1234.56");
-        assert_eq!(clean_amount("€100"), "100");
+    fn test_parse_amount_formats() {
+        let csv = "date,amount,description\n2024-01-15,$50.00,A\n2024-01-15,-$25.50,B\n\
+                   2024-01-15,\"1,234.56\",C\n2024-01-15,€100,D\n2024-01-15,(4.35),E";
+        let amounts: Vec<String> = parse_csv(csv.as_bytes(), "en-US", DateFormat::Iso)
+            .unwrap()
+            .transactions
+            .into_iter()
+            .map(|t| t.amount)
+            .collect();
+        assert_eq!(amounts, ["50.00", "-25.50", "1234.56", "100.00", "-4.35"]);
+
+        let csv = "date,amount,description\n15.01.2024,\"1.234,56 €\",A\n15.01.2024,1.234,B";
+        let result = parse_csv(csv.as_bytes(), "de-DE", DateFormat::Iso).unwrap();
+        assert_eq!(result.transactions[0].amount, "1234.56");
+        assert_eq!(result.transactions[1].amount, "1234.00");
     }
 
     // --- Empty / header-only files ---
 
     #[test]
     fn test_parse_empty_file() {
-        let result = parse_csv(b"");
+        let result = parse_csv(b"", "en-US", DateFormat::Iso);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_parse_headers_only() {
         let csv = b"date,amount,description";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 0);
         assert_eq!(result.errors.len(), 0);
     }
@@ -292,21 +298,21 @@ mod tests {
     #[test]
     fn test_parse_missing_date_column() {
         let csv = b"amount,description\n50.00,Groceries";
-        let result = parse_csv(csv);
+        let result = parse_csv(csv, "en-US", DateFormat::Iso);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_parse_missing_amount_column() {
         let csv = b"date,description\n2024-01-15,Groceries";
-        let result = parse_csv(csv);
+        let result = parse_csv(csv, "en-US", DateFormat::Iso);
         assert!(result.is_err());
     }
 
     #[test]
     fn test_parse_missing_description_column() {
         let csv = b"date,amount\n2024-01-15,50.00";
-        let result = parse_csv(csv);
+        let result = parse_csv(csv, "en-US", DateFormat::Iso);
         assert!(result.is_err());
     }
 
@@ -315,7 +321,7 @@ mod tests {
     #[test]
     fn test_parse_extra_columns() {
         let csv = b"date,amount,description,extra1,extra2\n2024-01-15,50.00,Groceries,foo,bar";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(result.errors.len(), 0);
     }
@@ -324,14 +330,14 @@ mod tests {
     fn test_parse_row_longer_than_header() {
         let csv =
             b"date,amount,description\n2024-01-15,50.00,Groceries,extra\n2024-01-16,25.50,Coffee";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 2);
     }
 
     #[test]
     fn test_parse_row_shorter_than_header() {
         let csv = b"date,amount,description\n2024-01-15,50.00";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(result.transactions[0].description, "");
     }
@@ -341,7 +347,7 @@ mod tests {
     #[test]
     fn test_parse_quoted_field_with_commas() {
         let csv = b"date,amount,description\n2024-01-15,50.00,\"Coffee, tea, and snacks\"";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(
             result.transactions[0].description,
@@ -352,14 +358,14 @@ mod tests {
     #[test]
     fn test_parse_quoted_field_with_escaped_quotes() {
         let csv = b"date,amount,description\n2024-01-15,50.00,\"The \"\"best\"\" coffee\"";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions[0].description, "The \"best\" coffee");
     }
 
     #[test]
     fn test_parse_quoted_field_with_newlines() {
         let csv = b"date,amount,description\n2024-01-15,50.00,\"Multi\nline\ndescription\"";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(
             result.transactions[0].description,
@@ -373,7 +379,7 @@ mod tests {
     fn test_parse_unicode_descriptions() {
         let csv =
             "date,amount,description\n2024-01-15,50.00,Café résumé\n2024-01-16,25.00,日本語テスト";
-        let result = parse_csv(csv.as_bytes()).unwrap();
+        let result = parse_csv(csv.as_bytes(), "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 2);
         assert_eq!(result.transactions[0].description, "Café résumé");
         assert_eq!(result.transactions[1].description, "日本語テスト");
@@ -382,14 +388,14 @@ mod tests {
     #[test]
     fn test_parse_unicode_emoji() {
         let csv = "date,amount,description\n2024-01-15,50.00,Coffee ☕";
-        let result = parse_csv(csv.as_bytes()).unwrap();
+        let result = parse_csv(csv.as_bytes(), "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions[0].description, "Coffee ☕");
     }
 
     #[test]
     fn test_parse_invalid_utf8() {
         let csv: &[u8] = &[0xFF, 0xFE, b',', b'a'];
-        let result = parse_csv(csv);
+        let result = parse_csv(csv, "en-US", DateFormat::Iso);
         assert!(result.is_err());
     }
 
@@ -398,7 +404,7 @@ mod tests {
     #[test]
     fn test_parse_empty_date_in_row() {
         let csv = b"date,amount,description\n,50.00,Groceries";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 0);
         assert_eq!(result.errors.len(), 1);
     }
@@ -406,7 +412,7 @@ mod tests {
     #[test]
     fn test_parse_empty_amount_in_row() {
         let csv = b"date,amount,description\n2024-01-15,,Groceries";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 0);
         assert_eq!(result.errors.len(), 1);
     }
@@ -414,71 +420,84 @@ mod tests {
     #[test]
     fn test_parse_invalid_amount_in_row() {
         let csv = b"date,amount,description\n2024-01-15,abc,Groceries";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 0);
         assert_eq!(result.errors.len(), 1);
     }
 
-    // --- Various date formats (parser passes them through) ---
+    // --- Various date formats (normalized to ISO) ---
 
     #[test]
     fn test_parse_various_date_formats() {
         let csv =
             b"date,amount,description\n01/15/2024,50.00,A\n15.01.2024,25.00,B\n2024-01-15,10.00,C";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 3);
-        assert_eq!(result.transactions[0].date, "01/15/2024");
-        assert_eq!(result.transactions[1].date, "15.01.2024");
-        assert_eq!(result.transactions[2].date, "2024-01-15");
+        assert!(result.transactions.iter().all(|t| t.date == "2024-01-15"));
+    }
+
+    #[test]
+    fn test_parse_dates_per_date_format() {
+        let csv = b"date,amount,description\n01/02/2024,50.00,A\n31/02/2024,25.00,B";
+        let result = parse_csv(csv, "en-US", DateFormat::DayMonthYear).unwrap();
+        assert_eq!(result.transactions.len(), 1);
+        assert_eq!(result.transactions[0].date, "2024-02-01");
+        assert_eq!(
+            result.errors,
+            ["Row 3: Invalid date \"31/02/2024\", expected DD.MM.YYYY"]
+        );
+
+        let result = parse_csv(csv, "en-US", DateFormat::MonthDayYear).unwrap();
+        assert_eq!(result.transactions[0].date, "2024-01-02");
     }
 
     // --- Negative amounts ---
 
     #[test]
-    fn test_clean_amount_negative_with_currency() {
-        assert_eq!(clean_amount("-$50.00"), "-50.00");
-        assert_eq!(clean_amount("-€1.234,56"), "-1234.56");
+    fn test_amount_negative_with_currency() {
+        assert_eq!(amount("-$50.00"), "-50.00");
+        assert_eq!(amount("-€1.234,56"), "-1234.56");
     }
 
     #[test]
-    fn test_clean_amount_negative_plain() {
-        assert_eq!(clean_amount("-50.00"), "-50.00");
-        assert_eq!(clean_amount("-1234"), "-1234");
+    fn test_amount_negative_plain() {
+        assert_eq!(amount("-50.00"), "-50.00");
+        assert_eq!(amount("-1234"), "-1234.00");
     }
 
     // --- European number formats ---
 
     #[test]
-    fn test_clean_amount_european_format() {
-        assert_eq!(clean_amount("1.234,56"), "1234.56");
-        assert_eq!(clean_amount("1.234.567,89"), "1234567.89");
+    fn test_amount_european_format() {
+        assert_eq!(amount("1.234,56"), "1234.56");
+        assert_eq!(amount("1.234.567,89"), "1234567.89");
     }
 
     #[test]
-    fn test_clean_amount_comma_only_decimal() {
-        assert_eq!(clean_amount("50,00"), "50.00");
+    fn test_amount_comma_only_decimal() {
+        assert_eq!(amount("50,00"), "50.00");
     }
 
     #[test]
     fn test_parse_european_amount_in_csv() {
         let csv = b"date,amount,description\n2024-01-15,\"1.234,56\",Test";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(result.transactions[0].amount, "1234.56");
     }
 
-    // --- clean_amount edge cases ---
+    // --- Amount edge cases ---
 
     #[test]
-    fn test_clean_amount_no_decimal() {
-        assert_eq!(clean_amount("100"), "100");
-        assert_eq!(clean_amount("$100"), "100");
+    fn test_amount_no_decimal() {
+        assert_eq!(amount("100"), "100.00");
+        assert_eq!(amount("$100"), "100.00");
     }
 
     #[test]
-    fn test_clean_amount_whitespace_and_symbols() {
-        assert_eq!(clean_amount("£ 99.99"), "99.99");
-        assert_eq!(clean_amount("CHF 1,250.00"), "1250.00");
+    fn test_amount_whitespace_and_symbols() {
+        assert_eq!(amount("£ 99.99"), "99.99");
+        assert_eq!(amount("CHF 1,250.00"), "1250.00");
     }
 
     // --- Optional columns ---
@@ -487,7 +506,7 @@ mod tests {
     fn test_parse_all_optional_columns() {
         let csv = b"date,amount,description,currency,category,tags,notes\n\
                      2024-01-15,50.00,Test,EUR,Food,\"groceries,weekly\",A note";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
         let t = &result.transactions[0];
         assert_eq!(t.currency, "EUR");
@@ -499,7 +518,7 @@ mod tests {
     #[test]
     fn test_parse_default_currency() {
         let csv = b"date,amount,description\n2024-01-15,50.00,Test";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions[0].currency, "USD");
     }
 
@@ -508,7 +527,7 @@ mod tests {
     #[test]
     fn test_parse_case_insensitive_headers() {
         let csv = b"Date,Amount,Description\n2024-01-15,50.00,Test";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
     }
 
@@ -517,7 +536,7 @@ mod tests {
     #[test]
     fn test_parse_whitespace_in_headers_and_values() {
         let csv = b"  date , amount , description  \n 2024-01-15 , 50.00 , Groceries ";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1);
         assert_eq!(result.transactions[0].date, "2024-01-15");
         assert_eq!(result.transactions[0].amount, "50.00");
@@ -529,7 +548,7 @@ mod tests {
     #[test]
     fn test_parse_row_numbers() {
         let csv = b"date,amount,description\n2024-01-15,50.00,First\n2024-01-16,25.00,Second";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions[0].row_number, 2);
         assert_eq!(result.transactions[1].row_number, 3);
     }
@@ -543,7 +562,7 @@ mod tests {
                      ,25.00,Missing date\n\
                      2024-01-17,abc,Bad amount\n\
                      2024-01-18,10.00,Also valid";
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 2);
         assert_eq!(result.errors.len(), 2);
         assert_eq!(result.transactions[0].description, "Valid");
@@ -558,7 +577,7 @@ mod tests {
         for i in 0..1000 {
             csv.push_str(&format!("2024-01-15,{}.00,Item {}\n", i, i));
         }
-        let result = parse_csv(csv.as_bytes()).unwrap();
+        let result = parse_csv(csv.as_bytes(), "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.transactions.len(), 1000);
         assert_eq!(result.errors.len(), 0);
     }
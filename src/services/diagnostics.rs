@@ -0,0 +1,391 @@
+//! Diagnostics bundle to attach to bug reports about failed fetches or
+//! imports.
+//!
+//! The bundle is a ZIP archive with the most recent API logs, the sessions
+//! of the transaction and trading imports with their first rows, the app
+//! version and migration level, the settings and the results of the
+//! database checks. Secrets in the settings are always redacted. Unless
+//! personal data is asked for, IBANs and the descriptions, payees and other
+//! free text of imported rows are redacted too, also where an error message
+//! quotes them.
+
+use std::collections::BTreeMap;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::db::migrations;
+use crate::db::queries::{api_logs, positions, settings};
+use crate::error::{AppError, AppResult};
+use crate::services::anonymize;
+
+/// Number of most recent API log entries in the bundle.
+pub const API_LOG_LIMIT: i64 = 200;
+/// Number of rows included per import session.
+pub const ROWS_PER_SESSION: i64 = 20;
+/// Length in characters the data of an import row is cut to.
+pub const MAX_ROW_DATA_LEN: usize = 500;
+
+pub const REDACTED: &str = "[redacted]";
+
+/// Fields of imported rows that hold personal data.
+const PERSONAL_FIELDS: &[&str] = &[
+    "description",
+    "payer",
+    "payee",
+    "notes",
+    "reference",
+    "counterparty_iban",
+    "creditor_id",
+    "mandate_reference",
+    "customer_reference",
+];
+
+/// Values shorter than this are not scrubbed from other text, so a payee
+/// like "A" does not blank out every "A" in the error messages.
+const MIN_KNOWN_VALUE_LEN: usize = 3;
+
+/// Setting keys containing one of these hold credentials.
+const SECRET_KEY_PARTS: &[&str] = &["password", "secret", "token"];
+
+static IBAN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[A-Z]{2}[0-9]{2}(?: ?[A-Z0-9]){11,30}\b").expect("valid IBAN pattern")
+});
+
+/// The import tables: (kind, session table, row table).
+const IMPORT_TABLES: &[(&str, &str, &str)] = &[
+    ("transactions", "import_sessions", "import_rows"),
+    ("trading", "trading_import_sessions", "trading_import_rows"),
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiagnosticsOptions {
+    /// Keep IBANs, payees and descriptions instead of redacting them.
+    pub include_personal: bool,
+}
+
+/// Scrubs personal data from the text that goes into the bundle.
+struct Redactor {
+    enabled: bool,
+    /// Personal values of the import rows, longest first.
+    known: Vec<String>,
+}
+
+impl Redactor {
+    fn new(conn: &Connection, options: &DiagnosticsOptions) -> AppResult<Self> {
+        let mut known = Vec::new();
+        if !options.include_personal {
+            for (_, _, rows_table) in IMPORT_TABLES {
+                let mut stmt = conn.prepare(&format!("SELECT data FROM {rows_table}"))?;
+                let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+                for data in rows {
+                    if let Ok(value) = serde_json::from_str::<Value>(&data?) {
+                        collect_personal(&value, &mut known);
+                    }
+                }
+            }
+        }
+        known.retain(|v| v.chars().count() >= MIN_KNOWN_VALUE_LEN);
+        known.sort_unstable_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        known.dedup();
+        Ok(Self {
+            enabled: !options.include_personal,
+            known,
+        })
+    }
+
+    fn text(&self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+        let mut text = IBAN.replace_all(text, REDACTED).into_owned();
+        for value in &self.known {
+            if text.contains(value.as_str()) {
+                text = text.replace(value.as_str(), REDACTED);
+            }
+        }
+        text
+    }
+
+    fn opt_text(&self, text: Option<&str>) -> Option<String> {
+        text.map(|t| self.text(t))
+    }
+
+    fn value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    if PERSONAL_FIELDS.contains(&key.as_str()) && !field.is_null() {
+                        *field = Value::String(REDACTED.into());
+                    } else {
+                        self.value(field);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.value(item)),
+            Value::String(s) => *s = self.text(s),
+            _ => {}
+        }
+    }
+
+    /// The data of an import row, redacted and cut short.
+    fn row_data(&self, data: &str) -> String {
+        let data = match serde_json::from_str::<Value>(data) {
+            Ok(mut value) if self.enabled => {
+                self.value(&mut value);
+                value.to_string()
+            }
+            Ok(_) => data.to_string(),
+            Err(_) => self.text(data),
+        };
+        truncate(&data, MAX_ROW_DATA_LEN)
+    }
+}
+
+fn collect_personal(value: &Value, known: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map {
+                match field {
+                    Value::String(s) if PERSONAL_FIELDS.contains(&key.as_str()) => {
+                        known.push(s.trim().to_string())
+                    }
+                    _ => collect_personal(field, known),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_personal(item, known)),
+        _ => {}
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn is_secret_setting(key: &str) -> bool {
+    anonymize::CLEARED_SETTINGS.contains(&key) || SECRET_KEY_PARTS.iter().any(|p| key.contains(p))
+}
+
+fn summary(conn: &Connection, options: &DiagnosticsOptions) -> AppResult<Value> {
+    let (migration_count, latest_migration): (i64, Option<String>) =
+        conn.query_row("SELECT COUNT(*), MAX(name) FROM _migrations", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    Ok(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "generated_at": chrono::Utc::now().to_rfc3339(),
+        "migrations_applied": migration_count,
+        "latest_migration": latest_migration,
+        "personal_data_included": options.include_personal,
+    }))
+}
+
+fn api_log_entries(conn: &Connection, redactor: &Redactor) -> AppResult<Value> {
+    let logs: Vec<Value> = api_logs::get_all_logs(conn, API_LOG_LIMIT)?
+        .into_iter()
+        .map(|log| {
+            json!({
+                "id": log.id,
+                "api_name": log.api_name,
+                "action": log.action,
+                "symbol": log.symbol,
+                "request_params": redactor.text(&log.request_params),
+                "status": log.status,
+                "response_summary": redactor.opt_text(log.response_summary.as_deref()),
+                "response_details": redactor.opt_text(log.response_details.as_deref()),
+                "duration_ms": log.duration_ms,
+                "created_at": log.created_at,
+                "retried_from": log.retried_from,
+            })
+        })
+        .collect();
+    Ok(Value::Array(logs))
+}
+
+fn import_sessions(conn: &Connection, redactor: &Redactor) -> AppResult<Value> {
+    let mut sessions = Vec::new();
+    for (kind, sessions_table, rows_table) in IMPORT_TABLES {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, status, total_rows, processed_rows, error_count, errors,
+                    created_at, updated_at
+             FROM {sessions_table} ORDER BY created_at DESC"
+        ))?;
+        let rows: Vec<(String, Value)> = stmt
+            .query_map([], |row| {
+                let errors: Option<String> = row.get(5)?;
+                let errors: Vec<String> = errors
+                    .and_then(|e| serde_json::from_str(&e).ok())
+                    .unwrap_or_default();
+                Ok((
+                    row.get::<_, String>(0)?,
+                    json!({
+                        "kind": kind,
+                        "id": row.get::<_, String>(0)?,
+                        "status": row.get::<_, String>(1)?,
+                        "total_rows": row.get::<_, i64>(2)?,
+                        "processed_rows": row.get::<_, i64>(3)?,
+                        "error_count": row.get::<_, i64>(4)?,
+                        "errors": errors.iter().map(|e| redactor.text(e)).collect::<Vec<_>>(),
+                        "created_at": row.get::<_, String>(6)?,
+                        "updated_at": row.get::<_, String>(7)?,
+                    }),
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let mut row_stmt = conn.prepare(&format!(
+            "SELECT row_index, status, error, data FROM {rows_table}
+             WHERE session_id = ? ORDER BY row_index LIMIT ?"
+        ))?;
+        for (id, mut session) in rows {
+            let session_rows: Vec<Value> = row_stmt
+                .query_map(rusqlite::params![id, ROWS_PER_SESSION], |row| {
+                    Ok(json!({
+                        "row_index": row.get::<_, i64>(0)?,
+                        "status": row.get::<_, String>(1)?,
+                        "error": redactor.opt_text(row.get::<_, Option<String>>(2)?.as_deref()),
+                        "data": redactor.row_data(&row.get::<_, String>(3)?),
+                    }))
+                })?
+                .collect::<Result<_, _>>()?;
+            session["rows"] = Value::Array(session_rows);
+            sessions.push(session);
+        }
+    }
+    Ok(Value::Array(sessions))
+}
+
+fn redacted_settings(conn: &Connection) -> AppResult<Value> {
+    let settings: BTreeMap<String, String> = settings::get_all_settings(conn)?
+        .into_iter()
+        .map(|(key, value)| {
+            let value = if is_secret_setting(&key) && !value.is_empty() {
+                REDACTED.to_string()
+            } else {
+                value
+            };
+            (key, value)
+        })
+        .collect();
+    Ok(json!(settings))
+}
+
+fn checks(conn: &Connection, migrations_dir: &Path) -> AppResult<Value> {
+    let integrity: Vec<String> = conn
+        .prepare("PRAGMA integrity_check")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    let foreign_keys: Vec<Value> = conn
+        .prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| {
+            Ok(json!({
+                "table": row.get::<_, String>(0)?,
+                "rowid": row.get::<_, Option<i64>>(1)?,
+                "parent": row.get::<_, String>(2)?,
+            }))
+        })?
+        .collect::<Result<_, _>>()?;
+    let migrations: Vec<Value> = migrations::migration_status(conn, migrations_dir)?
+        .into_iter()
+        .map(|m| {
+            json!({
+                "name": m.name,
+                "applied_at": m.applied_at,
+                "modified": m.modified,
+                "missing": m.missing,
+            })
+        })
+        .collect();
+    let allow_short = settings::get_settings(conn)?.allow_short_positions;
+    let (_, oversold) = positions::get_positions_with_warnings(conn, allow_short)?;
+    let oversold: Vec<Value> = oversold
+        .into_iter()
+        .map(|w| json!({"symbol": w.symbol, "date": w.date, "excess_quantity": w.excess_quantity}))
+        .collect();
+    Ok(json!({
+        "integrity_check": integrity,
+        "foreign_key_check": foreign_keys,
+        "migrations": migrations,
+        "oversold": oversold,
+    }))
+}
+
+/// Build the diagnostics bundle as ZIP archive bytes.
+pub fn build_bundle(
+    conn: &Connection,
+    migrations_dir: &Path,
+    options: &DiagnosticsOptions,
+) -> AppResult<Vec<u8>> {
+    let redactor = Redactor::new(conn, options)?;
+    let files = [
+        ("summary.json", summary(conn, options)?),
+        ("api_logs.json", api_log_entries(conn, &redactor)?),
+        ("import_sessions.json", import_sessions(conn, &redactor)?),
+        ("settings.json", redacted_settings(conn)?),
+        ("checks.json", checks(conn, migrations_dir)?),
+    ];
+
+    let zip_error = |e: zip::result::ZipError| AppError::Internal(format!("ZIP error: {e}"));
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        let json = serde_json::to_vec_pretty(&content)
+            .map_err(|e| AppError::Internal(format!("JSON error: {e}")))?;
+        zip.start_file(name, SimpleFileOptions::default())
+            .map_err(zip_error)?;
+        zip.write_all(&json)?;
+    }
+    Ok(zip.finish().map_err(zip_error)?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(known: &[&str]) -> Redactor {
+        Redactor {
+            enabled: true,
+            known: known.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_redacts_personal_fields_and_ibans() {
+        let r = redactor(&["Corner Bakery"]);
+        let data = r#"{"date":"2024-01-02","amount":"-3.50","description":"Corner Bakery",
+            "payee":null,"notes":"paid from DE89 3704 0044 0532 0130 00","tags":["x"]}"#;
+        let out = r.row_data(data);
+        assert!(!out.contains("Bakery"));
+        assert!(!out.contains("DE89"));
+        assert!(out.contains("\"payee\":null"));
+        assert!(out.contains("-3.50"));
+
+        assert_eq!(
+            r.text("Row 3: duplicate of Corner Bakery to GB29NWBK60161331926819"),
+            "Row 3: duplicate of [redacted] to [redacted]"
+        );
+        assert_eq!(r.text("EURUSD=X 2024-01-02"), "EURUSD=X 2024-01-02");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abc", 5), "abc");
+        assert_eq!(truncate("äbcdef", 3), "äbc…");
+    }
+
+    #[test]
+    fn test_secret_settings() {
+        assert!(is_secret_setting("notify_smtp_password"));
+        assert!(is_secret_setting("notify_webhook_url"));
+        assert!(!is_secret_setting("currency"));
+    }
+}
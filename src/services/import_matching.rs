@@ -0,0 +1,211 @@
+//! Suggestions for freshly parsed import rows: categories and tags from the
+//! rules, and pending transactions the rows may book.
+
+use regex::RegexBuilder;
+use tracing::{info, warn};
+
+use crate::date_utils::parse_iso_date;
+use crate::db::queries::{import, rules, tags, transactions};
+use crate::models::{RuleActionType, RuleMatchField, TransactionStatus};
+use crate::services::money;
+
+/// Assign the pending rows of `session_id` the category of the first rule
+/// that matches them and the tags of all matching rules. Failures are logged
+/// and leave the rows unchanged.
+pub fn apply_rules_to_import_rows(conn: &rusqlite::Connection, session_id: &str) {
+    let all_rules = match rules::list_rules(conn) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(session_id = %session_id, error = %e, "Failed to load rules for import");
+            return;
+        }
+    };
+
+    if all_rules.is_empty() {
+        return;
+    }
+
+    struct CompiledRule {
+        id: i64,
+        regex: regex::Regex,
+        match_field: RuleMatchField,
+        action_type: RuleActionType,
+        category_id: Option<i64>,
+        tag_name: Option<String>,
+    }
+
+    let compiled: Vec<CompiledRule> = all_rules
+        .iter()
+        .filter_map(|rule| {
+            let regex = RegexBuilder::new(&rule.pattern)
+                .case_insensitive(true)
+                .build()
+                .ok()?;
+
+            match rule.action_type {
+                RuleActionType::AssignCategory => {
+                    let cat_id: i64 = rule.action_value.parse().ok()?;
+                    Some(CompiledRule {
+                        id: rule.id,
+                        regex,
+                        match_field: rule.match_field,
+                        action_type: rule.action_type,
+                        category_id: Some(cat_id),
+                        tag_name: None,
+                    })
+                }
+                RuleActionType::AssignTag => {
+                    let tag_id: i64 = rule.action_value.parse().ok()?;
+                    let tag = tags::get_tag(conn, tag_id).ok()??;
+                    Some(CompiledRule {
+                        id: rule.id,
+                        regex,
+                        match_field: rule.match_field,
+                        action_type: rule.action_type,
+                        category_id: None,
+                        tag_name: Some(tag.name),
+                    })
+                }
+            }
+        })
+        .collect();
+
+    if compiled.is_empty() {
+        return;
+    }
+
+    let rows = match import::get_pending_rows(conn, session_id) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(session_id = %session_id, error = %e, "Failed to load rows for rule application");
+            return;
+        }
+    };
+
+    let mut affected = 0u64;
+
+    for row in &rows {
+        let mut matched_category: Option<(i64, i64)> = None;
+        let mut extra_tags: Vec<String> = Vec::new();
+        let mut rule_ids: Vec<i64> = Vec::new();
+
+        for cr in &compiled {
+            let data = &row.data;
+            if !cr.match_field.is_match(
+                &cr.regex,
+                &data.description,
+                data.payer.as_deref(),
+                data.payee.as_deref(),
+                data.counterparty_iban.as_deref(),
+            ) {
+                continue;
+            }
+            match cr.action_type {
+                RuleActionType::AssignCategory => {
+                    if let (None, Some(cat_id)) = (matched_category, cr.category_id) {
+                        matched_category = Some((cat_id, cr.id));
+                        rule_ids.push(cr.id);
+                    }
+                }
+                RuleActionType::AssignTag => {
+                    if let Some(ref name) = cr.tag_name {
+                        if !row.data.tags.contains(name) && !extra_tags.contains(name) {
+                            extra_tags.push(name.clone());
+                            rule_ids.push(cr.id);
+                        }
+                    }
+                }
+            }
+        }
+
+        let has_changes = matched_category.is_some() || !extra_tags.is_empty();
+        if !has_changes {
+            continue;
+        }
+        affected += 1;
+
+        if let Some((cat_id, rule_id)) = matched_category {
+            let _ = import::set_row_rule_category(conn, row.id, cat_id, rule_id);
+        }
+
+        if !extra_tags.is_empty() {
+            let mut data = row.data.clone();
+            data.tags.extend(extra_tags);
+            let _ = import::update_row_data(conn, row.id, &data);
+        }
+        let _ = import::update_row_rules(conn, row.id, &rule_ids);
+    }
+
+    info!(
+        session_id = %session_id,
+        rules_compiled = compiled.len(),
+        rows_affected = affected,
+        "Applied rules to import rows"
+    );
+}
+
+/// How many days an imported row's date may differ from a pending
+/// transaction it books.
+const PENDING_MATCH_DAYS: i64 = 7;
+
+/// Offer to merge import rows into pending transactions with the same amount
+/// and currency, a compatible account and a date close by. Each pending
+/// transaction is matched at most once, to the row with the closest date.
+pub fn match_pending_transactions(conn: &rusqlite::Connection, session_id: &str) {
+    let filter = transactions::TransactionFilter {
+        status: Some(TransactionStatus::Pending),
+        with_tags: false,
+        ..Default::default()
+    };
+    let (mut pending, rows) = match (
+        transactions::list_transactions(conn, &filter),
+        import::get_pending_rows(conn, session_id),
+    ) {
+        (Ok(pending), Ok(rows)) => (pending, rows),
+        (Err(e), _) => {
+            warn!(session_id = %session_id, error = %e, "Failed to load pending transactions");
+            return;
+        }
+        (_, Err(e)) => {
+            warn!(session_id = %session_id, error = %e, "Failed to load rows for pending matching");
+            return;
+        }
+    };
+    if pending.is_empty() {
+        return;
+    }
+
+    let mut matched = 0u64;
+    for row in &rows {
+        let Ok(amount_cents) = money::parse_amount(&row.data.amount, money::INPUT_LOCALE) else {
+            continue;
+        };
+        let Some(row_date) = parse_iso_date(&row.data.date) else {
+            continue;
+        };
+        let best = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| {
+                t.amount_cents == amount_cents
+                    && t.currency == row.data.currency
+                    && (t.account_id.is_none()
+                        || row.data.account_id.is_none()
+                        || t.account_id == row.data.account_id)
+            })
+            .filter_map(|(i, t)| {
+                let days = (parse_iso_date(&t.date)? - row_date).num_days().abs();
+                (days <= PENDING_MATCH_DAYS).then_some((i, days))
+            })
+            .min_by_key(|&(_, days)| days);
+
+        if let Some((index, _)) = best {
+            let transaction = pending.swap_remove(index);
+            if import::set_row_pending_match(conn, row.id, Some(transaction.id)).is_ok() {
+                matched += 1;
+            }
+        }
+    }
+
+    info!(session_id = %session_id, rows_matched = matched, "Matched import rows to pending transactions");
+}
@@ -0,0 +1,128 @@
+//! Removal of rows that appear in more than one file of an import.
+//!
+//! Bank exports covering consecutive periods often share the rows at their
+//! boundaries. A row is a duplicate when an earlier file of the same upload
+//! already contained it as often as the current file has seen it so far, so
+//! repeated identical rows within a single file (two coffees of the same
+//! price on the same day) are all kept.
+
+use std::collections::HashMap;
+
+use crate::services::csv_parser::ParsedTransaction;
+use crate::services::trading_csv_parser::ParsedTradingActivity;
+
+/// Fields that identify a row across files.
+pub trait OverlapKey {
+    fn overlap_key(&self) -> String;
+}
+
+impl OverlapKey for ParsedTransaction {
+    fn overlap_key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}",
+            self.date,
+            self.amount.trim(),
+            self.currency,
+            self.description.trim()
+        )
+    }
+}
+
+impl OverlapKey for ParsedTradingActivity {
+    fn overlap_key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.date,
+            self.symbol,
+            self.activity_type,
+            self.quantity.as_deref().unwrap_or("").trim(),
+            self.unit_price.as_deref().unwrap_or("").trim()
+        )
+    }
+}
+
+/// Tracks how often each row occurred in the files seen so far.
+#[derive(Debug, Default)]
+pub struct OverlapFilter {
+    /// Highest number of occurrences of a key in any finished file.
+    previous: HashMap<String, usize>,
+    /// Occurrences of a key in the current file.
+    current: HashMap<String, usize>,
+}
+
+impl OverlapFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start counting the rows of the next file.
+    pub fn start_file(&mut self) {
+        for (key, count) in self.current.drain() {
+            let previous = self.previous.entry(key).or_insert(0);
+            *previous = (*previous).max(count);
+        }
+    }
+
+    /// Whether `row` of the current file was already part of an earlier file.
+    pub fn is_duplicate(&mut self, row: &impl OverlapKey) -> bool {
+        let key = row.overlap_key();
+        let seen = self.current.entry(key.clone()).or_insert(0);
+        *seen += 1;
+        *seen <= self.previous.get(&key).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(date: &str, amount: &str, description: &str) -> ParsedTransaction {
+        ParsedTransaction {
+            date: date.into(),
+            amount: amount.into(),
+            currency: "EUR".into(),
+            description: description.into(),
+            category: None,
+            account_id: None,
+            tags: vec![],
+            notes: None,
+            value_date: None,
+            payer: None,
+            payee: None,
+            reference: None,
+            transaction_type: None,
+            counterparty_iban: None,
+            creditor_id: None,
+            mandate_reference: None,
+            customer_reference: None,
+            row_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_keeps_repeated_rows_within_a_file() {
+        let mut filter = OverlapFilter::new();
+        filter.start_file();
+        let coffee = transaction("2024-03-31", "-3.50", "Coffee");
+        assert!(!filter.is_duplicate(&coffee));
+        assert!(!filter.is_duplicate(&coffee));
+    }
+
+    #[test]
+    fn test_drops_rows_of_earlier_files() {
+        let mut filter = OverlapFilter::new();
+        let coffee = transaction("2024-03-31", "-3.50", "Coffee");
+        let rent = transaction("2024-04-01", "-900.00", "Rent");
+
+        filter.start_file();
+        assert!(!filter.is_duplicate(&coffee));
+        assert!(!filter.is_duplicate(&coffee));
+
+        filter.start_file();
+        assert!(filter.is_duplicate(&coffee));
+        assert!(filter.is_duplicate(&coffee));
+        // A third coffee on that day was not in the first file
+        assert!(!filter.is_duplicate(&coffee));
+        assert!(!filter.is_duplicate(&rent));
+    }
+}
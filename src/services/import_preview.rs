@@ -0,0 +1,169 @@
+//! Spending impact of an import before it is confirmed.
+//!
+//! For every month and category the pending expense rows of a session fall
+//! into, the projected total adds the rows to what was already spent in that
+//! month. A category is flagged when the projection exceeds 150% of its
+//! average spending over the preceding three months.
+
+use chrono::{Datelike, Months, NaiveDate};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::db::queries::{categories, import, transaction_sums};
+use crate::error::AppResult;
+use crate::models::ImportRow;
+use crate::services::{money, rule_suggestion};
+
+/// Number of months before the import month that make up the average.
+pub const TRAILING_MONTHS: u32 = 3;
+
+/// Projected totals above this percentage of the average are flagged.
+pub const OVER_AVERAGE_PERCENT: i64 = 150;
+
+/// Spending of one category in one month (`YYYY-MM`) once the import is
+/// confirmed. All amounts are positive spending in cents.
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryImpact {
+    pub month: String,
+    pub category_id: i64,
+    pub category_name: String,
+    pub existing_cents: i64,
+    pub import_cents: i64,
+    pub projected_cents: i64,
+    pub trailing_average_cents: i64,
+    pub over_average: bool,
+}
+
+/// Pending rows of an import that share a payee (see [`ImportRow::payee_key`]).
+#[derive(Debug, Clone, Serialize)]
+pub struct PayeeGroup {
+    pub key: String,
+    /// Payee as written in the first row of the group
+    pub name: String,
+    pub row_count: usize,
+    /// Category of the rows, if they all have the same one
+    pub category_id: Option<i64>,
+}
+
+/// Group rows by payee, largest groups first.
+pub fn payee_groups(rows: &[ImportRow]) -> Vec<PayeeGroup> {
+    let mut groups: Vec<PayeeGroup> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut mixed: HashSet<usize> = HashSet::new();
+    for row in rows {
+        let key = row.payee_key();
+        match index.get(&key) {
+            Some(&i) => {
+                let group = &mut groups[i];
+                group.row_count += 1;
+                if group.category_id != row.category_id {
+                    mixed.insert(i);
+                }
+            }
+            None => {
+                index.insert(key.clone(), groups.len());
+                groups.push(PayeeGroup {
+                    name: rule_suggestion::counterparty_name(row.payee_or_description()),
+                    key,
+                    row_count: 1,
+                    category_id: row.category_id,
+                });
+            }
+        }
+    }
+    for i in mixed {
+        groups[i].category_id = None;
+    }
+    groups.sort_by(|a, b| b.row_count.cmp(&a.row_count).then(a.key.cmp(&b.key)));
+    groups
+}
+
+/// Projected spending per month and category for the pending rows of an
+/// import session. Only categorized expense rows outside the Transfers
+/// subtree are considered.
+pub fn category_impact(conn: &Connection, session_id: &str) -> AppResult<Vec<CategoryImpact>> {
+    let all_categories = categories::list_categories(conn)?;
+    let excluded = categories::transfers_excluded_ids(&all_categories);
+    let names: HashMap<i64, &str> = all_categories
+        .iter()
+        .map(|c| (c.id, c.name.as_str()))
+        .collect();
+
+    let imported = import_spending(&import::get_pending_rows(conn, session_id)?, &excluded);
+    let months: BTreeSet<NaiveDate> = imported.keys().map(|&(month, _)| month).collect();
+    let excluded: Vec<i64> = excluded.into_iter().collect();
+
+    let mut existing = HashMap::new();
+    let mut trailing = HashMap::new();
+    for &month in &months {
+        let next = month + Months::new(1);
+        let start = month - Months::new(TRAILING_MONTHS);
+        existing.insert(month, spent_by_category(conn, month, next, &excluded)?);
+        trailing.insert(month, spent_by_category(conn, start, month, &excluded)?);
+    }
+
+    let impact = imported
+        .into_iter()
+        .map(|((month, category_id), import_cents)| {
+            let existing_cents = existing[&month].get(&category_id).copied().unwrap_or(0);
+            let trailing_cents = trailing[&month].get(&category_id).copied().unwrap_or(0);
+            let trailing_average_cents = trailing_cents / TRAILING_MONTHS as i64;
+            let projected_cents = existing_cents + import_cents;
+            CategoryImpact {
+                month: month.format("%Y-%m").to_string(),
+                category_id,
+                category_name: names.get(&category_id).copied().unwrap_or("").to_string(),
+                existing_cents,
+                import_cents,
+                projected_cents,
+                trailing_average_cents,
+                over_average: trailing_average_cents > 0
+                    && projected_cents * 100 > trailing_average_cents * OVER_AVERAGE_PERCENT,
+            }
+        })
+        .collect();
+    Ok(impact)
+}
+
+/// Sum the expense rows per (first day of month, category).
+fn import_spending(rows: &[ImportRow], excluded: &HashSet<i64>) -> BTreeMap<(NaiveDate, i64), i64> {
+    let mut totals = BTreeMap::new();
+    for row in rows {
+        let Some(category_id) = row.category_id.filter(|id| !excluded.contains(id)) else {
+            continue;
+        };
+        let Ok(amount_cents) = money::parse_amount(&row.data.amount, money::INPUT_LOCALE) else {
+            continue;
+        };
+        let Ok(date) = NaiveDate::parse_from_str(&row.data.date, "%Y-%m-%d") else {
+            continue;
+        };
+        if amount_cents >= 0 {
+            continue;
+        }
+        let month = date.with_day(1).unwrap_or(date);
+        *totals.entry((month, category_id)).or_insert(0) -= amount_cents;
+    }
+    totals
+}
+
+/// Net spending per category from `from` up to but excluding `until`.
+fn spent_by_category(
+    conn: &Connection,
+    from: NaiveDate,
+    until: NaiveDate,
+    excluded: &[i64],
+) -> AppResult<HashMap<i64, i64>> {
+    let to = until.pred_opt().unwrap_or(until);
+    let sums = transaction_sums::sum_by_category(
+        conn,
+        Some(&from.to_string()),
+        Some(&to.to_string()),
+        excluded,
+    )?;
+    Ok(sums
+        .into_iter()
+        .filter_map(|s| Some((s.category_id?, (-s.total_cents).max(0))))
+        .collect())
+}
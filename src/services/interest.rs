@@ -0,0 +1,243 @@
+//! Projected interest income for cash accounts.
+//!
+//! Interest accrues every month on the balance as of the last credit and is
+//! added to the balance at the end of each compounding period (calendar
+//! quarters and years for quarterly and yearly compounding). Projected
+//! amounts are reported per month as accrued, so for quarterly or yearly
+//! accounts the actual credit shows up as one larger amount at period end.
+
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::queries::balances;
+use crate::error::AppResult;
+use crate::models::account::{Account, InterestCompounding};
+
+/// Balance the projection starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterestBasis {
+    /// The account balance today
+    Current,
+    /// Average daily balance over the last compounding period
+    Average,
+}
+
+impl InterestBasis {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "current" => Some(Self::Current),
+            "average" => Some(Self::Average),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Current => "current",
+            Self::Average => "average",
+        }
+    }
+}
+
+/// Projected and booked interest for one calendar month (`YYYY-MM`).
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthlyInterest {
+    pub month: String,
+    pub projected_cents: i64,
+    /// Interest transactions booked to the account in this month.
+    pub actual_cents: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InterestProjection {
+    pub account_id: i64,
+    pub interest_rate_bps: i64,
+    pub compounding: InterestCompounding,
+    pub basis: &'static str,
+    pub balance_cents: i64,
+    pub total_projected_cents: i64,
+    pub months: Vec<MonthlyInterest>,
+}
+
+/// Interest accrued in each of `months` calendar months starting with
+/// `start`'s month. Amounts are rounded so that they add up to the rounded
+/// total.
+pub fn project_monthly_interest(
+    balance_cents: i64,
+    rate_bps: i64,
+    compounding: InterestCompounding,
+    start: NaiveDate,
+    months: u32,
+) -> Vec<i64> {
+    let monthly_rate = rate_bps as f64 / 10_000.0 / 12.0;
+    let mut balance = balance_cents as f64;
+    let mut pending = 0.0_f64;
+    let mut cumulative = 0.0_f64;
+    let mut result = Vec::with_capacity(months as usize);
+
+    for offset in 0..months {
+        let month = start.month0() + offset;
+        let interest = balance * monthly_rate;
+        let before = cumulative.round() as i64;
+        cumulative += interest;
+        pending += interest;
+        result.push(cumulative.round() as i64 - before);
+
+        // Credit accrued interest at the end of each compounding period
+        if (month % 12 + 1).is_multiple_of(compounding.months()) {
+            balance += pending;
+            pending = 0.0;
+        }
+    }
+    result
+}
+
+/// Average end-of-day balance over `from..=to`, given today's balance and
+/// the account's daily transaction sums after `from`, newest first.
+pub fn average_daily_balance(
+    current_cents: i64,
+    daily_sums_desc: &[(String, i64)],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> i64 {
+    let days = (to - from).num_days() + 1;
+    if days <= 0 {
+        return current_cents;
+    }
+    let mut balance = current_cents;
+    let mut sums = daily_sums_desc.iter().peekable();
+    let mut total: i128 = 0;
+    let mut day = to;
+    while day >= from {
+        let key = day.format("%Y-%m-%d").to_string();
+        while let Some((_, amount)) = sums.next_if(|(date, _)| *date > key) {
+            balance -= amount;
+        }
+        total += balance as i128;
+        day -= Duration::days(1);
+    }
+    (total / days as i128) as i64
+}
+
+/// Project interest for an account with an interest rate over the next
+/// `months` months, starting with the month of `today`.
+pub fn project_account(
+    conn: &Connection,
+    account: &Account,
+    rate_bps: i64,
+    basis: InterestBasis,
+    months: u32,
+    today: NaiveDate,
+) -> AppResult<InterestProjection> {
+    let current = balances::get_account_balance(conn, account.id)?;
+    let balance_cents = match basis {
+        InterestBasis::Current => current,
+        InterestBasis::Average => {
+            let from = today
+                .checked_sub_months(Months::new(account.interest_compounding.months()))
+                .unwrap_or(today)
+                + Duration::days(1);
+            let sums = balances::get_account_daily_sums(
+                conn,
+                account.id,
+                &from.format("%Y-%m-%d").to_string(),
+            )?;
+            average_daily_balance(current, &sums, from, today)
+        }
+    };
+
+    let start = today.with_day(1).unwrap_or(today);
+    let projected = project_monthly_interest(
+        balance_cents,
+        rate_bps,
+        account.interest_compounding,
+        start,
+        months,
+    );
+    let actual =
+        balances::get_monthly_interest(conn, account.id, &start.format("%Y-%m-%d").to_string())?;
+
+    let months = projected
+        .iter()
+        .zip(0..)
+        .map(|(&projected_cents, offset)| {
+            let month = start
+                .checked_add_months(Months::new(offset))
+                .unwrap_or(start)
+                .format("%Y-%m")
+                .to_string();
+            MonthlyInterest {
+                actual_cents: actual.get(&month).copied().unwrap_or(0),
+                month,
+                projected_cents,
+            }
+        })
+        .collect();
+
+    Ok(InterestProjection {
+        account_id: account.id,
+        interest_rate_bps: rate_bps,
+        compounding: account.interest_compounding,
+        basis: basis.as_str(),
+        balance_cents,
+        total_projected_cents: projected.iter().sum(),
+        months,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_monthly_compounding() {
+        // 1,000.00 at 12% a year: 10.00, then 10.10 on the grown balance
+        let months = project_monthly_interest(
+            100_000,
+            1200,
+            InterestCompounding::Monthly,
+            date("2024-01-01"),
+            3,
+        );
+        assert_eq!(months, vec![1000, 1010, 1020]);
+    }
+
+    #[test]
+    fn test_quarterly_compounding_credits_at_quarter_end() {
+        // Starting in February, the first credit is at the end of March
+        let months = project_monthly_interest(
+            100_000,
+            1200,
+            InterestCompounding::Quarterly,
+            date("2024-02-01"),
+            3,
+        );
+        assert_eq!(months, vec![1000, 1000, 1020]);
+    }
+
+    #[test]
+    fn test_rounding_sums_to_total() {
+        let months = project_monthly_interest(
+            12_345,
+            333,
+            InterestCompounding::Yearly,
+            date("2024-01-01"),
+            12,
+        );
+        let exact: f64 = 12_345.0 * 0.0333;
+        assert_eq!(months.iter().sum::<i64>(), exact.round() as i64);
+    }
+
+    #[test]
+    fn test_average_daily_balance() {
+        // Balance 100 today after a +50 deposit two days ago: 50, 100, 100 -> ~83.33
+        let sums = vec![("2024-03-09".to_string(), 5_000)];
+        let avg = average_daily_balance(10_000, &sums, date("2024-03-08"), date("2024-03-10"));
+        assert_eq!(avg, 8_333);
+    }
+}
@@ -1,13 +1,50 @@
 use crate::error::{AppError, AppResult};
 use crate::models::NewMarketData;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use time::{Date, Month, OffsetDateTime, Time};
 use tokio::time::sleep;
 use tracing::{debug, info, warn};
 use yahoo_finance_api as yahoo;
 
-/// Delay between API requests to avoid rate limiting
-const API_DELAY_MS: u64 = 500;
+/// Minimum interval between symbol searches from the same session
+pub const SEARCH_MIN_INTERVAL_MS: u64 = 300;
+
+/// Per-session throttle for the symbol search proxy, so fast typing in the
+/// autocomplete doesn't hammer the Yahoo API.
+pub struct SearchThrottle {
+    /// Maps session key → time of the last search that hit the API.
+    last_search: Mutex<HashMap<String, Instant>>,
+    min_interval: Duration,
+}
+
+impl Default for SearchThrottle {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(SEARCH_MIN_INTERVAL_MS))
+    }
+}
+
+impl SearchThrottle {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            last_search: Mutex::new(HashMap::new()),
+            min_interval,
+        }
+    }
+
+    /// Returns `true` and records the search if the session may search now.
+    pub fn try_acquire(&self, session: &str) -> bool {
+        let mut map = self.last_search.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        map.retain(|_, last| now.duration_since(*last) < self.min_interval);
+        if map.contains_key(session) {
+            return false;
+        }
+        map.insert(session.to_string(), now);
+        true
+    }
+}
 
 /// Fetch historical quotes for a symbol within a date range
 /// Returns closing prices for each trading day
@@ -66,6 +103,7 @@ pub async fn fetch_historical_quotes(
                 symbol: symbol.to_string(),
                 date,
                 close_price_cents,
+                close_price: Some(quote.close),
                 currency: currency.clone(),
             })
         })
@@ -117,6 +155,7 @@ pub async fn fetch_latest_quote(symbol: &str) -> AppResult<Option<NewMarketData>
         symbol: symbol.to_string(),
         date,
         close_price_cents,
+        close_price: Some(quote.close),
         currency,
     }))
 }
@@ -131,23 +170,34 @@ pub struct SymbolMetadata {
     pub quote_type: String,
 }
 
-/// Fetch metadata for a symbol (name, exchange, type)
-pub async fn fetch_symbol_metadata(symbol: &str) -> AppResult<Option<SymbolMetadata>> {
-    debug!(symbol = %symbol, "Fetching symbol metadata");
+/// Search Yahoo Finance for tickers matching a query
+pub async fn search_symbols(query: &str) -> AppResult<Vec<SymbolMetadata>> {
+    debug!(query = %query, "Searching symbols");
 
     let provider = yahoo::YahooConnector::new()
         .map_err(|e| AppError::Internal(format!("Failed to create Yahoo connector: {}", e)))?;
 
     let response = provider
-        .search_ticker_opt(symbol)
+        .search_ticker_opt(query)
         .await
         .map_err(|e| AppError::Internal(format!("Yahoo Finance API error: {}", e)))?;
 
-    // Find exact match for the symbol
-    let quote = response
+    Ok(response
         .quotes
         .into_iter()
-        .find(|q| q.symbol.to_uppercase() == symbol.to_uppercase());
+        .map(|q| SymbolMetadata {
+            symbol: q.symbol,
+            short_name: q.short_name,
+            long_name: q.long_name,
+            exchange: q.exchange,
+            quote_type: q.quote_type,
+        })
+        .collect())
+}
+
+/// Fetch metadata for a symbol (name, exchange, type)
+pub async fn fetch_symbol_metadata(symbol: &str) -> AppResult<Option<SymbolMetadata>> {
+    let quote = find_exact_match(symbol, search_symbols(symbol).await?);
 
     if quote.is_some() {
         debug!(symbol = %symbol, "Found symbol metadata");
@@ -155,13 +205,25 @@ pub async fn fetch_symbol_metadata(symbol: &str) -> AppResult<Option<SymbolMetad
         debug!(symbol = %symbol, "Symbol metadata not found");
     }
 
-    Ok(quote.map(|q| SymbolMetadata {
-        symbol: q.symbol,
-        short_name: q.short_name,
-        long_name: q.long_name,
-        exchange: q.exchange,
-        quote_type: q.quote_type,
-    }))
+    Ok(quote)
+}
+
+/// Pick the search result whose ticker equals the symbol (case-insensitive)
+pub fn find_exact_match(symbol: &str, results: Vec<SymbolMetadata>) -> Option<SymbolMetadata> {
+    results
+        .into_iter()
+        .find(|q| q.symbol.to_uppercase() == symbol.to_uppercase())
+}
+
+/// Suggest a close match for a symbol that wasn't found.
+/// Prefers exchange-suffixed variants (e.g. "VWCE" -> "VWCE.DE"), then the first result.
+pub fn suggest_symbol(symbol: &str, results: &[SymbolMetadata]) -> Option<String> {
+    let prefix = format!("{}.", symbol.to_uppercase());
+    results
+        .iter()
+        .find(|q| q.symbol.to_uppercase().starts_with(&prefix))
+        .or_else(|| results.first())
+        .map(|q| q.symbol.clone())
 }
 
 /// Parse a date string in YYYY-MM-DD format
@@ -191,9 +253,10 @@ fn parse_date(date_str: &str) -> AppResult<Date> {
         .map_err(|e| AppError::Validation(format!("Invalid date: {}", e)))
 }
 
-/// Fetch quotes for multiple symbols with rate limiting
+/// Fetch quotes for multiple symbols, pausing `delay` between requests
 pub async fn fetch_quotes_for_symbols(
     symbols: &[(&str, &str, &str)], // (symbol, start_date, end_date)
+    delay: Duration,
 ) -> Vec<(String, AppResult<Vec<NewMarketData>>)> {
     info!(
         symbol_count = symbols.len(),
@@ -204,7 +267,7 @@ pub async fn fetch_quotes_for_symbols(
     for (i, (symbol, start_date, end_date)) in symbols.iter().enumerate() {
         // Add delay between requests (except for the first one)
         if i > 0 {
-            sleep(Duration::from_millis(API_DELAY_MS)).await;
+            sleep(delay).await;
         }
 
         let result = fetch_historical_quotes(symbol, start_date, end_date).await;
@@ -214,9 +277,10 @@ pub async fn fetch_quotes_for_symbols(
     results
 }
 
-/// Fetch latest quotes for multiple symbols with rate limiting
+/// Fetch latest quotes for multiple symbols, pausing `delay` between requests
 pub async fn fetch_latest_quotes_for_symbols(
     symbols: &[&str],
+    delay: Duration,
 ) -> Vec<(String, AppResult<Option<NewMarketData>>)> {
     info!(
         symbol_count = symbols.len(),
@@ -227,7 +291,7 @@ pub async fn fetch_latest_quotes_for_symbols(
     for (i, symbol) in symbols.iter().enumerate() {
         // Add delay between requests (except for the first one)
         if i > 0 {
-            sleep(Duration::from_millis(API_DELAY_MS)).await;
+            sleep(delay).await;
         }
 
         let result = fetch_latest_quote(symbol).await;
@@ -236,3 +300,58 @@ pub async fn fetch_latest_quotes_for_symbols(
 
     results
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(symbol: &str) -> SymbolMetadata {
+        SymbolMetadata {
+            symbol: symbol.to_string(),
+            short_name: None,
+            long_name: None,
+            exchange: "GER".to_string(),
+            quote_type: "ETF".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_search_throttle_is_per_session() {
+        let throttle = SearchThrottle::new(Duration::from_secs(60));
+        assert!(throttle.try_acquire("a"));
+        assert!(!throttle.try_acquire("a"));
+        assert!(throttle.try_acquire("b"));
+
+        let throttle = SearchThrottle::new(Duration::ZERO);
+        assert!(throttle.try_acquire("a"));
+        assert!(throttle.try_acquire("a"));
+    }
+
+    #[test]
+    fn test_find_exact_match_ignores_case() {
+        let results = vec![meta("VWCE.DE"), meta("aapl")];
+        assert_eq!(
+            find_exact_match("AAPL", results).map(|m| m.symbol),
+            Some("aapl".to_string())
+        );
+        assert!(find_exact_match("VWCE", vec![meta("VWCE.DE")]).is_none());
+    }
+
+    #[test]
+    fn test_suggest_symbol_prefers_exchange_suffix() {
+        let results = vec![meta("VWCEX"), meta("VWCE.DE"), meta("VWCE.MI")];
+        assert_eq!(
+            suggest_symbol("vwce", &results),
+            Some("VWCE.DE".to_string())
+        );
+    }
+
+    #[test]
+    fn test_suggest_symbol_falls_back_to_first_result() {
+        assert_eq!(
+            suggest_symbol("APPL", &[meta("AAPL"), meta("APLE")]),
+            Some("AAPL".to_string())
+        );
+        assert_eq!(suggest_symbol("ZZZZ", &[]), None);
+    }
+}
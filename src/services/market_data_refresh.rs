@@ -0,0 +1,206 @@
+//! Fetching and storing market data for a symbol.
+//!
+//! Shared by the refresh buttons of the market data pages and the retry
+//! button of the API log detail page. Every request is recorded in the API
+//! logs; callers check `AppState::market_data_refresh` first so only one
+//! refresh runs at a time. Writes take the symbol's lock in
+//! `AppState::symbol_locks`, so they don't interleave with split
+//! adjustments.
+
+use crate::cache::DataDomain;
+use crate::db::queries::{api_logs, market_data as market_data_queries};
+use crate::models::NewApiLog;
+use crate::services::market_data;
+use crate::state::{AppState, MarketDataRefreshState};
+
+/// Fetch and store quotes for a single symbol and its date windows in the background
+pub fn spawn_symbol_fetch(
+    state: &AppState,
+    sym: String,
+    ranges: Vec<(String, String)>,
+    retried_from: Option<i64>,
+) {
+    // Set refresh state for single symbol
+    {
+        let mut refresh_state = state.market_data_refresh.lock().unwrap();
+        *refresh_state = MarketDataRefreshState {
+            is_refreshing: true,
+            processed_symbols: 0,
+            total_symbols: 1,
+            current_symbol: Some(sym.clone()),
+        };
+    }
+
+    // Spawn background task
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        fetch_symbol_ranges(&state_clone, &sym, &ranges, retried_from).await;
+
+        // Clear refresh state when done
+        {
+            let mut refresh_state = state_clone.market_data_refresh.lock().unwrap();
+            *refresh_state = MarketDataRefreshState::default();
+        }
+    });
+}
+
+/// Fetch and store quotes for each date window of a symbol, one request and
+/// API log entry per window, then the symbol metadata if not yet cached.
+/// `retried_from` links the entries to the log entry being retried.
+/// Returns `false` if any request failed.
+pub async fn fetch_symbol_ranges(
+    state: &AppState,
+    sym: &str,
+    ranges: &[(String, String)],
+    retried_from: Option<i64>,
+) -> bool {
+    let mut all_fetched = true;
+    let mut any_fetched = false;
+    let mut quote_currency = None;
+    for (i, (start, end)) in ranges.iter().enumerate() {
+        if i > 0 {
+            // Rate limiting between requests
+            tokio::time::sleep(state.market_data_delay()).await;
+        }
+
+        let start_time = std::time::Instant::now();
+        let request_params = serde_json::json!({
+            "symbol": sym,
+            "start_date": start,
+            "end_date": end,
+            "range": i + 1,
+            "ranges": ranges.len(),
+        })
+        .to_string();
+
+        match market_data::fetch_historical_quotes(sym, start, end).await {
+            Ok(data) => {
+                any_fetched = true;
+                let duration_ms = start_time.elapsed().as_millis() as i64;
+                if let Ok(conn) = state.db.get() {
+                    // Log success
+                    let _ = api_logs::insert_api_log(
+                        &conn,
+                        &NewApiLog {
+                            api_name: "yahoo_finance".to_string(),
+                            action: "fetch_historical_quotes".to_string(),
+                            symbol: Some(sym.to_string()),
+                            request_params,
+                            status: "success".to_string(),
+                            response_summary: Some(format!(
+                                "Retrieved {} data points for {} to {} (range {} of {})",
+                                data.len(),
+                                start,
+                                end,
+                                i + 1,
+                                ranges.len()
+                            )),
+                            response_details: Some(
+                                serde_json::json!({
+                                    "data_points": data.len(),
+                                    "first_date": data.first().map(|d| &d.date),
+                                    "last_date": data.last().map(|d| &d.date),
+                                })
+                                .to_string(),
+                            ),
+                            duration_ms: Some(duration_ms),
+                            retried_from,
+                        },
+                    );
+
+                    if data.is_empty() {
+                        if let Err(e) =
+                            market_data_queries::record_empty_range(&conn, sym, start, end)
+                        {
+                            tracing::error!("Failed to record empty range for {}: {}", sym, e);
+                        }
+                    }
+
+                    let _guard = state.symbol_locks.lock(&[sym]).await;
+                    if let Err(e) = market_data_queries::insert_market_data_batch(&conn, &data) {
+                        tracing::error!("Failed to insert market data for {}: {}", sym, e);
+                    } else {
+                        tracing::info!(
+                            "Fetched {} data points for {} ({} to {})",
+                            data.len(),
+                            sym,
+                            start,
+                            end
+                        );
+                        state.cache.invalidate_domains(&[DataDomain::MarketData]);
+                    }
+                }
+                if let Some(quote) = data.into_iter().next() {
+                    quote_currency = Some(quote.currency);
+                }
+            }
+            Err(e) => {
+                let duration_ms = start_time.elapsed().as_millis() as i64;
+                if let Ok(conn) = state.db.get() {
+                    // Log error
+                    let _ = api_logs::insert_api_log(
+                        &conn,
+                        &NewApiLog {
+                            api_name: "yahoo_finance".to_string(),
+                            action: "fetch_historical_quotes".to_string(),
+                            symbol: Some(sym.to_string()),
+                            request_params,
+                            status: "error".to_string(),
+                            response_summary: Some(format!(
+                                "{} (range {} of {})",
+                                e,
+                                i + 1,
+                                ranges.len()
+                            )),
+                            response_details: Some(format!("{:?}", e)),
+                            duration_ms: Some(duration_ms),
+                            retried_from,
+                        },
+                    );
+                }
+                tracing::error!(
+                    "Failed to fetch market data for {} ({} to {}): {}",
+                    sym,
+                    start,
+                    end,
+                    e
+                );
+                all_fetched = false;
+            }
+        }
+    }
+
+    if !any_fetched {
+        return all_fetched;
+    }
+    if let Ok(conn) = state.db.get() {
+        // Also fetch and store symbol metadata if not already cached
+        let meta = if market_data_queries::get_symbol_metadata(&conn, sym)
+            .ok()
+            .flatten()
+            .is_none()
+        {
+            market_data::fetch_symbol_metadata(sym).await.ok().flatten()
+        } else {
+            None
+        };
+
+        let _guard = state.symbol_locks.lock(&[sym]).await;
+        if let Some(meta) = meta {
+            let _ = market_data_queries::upsert_symbol_metadata(
+                &conn,
+                sym,
+                meta.short_name.as_deref(),
+                meta.long_name.as_deref(),
+                Some(&meta.exchange),
+                Some(&meta.quote_type),
+            );
+        }
+
+        // After the names, which are only fetched for new symbols
+        if let Some(currency) = quote_currency {
+            let _ = market_data_queries::detect_price_currency(&conn, sym, &currency);
+        }
+    }
+    all_fetched
+}
@@ -1,7 +1,27 @@
 pub mod analytics;
+pub mod anonymize;
+pub mod backup;
+pub mod capital_gains;
+pub mod card_cycle;
+pub mod cash_ledger;
 pub mod csv_parser;
+pub mod diagnostics;
+pub mod import_matching;
+pub mod import_overlap;
+pub mod import_preview;
+pub mod interest;
 pub mod market_data;
+pub mod market_data_refresh;
+pub mod money;
 pub mod net_worth;
+pub mod notify;
+pub mod position_history;
+pub mod positions;
+pub mod recurring_detection;
 pub mod retirement;
+pub mod rule_suggestion;
+pub mod splits;
 pub mod trading_csv_parser;
+pub mod trading_mirror;
+pub mod transfer_detection;
 pub mod xirr;
@@ -0,0 +1,346 @@
+//! Parsing of money amounts and quantities entered in forms or imported from
+//! CSV files, with exact decimal arithmetic instead of `f64`.
+
+use crate::error::{AppError, AppResult};
+use crate::filters::locale_separators;
+
+/// Locale of values submitted by `<input type="number">`, which always use a
+/// dot as decimal separator regardless of the user's locale.
+pub const INPUT_LOCALE: &str = "en-US";
+
+/// A parsed decimal number, split into its digits.
+#[derive(Debug, PartialEq)]
+struct Decimal {
+    negative: bool,
+    whole: String,
+    fraction: String,
+}
+
+impl Decimal {
+    /// Digits past the second decimal round half away from zero, so `1.005`
+    /// is always 101 cents.
+    fn cents(&self) -> Option<i64> {
+        let mut fraction = self.fraction.bytes().map(|b| (b - b'0') as i64);
+        let whole: i64 = if self.whole.is_empty() {
+            0
+        } else {
+            self.whole.parse().ok()?
+        };
+        let mut cents = whole
+            .checked_mul(100)?
+            .checked_add(fraction.next().unwrap_or(0) * 10 + fraction.next().unwrap_or(0))?;
+        if fraction.next().is_some_and(|d| d >= 5) {
+            cents = cents.checked_add(1)?;
+        }
+        Some(if self.negative { -cents } else { cents })
+    }
+
+    /// The number as `[-]123.45`, without thousands separators.
+    fn canonical(&self) -> String {
+        let whole = if self.whole.is_empty() {
+            "0"
+        } else {
+            &self.whole
+        };
+        let sign = if self.negative && (whole != "0" || self.fraction.bytes().any(|b| b != b'0')) {
+            "-"
+        } else {
+            ""
+        };
+        if self.fraction.is_empty() {
+            format!("{}{}", sign, whole)
+        } else {
+            format!("{}{}.{}", sign, whole, self.fraction)
+        }
+    }
+}
+
+/// Parse a money amount into cents.
+pub fn parse_amount(input: &str, locale: &str) -> AppResult<i64> {
+    parse_decimal(input, locale)
+        .and_then(|d| d.cents())
+        .ok_or_else(|| AppError::Validation(format!("Invalid amount '{}'", input.trim())))
+}
+
+/// Parse a (possibly fractional) quantity, e.g. a number of shares.
+pub fn parse_quantity(input: &str, locale: &str) -> AppResult<f64> {
+    normalize_decimal(input, locale)?
+        .parse()
+        .map_err(|_| AppError::Validation(format!("Invalid quantity '{}'", input.trim())))
+}
+
+/// Normalize a number to `[-]123.45` form, keeping all of its decimals.
+pub fn normalize_decimal(input: &str, locale: &str) -> AppResult<String> {
+    parse_decimal(input, locale)
+        .map(|d| d.canonical())
+        .ok_or_else(|| AppError::Validation(format!("Invalid number '{}'", input.trim())))
+}
+
+/// Format cents as `[-]123.45`, the inverse of [`parse_amount`] with
+/// [`INPUT_LOCALE`].
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let abs = cents.unsigned_abs();
+    format!("{}{}.{:02}", sign, abs / 100, abs % 100)
+}
+
+/// Format `value` with up to `decimals` decimals and without trailing zeros
+/// (`2.5`, `10`), for quantities and form inputs.
+pub fn format_decimal(value: f64, decimals: usize) -> String {
+    let formatted = format!("{:.*}", decimals, value);
+    if decimals == 0 {
+        return formatted;
+    }
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Parse a number with optional thousands separators (`1.234,56`,
+/// `1'234.56`), currency symbols or codes (`$5`, `EUR 5`) and a sign or
+/// parentheses (`5-`, `(5.00)`).
+fn parse_decimal(input: &str, locale: &str) -> Option<Decimal> {
+    let cleaned: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '\u{2060}' | '\'' | '\u{2019}'))
+        .collect();
+    let (negative, number) = strip_sign_and_symbols(&cleaned)?;
+    let (whole, fraction) = split_number(number, locale)?;
+    Some(Decimal {
+        negative,
+        whole: whole.trim_start_matches('0').to_string(),
+        fraction,
+    })
+}
+
+/// Strip parentheses, signs and currency symbols around the number itself.
+fn strip_sign_and_symbols(s: &str) -> Option<(bool, &str)> {
+    let (parenthesized, s) = match s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) => (true, inner),
+        None => (false, s),
+    };
+
+    let is_number_char = |c: char| c.is_ascii_digit() || c == '.' || c == ',';
+    let start = s.find(is_number_char)?;
+    let end = s.rfind(is_number_char)? + 1;
+    let affixes = [&s[..start], &s[end..]];
+
+    let mut signs = 0;
+    let mut negative = parenthesized;
+    for c in affixes.iter().flat_map(|a| a.chars()) {
+        match c {
+            '-' | '\u{2212}' => {
+                negative = true;
+                signs += 1;
+            }
+            '+' => signs += 1,
+            c if c.is_alphabetic() || is_currency_symbol(c) => {}
+            _ => return None,
+        }
+    }
+    if signs + usize::from(parenthesized) > 1 {
+        return None;
+    }
+    Some((negative, &s[start..end]))
+}
+
+fn is_currency_symbol(c: char) -> bool {
+    matches!(c, '$' | '£' | '¥' | '¢') || ('\u{20a0}'..='\u{20cf}').contains(&c)
+}
+
+/// Split a number of digits and separators into whole and fractional
+/// digits, checking the thousands grouping.
+fn split_number(s: &str, locale: &str) -> Option<(String, String)> {
+    if !s
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    {
+        return None;
+    }
+    let decimal = decimal_separator(s, locale);
+    let (whole, fraction) = match decimal.and_then(|d| s.rsplit_once(d)) {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (s, ""),
+    };
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+
+    // Thousands separators must all be the same and group by three digits
+    let separators: Vec<char> = whole.chars().filter(|c| !c.is_ascii_digit()).collect();
+    if separators.windows(2).any(|w| w[0] != w[1]) {
+        return None;
+    }
+    let groups: Vec<&str> = whole.split(['.', ',']).collect();
+    if groups.len() > 1
+        && (!(1..=3).contains(&groups[0].len()) || groups[1..].iter().any(|g| g.len() != 3))
+    {
+        return None;
+    }
+    Some((groups.concat(), fraction.to_string()))
+}
+
+/// The decimal separator used in `s`, if any.
+fn decimal_separator(s: &str, locale: &str) -> Option<char> {
+    let last_dot = s.rfind('.');
+    let last_comma = s.rfind(',');
+    let candidate = match (last_dot, last_comma) {
+        (Some(d), Some(c)) => return Some(if d > c { '.' } else { ',' }),
+        (Some(_), None) => '.',
+        (None, Some(_)) => ',',
+        (None, None) => return None,
+    };
+
+    // A separator that appears more than once groups thousands
+    if s.matches(candidate).count() > 1 {
+        return None;
+    }
+    // "1,234" is ambiguous: it's a decimal only where the locale says so
+    let (whole, fraction) = s.split_once(candidate)?;
+    let ambiguous = fraction.len() == 3 && (1..=3).contains(&whole.len()) && whole != "0";
+    if ambiguous && candidate != locale_separators(locale).1 {
+        return None;
+    }
+    Some(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{format_money_neutral, format_money_plain};
+    use proptest::prelude::*;
+
+    fn cents(s: &str) -> i64 {
+        parse_amount(s, "en-US").unwrap()
+    }
+
+    #[test]
+    fn test_format_decimal_trims_trailing_zeros() {
+        assert_eq!(format_decimal(2.5, 4), "2.5");
+        assert_eq!(format_decimal(10.0, 4), "10");
+        assert_eq!(format_decimal(0.123456, 4), "0.1235");
+        assert_eq!(format_decimal(10.0, 0), "10");
+    }
+
+    #[test]
+    fn test_float_traps() {
+        assert_eq!(cents("4.35"), 435);
+        assert_eq!(cents("0.29"), 29);
+        assert_eq!(cents("1.15"), 115);
+        assert_eq!(cents("19.99"), 1999);
+        assert_eq!(cents("1234.565"), 123_457);
+        assert_eq!(cents("1.005"), 101);
+        assert_eq!(cents("-1.005"), -101);
+        assert_eq!(cents("1.0049"), 100);
+    }
+
+    #[test]
+    fn test_separators() {
+        assert_eq!(cents("1,234.56"), 123_456);
+        assert_eq!(cents("1,234,567"), 123_456_700);
+        assert_eq!(cents("1 234.56"), 123_456);
+        assert_eq!(cents("1'234.56"), 123_456);
+        assert_eq!(cents(".5"), 50);
+        assert_eq!(cents("1,5"), 150);
+        assert_eq!(parse_amount("1.234,56", "de-DE").unwrap(), 123_456);
+        assert_eq!(parse_amount("1.234.567", "de-DE").unwrap(), 123_456_700);
+    }
+
+    #[test]
+    fn test_ambiguous_separator_uses_locale() {
+        assert_eq!(parse_amount("1,234", "en-US").unwrap(), 123_400);
+        assert_eq!(parse_amount("1,234", "de-DE").unwrap(), 123);
+        assert_eq!(parse_amount("1.234", "en-US").unwrap(), 123);
+        assert_eq!(parse_amount("1.234", "de-DE").unwrap(), 123_400);
+        assert_eq!(parse_amount("0,500", "en-US").unwrap(), 50);
+    }
+
+    #[test]
+    fn test_symbols_and_signs() {
+        assert_eq!(cents("$50.00"), 5000);
+        assert_eq!(cents("-$25.50"), -2550);
+        assert_eq!(cents("$-25.50"), -2550);
+        assert_eq!(cents("+12"), 1200);
+        assert_eq!(cents("(12.34)"), -1234);
+        assert_eq!(cents("($12.34)"), -1234);
+        assert_eq!(cents("12.34-"), -1234);
+        assert_eq!(cents("€100"), 10_000);
+        assert_eq!(cents("USD 100"), 10_000);
+        assert_eq!(parse_amount("1.234,56 €", "de-DE").unwrap(), 123_456);
+        assert_eq!(cents("-0.00"), 0);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for input in [
+            "",
+            "abc",
+            "-",
+            "$",
+            "1.2.3,4,5",
+            "12a3",
+            "--5",
+            "(-5)",
+            "1e5",
+            "1,23,456",
+            "1.5.0",
+            "12,34.56,7",
+        ] {
+            assert!(
+                parse_amount(input, "en-US").is_err(),
+                "accepted {:?}",
+                input
+            );
+        }
+        assert!(parse_amount("99999999999999999999", "en-US").is_err());
+    }
+
+    #[test]
+    fn test_quantity() {
+        assert_eq!(parse_quantity("10", INPUT_LOCALE).unwrap(), 10.0);
+        assert_eq!(parse_quantity("0.0001", INPUT_LOCALE).unwrap(), 0.0001);
+        assert_eq!(parse_quantity("1.234", INPUT_LOCALE).unwrap(), 1.234);
+        assert_eq!(
+            normalize_decimal("1.234,5678", "de-DE").unwrap(),
+            "1234.5678"
+        );
+        assert_eq!(normalize_decimal("-0", "en-US").unwrap(), "0");
+    }
+
+    const LOCALES: [&str; 3] = ["en-US", "de-DE", "fr-FR"];
+
+    proptest! {
+        #[test]
+        fn prop_formatted_amounts_round_trip(cents in -10_000_000_000_i64..10_000_000_000, locale in 0..LOCALES.len()) {
+            let locale = LOCALES[locale];
+            for currency in ["USD", "EUR", "GBP"] {
+                let plain = format_money_plain(cents, currency, locale);
+                prop_assert_eq!(parse_amount(&plain, locale).unwrap(), cents, "{}", plain);
+                let neutral = format_money_neutral(cents, currency, locale);
+                prop_assert_eq!(parse_amount(&neutral, locale).unwrap(), cents, "{}", neutral);
+            }
+            prop_assert_eq!(parse_amount(&format_cents(cents), INPUT_LOCALE).unwrap(), cents);
+        }
+
+        #[test]
+        fn prop_two_decimals_are_exact(whole in 0_i64..1_000_000_000, fraction in 0_i64..100) {
+            let input = format!("{}.{:02}", whole, fraction);
+            prop_assert_eq!(parse_amount(&input, INPUT_LOCALE).unwrap(), whole * 100 + fraction);
+        }
+
+        #[test]
+        fn prop_third_decimal_rounds_half_away_from_zero(cents in -1_000_000_i64..1_000_000, digit in 0_i64..10) {
+            let input = format!("{}{}", format_cents(cents), digit);
+            let expected = match (digit >= 5, cents < 0) {
+                (false, _) => cents,
+                (true, false) => cents + 1,
+                (true, true) => cents - 1,
+            };
+            prop_assert_eq!(parse_amount(&input, INPUT_LOCALE).unwrap(), expected);
+        }
+    }
+}
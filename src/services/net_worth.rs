@@ -1,19 +1,25 @@
+use crate::db::queries::market_data::get_price_conversions;
 use crate::db::queries::net_worth::{
     get_all_activities_ordered, get_all_market_data, get_daily_transaction_sums, get_earliest_date,
     get_last_trade_prices, get_latest_date,
 };
+use crate::models::market_data::{fx_pair, fx_rate};
 use crate::models::net_worth::{NetWorthDataPoint, NetWorthSummary};
 use crate::models::trading::TradingActivityType;
+use crate::services::cash_ledger;
 use chrono::{Duration, NaiveDate};
 use rusqlite::Connection;
 use std::collections::{BTreeMap, HashMap};
 
 /// Price lookup supporting exact date match and carry-forward
 struct PriceLookup {
-    /// symbol -> (date -> price_cents)
-    by_symbol: HashMap<String, BTreeMap<String, i64>>,
+    /// symbol -> (date -> close)
+    by_symbol: HashMap<String, BTreeMap<String, f64>>,
     /// symbol -> fallback_price_cents (from last trade)
     fallback: HashMap<String, i64>,
+    /// symbol -> (price scale, (price currency, activity currency)) for
+    /// market data that is not in the currency of the symbol's activities
+    conversions: HashMap<String, (f64, Option<(String, String)>)>,
 }
 
 impl PriceLookup {
@@ -21,14 +27,33 @@ impl PriceLookup {
         Self {
             by_symbol: HashMap::new(),
             fallback: HashMap::new(),
+            conversions: HashMap::new(),
         }
     }
 
-    fn add_market_data(&mut self, symbol: &str, date: &str, price_cents: i64) {
+    fn set_conversion(&mut self, symbol: &str, scale: f64, fx: Option<(String, String)>) {
+        self.conversions.insert(symbol.to_string(), (scale, fx));
+    }
+
+    /// Latest stored close of `symbol` on or before `date`.
+    fn market_price(&self, symbol: &str, date: &str) -> Option<f64> {
+        let prices = self.by_symbol.get(symbol)?;
+        prices
+            .get(date)
+            .or_else(|| {
+                prices
+                    .range(..=date.to_string())
+                    .next_back()
+                    .map(|(_, p)| p)
+            })
+            .copied()
+    }
+
+    fn add_market_data(&mut self, symbol: &str, date: &str, close: f64) {
         self.by_symbol
             .entry(symbol.to_string())
             .or_default()
-            .insert(date.to_string(), price_cents);
+            .insert(date.to_string(), close);
     }
 
     fn set_fallback(&mut self, symbol: &str, price_cents: i64) {
@@ -37,15 +62,19 @@ impl PriceLookup {
 
     /// Get price for a symbol on a specific date, with carry-forward
     fn get_price(&self, symbol: &str, date: &str) -> Option<i64> {
-        if let Some(prices) = self.by_symbol.get(symbol) {
-            // Try exact match first
-            if let Some(&price) = prices.get(date) {
-                return Some(price);
-            }
-            // Carry forward: find most recent price <= date
-            if let Some((&_, &price)) = prices.range(..=date.to_string()).next_back() {
-                return Some(price);
-            }
+        if let Some(close) = self.market_price(symbol, date) {
+            let (scale, rate) = match self.conversions.get(symbol) {
+                Some((scale, Some((from, to)))) => {
+                    let rate = fx_rate(
+                        self.market_price(&fx_pair(from, to), date),
+                        self.market_price(&fx_pair(to, from), date),
+                    );
+                    (*scale, rate.unwrap_or(1.0))
+                }
+                Some((scale, None)) => (*scale, 1.0),
+                None => (1.0, 1.0),
+            };
+            return Some((close * 100.0 * scale * rate).round() as i64);
         }
 
         // Fall back to last trade price
@@ -63,12 +92,15 @@ struct SymbolPosition {
 /// Running state of all positions
 struct PositionState {
     positions: HashMap<String, SymbolPosition>,
+    /// Net cash put into the portfolio: buys, fees and taxes minus sale proceeds
+    net_invested_cents: i64,
 }
 
 impl PositionState {
     fn new() -> Self {
         Self {
             positions: HashMap::new(),
+            net_invested_cents: 0,
         }
     }
 
@@ -94,12 +126,18 @@ impl PositionState {
         let entry = self.positions.entry(symbol.to_string()).or_default();
 
         match activity_type {
-            TradingActivityType::Buy => {
+            // Transferred shares count as invested at their cost basis
+            TradingActivityType::Buy
+            | TradingActivityType::TransferIn
+            | TradingActivityType::AddHolding => {
                 let cost = (qty * price as f64).round() as i64;
                 entry.quantity += qty;
                 entry.total_cost_cents = entry.total_cost_cents.saturating_add(cost);
+                self.net_invested_cents = self.net_invested_cents.saturating_add(cost);
             }
             TradingActivityType::Sell => {
+                let proceeds = (qty * price as f64).round() as i64;
+                self.net_invested_cents = self.net_invested_cents.saturating_sub(proceeds);
                 if entry.quantity > 0.0 {
                     let avg_cost = entry.total_cost_cents as f64 / entry.quantity;
                     let cost_reduction = (qty * avg_cost).round() as i64;
@@ -110,6 +148,16 @@ impl PositionState {
                     }
                 }
             }
+            TradingActivityType::TransferOut | TradingActivityType::RemoveHolding => {
+                if entry.quantity > 0.0 {
+                    let avg_cost = entry.total_cost_cents as f64 / entry.quantity;
+                    let cost_reduction = (qty.min(entry.quantity) * avg_cost).round() as i64;
+                    entry.quantity = (entry.quantity - qty).max(0.0);
+                    entry.total_cost_cents = entry.total_cost_cents.saturating_sub(cost_reduction);
+                    self.net_invested_cents =
+                        self.net_invested_cents.saturating_sub(cost_reduction);
+                }
+            }
             TradingActivityType::Split => {
                 // Split adjusts quantity but not total cost
                 if qty > 0.0 {
@@ -118,9 +166,9 @@ impl PositionState {
             }
             TradingActivityType::Fee | TradingActivityType::Tax => {
                 // These are expenses associated with the position
-                entry.total_cost_cents = entry
-                    .total_cost_cents
-                    .saturating_add((qty * price as f64).round() as i64);
+                let amount = (qty * price as f64).round() as i64;
+                entry.total_cost_cents = entry.total_cost_cents.saturating_add(amount);
+                self.net_invested_cents = self.net_invested_cents.saturating_add(amount);
             }
             TradingActivityType::Dividend => {
                 // Dividends don't affect position quantity or cost basis
@@ -189,6 +237,19 @@ fn build_cumulative_transactions(daily_sums: &[(String, i64)]) -> BTreeMap<Strin
     cumulative
 }
 
+/// Add `adjustments` to daily sums, keeping them sorted by date.
+fn merge_daily_sums(
+    daily_sums: Vec<(String, i64)>,
+    adjustments: impl IntoIterator<Item = (String, i64)>,
+) -> Vec<(String, i64)> {
+    let mut merged: BTreeMap<String, i64> = daily_sums.into_iter().collect();
+    for (date, amount) in adjustments {
+        let sum = merged.entry(date).or_insert(0);
+        *sum = sum.saturating_add(amount);
+    }
+    merged.into_iter().collect()
+}
+
 /// Get cumulative transaction value at date (carry forward if no exact match)
 fn get_cumulative_at_date(cumulative: &BTreeMap<String, i64>, date: &str) -> i64 {
     // Try exact match first
@@ -203,6 +264,18 @@ fn get_cumulative_at_date(cumulative: &BTreeMap<String, i64>, date: &str) -> i64
 }
 
 /// Calculate net worth history
+///
+/// Besides the total, each data point splits net worth into cumulative
+/// contributions and implied growth. Contributions are the cumulative cash
+/// flows plus the net cash invested in the portfolio, so growth is the
+/// portfolio's value beyond what was paid for it. Transfers need no special
+/// case: pairs between tracked accounts cancel out, and a one-sided transfer
+/// that funds a brokerage account offsets the deposit its trades count as.
+///
+/// Accounts that derive their cash from trading activities add the cash of
+/// their trades to the transaction component, minus the transactions their
+/// ledger ignores (see [`cash_ledger`]). Their trading is no contribution:
+/// only the transfers into them are.
 pub fn calculate_net_worth_history(conn: &Connection) -> rusqlite::Result<NetWorthSummary> {
     // Get date range
     let start_date = match get_earliest_date(conn)? {
@@ -215,7 +288,21 @@ pub fn calculate_net_worth_history(conn: &Connection) -> rusqlite::Result<NetWor
     };
 
     // Pre-fetch all data
-    let daily_transaction_sums = get_daily_transaction_sums(conn)?;
+    let ledgers = cash_ledger::replay(conn)?;
+    let daily_transaction_sums = merge_daily_sums(
+        get_daily_transaction_sums(conn)?,
+        ledgers.daily.iter().map(|d| {
+            (
+                d.date.clone(),
+                d.trading_cents.saturating_sub(d.ignored_cents),
+            )
+        }),
+    );
+    let daily_ledger_trading: Vec<(String, i64)> = ledgers
+        .daily
+        .iter()
+        .map(|d| (d.date.clone(), d.trading_cents))
+        .collect();
     let activities = get_all_activities_ordered(conn)?;
     let market_data = get_all_market_data(conn)?;
     let last_trade_prices = get_last_trade_prices(conn)?;
@@ -228,9 +315,19 @@ pub fn calculate_net_worth_history(conn: &Connection) -> rusqlite::Result<NetWor
     for (symbol, price, _date) in &last_trade_prices {
         price_lookup.set_fallback(symbol, *price);
     }
+    let conversions = get_price_conversions(conn)?;
+    for (_, symbol, _, _, _, _, currency, _) in &activities {
+        if let Some(meta) = conversions.get(symbol) {
+            let fx = meta
+                .fx_from(currency)
+                .map(|from| (from.to_string(), currency.clone()));
+            price_lookup.set_conversion(symbol, meta.price_scale, fx);
+        }
+    }
 
     // Build cumulative transaction sums
     let cumulative_transactions = build_cumulative_transactions(&daily_transaction_sums);
+    let cumulative_ledger_trading = build_cumulative_transactions(&daily_ledger_trading);
 
     // Generate date range
     let dates = generate_date_range(&start_date, &end_date);
@@ -241,13 +338,24 @@ pub fn calculate_net_worth_history(conn: &Connection) -> rusqlite::Result<NetWor
     // Sweep through dates
     let mut position_state = PositionState::new();
     let mut activity_idx = 0;
+    // Part of net_invested_cents paid from cash ledger accounts
+    let mut ledger_invested_cents = 0i64;
     let mut data_points = Vec::with_capacity(dates.len());
 
     for date in &dates {
         // Apply all activities up to and including this date
         while activity_idx < activities.len() && activities[activity_idx].0 <= *date {
-            let (_, symbol, activity_type, quantity, unit_price_cents, fee_cents, _currency) =
-                &activities[activity_idx];
+            let (
+                _,
+                symbol,
+                activity_type,
+                quantity,
+                unit_price_cents,
+                fee_cents,
+                _currency,
+                account_id,
+            ) = &activities[activity_idx];
+            let invested_before = position_state.net_invested_cents;
             position_state.apply_activity(
                 symbol,
                 activity_type,
@@ -255,6 +363,13 @@ pub fn calculate_net_worth_history(conn: &Connection) -> rusqlite::Result<NetWor
                 *unit_price_cents,
                 *fee_cents,
             );
+            if ledgers.contains(*account_id) {
+                ledger_invested_cents = ledger_invested_cents.saturating_add(
+                    position_state
+                        .net_invested_cents
+                        .saturating_sub(invested_before),
+                );
+            }
             activity_idx += 1;
         }
 
@@ -267,11 +382,22 @@ pub fn calculate_net_worth_history(conn: &Connection) -> rusqlite::Result<NetWor
         // Net worth = transaction cumulative + portfolio value
         let net_worth = transaction_component.saturating_add(portfolio_component);
 
+        // Contributions = cash flows + net cash invested. Transfers between
+        // tracked accounts cancel out in the cash flows; trades in cash
+        // ledger accounts only move money within the account.
+        let ledger_trading = get_cumulative_at_date(&cumulative_ledger_trading, date);
+        let contributions = transaction_component
+            .saturating_add(position_state.net_invested_cents)
+            .saturating_sub(ledger_invested_cents)
+            .saturating_sub(ledger_trading);
+
         data_points.push(NetWorthDataPoint {
             date: date.clone(),
             net_worth_cents: net_worth,
             transaction_component_cents: transaction_component,
             portfolio_component_cents: portfolio_component,
+            contributions_cents: contributions,
+            growth_cents: net_worth.saturating_sub(contributions),
         });
     }
 
@@ -0,0 +1,321 @@
+//! Notifications about failures in background work.
+//!
+//! Market data refreshes, scheduled backups and background imports run
+//! unattended, so their failures are easy to miss. They are sent by email over SMTP or posted to a
+//! webhook, such as an ntfy.sh topic. Each kind of failure is sent at most
+//! once an hour, so a provider outage doesn't turn into a flood of messages.
+//! Every attempt is recorded in the API logs with its outcome.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::db::queries::api_logs;
+use crate::error::{AppError, AppResult};
+use crate::models::api_log::NewApiLog;
+use crate::models::Settings;
+use crate::state::AppState;
+
+/// Minimum time between two notifications of the same kind.
+pub const RATE_LIMIT: Duration = Duration::from_secs(60 * 60);
+
+/// How long a single delivery attempt may take.
+const SEND_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The port of SMTP over implicit TLS; other ports use STARTTLS.
+const SMTPS_PORT: u16 = 465;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /// Value of ntfy's `Priority` header.
+    fn priority(&self) -> &'static str {
+        match self {
+            Severity::Info => "default",
+            Severity::Warning => "high",
+            Severity::Error => "urgent",
+        }
+    }
+
+    /// Value of ntfy's `Tags` header, shown as an emoji.
+    fn tag(&self) -> &'static str {
+        match self {
+            Severity::Info => "information_source",
+            Severity::Warning => "warning",
+            Severity::Error => "rotating_light",
+        }
+    }
+}
+
+/// Remembers when each kind of notification was last sent.
+pub struct NotificationThrottle {
+    /// Maps notification kind → time it was last sent.
+    last_sent: Mutex<HashMap<String, Instant>>,
+    min_interval: Duration,
+}
+
+impl Default for NotificationThrottle {
+    fn default() -> Self {
+        Self::new(RATE_LIMIT)
+    }
+}
+
+impl NotificationThrottle {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            last_sent: Mutex::new(HashMap::new()),
+            min_interval,
+        }
+    }
+
+    /// Returns `true` and records the notification if `kind` may be sent now.
+    pub fn try_acquire(&self, kind: &str) -> bool {
+        let mut map = self.last_sent.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        map.retain(|_, last| now.duration_since(*last) < self.min_interval);
+        if map.contains_key(kind) {
+            return false;
+        }
+        map.insert(kind.to_string(), now);
+        true
+    }
+}
+
+/// Send a notification through the configured channel, unless notifications
+/// are off or one of the same `kind` went out within the last hour.
+pub async fn notify(state: &AppState, kind: &str, severity: Severity, title: &str, body: &str) {
+    let settings = match state.load_settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            tracing::warn!(error = %e, kind, "Could not load notification settings");
+            return;
+        }
+    };
+    if !settings.notifications_enabled() {
+        return;
+    }
+    if !state.notification_throttle.try_acquire(kind) {
+        tracing::debug!(kind, "Notification suppressed by rate limit");
+        return;
+    }
+    // Failures are logged by `dispatch`
+    let _ = dispatch(state, &settings, kind, severity, title, body).await;
+}
+
+/// Notify about a market data refresh in which `failed` symbols could not be
+/// fetched, once their number reaches the configured threshold.
+pub async fn notify_refresh_failures(state: &AppState, failed: &[String]) {
+    let threshold = state
+        .load_settings()
+        .map(|s| s.notify_failure_threshold)
+        .unwrap_or(1);
+    if failed.is_empty() || failed.len() < threshold as usize {
+        return;
+    }
+    let body = format!(
+        "Fetching market data failed for {} symbol(s): {}",
+        failed.len(),
+        failed.join(", ")
+    );
+    notify(
+        state,
+        "market_data_refresh",
+        Severity::Warning,
+        "Market data refresh failed",
+        &body,
+    )
+    .await;
+}
+
+/// Send a test notification with `settings`, bypassing the rate limit.
+pub async fn send_test(state: &AppState, settings: &Settings) -> AppResult<()> {
+    if !settings.notifications_enabled() {
+        return Err(AppError::Validation(
+            "Choose a notification channel first".into(),
+        ));
+    }
+    dispatch(
+        state,
+        settings,
+        "test",
+        Severity::Info,
+        "Test notification",
+        "Notifications from Solvency are set up correctly.",
+    )
+    .await
+}
+
+/// Deliver a notification and record the attempt in the API logs.
+async fn dispatch(
+    state: &AppState,
+    settings: &Settings,
+    kind: &str,
+    severity: Severity,
+    title: &str,
+    body: &str,
+) -> AppResult<()> {
+    let start = Instant::now();
+    let (action, result) = match settings.notify_channel.as_str() {
+        "smtp" => ("send_email", send_email(settings, title, body).await),
+        "webhook" => (
+            "send_webhook",
+            send_webhook(settings, severity, title, body).await,
+        ),
+        other => (
+            "send",
+            Err(AppError::Validation(format!(
+                "Unknown notification channel: {}",
+                other
+            ))),
+        ),
+    };
+    let duration_ms = start.elapsed().as_millis() as i64;
+
+    match &result {
+        Ok(()) => tracing::info!(kind, channel = %settings.notify_channel, "Notification sent"),
+        Err(e) => {
+            tracing::error!(kind, channel = %settings.notify_channel, error = %e, "Notification failed")
+        }
+    }
+    if let Ok(conn) = state.db.get() {
+        let _ = api_logs::insert_api_log(
+            &conn,
+            &NewApiLog {
+                api_name: "notifications".to_string(),
+                action: action.to_string(),
+                symbol: None,
+                request_params: serde_json::json!({
+                    "kind": kind,
+                    "severity": severity.as_str(),
+                    "title": title,
+                })
+                .to_string(),
+                status: if result.is_ok() { "success" } else { "error" }.to_string(),
+                response_summary: Some(match &result {
+                    Ok(()) => format!("Sent \"{}\"", title),
+                    Err(e) => e.to_string(),
+                }),
+                response_details: result.as_ref().err().map(|e| format!("{:?}", e)),
+                duration_ms: Some(duration_ms),
+                retried_from: None,
+            },
+        );
+    }
+    result
+}
+
+async fn send_email(settings: &Settings, title: &str, body: &str) -> AppResult<()> {
+    let invalid = |what: &str, e: &dyn std::fmt::Display| {
+        AppError::Validation(format!("Invalid {}: {}", what, e))
+    };
+    let from: Mailbox = settings
+        .notify_smtp_from
+        .parse()
+        .map_err(|e| invalid("sender address", &e))?;
+    let mut message = Message::builder()
+        .from(from)
+        .subject(format!("[Solvency] {}", title))
+        .header(ContentType::TEXT_PLAIN);
+    for recipient in settings.notify_recipients() {
+        let to: Mailbox = recipient
+            .parse()
+            .map_err(|e| invalid("recipient address", &e))?;
+        message = message.to(to);
+    }
+    let message = message
+        .body(body.to_string())
+        .map_err(|e| invalid("email", &e))?;
+
+    let host = settings.notify_smtp_host.as_str();
+    let relay = if settings.notify_smtp_port == SMTPS_PORT {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+    };
+    let mut transport = relay
+        .map_err(|e| invalid("SMTP server", &e))?
+        .port(settings.notify_smtp_port)
+        .timeout(Some(SEND_TIMEOUT));
+    if !settings.notify_smtp_username.is_empty() {
+        transport = transport.credentials(Credentials::new(
+            settings.notify_smtp_username.clone(),
+            settings.notify_smtp_password.clone(),
+        ));
+    }
+    transport
+        .build()
+        .send(message)
+        .await
+        .map_err(|e| AppError::Internal(format!("Sending the email failed: {}", e)))?;
+    Ok(())
+}
+
+/// Post the notification in ntfy's format: the message as plain text body
+/// and the title, priority and tags as headers. Other webhooks receive the
+/// same plain text request.
+async fn send_webhook(
+    settings: &Settings,
+    severity: Severity,
+    title: &str,
+    body: &str,
+) -> AppResult<()> {
+    let client = reqwest::Client::builder()
+        .timeout(SEND_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Internal(format!("HTTP client error: {}", e)))?;
+    let response = client
+        .post(&settings.notify_webhook_url)
+        .header("Title", title)
+        .header("Priority", severity.priority())
+        .header("Tags", severity.tag())
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| AppError::Internal(format!("Posting to the webhook failed: {}", e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(AppError::Internal(format!(
+            "The webhook answered with status {}",
+            status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_allows_one_per_kind() {
+        let throttle = NotificationThrottle::new(Duration::from_secs(60));
+        assert!(throttle.try_acquire("scheduled_backup"));
+        assert!(!throttle.try_acquire("scheduled_backup"));
+        assert!(throttle.try_acquire("market_data_refresh"));
+    }
+
+    #[test]
+    fn test_throttle_expires() {
+        let throttle = NotificationThrottle::new(Duration::ZERO);
+        assert!(throttle.try_acquire("scheduled_backup"));
+        assert!(throttle.try_acquire("scheduled_backup"));
+    }
+}
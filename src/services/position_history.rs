@@ -0,0 +1,319 @@
+//! Full history of one position for export: activities with their values
+//! before splits, realized gains per sale, dividends, fees and taxes, and
+//! the current position. The JSON form re-imports through the trading
+//! activities import.
+
+use std::collections::HashMap;
+
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::queries::{split_adjustments, trading};
+use crate::error::{AppError, AppResult};
+use crate::models::trading::PositionWithMarketData;
+use crate::models::TradingActivityType;
+use crate::services::analytics::{format_cents, optional_cents};
+use crate::services::positions::{realized_sales, RealizedSale};
+
+/// One activity of a position history export. The fields up to `notes`
+/// match the trading activities JSON import and hold the values as entered,
+/// before any split adjusted them, so the import can replay the splits.
+#[derive(Serialize)]
+pub struct HistoryActivity {
+    id: i64,
+    date: String,
+    symbol: String,
+    quantity: Option<f64>,
+    activity_type: TradingActivityType,
+    unit_price_cents: Option<i64>,
+    currency: String,
+    fee_cents: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exchange_rate: Option<f64>,
+    account_name: Option<String>,
+    notes: Option<String>,
+    /// Quantity and price after all later splits, as stored
+    adjusted_quantity: Option<f64>,
+    adjusted_unit_price_cents: Option<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    split_adjustments: Vec<split_adjustments::SplitAdjustment>,
+}
+
+impl HistoryActivity {
+    /// Product of the ratios of all splits applied to the activity.
+    fn split_factor(&self) -> Option<f64> {
+        (!self.split_adjustments.is_empty()).then(|| {
+            self.split_adjustments
+                .iter()
+                .map(|a| a.split_ratio)
+                .product()
+        })
+    }
+}
+
+/// A dividend, fee or tax of the position.
+#[derive(Serialize)]
+pub struct HistoryEvent {
+    activity_id: i64,
+    date: String,
+    activity_type: TradingActivityType,
+    amount_cents: i64,
+    currency: String,
+    notes: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PositionSnapshot {
+    quantity: f64,
+    total_cost_cents: i64,
+    average_cost_cents: Option<i64>,
+    current_price_cents: Option<i64>,
+    price_date: Option<String>,
+    price_is_approximated: bool,
+    current_value_cents: Option<i64>,
+    gain_loss_cents: Option<i64>,
+}
+
+/// Everything known about one symbol, for tax questions and migrations.
+#[derive(Serialize)]
+pub struct PositionHistory {
+    symbol: String,
+    pub name: Option<String>,
+    currency: String,
+    /// `null` once the position is closed
+    position: Option<PositionSnapshot>,
+    activities: Vec<HistoryActivity>,
+    realized_gains: Vec<RealizedSale>,
+    events: Vec<HistoryEvent>,
+}
+
+pub const HEADERS: [&str; 16] = [
+    "record",
+    "activity_id",
+    "date",
+    "activity_type",
+    "quantity",
+    "unit_price",
+    "original_quantity",
+    "original_unit_price",
+    "split_factor",
+    "amount",
+    "cost_basis",
+    "fee",
+    "gain",
+    "currency",
+    "account_name",
+    "notes",
+];
+
+/// Assemble the history of `symbol` from its activities and split
+/// adjustments. `position` is the open position with its latest price, if
+/// any, and `account_names` maps account IDs to names.
+pub fn build(
+    conn: &Connection,
+    symbol: &str,
+    name: Option<String>,
+    position: Option<PositionWithMarketData>,
+    account_names: &HashMap<i64, String>,
+) -> AppResult<PositionHistory> {
+    let activities = trading::get_activities_for_symbol(conn, symbol)?;
+    if activities.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "No activities for symbol {}",
+            symbol
+        )));
+    }
+    let mut adjustments: HashMap<i64, Vec<split_adjustments::SplitAdjustment>> = HashMap::new();
+    for adjustment in split_adjustments::get_adjustments_for_symbol(conn, symbol)? {
+        adjustments
+            .entry(adjustment.target_activity_id)
+            .or_default()
+            .push(adjustment);
+    }
+
+    let currency = position
+        .as_ref()
+        .map(|p| p.position.currency.clone())
+        .unwrap_or_else(|| activities[0].currency.clone());
+
+    let events = activities
+        .iter()
+        .filter(|a| {
+            matches!(
+                a.activity_type,
+                TradingActivityType::Dividend | TradingActivityType::Fee | TradingActivityType::Tax
+            )
+        })
+        .map(|a| HistoryEvent {
+            activity_id: a.id,
+            date: a.date.clone(),
+            activity_type: a.activity_type,
+            amount_cents: a.unit_price_cents.unwrap_or(0),
+            currency: a.currency.clone(),
+            notes: a.notes.clone(),
+        })
+        .collect();
+    let realized_gains = realized_sales(&activities);
+
+    let history_activities = activities
+        .into_iter()
+        .map(|a| {
+            let split_adjustments = adjustments.remove(&a.id).unwrap_or_default();
+            // The first adjustment holds the values before any split
+            let (quantity, unit_price_cents) = match split_adjustments.first() {
+                Some(first) => (
+                    Some(first.original_quantity),
+                    first.original_unit_price_cents,
+                ),
+                None => (a.quantity, a.unit_price_cents),
+            };
+            HistoryActivity {
+                id: a.id,
+                date: a.date,
+                symbol: a.symbol,
+                quantity,
+                activity_type: a.activity_type,
+                unit_price_cents,
+                currency: a.currency,
+                fee_cents: a.fee_cents,
+                fee_currency: a.fee_currency,
+                exchange_rate: a.exchange_rate,
+                account_name: a.account_id.and_then(|id| account_names.get(&id).cloned()),
+                notes: a.notes,
+                adjusted_quantity: a.quantity,
+                adjusted_unit_price_cents: a.unit_price_cents,
+                split_adjustments,
+            }
+        })
+        .collect();
+
+    Ok(PositionHistory {
+        symbol: symbol.to_string(),
+        name,
+        currency,
+        position: position.map(|p| PositionSnapshot {
+            quantity: p.position.quantity,
+            total_cost_cents: p.position.total_cost_cents,
+            average_cost_cents: p.position.average_cost_cents(),
+            current_price_cents: p.current_price_cents,
+            price_date: p.price_date,
+            price_is_approximated: p.price_is_approximated,
+            current_value_cents: p.current_value_cents,
+            gain_loss_cents: p.gain_loss_cents,
+        }),
+        activities: history_activities,
+        realized_gains,
+        events,
+    })
+}
+
+/// Flatten a position history into CSV records, one section after another.
+pub fn csv_records(history: &PositionHistory) -> Vec<Vec<String>> {
+    let optional_qty = |q: Option<f64>| q.map(|q| q.to_string()).unwrap_or_default();
+    let mut records = Vec::new();
+
+    for a in &history.activities {
+        let factor = a.split_factor();
+        records.push(vec![
+            "activity".into(),
+            a.id.to_string(),
+            a.date.clone(),
+            a.activity_type.as_str().into(),
+            optional_qty(a.adjusted_quantity),
+            optional_cents(a.adjusted_unit_price_cents),
+            factor.map(|_| optional_qty(a.quantity)).unwrap_or_default(),
+            factor
+                .map(|_| optional_cents(a.unit_price_cents))
+                .unwrap_or_default(),
+            optional_qty(factor),
+            String::new(),
+            String::new(),
+            format_cents(a.fee_cents),
+            String::new(),
+            a.currency.clone(),
+            a.account_name.clone().unwrap_or_default(),
+            a.notes.clone().unwrap_or_default(),
+        ]);
+    }
+    for sale in &history.realized_gains {
+        records.push(vec![
+            "realized_gain".into(),
+            sale.activity_id.to_string(),
+            sale.date.clone(),
+            TradingActivityType::Sell.as_str().into(),
+            sale.quantity.to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            format_cents(sale.proceeds_cents),
+            format_cents(sale.cost_basis_cents),
+            format_cents(sale.fee_cents),
+            format_cents(sale.gain_cents),
+            history.currency.clone(),
+            String::new(),
+            String::new(),
+        ]);
+    }
+    for event in &history.events {
+        records.push(vec![
+            "event".into(),
+            event.activity_id.to_string(),
+            event.date.clone(),
+            event.activity_type.as_str().into(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            format_cents(event.amount_cents),
+            String::new(),
+            String::new(),
+            String::new(),
+            event.currency.clone(),
+            String::new(),
+            event.notes.clone().unwrap_or_default(),
+        ]);
+    }
+    if let Some(p) = &history.position {
+        records.push(vec![
+            "position".into(),
+            String::new(),
+            p.price_date.clone().unwrap_or_default(),
+            String::new(),
+            p.quantity.to_string(),
+            optional_cents(p.current_price_cents),
+            String::new(),
+            String::new(),
+            String::new(),
+            optional_cents(p.current_value_cents),
+            format_cents(p.total_cost_cents),
+            String::new(),
+            optional_cents(p.gain_loss_cents),
+            history.currency.clone(),
+            String::new(),
+            String::new(),
+        ]);
+    }
+    records
+}
+
+/// Download file name for a position history, e.g.
+/// `AAPL_Apple_Inc_history.csv`. Keeps ASCII letters, digits, `-` and `.`.
+pub fn filename(symbol: &str, name: Option<&str>, extension: &str) -> String {
+    let stem = match name {
+        Some(name) => format!("{} {} history", symbol, name),
+        None => format!("{} history", symbol),
+    };
+    let mut filename = String::new();
+    for c in stem.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+            filename.push(c);
+        } else if !filename.ends_with('_') {
+            filename.push('_');
+        }
+    }
+    format!("{}.{}", filename.trim_matches('_'), extension)
+}
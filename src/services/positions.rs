@@ -0,0 +1,413 @@
+//! Running cost basis of a single position, replayed from its activities.
+//!
+//! Quantities of activities before a split are already split-adjusted when
+//! stored, so the replay never rescales on split activities. Market prices
+//! are split-adjusted as well, which keeps the average cost comparable to
+//! the price series.
+
+use serde::Serialize;
+
+use crate::models::{TradingActivity, TradingActivityType};
+
+/// Quantities closer to zero than this count as no shares held.
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+/// Held quantity and cost after all activities of one date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostBasisStep {
+    pub date: String,
+    pub quantity: f64,
+    /// Remaining cost of the held shares, at average cost.
+    pub cost_cents: i64,
+    /// Fees and taxes minus dividends since the position was last opened.
+    pub carried_cents: i64,
+}
+
+impl CostBasisStep {
+    /// Average cost per share, or `None` when no shares are held.
+    pub fn average_cost_cents(&self) -> Option<i64> {
+        (self.quantity > QUANTITY_EPSILON)
+            .then(|| (self.cost_cents as f64 / self.quantity).round() as i64)
+    }
+
+    /// Price per share at which selling everything recovers the cost plus
+    /// fees and taxes, net of dividends received. `None` when no shares are held.
+    pub fn break_even_cents(&self) -> Option<i64> {
+        (self.quantity > QUANTITY_EPSILON)
+            .then(|| ((self.cost_cents + self.carried_cents) as f64 / self.quantity).round() as i64)
+    }
+}
+
+/// Position after one activity of [`replay`].
+pub struct ReplayStep<'a> {
+    pub activity: &'a TradingActivity,
+    pub quantity: f64,
+    pub cost_cents: i64,
+    pub carried_cents: i64,
+    /// Fraction of the held shares the activity disposed of, taken from
+    /// every lot alike as the cost is averaged.
+    pub disposed_share: f64,
+    /// Average cost of the shares the activity disposed of.
+    pub disposed_cost_cents: i64,
+    /// Share of the buy fees, fees and taxes since the position was opened
+    /// that the disposed shares carry.
+    pub disposed_fees_cents: i64,
+}
+
+/// Replay a symbol's activities in date order, one step per activity. Long
+/// positions only: disposals beyond the held quantity close the position,
+/// and fees and dividends reset once it is closed.
+pub fn replay<'a>(
+    activities: impl IntoIterator<Item = &'a TradingActivity>,
+) -> Vec<ReplayStep<'a>> {
+    let mut sorted: Vec<&TradingActivity> = activities.into_iter().collect();
+    sorted.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+
+    let mut steps = Vec::with_capacity(sorted.len());
+    let mut quantity = 0.0;
+    let mut cost_cents: i64 = 0;
+    let mut carried_cents: i64 = 0;
+    // Buy fees, fees and taxes not yet disposed of with shares
+    let mut fees_cents: i64 = 0;
+
+    for activity in sorted {
+        let qty = activity.quantity.unwrap_or(0.0);
+        let price = activity.unit_price_cents.unwrap_or(0);
+        let mut disposed_share = 0.0;
+        let mut disposed_cost_cents = 0;
+        let mut disposed_fees_cents = 0;
+
+        match activity.activity_type {
+            TradingActivityType::Buy
+            | TradingActivityType::TransferIn
+            | TradingActivityType::AddHolding => {
+                quantity += qty;
+                cost_cents += (qty * price as f64).round() as i64;
+                carried_cents += activity.fee_converted_cents();
+                fees_cents += activity.fee_converted_cents();
+            }
+            TradingActivityType::Sell
+            | TradingActivityType::TransferOut
+            | TradingActivityType::RemoveHolding => {
+                carried_cents += activity.fee_converted_cents();
+                if quantity > QUANTITY_EPSILON {
+                    disposed_share = qty.min(quantity) / quantity;
+                    disposed_cost_cents = (disposed_share * cost_cents as f64).round() as i64;
+                    disposed_fees_cents = (disposed_share * fees_cents as f64).round() as i64;
+                    cost_cents -= disposed_cost_cents;
+                    fees_cents -= disposed_fees_cents;
+                    quantity -= qty.min(quantity);
+                }
+                if quantity <= QUANTITY_EPSILON {
+                    quantity = 0.0;
+                    cost_cents = 0;
+                    carried_cents = 0;
+                    fees_cents = 0;
+                }
+            }
+            // Fee, tax and dividend activities store their total in unit_price_cents
+            TradingActivityType::Fee | TradingActivityType::Tax => {
+                carried_cents += price;
+                fees_cents += price;
+            }
+            TradingActivityType::Dividend => carried_cents -= price,
+            TradingActivityType::Split => {}
+        }
+
+        steps.push(ReplayStep {
+            activity,
+            quantity,
+            cost_cents,
+            carried_cents,
+            disposed_share,
+            disposed_cost_cents,
+            disposed_fees_cents,
+        });
+    }
+
+    steps
+}
+
+/// Replay a symbol's activities like [`replay`] and return one step per
+/// date with activities.
+pub fn cost_basis_steps(activities: &[TradingActivity]) -> Vec<CostBasisStep> {
+    let mut steps: Vec<CostBasisStep> = Vec::new();
+    for step in replay(activities) {
+        let step = CostBasisStep {
+            date: step.activity.date.clone(),
+            quantity: step.quantity,
+            cost_cents: step.cost_cents,
+            carried_cents: step.carried_cents,
+        };
+        match steps.last_mut() {
+            Some(last) if last.date == step.date => *last = step,
+            _ => steps.push(step),
+        }
+    }
+    steps
+}
+
+/// The last step on or before each of `dates` (ascending), or `None`
+/// before the first activity.
+pub fn steps_on_dates<'a>(
+    steps: &'a [CostBasisStep],
+    dates: &[String],
+) -> Vec<Option<&'a CostBasisStep>> {
+    let mut index = 0;
+    let mut current: Option<&CostBasisStep> = None;
+    dates
+        .iter()
+        .map(|date| {
+            while index < steps.len() && steps[index].date <= *date {
+                current = Some(&steps[index]);
+                index += 1;
+            }
+            current
+        })
+        .collect()
+}
+
+/// Average cost per share on each of `dates` (ascending), using the last
+/// step on or before the date. `None` where no shares were held, so charts
+/// break the line.
+pub fn average_cost_series(steps: &[CostBasisStep], dates: &[String]) -> Vec<Option<i64>> {
+    steps_on_dates(steps, dates)
+        .into_iter()
+        .map(|step| step.and_then(CostBasisStep::average_cost_cents))
+        .collect()
+}
+
+/// Break-even price per share of the position after all activities.
+pub fn break_even_price_cents(activities: &[TradingActivity]) -> Option<i64> {
+    cost_basis_steps(activities)
+        .last()
+        .and_then(CostBasisStep::break_even_cents)
+}
+
+/// Realized gain of one sale, at the average cost of the shares held.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RealizedSale {
+    pub activity_id: i64,
+    pub date: String,
+    pub quantity: f64,
+    pub proceeds_cents: i64,
+    /// Average cost of the sold shares that were held; shares sold beyond
+    /// the held quantity carry no cost.
+    pub cost_basis_cents: i64,
+    /// The sale's fee plus the sold shares' part of the buy fees, fees and
+    /// taxes since the position was opened.
+    pub fee_cents: i64,
+    pub gain_cents: i64,
+}
+
+/// The realized gain of every sale among a symbol's activities, oldest
+/// first, from the same replay as [`cost_basis_steps`]. Transfers and
+/// holding adjustments take their cost and fees along without realizing
+/// them.
+pub fn realized_sales(activities: &[TradingActivity]) -> Vec<RealizedSale> {
+    replay(activities)
+        .iter()
+        .filter_map(ReplayStep::realized_sale)
+        .collect()
+}
+
+impl ReplayStep<'_> {
+    /// The realized gain of the step, if its activity is a sale.
+    pub fn realized_sale(&self) -> Option<RealizedSale> {
+        let activity = self.activity;
+        if activity.activity_type != TradingActivityType::Sell {
+            return None;
+        }
+        let quantity = activity.quantity.unwrap_or(0.0);
+        let proceeds_cents =
+            (quantity * activity.unit_price_cents.unwrap_or(0) as f64).round() as i64;
+        let fee_cents = activity.fee_converted_cents() + self.disposed_fees_cents;
+        Some(RealizedSale {
+            activity_id: activity.id,
+            date: activity.date.clone(),
+            quantity,
+            proceeds_cents,
+            cost_basis_cents: self.disposed_cost_cents,
+            fee_cents,
+            gain_cents: proceeds_cents - self.disposed_cost_cents - fee_cents,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(
+        id: i64,
+        date: &str,
+        activity_type: TradingActivityType,
+        quantity: f64,
+        price_cents: i64,
+        fee_cents: i64,
+    ) -> TradingActivity {
+        TradingActivity {
+            id,
+            date: date.into(),
+            symbol: "AAPL".into(),
+            quantity: Some(quantity),
+            activity_type,
+            unit_price_cents: Some(price_cents),
+            currency: "USD".into(),
+            fee_cents,
+            fee_currency: None,
+            exchange_rate: None,
+            account_id: None,
+            notes: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_average_cost_series_follows_buys_and_sells() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000, 0),
+            activity(2, "2024-01-04", Buy, 10.0, 20_000, 0),
+            activity(3, "2024-01-06", Sell, 5.0, 30_000, 0),
+            activity(4, "2024-01-08", Sell, 15.0, 30_000, 0),
+        ];
+        let steps = cost_basis_steps(&activities);
+        let dates: Vec<String> = (1..=9).map(|d| format!("2024-01-0{}", d)).collect();
+
+        assert_eq!(
+            average_cost_series(&steps, &dates),
+            vec![
+                None,
+                Some(10_000),
+                Some(10_000),
+                Some(15_000),
+                Some(15_000),
+                // Selling at average cost keeps the average
+                Some(15_000),
+                Some(15_000),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_steps_on_dates_track_held_quantity() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000, 0),
+            activity(2, "2024-01-04", Sell, 4.0, 12_000, 0),
+        ];
+        let steps = cost_basis_steps(&activities);
+        let dates: Vec<String> = (1..=5).map(|d| format!("2024-01-0{}", d)).collect();
+
+        let quantities: Vec<f64> = steps_on_dates(&steps, &dates)
+            .into_iter()
+            .map(|step| step.map_or(0.0, |s| s.quantity))
+            .collect();
+        assert_eq!(quantities, vec![0.0, 10.0, 10.0, 6.0, 6.0]);
+    }
+
+    #[test]
+    fn test_break_even_includes_fees_and_dividends() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000, 500),
+            activity(2, "2024-02-01", Fee, 1.0, 1_500, 0),
+            activity(3, "2024-03-01", Dividend, 1.0, 1_000, 0),
+        ];
+
+        // (100_000 cost + 500 + 1_500 fees - 1_000 dividends) / 10 shares
+        assert_eq!(break_even_price_cents(&activities), Some(10_100));
+    }
+
+    #[test]
+    fn test_break_even_converts_foreign_fees() {
+        use TradingActivityType::*;
+        let mut buy = activity(1, "2024-01-02", Buy, 10.0, 10_000, 1_000);
+        buy.fee_currency = Some("EUR".into());
+        buy.exchange_rate = Some(1.1);
+
+        // (100_000 cost + 1_000 EUR at 1.1) / 10 shares
+        assert_eq!(break_even_price_cents(&[buy]), Some(10_110));
+    }
+
+    #[test]
+    fn test_closed_position_resets_carried_costs() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000, 1_000),
+            activity(2, "2024-01-03", Sell, 10.0, 12_000, 1_000),
+            activity(3, "2024-02-01", Buy, 4.0, 5_000, 0),
+        ];
+
+        assert_eq!(break_even_price_cents(&activities), Some(5_000));
+        assert_eq!(break_even_price_cents(&activities[..2]), None);
+    }
+
+    #[test]
+    fn test_split_does_not_rescale_adjusted_quantities() {
+        use TradingActivityType::*;
+        // Buy of 10 @ 100 before a 2:1 split, stored as 20 @ 50
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 20.0, 5_000, 0),
+            activity(2, "2024-03-01", Split, 2.0, 0, 0),
+        ];
+
+        let steps = cost_basis_steps(&activities);
+        assert_eq!(steps.last().unwrap().average_cost_cents(), Some(5_000));
+    }
+
+    #[test]
+    fn test_realized_sales_use_average_cost() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000, 0),
+            activity(2, "2024-01-04", Buy, 10.0, 20_000, 0),
+            activity(3, "2024-01-05", TransferOut, 4.0, 0, 0),
+            activity(4, "2024-01-06", Sell, 6.0, 30_000, 500),
+        ];
+
+        assert_eq!(
+            realized_sales(&activities),
+            vec![RealizedSale {
+                activity_id: 4,
+                date: "2024-01-06".into(),
+                quantity: 6.0,
+                proceeds_cents: 180_000,
+                cost_basis_cents: 90_000,
+                fee_cents: 500,
+                gain_cents: 89_500,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_realized_sales_include_fees_and_taxes() {
+        use TradingActivityType::*;
+        let activities = vec![
+            activity(1, "2024-01-02", Buy, 10.0, 10_000, 1_000),
+            activity(2, "2024-02-01", Fee, 1.0, 600, 0),
+            activity(3, "2024-02-15", Tax, 1.0, 400, 0),
+            activity(4, "2024-03-01", Dividend, 1.0, 5_000, 0),
+            activity(5, "2024-04-01", Sell, 5.0, 12_000, 300),
+            activity(6, "2024-05-01", Sell, 5.0, 12_000, 300),
+        ];
+
+        let sales = realized_sales(&activities);
+        // Each half carries half of the 1_000 buy fee, 600 fee and 400 tax
+        assert_eq!(sales[0].fee_cents, 300 + 1_000);
+        assert_eq!(sales[0].gain_cents, 60_000 - 50_000 - 1_300);
+        assert_eq!(sales[1].fee_cents, 300 + 1_000);
+        // Dividends count toward break-even, not toward the sale
+        assert_eq!(
+            cost_basis_steps(&activities[..4])
+                .last()
+                .unwrap()
+                .carried_cents,
+            1_000 + 600 + 400 - 5_000
+        );
+    }
+}
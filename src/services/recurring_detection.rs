@@ -0,0 +1,656 @@
+//! Detection of recurring expenses such as subscriptions.
+//!
+//! Expenses are grouped by counterparty: IBAN if known, else payee, else a
+//! normalized description. A group with at least three charges of about the
+//! same amount at a regular interval is recurring. Its cadence is derived
+//! from the median interval, and its cost is normalized to a monthly and an
+//! annual equivalent so that subscriptions of different cadences add up.
+
+use std::collections::HashMap;
+
+use chrono::{Months, NaiveDate};
+
+use crate::db::queries::transaction_sums;
+use crate::filters::{self, CurrencyFormat};
+
+/// Frequency classification for recurring expenses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Weekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Frequency {
+    pub fn label(self) -> &'static str {
+        match self {
+            Frequency::Weekly => "Weekly",
+            Frequency::Monthly => "Monthly",
+            Frequency::Quarterly => "Quarterly",
+            Frequency::Yearly => "Yearly",
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Quarterly => "quarterly",
+            Frequency::Yearly => "yearly",
+        }
+    }
+
+    fn annual_multiplier(self) -> i64 {
+        match self {
+            Frequency::Weekly => 52,
+            Frequency::Monthly => 12,
+            Frequency::Quarterly => 4,
+            Frequency::Yearly => 1,
+        }
+    }
+
+    fn sort_order(self) -> u8 {
+        match self {
+            Frequency::Weekly => 1,
+            Frequency::Monthly => 2,
+            Frequency::Quarterly => 3,
+            Frequency::Yearly => 4,
+        }
+    }
+
+    /// Approximate length of one cycle in days.
+    fn cycle_days(self) -> i64 {
+        match self {
+            Frequency::Weekly => 7,
+            Frequency::Monthly => 30,
+            Frequency::Quarterly => 91,
+            Frequency::Yearly => 365,
+        }
+    }
+
+    /// The date one cycle after `date`, keeping the day of the month for
+    /// monthly and longer cadences.
+    fn next_date(self, date: NaiveDate) -> NaiveDate {
+        let months = match self {
+            Frequency::Weekly => return date + chrono::Duration::days(7),
+            Frequency::Monthly => 1,
+            Frequency::Quarterly => 3,
+            Frequency::Yearly => 12,
+        };
+        date.checked_add_months(Months::new(months)).unwrap_or(date)
+    }
+}
+
+/// Classify a median day-interval into a frequency bucket.
+fn classify_interval(median_days: i64) -> Option<Frequency> {
+    match median_days {
+        5..=9 => Some(Frequency::Weekly),
+        28..=35 => Some(Frequency::Monthly),
+        85..=100 => Some(Frequency::Quarterly),
+        350..=380 => Some(Frequency::Yearly),
+        _ => None,
+    }
+}
+
+/// A detected recurring expense ready for display.
+#[derive(Clone)]
+pub struct RecurringExpense {
+    pub description: String,
+    /// Text to search the transactions for this expense with.
+    pub search_term: String,
+    /// Whether `search_term` is a payee rather than a description.
+    pub search_by_payee: bool,
+    pub frequency: Frequency,
+    pub frequency_label: String,
+    /// Sort order for frequency (1=weekly, 2=monthly, 3=quarterly, 4=yearly).
+    pub frequency_sort: u8,
+    pub typical_amount_cents: i64,
+    pub typical_amount_formatted: String,
+    pub last_date: String,
+    /// When the next charge is due if the cadence holds.
+    pub next_expected_date: String,
+    pub monthly_cost_cents: i64,
+    pub monthly_cost_formatted: String,
+    pub annual_cost_cents: i64,
+    pub annual_cost_formatted: String,
+    pub total_spent_cents: i64,
+    pub total_spent_formatted: String,
+    pub occurrence_count: usize,
+    /// True when the last occurrence is more than 365 days ago.
+    pub inactive: bool,
+    /// True when no charge came for more than one and a half cycles.
+    pub possibly_cancelled: bool,
+}
+
+impl RecurringExpense {
+    /// The transactions list filtered to this expense.
+    pub fn transactions_url(&self) -> String {
+        let field = if self.search_by_payee {
+            "payee"
+        } else {
+            "search"
+        };
+        format!(
+            "/transactions?{}={}",
+            field,
+            urlencoding::encode(&self.search_term)
+        )
+    }
+}
+
+/// Normalize a description for grouping: lowercase, strip non-alphanumeric,
+/// trim, and remove trailing numbers (invoice numbers, dates).
+fn normalize_description(desc: &str) -> String {
+    let normalized: String = desc
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect();
+    let trimmed = normalized.trim();
+    trimmed
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == ' ')
+        .trim()
+        .to_string()
+}
+
+/// Compute the grouping key for a transaction.
+/// Priority: IBAN > payee > normalized description.
+fn grouping_key(iban: &Option<String>, payee: &Option<String>, description: &str) -> String {
+    if let Some(ref iban) = iban {
+        let trimmed = iban.trim();
+        if !trimmed.is_empty() {
+            return format!("iban:{}", trimmed.to_uppercase());
+        }
+    }
+    if let Some(ref payee) = payee {
+        let trimmed = payee.trim();
+        if !trimmed.is_empty() {
+            return format!("payee:{}", normalize_description(trimmed));
+        }
+    }
+    format!("desc:{}", normalize_description(description))
+}
+
+struct GroupEntry {
+    date: NaiveDate,
+    amount_cents: i64,
+    display_name: String,
+    /// Whether `display_name` is the payee.
+    from_payee: bool,
+}
+
+/// Merge groups whose normalized keys share a common prefix.
+/// For example, "desc:spotify" and "desc:spotifysweden" get merged under
+/// the shorter key.  Only applies to non-IBAN keys with a minimum length.
+fn merge_prefix_groups(
+    groups: HashMap<String, Vec<GroupEntry>>,
+) -> HashMap<String, Vec<GroupEntry>> {
+    // Separate IBAN groups (never merged) from text groups
+    let mut iban_groups: HashMap<String, Vec<GroupEntry>> = HashMap::new();
+    let mut text_groups: HashMap<String, Vec<GroupEntry>> = HashMap::new();
+
+    for (key, entries) in groups {
+        if key.starts_with("iban:") {
+            iban_groups.insert(key, entries);
+        } else {
+            text_groups.insert(key, entries);
+        }
+    }
+
+    // Sort text keys by length (shortest first) for prefix merging
+    let mut keys: Vec<String> = text_groups.keys().cloned().collect();
+    keys.sort_by_key(|k| k.len());
+
+    // Map from original key -> canonical (merged) key
+    let mut canonical: HashMap<String, String> = HashMap::new();
+    for key in &keys {
+        canonical.insert(key.clone(), key.clone());
+    }
+
+    // Extract the raw suffix after "desc:" or "payee:"
+    fn raw_key(k: &str) -> &str {
+        k.split_once(':').map_or(k, |(_, rest)| rest)
+    }
+
+    const MIN_PREFIX_LEN: usize = 5;
+
+    for i in 0..keys.len() {
+        let short_raw = raw_key(&keys[i]);
+        if short_raw.len() < MIN_PREFIX_LEN {
+            continue;
+        }
+        for j in (i + 1)..keys.len() {
+            // Only merge keys of the same type prefix
+            let short_type = keys[i].split_once(':').map(|(t, _)| t);
+            let long_type = keys[j].split_once(':').map(|(t, _)| t);
+            if short_type != long_type {
+                continue;
+            }
+            let long_raw = raw_key(&keys[j]);
+            if long_raw.starts_with(short_raw) {
+                let canon_j = canonical[&keys[j]].clone();
+                let canon_i = canonical[&keys[i]].clone();
+                // Repoint j (and anything already pointing to j) to i's canonical
+                for val in canonical.values_mut() {
+                    if *val == canon_j {
+                        *val = canon_i.clone();
+                    }
+                }
+            }
+        }
+    }
+
+    // Rebuild text groups according to canonical mapping
+    let mut merged: HashMap<String, Vec<GroupEntry>> = HashMap::new();
+    for (key, entries) in text_groups {
+        let canon = canonical.get(&key).unwrap_or(&key).clone();
+        merged.entry(canon).or_default().extend(entries);
+    }
+
+    // Re-add IBAN groups
+    merged.extend(iban_groups);
+    merged
+}
+
+/// Compute the median of a sorted i64 slice.
+fn median(sorted: &[i64]) -> i64 {
+    let n = sorted.len();
+    if n == 0 {
+        return 0;
+    }
+    if n.is_multiple_of(2) {
+        (sorted[n / 2 - 1] + sorted[n / 2]) / 2
+    } else {
+        sorted[n / 2]
+    }
+}
+
+/// Search text for a display name: the name without trailing numbers, such
+/// as invoice numbers, so that it matches every charge of the group.
+fn search_term(display_name: &str) -> String {
+    let trimmed = display_name
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == ' ')
+        .trim();
+    if trimmed.is_empty() {
+        display_name.trim().to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Detect recurring expenses from raw transaction data.
+pub fn detect_recurring_expenses(
+    rows: Vec<transaction_sums::ExpenseRow>,
+    format: &CurrencyFormat,
+    locale: &str,
+    today: NaiveDate,
+) -> Vec<RecurringExpense> {
+    // Group by key
+    let mut groups: HashMap<String, Vec<GroupEntry>> = HashMap::new();
+
+    for row in &rows {
+        let key = grouping_key(&row.counterparty_iban, &row.payee, &row.description);
+        if let Ok(date) = NaiveDate::parse_from_str(&row.date, "%Y-%m-%d") {
+            let payee = row.payee.as_deref().filter(|p| !p.trim().is_empty());
+            groups.entry(key).or_default().push(GroupEntry {
+                date,
+                amount_cents: row.amount_cents,
+                display_name: payee.unwrap_or(&row.description).to_string(),
+                from_payee: payee.is_some(),
+            });
+        }
+    }
+
+    // Merge groups with similar prefixes
+    let groups = merge_prefix_groups(groups);
+
+    let mut results = Vec::new();
+    let money = |cents: i64| filters::format_money_neutral_with(cents, format, locale);
+
+    for (_key, mut entries) in groups {
+        if entries.len() < 3 {
+            continue;
+        }
+
+        entries.sort_by_key(|e| e.date);
+
+        // Compute median absolute amount
+        let mut sorted_amounts: Vec<i64> = entries.iter().map(|e| e.amount_cents.abs()).collect();
+        sorted_amounts.sort();
+        let median_amount = median(&sorted_amounts);
+
+        // Filter entries within 5% tolerance of median amount
+        let tolerance = (median_amount as f64 * 0.05).max(100.0) as i64;
+        let filtered: Vec<&GroupEntry> = entries
+            .iter()
+            .filter(|e| (e.amount_cents.abs() - median_amount).abs() <= tolerance)
+            .collect();
+
+        if filtered.len() < 3 {
+            continue;
+        }
+
+        // Compute intervals between consecutive dates
+        let dates: Vec<NaiveDate> = filtered.iter().map(|e| e.date).collect();
+        let mut intervals: Vec<i64> = dates.windows(2).map(|w| (w[1] - w[0]).num_days()).collect();
+
+        if intervals.is_empty() {
+            continue;
+        }
+
+        intervals.sort();
+        let median_interval = median(&intervals);
+
+        if let Some(frequency) = classify_interval(median_interval) {
+            let total_spent: i64 = filtered.iter().map(|e| e.amount_cents.abs()).sum();
+            let annual_cost = median_amount * frequency.annual_multiplier();
+            let monthly_cost = annual_cost / 12;
+            let latest = filtered.last().unwrap();
+            let description = latest.display_name.clone();
+            let last_date = *dates.last().unwrap();
+            let days_since = (today - last_date).num_days();
+            let inactive = days_since > 365;
+            let possibly_cancelled = days_since * 2 > frequency.cycle_days() * 3;
+
+            results.push(RecurringExpense {
+                search_term: search_term(&description),
+                search_by_payee: latest.from_payee,
+                description,
+                frequency,
+                frequency_label: frequency.label().to_string(),
+                frequency_sort: frequency.sort_order(),
+                typical_amount_cents: median_amount,
+                typical_amount_formatted: money(median_amount),
+                last_date: last_date.format("%Y-%m-%d").to_string(),
+                next_expected_date: frequency
+                    .next_date(last_date)
+                    .format("%Y-%m-%d")
+                    .to_string(),
+                monthly_cost_cents: monthly_cost,
+                monthly_cost_formatted: money(monthly_cost),
+                annual_cost_cents: annual_cost,
+                annual_cost_formatted: money(annual_cost),
+                total_spent_cents: total_spent,
+                total_spent_formatted: money(total_spent),
+                occurrence_count: filtered.len(),
+                inactive,
+                possibly_cancelled,
+            });
+        }
+    }
+
+    // Sort by estimated annual cost descending
+    results.sort_by_key(|s| std::cmp::Reverse(s.annual_cost_cents));
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_description() {
+        assert_eq!(normalize_description("Spotify AB"), "spotify ab");
+        assert_eq!(normalize_description("NETFLIX.COM 12345"), "netflixcom");
+        assert_eq!(normalize_description("  Hello World  "), "hello world");
+    }
+
+    #[test]
+    fn test_classify_interval() {
+        assert_eq!(classify_interval(7), Some(Frequency::Weekly));
+        assert_eq!(classify_interval(30), Some(Frequency::Monthly));
+        assert_eq!(classify_interval(91), Some(Frequency::Quarterly));
+        assert_eq!(classify_interval(365), Some(Frequency::Yearly));
+        assert_eq!(classify_interval(15), None);
+        assert_eq!(classify_interval(200), None);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[1, 2, 3]), 2);
+        assert_eq!(median(&[1, 2, 3, 4]), 2);
+        assert_eq!(median(&[10, 20, 30, 40, 50]), 30);
+        assert_eq!(median(&[]), 0);
+    }
+
+    #[test]
+    fn test_grouping_key_prefers_iban() {
+        let key = grouping_key(
+            &Some("DE89370400440532013000".to_string()),
+            &Some("Payee".to_string()),
+            "Description",
+        );
+        assert!(key.starts_with("iban:"));
+    }
+
+    #[test]
+    fn test_grouping_key_falls_back_to_payee() {
+        let key = grouping_key(&None, &Some("Netflix".to_string()), "NETFLIX.COM");
+        assert!(key.starts_with("payee:"));
+    }
+
+    #[test]
+    fn test_grouping_key_falls_back_to_description() {
+        let key = grouping_key(&None, &None, "NETFLIX.COM");
+        assert!(key.starts_with("desc:"));
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 12, 1).unwrap()
+    }
+
+    #[test]
+    fn test_detect_monthly_subscription() {
+        let rows: Vec<transaction_sums::ExpenseRow> = (0..6)
+            .map(|i| transaction_sums::ExpenseRow {
+                date: format!("2024-{:02}-15", i + 1),
+                amount_cents: -999,
+                description: "Spotify AB".to_string(),
+                payee: None,
+                counterparty_iban: None,
+            })
+            .collect();
+
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].frequency_label, "Monthly");
+        assert_eq!(results[0].occurrence_count, 6);
+        assert!(!results[0].inactive);
+    }
+
+    #[test]
+    fn test_inactive_subscription() {
+        // Last occurrence 2023-06-15, which is >365 days before 2024-12-01
+        let rows: Vec<transaction_sums::ExpenseRow> = (0..6)
+            .map(|i| transaction_sums::ExpenseRow {
+                date: format!("2023-{:02}-15", i + 1),
+                amount_cents: -999,
+                description: "Old Service".to_string(),
+                payee: None,
+                counterparty_iban: None,
+            })
+            .collect();
+
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert_eq!(results.len(), 1);
+        assert!(results[0].inactive);
+    }
+
+    #[test]
+    fn test_too_few_occurrences_not_detected() {
+        let rows = vec![
+            transaction_sums::ExpenseRow {
+                date: "2024-01-15".to_string(),
+                amount_cents: -999,
+                description: "One-off".to_string(),
+                payee: None,
+                counterparty_iban: None,
+            },
+            transaction_sums::ExpenseRow {
+                date: "2024-02-15".to_string(),
+                amount_cents: -999,
+                description: "One-off".to_string(),
+                payee: None,
+                counterparty_iban: None,
+            },
+        ];
+
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_irregular_intervals_not_detected() {
+        // Intervals: 14 days, 7 days -> median 10 -> no bucket match
+        let rows = vec![
+            transaction_sums::ExpenseRow {
+                date: "2024-01-01".to_string(),
+                amount_cents: -500,
+                description: "Random".to_string(),
+                payee: None,
+                counterparty_iban: None,
+            },
+            transaction_sums::ExpenseRow {
+                date: "2024-01-15".to_string(),
+                amount_cents: -500,
+                description: "Random".to_string(),
+                payee: None,
+                counterparty_iban: None,
+            },
+            transaction_sums::ExpenseRow {
+                date: "2024-01-22".to_string(),
+                amount_cents: -500,
+                description: "Random".to_string(),
+                payee: None,
+                counterparty_iban: None,
+            },
+        ];
+
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_prefix_merge_groups() {
+        let mut groups: HashMap<String, Vec<GroupEntry>> = HashMap::new();
+        let mk = |d: &str| GroupEntry {
+            date: NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap(),
+            amount_cents: -999,
+            display_name: "test".to_string(),
+            from_payee: false,
+        };
+        groups.insert(
+            "desc:spotify".to_string(),
+            vec![mk("2024-01-01"), mk("2024-02-01")],
+        );
+        groups.insert(
+            "desc:spotifysweden".to_string(),
+            vec![mk("2024-03-01"), mk("2024-04-01")],
+        );
+
+        let merged = merge_prefix_groups(groups);
+        // Should be merged into one group
+        assert_eq!(merged.len(), 1);
+        let entries = merged.values().next().unwrap();
+        assert_eq!(entries.len(), 4);
+    }
+
+    fn history(
+        dates: &[&str],
+        amount_cents: i64,
+        payee: &str,
+    ) -> Vec<transaction_sums::ExpenseRow> {
+        dates
+            .iter()
+            .map(|date| transaction_sums::ExpenseRow {
+                date: date.to_string(),
+                amount_cents,
+                description: format!("{} invoice", payee),
+                payee: Some(payee.to_string()),
+                counterparty_iban: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_monthly_cadence_normalized() {
+        let rows = history(
+            &["2024-08-03", "2024-09-03", "2024-10-03", "2024-11-03"],
+            -1200,
+            "Streamly",
+        );
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].frequency, Frequency::Monthly);
+        assert_eq!(results[0].monthly_cost_cents, 1200);
+        assert_eq!(results[0].annual_cost_cents, 14400);
+        assert_eq!(results[0].next_expected_date, "2024-12-03");
+        assert_eq!(results[0].search_term, "Streamly");
+        assert_eq!(
+            results[0].transactions_url(),
+            "/transactions?payee=Streamly"
+        );
+        assert!(!results[0].possibly_cancelled);
+    }
+
+    #[test]
+    fn test_quarterly_cadence_normalized() {
+        let rows = history(
+            &["2024-01-10", "2024-04-10", "2024-07-10", "2024-10-10"],
+            -3000,
+            "Insurance Co",
+        );
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].frequency, Frequency::Quarterly);
+        assert_eq!(results[0].monthly_cost_cents, 1000);
+        assert_eq!(results[0].next_expected_date, "2025-01-10");
+        assert!(!results[0].possibly_cancelled);
+    }
+
+    #[test]
+    fn test_yearly_cadence_normalized() {
+        let rows = history(
+            &["2021-03-01", "2022-03-01", "2023-03-01", "2024-03-01"],
+            -6000,
+            "Domain",
+        );
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].frequency, Frequency::Yearly);
+        assert_eq!(results[0].monthly_cost_cents, 500);
+        assert_eq!(results[0].next_expected_date, "2025-03-01");
+        assert!(!results[0].possibly_cancelled);
+    }
+
+    #[test]
+    fn test_possibly_cancelled_after_one_and_a_half_cycles() {
+        // Last charge 2024-09-15, 77 days before 2024-12-01, beyond 45 days
+        let rows = history(
+            &["2024-06-15", "2024-07-15", "2024-08-15", "2024-09-15"],
+            -999,
+            "Gym",
+        );
+        let results =
+            detect_recurring_expenses(rows, &CurrencyFormat::for_currency("EUR"), "en-US", today());
+        assert_eq!(results.len(), 1);
+        assert!(results[0].possibly_cancelled);
+        assert!(!results[0].inactive);
+    }
+
+    #[test]
+    fn test_search_term_drops_trailing_numbers() {
+        assert_eq!(search_term("NETFLIX.COM 12345"), "NETFLIX.COM");
+        assert_eq!(search_term("12345"), "12345");
+    }
+}
@@ -0,0 +1,211 @@
+//! Rule drafts suggested from a categorized transaction.
+//!
+//! A suggestion matches the counterparty IBAN if known, else the payee (or
+//! payer for income) up to the first word with digits, such as a store
+//! number, else the most distinctive word of the description: the one that
+//! recurs in the fewest other descriptions.
+
+use std::collections::HashSet;
+
+use crate::models::rule::{normalize_iban, RuleMatchField};
+use crate::models::Transaction;
+
+/// Shortest description word considered for a pattern.
+const MIN_TOKEN_LEN: usize = 3;
+
+/// A prefilled rule: name, pattern and the field it is matched against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSuggestion {
+    pub name: String,
+    pub pattern: String,
+    pub match_field: RuleMatchField,
+}
+
+/// Suggest a rule matching transactions like `transaction`. `descriptions`
+/// are those of all transactions, used to find a description word that is
+/// specific to this counterparty.
+pub fn suggest(transaction: &Transaction, descriptions: &[String]) -> Option<RuleSuggestion> {
+    let counterparty = transaction
+        .counterparty()
+        .map(counterparty_words)
+        .filter(|words| !words.is_empty());
+
+    if let Some(iban) = transaction
+        .counterparty_iban
+        .as_deref()
+        .map(normalize_iban)
+        .filter(|iban| !iban.is_empty())
+    {
+        let name = counterparty
+            .as_ref()
+            .map_or_else(|| iban.clone(), |words| words.join(" "));
+        return Some(RuleSuggestion {
+            name,
+            pattern: format!("^{}$", regex::escape(&iban)),
+            match_field: RuleMatchField::Iban,
+        });
+    }
+
+    if let Some(words) = counterparty {
+        let escaped: Vec<String> = words.iter().map(|w| regex::escape(w)).collect();
+        return Some(RuleSuggestion {
+            name: words.join(" "),
+            pattern: format!("^{}", escaped.join(r"\s+")),
+            match_field: RuleMatchField::Counterparty,
+        });
+    }
+
+    let token = distinctive_token(&transaction.description, descriptions)?;
+    Some(RuleSuggestion {
+        pattern: format!(r"\b{}\b", regex::escape(&token)),
+        name: token,
+        match_field: RuleMatchField::Description,
+    })
+}
+
+/// A counterparty name without trailing store numbers and references, the
+/// form suggestions match on. Names made only of such words are kept whole.
+pub fn counterparty_name(name: &str) -> String {
+    let words = counterparty_words(name);
+    if words.is_empty() {
+        name.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        words.join(" ")
+    }
+}
+
+/// [`counterparty_name`] in lower case, to compare counterparties.
+pub fn counterparty_key(name: &str) -> String {
+    counterparty_name(name).to_lowercase()
+}
+
+/// Leading words of a counterparty name, stopping at the first word with a
+/// digit (store numbers, dates, references).
+fn counterparty_words(name: &str) -> Vec<String> {
+    name.split_whitespace()
+        .take_while(|w| !w.chars().any(|c| c.is_ascii_digit()))
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Words of a description worth matching on: long enough, without digits.
+fn tokens(description: &str) -> Vec<String> {
+    description
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_TOKEN_LEN)
+        .filter(|w| !w.chars().any(|c| c.is_numeric()))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The word of `description` found in the fewest of `descriptions`, counting
+/// only words that occur in at least one other transaction. Ties go to the
+/// longer word. Falls back to the longest word if none recurs.
+fn distinctive_token(description: &str, descriptions: &[String]) -> Option<String> {
+    let mut candidates = tokens(description);
+    let mut seen = HashSet::new();
+    candidates.retain(|t| seen.insert(t.clone()));
+    let others: Vec<HashSet<String>> = descriptions
+        .iter()
+        .map(|d| tokens(d).into_iter().collect())
+        .collect();
+
+    let recurring = candidates
+        .iter()
+        .map(|t| (t, others.iter().filter(|o| o.contains(t)).count()))
+        .filter(|(_, count)| *count > 1)
+        .min_by(|(a, ca), (b, cb)| ca.cmp(cb).then(b.len().cmp(&a.len())))
+        .map(|(t, _)| t.clone());
+
+    recurring.or_else(|| {
+        candidates
+            .iter()
+            .rev()
+            .max_by_key(|t| t.chars().count())
+            .cloned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TransactionStatus;
+
+    fn transaction(description: &str, payee: Option<&str>, iban: Option<&str>) -> Transaction {
+        Transaction {
+            id: 1,
+            date: "2024-03-01".into(),
+            amount_cents: -2_500,
+            currency: "EUR".into(),
+            description: description.into(),
+            category_id: Some(1),
+            account_id: None,
+            notes: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+            value_date: None,
+            payer: None,
+            payee: payee.map(Into::into),
+            reference: None,
+            transaction_type: None,
+            counterparty_iban: iban.map(Into::into),
+            creditor_id: None,
+            mandate_reference: None,
+            customer_reference: None,
+            transfer_pair_id: None,
+            status: TransactionStatus::Posted,
+            auto_categorized: false,
+            auto_category_rule_id: None,
+            source_activity_id: None,
+        }
+    }
+
+    #[test]
+    fn test_prefers_iban_then_payee() {
+        let t = transaction(
+            "Card payment",
+            Some("REWE Markt 1234"),
+            Some("de89 3704 0044"),
+        );
+        let suggestion = suggest(&t, &[]).unwrap();
+        assert_eq!(suggestion.match_field, RuleMatchField::Iban);
+        assert_eq!(suggestion.pattern, "^DE8937040044$");
+        assert_eq!(suggestion.name, "REWE Markt");
+
+        let t = transaction("Card payment", Some("REWE Markt 1234"), None);
+        let suggestion = suggest(&t, &[]).unwrap();
+        assert_eq!(suggestion.match_field, RuleMatchField::Counterparty);
+        assert_eq!(suggestion.pattern, r"^REWE\s+Markt");
+    }
+
+    #[test]
+    fn test_counterparty_key() {
+        assert_eq!(counterparty_key("REWE  Markt 1234 Berlin"), "rewe markt");
+        assert_eq!(counterparty_key("rewe markt"), "rewe markt");
+        assert_eq!(counterparty_name("4711 0815"), "4711 0815");
+    }
+
+    #[test]
+    fn test_description_token_is_distinctive() {
+        let descriptions: Vec<String> = [
+            "CARD PAYMENT NETFLIX.COM 4711",
+            "CARD PAYMENT NETFLIX.COM 4712",
+            "CARD PAYMENT BAKERY",
+            "CARD PAYMENT GROCER",
+        ]
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+        let t = transaction("CARD PAYMENT NETFLIX.COM 4711", None, None);
+        let suggestion = suggest(&t, &descriptions).unwrap();
+        assert_eq!(suggestion.match_field, RuleMatchField::Description);
+        assert_eq!(suggestion.pattern, r"\bnetflix\b");
+
+        // Nothing recurs: the longest word
+        let t = transaction("Fee 12/2024 adjustment", None, None);
+        assert_eq!(suggest(&t, &[]).unwrap().name, "adjustment");
+        assert!(suggest(&transaction("12/2024", None, None), &[]).is_none());
+    }
+}
@@ -0,0 +1,215 @@
+//! Split ratios entered as "shares after" and "shares before".
+//!
+//! A split is stored as a single ratio (new shares per old share), which is
+//! easy to get backwards when typed by hand. The forms ask for both share
+//! counts instead, and [`check_against_prices`] compares the ratio with the
+//! price move in the stored market data. A provider that already adjusts
+//! its history for splits shows no jump, so applying the split would
+//! adjust the activities twice.
+
+use chrono::{Days, NaiveDate};
+
+use crate::models::trading::format_quantity;
+use crate::models::MarketData;
+use crate::services::money;
+
+/// How far around the split date closing prices are looked up.
+pub const PRICE_WINDOW_DAYS: u64 = 7;
+
+/// Largest share count tried when turning a stored ratio back into two
+/// share counts.
+const MAX_SHARES_BEFORE: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitRatio {
+    pub shares_after: f64,
+    pub shares_before: f64,
+}
+
+impl SplitRatio {
+    /// Validate the share counts of a split form.
+    pub fn parse(shares_after: &str, shares_before: &str) -> Result<Self, String> {
+        let parse = |s: &str, label: &str| {
+            money::parse_quantity(s.trim(), money::INPUT_LOCALE)
+                .ok()
+                .filter(|v| v.is_finite() && *v > 0.0)
+                .ok_or_else(|| format!("{} must be a positive number", label))
+        };
+        let split = Self {
+            shares_after: parse(shares_after, "Shares after")?,
+            shares_before: parse(shares_before, "Shares before")?,
+        };
+        if (split.ratio() - 1.0).abs() < 1e-9 {
+            return Err("Shares after and before are equal, which is not a split".into());
+        }
+        Ok(split)
+    }
+
+    /// Recover whole share counts from a stored ratio, e.g. 1.5 becomes
+    /// 3 for 2. Returns `None` if no small enough pair matches.
+    pub fn from_ratio(ratio: f64) -> Option<Self> {
+        if !ratio.is_finite() || ratio <= 0.0 {
+            return None;
+        }
+        (1..=MAX_SHARES_BEFORE).find_map(|before| {
+            let after = (ratio * before as f64).round();
+            let matches = after >= 1.0 && (after / before as f64 - ratio).abs() < 1e-6 * ratio;
+            matches.then_some(Self {
+                shares_after: after,
+                shares_before: before as f64,
+            })
+        })
+    }
+
+    /// New shares per old share, the value stored as the activity quantity.
+    pub fn ratio(&self) -> f64 {
+        self.shares_after / self.shares_before
+    }
+
+    pub fn shares_after_display(&self) -> String {
+        format_quantity(self.shares_after)
+    }
+
+    pub fn shares_before_display(&self) -> String {
+        format_quantity(self.shares_before)
+    }
+
+    /// e.g. "Each 1 share becomes 4; prices divided by 4".
+    pub fn description(&self) -> String {
+        let before = if self.shares_before == 1.0 {
+            "1 share becomes".to_string()
+        } else {
+            format!("{} shares become", self.shares_before_display())
+        };
+        let prices = if self.ratio() > 1.0 {
+            format!("divided by {}", format_quantity(self.ratio()))
+        } else {
+            format!("multiplied by {}", format_quantity(1.0 / self.ratio()))
+        };
+        format!(
+            "Each {} {}; prices {}",
+            before,
+            self.shares_after_display(),
+            prices
+        )
+    }
+}
+
+/// Compare `ratio` with the move between the last close before `date` and
+/// the first close on or after it. Returns a warning when the prices moved
+/// by less than half the split (on a log scale), which suggests they are
+/// already split-adjusted. `prices` may be in any order; `None` when there
+/// is too little data to tell.
+pub fn check_against_prices(prices: &[MarketData], date: NaiveDate, ratio: f64) -> Option<String> {
+    let window = Days::new(PRICE_WINDOW_DAYS);
+    let (from, to) = (
+        date.checked_sub_days(window)?,
+        date.checked_add_days(window)?,
+    );
+    let mut close_before: Option<(NaiveDate, i64)> = None;
+    let mut close_after: Option<(NaiveDate, i64)> = None;
+    for price in prices {
+        let Ok(day) = NaiveDate::parse_from_str(&price.date, "%Y-%m-%d") else {
+            continue;
+        };
+        if price.close_price_cents <= 0 || day < from || day > to {
+            continue;
+        }
+        if day < date {
+            if close_before.is_none_or(|(d, _)| day > d) {
+                close_before = Some((day, price.close_price_cents));
+            }
+        } else if close_after.is_none_or(|(d, _)| day < d) {
+            close_after = Some((day, price.close_price_cents));
+        }
+    }
+    let (_, before) = close_before?;
+    let (_, after) = close_after?;
+
+    let observed = before as f64 / after as f64;
+    if (observed / ratio).ln().abs() <= observed.ln().abs() {
+        return None;
+    }
+    Some(format!(
+        "Prices around this date moved by a factor of {} rather than {}. \
+         The market data may already be adjusted for this split, in which \
+         case recording it would adjust your activities twice.",
+        format_quantity(observed),
+        format_quantity(ratio)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(date: &str, cents: i64) -> MarketData {
+        MarketData {
+            id: 0,
+            symbol: "AAPL".into(),
+            date: date.into(),
+            close_price_cents: cents,
+            currency: "USD".into(),
+            fetched_at: String::new(),
+        }
+    }
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_describe() {
+        let split = SplitRatio::parse("4", "1").unwrap();
+        assert_eq!(split.ratio(), 4.0);
+        assert_eq!(
+            split.description(),
+            "Each 1 share becomes 4; prices divided by 4"
+        );
+
+        let reverse = SplitRatio::parse("1", "10").unwrap();
+        assert_eq!(reverse.ratio(), 0.1);
+        assert_eq!(
+            reverse.description(),
+            "Each 10 shares become 1; prices multiplied by 10"
+        );
+
+        assert!(SplitRatio::parse("0", "1").is_err());
+        assert!(SplitRatio::parse("2", "").is_err());
+        assert!(SplitRatio::parse("3", "3").is_err());
+    }
+
+    #[test]
+    fn test_from_ratio() {
+        assert_eq!(
+            SplitRatio::from_ratio(1.5),
+            Some(SplitRatio {
+                shares_after: 3.0,
+                shares_before: 2.0
+            })
+        );
+        assert_eq!(
+            SplitRatio::from_ratio(1.0 / 3.0).unwrap().shares_before,
+            3.0
+        );
+        assert_eq!(SplitRatio::from_ratio(20.0).unwrap().shares_after, 20.0);
+        assert_eq!(SplitRatio::from_ratio(std::f64::consts::PI), None);
+    }
+
+    #[test]
+    fn test_check_against_prices() {
+        let unadjusted = [close("2024-06-07", 40000), close("2024-06-10", 10200)];
+        assert_eq!(
+            check_against_prices(&unadjusted, day("2024-06-10"), 4.0),
+            None
+        );
+
+        let adjusted = [close("2024-06-07", 10000), close("2024-06-10", 10200)];
+        let warning = check_against_prices(&adjusted, day("2024-06-10"), 4.0).unwrap();
+        assert!(warning.contains("factor of 0.9804 rather than 4"));
+
+        // Prices outside the window don't count
+        let distant = [close("2024-05-01", 10000), close("2024-06-10", 10200)];
+        assert_eq!(check_against_prices(&distant, day("2024-06-10"), 4.0), None);
+    }
+}
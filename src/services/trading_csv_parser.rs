@@ -1,5 +1,8 @@
-use crate::error::AppError;
+use crate::date_utils::DateFormat;
+use crate::error::{AppError, AppResult};
+use crate::models::trading::normalize_fee_currency;
 use crate::models::TradingActivityType;
+use crate::services::money;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +14,12 @@ pub struct ParsedTradingActivity {
     pub unit_price: Option<String>,
     pub currency: String,
     pub fee: Option<String>,
+    /// Currency of `fee` if it differs from `currency`
+    #[serde(default)]
+    pub fee_currency: Option<String>,
+    /// Units of `currency` per unit of `fee_currency`
+    #[serde(default)]
+    pub exchange_rate: Option<String>,
     pub account_id: Option<i64>,
     pub row_number: usize,
 }
@@ -23,6 +32,14 @@ impl ParsedTradingActivity {
             .unwrap_or(&self.activity_type)
     }
 
+    /// Dividends, fees and taxes carry their cash amount in `unit_price`
+    /// and have no quantity.
+    pub fn is_amount_only(&self) -> bool {
+        self.activity_type
+            .parse::<TradingActivityType>()
+            .is_ok_and(|t| t.is_amount_only())
+    }
+
     pub fn quantity_display(&self) -> String {
         self.quantity.clone().unwrap_or_default()
     }
@@ -32,7 +49,11 @@ impl ParsedTradingActivity {
     }
 
     pub fn fee_display(&self) -> String {
-        self.fee.clone().unwrap_or_else(|| "0".to_string())
+        let fee = self.fee.clone().unwrap_or_else(|| "0".to_string());
+        match &self.fee_currency {
+            Some(fee_currency) => format!("{} {}", fee, fee_currency),
+            None => fee,
+        }
     }
 }
 
@@ -42,7 +63,14 @@ pub struct ParseResult {
     pub errors: Vec<String>,
 }
 
-pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
+/// Parse a trading activities CSV. Numbers are read according to `locale`
+/// and stored normalized as `[-]123.45`, dates according to `date_format`
+/// and stored as ISO.
+pub fn parse_csv(
+    content: &[u8],
+    locale: &str,
+    date_format: DateFormat,
+) -> Result<ParseResult, AppError> {
     let content_str =
         std::str::from_utf8(content).map_err(|e| AppError::CsvParse(e.to_string()))?;
 
@@ -69,7 +97,13 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
         .or_else(|| find_column(&headers, "unit_price"))
         .or_else(|| find_column(&headers, "price"));
     let currency_col = find_column(&headers, "currency");
+    let amount_col = find_column(&headers, "amount").or_else(|| find_column(&headers, "total"));
     let fee_col = find_column(&headers, "fee");
+    let fee_currency_col =
+        find_column(&headers, "feeCurrency").or_else(|| find_column(&headers, "fee_currency"));
+    let exchange_rate_col = find_column(&headers, "exchangeRate")
+        .or_else(|| find_column(&headers, "exchange_rate"))
+        .or_else(|| find_column(&headers, "fxRate"));
     let account_id_col = find_column(&headers, "account_id");
 
     let date_col =
@@ -105,6 +139,13 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
             errors.push(format!("Row {}: Missing date", row_number));
             continue;
         }
+        let date = match date_format.parse(&date) {
+            Ok(date) => date.to_string(),
+            Err(e) => {
+                errors.push(format!("Row {}: {}", row_number, e));
+                continue;
+            }
+        };
 
         if symbol.is_empty() {
             errors.push(format!("Row {}: Missing symbol", row_number));
@@ -116,52 +157,62 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
             continue;
         }
 
-        // Validate activity type
-        if activity_type.parse::<TradingActivityType>().is_err() {
-            errors.push(format!(
-                "Row {}: Invalid activity type '{}'",
-                row_number, activity_type
-            ));
-            continue;
-        }
-
-        let quantity = get_optional_field(&record, quantity_col).map(|q| clean_amount(&q));
-        let unit_price = get_optional_field(&record, unit_price_col).map(|p| clean_amount(&p));
-        let fee = get_optional_field(&record, fee_col).map(|f| clean_amount(&f));
-
-        // Validate numeric fields if present
-        if let Some(ref q) = quantity {
-            if q.parse::<f64>().is_err() {
+        // Validate activity type and normalize spellings like "Transfer In"
+        let parsed_type = match activity_type.parse::<TradingActivityType>() {
+            Ok(parsed_type) => parsed_type,
+            Err(_) => {
                 errors.push(format!(
-                    "Row {}: Invalid quantity '{}'",
-                    row_number,
-                    record.get(quantity_col.unwrap()).unwrap_or("")
+                    "Row {}: Invalid activity type '{}'",
+                    row_number, activity_type
                 ));
                 continue;
             }
-        }
+        };
+        let activity_type = parsed_type.as_str().to_string();
 
-        if let Some(ref p) = unit_price {
-            if p.parse::<f64>().is_err() {
-                errors.push(format!(
-                    "Row {}: Invalid unit price '{}'",
-                    row_number,
-                    record.get(unit_price_col.unwrap()).unwrap_or("")
-                ));
+        let quantity = normalize_field(get_optional_field(&record, quantity_col), |q| {
+            money::normalize_decimal(q, locale)
+        });
+        let quantity = match quantity {
+            Ok(quantity) => quantity,
+            Err(raw) => {
+                errors.push(format!("Row {}: Invalid quantity '{}'", row_number, raw));
                 continue;
             }
-        }
+        };
 
-        if let Some(ref f) = fee {
-            if f.parse::<f64>().is_err() {
-                errors.push(format!(
-                    "Row {}: Invalid fee '{}'",
-                    row_number,
-                    record.get(fee_col.unwrap()).unwrap_or("")
-                ));
+        let unit_price = normalize_field(get_optional_field(&record, unit_price_col), |p| {
+            money::parse_amount(p, locale).map(money::format_cents)
+        });
+        let unit_price = match unit_price {
+            Ok(unit_price) => unit_price,
+            Err(raw) => {
+                errors.push(format!("Row {}: Invalid unit price '{}'", row_number, raw));
                 continue;
             }
-        }
+        };
+
+        let amount = normalize_field(get_optional_field(&record, amount_col), |a| {
+            money::parse_amount(a, locale).map(|cents| money::format_cents(cents.abs()))
+        });
+        let amount = match amount {
+            Ok(amount) => amount,
+            Err(raw) => {
+                errors.push(format!("Row {}: Invalid amount '{}'", row_number, raw));
+                continue;
+            }
+        };
+
+        let fee = normalize_field(get_optional_field(&record, fee_col), |f| {
+            money::parse_amount(f, locale).map(money::format_cents)
+        });
+        let fee = match fee {
+            Ok(fee) => fee,
+            Err(raw) => {
+                errors.push(format!("Row {}: Invalid fee '{}'", row_number, raw));
+                continue;
+            }
+        };
 
         let currency = currency_col
             .and_then(|col| record.get(col))
@@ -169,18 +220,55 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
             .filter(|s| !s.is_empty())
             .unwrap_or_else(|| "USD".to_string());
 
+        let exchange_rate = normalize_field(get_optional_field(&record, exchange_rate_col), |r| {
+            money::normalize_decimal(r, locale)
+        });
+        let exchange_rate = match exchange_rate {
+            Ok(exchange_rate) => exchange_rate,
+            Err(raw) => {
+                errors.push(format!(
+                    "Row {}: Invalid exchange rate '{}'",
+                    row_number, raw
+                ));
+                continue;
+            }
+        };
+        let fee_currency = get_optional_field(&record, fee_currency_col);
+        let rate = exchange_rate.as_deref().and_then(|r| r.parse().ok());
+        let (fee_currency, exchange_rate) =
+            match normalize_fee_currency(&currency, fee_currency.as_deref(), rate) {
+                Ok((fee_currency, rate)) => (fee_currency, rate.and(exchange_rate)),
+                Err(e) => {
+                    errors.push(format!("Row {}: {}", row_number, e));
+                    continue;
+                }
+            };
+
         let account_id = account_id_col
             .and_then(|col| record.get(col))
             .map(|s| s.trim())
             .filter(|s| !s.is_empty())
             .and_then(|s| s.parse::<i64>().ok());
 
-        // Only BUY, SELL, and SPLIT use quantity - clear it for other activity types
-        let quantity = match activity_type.as_str() {
-            "BUY" | "SELL" | "SPLIT" => quantity,
-            _ => None,
+        // Only activities that move shares (and splits) use quantity - clear it for the rest
+        let quantity = if parsed_type.affects_holdings() {
+            quantity
+        } else {
+            None
+        };
+        // Amount-only activities store their cash amount as the unit price;
+        // the type already says which way the money moves, so signs are dropped
+        let unit_price = if parsed_type.is_amount_only() {
+            unit_price.or(amount)
+        } else {
+            unit_price
         };
 
+        if let Err(e) = check_required_fields(parsed_type, &quantity, &unit_price) {
+            errors.push(format!("Row {}: {}", row_number, e));
+            continue;
+        }
+
         activities.push(ParsedTradingActivity {
             date,
             symbol,
@@ -189,6 +277,8 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
             unit_price,
             currency,
             fee,
+            fee_currency,
+            exchange_rate,
             account_id,
             row_number,
         });
@@ -197,6 +287,41 @@ pub fn parse_csv(content: &[u8]) -> Result<ParseResult, AppError> {
     Ok(ParseResult { activities, errors })
 }
 
+/// Check that a row has the fields its activity type needs.
+fn check_required_fields(
+    activity_type: TradingActivityType,
+    quantity: &Option<String>,
+    unit_price: &Option<String>,
+) -> Result<(), String> {
+    let name = activity_type.as_str();
+    if (activity_type.is_acquisition() || activity_type.is_disposal()) && quantity.is_none() {
+        return Err(format!("{} needs a quantity", name));
+    }
+    if matches!(
+        activity_type,
+        TradingActivityType::Buy | TradingActivityType::Sell
+    ) && unit_price.is_none()
+    {
+        return Err(format!("{} needs a unit price", name));
+    }
+    if activity_type.is_amount_only() && unit_price.is_none() {
+        return Err(format!("{} needs an amount", name));
+    }
+    Ok(())
+}
+
+/// Whether a CSV export looks like trading activities rather than bank
+/// transactions, judged by the presence of a symbol column in its header.
+pub fn looks_like_trading_csv(content: &[u8]) -> bool {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content);
+    reader
+        .headers()
+        .is_ok_and(|headers| find_column(headers, "symbol").is_some())
+}
+
 fn find_column(headers: &csv::StringRecord, name: &str) -> Option<usize> {
     headers
         .iter()
@@ -209,41 +334,12 @@ fn get_optional_field(record: &csv::StringRecord, col: Option<usize>) -> Option<
         .filter(|s| !s.is_empty())
 }
 
-fn clean_amount(amount: &str) -> String {
-    // Determine which character is the decimal separator:
-    // If both . and , appear, the last one is the decimal separator
-    let last_dot = amount.rfind('.');
-    let last_comma = amount.rfind(',');
-
-    let decimal_char = match (last_dot, last_comma) {
-        (Some(d), Some(c)) => {
-            if d > c {
-                Some('.')
-            } else {
-                Some(',')
-            }
-        }
-        (Some(_), None) => Some('.'),
-        (None, Some(_)) => Some(','),
-        (None, None) => None,
-    };
-
-    let mut result = String::new();
-    let mut has_decimal = false;
-
-    for c in amount.chars() {
-        if c.is_ascii_digit() {
-            result.push(c);
-        } else if Some(c) == decimal_char && !has_decimal {
-            result.push('.');
-            has_decimal = true;
-        } else if c == '-' && result.is_empty() {
-            result.push(c);
-        }
-        // Skip thousand separators and currency symbols
-    }
-
-    result
+/// Normalize an optional numeric field, returning the raw value if invalid.
+fn normalize_field(
+    value: Option<String>,
+    normalize: impl Fn(&str) -> AppResult<String>,
+) -> Result<Option<String>, String> {
+    value.map(|v| normalize(&v).map_err(|_| v)).transpose()
 }
 
 #[cfg(test)]
@@ -254,7 +350,7 @@ mod tests {
     fn test_parse_simple_csv() {
         let csv = b"date,symbol,activityType,quantity,unitPrice,currency,fee\n2024-01-15,AAPL,BUY,10,150.00,USD,5.00\n2024-01-16,AAPL,DIVIDEND,100,2.50,USD,";
 
-        let result = parse_csv(csv).unwrap();
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
         assert_eq!(result.activities.len(), 2);
         assert_eq!(result.errors.len(), 0);
 
@@ -271,10 +367,89 @@ mod tests {
     }
 
     #[test]
-    fn test_clean_amount() {
-        assert_eq!(clean_amount("$50.00"), "50.00");
-        assert_eq!(clean_amount("-$25.50"), "-25.50");
-        assert_eq!(clean_amount("1,234.56"), "1234.56");
-        assert_eq!(clean_amount("100"), "100");
+    fn test_parse_transfer_types() {
+        let csv = b"date,symbol,activityType,quantity,unitPrice\n2024-01-15,AAPL,Transfer In,10,120.00\n2024-02-01,AAPL,remove_holding,2,";
+
+        let result = parse_csv(csv, "en-US", DateFormat::Iso).unwrap();
+        assert_eq!(result.errors.len(), 0);
+        assert_eq!(result.activities[0].activity_type, "TRANSFER_IN");
+        assert_eq!(result.activities[0].quantity, Some("10".to_string()));
+        assert_eq!(result.activities[1].activity_type, "REMOVE_HOLDING");
+        assert_eq!(result.activities[1].quantity, Some("2".to_string()));
+    }
+
+    #[test]
+    fn test_parse_amount_only_rows() {
+        let csv = "date,symbol,type,quantity,unitPrice,amount,fee\n\
+                   2024-03-01,AAPL,Dividend,,,12.40,\n\
+                   2024-03-01,AAPL,Tax,,,-1.86,\n\
+                   2024-03-05,AAPL,Fee,,2.00,,\n\
+                   2024-03-06,AAPL,Fee,,,,\n\
+                   2024-03-07,AAPL,Buy,5,,,\n\
+                   2024-03-08,AAPL,Sell,,170.00,,";
+        let result = parse_csv(csv.as_bytes(), "en-US", DateFormat::Iso).unwrap();
+
+        assert_eq!(result.activities.len(), 3);
+        assert_eq!(result.activities[0].unit_price.as_deref(), Some("12.40"));
+        assert_eq!(result.activities[0].quantity, None);
+        assert!(result.activities[0].is_amount_only());
+        assert_eq!(result.activities[1].unit_price.as_deref(), Some("1.86"));
+        assert_eq!(result.activities[2].unit_price.as_deref(), Some("2.00"));
+        assert_eq!(
+            result.errors,
+            [
+                "Row 5: FEE needs an amount",
+                "Row 6: BUY needs a unit price",
+                "Row 7: SELL needs a quantity",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fee_currency() {
+        let csv = "date,symbol,activityType,quantity,unitPrice,currency,fee,feeCurrency,fxRate\n\
+                   2024-01-15,AAPL,BUY,10,150.00,USD,5.00,eur,1.08\n\
+                   2024-01-16,AAPL,BUY,10,150.00,USD,5.00,USD,1.08\n\
+                   2024-01-17,AAPL,BUY,10,150.00,USD,5.00,EUR,\n\
+                   2024-01-18,AAPL,BUY,10,150.00,USD,5.00,EUR,-1";
+        let result = parse_csv(csv.as_bytes(), "en-US", DateFormat::Iso).unwrap();
+        assert_eq!(result.activities.len(), 3);
+        assert_eq!(result.activities[0].fee_currency.as_deref(), Some("EUR"));
+        assert_eq!(result.activities[0].exchange_rate.as_deref(), Some("1.08"));
+        assert_eq!(result.activities[0].fee_display(), "5.00 EUR");
+        assert_eq!(result.activities[1].fee_currency, None);
+        assert_eq!(result.activities[1].exchange_rate, None);
+        assert_eq!(result.activities[2].fee_currency.as_deref(), Some("EUR"));
+        assert_eq!(result.activities[2].exchange_rate, None);
+        assert_eq!(result.errors, ["Row 5: Invalid exchange rate"]);
+    }
+
+    #[test]
+    fn test_looks_like_trading_csv() {
+        assert!(looks_like_trading_csv(
+            b"Date,Symbol,Type,Quantity\n2024-01-15,AAPL,BUY,10"
+        ));
+        assert!(!looks_like_trading_csv(
+            b"date,description,amount\n2024-01-15,Coffee,-3.50"
+        ));
+        assert!(!looks_like_trading_csv(b""));
+    }
+
+    #[test]
+    fn test_parse_number_formats() {
+        let csv = "date,symbol,activity_type,quantity,unit_price,fee\n\
+                   2024-01-15,AAPL,BUY,\"1,000\",$150.005,(0.50)\n\
+                   2024-01-16,AAPL,BUY,0.5,abc,0";
+        let result = parse_csv(csv.as_bytes(), "en-US", DateFormat::Iso).unwrap();
+        assert_eq!(result.activities.len(), 1);
+        assert_eq!(result.activities[0].quantity.as_deref(), Some("1000"));
+        assert_eq!(result.activities[0].unit_price.as_deref(), Some("150.01"));
+        assert_eq!(result.activities[0].fee.as_deref(), Some("-0.50"));
+        assert_eq!(result.errors, ["Row 3: Invalid unit price 'abc'"]);
+
+        let csv = "date,symbol,activity_type,quantity,unit_price\n2024-01-15,SAP,BUY,\"2,5\",\"1.234,56\"";
+        let result = parse_csv(csv.as_bytes(), "de-DE", DateFormat::Iso).unwrap();
+        assert_eq!(result.activities[0].quantity.as_deref(), Some("2.5"));
+        assert_eq!(result.activities[0].unit_price.as_deref(), Some("1234.56"));
     }
 }
@@ -0,0 +1,91 @@
+//! Transactions mirroring dividend, fee and tax activities, so their cash
+//! shows up in income and spending analytics.
+
+use rusqlite::Connection;
+
+use crate::db::queries::{accounts, settings, trading, transactions};
+use crate::error::AppResult;
+use crate::models::transaction::{NewTransaction, TransactionStatus};
+use crate::models::{Settings, TradingActivity, TradingActivityType};
+use crate::services::cash_ledger::activity_cash_cents;
+
+/// Whether activities of this type are mirrored into transactions.
+pub fn is_mirrored(activity_type: TradingActivityType) -> bool {
+    matches!(
+        activity_type,
+        TradingActivityType::Dividend | TradingActivityType::Fee | TradingActivityType::Tax
+    )
+}
+
+/// The transaction mirroring `activity`, booked to the cash account linked
+/// to its securities account, if any.
+fn mirror_transaction(
+    conn: &Connection,
+    settings: &Settings,
+    activity: &TradingActivity,
+) -> AppResult<NewTransaction> {
+    let account_id = match activity.account_id {
+        Some(id) => accounts::get_account(conn, id)?.and_then(|a| a.cash_account_id),
+        None => None,
+    };
+    Ok(NewTransaction {
+        date: activity.date.clone(),
+        amount_cents: activity_cash_cents(
+            activity.activity_type,
+            activity.quantity,
+            activity.unit_price_cents,
+            activity.fee_converted_cents(),
+        ),
+        currency: activity.currency.clone(),
+        description: format!("{} {}", activity.activity_type.label(), activity.symbol),
+        category_id: settings.mirror_category(activity.activity_type),
+        account_id,
+        notes: activity.notes.clone(),
+        tag_ids: Vec::new(),
+        value_date: None,
+        payer: None,
+        payee: None,
+        reference: None,
+        transaction_type: None,
+        counterparty_iban: None,
+        creditor_id: None,
+        mandate_reference: None,
+        customer_reference: None,
+        status: TransactionStatus::Posted,
+    })
+}
+
+/// Bring the mirrored transaction of an activity in line with it after the
+/// activity was created, edited, deleted or restored. Date, amount and
+/// description follow the activity; category, tags and notes are left to
+/// the user. Existing mirrors keep following after `mirror_trading_cash` is
+/// turned off. Run it inside the database transaction that changed the
+/// activity.
+pub fn sync(conn: &Connection, activity_id: i64) -> AppResult<()> {
+    let existing = transactions::find_by_source_activity(conn, activity_id)?;
+    let activity =
+        trading::get_activity(conn, activity_id)?.filter(|a| is_mirrored(a.activity_type));
+
+    let Some(activity) = activity else {
+        // Deleted, or no longer a cash event
+        if let Some(id) = existing {
+            transactions::delete_transaction(conn, id)?;
+        }
+        return Ok(());
+    };
+
+    let settings = settings::get_settings(conn)?;
+    match existing {
+        Some(id) => {
+            let mirror = mirror_transaction(conn, &settings, &activity)?;
+            transactions::update_mirrored(conn, id, &mirror)?;
+        }
+        None if settings.mirror_trading_cash => {
+            let mirror = mirror_transaction(conn, &settings, &activity)?;
+            let id = transactions::create_transaction(conn, &mirror)?;
+            transactions::set_source_activity(conn, id, activity_id)?;
+        }
+        None => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,276 @@
+//! Transfers between own accounts, found by counterparty IBAN.
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::Connection;
+use serde::Serialize;
+use tracing::info;
+
+use crate::date_utils::parse_iso_date;
+use crate::db::queries::transactions::UnpairedRow;
+use crate::db::queries::{accounts, categories, transactions};
+use crate::error::AppResult;
+use crate::models::rule::normalize_iban;
+use crate::models::Account;
+
+/// Days the two sides of a transfer may be booked apart.
+pub const DATE_TOLERANCE_DAYS: i64 = 3;
+
+/// Two transactions linked as one transfer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransferPair {
+    pub outgoing_id: i64,
+    pub incoming_id: i64,
+    pub amount_cents: i64,
+    pub currency: String,
+}
+
+/// A transaction paying another own account, without a counterpart there.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnmatchedTransfer {
+    pub id: i64,
+    pub date: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub account_id: i64,
+    pub counterpart_account_id: i64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TransferDetection {
+    pub pairs: Vec<TransferPair>,
+    pub unmatched: Vec<UnmatchedTransfer>,
+}
+
+fn days_apart(a: &str, b: &str) -> Option<i64> {
+    Some((parse_iso_date(a)? - parse_iso_date(b)?).num_days().abs())
+}
+
+/// Pair the `rows` (unpaired transactions, ordered by date) whose
+/// counterparty IBAN belongs to another of the `accounts` with a row of the
+/// opposite amount and currency in that account. The closest date within
+/// [`DATE_TOLERANCE_DAYS`] wins. Rows without a counterpart, usually because
+/// the other statement is not imported yet, end up in `unmatched`.
+pub fn find_transfers(rows: &[UnpairedRow], accounts: &[Account]) -> TransferDetection {
+    let account_by_iban: HashMap<&str, i64> = accounts
+        .iter()
+        .filter_map(|a| Some((a.iban.as_deref()?, a.id)))
+        .collect();
+    let iban_of_account: HashMap<i64, &str> = account_by_iban
+        .iter()
+        .map(|(iban, id)| (*id, *iban))
+        .collect();
+
+    // Possible counterparts by account, amount and currency
+    let mut by_key: HashMap<(i64, i64, &str), Vec<&UnpairedRow>> = HashMap::new();
+    for row in rows {
+        by_key
+            .entry((row.account_id, row.amount_cents, row.currency.as_str()))
+            .or_default()
+            .push(row);
+    }
+
+    let mut paired: HashSet<i64> = HashSet::new();
+    let mut detection = TransferDetection::default();
+    for row in rows {
+        if paired.contains(&row.id) {
+            continue;
+        }
+        let Some(&target) = row
+            .counterparty_iban
+            .as_deref()
+            .and_then(|iban| account_by_iban.get(normalize_iban(iban).as_str()))
+        else {
+            continue;
+        };
+        if target == row.account_id {
+            continue;
+        }
+
+        let own_iban = iban_of_account.get(&row.account_id).copied();
+        let counterpart = by_key
+            .get(&(target, -row.amount_cents, row.currency.as_str()))
+            .into_iter()
+            .flatten()
+            .filter(|other| !paired.contains(&other.id))
+            .filter_map(|other| {
+                let days = days_apart(&row.date, &other.date)?;
+                (days <= DATE_TOLERANCE_DAYS).then_some((other, days))
+            })
+            // Prefer a counterpart naming this account, then the closest date
+            .min_by_key(|(other, days)| {
+                let names_us = own_iban.is_some()
+                    && other
+                        .counterparty_iban
+                        .as_deref()
+                        .map(normalize_iban)
+                        .as_deref()
+                        == own_iban;
+                (!names_us, *days, other.id)
+            })
+            .map(|(other, _)| *other);
+
+        match counterpart {
+            Some(other) => {
+                paired.insert(row.id);
+                paired.insert(other.id);
+                let (outgoing, incoming) = if row.amount_cents < 0 {
+                    (row, other)
+                } else {
+                    (other, row)
+                };
+                detection.pairs.push(TransferPair {
+                    outgoing_id: outgoing.id,
+                    incoming_id: incoming.id,
+                    amount_cents: incoming.amount_cents,
+                    currency: row.currency.clone(),
+                });
+            }
+            None => detection.unmatched.push(UnmatchedTransfer {
+                id: row.id,
+                date: row.date.clone(),
+                amount_cents: row.amount_cents,
+                currency: row.currency.clone(),
+                account_id: row.account_id,
+                counterpart_account_id: target,
+            }),
+        }
+    }
+    detection
+}
+
+/// Find transfers among all unpaired transactions, link them and book them
+/// to the Transfers category. Run it inside a database transaction.
+pub fn detect_and_link(conn: &Connection) -> AppResult<TransferDetection> {
+    let accounts = accounts::list_accounts(conn)?;
+    if accounts.iter().all(|a| a.iban.is_none()) {
+        return Ok(TransferDetection::default());
+    }
+    let rows = transactions::fetch_unpaired_with_account(conn)?;
+    let detection = find_transfers(&rows, &accounts);
+
+    let transfers_category = categories::transfers_category_id(&categories::list_categories(conn)?);
+    for pair in &detection.pairs {
+        transactions::link_transfer_pair(conn, pair.outgoing_id, pair.incoming_id)?;
+        if let Some(category_id) = transfers_category {
+            transactions::set_transfer_category(
+                conn,
+                pair.outgoing_id,
+                pair.incoming_id,
+                category_id,
+            )?;
+        }
+    }
+    info!(
+        pairs = detection.pairs.len(),
+        unmatched = detection.unmatched.len(),
+        "Detected transfers by IBAN"
+    );
+    Ok(detection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::account::{AccountType, InterestCompounding};
+
+    fn account(id: i64, iban: Option<&str>) -> Account {
+        Account {
+            id,
+            name: format!("Account {}", id),
+            account_type: AccountType::Cash,
+            active: true,
+            interest_rate_bps: None,
+            interest_compounding: InterestCompounding::default(),
+            derive_cash_from_trading: false,
+            statement_day: None,
+            due_day: None,
+            cash_account_id: None,
+            iban: iban.map(String::from),
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    fn row(
+        id: i64,
+        date: &str,
+        amount_cents: i64,
+        account_id: i64,
+        iban: Option<&str>,
+    ) -> UnpairedRow {
+        UnpairedRow {
+            id,
+            date: date.into(),
+            amount_cents,
+            currency: "EUR".into(),
+            account_id,
+            counterparty_iban: iban.map(String::from),
+        }
+    }
+
+    const CHECKING: &str = "DE89370400440532013000";
+    const SAVINGS: &str = "DE02120300000000202051";
+
+    #[test]
+    fn test_pairs_closest_opposite_amount() {
+        let accounts = [account(1, Some(CHECKING)), account(2, Some(SAVINGS))];
+        let rows = [
+            row(
+                1,
+                "2024-03-01",
+                -50_000,
+                1,
+                Some("de02 1203 0000 0000 2020 51"),
+            ),
+            row(2, "2024-03-01", 50_000, 2, None),
+            row(3, "2024-03-03", 50_000, 2, None),
+            // Too far apart
+            row(4, "2024-04-01", -20_000, 1, Some(SAVINGS)),
+            row(5, "2024-04-05", 20_000, 2, None),
+        ];
+
+        let detection = find_transfers(&rows, &accounts);
+        assert_eq!(
+            detection.pairs,
+            vec![TransferPair {
+                outgoing_id: 1,
+                incoming_id: 2,
+                amount_cents: 50_000,
+                currency: "EUR".into(),
+            }]
+        );
+        assert_eq!(detection.unmatched.len(), 1);
+        assert_eq!(detection.unmatched[0].id, 4);
+        assert_eq!(detection.unmatched[0].counterpart_account_id, 2);
+    }
+
+    #[test]
+    fn test_prefers_counterpart_naming_the_account() {
+        let accounts = [account(1, Some(CHECKING)), account(2, Some(SAVINGS))];
+        let rows = [
+            row(1, "2024-03-01", 10_000, 2, Some(CHECKING)),
+            row(2, "2024-03-01", -10_000, 1, None),
+            row(3, "2024-03-02", -10_000, 1, Some(SAVINGS)),
+        ];
+
+        let detection = find_transfers(&rows, &accounts);
+        assert_eq!(detection.pairs.len(), 1);
+        assert_eq!(detection.pairs[0].outgoing_id, 3);
+        assert_eq!(detection.pairs[0].incoming_id, 1);
+        assert!(detection.unmatched.is_empty());
+    }
+
+    #[test]
+    fn test_ignores_foreign_and_own_ibans() {
+        let accounts = [account(1, Some(CHECKING)), account(2, None)];
+        let rows = [
+            row(1, "2024-03-01", -5_000, 1, Some(SAVINGS)),
+            row(2, "2024-03-01", 5_000, 2, None),
+            row(3, "2024-03-02", -5_000, 1, Some(CHECKING)),
+        ];
+
+        let detection = find_transfers(&rows, &accounts);
+        assert_eq!(detection, TransferDetection::default());
+    }
+}
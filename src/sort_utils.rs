@@ -60,6 +60,16 @@ pub trait Sortable {
 
     /// Resolve sort parameters into a TableSort config.
     fn resolve_sort<C: SortableColumn>(&self) -> TableSort<C> {
+        self.resolve_sort_or(TableSort::default())
+    }
+
+    /// Resolve sort parameters, using `default` when neither `sort` nor `dir`
+    /// is given, e.g. a sort order stored in the settings.
+    fn resolve_sort_or<C: SortableColumn>(&self, default: TableSort<C>) -> TableSort<C> {
+        if self.sort_by().is_none() && self.sort_dir().is_none() {
+            return default;
+        }
+
         let column = self
             .sort_by()
             .and_then(|s| C::from_str(s))
@@ -94,6 +104,20 @@ pub struct TableSort<C: SortableColumn> {
 }
 
 impl<C: SortableColumn> TableSort<C> {
+    /// Parse a stored sort order of the form `column:dir`, e.g. `gainloss:desc`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (column, direction) = value.split_once(':')?;
+        Some(Self {
+            column: C::from_str(column)?,
+            direction: direction.parse().unwrap(),
+        })
+    }
+
+    /// The sort order in the form read by [`TableSort::parse`].
+    pub fn stored_value(&self) -> String {
+        format!("{}:{}", self.column.as_str(), self.direction.as_str())
+    }
+
     /// Generate SQL ORDER BY expression (e.g., "e.date DESC").
     pub fn sql_order_by(&self) -> String {
         format!("{} {}", self.column.sql_expression(), self.direction.sql())
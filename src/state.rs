@@ -1,11 +1,19 @@
 use crate::auth::LoginRateLimiter;
 use crate::cache::AppCache;
 use crate::config::Config;
+use crate::confirmation::DeleteConfirmations;
 use crate::db::DbPool;
 use crate::error::AppResult;
 use crate::filters::Icons;
-use crate::handlers::recurring_expenses::RecurringExpense;
-use crate::models::{Account, Category, CategoryWithPath, Settings, Tag};
+use crate::flash::FlashStore;
+use crate::idempotency::SubmittedForms;
+use crate::models::settings::DEFAULT_MARKET_DATA_DELAY_MS;
+use crate::models::{Account, Category, CategoryWithPath, NetWorthSummary, Settings, Tag};
+use crate::services::market_data::SearchThrottle;
+use crate::services::notify::NotificationThrottle;
+use crate::services::recurring_detection::RecurringExpense;
+use crate::symbol_locks::SymbolLocks;
+use crate::usage::UsageRecorder;
 use crate::xsrf::XsrfToken;
 use crate::VERSION;
 use serde::Deserialize;
@@ -13,6 +21,7 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// State for tracking market data refresh operations
 #[derive(Clone, Debug, Default)]
@@ -32,8 +41,38 @@ impl MarketDataRefreshState {
     }
 }
 
-/// Server-side session store holding valid session tokens.
-pub type SessionStore = Arc<Mutex<HashSet<String>>>;
+/// State for tracking symbol validation of a trading import session
+#[derive(Clone, Debug, Default)]
+pub struct SymbolValidationState {
+    pub is_running: bool,
+    pub cancelled: bool,
+    pub processed_symbols: usize,
+    pub total_symbols: usize,
+    pub unknown_symbols: usize,
+    pub current_symbol: Option<String>,
+}
+
+impl SymbolValidationState {
+    pub fn progress_percent(&self) -> u8 {
+        if self.total_symbols == 0 {
+            return 0;
+        }
+        ((self.processed_symbols as f64 / self.total_symbols as f64) * 100.0) as u8
+    }
+}
+
+/// Symbol validation progress keyed by trading import session id.
+pub type SymbolValidationStore = Arc<Mutex<HashMap<String, SymbolValidationState>>>;
+
+/// Transaction import sessions currently being confirmed by a background task.
+pub type RunningImports = Arc<Mutex<HashSet<String>>>;
+
+/// Server-side session store mapping valid session tokens to their login time.
+pub type SessionStore = Arc<Mutex<HashMap<String, Instant>>>;
+
+/// Unlocked password-protected share links, mapping share session tokens to
+/// the link token they were issued for.
+pub type ShareSessionStore = Arc<Mutex<HashMap<String, String>>>;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -42,9 +81,21 @@ pub struct AppState {
     pub manifest: JsManifest,
     pub xsrf_token: XsrfToken,
     pub market_data_refresh: Arc<Mutex<MarketDataRefreshState>>,
+    /// Held around split adjustments and market data writes of a symbol.
+    pub symbol_locks: Arc<SymbolLocks>,
+    pub symbol_validation: SymbolValidationStore,
+    pub running_imports: RunningImports,
     pub cache: Arc<AppCache>,
     pub sessions: SessionStore,
+    pub share_sessions: ShareSessionStore,
     pub login_rate_limiter: Arc<LoginRateLimiter>,
+    pub delete_confirmations: Arc<DeleteConfirmations>,
+    pub submitted_forms: Arc<SubmittedForms>,
+    pub flash: Arc<FlashStore>,
+    pub symbol_search_throttle: Arc<SearchThrottle>,
+    pub notification_throttle: Arc<NotificationThrottle>,
+    /// Usage statistics not yet written to the database.
+    pub usage: Arc<UsageRecorder>,
 }
 
 /// Pre-built base fields shared by every page template.
@@ -55,6 +106,8 @@ pub struct PageBase {
     pub manifest: JsManifest,
     pub version: &'static str,
     pub xsrf_token: String,
+    /// Nonce for inline scripts, see [`crate::csp`].
+    pub csp_nonce: String,
 }
 
 impl AppState {
@@ -71,9 +124,33 @@ impl AppState {
             manifest: self.manifest.clone(),
             version: VERSION,
             xsrf_token: self.xsrf_token.value().to_string(),
+            csp_nonce: crate::csp::current_nonce().unwrap_or_default(),
         })
     }
 
+    /// Run `f` inside an immediate transaction on a pooled connection.
+    ///
+    /// The transaction commits when `f` returns `Ok` and rolls back when it
+    /// returns an error, so a handler that writes several rows never leaves
+    /// half of them behind.
+    pub fn with_tx<T>(
+        &self,
+        f: impl FnOnce(&rusqlite::Transaction) -> AppResult<T>,
+    ) -> AppResult<T> {
+        let mut conn = self.db.get()?;
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    /// Pause between market data API requests, from the advanced settings.
+    pub fn market_data_delay(&self) -> Duration {
+        self.load_settings()
+            .map(|s| s.market_data_delay())
+            .unwrap_or(Duration::from_millis(DEFAULT_MARKET_DATA_DELAY_MS))
+    }
+
     pub fn cached_categories_with_path(&self) -> AppResult<Vec<CategoryWithPath>> {
         self.cache.load_categories_with_path(&self.db)
     }
@@ -98,6 +175,10 @@ impl AppState {
         self.cache
             .load_recurring_expenses(&self.db, &self.config.auth_mode)
     }
+
+    pub fn cached_net_worth(&self) -> AppResult<Arc<NetWorthSummary>> {
+        self.cache.load_net_worth(&self.db)
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -0,0 +1,120 @@
+//! Per-symbol write locks for split adjustments and market data.
+//!
+//! Creating, editing, deleting or restoring an activity rewrites the split
+//! adjustments of its symbol, and a market data refresh writes the prices,
+//! names and price currency of the symbol it fetched. Both take the lock of
+//! the symbol around their writes so neither sees the other half-done.
+//!
+//! The refresh waits for the lock; the activity handlers give up after
+//! [`EDIT_LOCK_TIMEOUT`] with a `409 Conflict` instead of hanging the
+//! request. Holders of several symbols take their locks in sorted order, so
+//! two of them never wait on each other.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::error::{AppError, AppResult};
+
+/// How long an activity handler waits for a symbol being refreshed.
+pub const EDIT_LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn sorted<'a>(symbols: &[&'a str]) -> Vec<&'a str> {
+    let mut symbols = symbols.to_vec();
+    symbols.sort_unstable();
+    symbols.dedup();
+    symbols
+}
+
+/// Locks held for a set of symbols, released on drop.
+pub struct SymbolGuard {
+    _guards: Vec<OwnedMutexGuard<()>>,
+}
+
+/// One lock per symbol, created on first use.
+#[derive(Default)]
+pub struct SymbolLocks {
+    locks: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+}
+
+impl SymbolLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The locks of `symbols`, in the order they must be taken. Locks
+    /// nobody holds or waits for are forgotten.
+    fn locks_for(&self, symbols: &[&str]) -> Vec<Arc<AsyncMutex<()>>> {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        sorted(symbols)
+            .into_iter()
+            .map(|symbol| locks.entry(symbol.to_string()).or_default().clone())
+            .collect()
+    }
+
+    /// Wait for the locks of `symbols`.
+    pub async fn lock(&self, symbols: &[&str]) -> SymbolGuard {
+        let mut guards = Vec::with_capacity(symbols.len());
+        for lock in self.locks_for(symbols) {
+            guards.push(lock.lock_owned().await);
+        }
+        SymbolGuard { _guards: guards }
+    }
+
+    /// Wait at most `timeout` for the locks of `symbols`, failing with a
+    /// conflict if a refresh holds one of them for longer.
+    pub async fn lock_for_edit(
+        &self,
+        symbols: &[&str],
+        timeout: Duration,
+    ) -> AppResult<SymbolGuard> {
+        tokio::time::timeout(timeout, self.lock(symbols))
+            .await
+            .map_err(|_| {
+                AppError::Conflict(format!(
+                    "Market data for {} is being refreshed, please retry shortly",
+                    sorted(symbols).join(", ")
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_edit_times_out_while_locked() {
+        let locks = SymbolLocks::new();
+        let guard = locks.lock(&["AAPL"]).await;
+
+        let err = locks
+            .lock_for_edit(&["MSFT", "AAPL"], Duration::from_millis(20))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, AppError::Conflict(_)));
+        // Other symbols are not affected
+        assert!(locks
+            .lock_for_edit(&["MSFT"], Duration::from_millis(20))
+            .await
+            .is_ok());
+
+        drop(guard);
+        assert!(locks
+            .lock_for_edit(&["AAPL", "AAPL"], Duration::from_millis(20))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unused_locks_are_forgotten() {
+        let locks = SymbolLocks::new();
+        drop(locks.lock(&["AAPL", "MSFT"]).await);
+        let _guard = locks.lock(&["VT"]).await;
+        assert_eq!(locks.locks.lock().unwrap().len(), 1);
+    }
+}
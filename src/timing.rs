@@ -0,0 +1,153 @@
+//! Per-request timing and slow query logging.
+//!
+//! Every pooled SQLite connection gets a profile hook (see
+//! [`crate::db::pool`]) that reports each statement's execution time to
+//! [`record_query`], and the pool reports how long each checkout waited for
+//! a free connection to [`record_checkout`]. Both are summed per request
+//! through a task-local accumulator, and [`server_timing_middleware`]
+//! reports the sum alongside the total handler time in a `Server-Timing`
+//! response header.
+//!
+//! [`capture_queries`] collects the statements a future runs, for tests that
+//! check which queries a handler issues.
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Threshold in milliseconds above which a statement is logged; 0 disables logging.
+static SLOW_QUERY_MS: AtomicU64 = AtomicU64::new(0);
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+tokio::task_local! {
+    static DB_TIME: Cell<Duration>;
+    static QUERY_LOG: RefCell<Vec<String>>;
+}
+
+/// Set the slow query threshold (`SOLVENCY_SLOW_QUERY_MS`). `None` disables logging.
+pub fn set_slow_query_threshold(ms: Option<u64>) {
+    SLOW_QUERY_MS.store(ms.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Profile callback installed on every pooled connection.
+///
+/// SQLite passes the statement text with placeholders unexpanded, so bound
+/// parameters never end up in the log.
+pub fn record_query(sql: &str, duration: Duration) {
+    // Queries outside a request scope (background tasks, startup) are not attributed
+    let _ = DB_TIME.try_with(|total| total.set(total.get() + duration));
+    let _ = QUERY_LOG.try_with(|log| log.borrow_mut().push(sql.to_string()));
+
+    let threshold = SLOW_QUERY_MS.load(Ordering::Relaxed);
+    if threshold > 0 && duration >= Duration::from_millis(threshold) {
+        let statement = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        tracing::warn!(
+            duration_ms = duration.as_secs_f64() * 1000.0,
+            sql = %statement,
+            "Slow query"
+        );
+    }
+}
+
+/// Called by the pool with the time a checkout waited for a connection.
+/// Waits above the slow query threshold are logged, as they point to a
+/// pool too small for the load.
+pub fn record_checkout(wait: Duration) {
+    let _ = DB_TIME.try_with(|total| total.set(total.get() + wait));
+
+    let threshold = SLOW_QUERY_MS.load(Ordering::Relaxed);
+    if threshold > 0 && wait >= Duration::from_millis(threshold) {
+        tracing::warn!(
+            wait_ms = wait.as_secs_f64() * 1000.0,
+            "Slow connection checkout"
+        );
+    }
+}
+
+/// Run `f` and return its output with the text of every statement it ran on
+/// pooled connections, in order.
+pub async fn capture_queries<F: Future>(f: F) -> (F::Output, Vec<String>) {
+    QUERY_LOG
+        .scope(RefCell::new(Vec::new()), async {
+            let output = f.await;
+            (output, QUERY_LOG.with(|log| log.take()))
+        })
+        .await
+}
+
+/// Format a `Server-Timing` header value from database and total handler time.
+fn server_timing_value(db: Duration, app: Duration) -> String {
+    format!(
+        "db;dur={:.1}, app;dur={:.1}",
+        db.as_secs_f64() * 1000.0,
+        app.as_secs_f64() * 1000.0
+    )
+}
+
+/// Middleware that adds a `Server-Timing` header with database and handler time.
+pub async fn server_timing_middleware(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let (db_time, mut response) = DB_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let response = next.run(request).await;
+            (DB_TIME.with(Cell::get), response)
+        })
+        .await;
+
+    let value = server_timing_value(db_time, start.elapsed());
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert(SERVER_TIMING, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_timing_value() {
+        let value = server_timing_value(Duration::from_micros(12_340), Duration::from_millis(45));
+        assert_eq!(value, "db;dur=12.3, app;dur=45.0");
+    }
+
+    #[tokio::test]
+    async fn test_record_query_accumulates_in_scope() {
+        let total = DB_TIME
+            .scope(Cell::new(Duration::ZERO), async {
+                record_query("SELECT 1", Duration::from_millis(2));
+                record_query("SELECT 2", Duration::from_millis(3));
+                DB_TIME.with(Cell::get)
+            })
+            .await;
+        assert_eq!(total, Duration::from_millis(5));
+    }
+
+    #[tokio::test]
+    async fn test_checkout_wait_counts_as_db_time() {
+        let total = DB_TIME
+            .scope(Cell::new(Duration::ZERO), async {
+                record_checkout(Duration::from_millis(4));
+                record_query("SELECT 1", Duration::from_millis(1));
+                DB_TIME.with(Cell::get)
+            })
+            .await;
+        assert_eq!(total, Duration::from_millis(5));
+    }
+
+    #[tokio::test]
+    async fn test_capture_queries() {
+        let ((), queries) = capture_queries(async {
+            record_query("SELECT 1", Duration::ZERO);
+            record_query("SELECT 2", Duration::ZERO);
+        })
+        .await;
+        assert_eq!(queries, vec!["SELECT 1", "SELECT 2"]);
+    }
+}
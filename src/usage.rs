@@ -0,0 +1,247 @@
+//! Opt-in usage statistics.
+//!
+//! When enabled in the settings, [`usage_middleware`] counts every request
+//! by route pattern, method, status and latency bucket, per day. Counts are
+//! kept in memory and written to the `usage_stats` table by [`flush`], which
+//! [`run_flusher`] calls once a minute, so browsing doesn't cause a database
+//! write per request. Client addresses, query strings and bodies are never
+//! recorded.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::date_utils;
+use crate::db::queries::usage_stats;
+use crate::error::AppResult;
+use crate::models::usage::{RouteUsage, UsageCount, UsageKey};
+use crate::state::AppState;
+
+/// How often pending counts are written to the database.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bounds of the latency buckets in milliseconds. Slower requests
+/// fall into [`OVERFLOW_BUCKET_MS`].
+pub const BUCKETS_MS: &[i64] = &[1, 2, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+pub const OVERFLOW_BUCKET_MS: i64 = i64::MAX;
+
+/// Request counts not yet written to the database.
+#[derive(Default)]
+pub struct UsageRecorder {
+    pending: Mutex<HashMap<UsageKey, UsageCount>>,
+}
+
+impl UsageRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, date: String, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let key = UsageKey {
+            date,
+            method: method.to_string(),
+            route: route.to_string(),
+            status,
+            duration_bucket_ms: duration_bucket(elapsed),
+        };
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let count = pending.entry(key).or_default();
+        count.count += 1;
+        count.total_duration_ms += elapsed.as_secs_f64() * 1000.0;
+    }
+
+    /// Remove and return all pending counts.
+    pub fn take(&self) -> Vec<(UsageKey, UsageCount)> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.drain().collect()
+    }
+}
+
+/// The latency bucket of a request that took `elapsed`.
+pub fn duration_bucket(elapsed: Duration) -> i64 {
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    BUCKETS_MS
+        .iter()
+        .copied()
+        .find(|&bound| ms <= bound as f64)
+        .unwrap_or(OVERFLOW_BUCKET_MS)
+}
+
+/// Display form of a latency bucket, e.g. "≤ 25 ms".
+pub fn bucket_label(bucket_ms: i64) -> String {
+    match bucket_ms {
+        OVERFLOW_BUCKET_MS => format!("> {} s", BUCKETS_MS[BUCKETS_MS.len() - 1] / 1000),
+        ms if ms >= 1000 => format!("≤ {} s", ms as f64 / 1000.0),
+        ms => format!("≤ {} ms", ms),
+    }
+}
+
+/// Middleware that counts requests to known routes while usage statistics
+/// are enabled. Static files and unknown paths are not counted.
+pub async fn usage_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .filter(|route| !route.starts_with("/static"));
+    let settings = match route {
+        Some(_) => state.load_settings().ok(),
+        None => None,
+    };
+    let (Some(route), Some(settings)) = (route, settings) else {
+        return next.run(request).await;
+    };
+    if !settings.usage_stats_enabled {
+        return next.run(request).await;
+    }
+
+    let method = request.method().clone();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    state.usage.record(
+        date_utils::today_in(&settings).to_string(),
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        start.elapsed(),
+    );
+    response
+}
+
+/// Write the pending counts to the database. Returns the number of rows
+/// written.
+pub fn flush(state: &AppState) -> AppResult<usize> {
+    let counts = state.usage.take();
+    if counts.is_empty() {
+        return Ok(0);
+    }
+    let mut conn = state.db.get()?;
+    usage_stats::add_counts(&mut conn, &counts)?;
+    tracing::debug!(rows = counts.len(), "Flushed usage statistics");
+    Ok(counts.len())
+}
+
+/// Background loop that flushes usage statistics. Spawn once at startup.
+pub async fn run_flusher(state: AppState) {
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = flush(&state) {
+            tracing::warn!(error = %e, "Flushing usage statistics failed");
+        }
+    }
+}
+
+/// Combine the counts of each route and method, busiest route first.
+pub fn summarize(counts: Vec<(UsageKey, UsageCount)>) -> Vec<RouteUsage> {
+    let mut buckets: HashMap<(String, String), Vec<(i64, i64)>> = HashMap::new();
+    let mut routes: HashMap<(String, String), RouteUsage> = HashMap::new();
+    for (key, count) in counts {
+        let id = (key.method.clone(), key.route.clone());
+        let usage = routes.entry(id.clone()).or_insert_with(|| RouteUsage {
+            method: key.method,
+            route: key.route,
+            ..Default::default()
+        });
+        usage.requests += count.count;
+        if key.status >= 400 {
+            usage.errors += count.count;
+        }
+        usage.total_duration_ms += count.total_duration_ms;
+        buckets
+            .entry(id)
+            .or_default()
+            .push((key.duration_bucket_ms, count.count));
+    }
+
+    let mut routes: Vec<RouteUsage> = routes
+        .into_iter()
+        .map(|(id, mut usage)| {
+            let mut counts = buckets.remove(&id).unwrap_or_default();
+            counts.sort_unstable();
+            let half = (usage.requests + 1) / 2;
+            let mut seen = 0;
+            for (bucket, count) in counts {
+                seen += count;
+                if seen >= half {
+                    usage.median_bucket_ms = bucket;
+                    break;
+                }
+            }
+            usage
+        })
+        .collect();
+    routes.sort_by(|a, b| {
+        b.requests
+            .cmp(&a.requests)
+            .then_with(|| a.route.cmp(&b.route))
+            .then_with(|| a.method.cmp(&b.method))
+    });
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(method: &str, route: &str, status: u16, bucket: i64) -> UsageKey {
+        UsageKey {
+            date: "2024-03-01".into(),
+            method: method.into(),
+            route: route.into(),
+            status,
+            duration_bucket_ms: bucket,
+        }
+    }
+
+    fn count(count: i64) -> UsageCount {
+        UsageCount {
+            count,
+            total_duration_ms: count as f64 * 10.0,
+        }
+    }
+
+    #[test]
+    fn test_duration_bucket() {
+        assert_eq!(duration_bucket(Duration::from_micros(300)), 1);
+        assert_eq!(duration_bucket(Duration::from_millis(25)), 25);
+        assert_eq!(duration_bucket(Duration::from_millis(26)), 50);
+        assert_eq!(duration_bucket(Duration::from_secs(60)), OVERFLOW_BUCKET_MS);
+    }
+
+    #[test]
+    fn test_bucket_label() {
+        assert_eq!(bucket_label(25), "≤ 25 ms");
+        assert_eq!(bucket_label(2500), "≤ 2.5 s");
+        assert_eq!(bucket_label(OVERFLOW_BUCKET_MS), "> 10 s");
+    }
+
+    #[test]
+    fn test_summarize() {
+        let routes = summarize(vec![
+            (key("GET", "/transactions", 200, 5), count(6)),
+            (key("GET", "/transactions", 200, 100), count(3)),
+            (key("GET", "/transactions", 500, 250), count(1)),
+            (key("GET", "/", 200, 10), count(2)),
+        ]);
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].route, "/transactions");
+        assert_eq!(routes[0].requests, 10);
+        assert_eq!(routes[0].errors, 1);
+        assert_eq!(routes[0].median_bucket_ms, 5);
+        assert_eq!(routes[0].error_rate_percent(), 10.0);
+        assert_eq!(routes[0].error_rate_label(), "10.0%");
+        assert_eq!(crate::models::usage::error_rate_label(0, 0), "–");
+        assert_eq!(routes[1].route, "/");
+        assert_eq!(routes[1].median_bucket_ms, 10);
+    }
+}
@@ -53,7 +53,8 @@ pub async fn xsrf_middleware(
     if !matches!(
         method,
         Method::POST | Method::PUT | Method::DELETE | Method::PATCH
-    ) {
+    ) || is_exempt(request.uri().path())
+    {
         // For non-mutating requests, just continue
         return next.run(request).await;
     }
@@ -128,6 +129,13 @@ pub async fn xsrf_middleware(
     xsrf_error_response()
 }
 
+/// Share links are opened without a session, so their pages must not carry
+/// the token of the owner's session. The auth middleware only lets the
+/// unlock form post there, and it is rate-limited like the login.
+fn is_exempt(path: &str) -> bool {
+    path.starts_with("/share/")
+}
+
 fn xsrf_error_response() -> Response {
     (StatusCode::FORBIDDEN, "Invalid or missing XSRF token").into_response()
 }
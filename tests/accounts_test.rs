@@ -0,0 +1,282 @@
+//! Integration tests for account import, export and credit card cycles.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestClient;
+use solvency::db::queries::accounts;
+
+fn parse(body: &str) -> serde_json::Value {
+    serde_json::from_str(body).expect("response should be JSON")
+}
+
+/// Re-importing an unmodified export changes nothing.
+#[tokio::test]
+async fn test_reimport_export_is_noop() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Broker", "Securities").await);
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute(
+            "UPDATE accounts SET active = 0, interest_rate_bps = 250,
+                    interest_compounding = 'quarterly'
+             WHERE name = 'Checking'",
+            [],
+        )
+        .unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let (status, export) = client.get("/accounts/export").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(export.contains("\"active\": false"));
+
+    let before = accounts::list_accounts(&client.state().db.get().unwrap()).unwrap();
+
+    let (status, body) = client.post_json("/accounts/import", &export).await;
+    assert_eq!(status, StatusCode::OK);
+    let result = parse(&body);
+    assert_eq!(result["imported"], 0);
+    assert_eq!(result["updated"], 0);
+    assert_eq!(result["unchanged"], 2);
+
+    let after = accounts::list_accounts(&client.state().db.get().unwrap()).unwrap();
+    assert_eq!(after.len(), 2);
+    for (a, b) in before.iter().zip(&after) {
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.active, b.active);
+        assert_eq!(a.interest_rate_bps, b.interest_rate_bps);
+        assert_eq!(a.interest_compounding, b.interest_compounding);
+        assert_eq!(a.updated_at, b.updated_at);
+    }
+}
+
+/// Existing accounts are matched by name and updated instead of duplicated.
+#[tokio::test]
+async fn test_import_updates_existing_accounts() {
+    let client = TestClient::new();
+    assert!(client.create_account("Savings", "Cash").await);
+    client.state().cache.invalidate();
+
+    let json = r#"[
+        {"name": "Savings", "account_type": "Cash", "active": false, "interest_rate_bps": 300},
+        {"name": "Depot", "account_type": "Securities"}
+    ]"#;
+    let (status, body) = client.post_json("/accounts/import", json).await;
+    assert_eq!(status, StatusCode::OK);
+    let result = parse(&body);
+    assert_eq!(result["imported"], 1);
+    assert_eq!(result["updated"], 1);
+
+    let list = accounts::list_accounts(&client.state().db.get().unwrap()).unwrap();
+    assert_eq!(list.len(), 2);
+    let savings = list.iter().find(|a| a.name == "Savings").unwrap();
+    assert!(!savings.active);
+    assert_eq!(savings.interest_rate_bps, Some(300));
+}
+
+/// The cash account of a securities account travels by name and is linked
+/// again on import, also to a cash account created by the same file.
+#[tokio::test]
+async fn test_cash_account_link_exported_by_name() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Broker"),
+                ("account_type", "Securities"),
+                ("active", "on"),
+                ("cash_account_id", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    client.state().cache.invalidate();
+
+    let (_, export) = client.get("/accounts/export").await;
+    assert!(export.contains("\"cash_account\": \"Checking\""));
+    let (_, body) = client.post_json("/accounts/import", &export).await;
+    assert_eq!(parse(&body)["unchanged"], 2);
+
+    // Securities first: the link still finds the cash account
+    let json = r#"[
+        {"name": "Broker", "account_type": "Securities", "cash_account": "Checking"},
+        {"name": "Checking", "account_type": "Cash"}
+    ]"#;
+    let target = TestClient::new();
+    let (status, body) = target.post_json("/accounts/import", json).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let list = accounts::list_accounts(&target.state().db.get().unwrap()).unwrap();
+    let checking = list.iter().find(|a| a.name == "Checking").unwrap();
+    let broker = list.iter().find(|a| a.name == "Broker").unwrap();
+    assert_eq!(broker.cash_account_id, Some(checking.id));
+
+    let json = r#"[
+        {"name": "Depot", "account_type": "Securities", "cash_account": "Savings"},
+        {"name": "Wallet", "account_type": "Cash", "cash_account": "Checking"}
+    ]"#;
+    let (status, body) = target.post_json("/accounts/import", json).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let result = parse(&body);
+    assert!(result["errors"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("cash account \"Savings\" not found"));
+    assert!(result["errors"][1]["error"]
+        .as_str()
+        .unwrap()
+        .contains("only securities accounts"));
+}
+
+/// An invalid record rejects the whole file unless `partial=1` is passed.
+#[tokio::test]
+async fn test_import_reports_invalid_records() {
+    let client = TestClient::new();
+
+    let json = r#"[
+        {"name": "Checking", "account_type": "Cash"},
+        {"name": "Crypto", "account_type": "Wallet"},
+        {"name": "  ", "account_type": "Cash"},
+        {"name": "Checking", "account_type": "Cash"}
+    ]"#;
+    let (status, body) = client.post_json("/accounts/import", json).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let result = parse(&body);
+    let errors = result["errors"].as_array().unwrap();
+    let indexes: Vec<u64> = errors
+        .iter()
+        .map(|e| e["index"].as_u64().unwrap())
+        .collect();
+    assert_eq!(indexes, vec![1, 2, 3]);
+    assert!(errors[0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("unknown account type"));
+    assert!(accounts::list_accounts(&client.state().db.get().unwrap())
+        .unwrap()
+        .is_empty());
+
+    let (status, body) = client.post_json("/accounts/import?partial=1", json).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse(&body)["imported"], 1);
+    let list = accounts::list_accounts(&client.state().db.get().unwrap()).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].name, "Checking");
+}
+
+/// Submitting the same new account form twice creates one account.
+#[tokio::test]
+async fn test_double_submit_creates_one_account() {
+    let client = TestClient::new();
+    let (status, body) = client.get("/accounts/new").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("name=\"form_token\""));
+
+    let form = [
+        ("form_token", "account-token"),
+        ("name", "Savings"),
+        ("account_type", "Cash"),
+        ("active", "on"),
+    ];
+    for _ in 0..2 {
+        let (status, _) = client.post_form("/accounts/create", &form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    let list = accounts::list_accounts(&client.state().db.get().unwrap()).unwrap();
+    assert_eq!(list.len(), 1);
+
+    let (status, body) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("form_token", "second-account-token"),
+                ("name", "Broker"),
+                ("account_type", "Cash"),
+                ("interest_rate", "250"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Invalid interest rate"));
+    assert!(body.contains("value=\"Broker\""));
+}
+
+/// A credit card's cycle summary counts spending per statement cycle, and
+/// transfers into the card reduce the amount due on the dashboard.
+#[tokio::test]
+async fn test_credit_card_cycle_summary() {
+    let client = TestClient::new();
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Visa"),
+                ("account_type", "CreditCard"),
+                ("active", "on"),
+                ("statement_day", "31"),
+                ("due_day", "15"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let (status, body) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Amex"),
+                ("account_type", "CreditCard"),
+                ("statement_day", "31"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Set both the statement day and the due day"));
+
+    let today = solvency::date_utils::today_in(&client.state().load_settings().unwrap());
+    let current = solvency::services::card_cycle::statement_cycle(today, 31, 15);
+    let previous = current.previous(31, 15);
+    let (previous_start, current_start) = (previous.start.to_string(), current.start.to_string());
+    for (date, amount, description) in [
+        (previous_start.as_str(), "-120.00", "Hotel"),
+        (previous_start.as_str(), "20.00", "Hotel refund"),
+        (current_start.as_str(), "-30.00", "Books"),
+        (current_start.as_str(), "40.00", "Card payment"),
+    ] {
+        assert!(
+            client
+                .create_transaction(date, amount, description, Some(1), None)
+                .await
+        );
+    }
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute(
+            "UPDATE transactions SET transfer_pair_id = id WHERE description = 'Card payment'",
+            [],
+        )
+        .unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let (status, body) = client.get("/api/accounts/1/cycle-summary").await;
+    assert_eq!(status, StatusCode::OK);
+    let summary = parse(&body);
+    assert_eq!(summary["previous"]["spending_cents"], 10000);
+    assert_eq!(summary["current"]["spending_cents"], 3000);
+    assert_eq!(summary["current"]["payments_cents"], 4000);
+    assert_eq!(summary["statement_balance_cents"], 10000);
+    assert_eq!(summary["amount_due_cents"], 6000);
+    assert_eq!(summary["due_date"], previous.due.to_string());
+
+    let (_, dashboard) = client.get("/").await;
+    assert!(dashboard.contains("card-payments"));
+    assert!(dashboard.contains("Visa"));
+
+    assert!(client.create_account("Checking", "Cash").await);
+    let (status, _) = client.get("/api/accounts/2/cycle-summary").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
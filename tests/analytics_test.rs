@@ -5,6 +5,8 @@ mod common;
 use axum::http::StatusCode;
 use common::TestClient;
 use serde::Deserialize;
+use solvency::db::queries::transactions;
+use solvency::handlers::transactions::TransactionFilterParams;
 
 #[derive(Debug, Deserialize)]
 struct CategorySpending {
@@ -131,6 +133,79 @@ async fn test_monthly_summary() {
     assert!(body.contains("2024-02"));
 }
 
+#[derive(Debug, Deserialize)]
+struct MonthlySummary {
+    month: String,
+    total_cents: i64,
+    income_cents: i64,
+    expense_cents: i64,
+    net_cents: i64,
+    savings_rate: Option<f64>,
+    savings_rate_formatted: Option<String>,
+}
+
+/// Test that a mixed month reports income, expenses and net separately,
+/// with transfers excluded from both sides.
+#[tokio::test]
+async fn test_monthly_summary_income_expense_split() {
+    let client = TestClient::new();
+
+    // Salary (Income = id 2), rent (Housing = id 6), transfer (Transfers = id 3)
+    assert!(
+        client
+            .create_transaction("2024-03-01", "3000.00", "Salary", None, Some(2))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-03-02", "-2900.00", "Rent", None, Some(6))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-03-03", "-500.00", "To savings", None, Some(3))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-03-03", "500.00", "From checking", None, Some(3))
+            .await
+    );
+
+    let (status, months): (_, Option<Vec<MonthlySummary>>) = client
+        .get_json("/api/analytics/monthly-summary?from_date=2024-02-01&to_date=2024-03-31")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let months = months.expect("Failed to parse monthly summary JSON");
+    assert_eq!(months.len(), 2);
+
+    let idle = &months[0];
+    assert_eq!(idle.month, "2024-02");
+    assert_eq!(
+        (idle.income_cents, idle.expense_cents, idle.net_cents),
+        (0, 0, 0)
+    );
+    assert_eq!(idle.savings_rate, None);
+    assert_eq!(idle.savings_rate_formatted, None);
+
+    let march = &months[1];
+    assert_eq!(march.month, "2024-03");
+    assert_eq!(march.income_cents, 300_000);
+    assert_eq!(march.expense_cents, 290_000);
+    assert_eq!(march.net_cents, 10_000);
+    assert_eq!(march.total_cents, 290_000);
+    let rate = march.savings_rate.unwrap();
+    assert!((rate - 1.0 / 30.0).abs() < 1e-9);
+    assert_eq!(march.savings_rate_formatted.as_deref(), Some("3.33%"));
+
+    // Net mode follows the income-positive convention of `net_cents`
+    let (_, months): (_, Option<Vec<MonthlySummary>>) = client
+        .get_json("/api/analytics/monthly-summary?from_date=2024-03-01&to_date=2024-03-31&mode=net")
+        .await;
+    let months = months.expect("Failed to parse monthly summary JSON");
+    assert_eq!(months[0].total_cents, 10_000);
+}
+
 /// Test sankey diagram data structure.
 #[tokio::test]
 async fn test_flow_sankey_structure() {
@@ -248,6 +323,115 @@ async fn test_flow_sankey_no_cycle_on_mixed_income_expense() {
     );
 }
 
+/// The accounts layer routes income through the receiving accounts into
+/// Budget and expenses from Budget through the paying accounts, while the
+/// default response keeps categories next to Budget.
+#[tokio::test]
+async fn test_flow_sankey_accounts_layer() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Card", "Cash").await);
+    for (date, amount, account, category) in [
+        ("2024-01-01", "1000.00", Some(1), Some(2)),
+        ("2024-01-05", "-300.00", Some(1), Some(6)),
+        ("2024-01-10", "-100.00", Some(2), Some(4)),
+        ("2024-01-11", "-50.00", Some(1), Some(4)),
+        ("2024-01-12", "-20.00", None, None),
+    ] {
+        assert!(
+            client
+                .create_transaction(date, amount, "Flow", account, category)
+                .await
+        );
+    }
+
+    let link = |body: &serde_json::Value, source: &str, target: &str| {
+        body["links"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|l| l["source"] == source && l["target"] == target)
+            .map(|l| l["value"].as_f64().unwrap())
+    };
+
+    let (_, plain) = client
+        .get_json::<serde_json::Value>("/api/analytics/flow-sankey")
+        .await;
+    let plain = plain.unwrap();
+    assert_eq!(link(&plain, "Budget", "Expenses"), Some(450.0));
+    assert!(!plain.to_string().contains("Checking"));
+
+    let (status, layered) = client
+        .get_json::<serde_json::Value>("/api/analytics/flow-sankey?layers=accounts")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let layered = layered.unwrap();
+    assert_eq!(link(&layered, "Budget", "Expenses"), None);
+    assert_eq!(link(&layered, "Income", "Checking (In)"), Some(1000.0));
+    assert_eq!(link(&layered, "Checking (In)", "Budget"), Some(1000.0));
+    assert_eq!(link(&layered, "Budget", "Checking (Out)"), Some(350.0));
+    assert_eq!(link(&layered, "Checking (Out)", "Expenses"), Some(350.0));
+    assert_eq!(link(&layered, "Card (Out)", "Expenses"), Some(100.0));
+    assert_eq!(link(&layered, "Expenses", "Food & Dining"), Some(150.0));
+    assert_eq!(
+        link(&layered, "No Account (Out)", "Uncategorized"),
+        Some(20.0)
+    );
+
+    // Account nodes sit in their own columns next to Budget
+    let depth = |name: &str| {
+        layered["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|n| n["name"] == name)
+            .map(|n| n["depth"].as_u64().unwrap())
+            .unwrap()
+    };
+    assert_eq!(depth("Checking (In)") + 1, depth("Budget"));
+    assert_eq!(depth("Budget") + 1, depth("Checking (Out)"));
+    assert_eq!(depth("Checking (Out)") + 1, depth("Expenses"));
+
+    let (status, _) = client.get("/api/analytics/flow-sankey?layers=banks").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// A refund to another account than the one paid with is netted against the
+/// paying account, so the account links still add up to the category total.
+#[tokio::test]
+async fn test_flow_sankey_accounts_layer_nets_refunds() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Card", "Cash").await);
+    for (date, amount, account, category) in [
+        ("2024-01-01", "1000.00", 1, 2),
+        ("2024-01-05", "-150.00", 1, 4),
+        ("2024-01-09", "30.00", 2, 4),
+    ] {
+        assert!(
+            client
+                .create_transaction(date, amount, "Flow", Some(account), Some(category))
+                .await
+        );
+    }
+
+    let (status, body) = client
+        .get_json::<serde_json::Value>("/api/analytics/flow-sankey?layers=accounts")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let links = body.unwrap()["links"].as_array().unwrap().clone();
+    let link = |source: &str, target: &str| {
+        links
+            .iter()
+            .find(|l| l["source"] == source && l["target"] == target)
+            .map(|l| l["value"].as_f64().unwrap())
+    };
+    assert_eq!(link("Budget", "Checking (Out)"), Some(120.0));
+    assert_eq!(link("Checking (Out)", "Expenses"), Some(120.0));
+    assert_eq!(link("Expenses", "Food & Dining"), Some(120.0));
+    assert!(!links.iter().any(|l| l.to_string().contains("Card")));
+}
+
 /// Test empty date range returns no data.
 #[tokio::test]
 async fn test_empty_date_range() {
@@ -271,3 +455,722 @@ async fn test_empty_date_range() {
         "Should return empty array for date range with no data"
     );
 }
+
+#[derive(Debug, Deserialize)]
+struct TreeNode {
+    name: String,
+    id: Option<i64>,
+    category_id: Option<i64>,
+    amount_cents: Option<i64>,
+    transaction_count: Option<i64>,
+    drilldown_url: Option<String>,
+    direction: String,
+    children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TreeResponse {
+    categories: Vec<TreeNode>,
+}
+
+fn find_node<'a>(nodes: &'a [TreeNode], name: &str) -> Option<&'a TreeNode> {
+    nodes.iter().find_map(|n| {
+        (n.name == name)
+            .then_some(n)
+            .or_else(|| find_node(&n.children, name))
+    })
+}
+
+/// Test that tree nodes carry transaction counts and drill-down filter ids.
+#[tokio::test]
+async fn test_category_tree_counts_and_links() {
+    let client = TestClient::new();
+    // Groceries (id=12) is a child of Food & Dining (id=4)
+    for desc in ["Market", "Supermarket"] {
+        assert!(
+            client
+                .create_transaction("2024-01-05", "-20.00", desc, None, Some(12))
+                .await
+        );
+    }
+    assert!(
+        client
+            .create_transaction("2024-01-06", "-10.00", "Food truck", None, Some(4))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-01-07", "-5.00", "Mystery", None, None)
+            .await
+    );
+
+    let (status, tree): (_, Option<TreeResponse>) = client
+        .get_json(
+            "/api/analytics/spending-by-category-tree?from_date=2024-01-01&to_date=2024-01-31",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let tree = tree.expect("Failed to parse tree JSON");
+
+    let groceries = find_node(&tree.categories, "Groceries").unwrap();
+    assert_eq!(groceries.category_id, Some(12));
+    assert_eq!(groceries.transaction_count, Some(2));
+
+    let other = find_node(&tree.categories, "Other Food & Dining").unwrap();
+    assert_eq!(other.category_id, Some(4));
+    assert_eq!(other.transaction_count, Some(1));
+
+    let food = find_node(&tree.categories, "Food & Dining").unwrap();
+    assert_eq!(food.transaction_count, Some(3));
+
+    let uncategorized = find_node(&tree.categories, "Uncategorized").unwrap();
+    assert_eq!(uncategorized.id, None);
+    assert_eq!(uncategorized.category_id, Some(0));
+    assert_eq!(uncategorized.transaction_count, Some(1));
+}
+
+fn tree_total(node: &TreeNode) -> i64 {
+    node.amount_cents.unwrap_or(0) + node.children.iter().map(tree_total).sum::<i64>()
+}
+
+/// Count and sum the transactions behind a drill-down URL, using the same
+/// filter the transaction list builds from its query string.
+fn drilldown_totals(client: &TestClient, url: &str) -> (i64, i64) {
+    let query = url.split_once('?').unwrap().1;
+    let params: TransactionFilterParams = serde_urlencoded::from_str(query).unwrap();
+    let filter = transactions::TransactionFilter {
+        category_id: params.category_id.filter(|&id| id != 0),
+        include_children: params.include_children,
+        uncategorized_only: params.is_uncategorized(),
+        from_date: params.from_date.clone(),
+        to_date: params.to_date.clone(),
+        status: params.status_filter(),
+        ..Default::default()
+    };
+    let conn = client.state().db.get().unwrap();
+    let list = transactions::list_transactions(&conn, &filter).unwrap();
+    let sum = list.iter().map(|t| t.transaction.amount_cents).sum::<i64>();
+    (list.len() as i64, -sum)
+}
+
+fn assert_drilldowns_match(client: &TestClient, nodes: &[TreeNode]) {
+    for node in nodes {
+        let url = node.drilldown_url.as_deref().unwrap();
+        assert_eq!(
+            drilldown_totals(client, url),
+            (node.transaction_count.unwrap(), tree_total(node)),
+            "drill-down for {} ({})",
+            node.name,
+            url
+        );
+        assert_drilldowns_match(client, &node.children);
+    }
+}
+
+/// Every tree node's drill-down link lists exactly the transactions that
+/// make up the node, including "Other X" and "Uncategorized".
+#[tokio::test]
+async fn test_category_tree_drilldowns_match_totals() {
+    let client = TestClient::new();
+    // Groceries (12) and Restaurants (13) are children of Food & Dining (4)
+    let rows = [
+        ("2024-01-05", "-20.00", Some(12)),
+        ("2024-01-06", "-7.50", Some(13)),
+        ("2024-01-07", "-10.00", Some(4)),
+        ("2024-01-08", "-3.00", Some(4)),
+        ("2024-01-09", "-5.00", None),
+        ("2024-01-10", "-40.00", Some(6)),
+        // Outside the requested range
+        ("2024-02-01", "-99.00", Some(4)),
+    ];
+    for (date, amount, category) in rows {
+        assert!(
+            client
+                .create_transaction(date, amount, "Spend", None, category)
+                .await
+        );
+    }
+
+    let (status, tree): (_, Option<TreeResponse>) = client
+        .get_json(
+            "/api/analytics/spending-by-category-tree?from_date=2024-01-01&to_date=2024-01-31",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let tree = tree.expect("Failed to parse tree JSON");
+
+    let food = find_node(&tree.categories, "Food & Dining").unwrap();
+    assert!(food
+        .drilldown_url
+        .as_deref()
+        .unwrap()
+        .contains("include_children=true"));
+    let other = find_node(&tree.categories, "Other Food & Dining").unwrap();
+    assert!(!other
+        .drilldown_url
+        .as_deref()
+        .unwrap()
+        .contains("include_children"));
+    assert_drilldowns_match(&client, &tree.categories);
+
+    let url = other.drilldown_url.as_deref().unwrap();
+    let (status, _) = client.get(url).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[derive(Debug, Deserialize)]
+struct CategoryComparison {
+    category: String,
+    current_cents: i64,
+    previous_cents: i64,
+    delta_cents: i64,
+    percent_change: Option<f64>,
+    percent_change_formatted: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModeSpending {
+    category: String,
+    amount_cents: i64,
+    direction: String,
+    percentage: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct MonthlySeries {
+    category: String,
+    direction: String,
+    totals: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MonthlyByCategory {
+    months: Vec<String>,
+    series: Vec<MonthlySeries>,
+}
+
+/// Test the expenses, income and net modes of the spending endpoints.
+#[tokio::test]
+async fn test_spending_modes() {
+    let client = TestClient::new();
+    // Salary on Income (id=2), rent on Housing (id=6), groceries (id=12)
+    // below Food & Dining (id=4), and a refund larger than the groceries
+    // booked on Food & Dining itself
+    for (date, amount, desc, cat) in [
+        ("2024-01-01", "3000.00", "Salary", 2),
+        ("2024-01-02", "-1000.00", "Rent", 6),
+        ("2024-01-03", "-30.00", "Market", 12),
+        ("2024-01-04", "50.00", "Refund", 4),
+        ("2024-02-03", "-80.00", "Restaurant", 4),
+    ] {
+        assert!(
+            client
+                .create_transaction(date, amount, desc, None, Some(cat))
+                .await
+        );
+    }
+    let range = "from_date=2024-01-01&to_date=2024-01-31";
+
+    let by_category = |mode: &str| {
+        let url = format!("/api/analytics/spending-by-category?{range}&mode={mode}");
+        let client = &client;
+        async move {
+            let (status, data): (_, Option<Vec<ModeSpending>>) = client.get_json(&url).await;
+            assert_eq!(status, StatusCode::OK);
+            data.unwrap()
+        }
+    };
+    let find = |data: &[ModeSpending], name: &str| -> (i64, String, f64) {
+        let c = data.iter().find(|c| c.category == name).unwrap();
+        (c.amount_cents, c.direction.clone(), c.percentage)
+    };
+
+    // Expenses: only net-negative categories, shares of total spending
+    let expenses = by_category("expenses").await;
+    assert_eq!(expenses.len(), 2);
+    let (rent, direction, share) = find(&expenses, "Housing");
+    assert_eq!((rent, direction.as_str()), (100_000, "expense"));
+    assert!((share - 100_000.0 / 103_000.0 * 100.0).abs() < 1e-9);
+
+    // Income includes the category whose refunds outweigh its spending
+    let income = by_category("income").await;
+    assert_eq!(income.len(), 2);
+    let (food, direction, share) = find(&income, "Food & Dining");
+    assert_eq!((food, direction.as_str()), (5_000, "income"));
+    assert!((share - 5_000.0 / 305_000.0 * 100.0).abs() < 1e-9);
+
+    // Net: every category, positive amounts with their direction
+    let net = by_category("net").await;
+    assert_eq!(net.len(), 4);
+    assert_eq!(find(&net, "Groceries").1, "expense");
+    assert_eq!(find(&net, "Food & Dining").1, "income");
+    let total: f64 = net.iter().map(|c| c.percentage).sum();
+    assert!((total - 100.0).abs() < 1e-9);
+
+    let (status, _) = client
+        .get(&format!(
+            "/api/analytics/spending-by-category?{range}&mode=bogus"
+        ))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Tree: a parent whose children go both ways is mixed
+    let (status, tree): (_, Option<TreeResponse>) = client
+        .get_json(&format!(
+            "/api/analytics/spending-by-category-tree?{range}&mode=net"
+        ))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let tree = tree.unwrap();
+    let food = find_node(&tree.categories, "Food & Dining").unwrap();
+    assert_eq!(food.direction, "mixed");
+    let other = find_node(&tree.categories, "Other Food & Dining").unwrap();
+    assert_eq!(
+        (other.amount_cents, other.direction.as_str()),
+        (Some(5_000), "income")
+    );
+    let groceries = find_node(&tree.categories, "Groceries").unwrap();
+    assert_eq!(
+        (groceries.amount_cents, groceries.direction.as_str()),
+        (Some(3_000), "expense")
+    );
+
+    // Monthly series in net mode keep the sign of months going the
+    // other way
+    let (status, monthly): (_, Option<MonthlyByCategory>) = client
+        .get_json(
+            "/api/analytics/monthly-by-category?from_date=2024-01-01&to_date=2024-02-29\
+             &category_ids=4,6&mode=net",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let monthly = monthly.unwrap();
+    assert_eq!(monthly.months, ["2024-01", "2024-02"]);
+    let food = monthly
+        .series
+        .iter()
+        .find(|s| s.category == "Food & Dining")
+        .unwrap();
+    assert_eq!(food.direction, "expense");
+    assert_eq!(food.totals, [-5_000, 8_000]);
+}
+
+#[derive(Debug, Deserialize)]
+struct SpendingComparison {
+    previous_from_date: String,
+    previous_to_date: String,
+    current_total_cents: i64,
+    previous_total_cents: i64,
+    total_percent_change_formatted: Option<String>,
+    categories: Vec<CategoryComparison>,
+}
+
+/// Test the spending comparison against the preceding period.
+#[tokio::test]
+async fn test_spending_comparison() {
+    let client = TestClient::new();
+
+    // Food & Dining (id=4) in both months, Transportation (id=5) only in
+    // February, Income (id=2) is left out
+    for (date, amount, category) in [
+        ("2024-02-10", "-30.00", 4),
+        ("2024-03-05", "-50.00", 4),
+        ("2024-03-20", "-10.00", 4),
+        ("2024-02-15", "-100.00", 5),
+        ("2024-03-01", "2000.00", 2),
+    ] {
+        assert!(
+            client
+                .create_transaction(date, amount, "Test", None, Some(category))
+                .await
+        );
+    }
+
+    let (status, parsed): (_, Option<SpendingComparison>) = client
+        .get_json("/api/analytics/spending-comparison?from_date=2024-03-01&to_date=2024-03-31")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let data = parsed.expect("Failed to parse JSON response");
+
+    // A calendar month is compared with the previous calendar month
+    assert_eq!(data.previous_from_date, "2024-02-01");
+    assert_eq!(data.previous_to_date, "2024-02-29");
+    assert_eq!(data.current_total_cents, 6000);
+    assert_eq!(data.previous_total_cents, 13000);
+    assert_eq!(
+        data.total_percent_change_formatted.as_deref(),
+        Some("-53.85%")
+    );
+
+    // Sorted by absolute change: Transportation dropped by 100, Food rose by 30
+    assert_eq!(data.categories.len(), 2);
+    let transport = &data.categories[0];
+    assert_eq!(transport.category, "Transportation");
+    assert_eq!(transport.current_cents, 0);
+    assert_eq!(transport.previous_cents, 10000);
+    assert_eq!(transport.delta_cents, -10000);
+    assert_eq!(transport.percent_change, Some(-100.0));
+    assert_eq!(
+        transport.percent_change_formatted.as_deref(),
+        Some("-100.00%")
+    );
+    let food = &data.categories[1];
+    assert_eq!(food.category, "Food & Dining");
+    assert_eq!(food.delta_cents, 3000);
+    assert_eq!(food.percent_change, Some(100.0));
+
+    // Percentages follow the decimals and locale settings
+    {
+        let conn = client.state().db.get().unwrap();
+        solvency::db::queries::settings::set_setting(&conn, "percent_decimals", "1").unwrap();
+        solvency::db::queries::settings::set_setting(&conn, "locale", "de-DE").unwrap();
+    }
+    client.state().cache.invalidate();
+    let (_, parsed): (_, Option<SpendingComparison>) = client
+        .get_json("/api/analytics/spending-comparison?from_date=2024-03-01&to_date=2024-03-31")
+        .await;
+    let data = parsed.unwrap();
+    assert_eq!(
+        data.total_percent_change_formatted.as_deref(),
+        Some("-53,8%")
+    );
+
+    // Custom ranges are compared with the same number of preceding days
+    let (_, parsed): (_, Option<SpendingComparison>) = client
+        .get_json("/api/analytics/spending-comparison?from_date=2024-03-05&to_date=2024-03-14")
+        .await;
+    let data = parsed.unwrap();
+    assert_eq!(data.previous_from_date, "2024-02-24");
+    assert_eq!(data.previous_to_date, "2024-03-04");
+
+    let (status, _) = client
+        .get("/api/analytics/spending-comparison?from_date=2024-03-31&to_date=2024-03-01")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client.get("/api/analytics/spending-comparison").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, body) = client.get("/spending").await;
+    assert!(body.contains("spending-comparison-body"));
+}
+
+#[derive(Debug, Deserialize)]
+struct SpendingPatternBucket {
+    index: i64,
+    label: String,
+    total_cents: i64,
+    transaction_count: i64,
+    average_cents: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpendingPatterns {
+    weekdays: Vec<SpendingPatternBucket>,
+    month_days: Vec<SpendingPatternBucket>,
+}
+
+/// Spending patterns group expenses by weekday and part of the month,
+/// leaving out income and transfers.
+#[tokio::test]
+async fn test_spending_patterns() {
+    let client = TestClient::new();
+
+    // 2024-01-06 and 2024-01-13 are Saturdays, 2024-01-22 is a Monday
+    for (date, amount, category) in [
+        ("2024-01-06", "-40.00", Some(4)),
+        ("2024-01-13", "-20.00", Some(4)),
+        ("2024-01-22", "-15.00", Some(5)),
+        ("2024-01-22", "1000.00", None),
+        ("2024-01-08", "-500.00", Some(3)),
+    ] {
+        assert!(
+            client
+                .create_transaction(date, amount, "Test", None, category)
+                .await
+        );
+    }
+
+    let (status, parsed): (_, Option<SpendingPatterns>) = client
+        .get_json("/api/analytics/spending-patterns?from_date=2024-01-01&to_date=2024-01-31")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let data = parsed.expect("Failed to parse JSON response");
+
+    // The default en-US locale starts weeks on Sunday
+    assert_eq!(data.weekdays.len(), 7);
+    assert_eq!(data.weekdays[0].index, 0);
+    assert_eq!(data.weekdays[0].label, "Sunday");
+    let saturday = &data.weekdays[6];
+    assert_eq!(saturday.total_cents, 6000);
+    assert_eq!(saturday.transaction_count, 2);
+    assert_eq!(saturday.average_cents, 3000);
+    assert_eq!(data.weekdays[1].total_cents, 1500);
+
+    let totals: Vec<i64> = data.month_days.iter().map(|b| b.total_cents).collect();
+    assert_eq!(totals, vec![4000, 2000, 1500]);
+    assert_eq!(data.month_days[2].label, "21–31");
+}
+
+/// Weekday names and order follow the locale setting.
+#[tokio::test]
+async fn test_spending_patterns_locale_week_start() {
+    let client = TestClient::new();
+    {
+        let conn = client.state().db.get().unwrap();
+        solvency::db::queries::settings::set_setting(&conn, "locale", "de-DE").unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let (_, parsed): (_, Option<SpendingPatterns>) =
+        client.get_json("/api/analytics/spending-patterns").await;
+    let data = parsed.expect("Failed to parse JSON response");
+    assert_eq!(data.weekdays[0].index, 1);
+    assert_eq!(data.weekdays[0].label, "Montag");
+    assert_eq!(data.weekdays[6].index, 0);
+}
+
+/// Dates `count` cycles of `months` apart, the last one `days_ago` days ago.
+fn cadence(months: u32, count: u32, days_ago: i64) -> Vec<String> {
+    let last = chrono::Local::now().date_naive() - chrono::Duration::days(days_ago);
+    (0..count)
+        .rev()
+        .map(|i| {
+            (last - chrono::Months::new(months * i))
+                .format("%Y-%m-%d")
+                .to_string()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_subscriptions_page_and_json() {
+    let client = TestClient::new();
+
+    let histories = [
+        (cadence(1, 4, 10), "-12.99", "Streamly Premium"),
+        (cadence(3, 4, 20), "-30.00", "Home Insurance"),
+        (cadence(12, 4, 30), "-60.00", "Domain Renewal"),
+        (cadence(1, 4, 100), "-20.00", "Old Gym"),
+    ];
+    for (dates, amount, description) in &histories {
+        for date in dates {
+            client
+                .create_transaction(date, amount, description, None, None)
+                .await;
+        }
+    }
+
+    let (status, body) = client.get("/spending/subscriptions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Streamly Premium"));
+    assert!(body.contains("/transactions?search=Streamly%20Premium"));
+    assert!(body.contains("Possibly Cancelled"));
+
+    let (status, summary) = client
+        .get_json::<serde_json::Value>("/api/spending/subscriptions")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let summary = summary.unwrap();
+    let active = summary["subscriptions"].as_array().unwrap();
+    let find = |name: &str| {
+        active
+            .iter()
+            .find(|s| s["description"] == name)
+            .unwrap_or_else(|| panic!("{} not detected", name))
+    };
+    assert_eq!(find("Streamly Premium")["frequency"], "monthly");
+    assert_eq!(find("Streamly Premium")["monthly_cost_cents"], 1299);
+    assert_eq!(find("Home Insurance")["frequency"], "quarterly");
+    assert_eq!(find("Home Insurance")["monthly_cost_cents"], 1000);
+    assert_eq!(find("Domain Renewal")["frequency"], "yearly");
+    assert_eq!(find("Domain Renewal")["monthly_cost_cents"], 500);
+    assert_eq!(summary["count"], 3);
+    assert_eq!(summary["monthly_total_cents"], 1299 + 1000 + 500);
+
+    let cancelled = summary["possibly_cancelled"].as_array().unwrap();
+    assert_eq!(cancelled.len(), 1);
+    assert_eq!(cancelled[0]["description"], "Old Gym");
+
+    let (status, body) = client.get("/transactions?search=Streamly%20Premium").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.matches("Streamly Premium").count() >= 4);
+    assert!(!body.contains("Home Insurance"));
+}
+
+/// Spending endpoints never show tags, so they must not query them even on a
+/// large ledger.
+#[tokio::test]
+async fn test_spending_endpoints_skip_tags_query() {
+    let client = TestClient::new();
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute_batch(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 20000)
+             INSERT INTO transactions (date, amount_cents, currency, description, category_id)
+             SELECT printf('2024-%02d-%02d', i % 12 + 1, i % 28 + 1), -(i % 5000) - 1, 'EUR',
+                    'Purchase ' || i, 4 + i % 2
+             FROM n;
+             INSERT INTO tags (name) VALUES ('Holiday');
+             INSERT INTO transaction_tags (transaction_id, tag_id)
+             SELECT id, 1 FROM transactions WHERE id % 10 = 0;",
+        )
+        .unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let uris = [
+        "/spending/category-transactions?category_id=4&from_date=2024-01-01&to_date=2024-12-31",
+        "/spending/monthly-transactions?month=2024-03&category_ids=4,5",
+        "/api/analytics/monthly-by-category?from_date=2024-01-01&to_date=2024-12-31&category_ids=4,5",
+    ];
+    for uri in uris {
+        let ((status, _), queries) = solvency::timing::capture_queries(client.get(uri)).await;
+        assert_eq!(status, StatusCode::OK, "{}", uri);
+        assert!(
+            queries.iter().any(|q| q.contains("FROM transactions")),
+            "{} ran no transaction query",
+            uri
+        );
+        assert!(
+            !queries.iter().any(|q| q.contains("transaction_tags")),
+            "{} loaded tags",
+            uri
+        );
+    }
+
+    // The transaction list shows tags and still loads them
+    let ((status, _), queries) =
+        solvency::timing::capture_queries(client.get("/transactions/table")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(queries.iter().any(|q| q.contains("transaction_tags")));
+}
+
+/// Saved charts are listed on the spending page with their data URL, can be
+/// updated and deleted, and lose the series of a deleted category.
+#[tokio::test]
+async fn test_saved_charts() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-05", "-50.00", "Groceries", None, Some(4))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-01-06", "-20.00", "Bus fare", None, Some(5))
+            .await
+    );
+
+    // Monthly charts by category need categories
+    let (status, _) = client
+        .post_form(
+            "/spending/charts",
+            &[("name", "Empty"), ("chart_type", "monthly-by-category")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = client
+        .post_form(
+            "/spending/charts",
+            &[
+                ("name", "Food vs transport"),
+                ("chart_type", "monthly-by-category"),
+                ("category_ids", "4"),
+                ("category_ids", "5"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (status, charts): (_, Option<serde_json::Value>) =
+        client.get_json("/spending/charts").await;
+    assert_eq!(status, StatusCode::OK);
+    let charts = charts.unwrap();
+    assert_eq!(charts.as_array().unwrap().len(), 1);
+    assert_eq!(charts[0]["chart_type"], "monthly-by-category");
+    assert_eq!(
+        charts[0]["params"]["category_ids"],
+        serde_json::json!([4, 5])
+    );
+    let id = charts[0]["id"].as_i64().unwrap();
+
+    let (_, body) = client
+        .get("/spending?from_date=2024-01-01&to_date=2024-01-31")
+        .await;
+    assert!(body.contains("Food vs transport"));
+    assert!(body.contains(
+        "/api/analytics/monthly-by-category?from_date=2024-01-01&#38;to_date=2024-01-31&#38;category_ids=4,5"
+    ));
+
+    // Deleting a category drops its series instead of breaking the chart
+    let (status, _) = client.delete_request("/categories/5").await;
+    assert!(status.is_success(), "{}", status);
+    client.state().cache.invalidate();
+    let (_, body) = client
+        .get("/spending?from_date=2024-01-01&to_date=2024-01-31")
+        .await;
+    assert!(body.contains("&#38;category_ids=4\""));
+    let (status, data): (_, Option<serde_json::Value>) = client
+        .get_json(
+            "/api/analytics/monthly-by-category?from_date=2024-01-01&to_date=2024-01-31&category_ids=4,5",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(data.unwrap()["series"].as_array().unwrap().len(), 1);
+
+    // Update to a comparison limited to one category
+    let (status, _) = client
+        .post_form(
+            &format!("/spending/charts/{}", id),
+            &[
+                ("name", "Food"),
+                ("chart_type", "comparison"),
+                ("category_ids", "4"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (_, chart): (_, Option<serde_json::Value>) =
+        client.get_json(&format!("/spending/charts/{}", id)).await;
+    assert_eq!(chart.unwrap()["chart_type"], "comparison");
+    let (_, comparison): (_, Option<serde_json::Value>) = client
+        .get_json(
+            "/api/analytics/spending-comparison?from_date=2024-01-01&to_date=2024-01-31&category_ids=4",
+        )
+        .await;
+    let comparison = comparison.unwrap();
+    assert_eq!(comparison["categories"].as_array().unwrap().len(), 1);
+    assert_eq!(comparison["current_total_cents"], 5_000);
+
+    // Only monthly charts by category can show income
+    let (status, _) = client
+        .post_form(
+            &format!("/spending/charts/{}", id),
+            &[
+                ("name", "Food"),
+                ("chart_type", "comparison"),
+                ("mode", "income"),
+                ("category_ids", "4"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Without any of its categories left, the chart shows an empty state
+    // rather than all categories
+    let (status, _) = client.delete_request("/categories/4").await;
+    assert!(status.is_success(), "{}", status);
+    client.state().cache.invalidate();
+    let (_, body) = client
+        .get("/spending?from_date=2024-01-01&to_date=2024-01-31")
+        .await;
+    assert!(body.contains("All categories of this chart have been deleted."));
+    assert!(!body.contains("/api/analytics/spending-comparison?"));
+
+    let (status, _) = client
+        .delete_request(&format!("/spending/charts/{}", id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = client.get(&format!("/spending/charts/{}", id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
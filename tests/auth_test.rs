@@ -2,9 +2,12 @@
 
 mod common;
 
-use axum::http::StatusCode;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
 use common::TestClient;
 use solvency::config::AuthMode;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
 
 // A valid Argon2 hash for the password "testpass123"
 // Generated with: echo -n 'testpass123' | argon2 somesalt -id -e
@@ -221,3 +224,63 @@ async fn test_no_auth_mode_allows_api_access() {
         "API should be accessible without auth when no password is set"
     );
 }
+
+// =============================================================================
+// Tests for session lifetime
+// =============================================================================
+
+/// Request an API endpoint through the auth middleware with a session cookie.
+async fn get_with_session(client: &TestClient, token: &str) -> StatusCode {
+    let request = Request::builder()
+        .uri("/api/analytics/spending-by-category")
+        .header("Cookie", format!("session={}", token))
+        .body(Body::empty())
+        .unwrap();
+    client
+        .router_with_auth()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .status()
+}
+
+/// Sessions older than the configured lifetime are rejected and forgotten;
+/// without a lifetime they stay valid.
+#[tokio::test]
+async fn test_session_ttl_expires_old_sessions() {
+    let client = auth_client();
+    let two_hours_ago = Instant::now()
+        .checked_sub(Duration::from_secs(2 * 3600))
+        .unwrap();
+    {
+        let mut sessions = client.state().sessions.lock().unwrap();
+        sessions.insert("fresh".into(), Instant::now());
+        sessions.insert("stale".into(), two_hours_ago);
+    }
+    assert_eq!(get_with_session(&client, "stale").await, StatusCode::OK);
+
+    let (status, _) = client
+        .post_form(
+            "/settings/advanced",
+            &[
+                ("log_filter", ""),
+                ("market_data_delay_ms", "500"),
+                ("session_ttl_hours", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    client.state().cache.invalidate();
+
+    assert_eq!(get_with_session(&client, "fresh").await, StatusCode::OK);
+    assert_eq!(
+        get_with_session(&client, "stale").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert!(!client
+        .state()
+        .sessions
+        .lock()
+        .unwrap()
+        .contains_key("stale"));
+}
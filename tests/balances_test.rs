@@ -167,3 +167,366 @@ async fn test_large_amounts() {
         "Large amount not displayed correctly"
     );
 }
+
+/// Test interest projection for an account with an interest rate.
+#[tokio::test]
+async fn test_account_interest_projection() {
+    let client = TestClient::new();
+
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Savings"),
+                ("account_type", "Cash"),
+                ("active", "on"),
+                ("interest_rate", "12"),
+                ("interest_compounding", "monthly"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(
+        client
+            .create_transaction("2024-01-01", "1000.00", "Deposit", Some(1), None)
+            .await
+    );
+
+    let (status, projection) = client
+        .get_json::<serde_json::Value>("/api/accounts/1/interest-projection")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let projection = projection.unwrap();
+    assert_eq!(projection["balance_cents"], 100_000);
+    assert_eq!(projection["compounding"], "monthly");
+    // 1,000.00 at 1% a month compounded over 12 months
+    assert_eq!(projection["total_projected_cents"], 12_683);
+    let months = projection["months"].as_array().unwrap();
+    assert_eq!(months.len(), 12);
+    assert_eq!(months[0]["projected_cents"], 1_000);
+    assert_eq!(months[1]["projected_cents"], 1_010);
+
+    // Interest booked this month shows up as actual interest
+    let month = months[0]["month"].as_str().unwrap().to_string();
+    let interest_category = {
+        let conn = client.state().db.get().unwrap();
+        conn.execute("INSERT INTO categories (name) VALUES ('Interest')", [])
+            .unwrap();
+        conn.last_insert_rowid()
+    };
+    let date = format!("{}-01", month);
+    assert!(
+        client
+            .create_transaction(&date, "2.50", "Interest", Some(1), Some(interest_category))
+            .await
+    );
+    let (_, projection) = client
+        .get_json::<serde_json::Value>("/api/accounts/1/interest-projection?months=3&basis=current")
+        .await;
+    let projection = projection.unwrap();
+    assert_eq!(projection["months"].as_array().unwrap().len(), 3);
+    assert_eq!(projection["months"][0]["actual_cents"], 250);
+    assert_eq!(projection["months"][1]["actual_cents"], 0);
+
+    // The dashboard sums projections across interest-bearing accounts
+    let (_, body) = client.get("/").await;
+    assert!(body.contains("projected-interest"));
+
+    // Quarterly and yearly credits compound less often, so they project less
+    // than monthly ones from the same balance
+    let (_, monthly) = client
+        .get_json::<serde_json::Value>("/api/accounts/1/interest-projection?basis=current")
+        .await;
+    let monthly = monthly.unwrap();
+    let first_month = monthly["months"][0]["projected_cents"].as_i64().unwrap();
+    let mut previous_total = monthly["total_projected_cents"].as_i64().unwrap();
+    for compounding in ["quarterly", "yearly"] {
+        let (status, _) = client
+            .post_form(
+                "/accounts/1/update",
+                &[
+                    ("name", "Savings"),
+                    ("account_type", "Cash"),
+                    ("active", "on"),
+                    ("interest_rate", "12"),
+                    ("interest_compounding", compounding),
+                ],
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (_, projection) = client
+            .get_json::<serde_json::Value>("/api/accounts/1/interest-projection?basis=current")
+            .await;
+        let projection = projection.unwrap();
+        assert_eq!(projection["compounding"], compounding);
+        assert_eq!(projection["months"][0]["projected_cents"], first_month);
+        let total = projection["total_projected_cents"].as_i64().unwrap();
+        assert!(total >= 12 * (first_month - 1) && total < previous_total);
+        previous_total = total;
+    }
+
+    // Invalid requests
+    let (status, _) = client
+        .get("/api/accounts/1/interest-projection?months=0")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client
+        .get("/api/accounts/1/interest-projection?basis=median")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client.get("/api/accounts/2/interest-projection").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client.get("/api/accounts/99/interest-projection").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = client
+        .post_form(
+            "/accounts/1/update",
+            &[
+                ("name", "Savings"),
+                ("account_type", "Cash"),
+                ("interest_rate", "-1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client
+        .post_form(
+            "/accounts/1/update",
+            &[
+                ("name", "Savings"),
+                ("account_type", "Cash"),
+                ("interest_rate", "12"),
+                ("interest_compounding", "daily"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Pending transactions count towards the balance only once marked as posted.
+#[tokio::test]
+async fn test_pending_transaction_excluded_until_posted() {
+    use solvency::db::queries::balances;
+
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(
+        client
+            .create_transaction("2024-01-01", "100.00", "Deposit", Some(1), None)
+            .await
+    );
+    let (status, _) = client
+        .post_form(
+            "/transactions/create",
+            &[
+                ("date", "2024-01-02"),
+                ("amount", "-30.00"),
+                ("currency", "USD"),
+                ("description", "Card payment"),
+                ("account_id", "1"),
+                ("pending", "on"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let balance = || {
+        let conn = client.state().db.get().unwrap();
+        balances::get_account_balance(&conn, 1).unwrap()
+    };
+    assert_eq!(balance(), 10_000);
+
+    let (status, body) = client
+        .get("/transactions/table?preset=all&status=pending")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Card payment"));
+    assert!(body.contains("Mark posted"));
+    assert!(!body.contains("Deposit"));
+
+    let (status, body) = client.post_form("/transactions/2/mark-posted", &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Card payment"));
+    assert!(!body.contains("Mark posted"));
+    assert_eq!(balance(), 7_000);
+
+    let (status, _) = client.post_form("/transactions/99/mark-posted", &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// Create a securities account that derives its cash from trading activities.
+async fn create_brokerage_account(client: &TestClient) {
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Brokerage"),
+                ("account_type", "Securities"),
+                ("active", "on"),
+                ("derive_cash_from_trading", "on"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+}
+
+async fn create_account_activity(
+    client: &TestClient,
+    date: &str,
+    activity_type: &str,
+    quantity: &str,
+    unit_price: &str,
+) {
+    let (status, _) = client
+        .post_form(
+            "/trading/activities/create",
+            &[
+                ("date", date),
+                ("symbol", "AAPL"),
+                ("activity_type", activity_type),
+                ("quantity", quantity),
+                ("unit_price", unit_price),
+                ("currency", "USD"),
+                ("fee", "1.00"),
+                ("account_id", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+}
+
+/// Test that a flagged securities account adds its derived cash to its
+/// positions, counting transfers but ignoring other transactions.
+#[tokio::test]
+async fn test_securities_account_derives_cash_from_trading() {
+    let client = TestClient::new();
+    create_brokerage_account(&client).await;
+
+    // Deposit (Transfers, id=3), buy for $800 + $1 fee, dividend of $50 - $1 fee
+    assert!(
+        client
+            .create_transaction("2024-01-01", "1000.00", "Deposit", Some(1), Some(3))
+            .await
+    );
+    create_account_activity(&client, "2024-01-02", "BUY", "10", "80.00").await;
+    create_account_activity(&client, "2024-02-01", "DIVIDEND", "1", "50.00").await;
+    // The dividend booked again as a transaction must not count twice
+    assert!(
+        client
+            .create_transaction("2024-02-01", "50.00", "AAPL dividend", Some(1), Some(2))
+            .await
+    );
+
+    let (status, body) = client.get("/balances").await;
+    assert_eq!(status, StatusCode::OK);
+    // $800 of shares at cost plus $248 of cash
+    assert!(body.contains("$1,048.00"), "Expected combined balance");
+    assert!(body.contains("incl. $248.00 cash"));
+    assert!(body.contains("ignored-transaction-warnings"));
+    assert!(body.contains("1 transaction</a> ignored"));
+    assert!(!body.contains("cash-drift-warnings"));
+
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(!body.contains("cash-drift-warnings"));
+
+    // Without the flag only the positions count, as before
+    let (status, _) = client
+        .post_form(
+            "/accounts/1/update",
+            &[
+                ("name", "Brokerage"),
+                ("account_type", "Securities"),
+                ("active", "on"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (_, body) = client.get("/balances").await;
+    assert!(body.contains("$800.00"));
+    assert!(!body.contains("incl."));
+    assert!(!body.contains("ignored-transaction-warnings"));
+}
+
+/// Test the balances API for external tools, including historical balances.
+#[tokio::test]
+async fn test_balances_api() {
+    let client = TestClient::new();
+    create_brokerage_account(&client).await;
+    assert!(client.create_account("Checking", "Cash").await);
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[("name", "Old savings"), ("account_type", "Cash")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    assert!(
+        client
+            .create_transaction("2024-01-01", "1000.00", "Deposit", Some(1), Some(3))
+            .await
+    );
+    create_account_activity(&client, "2024-01-02", "BUY", "10", "80.00").await;
+    assert!(
+        client
+            .create_transaction("2024-01-05", "500.00", "Salary", Some(2), None)
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-02-10", "-120.00", "Rent", Some(2), Some(1))
+            .await
+    );
+
+    let (status, json) = client
+        .get_json::<serde_json::Value>("/api/accounts/balances")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let accounts = json.unwrap();
+    let accounts = accounts.as_array().unwrap();
+    // The inactive account is left out
+    assert_eq!(accounts.len(), 2);
+
+    let brokerage = &accounts[0];
+    assert_eq!(brokerage["name"], "Brokerage");
+    assert_eq!(brokerage["account_type"], "Securities");
+    // Cash after the $801 buy, not the value of the shares
+    assert_eq!(brokerage["balance_cents"], 19900);
+    let checking = &accounts[1];
+    assert_eq!(checking["name"], "Checking");
+    assert_eq!(checking["currency"], "USD");
+    assert_eq!(checking["balance_cents"], 38000);
+    assert_eq!(checking["last_transaction_date"], "2024-02-10");
+
+    let (_, json) = client
+        .get_json::<serde_json::Value>("/api/accounts/balances?as_of=2024-01-01")
+        .await;
+    let accounts = json.unwrap();
+    assert_eq!(accounts[0]["balance_cents"], 100000);
+    assert_eq!(accounts[1]["balance_cents"], 0);
+    assert!(accounts[1]["last_transaction_date"].is_null());
+
+    let (status, _) = client.get("/api/accounts/balances?as_of=January").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Test that the positions page warns when derived cash goes negative.
+#[tokio::test]
+async fn test_negative_derived_cash_warns_on_positions_page() {
+    let client = TestClient::new();
+    create_brokerage_account(&client).await;
+
+    create_account_activity(&client, "2024-01-02", "BUY", "10", "80.00").await;
+    assert!(
+        client
+            .create_transaction("2024-01-05", "1000.00", "Deposit", Some(1), Some(3))
+            .await
+    );
+
+    let (status, body) = client.get("/trading/positions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("cash-drift-warnings"));
+    assert!(body.contains("Brokerage: -\u{2060}$801.00 on 2024-01-02"));
+}
@@ -6,6 +6,7 @@ use axum::body::Body;
 use axum::http::{Request, StatusCode};
 use common::TestClient;
 use http_body_util::BodyExt;
+use solvency::cache::DataDomain;
 use solvency::db::queries::{accounts, categories, tags};
 use solvency::models::{NewAccount, NewCategory, NewTag};
 use tower::ServiceExt;
@@ -52,6 +53,13 @@ async fn test_stale_cache_refreshes_after_invalidation() {
             name: "Savings".into(),
             account_type: solvency::models::AccountType::Cash,
             active: true,
+            interest_rate_bps: None,
+            interest_compounding: Default::default(),
+            derive_cash_from_trading: false,
+            statement_day: None,
+            due_day: None,
+            cash_account_id: None,
+            iban: None,
         },
     )
     .unwrap();
@@ -67,6 +75,24 @@ async fn test_stale_cache_refreshes_after_invalidation() {
     assert_eq!(fresh[0].name, "Savings");
 }
 
+/// Tags created by an import show up in the cached tag list once the import
+/// request invalidated its domains.
+#[tokio::test]
+async fn test_import_refreshes_cached_tags() {
+    let client = TestClient::new();
+    let state = client.state();
+    assert!(state.cached_tags().unwrap().is_empty());
+
+    tags::create_or_get_tag(&state.db.get().unwrap(), "imported").unwrap();
+    state
+        .cache
+        .invalidate_domains(DataDomain::for_path("/import/abc/confirm"));
+
+    let fresh = state.cached_tags().unwrap();
+    assert_eq!(fresh.len(), 1);
+    assert_eq!(fresh[0].name, "imported");
+}
+
 /// Invalidation affects all cached slots, not just one.
 #[tokio::test]
 async fn test_invalidation_clears_all_slots() {
@@ -108,6 +134,13 @@ async fn test_invalidation_clears_all_slots() {
             name: "Checking".into(),
             account_type: solvency::models::AccountType::Cash,
             active: true,
+            interest_rate_bps: None,
+            interest_compounding: Default::default(),
+            derive_cash_from_trading: false,
+            statement_day: None,
+            due_day: None,
+            cash_account_id: None,
+            iban: None,
         },
     )
     .unwrap();
@@ -233,6 +266,13 @@ async fn test_get_does_not_invalidate() {
             name: "Hidden".into(),
             account_type: solvency::models::AccountType::Cash,
             active: true,
+            interest_rate_bps: None,
+            interest_compounding: Default::default(),
+            derive_cash_from_trading: false,
+            statement_day: None,
+            due_day: None,
+            cash_account_id: None,
+            iban: None,
         },
     )
     .unwrap();
@@ -260,6 +300,13 @@ async fn test_failed_post_does_not_invalidate() {
             name: "Original".into(),
             account_type: solvency::models::AccountType::Cash,
             active: true,
+            interest_rate_bps: None,
+            interest_compounding: Default::default(),
+            derive_cash_from_trading: false,
+            statement_day: None,
+            due_day: None,
+            cash_account_id: None,
+            iban: None,
         },
     )
     .unwrap();
@@ -275,6 +322,13 @@ async fn test_failed_post_does_not_invalidate() {
             name: "Sneaky".into(),
             account_type: solvency::models::AccountType::Cash,
             active: true,
+            interest_rate_bps: None,
+            interest_compounding: Default::default(),
+            derive_cash_from_trading: false,
+            statement_day: None,
+            due_day: None,
+            cash_account_id: None,
+            iban: None,
         },
     )
     .unwrap();
@@ -351,3 +405,133 @@ async fn test_category_visible_after_creation() {
         "newly created category not visible"
     );
 }
+
+// ---------------------------------------------------------------------------
+// Per-domain versions
+// ---------------------------------------------------------------------------
+
+/// Mutating requests bump only the domains their path writes to.
+#[test]
+fn test_domains_for_path() {
+    use solvency::cache::DataDomain::{self, *};
+
+    let cases: &[(&str, &[DataDomain])] = &[
+        ("/transactions/create", &[Transactions]),
+        ("/transactions/bulk/clear-tags", &[Transactions]),
+        ("/import/abc/confirm", &[Transactions, Tags]),
+        ("/rules/1/apply", &[Transactions]),
+        ("/trading/activities/create", &[Trading, Transactions]),
+        ("/trading/import/upload", &[Trading]),
+        ("/trading/market-data/refresh", &[MarketData]),
+        ("/api/symbols/select", &[MarketData]),
+        ("/trading/api-logs/3/retry", &[]),
+        ("/categories/create", &[Categories]),
+        ("/tags/1/update", &[Tags]),
+        ("/accounts/create", &[Accounts]),
+        ("/settings/theme", &[Settings]),
+        ("/settings/import-database", &DataDomain::ALL),
+        ("/manage/import", &DataDomain::ALL),
+        ("/retirement/create", &[]),
+        ("/transactionsx", &DataDomain::ALL),
+    ];
+    for (path, expected) in cases {
+        assert_eq!(DataDomain::for_path(path), *expected, "{}", path);
+    }
+}
+
+/// The net worth series survives writes to domains it does not depend on
+/// and is recomputed after writes to those it does.
+#[tokio::test]
+async fn test_net_worth_series_invalidation_matrix() {
+    let client = TestClient::new();
+    let state = client.state();
+
+    // (path, form, whether the series is recomputed)
+    type Case<'a> = (&'a str, &'a [(&'a str, &'a str)], bool);
+    let cases: &[Case] = &[
+        (
+            "/categories/create",
+            &[("name", "Groceries"), ("color", "#22c55e")],
+            false,
+        ),
+        (
+            "/tags/create",
+            &[("name", "urgent"), ("color", "#ff0000")],
+            false,
+        ),
+        (
+            "/trading/activities/create",
+            &[
+                ("date", "2024-01-02"),
+                ("symbol", "VTI"),
+                ("activity_type", "BUY"),
+                ("quantity", "1"),
+                ("unit_price", "100.00"),
+                ("currency", "USD"),
+                ("fee", "0"),
+            ],
+            true,
+        ),
+        (
+            "/transactions/create",
+            &[
+                ("date", "2024-01-03"),
+                ("amount", "-5.00"),
+                ("currency", "USD"),
+                ("description", "Coffee"),
+            ],
+            true,
+        ),
+        (
+            "/accounts/create",
+            &[("name", "Bank"), ("account_type", "Cash"), ("active", "on")],
+            true,
+        ),
+        // Joins the Transfers subtree, which decides what counts as external
+        (
+            "/categories/create",
+            &[
+                ("name", "Savings"),
+                ("parent_id", "3"),
+                ("color", "#22c55e"),
+            ],
+            true,
+        ),
+    ];
+    for (path, form, recomputed) in cases {
+        let before = state.cached_net_worth().unwrap();
+        let (status, _) = post_form(client.router_with_cache(), path, form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER, "{}", path);
+        let after = state.cached_net_worth().unwrap();
+        assert_eq!(
+            !std::sync::Arc::ptr_eq(&before, &after),
+            *recomputed,
+            "{}",
+            path
+        );
+    }
+}
+
+/// The cache stats endpoint reports the version of every domain.
+#[tokio::test]
+async fn test_cache_stats_report_domain_versions() {
+    let client = TestClient::new();
+
+    let (status, _) = post_form(
+        client.router_with_cache(),
+        "/tags/create",
+        &[("name", "urgent"), ("color", "#ff0000")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let (status, body) = request(client.router_with_cache(), "GET", "/api/cache/stats").await;
+    assert_eq!(status, StatusCode::OK);
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let versions = stats["versions"].as_array().unwrap();
+    assert_eq!(versions.len(), 7);
+    for entry in versions {
+        let expected = if entry["domain"] == "tags" { 1 } else { 0 };
+        assert_eq!(entry["version"], expected, "{}", entry["domain"]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Integration tests for category colors: palette, automatic assignment
+//! and readable text colors.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestClient;
+use solvency::db::queries::categories;
+use solvency::palette;
+
+fn category_color(client: &TestClient, name: &str) -> String {
+    let conn = client.state().db.get().unwrap();
+    categories::list_categories(&conn)
+        .unwrap()
+        .into_iter()
+        .find(|c| c.name == name)
+        .unwrap()
+        .color
+}
+
+#[tokio::test]
+async fn test_new_category_gets_least_used_palette_color() {
+    let client = TestClient::new();
+    let used: Vec<String> = {
+        let conn = client.state().db.get().unwrap();
+        categories::list_categories(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.color)
+            .collect()
+    };
+    let expected = palette::least_used(used.iter().map(String::as_str));
+
+    let (status, _) = client
+        .post_form("/categories/create", &[("name", "Hobbies"), ("color", "")])
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(category_color(&client, "Hobbies"), expected);
+
+    // The next one avoids the color just taken
+    client
+        .post_form("/categories/create", &[("name", "Garden")])
+        .await;
+    assert_ne!(category_color(&client, "Garden"), expected);
+}
+
+#[tokio::test]
+async fn test_category_color_must_be_hex() {
+    let client = TestClient::new();
+
+    let (status, _) = client
+        .post_form(
+            "/categories/create",
+            &[("name", "Travel"), ("color", "#3B82F6")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(category_color(&client, "Travel"), "#3b82f6");
+
+    let (status, _) = client
+        .post_form(
+            "/categories/create",
+            &[("name", "Broken"), ("color", "red\" onclick=\"x")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_palette_endpoint_and_text_colors() {
+    let client = TestClient::new();
+
+    let (status, palette) = client.get_json::<serde_json::Value>("/api/palette").await;
+    assert_eq!(status, StatusCode::OK);
+    let palette = palette.unwrap();
+    let yellow = palette
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "Yellow")
+        .unwrap();
+    assert_eq!(yellow["color"], "#eab308");
+    assert_eq!(yellow["text_color"], "#000000");
+
+    client
+        .create_transaction("2024-01-05", "-20.00", "Lunch", None, Some(4))
+        .await;
+    let (_, spending) = client
+        .get_json::<serde_json::Value>(
+            "/api/analytics/spending-by-category?from_date=2024-01-01&to_date=2024-01-31",
+        )
+        .await;
+    let food = &spending.unwrap()[0];
+    assert_eq!(
+        food["text_color"],
+        palette::text_color(food["color"].as_str().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn test_repalette_recolors_all_categories() {
+    let client = TestClient::new();
+    client
+        .post_form(
+            "/categories/create",
+            &[("name", "Misc"), ("color", "#000000")],
+        )
+        .await;
+
+    let (status, _) = client.post_form("/categories/repalette", &[]).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let conn = client.state().db.get().unwrap();
+    let cats = categories::list_categories_with_path(&conn).unwrap();
+    let colors: Vec<&str> = cats.iter().map(|c| c.category.color.as_str()).collect();
+    assert_eq!(colors, palette::distribute(cats.len()));
+    assert_ne!(category_color(&client, "Misc"), "#000000");
+    for cat in &cats {
+        assert_eq!(cat.text_color, palette::text_color(&cat.category.color));
+    }
+}
+
+/// The categories tab carries a sparkline of the last 12 months of
+/// expenses for each category that had any.
+#[tokio::test]
+async fn test_categories_tab_embeds_expense_sparklines() {
+    use chrono::{Datelike, Months};
+    use solvency::db::queries::transaction_sums;
+
+    let client = TestClient::new();
+    let today = solvency::date_utils::today_in(&client.state().load_settings().unwrap());
+    let month_start = today.with_day(1).unwrap();
+    let date = |months_ago: u32| {
+        (month_start - Months::new(months_ago))
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+
+    // Food & Dining (4): this month and 11 months ago, plus a refund
+    for (date, amount) in [
+        (date(0), "-40.00"),
+        (date(0), "-10.00"),
+        (date(11), "-25.00"),
+        (date(0), "5.00"),
+    ] {
+        assert!(
+            client
+                .create_transaction(&date, amount, "Trend", None, Some(4))
+                .await
+        );
+    }
+    // Housing (6): only before the window
+    assert!(
+        client
+            .create_transaction(&date(12), "-900.00", "Old rent", None, Some(6))
+            .await
+    );
+
+    let sums = {
+        let conn = client.state().db.get().unwrap();
+        transaction_sums::monthly_sums_for_all_categories(&conn, 12, today, &Default::default())
+            .unwrap()
+    };
+    assert_eq!(
+        sums,
+        vec![
+            (4, date(11)[..7].to_string(), 2500),
+            (4, date(0)[..7].to_string(), 5000),
+        ]
+    );
+
+    let (status, body) = client.get("/manage?tab=categories").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"<template id="category-sparklines">"#));
+    assert!(body.contains(r#"data-category-id="4""#));
+    assert!(!body.contains(r#"data-category-id="6""#));
+    assert!(body.contains(r#"<polyline points="0.0,8.0 "#));
+
+    // Other tabs don't compute the trends
+    let (_, body) = client.get("/manage?tab=tags").await;
+    assert!(!body.contains("data-category-id="));
+}
@@ -12,15 +12,20 @@ use axum::routing::{get, post};
 use axum::Router;
 use http_body_util::BodyExt;
 use solvency::auth;
+use solvency::body_limit::DEFAULT_MAX_UPLOAD_MB;
 use solvency::cache::AppCache;
 use solvency::config::{AuthMode, Config};
+use solvency::confirmation::DeleteConfirmations;
+use solvency::db::pool::DEFAULT_POOL_SIZE;
 use solvency::db::queries::trading;
 use solvency::db::{create_in_memory_pool, migrations};
+use solvency::flash::FlashStore;
 use solvency::handlers;
+use solvency::idempotency::SubmittedForms;
 use solvency::models::TradingActivity;
 use solvency::state::{AppState, JsManifest, MarketDataRefreshState};
 use solvency::xsrf::{xsrf_middleware, XsrfToken};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tower::ServiceExt;
@@ -43,7 +48,7 @@ impl TestClient {
         let pool = create_in_memory_pool().expect("Failed to create in-memory pool");
         {
             let conn = pool.get().expect("Failed to get connection");
-            migrations::run_migrations(&conn, Path::new("migrations"))
+            migrations::run_migrations(&conn, Path::new("migrations"), false)
                 .expect("Failed to run migrations");
         }
 
@@ -54,6 +59,14 @@ impl TestClient {
             migrations_path: PathBuf::from("migrations"),
             static_path: PathBuf::from("static"),
             secure_cookies: false,
+            slow_query_ms: None,
+            backup_dir: None,
+            allow_force_delete: true,
+            allow_dirty_migrations: false,
+            max_upload_mb: DEFAULT_MAX_UPLOAD_MB,
+            csp: None,
+            db_pool_size: DEFAULT_POOL_SIZE,
+            db_connection_lifetime_secs: None,
             auth_mode,
         };
 
@@ -63,24 +76,54 @@ impl TestClient {
             manifest: JsManifest::default(),
             xsrf_token: XsrfToken::generate(),
             market_data_refresh: Arc::new(Mutex::new(MarketDataRefreshState::default())),
+            symbol_locks: Arc::new(solvency::symbol_locks::SymbolLocks::new()),
+            symbol_validation: Arc::new(Mutex::new(HashMap::new())),
+            running_imports: Arc::new(Mutex::new(HashSet::new())),
             cache: Arc::new(AppCache::new()),
-            sessions: Arc::new(Mutex::new(HashSet::new())),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            share_sessions: Arc::new(Mutex::new(HashMap::new())),
             login_rate_limiter: Arc::new(solvency::auth::LoginRateLimiter::new()),
+            delete_confirmations: Arc::new(DeleteConfirmations::new()),
+            submitted_forms: Arc::new(SubmittedForms::new()),
+            flash: Arc::new(FlashStore::new()),
+            symbol_search_throttle: Arc::new(
+                solvency::services::market_data::SearchThrottle::default(),
+            ),
+            notification_throttle: Arc::new(
+                solvency::services::notify::NotificationThrottle::default(),
+            ),
+            usage: Arc::new(solvency::usage::UsageRecorder::new()),
         };
 
         Self { state }
     }
 
+    /// Create a test client whose imports accept at most `mb` megabytes.
+    pub fn with_max_upload_mb(mb: usize) -> Self {
+        let mut client = Self::new();
+        let mut config = (*client.state.config).clone();
+        config.max_upload_mb = mb;
+        client.set_config(config);
+        client
+    }
+
+    /// Replace the configuration the routers are built with.
+    pub fn set_config(&mut self, config: Config) {
+        self.state.config = Arc::new(config);
+    }
+
     /// Get the router for making requests (without auth middleware for direct handler testing).
     pub fn router(&self) -> Router {
-        handlers::routes().with_state(self.state.clone())
+        handlers::routes(&self.state.config)
+            .layer(CookieManagerLayer::new())
+            .with_state(self.state.clone())
     }
 
     /// Get the full router with auth middleware applied (mimics production setup).
     pub fn router_with_auth(&self) -> Router {
         use axum::middleware;
 
-        handlers::routes()
+        handlers::routes(&self.state.config)
             .route("/login", get(auth::login_page))
             .route("/login", post(auth::login_submit))
             .route("/logout", post(auth::logout))
@@ -97,7 +140,7 @@ impl TestClient {
         use axum::middleware;
         use solvency::cache::cache_invalidation_middleware;
 
-        handlers::routes()
+        handlers::routes(&self.state.config)
             .layer(middleware::from_fn_with_state(
                 self.state.clone(),
                 cache_invalidation_middleware,
@@ -105,6 +148,71 @@ impl TestClient {
             .with_state(self.state.clone())
     }
 
+    /// Get the router with the flash message middleware applied.
+    pub fn router_with_flash(&self) -> Router {
+        use axum::middleware;
+        use solvency::flash::flash_middleware;
+
+        handlers::routes(&self.state.config)
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                flash_middleware,
+            ))
+            .layer(CookieManagerLayer::new())
+            .with_state(self.state.clone())
+    }
+
+    /// Get the router with the Server-Timing middleware applied.
+    pub fn router_with_timing(&self) -> Router {
+        use axum::middleware;
+        use solvency::timing::server_timing_middleware;
+
+        handlers::routes(&self.state.config)
+            .layer(middleware::from_fn(server_timing_middleware))
+            .with_state(self.state.clone())
+    }
+
+    /// Get the router with the usage statistics middleware applied.
+    pub fn router_with_usage(&self) -> Router {
+        use axum::middleware;
+        use solvency::usage::usage_middleware;
+
+        handlers::routes(&self.state.config)
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                usage_middleware,
+            ))
+            .with_state(self.state.clone())
+    }
+
+    /// Get the router with error pages and request ids (mimics production).
+    pub fn router_with_error_pages(&self) -> Router {
+        use axum::middleware;
+        use solvency::error_pages::error_page_middleware;
+        use solvency::request_id::request_id_middleware;
+
+        handlers::routes(&self.state.config)
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                error_page_middleware,
+            ))
+            .layer(middleware::from_fn(request_id_middleware))
+            .with_state(self.state.clone())
+    }
+
+    /// Get the router with the security headers middleware applied.
+    pub fn router_with_security_headers(&self) -> Router {
+        use axum::middleware;
+        use solvency::csp::security_headers_middleware;
+
+        handlers::routes(&self.state.config)
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                security_headers_middleware,
+            ))
+            .with_state(self.state.clone())
+    }
+
     /// Access the underlying application state.
     pub fn state(&self) -> &AppState {
         &self.state
@@ -115,11 +223,12 @@ impl TestClient {
         use axum::middleware;
 
         let xsrf_token = self.state.xsrf_token.clone();
-        handlers::routes()
+        handlers::routes(&self.state.config)
             .layer(middleware::from_fn(move |req, next| {
                 let token = xsrf_token.clone();
                 xsrf_middleware(token, req, next)
             }))
+            .layer(CookieManagerLayer::new())
             .with_state(self.state.clone())
     }
 
@@ -188,6 +297,26 @@ impl TestClient {
         (status, String::from_utf8_lossy(&body_bytes).to_string())
     }
 
+    /// Make a POST request with a JSON body and return status and body.
+    pub async fn post_json(&self, uri: &str, json: &str) -> (StatusCode, String) {
+        let response = self
+            .router()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(json.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body_bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8_lossy(&body_bytes).to_string())
+    }
+
     /// Make a multipart POST request with XSRF header and return status and body.
     pub async fn post_multipart(
         &self,
@@ -195,21 +324,34 @@ impl TestClient {
         field_name: &str,
         file_name: &str,
         file_content: &[u8],
+    ) -> (StatusCode, String) {
+        self.post_multipart_files(uri, field_name, &[(file_name, file_content)])
+            .await
+    }
+
+    /// Make a multipart POST request with several files in the same field.
+    pub async fn post_multipart_files(
+        &self,
+        uri: &str,
+        field_name: &str,
+        files: &[(&str, &[u8])],
     ) -> (StatusCode, String) {
         let boundary = "----TestBoundary12345";
         let mut body = Vec::new();
 
-        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
-        body.extend_from_slice(
-            format!(
-                "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
-                field_name, file_name
-            )
-            .as_bytes(),
-        );
-        body.extend_from_slice(b"Content-Type: text/csv\r\n\r\n");
-        body.extend_from_slice(file_content);
-        body.extend_from_slice(b"\r\n");
+        for (file_name, file_content) in files {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            body.extend_from_slice(
+                format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
+                    field_name, file_name
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(b"Content-Type: text/csv\r\n\r\n");
+            body.extend_from_slice(file_content);
+            body.extend_from_slice(b"\r\n");
+        }
         body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
 
         let response = self
@@ -398,6 +540,14 @@ impl TestClient {
         status == StatusCode::OK
     }
 
+    /// Restore a trading activity from the trash via the API.
+    pub async fn restore_trading_activity(&self, id: i64) -> bool {
+        let (status, _) = self
+            .post_form(&format!("/trading/activities/{}/restore", id), &[])
+            .await;
+        status == StatusCode::OK
+    }
+
     /// Update a trading activity via POST and return success status.
     pub async fn update_trading_activity(
         &self,
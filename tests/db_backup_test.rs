@@ -8,7 +8,8 @@ mod common;
 
 use axum::http::StatusCode;
 use common::TestClient;
-use solvency::db::queries::{accounts, categories, transactions};
+use solvency::db::queries::{accounts, categories, saved_charts, share_links, transactions};
+use solvency::models::{NewShareLink, ShareScope};
 use std::sync::LazyLock;
 use tokio::sync::Mutex;
 use transactions::TransactionFilter;
@@ -55,6 +56,17 @@ async fn test_export_import_round_trip() {
             .create_transaction("2024-06-01", "-50.00", "Weekly shop", Some(1), Some(1))
             .await
     );
+    let (status, _) = client_a
+        .post_form(
+            "/spending/charts",
+            &[
+                ("name", "Food"),
+                ("chart_type", "monthly-by-category"),
+                ("category_ids", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
 
     // Export
     let (status, exported) = client_a.get_bytes("/settings/export-database").await;
@@ -83,6 +95,11 @@ async fn test_export_import_round_trip() {
     let txns = transactions::list_transactions(&conn, &TransactionFilter::default()).unwrap();
     assert_eq!(txns.len(), 1);
     assert_eq!(txns[0].transaction.description, "Weekly shop");
+
+    let charts = saved_charts::list_saved_charts(&conn).unwrap();
+    assert_eq!(charts.len(), 1);
+    assert_eq!(charts[0].name, "Food");
+    assert_eq!(charts[0].params.category_ids, vec![1]);
 }
 
 /// Import overwrites the existing data in the target database.
@@ -132,6 +149,41 @@ async fn test_import_overwrites_existing_data() {
     assert_eq!(txns[0].transaction.description, "Initial deposit");
 }
 
+/// A backup whose migrations fail is rejected before the live data is touched.
+#[tokio::test]
+async fn test_import_failing_migrations_keeps_existing_data() {
+    let _guard = DB_BACKUP_LOCK.lock().await;
+
+    let client_a = TestClient::new();
+    let (_, exported) = client_a.get_bytes("/settings/export-database").await;
+    // Record a different checksum for an applied migration, as if the
+    // backup came from an instance with an edited migration file
+    let path = std::env::temp_dir().join(format!("solvency-tampered-{}.db", std::process::id()));
+    std::fs::write(&path, &exported).unwrap();
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute(
+            "UPDATE _migrations SET checksum = 'edited'
+             WHERE name = (SELECT MIN(name) FROM _migrations)",
+            [],
+        )
+        .unwrap();
+    let tampered = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let client_b = TestClient::new();
+    assert!(client_b.create_account("Credit Card", "Cash").await);
+    let (status, _) = client_b
+        .post_multipart("/settings/import-database", "file", "backup.db", &tampered)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let conn = client_b.state().db.get().unwrap();
+    let accts = accounts::list_accounts(&conn).unwrap();
+    assert_eq!(accts.len(), 1);
+    assert_eq!(accts[0].name, "Credit Card");
+}
+
 /// Uploading garbage bytes is rejected.
 #[tokio::test]
 async fn test_import_invalid_file_rejected() {
@@ -170,3 +222,400 @@ async fn test_import_empty_file_rejected() {
         "Expected validation error for empty file, got status={status}"
     );
 }
+
+/// Export an anonymized copy with `query` and import it into a fresh client.
+async fn import_anonymized(source: &TestClient, query: &str) -> TestClient {
+    let (status, exported) = source
+        .get_bytes(&format!("/settings/export-anonymized{}", query))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&exported[..16], SQLITE_MAGIC);
+    for secret in [
+        "Weekly shop",
+        "Checking",
+        "DE89370400440532013000",
+        "For my accountant",
+        "Delisted after the merger",
+        "BANK-TX-778120",
+        "BROKER-ORD-55031",
+    ] {
+        assert!(
+            !exported
+                .windows(secret.len())
+                .any(|w| w == secret.as_bytes()),
+            "{secret} leaked into the anonymized export"
+        );
+    }
+
+    let target = TestClient::new();
+    let (status, _) = target
+        .post_multipart("/settings/import-database", "file", "anon.db", &exported)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    target
+}
+
+/// The anonymized export scrubs text, keeps structure and symbols, is
+/// reproducible for a given seed, and renders on every page after import.
+#[tokio::test]
+async fn test_anonymized_export_round_trip() {
+    let _guard = DB_BACKUP_LOCK.lock().await;
+
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(
+        client
+            .create_transaction("2024-06-01", "-50.00", "Weekly shop", Some(1), Some(4))
+            .await
+    );
+    client
+        .state()
+        .db
+        .get()
+        .unwrap()
+        .execute_batch(
+            "UPDATE transactions SET counterparty_iban = 'DE89370400440532013000',
+                 external_id = 'BANK-TX-778120';
+             UPDATE accounts SET iban = 'DE89370400440532013000';",
+        )
+        .unwrap();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "VTI", "BUY", "10", "100.00")
+            .await
+    );
+    client
+        .state()
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "INSERT INTO trading_activities
+                 (date, symbol, activity_type, unit_price_cents, external_id)
+             VALUES ('2024-03-01', 'VTI', 'DIVIDEND', 1234, 'BROKER-ORD-55031')",
+            [],
+        )
+        .unwrap();
+    client
+        .state()
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "INSERT INTO symbol_metadata (symbol, ignored, ignore_note)
+             VALUES ('OLD', 1, 'Delisted after the merger')",
+            [],
+        )
+        .unwrap();
+    let share_token = share_links::create_share_link(
+        &client.state().db.get().unwrap(),
+        &NewShareLink {
+            scope: ShareScope::NetWorth,
+            label: "For my accountant".into(),
+            password_hash: None,
+            expires_in_days: None,
+        },
+    )
+    .unwrap()
+    .token;
+
+    let first = import_anonymized(&client, "?seed=42&scale=true").await;
+    let second = import_anonymized(&client, "?seed=42&scale=true").await;
+
+    let conn = first.state().db.get().unwrap();
+    assert!(share_links::list_share_links(&conn).unwrap().is_empty());
+    let (status, _) = first.get(&format!("/share/{}", share_token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let accts = accounts::list_accounts(&conn).unwrap();
+    assert_eq!(accts.len(), 1);
+    assert_eq!(accts[0].name, "Cash Account 1");
+    // Own IBANs are faked like counterparty IBANs, with the same mapping
+    let own_iban = accts[0].iban.clone().unwrap();
+    assert!(own_iban.starts_with("XX00"), "{own_iban}");
+
+    let txns = transactions::list_transactions(&conn, &TransactionFilter::default()).unwrap();
+    assert_eq!(txns.len(), 1);
+    assert_eq!(txns[0].transaction.category_id, Some(4));
+    assert_eq!(txns[0].transaction.counterparty_iban, Some(own_iban));
+    let other = transactions::list_transactions(
+        &second.state().db.get().unwrap(),
+        &TransactionFilter::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        txns[0].transaction.description,
+        other[0].transaction.description
+    );
+    assert_eq!(
+        txns[0].transaction.amount_cents,
+        other[0].transaction.amount_cents
+    );
+
+    // Dividend amounts reveal position sizes, so they are scaled too
+    let dividend: i64 = conn
+        .query_row(
+            "SELECT unit_price_cents FROM trading_activities WHERE activity_type = 'DIVIDEND'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_ne!(dividend, 1234);
+
+    let (_, csv) = first.get("/trading/positions/export?format=csv").await;
+    assert!(csv.contains("\nVTI,"), "symbol should be preserved: {csv}");
+
+    for page in [
+        "/",
+        "/transactions",
+        "/accounts",
+        "/balances",
+        "/spending",
+        "/recurring-expenses",
+        "/manage",
+        "/trading/activities",
+        "/trading/positions",
+        "/trading/positions/closed",
+        "/trading/net-worth",
+        "/settings",
+    ] {
+        let (status, _) = first.get(page).await;
+        assert_eq!(status, StatusCode::OK, "{page} failed to render");
+    }
+}
+
+/// Create an empty scratch directory unique to this test.
+fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("solvency-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Manual backup writes a SQLite snapshot and prunes per retention.
+#[tokio::test]
+async fn test_backup_now_writes_and_rotates() {
+    let client = TestClient::new();
+    let dir = scratch_dir("backup-now");
+    std::fs::create_dir_all(&dir).unwrap();
+    let stale = dir.join("solvency-20200101-000000.db");
+    std::fs::write(&stale, b"old").unwrap();
+
+    let (status, _) = client
+        .post_form(
+            "/settings/backup",
+            &[
+                ("backup_dir", dir.to_str().unwrap()),
+                ("backup_frequency", "daily"),
+                ("backup_retention", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = client.post_form("/settings/backup-now", &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Backup written to"));
+
+    let files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1, "retention of 1 should prune the stale file");
+    assert_ne!(files[0], stale);
+    let bytes = std::fs::read(&files[0]).unwrap();
+    assert_eq!(&bytes[..16], SQLITE_MAGIC);
+
+    let (_, page) = client.get("/settings").await;
+    assert!(!page.contains("The last backup failed"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A failed backup is surfaced as an error banner on the settings page.
+#[tokio::test]
+async fn test_backup_failure_shows_banner() {
+    let client = TestClient::new();
+    // A regular file where the directory should be makes the backup fail
+    let blocker = scratch_dir("backup-blocker");
+    std::fs::write(&blocker, b"").unwrap();
+
+    let (status, _) = client
+        .post_form(
+            "/settings/backup",
+            &[
+                ("backup_dir", blocker.to_str().unwrap()),
+                ("backup_frequency", "off"),
+                ("backup_retention", "3"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = client.post_form("/settings/backup-now", &[]).await;
+    assert_ne!(status, StatusCode::OK);
+
+    let (_, page) = client.get("/settings").await;
+    assert!(page.contains("The last backup failed"));
+
+    std::fs::remove_file(&blocker).unwrap();
+}
+
+/// Backup settings are validated.
+#[tokio::test]
+async fn test_backup_settings_validation() {
+    let client = TestClient::new();
+
+    let (status, _) = client
+        .post_form(
+            "/settings/backup",
+            &[
+                ("backup_dir", ""),
+                ("backup_frequency", "hourly"),
+                ("backup_retention", "3"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = client
+        .post_form(
+            "/settings/backup",
+            &[
+                ("backup_dir", ""),
+                ("backup_frequency", "daily"),
+                ("backup_retention", "0"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Unpack a diagnostics bundle into its files.
+fn unzip(bytes: &[u8]) -> std::collections::BTreeMap<String, String> {
+    use std::io::Read;
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+    (0..archive.len())
+        .map(|i| {
+            let mut file = archive.by_index(i).unwrap();
+            let mut content = String::new();
+            file.read_to_string(&mut content).unwrap();
+            (file.name().to_string(), content)
+        })
+        .collect()
+}
+
+/// The diagnostics bundle carries logs, import sessions, settings and checks
+/// without secrets, and without personal data unless it is asked for.
+#[tokio::test]
+async fn test_diagnostics_bundle_redacts_personal_data() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-06-01", "-50.00", "Weekly shop", None, None)
+            .await
+    );
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO settings (key, value) VALUES
+                ('notify_smtp_password', 'hunter2'),
+                ('notify_webhook_url', 'https://hooks.example.org/abc');
+            INSERT INTO api_logs (action, symbol, request_params, status, response_details)
+            VALUES ('fetch_historical_quotes', 'VTI', '{"symbol":"VTI"}', 'error',
+                    'No data (account DE89 3704 0044 0532 0130 00)');
+            INSERT INTO import_sessions (id, status, error_count, errors)
+            VALUES ('session-1', 'failed', 1,
+                    '["Row 2: duplicate of Dr. Secretname Clinic"]');
+            INSERT INTO trading_import_sessions (id, status)
+            VALUES ('session-2', 'preview');
+            INSERT INTO trading_import_rows (session_id, row_index, data)
+            VALUES ('session-2', 0,
+                    '{"symbol":"VTI","notes":"Gift from Jane Privatperson"}');
+            "#,
+        )
+        .unwrap();
+        let long_category = "x".repeat(1000);
+        for i in 0..25 {
+            let data = serde_json::json!({
+                "date": "2024-05-01",
+                "amount": "-42.00",
+                "currency": "EUR",
+                "description": "Dr. Secretname Clinic",
+                "payee": "Jane Privatperson",
+                "counterparty_iban": "DE89370400440532013000",
+                "category": if i == 0 { long_category.as_str() } else { "Health" },
+                "notes": null,
+            });
+            conn.execute(
+                "INSERT INTO import_rows (session_id, row_index, data, status, error)
+                 VALUES ('session-1', ?1, ?2, 'error', ?3)",
+                rusqlite::params![i, data.to_string(), "No rule matched Jane Privatperson"],
+            )
+            .unwrap();
+        }
+    }
+
+    let secrets = [
+        "Secretname",
+        "Privatperson",
+        "DE89",
+        "3704 0044",
+        "Weekly shop",
+        "hunter2",
+        "hooks.example.org",
+    ];
+    let (status, bytes) = client.get_bytes("/settings/diagnostics-bundle").await;
+    assert_eq!(status, StatusCode::OK);
+    let files = unzip(&bytes);
+    assert_eq!(
+        files.keys().map(String::as_str).collect::<Vec<_>>(),
+        [
+            "api_logs.json",
+            "checks.json",
+            "import_sessions.json",
+            "settings.json",
+            "summary.json"
+        ]
+    );
+    for (name, content) in &files {
+        for secret in secrets {
+            assert!(!content.contains(secret), "{secret} leaked into {name}");
+        }
+    }
+
+    let summary: serde_json::Value = serde_json::from_str(&files["summary.json"]).unwrap();
+    assert_eq!(summary["version"], env!("CARGO_PKG_VERSION"));
+    assert!(summary["latest_migration"].is_string());
+    assert_eq!(summary["personal_data_included"], false);
+
+    let settings: serde_json::Value = serde_json::from_str(&files["settings.json"]).unwrap();
+    assert_eq!(settings["notify_smtp_password"], "[redacted]");
+
+    let checks: serde_json::Value = serde_json::from_str(&files["checks.json"]).unwrap();
+    assert_eq!(checks["integrity_check"], serde_json::json!(["ok"]));
+
+    let sessions: serde_json::Value = serde_json::from_str(&files["import_sessions.json"]).unwrap();
+    let session = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["id"] == "session-1")
+        .unwrap();
+    assert_eq!(session["errors"][0], "Row 2: duplicate of [redacted]");
+    let rows = session["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 20);
+    assert!(rows[0]["data"].as_str().unwrap().chars().count() <= 501);
+    assert!(rows[1]["data"].as_str().unwrap().contains("-42.00"));
+
+    // Personal data on request, secrets never
+    let (status, bytes) = client
+        .get_bytes("/settings/diagnostics-bundle?include_personal=true")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let files = unzip(&bytes);
+    let everything: String = files.values().cloned().collect();
+    assert!(everything.contains("Privatperson"));
+    assert!(everything.contains("DE89370400440532013000"));
+    assert!(!everything.contains("hunter2"));
+    assert!(!everything.contains("hooks.example.org"));
+}
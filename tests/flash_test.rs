@@ -0,0 +1,95 @@
+//! Integration tests for flash messages.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use common::TestClient;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+/// Send a request through the flash-enabled router.
+async fn send(client: &TestClient, request: Request<Body>) -> (StatusCode, HeaderMap, String) {
+    let resp = client.router_with_flash().oneshot(request).await.unwrap();
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, String::from_utf8_lossy(&bytes).to_string())
+}
+
+fn get(uri: &str, cookie: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder().method("GET").uri(uri);
+    if let Some(cookie) = cookie {
+        builder = builder.header(header::COOKIE, cookie);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+fn set_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::SET_COOKIE)
+        .map(|v| v.to_str().unwrap().to_string())
+}
+
+/// A message set before a redirect is shown once on the next page load.
+#[tokio::test]
+async fn test_flash_cookie_round_trip() {
+    let client = TestClient::new();
+
+    let create = Request::builder()
+        .method("POST")
+        .uri("/accounts/create")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from("name=Checking&account_type=Cash"))
+        .unwrap();
+    let (status, headers, _) = send(&client, create).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let cookie = set_cookie(&headers).expect("flash cookie should be set");
+    assert!(cookie.starts_with("flash="), "unexpected cookie: {cookie}");
+    let cookie = cookie.split(';').next().unwrap().to_string();
+
+    let (status, headers, body) = send(&client, get("/accounts", Some(&cookie))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-flash-message=\"Account &#34;Checking&#34; created\""));
+    assert!(body.contains("data-flash-type=\"success\""));
+    let cleared = set_cookie(&headers).expect("flash cookie should be cleared");
+    assert!(
+        cleared.starts_with("flash=;"),
+        "unexpected cookie: {cleared}"
+    );
+
+    let (_, _, body) = send(&client, get("/accounts", Some(&cookie))).await;
+    assert!(!body.contains("data-flash-message"));
+}
+
+/// Pages without pending messages neither render nor set a flash cookie.
+#[tokio::test]
+async fn test_no_flash_without_messages() {
+    let client = TestClient::new();
+
+    let (status, headers, body) = send(&client, get("/accounts", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(set_cookie(&headers).is_none());
+    assert!(!body.contains("data-flash-message"));
+}
+
+/// HTMX deletes report back through an HX-Trigger toast instead.
+#[tokio::test]
+async fn test_delete_sends_toast_trigger() {
+    let client = TestClient::new();
+    assert!(client.create_account("Savings", "Cash").await);
+
+    let delete = Request::builder()
+        .method("DELETE")
+        .uri("/accounts/1")
+        .header("HX-Request", "true")
+        .body(Body::empty())
+        .unwrap();
+    let (status, headers, _) = send(&client, delete).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        headers.get("hx-trigger").unwrap(),
+        r#"{"showToast":{"message":"Account deleted","type":"success"}}"#
+    );
+    assert!(set_cookie(&headers).is_none());
+}
@@ -31,6 +31,37 @@ async fn test_import_upload_succeeds_with_xsrf() {
     assert_eq!(status, StatusCode::SEE_OTHER);
 }
 
+/// Uploads over the configured limit get a 413 that names the limit, and
+/// leave no import session behind.
+#[tokio::test]
+async fn test_import_upload_over_limit_is_rejected() {
+    let client = TestClient::with_max_upload_mb(1);
+    let csv = "2024-01-15,-1.00,USD,Coffee\n".repeat(50_000);
+
+    let (status, body) = client
+        .post_multipart("/import/upload", "files", "big.csv", csv.as_bytes())
+        .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(body.contains("larger than the 1 MB limit"), "{body}");
+
+    let (status, body) = client
+        .post_multipart(
+            "/settings/import-database",
+            "file",
+            "big.db",
+            csv.as_bytes(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(body.contains("larger than the 1 MB limit"), "{body}");
+
+    let conn = client.state().db.get().unwrap();
+    let sessions: i64 = conn
+        .query_row("SELECT COUNT(*) FROM import_sessions", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(sessions, 0);
+}
+
 /// Trading import upload without XSRF header must be rejected with 403.
 #[tokio::test]
 async fn test_trading_import_upload_rejected_without_xsrf() {
@@ -58,3 +89,1184 @@ async fn test_trading_import_upload_succeeds_with_xsrf() {
 
     assert_eq!(status, StatusCode::SEE_OTHER);
 }
+
+/// Create a trading import session in preview state with one BUY row per symbol.
+fn create_preview_session(client: &TestClient, symbols: &[&str]) -> String {
+    use solvency::db::queries::trading_import;
+    use solvency::models::TradingImportStatus;
+    use solvency::services::trading_csv_parser::ParsedTradingActivity;
+
+    let conn = client.state().db.get().unwrap();
+    let session_id = "test-session".to_string();
+    trading_import::create_import_session(&conn, &session_id).unwrap();
+    for (i, symbol) in symbols.iter().enumerate() {
+        let row = ParsedTradingActivity {
+            date: "2024-01-15".into(),
+            symbol: symbol.to_string(),
+            quantity: Some("1".into()),
+            activity_type: "BUY".into(),
+            unit_price: Some("100.00".into()),
+            currency: "USD".into(),
+            fee: None,
+            fee_currency: None,
+            exchange_rate: None,
+            account_id: None,
+            row_number: i + 2,
+        };
+        trading_import::insert_import_row(&conn, &session_id, i as i64, &row, None).unwrap();
+    }
+    let n = symbols.len() as i64;
+    trading_import::update_import_session_progress(&conn, &session_id, n, n).unwrap();
+    trading_import::update_import_session_status(&conn, &session_id, TradingImportStatus::Preview)
+        .unwrap();
+    session_id
+}
+
+/// Symbol validation uses cached metadata and finishes without warnings for known symbols.
+#[tokio::test]
+async fn test_trading_import_validate_symbols_cached() {
+    use solvency::db::queries::market_data;
+
+    let client = TestClient::new();
+    let session_id = create_preview_session(&client, &["AAPL", "AAPL"]);
+    {
+        let conn = client.state().db.get().unwrap();
+        market_data::upsert_symbol_metadata(&conn, "AAPL", Some("Apple"), None, None, None)
+            .unwrap();
+    }
+
+    let url = format!("/trading/import/{}/validate-symbols", session_id);
+    let (status, body) = client.post_form(&url, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("symbol-validation"));
+
+    let mut finished = false;
+    for _ in 0..50 {
+        let (status, body) = client.get(&url).await;
+        assert_eq!(status, StatusCode::OK);
+        if body.contains("Checked 1 symbol(s)") {
+            finished = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(finished, "Validation should finish without network calls");
+
+    let (_, rows) = client
+        .get(&format!("/trading/import/{}/rows", session_id))
+        .await;
+    assert!(!rows.contains("not found on Yahoo Finance"));
+}
+
+/// Warnings are shown in the preview but do not block the import.
+#[tokio::test]
+async fn test_trading_import_symbol_warning_is_advisory() {
+    use solvency::db::queries::trading_import;
+
+    let client = TestClient::new();
+    let session_id = create_preview_session(&client, &["VWCE"]);
+    {
+        let conn = client.state().db.get().unwrap();
+        let rows = trading_import::get_pending_import_rows(&conn, &session_id).unwrap();
+        trading_import::set_import_row_warning(
+            &conn,
+            rows[0].id,
+            Some("Symbol 'VWCE' not found on Yahoo Finance. Did you mean VWCE.DE?"),
+        )
+        .unwrap();
+    }
+
+    let (status, rows) = client
+        .get(&format!("/trading/import/{}/rows", session_id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(rows.contains("Did you mean VWCE.DE?"));
+
+    let (status, _) = client
+        .post_form(&format!("/trading/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut imported = false;
+    for _ in 0..50 {
+        if !client.get_activities_for_symbol("VWCE").is_empty() {
+            imported = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(imported, "Row with a warning should still be imported");
+}
+
+/// Cancelling validation for a session without a running check is a no-op.
+#[tokio::test]
+async fn test_trading_import_cancel_validation() {
+    let client = TestClient::new();
+    let session_id = create_preview_session(&client, &["AAPL"]);
+
+    let (status, _) = client
+        .post_form(
+            &format!("/trading/import/{}/validate-symbols/cancel", session_id),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+/// Create a transaction import session in preview state with one row per description.
+fn create_transaction_preview_session(client: &TestClient, descriptions: &[&str]) -> String {
+    use solvency::db::queries::import;
+    use solvency::models::ImportStatus;
+    use solvency::services::csv_parser::ParsedTransaction;
+
+    let conn = client.state().db.get().unwrap();
+    let session_id = "test-import".to_string();
+    import::create_session(&conn, &session_id).unwrap();
+    for (i, description) in descriptions.iter().enumerate() {
+        let row = ParsedTransaction {
+            date: "2024-07-01".into(),
+            amount: "-25.00".into(),
+            currency: "USD".into(),
+            description: description.to_string(),
+            category: None,
+            account_id: None,
+            tags: vec![],
+            notes: None,
+            value_date: None,
+            payer: None,
+            payee: None,
+            reference: None,
+            transaction_type: None,
+            counterparty_iban: None,
+            creditor_id: None,
+            mandate_reference: None,
+            customer_reference: None,
+            row_number: i + 2,
+        };
+        import::insert_row(&conn, &session_id, i as i64, &row, None).unwrap();
+    }
+    let n = descriptions.len() as i64;
+    import::update_session_progress(&conn, &session_id, n, n).unwrap();
+    import::update_session_status(&conn, &session_id, ImportStatus::Preview).unwrap();
+    session_id
+}
+
+/// Session tags apply to every row, row overrides replace them, and tags
+/// deleted before confirming are dropped without failing the import.
+#[tokio::test]
+async fn test_import_session_and_row_tags() {
+    use solvency::db::queries::{import, tags, transactions};
+
+    let client = TestClient::new();
+    let session_id = create_transaction_preview_session(&client, &["Hotel", "Taxi"]);
+    let (vacation, trip, doomed, taxi_row) = {
+        let conn = client.state().db.get().unwrap();
+        let rows = import::get_pending_rows(&conn, &session_id).unwrap();
+        (
+            tags::create_or_get_tag(&conn, "vacation 2024").unwrap().id,
+            tags::create_or_get_tag(&conn, "trip").unwrap().id,
+            tags::create_or_get_tag(&conn, "doomed").unwrap().id,
+            rows[1].id,
+        )
+    };
+
+    let (status, _) = client
+        .post_form(
+            &format!("/import/{}/tags", session_id),
+            &[
+                ("tag_ids", &vacation.to_string()),
+                ("tag_ids", &doomed.to_string()),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let (status, _) = client
+        .post_form(
+            &format!("/import/{}/rows/{}/category", session_id, taxi_row),
+            &[("tag_ids", ""), ("tag_ids", &trip.to_string())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    {
+        let conn = client.state().db.get().unwrap();
+        tags::delete_tag(&conn, doomed).unwrap();
+    }
+
+    let (status, _) = client
+        .post_form(&format!("/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut imported = Vec::new();
+    for _ in 0..50 {
+        let conn = client.state().db.get().unwrap();
+        imported =
+            transactions::list_transactions(&conn, &transactions::TransactionFilter::default())
+                .unwrap();
+        if imported.len() == 2 {
+            break;
+        }
+        drop(conn);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(imported.len(), 2, "Both rows should be imported");
+
+    let tag_names = |desc: &str| -> Vec<String> {
+        let t = imported.iter().find(|t| t.description == desc).unwrap();
+        t.tags.iter().map(|tag| tag.name.clone()).collect()
+    };
+    assert_eq!(tag_names("Hotel"), vec!["vacation 2024"]);
+    assert_eq!(tag_names("Taxi"), vec!["trip"]);
+}
+
+/// Setting a category by payee updates every pending row with the same
+/// normalized payee, falling back to the description, and the grouped rows
+/// view lists each payee with its row count.
+#[tokio::test]
+async fn test_import_set_category_by_payee() {
+    use solvency::db::queries::{categories, import};
+    use solvency::models::NewCategory;
+
+    let client = TestClient::new();
+    let session_id = create_transaction_preview_session(
+        &client,
+        &[
+            "REWE Markt 1234",
+            "REWE MARKT 5678",
+            "Card payment",
+            "Bakery",
+        ],
+    );
+    let (groceries, card_row) = {
+        let conn = client.state().db.get().unwrap();
+        let groceries = categories::create_category(
+            &conn,
+            &NewCategory {
+                name: "Groceries".into(),
+                parent_id: None,
+                color: "#00aa00".into(),
+                icon: "shopping-cart".into(),
+            },
+        )
+        .unwrap();
+        let mut card_row = import::get_pending_rows(&conn, &session_id).unwrap()[2].clone();
+        card_row.data.payee = Some("Rewe Markt 42".into());
+        import::update_row_data(&conn, card_row.id, &card_row.data).unwrap();
+        (groceries, card_row.id)
+    };
+    client.state().cache.invalidate();
+
+    let (status, body) = client
+        .get(&format!("/import/{}/rows?group=payee", session_id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"data-payee-key="rewe markt""#));
+    assert!(body.contains(">3</td>"));
+
+    let (status, body) = client
+        .post_form(
+            &format!("/import/{}/categories/by-payee", session_id),
+            &[
+                ("payee", "REWE Markt"),
+                ("category_id", &groceries.to_string()),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(response["payee"], "rewe markt");
+    assert_eq!(response["updated"], 3);
+
+    let conn = client.state().db.get().unwrap();
+    let rows = import::get_pending_rows(&conn, &session_id).unwrap();
+    let categorized: Vec<i64> = rows
+        .iter()
+        .filter(|r| r.category_id == Some(groceries))
+        .map(|r| r.id)
+        .collect();
+    assert_eq!(categorized.len(), 3);
+    assert!(categorized.contains(&card_row));
+    assert_eq!(rows[3].category_id, None);
+}
+
+/// Rules that changed an import row are credited once the row is imported.
+#[tokio::test]
+async fn test_import_credits_rule_matches() {
+    use solvency::db::queries::{import, rules};
+    use solvency::models::{NewRule, RuleActionType};
+
+    let client = TestClient::new();
+    let session_id = create_transaction_preview_session(&client, &["Hotel", "Taxi"]);
+    let rule_id = {
+        let conn = client.state().db.get().unwrap();
+        let rule_id = rules::create_rule(
+            &conn,
+            &NewRule {
+                name: "Travel".into(),
+                pattern: "hotel".into(),
+                match_field: Default::default(),
+                action_type: RuleActionType::AssignCategory,
+                action_value: "4".into(),
+            },
+        )
+        .unwrap();
+        let rows = import::get_pending_rows(&conn, &session_id).unwrap();
+        import::update_row_rules(&conn, rows[0].id, &[rule_id]).unwrap();
+        rule_id
+    };
+
+    let (status, _) = client
+        .post_form(&format!("/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        wait_for_import(&client, &session_id).await.status,
+        "completed"
+    );
+
+    let conn = client.state().db.get().unwrap();
+    let rule = rules::get_rule(&conn, rule_id).unwrap().unwrap();
+    assert_eq!(rule.match_count, 1);
+    assert!(rule.last_matched_at.is_some());
+    assert_eq!(
+        rules::list_recent_matches(&conn, rule_id, 10)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+/// Categories a rule set on import rows are marked for review unless changed
+/// by hand in the wizard; the review page, filter and dashboard list them,
+/// and accepting or editing the category clears the mark.
+#[tokio::test]
+async fn test_rule_categories_await_review() {
+    use solvency::db::queries::{import, rules, transactions};
+    use solvency::models::{NewRule, RuleActionType};
+
+    let client = TestClient::new();
+    let session_id =
+        create_transaction_preview_session(&client, &["Hotel Berlin", "Taxi", "Hotel Paris"]);
+    let (rule_id, paris_row) = {
+        let conn = client.state().db.get().unwrap();
+        let rule_id = rules::create_rule(
+            &conn,
+            &NewRule {
+                name: "Travel".into(),
+                pattern: "hotel".into(),
+                match_field: Default::default(),
+                action_type: RuleActionType::AssignCategory,
+                action_value: "4".into(),
+            },
+        )
+        .unwrap();
+        let rows = import::get_pending_rows(&conn, &session_id).unwrap();
+        import::set_row_rule_category(&conn, rows[0].id, 4, rule_id).unwrap();
+        import::set_row_rule_category(&conn, rows[2].id, 4, rule_id).unwrap();
+        (rule_id, rows[2].id)
+    };
+    let (status, _) = client
+        .post_form(
+            &format!("/import/{}/rows/{}/category", session_id, paris_row),
+            &[("category_id", "4")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = client
+        .post_form(&format!("/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        wait_for_import(&client, &session_id).await.status,
+        "completed"
+    );
+
+    let berlin = {
+        let conn = client.state().db.get().unwrap();
+        let all =
+            transactions::list_transactions(&conn, &transactions::TransactionFilter::default())
+                .unwrap();
+        let by_desc = |d: &str| all.iter().find(|t| t.description == d).unwrap().clone();
+        let berlin = by_desc("Hotel Berlin");
+        assert!(berlin.auto_categorized);
+        assert_eq!(berlin.auto_category_rule_id, Some(rule_id));
+        assert!(!by_desc("Hotel Paris").auto_categorized);
+        assert!(!by_desc("Taxi").auto_categorized);
+        berlin.id
+    };
+
+    let (_, body) = client.get("/transactions?auto_categorized=1").await;
+    assert!(body.contains("Hotel Berlin"));
+    assert!(!body.contains("Hotel Paris"));
+    let (_, body) = client.get("/transactions/review").await;
+    assert!(body.contains("Travel"));
+    assert!(body.contains("Hotel Berlin"));
+    let (_, body) = client.get("/").await;
+    assert!(body.contains(r#"<span id="auto-categorized-count">1</span>"#));
+
+    let (status, _) = client
+        .post_form(
+            "/transactions/review/accept",
+            &[("rule_id", &rule_id.to_string())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let count = |client: &TestClient| {
+        let conn = client.state().db.get().unwrap();
+        transactions::count_auto_categorized(&conn).unwrap()
+    };
+    assert_eq!(count(&client), 0);
+
+    // Saving the same category keeps the mark, another one clears it
+    {
+        let conn = client.state().db.get().unwrap();
+        transactions::mark_auto_categorized(&conn, berlin, 4, rule_id).unwrap();
+    }
+    let edit = |category: &'static str| {
+        vec![
+            ("date", "2024-07-01"),
+            ("amount", "-25.00"),
+            ("currency", "USD"),
+            ("description", "Hotel Berlin"),
+            ("category_id", category),
+        ]
+    };
+    let url = format!("/transactions/{}/update", berlin);
+    let (status, _) = client.post_form(&url, &edit("4")).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(count(&client), 1);
+    let (status, _) = client.post_form(&url, &edit("3")).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(count(&client), 0);
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ImportStatusJson {
+    status: String,
+    processed_rows: i64,
+    /// Only reported by the transaction importer.
+    #[serde(default)]
+    resumable: bool,
+    #[serde(default)]
+    resume_from_row_index: i64,
+}
+
+/// Poll the status endpoint at `url` until `done` holds.
+async fn wait_for_status(
+    client: &TestClient,
+    url: &str,
+    done: impl Fn(&ImportStatusJson) -> bool,
+) -> ImportStatusJson {
+    for _ in 0..200 {
+        let (_, status): (_, Option<ImportStatusJson>) = client.get_json(url).await;
+        let status = status.unwrap();
+        if done(&status) {
+            return status;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("Import did not finish");
+}
+
+/// Poll the status endpoint until the background import has stopped.
+async fn wait_for_import(client: &TestClient, session_id: &str) -> ImportStatusJson {
+    let url = format!("/import/{}/status.json", session_id);
+    wait_for_status(client, &url, |s| s.status == "completed" || s.resumable).await
+}
+
+/// A failure mid-confirm keeps committed batches, and confirming again
+/// resumes from the checkpoint without importing any row twice.
+#[tokio::test]
+async fn test_import_confirm_resumes_after_failure() {
+    use solvency::db::queries::transactions;
+
+    let client = TestClient::new();
+    let descriptions: Vec<String> = (0..1200).map(|i| format!("Row {}", i)).collect();
+    let refs: Vec<&str> = descriptions.iter().map(String::as_str).collect();
+    let session_id = create_transaction_preview_session(&client, &refs);
+
+    // Fail the second batch when row 700 is marked as imported
+    let run_sql = |sql: &str| client.state().db.get().unwrap().execute_batch(sql).unwrap();
+    run_sql(
+        "CREATE TRIGGER fail_row AFTER UPDATE OF status ON import_rows
+         WHEN NEW.row_index = 700 AND NEW.status = 'imported'
+         BEGIN SELECT RAISE(ABORT, 'simulated failure'); END;",
+    );
+
+    let confirm_url = format!("/import/{}/confirm", session_id);
+    let (status, _) = client.post_form(&confirm_url, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let interrupted = wait_for_import(&client, &session_id).await;
+    assert_eq!(interrupted.status, "importing");
+    assert!(interrupted.resumable);
+    assert_eq!(interrupted.processed_rows, 500);
+    assert_eq!(interrupted.resume_from_row_index, 500);
+
+    let count = |client: &TestClient| {
+        let conn = client.state().db.get().unwrap();
+        transactions::count_transactions(&conn, &transactions::TransactionFilter::default())
+            .unwrap()
+    };
+    assert_eq!(
+        count(&client),
+        500,
+        "Only the first batch should be committed"
+    );
+
+    run_sql("DROP TRIGGER fail_row;");
+
+    let (status, _) = client.post_form(&confirm_url, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let finished = wait_for_import(&client, &session_id).await;
+    assert_eq!(finished.status, "completed");
+    assert_eq!(finished.processed_rows, 1200);
+    assert_eq!(count(&client), 1200, "No row may be imported twice");
+
+    // A completed session cannot be confirmed again
+    let (status, _) = client.post_form(&confirm_url, &[]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// A session abandoned mid-wizard is listed as open, and resuming it keeps
+/// the row categories and session tags chosen before.
+#[tokio::test]
+async fn test_import_abandoned_session_resumes() {
+    use solvency::db::queries::{categories, import, tags, transactions};
+    use solvency::models::NewCategory;
+
+    let client = TestClient::new();
+    let (_, body) = client.get("/import").await;
+    assert!(!body.contains("open-import-sessions"));
+
+    let session_id = create_transaction_preview_session(&client, &["Hotel", "Taxi"]);
+    let (travel, vacation, hotel_row) = {
+        let conn = client.state().db.get().unwrap();
+        let travel = categories::create_category(
+            &conn,
+            &NewCategory {
+                name: "Travel".into(),
+                parent_id: None,
+                color: "#0000aa".into(),
+                icon: "plane".into(),
+            },
+        )
+        .unwrap();
+        let vacation = tags::create_or_get_tag(&conn, "vacation").unwrap().id;
+        let rows = import::get_pending_rows(&conn, &session_id).unwrap();
+        (travel, vacation, rows[0].id)
+    };
+    client.state().cache.invalidate();
+
+    // Choices made before the browser went away
+    let (status, _) = client
+        .post_form(
+            &format!("/import/{}/rows/{}/category", session_id, hotel_row),
+            &[("category_id", &travel.to_string())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = client
+        .post_form(
+            &format!("/import/{}/tags", session_id),
+            &[("tag_ids", &vacation.to_string())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let (_, body) = client.get("/import").await;
+    assert!(body.contains("open-import-sessions"));
+    let (status, body) = client.get("/import/sessions/open").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!("href=\"/import/{}\"", session_id)));
+    assert!(body.contains("1 of 2 rows categorized"));
+
+    let (status, body) = client.get(&format!("/import/{}", session_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!("value=\"{}\" checked", vacation)));
+
+    let (status, _) = client
+        .post_form(&format!("/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let finished = wait_for_import(&client, &session_id).await;
+    assert_eq!(finished.status, "completed");
+
+    let conn = client.state().db.get().unwrap();
+    let imported =
+        transactions::list_transactions(&conn, &transactions::TransactionFilter::default())
+            .unwrap();
+    let hotel = imported.iter().find(|t| t.description == "Hotel").unwrap();
+    assert_eq!(hotel.category_id, Some(travel));
+    assert!(imported
+        .iter()
+        .all(|t| t.tags.iter().any(|tag| tag.id == vacation)));
+
+    let (_, body) = client.get("/import/sessions/open").await;
+    assert!(body.contains("No unfinished imports"));
+}
+
+/// An imported row matching a pending transaction books that transaction
+/// instead of creating a second copy, unless the merge is declined.
+#[tokio::test]
+async fn test_import_merges_pending_transaction() {
+    use solvency::db::queries::{import, transactions};
+    use solvency::models::{ImportStatus, NewTransaction, TransactionStatus};
+
+    let client = TestClient::new();
+    {
+        let conn = client.state().db.get().unwrap();
+        let pending = |date: &str, description: &str| NewTransaction {
+            date: date.into(),
+            amount_cents: -2500,
+            currency: "USD".into(),
+            description: description.into(),
+            category_id: Some(4),
+            account_id: None,
+            notes: Some("Dinner with Sam".into()),
+            tag_ids: Vec::new(),
+            value_date: None,
+            payer: None,
+            payee: None,
+            reference: None,
+            transaction_type: None,
+            counterparty_iban: None,
+            creditor_id: None,
+            mandate_reference: None,
+            customer_reference: None,
+            status: TransactionStatus::Pending,
+        };
+        transactions::create_transaction(&conn, &pending("2024-07-01", "Restaurant")).unwrap();
+        transactions::create_transaction(&conn, &pending("2024-07-03", "Cinema")).unwrap();
+    }
+
+    let csv = b"date,amount,currency,description\n\
+                2024-07-01,-25.00,USD,RESTAURANT 123\n\
+                2024-07-01,-25.00,USD,CINEMA\n";
+    let (status, _) = client
+        .post_multipart("/import/upload", "files", "bank.csv", csv)
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let session_id: String = {
+        let conn = client.state().db.get().unwrap();
+        conn.query_row("SELECT id FROM import_sessions", [], |row| row.get(0))
+            .unwrap()
+    };
+    let mut rows = Vec::new();
+    for _ in 0..200 {
+        let conn = client.state().db.get().unwrap();
+        if import::get_session(&conn, &session_id).unwrap().status == ImportStatus::Preview {
+            rows = import::get_pending_rows(&conn, &session_id).unwrap();
+            break;
+        }
+        drop(conn);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let matches: Vec<Option<i64>> = rows
+        .iter()
+        .map(|r| r.pending_match.as_ref().map(|m| m.transaction_id))
+        .collect();
+    assert_eq!(matches, vec![Some(1), Some(2)]);
+
+    let (status, body) = client.get(&format!("/import/{}/rows", session_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Merge into pending “Restaurant”"));
+
+    // Decline the merge for the second row
+    let (status, _) = client
+        .post_form(
+            &format!("/import/{}/rows/{}/category", session_id, rows[1].id),
+            &[("merge_pending", "")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = client
+        .post_form(&format!("/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        wait_for_import(&client, &session_id).await.status,
+        "completed"
+    );
+
+    let conn = client.state().db.get().unwrap();
+    let all = transactions::list_transactions(&conn, &transactions::TransactionFilter::default())
+        .unwrap();
+    assert_eq!(all.len(), 3, "Only the declined row adds a transaction");
+
+    let merged = transactions::get_transaction(&conn, 1).unwrap().unwrap();
+    assert_eq!(merged.status, TransactionStatus::Posted);
+    assert_eq!(merged.description, "RESTAURANT 123");
+    assert_eq!(merged.date, "2024-07-01");
+    assert_eq!(merged.category_id, Some(4), "Category is kept");
+    assert_eq!(merged.notes.as_deref(), Some("Dinner with Sam"));
+
+    let declined = transactions::get_transaction(&conn, 2).unwrap().unwrap();
+    assert_eq!(declined.status, TransactionStatus::Pending);
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CategoryImpactJson {
+    month: String,
+    category_id: i64,
+    existing_cents: i64,
+    import_cents: i64,
+    projected_cents: i64,
+    trailing_average_cents: i64,
+    over_average: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ImpactStatusJson {
+    category_impact: Vec<CategoryImpactJson>,
+}
+
+/// The status JSON projects month-end spending per category for the preview
+/// rows and flags categories well above their trailing average.
+#[tokio::test]
+async fn test_import_status_category_impact() {
+    use solvency::db::queries::import;
+
+    let client = TestClient::new();
+    for date in ["2024-04-10", "2024-05-10", "2024-06-10"] {
+        assert!(
+            client
+                .create_transaction(date, "-100.00", "Groceries", None, Some(4))
+                .await
+        );
+    }
+    assert!(
+        client
+            .create_transaction("2024-07-02", "-130.00", "Groceries", None, Some(4))
+            .await
+    );
+
+    let session_id =
+        create_transaction_preview_session(&client, &["Market", "Bus", "To savings", "Unknown"]);
+    {
+        let conn = client.state().db.get().unwrap();
+        let rows = import::get_pending_rows(&conn, &session_id).unwrap();
+        import::update_row_category(&conn, rows[0].id, Some(4)).unwrap();
+        import::update_row_category(&conn, rows[1].id, Some(5)).unwrap();
+        import::update_row_category(&conn, rows[2].id, Some(3)).unwrap();
+    }
+
+    let (status, json): (_, Option<ImpactStatusJson>) = client
+        .get_json(&format!("/import/{}/status.json", session_id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let impact = json.unwrap().category_impact;
+    assert_eq!(
+        impact.len(),
+        2,
+        "Transfers and uncategorized rows are skipped"
+    );
+
+    let food = impact.iter().find(|c| c.category_id == 4).unwrap();
+    assert_eq!(food.month, "2024-07");
+    assert_eq!(food.existing_cents, 13_000);
+    assert_eq!(food.import_cents, 2_500);
+    assert_eq!(food.projected_cents, 15_500);
+    assert_eq!(food.trailing_average_cents, 10_000);
+    assert!(food.over_average);
+
+    let transport = impact.iter().find(|c| c.category_id == 5).unwrap();
+    assert_eq!(transport.projected_cents, 2_500);
+    assert!(
+        !transport.over_average,
+        "No history means no average to exceed"
+    );
+}
+
+/// Broker statement with cash-only dividend, tax and fee rows.
+const BROKER_STATEMENT_CSV: &str = "\
+Date,Symbol,Type,Quantity,Price,Amount,Currency,Fee
+2024-01-10,MSFT,Buy,8,375.50,-3004.00,USD,1.00
+2024-03-14,MSFT,Dividend,,,6.00,USD,
+2024-03-14,MSFT,Tax,,,-0.90,USD,
+2024-04-01,MSFT,Fee,,,-2.50,USD,
+2024-06-13,MSFT,Dividend,,,6.00,USD,
+2024-06-13,MSFT,Tax,,,-0.90,USD,
+";
+
+/// Store [`BROKER_STATEMENT_CSV`] as an import session awaiting confirmation
+/// and return its id.
+fn preview_broker_statement(client: &TestClient) -> String {
+    use solvency::date_utils::DateFormat;
+    use solvency::db::queries::trading_import;
+    use solvency::models::TradingImportStatus;
+    use solvency::services::trading_csv_parser::parse_csv;
+
+    let parsed = parse_csv(BROKER_STATEMENT_CSV.as_bytes(), "en-US", DateFormat::Iso).unwrap();
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+
+    let session_id = "broker-session".to_string();
+    let conn = client.state().db.get().unwrap();
+    trading_import::create_import_session(&conn, &session_id).unwrap();
+    for (i, row) in parsed.activities.iter().enumerate() {
+        trading_import::insert_import_row(&conn, &session_id, i as i64, row, None).unwrap();
+    }
+    let n = parsed.activities.len() as i64;
+    trading_import::update_import_session_progress(&conn, &session_id, n, n).unwrap();
+    trading_import::update_import_session_status(&conn, &session_id, TradingImportStatus::Preview)
+        .unwrap();
+    session_id
+}
+
+/// Amount-only rows import with the statement's cash amounts.
+#[tokio::test]
+async fn test_trading_import_amount_only_rows_match_statement() {
+    use solvency::db::queries::trading;
+    use solvency::models::TradingActivityType;
+
+    let client = TestClient::new();
+    let session_id = preview_broker_statement(&client);
+
+    let (_, rows) = client
+        .get(&format!("/trading/import/{}/rows", session_id))
+        .await;
+    assert!(rows.contains("total"));
+
+    let (status, _) = client
+        .post_form(&format!("/trading/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut activities = Vec::new();
+    for _ in 0..50 {
+        activities = client.get_activities_for_symbol("MSFT");
+        if activities.len() == 6 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(activities.len(), 6);
+
+    let total_of = |activity_type: TradingActivityType| -> i64 {
+        activities
+            .iter()
+            .filter(|a| a.activity_type == activity_type)
+            .map(|a| {
+                assert_eq!(a.quantity, None);
+                a.unit_price_cents.unwrap()
+            })
+            .sum()
+    };
+    assert_eq!(total_of(TradingActivityType::Dividend), 1200);
+    assert_eq!(total_of(TradingActivityType::Tax), 180);
+    assert_eq!(total_of(TradingActivityType::Fee), 250);
+
+    let buy = &activities[0];
+    assert_eq!(buy.total_value_cents(), Some(300_400));
+    assert_eq!(buy.fee_cents, 100);
+
+    let conn = client.state().db.get().unwrap();
+    assert_eq!(
+        trading::get_portfolio_fee_tax_totals(&conn).unwrap(),
+        (250, 180)
+    );
+}
+
+/// A row failing halfway is rolled back on its own: its activity is gone,
+/// the row is marked and the rest of the batch is imported.
+#[tokio::test]
+async fn test_trading_import_rolls_back_failed_rows() {
+    use solvency::db::queries::trading_import;
+    use solvency::models::TradingImportStatus;
+
+    let client = TestClient::new();
+    let session_id = preview_broker_statement(&client);
+    // Fail marking the first tax row imported, after its activity was written
+    client
+        .state()
+        .db
+        .get()
+        .unwrap()
+        .execute_batch(
+            "CREATE TRIGGER fail_row BEFORE UPDATE OF status ON trading_import_rows
+             WHEN NEW.status = 'imported' AND OLD.row_index = 2
+             BEGIN SELECT RAISE(ABORT, 'injected failure'); END;",
+        )
+        .unwrap();
+
+    let (status, _) = client
+        .post_form(&format!("/trading/import/{}/confirm", session_id), &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut session = None;
+    for _ in 0..50 {
+        let conn = client.state().db.get().unwrap();
+        let current = trading_import::get_import_session(&conn, &session_id).unwrap();
+        if current.status == TradingImportStatus::Completed {
+            session = Some(current);
+            break;
+        }
+        drop(conn);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let session = session.expect("import did not complete");
+    assert_eq!(session.processed_rows, 6);
+    assert_eq!(session.error_count, 1);
+    assert!(
+        session.errors[0].starts_with("Row 3: "),
+        "{:?}",
+        session.errors
+    );
+    assert_eq!(client.get_activities_for_symbol("MSFT").len(), 5);
+}
+
+/// An error in the middle of a JSON import rolls back the records before it.
+#[tokio::test]
+async fn test_trading_json_import_commits_nothing_on_failure() {
+    let client = TestClient::new();
+    client
+        .state()
+        .db
+        .get()
+        .unwrap()
+        .execute_batch(
+            "CREATE TRIGGER fail_insert BEFORE INSERT ON trading_activities
+             WHEN NEW.symbol = 'FAIL'
+             BEGIN SELECT RAISE(ABORT, 'injected failure'); END;",
+        )
+        .unwrap();
+
+    let body = serde_json::json!([
+        {"date": "2024-01-02", "symbol": "AAPL", "quantity": 1.0, "activity_type": "BUY",
+         "unit_price_cents": 10000},
+        {"date": "2024-01-03", "symbol": "FAIL", "quantity": 1.0, "activity_type": "BUY",
+         "unit_price_cents": 10000},
+        {"date": "2024-01-04", "symbol": "MSFT", "quantity": 1.0, "activity_type": "BUY",
+         "unit_price_cents": 10000},
+    ])
+    .to_string();
+    let (status, _) = client.post_json("/trading/activities/import", &body).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(client.get_activities_for_symbol("AAPL").is_empty());
+    assert!(client.get_activities_for_symbol("MSFT").is_empty());
+}
+
+/// The position preview merges pending rows with stored activities and
+/// flags symbols the import would oversell without blocking it.
+#[tokio::test]
+async fn test_trading_import_positions_preview() {
+    use solvency::db::queries::trading_import;
+    use solvency::models::TradingImportStatus;
+    use solvency::services::trading_csv_parser::ParsedTradingActivity;
+
+    let client = TestClient::new();
+    client
+        .create_trading_activity("2024-01-10", "AAPL", "BUY", "10", "150.00")
+        .await;
+
+    let session_id = "preview-session".to_string();
+    {
+        let conn = client.state().db.get().unwrap();
+        trading_import::create_import_session(&conn, &session_id).unwrap();
+        let rows = [
+            ("2024-02-01", "AAPL", "BUY", "5", "180.00"),
+            ("2024-03-01", "AAPL", "SELL", "20", "200.00"),
+            ("2024-03-01", "MSFT", "BUY", "2", "400.00"),
+            ("2024-03-02", "MSFT", "BOGUS", "1", "400.00"),
+        ];
+        for (i, (date, symbol, activity_type, quantity, price)) in rows.iter().enumerate() {
+            let row = ParsedTradingActivity {
+                date: date.to_string(),
+                symbol: symbol.to_string(),
+                quantity: Some(quantity.to_string()),
+                activity_type: activity_type.to_string(),
+                unit_price: Some(price.to_string()),
+                currency: "USD".into(),
+                fee: None,
+                fee_currency: None,
+                exchange_rate: None,
+                account_id: None,
+                row_number: i + 2,
+            };
+            trading_import::insert_import_row(&conn, &session_id, i as i64, &row, None).unwrap();
+        }
+        trading_import::update_import_session_status(
+            &conn,
+            &session_id,
+            TradingImportStatus::Preview,
+        )
+        .unwrap();
+    }
+
+    let (status, deltas) = client
+        .get_json::<serde_json::Value>(&format!("/trading/import/{}/preview.json", session_id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let deltas = deltas.unwrap();
+    let deltas = deltas.as_array().unwrap();
+    assert_eq!(deltas.len(), 2);
+
+    let aapl = &deltas[0];
+    assert_eq!(aapl["symbol"], "AAPL");
+    assert_eq!(aapl["quantity_before"], 10.0);
+    assert_eq!(aapl["quantity_after"], 0.0);
+    assert_eq!(aapl["average_cost_before_cents"], 15000);
+    assert_eq!(aapl["goes_negative"], true);
+
+    // The row with an invalid type is left out
+    let msft = &deltas[1];
+    assert_eq!(msft["symbol"], "MSFT");
+    assert_eq!(msft["quantity_before"], 0.0);
+    assert_eq!(msft["quantity_after"], 2.0);
+    assert_eq!(msft["average_cost_after_cents"], 40000);
+    assert_eq!(msft["goes_negative"], false);
+
+    let (status, body) = client
+        .get(&format!("/trading/import/{}/preview", session_id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("1 position(s) would go negative"));
+    assert!(body.contains("Goes negative"));
+
+    // Nothing was stored
+    assert_eq!(client.get_activities_for_symbol("MSFT").len(), 0);
+}
+
+/// Files covering overlapping periods are merged into one session: rows
+/// present in an earlier file are skipped, every row remembers its file and
+/// row indices stay contiguous.
+#[tokio::test]
+async fn test_import_multiple_files_skips_overlap() {
+    use solvency::db::queries::import;
+
+    let client = TestClient::new();
+    let q1 = b"date,amount,currency,description\n\
+               2024-03-30,-3.50,EUR,Coffee\n\
+               2024-03-31,-3.50,EUR,Coffee\n\
+               2024-03-31,-3.50,EUR,Coffee\n";
+    let q2 = b"date,amount,currency,description\n\
+               2024-03-31,-3.50,EUR,Coffee\n\
+               2024-03-31,-3.50,EUR,Coffee\n\
+               2024-04-01,-900.00,EUR,Rent\n\
+               2024-04-02,abc,EUR,Broken\n";
+    let (status, _) = client
+        .post_multipart_files(
+            "/import/upload",
+            "files",
+            &[("q1.csv", &q1[..]), ("q2.csv", &q2[..])],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let session_id: String = {
+        let conn = client.state().db.get().unwrap();
+        conn.query_row("SELECT id FROM import_sessions", [], |row| row.get(0))
+            .unwrap()
+    };
+
+    let url = format!("/import/{}/status.json", session_id);
+    wait_for_status(&client, &url, |s| s.status == "preview").await;
+    let session = {
+        let conn = client.state().db.get().unwrap();
+        import::get_session(&conn, &session_id).unwrap()
+    };
+    assert_eq!(session.total_rows, 4);
+
+    let stats: Vec<(String, i64, i64, i64)> = session
+        .file_stats
+        .iter()
+        .map(|f| (f.file_name.clone(), f.rows, f.duplicates, f.errors))
+        .collect();
+    assert_eq!(
+        stats,
+        vec![
+            ("q1.csv".to_string(), 3, 0, 0),
+            ("q2.csv".to_string(), 1, 2, 1),
+        ]
+    );
+
+    let rows = {
+        let conn = client.state().db.get().unwrap();
+        import::get_pending_rows(&conn, &session_id).unwrap()
+    };
+    let indexed: Vec<(i64, Option<&str>, &str)> = rows
+        .iter()
+        .map(|r| {
+            (
+                r.row_index,
+                r.source_file.as_deref(),
+                r.data.description.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        indexed,
+        vec![
+            (0, Some("q1.csv"), "Coffee"),
+            (1, Some("q1.csv"), "Coffee"),
+            (2, Some("q1.csv"), "Coffee"),
+            (3, Some("q2.csv"), "Rent"),
+        ]
+    );
+
+    let (status, body) = client.get(&format!("/import/{}/status", session_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("id=\"import-file-stats\""));
+    assert!(body.contains("q2.csv"));
+}
+
+/// The trading importer merges several files in the same way.
+#[tokio::test]
+async fn test_trading_import_multiple_files_skips_overlap() {
+    use solvency::db::queries::trading_import;
+
+    let client = TestClient::new();
+    let header = "date,symbol,activity_type,quantity,unit_price,currency\n";
+    let first = format!("{header}2024-01-15,AAPL,buy,10,150.00,USD\n");
+    let second =
+        format!("{header}2024-01-15,AAPL,buy,10,150.00,USD\n2024-04-02,MSFT,buy,5,400.00,USD\n");
+    let (status, _) = client
+        .post_multipart_files(
+            "/trading/import/upload",
+            "files",
+            &[
+                ("jan.csv", first.as_bytes()),
+                ("apr.csv", second.as_bytes()),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let session_id: String = {
+        let conn = client.state().db.get().unwrap();
+        conn.query_row("SELECT id FROM trading_import_sessions", [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    };
+
+    let url = format!("/trading/import/{}/status.json", session_id);
+    wait_for_status(&client, &url, |s| s.status == "preview").await;
+    let session = {
+        let conn = client.state().db.get().unwrap();
+        trading_import::get_import_session(&conn, &session_id).unwrap()
+    };
+    assert_eq!(session.total_rows, 2);
+    assert_eq!(session.file_stats.len(), 2);
+    assert_eq!(session.file_stats[1].duplicates, 1);
+
+    let conn = client.state().db.get().unwrap();
+    let rows = trading_import::get_pending_import_rows(&conn, &session_id).unwrap();
+    let indexed: Vec<(i64, Option<&str>, &str)> = rows
+        .iter()
+        .map(|r| {
+            (
+                r.row_index,
+                r.source_file.as_deref(),
+                r.data.symbol.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        indexed,
+        vec![(0, Some("jan.csv"), "AAPL"), (1, Some("apr.csv"), "MSFT")]
+    );
+}
@@ -0,0 +1,545 @@
+//! Integration tests for market data maintenance endpoints.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestClient;
+use serde::Deserialize;
+use solvency::db::queries::{api_logs, market_data};
+use solvency::handlers::trading_positions::count_stale_positions;
+use solvency::models::{NewApiLog, NewMarketData};
+use solvency::services::market_data::SymbolMetadata;
+
+#[derive(Debug, Deserialize)]
+struct PriceChart {
+    data: Vec<serde_json::Value>,
+    missing_ranges: Vec<(String, String)>,
+}
+
+/// Seed one closing price per weekday between two dates (inclusive).
+fn seed_weekday_prices(client: &TestClient, symbol: &str, from: &str, to: &str) {
+    use chrono::Datelike;
+
+    let mut date = chrono::NaiveDate::parse_from_str(from, "%Y-%m-%d").unwrap();
+    let end = chrono::NaiveDate::parse_from_str(to, "%Y-%m-%d").unwrap();
+    let mut data = Vec::new();
+    while date <= end {
+        if date.weekday().num_days_from_monday() < 5 {
+            data.push(NewMarketData {
+                symbol: symbol.to_string(),
+                date: date.format("%Y-%m-%d").to_string(),
+                close_price_cents: 10_000,
+                close_price: None,
+                currency: "USD".to_string(),
+            });
+        }
+        date += chrono::Duration::days(1);
+    }
+    let conn = client.state().db.get().unwrap();
+    market_data::insert_market_data_batch(&conn, &data).unwrap();
+}
+
+/// Test that deleting a date range removes only those prices and shows up as a gap.
+#[tokio::test]
+async fn test_delete_range_creates_gap() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "1", "100.00")
+            .await
+    );
+    seed_weekday_prices(&client, "AAPL", "2024-01-01", "2024-02-29");
+    seed_weekday_prices(&client, "MSFT", "2024-01-01", "2024-02-29");
+
+    let (status, _) = client
+        .delete_request("/trading/market-data/AAPL/range?from=2024-01-15&to=2024-01-26")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let conn = client.state().db.get().unwrap();
+    let aapl = market_data::get_prices_for_symbol(&conn, "AAPL").unwrap();
+    assert!(aapl
+        .iter()
+        .all(|d| d.date.as_str() < "2024-01-15" || d.date.as_str() > "2024-01-26"));
+    assert_eq!(aapl.len(), 44 - 10);
+    // Other symbols are untouched
+    let msft = market_data::get_prices_for_symbol(&conn, "MSFT").unwrap();
+    assert_eq!(msft.len(), 44);
+
+    let (status, chart): (_, Option<PriceChart>) = client.get_json("/api/market-data/AAPL").await;
+    assert_eq!(status, StatusCode::OK);
+    let chart = chart.expect("Failed to parse chart JSON");
+    assert_eq!(chart.data.len(), 34);
+    assert!(chart
+        .missing_ranges
+        .contains(&("2024-01-15".to_string(), "2024-01-28".to_string())));
+}
+
+/// Test that inverted or malformed ranges are rejected without deleting anything.
+#[tokio::test]
+async fn test_delete_range_validation() {
+    let client = TestClient::new();
+    seed_weekday_prices(&client, "AAPL", "2024-01-01", "2024-01-31");
+
+    let (status, _) = client
+        .delete_request("/trading/market-data/AAPL/range?from=2024-01-20&to=2024-01-10")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = client
+        .delete_request("/trading/market-data/AAPL/range?from=yesterday&to=2024-01-10")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let conn = client.state().db.get().unwrap();
+    let aapl = market_data::get_prices_for_symbol(&conn, "AAPL").unwrap();
+    assert_eq!(aapl.len(), 23);
+}
+
+/// Test that re-fetching rejects an inverted range.
+#[tokio::test]
+async fn test_refetch_range_validation() {
+    let client = TestClient::new();
+    let (status, _) = client
+        .post_form(
+            "/trading/market-data/AAPL/refetch-range",
+            &[("from", "2024-02-01"), ("to", "2024-01-01")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+fn search_result(symbol: &str, name: &str) -> SymbolMetadata {
+    SymbolMetadata {
+        symbol: symbol.to_string(),
+        short_name: None,
+        long_name: Some(name.to_string()),
+        exchange: "GER".to_string(),
+        quote_type: "ETF".to_string(),
+    }
+}
+
+/// Cached search results are served without hitting Yahoo, and picking one
+/// stores its metadata.
+#[tokio::test]
+async fn test_symbol_search_uses_cache_and_select_stores_metadata() {
+    let client = TestClient::new();
+    client.state().cache.store_symbol_search(
+        "vanguard all-world",
+        vec![search_result("VWCE.DE", "Vanguard FTSE All-World")],
+    );
+
+    let (status, body) = client
+        .get("/api/symbols/search?q=Vanguard%20All-World")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(response["available"], true);
+    assert_eq!(response["results"][0]["symbol"], "VWCE.DE");
+    assert_eq!(response["results"][0]["name"], "Vanguard FTSE All-World");
+    assert_eq!(response["results"][0]["quote_type"], "ETF");
+
+    let (status, _) = client
+        .post_form("/api/symbols/select", &[("symbol", "vwce.de")])
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let conn = client.state().db.get().unwrap();
+    let meta = market_data::get_symbol_metadata(&conn, "VWCE.DE")
+        .unwrap()
+        .unwrap();
+    assert_eq!(meta.long_name.as_deref(), Some("Vanguard FTSE All-World"));
+}
+
+#[tokio::test]
+async fn test_symbol_search_empty_query_and_unknown_selection() {
+    let client = TestClient::new();
+
+    let (status, body) = client.get("/api/symbols/search?q=%20").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(response["results"].as_array().unwrap().len(), 0);
+
+    let (status, _) = client
+        .post_form("/api/symbols/select", &[("symbol", "NOPE")])
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// Test that prices quoted in pence are scaled and converted into the
+/// currency of the activities, without rewriting the stored prices.
+#[tokio::test]
+async fn test_minor_currency_prices_scaled_and_converted() {
+    let client = TestClient::new();
+    let (status, _) = client
+        .post_form(
+            "/trading/activities/create",
+            &[
+                ("date", "2024-01-10"),
+                ("symbol", "VUKE.L"),
+                ("activity_type", "BUY"),
+                ("quantity", "10"),
+                ("unit_price", "30.00"),
+                ("currency", "EUR"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    {
+        let conn = client.state().db.get().unwrap();
+        market_data::insert_market_data_batch(
+            &conn,
+            &[
+                NewMarketData {
+                    symbol: "VUKE.L".into(),
+                    date: "2024-01-09".into(),
+                    close_price_cents: 250_000,
+                    close_price: None,
+                    currency: "GBp".into(),
+                },
+                NewMarketData {
+                    symbol: "GBPEUR=X".into(),
+                    date: "2024-01-08".into(),
+                    close_price_cents: 117,
+                    close_price: None,
+                    currency: "EUR".into(),
+                },
+            ],
+        )
+        .unwrap();
+        market_data::detect_price_currency(&conn, "VUKE.L", "GBp").unwrap();
+        let meta = market_data::get_symbol_metadata(&conn, "VUKE.L")
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.price_scale, 0.01);
+        assert_eq!(meta.price_currency.as_deref(), Some("GBP"));
+    }
+    client.state().cache.invalidate();
+
+    let position_value = |nodes: serde_json::Value| {
+        nodes[0]["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|n| n["name"] == "VUKE.L")
+            .unwrap()["amount_cents"]
+            .as_i64()
+            .unwrap()
+    };
+    // 2500 pence are 25 GBP, or 29.25 EUR at 1.17
+    let (_, nodes) = client
+        .get_json::<serde_json::Value>("/api/net-worth/account-allocation")
+        .await;
+    assert_eq!(position_value(nodes.unwrap()), 29_250);
+    let (_, chart) = client
+        .get_json::<serde_json::Value>("/api/net-worth/chart")
+        .await;
+    let chart = chart.unwrap();
+    assert_eq!(
+        chart["net_worth"].as_array().unwrap().last().unwrap(),
+        29_250
+    );
+
+    // A manual setting is not overwritten by detection and applies at once
+    let (status, _) = client
+        .post_form(
+            "/trading/market-data/VUKE.L/price-scale",
+            &[("price_scale", "1"), ("price_currency", "eur")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    {
+        let conn = client.state().db.get().unwrap();
+        market_data::detect_price_currency(&conn, "VUKE.L", "GBp").unwrap();
+    }
+    let (_, nodes) = client
+        .get_json::<serde_json::Value>("/api/net-worth/account-allocation")
+        .await;
+    assert_eq!(position_value(nodes.unwrap()), 2_500_000);
+
+    let (_, body) = client.get("/trading/market-data/VUKE.L").await;
+    assert!(body.contains(r#"name="price_currency" value="EUR""#));
+
+    let (status, _) = client
+        .post_form(
+            "/trading/market-data/VUKE.L/price-scale",
+            &[("price_scale", "0"), ("price_currency", "GBP")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Test that prices are converted with the stored FX rate at full precision,
+/// also when only the reverse currency pair is stored.
+#[tokio::test]
+async fn test_fx_rates_keep_full_precision() {
+    let client = TestClient::new();
+    let (status, _) = client
+        .post_form(
+            "/trading/activities/create",
+            &[
+                ("date", "2024-01-10"),
+                ("symbol", "7203.T"),
+                ("activity_type", "BUY"),
+                ("quantity", "100"),
+                ("unit_price", "15.00"),
+                ("currency", "EUR"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let price = |symbol: &str, close: f64, currency: &str| NewMarketData {
+        symbol: symbol.into(),
+        date: "2024-01-09".into(),
+        close_price_cents: (close * 100.0).round() as i64,
+        close_price: Some(close),
+        currency: currency.into(),
+    };
+    {
+        let conn = client.state().db.get().unwrap();
+        // Only the reverse pair is stored
+        market_data::insert_market_data_batch(
+            &conn,
+            &[
+                price("7203.T", 2400.0, "JPY"),
+                price("EURJPY=X", 160.0, "JPY"),
+            ],
+        )
+        .unwrap();
+        market_data::detect_price_currency(&conn, "7203.T", "JPY").unwrap();
+        let factor = market_data::get_price_factor(&conn, "7203.T", "EUR", "2024-01-10").unwrap();
+        assert!((factor - 1.0 / 160.0).abs() < 1e-12);
+    }
+    client.state().cache.invalidate();
+
+    let position_value = |nodes: serde_json::Value| {
+        nodes[0]["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|n| n["name"] == "7203.T")
+            .unwrap()["amount_cents"]
+            .as_i64()
+            .unwrap()
+    };
+    // 240,000 JPY at 160 JPY per EUR are 1,500 EUR
+    let (_, nodes) = client
+        .get_json::<serde_json::Value>("/api/net-worth/account-allocation")
+        .await;
+    assert_eq!(position_value(nodes.unwrap()), 150_000);
+
+    // A direct rate below one cent is no longer rounded to zero
+    {
+        let conn = client.state().db.get().unwrap();
+        market_data::insert_market_data_batch(&conn, &[price("JPYEUR=X", 0.00625, "EUR")]).unwrap();
+        let rate = market_data::get_fx_rate(&conn, "JPY", "EUR", None).unwrap();
+        assert_eq!(rate, Some(0.00625));
+    }
+    client.state().cache.invalidate();
+    let (_, nodes) = client
+        .get_json::<serde_json::Value>("/api/net-worth/account-allocation")
+        .await;
+    assert_eq!(position_value(nodes.unwrap()), 150_000);
+}
+
+/// A refresh fetches only the windows without data, skipping holiday-sized
+/// gaps, plus the recent end once it is older than a few days.
+#[tokio::test]
+async fn test_missing_ranges_per_symbol() {
+    let client = TestClient::new();
+    for (date, symbol, kind) in [
+        ("2024-01-01", "AAPL", "BUY"),
+        ("2024-03-29", "AAPL", "SELL"),
+        ("2024-01-01", "MSFT", "BUY"),
+        ("2024-01-01", "NVDA", "BUY"),
+    ] {
+        assert!(
+            client
+                .create_trading_activity(date, symbol, kind, "1", "100.00")
+                .await
+        );
+    }
+    // AAPL: a two-week gap in February and a one-day gap on Jan 15
+    seed_weekday_prices(&client, "AAPL", "2024-01-01", "2024-01-12");
+    seed_weekday_prices(&client, "AAPL", "2024-01-16", "2024-02-02");
+    seed_weekday_prices(&client, "AAPL", "2024-02-19", "2024-03-29");
+    // MSFT: complete up to two days ago
+    seed_weekday_prices(&client, "MSFT", "2024-01-01", "2024-06-12");
+
+    let conn = client.state().db.get().unwrap();
+    let missing = market_data::get_missing_ranges_per_symbol(&conn, "2024-06-14").unwrap();
+    assert_eq!(
+        missing,
+        vec![
+            (
+                "AAPL".to_string(),
+                vec![("2024-02-05".to_string(), "2024-02-18".to_string())]
+            ),
+            (
+                "NVDA".to_string(),
+                vec![("2024-01-01".to_string(), "2024-06-14".to_string())]
+            ),
+        ]
+    );
+
+    // A week later, MSFT needs only the recent end
+    let missing = market_data::get_missing_ranges_per_symbol(&conn, "2024-06-21").unwrap();
+    let msft = missing.iter().find(|(s, _)| s == "MSFT").unwrap();
+    assert_eq!(
+        msft.1,
+        vec![("2024-06-12".to_string(), "2024-06-21".to_string())]
+    );
+
+    let (status, body) = client.get("/trading/market-data").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Fetch Missing Data"));
+
+    // Windows a fetch came back empty for are not offered again
+    market_data::record_empty_range(&conn, "AAPL", "2024-02-05", "2024-02-18").unwrap();
+    market_data::record_empty_range(&conn, "NVDA", "2024-01-01", "2024-06-14").unwrap();
+    let missing = market_data::get_missing_ranges_per_symbol(&conn, "2024-06-14").unwrap();
+    assert!(missing.is_empty(), "{:?}", missing);
+    let missing = market_data::get_missing_ranges_per_symbol(&conn, "2024-06-21").unwrap();
+    let nvda = missing.iter().find(|(s, _)| s == "NVDA").unwrap();
+    assert_eq!(
+        nvda.1,
+        vec![("2024-06-14".to_string(), "2024-06-21".to_string())]
+    );
+
+    // Deleting a range forgets that it was empty
+    market_data::delete_range(&conn, "AAPL", "2024-02-10", "2024-02-12").unwrap();
+    let missing = market_data::get_missing_ranges_per_symbol(&conn, "2024-06-14").unwrap();
+    assert_eq!(
+        missing,
+        vec![(
+            "AAPL".to_string(),
+            vec![("2024-02-05".to_string(), "2024-02-18".to_string())]
+        )]
+    );
+}
+
+fn insert_log(client: &TestClient, action: &str, retried_from: Option<i64>) -> i64 {
+    let conn = client.state().db.get().unwrap();
+    api_logs::insert_api_log(
+        &conn,
+        &NewApiLog {
+            api_name: "yahoo_finance".to_string(),
+            action: action.to_string(),
+            symbol: Some("AAPL".to_string()),
+            request_params: r#"{"symbol":"AAPL","start_date":"2024-01-01","end_date":"2024-01-31","range":1,"ranges":1}"#.to_string(),
+            status: "error".to_string(),
+            response_summary: Some("Yahoo Finance API error".to_string()),
+            response_details: None,
+            duration_ms: Some(120),
+            retried_from,
+        },
+    )
+    .unwrap()
+}
+
+/// Only quote fetches offer a retry, retries link back to their entry, and a
+/// retry waits for a running refresh instead of starting a second one.
+#[tokio::test]
+async fn test_api_log_retry() {
+    let client = TestClient::new();
+    let fetch = insert_log(&client, "fetch_historical_quotes", None);
+    let notify = insert_log(&client, "send_notification", None);
+    let retry = insert_log(&client, "fetch_historical_quotes", Some(fetch));
+
+    let (_, body) = client.get(&format!("/trading/api-logs/{}", fetch)).await;
+    assert!(body.contains(&format!("/trading/api-logs/{}/retry", fetch)));
+    assert!(body.contains(&format!("href=\"/trading/api-logs/{}\"", retry)));
+    let (_, body) = client.get(&format!("/trading/api-logs/{}", retry)).await;
+    assert!(body.contains(&format!("Retry of #{}", fetch)));
+    let (_, body) = client.get(&format!("/trading/api-logs/{}", notify)).await;
+    assert!(!body.contains("/retry"));
+
+    let (status, _) = client
+        .post_form(&format!("/trading/api-logs/{}/retry", notify), &[])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    client
+        .state()
+        .market_data_refresh
+        .lock()
+        .unwrap()
+        .is_refreshing = true;
+    let (status, _) = client
+        .post_form(&format!("/trading/api-logs/{}/retry", fetch), &[])
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let conn = client.state().db.get().unwrap();
+    assert_eq!(api_logs::get_retries(&conn, fetch).unwrap().len(), 1);
+}
+
+/// Ignored symbols keep their data but are left out of refreshes and stale
+/// price counts, until they are taken back in.
+#[tokio::test]
+async fn test_ignored_symbols() {
+    let client = TestClient::new();
+    for symbol in ["AAPL", "DEAD"] {
+        assert!(
+            client
+                .create_trading_activity("2024-01-01", symbol, "BUY", "1", "100.00")
+                .await
+        );
+    }
+    seed_weekday_prices(&client, "DEAD", "2024-01-01", "2024-01-12");
+    let settings = client.state().load_settings().unwrap();
+    let stale_before = {
+        let conn = client.state().db.get().unwrap();
+        count_stale_positions(&conn, &settings).unwrap()
+    };
+
+    let (status, _) = client
+        .post_form(
+            "/trading/market-data/DEAD/ignore",
+            &[
+                ("ignored", "true"),
+                ("note", "Delisted"),
+                ("return_to", "/trading/market-data"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    {
+        let conn = client.state().db.get().unwrap();
+        let missing = market_data::get_missing_ranges_per_symbol(&conn, "2024-06-14").unwrap();
+        let symbols: Vec<&str> = missing.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(symbols, ["AAPL"]);
+        assert_eq!(
+            market_data::get_prices_for_symbol(&conn, "DEAD")
+                .unwrap()
+                .len(),
+            10
+        );
+        assert_eq!(
+            count_stale_positions(&conn, &settings).unwrap(),
+            stale_before - 1
+        );
+    }
+
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(body.contains(&format!(
+        "{} position(s) are valued with a price older",
+        stale_before - 1
+    )));
+    let (_, body) = client.get("/trading/market-data").await;
+    assert!(body.contains("Ignored Symbols (1)"));
+    assert!(body.contains("Delisted"));
+    let (_, body) = client.get("/trading/market-data/DEAD").await;
+    assert!(body.contains("Stop Ignoring"));
+
+    let (status, _) = client
+        .post_form("/trading/market-data/DEAD/ignore", &[("ignored", "false")])
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let conn = client.state().db.get().unwrap();
+    let missing = market_data::get_missing_ranges_per_symbol(&conn, "2024-06-14").unwrap();
+    assert!(missing.iter().any(|(s, _)| s == "DEAD"));
+    assert_eq!(
+        count_stale_positions(&conn, &settings).unwrap(),
+        stale_before
+    );
+}
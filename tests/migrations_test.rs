@@ -0,0 +1,135 @@
+//! Integration tests for migration checksums and status reporting.
+
+use rusqlite::Connection;
+use solvency::db::migrations::{self, MigrationError};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Copy the real migrations into a scratch directory that tests may edit.
+fn copy_migrations() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for entry in fs::read_dir("migrations").unwrap() {
+        let path = entry.unwrap().path();
+        fs::copy(&path, dir.path().join(path.file_name().unwrap())).unwrap();
+    }
+    dir
+}
+
+fn append(dir: &Path, name: &str, sql: &str) {
+    let path = dir.join(name);
+    let mut contents = fs::read_to_string(&path).unwrap_or_default();
+    contents.push_str(sql);
+    fs::write(path, contents).unwrap();
+}
+
+/// A fresh database applies every migration and records a checksum for each.
+#[test]
+fn test_fresh_database_applies_everything() {
+    let dir = copy_migrations();
+    let conn = Connection::open_in_memory().unwrap();
+
+    migrations::run_migrations(&conn, dir.path(), false).unwrap();
+
+    let statuses = migrations::migration_status(&conn, dir.path()).unwrap();
+    let file_count = fs::read_dir(dir.path()).unwrap().count();
+    assert_eq!(statuses.len(), file_count);
+    assert!(statuses
+        .iter()
+        .all(|s| s.applied_at.is_some() && !s.modified && !s.missing));
+
+    let unchecked: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM _migrations WHERE checksum IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(unchecked, 0);
+
+    // Running again is a no-op
+    migrations::run_migrations(&conn, dir.path(), false).unwrap();
+}
+
+/// Editing an applied migration refuses to start unless dirty migrations
+/// are allowed, and the error names the file.
+#[test]
+fn test_tampered_migration_is_detected() {
+    let dir = copy_migrations();
+    let conn = Connection::open_in_memory().unwrap();
+    migrations::run_migrations(&conn, dir.path(), false).unwrap();
+
+    append(dir.path(), "004_trading.sql", "\n-- edited later\n");
+
+    let err = migrations::run_migrations(&conn, dir.path(), false).unwrap_err();
+    assert!(matches!(&err, MigrationError::ChecksumMismatch(name) if name == "004_trading.sql"));
+    assert!(err.to_string().contains("004_trading.sql"));
+
+    migrations::run_migrations(&conn, dir.path(), true).unwrap();
+
+    let statuses = migrations::migration_status(&conn, dir.path()).unwrap();
+    let modified: Vec<_> = statuses.iter().filter(|s| s.modified).collect();
+    assert_eq!(modified.len(), 1);
+    assert_eq!(modified[0].name, "004_trading.sql");
+}
+
+/// New files show up as pending until applied; removed files are flagged.
+#[test]
+fn test_status_lists_pending_and_missing() {
+    let dir = copy_migrations();
+    let conn = Connection::open_in_memory().unwrap();
+
+    let statuses = migrations::migration_status(&conn, dir.path()).unwrap();
+    assert!(statuses.iter().all(|s| s.applied_at.is_none()));
+
+    migrations::run_migrations(&conn, dir.path(), false).unwrap();
+    append(
+        dir.path(),
+        "999_later.sql",
+        "CREATE TABLE later (id INTEGER);",
+    );
+    fs::remove_file(dir.path().join("001_initial.sql")).unwrap();
+
+    let statuses = migrations::migration_status(&conn, dir.path()).unwrap();
+    let later = statuses.iter().find(|s| s.name == "999_later.sql").unwrap();
+    assert_eq!(later.applied_at, None);
+    let initial = statuses
+        .iter()
+        .find(|s| s.name == "001_initial.sql")
+        .unwrap();
+    assert!(initial.missing);
+    assert!(initial.applied_at.is_some());
+}
+
+/// Databases migrated before checksums existed get them backfilled.
+#[test]
+fn test_checksums_backfilled_for_old_databases() {
+    let dir = copy_migrations();
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE _migrations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            applied_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )
+    .unwrap();
+    let initial = fs::read_to_string(dir.path().join("001_initial.sql")).unwrap();
+    conn.execute_batch(&initial).unwrap();
+    conn.execute(
+        "INSERT INTO _migrations (name) VALUES ('001_initial.sql')",
+        [],
+    )
+    .unwrap();
+
+    migrations::run_migrations(&conn, dir.path(), false).unwrap();
+
+    let checksum: String = conn
+        .query_row(
+            "SELECT checksum FROM _migrations WHERE name = '001_initial.sql'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(checksum, migrations::checksum(&initial));
+}
@@ -1,9 +1,18 @@
-//! Miscellaneous integration tests (unicode, health check).
+//! Miscellaneous integration tests (unicode, health check, request timing,
+//! request ids, currency display, timezone and advanced settings, dashboard
+//! digest, tag search, body size limits, clearing the database, failure
+//! notifications, usage statistics, security headers, start page and navigation).
 
 mod common;
 
-use axum::http::StatusCode;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
 use common::TestClient;
+use http_body_util::BodyExt;
+use solvency::db::queries::{api_logs, categories, settings};
+use solvency::services::notify::{self, Severity};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
 
 /// Test health endpoint.
 #[tokio::test]
@@ -40,3 +49,727 @@ async fn test_unicode_descriptions() {
     let (status, _) = client.get("/api/analytics/spending-by-category").await;
     assert_eq!(status, StatusCode::OK);
 }
+
+/// Parse a `Server-Timing` header into (name, duration) pairs.
+fn parse_server_timing(value: &str) -> Vec<(String, f64)> {
+    value
+        .split(", ")
+        .map(|metric| {
+            let (name, dur) = metric.split_once(";dur=").expect("metric without dur");
+            (name.to_string(), dur.parse().expect("non-numeric dur"))
+        })
+        .collect()
+}
+
+/// Test that responses carry a structured Server-Timing header.
+#[tokio::test]
+async fn test_server_timing_header() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-01", "-50.00", "Groceries", None, Some(4))
+            .await
+    );
+
+    let response = client
+        .router_with_timing()
+        .oneshot(
+            Request::builder()
+                .uri("/transactions")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let header = response
+        .headers()
+        .get("server-timing")
+        .expect("Server-Timing header missing")
+        .to_str()
+        .unwrap();
+    let metrics = parse_server_timing(header);
+    let names: Vec<&str> = metrics.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, vec!["db", "app"]);
+
+    let (db, app) = (metrics[0].1, metrics[1].1);
+    assert!(db >= 0.0 && app >= 0.0);
+    assert!(db <= app, "Database time cannot exceed handler time");
+}
+
+/// A failing request shows its id on the error page and in the JSON body,
+/// matching the `X-Request-Id` response header.
+#[tokio::test]
+async fn test_request_id_on_error_responses() {
+    let client = TestClient::new();
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute_batch("DROP TABLE transactions").unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let send = |uri: &str, request_id: Option<&str>| {
+        let mut request = Request::builder().uri(uri);
+        if let Some(id) = request_id {
+            request = request.header("x-request-id", id);
+        }
+        client
+            .router_with_error_pages()
+            .oneshot(request.body(Body::empty()).unwrap())
+    };
+    let header_and_body = |response: axum::response::Response| async move {
+        let id = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (id, String::from_utf8_lossy(&body).to_string())
+    };
+
+    // An incoming id is kept
+    let response = send("/transactions", Some("sync-run-7")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let (id, body) = header_and_body(response).await;
+    assert_eq!(id, "sync-run-7");
+    assert!(body.contains("Request ID"));
+    assert!(body.contains("sync-run-7"));
+
+    // Otherwise one is generated, and API errors carry it as JSON
+    let response = send("/api/analytics/spending-by-category", None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let (id, body) = header_and_body(response).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["request_id"], id.as_str());
+    assert_eq!(json["error"], "Database error");
+
+    // Successful responses carry the header too
+    let response = send("/health", None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers()["x-request-id"].is_empty());
+}
+
+/// Save currency display settings on a fresh client with one 1,234.56 EUR
+/// balance, returning the save status and the balances page.
+async fn balances_with_currency_display(
+    symbol: &str,
+    position: &str,
+    decimals: &str,
+) -> (StatusCode, String) {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(
+        client
+            .create_transaction("2024-01-01", "1234.56", "Salary", Some(1), None)
+            .await
+    );
+
+    let (status, _) = client
+        .post_form(
+            "/settings/update",
+            &[
+                ("theme", "system"),
+                ("currency", "EUR"),
+                ("date_format", "YYYY-MM-DD"),
+                ("page_size", "25"),
+                ("locale", "de-DE"),
+                ("currency_symbol", symbol),
+                ("symbol_position", position),
+                ("currency_decimals", decimals),
+            ],
+        )
+        .await;
+    let (_, body) = client.get("/balances").await;
+    (status, body)
+}
+
+/// Test custom currency symbol placement and decimals in formatted amounts.
+#[tokio::test]
+async fn test_currency_display_settings() {
+    let (status, body) = balances_with_currency_display("\u{20ac}", "suffix", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        body.contains("1.234,56\u{00a0}\u{20ac}"),
+        "Suffix format missing"
+    );
+
+    let (status, body) = balances_with_currency_display("\u{00a5}", "prefix", "0").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        body.contains("\u{00a5}1.235<"),
+        "Zero-decimal format missing"
+    );
+
+    let (status, _) = balances_with_currency_display("", "prefix", "5").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = balances_with_currency_display("", "middle", "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Test the dashboard shows changes since the previous visit once.
+#[tokio::test]
+async fn test_dashboard_visit_digest() {
+    let client = TestClient::new();
+
+    let (status, body) = client.get("/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains("Since your last visit"));
+
+    assert!(
+        client
+            .create_transaction("2024-03-01", "-12.50", "Bakery", None, None)
+            .await
+    );
+
+    let (_, body) = client.get("/").await;
+    assert!(body.contains("Since your last visit"));
+    assert!(body.contains("Net worth change"));
+
+    let (status, digest) = client
+        .get_json::<serde_json::Value>("/api/dashboard/digest?since=2000-01-01")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let digest = digest.expect("digest JSON");
+    assert_eq!(digest["transactions_added"], 1);
+    assert_eq!(digest["net_worth_change_cents"], -1250);
+    assert_eq!(digest["since"], "2000-01-01 00:00:00");
+
+    // The change follows the net worth series: a transaction dated before
+    // `since` counts as added but does not change net worth since then
+    let (_, digest) = client
+        .get_json::<serde_json::Value>("/api/dashboard/digest?since=2024-03-02")
+        .await;
+    let digest = digest.expect("digest JSON");
+    assert_eq!(digest["transactions_added"], 1);
+    assert_eq!(digest["net_worth_change_cents"], 0);
+
+    let (status, _) = client.get("/api/dashboard/digest?since=yesterday").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Test the timezone setting accepts IANA names only.
+#[tokio::test]
+async fn test_timezone_setting() {
+    let client = TestClient::new();
+    let save = |timezone: &'static str| {
+        let client = &client;
+        async move {
+            client
+                .post_form(
+                    "/settings/update",
+                    &[
+                        ("theme", "system"),
+                        ("currency", "USD"),
+                        ("date_format", "YYYY-MM-DD"),
+                        ("page_size", "25"),
+                        ("locale", "en-US"),
+                        ("timezone", timezone),
+                    ],
+                )
+                .await
+                .0
+        }
+    };
+
+    assert_eq!(save("Mars/Olympus_Mons").await, StatusCode::BAD_REQUEST);
+    assert_eq!(save("Australia/Sydney").await, StatusCode::OK);
+
+    let (status, body) = client.get("/settings").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"value="Australia/Sydney""#));
+}
+
+/// Test the advanced settings validate their input, persist, and list the
+/// startup-only options with their environment variables.
+#[tokio::test]
+async fn test_advanced_settings() {
+    let client = TestClient::new();
+    let save = |log_filter: &'static str, delay: &'static str| {
+        let client = &client;
+        async move {
+            client
+                .post_form(
+                    "/settings/advanced",
+                    &[
+                        ("log_filter", log_filter),
+                        ("market_data_delay_ms", delay),
+                        ("session_ttl_hours", "12"),
+                    ],
+                )
+                .await
+                .0
+        }
+    };
+
+    assert_eq!(
+        save("solvency=loudest", "500").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(save("solvency=info", "-1").await, StatusCode::BAD_REQUEST);
+    assert_eq!(save("solvency=info", "1200").await, StatusCode::OK);
+
+    let settings = client.state().load_settings().unwrap();
+    assert_eq!(settings.log_filter, "solvency=info");
+    assert_eq!(settings.market_data_delay_ms, 1200);
+    assert_eq!(settings.session_ttl_hours, 12);
+    // Unchecked checkbox
+    assert!(!settings.trust_proxy_headers);
+
+    let (status, body) = client.get("/settings/advanced").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"value="solvency=info""#));
+    assert!(body.contains("SOLVENCY_SECURE_COOKIES"));
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TagHit {
+    name: String,
+    usage_count: i64,
+}
+
+/// Test tag search ranks prefix, substring and fuzzy matches with usage counts.
+#[tokio::test]
+async fn test_tag_search_ranking() {
+    let client = TestClient::new();
+    for name in ["bigrocks", "gracery", "groceries", "grocery-run", "travel"] {
+        let (status, _) = client.post_form("/tags/create", &[("name", name)]).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    for _ in 0..3 {
+        assert!(
+            client
+                .create_transaction("2024-01-01", "-5.00", "Shop", None, None)
+                .await
+        );
+    }
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute_batch(
+            "INSERT INTO transaction_tags (transaction_id, tag_id)
+             SELECT e.id, t.id FROM transactions e, tags t WHERE t.name = 'grocery-run' AND e.id <= 2;
+             INSERT INTO transaction_tags (transaction_id, tag_id)
+             SELECT e.id, t.id FROM transactions e, tags t WHERE t.name = 'groceries' AND e.id = 3;",
+        )
+        .unwrap();
+    }
+
+    let (status, hits): (_, Option<Vec<TagHit>>) = client.get_json("/tags/search?q=GROC").await;
+    assert_eq!(status, StatusCode::OK);
+    let hits = hits.unwrap();
+    let names: Vec<&str> = hits.iter().map(|h| h.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["grocery-run", "groceries", "bigrocks", "gracery"]
+    );
+    assert_eq!(hits[0].usage_count, 2);
+    assert_eq!(hits[2].usage_count, 0);
+
+    let (_, hits): (_, Option<Vec<TagHit>>) = client.get_json("/tags/search").await;
+    assert_eq!(hits.unwrap().len(), 5);
+}
+
+/// Form routes reject bodies over 2 MB with a 413 that names the limit,
+/// while imports accept them.
+#[tokio::test]
+async fn test_form_body_limit() {
+    let client = TestClient::new();
+    let long = "x".repeat(3 * 1024 * 1024);
+
+    let (status, body) = client
+        .post_form(
+            "/transactions/create",
+            &[
+                ("date", "2024-01-01"),
+                ("amount", "-1.00"),
+                ("description", &long),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(body.contains("larger than the 2 MB limit"), "{body}");
+
+    // A body announced as too large is rejected without reading it
+    let response = client
+        .router()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/transactions/create")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .header("Content-Length", (3 * 1024 * 1024).to_string())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let json = format!(
+        r#"[{{"date": "2024-01-01", "amount_cents": -100, "currency": "USD", "description": "Big", "notes": "{long}"}}]"#
+    );
+    let (status, _) = client.post_json("/transactions/import", &json).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+/// Clearing selected scopes keeps categories and the other scopes' data;
+/// "everything" wipes all of it.
+#[tokio::test]
+async fn test_clear_database_scopes() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-01", "-10.00", "Zanzibar espresso", None, Some(4))
+            .await
+    );
+    client
+        .create_trading_activity("2024-01-02", "AAPL", "BUY", "10", "150.00")
+        .await;
+
+    // Without a scope nothing happens
+    let (status, _) = client
+        .delete_request("/settings/clear-database?force=1")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The confirmation token is required, and only clears the scopes it
+    // was issued for
+    let (status, body) = client
+        .delete_request("/settings/clear-database?scope=transactions")
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let pending: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let token = pending["confirm_token"].as_str().unwrap();
+    let (status, _) = client
+        .delete_request(&format!(
+            "/settings/clear-database?scope=everything&confirm={}",
+            token
+        ))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, page) = client.get("/trading/activities").await;
+    assert!(page.contains("AAPL"));
+
+    let (status, body) = client
+        .delete_request("/settings/clear-database?scope=transactions&force=1")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(result["deleted"], serde_json::json!({"transactions": 1}));
+
+    let category_count = || {
+        let conn = client.state().db.get().unwrap();
+        categories::list_categories(&conn).unwrap().len()
+    };
+    assert!(category_count() > 0);
+    let (_, page) = client.get("/trading/activities").await;
+    assert!(page.contains("AAPL"));
+
+    let (status, body) = client
+        .delete_request("/settings/clear-database?scope=trading&scope=market_data&force=1")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(result["deleted"]["trading"], 1);
+    assert_eq!(result["deleted"]["market_data"], 0);
+    let (_, page) = client.get("/trading/activities").await;
+    assert!(!page.contains("AAPL"));
+
+    let (status, body) = client
+        .delete_request("/settings/clear-database?scope=everything&force=1")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(result["deleted"]["categories"].as_u64().unwrap() > 0);
+    assert_eq!(category_count(), 0);
+}
+
+/// Start a webhook receiver on a free local port. Returns its URL and the
+/// titles of the notifications it received.
+async fn webhook_receiver() -> (String, Arc<Mutex<Vec<String>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let store = received.clone();
+    let app = axum::Router::new().route(
+        "/topic",
+        axum::routing::post(move |headers: axum::http::HeaderMap| async move {
+            let title = headers
+                .get("Title")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            store.lock().unwrap().push(title);
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/topic", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, received)
+}
+
+/// Notifications go to the configured webhook, at most once an hour per
+/// kind of failure, and every attempt is logged.
+#[tokio::test]
+async fn test_webhook_notifications() {
+    let client = TestClient::new();
+    let (url, received) = webhook_receiver().await;
+    let form = [
+        ("notify_channel", "webhook"),
+        ("notify_webhook_url", url.as_str()),
+        ("notify_failure_threshold", "2"),
+    ];
+
+    let (status, _) = client
+        .post_form(
+            "/settings/notifications",
+            &[
+                ("notify_channel", "pager"),
+                ("notify_failure_threshold", "2"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = client
+        .post_form("/settings/notifications/test", &form)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(*received.lock().unwrap(), vec!["Test notification"]);
+
+    let (status, _) = client.post_form("/settings/notifications", &form).await;
+    assert_eq!(status, StatusCode::OK);
+    client.state().cache.invalidate();
+
+    let state = client.state();
+    notify::notify(
+        state,
+        "scheduled_backup",
+        Severity::Error,
+        "Backup failed",
+        "",
+    )
+    .await;
+    notify::notify(
+        state,
+        "scheduled_backup",
+        Severity::Error,
+        "Backup failed",
+        "",
+    )
+    .await;
+    notify::notify_refresh_failures(state, &["AAPL".into()]).await;
+    notify::notify_refresh_failures(state, &["AAPL".into(), "MSFT".into()]).await;
+    assert_eq!(
+        *received.lock().unwrap(),
+        vec![
+            "Test notification",
+            "Backup failed",
+            "Market data refresh failed"
+        ]
+    );
+
+    let conn = state.db.get().unwrap();
+    let logs = api_logs::get_all_logs(&conn, 10).unwrap();
+    assert_eq!(
+        logs.iter()
+            .filter(|log| log.api_name == "notifications" && !log.is_error())
+            .count(),
+        3
+    );
+}
+
+/// Requests are counted per route pattern only while usage statistics are
+/// enabled, and can be purged.
+#[tokio::test]
+async fn test_usage_statistics() {
+    use solvency::db::queries::{settings, usage_stats};
+
+    let client = TestClient::new();
+    let request = |uri: &str| {
+        client
+            .router_with_usage()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    // Off by default
+    request("/transactions").await.unwrap();
+    solvency::usage::flush(client.state()).unwrap();
+    {
+        let conn = client.state().db.get().unwrap();
+        assert_eq!(usage_stats::count_rows(&conn).unwrap(), 0);
+    }
+
+    let (status, _) = client
+        .post_form("/settings/usage", &[("usage_stats_enabled", "on")])
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    client.state().cache.invalidate();
+
+    request("/transactions").await.unwrap();
+    request("/transactions").await.unwrap();
+    request("/accounts/999/edit").await.unwrap();
+    request("/static/app.css").await.unwrap();
+
+    let (status, body) = client.get("/settings/usage?days=7").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("id=\"usage-top-pages\""));
+    assert!(body.contains("/accounts/:id/edit"));
+    assert!(!body.contains("/accounts/999"));
+
+    let conn = client.state().db.get().unwrap();
+    let since = chrono::Local::now().date_naive().to_string();
+    let routes = solvency::usage::summarize(usage_stats::counts_since(&conn, &since).unwrap());
+    assert_eq!(routes.len(), 2);
+    let transactions = routes.iter().find(|r| r.route == "/transactions").unwrap();
+    assert_eq!(transactions.requests, 2);
+    assert_eq!(transactions.errors, 0);
+    let edit = routes
+        .iter()
+        .find(|r| r.route == "/accounts/:id/edit")
+        .unwrap();
+    assert_eq!(edit.errors, 1);
+
+    // Disabling stops collection and keeps the data until purged
+    settings::set_setting(&conn, "usage_stats_enabled", "false").unwrap();
+    drop(conn);
+    client.state().cache.invalidate();
+    request("/transactions").await.unwrap();
+    assert!(client.state().usage.take().is_empty());
+
+    let (status, body) = client.get("/settings/usage").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Delete Statistics"));
+
+    let (status, _) = client.delete_request("/settings/usage").await;
+    assert_eq!(status, StatusCode::OK);
+    let conn = client.state().db.get().unwrap();
+    assert_eq!(usage_stats::count_rows(&conn).unwrap(), 0);
+}
+
+/// Pages are sent with a Content-Security-Policy whose nonce is on their
+/// inline scripts, plus the other security headers.
+#[tokio::test]
+async fn test_security_headers_and_csp_nonce() {
+    let get = |client: &TestClient| {
+        client.router_with_security_headers().oneshot(
+            Request::builder()
+                .uri("/settings")
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let client = TestClient::new();
+    let response = get(&client).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers().clone();
+    let policy = headers["content-security-policy"].to_str().unwrap();
+    let nonce = policy
+        .split("'nonce-")
+        .nth(1)
+        .and_then(|rest| rest.split('\'').next())
+        .expect("policy should carry a nonce");
+    assert!(policy.contains("frame-ancestors 'none'"));
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["referrer-policy"], "same-origin");
+    assert_eq!(headers["x-frame-options"], "DENY");
+    assert!(headers.get("strict-transport-security").is_none());
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains(&format!("<script nonce=\"{}\">", nonce)));
+    assert!(!body.contains("<script>"));
+
+    // Each request gets its own nonce
+    let again = get(&client).await.unwrap();
+    assert!(!again.headers()["content-security-policy"]
+        .to_str()
+        .unwrap()
+        .contains(nonce));
+
+    // An override replaces the policy; HSTS is sent with secure cookies
+    let mut client = TestClient::new();
+    let mut config = (*client.state().config).clone();
+    config.csp = Some("script-src 'self' 'nonce-{nonce}' https://cdn.example.com".into());
+    config.secure_cookies = true;
+    client.set_config(config);
+    let response = get(&client).await.unwrap();
+    let policy = response.headers()["content-security-policy"]
+        .to_str()
+        .unwrap();
+    assert!(policy.starts_with("script-src 'self' 'nonce-"));
+    assert!(policy.ends_with("' https://cdn.example.com"));
+    assert!(!policy.contains("{nonce}"));
+    assert!(response.headers()["strict-transport-security"]
+        .to_str()
+        .unwrap()
+        .starts_with("max-age="));
+}
+
+/// The start page setting decides where `GET /` leads, and the sidebar
+/// follows the saved order and visibility but never hides the settings page.
+#[tokio::test]
+async fn test_start_page_and_navigation_order() {
+    let client = TestClient::new();
+    let (status, html) = client.get("/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("href=\"/dashboard\""));
+
+    let (status, _) = client
+        .post_form(
+            "/settings/navigation",
+            &[
+                ("start_page", "positions"),
+                ("visible", "positions"),
+                ("visible", "net_worth"),
+                ("visible", "spending"),
+                ("visible", "transactions"),
+                ("visible", "settings"),
+                ("position_net_worth", "1"),
+                ("position_positions", "2"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    client.state().cache.invalidate();
+
+    let response = client
+        .router()
+        .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()["location"], "/trading/positions");
+
+    let (status, html) = client.get("/dashboard").await;
+    assert_eq!(status, StatusCode::OK);
+    let net_worth = html.find("href=\"/trading/net-worth\"").unwrap();
+    let positions = html.find("href=\"/trading/positions\"").unwrap();
+    let spending = html.find("href=\"/spending\"").unwrap();
+    assert!(net_worth < positions && positions < spending);
+    assert!(!html.contains("href=\"/retirement\""));
+    assert!(!html.contains("href=\"/dashboard\""));
+    assert!(html.contains("href=\"/settings\""));
+
+    // Hiding the settings page or picking an unknown start page is refused
+    let (status, _) = client
+        .post_form(
+            "/settings/navigation",
+            &[("start_page", "dashboard"), ("visible", "dashboard")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client
+        .post_form(
+            "/settings/navigation",
+            &[("start_page", "retirement"), ("visible", "settings")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let conn = client.state().db.get().unwrap();
+    let saved = settings::get_settings(&conn).unwrap();
+    assert_eq!(saved.start_page, "positions");
+    assert_eq!(saved.nav_order, ["net_worth", "positions"]);
+    assert!(saved.is_nav_hidden("retirement"));
+}
@@ -0,0 +1,277 @@
+//! Integration tests for the net worth page and chart API.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestClient;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct NetWorthChart {
+    labels: Vec<String>,
+    net_worth: Vec<i64>,
+    contributions: Vec<i64>,
+    growth: Vec<i64>,
+    components: Vec<ComponentSeries>,
+    from_date: Option<String>,
+    to_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ComponentSeries {
+    key: String,
+    values: Vec<i64>,
+}
+
+/// Test that contributions and growth add up to the total, with transfer pairs
+/// and market gains attributed correctly.
+#[tokio::test]
+async fn test_chart_contributions_vs_growth() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+
+    // Salary (Income, id=2) is an external contribution
+    assert!(
+        client
+            .create_transaction("2024-01-01", "1000.00", "Salary", Some(1), Some(2))
+            .await
+    );
+    // Transfer pair between tracked accounts (Transfers, id=3) must not count
+    assert!(
+        client
+            .create_transaction("2024-01-02", "-200.00", "To savings", Some(1), Some(3))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-01-02", "200.00", "From checking", Some(2), Some(3))
+            .await
+    );
+
+    // Invest $120 in total; the last trade price values 11 shares at $220
+    assert!(
+        client
+            .create_trading_activity("2024-01-03", "AAPL", "BUY", "10", "10.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-01-04", "AAPL", "BUY", "1", "20.00")
+            .await
+    );
+
+    let (status, chart): (_, Option<NetWorthChart>) = client.get_json("/api/net-worth/chart").await;
+    assert_eq!(status, StatusCode::OK);
+    let chart = chart.expect("Failed to parse chart JSON");
+
+    assert_eq!(chart.labels.last().unwrap(), "2024-01-04");
+    assert_eq!(*chart.net_worth.last().unwrap(), 122_000);
+    assert_eq!(*chart.contributions.last().unwrap(), 112_000);
+    assert_eq!(*chart.growth.last().unwrap(), 10_000);
+
+    // The transfer day adds nothing to contributions
+    assert_eq!(chart.contributions[0], chart.contributions[1]);
+}
+
+/// Test that the chart honors the selected date range.
+#[tokio::test]
+async fn test_chart_date_range() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-01", "100.00", "Gift", None, Some(2))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-01-10", "50.00", "Gift", None, Some(2))
+            .await
+    );
+
+    let (status, chart): (_, Option<NetWorthChart>) = client
+        .get_json("/api/net-worth/chart?from_date=2024-01-05&to_date=2024-01-07")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let chart = chart.expect("Failed to parse chart JSON");
+    assert_eq!(chart.labels, vec!["2024-01-05", "2024-01-06", "2024-01-07"]);
+    assert!(chart.net_worth.iter().all(|v| *v == 10_000));
+}
+
+/// Test that the summary shows period contributions and growth.
+#[tokio::test]
+async fn test_index_period_summary() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-01", "100.00", "Gift", None, Some(2))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-02-01", "25.00", "Gift", None, Some(2))
+            .await
+    );
+
+    let (status, body) = client
+        .get("/trading/net-worth?from_date=2024-01-15&to_date=2024-02-15")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Contributions"));
+    assert!(
+        body.contains("+\u{2060}$25.00"),
+        "Expected period contributions of $25"
+    );
+}
+
+/// Test that the component series sum to the total and can be limited.
+#[tokio::test]
+async fn test_chart_components() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-01", "1000.00", "Salary", None, Some(2))
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-01-02", "AAPL", "BUY", "3", "33.33")
+            .await
+    );
+
+    let (_, chart): (_, Option<NetWorthChart>) = client.get_json("/api/net-worth/chart").await;
+    let chart = chart.expect("Failed to parse chart JSON");
+    let keys: Vec<&str> = chart.components.iter().map(|c| c.key.as_str()).collect();
+    assert_eq!(keys, vec!["cash", "securities"]);
+    for (i, total) in chart.net_worth.iter().enumerate() {
+        let sum: i64 = chart.components.iter().map(|c| c.values[i]).sum();
+        assert_eq!(sum, *total, "Components must sum to the total at {}", i);
+    }
+    assert_eq!(chart.components[1].values.last(), Some(&9_999));
+
+    let (_, chart): (_, Option<NetWorthChart>) = client
+        .get_json("/api/net-worth/chart?components=securities")
+        .await;
+    let chart = chart.expect("Failed to parse chart JSON");
+    assert_eq!(chart.components.len(), 1);
+    assert_eq!(chart.components[0].key, "securities");
+
+    let (_, chart): (_, Option<NetWorthChart>) =
+        client.get_json("/api/net-worth/chart?components=").await;
+    assert!(chart.unwrap().components.is_empty());
+}
+
+/// Test that a securities account deriving cash from trading counts that
+/// cash, with only the deposit as contribution.
+#[tokio::test]
+async fn test_chart_includes_derived_trading_cash() {
+    let client = TestClient::new();
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Brokerage"),
+                ("account_type", "Securities"),
+                ("derive_cash_from_trading", "on"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    // One-sided deposit (Transfers, id=3) from an untracked account
+    assert!(
+        client
+            .create_transaction("2024-01-01", "1000.00", "Deposit", Some(1), Some(3))
+            .await
+    );
+    for (date, activity_type, price) in [
+        ("2024-01-02", "BUY", "80.00"),
+        ("2024-01-03", "DIVIDEND", "50.00"),
+    ] {
+        let quantity = if activity_type == "BUY" { "10" } else { "1" };
+        let (status, _) = client
+            .post_form(
+                "/trading/activities/create",
+                &[
+                    ("date", date),
+                    ("symbol", "AAPL"),
+                    ("activity_type", activity_type),
+                    ("quantity", quantity),
+                    ("unit_price", price),
+                    ("currency", "USD"),
+                    ("fee", "0"),
+                    ("account_id", "1"),
+                ],
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    // Ignored: the ledger already has the dividend
+    assert!(
+        client
+            .create_transaction("2024-01-03", "50.00", "Dividend", Some(1), Some(2))
+            .await
+    );
+
+    let (_, chart): (_, Option<NetWorthChart>) = client.get_json("/api/net-worth/chart").await;
+    let chart = chart.expect("Failed to parse chart JSON");
+    assert_eq!(chart.net_worth, vec![100_000, 100_000, 105_000]);
+    assert_eq!(chart.contributions, vec![100_000, 100_000, 100_000]);
+    assert_eq!(*chart.growth.last().unwrap(), 5_000);
+    assert_eq!(chart.components[0].key, "cash");
+    assert_eq!(chart.components[0].values, vec![100_000, 20_000, 25_000]);
+}
+
+/// "All" starts with the first transaction or trading activity, "Since First
+/// Investment" with the first trading activity, and the chart reports the
+/// requested range clamped to the data. Other pages ignore the net worth
+/// preset.
+#[tokio::test]
+async fn test_date_presets_follow_data_extent() {
+    let client = TestClient::new();
+
+    let (status, body) = client.get("/trading/net-worth").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("No data yet"));
+    assert!(!body.contains("1970-01-01"));
+    let (_, chart): (_, Option<NetWorthChart>) =
+        client.get_json("/api/net-worth/chart?preset=all").await;
+    let chart = chart.unwrap();
+    assert!(chart.labels.is_empty());
+    assert_eq!((chart.from_date, chart.to_date), (None, None));
+
+    client
+        .create_transaction("2023-01-05", "1000.00", "Salary", None, None)
+        .await;
+    assert!(
+        client
+            .create_trading_activity("2023-06-01", "VT", "BUY", "1", "100.00")
+            .await
+    );
+    client.state().cache.invalidate();
+
+    let (_, body) = client.get("/trading/net-worth?preset=all").await;
+    assert!(body.contains(r#"data-from-date="2023-01-05""#));
+    assert!(body.contains(r#"data-to-date="2023-06-01""#));
+
+    let (_, body) = client
+        .get("/trading/net-worth?preset=since_first_investment")
+        .await;
+    assert!(body.contains("Since First Investment"));
+    assert!(body.contains(r#"data-from-date="2023-06-01""#));
+
+    // Other pages don't offer the preset and fall back to the default range
+    let (status, body) = client
+        .get("/transactions?preset=since_first_investment")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains("Since First Investment"));
+
+    let (_, chart): (_, Option<NetWorthChart>) = client
+        .get_json("/api/net-worth/chart?from_date=2000-01-01&to_date=2099-12-31")
+        .await;
+    let chart = chart.unwrap();
+    assert_eq!(chart.from_date.as_deref(), Some("2023-01-05"));
+    assert_eq!(chart.to_date.as_deref(), Some("2023-06-01"));
+    assert_eq!(chart.labels.first().map(String::as_str), Some("2023-01-05"));
+}
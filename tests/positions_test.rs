@@ -281,6 +281,161 @@ async fn test_position_chart_data() {
     assert!(body.contains("\"symbol\":\"NVDA\""));
 }
 
+/// The chart carries the running average cost, null once the position is
+/// closed, and the break-even price of the reopened position.
+#[tokio::test]
+async fn test_position_chart_cost_basis() {
+    let client = TestClient::new();
+    for (date, kind, qty, price) in [
+        ("2024-01-01", "BUY", "5", "100.00"),
+        ("2024-01-02", "BUY", "5", "120.00"),
+        ("2024-01-03", "SELL", "10", "130.00"),
+        ("2024-01-04", "BUY", "2", "90.00"),
+    ] {
+        assert!(
+            client
+                .create_trading_activity(date, "AMD", kind, qty, price)
+                .await
+        );
+    }
+
+    let (_, chart) = client
+        .get_json::<serde_json::Value>("/api/positions/AMD/chart")
+        .await;
+    let chart = chart.unwrap();
+    let costs: Vec<Option<i64>> = chart["cost_basis"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["avg_cost_cents"].as_i64())
+        .collect();
+    assert_eq!(costs, vec![Some(10000), Some(11000), None, Some(9000)]);
+    assert_eq!(chart["break_even_cents"], 9000);
+
+    let (_, body) = client.get("/trading/positions/AMD").await;
+    assert!(body.contains("Break-even"));
+}
+
+async fn chart_json(client: &TestClient, query: &str) -> serde_json::Value {
+    let (status, chart) = client
+        .get_json::<serde_json::Value>(&format!("/api/positions/VTI/chart?{}", query))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", query);
+    chart.unwrap()
+}
+
+/// Quick ranges count back from the latest price, long ranges are thinned
+/// out, markers follow the range and the value series multiplies the price
+/// by the quantity held.
+#[tokio::test]
+async fn test_position_chart_ranges_and_value_series() {
+    let client = TestClient::new();
+    {
+        let conn = client.state().db.get().unwrap();
+        // Daily prices from 2021-01-01 to 2024-04-14, rising by a cent a day
+        conn.execute_batch(
+            "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 1199)
+             INSERT INTO market_data (symbol, date, close_price_cents, currency)
+             SELECT 'VTI', date('2021-01-01', '+' || i || ' days'), 10000 + i, 'USD' FROM n;",
+        )
+        .unwrap();
+    }
+    for (date, qty) in [("2021-06-01", "10"), ("2024-04-01", "5")] {
+        assert!(
+            client
+                .create_trading_activity(date, "VTI", "BUY", qty, "100.00")
+                .await
+        );
+    }
+
+    let dates = |chart: &serde_json::Value| -> Vec<String> {
+        chart["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["date"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let max = chart_json(&client, "").await;
+    assert_eq!(max["range"]["range"], "max");
+    assert_eq!(max["range"]["from_date"], "2021-01-01");
+    assert_eq!(max["range"]["to_date"], "2024-04-14");
+    let max_dates = dates(&max);
+    assert!(max_dates.len() <= 510, "{} points", max_dates.len());
+    assert_eq!(max_dates.first().unwrap(), "2021-01-01");
+    assert_eq!(max_dates.last().unwrap(), "2024-04-14");
+    // Activity dates survive the thinning
+    assert!(max_dates.contains(&"2021-06-01".to_string()));
+    assert_eq!(max["activities"].as_array().unwrap().len(), 2);
+    assert_eq!(max["cost_basis"].as_array().unwrap().len(), max_dates.len());
+
+    let month = chart_json(&client, "range=1m").await;
+    assert_eq!(month["range"]["range"], "1m");
+    assert_eq!(month["range"]["from_date"], "2024-03-14");
+    let month_dates = dates(&month);
+    assert_eq!(month_dates.len(), 32);
+    assert_eq!(month_dates.first().unwrap(), "2024-03-14");
+    let markers = month["activities"].as_array().unwrap();
+    assert_eq!(markers.len(), 1);
+    assert_eq!(markers[0]["date"], "2024-04-01");
+
+    let value = chart_json(
+        &client,
+        "from_date=2024-03-31&to_date=2024-04-01&series=value",
+    )
+    .await;
+    assert_eq!(value["series"], "value");
+    assert!(value["range"]["range"].is_null());
+    assert_eq!(value["range"]["from_date"], "2024-03-31");
+    let points = value["data"].as_array().unwrap();
+    assert_eq!(points.len(), 2);
+    // 10 shares at 111.85, then 15 shares at 111.86
+    assert_eq!(points[0]["value_cents"], 111_850);
+    assert_eq!(points[1]["value_cents"], 167_790);
+    assert_eq!(value["cost_basis"][1]["cost_cents"], 150_000);
+
+    // The price series carries no values
+    assert!(month["data"][0].get("value_cents").is_none());
+
+    let (status, _) = client.get("/api/positions/VTI/chart?range=5y").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client.get("/api/positions/VTI/chart?series=volume").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Frequent trades don't push the chart past its point limit: only the
+/// largest trades get a marker, and their dates count against the limit.
+#[tokio::test]
+async fn test_position_chart_caps_markers() {
+    let client = TestClient::new();
+    {
+        let conn = client.state().db.get().unwrap();
+        // 1,200 daily prices and a buy every other day, the last one largest
+        conn.execute_batch(
+            "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 1199)
+             INSERT INTO market_data (symbol, date, close_price_cents, currency)
+             SELECT 'VTI', date('2021-01-01', '+' || i || ' days'), 10000 + i, 'USD' FROM n;
+             WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 599)
+             INSERT INTO trading_activities (date, symbol, quantity, activity_type, unit_price_cents)
+             SELECT date('2021-01-01', '+' || (2 * i) || ' days'), 'VTI', 1 + i, 'BUY', 10000
+             FROM n;",
+        )
+        .unwrap();
+    }
+
+    let chart = chart_json(&client, "").await;
+    let markers = chart["activities"].as_array().unwrap();
+    assert_eq!(markers.len(), 100);
+    assert_eq!(markers.last().unwrap()["date"], "2024-04-13");
+    let points = chart["data"].as_array().unwrap();
+    assert!(points.len() <= 500, "{} points", points.len());
+    let dates: Vec<&str> = points.iter().map(|p| p["date"].as_str().unwrap()).collect();
+    assert!(markers
+        .iter()
+        .all(|m| dates.contains(&m["date"].as_str().unwrap())));
+}
+
 /// Test position chart API for non-existent symbol.
 #[tokio::test]
 async fn test_position_chart_nonexistent() {
@@ -292,3 +447,1197 @@ async fn test_position_chart_nonexistent() {
     // Should still return valid JSON, just with no trade data
     assert!(body.contains("\"symbol\":\"DOESNOTEXIST\""));
 }
+
+/// Test CSV export of open positions follows the requested sort order.
+#[tokio::test]
+async fn test_positions_csv_export() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "10", "150.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-01-02", "MSFT", "BUY", "4", "100.00")
+            .await
+    );
+
+    let (status, body) = client
+        .get("/trading/positions/export?format=csv&sort=symbol&dir=desc")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("symbol,name,quantity,average_cost,total_cost"));
+    assert!(
+        lines[1].starts_with("MSFT,"),
+        "Expected MSFT first: {}",
+        lines[1]
+    );
+    assert_eq!(
+        lines[2],
+        "AAPL,,10,150.00,1500.00,150.00,2024-01-01,1500.00,0.00,0.00,USD,true"
+    );
+
+    let (status, _) = client.get("/trading/positions/export?format=xlsx").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Test CSV export of closed positions.
+#[tokio::test]
+async fn test_closed_positions_csv_export() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "GOOG", "BUY", "5", "100.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-03-01", "GOOG", "SELL", "5", "120.00")
+            .await
+    );
+
+    let (status, body) = client
+        .get("/trading/positions/closed/export?format=csv")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with("GOOG,,500.00,600.00,100.00,"));
+    assert!(lines[1].ends_with("2024-01-01,2024-03-01"));
+}
+
+/// Loss sales followed by a buy of the same symbol within the window are
+/// flagged on the capital gains report, its export and the wash-sale API.
+#[tokio::test]
+async fn test_capital_gains_flag_wash_sales() {
+    let client = TestClient::new();
+    for (date, symbol, kind, qty, price) in [
+        ("2024-01-02", "GOOG", "BUY", "10", "100.00"),
+        ("2024-12-20", "GOOG", "SELL", "10", "80.00"),
+        ("2025-01-10", "GOOG", "BUY", "4", "75.00"),
+        ("2024-02-01", "MSFT", "BUY", "5", "100.00"),
+        ("2024-06-03", "MSFT", "SELL", "5", "90.00"),
+    ] {
+        assert!(
+            client
+                .create_trading_activity(date, symbol, kind, qty, price)
+                .await
+        );
+    }
+
+    let (status, body) = client.get("/api/trading/wash-sales?year=2024").await;
+    assert_eq!(status, StatusCode::OK);
+    let wash_sales: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let wash_sales = wash_sales.as_array().unwrap();
+    assert_eq!(wash_sales.len(), 1);
+    assert_eq!(wash_sales[0]["symbol"], "GOOG");
+    assert_eq!(wash_sales[0]["gain_cents"], -20_000);
+    assert_eq!(wash_sales[0]["repurchases"][0]["date"], "2025-01-10");
+    assert_eq!(wash_sales[0]["repurchases"][0]["quantity"], 4.0);
+
+    let (status, csv) = client
+        .get("/trading/capital-gains/export?format=csv&year=2024")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].ends_with(",2024-06-03,MSFT,5,450.00,500.00,0.00,-50.00,USD,false,,"));
+    assert!(
+        lines[2].ends_with(",2024-12-20,GOOG,10,800.00,1000.00,0.00,-200.00,USD,true,2025-01-10,4")
+    );
+
+    let (status, page) = client.get("/trading/capital-gains?year=2024").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("Wash Sale"));
+    assert!(page.contains("/trading/positions/MSFT"));
+
+    // A window shorter than the 21 days to the repurchase clears the flag
+    let conn = client.state().db.get().unwrap();
+    solvency::db::queries::settings::set_setting(&conn, "wash_sale_window_days", "20").unwrap();
+    client.state().cache.invalidate();
+    let (_, body) = client.get("/api/trading/wash-sales?year=2024").await;
+    assert_eq!(body, "[]");
+
+    let (status, _) = client.get("/api/trading/wash-sales?year=abc").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Filtering closed positions by date lists each holding period that ended in
+/// the range, including those of symbols that were bought again later.
+#[tokio::test]
+async fn test_closed_positions_date_filter() {
+    let client = TestClient::new();
+
+    for (date, symbol, kind, qty, price) in [
+        ("2023-01-10", "GOOG", "BUY", "5", "100.00"),
+        ("2023-06-01", "GOOG", "SELL", "5", "120.00"),
+        ("2024-02-01", "GOOG", "BUY", "2", "130.00"),
+        ("2024-01-01", "MSFT", "BUY", "4", "200.00"),
+        ("2024-03-01", "MSFT", "SELL", "4", "210.00"),
+    ] {
+        assert!(
+            client
+                .create_trading_activity(date, symbol, kind, qty, price)
+                .await
+        );
+    }
+
+    // Without a range, only symbols that are closed now are listed
+    let (status, body) = client.get("/trading/positions/closed").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("/trading/positions/MSFT"));
+    assert!(!body.contains("/trading/positions/GOOG"));
+
+    let range_2023 = "from_date=2023-01-01&to_date=2023-12-31";
+    let (status, body) = client
+        .get(&format!("/trading/positions/closed?{range_2023}"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("/trading/positions/GOOG"));
+    assert!(!body.contains("/trading/positions/MSFT"));
+    // Sort links keep the range
+    assert!(body.contains("sort=proceeds&#38;dir=desc&from_date=2023-01-01&#38;to_date=2023-12-31"));
+
+    let (_, body) = client
+        .get("/trading/positions/closed?from_date=2024-01-01&to_date=2024-12-31")
+        .await;
+    assert!(body.contains("/trading/positions/MSFT"));
+    assert!(!body.contains("/trading/positions/GOOG"));
+
+    let (_, body) = client
+        .get("/trading/positions/closed?from_date=2022-01-01&to_date=2022-12-31")
+        .await;
+    assert!(body.contains("No positions were closed in this period."));
+
+    let (status, body) = client
+        .get(&format!(
+            "/trading/positions/closed/export?format=csv&{range_2023}"
+        ))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 2, "{body}");
+    assert!(lines[1].starts_with("GOOG,,500.00,600.00,100.00,"));
+    assert!(lines[1].ends_with("2023-01-10,2023-06-01"));
+}
+
+/// Transferred-in shares carry their cost basis into the position like a buy.
+#[tokio::test]
+async fn test_transfer_in_sets_cost_basis() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "VTI", "TRANSFER_IN", "10", "100.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-02-01", "VTI", "SELL", "4", "150.00")
+            .await
+    );
+
+    let (status, body) = client.get("/trading/positions/export?format=csv").await;
+    assert_eq!(status, StatusCode::OK);
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(
+        lines[1].starts_with("VTI,,6,100.00,600.00,"),
+        "Unexpected position: {}",
+        lines[1]
+    );
+}
+
+/// Shares transferred out leave at cost, so only the sold shares are realized.
+#[tokio::test]
+async fn test_transfer_out_closes_position_without_gain() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "VTI", "TRANSFER_IN", "10", "100.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-02-01", "VTI", "SELL", "5", "120.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-03-01", "VTI", "TRANSFER_OUT", "5", "")
+            .await
+    );
+
+    let (status, body) = client
+        .get("/trading/positions/closed/export?format=csv")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(
+        lines[1].starts_with("VTI,,500.00,600.00,100.00,"),
+        "Unexpected closed position: {}",
+        lines[1]
+    );
+}
+
+/// A later split adjusts transferred-in lots like bought ones.
+#[tokio::test]
+async fn test_split_adjusts_transferred_lots() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "VTI", "ADD_HOLDING", "10", "100.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-02-01", "VTI", "SPLIT", "2", "")
+            .await
+    );
+
+    let (status, body) = client.get("/trading/positions/export?format=csv").await;
+    assert_eq!(status, StatusCode::OK);
+    let lines: Vec<&str> = body.lines().collect();
+    assert!(
+        lines[1].starts_with("VTI,,20,50.00,1000.00,"),
+        "Unexpected position: {}",
+        lines[1]
+    );
+}
+
+/// Test the grouped activities view summarizes per symbol and sorts by summary columns.
+#[tokio::test]
+async fn test_activities_grouped_by_symbol() {
+    let client = TestClient::new();
+    for (date, symbol, kind, qty, price) in [
+        ("2024-01-02", "AAPL", "BUY", "10", "150.00"),
+        ("2024-02-02", "AAPL", "SELL", "4", "160.00"),
+        ("2024-03-02", "MSFT", "BUY", "5", "100.00"),
+    ] {
+        assert!(
+            client
+                .create_trading_activity(date, symbol, kind, qty, price)
+                .await
+        );
+    }
+
+    let range = "from_date=2024-01-01&to_date=2024-12-31";
+    let (status, body) = client
+        .get(&format!(
+            "/trading/activities/table?view=grouped&{range}&sort=count&dir=desc"
+        ))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let aapl = body.find("AAPL\n").unwrap();
+    let msft = body.find("MSFT\n").unwrap();
+    assert!(
+        aapl < msft,
+        "AAPL has more activities and should sort first"
+    );
+    assert!(body.contains(">6<"), "Net quantity of AAPL should be 6");
+    assert!(body.contains("$1,500.00"), "AAPL invested 1,500.00");
+    assert!(
+        body.contains("view=grouped"),
+        "Sort links should keep the view"
+    );
+
+    let (_, body) = client
+        .get(&format!(
+            "/trading/activities/table?view=grouped&{range}&sort=count&dir=asc"
+        ))
+        .await;
+    assert!(body.find("MSFT\n").unwrap() < body.find("AAPL\n").unwrap());
+
+    let (status, body) = client
+        .get(&format!("/trading/activities?view=grouped&{range}"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("activity-group-1"));
+
+    // Expanding a group loads that symbol's activities through the flat table
+    let (status, body) = client
+        .get(&format!("/trading/activities/table?{range}&symbol=AAPL"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("AAPL"));
+    assert!(!body.contains("MSFT"));
+}
+
+fn enable_short_positions(client: &TestClient) {
+    let conn = client.state().db.get().unwrap();
+    solvency::db::queries::settings::set_setting(&conn, "allow_short_positions", "true").unwrap();
+    client.state().cache.invalidate();
+}
+
+/// Without the setting, selling more than held is clamped and flagged.
+#[tokio::test]
+async fn test_oversell_clamped_and_warned_by_default() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "VTI", "BUY", "5", "100.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-02-01", "VTI", "SELL", "8", "110.00")
+            .await
+    );
+
+    let (_, body) = client.get("/trading/positions/export?format=csv").await;
+    assert_eq!(body.lines().count(), 1, "no open position expected: {body}");
+
+    let (status, body) = client.get("/trading/positions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("oversold-warnings"));
+    assert!(body.contains("VTI on 2024-02-01: 3 more shares than held"));
+}
+
+/// With the setting enabled, a sale without holdings opens a short that
+/// buys later cover, realizing entry proceeds minus cover cost.
+#[tokio::test]
+async fn test_short_position_open_and_cover() {
+    let client = TestClient::new();
+    enable_short_positions(&client);
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "TSLA", "SELL", "10", "200.00")
+            .await
+    );
+    let (_, body) = client.get("/trading/positions/export?format=csv").await;
+    assert!(
+        body.contains("TSLA,,-10,200.00,-2000.00,"),
+        "short position expected: {body}"
+    );
+
+    let (_, page) = client.get("/trading/positions").await;
+    assert!(page.contains("short-positions"));
+    assert!(!page.contains("oversold-warnings"));
+
+    assert!(
+        client
+            .create_trading_activity("2024-02-01", "TSLA", "BUY", "4", "150.00")
+            .await
+    );
+    let (_, body) = client.get("/trading/positions/export?format=csv").await;
+    assert!(
+        body.contains("TSLA,,-6,200.00,-1200.00,"),
+        "partially covered short expected: {body}"
+    );
+
+    assert!(
+        client
+            .create_trading_activity("2024-03-01", "TSLA", "BUY", "6", "150.00")
+            .await
+    );
+    let (_, body) = client.get("/trading/positions/export?format=csv").await;
+    assert_eq!(body.lines().count(), 1, "short should be closed: {body}");
+
+    let (_, body) = client
+        .get("/trading/positions/closed/export?format=csv")
+        .await;
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 2, "closed short expected: {body}");
+    assert!(
+        lines[1].starts_with("TSLA,,1500.00,2000.00,500.00,"),
+        "unexpected closed short: {}",
+        lines[1]
+    );
+}
+
+/// Fee totals include per-trade fees and FEE activities, grouped per
+/// currency so amounts in different currencies are never added up.
+#[tokio::test]
+async fn test_fee_totals_by_symbol_year_and_account() {
+    let client = TestClient::new();
+    assert!(client.create_account("Broker", "Securities").await);
+
+    let activity = |date: &'static str,
+                    symbol: &'static str,
+                    kind: &'static str,
+                    quantity: &'static str,
+                    price: &'static str,
+                    currency: &'static str,
+                    fee: &'static str| {
+        vec![
+            ("date", date),
+            ("symbol", symbol),
+            ("activity_type", kind),
+            ("quantity", quantity),
+            ("unit_price", price),
+            ("currency", currency),
+            ("fee", fee),
+            ("account_id", "1"),
+        ]
+    };
+    for form in [
+        activity("2023-05-01", "VTI", "BUY", "10", "100.00", "USD", "5.00"),
+        activity("2024-02-01", "VTI", "FEE", "1", "3.00", "USD", "0"),
+        activity("2024-03-01", "VTI", "TAX", "1", "2.50", "USD", "0"),
+        activity("2024-04-01", "SAP", "BUY", "4", "50.00", "EUR", "1.00"),
+    ] {
+        let (status, _) = client.post_form("/trading/activities/create", &form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    let (status, by_symbol) = client
+        .get_json::<serde_json::Value>("/api/trading/fees?group_by=symbol")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let by_symbol = by_symbol.unwrap();
+    let rows = by_symbol.as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["group"], "SAP");
+    assert_eq!(rows[0]["currency"], "EUR");
+    assert_eq!(rows[0]["fees_cents"], 100);
+    assert_eq!(rows[1]["group"], "VTI");
+    assert_eq!(rows[1]["fees_cents"], 800);
+    assert_eq!(rows[1]["taxes_cents"], 250);
+    assert_eq!(rows[1]["invested_cents"], 100_000);
+    assert_eq!(rows[1]["fee_drag_percent"], 0.8);
+
+    let (_, by_year) = client
+        .get_json::<serde_json::Value>("/api/trading/fees?group_by=year")
+        .await;
+    let by_year = by_year.unwrap();
+    let years: Vec<(&str, &str)> = by_year
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            (
+                r["group"].as_str().unwrap(),
+                r["currency"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        years,
+        vec![("2023", "USD"), ("2024", "EUR"), ("2024", "USD")]
+    );
+
+    let (_, by_account) = client
+        .get_json::<serde_json::Value>("/api/trading/fees?group_by=account")
+        .await;
+    let by_account = by_account.unwrap();
+    let rows = by_account.as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert!(rows.iter().all(|r| r["group"] == "Broker"));
+
+    let (status, _) = client.get("/api/trading/fees?group_by=broker").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, page) = client.get("/trading/positions").await;
+    assert!(page.contains("fee-totals"));
+}
+
+/// A fee charged in another currency is converted with the trade's exchange
+/// rate; without a rate it counts unconverted and is flagged.
+#[tokio::test]
+async fn test_fee_currency_converted_with_exchange_rate() {
+    let client = TestClient::new();
+    let buy = |date: &'static str, fee_currency: &'static str, rate: &'static str| {
+        vec![
+            ("date", date),
+            ("symbol", "VTI"),
+            ("activity_type", "BUY"),
+            ("quantity", "10"),
+            ("unit_price", "100.00"),
+            ("currency", "USD"),
+            ("fee", "5.00"),
+            ("fee_currency", fee_currency),
+            ("exchange_rate", rate),
+        ]
+    };
+    for form in [
+        buy("2024-01-02", "eur", "1.1"),
+        buy("2024-01-03", "EUR", ""),
+    ] {
+        let (status, _) = client.post_form("/trading/activities/create", &form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    let (status, _) = client
+        .post_form(
+            "/trading/activities/create",
+            &buy("2024-01-04", "EUR", "-1"),
+        )
+        .await;
+    assert_ne!(status, StatusCode::SEE_OTHER);
+
+    let (_, fees) = client
+        .get_json::<serde_json::Value>("/api/trading/fees?group_by=symbol")
+        .await;
+    assert_eq!(fees.unwrap()[0]["fees_cents"], 1_050);
+
+    let (_, export) = client
+        .get_json::<serde_json::Value>("/trading/activities/export")
+        .await;
+    let export = export.unwrap();
+    let rows = export.as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    let converted = rows.iter().find(|r| r["date"] == "2024-01-02").unwrap();
+    assert_eq!(converted["fee_cents"], 500);
+    assert_eq!(converted["fee_currency"], "EUR");
+    assert_eq!(converted["exchange_rate"], 1.1);
+    assert_eq!(converted["fee_in_currency_cents"], 550);
+    let unconverted = rows.iter().find(|r| r["date"] == "2024-01-03").unwrap();
+    assert!(unconverted["fee_in_currency_cents"].is_null());
+
+    let (_, page) = client.get("/trading/activities").await;
+    assert!(page.contains("No exchange rate"));
+}
+
+/// The history export of one symbol carries pre-split values, realized
+/// gains and dividends, and its JSON re-imports into the same position.
+#[tokio::test]
+async fn test_position_history_export() {
+    let client = TestClient::new();
+    for (date, kind, qty, price) in [
+        ("2024-01-02", "BUY", "10", "100.00"),
+        ("2024-02-01", "SPLIT", "2", ""),
+        ("2024-03-01", "SELL", "4", "60.00"),
+        ("2024-04-01", "DIVIDEND", "1", "25.00"),
+    ] {
+        assert!(
+            client
+                .create_trading_activity(date, "VTI", kind, qty, price)
+                .await
+        );
+    }
+
+    let (status, body) = client
+        .get("/trading/positions/VTI/export?format=json")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let history: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let buy = &history["activities"][0];
+    assert_eq!(buy["quantity"], 10.0);
+    assert_eq!(buy["unit_price_cents"], 10_000);
+    assert_eq!(buy["adjusted_quantity"], 20.0);
+    assert_eq!(buy["split_adjustments"][0]["split_ratio"], 2.0);
+    let sale = &history["realized_gains"][0];
+    assert_eq!(sale["proceeds_cents"], 24_000);
+    assert_eq!(sale["cost_basis_cents"], 20_000);
+    assert_eq!(sale["gain_cents"], 4_000);
+    assert_eq!(history["events"][0]["activity_type"], "DIVIDEND");
+    assert_eq!(history["events"][0]["amount_cents"], 2_500);
+    assert_eq!(history["position"]["quantity"], 16.0);
+
+    let (status, csv) = client.get("/trading/positions/VTI/export").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(csv.starts_with("record,activity_id,date,"));
+    assert!(csv.contains(",2024-01-02,BUY,20,50.00,10,100.00,2,"));
+    assert!(csv.contains(",2024-03-01,SELL,4,,,,,240.00,200.00,0.00,40.00,USD,"));
+
+    // The JSON export re-imports into the same position
+    let other = TestClient::new();
+    let (status, _) = other.post_json("/trading/activities/import", &body).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, reimported) = other.get("/trading/positions/VTI/export?format=json").await;
+    let reimported: serde_json::Value = serde_json::from_str(&reimported).unwrap();
+    assert_eq!(reimported["position"], history["position"]);
+    assert_eq!(reimported["realized_gains"], history["realized_gains"]);
+
+    let (status, _) = client.get("/trading/positions/NONE/export").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = client
+        .get("/trading/positions/VTI/export?format=xlsx")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Positions worth less than the dust threshold collapse into one row that
+/// still counts towards the totals, and into "Other" in the allocation chart.
+#[tokio::test]
+async fn test_dust_positions_collapsed() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "10", "150.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-01-02", "XYZ", "BUY", "0.01", "20.00")
+            .await
+    );
+
+    let (status, body) = client.get("/trading/positions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("1 small position under"));
+    assert!(!body.contains("/trading/positions/XYZ"));
+    assert!(body.contains("1,500.20"), "dust missing from the totals");
+
+    let (_, body) = client.get("/trading/positions?show_dust=1").await;
+    assert!(body.contains("/trading/positions/XYZ"));
+    assert!(body.contains("Hide Small Positions"));
+    assert!(!body.contains("small position under"));
+
+    // Dust is still an open position, not a closed one
+    let (_, body) = client.get("/trading/positions/closed").await;
+    assert!(!body.contains("XYZ"));
+
+    let (status, nodes) = client
+        .get_json::<serde_json::Value>("/api/net-worth/account-allocation")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let nodes = nodes.unwrap();
+    let securities = nodes
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|n| n["name"] == "Other Securities")
+        .unwrap();
+    let names: Vec<_> = securities["children"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| (c["name"].as_str().unwrap(), c["amount_cents"].as_i64()))
+        .collect();
+    assert_eq!(names, vec![("AAPL", Some(150_000)), ("Other", Some(20))]);
+
+    // A threshold of zero lists every position
+    let conn = client.state().db.get().unwrap();
+    solvency::db::queries::settings::set_setting(&conn, "dust_threshold_cents", "0").unwrap();
+    client.state().cache.invalidate();
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(body.contains("/trading/positions/XYZ"));
+}
+
+/// A stored default sort applies when the URL has no sort parameters.
+#[tokio::test]
+async fn test_default_sort_from_settings() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAA", "BUY", "10", "10.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "BBB", "BUY", "5", "10.00")
+            .await
+    );
+    let order = |body: &str| body.find(">AAA<").unwrap() < body.find(">BBB<").unwrap();
+
+    let (status, _) = client
+        .post_form(
+            "/settings/default-sort",
+            &[("table", "positions"), ("sort", "quantity"), ("dir", "asc")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(!order(&body), "BBB has the smaller quantity");
+    let (_, body) = client.get("/trading/positions?sort=symbol&dir=asc").await;
+    assert!(order(&body), "explicit parameters win");
+
+    // The export follows the same default as the table
+    let (status, _) = client
+        .post_form(
+            "/settings/default-sort",
+            &[
+                ("table", "positions"),
+                ("sort", "quantity"),
+                ("dir", "desc"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    client.state().cache.invalidate();
+    let (_, csv) = client.get("/trading/positions/export?format=csv").await;
+    assert!(csv.find("AAA").unwrap() < csv.find("BBB").unwrap());
+
+    // Resetting restores the built-in default, symbol descending
+    let (status, _) = client
+        .post_form("/settings/default-sort", &[("table", "positions")])
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    client.state().cache.invalidate();
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(!order(&body));
+    let (_, body) = client.get("/trading/positions?dir=asc").await;
+    assert!(order(&body));
+
+    for form in [
+        [("table", "positions"), ("sort", "status"), ("dir", "asc")],
+        [("table", "holdings"), ("sort", "symbol"), ("dir", "asc")],
+        [("table", "market_data"), ("sort", "status"), ("dir", "up")],
+    ] {
+        let (status, _) = client.post_form("/settings/default-sort", &form).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{form:?}");
+    }
+    let (status, _) = client
+        .post_form(
+            "/settings/default-sort",
+            &[("table", "market_data"), ("sort", "status"), ("dir", "asc")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+}
+
+/// Approximated and old prices are flagged as stale, on the pages and in
+/// the JSON endpoints; fresh ones are not.
+#[tokio::test]
+async fn test_stale_prices_flagged() {
+    use solvency::db::queries::market_data;
+    use solvency::models::market_data::NewMarketData;
+
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "10", "100.00")
+            .await
+    );
+
+    // Without market data the price is approximated from the purchase
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(body.contains("stale-price-warning"));
+    let (_, body) = client.get("/").await;
+    assert!(body.contains(r#"<span id="stale-price-count">1</span>"#));
+    let (_, body) = client.get("/trading/net-worth").await;
+    assert!(body.contains("stale-price-warning"));
+
+    let (status, nodes) = client
+        .get_json::<serde_json::Value>("/api/net-worth/account-allocation")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let aapl = nodes.unwrap()[0]["children"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|n| n["name"] == "AAPL")
+        .cloned()
+        .unwrap();
+    assert_eq!(aapl["is_stale"], true);
+    assert_eq!(aapl["price_date"], "2024-01-01");
+
+    let (status, metrics) = client
+        .get_json::<serde_json::Value>("/api/trading/portfolio-metrics")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let metrics = metrics.unwrap();
+    assert_eq!(metrics["stale_price_count"], 1);
+    assert_eq!(metrics["total_cost_cents"], 100_000);
+    assert_eq!(metrics["positions"][0]["is_stale"], true);
+    assert_eq!(metrics["positions"][0]["price_date"], "2024-01-01");
+
+    let today = chrono::Local::now().date_naive();
+    {
+        let conn = client.state().db.get().unwrap();
+        market_data::insert_market_data_batch(
+            &conn,
+            &[NewMarketData {
+                symbol: "AAPL".into(),
+                date: today.format("%Y-%m-%d").to_string(),
+                close_price_cents: 12_000,
+                close_price: None,
+                currency: "USD".into(),
+            }],
+        )
+        .unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(!body.contains("stale-price-warning"));
+    let (_, body) = client.get("/").await;
+    assert!(!body.contains("stale-price-count"));
+    let (_, metrics) = client
+        .get_json::<serde_json::Value>("/api/trading/portfolio-metrics")
+        .await;
+    let metrics = metrics.unwrap();
+    assert_eq!(metrics["stale_price_count"], 0);
+    assert_eq!(metrics["total_value_cents"], 120_000);
+    assert_eq!(metrics["total_gain_loss_cents"], 20_000);
+}
+
+/// Submitting the same new activity form twice records one activity.
+#[tokio::test]
+async fn test_double_submit_creates_one_activity() {
+    let client = TestClient::new();
+    let (status, body) = client.get("/trading/activities/new").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("name=\"form_token\""));
+
+    let form = [
+        ("form_token", "activity-token"),
+        ("date", "2024-01-15"),
+        ("symbol", "AAPL"),
+        ("activity_type", "BUY"),
+        ("quantity", "10"),
+        ("unit_price", "150.00"),
+        ("currency", "USD"),
+    ];
+    for _ in 0..2 {
+        let (status, _) = client.post_form("/trading/activities/create", &form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    assert_eq!(client.get_activities_for_symbol("AAPL").len(), 1);
+
+    let mut invalid = form;
+    invalid[0] = ("form_token", "invalid-activity-token");
+    invalid[3] = ("activity_type", "GIFT");
+    let (status, body) = client
+        .post_form("/trading/activities/create", &invalid)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Invalid activity type"));
+    assert!(body.contains("value=\"150.00\""));
+    assert_eq!(client.get_activities_for_symbol("AAPL").len(), 1);
+}
+
+/// The duplicate form copies an activity but for its date, quantity and
+/// price; the new activity gets later splits applied like any other and is
+/// independent of the source.
+#[tokio::test]
+async fn test_duplicate_activity() {
+    use solvency::models::TradingActivityType;
+
+    let client = TestClient::new();
+    let mut form = vec![
+        ("date", "2024-01-15"),
+        ("symbol", "VT"),
+        ("activity_type", "BUY"),
+        ("quantity", "10"),
+        ("unit_price", "100.00"),
+        ("currency", "EUR"),
+        ("fee", "1.50"),
+        ("notes", "Savings plan"),
+    ];
+    let (status, _) = client.post_form("/trading/activities/create", &form).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert!(
+        client
+            .create_trading_activity("2024-06-15", "VT", "SPLIT", "2", "")
+            .await
+    );
+    let source = client
+        .get_activities_for_symbol("VT")
+        .into_iter()
+        .find(|a| a.activity_type == TradingActivityType::Buy)
+        .unwrap();
+
+    let today = solvency::date_utils::today_in(&client.state().load_settings().unwrap());
+    let (status, body) = client
+        .get(&format!("/trading/activities/{}/duplicate", source.id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!(
+        "action=\"/trading/activities/{}/duplicate\"",
+        source.id
+    )));
+    assert!(body.contains(&format!("value=\"{}\"", today.format("%Y-%m-%d"))));
+    assert!(body.contains("value=\"VT\""));
+    assert!(body.contains("value=\"1.50\""));
+    assert!(body.contains("Savings plan"));
+    assert!(body.contains("<option value=\"EUR\" selected>"));
+    assert!(body.contains("name=\"quantity\" value=\"\""));
+    assert!(body.contains("name=\"unit_price\" value=\"\""));
+
+    // Dated before the split, the copy is split-adjusted on creation
+    form[0] = ("date", "2024-02-15");
+    form[3] = ("quantity", "5");
+    form[4] = ("unit_price", "110.00");
+    let (status, _) = client
+        .post_form(
+            &format!("/trading/activities/{}/duplicate", source.id),
+            &form,
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let copy = client
+        .get_activities_for_symbol("VT")
+        .into_iter()
+        .find(|a| a.date == "2024-02-15")
+        .unwrap();
+    assert_eq!(copy.quantity, Some(10.0));
+    assert_eq!(copy.unit_price_cents, Some(5_500));
+    assert_eq!(copy.fee_cents, 150);
+
+    assert!(client.delete_trading_activity(copy.id).await);
+    let activities = client.get_activities_for_symbol("VT");
+    assert_eq!(activities.len(), 2);
+    let unchanged = activities.iter().find(|a| a.id == source.id).unwrap();
+    assert_eq!(unchanged.quantity, Some(20.0));
+    assert_eq!(unchanged.unit_price_cents, Some(5_000));
+
+    let (status, _) = client.get("/trading/activities/999999/duplicate").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// The activities export honors the page's symbol and date filters and its
+/// sort, and names the file after them.
+#[tokio::test]
+async fn test_activities_export_follows_filters() {
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    let client = TestClient::new();
+    for (date, symbol, price) in [
+        ("2023-12-15", "AAPL", "90.00"),
+        ("2024-02-01", "AAPL", "100.00"),
+        ("2024-05-01", "AAPL", "110.00"),
+        ("2024-03-01", "MSFT", "300.00"),
+    ] {
+        assert!(
+            client
+                .create_trading_activity(date, symbol, "BUY", "1", price)
+                .await
+        );
+    }
+
+    let response = client
+        .router()
+        .oneshot(
+            Request::builder()
+                .uri("/trading/activities/export?symbol=AAPL&from_date=2024-01-01&to_date=2024-12-31&sort=date&dir=asc&format=csv")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"trading_activities_AAPL_2024.csv\""
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8_lossy(&body);
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("date,symbol,activity_type,quantity,unit_price"));
+    assert!(lines[1].starts_with("2024-02-01,AAPL,BUY,1,100.00,"));
+    assert!(lines[2].starts_with("2024-05-01,AAPL,BUY,1,110.00,"));
+
+    let (status, export) = client
+        .get_json::<serde_json::Value>(
+            "/trading/activities/export?from_date=2024-03-01&to_date=2024-03-31",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let export = export.unwrap();
+    let rows = export.as_array().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["symbol"], "MSFT");
+
+    let (_, page) = client.get("/trading/activities?symbol=AAPL").await;
+    assert!(page.contains("/trading/activities/export?"));
+    assert!(page.contains("preset=all&#38;symbol=AAPL&#38;format=csv"));
+}
+
+/// A dividend reinvestment creates the dividend, tax and buy together, with
+/// notes linking them; a buy above the net dividend is rejected.
+#[tokio::test]
+async fn test_record_dividend_reinvestment() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-02", "VWRL", "BUY", "10", "100.00")
+            .await
+    );
+
+    let drip = |gross: &'static str, quantity: &'static str| {
+        [
+            ("symbol", "VWRL"),
+            ("date", "2024-03-15"),
+            ("currency", "USD"),
+            ("account_id", ""),
+            ("gross_dividend", gross),
+            ("tax", "3.00"),
+            ("quantity", quantity),
+            ("unit_price", "100.00"),
+            ("notes", "Q1"),
+        ]
+    };
+
+    // Net 17.00, reinvested 20.00
+    let (status, _) = client
+        .post_form("/trading/activities/drip", &drip("20.00", "0.2"))
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let conn = client.state().db.get().unwrap();
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM trading_activities", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(count, 1);
+
+    // Net 17.00, reinvested 16.99
+    let (status, _) = client
+        .post_form("/trading/activities/drip", &drip("20.00", "0.1699"))
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, activity_type, quantity, unit_price_cents, notes
+             FROM trading_activities WHERE date = '2024-03-15' ORDER BY id",
+        )
+        .unwrap();
+    let rows: Vec<(i64, String, Option<f64>, i64, String)> = stmt
+        .query_map([], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    drop(stmt);
+    drop(conn);
+    let types: Vec<&str> = rows.iter().map(|r| r.1.as_str()).collect();
+    assert_eq!(types, ["DIVIDEND", "TAX", "BUY"]);
+    assert_eq!(rows[0].3, 2_000);
+    assert_eq!(rows[1].3, 300);
+    assert_eq!(rows[2].2, Some(0.1699));
+    let note = format!(
+        "DRIP #{}: dividend #{}, tax #{}, buy #{}\nQ1",
+        rows[0].0, rows[0].0, rows[1].0, rows[2].0
+    );
+    assert!(rows.iter().all(|r| r.4 == note));
+
+    let (_, body) = client
+        .get("/trading/activities?from_date=2024-01-01&to_date=2024-12-31")
+        .await;
+    assert_eq!(body.matches(">DRIP</span>").count(), 3);
+
+    let (status, body) = client.get("/trading/positions/VWRL").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("10.1699"));
+}
+
+/// A dividend reinvestment dated before a recorded split is adjusted for
+/// the split like any other buy.
+#[tokio::test]
+async fn test_backdated_dividend_reinvestment_before_split() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-02", "VWRL", "BUY", "10", "100.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-06-01", "VWRL", "SPLIT", "2", "")
+            .await
+    );
+
+    let (status, _) = client
+        .post_form(
+            "/trading/activities/drip",
+            &[
+                ("symbol", "VWRL"),
+                ("date", "2024-03-15"),
+                ("currency", "USD"),
+                ("account_id", ""),
+                ("gross_dividend", "20.00"),
+                ("tax", ""),
+                ("quantity", "0.2"),
+                ("unit_price", "100.00"),
+                ("notes", ""),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let conn = client.state().db.get().unwrap();
+    let (quantity, price): (f64, i64) = conn
+        .query_row(
+            "SELECT quantity, unit_price_cents FROM trading_activities
+             WHERE activity_type = 'BUY' AND date = '2024-03-15'",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(quantity, 0.4);
+    assert_eq!(price, 5_000);
+}
+
+/// Positions in other currencies are converted into the display currency at
+/// the latest rate; those without a rate are listed apart from the totals.
+#[tokio::test]
+async fn test_positions_in_display_currency() {
+    use solvency::db::queries::market_data;
+    use solvency::models::market_data::NewMarketData;
+
+    let client = TestClient::new();
+    let buy = |symbol: &'static str, currency: &'static str| {
+        vec![
+            ("date", "2024-01-02"),
+            ("symbol", symbol),
+            ("activity_type", "BUY"),
+            ("quantity", "10"),
+            ("unit_price", "100.00"),
+            ("currency", currency),
+            ("fee", "0"),
+        ]
+    };
+    for form in [buy("AAPL", "USD"), buy("SAP", "EUR"), buy("BARC", "GBP")] {
+        let (status, _) = client.post_form("/trading/activities/create", &form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    {
+        let conn = client.state().db.get().unwrap();
+        market_data::insert_market_data_batch(
+            &conn,
+            &[
+                NewMarketData {
+                    symbol: "EURUSD=X".into(),
+                    date: "2024-01-01".into(),
+                    close_price_cents: 105,
+                    close_price: None,
+                    currency: "USD".into(),
+                },
+                NewMarketData {
+                    symbol: "EURUSD=X".into(),
+                    date: "2024-01-02".into(),
+                    close_price_cents: 110,
+                    close_price: None,
+                    currency: "USD".into(),
+                },
+            ],
+        )
+        .unwrap();
+    }
+    client.state().cache.invalidate();
+
+    // USD from the settings: 1,000 USD plus 1,000 EUR at 1.10
+    let (status, body) = client.get("/trading/positions").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("2,100.00"), "EUR position not converted");
+    assert!(body.contains("&asymp;"));
+    assert!(body.contains("converted at 1.1000"));
+    let (_, unconverted) = body.split_once(r#"id="unconverted-positions""#).unwrap();
+    assert!(unconverted.contains("BARC"));
+    assert!(!unconverted.contains("SAP"));
+
+    // Sorting compares the converted values: 1,100 USD for SAP over 1,000
+    let row_of =
+        |body: &str, symbol: &str| body.find(&format!("/trading/positions/{symbol}'")).unwrap();
+    let (_, body) = client.get("/trading/positions?sort=value&dir=desc").await;
+    assert!(row_of(&body, "SAP") < row_of(&body, "AAPL"));
+    let (_, body) = client.get("/trading/positions?sort=value&dir=asc").await;
+    assert!(row_of(&body, "AAPL") < row_of(&body, "SAP"));
+
+    // The inverse pair converts USD into EUR
+    let (status, body) = client.get("/trading/positions?display_currency=eur").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("1,909.09"), "USD position not converted");
+    assert!(body.contains("display_currency=EUR"));
+
+    let (status, _) = client.get("/trading/positions?display_currency=EU1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
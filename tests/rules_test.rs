@@ -0,0 +1,204 @@
+//! Integration tests for rule creation, statistics and suggestions.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestClient;
+use solvency::db::queries::rules;
+use solvency::error::AppError;
+use solvency::models::{NewRule, RuleActionType, RuleMatchField};
+
+fn rule_count(client: &TestClient) -> i64 {
+    let conn = client.state().db.get().unwrap();
+    conn.query_row("SELECT COUNT(*) FROM rules", [], |row| row.get(0))
+        .unwrap()
+}
+
+/// Applying a rule counts the transactions it changed, lists them on the
+/// matches page, and the statistics can be reset.
+#[tokio::test]
+async fn test_rule_statistics_apply_matches_and_reset() {
+    let client = TestClient::new();
+    for description in ["Coffee Shop", "Coffee Beans", "Rent"] {
+        assert!(
+            client
+                .create_transaction("2024-03-01", "-5.00", description, None, None)
+                .await
+        );
+    }
+    for (name, pattern) in [("Coffee", "coffee"), ("Unused", "lottery")] {
+        let (status, _) = client
+            .post_form(
+                "/rules/create",
+                &[
+                    ("name", name),
+                    ("pattern", pattern),
+                    ("action_type", "assign_category"),
+                    ("action_value", "4"),
+                ],
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    let (status, _) = client
+        .post_form("/rules/1/apply", &[("scope", "all")])
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let (_, detail) = client.get("/rules/1").await;
+    assert!(detail.contains("rule-statistics"));
+    assert!(detail.contains("2 transactions"));
+
+    let (status, matches) = client.get("/rules/1/matches").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(matches.contains("Coffee Shop"));
+    assert!(matches.contains("Coffee Beans"));
+    assert!(!matches.contains("Rent"));
+
+    // Never-matched rules come first when sorting by last match
+    let (_, page) = client
+        .get("/manage?tab=rules&sort=lastmatched&dir=asc")
+        .await;
+    let unused = page.find("rule-2").unwrap();
+    let coffee = page.find("rule-1").unwrap();
+    assert!(unused < coffee);
+    let (_, page) = client.get("/manage?tab=rules&sort=matches&dir=desc").await;
+    assert!(page.find("rule-1").unwrap() < page.find("rule-2").unwrap());
+
+    let (status, _) = client.post_form("/rules/1/reset-stats", &[]).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (_, detail) = client.get("/rules/1").await;
+    assert!(detail.contains("0 transactions"));
+    assert!(detail.contains("Never"));
+    let (_, matches) = client.get("/rules/1/matches").await;
+    assert!(!matches.contains("Coffee Shop"));
+
+    let (status, _) = client.post_form("/rules/99/reset-stats", &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// A rule suggested from a transaction matches its counterparty IBAN and can
+/// be applied to the uncategorized transactions it matches on creation.
+#[tokio::test]
+async fn test_rule_suggestion_matches_iban_and_applies() {
+    let client = TestClient::new();
+    for description in ["Rent March", "Rent April", "Groceries"] {
+        assert!(
+            client
+                .create_transaction("2024-03-01", "-500.00", description, None, None)
+                .await
+        );
+    }
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute(
+            "UPDATE transactions SET payee = 'Landlord GmbH', counterparty_iban = 'DE89 3704 0044'
+             WHERE description LIKE 'Rent%'",
+            [],
+        )
+        .unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let (status, body) = client.get("/transactions/1/rule-suggestion").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("^DE8937040044$"));
+    assert!(body.contains("Landlord GmbH"));
+    assert!(body.contains("apply_uncategorized"));
+
+    let (status, _) = client
+        .post_form(
+            "/rules/create",
+            &[
+                ("name", "Landlord GmbH"),
+                ("pattern", "^DE8937040044$"),
+                ("match_field", "iban"),
+                ("action_type", "assign_category"),
+                ("action_value", "4"),
+                ("apply_uncategorized", "on"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let conn = client.state().db.get().unwrap();
+    let categorized: Vec<String> = conn
+        .prepare("SELECT description FROM transactions WHERE category_id = 4 ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(categorized, ["Rent March", "Rent April"]);
+
+    let (_, detail) = client.get("/rules/1").await;
+    assert!(detail.contains("IBAN"));
+    assert!(detail.contains("2 transactions"));
+}
+
+/// A rule whose value cannot be applied to the transactions it matches is
+/// not stored either.
+#[tokio::test]
+async fn test_rule_that_fails_to_apply_is_not_created() {
+    let client = TestClient::new();
+    client
+        .create_transaction("2024-03-01", "-4.50", "Coffee Shop", None, None)
+        .await;
+
+    let (status, _) = client
+        .post_form(
+            "/rules/create",
+            &[
+                ("name", "Coffee"),
+                ("pattern", "Coffee"),
+                ("action_type", "assign_category"),
+                ("action_value", "not-a-category"),
+                ("apply_uncategorized", "on"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(rule_count(&client), 0);
+}
+
+/// Writes made before a failure in the middle of a transaction scope are
+/// rolled back.
+#[tokio::test]
+async fn test_with_tx_rolls_back_on_error() {
+    let client = TestClient::new();
+
+    let result: Result<(), AppError> = client.state().with_tx(|tx| {
+        for i in 0..3 {
+            rules::create_rule(
+                tx,
+                &NewRule {
+                    name: format!("Rule {}", i),
+                    pattern: "x".into(),
+                    match_field: RuleMatchField::default(),
+                    action_type: RuleActionType::AssignTag,
+                    action_value: "1".into(),
+                },
+            )?;
+        }
+        Err(AppError::Validation("Injected failure".into()))
+    });
+    assert!(result.is_err());
+    assert_eq!(rule_count(&client), 0);
+
+    let created = client.state().with_tx(|tx| {
+        rules::create_rule(
+            tx,
+            &NewRule {
+                name: "Kept".into(),
+                pattern: "x".into(),
+                match_field: RuleMatchField::default(),
+                action_type: RuleActionType::AssignTag,
+                action_value: "1".into(),
+            },
+        )
+        .map_err(Into::into)
+    });
+    assert!(created.is_ok());
+    assert_eq!(rule_count(&client), 1);
+}
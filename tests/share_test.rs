@@ -0,0 +1,267 @@
+//! Integration tests for read-only share links.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::TestClient;
+use http_body_util::BodyExt;
+use solvency::config::AuthMode;
+use solvency::db::queries::share_links;
+use solvency::models::{NewShareLink, ShareLink, ShareScope};
+use tower::ServiceExt;
+
+// Login password hash as in auth_test.rs
+const TEST_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$rJQH0emBAuPPLwwjO9XGAN1BANE";
+
+fn auth_client() -> TestClient {
+    TestClient::with_auth_mode(AuthMode::Password(TEST_PASSWORD_HASH.to_string()))
+}
+
+fn create_link(client: &TestClient, scope: ShareScope) -> ShareLink {
+    let conn = client.state().db.get().unwrap();
+    share_links::create_share_link(
+        &conn,
+        &NewShareLink {
+            scope,
+            label: "For my accountant".into(),
+            password_hash: None,
+            expires_in_days: Some(7),
+        },
+    )
+    .unwrap()
+}
+
+/// Create a link with a password through the settings form, which hashes it.
+async fn create_protected_link(client: &TestClient, password: &str) -> ShareLink {
+    let (status, _) = client
+        .post_form(
+            "/settings/share-links",
+            &[("scope", "net-worth"), ("password", password)],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let conn = client.state().db.get().unwrap();
+    share_links::list_share_links(&conn)
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap()
+}
+
+/// Send a request through the auth middleware and return status, headers and body.
+async fn send(
+    client: &TestClient,
+    request: Request<Body>,
+) -> (StatusCode, axum::http::HeaderMap, String) {
+    let response = client.router_with_auth().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, String::from_utf8_lossy(&body).to_string())
+}
+
+#[tokio::test]
+async fn test_share_link_shows_snapshot_without_login() {
+    let client = auth_client();
+    let this_month = chrono::Local::now().format("%Y-%m-01").to_string();
+    client
+        .create_transaction(&this_month, "-42.50", "Groceries", None, Some(4))
+        .await;
+    let link = create_link(&client, ShareScope::SpendingReport);
+
+    let (status, body) = client.get_with_auth(&link.path()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("For my accountant"));
+    assert!(body.contains("Food &#38; Dining"));
+    assert!(body.contains("42.50"));
+    // No app navigation in the snapshot
+    assert!(!body.contains("href=\"/transactions\""));
+    assert!(!body.contains("href=\"/settings\""));
+
+    for scope in [ShareScope::NetWorth, ShareScope::Positions] {
+        let link = create_link(&client, scope);
+        let (status, body) = client.get_with_auth(&link.path()).await;
+        assert_eq!(status, StatusCode::OK, "{}", scope);
+        assert!(body.contains(scope.label()));
+    }
+}
+
+#[tokio::test]
+async fn test_revoked_expired_and_unknown_links_are_not_found() {
+    let client = auth_client();
+    let revoked = create_link(&client, ShareScope::NetWorth);
+    let expired = create_link(&client, ShareScope::NetWorth);
+    {
+        let conn = client.state().db.get().unwrap();
+        share_links::delete_share_link(&conn, revoked.id).unwrap();
+        conn.execute(
+            "UPDATE share_links SET expires_at = datetime('now', '-1 minute') WHERE id = ?",
+            [expired.id],
+        )
+        .unwrap();
+    }
+
+    for path in [
+        revoked.path(),
+        expired.path(),
+        "/share/doesnotexist".to_string(),
+    ] {
+        let (status, body) = client.get_with_auth(&path).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", path);
+        assert!(!body.contains("Net Worth"));
+    }
+}
+
+#[tokio::test]
+async fn test_share_scope_does_not_extend_to_other_routes() {
+    let client = auth_client();
+    let link = create_link(&client, ShareScope::Positions);
+
+    // Only viewing and unlocking the token itself is allowed
+    let (status, _, _) = send(
+        &client,
+        Request::builder()
+            .method("DELETE")
+            .uri(link.path())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = client
+        .get_with_auth(&format!("{}/../../transactions", link.path()))
+        .await;
+    assert_ne!(status, StatusCode::OK);
+    let (status, _) = client
+        .get_with_auth(&format!("{}/export", link.path()))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Mutating routes still require a login
+    let (status, _, _) = send(
+        &client,
+        Request::builder()
+            .method("DELETE")
+            .uri("/transactions/delete-all")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (status, _) = client.get_with_auth("/settings/share-links").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+}
+
+#[tokio::test]
+async fn test_password_protected_link_requires_unlock() {
+    let client = auth_client();
+    let link = create_protected_link(&client, "opensesame").await;
+
+    let (status, body) = client.get_with_auth(&link.path()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("type=\"password\""));
+    assert!(!body.contains("Month-End Values"));
+    // The owner's XSRF token is not handed out with the link
+    assert!(!body.contains(&client.state().xsrf_token.value()));
+
+    let unlock = |password: &str| {
+        Request::builder()
+            .method("POST")
+            .uri(link.path())
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(format!("password={}", password)))
+            .unwrap()
+    };
+
+    let (status, headers, _) = send(&client, unlock("wrong")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(headers.get(header::SET_COOKIE).is_none());
+
+    let (status, headers, _) = send(&client, unlock("opensesame")).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let set_cookie = headers
+        .get(header::SET_COOKIE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(set_cookie.contains(&format!("Path={}", link.path())));
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+    let with_cookie = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header(header::COOKIE, cookie.clone())
+            .body(Body::empty())
+            .unwrap()
+    };
+    let (status, _, body) = send(&client, with_cookie(&link.path())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains("type=\"password\""));
+
+    // The share session is no login session
+    let (status, _, _) = send(&client, with_cookie("/net-worth")).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    // Nor does it unlock other links
+    let other = create_protected_link(&client, "opensesame").await;
+    let (_, _, body) = send(&client, with_cookie(&other.path())).await;
+    assert!(body.contains("type=\"password\""));
+
+    // The unlock form posts without an XSRF token; other forms still need one
+    let response = client
+        .router_with_xsrf()
+        .oneshot(unlock("opensesame"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let revoke = Request::builder()
+        .method("DELETE")
+        .uri(format!("/settings/share-links/{}", other.id))
+        .body(Body::empty())
+        .unwrap();
+    let response = client.router_with_xsrf().oneshot(revoke).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_create_and_revoke_share_link_from_settings() {
+    let client = TestClient::new();
+
+    let (status, _) = client
+        .post_form(
+            "/settings/share-links",
+            &[
+                ("scope", "positions"),
+                ("label", "Portfolio"),
+                ("password", "secret"),
+                ("expires_in_days", "14"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let conn = client.state().db.get().unwrap();
+    let links = share_links::list_share_links(&conn).unwrap();
+    assert_eq!(links.len(), 1);
+    let link = &links[0];
+    assert_eq!(link.scope, ShareScope::Positions);
+    assert!(link.has_password());
+    assert!(link.expires_at.is_some());
+
+    let (_, body) = client.get("/settings/share-links").await;
+    assert!(body.contains(&link.path()));
+
+    let (status, _) = client
+        .post_form("/settings/share-links", &[("scope", "transactions")])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = client
+        .delete_request(&format!("/settings/share-links/{}", link.id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(share_links::list_share_links(&conn).unwrap().is_empty());
+}
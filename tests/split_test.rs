@@ -2,10 +2,12 @@
 //!
 //! Splits adjust the quantity and unit_price of prior BUY/SELL activities
 //! at creation time. When a BUY/SELL is created before an existing split,
-//! its values are adjusted too. Deleting a split reverses the adjustments.
+//! its values are adjusted too. Deleting a split reverses the adjustments,
+//! and restoring it from the trash applies them again.
 
 mod common;
 
+use axum::http::StatusCode;
 use common::TestClient;
 use solvency::models::TradingActivityType;
 
@@ -683,3 +685,549 @@ async fn test_multiple_buys_before_split() {
     assert_eq!(buys[2].quantity, Some(150.0)); // 15 * 10
     assert_eq!(buys[2].unit_price_cents, Some(1800)); // $18
 }
+
+// =========================================================================
+// Trash and restore
+// =========================================================================
+
+/// Quantity and price of every activity for a symbol, in date order.
+fn snapshot(client: &TestClient, symbol: &str) -> Vec<(i64, Option<f64>, Option<i64>)> {
+    client
+        .get_activities_for_symbol(symbol)
+        .iter()
+        .map(|a| (a.id, a.quantity, a.unit_price_cents))
+        .collect()
+}
+
+/// Deleting and restoring a split leaves every activity exactly as if the
+/// split had never been deleted.
+#[tokio::test]
+async fn test_restore_split_reapplies_adjustments() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "TSLA", "BUY", "10", "600.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-03-01", "TSLA", "SELL", "4", "700.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-06-01", "TSLA", "SPLIT", "2", "")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-07-01", "TSLA", "BUY", "5", "300.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-09-01", "TSLA", "SPLIT", "3", "")
+            .await
+    );
+
+    let before = snapshot(&client, "TSLA");
+    let split_a = client
+        .get_activities_for_symbol("TSLA")
+        .into_iter()
+        .find(|a| a.activity_type == TradingActivityType::Split && a.quantity == Some(2.0))
+        .unwrap();
+
+    assert!(client.delete_trading_activity(split_a.id).await);
+    let activities = client.get_activities_for_symbol("TSLA");
+    assert_eq!(activities.len(), 4);
+    // First BUY now only reflects the 3:1 split
+    assert_eq!(activities[0].quantity, Some(30.0));
+
+    assert!(client.restore_trading_activity(split_a.id).await);
+    assert_eq!(snapshot(&client, "TSLA"), before);
+}
+
+/// A BUY restored from the trash picks up splits added while it was deleted.
+#[tokio::test]
+async fn test_restore_buy_applies_later_splits() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "100", "300.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-06-15", "AAPL", "SPLIT", "2", "")
+            .await
+    );
+
+    let buy = client.get_activities_for_symbol("AAPL")[0].clone();
+    assert!(client.delete_trading_activity(buy.id).await);
+
+    assert!(
+        client
+            .create_trading_activity("2024-09-01", "AAPL", "SPLIT", "4", "")
+            .await
+    );
+
+    assert!(client.restore_trading_activity(buy.id).await);
+    let restored = client.get_activities_for_symbol("AAPL")[0].clone();
+    assert_eq!(restored.id, buy.id);
+    assert_eq!(restored.quantity, Some(800.0));
+    assert_eq!(restored.unit_price_cents, Some(3750));
+}
+
+/// Deleted activities are hidden from positions, listed in the trash, and
+/// purged once they are older than the retention period.
+#[tokio::test]
+async fn test_trash_lists_and_purges_deleted_activities() {
+    let client = TestClient::new();
+
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "MSFT", "BUY", "10", "400.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-02-01", "GOOG", "BUY", "3", "140.00")
+            .await
+    );
+    let msft = client.get_activities_for_symbol("MSFT")[0].clone();
+    let goog = client.get_activities_for_symbol("GOOG")[0].clone();
+    assert!(client.delete_trading_activity(msft.id).await);
+    assert!(client.delete_trading_activity(goog.id).await);
+
+    assert!(client.get_activities_for_symbol("MSFT").is_empty());
+    let (_, body) = client.get("/trading/positions").await;
+    assert!(!body.contains("MSFT"));
+
+    let (status, body) = client.get("/trading/activities/trash").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("MSFT"));
+    assert!(body.contains("GOOG"));
+
+    // Age the MSFT deletion past the retention period
+    {
+        let conn = client.state().db.get().unwrap();
+        conn.execute(
+            "UPDATE trading_activities SET deleted_at = datetime('now', '-31 days') WHERE id = ?",
+            [msft.id],
+        )
+        .unwrap();
+    }
+
+    let (_, body) = client.get("/trading/activities/trash").await;
+    assert!(!body.contains("MSFT"));
+    assert!(body.contains("GOOG"));
+    assert!(!client.restore_trading_activity(msft.id).await);
+
+    let (status, _) = client.delete_request("/trading/activities/trash").await;
+    assert_eq!(status, 200);
+    let (_, body) = client.get("/trading/activities/trash").await;
+    assert!(body.contains("The trash is empty."));
+}
+
+/// Re-importing an activity by external_id compares and updates its values
+/// as imported, so split adjustments are neither mistaken for changes nor
+/// lost on update.
+#[tokio::test]
+async fn test_json_import_by_external_id_respects_splits() {
+    let client = TestClient::new();
+    let buy = |price: i64| {
+        serde_json::json!([{
+            "external_id": "broker-42",
+            "date": "2024-01-01",
+            "symbol": "AAPL",
+            "quantity": 100.0,
+            "activity_type": "BUY",
+            "unit_price_cents": price,
+        }])
+        .to_string()
+    };
+    let import = |body: String| {
+        let client = &client;
+        async move {
+            let (status, body) = client.post_json("/trading/activities/import", &body).await;
+            assert_eq!(status, axum::http::StatusCode::OK, "{}", body);
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        }
+    };
+
+    assert_eq!(import(buy(30000)).await["created"], 1);
+    assert!(
+        client
+            .create_trading_activity("2024-06-15", "AAPL", "SPLIT", "2", "")
+            .await
+    );
+
+    assert_eq!(import(buy(30000)).await["unchanged"], 1);
+
+    assert_eq!(import(buy(32000)).await["updated"], 1);
+    let activities = client.get_activities_for_symbol("AAPL");
+    let buy_row = activities
+        .iter()
+        .find(|a| a.activity_type == TradingActivityType::Buy)
+        .unwrap();
+    assert_eq!(buy_row.quantity, Some(200.0));
+    assert_eq!(buy_row.unit_price_cents, Some(16000));
+    assert_eq!(activities.len(), 2);
+
+    // A trashed activity is not silently brought back
+    let (status, _) = client
+        .delete_request(&format!("/trading/activities/{}/delete", buy_row.id))
+        .await;
+    assert!(status.is_success());
+    let result = import(buy(32000)).await;
+    assert_eq!(result["failed"], 1);
+    assert!(result["records"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("trash"));
+}
+
+/// The JSON export carries values as entered, so importing it elsewhere
+/// replays the splits once. Records are imported in date order and checked
+/// one by one.
+#[tokio::test]
+async fn test_json_export_round_trip_with_splits() {
+    let client = TestClient::new();
+    let body = serde_json::json!([
+        {"date": "2024-06-15", "symbol": "AAPL", "quantity": 2.0, "activity_type": "SPLIT"},
+        {"date": "2024-09-01", "symbol": "AAPL", "quantity": 50.0, "activity_type": "SELL",
+         "unit_price_cents": 20000, "account_name": "Unknown Broker"},
+        {"date": "2024-01-01", "symbol": "AAPL", "quantity": 100.0, "activity_type": "BUY",
+         "unit_price_cents": 30000, "notes": "first lot"},
+        {"date": "2024-13-01", "symbol": "AAPL", "quantity": 1.0, "activity_type": "BUY",
+         "unit_price_cents": 100},
+        {"date": "2024-02-01", "symbol": "AAPL", "activity_type": "BUY", "unit_price_cents": 100},
+    ])
+    .to_string();
+    let (status, result) = client.post_json("/trading/activities/import", &body).await;
+    assert_eq!(status, axum::http::StatusCode::OK, "{}", result);
+    let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+    assert_eq!(result["created"], 3);
+    assert_eq!(result["failed"], 2);
+    let records = result["records"].as_array().unwrap();
+    let indices: Vec<_> = records
+        .iter()
+        .map(|r| r["index"].as_u64().unwrap())
+        .collect();
+    assert_eq!(indices, vec![0, 1, 2, 3, 4]);
+    assert!(records[3]["error"]
+        .as_str()
+        .unwrap()
+        .contains("invalid date"));
+    assert!(records[4]["error"].as_str().unwrap().contains("quantity"));
+    assert_eq!(
+        records[1]["warnings"][0],
+        "Unknown account \"Unknown Broker\" was dropped"
+    );
+    assert_eq!(
+        result["unknown_names"]["accounts"]["dropped"][0],
+        "Unknown Broker"
+    );
+
+    let (status, export) = client
+        .get_json::<serde_json::Value>("/trading/activities/export?format=json")
+        .await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    let export = export.unwrap();
+    let buy = export
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["activity_type"] == "BUY")
+        .unwrap();
+    assert_eq!(buy["quantity"], 100.0);
+    assert_eq!(buy["unit_price_cents"], 30000);
+    assert_eq!(buy["adjusted_quantity"], 200.0);
+    assert_eq!(buy["notes"], "first lot");
+
+    let other = TestClient::new();
+    let (status, _) = other
+        .post_json("/trading/activities/import", &export.to_string())
+        .await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    let activities = other.get_activities_for_symbol("AAPL");
+    let buy = activities
+        .iter()
+        .find(|a| a.activity_type == TradingActivityType::Buy)
+        .unwrap();
+    assert_eq!(buy.quantity, Some(200.0));
+    assert_eq!(buy.unit_price_cents, Some(15000));
+    let sell = activities
+        .iter()
+        .find(|a| a.activity_type == TradingActivityType::Sell)
+        .unwrap();
+    assert_eq!(sell.quantity, Some(50.0));
+
+    let (status, _) = client.get("/trading/activities/export?format=xlsx").await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+}
+
+// =========================================================================
+// Split helper fields
+// =========================================================================
+
+/// Shares after and before replace the quantity of a split, and the edit
+/// form shows them again.
+#[tokio::test]
+async fn test_split_entered_as_shares_after_and_before() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "30", "300.00")
+            .await
+    );
+
+    let split_form = |after: &'static str, before: &'static str| {
+        [
+            ("date", "2024-06-10"),
+            ("symbol", "AAPL"),
+            ("activity_type", "SPLIT"),
+            ("quantity", ""),
+            ("currency", "USD"),
+            ("shares_after", after),
+            ("shares_before", before),
+        ]
+    };
+    let (status, _) = client
+        .post_form("/trading/activities/create", &split_form("3", ""))
+        .await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+
+    let (status, _) = client
+        .post_form("/trading/activities/create", &split_form("3", "2"))
+        .await;
+    assert_eq!(status, axum::http::StatusCode::SEE_OTHER);
+
+    let activities = client.get_activities_for_symbol("AAPL");
+    let buy = activities
+        .iter()
+        .find(|a| a.activity_type == TradingActivityType::Buy)
+        .unwrap();
+    assert_eq!(buy.quantity, Some(45.0));
+    assert_eq!(buy.unit_price_cents, Some(20000));
+    let split = activities
+        .iter()
+        .find(|a| a.activity_type == TradingActivityType::Split)
+        .unwrap();
+    assert_eq!(split.quantity, Some(1.5));
+
+    let (_, body) = client
+        .get(&format!("/trading/activities/{}/edit", split.id))
+        .await;
+    assert!(body.contains(r#"name="shares_after" value="3""#));
+    assert!(body.contains(r#"name="shares_before" value="2""#));
+}
+
+/// Editing a split's quantity while the edit form still holds the old
+/// shares after and before is rejected rather than silently ignored.
+#[tokio::test]
+async fn test_edit_split_quantity_conflicting_with_shares() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "100", "300.00")
+            .await
+    );
+    assert!(
+        client
+            .create_trading_activity("2024-06-15", "AAPL", "SPLIT", "2", "")
+            .await
+    );
+    let split_id = client
+        .get_activities_for_symbol("AAPL")
+        .iter()
+        .find(|a| a.activity_type == TradingActivityType::Split)
+        .unwrap()
+        .id;
+    let update = |quantity: &'static str, after: &'static str, before: &'static str| {
+        let client = &client;
+        async move {
+            client
+                .post_form(
+                    &format!("/trading/activities/{}/update", split_id),
+                    &[
+                        ("date", "2024-06-15"),
+                        ("symbol", "AAPL"),
+                        ("activity_type", "SPLIT"),
+                        ("quantity", quantity),
+                        ("currency", "USD"),
+                        ("shares_after", after),
+                        ("shares_before", before),
+                    ],
+                )
+                .await
+                .0
+        }
+    };
+    let buy_quantity = || {
+        client
+            .get_activities_for_symbol("AAPL")
+            .iter()
+            .find(|a| a.activity_type == TradingActivityType::Buy)
+            .unwrap()
+            .quantity
+    };
+
+    assert_eq!(update("3", "2", "1").await, StatusCode::BAD_REQUEST);
+    assert_eq!(buy_quantity(), Some(200.0));
+
+    assert_eq!(update("3", "", "").await, StatusCode::SEE_OTHER);
+    assert_eq!(buy_quantity(), Some(300.0));
+
+    assert_eq!(update("4", "4", "1").await, StatusCode::SEE_OTHER);
+    assert_eq!(buy_quantity(), Some(400.0));
+}
+
+/// The preview describes the split and warns when the stored prices show
+/// no jump on the split date.
+#[tokio::test]
+async fn test_split_preview_warns_about_adjusted_prices() {
+    use solvency::db::queries::market_data;
+    use solvency::models::NewMarketData;
+
+    let client = TestClient::new();
+    let (_, body) = client
+        .get("/trading/activities/split-preview?symbol=NVDA&date=2024-06-10&shares_after=1&shares_before=10")
+        .await;
+    assert!(body.contains("Each 10 shares become 1; prices multiplied by 10"));
+
+    let price = |date: &str, cents: i64| NewMarketData {
+        symbol: "NVDA".into(),
+        date: date.into(),
+        close_price_cents: cents,
+        close_price: None,
+        currency: "USD".into(),
+    };
+    {
+        let conn = client.state().db.get().unwrap();
+        market_data::insert_market_data_batch(
+            &conn,
+            &[price("2024-06-07", 12000), price("2024-06-10", 12100)],
+        )
+        .unwrap();
+    }
+
+    let (_, body) = client
+        .get("/trading/activities/split-preview?symbol=NVDA&date=2024-06-10&shares_after=10&shares_before=1")
+        .await;
+    assert!(body.contains("Each 1 share becomes 10; prices divided by 10"));
+    assert!(body.contains("may already be adjusted"));
+
+    let (_, body) = client
+        .get("/trading/activities/split-preview?symbol=NVDA&date=2024-06-10&shares_after=10&shares_before=0")
+        .await;
+    assert!(body.contains("Shares before must be a positive number"));
+}
+
+// =========================================================================
+// Locking against market data refreshes
+// =========================================================================
+
+/// Split edits wait for a refresh writing the same symbol and give up
+/// with a conflict after a while; other symbols are not held up.
+#[tokio::test]
+async fn test_split_edits_conflict_with_running_refresh() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "100", "300.00")
+            .await
+    );
+    let buy_id = client.get_activities_for_symbol("AAPL")[0].id;
+
+    let guard = client.state().symbol_locks.lock(&["AAPL"]).await;
+    let split = [
+        ("date", "2024-06-15"),
+        ("symbol", "AAPL"),
+        ("activity_type", "SPLIT"),
+        ("quantity", "2"),
+        ("unit_price", ""),
+        ("currency", "USD"),
+        ("fee", "0"),
+    ];
+    let (status, body) = client.post_form("/trading/activities/create", &split).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body.contains("please retry shortly"));
+    let (status, _) = client
+        .delete_request(&format!("/trading/activities/{}/delete", buy_id))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "MSFT", "BUY", "10", "400.00")
+            .await
+    );
+
+    // Nothing was written for the held symbol
+    let activities = client.get_activities_for_symbol("AAPL");
+    assert_eq!(activities.len(), 1);
+    assert_eq!(activities[0].quantity, Some(100.0));
+
+    drop(guard);
+    let (status, _) = client.post_form("/trading/activities/create", &split).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        client.get_activities_for_symbol("AAPL")[0].quantity,
+        Some(200.0)
+    );
+}
+
+/// A split created while a refresh writes prices of its symbol runs after
+/// the refresh is done, and adjusts the earlier buy exactly once.
+#[tokio::test]
+async fn test_split_waits_for_concurrent_refresh() {
+    use solvency::db::queries::market_data;
+    use solvency::models::NewMarketData;
+    use std::time::{Duration, Instant};
+
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_trading_activity("2024-01-01", "AAPL", "BUY", "100", "300.00")
+            .await
+    );
+
+    let state = client.state().clone();
+    let start = Instant::now();
+    let refresh = async {
+        let _guard = state.symbol_locks.lock(&["AAPL"]).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let conn = state.db.get().unwrap();
+        market_data::insert_market_data_batch(
+            &conn,
+            &[NewMarketData {
+                symbol: "AAPL".into(),
+                date: "2024-06-14".into(),
+                close_price_cents: 30000,
+                close_price: None,
+                currency: "USD".into(),
+            }],
+        )
+        .unwrap();
+        start.elapsed()
+    };
+    let split = async {
+        let created = client
+            .create_trading_activity("2024-06-15", "AAPL", "SPLIT", "2", "")
+            .await;
+        (created, start.elapsed())
+    };
+    let (refreshed_after, (created, split_after)) = tokio::join!(refresh, split);
+
+    assert!(created);
+    assert!(split_after >= refreshed_after);
+    let activities = client.get_activities_for_symbol("AAPL");
+    let buy = activities
+        .iter()
+        .find(|a| a.activity_type == TradingActivityType::Buy)
+        .expect("BUY not found");
+    assert_eq!(buy.quantity, Some(200.0));
+    assert_eq!(buy.unit_price_cents, Some(15000));
+}
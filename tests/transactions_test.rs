@@ -4,6 +4,8 @@ mod common;
 
 use axum::http::StatusCode;
 use common::TestClient;
+use solvency::db::pool::STATEMENT_CACHE_CAPACITY;
+use solvency::db::queries::{settings, tags, transaction_sums, transactions};
 
 /// Searching transactions with an empty category_id (from the "All Categories"
 /// select option) must not return 400.
@@ -38,6 +40,39 @@ async fn test_search_filters_by_description() {
     assert!(!body.contains("Electric Bill"));
 }
 
+/// The search box matches descriptions only; payees have their own filter.
+#[tokio::test]
+async fn test_payee_filter_is_separate_from_search() {
+    let client = TestClient::new();
+    for (description, payee) in [("Card payment 0412", "Rewe"), ("Rewe voucher", "")] {
+        let (status, _) = client
+            .post_form(
+                "/transactions/create",
+                &[
+                    ("date", "2024-01-01"),
+                    ("amount", "-20.00"),
+                    ("currency", "USD"),
+                    ("description", description),
+                    ("payee", payee),
+                ],
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+
+    let (_, body) = client
+        .get("/transactions/table?search=Rewe&from_date=2024-01-01&to_date=2024-12-31")
+        .await;
+    assert!(body.contains("Rewe voucher"));
+    assert!(!body.contains("Card payment 0412"));
+
+    let (_, body) = client
+        .get("/transactions/table?payee=rewe&search=&from_date=2024-01-01&to_date=2024-12-31")
+        .await;
+    assert!(body.contains("Card payment 0412"));
+    assert!(!body.contains("Rewe voucher"));
+}
+
 /// Filtering by category_id returns only transactions in that category.
 #[tokio::test]
 async fn test_filter_by_category_id() {
@@ -54,3 +89,1220 @@ async fn test_filter_by_category_id() {
     assert_eq!(status, StatusCode::OK);
     assert!(body.contains("Cat-1 item"));
 }
+
+/// A category filter matches the category alone unless `include_children`
+/// is set.
+#[tokio::test]
+async fn test_filter_by_category_include_children() {
+    let client = TestClient::new();
+    // Groceries (id=12) is a child of Food & Dining (id=4)
+    client
+        .create_transaction("2024-03-01", "-10.00", "Parent item", None, Some(4))
+        .await;
+    client
+        .create_transaction("2024-03-02", "-20.00", "Child item", None, Some(12))
+        .await;
+
+    let (_, body) = client
+        .get("/transactions/table?category_id=4&from_date=2024-01-01&to_date=2024-12-31")
+        .await;
+    assert!(body.contains("Parent item"));
+    assert!(!body.contains("Child item"));
+
+    let (_, body) = client
+        .get("/transactions/table?category_id=4&include_children=true&from_date=2024-01-01&to_date=2024-12-31")
+        .await;
+    assert!(body.contains("Parent item"));
+    assert!(body.contains("Child item"));
+}
+
+/// Deleting all transactions takes two requests: the first only hands out a
+/// confirmation token, the second (carrying it) deletes and reports the count.
+#[tokio::test]
+async fn test_delete_all_requires_confirmation_token() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-01", "-10.00", "Zanzibar espresso", None, None)
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-01-02", "-20.00", "Lunch", None, None)
+            .await
+    );
+
+    let (status, body) = client.delete_request("/transactions/delete-all").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let pending: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let token = pending["confirm_token"].as_str().unwrap().to_string();
+    assert_eq!(
+        pending["message"],
+        "This permanently deletes 2 transactions."
+    );
+
+    // Nothing was deleted yet, and a token for another endpoint is rejected
+    let (_, page) = client.get("/transactions").await;
+    assert!(page.contains("Zanzibar espresso"));
+    let (status, _) = client
+        .delete_request(&format!("/categories/delete-all?confirm={}", token))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // The token was consumed by the mismatched request
+    let (status, _) = client
+        .delete_request(&format!("/transactions/delete-all?confirm={}", token))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, body) = client.delete_request("/transactions/delete-all").await;
+    let pending: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let token = pending["confirm_token"].as_str().unwrap();
+    let (status, body) = client
+        .delete_request(&format!("/transactions/delete-all?confirm={}", token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(result["deleted"]["transactions"], 2);
+
+    let (_, page) = client.get("/transactions").await;
+    assert!(!page.contains("Zanzibar espresso"));
+}
+
+/// The test suite may skip confirmation with `force=1`.
+#[tokio::test]
+async fn test_delete_all_force() {
+    let client = TestClient::new();
+    assert!(
+        client
+            .create_transaction("2024-01-01", "-10.00", "Zanzibar espresso", None, None)
+            .await
+    );
+
+    let (status, body) = client
+        .delete_request("/transactions/delete-all?force=1")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(result["deleted"]["transactions"], 1);
+}
+
+/// Save the transaction defaults through the settings form.
+async fn save_defaults(client: &TestClient, account_id: &str, category_id: &str) -> StatusCode {
+    let (status, _) = client
+        .post_form(
+            "/settings/update",
+            &[
+                ("theme", "system"),
+                ("currency", "USD"),
+                ("date_format", "YYYY-MM-DD"),
+                ("page_size", "25"),
+                ("locale", "en-US"),
+                ("default_account_id", account_id),
+                ("default_category_id", category_id),
+            ],
+        )
+        .await;
+    client.state().cache.invalidate();
+    status
+}
+
+/// The default account and category are pre-selected and filled in when
+/// the form omits the fields, and cleared when the account is deleted.
+#[tokio::test]
+async fn test_default_account_and_category() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert_eq!(save_defaults(&client, "1", "4").await, StatusCode::OK);
+
+    let (_, page) = client.get("/transactions/new").await;
+    assert!(page.contains(r#"<option value="1" selected>Checking"#));
+
+    assert!(
+        client
+            .create_transaction("2024-05-01", "-10.00", "Defaulted", None, None)
+            .await
+    );
+    let (status, _) = client
+        .post_form(
+            "/transactions/create",
+            &[
+                ("date", "2024-05-02"),
+                ("amount", "-5.00"),
+                ("currency", "USD"),
+                ("description", "Explicitly empty"),
+                ("account_id", ""),
+                ("category_id", ""),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    {
+        let conn = client.state().db.get().unwrap();
+        let defaulted = transactions::get_transaction(&conn, 1).unwrap().unwrap();
+        assert_eq!(defaulted.account_id, Some(1));
+        assert_eq!(defaulted.category_id, Some(4));
+        let explicit = transactions::get_transaction(&conn, 2).unwrap().unwrap();
+        assert_eq!(explicit.account_id, None);
+        assert_eq!(explicit.category_id, None);
+    }
+
+    let (status, _) = client.delete_request("/accounts/1").await;
+    assert_eq!(status, StatusCode::OK);
+    let conn = client.state().db.get().unwrap();
+    let settings = settings::get_settings(&conn).unwrap();
+    assert_eq!(settings.default_account_id, None);
+    assert_eq!(settings.default_category_id, Some(4));
+}
+
+/// Defaults must reference existing entities of the right kind.
+#[tokio::test]
+async fn test_default_references_are_validated() {
+    let client = TestClient::new();
+    assert!(client.create_account("Broker", "Securities").await);
+
+    assert_eq!(
+        save_defaults(&client, "", "999").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        save_defaults(&client, "1", "").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(save_defaults(&client, "", "").await, StatusCode::OK);
+
+    // Credit cards hold transactions like cash accounts
+    assert!(client.create_account("Visa", "CreditCard").await);
+    assert_eq!(save_defaults(&client, "2", "").await, StatusCode::OK);
+}
+
+/// Unknown category, account and tag names are dropped with a warning, or
+/// created when the import asks for it.
+#[tokio::test]
+async fn test_json_import_reports_and_creates_unknown_names() {
+    let client = TestClient::new();
+    let (status, _) = client
+        .post_form("/tags/create", &[("name", "known"), ("color", "#ff0000")])
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let body = serde_json::json!([{
+        "date": "2024-03-01",
+        "amount_cents": -500,
+        "description": "Lunch",
+        "category_name": "Eating Out",
+        "account_name": "Wallet",
+        "tags": ["known", "work"],
+    }])
+    .to_string();
+
+    let (status, dropped) = client.post_json("/transactions/import", &body).await;
+    assert_eq!(status, StatusCode::OK);
+    let dropped: serde_json::Value = serde_json::from_str(&dropped).unwrap();
+    assert_eq!(
+        dropped["records"][0]["warnings"],
+        serde_json::json!([
+            "Unknown category \"Eating Out\" was dropped",
+            "Unknown account \"Wallet\" was dropped",
+            "Unknown tag \"work\" was dropped",
+        ])
+    );
+    assert_eq!(
+        dropped["unknown_names"]["tags"],
+        serde_json::json!({"created": [], "dropped": ["work"]})
+    );
+
+    let (status, created) = client
+        .post_json("/transactions/import?create_missing=true", &body)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let created: serde_json::Value = serde_json::from_str(&created).unwrap();
+    assert!(created["records"][0].get("warnings").is_none());
+    assert_eq!(
+        created["unknown_names"]["categories"]["created"],
+        serde_json::json!(["Eating Out"])
+    );
+    assert_eq!(
+        created["unknown_names"]["accounts"]["created"],
+        serde_json::json!(["Wallet"])
+    );
+    assert_eq!(
+        created["unknown_names"]["tags"]["created"],
+        serde_json::json!(["work"])
+    );
+
+    let id = created["records"][0]["id"].as_i64().unwrap();
+    let conn = client.state().db.get().unwrap();
+    let txn = transactions::get_transaction(&conn, id).unwrap().unwrap();
+    assert_eq!(txn.category_name.as_deref(), Some("Eating Out"));
+    assert_eq!(txn.account_name.as_deref(), Some("Wallet"));
+    let mut tag_names: Vec<&str> = txn.tags.iter().map(|t| t.name.as_str()).collect();
+    tag_names.sort_unstable();
+    assert_eq!(tag_names, ["known", "work"]);
+    assert!(client
+        .state()
+        .cached_accounts()
+        .unwrap()
+        .iter()
+        .any(|a| a.name == "Wallet"));
+}
+
+/// Re-importing records with an external_id updates the transactions created
+/// for them instead of inserting duplicates.
+#[tokio::test]
+async fn test_json_import_is_idempotent_with_external_ids() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+
+    let record = |amount: i64, account: &str| {
+        serde_json::json!([{
+            "external_id": "bank-1",
+            "date": "2024-03-01",
+            "amount_cents": amount,
+            "description": "Coffee",
+            "account_name": account,
+        }, {
+            "date": "2024-03-02",
+            "amount_cents": -100,
+            "description": "No external id",
+        }])
+        .to_string()
+    };
+    let import = |body: String| {
+        let client = &client;
+        async move {
+            let (status, body) = client.post_json("/transactions/import", &body).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        }
+    };
+
+    let first = import(record(-350, "Checking")).await;
+    assert_eq!(first["created"], 2);
+    assert_eq!(first["records"][0]["outcome"], "created");
+    let id = first["records"][0]["id"].as_i64().unwrap();
+
+    let retry = import(record(-350, "Checking")).await;
+    assert_eq!(retry["created"], 1);
+    assert_eq!(retry["unchanged"], 1);
+    assert_eq!(retry["records"][0]["outcome"], "unchanged");
+    assert_eq!(retry["records"][0]["id"], id);
+
+    let changed = import(record(-400, "Checking")).await;
+    assert_eq!(changed["updated"], 1);
+    let conn = client.state().db.get().unwrap();
+    let txn = transactions::get_transaction(&conn, id).unwrap().unwrap();
+    assert_eq!(txn.amount_cents, -400);
+
+    // Moving the record to another account is a conflict, not an overwrite
+    let conflict = import(record(-400, "Savings")).await;
+    assert_eq!(conflict["failed"], 1);
+    assert_eq!(conflict["records"][0]["outcome"], "error");
+    assert!(conflict["records"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("Checking"));
+    let txn = transactions::get_transaction(&conn, id).unwrap().unwrap();
+    assert_eq!(txn.account_name.as_deref(), Some("Checking"));
+
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM transactions WHERE external_id = 'bank-1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+/// A filter-based bulk action on a parent category leaves transactions of
+/// its subcategories alone unless `include_children` is set.
+#[tokio::test]
+async fn test_bulk_category_on_parent_skips_children() {
+    let client = TestClient::new();
+    // Groceries (id=12) is a child of Food & Dining (id=4)
+    assert!(
+        client
+            .create_transaction("2024-03-01", "-10.00", "Parent item", None, Some(4))
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("2024-03-02", "-20.00", "Child item", None, Some(12))
+            .await
+    );
+    let categories = || -> Vec<(String, i64)> {
+        let conn = client.state().db.get().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT description, category_id FROM transactions ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+
+    let (status, _) = client
+        .post_form(
+            "/transactions/bulk-category",
+            &[("category_id", "4"), ("set_category_id", "6")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        categories(),
+        vec![("Parent item".into(), 6), ("Child item".into(), 12)]
+    );
+
+    let (status, _) = client
+        .post_form(
+            "/transactions/bulk-category",
+            &[
+                ("category_id", "4"),
+                ("include_children", "true"),
+                ("set_category_id", "6"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        categories(),
+        vec![("Parent item".into(), 6), ("Child item".into(), 6)]
+    );
+}
+
+/// Test that bulk remove-tag and clear-tags only touch matching transactions.
+#[tokio::test]
+async fn test_bulk_remove_and_clear_tags() {
+    let client = TestClient::new();
+    for description in ["Coffee", "Coffee beans", "Rent"] {
+        assert!(
+            client
+                .create_transaction("2024-01-15", "-5.00", description, None, None)
+                .await
+        );
+    }
+    let (trip, work) = {
+        let conn = client.state().db.get().unwrap();
+        (
+            tags::create_or_get_tag(&conn, "trip").unwrap().id,
+            tags::create_or_get_tag(&conn, "work").unwrap().id,
+        )
+    };
+    for tag_id in [trip, work] {
+        let tag = tag_id.to_string();
+        let (status, _) = client
+            .post_form("/transactions/bulk-tag", &[("set_tag_id", tag.as_str())])
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let tag_count = |tag_id: i64| -> i64 {
+        let conn = client.state().db.get().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM transaction_tags WHERE tag_id = ?",
+            [tag_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+
+    let trip_id = trip.to_string();
+    let (status, _) = client
+        .post_form(
+            "/transactions/bulk/remove-tag",
+            &[("set_tag_id", trip_id.as_str()), ("search", "Coffee")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tag_count(trip), 1);
+    assert_eq!(tag_count(work), 3);
+
+    let (status, _) = client
+        .post_form("/transactions/bulk/remove-tag", &[("set_tag_id", "")])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = client
+        .post_form("/transactions/bulk/clear-tags", &[("search", "Rent")])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tag_count(trip), 0);
+    assert_eq!(tag_count(work), 2);
+
+    // The tags themselves survive
+    let conn = client.state().db.get().unwrap();
+    assert_eq!(tags::list_tags(&conn).unwrap().len(), 2);
+}
+
+/// ID-based bulk operations change exactly the selected transactions and
+/// reject unknown IDs or oversized selections without changing anything.
+#[tokio::test]
+async fn test_bulk_operations_by_ids() {
+    let client = TestClient::new();
+    for description in ["Coffee", "Lunch", "Rent"] {
+        assert!(
+            client
+                .create_transaction("2024-01-15", "-5.00", description, None, None)
+                .await
+        );
+    }
+    let (ids, tag_id) = {
+        let conn = client.state().db.get().unwrap();
+        let ids: Vec<String> = transactions::list_transactions(&conn, &Default::default())
+            .unwrap()
+            .iter()
+            .filter(|t| t.transaction.description != "Rent")
+            .map(|t| t.transaction.id.to_string())
+            .collect();
+        (ids, tags::create_or_get_tag(&conn, "trip").unwrap().id)
+    };
+    assert_eq!(ids.len(), 2);
+    let selected = |extra: &[(&'static str, &str)]| -> Vec<(&'static str, String)> {
+        ids.iter()
+            .map(|id| ("transaction_ids[]", id.clone()))
+            .chain(extra.iter().map(|(k, v)| (*k, v.to_string())))
+            .collect()
+    };
+    let post = |uri: &'static str, fields: Vec<(&'static str, String)>| {
+        let client = &client;
+        async move {
+            let refs: Vec<(&str, &str)> = fields.iter().map(|(k, v)| (*k, v.as_str())).collect();
+            let (status, body) = client.post_form(uri, &refs).await;
+            let json = serde_json::from_str::<serde_json::Value>(&body).ok();
+            (status, json)
+        }
+    };
+    let count = |sql: &str| -> i64 {
+        let conn = client.state().db.get().unwrap();
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    };
+
+    let (status, json) = post(
+        "/transactions/bulk/by-ids/category",
+        selected(&[("set_category_id", "4")]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json.unwrap()["changed"], 2);
+    assert_eq!(
+        count("SELECT COUNT(*) FROM transactions WHERE category_id = 4"),
+        2
+    );
+
+    let (status, json) = post(
+        "/transactions/bulk/by-ids/tag",
+        selected(&[("set_tag_id", &tag_id.to_string())]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json.unwrap()["selected"], 2);
+    assert_eq!(count("SELECT COUNT(*) FROM transaction_tags"), 2);
+
+    let (status, json) = post(
+        "/transactions/bulk/by-ids/account",
+        selected(&[("set_account_id", "0")]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json.unwrap()["changed"], 2);
+
+    // A stale ID fails the whole request
+    let (status, _) = post(
+        "/transactions/bulk/by-ids/delete",
+        selected(&[("transaction_ids[]", "999999")]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(count("SELECT COUNT(*) FROM transactions"), 3);
+
+    let too_many: Vec<(&str, String)> = (1..=1001)
+        .map(|id| ("transaction_ids[]", id.to_string()))
+        .collect();
+    let (status, _) = post("/transactions/bulk/by-ids/delete", too_many).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post("/transactions/bulk/by-ids/delete", Vec::new()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, json) = post("/transactions/bulk/by-ids/delete", selected(&[])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json.unwrap()["changed"], 2);
+    assert_eq!(count("SELECT COUNT(*) FROM transactions"), 1);
+    assert_eq!(count("SELECT COUNT(*) FROM transaction_tags"), 0);
+}
+
+/// Hidden columns are left out of both the full page and the HTMX table
+/// partial, and compact density marks the table.
+#[tokio::test]
+async fn test_table_column_preferences() {
+    let client = TestClient::new();
+    client
+        .create_transaction("2024-03-01", "-12.50", "Lunch", None, Some(4))
+        .await;
+
+    let (_, html) = client
+        .get("/transactions/table?from_date=2024-01-01&to_date=2024-12-31")
+        .await;
+    assert!(html.contains("sort=counterparty"));
+    assert!(html.contains("<span>Food &#38; Dining</span>"));
+    assert!(!html.contains("table-compact"));
+
+    let (status, _) = client
+        .post_form(
+            "/settings/table-columns",
+            &[
+                ("columns", "date"),
+                ("columns", "description"),
+                ("columns", "amount"),
+                ("density", "compact"),
+                ("return_to", "/transactions?sort=amount"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let conn = client.state().db.get().unwrap();
+    let saved = settings::get_settings(&conn).unwrap();
+    assert_eq!(saved.transaction_columns, ["date", "description", "amount"]);
+    assert_eq!(saved.table_density, "compact");
+    drop(conn);
+    client.state().cache.invalidate();
+
+    for uri in ["/transactions", "/transactions/table"] {
+        let (status, html) = client
+            .get(&format!("{uri}?from_date=2024-01-01&to_date=2024-12-31"))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Lunch"), "{uri}");
+        assert!(html.contains("table-compact"), "{uri}");
+        // No sort links or cells for the hidden columns
+        assert!(html.contains("sort=amount"), "{uri}");
+        assert!(!html.contains("sort=counterparty"), "{uri}");
+        assert!(!html.contains("sort=category"), "{uri}");
+        assert!(!html.contains("<span>Food &#38; Dining</span>"), "{uri}");
+    }
+
+    // At least one column must stay visible
+    let (status, _) = client
+        .post_form("/settings/table-columns", &[("density", "compact")])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = client
+        .post_form(
+            "/settings/table-columns",
+            &[("columns", "date"), ("density", "tiny")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Filtered to one account and sorted by date, the table shows the balance
+/// after each row, including transactions before the date range.
+#[tokio::test]
+async fn test_running_balance_column() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+    for (date, amount, description, account) in [
+        ("2023-12-31", "100.00", "Opening deposit", 1),
+        ("2024-01-05", "-10.00", "Coffee", 1),
+        ("2024-01-05", "-20.00", "Lunch", 1),
+        ("2024-01-06", "1000.00", "Savings deposit", 2),
+        ("2024-01-10", "5.00", "Refund", 1),
+    ] {
+        assert!(
+            client
+                .create_transaction(date, amount, description, Some(account), None)
+                .await
+        );
+    }
+    let url = |query: &str| {
+        format!(
+            "/transactions/table?from_date=2024-01-01&to_date=2024-01-31&{}",
+            query
+        )
+    };
+    let positions = |body: &str, needles: &[&str]| -> Vec<usize> {
+        needles
+            .iter()
+            .map(|n| body.find(n).unwrap_or_else(|| panic!("{} missing", n)))
+            .collect()
+    };
+
+    let (status, body) = client.get(&url("account_id=1&sort=date&dir=asc")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Balance"));
+    assert!(!body.contains("Savings deposit"));
+    let pos = positions(
+        &body,
+        &["Coffee", "$90.00", "Lunch", "$70.00", "Refund", "$75.00"],
+    );
+    assert!(pos.windows(2).all(|w| w[0] < w[1]), "{:?}", pos);
+
+    // Newest first, same-day rows in reverse ID order
+    let (_, body) = client.get(&url("account_id=1&sort=date&dir=desc")).await;
+    let pos = positions(
+        &body,
+        &["Refund", "$75.00", "Lunch", "$70.00", "Coffee", "$90.00"],
+    );
+    assert!(pos.windows(2).all(|w| w[0] < w[1]), "{:?}", pos);
+
+    // Later pages start from the balance before their first row
+    {
+        let conn = client.state().db.get().unwrap();
+        settings::set_setting(&conn, "page_size", "2").unwrap();
+    }
+    client.state().cache.invalidate();
+    let (_, body) = client
+        .get(&url("account_id=1&sort=date&dir=desc&page=2"))
+        .await;
+    assert!(body.contains("Coffee") && body.contains("$90.00"));
+    assert!(!body.contains("Lunch"));
+
+    // Meaningless across accounts or in amount order
+    let (_, body) = client.get(&url("sort=date&dir=asc")).await;
+    assert!(!body.contains("Balance"));
+    let (_, body) = client.get(&url("account_id=1&sort=amount&dir=asc")).await;
+    assert!(!body.contains("Balance"));
+}
+
+/// Dates are typed and shown in the configured format but stored as ISO.
+/// Invalid dates are rejected as validation errors.
+#[tokio::test]
+async fn test_date_format_setting() {
+    let client = TestClient::new();
+    {
+        let conn = client.state().db.get().unwrap();
+        settings::set_setting(&conn, "date_format", "DD.MM.YYYY").unwrap();
+    }
+    client.state().cache.invalidate();
+
+    assert!(
+        client
+            .create_transaction("03.11.2024", "-10.00", "Typed the German way", None, None)
+            .await
+    );
+    assert!(
+        client
+            .create_transaction("01/02/2024", "-5.00", "Ambiguous", None, None)
+            .await
+    );
+    let (status, body) = client
+        .post_form(
+            "/transactions/create",
+            &[
+                ("date", "31.02.2024"),
+                ("amount", "-1.00"),
+                ("currency", "USD"),
+                ("description", "No such day"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("expected DD.MM.YYYY"), "{}", body);
+
+    let conn = client.state().db.get().unwrap();
+    let dates: Vec<String> = conn
+        .prepare("SELECT date FROM transactions ORDER BY date")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(dates, ["2024-02-01", "2024-11-03"]);
+
+    let (_, body) = client.get("/transactions/1").await;
+    assert!(body.contains("03.11.2024"));
+    assert!(!body.contains("2024-11-03</"));
+}
+
+fn count_rows(client: &TestClient, table: &str) -> i64 {
+    let conn = client.state().db.get().unwrap();
+    conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+        row.get(0)
+    })
+    .unwrap()
+}
+
+/// Submitting the same new transaction form twice creates one transaction.
+#[tokio::test]
+async fn test_double_submit_creates_one_transaction() {
+    let client = TestClient::new();
+    let (status, body) = client.get("/transactions/new").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("name=\"form_token\""));
+
+    let form = [
+        ("form_token", "double-submit-token"),
+        ("date", "2024-05-01"),
+        ("amount", "-12.50"),
+        ("currency", "USD"),
+        ("description", "Bakery"),
+    ];
+    for _ in 0..2 {
+        let (status, _) = client.post_form("/transactions/create", &form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    assert_eq!(count_rows(&client, "transactions"), 1);
+
+    // Forms without a token are not deduplicated.
+    let (status, _) = client.post_form("/transactions/create", &form[1..]).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(count_rows(&client, "transactions"), 2);
+}
+
+/// A new transaction form that fails validation is shown again with the
+/// entered values and its token, which stays usable.
+#[tokio::test]
+async fn test_invalid_transaction_form_is_rerendered() {
+    let client = TestClient::new();
+    let tag = {
+        let conn = client.state().db.get().unwrap();
+        tags::create_tag(
+            &conn,
+            &solvency::models::NewTag {
+                name: "Trip".into(),
+                color: "#336699".into(),
+                style: Default::default(),
+            },
+        )
+        .unwrap()
+    };
+    client.state().cache.invalidate();
+    let tag = tag.to_string();
+
+    let mut form = vec![
+        ("form_token", "retry-token"),
+        ("date", "2024-05-01"),
+        ("amount", "twelve"),
+        ("currency", "EUR"),
+        ("description", "Museum tickets"),
+        ("tag_ids", tag.as_str()),
+    ];
+    let (status, body) = client.post_form("/transactions/create", &form).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Invalid amount"));
+    assert!(body.contains("value=\"Museum tickets\""));
+    assert!(body.contains("value=\"retry-token\""));
+    assert!(body.contains("<option value=\"EUR\" selected>"));
+    assert_eq!(count_rows(&client, "transactions"), 0);
+
+    form[2] = ("amount", "-24.00");
+    for _ in 0..2 {
+        let (status, _) = client.post_form("/transactions/create", &form).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    assert_eq!(count_rows(&client, "transactions"), 1);
+    let conn = client.state().db.get().unwrap();
+    let tagged: i64 = conn
+        .query_row("SELECT COUNT(*) FROM transaction_tags", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(tagged, 1);
+}
+
+/// The duplicate form is filled in from the source transaction but dated
+/// today, and the transaction it creates is independent of the source.
+#[tokio::test]
+async fn test_duplicate_transaction() {
+    let client = TestClient::new();
+    let tag = {
+        let conn = client.state().db.get().unwrap();
+        tags::create_tag(
+            &conn,
+            &solvency::models::NewTag {
+                name: "Flat".into(),
+                color: "#336699".into(),
+                style: Default::default(),
+            },
+        )
+        .unwrap()
+    };
+    client.state().cache.invalidate();
+    let tag = tag.to_string();
+
+    let mut form = vec![
+        ("date", "2024-05-01"),
+        ("amount", "-950.00"),
+        ("currency", "EUR"),
+        ("description", "Rent"),
+        ("category_id", "6"),
+        ("notes", "Flat 3"),
+        ("tag_ids", tag.as_str()),
+    ];
+    let (status, _) = client.post_form("/transactions/create", &form).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let max_id = |client: &TestClient| -> i64 {
+        let conn = client.state().db.get().unwrap();
+        conn.query_row("SELECT MAX(id) FROM transactions", [], |row| row.get(0))
+            .unwrap()
+    };
+    let source = max_id(&client);
+
+    let today = solvency::date_utils::today_in(&client.state().load_settings().unwrap());
+    let (status, body) = client
+        .get(&format!("/transactions/{source}/duplicate"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!("action=\"/transactions/{source}/duplicate\"")));
+    assert!(body.contains(&format!("value=\"{}\"", today.format("%Y-%m-%d"))));
+    assert!(body.contains("value=\"-950.00\""));
+    assert!(body.contains("value=\"Rent\""));
+    assert!(body.contains("Flat 3"));
+    assert!(body.contains("<option value=\"EUR\" selected>"));
+    assert!(body.contains("<option value=\"6\" selected>"));
+    assert!(body.contains(&format!("value=\"{tag}\" checked")));
+
+    // A failed submission shows the duplicate form again
+    form[0] = ("date", "2024-06-01");
+    form[1] = ("amount", "lots");
+    let (status, body) = client
+        .post_form(&format!("/transactions/{source}/duplicate"), &form)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains(&format!("action=\"/transactions/{source}/duplicate\"")));
+
+    form[1] = ("amount", "-975.00");
+    let (status, _) = client
+        .post_form(&format!("/transactions/{source}/duplicate"), &form)
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let copy = max_id(&client);
+    assert_ne!(copy, source);
+
+    let (status, _) = client
+        .delete_request(&format!("/transactions/{copy}/delete"))
+        .await;
+    assert!(status.is_success() || status.is_redirection());
+    let conn = client.state().db.get().unwrap();
+    assert!(transactions::get_transaction(&conn, copy)
+        .unwrap()
+        .is_none());
+    let original = transactions::get_transaction(&conn, source)
+        .unwrap()
+        .unwrap();
+    assert_eq!(original.date, "2024-05-01");
+    assert_eq!(original.amount_cents, -95_000);
+    assert_eq!(original.tags.len(), 1);
+    drop(conn);
+
+    let (status, _) = client.get("/transactions/999999/duplicate").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// Insert 5000 transactions for the statement cache tests.
+fn seed_lookup_rows(conn: &rusqlite::Connection) {
+    conn.execute_batch(
+        "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+         INSERT INTO transactions (date, amount_cents, currency, description, category_id)
+         SELECT printf('2024-%02d-%02d', i % 12 + 1, i % 28 + 1), -(i % 5000) - 1, 'EUR',
+                'Purchase ' || i, 4 + i % 2
+         FROM n;",
+    )
+    .unwrap();
+}
+
+/// Look up every seeded transaction with the given statement cache capacity.
+fn lookup_all(conn: &rusqlite::Connection, capacity: usize) -> Vec<String> {
+    conn.set_prepared_statement_cache_capacity(capacity);
+    (1..=5000)
+        .map(|id| {
+            let txn = transactions::get_transaction(conn, id).unwrap().unwrap();
+            format!("{:?}", txn)
+        })
+        .collect()
+}
+
+/// Point lookups return the same rows with and without the statement cache.
+#[tokio::test]
+async fn test_statement_cache_returns_same_rows() {
+    let client = TestClient::new();
+    let conn = client.state().db.get().unwrap();
+    seed_lookup_rows(&conn);
+
+    let uncached_rows = lookup_all(&conn, 0);
+    let cached_rows = lookup_all(&conn, STATEMENT_CACHE_CAPACITY);
+    assert_eq!(uncached_rows, cached_rows);
+}
+
+#[tokio::test]
+async fn test_trading_cash_events_are_mirrored() {
+    use solvency::db::queries::categories;
+    use solvency::models::category::NewCategory;
+
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Broker"),
+                ("account_type", "Securities"),
+                ("active", "on"),
+                ("cash_account_id", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let dividend = |price: &'static str| {
+        vec![
+            ("date", "2024-03-15"),
+            ("symbol", "VTI"),
+            ("activity_type", "DIVIDEND"),
+            ("quantity", "1"),
+            ("unit_price", price),
+            ("currency", "USD"),
+            ("fee", "0"),
+            ("account_id", "2"),
+        ]
+    };
+
+    // Off by default
+    let (status, _) = client
+        .post_form("/trading/activities/create", &dividend("10.00"))
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    {
+        let conn = client.state().db.get().unwrap();
+        assert_eq!(
+            transactions::find_by_source_activity(&conn, 1).unwrap(),
+            None
+        );
+    }
+
+    let income = {
+        let conn = client.state().db.get().unwrap();
+        let income = categories::create_category(
+            &conn,
+            &NewCategory {
+                name: "Dividends".into(),
+                parent_id: None,
+                color: "#00aa00".into(),
+                icon: "coins".into(),
+            },
+        )
+        .unwrap();
+        settings::set_setting(&conn, "mirror_trading_cash", "true").unwrap();
+        settings::set_setting(&conn, "mirror_dividend_category_id", &income.to_string()).unwrap();
+        income
+    };
+    client.state().cache.invalidate();
+
+    let (status, _) = client
+        .post_form("/trading/activities/create", &dividend("12.50"))
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let mirror_id = {
+        let conn = client.state().db.get().unwrap();
+        let id = transactions::find_by_source_activity(&conn, 2)
+            .unwrap()
+            .expect("dividend is mirrored");
+        let mirror = transactions::get_transaction(&conn, id).unwrap().unwrap();
+        assert_eq!(mirror.amount_cents, 1250);
+        assert_eq!(mirror.description, "Dividend VTI");
+        assert_eq!(mirror.category_id, Some(income));
+        assert_eq!(mirror.account_id, Some(1));
+        assert_eq!(mirror.source_activity_id, Some(2));
+        id
+    };
+
+    // Editing the activity updates the mirror
+    let (status, _) = client
+        .post_form("/trading/activities/2/update", &dividend("14.00"))
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    {
+        let conn = client.state().db.get().unwrap();
+        let mirror = transactions::get_transaction(&conn, mirror_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(mirror.amount_cents, 1400);
+        assert_eq!(mirror.category_id, Some(income));
+    }
+
+    // The list filter separates mirrored transactions
+    client
+        .create_transaction("2024-03-20", "-30.00", "Corner Shop", None, None)
+        .await;
+    let (_, body) = client.get("/transactions?mirrored=1").await;
+    assert!(body.contains("Dividend VTI"));
+    assert!(!body.contains("Corner Shop"));
+    let (_, body) = client.get("/transactions?mirrored=0").await;
+    assert!(!body.contains("Dividend VTI"));
+    assert!(body.contains("Corner Shop"));
+
+    // Deleting the activity deletes the mirror, restoring it brings it back
+    let (status, _) = client.delete_request("/trading/activities/2/delete").await;
+    assert_eq!(status, StatusCode::OK);
+    {
+        let conn = client.state().db.get().unwrap();
+        assert_eq!(
+            transactions::find_by_source_activity(&conn, 2).unwrap(),
+            None
+        );
+        assert!(transactions::get_transaction(&conn, mirror_id)
+            .unwrap()
+            .is_none());
+    }
+    let (status, _) = client.post_form("/trading/activities/2/restore", &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let conn = client.state().db.get().unwrap();
+    assert!(transactions::find_by_source_activity(&conn, 2)
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test]
+async fn test_mirrors_duplicated_by_statement_lines_are_counted_once() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Broker"),
+                ("account_type", "Securities"),
+                ("active", "on"),
+                ("cash_account_id", "1"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    {
+        let conn = client.state().db.get().unwrap();
+        settings::set_setting(&conn, "mirror_trading_cash", "true").unwrap();
+    }
+    client.state().cache.invalidate();
+
+    let (status, _) = client
+        .post_form(
+            "/trading/activities/create",
+            &[
+                ("date", "2024-03-15"),
+                ("symbol", "VTI"),
+                ("activity_type", "DIVIDEND"),
+                ("quantity", "1"),
+                ("unit_price", "12.50"),
+                ("currency", "USD"),
+                ("fee", "0"),
+                ("account_id", "2"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let total = |client: &TestClient| {
+        let conn = client.state().db.get().unwrap();
+        let by_category =
+            transaction_sums::sum_by_category(&conn, Some("2024-03-01"), Some("2024-03-31"), &[])
+                .unwrap();
+        (
+            transaction_sums::sum_amount_cents(&conn, Some("2024-03-01"), Some("2024-03-31"))
+                .unwrap(),
+            by_category.iter().map(|c| c.total_cents).sum::<i64>(),
+        )
+    };
+    // Without a statement line the mirror is the only record of the dividend
+    assert_eq!(total(&client), (1250, 1250));
+
+    // The broker's cash statement books the same dividend
+    assert!(
+        client
+            .create_transaction("2024-03-15", "12.50", "VTI distribution", Some(1), None)
+            .await
+    );
+    assert_eq!(total(&client), (1250, 1250));
+
+    // A statement line on another day is a different event
+    assert!(
+        client
+            .create_transaction("2024-03-20", "12.50", "VTI distribution", Some(1), None)
+            .await
+    );
+    assert_eq!(total(&client), (2500, 2500));
+}
+
+#[tokio::test]
+async fn test_transfers_detected_by_iban() {
+    let client = TestClient::new();
+    for (name, iban) in [
+        ("Checking", "de89 3704 0044 0532 0130 00"),
+        ("Savings", "DE02120300000000202051"),
+    ] {
+        let (status, _) = client
+            .post_form(
+                "/accounts/create",
+                &[
+                    ("name", name),
+                    ("account_type", "Cash"),
+                    ("active", "on"),
+                    ("iban", iban),
+                ],
+            )
+            .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    let (status, _) = client
+        .post_form(
+            "/accounts/create",
+            &[
+                ("name", "Other"),
+                ("account_type", "Cash"),
+                ("iban", "12345"),
+            ],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The import pairs the transfer with both sides in it
+    let (status, body) = client
+        .post_json(
+            "/transactions/import",
+            r#"[
+                {"date": "2024-03-01", "amount_cents": -50000, "description": "To savings",
+                 "account_name": "Checking", "counterparty_iban": "DE02 1203 0000 0000 2020 51"},
+                {"date": "2024-03-03", "amount_cents": 50000, "description": "Incoming",
+                 "account_name": "Savings"},
+                {"date": "2024-03-10", "amount_cents": -10000, "description": "To savings",
+                 "account_name": "Checking", "counterparty_iban": "DE02120300000000202051"}
+            ]"#,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["transfers"]["pairs"].as_array().unwrap().len(), 1);
+    assert_eq!(body["transfers"]["pairs"][0]["amount_cents"], 50000);
+    assert_eq!(body["transfers"]["unmatched"][0]["amount_cents"], -10000);
+    {
+        let conn = client.state().db.get().unwrap();
+        let outgoing = transactions::get_transaction(&conn, 1).unwrap().unwrap();
+        assert_eq!(outgoing.transfer_pair_id, Some(2));
+        assert_eq!(outgoing.category_name.as_deref(), Some("Transfers"));
+    }
+
+    // The missing side arrives later and is paired on request
+    assert!(
+        client
+            .create_transaction("2024-03-11", "100.00", "Incoming", Some(2), None)
+            .await
+    );
+    let (status, body) = client
+        .post_form("/transactions/detect-transfers", &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["pairs"][0]["outgoing_id"], 3);
+    assert_eq!(body["pairs"][0]["incoming_id"], 4);
+    assert!(body["unmatched"].as_array().unwrap().is_empty());
+
+    // Linked transactions are not paired again
+    let (_, body) = client
+        .post_form("/transactions/detect-transfers", &[])
+        .await;
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(body["pairs"].as_array().unwrap().is_empty());
+}
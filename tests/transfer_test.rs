@@ -0,0 +1,185 @@
+//! Integration tests for account-to-account transfers.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestClient;
+use serde::Deserialize;
+use solvency::db::queries::transactions;
+
+#[derive(Debug, Deserialize)]
+struct MonthlySummary {
+    total_cents: i64,
+}
+
+async fn create_transfer(client: &TestClient, amount: &str, from: &str, to: &str) -> StatusCode {
+    let (status, _) = client
+        .post_form(
+            "/transactions/transfer/create",
+            &[
+                ("date", "2024-01-10"),
+                ("amount", amount),
+                ("from_account", from),
+                ("to_account", to),
+                ("note", "Monthly savings"),
+            ],
+        )
+        .await;
+    status
+}
+
+/// Test that the transfer form renders with the available accounts.
+#[tokio::test]
+async fn test_transfer_form_renders() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+
+    let (status, body) = client.get("/transactions/transfer").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Checking"));
+    assert!(body.contains("Savings"));
+}
+
+/// Test that a transfer creates two linked Transfers-category transactions.
+#[tokio::test]
+async fn test_transfer_creates_linked_pair() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+
+    assert_eq!(
+        create_transfer(&client, "250.00", "1", "2").await,
+        StatusCode::SEE_OTHER
+    );
+
+    let conn = client.state().db.get().unwrap();
+    let out = transactions::get_transaction(&conn, 1).unwrap().unwrap();
+    let inc = transactions::get_transaction(&conn, 2).unwrap().unwrap();
+    assert_eq!(out.amount_cents, -25_000);
+    assert_eq!(out.account_id, Some(1));
+    assert_eq!(inc.amount_cents, 25_000);
+    assert_eq!(inc.account_id, Some(2));
+    assert_eq!(out.category_id, Some(3));
+    assert_eq!(inc.category_id, Some(3));
+    assert_eq!(out.transfer_pair_id, Some(2));
+    assert_eq!(inc.transfer_pair_id, Some(1));
+    assert_eq!(out.notes.as_deref(), Some("Monthly savings"));
+
+    let (status, body) = client.get("/balances").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("250.00"));
+}
+
+/// Test that transfer validation rejects bad input without writing anything.
+#[tokio::test]
+async fn test_transfer_validation() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+    let (status, _) = client
+        .post_form(
+            "/accounts/2/update",
+            &[("name", "Savings"), ("account_type", "Cash")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert!(client.create_account("Brokerage Cash", "Cash").await);
+
+    // Same account on both sides
+    assert_eq!(
+        create_transfer(&client, "10.00", "1", "1").await,
+        StatusCode::BAD_REQUEST
+    );
+    // Non-positive amount
+    assert_eq!(
+        create_transfer(&client, "-10.00", "1", "3").await,
+        StatusCode::BAD_REQUEST
+    );
+    // Inactive destination
+    assert_eq!(
+        create_transfer(&client, "10.00", "1", "2").await,
+        StatusCode::BAD_REQUEST
+    );
+
+    let conn = client.state().db.get().unwrap();
+    let filter = transactions::TransactionFilter::default();
+    assert_eq!(transactions::count_transactions(&conn, &filter).unwrap(), 0);
+}
+
+/// Test that analytics ignore a transfer pair.
+#[tokio::test]
+async fn test_transfer_ignored_by_analytics() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+    assert!(
+        client
+            .create_transaction("2024-01-05", "-40.00", "Groceries", Some(1), Some(4))
+            .await
+    );
+    assert_eq!(
+        create_transfer(&client, "500.00", "1", "2").await,
+        StatusCode::SEE_OTHER
+    );
+
+    let (status, summary): (_, Option<Vec<MonthlySummary>>) = client
+        .get_json("/api/analytics/monthly-summary?from_date=2024-01-01&to_date=2024-01-31")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let summary = summary.expect("Failed to parse JSON");
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].total_cents, 4_000);
+}
+
+/// Test that editing one side can keep the linked counterpart in sync.
+#[tokio::test]
+async fn test_transfer_edit_syncs_pair() {
+    let client = TestClient::new();
+    assert!(client.create_account("Checking", "Cash").await);
+    assert!(client.create_account("Savings", "Cash").await);
+    assert_eq!(
+        create_transfer(&client, "100.00", "1", "2").await,
+        StatusCode::SEE_OTHER
+    );
+
+    let (status, body) = client.get("/transactions/1/edit").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("sync_transfer_pair"));
+
+    let edit = |sync: &'static str| {
+        let mut form = vec![
+            ("date", "2024-01-12"),
+            ("amount", "-120.00"),
+            ("currency", "USD"),
+            ("description", "Transfer to Savings"),
+            ("category_id", "3"),
+            ("account_id", "1"),
+        ];
+        if !sync.is_empty() {
+            form.push(("sync_transfer_pair", sync));
+        }
+        form
+    };
+
+    let (status, _) = client
+        .post_form("/transactions/1/update", &edit("on"))
+        .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    {
+        let conn = client.state().db.get().unwrap();
+        let inc = transactions::get_transaction(&conn, 2).unwrap().unwrap();
+        assert_eq!(inc.amount_cents, 12_000);
+        assert_eq!(inc.date, "2024-01-12");
+        assert_eq!(inc.description, "Transfer from Checking");
+    }
+
+    // Without the checkbox only the edited side changes
+    let mut form = edit("");
+    form[1] = ("amount", "-90.00");
+    let (status, _) = client.post_form("/transactions/1/update", &form).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let conn = client.state().db.get().unwrap();
+    let inc = transactions::get_transaction(&conn, 2).unwrap().unwrap();
+    assert_eq!(inc.amount_cents, 12_000);
+}